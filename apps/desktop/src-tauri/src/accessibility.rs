@@ -0,0 +1,179 @@
+//! Accessibility.
+//!
+//! Optional text-to-speech announcements: while on, messages arriving in the
+//! conversation being viewed are read aloud, and so are mentions anywhere.
+//! Conversations can be left out one at a time. Speech goes through the
+//! platform's TTS service (Speech Dispatcher, AVFoundation or WinRT), started
+//! the first time something is spoken.
+
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+use tts::Tts;
+
+use crate::db::MessageStore;
+
+/// Profile setting holding the conversations left out of announcements, as JSON
+pub const TTS_MUTED_SETTING: &str = "tts_muted_conversations";
+
+/// Text-to-speech (off by default)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AccessibilitySettings {
+    /// Read out messages in the conversation being viewed
+    pub tts_enabled: bool,
+    /// Also read out mentions in other conversations
+    pub speak_mentions: bool,
+    /// Multiple of the voice's normal rate
+    pub speech_rate: f32,
+}
+
+impl Default for AccessibilitySettings {
+    fn default() -> Self {
+        Self {
+            tts_enabled: false,
+            speak_mentions: true,
+            speech_rate: 1.0,
+        }
+    }
+}
+
+/// A conversation as the frontend knows it
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(tag = "type", content = "id", rename_all = "snake_case")]
+pub enum Conversation {
+    Friend(u32),
+    Channel(String),
+}
+
+/// Shared between commands and the tox thread's callbacks
+pub struct Accessibility {
+    settings: Mutex<AccessibilitySettings>,
+    /// The conversation open in the app while its window has focus
+    focused: Mutex<Option<Conversation>>,
+    /// Left out of announcements, for the logged-in profile
+    muted: Mutex<HashSet<Conversation>>,
+    /// Created on first use, as connecting to the TTS service can be slow
+    speaker: Mutex<Option<Tts>>,
+}
+
+impl Accessibility {
+    pub fn new(settings: AccessibilitySettings) -> Self {
+        Self {
+            settings: Mutex::new(settings),
+            focused: Mutex::new(None),
+            muted: Mutex::new(HashSet::new()),
+            speaker: Mutex::new(None),
+        }
+    }
+
+    /// Load the profile's muted conversations
+    pub fn load(&self, store: &MessageStore) -> Result<(), String> {
+        let loaded = muted_conversations(store)?;
+        if let Ok(mut muted) = self.muted.lock() {
+            *muted = loaded.into_iter().collect();
+        }
+        Ok(())
+    }
+
+    pub fn settings(&self) -> AccessibilitySettings {
+        self.settings.lock().map(|s| s.clone()).unwrap_or_default()
+    }
+
+    /// Apply new settings; turning speech off stops anything being read
+    pub fn set_settings(&self, settings: AccessibilitySettings) {
+        if !settings.tts_enabled {
+            self.stop();
+        }
+        if let Ok(mut current) = self.settings.lock() {
+            *current = settings;
+        }
+    }
+
+    pub fn set_focused(&self, conversation: Option<Conversation>) {
+        if let Ok(mut focused) = self.focused.lock() {
+            *focused = conversation;
+        }
+    }
+
+    pub fn is_muted(&self, conversation: &Conversation) -> bool {
+        self.muted.lock().is_ok_and(|muted| muted.contains(conversation))
+    }
+
+    /// Leave a conversation out of announcements, or put it back, and save the choice
+    pub fn set_muted(&self, store: &MessageStore, conversation: Conversation, muted: bool) -> Result<(), String> {
+        let list: Vec<Conversation> = {
+            let mut current = self.muted.lock().map_err(|e| e.to_string())?;
+            if muted {
+                current.insert(conversation);
+            } else {
+                current.remove(&conversation);
+            }
+            current.iter().cloned().collect()
+        };
+        let json =
+            serde_json::to_string(&list).map_err(|e| format!("Failed to serialize muted conversations: {e}"))?;
+        store.set_setting(TTS_MUTED_SETTING, &json)
+    }
+
+    /// Read out a message that just arrived, if it's in the conversation being
+    /// viewed or mentions us. `sender` is None in direct messages.
+    pub fn announce(&self, conversation: &Conversation, sender: Option<&str>, text: &str, mentions_me: bool) {
+        let settings = self.settings();
+        if !settings.tts_enabled || text.trim().is_empty() || self.is_muted(conversation) {
+            return;
+        }
+        let focused = self.focused.lock().is_ok_and(|f| f.as_ref() == Some(conversation));
+        let spoken = match sender {
+            Some(sender) if focused => format!("{sender}: {text}"),
+            Some(sender) if mentions_me && settings.speak_mentions => format!("{sender} mentioned you: {text}"),
+            None if focused => text.to_string(),
+            _ => return,
+        };
+        if let Err(e) = self.speak(&spoken, false) {
+            warn!("{e}");
+        }
+    }
+
+    /// Read text aloud, cutting off anything being read if `interrupt`
+    pub fn speak(&self, text: &str, interrupt: bool) -> Result<(), String> {
+        let rate = self.settings().speech_rate;
+        let mut speaker = self.speaker.lock().map_err(|e| e.to_string())?;
+        if speaker.is_none() {
+            *speaker = Some(Tts::default().map_err(|e| format!("Text-to-speech is unavailable: {e}"))?);
+        }
+        let Some(tts) = speaker.as_mut() else {
+            return Ok(());
+        };
+        let rate = (tts.normal_rate() * rate).clamp(tts.min_rate(), tts.max_rate());
+        if let Err(e) = tts.set_rate(rate) {
+            warn!("Failed to set the speech rate: {e}");
+        }
+        tts.speak(text, interrupt)
+            .map(|_| ())
+            .map_err(|e| format!("Failed to speak: {e}"))
+    }
+
+    /// Stop reading
+    pub fn stop(&self) {
+        if let Ok(mut speaker) = self.speaker.lock() {
+            if let Some(tts) = speaker.as_mut() {
+                if let Err(e) = tts.stop() {
+                    warn!("Failed to stop speech: {e}");
+                }
+            }
+        }
+    }
+}
+
+/// Conversations left out of announcements
+pub fn muted_conversations(store: &MessageStore) -> Result<Vec<Conversation>, String> {
+    match store.get_setting(TTS_MUTED_SETTING)? {
+        Some(json) => {
+            serde_json::from_str(&json).map_err(|e| format!("Failed to parse muted conversations: {e}"))
+        }
+        None => Ok(Vec::new()),
+    }
+}
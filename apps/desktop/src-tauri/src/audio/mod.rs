@@ -5,10 +5,12 @@
 //! - Audio playback to speakers (via cpal)
 //! - Audio mixing for voice channels (multiple simultaneous streams)
 //! - Resampling to/from ToxAV's required formats
+//! - Recording voice notes as Ogg Opus files
 
 pub mod capture;
 pub mod mixer;
 pub mod playback;
+pub mod voice_note;
 
 pub use capture::AudioCapture;
 pub use mixer::AudioMixer;
@@ -18,14 +18,10 @@ pub struct AudioPlayback {
 }
 
 impl AudioPlayback {
-    /// Start audio playback on the default output device.
-    ///
-    /// Takes a shared mixer that combines audio from multiple sources.
-    pub fn start(mixer: Arc<Mutex<AudioMixer>>) -> AudioResult<Self> {
-        Self::start_with_device(None, mixer)
-    }
-
     /// Start audio playback on a specific device (or default if None).
+    ///
+    /// Takes a shared mixer that combines audio from multiple sources, so a
+    /// new playback can replace a running one without losing queued audio.
     pub fn start_with_device(
         device_id: Option<&str>,
         mixer: Arc<Mutex<AudioMixer>>,
@@ -0,0 +1,308 @@
+//! Voice notes: short microphone recordings sent as Ogg Opus files.
+//!
+//! A voice note is an ordinary file transfer named `voice-*.ogg`. Its
+//! duration and a coarse waveform are written into the OpusTags header, so
+//! the receiving side can draw the player without decoding any audio.
+
+use std::io::{Cursor, Read, Seek};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use ogg::writing::PacketWriteEndInfo;
+use ogg::{PacketReader, PacketWriter};
+use tokio::sync::mpsc;
+use tracing::info;
+
+use super::{AudioCapture, AudioResult, TOXAV_FRAME_DURATION_MS, TOXAV_SAMPLE_RATE, TOXAV_SAMPLES_PER_FRAME};
+
+/// MIME type of voice note attachments
+pub const VOICE_NOTE_MIME: &str = "audio/ogg";
+
+/// Filename prefix that marks an Ogg file as a voice note
+pub const VOICE_NOTE_PREFIX: &str = "voice-";
+
+/// Default (and upper) limit on a recording's length
+pub const MAX_VOICE_NOTE_SECS: u32 = 5 * 60;
+
+/// Number of bars in the stored waveform
+pub const WAVEFORM_BARS: usize = 64;
+
+/// Opus bitrate for voice notes; plenty for speech at 48 kHz mono
+const VOICE_NOTE_BITRATE: i32 = 32_000;
+
+/// Largest Opus packet we expect for a single 20 ms frame
+const MAX_OPUS_PACKET: usize = 4000;
+
+/// Ogg stream serial; there is only ever one logical stream per file
+const OGG_SERIAL: u32 = 0x5443_5643;
+
+const TAG_DURATION: &str = "TOXCORD_DURATION_MS";
+const TAG_WAVEFORM: &str = "TOXCORD_WAVEFORM";
+
+/// Stop flags for a recording in progress, shared with the command that stops it
+#[derive(Debug, Default)]
+pub struct RecordingControl {
+    stop: AtomicBool,
+    cancelled: AtomicBool,
+}
+
+impl RecordingControl {
+    /// Finish the recording. With `send` false the audio is discarded.
+    pub fn stop(&self, send: bool) {
+        if !send {
+            self.cancelled.store(true, Ordering::SeqCst);
+        }
+        self.stop.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+/// What the OpusTags header says about a voice note
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VoiceNoteInfo {
+    pub duration_ms: u64,
+    /// Peak level per bar, scaled so the loudest bar is 255
+    pub waveform: Vec<u8>,
+}
+
+/// Whether a filename follows the voice note naming convention
+pub fn is_voice_note(filename: &str) -> bool {
+    let lower = filename.to_ascii_lowercase();
+    lower.starts_with(VOICE_NOTE_PREFIX) && lower.ends_with(".ogg")
+}
+
+/// Record from the default microphone until `control` is stopped or `max` elapses.
+/// Blocks the calling thread; returns 48 kHz mono PCM.
+pub fn record(control: &RecordingControl, max: Duration) -> AudioResult<Vec<i16>> {
+    let (frame_tx, mut frame_rx) = mpsc::unbounded_channel();
+    let capture = AudioCapture::start(frame_tx)?;
+    let max_samples = (max.as_millis() as u64 * TOXAV_SAMPLE_RATE as u64 / 1000) as usize;
+    let mut pcm = Vec::with_capacity(max_samples.min(TOXAV_SAMPLE_RATE as usize * 60));
+
+    'recording: while !control.stop.load(Ordering::SeqCst) && pcm.len() < max_samples {
+        loop {
+            match frame_rx.try_recv() {
+                Ok(frame) => pcm.extend_from_slice(&frame),
+                Err(mpsc::error::TryRecvError::Empty) => break,
+                Err(mpsc::error::TryRecvError::Disconnected) => break 'recording,
+            }
+        }
+        std::thread::sleep(Duration::from_millis(TOXAV_FRAME_DURATION_MS as u64));
+    }
+
+    drop(capture);
+    pcm.truncate(max_samples);
+    info!("Recorded voice note: {} ms", duration_ms(pcm.len()));
+    Ok(pcm)
+}
+
+/// Length of `samples` of 48 kHz mono audio
+pub fn duration_ms(samples: usize) -> u64 {
+    samples as u64 * 1000 / TOXAV_SAMPLE_RATE as u64
+}
+
+/// Downsample PCM to at most `bars` peak levels, normalized to 0-255
+pub fn waveform(pcm: &[i16], bars: usize) -> Vec<u8> {
+    if pcm.is_empty() || bars == 0 {
+        return Vec::new();
+    }
+    let peaks: Vec<u16> = pcm
+        .chunks(pcm.len().div_ceil(bars))
+        .map(|chunk| chunk.iter().map(|s| s.unsigned_abs()).max().unwrap_or(0))
+        .collect();
+    let loudest = peaks.iter().copied().max().unwrap_or(0).max(1) as u32;
+    peaks.iter().map(|&p| (p as u32 * 255 / loudest) as u8).collect()
+}
+
+/// Encode PCM as an Ogg Opus voice note
+pub fn encode(pcm: &[i16]) -> Result<Vec<u8>, String> {
+    let mut encoder = opus::Encoder::new(TOXAV_SAMPLE_RATE, opus::Channels::Mono, opus::Application::Voip)
+        .map_err(|e| format!("Failed to create Opus encoder: {e}"))?;
+    encoder
+        .set_bitrate(opus::Bitrate::Bits(VOICE_NOTE_BITRATE))
+        .map_err(|e| format!("Failed to set Opus bitrate: {e}"))?;
+    let pre_skip = encoder
+        .get_lookahead()
+        .map_err(|e| format!("Failed to query Opus lookahead: {e}"))? as usize;
+
+    // Pad with the encoder's lookahead so the tail of the recording isn't cut off
+    let mut input = pcm.to_vec();
+    input.resize(pcm.len() + pre_skip, 0);
+
+    let mut packets = Vec::with_capacity(input.len().div_ceil(TOXAV_SAMPLES_PER_FRAME));
+    let mut frame = [0i16; TOXAV_SAMPLES_PER_FRAME];
+    let mut output = [0u8; MAX_OPUS_PACKET];
+    for chunk in input.chunks(TOXAV_SAMPLES_PER_FRAME) {
+        frame[..chunk.len()].copy_from_slice(chunk);
+        frame[chunk.len()..].fill(0);
+        let len = encoder
+            .encode(&frame, &mut output)
+            .map_err(|e| format!("Failed to encode voice note: {e}"))?;
+        packets.push(output[..len].to_vec());
+    }
+
+    let info = VoiceNoteInfo {
+        duration_ms: duration_ms(pcm.len()),
+        waveform: waveform(pcm, WAVEFORM_BARS),
+    };
+    write_ogg(packets, pre_skip as u16, pcm.len() as u64, &info)
+        .map_err(|e| format!("Failed to write voice note: {e}"))
+}
+
+/// Read the voice note metadata from an Ogg Opus file
+pub fn read_info(path: &Path) -> Option<VoiceNoteInfo> {
+    let file = std::fs::File::open(path).ok()?;
+    parse_info(file)
+}
+
+/// Mux 20 ms Opus packets into an Ogg stream (RFC 7845)
+fn write_ogg(packets: Vec<Vec<u8>>, pre_skip: u16, samples: u64, info: &VoiceNoteInfo) -> std::io::Result<Vec<u8>> {
+    let mut writer = PacketWriter::new(Vec::new());
+    writer.write_packet(opus_head(pre_skip), OGG_SERIAL, PacketWriteEndInfo::EndPage, 0)?;
+    writer.write_packet(opus_tags(info), OGG_SERIAL, PacketWriteEndInfo::EndPage, 0)?;
+
+    // Granule positions count decoded samples, including the pre-skip;
+    // the last one is clamped so players drop the padding
+    let end = pre_skip as u64 + samples;
+    let count = packets.len();
+    for (i, packet) in packets.into_iter().enumerate() {
+        let granule = ((i as u64 + 1) * TOXAV_SAMPLES_PER_FRAME as u64).min(end);
+        let end_info = if i + 1 == count {
+            PacketWriteEndInfo::EndStream
+        } else {
+            PacketWriteEndInfo::NormalPacket
+        };
+        writer.write_packet(packet, OGG_SERIAL, end_info, granule)?;
+    }
+
+    Ok(writer.into_inner())
+}
+
+fn opus_head(pre_skip: u16) -> Vec<u8> {
+    let mut head = Vec::with_capacity(19);
+    head.extend_from_slice(b"OpusHead");
+    head.push(1); // version
+    head.push(1); // channels
+    head.extend_from_slice(&pre_skip.to_le_bytes());
+    head.extend_from_slice(&TOXAV_SAMPLE_RATE.to_le_bytes());
+    head.extend_from_slice(&0i16.to_le_bytes()); // output gain
+    head.push(0); // channel mapping family
+    head
+}
+
+fn opus_tags(info: &VoiceNoteInfo) -> Vec<u8> {
+    let vendor = concat!("toxcord ", env!("CARGO_PKG_VERSION"));
+    let waveform: String = info.waveform.iter().map(|b| format!("{b:02x}")).collect();
+    let comments = [
+        format!("{TAG_DURATION}={}", info.duration_ms),
+        format!("{TAG_WAVEFORM}={waveform}"),
+    ];
+
+    let mut tags = Vec::new();
+    tags.extend_from_slice(b"OpusTags");
+    tags.extend_from_slice(&(vendor.len() as u32).to_le_bytes());
+    tags.extend_from_slice(vendor.as_bytes());
+    tags.extend_from_slice(&(comments.len() as u32).to_le_bytes());
+    for comment in &comments {
+        tags.extend_from_slice(&(comment.len() as u32).to_le_bytes());
+        tags.extend_from_slice(comment.as_bytes());
+    }
+    tags
+}
+
+fn parse_info<R: Read + Seek>(reader: R) -> Option<VoiceNoteInfo> {
+    let mut packets = PacketReader::new(reader);
+    let head = packets.read_packet().ok()??;
+    if !head.data.starts_with(b"OpusHead") {
+        return None;
+    }
+    let tags = packets.read_packet().ok()??;
+    let comments = parse_comments(&tags.data)?;
+
+    let mut duration_ms = None;
+    let mut waveform = Vec::new();
+    for comment in comments {
+        match comment.split_once('=') {
+            Some((TAG_DURATION, value)) => duration_ms = value.parse().ok(),
+            Some((TAG_WAVEFORM, value)) => {
+                waveform = (0..value.len() / 2)
+                    .filter_map(|i| u8::from_str_radix(value.get(i * 2..i * 2 + 2)?, 16).ok())
+                    .collect();
+            }
+            _ => {}
+        }
+    }
+
+    Some(VoiceNoteInfo {
+        duration_ms: duration_ms?,
+        waveform,
+    })
+}
+
+fn parse_comments(data: &[u8]) -> Option<Vec<String>> {
+    let mut cursor = Cursor::new(data.strip_prefix(b"OpusTags")?);
+    let vendor_len = read_u32(&mut cursor)? as u64;
+    cursor.set_position(cursor.position() + vendor_len);
+
+    let count = read_u32(&mut cursor)?;
+    let mut comments = Vec::new();
+    for _ in 0..count {
+        let len = read_u32(&mut cursor)? as usize;
+        let mut comment = vec![0u8; len];
+        cursor.read_exact(&mut comment).ok()?;
+        comments.push(String::from_utf8_lossy(&comment).into_owned());
+    }
+    Some(comments)
+}
+
+fn read_u32(cursor: &mut Cursor<&[u8]>) -> Option<u32> {
+    let mut bytes = [0u8; 4];
+    cursor.read_exact(&mut bytes).ok()?;
+    Some(u32::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_voice_note() {
+        assert!(is_voice_note("voice-20250101-120000.ogg"));
+        assert!(is_voice_note("Voice-note.OGG"));
+        assert!(!is_voice_note("song.ogg"));
+        assert!(!is_voice_note("voice-memo.mp3"));
+    }
+
+    #[test]
+    fn test_waveform_is_normalized() {
+        let mut pcm = vec![0i16; 4800];
+        pcm[100] = 1000;
+        pcm[4000] = -4000;
+        let bars = waveform(&pcm, 8);
+        assert_eq!(bars.len(), 8);
+        assert_eq!(bars[0], 63);
+        assert_eq!(bars[6], 255);
+        assert_eq!(bars[3], 0);
+        assert!(waveform(&[], 8).is_empty());
+    }
+
+    #[test]
+    fn test_ogg_roundtrip() {
+        let info = VoiceNoteInfo {
+            duration_ms: 1500,
+            waveform: vec![0, 17, 255, 128],
+        };
+        let packets = vec![vec![0xfc, 0x01]; 80];
+        let data = write_ogg(packets, 312, 72_000, &info).unwrap();
+        assert_eq!(parse_info(Cursor::new(data)), Some(info));
+    }
+
+    #[test]
+    fn test_parse_rejects_other_files() {
+        assert_eq!(parse_info(Cursor::new(b"not an ogg file".to_vec())), None);
+    }
+}
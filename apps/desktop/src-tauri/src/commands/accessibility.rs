@@ -0,0 +1,102 @@
+//! Tauri commands for accessibility (text-to-speech announcements, command palette).
+
+use tauri::State;
+
+use crate::accessibility::{self, AccessibilitySettings, Conversation};
+use crate::error::{CommandResult, ToxcordError};
+use crate::managers::av_manager::CallStatus;
+use crate::managers::command_palette::{self, CallContext, PaletteCommand, MAX_RESULTS};
+use crate::AppState;
+
+#[tauri::command]
+pub async fn get_accessibility_settings(state: State<'_, AppState>) -> CommandResult<AccessibilitySettings> {
+    Ok(state.settings.lock().await.accessibility.clone())
+}
+
+#[tauri::command]
+pub async fn set_accessibility_settings(
+    state: State<'_, AppState>,
+    settings: AccessibilitySettings,
+) -> CommandResult<()> {
+    if !(0.25..=4.0).contains(&settings.speech_rate) {
+        return Err(ToxcordError::invalid("Speech rate must be between 0.25 and 4"));
+    }
+    {
+        let mut app_settings = state.settings.lock().await;
+        app_settings.accessibility = settings.clone();
+        app_settings.save()?;
+    }
+    state.accessibility.set_settings(settings);
+    Ok(())
+}
+
+/// Tell the backend which conversation is on screen, or None when the window
+/// loses focus, so only its messages are read out
+#[tauri::command]
+pub async fn set_focused_conversation(
+    state: State<'_, AppState>,
+    conversation: Option<Conversation>,
+) -> CommandResult<()> {
+    state.accessibility.set_focused(conversation);
+    Ok(())
+}
+
+/// Leave a conversation out of announcements (muted) or put it back
+#[tauri::command]
+pub async fn set_conversation_tts(
+    state: State<'_, AppState>,
+    conversation: Conversation,
+    muted: bool,
+) -> CommandResult<()> {
+    let store = state.message_store.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+    Ok(state.accessibility.set_muted(&store, conversation, muted)?)
+}
+
+/// Conversations left out of announcements
+#[tauri::command]
+pub async fn get_muted_tts_conversations(state: State<'_, AppState>) -> CommandResult<Vec<Conversation>> {
+    let store_guard = state.message_store.lock().await;
+    let store = store_guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
+    Ok(accessibility::muted_conversations(store)?)
+}
+
+/// Read text aloud now, e.g. a message picked by the user. Cuts off anything being read.
+#[tauri::command]
+pub async fn speak_message(state: State<'_, AppState>, text: String) -> CommandResult<()> {
+    if text.trim().is_empty() {
+        return Err(ToxcordError::invalid("Nothing to read"));
+    }
+    let accessibility = state.accessibility.clone();
+    tokio::task::spawn_blocking(move || accessibility.speak(&text, true))
+        .await
+        .map_err(|e| format!("Speech task failed: {e}"))??;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn stop_speaking(state: State<'_, AppState>) -> CommandResult<()> {
+    state.accessibility.stop();
+    Ok(())
+}
+
+/// Commands for the command palette matching what's been typed, best first
+#[tauri::command]
+pub async fn query_commands(state: State<'_, AppState>, prefix: String) -> CommandResult<Vec<PaletteCommand>> {
+    let store = state.message_store.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+
+    let mut calls = CallContext::default();
+    if let Some(tox) = state.tox_manager.lock().await.clone() {
+        let mgr = tox.lock().await;
+        (calls.muted, calls.deafened) = mgr.get_global_audio_state().await?;
+        calls.calls = mgr
+            .list_calls()
+            .await?
+            .into_iter()
+            .filter(|c| matches!(c.state, CallStatus::RingingOutgoing | CallStatus::RingingIncoming | CallStatus::InProgress))
+            .map(|c| c.friend_number)
+            .collect();
+    }
+
+    let commands = store.call(move |store| command_palette::build(store, &calls)).await?;
+    Ok(command_palette::search(commands, &prefix, MAX_RESULTS))
+}
@@ -1,23 +1,66 @@
+use std::path::Path;
 use std::sync::Arc;
 
+use qrcode::render::svg;
+use qrcode::QrCode;
 use tauri::State;
 use tokio::sync::oneshot;
+use toxcord_core::profile_dirs::{self, ProfileDir};
+use toxcord_protocol::recovery_phrase;
+use toxcord_protocol::rich_presence::{Activity, ActivityKind, CustomStatus};
 
+use crate::db::message_store::NospamChangeRecord;
 use crate::db::MessageStore;
+use crate::error::{CommandResult, ToxcordError};
+use crate::managers::bridge_manager::BridgeManager;
 use crate::managers::tox_manager::{ToxCommand, ToxManager};
 use crate::AppState;
 
-/// Get the database directory for a profile
-fn get_db_path(profile_name: &str) -> std::path::PathBuf {
-    dirs::data_dir()
-        .unwrap_or_else(|| std::path::PathBuf::from("."))
-        .join("toxcord")
-        .join("profiles")
-        .join(format!("{profile_name}.db"))
+/// Profile names become directory names, so they can't hold path separators.
+/// Checked wherever a name from the frontend is turned into a path.
+fn validate_profile_name(profile_name: &str) -> CommandResult<()> {
+    if profile_name.trim().is_empty() {
+        return Err(ToxcordError::invalid("Profile name cannot be empty"));
+    }
+    if profile_name.starts_with('.') || profile_name.contains(['/', '\\', ':']) {
+        return Err(ToxcordError::invalid("Profile name cannot contain '/', '\\', ':' or start with '.'"));
+    }
+    Ok(())
+}
+
+/// Check that a profile can be renamed or copied to a new name
+fn check_profile_target(from: &ProfileDir, to_name: &str) -> CommandResult<ProfileDir> {
+    validate_profile_name(to_name)?;
+    if !from.exists() {
+        return Err(ToxcordError::not_found("Profile not found"));
+    }
+    let to = ProfileDir::new(to_name);
+    if to.dir.exists() {
+        return Err(ToxcordError::invalid(format!("Profile '{to_name}' already exists")));
+    }
+    Ok(to)
+}
+
+/// Copy a directory recursively, skipping the entries `skip` names
+fn copy_dir(from: &Path, to: &Path, skip: &[&str]) -> std::io::Result<()> {
+    std::fs::create_dir_all(to)?;
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        if skip.iter().any(|s| name == *s) {
+            continue;
+        }
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &to.join(&name), &[])?;
+        } else {
+            std::fs::copy(entry.path(), to.join(&name))?;
+        }
+    }
+    Ok(())
 }
 
 #[tauri::command]
-pub async fn list_profiles() -> Result<Vec<String>, String> {
+pub async fn list_profiles() -> CommandResult<Vec<String>> {
     Ok(ToxManager::list_profiles())
 }
 
@@ -25,45 +68,112 @@ pub async fn list_profiles() -> Result<Vec<String>, String> {
 pub async fn delete_profile(
     state: State<'_, AppState>,
     profile_name: String,
-) -> Result<(), String> {
+) -> CommandResult<()> {
     // Make sure we're not deleting a currently loaded profile
     {
         let guard = state.tox_manager.lock().await;
         if guard.is_some() {
-            return Err("Cannot delete profile while logged in. Please logout first.".to_string());
+            return Err(ToxcordError::invalid("Cannot delete profile while logged in. Please logout first."));
         }
     }
 
-    // Delete the .tox profile file
-    let profile_dir = dirs::data_dir()
-        .unwrap_or_else(|| std::path::PathBuf::from("."))
-        .join("toxcord")
-        .join("profiles");
+    validate_profile_name(&profile_name)?;
+    let profile = ProfileDir::new(&profile_name);
+    if !profile.exists() {
+        return Err(ToxcordError::not_found(format!("Profile '{profile_name}' not found")));
+    }
 
-    let tox_path = profile_dir.join(format!("{profile_name}.tox"));
-    let db_path = profile_dir.join(format!("{profile_name}.db"));
+    // Savedata, messages, attachments, cache and logs all go with the directory
+    std::fs::remove_dir_all(&profile.dir).map_err(|e| format!("Failed to delete profile: {e}"))?;
 
-    // Check if profile exists
-    if !tox_path.exists() {
-        return Err(format!("Profile '{profile_name}' not found"));
-    }
+    Ok(())
+}
 
-    // Delete the .tox file
-    if let Err(e) = std::fs::remove_file(&tox_path) {
-        return Err(format!("Failed to delete profile: {e}"));
+/// Give a profile a new name. Only while logged out.
+#[tauri::command]
+pub async fn rename_profile(state: State<'_, AppState>, profile_name: String, new_name: String) -> CommandResult<()> {
+    if state.tox_manager.lock().await.is_some() {
+        return Err(ToxcordError::invalid("Cannot rename a profile while logged in. Please logout first."));
     }
+    validate_profile_name(&profile_name)?;
+    let from = ProfileDir::new(&profile_name);
+    let to = check_profile_target(&from, new_name.trim())?;
 
-    // Delete the database file if it exists
-    if db_path.exists() {
-        if let Err(e) = std::fs::remove_file(&db_path) {
-            tracing::warn!("Failed to delete profile database: {e}");
-            // Don't fail the whole operation if DB deletion fails
+    // Stored attachment paths are fixed up when the profile next loads
+    std::fs::rename(&from.dir, &to.dir).map_err(|e| format!("Failed to rename profile: {e}"))?;
+    tracing::info!("Renamed profile '{profile_name}' to '{}'", new_name.trim());
+    Ok(())
+}
+
+/// Copy a profile, with its messages and attachments, under a new name. The
+/// copy is the same Tox identity, so only one of the two should be online at
+/// a time.
+#[tauri::command]
+pub async fn duplicate_profile(state: State<'_, AppState>, profile_name: String, new_name: String) -> CommandResult<()> {
+    // A loaded database may have writes not yet in its main file
+    if state.tox_manager.lock().await.is_some() {
+        return Err(ToxcordError::invalid("Cannot copy a profile while logged in. Please logout first."));
+    }
+    validate_profile_name(&profile_name)?;
+    let from = ProfileDir::new(&profile_name);
+    let to = check_profile_target(&from, new_name.trim())?;
+
+    // Logs stay with the original; the cache refills on its own
+    let copied = tokio::task::spawn_blocking({
+        let (from, to) = (from.dir.clone(), to.dir.clone());
+        move || copy_dir(&from, &to, &[profile_dirs::LOGS_DIR, profile_dirs::ASSETS_DIR])
+    })
+    .await
+    .map_err(|e| format!("Failed to copy profile: {e}"))?;
+    if let Err(e) = copied {
+        if let Err(e) = std::fs::remove_dir_all(&to.dir) {
+            tracing::warn!("Failed to clean up partial profile copy: {e}");
         }
+        return Err(ToxcordError::Other(format!("Failed to copy profile: {e}")));
     }
-
+    tracing::info!("Copied profile '{profile_name}' to '{}'", new_name.trim());
     Ok(())
 }
 
+/// Where a profile's data lives and how much space each part takes, in bytes
+#[derive(serde::Serialize)]
+pub struct ProfileStorageInfo {
+    pub path: String,
+    pub total_bytes: u64,
+    pub savedata_bytes: u64,
+    pub database_bytes: u64,
+    pub attachments_bytes: u64,
+    pub cache_bytes: u64,
+    pub logs_bytes: u64,
+}
+
+#[tauri::command]
+pub async fn get_profile_storage_info(profile_name: String) -> CommandResult<ProfileStorageInfo> {
+    validate_profile_name(&profile_name)?;
+    let profile = ProfileDir::new(&profile_name);
+    if !profile.exists() {
+        return Err(ToxcordError::not_found(format!("Profile '{profile_name}' not found")));
+    }
+    let info = tokio::task::spawn_blocking(move || {
+        let database_bytes = ["", "-wal", "-shm"]
+            .iter()
+            .map(|suffix| profile_dirs::disk_usage(&profile.dir.join(format!("{}{suffix}", profile_dirs::DATABASE_FILE))))
+            .sum();
+        ProfileStorageInfo {
+            path: profile.dir.to_string_lossy().to_string(),
+            total_bytes: profile_dirs::disk_usage(&profile.dir),
+            savedata_bytes: profile_dirs::disk_usage(&profile.savedata()),
+            database_bytes,
+            attachments_bytes: profile_dirs::disk_usage(&profile.attachments()),
+            cache_bytes: profile_dirs::disk_usage(&profile.assets()),
+            logs_bytes: profile_dirs::disk_usage(&profile.logs()),
+        }
+    })
+    .await
+    .map_err(|e| format!("Failed to measure profile storage: {e}"))?;
+    Ok(info)
+}
+
 #[tauri::command]
 pub async fn create_profile(
     app_handle: tauri::AppHandle,
@@ -71,23 +181,65 @@ pub async fn create_profile(
     profile_name: String,
     password: String,
     display_name: String,
-) -> Result<serde_json::Value, String> {
+) -> CommandResult<serde_json::Value> {
+    start_new_profile(app_handle, &state, profile_name, password, display_name, None).await
+}
+
+/// Create a profile with the identity a recovery phrase was made from. The
+/// Tox ID is back, with a new nospam; friends and guilds have to be added
+/// again.
+#[tauri::command]
+pub async fn restore_profile(
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+    profile_name: String,
+    password: String,
+    display_name: String,
+    recovery_phrase: String,
+) -> CommandResult<serde_json::Value> {
+    let secret_key = recovery_phrase::from_phrase(&recovery_phrase).map_err(ToxcordError::invalid)?;
+    start_new_profile(app_handle, &state, profile_name, password, display_name, Some(secret_key)).await
+}
+
+async fn start_new_profile(
+    app_handle: tauri::AppHandle,
+    state: &AppState,
+    profile_name: String,
+    password: String,
+    display_name: String,
+    secret_key: Option<[u8; 32]>,
+) -> CommandResult<serde_json::Value> {
     {
         let guard = state.tox_manager.lock().await;
         if guard.is_some() {
-            return Err("Already connected to a profile".to_string());
+            return Err(ToxcordError::AlreadyLoggedIn);
         }
     }
+    // A replayed session ends when a real one starts
+    state.event_replay.lock().unwrap().stop_playback();
+
+    validate_profile_name(&profile_name)?;
+    if ProfileDir::new(&profile_name).exists() {
+        return Err(ToxcordError::invalid(format!("Profile '{profile_name}' already exists")));
+    }
 
     // Initialize database
-    let db_path = get_db_path(&profile_name);
+    let db_path = ProfileDir::new(&profile_name).database();
     let store = Arc::new(MessageStore::open(&db_path, &password)?);
+    if let Err(e) = profile_dirs::relocate_stored_paths(&store) {
+        tracing::warn!("{e}");
+    }
+
+    if let Err(e) = crate::logging::open_profile_log(&profile_name) {
+        tracing::warn!("{e}");
+    }
 
     let manager = ToxManager::create_profile(
-        app_handle,
+        app_handle.clone(),
         &profile_name,
         &password,
         &display_name,
+        secret_key,
         store.clone(),
     )?;
 
@@ -104,6 +256,7 @@ pub async fn create_profile(
     // Save profile in DB
     store.upsert_profile(address.as_str(), &profile_info.name, &profile_info.status_message)?;
 
+    *state.bridges.lock().await = Some(BridgeManager::start(app_handle, store.clone(), manager.clone()));
     {
         let mut guard = state.tox_manager.lock().await;
         *guard = Some(manager);
@@ -126,19 +279,30 @@ pub async fn load_profile(
     state: State<'_, AppState>,
     profile_name: String,
     password: String,
-) -> Result<serde_json::Value, String> {
+) -> CommandResult<serde_json::Value> {
     {
         let guard = state.tox_manager.lock().await;
         if guard.is_some() {
-            return Err("Already connected to a profile".to_string());
+            return Err(ToxcordError::AlreadyLoggedIn);
         }
     }
+    // A replayed session ends when a real one starts
+    state.event_replay.lock().unwrap().stop_playback();
 
     // Initialize database
-    let db_path = get_db_path(&profile_name);
+    validate_profile_name(&profile_name)?;
+    let db_path = ProfileDir::new(&profile_name).database();
     let store = Arc::new(MessageStore::open(&db_path, &password)?);
+    // The profile's directory may have been renamed or copied since last time
+    if let Err(e) = profile_dirs::relocate_stored_paths(&store) {
+        tracing::warn!("{e}");
+    }
 
-    let manager = ToxManager::load_profile(app_handle, &profile_name, &password, store.clone())?;
+    if let Err(e) = crate::logging::open_profile_log(&profile_name) {
+        tracing::warn!("{e}");
+    }
+
+    let manager = ToxManager::load_profile(app_handle.clone(), &profile_name, &password, store.clone())?;
 
     let address = {
         let mgr = manager.lock().await;
@@ -152,6 +316,7 @@ pub async fn load_profile(
 
     store.upsert_profile(address.as_str(), &profile_info.name, &profile_info.status_message)?;
 
+    *state.bridges.lock().await = Some(BridgeManager::start(app_handle, store.clone(), manager.clone()));
     {
         let mut guard = state.tox_manager.lock().await;
         *guard = Some(manager);
@@ -168,19 +333,51 @@ pub async fn load_profile(
     }))
 }
 
+/// Change the profile password: the message database is re-encrypted and the
+/// profile saved with the new one
 #[tauri::command]
-pub async fn get_tox_id(state: State<'_, AppState>) -> Result<String, String> {
+pub async fn change_password(
+    state: State<'_, AppState>,
+    current_password: String,
+    new_password: String,
+) -> CommandResult<()> {
+    if new_password.is_empty() {
+        return Err(ToxcordError::invalid("New password cannot be empty"));
+    }
+    let store = state.message_store.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+    let tox = state.tox_manager.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+    let mgr = tox.lock().await;
+    mgr.verify_password(&current_password)?;
+
+    // Without a password the database was never encrypted; that happens on the next login
+    let encrypted = !current_password.is_empty();
+    if encrypted {
+        store.rekey(&new_password)?;
+    }
+    if let Err(e) = mgr.change_password(&new_password).await {
+        if encrypted {
+            if let Err(e) = store.rekey(&current_password) {
+                tracing::error!("Failed to restore database key: {e}");
+            }
+        }
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_tox_id(state: State<'_, AppState>) -> CommandResult<String> {
     let guard = state.tox_manager.lock().await;
-    let manager = guard.as_ref().ok_or("Not connected")?;
+    let manager = guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
     let mgr = manager.lock().await;
     let address = mgr.get_address().await?;
     Ok(address.to_string())
 }
 
 #[tauri::command]
-pub async fn get_connection_status(state: State<'_, AppState>) -> Result<serde_json::Value, String> {
+pub async fn get_connection_status(state: State<'_, AppState>) -> CommandResult<serde_json::Value> {
     let guard = state.tox_manager.lock().await;
-    let manager = guard.as_ref().ok_or("Not connected")?;
+    let manager = guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
     let mgr = manager.lock().await;
     let status = mgr.get_connection_status().await?;
     Ok(serde_json::json!({
@@ -194,9 +391,9 @@ pub async fn get_connection_status(state: State<'_, AppState>) -> Result<serde_j
 }
 
 #[tauri::command]
-pub async fn get_profile_info(state: State<'_, AppState>) -> Result<serde_json::Value, String> {
+pub async fn get_profile_info(state: State<'_, AppState>) -> CommandResult<serde_json::Value> {
     let guard = state.tox_manager.lock().await;
-    let manager = guard.as_ref().ok_or("Not connected")?;
+    let manager = guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
     let mgr = manager.lock().await;
     let info = mgr.get_profile_info().await?;
     Ok(serde_json::json!({
@@ -210,30 +407,214 @@ pub async fn get_profile_info(state: State<'_, AppState>) -> Result<serde_json::
 pub async fn set_display_name(
     state: State<'_, AppState>,
     name: String,
-) -> Result<(), String> {
+) -> CommandResult<()> {
     let guard = state.tox_manager.lock().await;
-    let manager = guard.as_ref().ok_or("Not connected")?;
+    let manager = guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
     let mgr = manager.lock().await;
     let (tx, rx) = oneshot::channel();
     mgr.send_command(ToxCommand::SetName(name, tx)).await?;
-    rx.await.map_err(|_| "Failed to receive response".to_string())?
+    rx.await.map_err(|_| "Failed to receive response".to_string())??;
+    drop(mgr);
+    drop(guard);
+    crate::commands::friends::refresh_lan_announcement(&state).await;
+    Ok(())
 }
 
 #[tauri::command]
 pub async fn set_status_message(
     state: State<'_, AppState>,
     message: String,
-) -> Result<(), String> {
+) -> CommandResult<()> {
     let guard = state.tox_manager.lock().await;
-    let manager = guard.as_ref().ok_or("Not connected")?;
+    let manager = guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
     let mgr = manager.lock().await;
     let (tx, rx) = oneshot::channel();
     mgr.send_command(ToxCommand::SetStatusMessage(message, tx)).await?;
-    rx.await.map_err(|_| "Failed to receive response".to_string())?
+    Ok(rx.await.map_err(|_| "Failed to receive response".to_string())??)
+}
+
+#[tauri::command]
+pub async fn set_user_status(
+    state: State<'_, AppState>,
+    status: String,
+) -> CommandResult<()> {
+    let status = match status.as_str() {
+        "online" => toxcord_tox::UserStatus::None,
+        "away" => toxcord_tox::UserStatus::Away,
+        "busy" => toxcord_tox::UserStatus::Busy,
+        other => return Err(ToxcordError::invalid(format!("Unknown status: {other}"))),
+    };
+    let guard = state.tox_manager.lock().await;
+    let manager = guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
+    let mgr = manager.lock().await;
+    Ok(mgr.set_status(status).await?)
+}
+
+/// Set what we're doing ("Playing X"), shown to friends and guild peers
+#[tauri::command]
+pub async fn set_activity(
+    state: State<'_, AppState>,
+    kind: ActivityKind,
+    name: String,
+    details: Option<String>,
+) -> CommandResult<Activity> {
+    let activity = Activity {
+        kind,
+        name: name.trim().to_string(),
+        details: details.map(|d| d.trim().to_string()).filter(|d| !d.is_empty()),
+        started_at: Some(chrono::Utc::now().timestamp()),
+    };
+    activity.validate()?;
+    send_activity(&state, Some(activity.clone())).await?;
+    Ok(activity)
+}
+
+#[tauri::command]
+pub async fn clear_activity(state: State<'_, AppState>) -> CommandResult<()> {
+    Ok(send_activity(&state, None).await?)
+}
+
+#[tauri::command]
+pub async fn get_activity(state: State<'_, AppState>) -> CommandResult<Option<Activity>> {
+    let guard = state.tox_manager.lock().await;
+    let manager = guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
+    let mgr = manager.lock().await;
+    Ok(mgr.rich_presence().own_activity())
+}
+
+async fn send_activity(state: &AppState, activity: Option<Activity>) -> CommandResult<()> {
+    let guard = state.tox_manager.lock().await;
+    let manager = guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
+    let mgr = manager.lock().await;
+    let (tx, rx) = oneshot::channel();
+    mgr.send_command(ToxCommand::SetActivity(activity, tx)).await?;
+    Ok(rx.await.map_err(|_| "Failed to receive response".to_string())?)
+}
+
+/// Set our custom status (emoji and text), optionally clearing itself at
+/// `expires_at` (Unix seconds)
+#[tauri::command]
+pub async fn set_custom_status(
+    state: State<'_, AppState>,
+    emoji: Option<String>,
+    text: String,
+    expires_at: Option<i64>,
+) -> CommandResult<CustomStatus> {
+    if expires_at.is_some_and(|t| t <= chrono::Utc::now().timestamp()) {
+        return Err(ToxcordError::invalid("Expiry time is in the past"));
+    }
+    let status = CustomStatus {
+        emoji: emoji.map(|e| e.trim().to_string()).filter(|e| !e.is_empty()),
+        text: text.trim().to_string(),
+        expires_at,
+    };
+    status.validate()?;
+    send_custom_status(&state, Some(status.clone())).await?;
+    Ok(status)
+}
+
+#[tauri::command]
+pub async fn clear_custom_status(state: State<'_, AppState>) -> CommandResult<()> {
+    Ok(send_custom_status(&state, None).await?)
+}
+
+#[tauri::command]
+pub async fn get_custom_status(state: State<'_, AppState>) -> CommandResult<Option<CustomStatus>> {
+    let guard = state.tox_manager.lock().await;
+    let manager = guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
+    let mgr = manager.lock().await;
+    Ok(mgr.rich_presence().own_custom_status())
+}
+
+async fn send_custom_status(state: &AppState, status: Option<CustomStatus>) -> CommandResult<()> {
+    let guard = state.tox_manager.lock().await;
+    let manager = guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
+    let mgr = manager.lock().await;
+    let (tx, rx) = oneshot::channel();
+    mgr.send_command(ToxCommand::SetCustomStatus(status, tx)).await?;
+    Ok(rx.await.map_err(|_| "Failed to receive response".to_string())??)
+}
+
+/// Get the nospam part of our Tox ID as 8 hex characters
+#[tauri::command]
+pub async fn get_nospam(state: State<'_, AppState>) -> CommandResult<String> {
+    let guard = state.tox_manager.lock().await;
+    let manager = guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
+    let mgr = manager.lock().await;
+    Ok(format!("{:08X}", mgr.get_nospam().await?))
+}
+
+/// Set the nospam from 8 hex characters and return the new Tox ID.
+/// Friend requests sent to the old Tox ID stop arriving; friends are unaffected.
+#[tauri::command]
+pub async fn set_nospam(state: State<'_, AppState>, nospam: String) -> CommandResult<String> {
+    let nospam = nospam.trim();
+    if nospam.len() != 8 {
+        return Err(ToxcordError::invalid("Nospam must be 8 hex characters"));
+    }
+    let nospam = u32::from_str_radix(nospam, 16).map_err(|_| "Nospam must be 8 hex characters".to_string())?;
+    Ok(change_nospam(&state, nospam).await?)
+}
+
+/// Rotate to a random nospam and return the new Tox ID
+#[tauri::command]
+pub async fn randomize_nospam(state: State<'_, AppState>) -> CommandResult<String> {
+    // The first four bytes of a v4 UUID are fully random
+    let bytes = uuid::Uuid::new_v4().into_bytes();
+    let nospam = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    Ok(change_nospam(&state, nospam).await?)
+}
+
+/// Our previous Tox IDs, most recent change first
+#[tauri::command]
+pub async fn get_nospam_history(state: State<'_, AppState>) -> CommandResult<Vec<NospamChangeRecord>> {
+    let store_guard = state.message_store.lock().await;
+    let store = store_guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
+    Ok(store.get_nospam_history()?)
+}
+
+/// The profile's recovery phrase, with a QR code of it for printing
+#[derive(serde::Serialize)]
+pub struct RecoveryPhrase {
+    /// 24 words, separated by spaces
+    pub phrase: String,
+    /// SVG QR code of the phrase
+    pub qr_svg: String,
+}
+
+/// The recovery phrase for our identity. It's the secret key itself, so the
+/// password is asked for again before it's shown.
+#[tauri::command]
+pub async fn get_recovery_phrase(state: State<'_, AppState>, password: String) -> CommandResult<RecoveryPhrase> {
+    let guard = state.tox_manager.lock().await;
+    let manager = guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
+    let mgr = manager.lock().await;
+    mgr.verify_password(&password).map_err(|_| ToxcordError::WrongPassword)?;
+
+    let phrase = recovery_phrase::to_phrase(&mgr.get_secret_key().await?);
+    let qr_svg = QrCode::new(phrase.as_bytes())
+        .map_err(|e| format!("Failed to generate QR code: {e}"))?
+        .render::<svg::Color>()
+        .min_dimensions(256, 256)
+        .build();
+    Ok(RecoveryPhrase { phrase, qr_svg })
+}
+
+async fn change_nospam(state: &AppState, nospam: u32) -> CommandResult<String> {
+    let address = {
+        let guard = state.tox_manager.lock().await;
+        let manager = guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
+        let mgr = manager.lock().await;
+        mgr.set_nospam(nospam).await?.to_string()
+    };
+    crate::commands::friends::refresh_lan_announcement(state).await;
+    Ok(address)
 }
 
 #[tauri::command]
-pub async fn logout(state: State<'_, AppState>) -> Result<(), String> {
+pub async fn logout(app_handle: tauri::AppHandle, state: State<'_, AppState>) -> CommandResult<()> {
+    state.lan_discovery.lock().await.take();
+    state.bridges.lock().await.take();
     {
         let mut guard = state.tox_manager.lock().await;
         if let Some(manager) = guard.take() {
@@ -245,5 +626,7 @@ pub async fn logout(state: State<'_, AppState>) -> Result<(), String> {
         let mut guard = state.message_store.lock().await;
         *guard = None;
     }
+    crate::logging::close_profile_log();
+    crate::tray::set_unread_count(&app_handle, 0);
     Ok(())
 }
@@ -3,23 +3,67 @@
 use tauri::State;
 
 use crate::audio::{AudioCapture, AudioDevice, AudioPlayback};
-use crate::managers::av_manager::CallState;
-use crate::video::{ScreenCapture, ScreenInfo, VideoCapture, VideoDevice};
+use crate::error::{CommandResult, ToxcordError};
+use crate::managers::av_manager::{CallState, VideoStream};
+use crate::db::message_store::{CallPreferences, MissedCallRecord};
+use crate::managers::tox_manager::{DND_AUTO_REPLY_SETTING, MISSED_CALL_REPLY_SETTING};
+use crate::pip;
+use crate::settings::VideoSettings;
+use crate::video::background::BackgroundMode;
+use crate::video::preview::CameraPreview;
+use crate::video::{ScreenCapture, ScreenInfo, ScreenShareOptions, VideoCapture, VideoDevice};
 use crate::AppState;
 
-/// Start a call with a friend
+/// Audio bitrates Opus accepts, in kbit/s
+const AUDIO_BIT_RATE_RANGE: std::ops::RangeInclusive<u32> = 6..=510;
+
+/// Video bitrates offered, in kbit/s
+const VIDEO_BIT_RATE_RANGE: std::ops::RangeInclusive<u32> = 100..=10_000;
+
+/// Call quality for a friend: their own preferences, or the app-wide default
+pub async fn call_preferences(state: &AppState, friend_number: u32) -> Result<CallPreferences, String> {
+    let default = state.settings.lock().await.call_quality;
+    let Some(store) = state.message_store.lock().await.clone() else {
+        return Ok(default);
+    };
+    Ok(store.get_call_preferences(friend_number)?.unwrap_or(default))
+}
+
+fn validate_call_preferences(preferences: &CallPreferences) -> CommandResult<()> {
+    if !AUDIO_BIT_RATE_RANGE.contains(&preferences.audio_bit_rate) {
+        return Err(ToxcordError::invalid(format!(
+            "Audio bitrate must be between {} and {} kbit/s",
+            AUDIO_BIT_RATE_RANGE.start(),
+            AUDIO_BIT_RATE_RANGE.end()
+        )));
+    }
+    if !VIDEO_BIT_RATE_RANGE.contains(&preferences.video_bit_rate) {
+        return Err(ToxcordError::invalid(format!(
+            "Video bitrate must be between {} and {} kbit/s",
+            VIDEO_BIT_RATE_RANGE.start(),
+            VIDEO_BIT_RATE_RANGE.end()
+        )));
+    }
+    Ok(())
+}
+
+/// Start a call with a friend. Without `with_video`, the camera follows the
+/// friend's call preferences.
 #[tauri::command]
 pub async fn call_friend(
     state: State<'_, AppState>,
     friend_number: u32,
-    with_video: bool,
-) -> Result<(), String> {
+    with_video: Option<bool>,
+) -> CommandResult<()> {
+    let quality = call_preferences(&state, friend_number).await?;
+    let with_video = with_video.unwrap_or(quality.video_by_default);
+
     // Get the ToxAV manager and initiate call
     let tox_guard = state.tox_manager.lock().await;
-    let tox = tox_guard.as_ref().ok_or("Not logged in")?;
+    let tox = tox_guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
 
     let mgr = tox.lock().await;
-    mgr.call(friend_number, with_video).await?;
+    mgr.call(friend_number, with_video, quality).await?;
 
     Ok(())
 }
@@ -30,12 +74,13 @@ pub async fn answer_call(
     state: State<'_, AppState>,
     friend_number: u32,
     with_video: bool,
-) -> Result<(), String> {
+) -> CommandResult<()> {
+    let quality = call_preferences(&state, friend_number).await?;
     let tox_guard = state.tox_manager.lock().await;
-    let tox = tox_guard.as_ref().ok_or("Not logged in")?;
+    let tox = tox_guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
 
     let mgr = tox.lock().await;
-    mgr.answer(friend_number, with_video).await?;
+    mgr.answer(friend_number, with_video, quality).await?;
 
     Ok(())
 }
@@ -43,14 +88,16 @@ pub async fn answer_call(
 /// Hangup/reject a call
 #[tauri::command]
 pub async fn hangup_call(
+    app_handle: tauri::AppHandle,
     state: State<'_, AppState>,
     friend_number: u32,
-) -> Result<(), String> {
+) -> CommandResult<()> {
     let tox_guard = state.tox_manager.lock().await;
-    let tox = tox_guard.as_ref().ok_or("Not logged in")?;
+    let tox = tox_guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
 
     let mgr = tox.lock().await;
     mgr.hangup(friend_number).await?;
+    pip::close(&app_handle);
 
     Ok(())
 }
@@ -61,9 +108,9 @@ pub async fn toggle_mute(
     state: State<'_, AppState>,
     friend_number: u32,
     muted: bool,
-) -> Result<(), String> {
+) -> CommandResult<()> {
     let tox_guard = state.tox_manager.lock().await;
-    let tox = tox_guard.as_ref().ok_or("Not logged in")?;
+    let tox = tox_guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
 
     let mgr = tox.lock().await;
     if muted {
@@ -75,15 +122,41 @@ pub async fn toggle_mute(
     Ok(())
 }
 
+/// Mute/unmute the microphone across all calls
+#[tauri::command]
+pub async fn set_global_mute(
+    state: State<'_, AppState>,
+    muted: bool,
+) -> CommandResult<()> {
+    let tox_guard = state.tox_manager.lock().await;
+    let tox = tox_guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
+
+    let mgr = tox.lock().await;
+    Ok(mgr.set_global_mute(muted).await?)
+}
+
+/// Deafen/undeafen incoming audio across all calls
+#[tauri::command]
+pub async fn set_global_deafen(
+    state: State<'_, AppState>,
+    deafened: bool,
+) -> CommandResult<()> {
+    let tox_guard = state.tox_manager.lock().await;
+    let tox = tox_guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
+
+    let mgr = tox.lock().await;
+    Ok(mgr.set_global_deafen(deafened).await?)
+}
+
 /// Toggle video for a call
 #[tauri::command]
 pub async fn toggle_video(
     state: State<'_, AppState>,
     friend_number: u32,
     enabled: bool,
-) -> Result<(), String> {
+) -> CommandResult<()> {
     let tox_guard = state.tox_manager.lock().await;
-    let tox = tox_guard.as_ref().ok_or("Not logged in")?;
+    let tox = tox_guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
 
     let mgr = tox.lock().await;
     if enabled {
@@ -100,30 +173,227 @@ pub async fn toggle_video(
 pub async fn get_call_state(
     state: State<'_, AppState>,
     friend_number: u32,
-) -> Result<Option<CallState>, String> {
+) -> CommandResult<Option<CallState>> {
     let tox_guard = state.tox_manager.lock().await;
-    let tox = tox_guard.as_ref().ok_or("Not logged in")?;
+    let tox = tox_guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
 
     let mgr = tox.lock().await;
     Ok(mgr.get_call_state(friend_number).await)
 }
 
+/// List the video streams being received, for tiling
+#[tauri::command]
+pub async fn list_video_streams(state: State<'_, AppState>) -> CommandResult<Vec<VideoStream>> {
+    let tox = state.tox_manager.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+    let streams = tox.lock().await.list_video_streams().await?;
+    Ok(streams)
+}
+
+/// Show a peer's video again after `unsubscribe_video_stream`
+#[tauri::command]
+pub async fn subscribe_video_stream(state: State<'_, AppState>, friend_number: u32) -> CommandResult<()> {
+    let tox = state.tox_manager.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+    tox.lock().await.set_stream_subscribed(friend_number, true).await?;
+    Ok(())
+}
+
+/// Stop converting and serving a peer's video, e.g. when its tile is hidden
+#[tauri::command]
+pub async fn unsubscribe_video_stream(state: State<'_, AppState>, friend_number: u32) -> CommandResult<()> {
+    let tox = state.tox_manager.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+    tox.lock().await.set_stream_subscribed(friend_number, false).await?;
+    Ok(())
+}
+
+/// Open the picture-in-picture window for a call, or close it if it's open.
+/// Returns whether it's open afterwards.
+#[tauri::command]
+pub async fn toggle_call_pip(
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+    friend_number: u32,
+) -> CommandResult<bool> {
+    if pip::is_open(&app_handle) {
+        pip::close(&app_handle);
+        return Ok(false);
+    }
+
+    let in_call = match state.tox_manager.lock().await.as_ref() {
+        Some(tox) => tox.lock().await.get_call_state(friend_number).await.is_some(),
+        None => false,
+    };
+    if !in_call {
+        return Err(ToxcordError::invalid("Not in a call with this friend"));
+    }
+
+    let position = state.settings.lock().await.pip_position;
+    pip::open(&app_handle, friend_number, position)?;
+    Ok(true)
+}
+
+#[derive(serde::Serialize)]
+pub struct DoNotDisturbInfo {
+    pub enabled: bool,
+    /// Sent to callers at most once an hour while Do Not Disturb is on
+    pub auto_reply: String,
+}
+
+/// Turn Do Not Disturb on or off. It's tied to the Busy status, so this
+/// switches between Busy and Online.
+#[tauri::command]
+pub async fn set_do_not_disturb(state: State<'_, AppState>, enabled: bool) -> CommandResult<()> {
+    let tox_guard = state.tox_manager.lock().await;
+    let tox = tox_guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
+
+    let mgr = tox.lock().await;
+    let status = if enabled {
+        toxcord_tox::UserStatus::Busy
+    } else {
+        toxcord_tox::UserStatus::None
+    };
+    Ok(mgr.set_status(status).await?)
+}
+
+#[tauri::command]
+pub async fn get_do_not_disturb(state: State<'_, AppState>) -> CommandResult<DoNotDisturbInfo> {
+    let enabled = {
+        let tox_guard = state.tox_manager.lock().await;
+        let tox = tox_guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
+        let mgr = tox.lock().await;
+        mgr.get_profile_info().await?.status == toxcord_tox::UserStatus::Busy
+    };
+
+    let store_guard = state.message_store.lock().await;
+    let store = store_guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
+    Ok(DoNotDisturbInfo {
+        enabled,
+        auto_reply: store.get_setting(DND_AUTO_REPLY_SETTING)?.unwrap_or_default(),
+    })
+}
+
+/// Set the message sent to declined callers. An empty message turns the auto-reply off.
+#[tauri::command]
+pub async fn set_dnd_auto_reply(state: State<'_, AppState>, message: String) -> CommandResult<()> {
+    let store_guard = state.message_store.lock().await;
+    let store = store_guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
+    Ok(store.set_setting(DND_AUTO_REPLY_SETTING, message.trim())?)
+}
+
+// ─── Missed Calls ─────────────────────────────────────────────────────────
+
+#[derive(serde::Serialize)]
+pub struct MissedCallCount {
+    pub friend_number: u32,
+    pub count: i64,
+}
+
+/// Missed calls, newest first, from one friend or everyone
+#[tauri::command]
+pub async fn get_missed_calls(
+    state: State<'_, AppState>,
+    friend_number: Option<u32>,
+    limit: Option<i64>,
+) -> CommandResult<Vec<MissedCallRecord>> {
+    let store = state.message_store.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+    let limit = limit.unwrap_or(50);
+    Ok(store.call(move |store| store.get_missed_calls(friend_number, limit)).await?)
+}
+
+/// Unseen missed calls per friend, for badges
+#[tauri::command]
+pub async fn get_missed_call_counts(state: State<'_, AppState>) -> CommandResult<Vec<MissedCallCount>> {
+    let store = state.message_store.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+    let counts = store.call(|store| store.get_unseen_missed_call_counts()).await?;
+    Ok(counts
+        .into_iter()
+        .map(|(friend_number, count)| MissedCallCount {
+            friend_number: friend_number as u32,
+            count,
+        })
+        .collect())
+}
+
+#[tauri::command]
+pub async fn mark_missed_calls_seen(state: State<'_, AppState>, friend_number: u32) -> CommandResult<()> {
+    let store = state.message_store.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+    Ok(store.mark_missed_calls_seen(friend_number)?)
+}
+
+#[tauri::command]
+pub async fn get_missed_call_reply(state: State<'_, AppState>) -> CommandResult<String> {
+    let store = state.message_store.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+    Ok(store.get_setting(MISSED_CALL_REPLY_SETTING)?.unwrap_or_default())
+}
+
+/// Set the message sent to callers whose call rang out. An empty message turns it off.
+#[tauri::command]
+pub async fn set_missed_call_reply(state: State<'_, AppState>, message: String) -> CommandResult<()> {
+    let store = state.message_store.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+    Ok(store.set_setting(MISSED_CALL_REPLY_SETTING, message.trim())?)
+}
+
+/// A friend's call preferences, or None if they use the defaults
+#[tauri::command]
+pub async fn get_call_preferences(
+    state: State<'_, AppState>,
+    friend_number: u32,
+) -> CommandResult<Option<CallPreferences>> {
+    let store = state.message_store.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+    Ok(store.get_call_preferences(friend_number)?)
+}
+
+/// Set the quality of calls with a friend. Applies from the next call.
+#[tauri::command]
+pub async fn set_call_preferences(
+    state: State<'_, AppState>,
+    friend_number: u32,
+    preferences: CallPreferences,
+) -> CommandResult<()> {
+    validate_call_preferences(&preferences)?;
+    let store = state.message_store.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+    Ok(store.set_call_preferences(friend_number, &preferences)?)
+}
+
+/// Go back to the default call quality for a friend
+#[tauri::command]
+pub async fn clear_call_preferences(state: State<'_, AppState>, friend_number: u32) -> CommandResult<()> {
+    let store = state.message_store.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+    Ok(store.clear_call_preferences(friend_number)?)
+}
+
+/// Call quality used for friends without their own preferences
+#[tauri::command]
+pub async fn get_default_call_preferences(state: State<'_, AppState>) -> CommandResult<CallPreferences> {
+    Ok(state.settings.lock().await.call_quality)
+}
+
+#[tauri::command]
+pub async fn set_default_call_preferences(
+    state: State<'_, AppState>,
+    preferences: CallPreferences,
+) -> CommandResult<()> {
+    validate_call_preferences(&preferences)?;
+    let mut settings = state.settings.lock().await;
+    settings.call_quality = preferences;
+    Ok(settings.save()?)
+}
+
 /// List available audio input devices
 #[tauri::command]
-pub fn list_audio_input_devices() -> Result<Vec<AudioDevice>, String> {
-    AudioCapture::list_devices().map_err(|e| e.to_string())
+pub fn list_audio_input_devices() -> CommandResult<Vec<AudioDevice>> {
+    Ok(AudioCapture::list_devices()?)
 }
 
 /// List available audio output devices
 #[tauri::command]
-pub fn list_audio_output_devices() -> Result<Vec<AudioDevice>, String> {
-    AudioPlayback::list_devices().map_err(|e| e.to_string())
+pub fn list_audio_output_devices() -> CommandResult<Vec<AudioDevice>> {
+    Ok(AudioPlayback::list_devices()?)
 }
 
 /// List available video input devices (cameras)
 #[tauri::command]
-pub fn list_video_devices() -> Result<Vec<VideoDevice>, String> {
-    VideoCapture::list_devices().map_err(|e| e.to_string())
+pub fn list_video_devices() -> CommandResult<Vec<VideoDevice>> {
+    Ok(VideoCapture::list_devices()?)
 }
 
 /// Set the selected microphone device
@@ -131,22 +401,29 @@ pub fn list_video_devices() -> Result<Vec<VideoDevice>, String> {
 pub async fn set_audio_input_device(
     state: State<'_, AppState>,
     device_id: String,
-) -> Result<(), String> {
+) -> CommandResult<()> {
     let index = device_id.parse::<u32>().ok();
     *state.selected_mic_index.lock().await = index;
     tracing::info!("Selected microphone device index: {:?}", index);
     Ok(())
 }
 
-/// Set the selected speaker device
+/// Set the selected speaker device. During a call, playback moves to it
+/// right away.
 #[tauri::command]
 pub async fn set_audio_output_device(
     state: State<'_, AppState>,
     device_id: String,
-) -> Result<(), String> {
-    let index = device_id.parse::<u32>().ok();
-    *state.selected_speaker_index.lock().await = index;
-    tracing::info!("Selected speaker device index: {:?}", index);
+) -> CommandResult<()> {
+    // Device ids are names; an empty one means the system default
+    let device = Some(device_id).filter(|id| !id.is_empty());
+    *state.selected_speaker.lock().await = device.clone();
+    tracing::info!("Selected speaker device: {:?}", device);
+
+    let tox = state.tox_manager.lock().await.clone();
+    if let Some(tox) = tox {
+        tox.lock().await.switch_audio_output(device).await?;
+    }
     Ok(())
 }
 
@@ -155,13 +432,107 @@ pub async fn set_audio_output_device(
 pub async fn set_video_device(
     state: State<'_, AppState>,
     device_id: String,
-) -> Result<(), String> {
+) -> CommandResult<()> {
     let index = device_id.parse::<u32>().ok();
     *state.selected_camera_index.lock().await = index;
     tracing::info!("Selected camera device index: {:?}", index);
     Ok(())
 }
 
+/// Switch to another camera, mid-call if there is one. Unlike
+/// `set_video_device` this moves a running capture (or preview) over right away.
+#[tauri::command]
+pub async fn switch_camera(
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+    device_id: String,
+) -> CommandResult<()> {
+    let index = device_id.parse::<u32>().ok();
+    *state.selected_camera_index.lock().await = index;
+
+    let tox = state.tox_manager.lock().await.clone();
+    if let Some(tox) = tox {
+        tox.lock().await.switch_camera(index).await?;
+    }
+
+    let mut preview = state.camera_preview.lock().map_err(|e| e.to_string())?;
+    if preview.take().is_some() {
+        *preview = Some(CameraPreview::start(
+            app_handle,
+            index,
+            state.video_background.clone(),
+            state.video_frames.clone(),
+        )?);
+    }
+    Ok(())
+}
+
+/// Get the video output settings
+#[tauri::command]
+pub async fn get_video_settings(state: State<'_, AppState>) -> CommandResult<VideoSettings> {
+    Ok(state.settings.lock().await.video.clone())
+}
+
+/// Set the largest size incoming video is drawn at. Video is scaled down to
+/// fit, keeping its aspect ratio.
+#[tauri::command]
+pub async fn set_video_max_output(
+    state: State<'_, AppState>,
+    width: u16,
+    height: u16,
+) -> CommandResult<()> {
+    if width < 160 || height < 120 {
+        return Err(ToxcordError::invalid("Video output must be at least 160x120"));
+    }
+    {
+        let mut settings = state.settings.lock().await;
+        settings.video.max_output_width = width;
+        settings.video.max_output_height = height;
+        settings.save()?;
+    }
+    state.video_frames.set_max_output(width, height);
+    Ok(())
+}
+
+/// Set the effect applied to our camera video. Takes effect on the next frame.
+#[tauri::command]
+pub async fn set_video_background_mode(state: State<'_, AppState>, mode: BackgroundMode) -> CommandResult<()> {
+    {
+        let mut settings = state.settings.lock().await;
+        settings.video.background_mode = mode;
+        settings.save()?;
+    }
+    state.video_background.set(mode);
+    Ok(())
+}
+
+/// Show the selected camera without a call, e.g. to check framing before
+/// joining one. Frames arrive like the local video of a call.
+#[tauri::command]
+pub async fn start_camera_preview(
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> CommandResult<()> {
+    let device_index = *state.selected_camera_index.lock().await;
+    let mut preview = state.camera_preview.lock().map_err(|e| e.to_string())?;
+    // Restart so a newly selected camera is used
+    *preview = None;
+    *preview = Some(CameraPreview::start(
+        app_handle,
+        device_index,
+        state.video_background.clone(),
+        state.video_frames.clone(),
+    )?);
+    Ok(())
+}
+
+/// Stop the camera preview
+#[tauri::command]
+pub async fn stop_camera_preview(state: State<'_, AppState>) -> CommandResult<()> {
+    state.camera_preview.lock().map_err(|e| e.to_string())?.take();
+    Ok(())
+}
+
 /// Camera status for diagnostics
 #[derive(serde::Serialize)]
 pub struct CameraStatus {
@@ -248,7 +619,7 @@ pub fn check_camera_status() -> CameraStatus {
 
 /// Try to load the UVC video driver (requires pkexec for graphical sudo)
 #[tauri::command]
-pub async fn load_camera_driver() -> Result<(), String> {
+pub async fn load_camera_driver() -> CommandResult<()> {
     #[cfg(target_os = "linux")]
     {
         use std::process::Command;
@@ -268,13 +639,13 @@ pub async fn load_camera_driver() -> Result<(), String> {
         } else {
             let stderr = String::from_utf8_lossy(&output.stderr);
             tracing::warn!("Failed to load driver: {}", stderr);
-            Err(format!("Failed to load camera driver: {}", stderr))
+            Err(ToxcordError::Other(format!("Failed to load camera driver: {}", stderr)))
         }
     }
 
     #[cfg(not(target_os = "linux"))]
     {
-        Err("Driver loading only supported on Linux".to_string())
+        Err(ToxcordError::Other("Driver loading only supported on Linux".to_string()))
     }
 }
 
@@ -282,25 +653,34 @@ pub async fn load_camera_driver() -> Result<(), String> {
 
 /// List available screens for sharing
 #[tauri::command]
-pub fn list_screens() -> Result<Vec<ScreenInfo>, String> {
-    ScreenCapture::list_screens().map_err(|e| e.to_string())
+pub fn list_screens() -> CommandResult<Vec<ScreenInfo>> {
+    Ok(ScreenCapture::list_screens()?)
 }
 
-/// Start screen sharing (replaces camera capture)
+/// Start screen sharing (replaces camera capture). Calling it again while
+/// sharing restarts the share with the new screen and options.
 #[tauri::command]
 pub async fn start_screen_share(
     state: State<'_, AppState>,
     screen_id: Option<u32>,
-) -> Result<(), String> {
-    tracing::info!("Starting screen share with screen_id: {:?}", screen_id);
+    options: Option<ScreenShareOptions>,
+) -> CommandResult<()> {
+    let options = options.unwrap_or_default();
+    if let Some(region) = options.region {
+        if region.width < 2 || region.height < 2 {
+            return Err(ToxcordError::invalid("Shared region is too small"));
+        }
+    }
+    tracing::info!("Starting screen share with screen_id: {:?} ({:?})", screen_id, options);
     *state.screen_share_id.lock().await = screen_id;
+    *state.screen_share_options.lock().await = options;
     *state.is_screen_sharing.lock().await = true;
     Ok(())
 }
 
 /// Stop screen sharing (switch back to camera)
 #[tauri::command]
-pub async fn stop_screen_share(state: State<'_, AppState>) -> Result<(), String> {
+pub async fn stop_screen_share(state: State<'_, AppState>) -> CommandResult<()> {
     tracing::info!("Stopping screen share");
     *state.is_screen_sharing.lock().await = false;
     Ok(())
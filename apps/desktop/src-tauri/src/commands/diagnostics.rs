@@ -0,0 +1,314 @@
+//! Tauri commands for the troubleshooting report, network status, power
+//! saving, crash reports and event recording.
+
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tauri::State;
+use tokio::net::TcpStream;
+use toxcord_tox::tox::{default_bootstrap_nodes, toxcore_version};
+use toxcord_tox::ProxyType;
+
+use crate::audio::{AudioCapture, AudioPlayback};
+use crate::crash::{self, CrashReport};
+use crate::error::{CommandResult, ToxcordError};
+use crate::managers::event_replay::{self, RecordingInfo, ReplayStatus};
+use crate::managers::power_saving::{self, PowerSavingMode};
+use crate::managers::tox_manager::{DhtInfo, NetworkMode, ProxyConfig, ToxDiagnostics};
+use crate::video::VideoCapture;
+use crate::AppState;
+
+/// How long to wait for a bootstrap node to accept a TCP connection
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+#[derive(Serialize)]
+pub struct DiagnosticsReport {
+    pub app_version: String,
+    pub toxcore_version: String,
+    /// None when not logged in
+    pub tox: Option<ToxDiagnostics>,
+    pub proxy: ProxyStatus,
+    pub bootstrap_nodes: Vec<BootstrapNodeStatus>,
+    /// None when not logged in
+    pub database: Option<DatabaseStatus>,
+    pub audio_input: DeviceStatus,
+    pub audio_output: DeviceStatus,
+    pub video_input: DeviceStatus,
+}
+
+#[derive(Serialize)]
+pub struct ProxyStatus {
+    /// "none", "socks5" or "http"
+    pub kind: &'static str,
+    pub host: Option<String>,
+    pub port: u16,
+}
+
+#[derive(Serialize)]
+pub struct BootstrapNodeStatus {
+    pub address: String,
+    pub port: u16,
+    /// Whether a TCP relay port accepted a connection. None when not probed,
+    /// e.g. behind a proxy, where probing directly would bypass it
+    pub reachable: Option<bool>,
+    pub latency_ms: Option<u64>,
+}
+
+#[derive(Serialize)]
+pub struct DatabaseStatus {
+    pub size_bytes: Option<u64>,
+    pub schema_version: Option<i32>,
+}
+
+#[derive(Serialize)]
+pub struct DeviceStatus {
+    pub count: usize,
+    /// Why the devices couldn't be listed
+    pub error: Option<String>,
+}
+
+/// Collect a report of the connection, database and devices for troubleshooting
+#[tauri::command]
+pub async fn get_diagnostics(
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> CommandResult<DiagnosticsReport> {
+    let tox = match state.tox_manager.lock().await.as_ref() {
+        Some(manager) => manager.lock().await.get_diagnostics().await.ok(),
+        None => None,
+    };
+
+    let database = match state.message_store.lock().await.clone() {
+        Some(store) => Some(
+            store
+                .call(|store| {
+                    Ok(DatabaseStatus {
+                        size_bytes: store.database_size().ok(),
+                        schema_version: store.schema_version().ok(),
+                    })
+                })
+                .await?,
+        ),
+        None => None,
+    };
+
+    let proxy_config = ProxyConfig::from_env();
+    let bootstrap_nodes = probe_bootstrap_nodes(proxy_config.proxy_type == ProxyType::None).await;
+
+    // Listing devices can block on the audio and video backends
+    let (audio_input, audio_output, video_input) = tokio::task::spawn_blocking(|| {
+        (
+            device_status(AudioCapture::list_devices().map_err(|e| e.to_string())),
+            device_status(AudioPlayback::list_devices().map_err(|e| e.to_string())),
+            device_status(VideoCapture::list_devices().map_err(|e| e.to_string())),
+        )
+    })
+    .await
+    .map_err(|e| format!("Failed to list devices: {e}"))?;
+
+    Ok(DiagnosticsReport {
+        app_version: app_handle.package_info().version.to_string(),
+        toxcore_version: toxcore_version(),
+        tox,
+        proxy: ProxyStatus {
+            kind: match proxy_config.proxy_type {
+                ProxyType::None => "none",
+                ProxyType::Socks5 => "socks5",
+                ProxyType::Http => "http",
+            },
+            host: proxy_config.host,
+            port: proxy_config.port,
+        },
+        bootstrap_nodes,
+        database,
+        audio_input,
+        audio_output,
+        video_input,
+    })
+}
+
+/// DHT nodes, ports and relays in use, for checking what the connection exposes
+#[tauri::command]
+pub async fn get_dht_info(state: State<'_, AppState>) -> CommandResult<DhtInfo> {
+    let tox = state.tox_manager.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+    let info = tox.lock().await.get_dht_info().await?;
+    Ok(info)
+}
+
+#[tauri::command]
+pub async fn get_network_mode(state: State<'_, AppState>) -> CommandResult<NetworkMode> {
+    Ok(state.settings.lock().await.network_mode)
+}
+
+/// Switch between the public DHT, LAN only and offline. Saved for the next
+/// launch, and applied right away when logged in.
+#[tauri::command]
+pub async fn set_network_mode(state: State<'_, AppState>, mode: NetworkMode) -> CommandResult<()> {
+    {
+        let mut settings = state.settings.lock().await;
+        settings.network_mode = mode;
+        settings.save()?;
+    }
+    if let Some(tox) = state.tox_manager.lock().await.clone() {
+        tox.lock().await.set_network_mode(mode).await?;
+    }
+    Ok(())
+}
+
+/// The power saving mode, and whether we're on battery now
+#[derive(Serialize)]
+pub struct PowerSavingInfo {
+    pub mode: PowerSavingMode,
+    pub on_battery: bool,
+}
+
+#[tauri::command]
+pub async fn get_power_saving(state: State<'_, AppState>) -> CommandResult<PowerSavingInfo> {
+    let mode = state.settings.lock().await.power_saving;
+    let on_battery = tokio::task::spawn_blocking(power_saving::on_battery).await.unwrap_or_default();
+    Ok(PowerSavingInfo { mode, on_battery })
+}
+
+/// Choose when power saving is on. Saved for the next launch, and applied
+/// right away when logged in.
+#[tauri::command]
+pub async fn set_power_saving(state: State<'_, AppState>, mode: PowerSavingMode) -> CommandResult<()> {
+    {
+        let mut settings = state.settings.lock().await;
+        settings.power_saving = mode;
+        settings.save()?;
+    }
+    if let Some(tox) = state.tox_manager.lock().await.clone() {
+        tox.lock().await.set_power_saving(mode).await?;
+    }
+    Ok(())
+}
+
+/// Crash reports from earlier runs that the user hasn't shared or dismissed
+#[tauri::command]
+pub async fn get_pending_crash_reports() -> Vec<CrashReport> {
+    tokio::task::spawn_blocking(crash::pending).await.unwrap_or_default()
+}
+
+/// Share a crash report: it's saved to the downloads folder and a new issue
+/// is opened to attach it to. Returns the saved file's path.
+#[tauri::command]
+pub fn submit_crash_report(id: String) -> CommandResult<String> {
+    Ok(crash::submit(&id)?.to_string_lossy().to_string())
+}
+
+/// Delete a crash report without sharing it
+#[tauri::command]
+pub fn dismiss_crash_report(id: String) -> CommandResult<()> {
+    Ok(crash::dismiss(&id)?)
+}
+
+// ─── Event recording and replay ───
+
+#[tauri::command]
+pub fn get_event_replay_status(state: State<'_, AppState>) -> ReplayStatus {
+    state.event_replay.lock().unwrap().status()
+}
+
+/// Start capturing the events sent to the UI, to replay later
+#[tauri::command]
+pub fn start_event_recording(app_handle: tauri::AppHandle, state: State<'_, AppState>) -> CommandResult<()> {
+    Ok(state.event_replay.lock().unwrap().start_recording(&app_handle)?)
+}
+
+/// Stop capturing and save the recording, returning its path
+#[tauri::command]
+pub fn stop_event_recording(
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+    description: Option<String>,
+) -> CommandResult<String> {
+    let path = state
+        .event_replay
+        .lock()
+        .unwrap()
+        .stop_recording(&app_handle, description.unwrap_or_default())?;
+    Ok(path.to_string_lossy().to_string())
+}
+
+#[tauri::command]
+pub async fn list_event_recordings() -> Vec<RecordingInfo> {
+    tokio::task::spawn_blocking(event_replay::list).await.unwrap_or_default()
+}
+
+/// Play a saved recording to the UI, `speed` times as fast (1 by default).
+/// Only while logged out: there's no Tox instance behind a replay.
+#[tauri::command]
+pub async fn start_event_replay(
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+    name: String,
+    speed: Option<f64>,
+) -> CommandResult<()> {
+    if state.tox_manager.lock().await.is_some() {
+        return Err(ToxcordError::AlreadyLoggedIn);
+    }
+    let script = tokio::task::spawn_blocking(move || event_replay::load(&name))
+        .await
+        .map_err(|e| e.to_string())??;
+    Ok(state
+        .event_replay
+        .lock()
+        .unwrap()
+        .play(app_handle, script, speed.unwrap_or(1.0))?)
+}
+
+#[tauri::command]
+pub fn stop_event_replay(state: State<'_, AppState>) {
+    state.event_replay.lock().unwrap().stop_playback();
+}
+
+fn device_status<T>(devices: Result<Vec<T>, String>) -> DeviceStatus {
+    match devices {
+        Ok(devices) => DeviceStatus {
+            count: devices.len(),
+            error: None,
+        },
+        Err(e) => DeviceStatus {
+            count: 0,
+            error: Some(e),
+        },
+    }
+}
+
+/// Try each node's first TCP relay port, all at once
+async fn probe_bootstrap_nodes(probe: bool) -> Vec<BootstrapNodeStatus> {
+    let probes: Vec<_> = default_bootstrap_nodes()
+        .into_iter()
+        .map(|node| {
+            let tcp_port = node.tcp_ports.first().copied().filter(|_| probe);
+            let address = node.address.clone();
+            let task = tcp_port.map(|port| tokio::spawn(probe_node(address, port)));
+            (node, task)
+        })
+        .collect();
+
+    let mut statuses = Vec::with_capacity(probes.len());
+    for (node, task) in probes {
+        let latency = match task {
+            Some(task) => Some(task.await.ok().flatten()),
+            None => None,
+        };
+        statuses.push(BootstrapNodeStatus {
+            address: node.address,
+            port: node.port,
+            reachable: latency.map(|l| l.is_some()),
+            latency_ms: latency.flatten().map(|l| l.as_millis() as u64),
+        });
+    }
+    statuses
+}
+
+/// Time to connect, or None if the node couldn't be reached
+async fn probe_node(address: String, port: u16) -> Option<Duration> {
+    let started = Instant::now();
+    match tokio::time::timeout(PROBE_TIMEOUT, TcpStream::connect((address.as_str(), port))).await {
+        Ok(Ok(_)) => Some(started.elapsed()),
+        _ => None,
+    }
+}
@@ -0,0 +1,165 @@
+use tauri::State;
+use tokio::sync::oneshot;
+use toxcord_protocol::guild_events::{EventPacket, GuildEvent, RsvpStatus};
+
+use crate::commands::polls::{group_number, self_identity};
+use crate::db::message_store::GuildEventRecord;
+use crate::error::{CommandResult, ToxcordError};
+use crate::managers::tox_manager::ToxCommand;
+use crate::AppState;
+
+#[derive(serde::Serialize)]
+pub struct GuildEventInfo {
+    pub id: String,
+    pub guild_id: String,
+    pub creator_public_key: String,
+    pub creator_name: String,
+    pub title: String,
+    pub description: String,
+    /// Unix seconds
+    pub start_time: i64,
+    pub going: usize,
+    pub interested: usize,
+    /// Our own answer: "going", "interested" or "not_going"
+    pub my_rsvp: Option<String>,
+}
+
+/// Schedule an event in a guild
+#[tauri::command]
+pub async fn create_event(
+    guild_id: String,
+    title: String,
+    description: String,
+    start_time: i64,
+    state: State<'_, AppState>,
+) -> CommandResult<GuildEventInfo> {
+    if start_time <= chrono::Utc::now().timestamp() {
+        return Err(ToxcordError::invalid("Events must start in the future"));
+    }
+    let store = state
+        .message_store
+        .lock()
+        .await
+        .clone()
+        .ok_or(ToxcordError::NotLoggedIn)?;
+
+    let event = GuildEvent {
+        id: uuid::Uuid::new_v4().to_string(),
+        title: title.trim().to_string(),
+        description: description.trim().to_string(),
+        start_time,
+    };
+    event.validate()?;
+
+    let group_number = group_number(&state, &guild_id).await?;
+    send_event_packet(&state, group_number, &EventPacket::Create { event: event.clone() }).await?;
+    let (self_pk, self_name) = self_identity(&state, group_number).await?;
+
+    let record = GuildEventRecord {
+        id: event.id,
+        guild_id,
+        creator_public_key: self_pk.clone(),
+        creator_name: self_name,
+        title: event.title,
+        description: event.description,
+        start_time,
+    };
+    store.insert_guild_event(&record)?;
+    Ok(event_info(&store, record, &self_pk)?)
+}
+
+/// Answer an event, replacing our earlier answer
+#[tauri::command]
+pub async fn rsvp_event(
+    guild_id: String,
+    event_id: String,
+    status: RsvpStatus,
+    state: State<'_, AppState>,
+) -> CommandResult<GuildEventInfo> {
+    let store = state
+        .message_store
+        .lock()
+        .await
+        .clone()
+        .ok_or(ToxcordError::NotLoggedIn)?;
+    let event = store
+        .get_guild_event(&event_id)?
+        .filter(|e| e.guild_id == guild_id)
+        .ok_or_else(|| ToxcordError::not_found("Event not found"))?;
+
+    let group_number = group_number(&state, &guild_id).await?;
+    send_event_packet(
+        &state,
+        group_number,
+        &EventPacket::Rsvp {
+            event_id: event_id.clone(),
+            status,
+        },
+    )
+    .await?;
+    let (self_pk, _) = self_identity(&state, group_number).await?;
+    store.set_event_rsvp(&event_id, &self_pk, status.as_str())?;
+
+    Ok(event_info(&store, event, &self_pk)?)
+}
+
+/// Upcoming events of a guild, soonest first
+#[tauri::command]
+pub async fn get_guild_events(
+    guild_id: String,
+    state: State<'_, AppState>,
+) -> CommandResult<Vec<GuildEventInfo>> {
+    let store = state
+        .message_store
+        .lock()
+        .await
+        .clone()
+        .ok_or(ToxcordError::NotLoggedIn)?;
+    let group_number = group_number(&state, &guild_id).await?;
+    let (self_pk, _) = self_identity(&state, group_number).await?;
+
+    Ok(store
+        .get_guild_events(&guild_id, chrono::Utc::now().timestamp())?
+        .into_iter()
+        .map(|event| event_info(&store, event, &self_pk))
+        .collect::<Result<_, String>>()?)
+}
+
+fn event_info(store: &crate::db::MessageStore, event: GuildEventRecord, self_pk: &str) -> Result<GuildEventInfo, String> {
+    let rsvps = store.get_event_rsvps(&event.id)?;
+    let count = |status: RsvpStatus| rsvps.iter().filter(|(_, s)| s == status.as_str()).count();
+    let my_rsvp = rsvps
+        .iter()
+        .find(|(pk, _)| pk.eq_ignore_ascii_case(self_pk))
+        .map(|(_, status)| status.clone());
+
+    Ok(GuildEventInfo {
+        going: count(RsvpStatus::Going),
+        interested: count(RsvpStatus::Interested),
+        my_rsvp,
+        id: event.id,
+        guild_id: event.guild_id,
+        creator_public_key: event.creator_public_key,
+        creator_name: event.creator_name,
+        title: event.title,
+        description: event.description,
+        start_time: event.start_time,
+    })
+}
+
+async fn send_event_packet(state: &AppState, group_number: u32, packet: &EventPacket) -> CommandResult<()> {
+    let tox = state
+        .tox_manager
+        .lock()
+        .await
+        .clone()
+        .ok_or(ToxcordError::NotLoggedIn)?;
+    let (tx, rx) = oneshot::channel();
+    tox.lock()
+        .await
+        .send_command(ToxCommand::GroupSendCustomPacket(group_number, packet.to_bytes(), tx))
+        .await?;
+    rx.await
+        .map_err(|_| "Failed to receive response".to_string())?
+        .map_err(|e| format!("Failed to send event: {e}").into())
+}
@@ -0,0 +1,493 @@
+//! Tauri commands for file attachments.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tauri::ipc::Response;
+use tauri::State;
+use toxcord_core::assets::{AssetStore, AssetUsage};
+use toxcord_protocol::file_share::{self, FileOffer};
+use toxcord_protocol::stickers::{sticker_filename, StickerRef};
+
+use crate::audio::voice_note::{self, RecordingControl};
+use crate::db::message_store::{AttachmentRecord, DirectMessageRecord, StickerPackRecord};
+use crate::db::MessageStore;
+use crate::error::{CommandResult, ToxcordError};
+use crate::managers::file_manager;
+use crate::managers::guild_manager::GuildManager;
+use crate::managers::stickers::{self, StickerInfo};
+use crate::AppState;
+
+/// Get the attachments recorded against a message
+#[tauri::command]
+pub async fn get_message_attachments(
+    state: State<'_, AppState>,
+    message_id: String,
+) -> CommandResult<Vec<AttachmentRecord>> {
+    let store_guard = state.message_store.lock().await;
+    let store = store_guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
+    Ok(store.get_attachments_for_message(&message_id)?)
+}
+
+/// Get the PNG thumbnail for an attachment as raw bytes.
+/// Regenerates thumbnails evicted from the media cache, and falls back to the
+/// original file when there is no thumbnail.
+#[tauri::command]
+pub async fn get_attachment_thumbnail(
+    state: State<'_, AppState>,
+    attachment_id: String,
+) -> CommandResult<Response> {
+    let store = state.message_store.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+    let attachment = store
+        .get_attachment(&attachment_id)?
+        .ok_or_else(|| ToxcordError::not_found("Attachment not found"))?;
+
+    let path = match attachment.thumbnail_path {
+        Some(thumbnail_path) => tokio::task::spawn_blocking(move || {
+            let assets = file_manager::asset_store(&store);
+            let Some(hash) = assets.hash_of(Path::new(&thumbnail_path)) else {
+                // From before the media cache
+                return thumbnail_path;
+            };
+            if let Ok(Some(cached)) = assets.get(&hash) {
+                return cached.to_string_lossy().to_string();
+            }
+            match file_manager::cache_thumbnail(&assets, Path::new(&attachment.file_path)) {
+                Ok((cached, _, _)) => {
+                    let cached = cached.to_string_lossy().to_string();
+                    if let Err(e) = store.set_attachment_thumbnail(&attachment.id, &cached) {
+                        tracing::warn!("Failed to update thumbnail: {e}");
+                    }
+                    cached
+                }
+                Err(_) => attachment.file_path,
+            }
+        })
+        .await
+        .map_err(|e| format!("Thumbnail task failed: {e}"))?,
+        None => attachment.file_path,
+    };
+    let data = tokio::fs::read(&path)
+        .await
+        .map_err(|e| format!("Failed to read thumbnail: {e}"))?;
+    Ok(Response::new(data))
+}
+
+/// Open an attachment with the system's default application
+#[tauri::command]
+pub async fn open_attachment(
+    state: State<'_, AppState>,
+    attachment_id: String,
+) -> CommandResult<()> {
+    let attachment = {
+        let store_guard = state.message_store.lock().await;
+        let store = store_guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
+        store
+            .get_attachment(&attachment_id)?
+            .ok_or_else(|| ToxcordError::not_found("Attachment not found"))?
+    };
+
+    Ok(tauri_plugin_opener::open_path(&attachment.file_path, None::<&str>)
+        .map_err(|e| format!("Failed to open attachment: {e}"))?)
+}
+
+/// Send the image on the system clipboard as a PNG, either to a friend as a
+/// Tox file transfer or to a guild channel via group file sharing.
+/// The image is stored as an attachment on our side so it shows inline right away.
+#[tauri::command]
+pub async fn send_clipboard_image(
+    state: State<'_, AppState>,
+    friend_number: Option<u32>,
+    guild_id: Option<String>,
+    channel_id: Option<String>,
+) -> CommandResult<serde_json::Value> {
+    let png = tokio::task::spawn_blocking(file_manager::clipboard_image_png)
+        .await
+        .map_err(|e| format!("Clipboard task failed: {e}"))??;
+    let filename = format!("clipboard-{}.png", chrono::Utc::now().format("%Y%m%d-%H%M%S"));
+    send_to_recipient(&state, friend_number, guild_id, channel_id, &filename, png).await
+}
+
+/// Record a voice note from the microphone and send it to a friend or a guild channel
+/// once `stop_voice_message` is called or `max_duration_secs` runs out (5 minutes at most).
+/// Resolves with the sent message, or `null` if the recording was cancelled.
+#[tauri::command]
+pub async fn record_voice_message(
+    state: State<'_, AppState>,
+    friend_number: Option<u32>,
+    guild_id: Option<String>,
+    channel_id: Option<String>,
+    max_duration_secs: Option<u32>,
+) -> CommandResult<serde_json::Value> {
+    if friend_number.is_none() && (guild_id.is_none() || channel_id.is_none()) {
+        return Err(ToxcordError::invalid("No recipient"));
+    }
+
+    let control = {
+        let mut recording = state.voice_recording.lock().await;
+        if recording.is_some() {
+            return Err(ToxcordError::invalid("Already recording a voice message"));
+        }
+        let control = Arc::new(RecordingControl::default());
+        *recording = Some(control.clone());
+        control
+    };
+
+    let max_secs = max_duration_secs
+        .unwrap_or(voice_note::MAX_VOICE_NOTE_SECS)
+        .clamp(1, voice_note::MAX_VOICE_NOTE_SECS);
+    let recorded = tokio::task::spawn_blocking(move || {
+        let pcm = voice_note::record(&control, Duration::from_secs(max_secs as u64)).map_err(|e| e.to_string())?;
+        if control.is_cancelled() || pcm.is_empty() {
+            return Ok(None);
+        }
+        voice_note::encode(&pcm).map(Some)
+    })
+    .await;
+    *state.voice_recording.lock().await = None;
+
+    let Some(data) = recorded.map_err(|e| format!("Recording task failed: {e}"))?? else {
+        return Ok(serde_json::Value::Null);
+    };
+    let filename = format!(
+        "{}{}.ogg",
+        voice_note::VOICE_NOTE_PREFIX,
+        chrono::Utc::now().format("%Y%m%d-%H%M%S")
+    );
+    send_to_recipient(&state, friend_number, guild_id, channel_id, &filename, data).await
+}
+
+/// Stop the voice note being recorded. With `send` false the recording is discarded.
+#[tauri::command]
+pub async fn stop_voice_message(state: State<'_, AppState>, send: bool) -> CommandResult<()> {
+    let recording = state.voice_recording.lock().await;
+    let control = recording.as_ref().ok_or("Not recording a voice message")?;
+    control.stop(send);
+    Ok(())
+}
+
+/// Share a file from disk in a guild channel
+#[tauri::command]
+pub async fn send_channel_file(
+    state: State<'_, AppState>,
+    guild_id: String,
+    channel_id: String,
+    path: String,
+) -> CommandResult<serde_json::Value> {
+    let path = PathBuf::from(path);
+    let filename = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .ok_or("Invalid file path")?;
+    let data = tokio::fs::read(&path)
+        .await
+        .map_err(|e| format!("Failed to read file: {e}"))?;
+    share_in_channel(&state, &guild_id, &channel_id, &filename, data).await
+}
+
+async fn send_to_recipient(
+    state: &AppState,
+    friend_number: Option<u32>,
+    guild_id: Option<String>,
+    channel_id: Option<String>,
+    filename: &str,
+    data: Vec<u8>,
+) -> CommandResult<serde_json::Value> {
+    match (friend_number, guild_id, channel_id) {
+        (Some(friend_number), _, _) => send_to_friend(state, friend_number, filename, data).await,
+        (None, Some(guild_id), Some(channel_id)) => {
+            share_in_channel(state, &guild_id, &channel_id, filename, data).await
+        }
+        _ => Err(ToxcordError::invalid("No recipient")),
+    }
+}
+
+async fn send_to_friend(
+    state: &AppState,
+    friend_number: u32,
+    filename: &str,
+    data: Vec<u8>,
+) -> CommandResult<serde_json::Value> {
+    let msg_id = uuid::Uuid::new_v4().to_string();
+    let timestamp = chrono::Utc::now().to_rfc3339();
+    let mime_type = file_manager::inline_mime_type(filename).unwrap_or("application/octet-stream");
+    let store = state.message_store.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+    let (transfer_id, path, attachment) = store_local_copy(store.clone(), &msg_id, filename, mime_type, data).await?;
+
+    // Persist before sending so the message exists when the transfer completes.
+    // Delivered is set once the friend has received the whole file.
+    {
+        store.insert_direct_message(&DirectMessageRecord {
+            id: msg_id.clone(),
+            friend_number: friend_number as i64,
+            sender: "self".to_string(),
+            content: filename.to_string(),
+            message_type: file_manager::file_message_type(filename).to_string(),
+            timestamp: timestamp.clone(),
+            is_outgoing: true,
+            delivered: false,
+            read: false,
+            expires_at: store.disappearing_expiry(friend_number)?,
+        })?;
+        store.insert_attachment(&attachment)?;
+    }
+
+    let guard = state.tox_manager.lock().await;
+    let manager = guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
+    let mgr = manager.lock().await;
+    let sent = mgr.send_file(friend_number, &transfer_id, &msg_id, filename, &path).await;
+
+    Ok(serde_json::json!({
+        "id": msg_id,
+        "timestamp": timestamp,
+        "filename": filename,
+        "message_type": file_manager::file_message_type(filename),
+        "attachment": attachment,
+        "delivered": false,
+        "error": sent.err(),
+    }))
+}
+
+async fn share_in_channel(
+    state: &AppState,
+    guild_id: &str,
+    channel_id: &str,
+    filename: &str,
+    data: Vec<u8>,
+) -> CommandResult<serde_json::Value> {
+    if data.is_empty() {
+        return Err(ToxcordError::invalid("File is empty"));
+    }
+    if data.len() as u64 > file_share::MAX_GROUP_FILE_SIZE {
+        return Err(ToxcordError::invalid(format!(
+            "Files shared in channels are limited to {} MB",
+            file_share::MAX_GROUP_FILE_SIZE / (1024 * 1024)
+        )));
+    }
+
+    let store = state.message_store.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+    let tox = state.tox_manager.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+
+    let msg_id = uuid::Uuid::new_v4().to_string();
+    let mime_type = file_manager::inline_mime_type(filename).unwrap_or("application/octet-stream");
+    let offer = FileOffer {
+        hash: file_share::hash_to_hex(&file_share::sha256(&data)),
+        filename: filename.to_string(),
+        mime_type: mime_type.to_string(),
+        size: data.len() as u64,
+        channel: None,
+    };
+
+    // Stored (with its hash) before the offer goes out, so we can serve requests right away
+    let (_, _, mut attachment) = store_local_copy(store.clone(), &msg_id, filename, mime_type, data).await?;
+    attachment.transfer_id = None;
+    attachment.sha256 = Some(offer.hash.clone());
+    store.insert_attachment(&attachment)?;
+
+    let gm = GuildManager::new(store);
+    let record = gm
+        .send_channel_file(guild_id, channel_id, &msg_id, offer, &tox)
+        .await?;
+
+    Ok(serde_json::json!({
+        "id": record.id,
+        "channel_id": record.channel_id,
+        "timestamp": record.timestamp,
+        "filename": record.content,
+        "message_type": record.message_type,
+        "attachment": attachment,
+    }))
+}
+
+// ─── Stickers ───
+
+/// Install a sticker pack from image files, in order
+#[tauri::command]
+pub async fn import_sticker_pack(
+    state: State<'_, AppState>,
+    name: String,
+    paths: Vec<String>,
+) -> CommandResult<StickerPackRecord> {
+    let store = state.message_store.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+    let paths: Vec<PathBuf> = paths.into_iter().map(PathBuf::from).collect();
+    Ok(sticker_task(move || stickers::import_pack(&store, &name, &paths)).await?)
+}
+
+#[tauri::command]
+pub async fn get_sticker_packs(state: State<'_, AppState>) -> CommandResult<Vec<StickerPackRecord>> {
+    let store_guard = state.message_store.lock().await;
+    let store = store_guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
+    Ok(store.get_sticker_packs()?)
+}
+
+/// Remove a sticker pack and its images
+#[tauri::command]
+pub async fn delete_sticker_pack(state: State<'_, AppState>, pack_hash: String) -> CommandResult<bool> {
+    let store = state.message_store.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+    Ok(sticker_task(move || stickers::delete_pack(&store, &pack_hash)).await?)
+}
+
+/// Look up the sticker a message refers to. None if it isn't a sticker reference.
+#[tauri::command]
+pub async fn resolve_sticker(state: State<'_, AppState>, reference: String) -> CommandResult<Option<StickerInfo>> {
+    let Some(reference) = StickerRef::parse(&reference) else {
+        return Ok(None);
+    };
+    let store = state.message_store.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+    Ok(Some(sticker_task(move || Ok(stickers::resolve(&store, &reference))).await?))
+}
+
+/// Get the image of one of our stickers as raw bytes
+#[tauri::command]
+pub async fn get_sticker_image(state: State<'_, AppState>, pack_hash: String, index: u32) -> CommandResult<Response> {
+    let store = state.message_store.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+    let reference = StickerRef { pack: pack_hash.to_lowercase(), index };
+    let (path, _) = sticker_task(move || stickers::image(&store, &reference)).await?;
+    Ok(Response::new(read_sticker(path).await?))
+}
+
+/// Send a sticker from one of our packs to a friend or a channel. It goes as a
+/// reference if they're known to have the pack, and as an image file otherwise.
+#[tauri::command]
+pub async fn send_sticker(
+    state: State<'_, AppState>,
+    friend_number: Option<u32>,
+    guild_id: Option<String>,
+    channel_id: Option<String>,
+    pack_hash: String,
+    index: u32,
+) -> CommandResult<serde_json::Value> {
+    let store = state.message_store.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+    let reference = StickerRef { pack: pack_hash.to_lowercase(), index };
+    let (path, mime_type) = {
+        let (store, reference) = (store.clone(), reference.clone());
+        sticker_task(move || stickers::image(&store, &reference)).await?
+    };
+    let info = {
+        let (store, reference) = (store.clone(), reference.clone());
+        sticker_task(move || Ok(stickers::resolve(&store, &reference))).await?
+    };
+
+    let mut sent = match (friend_number, guild_id, channel_id) {
+        (Some(friend_number), _, _) => {
+            let public_key = store.get_friend_public_key(friend_number)?;
+            let has_pack = match public_key {
+                Some(pk) => stickers::all_have_pack(&store, &reference.pack, &[pk])?,
+                None => false,
+            };
+            if has_pack {
+                super::messaging::send_friend_text(&state, friend_number, reference.to_text(), "sticker").await?
+            } else {
+                let data = read_sticker(path).await?;
+                let filename = sticker_filename(&reference, &mime_type);
+                send_to_friend(&state, friend_number, &filename, data).await?
+            }
+        }
+        (None, Some(guild_id), Some(channel_id)) => {
+            if channel_has_pack(&state, &store, &guild_id, &reference.pack).await? {
+                let tox = state.tox_manager.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+                let record = GuildManager::new(store)
+                    .send_channel_sticker(&guild_id, &channel_id, &reference, &tox)
+                    .await?;
+                serde_json::json!({
+                    "id": record.id,
+                    "channel_id": record.channel_id,
+                    "timestamp": record.timestamp,
+                    "content": record.content,
+                    "message_type": record.message_type,
+                })
+            } else {
+                let data = read_sticker(path).await?;
+                let filename = sticker_filename(&reference, &mime_type);
+                share_in_channel(&state, &guild_id, &channel_id, &filename, data).await?
+            }
+        }
+        _ => return Err(ToxcordError::invalid("No recipient")),
+    };
+    sent["sticker"] = serde_json::json!(info);
+    Ok(sent)
+}
+
+/// Whether every other member of a guild is known to have a sticker pack
+async fn channel_has_pack(state: &AppState, store: &MessageStore, guild_id: &str, pack_hash: &str) -> Result<bool, String> {
+    let group_number = super::polls::group_number(state, guild_id).await?;
+    let (self_pk, _) = super::polls::self_identity(state, group_number).await?;
+    let members: Vec<String> = store
+        .get_guild_members(guild_id)?
+        .into_iter()
+        .map(|m| m.public_key)
+        .filter(|pk| !pk.eq_ignore_ascii_case(&self_pk))
+        .collect();
+    stickers::all_have_pack(store, pack_hash, &members)
+}
+
+async fn read_sticker(path: PathBuf) -> Result<Vec<u8>, String> {
+    tokio::fs::read(&path)
+        .await
+        .map_err(|e| format!("Failed to read sticker: {e}"))
+}
+
+/// Run a sticker pack operation on the blocking thread pool
+async fn sticker_task<T, F>(f: F) -> Result<T, String>
+where
+    F: FnOnce() -> Result<T, String> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| format!("Sticker task failed: {e}"))?
+}
+
+/// How much the media cache holds, and its limit
+#[tauri::command]
+pub async fn get_media_cache_usage(state: State<'_, AppState>) -> CommandResult<AssetUsage> {
+    let store = state.message_store.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+    Ok(with_assets(store, |assets| assets.usage()).await?)
+}
+
+/// Set the media cache limit, evicting down to it
+#[tauri::command]
+pub async fn set_media_cache_limit(state: State<'_, AppState>, limit_mb: u64) -> CommandResult<()> {
+    let store = state.message_store.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+    let limit_bytes = limit_mb.saturating_mul(1024 * 1024);
+    Ok(with_assets(store, move |assets| assets.set_limit(limit_bytes)).await?)
+}
+
+/// Empty the media cache. Returns the bytes freed.
+#[tauri::command]
+pub async fn clear_media_cache(state: State<'_, AppState>) -> CommandResult<i64> {
+    let store = state.message_store.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+    Ok(with_assets(store, |assets| assets.clear()).await?)
+}
+
+/// Run a media cache operation on the blocking thread pool
+async fn with_assets<T, F>(store: Arc<MessageStore>, f: F) -> Result<T, String>
+where
+    F: FnOnce(&AssetStore) -> Result<T, String> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(move || f(&file_manager::asset_store(&store)))
+        .await
+        .map_err(|e| format!("Media cache task failed: {e}"))?
+}
+
+/// Save our own copy of a file being sent and build its attachment record
+async fn store_local_copy(
+    store: Arc<MessageStore>,
+    message_id: &str,
+    filename: &str,
+    mime_type: &str,
+    data: Vec<u8>,
+) -> Result<(String, PathBuf, AttachmentRecord), String> {
+    let (message_id, filename, mime_type) = (message_id.to_string(), filename.to_string(), mime_type.to_string());
+    tokio::task::spawn_blocking(move || {
+        let (id, path) = file_manager::save_attachment(&store, &filename, &data)?;
+        let assets = file_manager::asset_store(&store);
+        let attachment =
+            file_manager::build_attachment(&assets, &message_id, &id, &filename, &mime_type, data.len() as u64, &path);
+        Ok((id, path, attachment))
+    })
+    .await
+    .map_err(|e| format!("Attachment task failed: {e}"))?
+}
@@ -1,6 +1,17 @@
+use qrcode::render::svg;
+use qrcode::QrCode;
 use tauri::State;
 use tokio::sync::oneshot;
+use toxcord_protocol::fingerprint;
+use toxcord_protocol::lan::LanAnnouncement;
+use toxcord_protocol::links::{self, Link};
+use toxcord_protocol::status_notes::StatusNote;
 
+use crate::db::message_store::{FriendGroupRecord, FriendStatusRecord, LinkedDeviceRecord};
+use crate::db::presence::{self, PresenceSummary};
+use crate::error::{CommandResult, ToxcordError};
+use crate::managers::file_manager;
+use crate::managers::lan_discovery::LanDiscovery;
 use crate::managers::tox_manager::ToxCommand;
 use crate::AppState;
 
@@ -9,27 +20,27 @@ pub async fn add_friend(
     state: State<'_, AppState>,
     tox_id: String,
     message: String,
-) -> Result<u32, String> {
+) -> CommandResult<u32> {
     let guard = state.tox_manager.lock().await;
-    let manager = guard.as_ref().ok_or("Not connected")?;
+    let manager = guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
     let mgr = manager.lock().await;
     let (tx, rx) = oneshot::channel();
     mgr.send_command(ToxCommand::FriendAdd(tox_id, message, tx)).await?;
-    rx.await.map_err(|_| "Failed to receive response".to_string())?
+    Ok(rx.await.map_err(|_| "Failed to receive response".to_string())??)
 }
 
 #[tauri::command]
 pub async fn accept_friend_request(
     state: State<'_, AppState>,
     public_key: String,
-) -> Result<u32, String> {
+) -> CommandResult<u32> {
     // Parse hex public key to bytes
     let pk_bytes = hex_to_bytes_32(&public_key)?;
 
     // Accept in Tox
     let friend_number = {
         let guard = state.tox_manager.lock().await;
-        let manager = guard.as_ref().ok_or("Not connected")?;
+        let manager = guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
         let mgr = manager.lock().await;
         let (tx, rx) = oneshot::channel();
         mgr.send_command(ToxCommand::FriendAccept(pk_bytes, tx)).await?;
@@ -50,9 +61,9 @@ pub async fn accept_friend_request(
 pub async fn deny_friend_request(
     state: State<'_, AppState>,
     public_key: String,
-) -> Result<(), String> {
+) -> CommandResult<()> {
     let store_guard = state.message_store.lock().await;
-    let store = store_guard.as_ref().ok_or("Not connected")?;
+    let store = store_guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
     store.remove_friend_request(&public_key)?;
     Ok(())
 }
@@ -61,11 +72,11 @@ pub async fn deny_friend_request(
 pub async fn remove_friend(
     state: State<'_, AppState>,
     friend_number: u32,
-) -> Result<(), String> {
+) -> CommandResult<()> {
     // Remove from Tox
     {
         let guard = state.tox_manager.lock().await;
-        let manager = guard.as_ref().ok_or("Not connected")?;
+        let manager = guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
         let mgr = manager.lock().await;
         let (tx, rx) = oneshot::channel();
         mgr.send_command(ToxCommand::FriendDelete(friend_number, tx)).await?;
@@ -81,32 +92,128 @@ pub async fn remove_friend(
     Ok(())
 }
 
+/// Retry the connection to a friend who shows offline though they should be
+/// online. Returns whether a new connection attempt was started; if not, the
+/// network was only bootstrapped again.
+#[tauri::command]
+pub async fn refresh_friend_connection(state: State<'_, AppState>, friend_number: u32) -> CommandResult<bool> {
+    let tox = state.tox_manager.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+    let retried = tox.lock().await.refresh_friend_connections(Some(friend_number)).await?;
+    Ok(retried.contains(&friend_number))
+}
+
+/// Retry the connections to every offline friend, e.g. after the network
+/// changed. Returns the friends retried.
+#[tauri::command]
+pub async fn reconnect_all_friends(state: State<'_, AppState>) -> CommandResult<Vec<u32>> {
+    let tox = state.tox_manager.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+    let retried = tox.lock().await.refresh_friend_connections(None).await?;
+    Ok(retried)
+}
+
 #[tauri::command]
 pub async fn get_friends(
     state: State<'_, AppState>,
-) -> Result<serde_json::Value, String> {
+) -> CommandResult<serde_json::Value> {
+    Ok(serde_json::json!(friend_list(&state, None).await?))
+}
+
+/// Most friends returned in one page
+const MAX_FRIENDS_PAGE: usize = 200;
+
+/// A page of the friends list, with the revision to ask for changes from
+#[tauri::command]
+pub async fn get_friends_page(
+    state: State<'_, AppState>,
+    offset: usize,
+    limit: usize,
+) -> CommandResult<serde_json::Value> {
+    // Read before the list, so changes made while it's built come in the next delta
+    let revision = friends_revision(&state).await?;
+    let friends = friend_list(&state, None).await?;
+    let total = friends.len();
+    let page: Vec<_> = friends
+        .into_iter()
+        .skip(offset)
+        .take(limit.min(MAX_FRIENDS_PAGE))
+        .collect();
+    Ok(serde_json::json!({
+        "friends": page,
+        "total": total,
+        "revision": revision,
+    }))
+}
+
+/// Friends changed or removed since a revision from `get_friends_page` or an earlier call
+#[tauri::command]
+pub async fn get_friends_changed_since(
+    state: State<'_, AppState>,
+    revision: i64,
+) -> CommandResult<serde_json::Value> {
+    let store = state.message_store.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+    let changes = store.call(move |store| store.friends_changed_since(revision)).await?;
+    let friends = if changes.changed.is_empty() {
+        vec![]
+    } else {
+        friend_list(&state, Some(&changes.changed)).await?
+    };
+    Ok(serde_json::json!({
+        "friends": friends,
+        "removed": changes.removed,
+        "revision": changes.revision,
+    }))
+}
+
+async fn friends_revision(state: &AppState) -> CommandResult<i64> {
+    let store = state.message_store.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+    Ok(store.call(|store| store.friends_revision()).await?)
+}
+
+/// Friends in list order, or only the given friend numbers
+async fn friend_list(state: &AppState, only: Option<&[u32]>) -> CommandResult<Vec<serde_json::Value>> {
     // Get live data from Tox
     let tox_friends = {
         let guard = state.tox_manager.lock().await;
-        let manager = guard.as_ref().ok_or("Not connected")?;
+        let manager = guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
         let mgr = manager.lock().await;
         let (tx, rx) = oneshot::channel();
         mgr.send_command(ToxCommand::FriendList(tx)).await?;
         rx.await.map_err(|_| "Failed to receive response".to_string())?
     };
 
-    // Merge with DB data (for last_seen, notes)
+    let rich_presence = {
+        let guard = state.tox_manager.lock().await;
+        let manager = guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
+        let mgr = manager.lock().await;
+        mgr.rich_presence().clone()
+    };
+
+    // Merge with DB data (for last_seen, notes, groups)
     let store_guard = state.message_store.lock().await;
-    let db_friends = if let Some(store) = store_guard.as_ref() {
-        store.get_friends().unwrap_or_default()
+    let (db_friends, groups) = if let Some(store) = store_guard.as_ref() {
+        (store.get_friends().unwrap_or_default(), store.get_friend_groups().unwrap_or_default())
     } else {
-        vec![]
+        (vec![], vec![])
     };
 
-    let friends: Vec<serde_json::Value> = tox_friends
+    // Grouped friends in group order, then ungrouped ones
+    let mut tox_friends: Vec<_> = tox_friends
         .iter()
-        .map(|tf| {
-            let db_match = db_friends.iter().find(|df| df.friend_number == tf.number as i64);
+        .filter(|tf| only.is_none_or(|only| only.contains(&tf.number)))
+        .map(|tf| (tf, db_friends.iter().find(|df| df.friend_number == tf.number as i64)))
+        .collect();
+    tox_friends.sort_by_cached_key(|(tf, db_match)| {
+        let group_index = db_match
+            .and_then(|d| d.group_id.as_ref())
+            .and_then(|id| groups.iter().position(|g| &g.id == id))
+            .unwrap_or(groups.len());
+        let group_position = db_match.map(|d| d.group_position).unwrap_or_default();
+        (group_index, group_position, tf.name.to_lowercase())
+    });
+
+    let friends: Vec<serde_json::Value> = tox_friends
+        .into_iter()
+        .map(|(tf, db_match)| {
             serde_json::json!({
                 "friend_number": tf.number,
                 "public_key": tf.public_key.0,
@@ -114,25 +221,567 @@ pub async fn get_friends(
                 "status_message": tf.status_message,
                 "user_status": format!("{:?}", tf.status).to_lowercase(),
                 "connection_status": format!("{:?}", tf.connection_status).to_lowercase(),
+                "connection_type": tf.connection_status.connection_type(),
                 "last_seen": db_match.and_then(|d| d.last_seen.clone()),
                 "notes": db_match.map(|d| d.notes.clone()).unwrap_or_default(),
+                "activity": rich_presence.activity(&tf.public_key.0),
+                "custom_status": rich_presence.custom_status(&tf.public_key.0),
+                "group_id": db_match.and_then(|d| d.group_id.clone()),
+                "group_position": db_match.map(|d| d.group_position).unwrap_or_default(),
             })
         })
         .collect();
 
-    Ok(serde_json::json!(friends))
+    Ok(friends)
 }
 
 #[tauri::command]
 pub async fn get_friend_requests(
     state: State<'_, AppState>,
-) -> Result<serde_json::Value, String> {
+) -> CommandResult<serde_json::Value> {
     let store_guard = state.message_store.lock().await;
-    let store = store_guard.as_ref().ok_or("Not connected")?;
+    let store = store_guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
     let requests = store.get_friend_requests()?;
     Ok(serde_json::json!(requests))
 }
 
+/// Last seen, current session length and the last week's online pattern for a friend
+#[tauri::command]
+pub async fn get_friend_presence_summary(
+    state: State<'_, AppState>,
+    friend_number: u32,
+) -> CommandResult<PresenceSummary> {
+    let store_guard = state.message_store.lock().await;
+    let store = store_guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
+    let friend = store
+        .get_friends()?
+        .into_iter()
+        .find(|f| f.friend_number == friend_number as i64)
+        .ok_or_else(|| ToxcordError::not_found("Friend not found"))?;
+
+    let now = chrono::Utc::now();
+    let since = (now - chrono::Duration::days(presence::PATTERN_DAYS))
+        .format("%Y-%m-%d %H:%M:%S")
+        .to_string();
+    let sessions = store.get_presence_sessions(friend_number, &since)?;
+    Ok(presence::summarize(
+        &sessions,
+        friend.last_seen.as_deref(),
+        friend.connection_status != "none",
+        now,
+    ))
+}
+
+#[derive(serde::Serialize)]
+pub struct FriendConnectionInfo {
+    pub friend_number: u32,
+    /// "direct" over UDP, "relayed" through a TCP relay, or "none"
+    pub connection_type: &'static str,
+    /// When the connection was last established, RFC 3339
+    pub last_handshake_at: Option<String>,
+    pub last_seen: Option<String>,
+}
+
+/// How a friend is connected, to explain high latency: relayed traffic goes
+/// through a TCP relay instead of straight to them
+#[tauri::command]
+pub async fn get_friend_connection_info(
+    state: State<'_, AppState>,
+    friend_number: u32,
+) -> CommandResult<FriendConnectionInfo> {
+    let tox_friend = {
+        let tox = state.tox_manager.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+        let (tx, rx) = oneshot::channel();
+        tox.lock().await.send_command(ToxCommand::FriendList(tx)).await?;
+        rx.await
+            .map_err(|_| "Failed to receive response".to_string())?
+            .into_iter()
+            .find(|f| f.number == friend_number)
+            .ok_or_else(|| ToxcordError::not_found("Friend not found"))?
+    };
+
+    let store = state.message_store.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+    let last_seen = store
+        .get_friends()?
+        .into_iter()
+        .find(|f| f.friend_number == friend_number as i64)
+        .and_then(|f| f.last_seen);
+    let to_rfc3339 = |t: Option<String>| t.as_deref().and_then(presence::parse_db_time).map(|t| t.to_rfc3339());
+
+    Ok(FriendConnectionInfo {
+        friend_number,
+        connection_type: tox_friend.connection_status.connection_type(),
+        last_handshake_at: to_rfc3339(store.get_friend_last_handshake(friend_number)?),
+        last_seen: to_rfc3339(last_seen),
+    })
+}
+
+// ─── Identity verification ─────────────────────────────────────────
+
+#[derive(serde::Serialize)]
+pub struct IdentityFingerprint {
+    pub public_key: String,
+    /// 30 digits in groups of five
+    pub fingerprint: String,
+    /// SVG QR code of the verification payload, for a friend to scan
+    pub qr_svg: String,
+}
+
+#[derive(serde::Serialize)]
+pub struct FriendVerification {
+    /// "verified", "unverified", or "key_changed" if the verified key no longer matches
+    pub state: String,
+    pub public_key: String,
+    pub fingerprint: String,
+    /// 60 digits both sides see; compare it in person or over a trusted channel
+    pub safety_number: String,
+    pub verified_at: Option<String>,
+}
+
+/// Our own fingerprint and verification QR code
+#[tauri::command]
+pub async fn get_identity_fingerprint(state: State<'_, AppState>) -> CommandResult<IdentityFingerprint> {
+    let public_key = self_public_key(&state).await?;
+    let key = fingerprint::public_key_from_hex(&public_key).ok_or("Invalid public key")?;
+    let qr_svg = QrCode::new(fingerprint::verification_uri(&key).as_bytes())
+        .map_err(|e| format!("Failed to generate QR code: {e}"))?
+        .render::<svg::Color>()
+        .min_dimensions(256, 256)
+        .build();
+
+    Ok(IdentityFingerprint {
+        fingerprint: fingerprint::fingerprint(&key),
+        public_key,
+        qr_svg,
+    })
+}
+
+#[tauri::command]
+pub async fn get_friend_verification(
+    state: State<'_, AppState>,
+    friend_number: u32,
+) -> CommandResult<FriendVerification> {
+    let self_key = self_public_key(&state).await?;
+    let store_guard = state.message_store.lock().await;
+    let store = store_guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
+    let public_key = store
+        .get_friend_public_key(friend_number)?
+        .ok_or_else(|| ToxcordError::not_found("Friend not found"))?
+        .to_uppercase();
+
+    let friend_key = fingerprint::public_key_from_hex(&public_key).ok_or("Invalid public key")?;
+    let self_key = fingerprint::public_key_from_hex(&self_key).ok_or("Invalid public key")?;
+    let verification = store.get_friend_verification(friend_number)?;
+    let status = match &verification {
+        Some((verified_key, _)) if *verified_key == public_key => "verified",
+        Some(_) => "key_changed",
+        None => "unverified",
+    };
+
+    Ok(FriendVerification {
+        state: status.to_string(),
+        fingerprint: fingerprint::fingerprint(&friend_key),
+        safety_number: fingerprint::safety_number(&self_key, &friend_key),
+        verified_at: verification.map(|(_, at)| at),
+        public_key,
+    })
+}
+
+/// Mark a friend as verified once the user has compared the safety number out of band.
+/// With `scanned_code` (the friend's QR payload) the key is checked before recording it.
+#[tauri::command]
+pub async fn verify_friend(
+    state: State<'_, AppState>,
+    friend_number: u32,
+    scanned_code: Option<String>,
+) -> CommandResult<()> {
+    let store_guard = state.message_store.lock().await;
+    let store = store_guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
+    let public_key = store.get_friend_public_key(friend_number)?.ok_or_else(|| ToxcordError::not_found("Friend not found"))?;
+    let friend_key = fingerprint::public_key_from_hex(&public_key).ok_or("Invalid public key")?;
+
+    if let Some(code) = scanned_code {
+        let scanned = fingerprint::parse_verification_uri(&code).ok_or("Not a verification code")?;
+        if scanned != friend_key {
+            return Err(ToxcordError::invalid("Scanned key doesn't match this friend"));
+        }
+    }
+    Ok(store.set_friend_verified(friend_number, &public_key)?)
+}
+
+#[tauri::command]
+pub async fn unverify_friend(state: State<'_, AppState>, friend_number: u32) -> CommandResult<()> {
+    let store_guard = state.message_store.lock().await;
+    let store = store_guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
+    Ok(store.clear_friend_verification(friend_number)?)
+}
+
+// ─── Contact sharing ───────────────────────────────────────────────
+
+#[derive(serde::Serialize)]
+pub struct ContactQr {
+    /// Our add link, with our name
+    pub uri: String,
+    /// SVG QR code of the link
+    pub qr_svg: String,
+}
+
+/// Our Tox ID as an add link and a QR code, for someone to scan or paste
+#[tauri::command]
+pub async fn get_tox_id_qr(state: State<'_, AppState>) -> CommandResult<ContactQr> {
+    let uri = self_contact_link(&state).await?.to_uri();
+    let qr_svg = QrCode::new(uri.as_bytes())
+        .map_err(|e| format!("Failed to generate QR code: {e}"))?
+        .render::<svg::Color>()
+        .min_dimensions(256, 256)
+        .build();
+    Ok(ContactQr { uri, qr_svg })
+}
+
+/// Read a pasted add link, `tox:` URI or Tox ID
+#[tauri::command]
+pub async fn parse_contact_link(text: String) -> CommandResult<Link> {
+    Link::parse(&text).ok_or_else(|| ToxcordError::invalid("Not a Tox ID or Toxcord link"))
+}
+
+/// Read a link from a QR code in an image file, or in the clipboard image
+/// when there's no path
+#[tauri::command]
+pub async fn scan_contact_qr(path: Option<String>) -> CommandResult<Link> {
+    let text = tokio::task::spawn_blocking(move || {
+        let img = match path {
+            Some(path) => image::open(&path).map_err(|e| format!("Failed to open image: {e}"))?,
+            None => image::DynamicImage::ImageRgba8(file_manager::clipboard_image()?),
+        };
+        file_manager::read_qr_code(&img)
+    })
+    .await
+    .map_err(|e| format!("QR code task failed: {e}"))??;
+    Link::parse(&text).ok_or_else(|| ToxcordError::invalid("The QR code doesn't hold a Tox ID or Toxcord link"))
+}
+
+/// Send a contact card to a friend: `contact` if given, otherwise ourselves.
+/// Only friends we added have a Tox ID we can share.
+#[tauri::command]
+pub async fn share_contact(
+    state: State<'_, AppState>,
+    friend_number: u32,
+    contact: Option<u32>,
+) -> CommandResult<serde_json::Value> {
+    let link = match contact {
+        None => self_contact_link(&state).await?,
+        Some(contact) => {
+            let store = state.message_store.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+            let friend = store
+                .get_friends()?
+                .into_iter()
+                .find(|f| f.friend_number == contact as i64)
+                .ok_or_else(|| ToxcordError::not_found("Friend not found"))?;
+            let tox_id = store.get_friend_tox_id(contact)?.ok_or_else(|| {
+                ToxcordError::invalid("Their Tox ID isn't known, since they added you; ask them to share it")
+            })?;
+            Link::AddFriend { tox_id, name: Some(friend.name).filter(|n| !n.is_empty()) }
+        }
+    };
+    crate::commands::messaging::send_direct_message(state, friend_number, link.to_uri()).await
+}
+
+async fn self_contact_link(state: &AppState) -> CommandResult<Link> {
+    let guard = state.tox_manager.lock().await;
+    let manager = guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
+    let mgr = manager.lock().await;
+    let profile = mgr.get_profile_info().await?;
+    let tox_id = profile.tox_id.to_string().to_uppercase();
+    if !links::is_valid_tox_id(&tox_id) {
+        return Err("Invalid Tox ID".into());
+    }
+    Ok(Link::AddFriend { tox_id, name: Some(profile.name).filter(|n| !n.is_empty()) })
+}
+
+// ─── LAN discovery ─────────────────────────────────────────────────
+
+const LAN_FRIEND_REQUEST_MESSAGE: &str = "Hi! I found you on the local network.";
+
+#[derive(serde::Serialize)]
+pub struct LanPeerInfo {
+    pub address: String,
+    pub public_key: String,
+    pub name: String,
+    pub ip: String,
+    pub is_friend: bool,
+}
+
+/// Start announcing ourselves and listening for other Toxcord instances on the LAN
+#[tauri::command]
+pub async fn start_lan_discovery(
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> CommandResult<()> {
+    let announcement = lan_announcement(&state).await?;
+    let mut discovery = state.lan_discovery.lock().await;
+    if discovery.is_none() {
+        *discovery = Some(LanDiscovery::start(app_handle, announcement)?);
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn stop_lan_discovery(state: State<'_, AppState>) -> CommandResult<()> {
+    state.lan_discovery.lock().await.take();
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn is_lan_discovery_enabled(state: State<'_, AppState>) -> CommandResult<bool> {
+    Ok(state.lan_discovery.lock().await.is_some())
+}
+
+#[tauri::command]
+pub async fn get_lan_peers(state: State<'_, AppState>) -> CommandResult<Vec<LanPeerInfo>> {
+    let peers = match state.lan_discovery.lock().await.as_ref() {
+        Some(discovery) => discovery.peers(),
+        None => return Ok(Vec::new()),
+    };
+    let friend_keys: std::collections::HashSet<String> = {
+        let store_guard = state.message_store.lock().await;
+        let store = store_guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
+        store
+            .get_friends()?
+            .into_iter()
+            .map(|f| f.public_key.to_uppercase())
+            .collect()
+    };
+    Ok(peers
+        .into_iter()
+        .map(|p| LanPeerInfo {
+            is_friend: friend_keys.contains(&p.public_key),
+            address: p.address,
+            public_key: p.public_key,
+            name: p.name,
+            ip: p.ip,
+        })
+        .collect())
+}
+
+/// Send a friend request to a peer found on the LAN
+#[tauri::command]
+pub async fn add_lan_peer(
+    state: State<'_, AppState>,
+    public_key: String,
+    message: Option<String>,
+) -> CommandResult<u32> {
+    let address = state
+        .lan_discovery
+        .lock()
+        .await
+        .as_ref()
+        .and_then(|d| d.peer_address(&public_key))
+        .ok_or("Peer is no longer on the local network")?;
+    let message = message
+        .filter(|m| !m.trim().is_empty())
+        .unwrap_or_else(|| LAN_FRIEND_REQUEST_MESSAGE.to_string());
+
+    let guard = state.tox_manager.lock().await;
+    let manager = guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
+    let mgr = manager.lock().await;
+    let (tx, rx) = oneshot::channel();
+    mgr.send_command(ToxCommand::FriendAdd(address, message, tx)).await?;
+    Ok(rx.await.map_err(|_| "Failed to receive response".to_string())??)
+}
+
+// ─── Friend groups ─────────────────────────────────────────────────
+
+/// Groups in display order. Friends list their `group_id` in `get_friends`.
+#[tauri::command]
+pub async fn get_friend_groups(state: State<'_, AppState>) -> CommandResult<Vec<FriendGroupRecord>> {
+    let store_guard = state.message_store.lock().await;
+    let store = store_guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
+    Ok(store.get_friend_groups()?)
+}
+
+#[tauri::command]
+pub async fn create_friend_group(state: State<'_, AppState>, name: String) -> CommandResult<FriendGroupRecord> {
+    let name = validate_group_name(&name)?;
+    let store_guard = state.message_store.lock().await;
+    let store = store_guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
+    Ok(store.create_friend_group(name)?)
+}
+
+#[tauri::command]
+pub async fn rename_friend_group(state: State<'_, AppState>, group_id: String, name: String) -> CommandResult<()> {
+    let name = validate_group_name(&name)?;
+    let store_guard = state.message_store.lock().await;
+    let store = store_guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
+    if !store.rename_friend_group(&group_id, name)? {
+        return Err(ToxcordError::not_found("Friend group not found"));
+    }
+    Ok(())
+}
+
+/// Delete a group; its friends become ungrouped
+#[tauri::command]
+pub async fn delete_friend_group(state: State<'_, AppState>, group_id: String) -> CommandResult<()> {
+    let store_guard = state.message_store.lock().await;
+    let store = store_guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
+    Ok(store.delete_friend_group(&group_id)?)
+}
+
+/// Set the order of groups, top to bottom
+#[tauri::command]
+pub async fn reorder_friend_groups(state: State<'_, AppState>, group_ids: Vec<String>) -> CommandResult<()> {
+    let store_guard = state.message_store.lock().await;
+    let store = store_guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
+    Ok(store.reorder_friend_groups(&group_ids)?)
+}
+
+#[tauri::command]
+pub async fn set_friend_group_collapsed(
+    state: State<'_, AppState>,
+    group_id: String,
+    collapsed: bool,
+) -> CommandResult<()> {
+    let store_guard = state.message_store.lock().await;
+    let store = store_guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
+    Ok(store.set_friend_group_collapsed(&group_id, collapsed)?)
+}
+
+/// Move a friend into a group, or out of all groups with `group_id: null`.
+/// `position` places them within the group; by default they go last.
+#[tauri::command]
+pub async fn set_friend_group(
+    state: State<'_, AppState>,
+    friend_number: u32,
+    group_id: Option<String>,
+    position: Option<i64>,
+) -> CommandResult<()> {
+    let store_guard = state.message_store.lock().await;
+    let store = store_guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
+    if let Some(group_id) = &group_id {
+        if !store.get_friend_groups()?.iter().any(|g| &g.id == group_id) {
+            return Err(ToxcordError::not_found("Friend group not found"));
+        }
+    }
+    Ok(store.set_friend_group(friend_number, group_id.as_deref(), position.map(|p| p.max(0)))?)
+}
+
+fn validate_group_name(name: &str) -> Result<&str, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Group name cannot be empty".to_string());
+    }
+    if name.chars().count() > 64 {
+        return Err("Group name is longer than 64 characters".to_string());
+    }
+    Ok(name)
+}
+
+// ─── Linked devices ────────────────────────────────────────────────
+
+/// Mark a friend as another device of ours. Both devices have to link each
+/// other before they sync.
+#[tauri::command]
+pub async fn link_device(state: State<'_, AppState>, friend_number: u32) -> CommandResult<()> {
+    {
+        let store_guard = state.message_store.lock().await;
+        let store = store_guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
+        let public_key = store
+            .get_friend_public_key(friend_number)?
+            .filter(|pk| !pk.is_empty())
+            .ok_or("Unknown friend")?;
+        store.link_device(friend_number, &public_key)?;
+    }
+    // Sync right away if the device is online; it's fine if it isn't
+    let _ = sync_device(state, friend_number).await;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn unlink_device(state: State<'_, AppState>, friend_number: u32) -> CommandResult<bool> {
+    let store_guard = state.message_store.lock().await;
+    let store = store_guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
+    Ok(store.unlink_device(friend_number)?)
+}
+
+#[tauri::command]
+pub async fn get_linked_devices(state: State<'_, AppState>) -> CommandResult<Vec<LinkedDeviceRecord>> {
+    let store_guard = state.message_store.lock().await;
+    let store = store_guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
+    Ok(store.get_linked_devices()?)
+}
+
+/// Sync with a linked device now instead of waiting for it to reconnect
+#[tauri::command]
+pub async fn sync_device(state: State<'_, AppState>, friend_number: u32) -> CommandResult<()> {
+    let guard = state.tox_manager.lock().await;
+    let manager = guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
+    let mgr = manager.lock().await;
+    let (tx, rx) = oneshot::channel();
+    mgr.send_command(ToxCommand::SyncDevice(friend_number, tx)).await?;
+    Ok(rx.await.map_err(|_| "Failed to receive response".to_string())??)
+}
+
+/// Post a status note to every friend. It expires after a day.
+#[tauri::command]
+pub async fn post_status_note(state: State<'_, AppState>, text: String) -> CommandResult<StatusNote> {
+    let guard = state.tox_manager.lock().await;
+    let manager = guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
+    let mgr = manager.lock().await;
+    let (tx, rx) = oneshot::channel();
+    mgr.send_command(ToxCommand::PostStatusNote(text, tx)).await?;
+    Ok(rx.await.map_err(|_| "Failed to receive response".to_string())??)
+}
+
+/// Our status notes that haven't expired, newest first
+#[tauri::command]
+pub async fn get_own_status_notes(state: State<'_, AppState>) -> CommandResult<Vec<StatusNote>> {
+    let store_guard = state.message_store.lock().await;
+    let store = store_guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
+    Ok(store.get_own_status_notes(chrono::Utc::now().timestamp())?)
+}
+
+/// Friends' status notes that haven't expired, newest first
+#[tauri::command]
+pub async fn get_friend_statuses(state: State<'_, AppState>) -> CommandResult<Vec<FriendStatusRecord>> {
+    let store_guard = state.message_store.lock().await;
+    let store = store_guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
+    Ok(store.get_friend_status_notes(chrono::Utc::now().timestamp())?)
+}
+
+/// Re-announce after our name or address changed
+pub(crate) async fn refresh_lan_announcement(state: &AppState) {
+    if state.lan_discovery.lock().await.is_none() {
+        return;
+    }
+    match lan_announcement(state).await {
+        Ok(announcement) => {
+            if let Some(discovery) = state.lan_discovery.lock().await.as_ref() {
+                discovery.set_announcement(announcement);
+            }
+        }
+        Err(e) => tracing::warn!("Failed to refresh LAN announcement: {e}"),
+    }
+}
+
+async fn lan_announcement(state: &AppState) -> CommandResult<LanAnnouncement> {
+    let guard = state.tox_manager.lock().await;
+    let manager = guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
+    let mgr = manager.lock().await;
+    let profile = mgr.get_profile_info().await?;
+    Ok(LanAnnouncement {
+        address: profile.tox_id.to_string(),
+        name: profile.name,
+    })
+}
+
+/// Our public key (the first 64 hex characters of the Tox ID)
+async fn self_public_key(state: &AppState) -> CommandResult<String> {
+    let guard = state.tox_manager.lock().await;
+    let manager = guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
+    let mgr = manager.lock().await;
+    let profile = mgr.get_profile_info().await?;
+    Ok(profile.tox_id.as_str()[..64].to_uppercase())
+}
+
 /// Parse a 64-char hex public key into a [u8; 32]
 fn hex_to_bytes_32(hex: &str) -> Result<[u8; 32], String> {
     if hex.len() != 64 {
@@ -1,8 +1,23 @@
 use tauri::State;
 use tokio::sync::oneshot;
-
+use toxcord_core::incoming::{keep_ignored_messages, KEEP_IGNORED_MESSAGES_SETTING};
+use toxcord_protocol::bridge::BridgeProtocol;
+use toxcord_protocol::guild_directory::{self, DirectoryEntry};
+use toxcord_protocol::guild_layout;
+use toxcord_protocol::guild_manifest::{
+    BaseRole, CustomRole, FilterAction, FilterRule, GuildManifest, Onboarding, WordFilter,
+};
+use toxcord_protocol::markdown;
+use toxcord_protocol::purge::PurgePacket;
+use toxcord_protocol::rich_presence::{Activity, CustomStatus};
+
+use crate::db::message_store::{
+    AuditLogRecord, ChannelBridgeRecord, ChannelMessageRecord, ChannelRecord, GroupInviteRecord,
+    MessageCursor, MessagePage,
+};
+use crate::error::{CommandResult, ToxcordError};
 use crate::managers::guild_manager::GuildManager;
-use crate::managers::tox_manager::ToxCommand;
+use crate::managers::tox_manager::{ToxCommand, ToxManager};
 use crate::AppState;
 
 // ─── Response types ────────────────────────────────────────────────
@@ -25,6 +40,10 @@ pub struct ChannelInfo {
     pub topic: String,
     pub channel_type: String,
     pub position: i64,
+    /// Seconds between messages per member, from the guild manifest; 0 is off
+    pub slow_mode_secs: u32,
+    /// all, mentions or nothing
+    pub notification_level: String,
 }
 
 #[derive(serde::Serialize)]
@@ -37,39 +56,109 @@ pub struct ChannelMessageInfo {
     pub message_type: String,
     pub timestamp: String,
     pub is_own: bool,
+    /// Parsed markdown of the content
+    pub formatted: Vec<markdown::Node>,
+    /// The message mentions us
+    pub mentions_me: bool,
+    /// The message matched the guild word filter and is shown behind a warning
+    pub filtered: bool,
+    /// The message came from another member's history after we joined
+    pub backfilled: bool,
+    /// The message was signed by the Tox identity we know for the sender;
+    /// `None` if it wasn't checked
+    pub sender_verified: Option<bool>,
+    /// The Tox key that signed the message, if it was signed
+    pub signer_public_key: Option<String>,
+}
+
+#[derive(serde::Serialize)]
+pub struct MentionableMember {
+    pub public_key: String,
+    pub name: String,
+    /// Text to insert into the message to mention this member
+    pub mention: String,
 }
 
 #[derive(serde::Serialize)]
 pub struct MemberInfo {
-    pub peer_id: u32,
+    /// None for offline members
+    pub peer_id: Option<u32>,
     pub name: String,
     pub public_key: String,
     pub role: String,
+    /// online, away, busy or offline
     pub status: String,
+    /// When an offline member was last seen
+    pub last_seen: Option<String>,
+    pub activity: Option<Activity>,
+    pub custom_status: Option<CustomStatus>,
+    /// We ignore this member
+    pub ignored: bool,
+    /// Cosmetic role from the guild manifest
+    pub custom_role: Option<CustomRole>,
+}
+
+#[derive(serde::Serialize)]
+pub struct OnboardingInfo {
+    #[serde(flatten)]
+    pub onboarding: Onboarding,
+    /// We've accepted the current rules, or don't have to
+    pub may_post: bool,
+}
+
+/// A guild in the public directory
+#[derive(serde::Serialize)]
+pub struct PublicGuildInfo {
+    #[serde(flatten)]
+    pub entry: DirectoryEntry,
+    /// Our guild for this chat ID, if we're in it
+    pub joined_guild_id: Option<String>,
 }
 
 // ─── Commands ──────────────────────────────────────────────────────
 
+/// Create a guild, needing `password` to join if one is given. A public guild
+/// can be joined by anyone with its chat ID; a private one only by invite.
 #[tauri::command]
 pub async fn create_guild(
     name: String,
+    password: Option<String>,
+    public: Option<bool>,
     state: State<'_, AppState>,
-) -> Result<GuildInfo, String> {
+) -> CommandResult<GuildInfo> {
     let store = state
         .message_store
         .lock()
         .await
         .clone()
-        .ok_or("Not logged in")?;
+        .ok_or(ToxcordError::NotLoggedIn)?;
     let tox = state
         .tox_manager
         .lock()
         .await
         .clone()
-        .ok_or("Not logged in")?;
+        .ok_or(ToxcordError::NotLoggedIn)?;
 
-    let gm = GuildManager::new(store);
-    let record = gm.create_guild(&name, &tox).await?;
+    let gm = GuildManager::new(store.clone());
+    let public = public.unwrap_or(false);
+    let privacy = if public {
+        toxcord_tox::GroupPrivacyState::Public
+    } else {
+        toxcord_tox::GroupPrivacyState::Private
+    };
+    let record = gm.create_guild(&name, privacy, &tox).await?;
+    if let Some(password) = password.filter(|p| !p.is_empty()) {
+        gm.set_guild_password(&record.id, &password, &tox).await?;
+    }
+    // List it in our own directory, where its chat ID can be copied to share
+    if public {
+        let entry = store
+            .get_guild_chat_id(&record.id)?
+            .and_then(|chat_id| DirectoryEntry::new(&chat_id, &record.name, ""));
+        if let Some(entry) = entry {
+            store.add_directory_guild(&entry)?;
+        }
+    }
 
     Ok(GuildInfo {
         id: record.id,
@@ -82,13 +171,13 @@ pub async fn create_guild(
 }
 
 #[tauri::command]
-pub async fn get_guilds(state: State<'_, AppState>) -> Result<Vec<GuildInfo>, String> {
+pub async fn get_guilds(state: State<'_, AppState>) -> CommandResult<Vec<GuildInfo>> {
     let store = state
         .message_store
         .lock()
         .await
         .clone()
-        .ok_or("Not logged in")?;
+        .ok_or(ToxcordError::NotLoggedIn)?;
 
     let gm = GuildManager::new(store);
     let guilds = gm.get_guilds()?;
@@ -112,28 +201,35 @@ pub async fn get_guilds(state: State<'_, AppState>) -> Result<Vec<GuildInfo>, St
 pub async fn get_guild_channels(
     guild_id: String,
     state: State<'_, AppState>,
-) -> Result<Vec<ChannelInfo>, String> {
+) -> CommandResult<Vec<ChannelInfo>> {
     let store = state
         .message_store
         .lock()
         .await
         .clone()
-        .ok_or("Not logged in")?;
+        .ok_or(ToxcordError::NotLoggedIn)?;
 
-    let gm = GuildManager::new(store);
+    let manifest = store.get_guild_manifest(&guild_id)?;
+    let gm = GuildManager::new(store.clone());
     let channels = gm.get_guild_channels(&guild_id)?;
 
-    Ok(channels
+    let channels = channels
         .into_iter()
-        .map(|c| ChannelInfo {
-            id: c.id,
-            guild_id: c.guild_id,
-            name: c.name,
-            topic: c.topic,
-            channel_type: c.channel_type,
-            position: c.position,
+        .map(|c| {
+            let settings = manifest.channel(&c.name);
+            Ok(ChannelInfo {
+                slow_mode_secs: settings.slow_mode_secs,
+                notification_level: store.get_channel_notification_level(&c.id)?,
+                channel_type: if settings.announcement { "announcement".to_string() } else { c.channel_type },
+                id: c.id,
+                guild_id: c.guild_id,
+                name: c.name,
+                topic: c.topic,
+                position: c.position,
+            })
         })
-        .collect())
+        .collect::<Result<Vec<_>, String>>()?;
+    Ok(channels)
 }
 
 #[tauri::command]
@@ -141,18 +237,23 @@ pub async fn create_channel(
     guild_id: String,
     name: String,
     state: State<'_, AppState>,
-) -> Result<ChannelInfo, String> {
+) -> CommandResult<ChannelInfo> {
     let store = state
         .message_store
         .lock()
         .await
         .clone()
-        .ok_or("Not logged in")?;
+        .ok_or(ToxcordError::NotLoggedIn)?;
 
-    let gm = GuildManager::new(store);
+    guild_layout::validate_name(&name)?;
+    let gm = GuildManager::new(store.clone());
     let channel = gm.add_channel(&guild_id, &name)?;
+    share_layout_change(&state, &guild_id, &[(&channel, LayoutChange::Add)]).await;
+    record_channel_audit(&state, &store, &guild_id, "channel_create", &channel.name, None, "").await;
 
     Ok(ChannelInfo {
+        slow_mode_secs: store.get_guild_manifest(&guild_id)?.channel(&channel.name).slow_mode_secs,
+        notification_level: store.get_channel_notification_level(&channel.id)?,
         id: channel.id,
         guild_id: channel.guild_id,
         name: channel.name,
@@ -167,16 +268,22 @@ pub async fn delete_channel(
     guild_id: String,
     channel_id: String,
     state: State<'_, AppState>,
-) -> Result<(), String> {
+) -> CommandResult<()> {
     let store = state
         .message_store
         .lock()
         .await
         .clone()
-        .ok_or("Not logged in")?;
-
-    let gm = GuildManager::new(store);
-    gm.remove_channel(&guild_id, &channel_id)
+        .ok_or(ToxcordError::NotLoggedIn)?;
+
+    let channel = store.get_channel(&channel_id)?;
+    let gm = GuildManager::new(store.clone());
+    gm.remove_channel(&guild_id, &channel_id)?;
+    if let Some(channel) = channel {
+        share_layout_change(&state, &guild_id, &[(&channel, LayoutChange::Delete)]).await;
+        record_channel_audit(&state, &store, &guild_id, "channel_delete", &channel.name, None, "").await;
+    }
+    Ok(())
 }
 
 #[tauri::command]
@@ -185,26 +292,31 @@ pub async fn send_channel_message(
     channel_id: String,
     message: String,
     state: State<'_, AppState>,
-) -> Result<ChannelMessageInfo, String> {
+) -> CommandResult<ChannelMessageInfo> {
     let store = state
         .message_store
         .lock()
         .await
         .clone()
-        .ok_or("Not logged in")?;
+        .ok_or(ToxcordError::NotLoggedIn)?;
     let tox = state
         .tox_manager
         .lock()
         .await
         .clone()
-        .ok_or("Not logged in")?;
+        .ok_or(ToxcordError::NotLoggedIn)?;
 
     let gm = GuildManager::new(store);
     let record = gm
         .send_channel_message(&guild_id, &channel_id, &message, &tox)
         .await?;
 
+    if let Some(bridges) = state.bridges.lock().await.as_ref() {
+        bridges.relay(&channel_id, &record.sender_name, &record.content);
+    }
+
     Ok(ChannelMessageInfo {
+        formatted: markdown::parse(&record.content),
         id: record.id,
         channel_id: record.channel_id,
         sender_public_key: record.sender_public_key,
@@ -213,6 +325,11 @@ pub async fn send_channel_message(
         message_type: record.message_type,
         timestamp: record.timestamp,
         is_own: true,
+        mentions_me: false,
+        filtered: false,
+        backfilled: false,
+        sender_verified: None,
+        signer_public_key: None,
     })
 }
 
@@ -222,24 +339,63 @@ pub async fn get_channel_messages(
     limit: Option<i64>,
     before_timestamp: Option<String>,
     state: State<'_, AppState>,
-) -> Result<Vec<ChannelMessageInfo>, String> {
+) -> CommandResult<Vec<ChannelMessageInfo>> {
     let store = state
         .message_store
         .lock()
         .await
         .clone()
-        .ok_or("Not logged in")?;
+        .ok_or(ToxcordError::NotLoggedIn)?;
 
-    let gm = GuildManager::new(store);
-    let messages = gm.get_channel_messages(
-        &channel_id,
-        limit.unwrap_or(50),
-        before_timestamp.as_deref(),
-    )?;
-
-    // We need our own public key to determine is_own.
-    // Get it from tox_manager if available.
-    let self_pk = if let Some(tox) = state.tox_manager.lock().await.clone() {
+    let messages = store
+        .call(move |store| {
+            store.get_channel_messages(&channel_id, limit.unwrap_or(50), before_timestamp.as_deref())
+        })
+        .await?;
+
+    let self_pk = self_public_key(&state).await;
+    Ok(messages
+        .into_iter()
+        .map(|m| channel_message_info(m, self_pk.as_deref()))
+        .collect())
+}
+
+/// A page of a channel's messages, newest first, older than `before`, for infinite scroll
+#[tauri::command]
+pub async fn get_channel_messages_page(
+    channel_id: String,
+    limit: Option<i64>,
+    before: Option<MessageCursor>,
+    state: State<'_, AppState>,
+) -> CommandResult<MessagePage<ChannelMessageInfo>> {
+    let store = state
+        .message_store
+        .lock()
+        .await
+        .clone()
+        .ok_or(ToxcordError::NotLoggedIn)?;
+
+    let page = store
+        .call(move |store| {
+            store.get_channel_messages_page(&channel_id, limit.unwrap_or(50), before.as_ref())
+        })
+        .await?;
+
+    let self_pk = self_public_key(&state).await;
+    Ok(MessagePage {
+        messages: page
+            .messages
+            .into_iter()
+            .map(|m| channel_message_info(m, self_pk.as_deref()))
+            .collect(),
+        has_more: page.has_more,
+        total: page.total,
+    })
+}
+
+/// Our public key, to tell which messages are our own
+pub(super) async fn self_public_key(state: &AppState) -> Option<String> {
+    if let Some(tox) = state.tox_manager.lock().await.clone() {
         let (tx, rx) = oneshot::channel();
         if tox
             .lock()
@@ -257,76 +413,152 @@ pub async fn get_channel_messages(
         }
     } else {
         None
-    };
+    }
+}
 
-    Ok(messages
+pub(super) fn channel_message_info(m: ChannelMessageRecord, self_pk: Option<&str>) -> ChannelMessageInfo {
+    let is_own = self_pk
+        .map(|pk| m.sender_public_key.to_uppercase() == pk)
+        .unwrap_or(false);
+    ChannelMessageInfo {
+        formatted: super::messaging::format_message(&m.message_type, &m.content),
+        id: m.id,
+        channel_id: m.channel_id,
+        sender_public_key: m.sender_public_key,
+        sender_name: m.sender_name,
+        content: m.content,
+        message_type: m.message_type,
+        timestamp: m.timestamp,
+        is_own,
+        mentions_me: m.mentions_me,
+        filtered: m.filtered,
+        backfilled: m.backfilled,
+        sender_verified: m.sender_verified,
+        signer_public_key: m.signer_public_key,
+    }
+}
+
+/// Members of the channel's guild whose name starts with `prefix`, most recently seen first
+#[tauri::command]
+pub async fn get_mentionable_members(
+    channel_id: String,
+    prefix: String,
+    limit: Option<i64>,
+    state: State<'_, AppState>,
+) -> CommandResult<Vec<MentionableMember>> {
+    let store = state
+        .message_store
+        .lock()
+        .await
+        .clone()
+        .ok_or(ToxcordError::NotLoggedIn)?;
+
+    Ok(store
+        .get_mentionable_members(&channel_id, prefix.trim_start_matches('@'), limit.unwrap_or(10))?
         .into_iter()
-        .map(|m| {
-            let is_own = self_pk
-                .as_ref()
-                .map(|pk| m.sender_public_key.to_uppercase() == *pk)
-                .unwrap_or(false);
-            ChannelMessageInfo {
-                id: m.id,
-                channel_id: m.channel_id,
-                sender_public_key: m.sender_public_key,
-                sender_name: m.sender_name,
-                content: m.content,
-                message_type: m.message_type,
-                timestamp: m.timestamp,
-                is_own,
-            }
+        .map(|m| MentionableMember {
+            mention: markdown::mention(&m.public_key),
+            public_key: m.public_key,
+            name: m.name,
         })
         .collect())
 }
 
+/// Clear the mention badge for a channel once it has been viewed
 #[tauri::command]
-pub async fn invite_to_guild(
-    guild_id: String,
-    friend_number: u32,
+pub async fn mark_mentions_read(
+    channel_id: String,
+    app_handle: tauri::AppHandle,
     state: State<'_, AppState>,
-) -> Result<(), String> {
+) -> CommandResult<()> {
     let store = state
         .message_store
         .lock()
         .await
         .clone()
-        .ok_or("Not logged in")?;
-    let tox = state
-        .tox_manager
+        .ok_or(ToxcordError::NotLoggedIn)?;
+
+    store.mark_mentions_read(&channel_id)?;
+    crate::tray::refresh_unread_badge(&app_handle, &store);
+    Ok(())
+}
+
+/// Set how much of a channel's activity counts as unread and notifies us:
+/// "all", "mentions" or "nothing". `None` goes back to the default, which is
+/// mentions only in large guilds. Applies to messages from then on.
+#[tauri::command]
+pub async fn set_channel_notification_level(
+    channel_id: String,
+    level: Option<String>,
+    state: State<'_, AppState>,
+) -> CommandResult<()> {
+    let store = state
+        .message_store
         .lock()
         .await
         .clone()
-        .ok_or("Not logged in")?;
+        .ok_or(ToxcordError::NotLoggedIn)?;
 
-    let gm = GuildManager::new(store);
-    gm.invite_to_guild(&guild_id, friend_number, &tox).await
+    if let Some(level) = &level {
+        if !matches!(level.as_str(), "all" | "mentions" | "nothing") {
+            return Err(ToxcordError::invalid("Invalid notification level"));
+        }
+    }
+    store.get_channel(&channel_id)?.ok_or_else(|| ToxcordError::not_found("Channel not found"))?;
+    Ok(store.set_channel_notification_level(&channel_id, level.as_deref())?)
 }
 
 #[tauri::command]
-pub async fn accept_guild_invite(
+pub async fn invite_to_guild(
+    guild_id: String,
     friend_number: u32,
-    invite_data: Vec<u8>,
-    group_name: String,
     state: State<'_, AppState>,
-) -> Result<GuildInfo, String> {
+) -> CommandResult<()> {
     let store = state
         .message_store
         .lock()
         .await
         .clone()
-        .ok_or("Not logged in")?;
+        .ok_or(ToxcordError::NotLoggedIn)?;
     let tox = state
         .tox_manager
         .lock()
         .await
         .clone()
-        .ok_or("Not logged in")?;
+        .ok_or(ToxcordError::NotLoggedIn)?;
 
     let gm = GuildManager::new(store);
-    let record = gm
-        .accept_guild_invite(friend_number, &invite_data, &group_name, &tox)
+    Ok(gm.invite_to_guild(&guild_id, friend_number, &tox).await?)
+}
+
+/// Group invites waiting to be accepted or declined
+#[tauri::command]
+pub async fn get_group_invites(state: State<'_, AppState>) -> CommandResult<Vec<GroupInviteRecord>> {
+    let store = state.message_store.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+    Ok(store.get_group_invites()?)
+}
+
+/// `password` is needed if the invite says the group has one
+#[tauri::command]
+pub async fn accept_group_invite(
+    invite_id: String,
+    password: Option<String>,
+    state: State<'_, AppState>,
+) -> CommandResult<GuildInfo> {
+    Ok(join_group_invite(&state, &invite_id, password.as_deref().unwrap_or("")).await?)
+}
+
+/// Join the group of a stored invite and forget the invite. Also how
+/// invites from friends with auto-accept on are joined.
+pub(crate) async fn join_group_invite(state: &AppState, invite_id: &str, password: &str) -> CommandResult<GuildInfo> {
+    let store = state.message_store.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+    let tox = state.tox_manager.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+
+    let invite = store.get_group_invite(invite_id)?.ok_or_else(|| ToxcordError::not_found("Invite not found"))?;
+    let record = GuildManager::new(store.clone())
+        .accept_guild_invite(invite.friend_number as u32, &invite.invite_data, &invite.group_name, password, &tox)
         .await?;
+    store.remove_group_invite(invite_id)?;
 
     Ok(GuildInfo {
         id: record.id,
@@ -338,44 +570,69 @@ pub async fn accept_guild_invite(
     })
 }
 
+#[tauri::command]
+pub async fn decline_group_invite(invite_id: String, state: State<'_, AppState>) -> CommandResult<()> {
+    let store = state.message_store.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+    Ok(store.remove_group_invite(&invite_id)?)
+}
+
+#[tauri::command]
+pub async fn get_group_invite_auto_accept(friend_number: u32, state: State<'_, AppState>) -> CommandResult<bool> {
+    let store = state.message_store.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+    Ok(store.get_auto_accept_group_invites(friend_number)?)
+}
+
+/// Join groups a friend invites us to without asking
+#[tauri::command]
+pub async fn set_group_invite_auto_accept(
+    friend_number: u32,
+    enabled: bool,
+    state: State<'_, AppState>,
+) -> CommandResult<()> {
+    let store = state.message_store.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+    Ok(store.set_auto_accept_group_invites(friend_number, enabled)?)
+}
+
+/// Online members from Tox, followed by offline ones from the member cache
 #[tauri::command]
 pub async fn get_guild_members(
     guild_id: String,
     state: State<'_, AppState>,
-) -> Result<Vec<MemberInfo>, String> {
+) -> CommandResult<Vec<MemberInfo>> {
     let store = state
         .message_store
         .lock()
         .await
         .clone()
-        .ok_or("Not logged in")?;
+        .ok_or(ToxcordError::NotLoggedIn)?;
     let tox = state
         .tox_manager
         .lock()
         .await
         .clone()
-        .ok_or("Not logged in")?;
+        .ok_or(ToxcordError::NotLoggedIn)?;
 
     let guild = GuildManager::new(store.clone())
         .get_guilds()?
         .into_iter()
         .find(|g| g.id == guild_id)
-        .ok_or("Guild not found")?;
+        .ok_or_else(|| ToxcordError::not_found("Guild not found"))?;
 
     let group_number = guild
         .metadata_group_number
         .ok_or("Guild has no group number")? as u32;
 
     let (tx, rx) = oneshot::channel();
-    tox.lock()
-        .await
-        .send_command(ToxCommand::GroupGetPeerList(group_number, tx))
-        .await?;
+    let rich_presence = {
+        let mgr = tox.lock().await;
+        mgr.send_command(ToxCommand::GroupGetPeerList(group_number, tx)).await?;
+        mgr.rich_presence().clone()
+    };
     let peers = rx
         .await
         .map_err(|_| "Failed to receive response".to_string())?;
 
-    Ok(peers
+    let mut members: Vec<MemberInfo> = peers
         .into_iter()
         .map(|p| {
             let role_str = match p.role {
@@ -389,15 +646,86 @@ pub async fn get_guild_members(
                 toxcord_tox::UserStatus::Away => "away",
                 toxcord_tox::UserStatus::Busy => "busy",
             };
+            let activity = rich_presence.activity(&p.public_key);
+            let custom_status = rich_presence.custom_status(&p.public_key);
             MemberInfo {
-                peer_id: p.peer_id,
+                peer_id: Some(p.peer_id),
                 name: p.name,
                 public_key: p.public_key,
                 role: role_str.to_string(),
                 status: status_str.to_string(),
+                last_seen: None,
+                activity,
+                custom_status,
+                ignored: false,
+                custom_role: None,
             }
         })
-        .collect())
+        .collect();
+
+    // Roles are only known while members are online, so remember them
+    for member in &members {
+        if let Err(e) = store.set_guild_member_role(&guild_id, &member.public_key, &member.role) {
+            tracing::warn!("{e}");
+        }
+    }
+
+    let offline: Vec<MemberInfo> = store
+        .get_guild_members(&guild_id)?
+        .into_iter()
+        .filter(|cached| !members.iter().any(|m| m.public_key.eq_ignore_ascii_case(&cached.public_key)))
+        .map(|cached| MemberInfo {
+            peer_id: None,
+            name: cached.name,
+            public_key: cached.public_key,
+            role: cached.role,
+            status: "offline".to_string(),
+            last_seen: Some(cached.last_seen),
+            activity: None,
+            custom_status: None,
+            ignored: false,
+            custom_role: None,
+        })
+        .collect();
+    members.extend(offline);
+
+    let ignored = store.get_ignored_guild_members(&guild_id)?;
+    let manifest = store.get_guild_manifest(&guild_id)?;
+    for member in &mut members {
+        member.ignored = ignored.iter().any(|key| key.eq_ignore_ascii_case(&member.public_key));
+        member.custom_role = manifest.member_role(&member.public_key).cloned();
+    }
+
+    Ok(members)
+}
+
+/// Ignore a guild member: their messages, files and status changes aren't
+/// shown, and messages already stored are left out of history. Only ever
+/// local; they aren't told.
+#[tauri::command]
+pub async fn ignore_guild_member(guild_id: String, public_key: String, state: State<'_, AppState>) -> CommandResult<()> {
+    let store = state.message_store.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+    store.get_guild(&guild_id)?.ok_or_else(|| ToxcordError::not_found("Guild not found"))?;
+    Ok(store.set_guild_member_ignored(&guild_id, &public_key, true)?)
+}
+
+#[tauri::command]
+pub async fn unignore_guild_member(guild_id: String, public_key: String, state: State<'_, AppState>) -> CommandResult<()> {
+    let store = state.message_store.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+    Ok(store.set_guild_member_ignored(&guild_id, &public_key, false)?)
+}
+
+/// Whether messages from ignored members are stored, hidden, rather than dropped
+#[tauri::command]
+pub async fn get_keep_ignored_messages(state: State<'_, AppState>) -> CommandResult<bool> {
+    let store = state.message_store.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+    Ok(keep_ignored_messages(&store))
+}
+
+#[tauri::command]
+pub async fn set_keep_ignored_messages(keep: bool, state: State<'_, AppState>) -> CommandResult<()> {
+    let store = state.message_store.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+    Ok(store.set_setting(KEEP_IGNORED_MESSAGES_SETTING, if keep { "true" } else { "false" })?)
 }
 
 #[tauri::command]
@@ -406,25 +734,25 @@ pub async fn set_channel_topic(
     _channel_id: String,
     topic: String,
     state: State<'_, AppState>,
-) -> Result<(), String> {
+) -> CommandResult<()> {
     let store = state
         .message_store
         .lock()
         .await
         .clone()
-        .ok_or("Not logged in")?;
+        .ok_or(ToxcordError::NotLoggedIn)?;
     let tox = state
         .tox_manager
         .lock()
         .await
         .clone()
-        .ok_or("Not logged in")?;
+        .ok_or(ToxcordError::NotLoggedIn)?;
 
     let guild = GuildManager::new(store)
         .get_guilds()?
         .into_iter()
         .find(|g| g.id == guild_id)
-        .ok_or("Guild not found")?;
+        .ok_or_else(|| ToxcordError::not_found("Guild not found"))?;
 
     let group_number = guild
         .metadata_group_number
@@ -435,8 +763,8 @@ pub async fn set_channel_topic(
         .await
         .send_command(ToxCommand::GroupSetTopic(group_number, topic, tx))
         .await?;
-    rx.await
-        .map_err(|_| "Failed to receive response".to_string())?
+    Ok(rx.await
+        .map_err(|_| "Failed to receive response".to_string())??)
 }
 
 #[tauri::command]
@@ -444,25 +772,25 @@ pub async fn kick_member(
     guild_id: String,
     peer_id: u32,
     state: State<'_, AppState>,
-) -> Result<(), String> {
+) -> CommandResult<()> {
     let store = state
         .message_store
         .lock()
         .await
         .clone()
-        .ok_or("Not logged in")?;
+        .ok_or(ToxcordError::NotLoggedIn)?;
     let tox = state
         .tox_manager
         .lock()
         .await
         .clone()
-        .ok_or("Not logged in")?;
+        .ok_or(ToxcordError::NotLoggedIn)?;
 
     let guild = GuildManager::new(store)
         .get_guilds()?
         .into_iter()
         .find(|g| g.id == guild_id)
-        .ok_or("Guild not found")?;
+        .ok_or_else(|| ToxcordError::not_found("Guild not found"))?;
 
     let group_number = guild
         .metadata_group_number
@@ -473,8 +801,8 @@ pub async fn kick_member(
         .await
         .send_command(ToxCommand::GroupKickPeer(group_number, peer_id, tx))
         .await?;
-    rx.await
-        .map_err(|_| "Failed to receive response".to_string())?
+    Ok(rx.await
+        .map_err(|_| "Failed to receive response".to_string())??)
 }
 
 #[tauri::command]
@@ -483,25 +811,25 @@ pub async fn set_member_role(
     peer_id: u32,
     role: String,
     state: State<'_, AppState>,
-) -> Result<(), String> {
+) -> CommandResult<()> {
     let store = state
         .message_store
         .lock()
         .await
         .clone()
-        .ok_or("Not logged in")?;
+        .ok_or(ToxcordError::NotLoggedIn)?;
     let tox = state
         .tox_manager
         .lock()
         .await
         .clone()
-        .ok_or("Not logged in")?;
+        .ok_or(ToxcordError::NotLoggedIn)?;
 
     let guild = GuildManager::new(store)
         .get_guilds()?
         .into_iter()
         .find(|g| g.id == guild_id)
-        .ok_or("Guild not found")?;
+        .ok_or_else(|| ToxcordError::not_found("Guild not found"))?;
 
     let group_number = guild
         .metadata_group_number
@@ -511,7 +839,7 @@ pub async fn set_member_role(
         "moderator" => 1,
         "user" => 2,
         "observer" => 3,
-        _ => return Err("Invalid role".to_string()),
+        _ => return Err(ToxcordError::invalid("Invalid role")),
     };
 
     let (tx, rx) = oneshot::channel();
@@ -519,8 +847,8 @@ pub async fn set_member_role(
         .await
         .send_command(ToxCommand::GroupSetRole(group_number, peer_id, role_num, tx))
         .await?;
-    rx.await
-        .map_err(|_| "Failed to receive response".to_string())?
+    Ok(rx.await
+        .map_err(|_| "Failed to receive response".to_string())??)
 }
 
 #[tauri::command]
@@ -528,16 +856,16 @@ pub async fn rename_guild(
     guild_id: String,
     name: String,
     state: State<'_, AppState>,
-) -> Result<(), String> {
+) -> CommandResult<()> {
     let store = state
         .message_store
         .lock()
         .await
         .clone()
-        .ok_or("Not logged in")?;
+        .ok_or(ToxcordError::NotLoggedIn)?;
 
     let gm = GuildManager::new(store);
-    gm.update_guild_name(&guild_id, &name)
+    Ok(gm.update_guild_name(&guild_id, &name)?)
 }
 
 #[tauri::command]
@@ -545,38 +873,191 @@ pub async fn rename_channel(
     channel_id: String,
     name: String,
     state: State<'_, AppState>,
-) -> Result<(), String> {
+) -> CommandResult<()> {
     let store = state
         .message_store
         .lock()
         .await
         .clone()
-        .ok_or("Not logged in")?;
+        .ok_or(ToxcordError::NotLoggedIn)?;
+
+    let channel = store.get_channel(&channel_id)?.ok_or_else(|| ToxcordError::not_found("Channel not found"))?;
+    guild_layout::validate_name(&name)?;
+    let gm = GuildManager::new(store.clone());
+    gm.rename_channel(&channel_id, &name)?;
+    share_layout_change(&state, &channel.guild_id, &[(&channel, LayoutChange::Rename(name.clone()))]).await;
+    record_channel_audit(&state, &store, &channel.guild_id, "channel_rename", &channel.name, None, &name).await;
+    Ok(())
+}
 
-    let gm = GuildManager::new(store);
-    gm.rename_channel(&channel_id, &name)
+/// Put a guild's channels in the given order
+#[tauri::command]
+pub async fn reorder_channels(
+    guild_id: String,
+    channel_ids: Vec<String>,
+    state: State<'_, AppState>,
+) -> CommandResult<()> {
+    let store = state
+        .message_store
+        .lock()
+        .await
+        .clone()
+        .ok_or(ToxcordError::NotLoggedIn)?;
+
+    let channels = store.get_channels(&guild_id)?;
+    let mut moved = Vec::new();
+    for (position, channel_id) in channel_ids.iter().enumerate() {
+        let Some(channel) = channels.iter().find(|c| &c.id == channel_id) else {
+            continue;
+        };
+        if channel.position != position as i64 {
+            store.set_channel_position(&channel.id, position as i64)?;
+            moved.push((channel, LayoutChange::Move(position as i64)));
+        }
+    }
+    share_layout_change(&state, &guild_id, &moved).await;
+    Ok(())
 }
 
 #[tauri::command]
 pub async fn leave_guild(
     guild_id: String,
     state: State<'_, AppState>,
-) -> Result<(), String> {
+) -> CommandResult<()> {
     let store = state
         .message_store
         .lock()
         .await
         .clone()
-        .ok_or("Not logged in")?;
+        .ok_or(ToxcordError::NotLoggedIn)?;
     let tox = state
         .tox_manager
         .lock()
         .await
         .clone()
-        .ok_or("Not logged in")?;
+        .ok_or(ToxcordError::NotLoggedIn)?;
 
     let gm = GuildManager::new(store);
-    gm.delete_guild(&guild_id, &tox).await
+    Ok(gm.delete_guild(&guild_id, &tox).await?)
+}
+
+/// Join a guild's group again by its stored chat ID, if the group was lost
+/// from the savedata or joining was refused for a missing password
+#[tauri::command]
+pub async fn rejoin_guild(
+    guild_id: String,
+    password: Option<String>,
+    state: State<'_, AppState>,
+) -> CommandResult<GuildInfo> {
+    let store = state.message_store.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+    let tox = state.tox_manager.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+
+    let record = GuildManager::new(store)
+        .rejoin_guild(&guild_id, password.as_deref().unwrap_or(""), &tox)
+        .await?;
+    Ok(GuildInfo {
+        id: record.id,
+        name: record.name,
+        group_number: record.metadata_group_number,
+        owner_public_key: record.owner_public_key,
+        guild_type: record.guild_type,
+        created_at: record.created_at,
+    })
+}
+
+/// Join a public guild from a `toxcord://join` link
+#[tauri::command]
+pub async fn join_guild_link(
+    chat_id: String,
+    name: Option<String>,
+    password: Option<String>,
+    state: State<'_, AppState>,
+) -> CommandResult<GuildInfo> {
+    let store = state.message_store.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+    let tox = state.tox_manager.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+
+    let record = GuildManager::new(store)
+        .join_guild(&chat_id, name.as_deref().unwrap_or(""), password.as_deref().unwrap_or(""), &tox)
+        .await?;
+    Ok(GuildInfo {
+        id: record.id,
+        name: record.name,
+        group_number: record.metadata_group_number,
+        owner_public_key: record.owner_public_key,
+        guild_type: record.guild_type,
+        created_at: record.created_at,
+    })
+}
+
+/// Set the password needed to join a guild, or remove it with an empty one.
+/// Founder only.
+#[tauri::command]
+pub async fn set_guild_password(guild_id: String, password: String, state: State<'_, AppState>) -> CommandResult<()> {
+    let store = state.message_store.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+    let tox = state.tox_manager.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+    Ok(GuildManager::new(store).set_guild_password(&guild_id, &password, &tox).await?)
+}
+
+/// Whether joining a guild needs a password
+#[tauri::command]
+pub async fn get_guild_password_protected(guild_id: String, state: State<'_, AppState>) -> CommandResult<bool> {
+    let store = state.message_store.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+    let tox = state.tox_manager.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+
+    let guild = store.get_guild(&guild_id)?.ok_or_else(|| ToxcordError::not_found("Guild not found"))?;
+    let group_number = guild
+        .metadata_group_number
+        .ok_or("Guild has no group number")? as u32;
+    let (tx, rx) = oneshot::channel();
+    tox.lock()
+        .await
+        .send_command(ToxCommand::GroupGetInfo(group_number, tx))
+        .await?;
+    let info = rx.await.map_err(|_| "Failed to receive response".to_string())??;
+    Ok(info.password_protected)
+}
+
+// ─── Guild directory ───────────────────────────────────────────────
+
+/// Public guilds in the directory, curated and added by the user. Join one
+/// with `join_guild_link`.
+#[tauri::command]
+pub async fn browse_public_guilds(state: State<'_, AppState>) -> CommandResult<Vec<PublicGuildInfo>> {
+    let store = state.message_store.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+    let joined = store.get_guild_chat_ids()?;
+
+    let guilds = guild_directory::merge(store.get_directory_guilds()?)
+        .into_iter()
+        .map(|entry| PublicGuildInfo {
+            joined_guild_id: joined
+                .iter()
+                .find(|(_, chat_id)| chat_id.eq_ignore_ascii_case(&entry.chat_id))
+                .map(|(guild_id, _)| guild_id.clone()),
+            entry,
+        })
+        .collect();
+    Ok(guilds)
+}
+
+/// Add a public guild to the directory, or update its name and description
+#[tauri::command]
+pub async fn add_public_guild(
+    chat_id: String,
+    name: String,
+    description: String,
+    state: State<'_, AppState>,
+) -> CommandResult<()> {
+    let store = state.message_store.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+    let entry = DirectoryEntry::new(&chat_id, &name, &description)
+        .ok_or_else(|| ToxcordError::invalid("A 64 digit chat ID and a name are needed"))?;
+    Ok(store.add_directory_guild(&entry)?)
+}
+
+/// Remove a guild the user added from the directory
+#[tauri::command]
+pub async fn remove_public_guild(chat_id: String, state: State<'_, AppState>) -> CommandResult<bool> {
+    let store = state.message_store.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+    Ok(store.remove_directory_guild(&chat_id)?)
 }
 
 #[tauri::command]
@@ -584,19 +1065,19 @@ pub async fn create_dm_group(
     name: String,
     friend_numbers: Vec<u32>,
     state: State<'_, AppState>,
-) -> Result<GuildInfo, String> {
+) -> CommandResult<GuildInfo> {
     let store = state
         .message_store
         .lock()
         .await
         .clone()
-        .ok_or("Not logged in")?;
+        .ok_or(ToxcordError::NotLoggedIn)?;
     let tox = state
         .tox_manager
         .lock()
         .await
         .clone()
-        .ok_or("Not logged in")?;
+        .ok_or(ToxcordError::NotLoggedIn)?;
 
     let gm = GuildManager::new(store);
     let record = gm.create_dm_group(&name, &friend_numbers, &tox).await?;
@@ -616,24 +1097,25 @@ pub async fn send_dm_group_message(
     guild_id: String,
     message: String,
     state: State<'_, AppState>,
-) -> Result<ChannelMessageInfo, String> {
+) -> CommandResult<ChannelMessageInfo> {
     let store = state
         .message_store
         .lock()
         .await
         .clone()
-        .ok_or("Not logged in")?;
+        .ok_or(ToxcordError::NotLoggedIn)?;
     let tox = state
         .tox_manager
         .lock()
         .await
         .clone()
-        .ok_or("Not logged in")?;
+        .ok_or(ToxcordError::NotLoggedIn)?;
 
     let gm = GuildManager::new(store);
     let record = gm.send_dm_group_message(&guild_id, &message, &tox).await?;
 
     Ok(ChannelMessageInfo {
+        formatted: markdown::parse(&record.content),
         id: record.id,
         channel_id: record.channel_id,
         sender_public_key: record.sender_public_key,
@@ -642,17 +1124,22 @@ pub async fn send_dm_group_message(
         message_type: record.message_type,
         timestamp: record.timestamp,
         is_own: true,
+        mentions_me: false,
+        filtered: false,
+        backfilled: false,
+        sender_verified: None,
+        signer_public_key: None,
     })
 }
 
 #[tauri::command]
-pub async fn get_dm_groups(state: State<'_, AppState>) -> Result<Vec<GuildInfo>, String> {
+pub async fn get_dm_groups(state: State<'_, AppState>) -> CommandResult<Vec<GuildInfo>> {
     let store = state
         .message_store
         .lock()
         .await
         .clone()
-        .ok_or("Not logged in")?;
+        .ok_or(ToxcordError::NotLoggedIn)?;
 
     let gm = GuildManager::new(store);
     let guilds = gm.get_guilds()?;
@@ -670,3 +1157,827 @@ pub async fn get_dm_groups(state: State<'_, AppState>) -> Result<Vec<GuildInfo>,
         })
         .collect())
 }
+
+// ─── Channel bridges ───────────────────────────────────────────────
+
+/// Link a channel to an IRC channel or Matrix room (guild founder only).
+/// IRC uses `server` as host[:port] and `nickname`; Matrix uses `server` as
+/// the homeserver URL and `access_token`. `target` is the IRC channel or the
+/// Matrix room ID/alias.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn link_channel_bridge(
+    guild_id: String,
+    channel_id: String,
+    protocol: String,
+    server: String,
+    target: String,
+    nickname: Option<String>,
+    access_token: Option<String>,
+    use_tls: Option<bool>,
+    state: State<'_, AppState>,
+) -> CommandResult<ChannelBridgeInfo> {
+    let store = ensure_founder(&state, &guild_id).await?;
+    if !store.get_channels(&guild_id)?.iter().any(|c| c.id == channel_id) {
+        return Err(ToxcordError::not_found("Channel not found"));
+    }
+
+    let protocol = BridgeProtocol::parse(&protocol).ok_or_else(|| format!("Unknown bridge protocol: {protocol}"))?;
+    let server = server.trim().to_string();
+    let target = target.trim().to_string();
+    let nickname = nickname.unwrap_or_default().trim().to_string();
+    let access_token = access_token.unwrap_or_default().trim().to_string();
+    match protocol {
+        BridgeProtocol::Irc => {
+            if server.is_empty() {
+                return Err(ToxcordError::invalid("IRC server is required"));
+            }
+            if !target.starts_with(['#', '&']) || target.contains([' ', ',']) {
+                return Err(ToxcordError::invalid("IRC target must be a channel like #toxcord"));
+            }
+            if nickname.is_empty() || nickname.contains(' ') {
+                return Err(ToxcordError::invalid("IRC nickname is required and can't contain spaces"));
+            }
+        }
+        BridgeProtocol::Matrix => {
+            if !server.starts_with("https://") && !server.starts_with("http://") {
+                return Err(ToxcordError::invalid("Matrix homeserver must be an http(s) URL"));
+            }
+            if !target.starts_with(['!', '#']) {
+                return Err(ToxcordError::invalid("Matrix target must be a room ID or alias"));
+            }
+            if access_token.is_empty() {
+                return Err(ToxcordError::invalid("Matrix access token is required"));
+            }
+        }
+    }
+
+    let bridge = ChannelBridgeRecord {
+        channel_id: channel_id.clone(),
+        guild_id,
+        protocol: protocol.as_str().to_string(),
+        server,
+        target,
+        nickname,
+        access_token,
+        use_tls: use_tls.unwrap_or(true),
+        enabled: true,
+        created_at: String::new(),
+    };
+    store.set_channel_bridge(&bridge)?;
+    let running = match state.bridges.lock().await.as_ref() {
+        Some(bridges) => {
+            bridges.connect(bridge);
+            true
+        }
+        None => false,
+    };
+    let bridge = store
+        .get_channel_bridge(&channel_id)?
+        .ok_or("Bridge not found after saving")?;
+    Ok(ChannelBridgeInfo { bridge, running })
+}
+
+/// Remove a channel's bridge (guild founder only)
+#[tauri::command]
+pub async fn unlink_channel_bridge(
+    guild_id: String,
+    channel_id: String,
+    state: State<'_, AppState>,
+) -> CommandResult<()> {
+    let store = ensure_founder(&state, &guild_id).await?;
+    if let Some(bridges) = state.bridges.lock().await.as_ref() {
+        bridges.disconnect(&channel_id);
+    }
+    if !store.remove_channel_bridge(&channel_id)? {
+        return Err(ToxcordError::invalid("Channel is not bridged"));
+    }
+    Ok(())
+}
+
+/// Pause or resume a channel's bridge without forgetting its settings
+#[tauri::command]
+pub async fn set_channel_bridge_enabled(
+    guild_id: String,
+    channel_id: String,
+    enabled: bool,
+    state: State<'_, AppState>,
+) -> CommandResult<()> {
+    let store = ensure_founder(&state, &guild_id).await?;
+    let bridge = store
+        .get_channel_bridge(&channel_id)?
+        .ok_or("Channel is not bridged")?;
+    store.set_channel_bridge_enabled(&channel_id, enabled)?;
+
+    if let Some(bridges) = state.bridges.lock().await.as_ref() {
+        if enabled {
+            bridges.connect(ChannelBridgeRecord { enabled, ..bridge });
+        } else {
+            bridges.disconnect(&channel_id);
+        }
+    }
+    Ok(())
+}
+
+#[derive(serde::Serialize)]
+pub struct ChannelBridgeInfo {
+    #[serde(flatten)]
+    pub bridge: ChannelBridgeRecord,
+    /// Whether the bridge task is running (it may still be reconnecting)
+    pub running: bool,
+}
+
+/// Bridges of a guild's channels. Access tokens are never returned.
+#[tauri::command]
+pub async fn get_channel_bridges(
+    guild_id: String,
+    state: State<'_, AppState>,
+) -> CommandResult<Vec<ChannelBridgeInfo>> {
+    let store = state
+        .message_store
+        .lock()
+        .await
+        .clone()
+        .ok_or(ToxcordError::NotLoggedIn)?;
+    let bridges = state.bridges.lock().await;
+
+    Ok(store
+        .get_channel_bridges()?
+        .into_iter()
+        .filter(|b| b.guild_id == guild_id)
+        .map(|bridge| ChannelBridgeInfo {
+            running: bridges.as_ref().is_some_and(|m| m.is_running(&bridge.channel_id)),
+            bridge,
+        })
+        .collect())
+}
+
+// ─── Guild manifest ────────────────────────────────────────────────
+
+/// Set a channel's slow mode (seconds between messages per member, 0 for off)
+/// and share it with the guild. Founders and moderators only.
+#[tauri::command]
+pub async fn set_channel_slow_mode(
+    guild_id: String,
+    channel_id: String,
+    seconds: u32,
+    state: State<'_, AppState>,
+) -> CommandResult<()> {
+    let (store, tox, group_number) = ensure_moderator(&state, &guild_id).await?;
+    let channel = store.get_channel(&channel_id)?.ok_or_else(|| ToxcordError::not_found("Channel not found"))?;
+
+    let mut manifest = store.get_guild_manifest(&guild_id)?;
+    manifest.set_slow_mode(&channel.name, seconds)?;
+    manifest.version += 1;
+    store.set_guild_manifest(&guild_id, &manifest)?;
+    Ok(broadcast_manifest(&tox, group_number, &manifest).await?)
+}
+
+/// Hand the guild to another member: they're made a moderator and named owner
+/// in the manifest, and we stay on as a co-founder. Owner only.
+#[tauri::command]
+pub async fn transfer_ownership(
+    guild_id: String,
+    peer_id: u32,
+    state: State<'_, AppState>,
+) -> CommandResult<()> {
+    let store = ensure_founder(&state, &guild_id).await?;
+    let tox = state.tox_manager.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+    let group_number = store
+        .get_guild(&guild_id)?
+        .and_then(|g| g.metadata_group_number)
+        .ok_or("Guild has no group number")? as u32;
+
+    let (peers_tx, peers_rx) = oneshot::channel();
+    let (pk_tx, pk_rx) = oneshot::channel();
+    {
+        let tox = tox.lock().await;
+        tox.send_command(ToxCommand::GroupGetPeerList(group_number, peers_tx)).await?;
+        tox.send_command(ToxCommand::GroupGetSelfPk(group_number, pk_tx)).await?;
+    }
+    let peer = peers_rx
+        .await
+        .map_err(|_| "Failed to receive response".to_string())?
+        .into_iter()
+        .find(|p| p.peer_id == peer_id)
+        .ok_or("That member isn't online")?;
+    let self_pk = pk_rx
+        .await
+        .map_err(|_| "Failed to receive response".to_string())??;
+
+    let mut manifest = store.get_guild_manifest(&guild_id)?;
+    manifest.transfer_ownership(&self_pk, &peer.public_key)?;
+
+    // Moderators can manage the group itself; only the founder can make them
+    if !matches!(peer.role, toxcord_tox::GroupRole::Founder | toxcord_tox::GroupRole::Moderator) {
+        let (tx, rx) = oneshot::channel();
+        tox.lock()
+            .await
+            .send_command(ToxCommand::GroupSetRole(group_number, peer_id, 1, tx))
+            .await?;
+        if let Err(e) = rx.await.map_err(|_| "Failed to receive response".to_string())? {
+            tracing::warn!("Couldn't make the new owner a moderator: {e}");
+        }
+    }
+
+    manifest.version += 1;
+    store.set_guild_manifest(&guild_id, &manifest)?;
+    store.update_guild_owner(&guild_id, &manifest.owner)?;
+    broadcast_manifest(&tox, group_number, &manifest).await?;
+    record_channel_audit(&state, &store, &guild_id, "ownership_transfer", &peer.name, Some(&peer.public_key), "").await;
+    Ok(())
+}
+
+/// Make a channel an announcement channel, where only founders and moderators
+/// can post, or a normal one again. Founders and moderators only.
+#[tauri::command]
+pub async fn set_channel_announcement(
+    guild_id: String,
+    channel_id: String,
+    announcement: bool,
+    state: State<'_, AppState>,
+) -> CommandResult<()> {
+    let (store, tox, group_number) = ensure_moderator(&state, &guild_id).await?;
+    let channel = store.get_channel(&channel_id)?.ok_or_else(|| ToxcordError::not_found("Channel not found"))?;
+
+    let mut manifest = store.get_guild_manifest(&guild_id)?;
+    manifest.set_announcement(&channel.name, announcement);
+    manifest.version += 1;
+    store.set_guild_manifest(&guild_id, &manifest)?;
+    Ok(broadcast_manifest(&tox, group_number, &manifest).await?)
+}
+
+#[tauri::command]
+pub async fn get_word_filter(guild_id: String, state: State<'_, AppState>) -> CommandResult<WordFilter> {
+    let store = state
+        .message_store
+        .lock()
+        .await
+        .clone()
+        .ok_or(ToxcordError::NotLoggedIn)?;
+    Ok(store.get_guild_manifest(&guild_id)?.word_filter)
+}
+
+/// Replace the guild's word filter and share it with the guild. Applies to
+/// messages received from then on. Founders and moderators only.
+#[tauri::command]
+pub async fn set_word_filter(
+    guild_id: String,
+    rules: Vec<FilterRule>,
+    action: FilterAction,
+    state: State<'_, AppState>,
+) -> CommandResult<()> {
+    let (store, tox, group_number) = ensure_moderator(&state, &guild_id).await?;
+    let word_filter = WordFilter { rules, action };
+    word_filter.validate()?;
+
+    let mut manifest = store.get_guild_manifest(&guild_id)?;
+    manifest.word_filter = word_filter;
+    manifest.version += 1;
+    store.set_guild_manifest(&guild_id, &manifest)?;
+    Ok(broadcast_manifest(&tox, group_number, &manifest).await?)
+}
+
+#[tauri::command]
+pub async fn get_guild_onboarding(guild_id: String, state: State<'_, AppState>) -> CommandResult<OnboardingInfo> {
+    let store = state
+        .message_store
+        .lock()
+        .await
+        .clone()
+        .ok_or(ToxcordError::NotLoggedIn)?;
+    let onboarding = store.get_guild_manifest(&guild_id)?.onboarding;
+    let accepted = store.get_accepted_guild_rules(&guild_id)?;
+    Ok(OnboardingInfo {
+        may_post: onboarding.may_post(accepted.as_deref()),
+        onboarding,
+    })
+}
+
+/// Set the welcome message shown when someone new joins, the rules, and
+/// whether members must accept the rules before posting, and share them with
+/// the guild. Founders and moderators only; we accept the new rules ourselves.
+#[tauri::command]
+pub async fn set_guild_onboarding(
+    guild_id: String,
+    welcome_message: String,
+    rules: String,
+    require_rules_ack: bool,
+    state: State<'_, AppState>,
+) -> CommandResult<()> {
+    let (store, tox, group_number) = ensure_moderator(&state, &guild_id).await?;
+    let onboarding = Onboarding { welcome_message, rules, require_rules_ack };
+    onboarding.validate()?;
+
+    let mut manifest = store.get_guild_manifest(&guild_id)?;
+    manifest.onboarding = onboarding;
+    manifest.version += 1;
+    store.set_guild_manifest(&guild_id, &manifest)?;
+    store.accept_guild_rules(&guild_id, &manifest.onboarding.rules_hash())?;
+    Ok(broadcast_manifest(&tox, group_number, &manifest).await?)
+}
+
+/// Accept the guild's rules as they are now. Only recorded locally.
+#[tauri::command]
+pub async fn accept_guild_rules(guild_id: String, state: State<'_, AppState>) -> CommandResult<()> {
+    let store = state
+        .message_store
+        .lock()
+        .await
+        .clone()
+        .ok_or(ToxcordError::NotLoggedIn)?;
+    let onboarding = store.get_guild_manifest(&guild_id)?.onboarding;
+    if onboarding.rules.trim().is_empty() {
+        return Err(ToxcordError::invalid("This server has no rules to accept"));
+    }
+    Ok(store.accept_guild_rules(&guild_id, &onboarding.rules_hash())?)
+}
+
+#[tauri::command]
+pub async fn get_guild_roles(guild_id: String, state: State<'_, AppState>) -> CommandResult<Vec<CustomRole>> {
+    let store = state
+        .message_store
+        .lock()
+        .await
+        .clone()
+        .ok_or(ToxcordError::NotLoggedIn)?;
+    Ok(store.get_guild_manifest(&guild_id)?.roles)
+}
+
+/// Add a custom role below the existing ones and share it with the guild.
+/// Returns its id. Founders and moderators only.
+#[tauri::command]
+pub async fn create_guild_role(
+    guild_id: String,
+    name: String,
+    color: String,
+    icon: String,
+    base_role: BaseRole,
+    state: State<'_, AppState>,
+) -> CommandResult<u32> {
+    let (store, tox, group_number) = ensure_moderator(&state, &guild_id).await?;
+    let mut manifest = store.get_guild_manifest(&guild_id)?;
+    let id = manifest.add_role(&name, &color, &icon, base_role)?;
+    manifest.version += 1;
+    store.set_guild_manifest(&guild_id, &manifest)?;
+    broadcast_manifest(&tox, group_number, &manifest).await?;
+    Ok(id)
+}
+
+/// Change a custom role. Online members who have it are given its NGC role
+/// if that changed. Founders and moderators only.
+#[tauri::command]
+pub async fn update_guild_role(guild_id: String, role: CustomRole, state: State<'_, AppState>) -> CommandResult<()> {
+    let (store, tox, group_number) = ensure_moderator(&state, &guild_id).await?;
+    let mut manifest = store.get_guild_manifest(&guild_id)?;
+    let old_base = manifest.role(role.id).ok_or_else(|| ToxcordError::not_found("Role not found"))?.base_role;
+    let (id, base_role) = (role.id, role.base_role);
+    manifest.update_role(role)?;
+
+    if base_role != old_base {
+        for (public_key, _) in manifest.member_roles.iter().filter(|(_, r)| **r == id) {
+            if let Err(e) = apply_base_role(&tox, group_number, public_key, base_role).await {
+                tracing::warn!("Couldn't update the NGC role of {public_key}: {e}");
+            }
+        }
+    }
+
+    manifest.version += 1;
+    store.set_guild_manifest(&guild_id, &manifest)?;
+    Ok(broadcast_manifest(&tox, group_number, &manifest).await?)
+}
+
+/// Delete a custom role. Its members keep their NGC role. Founders and
+/// moderators only.
+#[tauri::command]
+pub async fn delete_guild_role(guild_id: String, role_id: u32, state: State<'_, AppState>) -> CommandResult<()> {
+    let (store, tox, group_number) = ensure_moderator(&state, &guild_id).await?;
+    let mut manifest = store.get_guild_manifest(&guild_id)?;
+    manifest.remove_role(role_id)?;
+    manifest.version += 1;
+    store.set_guild_manifest(&guild_id, &manifest)?;
+    Ok(broadcast_manifest(&tox, group_number, &manifest).await?)
+}
+
+/// Put the custom roles in a new order, highest first. Founders and
+/// moderators only.
+#[tauri::command]
+pub async fn reorder_guild_roles(guild_id: String, role_ids: Vec<u32>, state: State<'_, AppState>) -> CommandResult<()> {
+    let (store, tox, group_number) = ensure_moderator(&state, &guild_id).await?;
+    let mut manifest = store.get_guild_manifest(&guild_id)?;
+    manifest.reorder_roles(&role_ids)?;
+    manifest.version += 1;
+    store.set_guild_manifest(&guild_id, &manifest)?;
+    Ok(broadcast_manifest(&tox, group_number, &manifest).await?)
+}
+
+/// Give a member a custom role, or take theirs away with `None`. If they're
+/// online they're given the role's NGC role now, otherwise when they next
+/// join. Founders and moderators only.
+#[tauri::command]
+pub async fn assign_guild_role(
+    guild_id: String,
+    public_key: String,
+    role_id: Option<u32>,
+    state: State<'_, AppState>,
+) -> CommandResult<()> {
+    let (store, tox, group_number) = ensure_moderator(&state, &guild_id).await?;
+    let mut manifest = store.get_guild_manifest(&guild_id)?;
+    manifest.assign_role(&public_key, role_id)?;
+    if let Some(role) = manifest.member_role(&public_key) {
+        apply_base_role(&tox, group_number, &public_key, role.base_role).await?;
+    }
+    manifest.version += 1;
+    store.set_guild_manifest(&guild_id, &manifest)?;
+    Ok(broadcast_manifest(&tox, group_number, &manifest).await?)
+}
+
+/// Give an online member the NGC role behind a custom role. Offline members
+/// and the founder are left alone.
+async fn apply_base_role(
+    tox: &std::sync::Arc<tokio::sync::Mutex<ToxManager>>,
+    group_number: u32,
+    public_key: &str,
+    base_role: BaseRole,
+) -> Result<(), String> {
+    let (tx, rx) = oneshot::channel();
+    tox.lock()
+        .await
+        .send_command(ToxCommand::GroupGetPeerList(group_number, tx))
+        .await?;
+    let peers = rx
+        .await
+        .map_err(|_| "Failed to receive response".to_string())?;
+    let Some(peer) = peers.into_iter().find(|p| p.public_key.eq_ignore_ascii_case(public_key)) else {
+        return Ok(());
+    };
+    let (role_num, wanted) = match base_role {
+        BaseRole::Moderator => (1, toxcord_tox::GroupRole::Moderator),
+        BaseRole::User => (2, toxcord_tox::GroupRole::User),
+        BaseRole::Observer => (3, toxcord_tox::GroupRole::Observer),
+    };
+    if peer.role == wanted || peer.role == toxcord_tox::GroupRole::Founder {
+        return Ok(());
+    }
+
+    let (tx, rx) = oneshot::channel();
+    tox.lock()
+        .await
+        .send_command(ToxCommand::GroupSetRole(group_number, peer.peer_id, role_num, tx))
+        .await?;
+    rx.await
+        .map_err(|_| "Failed to receive response".to_string())?
+}
+
+async fn broadcast_manifest(
+    tox: &std::sync::Arc<tokio::sync::Mutex<ToxManager>>,
+    group_number: u32,
+    manifest: &GuildManifest,
+) -> Result<(), String> {
+    let (tx, rx) = oneshot::channel();
+    tox.lock()
+        .await
+        .send_command(ToxCommand::GroupSendCustomPacket(group_number, manifest.to_packet(), tx))
+        .await?;
+    rx.await
+        .map_err(|_| "Failed to receive response".to_string())?
+        .map_err(|e| format!("Failed to share guild settings: {e}"))
+}
+
+// ─── Bulk deletion ─────────────────────────────────────────────────
+
+/// Delete a channel's messages older than `before_timestamp`, or its last
+/// `last_n` messages, for everyone in the guild. Founders and moderators only.
+/// Returns how many messages were deleted locally.
+#[tauri::command]
+pub async fn purge_channel_messages(
+    guild_id: String,
+    channel_id: String,
+    before_timestamp: Option<String>,
+    last_n: Option<i64>,
+    state: State<'_, AppState>,
+) -> CommandResult<usize> {
+    let (store, tox, group_number) = ensure_moderator(&state, &guild_id).await?;
+    let channel = store
+        .get_channel(&channel_id)?
+        .filter(|c| c.guild_id == guild_id)
+        .ok_or_else(|| ToxcordError::not_found("Channel not found"))?;
+
+    // Local timestamps bound the local delete; peers get whole seconds
+    let (since, before) = match (before_timestamp, last_n) {
+        (Some(before), None) => (None, Some(before)),
+        (None, Some(n)) if n > 0 => {
+            let newest = store.get_channel_messages(&channel_id, n, None)?;
+            match newest.last() {
+                Some(oldest) => (Some(oldest.timestamp.clone()), None),
+                None => return Ok(0),
+            }
+        }
+        _ => return Err(ToxcordError::invalid("Give either a timestamp or a positive message count")),
+    };
+    let to_secs = |timestamp: &Option<String>| -> Result<Option<i64>, String> {
+        timestamp
+            .as_deref()
+            .map(|t| {
+                chrono::DateTime::parse_from_rfc3339(t)
+                    .map(|t| t.timestamp())
+                    .map_err(|e| format!("Invalid timestamp: {e}"))
+            })
+            .transpose()
+    };
+    let packet = PurgePacket::Range {
+        channel: channel.name.clone(),
+        since: to_secs(&since)?,
+        before: to_secs(&before)?,
+    };
+    send_purge(&tox, group_number, &packet).await?;
+
+    let count = store.delete_channel_messages_in_range(&channel_id, since.as_deref(), before.as_deref())?;
+    let details = format!("{count} messages");
+    record_channel_audit(&state, &store, &guild_id, "message_purge", &channel.name, None, &details).await;
+    Ok(count)
+}
+
+/// Delete every message a member sent in a channel, for everyone in the guild.
+/// Founders and moderators only. Returns how many messages were deleted locally.
+#[tauri::command]
+pub async fn delete_messages_from_peer(
+    guild_id: String,
+    channel_id: String,
+    public_key: String,
+    state: State<'_, AppState>,
+) -> CommandResult<usize> {
+    let (store, tox, group_number) = ensure_moderator(&state, &guild_id).await?;
+    let channel = store
+        .get_channel(&channel_id)?
+        .filter(|c| c.guild_id == guild_id)
+        .ok_or_else(|| ToxcordError::not_found("Channel not found"))?;
+
+    let packet = PurgePacket::FromMember {
+        channel: channel.name.clone(),
+        public_key: public_key.to_uppercase(),
+    };
+    send_purge(&tox, group_number, &packet).await?;
+
+    let count = store.delete_channel_messages_from(&channel_id, &public_key)?;
+    let details = format!("{count} messages");
+    record_channel_audit(&state, &store, &guild_id, "message_purge", &channel.name, Some(&public_key), &details).await;
+    Ok(count)
+}
+
+async fn send_purge(
+    tox: &std::sync::Arc<tokio::sync::Mutex<ToxManager>>,
+    group_number: u32,
+    packet: &PurgePacket,
+) -> Result<(), String> {
+    let (tx, rx) = oneshot::channel();
+    tox.lock()
+        .await
+        .send_command(ToxCommand::GroupSendCustomPacket(group_number, packet.to_bytes(), tx))
+        .await?;
+    rx.await
+        .map_err(|_| "Failed to receive response".to_string())?
+        .map_err(|e| format!("Failed to send purge: {e}"))
+}
+
+// ─── Audit log ─────────────────────────────────────────────────────
+
+/// Moderation history of a guild, newest first. Founders and moderators only.
+#[tauri::command]
+pub async fn get_audit_log(
+    guild_id: String,
+    limit: Option<i64>,
+    before_id: Option<i64>,
+    state: State<'_, AppState>,
+) -> CommandResult<Vec<AuditLogRecord>> {
+    let (store, _, _) = ensure_moderator(&state, &guild_id)
+        .await
+        .map_err(|_| "Only founders and moderators can view the audit log".to_string())?;
+    Ok(store.get_audit_log(&guild_id, limit.unwrap_or(50).clamp(1, 200), before_id)?)
+}
+
+/// Record a channel change we made, optionally aimed at one member. Channels
+/// are local, so this is best effort and never fails the change itself.
+async fn record_channel_audit(
+    state: &AppState,
+    store: &crate::db::MessageStore,
+    guild_id: &str,
+    action: &str,
+    channel_name: &str,
+    member_public_key: Option<&str>,
+    details: &str,
+) {
+    let Some(tox) = state.tox_manager.lock().await.clone() else {
+        return;
+    };
+    let group_number = match store.get_guild(guild_id) {
+        Ok(Some(guild)) => match guild.metadata_group_number {
+            Some(n) => n as u32,
+            None => return,
+        },
+        _ => return,
+    };
+
+    let (pk_tx, pk_rx) = oneshot::channel();
+    let (profile_tx, profile_rx) = oneshot::channel();
+    {
+        let tox = tox.lock().await;
+        if tox.send_command(ToxCommand::GroupGetSelfPk(group_number, pk_tx)).await.is_err()
+            || tox.send_command(ToxCommand::GetProfileInfo(profile_tx)).await.is_err()
+        {
+            return;
+        }
+    }
+    let (Ok(Ok(self_pk)), Ok(profile)) = (pk_rx.await, profile_rx.await) else {
+        return;
+    };
+
+    if let Err(e) =
+        store.add_audit_entry(guild_id, action, &self_pk, &profile.name, member_public_key, channel_name, details)
+    {
+        tracing::warn!("{e}");
+    }
+}
+
+/// Check that we're a founder or moderator of the guild; returns the store,
+/// the tox manager and the guild's group number
+async fn ensure_moderator(
+    state: &AppState,
+    guild_id: &str,
+) -> CommandResult<(std::sync::Arc<crate::db::MessageStore>, std::sync::Arc<tokio::sync::Mutex<ToxManager>>, u32)> {
+    let store = state
+        .message_store
+        .lock()
+        .await
+        .clone()
+        .ok_or(ToxcordError::NotLoggedIn)?;
+    let tox = state
+        .tox_manager
+        .lock()
+        .await
+        .clone()
+        .ok_or(ToxcordError::NotLoggedIn)?;
+
+    let guild = store.get_guild(guild_id)?.ok_or_else(|| ToxcordError::not_found("Guild not found"))?;
+    let group_number = guild
+        .metadata_group_number
+        .ok_or("Guild has no group number")? as u32;
+
+    let (tx, rx) = oneshot::channel();
+    tox.lock()
+        .await
+        .send_command(ToxCommand::GroupGetSelfRole(group_number, tx))
+        .await?;
+    let role = rx
+        .await
+        .map_err(|_| "Failed to receive response".to_string())??;
+    if !matches!(role, toxcord_tox::GroupRole::Founder | toxcord_tox::GroupRole::Moderator)
+        && !is_co_founder(&store, &tox, guild_id, group_number).await?
+    {
+        return Err(ToxcordError::NotPermitted(
+            "Only founders and moderators can change guild settings".to_string(),
+        ));
+    }
+    Ok((store, tox, group_number))
+}
+
+/// A channel change to record in the guild's layout
+enum LayoutChange {
+    Add,
+    Rename(String),
+    Move(i64),
+    Delete,
+}
+
+/// Record channel changes in the guild's layout and share them with the
+/// guild, if it's a server we moderate. Changes made by other members stay
+/// local. The channels are as they were before the change.
+async fn share_layout_change(state: &AppState, guild_id: &str, changes: &[(&ChannelRecord, LayoutChange)]) {
+    if changes.is_empty() {
+        return;
+    }
+    if let Err(e) = try_share_layout_change(state, guild_id, changes).await {
+        tracing::warn!("Couldn't share channel changes in guild {guild_id}: {e}");
+    }
+}
+
+async fn try_share_layout_change(
+    state: &AppState,
+    guild_id: &str,
+    changes: &[(&ChannelRecord, LayoutChange)],
+) -> CommandResult<()> {
+    let store = state.message_store.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+    if store.get_guild(guild_id)?.is_none_or(|g| g.guild_type != "server") {
+        return Ok(());
+    }
+    let Ok((store, tox, group_number)) = ensure_moderator(state, guild_id).await else {
+        return Ok(());
+    };
+
+    let (pk_tx, pk_rx) = oneshot::channel();
+    tox.lock()
+        .await
+        .send_command(ToxCommand::GroupGetSelfPk(group_number, pk_tx))
+        .await?;
+    let self_pk = pk_rx
+        .await
+        .map_err(|_| "Failed to receive response".to_string())??;
+
+    let mut layout = store.get_guild_layout(guild_id)?;
+    let mut keys = Vec::new();
+    for (channel, change) in changes {
+        let key = match store.get_channel_layout_key(&channel.id)? {
+            Some(key) => key,
+            None => {
+                // First time this channel is shared: track it by its current name
+                let key = layout.new_key(&channel.name, || uuid::Uuid::new_v4().to_string());
+                layout.add_channel(&key, &channel.name, channel.position, &self_pk);
+                store.set_channel_layout_key(&channel.id, &key)?;
+                key
+            }
+        };
+        match change {
+            LayoutChange::Add => {}
+            LayoutChange::Rename(name) => layout.rename_channel(&key, name, &self_pk)?,
+            LayoutChange::Move(position) => layout.move_channel(&key, *position, &self_pk)?,
+            LayoutChange::Delete => layout.delete_channel(&key, &self_pk)?,
+        }
+        keys.push(key);
+    }
+    store.set_guild_layout(guild_id, &layout)?;
+
+    let keys: Vec<&str> = keys.iter().map(String::as_str).collect();
+    for packet in layout.select(&keys).to_packets() {
+        broadcast_packet(&tox, group_number, packet).await?;
+    }
+    Ok(())
+}
+
+async fn broadcast_packet(
+    tox: &std::sync::Arc<tokio::sync::Mutex<ToxManager>>,
+    group_number: u32,
+    packet: Vec<u8>,
+) -> Result<(), String> {
+    let (tx, rx) = oneshot::channel();
+    tox.lock()
+        .await
+        .send_command(ToxCommand::GroupSendCustomPacket(group_number, packet, tx))
+        .await?;
+    rx.await
+        .map_err(|_| "Failed to receive response".to_string())?
+        .map_err(|e| format!("Failed to share channel changes: {e}"))
+}
+
+/// Whether we own or co-founded the guild, by its manifest
+async fn is_co_founder(
+    store: &crate::db::MessageStore,
+    tox: &tokio::sync::Mutex<ToxManager>,
+    guild_id: &str,
+    group_number: u32,
+) -> Result<bool, String> {
+    let (tx, rx) = oneshot::channel();
+    tox.lock()
+        .await
+        .send_command(ToxCommand::GroupGetSelfPk(group_number, tx))
+        .await?;
+    let self_pk = rx
+        .await
+        .map_err(|_| "Failed to receive response".to_string())??;
+    Ok(store.get_guild_manifest(guild_id)?.can_manage(&self_pk))
+}
+
+/// Check that we founded the guild; returns the store
+async fn ensure_founder(state: &AppState, guild_id: &str) -> CommandResult<std::sync::Arc<crate::db::MessageStore>> {
+    let store = state
+        .message_store
+        .lock()
+        .await
+        .clone()
+        .ok_or(ToxcordError::NotLoggedIn)?;
+    let tox = state
+        .tox_manager
+        .lock()
+        .await
+        .clone()
+        .ok_or(ToxcordError::NotLoggedIn)?;
+
+    let guild = store.get_guild(guild_id)?.ok_or_else(|| ToxcordError::not_found("Guild not found"))?;
+    let group_number = guild
+        .metadata_group_number
+        .ok_or("Guild has no group number")? as u32;
+
+    let (tx, rx) = oneshot::channel();
+    tox.lock()
+        .await
+        .send_command(ToxCommand::GroupGetSelfPk(group_number, tx))
+        .await?;
+    let self_pk = rx
+        .await
+        .map_err(|_| "Failed to receive response".to_string())??;
+
+    let owner = !guild.owner_public_key.is_empty() && guild.owner_public_key.eq_ignore_ascii_case(&self_pk);
+    if !owner && !store.get_guild_manifest(guild_id)?.can_manage(&self_pk) {
+        return Err(ToxcordError::NotPermitted("Only the guild founder can manage bridges".to_string()));
+    }
+    Ok(store)
+}
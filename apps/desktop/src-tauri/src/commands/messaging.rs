@@ -1,97 +1,200 @@
 use tauri::State;
 use tokio::sync::oneshot;
+use toxcord_core::conversation_lock::{derive_key, ConversationLocks, Opened};
+use toxcord_protocol::conversation_lock::{self, LOCK_PREFIX};
+use toxcord_protocol::disappearing;
+use toxcord_protocol::links::Link;
+use toxcord_protocol::markdown;
 
-use crate::db::message_store::DirectMessageRecord;
-use crate::managers::tox_manager::ToxCommand;
+use super::guilds::{channel_message_info, self_public_key, ChannelMessageInfo};
+use crate::accessibility::Conversation;
+use crate::db::message_store::{
+    DirectMessageRecord, MessageAnchor, MessageCursor, MessageHistory, MessagePage, MessageWindow,
+    RecentConversationRecord, ScheduledMessageRecord,
+};
+use crate::db::MessageStore;
+use crate::error::{CommandResult, ToxcordError};
+use crate::managers::av_manager::CallStatus;
+use crate::managers::incognito;
+use crate::managers::tox_manager::{AutoReplySettings, ToxCommand};
+use crate::managers::usage_stats::{self, UsageStats};
 use crate::AppState;
 
+/// A direct message with its content parsed for rendering
+#[derive(serde::Serialize)]
+pub struct DirectMessageInfo {
+    #[serde(flatten)]
+    pub record: DirectMessageRecord,
+    pub formatted: Vec<markdown::Node>,
+    /// Encrypted with a conversation passphrase we haven't entered; `content` is empty
+    pub locked: bool,
+}
+
+impl DirectMessageInfo {
+    /// Decrypt the stored content if it's a conversation lock envelope
+    fn open(mut record: DirectMessageRecord, locks: &ConversationLocks) -> Self {
+        let locked = match locks.open(record.friend_number as u32, &record.content) {
+            Opened::Plain(_) => false,
+            Opened::Decrypted(text) => {
+                record.content = text;
+                false
+            }
+            Opened::Locked => {
+                record.content.clear();
+                true
+            }
+        };
+        let formatted = format_message(&record.message_type, &record.content);
+        Self { record, formatted, locked }
+    }
+}
+
+/// Parse the markdown of a text message. File and voice messages hold a filename and stickers a
+/// reference, so they get none.
+pub(crate) fn format_message(message_type: &str, content: &str) -> Vec<markdown::Node> {
+    match message_type {
+        "normal" | "action" => markdown::parse(content),
+        _ => Vec::new(),
+    }
+}
+
 #[tauri::command]
 pub async fn send_direct_message(
     state: State<'_, AppState>,
     friend_number: u32,
     message: String,
-) -> Result<serde_json::Value, String> {
+) -> CommandResult<serde_json::Value> {
     if message.trim().is_empty() {
-        return Err("Message cannot be empty".to_string());
+        return Err(ToxcordError::invalid("Message cannot be empty"));
     }
+    send_friend_text(&state, friend_number, message, "normal").await
+}
 
+/// Send text to a friend and store it as `message_type`, queueing it if they're offline
+pub(crate) async fn send_friend_text(
+    state: &AppState,
+    friend_number: u32,
+    message: String,
+    message_type: &str,
+) -> CommandResult<serde_json::Value> {
     let msg_id = uuid::Uuid::new_v4().to_string();
     let timestamp = chrono::Utc::now().to_rfc3339();
+    let formatted = format_message(message_type, &message);
+    let store = state.message_store.lock().await.clone();
 
-    // Split long messages using the protocol codec
-    let chunks = toxcord_protocol::codec::split_friend_message(&message);
-
-    // Send each chunk via Tox
+    // Send via Tox; the tox thread splits long messages into tagged parts
     let guard = state.tox_manager.lock().await;
-    let manager = guard.as_ref().ok_or("Not connected")?;
+    let manager = guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
     let mgr = manager.lock().await;
 
-    for chunk in &chunks {
-        let (tx, rx) = oneshot::channel();
-        mgr.send_command(ToxCommand::FriendSendMessage(friend_number, chunk.clone(), tx))
-            .await?;
-        // If sending fails (e.g., friend offline), queue for later
-        match rx.await.map_err(|_| "Failed to receive response".to_string())? {
-            Ok(_tox_msg_id) => {}
-            Err(e) => {
-                // Queue for offline delivery
-                drop(mgr);
-                drop(guard);
-
-                let store_guard = state.message_store.lock().await;
-                if let Some(store) = store_guard.as_ref() {
-                    // Save as outgoing message anyway (for UI display)
-                    let record = DirectMessageRecord {
-                        id: msg_id.clone(),
-                        friend_number: friend_number as i64,
-                        sender: "self".to_string(),
-                        content: message.clone(),
-                        message_type: "normal".to_string(),
-                        timestamp: timestamp.clone(),
-                        is_outgoing: true,
-                        delivered: false,
-                        read: false,
-                    };
-                    store.insert_direct_message(&record).ok();
-
-                    // Queue for offline delivery
-                    store.queue_offline_message(
-                        "friend",
-                        &friend_number.to_string(),
-                        "text",
-                        &message,
-                    ).ok();
+    // Sending fails the same way offline or not, so don't queue what can't be encrypted
+    let locks = mgr.conversation_locks().clone();
+    if locks.state(friend_number) == Some(false) {
+        return Err(ToxcordError::invalid("This conversation is locked; enter its passphrase to send messages"));
+    }
+    let stored_content = locks.content_to_store(friend_number, &message)?;
+
+    // Messages sent during a call are tagged with it, for the call chat
+    let call_session = mgr
+        .get_call_state(friend_number)
+        .await
+        .filter(|c| c.state == CallStatus::InProgress)
+        .and_then(|c| c.session_id);
+
+    // Incognito messages are never stored, so they can't be queued either
+    let incognito = mgr.incognito().is_active(friend_number);
+    let expires_at = match store.as_deref() {
+        Some(store) if !incognito => store.disappearing_expiry(friend_number)?,
+        _ => None,
+    };
+
+    let (tx, rx) = oneshot::channel();
+    mgr.send_command(ToxCommand::FriendSendMessage(friend_number, message.clone(), tx))
+        .await?;
+    // If sending fails (e.g., friend offline), queue for later
+    match rx.await.map_err(|_| "Failed to receive response".to_string())? {
+        Ok(_tox_msg_id) => {}
+        Err(e) if incognito => {
+            return Ok(serde_json::json!({
+                "id": msg_id,
+                "timestamp": timestamp,
+                "delivered": false,
+                "queued": false,
+                "error": e.to_string(),
+                "formatted": formatted,
+                "expires_at": expires_at,
+                "incognito": true,
+            }));
+        }
+        Err(e) => {
+            // Queue for offline delivery
+            drop(mgr);
+            drop(guard);
+
+            let store_guard = state.message_store.lock().await;
+            if let Some(store) = store_guard.as_ref() {
+                // Save as outgoing message anyway (for UI display)
+                let record = DirectMessageRecord {
+                    id: msg_id.clone(),
+                    friend_number: friend_number as i64,
+                    sender: "self".to_string(),
+                    content: stored_content.clone(),
+                    message_type: message_type.to_string(),
+                    timestamp: timestamp.clone(),
+                    is_outgoing: true,
+                    delivered: false,
+                    read: false,
+                    expires_at,
+                };
+                store.insert_direct_message(&record).ok();
+                if let Some(session) = &call_session {
+                    store.set_direct_message_call_session(&msg_id, session).ok();
                 }
 
-                return Ok(serde_json::json!({
-                    "id": msg_id,
-                    "timestamp": timestamp,
-                    "delivered": false,
-                    "queued": true,
-                    "error": e,
-                }));
+                // Queue for offline delivery
+                store.queue_offline_message(
+                    "friend",
+                    &friend_number.to_string(),
+                    "text",
+                    &message,
+                ).ok();
             }
+
+            return Ok(serde_json::json!({
+                "id": msg_id,
+                "timestamp": timestamp,
+                "delivered": false,
+                "queued": true,
+                "error": e.to_string(),
+                "formatted": formatted,
+                "expires_at": expires_at,
+                "incognito": false,
+            }));
         }
     }
 
-    // All chunks sent successfully — persist to DB
+    // Sent successfully — persist to DB
     drop(mgr);
     drop(guard);
 
     let store_guard = state.message_store.lock().await;
-    if let Some(store) = store_guard.as_ref() {
+    if let Some(store) = store_guard.as_ref().filter(|_| !incognito) {
         let record = DirectMessageRecord {
             id: msg_id.clone(),
             friend_number: friend_number as i64,
             sender: "self".to_string(),
-            content: message,
-            message_type: "normal".to_string(),
+            content: stored_content,
+            message_type: message_type.to_string(),
             timestamp: timestamp.clone(),
             is_outgoing: true,
             delivered: true,
             read: false,
+            expires_at,
         };
         store.insert_direct_message(&record)?;
+        if let Some(session) = &call_session {
+            store.set_direct_message_call_session(&msg_id, session)?;
+        }
     }
 
     Ok(serde_json::json!({
@@ -99,6 +202,9 @@ pub async fn send_direct_message(
         "timestamp": timestamp,
         "delivered": true,
         "queued": false,
+        "formatted": formatted,
+        "expires_at": expires_at,
+        "incognito": incognito,
     }))
 }
 
@@ -108,18 +214,245 @@ pub async fn get_direct_messages(
     friend_number: u32,
     limit: Option<i64>,
     before_timestamp: Option<String>,
-) -> Result<Vec<DirectMessageRecord>, String> {
-    let store_guard = state.message_store.lock().await;
-    let store = store_guard.as_ref().ok_or("Not connected")?;
+) -> CommandResult<Vec<DirectMessageInfo>> {
+    let locks = match state.tox_manager.lock().await.as_ref() {
+        Some(manager) => manager.lock().await.conversation_locks().clone(),
+        None => ConversationLocks::default(),
+    };
+    let store = state.message_store.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+
+    let limit = limit.unwrap_or(50);
+    let messages = store
+        .call(move |store| store.get_direct_messages(friend_number, limit, before_timestamp.as_deref()))
+        .await?;
+
+    Ok(messages.into_iter().map(|m| DirectMessageInfo::open(m, &locks)).collect())
+}
+
+/// Messages exchanged during a call, oldest first. Defaults to the call in
+/// progress with the friend.
+#[tauri::command]
+pub async fn get_call_messages(
+    state: State<'_, AppState>,
+    friend_number: u32,
+    session_id: Option<String>,
+) -> CommandResult<Vec<DirectMessageInfo>> {
+    let (locks, current) = match state.tox_manager.lock().await.as_ref() {
+        Some(manager) => {
+            let mgr = manager.lock().await;
+            let current = mgr.get_call_state(friend_number).await.and_then(|c| c.session_id);
+            (mgr.conversation_locks().clone(), current)
+        }
+        None => (ConversationLocks::default(), None),
+    };
+    let Some(session_id) = session_id.or(current) else {
+        return Ok(Vec::new());
+    };
+    let store = state.message_store.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+
+    let messages = store.call(move |store| store.get_call_messages(&session_id)).await?;
+    Ok(messages
+        .into_iter()
+        .filter(|m| m.friend_number == friend_number as i64)
+        .map(|m| DirectMessageInfo::open(m, &locks))
+        .collect())
+}
+
+/// A page of direct messages, newest first, older than `before`, for infinite scroll
+#[tauri::command]
+pub async fn get_direct_messages_page(
+    state: State<'_, AppState>,
+    friend_number: u32,
+    limit: Option<i64>,
+    before: Option<MessageCursor>,
+) -> CommandResult<MessagePage<DirectMessageInfo>> {
+    let locks = match state.tox_manager.lock().await.as_ref() {
+        Some(manager) => manager.lock().await.conversation_locks().clone(),
+        None => ConversationLocks::default(),
+    };
+    let store = state.message_store.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
 
     let limit = limit.unwrap_or(50);
-    let messages = store.get_direct_messages(
-        friend_number,
-        limit,
-        before_timestamp.as_deref(),
-    )?;
+    let page = store
+        .call(move |store| store.get_direct_messages_page(friend_number, limit, before.as_ref()))
+        .await?;
 
-    Ok(messages)
+    Ok(MessagePage {
+        messages: page
+            .messages
+            .into_iter()
+            .map(|m| DirectMessageInfo::open(m, &locks))
+            .collect(),
+        has_more: page.has_more,
+        total: page.total,
+    })
+}
+
+/// Messages around a message or a date, in whichever conversation was asked for
+#[derive(serde::Serialize)]
+#[serde(untagged)]
+pub enum ConversationWindow {
+    Friend(MessageWindow<DirectMessageInfo>),
+    Channel(MessageWindow<ChannelMessageInfo>),
+}
+
+/// Up to `context` messages either side of a message or a date, newest first,
+/// for jumping to search results, pins, message links and dates
+#[tauri::command]
+pub async fn get_messages_around(
+    state: State<'_, AppState>,
+    conversation: Conversation,
+    anchor: MessageAnchor,
+    context: Option<i64>,
+) -> CommandResult<ConversationWindow> {
+    let context = context.unwrap_or(25).clamp(0, 200);
+    let store = state.message_store.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+    let missing = || ToxcordError::not_found("That message isn't in your history");
+
+    match conversation {
+        Conversation::Friend(friend_number) => {
+            let locks = match state.tox_manager.lock().await.as_ref() {
+                Some(manager) => manager.lock().await.conversation_locks().clone(),
+                None => ConversationLocks::default(),
+            };
+            let window = store
+                .call(move |store| store.get_direct_messages_around(friend_number, &anchor, context))
+                .await?
+                .ok_or_else(missing)?;
+            Ok(ConversationWindow::Friend(MessageWindow {
+                messages: window.messages.into_iter().map(|m| DirectMessageInfo::open(m, &locks)).collect(),
+                target_id: window.target_id,
+                has_older: window.has_older,
+                has_newer: window.has_newer,
+            }))
+        }
+        Conversation::Channel(channel_id) => {
+            let window = store
+                .call(move |store| store.get_channel_messages_around(&channel_id, &anchor, context))
+                .await?
+                .ok_or_else(missing)?;
+            let self_pk = self_public_key(&state).await;
+            Ok(ConversationWindow::Channel(MessageWindow {
+                messages: window
+                    .messages
+                    .into_iter()
+                    .map(|m| channel_message_info(m, self_pk.as_deref()))
+                    .collect(),
+                target_id: window.target_id,
+                has_older: window.has_older,
+                has_newer: window.has_newer,
+            }))
+        }
+    }
+}
+
+/// Where a message link points in this profile
+#[derive(serde::Serialize)]
+pub struct MessageLocation {
+    pub conversation: Conversation,
+    /// The guild, for a channel message
+    pub guild_id: Option<String>,
+    pub message_id: String,
+}
+
+/// A `toxcord://` link to a message. Channel message links work for every
+/// member of the guild; direct message links only for us.
+#[tauri::command]
+pub async fn get_message_link(
+    state: State<'_, AppState>,
+    conversation: Conversation,
+    message_id: String,
+) -> CommandResult<String> {
+    let store = state.message_store.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+    // A guild's chat ID is learned once we've connected to it
+    let link = store
+        .call(move |store| {
+            Ok(match conversation {
+                Conversation::Friend(friend_number) => store
+                    .get_friend_public_key(friend_number)?
+                    .map(|public_key| Link::DirectMessage { public_key: public_key.to_uppercase(), message_id }),
+                Conversation::Channel(channel_id) => match store.get_channel(&channel_id)? {
+                    Some(channel) => store
+                        .get_guild_chat_id(&channel.guild_id)?
+                        .map(|chat_id| Link::ChannelMessage { chat_id, channel: channel.name, message_id }),
+                    None => None,
+                },
+            })
+        })
+        .await?
+        .ok_or_else(|| ToxcordError::not_found("This conversation can't be linked to yet"))?;
+    Ok(link.to_uri())
+}
+
+/// Find the conversation a message link points at, without checking that the
+/// message itself is in our history
+#[tauri::command]
+pub async fn resolve_message_link(state: State<'_, AppState>, link: Link) -> CommandResult<MessageLocation> {
+    if !matches!(link, Link::ChannelMessage { .. } | Link::DirectMessage { .. }) {
+        return Err(ToxcordError::invalid("Not a message link"));
+    }
+    let store = state.message_store.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+    let location = store
+        .call(move |store| {
+            Ok(match link {
+                Link::DirectMessage { public_key, message_id } => store
+                    .get_friend_number_by_public_key(&public_key)?
+                    .map(|friend_number| MessageLocation {
+                        conversation: Conversation::Friend(friend_number),
+                        guild_id: None,
+                        message_id,
+                    }),
+                Link::ChannelMessage { chat_id, channel, message_id } => {
+                    let guild_id = store
+                        .get_guild_chat_ids()?
+                        .into_iter()
+                        .find(|(_, known)| known.eq_ignore_ascii_case(&chat_id))
+                        .map(|(guild_id, _)| guild_id);
+                    match guild_id {
+                        Some(guild_id) => store
+                            .get_channels(&guild_id)?
+                            .into_iter()
+                            .find(|c| c.name == channel)
+                            .map(|c| MessageLocation {
+                                conversation: Conversation::Channel(c.id),
+                                guild_id: Some(guild_id),
+                                message_id,
+                            }),
+                        None => None,
+                    }
+                }
+                Link::AddFriend { .. } | Link::JoinGuild { .. } => None,
+            })
+        })
+        .await?;
+    Ok(location.ok_or_else(|| ToxcordError::not_found("That conversation isn't in this profile"))?)
+}
+
+/// What a message said before each edit, oldest first, ending with what it
+/// says now
+#[tauri::command]
+pub async fn get_message_history(state: State<'_, AppState>, message_id: String) -> CommandResult<MessageHistory> {
+    let locks = match state.tox_manager.lock().await.as_ref() {
+        Some(manager) => manager.lock().await.conversation_locks().clone(),
+        None => ConversationLocks::default(),
+    };
+    let store = state.message_store.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+    let mut history = store
+        .call(move |store| store.get_message_history(&message_id))
+        .await?
+        .ok_or_else(|| ToxcordError::not_found("That message isn't in your history"))?;
+
+    // Earlier versions of a locked conversation's messages are encrypted too
+    if let Some(friend_number) = history.friend_number {
+        for revision in &mut history.revisions {
+            revision.content = match locks.open(friend_number as u32, &revision.content) {
+                Opened::Plain(_) => continue,
+                Opened::Decrypted(text) => text,
+                Opened::Locked => String::new(),
+            };
+        }
+    }
+    Ok(history)
 }
 
 #[tauri::command]
@@ -127,22 +460,480 @@ pub async fn set_typing(
     state: State<'_, AppState>,
     friend_number: u32,
     is_typing: bool,
-) -> Result<(), String> {
+) -> CommandResult<()> {
     let guard = state.tox_manager.lock().await;
-    let manager = guard.as_ref().ok_or("Not connected")?;
+    let manager = guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
     let mgr = manager.lock().await;
     let (tx, rx) = oneshot::channel();
     mgr.send_command(ToxCommand::SetTyping(friend_number, is_typing, tx))
         .await?;
-    rx.await.map_err(|_| "Failed to receive response".to_string())?
+    Ok(rx.await.map_err(|_| "Failed to receive response".to_string())??)
 }
 
 #[tauri::command]
 pub async fn mark_messages_read(
+    app_handle: tauri::AppHandle,
     state: State<'_, AppState>,
     friend_number: u32,
-) -> Result<(), String> {
+) -> CommandResult<()> {
+    let store_guard = state.message_store.lock().await;
+    let store = store_guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
+    store.mark_messages_read(friend_number)?;
+    crate::tray::refresh_unread_badge(&app_handle, store);
+    Ok(())
+}
+
+/// A message we sent, for recalling it in the composer
+#[derive(serde::Serialize)]
+pub struct OwnMessage {
+    pub id: String,
+    pub content: String,
+    pub message_type: String,
+    pub timestamp: String,
+}
+
+/// Most messages returned for history recall
+const OWN_MESSAGE_HISTORY_LIMIT: i64 = 100;
+
+/// Our most recent text messages in a conversation, newest first: with a friend
+/// (`target_type` "friend", `target_id` the friend number) or in a guild channel
+/// ("channel", the channel id). The first is the one to edit on up-arrow; the
+/// rest are recalled one at a time as the user keeps going up.
+#[tauri::command]
+pub async fn get_own_recent_messages(
+    state: State<'_, AppState>,
+    target_type: String,
+    target_id: String,
+    limit: Option<i64>,
+) -> CommandResult<Vec<OwnMessage>> {
+    let limit = limit.unwrap_or(20).clamp(1, OWN_MESSAGE_HISTORY_LIMIT);
+    let store = state.message_store.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+
+    match target_type.as_str() {
+        "friend" => {
+            let friend_number = target_id.parse::<u32>().map_err(|_| "Invalid friend number".to_string())?;
+            let locks = match state.tox_manager.lock().await.as_ref() {
+                Some(manager) => manager.lock().await.conversation_locks().clone(),
+                None => ConversationLocks::default(),
+            };
+            let messages = store
+                .call(move |store| store.get_own_recent_direct_messages(friend_number, limit))
+                .await?;
+            Ok(messages
+                .into_iter()
+                .map(|m| DirectMessageInfo::open(m, &locks))
+                // Nothing to recall from messages we can't decrypt
+                .filter(|m| !m.locked)
+                .map(|m| OwnMessage {
+                    id: m.record.id,
+                    content: m.record.content,
+                    message_type: m.record.message_type,
+                    timestamp: m.record.timestamp,
+                })
+                .collect())
+        }
+        "channel" => {
+            let channel = store.get_channel(&target_id)?.ok_or_else(|| ToxcordError::not_found("Channel not found"))?;
+            let group_number = super::polls::group_number(&state, &channel.guild_id).await?;
+            // Our messages are stored under our key in the guild's group
+            let (self_pk, _) = super::polls::self_identity(&state, group_number).await?;
+            let messages = store
+                .call(move |store| store.get_own_recent_channel_messages(&target_id, &self_pk, limit))
+                .await?;
+            Ok(messages
+                .into_iter()
+                .map(|m| OwnMessage {
+                    id: m.id,
+                    content: m.content,
+                    message_type: m.message_type,
+                    timestamp: m.timestamp,
+                })
+                .collect())
+        }
+        _ => Err(ToxcordError::invalid(format!("Unknown target type '{target_type}'"))),
+    }
+}
+
+/// Schedule a message to a friend (`target_type` "friend", `target_id` the friend
+/// number) or a guild channel ("channel", the channel id, plus `guild_id`).
+/// `send_at` is an RFC 3339 timestamp; the tox thread sends it once it's due.
+#[tauri::command]
+pub async fn schedule_message(
+    state: State<'_, AppState>,
+    target_type: String,
+    target_id: String,
+    guild_id: Option<String>,
+    content: String,
+    send_at: String,
+) -> CommandResult<ScheduledMessageRecord> {
+    if content.trim().is_empty() {
+        return Err(ToxcordError::invalid("Message cannot be empty"));
+    }
+    match target_type.as_str() {
+        "friend" => {
+            target_id.parse::<u32>().map_err(|_| "Invalid friend number".to_string())?;
+        }
+        "channel" if guild_id.is_none() => return Err(ToxcordError::invalid("Channel messages need a guild")),
+        "channel" => {}
+        _ => return Err(ToxcordError::invalid(format!("Unknown target type '{target_type}'"))),
+    }
+
+    // Normalized so the due check can compare timestamps as strings
+    let send_at = chrono::DateTime::parse_from_rfc3339(&send_at)
+        .map_err(|e| format!("Invalid send time: {e}"))?
+        .with_timezone(&chrono::Utc)
+        .to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+
+    let record = ScheduledMessageRecord {
+        id: uuid::Uuid::new_v4().to_string(),
+        target_type,
+        target_id,
+        guild_id,
+        content,
+        send_at,
+        status: "pending".to_string(),
+        message_id: None,
+        created_at: chrono::Utc::now().to_rfc3339(),
+    };
+
+    let store_guard = state.message_store.lock().await;
+    let store = store_guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
+    store.insert_scheduled_message(&record)?;
+    Ok(record)
+}
+
+/// Scheduled messages that haven't been sent yet, soonest first
+#[tauri::command]
+pub async fn get_scheduled_messages(
+    state: State<'_, AppState>,
+) -> CommandResult<Vec<ScheduledMessageRecord>> {
+    let store_guard = state.message_store.lock().await;
+    let store = store_guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
+    Ok(store.get_pending_scheduled_messages()?)
+}
+
+#[tauri::command]
+pub async fn cancel_scheduled_message(
+    state: State<'_, AppState>,
+    id: String,
+) -> CommandResult<()> {
+    let store_guard = state.message_store.lock().await;
+    let store = store_guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
+    if !store.cancel_scheduled_message(&id)? {
+        return Err(ToxcordError::not_found("Scheduled message not found or already sent"));
+    }
+    Ok(())
+}
+
+/// Star a conversation: `kind` "dm" (`id` the friend's public key), "dm_group"
+/// (the guild id) or "channel" (the channel id)
+#[tauri::command]
+pub async fn star_conversation(state: State<'_, AppState>, kind: String, id: String) -> CommandResult<()> {
+    validate_conversation_kind(&kind)?;
+    let store_guard = state.message_store.lock().await;
+    let store = store_guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
+    Ok(store.star_conversation(&kind, &id)?)
+}
+
+#[tauri::command]
+pub async fn unstar_conversation(state: State<'_, AppState>, kind: String, id: String) -> CommandResult<()> {
+    validate_conversation_kind(&kind)?;
+    let store_guard = state.message_store.lock().await;
+    let store = store_guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
+    Ok(store.unstar_conversation(&kind, &id)?)
+}
+
+/// DMs, DM groups and channels by last activity, for the quick switcher
+#[tauri::command]
+pub async fn get_recent_conversations(
+    state: State<'_, AppState>,
+    limit: Option<i64>,
+) -> CommandResult<Vec<RecentConversationRecord>> {
+    let store = state.message_store.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+    Ok(store
+        .call(move |store| store.get_recent_conversations(limit.unwrap_or(50).clamp(1, 500)))
+        .await?)
+}
+
+/// Local usage statistics for a calendar year (this year by default)
+#[tauri::command]
+pub async fn get_usage_stats(state: State<'_, AppState>, year: Option<i32>) -> CommandResult<UsageStats> {
+    let store = state.message_store.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+    Ok(store.call(move |store| usage_stats::for_year(store, year)).await?)
+}
+
+fn validate_conversation_kind(kind: &str) -> Result<(), String> {
+    match kind {
+        "dm" | "dm_group" | "channel" => Ok(()),
+        _ => Err(format!("Unknown conversation kind '{kind}'")),
+    }
+}
+
+/// Add a word to the profile's custom spellcheck dictionary
+#[tauri::command]
+pub async fn add_dictionary_word(state: State<'_, AppState>, word: String) -> CommandResult<()> {
+    let word = word.trim();
+    if word.is_empty() || word.contains(char::is_whitespace) {
+        return Err(ToxcordError::invalid("Dictionary entries must be a single word"));
+    }
+    let store_guard = state.message_store.lock().await;
+    let store = store_guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
+    Ok(store.add_dictionary_word(word)?)
+}
+
+#[tauri::command]
+pub async fn remove_dictionary_word(state: State<'_, AppState>, word: String) -> CommandResult<()> {
+    let store_guard = state.message_store.lock().await;
+    let store = store_guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
+    if !store.remove_dictionary_word(word.trim())? {
+        return Err(ToxcordError::not_found("Word not in dictionary"));
+    }
+    Ok(())
+}
+
+/// Words in the custom dictionary, alphabetically
+#[tauri::command]
+pub async fn get_dictionary_words(state: State<'_, AppState>) -> CommandResult<Vec<String>> {
+    let store_guard = state.message_store.lock().await;
+    let store = store_guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
+    Ok(store.get_dictionary_words()?)
+}
+
+/// Get the reply sent to friends who message us while we're Away or Busy
+#[tauri::command]
+pub async fn get_auto_reply(state: State<'_, AppState>) -> CommandResult<AutoReplySettings> {
     let store_guard = state.message_store.lock().await;
-    let store = store_guard.as_ref().ok_or("Not connected")?;
-    store.mark_messages_read(friend_number)
+    let store = store_guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
+    Ok(AutoReplySettings::load(store)?)
+}
+
+#[tauri::command]
+pub async fn set_auto_reply(
+    state: State<'_, AppState>,
+    settings: AutoReplySettings,
+) -> CommandResult<()> {
+    if settings.enabled && settings.message.trim().is_empty() {
+        return Err(ToxcordError::invalid("Auto-reply message is empty"));
+    }
+    if settings.interval_hours == 0 {
+        return Err(ToxcordError::invalid("Auto-reply interval must be at least an hour"));
+    }
+    let store_guard = state.message_store.lock().await;
+    let store = store_guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
+    Ok(settings.save(store)?)
+}
+
+// ─── Conversation locks ─────────────────────────────────────────────
+
+#[derive(serde::Serialize)]
+pub struct ConversationLockInfo {
+    pub enabled: bool,
+    /// The passphrase has been entered since the profile loaded
+    pub unlocked: bool,
+    pub store_plaintext: bool,
+}
+
+/// Lock a conversation with a passphrase agreed with the friend, or enter it
+/// again after a restart. Both sides must enable it with the same passphrase.
+#[tauri::command]
+pub async fn enable_conversation_lock(
+    state: State<'_, AppState>,
+    friend_number: u32,
+    passphrase: String,
+    store_plaintext: bool,
+) -> CommandResult<ConversationLockInfo> {
+    let store = state
+        .message_store
+        .lock()
+        .await
+        .clone()
+        .ok_or(ToxcordError::NotLoggedIn)?;
+    let tox = state
+        .tox_manager
+        .lock()
+        .await
+        .clone()
+        .ok_or(ToxcordError::NotLoggedIn)?;
+    let friend_pk = store
+        .get_friend_public_key(friend_number)?
+        .ok_or_else(|| ToxcordError::not_found("Friend not found"))?;
+
+    let (tx, rx) = oneshot::channel();
+    let locks = {
+        let mgr = tox.lock().await;
+        mgr.send_command(ToxCommand::GetProfileInfo(tx)).await?;
+        mgr.conversation_locks().clone()
+    };
+    let self_pk = rx
+        .await
+        .map(|p| p.tox_id.as_str()[..64].to_uppercase())
+        .map_err(|_| "Failed to receive response".to_string())?;
+
+    let key = tokio::task::spawn_blocking(move || derive_key(&passphrase, &self_pk, &friend_pk))
+        .await
+        .map_err(|e| format!("Failed to derive conversation key: {e}"))??;
+
+    // A wrong passphrase can't open messages already encrypted with the right one
+    let encrypted = store.get_encrypted_direct_messages(friend_number, LOCK_PREFIX)?;
+    if let Some((_, envelope)) = encrypted.last() {
+        let opens = conversation_lock::open(envelope).is_some_and(|c| key.decrypt(&c).is_ok());
+        if !opens {
+            return Err(ToxcordError::invalid("Wrong passphrase for this conversation"));
+        }
+    }
+
+    store.set_conversation_lock(friend_number, store_plaintext)?;
+    locks.unlock(friend_number, store_plaintext, key);
+    if store_plaintext {
+        decrypt_stored_messages(&store, &locks, friend_number)?;
+    }
+
+    Ok(ConversationLockInfo {
+        enabled: true,
+        unlocked: true,
+        store_plaintext,
+    })
+}
+
+/// Turn a conversation lock off. Messages stored encrypted are decrypted
+/// first, so the passphrase must have been entered.
+#[tauri::command]
+pub async fn disable_conversation_lock(state: State<'_, AppState>, friend_number: u32) -> CommandResult<()> {
+    let store = state
+        .message_store
+        .lock()
+        .await
+        .clone()
+        .ok_or(ToxcordError::NotLoggedIn)?;
+    let tox = state
+        .tox_manager
+        .lock()
+        .await
+        .clone()
+        .ok_or(ToxcordError::NotLoggedIn)?;
+    let locks = tox.lock().await.conversation_locks().clone();
+
+    if locks.state(friend_number) == Some(false) {
+        return Err(ToxcordError::invalid("Enter the conversation passphrase before turning the lock off"));
+    }
+    decrypt_stored_messages(&store, &locks, friend_number)?;
+    store.remove_conversation_lock(friend_number)?;
+    locks.remove(friend_number);
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_conversation_lock(state: State<'_, AppState>, friend_number: u32) -> CommandResult<ConversationLockInfo> {
+    let store = state
+        .message_store
+        .lock()
+        .await
+        .clone()
+        .ok_or(ToxcordError::NotLoggedIn)?;
+    let unlocked = match state.tox_manager.lock().await.as_ref() {
+        Some(tox) => tox.lock().await.conversation_locks().state(friend_number) == Some(true),
+        None => false,
+    };
+    let store_plaintext = store.get_conversation_lock(friend_number)?;
+    Ok(ConversationLockInfo {
+        enabled: store_plaintext.is_some(),
+        unlocked,
+        store_plaintext: store_plaintext.unwrap_or(false),
+    })
+}
+
+/// Replace a conversation's stored envelopes with their plaintext
+fn decrypt_stored_messages(store: &MessageStore, locks: &ConversationLocks, friend_number: u32) -> Result<(), String> {
+    for (id, envelope) in store.get_encrypted_direct_messages(friend_number, LOCK_PREFIX)? {
+        if let Opened::Decrypted(text) = locks.open(friend_number, &envelope) {
+            store.set_direct_message_content(&id, &text)?;
+        }
+    }
+    Ok(())
+}
+
+// ─── Disappearing messages ──────────────────────────────────────────
+
+/// Set how long messages with a friend last, in seconds (0 turns it off).
+/// The friend's side follows; whoever changes it last wins.
+#[tauri::command]
+pub async fn set_disappearing_timer(
+    state: State<'_, AppState>,
+    friend_number: u32,
+    seconds: u32,
+) -> CommandResult<()> {
+    disappearing::validate_timer(seconds)?;
+    let guard = state.tox_manager.lock().await;
+    let manager = guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
+    let (tx, rx) = oneshot::channel();
+    manager
+        .lock()
+        .await
+        .send_command(ToxCommand::SetDisappearingTimer(friend_number, seconds, tx))
+        .await?;
+    Ok(rx.await.map_err(|_| "Failed to receive response".to_string())??)
+}
+
+/// A friend conversation's disappearing timer in seconds, 0 when off
+#[tauri::command]
+pub async fn get_disappearing_timer(state: State<'_, AppState>, friend_number: u32) -> CommandResult<u32> {
+    let store = state
+        .message_store
+        .lock()
+        .await
+        .clone()
+        .ok_or(ToxcordError::NotLoggedIn)?;
+    Ok(store.get_disappearing_timer(friend_number)?.map_or(0, |(seconds, _)| seconds))
+}
+
+// ─── Incognito ──────────────────────────────────────────────────────
+
+#[derive(serde::Serialize)]
+pub struct IncognitoInfo {
+    /// Messages in the conversation aren't stored right now
+    pub active: bool,
+    /// The conversation starts in incognito every time the profile loads
+    pub default: bool,
+}
+
+/// Turn incognito on or off for a conversation until the profile is closed
+#[tauri::command]
+pub async fn set_incognito(state: State<'_, AppState>, friend_number: u32, enabled: bool) -> CommandResult<()> {
+    let guard = state.tox_manager.lock().await;
+    let manager = guard.as_ref().ok_or(ToxcordError::NotLoggedIn)?;
+    manager.lock().await.incognito().set_active(friend_number, enabled);
+    Ok(())
+}
+
+/// Choose whether a conversation starts in incognito. It's switched to match now too.
+#[tauri::command]
+pub async fn set_incognito_default(state: State<'_, AppState>, friend_number: u32, enabled: bool) -> CommandResult<()> {
+    let store = state
+        .message_store
+        .lock()
+        .await
+        .clone()
+        .ok_or(ToxcordError::NotLoggedIn)?;
+    let public_key = store.get_friend_public_key(friend_number)?.ok_or_else(|| ToxcordError::not_found("Friend not found"))?;
+    incognito::set_default(&store, &public_key, enabled)?;
+    set_incognito(state, friend_number, enabled).await
+}
+
+#[tauri::command]
+pub async fn get_incognito(state: State<'_, AppState>, friend_number: u32) -> CommandResult<IncognitoInfo> {
+    let store = state
+        .message_store
+        .lock()
+        .await
+        .clone()
+        .ok_or(ToxcordError::NotLoggedIn)?;
+    let active = match state.tox_manager.lock().await.as_ref() {
+        Some(tox) => tox.lock().await.incognito().is_active(friend_number),
+        None => false,
+    };
+    let default = match store.get_friend_public_key(friend_number)? {
+        Some(public_key) => incognito::default_friends(&store)?.contains(&public_key.to_uppercase()),
+        None => false,
+    };
+    Ok(IncognitoInfo { active, default })
 }
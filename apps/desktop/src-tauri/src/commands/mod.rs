@@ -1,5 +1,11 @@
+pub mod accessibility;
 pub mod auth;
 pub mod calls;
+pub mod diagnostics;
+pub mod events;
+pub mod files;
 pub mod friends;
 pub mod guilds;
 pub mod messaging;
+pub mod polls;
+pub mod system;
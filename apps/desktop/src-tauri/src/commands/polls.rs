@@ -0,0 +1,220 @@
+use tauri::State;
+use tokio::sync::oneshot;
+use toxcord_protocol::polls::{self, Poll, PollPacket};
+
+use crate::db::message_store::{ChannelMessageRecord, PollRecord};
+use crate::error::{CommandResult, ToxcordError};
+use crate::managers::tox_manager::ToxCommand;
+use crate::AppState;
+
+#[derive(serde::Serialize)]
+pub struct PollInfo {
+    pub id: String,
+    pub channel_id: String,
+    pub creator_public_key: String,
+    pub creator_name: String,
+    pub question: String,
+    pub options: Vec<String>,
+    /// Votes per option
+    pub tallies: Vec<i64>,
+    /// Index of the option we voted for
+    pub my_vote: Option<u32>,
+    /// Unix seconds
+    pub closes_at: i64,
+    pub closed: bool,
+}
+
+/// Post a poll in a channel. It closes after `duration_secs`.
+#[tauri::command]
+pub async fn create_poll(
+    guild_id: String,
+    channel_id: String,
+    question: String,
+    options: Vec<String>,
+    duration_secs: i64,
+    state: State<'_, AppState>,
+) -> CommandResult<PollInfo> {
+    polls::validate_duration(duration_secs)?;
+    let store = state
+        .message_store
+        .lock()
+        .await
+        .clone()
+        .ok_or(ToxcordError::NotLoggedIn)?;
+    let channel = store.get_channel(&channel_id)?.ok_or_else(|| ToxcordError::not_found("Channel not found"))?;
+
+    let poll = Poll {
+        id: uuid::Uuid::new_v4().to_string(),
+        channel: channel.name,
+        question: question.trim().to_string(),
+        options: options.iter().map(|o| o.trim().to_string()).collect(),
+        closes_at: chrono::Utc::now().timestamp() + duration_secs,
+    };
+    poll.validate()?;
+
+    let group_number = group_number(&state, &guild_id).await?;
+    send_poll_packet(&state, group_number, &PollPacket::Create { poll: poll.clone() }).await?;
+    let (self_pk, self_name) = self_identity(&state, group_number).await?;
+
+    let record = PollRecord {
+        id: poll.id.clone(),
+        channel_id: channel_id.clone(),
+        creator_public_key: self_pk.clone(),
+        creator_name: self_name.clone(),
+        question: poll.question.clone(),
+        options: poll.options,
+        closes_at: poll.closes_at,
+        closed: false,
+    };
+    store.insert_poll(&record)?;
+    store.insert_channel_message(&ChannelMessageRecord {
+        id: poll.id.clone(),
+        channel_id,
+        sender_public_key: self_pk.clone(),
+        sender_name: self_name,
+        content: poll.question,
+        message_type: "poll".to_string(),
+        timestamp: chrono::Utc::now().to_rfc3339(),
+        mentions_me: false,
+        notify: false,
+        filtered: false,
+        backfilled: false,
+        sender_verified: None,
+        signer_public_key: None,
+        signature: None,
+    })?;
+
+    Ok(poll_info(&store, record, &self_pk)?)
+}
+
+/// Vote for an option of an open poll, replacing our earlier vote
+#[tauri::command]
+pub async fn vote_poll(
+    guild_id: String,
+    poll_id: String,
+    option: u32,
+    state: State<'_, AppState>,
+) -> CommandResult<PollInfo> {
+    let store = state
+        .message_store
+        .lock()
+        .await
+        .clone()
+        .ok_or(ToxcordError::NotLoggedIn)?;
+    let poll = store.get_poll(&poll_id)?.ok_or_else(|| ToxcordError::not_found("Poll not found"))?;
+    if poll.closed || chrono::Utc::now().timestamp() >= poll.closes_at {
+        return Err(ToxcordError::invalid("This poll has closed"));
+    }
+    if option as usize >= poll.options.len() {
+        return Err(ToxcordError::invalid("Invalid poll option"));
+    }
+
+    let group_number = group_number(&state, &guild_id).await?;
+    send_poll_packet(
+        &state,
+        group_number,
+        &PollPacket::Vote {
+            poll_id: poll_id.clone(),
+            option,
+        },
+    )
+    .await?;
+    let (self_pk, _) = self_identity(&state, group_number).await?;
+    store.set_poll_vote(&poll_id, &self_pk, option)?;
+
+    Ok(poll_info(&store, poll, &self_pk)?)
+}
+
+#[tauri::command]
+pub async fn get_poll(
+    guild_id: String,
+    poll_id: String,
+    state: State<'_, AppState>,
+) -> CommandResult<PollInfo> {
+    let store = state
+        .message_store
+        .lock()
+        .await
+        .clone()
+        .ok_or(ToxcordError::NotLoggedIn)?;
+    let poll = store.get_poll(&poll_id)?.ok_or_else(|| ToxcordError::not_found("Poll not found"))?;
+    let group_number = group_number(&state, &guild_id).await?;
+    let (self_pk, _) = self_identity(&state, group_number).await?;
+    Ok(poll_info(&store, poll, &self_pk)?)
+}
+
+fn poll_info(store: &crate::db::MessageStore, poll: PollRecord, self_pk: &str) -> Result<PollInfo, String> {
+    Ok(PollInfo {
+        tallies: store.get_poll_tallies(&poll.id, poll.options.len())?,
+        my_vote: store.get_poll_vote(&poll.id, self_pk)?,
+        closed: poll.closed || chrono::Utc::now().timestamp() >= poll.closes_at,
+        id: poll.id,
+        channel_id: poll.channel_id,
+        creator_public_key: poll.creator_public_key,
+        creator_name: poll.creator_name,
+        question: poll.question,
+        options: poll.options,
+        closes_at: poll.closes_at,
+    })
+}
+
+pub(crate) async fn group_number(state: &AppState, guild_id: &str) -> CommandResult<u32> {
+    let store = state
+        .message_store
+        .lock()
+        .await
+        .clone()
+        .ok_or(ToxcordError::NotLoggedIn)?;
+    let guild = store.get_guild(guild_id)?.ok_or_else(|| ToxcordError::not_found("Guild not found"))?;
+    Ok(guild
+        .metadata_group_number
+        .ok_or("Guild has no group number")? as u32)
+}
+
+async fn send_poll_packet(state: &AppState, group_number: u32, packet: &PollPacket) -> CommandResult<()> {
+    let tox = state
+        .tox_manager
+        .lock()
+        .await
+        .clone()
+        .ok_or(ToxcordError::NotLoggedIn)?;
+    let (tx, rx) = oneshot::channel();
+    tox.lock()
+        .await
+        .send_command(ToxCommand::GroupSendCustomPacket(group_number, packet.to_bytes(), tx))
+        .await?;
+    rx.await
+        .map_err(|_| "Failed to receive response".to_string())?
+        .map_err(|e| format!("Failed to send poll: {e}").into())
+}
+
+/// Our public key in the group and our display name
+pub(crate) async fn self_identity(state: &AppState, group_number: u32) -> CommandResult<(String, String)> {
+    let tox = state
+        .tox_manager
+        .lock()
+        .await
+        .clone()
+        .ok_or(ToxcordError::NotLoggedIn)?;
+
+    let (pk_tx, pk_rx) = oneshot::channel();
+    tox.lock()
+        .await
+        .send_command(ToxCommand::GroupGetSelfPk(group_number, pk_tx))
+        .await?;
+    let self_pk = pk_rx
+        .await
+        .map_err(|_| "Failed to receive response".to_string())??;
+
+    let (info_tx, info_rx) = oneshot::channel();
+    tox.lock()
+        .await
+        .send_command(ToxCommand::GetProfileInfo(info_tx))
+        .await?;
+    let self_name = info_rx
+        .await
+        .map(|p| p.name)
+        .map_err(|_| "Failed to receive response".to_string())?;
+
+    Ok((self_pk, self_name))
+}
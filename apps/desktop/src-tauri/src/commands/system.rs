@@ -0,0 +1,241 @@
+//! Tauri commands for OS integration (start on login, global shortcuts, bot API, logs, saving, sleep,
+//! opened links, updates).
+
+use tauri::State;
+use tauri_plugin_autostart::ManagerExt;
+use toxcord_protocol::links::Link;
+
+use crate::deep_link;
+use crate::error::{CommandResult, ToxcordError};
+use crate::managers::bot_api::{self, BotApiServer};
+use crate::managers::shortcut_manager::ShortcutManager;
+use crate::settings::{BotApiSettings, ShortcutBindings};
+use crate::updater::{self, UpdateInfo, UpdateSettings};
+use crate::AppState;
+
+/// Register Toxcord to start on login (launched minimized to the tray)
+#[tauri::command]
+pub fn enable_autostart(app_handle: tauri::AppHandle) -> CommandResult<()> {
+    Ok(app_handle
+        .autolaunch()
+        .enable()
+        .map_err(|e| format!("Failed to enable autostart: {e}"))?)
+}
+
+/// Remove the start-on-login registration
+#[tauri::command]
+pub fn disable_autostart(app_handle: tauri::AppHandle) -> CommandResult<()> {
+    Ok(app_handle
+        .autolaunch()
+        .disable()
+        .map_err(|e| format!("Failed to disable autostart: {e}"))?)
+}
+
+/// Check whether Toxcord is registered to start on login
+#[tauri::command]
+pub fn is_autostart_enabled(app_handle: tauri::AppHandle) -> CommandResult<bool> {
+    Ok(app_handle
+        .autolaunch()
+        .is_enabled()
+        .map_err(|e| format!("Failed to query autostart: {e}"))?)
+}
+
+/// Get the configured global shortcut bindings
+#[tauri::command]
+pub async fn get_shortcuts(state: State<'_, AppState>) -> CommandResult<ShortcutBindings> {
+    Ok(state.settings.lock().await.shortcuts.clone())
+}
+
+/// Save new global shortcut bindings and re-register them with the OS
+#[tauri::command]
+pub async fn set_shortcuts(
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+    bindings: ShortcutBindings,
+) -> CommandResult<()> {
+    ShortcutManager::validate(&bindings)?;
+    {
+        let mut settings = state.settings.lock().await;
+        settings.shortcuts = bindings.clone();
+        settings.save()?;
+    }
+    Ok(ShortcutManager::apply(&app_handle, &bindings)?)
+}
+
+// ─── Bot API ────────────────────────────────────────────────────────
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct BotApiStatus {
+    pub enabled: bool,
+    /// False if enabled but the port could not be bound
+    pub running: bool,
+    pub port: u16,
+    pub token: String,
+}
+
+/// Get the bot API settings and whether the server is listening
+#[tauri::command]
+pub async fn get_bot_api_settings(state: State<'_, AppState>) -> CommandResult<BotApiStatus> {
+    let settings = state.settings.lock().await.bot_api.clone();
+    Ok(bot_api_status(&state, settings).await)
+}
+
+/// Turn the bot API on or off. A token is generated the first time it is enabled.
+#[tauri::command]
+pub async fn set_bot_api_enabled(
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+    enabled: bool,
+    port: Option<u16>,
+) -> CommandResult<BotApiStatus> {
+    let settings = {
+        let mut settings = state.settings.lock().await;
+        settings.bot_api.enabled = enabled;
+        if let Some(port) = port {
+            settings.bot_api.port = port;
+        }
+        if settings.bot_api.token.is_empty() {
+            settings.bot_api.token = bot_api::generate_token();
+        }
+        settings.save()?;
+        settings.bot_api.clone()
+    };
+    restart_bot_api(app_handle, &state, &settings).await?;
+    Ok(bot_api_status(&state, settings).await)
+}
+
+/// Replace the bot API token, disconnecting every client using the old one
+#[tauri::command]
+pub async fn regenerate_bot_api_token(
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> CommandResult<BotApiStatus> {
+    let settings = {
+        let mut settings = state.settings.lock().await;
+        settings.bot_api.token = bot_api::generate_token();
+        settings.save()?;
+        settings.bot_api.clone()
+    };
+    restart_bot_api(app_handle, &state, &settings).await?;
+    Ok(bot_api_status(&state, settings).await)
+}
+
+/// Most log lines returned to the log viewer
+const MAX_LOG_LINES: usize = 5000;
+
+/// Get the last lines of the loaded profile's log
+#[tauri::command]
+pub fn get_recent_logs(lines: Option<usize>) -> CommandResult<Vec<String>> {
+    Ok(crate::logging::recent_lines(lines.unwrap_or(500).min(MAX_LOG_LINES))?)
+}
+
+/// Save the loaded profile's logs to the downloads folder, returning the file's path
+#[tauri::command]
+pub fn export_logs() -> CommandResult<String> {
+    let dir = dirs::download_dir()
+        .or_else(dirs::home_dir)
+        .unwrap_or_else(|| std::path::PathBuf::from("."));
+    let timestamp = chrono::Local::now().format("%Y%m%d-%H%M%S");
+    let path = dir.join(format!("toxcord-logs-{timestamp}.log"));
+    crate::logging::export(&path)?;
+    Ok(path.to_string_lossy().to_string())
+}
+
+/// Write the profile to disk now. Changes are otherwise saved in batches,
+/// at most every few seconds.
+#[tauri::command]
+pub async fn force_save(state: State<'_, AppState>) -> CommandResult<()> {
+    let tox = state.tox_manager.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+    tox.lock().await.save_profile().await?;
+    Ok(())
+}
+
+/// Save the profile and end calls before the computer sleeps
+#[tauri::command]
+pub async fn system_suspending(state: State<'_, AppState>) -> CommandResult<()> {
+    let tox = state.tox_manager.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+    tox.lock().await.suspend().await?;
+    Ok(())
+}
+
+/// The last `toxcord://` link opened that hasn't been handled, clearing it
+#[tauri::command]
+pub fn take_pending_link(app_handle: tauri::AppHandle) -> Option<Link> {
+    deep_link::take_pending(&app_handle)
+}
+
+/// Reconnect after the computer woke up. Usually not needed, as a wake is
+/// noticed from the clock jumping.
+#[tauri::command]
+pub async fn system_resumed(state: State<'_, AppState>) -> CommandResult<()> {
+    let tox = state.tox_manager.lock().await.clone().ok_or(ToxcordError::NotLoggedIn)?;
+    tox.lock().await.resume().await?;
+    Ok(())
+}
+
+// ─── Updates ────────────────────────────────────────────────────────
+
+/// Check for a newer release now, whether or not background checks are enabled.
+/// One found is announced even if it was skipped before.
+#[tauri::command]
+pub async fn check_for_updates(app_handle: tauri::AppHandle) -> CommandResult<Option<UpdateInfo>> {
+    let update = updater::check(&app_handle).await?;
+    if let Some(update) = &update {
+        updater::announce(&app_handle, update);
+    }
+    Ok(update)
+}
+
+/// The newer release found by the last check, if any
+#[tauri::command]
+pub async fn get_available_update(app_handle: tauri::AppHandle) -> Option<UpdateInfo> {
+    updater::available(&app_handle).await
+}
+
+/// Download and install the available update, then restart
+#[tauri::command]
+pub async fn install_update(app_handle: tauri::AppHandle) -> CommandResult<()> {
+    Ok(updater::install(&app_handle).await?)
+}
+
+#[tauri::command]
+pub async fn get_update_settings(state: State<'_, AppState>) -> CommandResult<UpdateSettings> {
+    Ok(state.settings.lock().await.updates.clone())
+}
+
+#[tauri::command]
+pub async fn set_update_settings(state: State<'_, AppState>, settings: UpdateSettings) -> CommandResult<()> {
+    let mut app_settings = state.settings.lock().await;
+    app_settings.updates = settings;
+    Ok(app_settings.save()?)
+}
+
+async fn restart_bot_api(
+    app_handle: tauri::AppHandle,
+    state: &AppState,
+    settings: &BotApiSettings,
+) -> Result<(), String> {
+    let mut server = state.bot_api.lock().await;
+    if let Some(old) = server.take() {
+        old.stop().await;
+    }
+    if settings.enabled {
+        *server = Some(BotApiServer::start(app_handle, settings.port, settings.token.clone()).await?);
+    }
+    Ok(())
+}
+
+async fn bot_api_status(state: &AppState, settings: BotApiSettings) -> BotApiStatus {
+    let running = state
+        .bot_api
+        .lock()
+        .await
+        .as_ref()
+        .is_some_and(|server| server.port() == settings.port);
+    BotApiStatus {
+        enabled: settings.enabled,
+        running,
+        port: settings.port,
+        token: settings.token,
+    }
+}
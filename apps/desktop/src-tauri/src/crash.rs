@@ -0,0 +1,212 @@
+//! Crash reports.
+//!
+//! A panic hook writes a report to `crashes/` in the data directory: the panic
+//! message and location, a backtrace, versions, and the last lines logged.
+//! Keys and addresses are shortened as in the log file, and the home directory
+//! is replaced with `~`. Nothing is sent anywhere. On the next launch the
+//! frontend lists the reports and asks; one the user chooses to share is saved
+//! to their downloads folder and the issue tracker is opened to attach it to.
+
+use std::backtrace::Backtrace;
+use std::panic::PanicHookInfo;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use tracing::{error, warn};
+
+use crate::logging;
+
+/// Where shared reports are filed
+const ISSUES_URL: &str = "https://github.com/D4M13N-D3V/Toxcord/issues/new";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrashReport {
+    /// File name without the extension
+    pub id: String,
+    /// RFC 3339
+    pub time: String,
+    pub app_version: String,
+    pub toxcore_version: String,
+    pub os: String,
+    pub arch: String,
+    pub thread: String,
+    pub message: String,
+    /// file:line:column the panic was raised at
+    pub location: Option<String>,
+    pub backtrace: String,
+    pub log_tail: Vec<String>,
+}
+
+impl CrashReport {
+    fn capture(app_version: &str, info: &PanicHookInfo<'_>) -> Self {
+        let message = info
+            .payload()
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| info.payload().downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "Unknown panic".to_string());
+        let now = chrono::Local::now();
+        let short_id = uuid::Uuid::new_v4().simple().to_string();
+        Self {
+            id: format!("crash-{}-{}", now.format("%Y%m%d-%H%M%S"), &short_id[..8]),
+            time: now.to_rfc3339(),
+            app_version: app_version.to_string(),
+            toxcore_version: toxcord_tox::tox::toxcore_version(),
+            os: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+            thread: std::thread::current().name().unwrap_or("unnamed").to_string(),
+            message: scrub(&message),
+            location: info.location().map(|l| scrub(&l.to_string())),
+            backtrace: scrub(&Backtrace::force_capture().to_string()),
+            log_tail: logging::tail(),
+        }
+    }
+
+    /// The report as plain text, for attaching to an issue
+    pub fn to_text(&self) -> String {
+        let mut text = format!(
+            "Toxcord crash report {}\n\nTime: {}\nVersion: {}\ntoxcore: {}\nPlatform: {} {}\nThread: {}\n\nPanic: {}\n",
+            self.id, self.time, self.app_version, self.toxcore_version, self.os, self.arch, self.thread, self.message
+        );
+        if let Some(location) = &self.location {
+            text.push_str(&format!("At: {location}\n"));
+        }
+        text.push_str(&format!("\nBacktrace:\n{}\n\nRecent logs:\n", self.backtrace));
+        for line in &self.log_tail {
+            text.push_str(line);
+            text.push('\n');
+        }
+        text
+    }
+}
+
+/// Write a report for every panic, then carry on with the default hook
+pub fn install_panic_hook(app_version: String) {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let report = CrashReport::capture(&app_version, info);
+        match save(&report) {
+            Ok(path) => error!("Panic: {} - crash report saved to {}", report.message, path.display()),
+            Err(e) => error!("Panic: {} - {e}", report.message),
+        }
+        default_hook(info);
+    }));
+}
+
+fn crashes_dir() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("toxcord")
+        .join("crashes")
+}
+
+fn report_path(id: &str) -> Result<PathBuf, String> {
+    // Ids come from the frontend; keep them to names we generate
+    if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+        return Err("Invalid crash report id".to_string());
+    }
+    Ok(crashes_dir().join(format!("{id}.json")))
+}
+
+fn save(report: &CrashReport) -> Result<PathBuf, String> {
+    std::fs::create_dir_all(crashes_dir()).map_err(|e| format!("Failed to create crash report dir: {e}"))?;
+    let path = report_path(&report.id)?;
+    let json = serde_json::to_string_pretty(report).map_err(|e| format!("Failed to serialize crash report: {e}"))?;
+    std::fs::write(&path, json).map_err(|e| format!("Failed to write crash report: {e}"))?;
+    Ok(path)
+}
+
+/// Reports not yet shared or dismissed, oldest first
+pub fn pending() -> Vec<CrashReport> {
+    let Ok(entries) = std::fs::read_dir(crashes_dir()) else {
+        return Vec::new();
+    };
+    let mut reports: Vec<CrashReport> = entries
+        .flatten()
+        .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|entry| {
+            let data = std::fs::read_to_string(entry.path()).ok()?;
+            serde_json::from_str(&data)
+                .inspect_err(|e| warn!("Ignoring unreadable crash report {}: {e}", entry.path().display()))
+                .ok()
+        })
+        .collect();
+    reports.sort_by(|a, b| a.time.cmp(&b.time));
+    reports
+}
+
+fn load(id: &str) -> Result<CrashReport, String> {
+    let data = std::fs::read_to_string(report_path(id)?).map_err(|e| format!("Crash report not found: {e}"))?;
+    serde_json::from_str(&data).map_err(|e| format!("Failed to parse crash report: {e}"))
+}
+
+/// Delete a report without sharing it
+pub fn dismiss(id: &str) -> Result<(), String> {
+    match std::fs::remove_file(report_path(id)?) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(format!("Failed to delete crash report: {e}")),
+    }
+}
+
+/// Save a report to the downloads folder and open a new issue to attach it
+/// to, returning the saved file's path. The report is no longer pending.
+pub fn submit(id: &str) -> Result<PathBuf, String> {
+    let report = load(id)?;
+    let dir = dirs::download_dir()
+        .or_else(dirs::home_dir)
+        .unwrap_or_else(|| PathBuf::from("."));
+    let path = dir.join(format!("toxcord-{}.txt", report.id));
+    std::fs::write(&path, report.to_text()).map_err(|e| format!("Failed to save crash report: {e}"))?;
+    dismiss(id)?;
+
+    let mut url = tauri::Url::parse(ISSUES_URL).map_err(|e| e.to_string())?;
+    let title: String = report.message.lines().next().unwrap_or_default().chars().take(80).collect();
+    url.query_pairs_mut()
+        .append_pair("title", &format!("Crash: {title}"))
+        .append_pair(
+            "body",
+            &format!(
+                "Toxcord {} on {} {} crashed.\n\nWhat were you doing?\n\n\n(Attach {} here.)",
+                report.app_version,
+                report.os,
+                report.arch,
+                path.file_name().unwrap_or_default().to_string_lossy()
+            ),
+        );
+    if let Err(e) = tauri_plugin_opener::open_url(url.as_str(), None::<&str>) {
+        warn!("Failed to open the issue tracker: {e}");
+    }
+    Ok(path)
+}
+
+/// Redact keys as the log file does, and hide the user's home directory
+fn scrub(text: &str) -> String {
+    let text = logging::redact(text);
+    match dirs::home_dir().map(|home| home.to_string_lossy().to_string()) {
+        Some(home) if home.len() > 1 => text.replace(&home, "~"),
+        _ => text,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_path_rejects_traversal() {
+        assert!(report_path("crash-20240101-000000-abcdef12").is_ok());
+        assert!(report_path("../settings").is_err());
+        assert!(report_path("").is_err());
+    }
+
+    #[test]
+    fn test_scrub() {
+        let key = "76518406F6A9F2217E8DC487CC783C25CC16A15EB36FF32E335A235342C48A39";
+        assert_eq!(scrub(&format!("No friend {key}")), "No friend 76518406…");
+        if let Some(home) = dirs::home_dir().filter(|h| h.to_string_lossy().len() > 1) {
+            let path = home.join("toxcord").join("profile.tox");
+            assert!(!scrub(&path.to_string_lossy()).contains(&*home.to_string_lossy()));
+        }
+    }
+}
@@ -0,0 +1,70 @@
+//! `toxcord://` links opened from outside the app.
+//!
+//! The deep link plugin registers the scheme with the OS. A link that starts
+//! the app arrives as its start URL; one opened while it's already running is
+//! handed to the running instance by the single instance plugin. Either way
+//! the link is parsed, kept as pending and announced on `deep-link://open`,
+//! and the frontend asks before adding the friend or joining the guild, or
+//! jumps to the message a message link points at. The pending link is for a
+//! frontend that wasn't listening yet, such as the login page: it reads it
+//! with `take_pending_link` once a profile is loaded.
+
+use tauri::{AppHandle, Emitter, Manager};
+use tauri_plugin_deep_link::DeepLinkExt;
+use toxcord_protocol::links::Link;
+use tracing::{info, warn};
+
+use crate::tray;
+use crate::AppState;
+
+pub const DEEP_LINK_EVENT: &str = "deep-link://open";
+
+/// Handle the start URL and listen for links opened later
+pub fn setup(app: &AppHandle) {
+    // Other platforms register the scheme when the app is installed
+    #[cfg(any(target_os = "linux", all(debug_assertions, windows)))]
+    if let Err(e) = app.deep_link().register_all() {
+        warn!("Failed to register toxcord:// links: {e}");
+    }
+
+    match app.deep_link().get_current() {
+        Ok(urls) => {
+            for url in urls.unwrap_or_default() {
+                open(app, url.as_str());
+            }
+        }
+        Err(e) => warn!("Failed to read the link the app was opened with: {e}"),
+    }
+
+    let handle = app.clone();
+    app.deep_link().on_open_url(move |event| {
+        for url in event.urls() {
+            open(&handle, url.as_str());
+        }
+    });
+}
+
+fn open(app: &AppHandle, url: &str) {
+    let Some(link) = Link::parse(url) else {
+        warn!("Ignoring unrecognized link {url}");
+        return;
+    };
+    info!("Opened {} link", match link {
+        Link::AddFriend { .. } => "add friend",
+        Link::JoinGuild { .. } => "join guild",
+        Link::ChannelMessage { .. } | Link::DirectMessage { .. } => "message",
+    });
+
+    if let Ok(mut pending) = app.state::<AppState>().pending_link.lock() {
+        *pending = Some(link.clone());
+    }
+    tray::show_main_window(app);
+    if let Err(e) = app.emit(DEEP_LINK_EVENT, &link) {
+        warn!("Failed to emit link event: {e}");
+    }
+}
+
+/// The last link opened that the frontend hasn't handled
+pub fn take_pending(app: &AppHandle) -> Option<Link> {
+    app.state::<AppState>().pending_link.lock().ok()?.take()
+}
@@ -0,0 +1,186 @@
+//! Errors returned by Tauri commands.
+//!
+//! Commands return `ToxcordError`, which reaches the frontend as
+//! `{ code, message }` so the UI can react to the kind of failure (e.g.
+//! offer to retry once a friend is online) rather than only show the text.
+//!
+//! Errors are classified by their type: `ToxError` and `OpenError` variants
+//! map to codes, and code that knows what went wrong returns the variant
+//! itself. Most of the app still reports errors as strings; those are
+//! `other`, whatever they say.
+
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+use toxcord_core::db::message_store::OpenError;
+use toxcord_tox::ToxError;
+
+use crate::audio::AudioError;
+use crate::video::VideoError;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ToxcordError {
+    #[error("Not logged in")]
+    NotLoggedIn,
+
+    #[error("Already connected to a profile")]
+    AlreadyLoggedIn,
+
+    #[error("Wrong password")]
+    WrongPassword,
+
+    #[error("{0}")]
+    NotFound(String),
+
+    #[error("{0}")]
+    InvalidInput(String),
+
+    #[error("Friend is offline")]
+    FriendOffline,
+
+    #[error("Message is too long")]
+    MessageTooLong,
+
+    #[error("Slow mode is on in #{channel}: you can send again in {remaining_secs}s")]
+    SlowMode { channel: String, remaining_secs: u32 },
+
+    #[error("{0}")]
+    NotPermitted(String),
+
+    #[error("{0}")]
+    Tox(String),
+
+    #[error("{0}")]
+    Audio(String),
+
+    #[error("{0}")]
+    Video(String),
+
+    #[error("{0}")]
+    Other(String),
+}
+
+/// Result of a Tauri command
+pub type CommandResult<T> = Result<T, ToxcordError>;
+
+impl ToxcordError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::NotLoggedIn => "not_logged_in",
+            Self::AlreadyLoggedIn => "already_logged_in",
+            Self::WrongPassword => "wrong_password",
+            Self::NotFound(_) => "not_found",
+            Self::InvalidInput(_) => "invalid_input",
+            Self::FriendOffline => "friend_offline",
+            Self::MessageTooLong => "message_too_long",
+            Self::SlowMode { .. } => "slow_mode",
+            Self::NotPermitted(_) => "not_permitted",
+            Self::Tox(_) => "tox",
+            Self::Audio(_) => "audio",
+            Self::Video(_) => "video",
+            Self::Other(_) => "other",
+        }
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::NotFound(message.into())
+    }
+
+    pub fn invalid(message: impl Into<String>) -> Self {
+        Self::InvalidInput(message.into())
+    }
+}
+
+impl Serialize for ToxcordError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("ToxcordError", 3)?;
+        state.serialize_field("code", self.code())?;
+        state.serialize_field("message", &self.to_string())?;
+        // How long until slow mode lets the user send again
+        let remaining_secs = match self {
+            Self::SlowMode { remaining_secs, .. } => Some(*remaining_secs),
+            _ => None,
+        };
+        state.serialize_field("remaining_secs", &remaining_secs)?;
+        state.end()
+    }
+}
+
+impl From<String> for ToxcordError {
+    fn from(message: String) -> Self {
+        Self::Other(message)
+    }
+}
+
+impl From<&str> for ToxcordError {
+    fn from(message: &str) -> Self {
+        Self::from(message.to_string())
+    }
+}
+
+impl From<ToxError> for ToxcordError {
+    fn from(e: ToxError) -> Self {
+        match e {
+            ToxError::FriendNotConnected => Self::FriendOffline,
+            ToxError::MessageTooLong => Self::MessageTooLong,
+            other => Self::Tox(other.to_string()),
+        }
+    }
+}
+
+impl From<OpenError> for ToxcordError {
+    fn from(e: OpenError) -> Self {
+        match e {
+            OpenError::WrongPassword => Self::WrongPassword,
+            other => Self::Other(other.to_string()),
+        }
+    }
+}
+
+impl From<AudioError> for ToxcordError {
+    fn from(e: AudioError) -> Self {
+        Self::Audio(e.to_string())
+    }
+}
+
+impl From<VideoError> for ToxcordError {
+    fn from(e: VideoError) -> Self {
+        Self::Video(e.to_string())
+    }
+}
+
+/// For callers outside the command layer that still work with strings
+impl From<ToxcordError> for String {
+    fn from(e: ToxcordError) -> Self {
+        e.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tox_errors_keep_their_code() {
+        let cases = [
+            (ToxError::FriendNotConnected, "friend_offline"),
+            (ToxError::MessageTooLong, "message_too_long"),
+            (ToxError::Group("group_send_message failed: group 3 not found".into()), "tox"),
+        ];
+        for (tox_error, code) in cases {
+            assert_eq!(ToxcordError::from(tox_error).code(), code);
+        }
+    }
+
+    #[test]
+    fn test_strings_are_not_classified() {
+        // Only the type of an error decides its code, not what it says
+        for message in ["Friend not found", "Not connected", "Wrong password", "Message is too long"] {
+            assert_eq!(ToxcordError::from(message).code(), "other");
+        }
+        let slow = ToxcordError::SlowMode { channel: "general".into(), remaining_secs: 12 };
+        let json = serde_json::to_value(&slow).unwrap();
+        assert_eq!(json["code"], "slow_mode");
+        assert_eq!(json["remaining_secs"], 12);
+        assert_eq!(json["message"], "Slow mode is on in #general: you can send again in 12s");
+    }
+}
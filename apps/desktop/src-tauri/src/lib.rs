@@ -1,14 +1,27 @@
+mod accessibility;
 mod audio;
 mod commands;
-mod db;
+mod crash;
+mod deep_link;
+mod error;
+mod logging;
 mod managers;
+mod pip;
+mod settings;
+mod tray;
+mod updater;
 mod video;
 
 use std::sync::Arc;
+use tauri::{Manager, WindowEvent};
 use tokio::sync::Mutex;
+use toxcord_core::db;
 
 use db::MessageStore;
+use managers::bot_api::BotApiServer;
+use managers::shortcut_manager::ShortcutManager;
 use managers::tox_manager::ToxManager;
+use settings::AppSettings;
 
 /// Global application state shared across Tauri commands
 pub struct AppState {
@@ -16,57 +29,284 @@ pub struct AppState {
     pub message_store: Mutex<Option<Arc<MessageStore>>>,
     /// Selected audio input device index (None = default)
     pub selected_mic_index: Mutex<Option<u32>>,
-    /// Selected audio output device index (None = default)
-    pub selected_speaker_index: Mutex<Option<u32>>,
+    /// Selected audio output device name (None = default)
+    pub selected_speaker: Mutex<Option<String>>,
     /// Selected video device index (None = default)
     pub selected_camera_index: Mutex<Option<u32>>,
     /// Whether screen sharing is active (replaces camera)
     pub is_screen_sharing: Mutex<bool>,
     /// Selected screen ID for sharing (None = primary)
     pub screen_share_id: Mutex<Option<u32>>,
+    /// Region and frame rate for screen sharing
+    pub screen_share_options: Mutex<video::ScreenShareOptions>,
+    /// App-wide settings (persisted to settings.json)
+    pub settings: Mutex<AppSettings>,
+    /// Voice note being recorded, if any
+    pub voice_recording: Mutex<Option<Arc<audio::voice_note::RecordingControl>>>,
+    /// LAN discovery, while enabled
+    pub lan_discovery: Mutex<Option<managers::lan_discovery::LanDiscovery>>,
+    /// Local bot API server, while enabled
+    pub bot_api: Mutex<Option<managers::bot_api::BotApiServer>>,
+    /// IRC/Matrix bridges of the logged-in profile
+    pub bridges: Mutex<Option<managers::bridge_manager::BridgeManager>>,
+    /// Latest video frames, served to the frontend over `video://`
+    pub video_frames: Arc<video::frames::FrameBuffer>,
+    /// Background effect for outgoing camera video
+    pub video_background: Arc<video::background::BackgroundSetting>,
+    /// Text-to-speech announcements
+    pub accessibility: Arc<accessibility::Accessibility>,
+    /// Camera preview outside a call, while running
+    pub camera_preview: std::sync::Mutex<Option<video::preview::CameraPreview>>,
+    /// `toxcord://` link opened but not yet handled by the frontend
+    pub pending_link: std::sync::Mutex<Option<toxcord_protocol::links::Link>>,
+    /// Newer release found by the last update check
+    pub available_update: Mutex<Option<tauri_plugin_updater::Update>>,
+    /// Event recording and replay for UI development
+    pub event_replay: std::sync::Mutex<managers::event_replay::EventReplay>,
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| "toxcord=debug,toxcord_tox=debug".into()),
-        )
-        .init();
+    logging::init();
+    crash::install_panic_hook(env!("CARGO_PKG_VERSION").to_string());
+    toxcord_core::profile_dirs::migrate_legacy_layout();
+
+    let app_settings = AppSettings::load();
+    let shortcuts = app_settings.shortcuts.clone();
+    let bot_api = app_settings.bot_api.clone();
+    let video_frames = Arc::new(video::frames::FrameBuffer::new());
+    video_frames.set_max_output(app_settings.video.max_output_width, app_settings.video.max_output_height);
+    let video_background = Arc::new(video::background::BackgroundSetting::new(app_settings.video.background_mode));
+    let accessibility = Arc::new(accessibility::Accessibility::new(app_settings.accessibility.clone()));
 
     tauri::Builder::default()
+        // Links opened while running come to this instance instead of starting another
+        .plugin(tauri_plugin_single_instance::init(|app, _args, _cwd| {
+            tray::show_main_window(app);
+        }))
+        .plugin(tauri_plugin_deep_link::init())
         .plugin(tauri_plugin_shell::init())
+        .plugin(
+            tauri_plugin_autostart::Builder::new()
+                .arg(tray::START_MINIMIZED_FLAG)
+                .build(),
+        )
+        .plugin(tauri_plugin_global_shortcut::Builder::new().build())
+        .plugin(tauri_plugin_updater::Builder::new().build())
         .manage(AppState {
             tox_manager: Mutex::new(None),
             message_store: Mutex::new(None),
             selected_mic_index: Mutex::new(None),
-            selected_speaker_index: Mutex::new(None),
+            selected_speaker: Mutex::new(None),
             selected_camera_index: Mutex::new(None),
             is_screen_sharing: Mutex::new(false),
             screen_share_id: Mutex::new(None),
+            screen_share_options: Mutex::new(Default::default()),
+            settings: Mutex::new(app_settings),
+            voice_recording: Mutex::new(None),
+            lan_discovery: Mutex::new(None),
+            bot_api: Mutex::new(None),
+            bridges: Mutex::new(None),
+            video_frames,
+            video_background,
+            accessibility,
+            camera_preview: std::sync::Mutex::new(None),
+            pending_link: std::sync::Mutex::new(None),
+            available_update: Mutex::new(None),
+            event_replay: std::sync::Mutex::new(Default::default()),
+        })
+        .register_uri_scheme_protocol(video::frames::VIDEO_PROTOCOL, |ctx, request| {
+            ctx.app_handle().state::<AppState>().video_frames.serve(&request)
+        })
+        .setup(move |app| {
+            tray::setup_tray(app.handle())?;
+            deep_link::setup(app.handle());
+            if let Err(e) = ShortcutManager::apply(app.handle(), &shortcuts) {
+                tracing::warn!("{e}");
+            }
+            if bot_api.enabled {
+                let handle = app.handle().clone();
+                tauri::async_runtime::spawn(async move {
+                    match BotApiServer::start(handle.clone(), bot_api.port, bot_api.token).await {
+                        Ok(server) => *handle.state::<AppState>().bot_api.lock().await = Some(server),
+                        Err(e) => tracing::warn!("{e}"),
+                    }
+                });
+            }
+            updater::spawn_checker(app.handle().clone());
+            // The window starts hidden; only show it when not launched minimized (e.g. autostart)
+            if !std::env::args().any(|a| a == tray::START_MINIMIZED_FLAG) {
+                tray::show_main_window(app.handle());
+            }
+            Ok(())
+        })
+        .on_window_event(|window, event| {
+            if let WindowEvent::CloseRequested { api, .. } = event {
+                if window.label() == pip::PIP_WINDOW {
+                    pip::remember_position(window);
+                    return;
+                }
+                // Closing the window keeps Tox running in the tray; quit lives in the tray menu
+                let _ = window.hide();
+                api.prevent_close();
+            }
         })
         .invoke_handler(tauri::generate_handler![
             commands::auth::list_profiles,
             commands::auth::create_profile,
+            commands::auth::restore_profile,
             commands::auth::load_profile,
             commands::auth::delete_profile,
+            commands::auth::rename_profile,
+            commands::auth::duplicate_profile,
+            commands::auth::get_profile_storage_info,
+            commands::auth::change_password,
             commands::auth::get_tox_id,
             commands::auth::get_connection_status,
             commands::auth::get_profile_info,
             commands::auth::logout,
+            commands::system::enable_autostart,
+            commands::system::disable_autostart,
+            commands::system::is_autostart_enabled,
+            commands::system::get_shortcuts,
+            commands::system::set_shortcuts,
+            commands::system::get_bot_api_settings,
+            commands::system::set_bot_api_enabled,
+            commands::system::regenerate_bot_api_token,
+            commands::system::get_recent_logs,
+            commands::diagnostics::get_diagnostics,
+            commands::diagnostics::get_dht_info,
+            commands::diagnostics::get_network_mode,
+            commands::diagnostics::set_network_mode,
+            commands::diagnostics::get_power_saving,
+            commands::diagnostics::set_power_saving,
+            commands::diagnostics::get_pending_crash_reports,
+            commands::diagnostics::submit_crash_report,
+            commands::diagnostics::dismiss_crash_report,
+            commands::diagnostics::get_event_replay_status,
+            commands::diagnostics::start_event_recording,
+            commands::diagnostics::stop_event_recording,
+            commands::diagnostics::list_event_recordings,
+            commands::diagnostics::start_event_replay,
+            commands::diagnostics::stop_event_replay,
+            commands::system::export_logs,
+            commands::system::force_save,
+            commands::system::system_suspending,
+            commands::system::system_resumed,
+            commands::system::take_pending_link,
+            commands::system::check_for_updates,
+            commands::system::get_available_update,
+            commands::system::install_update,
+            commands::system::get_update_settings,
+            commands::system::set_update_settings,
             commands::auth::set_display_name,
             commands::auth::set_status_message,
+            commands::auth::set_user_status,
+            commands::auth::set_activity,
+            commands::auth::clear_activity,
+            commands::auth::get_activity,
+            commands::auth::set_custom_status,
+            commands::auth::clear_custom_status,
+            commands::auth::get_custom_status,
+            commands::auth::get_nospam,
+            commands::auth::set_nospam,
+            commands::auth::randomize_nospam,
+            commands::auth::get_nospam_history,
+            commands::auth::get_recovery_phrase,
             commands::friends::add_friend,
             commands::friends::accept_friend_request,
             commands::friends::deny_friend_request,
             commands::friends::remove_friend,
             commands::friends::get_friends,
+            commands::friends::get_friends_page,
+            commands::friends::get_friends_changed_since,
             commands::friends::get_friend_requests,
+            commands::friends::get_friend_presence_summary,
+            commands::friends::get_friend_connection_info,
+            commands::friends::refresh_friend_connection,
+            commands::friends::reconnect_all_friends,
+            commands::friends::get_identity_fingerprint,
+            commands::friends::get_friend_verification,
+            commands::friends::verify_friend,
+            commands::friends::unverify_friend,
+            commands::friends::get_tox_id_qr,
+            commands::friends::parse_contact_link,
+            commands::friends::scan_contact_qr,
+            commands::friends::share_contact,
+            commands::friends::start_lan_discovery,
+            commands::friends::stop_lan_discovery,
+            commands::friends::is_lan_discovery_enabled,
+            commands::friends::get_lan_peers,
+            commands::friends::add_lan_peer,
+            commands::friends::link_device,
+            commands::friends::unlink_device,
+            commands::friends::get_linked_devices,
+            commands::friends::sync_device,
+            commands::friends::post_status_note,
+            commands::friends::get_own_status_notes,
+            commands::friends::get_friend_statuses,
+            commands::friends::get_friend_groups,
+            commands::friends::create_friend_group,
+            commands::friends::rename_friend_group,
+            commands::friends::delete_friend_group,
+            commands::friends::reorder_friend_groups,
+            commands::friends::set_friend_group_collapsed,
+            commands::friends::set_friend_group,
             commands::messaging::send_direct_message,
             commands::messaging::get_direct_messages,
+            commands::messaging::get_direct_messages_page,
+            commands::messaging::get_messages_around,
+            commands::messaging::get_message_link,
+            commands::messaging::resolve_message_link,
+            commands::messaging::get_message_history,
+            commands::messaging::get_own_recent_messages,
+            commands::messaging::get_call_messages,
             commands::messaging::set_typing,
             commands::messaging::mark_messages_read,
+            commands::messaging::schedule_message,
+            commands::messaging::get_scheduled_messages,
+            commands::messaging::cancel_scheduled_message,
+            commands::messaging::star_conversation,
+            commands::messaging::unstar_conversation,
+            commands::messaging::get_recent_conversations,
+            commands::messaging::get_usage_stats,
+            commands::messaging::add_dictionary_word,
+            commands::messaging::remove_dictionary_word,
+            commands::messaging::get_dictionary_words,
+            commands::messaging::get_auto_reply,
+            commands::messaging::set_auto_reply,
+            commands::messaging::enable_conversation_lock,
+            commands::messaging::disable_conversation_lock,
+            commands::messaging::get_conversation_lock,
+            commands::messaging::set_disappearing_timer,
+            commands::messaging::get_disappearing_timer,
+            commands::messaging::set_incognito,
+            commands::messaging::set_incognito_default,
+            commands::messaging::get_incognito,
+            commands::accessibility::get_accessibility_settings,
+            commands::accessibility::set_accessibility_settings,
+            commands::accessibility::set_focused_conversation,
+            commands::accessibility::set_conversation_tts,
+            commands::accessibility::get_muted_tts_conversations,
+            commands::accessibility::speak_message,
+            commands::accessibility::stop_speaking,
+            commands::accessibility::query_commands,
+            commands::files::get_message_attachments,
+            commands::files::get_attachment_thumbnail,
+            commands::files::open_attachment,
+            commands::files::get_media_cache_usage,
+            commands::files::set_media_cache_limit,
+            commands::files::clear_media_cache,
+            commands::files::send_clipboard_image,
+            commands::files::send_channel_file,
+            commands::files::record_voice_message,
+            commands::files::stop_voice_message,
+            commands::files::import_sticker_pack,
+            commands::files::get_sticker_packs,
+            commands::files::delete_sticker_pack,
+            commands::files::resolve_sticker,
+            commands::files::get_sticker_image,
+            commands::files::send_sticker,
             commands::guilds::create_guild,
             commands::guilds::get_guilds,
             commands::guilds::get_guild_channels,
@@ -74,25 +314,80 @@ pub fn run() {
             commands::guilds::delete_channel,
             commands::guilds::send_channel_message,
             commands::guilds::get_channel_messages,
+            commands::guilds::get_channel_messages_page,
+            commands::guilds::get_mentionable_members,
+            commands::guilds::mark_mentions_read,
+            commands::guilds::set_channel_notification_level,
             commands::guilds::invite_to_guild,
-            commands::guilds::accept_guild_invite,
+            commands::guilds::get_group_invites,
+            commands::guilds::accept_group_invite,
+            commands::guilds::decline_group_invite,
+            commands::guilds::get_group_invite_auto_accept,
+            commands::guilds::set_group_invite_auto_accept,
             commands::guilds::get_guild_members,
             commands::guilds::set_channel_topic,
             commands::guilds::kick_member,
             commands::guilds::set_member_role,
             commands::guilds::rename_guild,
             commands::guilds::rename_channel,
+            commands::guilds::reorder_channels,
             commands::guilds::leave_guild,
+            commands::guilds::rejoin_guild,
+            commands::guilds::join_guild_link,
+            commands::guilds::set_guild_password,
+            commands::guilds::get_guild_password_protected,
+            commands::guilds::ignore_guild_member,
+            commands::guilds::unignore_guild_member,
+            commands::guilds::get_keep_ignored_messages,
+            commands::guilds::set_keep_ignored_messages,
+            commands::guilds::browse_public_guilds,
+            commands::guilds::add_public_guild,
+            commands::guilds::remove_public_guild,
             commands::guilds::create_dm_group,
             commands::guilds::send_dm_group_message,
             commands::guilds::get_dm_groups,
+            commands::guilds::link_channel_bridge,
+            commands::guilds::unlink_channel_bridge,
+            commands::guilds::set_channel_bridge_enabled,
+            commands::guilds::get_channel_bridges,
+            commands::guilds::get_audit_log,
+            commands::guilds::set_channel_slow_mode,
+            commands::guilds::set_channel_announcement,
+            commands::guilds::transfer_ownership,
+            commands::guilds::get_word_filter,
+            commands::guilds::set_word_filter,
+            commands::guilds::get_guild_onboarding,
+            commands::guilds::set_guild_onboarding,
+            commands::guilds::accept_guild_rules,
+            commands::guilds::get_guild_roles,
+            commands::guilds::create_guild_role,
+            commands::guilds::update_guild_role,
+            commands::guilds::delete_guild_role,
+            commands::guilds::reorder_guild_roles,
+            commands::guilds::assign_guild_role,
+            commands::guilds::purge_channel_messages,
+            commands::guilds::delete_messages_from_peer,
+            // Poll commands
+            commands::polls::create_poll,
+            commands::polls::vote_poll,
+            commands::polls::get_poll,
+            // Guild event commands
+            commands::events::create_event,
+            commands::events::rsvp_event,
+            commands::events::get_guild_events,
             // Call commands
             commands::calls::call_friend,
             commands::calls::answer_call,
             commands::calls::hangup_call,
             commands::calls::toggle_mute,
+            commands::calls::set_global_mute,
+            commands::calls::set_global_deafen,
             commands::calls::toggle_video,
             commands::calls::get_call_state,
+            commands::calls::toggle_call_pip,
+            commands::calls::list_video_streams,
+            commands::calls::subscribe_video_stream,
+            commands::calls::unsubscribe_video_stream,
             commands::calls::list_audio_input_devices,
             commands::calls::list_audio_output_devices,
             commands::calls::list_video_devices,
@@ -105,6 +400,25 @@ pub fn run() {
             commands::calls::list_screens,
             commands::calls::start_screen_share,
             commands::calls::stop_screen_share,
+            commands::calls::set_do_not_disturb,
+            commands::calls::get_do_not_disturb,
+            commands::calls::set_dnd_auto_reply,
+            commands::calls::get_missed_calls,
+            commands::calls::get_missed_call_counts,
+            commands::calls::mark_missed_calls_seen,
+            commands::calls::get_missed_call_reply,
+            commands::calls::get_call_preferences,
+            commands::calls::set_call_preferences,
+            commands::calls::clear_call_preferences,
+            commands::calls::get_default_call_preferences,
+            commands::calls::set_default_call_preferences,
+            commands::calls::set_missed_call_reply,
+            commands::calls::get_video_settings,
+            commands::calls::set_video_max_output,
+            commands::calls::set_video_background_mode,
+            commands::calls::start_camera_preview,
+            commands::calls::switch_camera,
+            commands::calls::stop_camera_preview,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -0,0 +1,231 @@
+//! Logging
+//!
+//! Logs always go to stdout. While a profile is loaded they are also written
+//! to `logs/toxcord.log` in its directory, so users can attach them to
+//! bug reports without running from a terminal. The file rolls over to
+//! `toxcord.log.1` at `MAX_LOG_BYTES`, and Tox IDs and public keys are
+//! shortened before anything is written to it. The last few lines are also
+//! kept in memory, redacted the same way, for crash reports.
+
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use toxcord_core::profile_dirs::ProfileDir;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt, EnvFilter};
+
+use crate::error::{CommandResult, ToxcordError};
+
+/// Size at which the log file rolls over
+const MAX_LOG_BYTES: u64 = 2 * 1024 * 1024;
+
+/// Hex runs at least this long are keys or addresses (public keys are 64
+/// characters, Tox IDs 76)
+const REDACT_MIN_HEX: usize = 64;
+
+/// Characters of a redacted key that are kept
+const REDACT_KEEP: usize = 8;
+
+/// Lines of recent logs kept in memory
+const TAIL_LINES: usize = 200;
+
+/// The open log file of the loaded profile
+static PROFILE_LOG: Mutex<Option<LogFile>> = Mutex::new(None);
+
+/// The last `TAIL_LINES` lines logged, redacted, whether or not a profile is loaded
+static TAIL: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+pub fn init() {
+    tracing_subscriber::registry()
+        .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| "toxcord=debug,toxcord_tox=debug".into()))
+        .with(fmt::layer())
+        .with(fmt::layer().with_ansi(false).with_writer(ProfileLogWriter::default))
+        .init();
+}
+
+pub fn log_path(profile_name: &str) -> PathBuf {
+    ProfileDir::new(profile_name).log_file()
+}
+
+fn rolled_path(path: &Path) -> PathBuf {
+    let mut rolled = path.as_os_str().to_owned();
+    rolled.push(".1");
+    PathBuf::from(rolled)
+}
+
+/// Start writing logs to a profile's log file
+pub fn open_profile_log(profile_name: &str) -> Result<(), String> {
+    let log = LogFile::open(log_path(profile_name))?;
+    let mut current = PROFILE_LOG.lock().map_err(|e| e.to_string())?;
+    *current = Some(log);
+    Ok(())
+}
+
+/// Stop writing logs to a file, e.g. on logout
+pub fn close_profile_log() {
+    if let Ok(mut current) = PROFILE_LOG.lock() {
+        *current = None;
+    }
+}
+
+/// The loaded profile's logs, oldest first, including the rolled-over file
+fn read_profile_logs() -> CommandResult<String> {
+    let path = PROFILE_LOG
+        .lock()
+        .map_err(|e| e.to_string())?
+        .as_ref()
+        .map(|log| log.path.clone())
+        .ok_or(ToxcordError::NotLoggedIn)?;
+
+    let mut logs = String::new();
+    for file in [rolled_path(&path), path] {
+        match std::fs::read(&file) {
+            Ok(bytes) => logs.push_str(&String::from_utf8_lossy(&bytes)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(format!("Failed to read log file: {e}").into()),
+        }
+    }
+    Ok(logs)
+}
+
+/// The last `count` lines of the loaded profile's logs
+pub fn recent_lines(count: usize) -> CommandResult<Vec<String>> {
+    let logs = read_profile_logs()?;
+    let lines: Vec<&str> = logs.lines().collect();
+    let start = lines.len().saturating_sub(count);
+    Ok(lines[start..].iter().map(|line| line.to_string()).collect())
+}
+
+/// The last lines logged, oldest first. Empty if the buffer is in use, as it
+/// is when a panic happened while logging.
+pub fn tail() -> Vec<String> {
+    TAIL.try_lock().map(|tail| tail.iter().cloned().collect()).unwrap_or_default()
+}
+
+/// Write the loaded profile's logs to a single file
+pub fn export(dest: &Path) -> CommandResult<()> {
+    let logs = read_profile_logs()?;
+    std::fs::write(dest, logs).map_err(|e| format!("Failed to export logs: {e}").into())
+}
+
+struct LogFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+}
+
+impl LogFile {
+    fn open(path: PathBuf) -> Result<Self, String> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create log dir: {e}"))?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| format!("Failed to open log file: {e}"))?;
+        let size = file.metadata().map(|m| m.len()).unwrap_or(0);
+        Ok(Self { path, file, size })
+    }
+
+    fn append(&mut self, line: &str) -> io::Result<()> {
+        if self.size >= MAX_LOG_BYTES {
+            self.roll()?;
+        }
+        self.file.write_all(line.as_bytes())?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+
+    fn roll(&mut self) -> io::Result<()> {
+        std::fs::rename(&self.path, rolled_path(&self.path))?;
+        self.file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+/// Collects one formatted event and appends it to the profile log when dropped
+#[derive(Default)]
+struct ProfileLogWriter {
+    buf: Vec<u8>,
+}
+
+impl Write for ProfileLogWriter {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(bytes);
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for ProfileLogWriter {
+    fn drop(&mut self) {
+        if self.buf.is_empty() {
+            return;
+        }
+        let text = redact(&String::from_utf8_lossy(&self.buf));
+        if let Ok(mut tail) = TAIL.lock() {
+            for line in text.lines() {
+                if tail.len() == TAIL_LINES {
+                    tail.pop_front();
+                }
+                tail.push_back(line.to_string());
+            }
+        }
+        let Ok(mut current) = PROFILE_LOG.lock() else {
+            return;
+        };
+        if let Some(log) = current.as_mut() {
+            // Logging from here would come straight back to us
+            let _ = log.append(&text);
+        }
+    }
+}
+
+/// Shorten runs of hex long enough to be keys or addresses
+pub fn redact(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut run = String::new();
+    for c in text.chars().chain(std::iter::once('\n')) {
+        if c.is_ascii_hexdigit() {
+            run.push(c);
+            continue;
+        }
+        if run.len() >= REDACT_MIN_HEX {
+            out.push_str(&run[..REDACT_KEEP]);
+            out.push('…');
+        } else {
+            out.push_str(&run);
+        }
+        run.clear();
+        out.push(c);
+    }
+    out.pop();
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact_keys() {
+        let tox_id = "76518406F6A9F2217E8DC487CC783C25CC16A15EB36FF32E335A235342C48A39218F515C39A6";
+        let line = format!("Friend request from {tox_id} (abc123)\n");
+        assert_eq!(redact(&line), "Friend request from 76518406… (abc123)\n");
+    }
+
+    #[test]
+    fn test_redact_leaves_short_hex() {
+        let line = "Message 0xdeadbeef at 2024-01-01T00:00:00Z";
+        assert_eq!(redact(line), line);
+    }
+}
@@ -2,9 +2,20 @@
 //!
 //! Manages ToxAV call state. Audio capture/playback is managed separately
 //! on the tox thread since cpal types are not Send.
+//!
+//! Each peer sending video is tracked as a stream, so the UI can tile several
+//! at once. Frames are only converted for display for subscribed streams;
+//! streams are subscribed when they start, and the UI unsubscribes from the
+//! ones it isn't showing.
+//!
+//! Connected calls are also watched for liveness: a call whose peer says it's
+//! sending but delivers no frames, or whose peer has gone offline, is dropped
+//! by the tox thread once it's been that way for a while.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use tauri::Emitter;
 use tracing::{debug, error, info, warn};
@@ -12,7 +23,15 @@ use tracing::{debug, error, info, warn};
 use toxcord_tox::{CallStateFlags, ToxAvEventHandler};
 
 use crate::audio::AudioMixer;
+use crate::video::convert::YuvPlanes;
+use crate::video::frames::{FrameBuffer, VideoSource};
 
+/// A connected call whose peer is sending but delivers no frames for this
+/// long is dropped
+pub const CALL_MEDIA_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A connected call whose peer has been offline this long is dropped
+pub const CALL_OFFLINE_TIMEOUT: Duration = Duration::from_secs(15);
 
 /// Call state for a single call
 #[derive(Debug, Clone, serde::Serialize)]
@@ -24,6 +43,50 @@ pub struct CallState {
     pub is_audio_muted: bool,
     pub is_video_muted: bool,
     pub started_at: Option<String>,
+    /// Identifies the call once connected; messages exchanged during it are
+    /// tagged with this
+    pub session_id: Option<String>,
+}
+
+/// Video being received from a peer
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct VideoStream {
+    pub friend_number: u32,
+    pub width: u16,
+    pub height: u16,
+    /// Whether frames are converted and served for display
+    pub subscribed: bool,
+}
+
+/// When a connected call last showed signs of life
+#[derive(Debug, Clone, Copy)]
+struct CallLiveness {
+    last_frame: Instant,
+    /// Whether the peer says it's sending audio or video; a peer that has
+    /// muted everything sends no frames and isn't stalled
+    peer_sending: bool,
+    offline_since: Option<Instant>,
+}
+
+/// Why a call was dropped without either side hanging up
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StallReason {
+    /// No frames from a peer that says it's sending
+    NoMedia,
+    /// The peer's Tox connection went away
+    PeerOffline,
+    /// The computer went to sleep
+    Suspended,
+}
+
+impl StallReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::NoMedia => "no_media",
+            Self::PeerOffline => "peer_offline",
+            Self::Suspended => "suspended",
+        }
+    }
 }
 
 /// Call status
@@ -71,17 +134,40 @@ pub enum ToxAvEvent {
         friend_number: u32,
         level: f32,
     },
-    /// Video frame received from a peer
-    VideoFrame {
+    /// Video capture error (e.g., no camera available)
+    VideoError {
+        error: String,
+    },
+    /// Microphone muted/unmuted globally (e.g., from the tray menu)
+    GlobalMuteChanged {
+        muted: bool,
+    },
+    /// Incoming audio deafened/undeafened globally
+    GlobalDeafenChanged {
+        deafened: bool,
+    },
+    /// A peer started sending video, or changed its resolution
+    VideoStreamStarted {
         friend_number: u32,
         width: u16,
         height: u16,
-        /// YUV420 data: Y plane followed by U plane followed by V plane
-        data: Vec<u8>,
     },
-    /// Video capture error (e.g., no camera available)
-    VideoError {
-        error: String,
+    /// A peer stopped sending video
+    VideoStreamEnded {
+        friend_number: u32,
+    },
+    /// A connected call was ended by the watchdog
+    CallDropped {
+        friend_number: u32,
+        reason: String,
+    },
+    /// An incoming call wasn't answered
+    MissedCall {
+        id: String,
+        friend_number: u32,
+        timestamp: String,
+        with_video: bool,
+        reason: String,
     },
 }
 
@@ -96,15 +182,19 @@ pub struct AvManager {
     is_muted: bool,
     /// Whether audio is globally deafened
     is_deafened: bool,
+    /// Incoming video keyed by friend_number
+    streams: HashMap<u32, VideoStream>,
+    /// Peers whose video the UI doesn't show, kept until their call ends
+    unsubscribed: HashSet<u32>,
+    /// Liveness of connected calls keyed by friend_number
+    liveness: HashMap<u32, CallLiveness>,
+    /// Seconds connected in calls that have ended, by friend, for usage statistics
+    finished_call_time: Vec<(u32, i64)>,
 }
 
 impl AvManager {
     pub fn new() -> Self {
-        Self {
-            calls: HashMap::new(),
-            is_muted: false,
-            is_deafened: false,
-        }
+        Self::default()
     }
 
     /// Start a call with a friend
@@ -117,6 +207,7 @@ impl AvManager {
             is_audio_muted: false,
             is_video_muted: !with_video,
             started_at: None,
+            session_id: None,
         };
         self.calls.insert(friend_number, call);
         info!("Started call with friend {}", friend_number);
@@ -132,6 +223,7 @@ impl AvManager {
             is_audio_muted: false,
             is_video_muted: !video_enabled,
             started_at: None,
+            session_id: None,
         };
         self.calls.insert(friend_number, call);
         info!("Incoming call from friend {} (audio: {}, video: {})",
@@ -143,6 +235,9 @@ impl AvManager {
         if let Some(call) = self.calls.get_mut(&friend_number) {
             let old_state = call.state;
             if state.error || state.finished {
+                if let Some(seconds) = connected_seconds(call) {
+                    self.finished_call_time.push((friend_number, seconds));
+                }
                 call.state = if state.error {
                     CallStatus::Error
                 } else {
@@ -152,8 +247,20 @@ impl AvManager {
                 if call.state != CallStatus::InProgress {
                     call.state = CallStatus::InProgress;
                     call.started_at = Some(chrono::Utc::now().to_rfc3339());
+                    call.session_id = Some(uuid::Uuid::new_v4().to_string());
                     info!("Call with friend {} transitioned from {:?} to InProgress", friend_number, old_state);
                 }
+                let liveness = self.liveness.entry(friend_number).or_insert(CallLiveness {
+                    last_frame: Instant::now(),
+                    peer_sending: false,
+                    offline_since: None,
+                });
+                let was_sending = liveness.peer_sending;
+                liveness.peer_sending = state.sending_audio || state.sending_video;
+                // Frames can't be expected before the peer starts sending
+                if liveness.peer_sending && !was_sending {
+                    liveness.last_frame = Instant::now();
+                }
             }
 
             // Update audio capability from callback
@@ -180,17 +287,113 @@ impl AvManager {
     /// End a call
     pub fn end_call(&mut self, friend_number: u32) {
         if let Some(call) = self.calls.get_mut(&friend_number) {
+            if let Some(seconds) = connected_seconds(call) {
+                self.finished_call_time.push((friend_number, seconds));
+            }
             call.state = CallStatus::Ended;
         }
         self.calls.remove(&friend_number);
+        self.streams.remove(&friend_number);
+        self.unsubscribed.remove(&friend_number);
+        self.liveness.remove(&friend_number);
         info!("Ended call with friend {}", friend_number);
     }
 
+    /// Time spent in calls that ended since this was last called
+    pub fn take_finished_call_time(&mut self) -> Vec<(u32, i64)> {
+        std::mem::take(&mut self.finished_call_time)
+    }
+
+    /// Note that a frame arrived from a peer
+    pub fn frame_received(&mut self, friend_number: u32) {
+        if let Some(liveness) = self.liveness.get_mut(&friend_number) {
+            liveness.last_frame = Instant::now();
+        }
+    }
+
+    /// Connected calls that have stalled as of `now`, given whether each peer
+    /// is currently online
+    pub fn stalled_calls(&mut self, now: Instant, is_online: impl Fn(u32) -> bool) -> Vec<(u32, StallReason)> {
+        let mut stalled = Vec::new();
+        for (&friend_number, liveness) in &mut self.liveness {
+            if is_online(friend_number) {
+                liveness.offline_since = None;
+            } else {
+                let since = *liveness.offline_since.get_or_insert(now);
+                if now.duration_since(since) >= CALL_OFFLINE_TIMEOUT {
+                    stalled.push((friend_number, StallReason::PeerOffline));
+                    continue;
+                }
+            }
+            if liveness.peer_sending && now.duration_since(liveness.last_frame) >= CALL_MEDIA_TIMEOUT {
+                stalled.push((friend_number, StallReason::NoMedia));
+            }
+        }
+        stalled
+    }
+
+    /// Record a video frame from a peer. Returns the stream if it just
+    /// started or changed resolution, and whether the frame should be shown.
+    pub fn video_frame_received(&mut self, friend_number: u32, width: u16, height: u16) -> (Option<VideoStream>, bool) {
+        let subscribed = !self.unsubscribed.contains(&friend_number);
+        let changed = match self.streams.get(&friend_number) {
+            Some(stream) => (stream.width, stream.height) != (width, height),
+            None => true,
+        };
+        if !changed {
+            return (None, subscribed);
+        }
+        let stream = VideoStream {
+            friend_number,
+            width,
+            height,
+            subscribed,
+        };
+        self.streams.insert(friend_number, stream.clone());
+        (Some(stream), subscribed)
+    }
+
+    /// Forget a peer's stream when they stop sending video. Returns whether
+    /// there was one.
+    pub fn end_video_stream(&mut self, friend_number: u32) -> bool {
+        self.streams.remove(&friend_number).is_some()
+    }
+
+    /// Streams currently being received
+    pub fn video_streams(&self) -> Vec<VideoStream> {
+        self.streams.values().cloned().collect()
+    }
+
+    /// Show or stop showing a peer's video. Returns false if there's no call
+    /// with them.
+    pub fn set_stream_subscribed(&mut self, friend_number: u32, subscribed: bool) -> bool {
+        if !self.calls.contains_key(&friend_number) {
+            return false;
+        }
+        if subscribed {
+            self.unsubscribed.remove(&friend_number);
+        } else {
+            self.unsubscribed.insert(friend_number);
+        }
+        if let Some(stream) = self.streams.get_mut(&friend_number) {
+            stream.subscribed = subscribed;
+        }
+        true
+    }
+
     /// Get call state for a friend
     pub fn get_call(&self, friend_number: u32) -> Option<&CallState> {
         self.calls.get(&friend_number)
     }
 
+    /// Session of the call in progress with a friend, if any
+    pub fn call_session(&self, friend_number: u32) -> Option<String> {
+        self.calls
+            .get(&friend_number)
+            .filter(|c| c.state == CallStatus::InProgress)
+            .and_then(|c| c.session_id.clone())
+    }
+
     /// Get all active calls
     pub fn get_all_calls(&self) -> Vec<&CallState> {
         self.calls.values().collect()
@@ -245,13 +448,32 @@ impl AvManager {
     }
 }
 
-/// ToxAV event handler that forwards events to the frontend via Tauri
-/// and pushes received audio to the mixer for playback
+/// How long a call has been connected, if it is
+fn connected_seconds(call: &CallState) -> Option<i64> {
+    if call.state != CallStatus::InProgress {
+        return None;
+    }
+    let started = chrono::DateTime::parse_from_rfc3339(call.started_at.as_deref()?).ok()?;
+    Some((chrono::Utc::now() - started.with_timezone(&chrono::Utc)).num_seconds().max(0))
+}
+
+/// ToxAV event handler that forwards events to the frontend via Tauri,
+/// pushes received audio to the mixer for playback and received video to
+/// the frame buffer
 pub struct TauriAvEventHandler {
     app_handle: tauri::AppHandle,
     av_manager: Arc<std::sync::Mutex<AvManager>>,
     /// Mixer for combining audio from multiple sources
     mixer: Arc<std::sync::Mutex<AudioMixer>>,
+    /// Latest frames for the frontend
+    video_frames: Arc<FrameBuffer>,
+    /// Set while our status is Busy; incoming calls are declined
+    do_not_disturb: Arc<AtomicBool>,
+    /// Sender to queue declined calls, as (friend, with video), for the tox
+    /// thread to hang up
+    declined_call_tx: std::sync::mpsc::Sender<(u32, bool)>,
+    /// Sender to queue calls that rang out unanswered, as (friend, with video)
+    missed_call_tx: std::sync::mpsc::Sender<(u32, bool)>,
 }
 
 impl TauriAvEventHandler {
@@ -259,11 +481,19 @@ impl TauriAvEventHandler {
         app_handle: tauri::AppHandle,
         av_manager: Arc<std::sync::Mutex<AvManager>>,
         mixer: Arc<std::sync::Mutex<AudioMixer>>,
+        video_frames: Arc<FrameBuffer>,
+        do_not_disturb: Arc<AtomicBool>,
+        declined_call_tx: std::sync::mpsc::Sender<(u32, bool)>,
+        missed_call_tx: std::sync::mpsc::Sender<(u32, bool)>,
     ) -> Self {
         Self {
             app_handle,
             av_manager,
             mixer,
+            video_frames,
+            do_not_disturb,
+            declined_call_tx,
+            missed_call_tx,
         }
     }
 
@@ -278,6 +508,13 @@ impl ToxAvEventHandler for TauriAvEventHandler {
     fn on_call(&self, friend_number: u32, audio_enabled: bool, video_enabled: bool) {
         info!("Incoming call from friend {}", friend_number);
 
+        // Don't ring; the tox thread hangs up, which the caller sees as busy
+        if self.do_not_disturb.load(Ordering::Relaxed) {
+            info!("Declining call from friend {} (Do Not Disturb)", friend_number);
+            let _ = self.declined_call_tx.send((friend_number, video_enabled));
+            return;
+        }
+
         // Update manager state synchronously using blocking lock
         if let Ok(mut mgr) = self.av_manager.lock() {
             mgr.handle_incoming_call(friend_number, audio_enabled, video_enabled);
@@ -304,9 +541,29 @@ impl ToxAvEventHandler for TauriAvEventHandler {
             "unknown"
         };
 
-        // Update manager state
-        if let Ok(mut mgr) = self.av_manager.lock() {
-            mgr.update_call_state(friend_number, state);
+        // Update manager state, ending their video stream if they stopped sending it
+        let mut rang_out = None;
+        let stream_ended = match self.av_manager.lock() {
+            Ok(mut mgr) => {
+                if state.finished || state.error {
+                    rang_out = mgr
+                        .get_call(friend_number)
+                        .filter(|c| c.state == CallStatus::RingingIncoming)
+                        .map(|c| c.has_video);
+                }
+                mgr.update_call_state(friend_number, state);
+                let stopped_video = state.finished || state.error || !state.sending_video;
+                stopped_video && mgr.end_video_stream(friend_number)
+            }
+            Err(_) => false,
+        };
+        if stream_ended {
+            self.video_frames.remove(VideoSource::Friend(friend_number));
+            self.emit(ToxAvEvent::VideoStreamEnded { friend_number });
+        }
+        // The caller gave up before we answered
+        if let Some(with_video) = rang_out {
+            let _ = self.missed_call_tx.send((friend_number, with_video));
         }
 
         self.emit(ToxAvEvent::CallStateChange {
@@ -330,6 +587,8 @@ impl ToxAvEventHandler for TauriAvEventHandler {
             if let Ok(mut mixer) = self.mixer.lock() {
                 mixer.remove_source(friend_number);
             }
+            self.video_frames.remove(VideoSource::Friend(friend_number));
+            crate::pip::close(&self.app_handle);
         }
     }
 
@@ -347,6 +606,18 @@ impl ToxAvEventHandler for TauriAvEventHandler {
             friend_number, sample_count, channels, sampling_rate, pcm.len()
         );
 
+        // Drop incoming audio while deafened
+        let deafened = match self.av_manager.lock() {
+            Ok(mut mgr) => {
+                mgr.frame_received(friend_number);
+                mgr.is_deafened()
+            }
+            Err(_) => false,
+        };
+        if deafened {
+            return;
+        }
+
         // Push received audio to the mixer for playback
         if let Ok(mut mixer) = self.mixer.lock() {
             mixer.push_frame(friend_number, pcm.to_vec());
@@ -371,62 +642,37 @@ impl ToxAvEventHandler for TauriAvEventHandler {
             friend_number, width, height, y_stride, u_stride, v_stride
         );
 
-        // Handle stride correction if needed
-        let w = width as usize;
-        let h = height as usize;
-        let uv_w = w / 2;
-        let uv_h = h / 2;
-
-        let y_stride_abs = y_stride.unsigned_abs() as usize;
-        let u_stride_abs = u_stride.unsigned_abs() as usize;
-        let v_stride_abs = v_stride.unsigned_abs() as usize;
-
-        // Copy data, removing stride padding if present
-        let y_data: Vec<u8> = if y_stride_abs == w {
-            y[..w * h].to_vec()
-        } else {
-            let mut data = Vec::with_capacity(w * h);
-            for row in 0..h {
-                let start = row * y_stride_abs;
-                data.extend_from_slice(&y[start..start + w]);
-            }
-            data
-        };
-
-        let u_data: Vec<u8> = if u_stride_abs == uv_w {
-            u[..uv_w * uv_h].to_vec()
-        } else {
-            let mut data = Vec::with_capacity(uv_w * uv_h);
-            for row in 0..uv_h {
-                let start = row * u_stride_abs;
-                data.extend_from_slice(&u[start..start + uv_w]);
+        let (started, subscribed) = match self.av_manager.lock() {
+            Ok(mut mgr) => {
+                mgr.frame_received(friend_number);
+                mgr.video_frame_received(friend_number, width, height)
             }
-            data
+            Err(_) => (None, true),
         };
+        if let Some(stream) = started {
+            info!("Video stream from friend {friend_number} at {width}x{height}");
+            self.emit(ToxAvEvent::VideoStreamStarted {
+                friend_number: stream.friend_number,
+                width: stream.width,
+                height: stream.height,
+            });
+        }
+        if !subscribed {
+            return;
+        }
 
-        let v_data: Vec<u8> = if v_stride_abs == uv_w {
-            v[..uv_w * uv_h].to_vec()
-        } else {
-            let mut data = Vec::with_capacity(uv_w * uv_h);
-            for row in 0..uv_h {
-                let start = row * v_stride_abs;
-                data.extend_from_slice(&v[start..start + uv_w]);
-            }
-            data
+        // Drops the stride padding and scales to the output limit
+        let frame = YuvPlanes {
+            y,
+            u,
+            v,
+            y_stride: y_stride.unsigned_abs() as usize,
+            u_stride: u_stride.unsigned_abs() as usize,
+            v_stride: v_stride.unsigned_abs() as usize,
+            width: width as usize,
+            height: height as usize,
         };
-
-        // Combine YUV planes into single buffer
-        let mut data = Vec::with_capacity(y_data.len() + u_data.len() + v_data.len());
-        data.extend_from_slice(&y_data);
-        data.extend_from_slice(&u_data);
-        data.extend_from_slice(&v_data);
-
-        self.emit(ToxAvEvent::VideoFrame {
-            friend_number,
-            width,
-            height,
-            data,
-        });
+        self.video_frames.publish_yuv(VideoSource::Friend(friend_number), &frame);
     }
 
     fn on_audio_bit_rate(&self, friend_number: u32, audio_bit_rate: u32) {
@@ -443,3 +689,60 @@ impl ToxAvEventHandler for TauriAvEventHandler {
         );
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flags(sending: bool) -> CallStateFlags {
+        CallStateFlags {
+            error: false,
+            finished: false,
+            sending_audio: sending,
+            sending_video: false,
+            accepting_audio: true,
+            accepting_video: false,
+        }
+    }
+
+    #[test]
+    fn test_stalled_calls() {
+        let mut mgr = AvManager::new();
+        mgr.start_call(1, false);
+        mgr.update_call_state(1, flags(true));
+        mgr.start_call(2, false);
+        mgr.update_call_state(2, flags(false));
+
+        let now = Instant::now();
+        assert!(mgr.stalled_calls(now, |_| true).is_empty());
+
+        // Only the peer that claims to be sending counts as silent
+        let later = now + CALL_MEDIA_TIMEOUT;
+        assert_eq!(mgr.stalled_calls(later, |_| true), vec![(1, StallReason::NoMedia)]);
+
+        // Going offline is timed from when it's first seen
+        mgr.end_call(1);
+        assert!(mgr.stalled_calls(later, |_| false).is_empty());
+        let stalled = mgr.stalled_calls(later + CALL_OFFLINE_TIMEOUT, |_| false);
+        assert_eq!(stalled, vec![(2, StallReason::PeerOffline)]);
+    }
+
+    #[test]
+    fn test_finished_call_time() {
+        let mut mgr = AvManager::new();
+        // Calls that never connected don't count
+        mgr.start_call(1, false);
+        mgr.end_call(1);
+        assert!(mgr.take_finished_call_time().is_empty());
+
+        mgr.start_call(2, false);
+        mgr.update_call_state(2, flags(true));
+        mgr.update_call_state(2, CallStateFlags { finished: true, ..flags(false) });
+        // Hanging up after the peer did isn't counted twice
+        mgr.end_call(2);
+        let finished = mgr.take_finished_call_time();
+        assert_eq!(finished.len(), 1);
+        assert_eq!(finished[0].0, 2);
+        assert!(mgr.take_finished_call_time().is_empty());
+    }
+}
@@ -0,0 +1,318 @@
+//! Bot API
+//!
+//! An optional local WebSocket server for bots and bridges. Clients connect to
+//! `ws://127.0.0.1:<port>/?token=<token>` (or send the token as an
+//! `Authorization: Bearer` header) and speak JSON-RPC 2.0, one object per text
+//! frame:
+//!
+//! - every `tox://event` is pushed as an `event` notification, with the same
+//!   `{ "type", "data" }` params the UI receives
+//! - requests call the same commands as the UI (see [`dispatch`])
+//!
+//! Off by default. The server only listens on loopback, but any local process
+//! holding the token can read and send messages as the logged-in profile.
+
+use std::net::Ipv4Addr;
+use std::sync::Arc;
+
+use futures_util::{SinkExt, StreamExt};
+use serde::Deserialize;
+use serde_json::Value;
+use tauri::{AppHandle, EventId, Listener, Manager};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{broadcast, watch};
+use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tokio_tungstenite::tungstenite::http::StatusCode;
+use tokio_tungstenite::tungstenite::Message;
+use toxcord_core::rpc::{self, Notification, RpcError, METHOD_NOT_FOUND};
+use tracing::{debug, info, warn};
+
+use crate::commands;
+use crate::error::{CommandResult, ToxcordError};
+use crate::AppState;
+
+pub const DEFAULT_PORT: u16 = 33450;
+
+/// Events buffered per client before a slow client starts missing them
+const EVENT_BUFFER: usize = 256;
+
+/// Handle to the running server. Dropping it closes the port and disconnects
+/// every client.
+pub struct BotApiServer {
+    app_handle: AppHandle,
+    port: u16,
+    listener_id: EventId,
+    shutdown: watch::Sender<bool>,
+    accept_task: Option<tauri::async_runtime::JoinHandle<()>>,
+}
+
+impl BotApiServer {
+    /// Bind `127.0.0.1:port` and start accepting clients that present `token`
+    pub async fn start(app_handle: AppHandle, port: u16, token: String) -> Result<Self, String> {
+        if token.is_empty() {
+            return Err("Bot API token is empty".to_string());
+        }
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))
+            .await
+            .map_err(|e| format!("Failed to bind bot API port {port}: {e}"))?;
+
+        let (events_tx, _) = broadcast::channel::<String>(EVENT_BUFFER);
+        let forward = events_tx.clone();
+        let listener_id = app_handle.listen_any("tox://event", move |event| {
+            // Fails only when no client is connected
+            let _ = forward.send(event.payload().to_string());
+        });
+
+        let (shutdown, shutdown_rx) = watch::channel(false);
+        let accept_task = tauri::async_runtime::spawn(accept_clients(
+            listener,
+            app_handle.clone(),
+            Arc::new(token),
+            events_tx,
+            shutdown_rx,
+        ));
+
+        info!("Bot API listening on 127.0.0.1:{port}");
+        Ok(Self {
+            app_handle,
+            port,
+            listener_id,
+            shutdown,
+            accept_task: Some(accept_task),
+        })
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// Stop and wait until the port is released
+    pub async fn stop(mut self) {
+        let _ = self.shutdown.send(true);
+        if let Some(task) = self.accept_task.take() {
+            let _ = task.await;
+        }
+    }
+}
+
+impl Drop for BotApiServer {
+    fn drop(&mut self) {
+        self.app_handle.unlisten(self.listener_id);
+        let _ = self.shutdown.send(true);
+        info!("Bot API stopped");
+    }
+}
+
+/// A new random token for the bot API
+pub fn generate_token() -> String {
+    format!("{}{}", uuid::Uuid::new_v4().simple(), uuid::Uuid::new_v4().simple())
+}
+
+async fn accept_clients(
+    listener: TcpListener,
+    app_handle: AppHandle,
+    token: Arc<String>,
+    events: broadcast::Sender<String>,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    loop {
+        tokio::select! {
+            _ = shutdown.changed() => break,
+            accepted = listener.accept() => match accepted {
+                Ok((stream, addr)) => {
+                    debug!("Bot API connection from {addr}");
+                    let app_handle = app_handle.clone();
+                    let token = token.clone();
+                    let events = events.subscribe();
+                    let shutdown = shutdown.clone();
+                    tauri::async_runtime::spawn(async move {
+                        if let Err(e) = handle_client(stream, app_handle, token, events, shutdown).await {
+                            debug!("Bot API client {addr}: {e}");
+                        }
+                    });
+                }
+                Err(e) => warn!("Bot API accept failed: {e}"),
+            },
+        }
+    }
+}
+
+async fn handle_client(
+    stream: TcpStream,
+    app_handle: AppHandle,
+    token: Arc<String>,
+    mut events: broadcast::Receiver<String>,
+    mut shutdown: watch::Receiver<bool>,
+) -> Result<(), String> {
+    let authorize = |request: &Request, response: Response| -> Result<Response, ErrorResponse> {
+        if request_token(request).is_some_and(|t| tokens_match(t, &token)) {
+            Ok(response)
+        } else {
+            let mut error = ErrorResponse::new(Some("Invalid or missing token".to_string()));
+            *error.status_mut() = StatusCode::UNAUTHORIZED;
+            Err(error)
+        }
+    };
+    let socket = tokio_tungstenite::accept_hdr_async(stream, authorize)
+        .await
+        .map_err(|e| format!("Handshake failed: {e}"))?;
+    let (mut sink, mut source) = socket.split();
+
+    loop {
+        let reply = tokio::select! {
+            _ = shutdown.changed() => break,
+            event = events.recv() => match event {
+                Ok(payload) => match serde_json::from_str(&payload) {
+                    Ok(params) => serde_json::to_string(&Notification::event(params)).ok(),
+                    Err(_) => None,
+                },
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    warn!("Bot API client fell behind, dropped {missed} events");
+                    None
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+            message = source.next() => match message {
+                Some(Ok(Message::Text(text))) => handle_request(&app_handle, text.as_str()).await,
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => None,
+            },
+        };
+        if let Some(reply) = reply {
+            sink.send(Message::text(reply))
+                .await
+                .map_err(|e| format!("Failed to send: {e}"))?;
+        }
+    }
+
+    let _ = sink.close().await;
+    Ok(())
+}
+
+/// Token from the `token` query parameter or a bearer `Authorization` header
+fn request_token(request: &Request) -> Option<&str> {
+    let from_query = request
+        .uri()
+        .query()
+        .and_then(|query| query.split('&').find_map(|pair| pair.strip_prefix("token=")));
+    let from_header = request
+        .headers()
+        .get("authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    from_query.or(from_header)
+}
+
+/// Compare without exiting at the first differing byte
+fn tokens_match(given: &str, expected: &str) -> bool {
+    given.len() == expected.len()
+        && given
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// Run one request and serialize its response. Notifications get no response.
+async fn handle_request(app_handle: &AppHandle, text: &str) -> Option<String> {
+    let response = match rpc::parse_request(text) {
+        Ok(request) => {
+            let result = dispatch(app_handle, &request).await;
+            rpc::Response::new(request.id?, result)
+        }
+        Err(response) => response,
+    };
+    serde_json::to_string(&response).ok()
+}
+
+#[derive(Deserialize)]
+struct DirectMessageParams {
+    friend_number: u32,
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct DirectHistoryParams {
+    friend_number: u32,
+    limit: Option<i64>,
+    before_timestamp: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct GuildParams {
+    guild_id: String,
+}
+
+#[derive(Deserialize)]
+struct ChannelMessageParams {
+    guild_id: String,
+    channel_id: String,
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct ChannelHistoryParams {
+    channel_id: String,
+    limit: Option<i64>,
+    before_timestamp: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct StatusMessageParams {
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct StatusParams {
+    status: String,
+}
+
+/// Call the command behind `request.method`
+async fn dispatch(app_handle: &AppHandle, request: &rpc::Request) -> Result<Value, RpcError> {
+    let state = app_handle.state::<AppState>();
+    let result = match request.method.as_str() {
+        "get_profile_info" => commands::auth::get_profile_info(state).await,
+        "get_friends" => commands::friends::get_friends(state).await,
+        "send_direct_message" => {
+            let p: DirectMessageParams = rpc::params(request)?;
+            commands::messaging::send_direct_message(state, p.friend_number, p.message).await
+        }
+        "get_direct_messages" => {
+            let p: DirectHistoryParams = rpc::params(request)?;
+            commands::messaging::get_direct_messages(state, p.friend_number, p.limit, p.before_timestamp)
+                .await
+                .and_then(to_value)
+        }
+        "get_guilds" => commands::guilds::get_guilds(state).await.and_then(to_value),
+        "get_guild_channels" => {
+            let p: GuildParams = rpc::params(request)?;
+            commands::guilds::get_guild_channels(p.guild_id, state).await.and_then(to_value)
+        }
+        "send_channel_message" => {
+            let p: ChannelMessageParams = rpc::params(request)?;
+            commands::guilds::send_channel_message(p.guild_id, p.channel_id, p.message, state)
+                .await
+                .and_then(to_value)
+        }
+        "get_channel_messages" => {
+            let p: ChannelHistoryParams = rpc::params(request)?;
+            commands::guilds::get_channel_messages(p.channel_id, p.limit, p.before_timestamp, state)
+                .await
+                .and_then(to_value)
+        }
+        "set_status_message" => {
+            let p: StatusMessageParams = rpc::params(request)?;
+            commands::auth::set_status_message(state, p.message).await.and_then(to_value)
+        }
+        "set_user_status" => {
+            let p: StatusParams = rpc::params(request)?;
+            commands::auth::set_user_status(state, p.status).await.and_then(to_value)
+        }
+        other => return Err(RpcError::new(METHOD_NOT_FOUND, format!("Unknown method: {other}"))),
+    };
+    result.map_err(RpcError::internal)
+}
+
+fn to_value<T: serde::Serialize>(value: T) -> CommandResult<Value> {
+    serde_json::to_value(value).map_err(|e| ToxcordError::Other(format!("Failed to serialize result: {e}")))
+}
@@ -0,0 +1,485 @@
+//! Channel bridges
+//!
+//! Relays a guild channel to an IRC channel or Matrix room, both ways. Each
+//! enabled bridge runs as its own task and reconnects after failures:
+//!
+//! - Tox -> external: `GroupMessage` events for the channel (seen through a
+//!   `tox://event` listener) and our own sends, passed to [`BridgeManager::relay`]
+//! - external -> Tox: sent to the channel as our own message with a `<name>`
+//!   prefix, and announced to the UI as `ToxEvent::BridgedMessage`
+//!
+//! Our own sends never come back as `GroupMessage`, so relayed messages
+//! don't loop.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::Duration;
+
+use serde_json::{json, Value};
+use tauri::{AppHandle, Emitter, EventId, Listener};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::sync::{mpsc, watch, Mutex};
+use toxcord_protocol::bridge::{
+    irc_privmsg_lines, irc_relay_text, matrix_localpart, relay_text, BridgeProtocol, IrcMessage,
+};
+use tracing::{error, info, warn};
+
+use super::guild_manager::GuildManager;
+use super::tox_manager::{ToxEvent, ToxManager};
+use crate::db::message_store::ChannelBridgeRecord;
+use crate::db::MessageStore;
+
+/// Wait before reconnecting a failed bridge
+const RECONNECT_DELAY: Duration = Duration::from_secs(30);
+
+/// Matrix long-poll timeout
+const SYNC_TIMEOUT_MS: u64 = 30_000;
+
+struct RunningBridge {
+    /// Text to relay to the external side
+    outgoing: mpsc::UnboundedSender<String>,
+    shutdown: watch::Sender<bool>,
+}
+
+/// Runs the bridges of the logged-in profile. Dropping it stops them all.
+pub struct BridgeManager {
+    app_handle: AppHandle,
+    store: Arc<MessageStore>,
+    tox: Arc<Mutex<ToxManager>>,
+    /// Running bridges by channel ID
+    bridges: Arc<StdMutex<HashMap<String, RunningBridge>>>,
+    listener_id: EventId,
+}
+
+impl BridgeManager {
+    /// Start every enabled bridge in the store
+    pub fn start(app_handle: AppHandle, store: Arc<MessageStore>, tox: Arc<Mutex<ToxManager>>) -> Self {
+        let bridges: Arc<StdMutex<HashMap<String, RunningBridge>>> = Arc::new(StdMutex::new(HashMap::new()));
+        let routes = bridges.clone();
+        let listener_id = app_handle.listen_any("tox://event", move |event| {
+            let Some((channel_id, text)) = received_message(event.payload()) else {
+                return;
+            };
+            if let Some(bridge) = routes.lock().ok().as_ref().and_then(|b| b.get(&channel_id)) {
+                let _ = bridge.outgoing.send(text);
+            }
+        });
+
+        let manager = Self {
+            app_handle,
+            store,
+            tox,
+            bridges,
+            listener_id,
+        };
+        match manager.store.get_channel_bridges() {
+            Ok(bridges) => {
+                for bridge in bridges.into_iter().filter(|b| b.enabled) {
+                    manager.connect(bridge);
+                }
+            }
+            Err(e) => error!("{e}"),
+        }
+        manager
+    }
+
+    /// Start the bridge for a channel, replacing a running one
+    pub fn connect(&self, bridge: ChannelBridgeRecord) {
+        let (outgoing, outgoing_rx) = mpsc::unbounded_channel();
+        let (shutdown, shutdown_rx) = watch::channel(false);
+        let relay = Relay {
+            app_handle: self.app_handle.clone(),
+            store: self.store.clone(),
+            tox: self.tox.clone(),
+            guild_id: bridge.guild_id.clone(),
+            channel_id: bridge.channel_id.clone(),
+        };
+        let channel_id = bridge.channel_id.clone();
+        tauri::async_runtime::spawn(run_bridge(bridge, relay, outgoing_rx, shutdown_rx));
+
+        if let Ok(mut bridges) = self.bridges.lock() {
+            if let Some(old) = bridges.insert(channel_id, RunningBridge { outgoing, shutdown }) {
+                let _ = old.shutdown.send(true);
+            }
+        }
+    }
+
+    /// Stop the bridge for a channel, if running
+    pub fn disconnect(&self, channel_id: &str) {
+        if let Some(bridge) = self.bridges.lock().ok().and_then(|mut b| b.remove(channel_id)) {
+            let _ = bridge.shutdown.send(true);
+        }
+    }
+
+    pub fn is_running(&self, channel_id: &str) -> bool {
+        self.bridges.lock().is_ok_and(|b| b.contains_key(channel_id))
+    }
+
+    /// Relay a message we sent in a channel
+    pub fn relay(&self, channel_id: &str, sender_name: &str, content: &str) {
+        if let Some(bridge) = self.bridges.lock().ok().as_ref().and_then(|b| b.get(channel_id)) {
+            let _ = bridge.outgoing.send(relay_text(sender_name, content));
+        }
+    }
+}
+
+impl Drop for BridgeManager {
+    fn drop(&mut self) {
+        self.app_handle.unlisten(self.listener_id);
+        if let Ok(mut bridges) = self.bridges.lock() {
+            for (_, bridge) in bridges.drain() {
+                let _ = bridge.shutdown.send(true);
+            }
+        }
+    }
+}
+
+/// Channel and relay text of a received channel message event
+fn received_message(payload: &str) -> Option<(String, String)> {
+    let event: Value = serde_json::from_str(payload).ok()?;
+    if event["type"] != "GroupMessage" {
+        return None;
+    }
+    let data = &event["data"];
+    let channel_id = data["channel_id"].as_str()?.to_string();
+    let sender = data["sender_name"].as_str().unwrap_or("unknown");
+    let message = data["message"].as_str()?;
+    let text = match data["message_type"].as_str() {
+        Some("normal") => relay_text(sender, message),
+        Some("action") => format!("* {sender} {message}"),
+        // File offers carry the file name
+        _ => relay_text(sender, &format!("shared a file: {message}")),
+    };
+    Some((channel_id, text))
+}
+
+/// Posts messages from the external side into the Tox channel
+struct Relay {
+    app_handle: AppHandle,
+    store: Arc<MessageStore>,
+    tox: Arc<Mutex<ToxManager>>,
+    guild_id: String,
+    channel_id: String,
+}
+
+impl Relay {
+    async fn post(&self, content: String) {
+        let gm = GuildManager::new(self.store.clone());
+        match gm.send_channel_message(&self.guild_id, &self.channel_id, &content, &self.tox).await {
+            Ok(record) => {
+                if let Err(e) = self.app_handle.emit("tox://event", &ToxEvent::BridgedMessage {
+                    channel_id: record.channel_id,
+                    id: record.id,
+                    sender_name: record.sender_name,
+                    content: record.content,
+                    timestamp: record.timestamp,
+                }) {
+                    error!("Failed to emit bridged message: {e}");
+                }
+            }
+            Err(e) => warn!("Failed to relay bridged message to channel {}: {e}", self.channel_id),
+        }
+    }
+}
+
+async fn run_bridge(
+    bridge: ChannelBridgeRecord,
+    relay: Relay,
+    mut outgoing: mpsc::UnboundedReceiver<String>,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    let Some(protocol) = BridgeProtocol::parse(&bridge.protocol) else {
+        error!("Unknown bridge protocol '{}' for channel {}", bridge.protocol, bridge.channel_id);
+        return;
+    };
+    info!("Starting {} bridge for channel {} to {}", protocol.as_str(), bridge.channel_id, bridge.target);
+
+    loop {
+        let result = match protocol {
+            BridgeProtocol::Irc => run_irc(&bridge, &relay, &mut outgoing, &mut shutdown).await,
+            BridgeProtocol::Matrix => run_matrix(&bridge, &relay, &mut outgoing, &mut shutdown).await,
+        };
+        if *shutdown.borrow() {
+            break;
+        }
+        match result {
+            Ok(()) => break,
+            Err(e) => warn!("Bridge for channel {} failed: {e} - reconnecting", bridge.channel_id),
+        }
+        tokio::select! {
+            _ = shutdown.changed() => break,
+            _ = tokio::time::sleep(RECONNECT_DELAY) => {}
+        }
+    }
+
+    info!("Stopped bridge for channel {}", bridge.channel_id);
+}
+
+async fn run_irc(
+    bridge: &ChannelBridgeRecord,
+    relay: &Relay,
+    outgoing: &mut mpsc::UnboundedReceiver<String>,
+    shutdown: &mut watch::Receiver<bool>,
+) -> Result<(), String> {
+    let default_port = if bridge.use_tls { 6697 } else { 6667 };
+    let (host, port) = match bridge.server.rsplit_once(':') {
+        Some((host, port)) => (
+            host,
+            port.parse()
+                .map_err(|_| format!("Invalid IRC port: {port}"))?,
+        ),
+        None => (bridge.server.as_str(), default_port),
+    };
+    let tcp = TcpStream::connect((host, port))
+        .await
+        .map_err(|e| format!("Failed to connect to {host}:{port}: {e}"))?;
+
+    if bridge.use_tls {
+        let mut roots = tokio_rustls::rustls::RootCertStore::empty();
+        roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+        let config = tokio_rustls::rustls::ClientConfig::builder()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        let domain = tokio_rustls::rustls::pki_types::ServerName::try_from(host.to_string())
+            .map_err(|e| format!("Invalid IRC host {host}: {e}"))?;
+        let tls = tokio_rustls::TlsConnector::from(Arc::new(config))
+            .connect(domain, tcp)
+            .await
+            .map_err(|e| format!("TLS handshake with {host} failed: {e}"))?;
+        irc_session(tls, bridge, relay, outgoing, shutdown).await
+    } else {
+        irc_session(tcp, bridge, relay, outgoing, shutdown).await
+    }
+}
+
+async fn irc_session<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: S,
+    bridge: &ChannelBridgeRecord,
+    relay: &Relay,
+    outgoing: &mut mpsc::UnboundedReceiver<String>,
+    shutdown: &mut watch::Receiver<bool>,
+) -> Result<(), String> {
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut lines = BufReader::new(reader).lines();
+    let mut nick = bridge.nickname.clone();
+    let mut joined = false;
+
+    send_irc(
+        &mut writer,
+        &format!("NICK {nick}\r\nUSER {nick} 0 * :Toxcord bridge\r\n"),
+    )
+    .await?;
+
+    loop {
+        tokio::select! {
+            _ = shutdown.changed() => {
+                let _ = send_irc(&mut writer, "QUIT :Bridge stopped\r\n").await;
+                return Ok(());
+            }
+            line = lines.next_line() => {
+                let line = line
+                    .map_err(|e| format!("IRC read failed: {e}"))?
+                    .ok_or("IRC server closed the connection")?;
+                let Some(msg) = IrcMessage::parse(&line) else {
+                    continue;
+                };
+                match msg.command.as_str() {
+                    "PING" => {
+                        let token = msg.params.first().map(String::as_str).unwrap_or_default();
+                        send_irc(&mut writer, &format!("PONG :{token}\r\n")).await?;
+                    }
+                    // Registered
+                    "001" => send_irc(&mut writer, &format!("JOIN {}\r\n", bridge.target)).await?,
+                    // Nickname in use
+                    "433" => {
+                        nick.push('_');
+                        send_irc(&mut writer, &format!("NICK {nick}\r\n")).await?;
+                    }
+                    "JOIN" if msg.nick() == Some(nick.as_str()) => joined = true,
+                    "PRIVMSG" => {
+                        let (Some(sender), [target, text]) = (msg.nick(), msg.params.as_slice()) else {
+                            continue;
+                        };
+                        if !target.eq_ignore_ascii_case(&bridge.target) {
+                            continue;
+                        }
+                        if let Some(content) = irc_relay_text(sender, text) {
+                            relay.post(content).await;
+                        }
+                    }
+                    "ERROR" => return Err(format!("IRC error: {}", msg.params.join(" "))),
+                    _ => {}
+                }
+            }
+            text = outgoing.recv(), if joined => {
+                let Some(text) = text else {
+                    return Ok(());
+                };
+                for line in irc_privmsg_lines(&bridge.target, &text) {
+                    send_irc(&mut writer, &line).await?;
+                }
+            }
+        }
+    }
+}
+
+async fn send_irc<W: AsyncWrite + Unpin>(writer: &mut W, data: &str) -> Result<(), String> {
+    writer
+        .write_all(data.as_bytes())
+        .await
+        .map_err(|e| format!("IRC write failed: {e}"))
+}
+
+async fn run_matrix(
+    bridge: &ChannelBridgeRecord,
+    relay: &Relay,
+    outgoing: &mut mpsc::UnboundedReceiver<String>,
+    shutdown: &mut watch::Receiver<bool>,
+) -> Result<(), String> {
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_millis(SYNC_TIMEOUT_MS) + Duration::from_secs(30))
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {e}"))?;
+    let matrix = Matrix {
+        client,
+        homeserver: bridge.server.clone(),
+        access_token: bridge.access_token.clone(),
+    };
+
+    let whoami = matrix
+        .request(reqwest::Method::GET, &["account", "whoami"], None)
+        .await?;
+    let own_user = whoami["user_id"].as_str().unwrap_or_default().to_string();
+    // Joining is a no-op if already in the room, and resolves aliases to a room ID
+    let joined = matrix
+        .request(
+            reqwest::Method::POST,
+            &["join", &bridge.target],
+            Some(json!({})),
+        )
+        .await?;
+    let room_id = joined["room_id"]
+        .as_str()
+        .ok_or("Matrix join returned no room ID")?
+        .to_string();
+
+    let filter = json!({
+        "room": { "rooms": [room_id], "timeline": { "limit": 50 } },
+        "presence": { "types": [] },
+        "account_data": { "types": [] },
+    })
+    .to_string();
+    let mut since: Option<String> = None;
+
+    loop {
+        let body = {
+            let sync = matrix.sync(&filter, since.as_deref());
+            tokio::pin!(sync);
+            loop {
+                tokio::select! {
+                    _ = shutdown.changed() => return Ok(()),
+                    text = outgoing.recv() => {
+                        let Some(text) = text else {
+                            return Ok(());
+                        };
+                        let txn_id = uuid::Uuid::new_v4().to_string();
+                        let content = json!({ "msgtype": "m.text", "body": text });
+                        matrix
+                            .request(reqwest::Method::PUT, &["rooms", &room_id, "send", "m.room.message", &txn_id], Some(content))
+                            .await?;
+                    }
+                    body = &mut sync => break body?,
+                }
+            }
+        };
+
+        // The first sync only marks where to start; don't replay room history
+        if since.is_some() {
+            let events = body["rooms"]["join"][&room_id]["timeline"]["events"]
+                .as_array()
+                .cloned()
+                .unwrap_or_default();
+            for event in events {
+                let sender = event["sender"].as_str().unwrap_or_default();
+                if event["type"] != "m.room.message" || sender == own_user {
+                    continue;
+                }
+                let Some(text) = event["content"]["body"].as_str() else {
+                    continue;
+                };
+                let name = matrix_localpart(sender);
+                let content = match event["content"]["msgtype"].as_str() {
+                    Some("m.emote") => format!("* {name} {text}"),
+                    _ => relay_text(name, text),
+                };
+                relay.post(content).await;
+            }
+        }
+        since = body["next_batch"].as_str().map(str::to_string);
+    }
+}
+
+struct Matrix {
+    client: reqwest::Client,
+    homeserver: String,
+    access_token: String,
+}
+
+impl Matrix {
+    /// Call a client-server API endpoint under `/_matrix/client/v3`
+    async fn request(
+        &self,
+        method: reqwest::Method,
+        path: &[&str],
+        body: Option<Value>,
+    ) -> Result<Value, String> {
+        self.send(self.client.request(method, self.url(path)?), body)
+            .await
+    }
+
+    async fn sync(&self, filter: &str, since: Option<&str>) -> Result<Value, String> {
+        let timeout = if since.is_some() { SYNC_TIMEOUT_MS } else { 0 };
+        let mut request = self
+            .client
+            .get(self.url(&["sync"])?)
+            .query(&[("filter", filter), ("timeout", &timeout.to_string())]);
+        if let Some(since) = since {
+            request = request.query(&[("since", since)]);
+        }
+        self.send(request, None).await
+    }
+
+    fn url(&self, path: &[&str]) -> Result<reqwest::Url, String> {
+        let mut url = reqwest::Url::parse(&self.homeserver)
+            .map_err(|e| format!("Invalid homeserver URL: {e}"))?;
+        url.path_segments_mut()
+            .map_err(|_| "Invalid homeserver URL".to_string())?
+            .pop_if_empty()
+            .extend(["_matrix", "client", "v3"])
+            .extend(path);
+        Ok(url)
+    }
+
+    async fn send(
+        &self,
+        request: reqwest::RequestBuilder,
+        body: Option<Value>,
+    ) -> Result<Value, String> {
+        let mut request = request.bearer_auth(&self.access_token);
+        if let Some(body) = body {
+            request = request.json(&body);
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|e| format!("Matrix request failed: {e}"))?;
+        let status = response.status();
+        let body: Value = response.json().await.unwrap_or_default();
+        if !status.is_success() {
+            let error = body["error"].as_str().unwrap_or_else(|| status.as_str());
+            return Err(format!("Matrix request failed: {error}"));
+        }
+        Ok(body)
+    }
+}
@@ -0,0 +1,248 @@
+//! Command palette
+//!
+//! Lists what can be done from the keyboard: opening conversations and
+//! guilds, calling friends, joining guilds we've been invited to, and app-wide
+//! actions like muting. Commands are ranked against what's been typed with a
+//! fuzzy match; the frontend carries out the one picked.
+
+use serde::{Deserialize, Serialize};
+
+use crate::db::MessageStore;
+
+/// Most commands returned for a query
+pub const MAX_RESULTS: usize = 50;
+
+/// What a command does, carried out by the frontend
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PaletteAction {
+    OpenDm { friend_number: u32 },
+    OpenDmGroup { guild_id: String },
+    OpenChannel { guild_id: String, channel_id: String },
+    OpenGuild { guild_id: String },
+    CallFriend { friend_number: u32, video: bool },
+    HangUp { friend_number: u32 },
+    AcceptInvite { invite_id: String },
+    SetMute { muted: bool },
+    SetDeafen { deafened: bool },
+    /// "friends" or "settings"
+    OpenPage { page: String },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PaletteCommand {
+    pub title: String,
+    /// Context shown under the title, also searched
+    pub subtitle: Option<String>,
+    pub action: PaletteAction,
+}
+
+impl PaletteCommand {
+    fn new(title: impl Into<String>, subtitle: Option<String>, action: PaletteAction) -> Self {
+        Self {
+            title: title.into(),
+            subtitle,
+            action,
+        }
+    }
+}
+
+/// Call state the commands depend on
+#[derive(Debug, Default)]
+pub struct CallContext {
+    pub muted: bool,
+    pub deafened: bool,
+    /// Friends we're in a call with, or ringing
+    pub calls: Vec<u32>,
+}
+
+/// Every command available now. Conversations come first, most recently
+/// active first, which is the order shown before anything is typed.
+pub fn build(store: &MessageStore, calls: &CallContext) -> Result<Vec<PaletteCommand>, String> {
+    let mut commands = Vec::new();
+
+    for conversation in store.get_recent_conversations(i64::MAX)? {
+        let command = match conversation.kind.as_str() {
+            "dm" => conversation.friend_number.map(|friend_number| {
+                PaletteCommand::new(
+                    &conversation.name,
+                    Some("Direct message".to_string()),
+                    PaletteAction::OpenDm { friend_number: friend_number as u32 },
+                )
+            }),
+            "dm_group" => conversation.guild_id.map(|guild_id| {
+                PaletteCommand::new(
+                    &conversation.name,
+                    Some("Group DM".to_string()),
+                    PaletteAction::OpenDmGroup { guild_id },
+                )
+            }),
+            "channel" => conversation.guild_id.map(|guild_id| {
+                PaletteCommand::new(
+                    format!("#{}", conversation.name),
+                    conversation.guild_name,
+                    PaletteAction::OpenChannel { guild_id, channel_id: conversation.id },
+                )
+            }),
+            _ => None,
+        };
+        commands.extend(command);
+    }
+
+    for guild in store.get_guilds()?.into_iter().filter(|g| g.guild_type == "server") {
+        commands.push(PaletteCommand::new(
+            format!("Open {}", guild.name),
+            Some("Guild".to_string()),
+            PaletteAction::OpenGuild { guild_id: guild.id },
+        ));
+    }
+
+    for invite in store.get_group_invites()? {
+        commands.push(PaletteCommand::new(
+            format!("Join {}", invite.group_name),
+            Some("Guild invite".to_string()),
+            PaletteAction::AcceptInvite { invite_id: invite.id },
+        ));
+    }
+
+    for friend in store.get_friends()? {
+        let friend_number = friend.friend_number as u32;
+        if calls.calls.contains(&friend_number) {
+            commands.push(PaletteCommand::new(
+                format!("Hang up on {}", friend.name),
+                Some("Call".to_string()),
+                PaletteAction::HangUp { friend_number },
+            ));
+        } else if friend.connection_status != "none" {
+            commands.push(PaletteCommand::new(
+                format!("Call {}", friend.name),
+                Some("Voice call".to_string()),
+                PaletteAction::CallFriend { friend_number, video: false },
+            ));
+            commands.push(PaletteCommand::new(
+                format!("Video call {}", friend.name),
+                Some("Video call".to_string()),
+                PaletteAction::CallFriend { friend_number, video: true },
+            ));
+        }
+    }
+
+    commands.push(PaletteCommand::new(
+        if calls.muted { "Unmute microphone" } else { "Mute microphone" },
+        None,
+        PaletteAction::SetMute { muted: !calls.muted },
+    ));
+    commands.push(PaletteCommand::new(
+        if calls.deafened { "Undeafen" } else { "Deafen" },
+        None,
+        PaletteAction::SetDeafen { deafened: !calls.deafened },
+    ));
+    commands.push(PaletteCommand::new(
+        "Friends",
+        None,
+        PaletteAction::OpenPage { page: "friends".to_string() },
+    ));
+    commands.push(PaletteCommand::new(
+        "Settings",
+        None,
+        PaletteAction::OpenPage { page: "settings".to_string() },
+    ));
+
+    Ok(commands)
+}
+
+/// The commands matching `query`, best first. Ties keep their order, so an
+/// empty query lists conversations by activity.
+pub fn search(commands: Vec<PaletteCommand>, query: &str, limit: usize) -> Vec<PaletteCommand> {
+    let mut scored: Vec<(i64, PaletteCommand)> = commands
+        .into_iter()
+        .filter_map(|command| {
+            let title = fuzzy_score(query, &command.title);
+            // Matching the context alone counts for less
+            let subtitle = command
+                .subtitle
+                .as_deref()
+                .and_then(|s| fuzzy_score(query, &format!("{} {s}", command.title)))
+                .map(|score| score - 10);
+            title.max(subtitle).map(|score| (score, command))
+        })
+        .collect();
+    scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+    scored.into_iter().take(limit).map(|(_, command)| command).collect()
+}
+
+/// How well `query` matches `text`, or None if its characters don't all
+/// appear in order. Case-insensitive; matches at the start of words and
+/// runs of matching characters score higher, gaps lower.
+pub fn fuzzy_score(query: &str, text: &str) -> Option<i64> {
+    let query: Vec<char> = query.to_lowercase().chars().filter(|c| !c.is_whitespace()).collect();
+    if query.is_empty() {
+        return Some(0);
+    }
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+
+    let mut score = 0;
+    let mut matched = 0;
+    let mut previous: Option<usize> = None;
+    for (i, &c) in text.iter().enumerate() {
+        if matched == query.len() {
+            break;
+        }
+        if c != query[matched] {
+            continue;
+        }
+        score += 1;
+        if i == 0 || !text[i - 1].is_alphanumeric() {
+            score += 8;
+        }
+        match previous {
+            Some(p) if p + 1 == i => score += 5,
+            Some(p) => score -= ((i - p - 1) as i64).min(3),
+            None => score -= (i as i64).min(3),
+        }
+        previous = Some(i);
+        matched += 1;
+    }
+    (matched == query.len()).then_some(score)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fuzzy_score() {
+        assert_eq!(fuzzy_score("", "anything"), Some(0));
+        assert_eq!(fuzzy_score("xyz", "Alice"), None);
+        assert_eq!(fuzzy_score("eca", "Alice"), None);
+        assert!(fuzzy_score("ALI", "alice").is_some());
+
+        // Prefixes beat scattered matches, and word starts beat the middle of words
+        assert!(fuzzy_score("gen", "#general") > fuzzy_score("gen", "#gardening"));
+        assert!(fuzzy_score("cb", "Call Bob") > fuzzy_score("cb", "Jacob"));
+        assert!(fuzzy_score("bob", "Bob") > fuzzy_score("bob", "Call Bob"));
+    }
+
+    #[test]
+    fn test_search() {
+        let open = |name: &str, n: u32| {
+            PaletteCommand::new(name, Some("Direct message".to_string()), PaletteAction::OpenDm { friend_number: n })
+        };
+        let commands = vec![
+            open("Alice", 0),
+            open("Bob", 1),
+            PaletteCommand::new("Call Bob", None, PaletteAction::CallFriend { friend_number: 1, video: false }),
+        ];
+
+        // Nothing typed keeps the given order
+        let all = search(commands.clone(), "", MAX_RESULTS);
+        assert_eq!(all.iter().map(|c| c.title.as_str()).collect::<Vec<_>>(), ["Alice", "Bob", "Call Bob"]);
+
+        let bob = search(commands.clone(), "bob", MAX_RESULTS);
+        assert_eq!(bob.iter().map(|c| c.title.as_str()).collect::<Vec<_>>(), ["Bob", "Call Bob"]);
+
+        // The subtitle is searched too
+        assert_eq!(search(commands.clone(), "alice direct", MAX_RESULTS).len(), 1);
+        assert_eq!(search(commands, "", 1).len(), 1);
+    }
+}
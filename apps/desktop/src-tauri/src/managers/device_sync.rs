@@ -0,0 +1,304 @@
+//! Multi-device sync
+//!
+//! Two Toxcord instances of the same user become friends, and each side
+//! links the other as its own device. Whenever a linked device comes online
+//! both sides send a `Hello`; the answer is our friend list, our guilds'
+//! chat IDs and the direct messages the other device hasn't received yet.
+//!
+//! Friends learned from a linked device are added without a request, so the
+//! connection only comes up once that friend accepts this device too.
+//!
+//! Runs on the tox thread: callbacks queue a `DeviceSyncAction` and the main
+//! loop calls [`handle`].
+
+use tauri::{AppHandle, Emitter};
+use toxcord_protocol::device_sync::{self, SyncDirectMessage, SyncFriend, SyncGuild, SyncMessage};
+use toxcord_protocol::fingerprint::public_key_from_hex;
+use toxcord_tox::ToxInstance;
+use tracing::{debug, error, info, warn};
+
+use super::tox_manager::ToxEvent;
+use crate::db::message_store::DirectMessageRecord;
+use crate::db::MessageStore;
+
+/// Most direct messages sent per sync; the rest follow on the next one
+const HISTORY_SYNC_LIMIT: i64 = 500;
+
+pub enum DeviceSyncAction {
+    /// A friend came online
+    Connected(u32),
+    /// A sync packet arrived from a friend
+    Received(u32, SyncMessage),
+}
+
+/// Process one queued action. Returns true if friends or groups were added
+/// and the profile needs saving.
+pub fn handle(tox: &ToxInstance, store: &MessageStore, app_handle: &AppHandle, action: DeviceSyncAction) -> bool {
+    let friend_number = match &action {
+        DeviceSyncAction::Connected(n) | DeviceSyncAction::Received(n, _) => *n,
+    };
+    let device = match store.get_linked_device(friend_number) {
+        Ok(Some(device)) => device,
+        Ok(None) => return false,
+        Err(e) => {
+            error!("{e}");
+            return false;
+        }
+    };
+
+    match action {
+        DeviceSyncAction::Connected(_) => {
+            send_hello(tox, friend_number, device.received_until);
+            false
+        }
+        DeviceSyncAction::Received(_, SyncMessage::Hello { device_name, since }) => {
+            if let Err(e) = store.set_device_name(friend_number, &device_name) {
+                error!("{e}");
+            }
+            send_state(tox, store, friend_number, since.as_deref());
+            false
+        }
+        DeviceSyncAction::Received(_, SyncMessage::Friends { friends }) => {
+            let added = apply_friends(tox, store, &friends);
+            finish_sync(store, app_handle, friend_number, added, 0, 0, None);
+            added > 0
+        }
+        DeviceSyncAction::Received(_, SyncMessage::Guilds { guilds }) => {
+            let joined = apply_guilds(tox, store, &guilds);
+            finish_sync(store, app_handle, friend_number, 0, joined, 0, None);
+            joined > 0
+        }
+        DeviceSyncAction::Received(_, SyncMessage::History { messages }) => {
+            let added = apply_history(store, &messages);
+            let newest = messages.iter().map(|m| m.timestamp.as_str()).max();
+            finish_sync(store, app_handle, friend_number, 0, 0, added, newest);
+            false
+        }
+    }
+}
+
+/// Send a Hello to a linked device if it is online
+pub fn send_hello(tox: &ToxInstance, friend_number: u32, received_until: Option<String>) {
+    let hello = SyncMessage::Hello {
+        device_name: tox.self_name(),
+        since: received_until,
+    };
+    send_packets(tox, friend_number, vec![hello.to_bytes()]);
+}
+
+fn send_state(tox: &ToxInstance, store: &MessageStore, friend_number: u32, since: Option<&str>) {
+    let self_pk = tox.self_public_key().0;
+    let linked: Vec<String> = store
+        .get_linked_devices()
+        .map(|d| d.into_iter().map(|d| d.public_key).collect())
+        .unwrap_or_default();
+
+    let friends: Vec<SyncFriend> = match store.get_friends() {
+        Ok(friends) => friends
+            .into_iter()
+            .filter(|f| !f.public_key.is_empty() && !linked.iter().any(|pk| pk.eq_ignore_ascii_case(&f.public_key)))
+            .map(|f| SyncFriend {
+                public_key: f.public_key,
+                name: f.name,
+            })
+            .collect(),
+        Err(e) => {
+            error!("{e}");
+            Vec::new()
+        }
+    };
+
+    let guilds: Vec<SyncGuild> = tox
+        .group_list()
+        .into_iter()
+        .filter_map(|group_number| {
+            let chat_id = tox.group_get_chat_id(group_number).ok()?;
+            let guild = store.get_guild_by_group_number(group_number as i64).ok()??;
+            Some(SyncGuild {
+                chat_id: hex_upper(&chat_id),
+                name: guild.name,
+                guild_type: guild.guild_type,
+            })
+        })
+        .collect();
+
+    let messages: Vec<SyncDirectMessage> = match store.get_direct_messages_since(since, HISTORY_SYNC_LIMIT) {
+        Ok(messages) => messages
+            .into_iter()
+            .filter(|(pk, _)| !pk.eq_ignore_ascii_case(&self_pk))
+            .map(|(peer_public_key, m)| SyncDirectMessage {
+                id: m.id,
+                peer_public_key,
+                content: m.content,
+                message_type: m.message_type,
+                timestamp: m.timestamp,
+                is_outgoing: m.is_outgoing,
+            })
+            .collect(),
+        Err(e) => {
+            error!("{e}");
+            Vec::new()
+        }
+    };
+
+    debug!(
+        "Syncing {} friends, {} guilds, {} messages to device {friend_number}",
+        friends.len(),
+        guilds.len(),
+        messages.len()
+    );
+    let mut packets = device_sync::pack(&friends, |friends| SyncMessage::Friends { friends });
+    packets.extend(device_sync::pack(&guilds, |guilds| SyncMessage::Guilds { guilds }));
+    packets.extend(device_sync::pack(&messages, |messages| SyncMessage::History { messages }));
+    send_packets(tox, friend_number, packets);
+}
+
+fn send_packets(tox: &ToxInstance, friend_number: u32, packets: Vec<Vec<u8>>) {
+    for packet in packets {
+        if let Err(e) = tox.friend_send_lossless_packet(friend_number, &packet) {
+            warn!("Failed to send sync packet to device {friend_number}: {e}");
+            return;
+        }
+    }
+}
+
+fn apply_friends(tox: &ToxInstance, store: &MessageStore, friends: &[SyncFriend]) -> usize {
+    let self_pk = tox.self_public_key().0;
+    let mut added = 0;
+    for friend in friends {
+        if friend.public_key.eq_ignore_ascii_case(&self_pk) {
+            continue;
+        }
+        match store.get_friend_number_by_public_key(&friend.public_key) {
+            Ok(None) => {}
+            Ok(Some(_)) => continue,
+            Err(e) => {
+                error!("{e}");
+                continue;
+            }
+        }
+        let Some(pk) = public_key_from_hex(&friend.public_key) else {
+            continue;
+        };
+        match tox.friend_add_norequest(&pk) {
+            Ok(friend_number) => {
+                if let Err(e) = store.upsert_friend(friend_number, &friend.public_key.to_uppercase(), &friend.name, "") {
+                    error!("Failed to persist synced friend: {e}");
+                }
+                added += 1;
+            }
+            Err(e) => warn!("Failed to add synced friend {}: {e}", friend.public_key),
+        }
+    }
+    added
+}
+
+fn apply_guilds(tox: &ToxInstance, store: &MessageStore, guilds: &[SyncGuild]) -> usize {
+    let known: Vec<[u8; 32]> = tox
+        .group_list()
+        .into_iter()
+        .filter_map(|g| tox.group_get_chat_id(g).ok())
+        .collect();
+    let self_name = tox.self_name();
+
+    let mut joined = 0;
+    for guild in guilds {
+        let Some(chat_id) = public_key_from_hex(&guild.chat_id) else {
+            continue;
+        };
+        if known.contains(&chat_id) {
+            continue;
+        }
+        let group_number = match tox.group_join(&chat_id, &self_name, "") {
+            Ok(n) => n,
+            Err(e) => {
+                warn!("Failed to join synced guild '{}': {e}", guild.name);
+                continue;
+            }
+        };
+
+        let guild_type = if guild.guild_type == "dm_group" { "dm_group" } else { "server" };
+        let guild_id = uuid::Uuid::new_v4().to_string();
+        if let Err(e) = store.insert_guild(&guild_id, &guild.name, Some(group_number as i64), "", guild_type) {
+            error!("Failed to persist synced guild: {e}");
+            continue;
+        }
+        if let Err(e) = store.set_guild_chat_id(&guild_id, &guild.chat_id) {
+            error!("{e}");
+        }
+        let channel_name = if guild_type == "dm_group" { "messages" } else { "general" };
+        let channel_id = uuid::Uuid::new_v4().to_string();
+        if let Err(e) = store.insert_channel(&channel_id, &guild_id, channel_name, "text", 0) {
+            error!("Failed to create default channel: {e}");
+        }
+        joined += 1;
+    }
+    joined
+}
+
+fn apply_history(store: &MessageStore, messages: &[SyncDirectMessage]) -> usize {
+    let mut added = 0;
+    for message in messages {
+        let friend_number = match store.get_friend_number_by_public_key(&message.peer_public_key) {
+            Ok(Some(n)) => n,
+            Ok(None) => continue,
+            Err(e) => {
+                error!("{e}");
+                continue;
+            }
+        };
+        let record = DirectMessageRecord {
+            id: message.id.clone(),
+            friend_number: friend_number as i64,
+            sender: if message.is_outgoing { "self" } else { "friend" }.to_string(),
+            content: message.content.clone(),
+            message_type: message.message_type.clone(),
+            timestamp: message.timestamp.clone(),
+            is_outgoing: message.is_outgoing,
+            delivered: true,
+            // Read on the device it arrived on
+            read: true,
+            // Follows this device's timer for the conversation
+            expires_at: store.disappearing_expiry(friend_number).unwrap_or_else(|e| {
+                error!("{e}");
+                None
+            }),
+        };
+        match store.insert_direct_message_if_missing(&record) {
+            Ok(true) => added += 1,
+            Ok(false) => {}
+            Err(e) => error!("Failed to persist synced message: {e}"),
+        }
+    }
+    added
+}
+
+fn finish_sync(
+    store: &MessageStore,
+    app_handle: &AppHandle,
+    friend_number: u32,
+    friends_added: usize,
+    guilds_joined: usize,
+    messages_added: usize,
+    newest_message: Option<&str>,
+) {
+    if let Err(e) = store.record_device_sync(friend_number, newest_message) {
+        error!("{e}");
+    }
+    if friends_added + guilds_joined + messages_added == 0 {
+        return;
+    }
+    info!("Synced from device {friend_number}: {friends_added} friends, {guilds_joined} guilds, {messages_added} messages");
+    if let Err(e) = app_handle.emit("tox://event", &ToxEvent::DeviceSynced {
+        friend_number,
+        friends_added,
+        guilds_joined,
+        messages_added,
+    }) {
+        error!("Failed to emit device sync event: {e}");
+    }
+}
+
+fn hex_upper(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02X}")).collect()
+}
@@ -0,0 +1,255 @@
+//! Event recording and replay
+//!
+//! Recording captures every `tox://event` and `toxav://event` the backend
+//! emits, with the time since recording started, and saves them as a JSON
+//! script in `recordings/` in the data directory:
+//!
+//! ```json
+//! { "description": "...", "events": [
+//!     { "at_ms": 1200, "channel": "tox://event", "payload": { "type": "FriendMessage", "data": { ... } } }
+//! ] }
+//! ```
+//!
+//! Replay emits a script's events to the frontend on the same schedule with
+//! no Tox instance behind them, for working on the UI or giving a demo
+//! without a second machine. Scripts can be written by hand too; payloads are
+//! passed through as they are. Replay is only allowed while logged out, so
+//! the bridges and the bot API never act on made-up events.
+
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, EventId, Listener};
+use tracing::{info, warn};
+
+/// Channels that are recorded and may be replayed
+pub const CHANNELS: [&str; 2] = ["tox://event", "toxav://event"];
+
+/// Emitted when a replay reaches the end of its script
+pub const REPLAY_FINISHED_EVENT: &str = "replay://finished";
+
+/// Wait before the first event, so the page that started a replay has its
+/// listeners attached
+const REPLAY_LEAD_IN: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScriptEvent {
+    /// Milliseconds from the start of the script
+    pub at_ms: u64,
+    pub channel: String,
+    pub payload: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EventScript {
+    #[serde(default)]
+    pub description: String,
+    pub events: Vec<ScriptEvent>,
+}
+
+/// A saved script, as listed to the frontend
+#[derive(Debug, Clone, Serialize)]
+pub struct RecordingInfo {
+    /// File name without the extension
+    pub name: String,
+    pub description: String,
+    pub event_count: usize,
+    pub duration_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReplayStatus {
+    pub recording: bool,
+    /// Events captured by the recording in progress
+    pub recorded_events: usize,
+    pub playing: bool,
+}
+
+struct Recording {
+    listener_ids: Vec<EventId>,
+    events: Arc<Mutex<Vec<ScriptEvent>>>,
+}
+
+/// The recording and replay in progress, if any
+#[derive(Default)]
+pub struct EventReplay {
+    recording: Option<Recording>,
+    playback: Option<tauri::async_runtime::JoinHandle<()>>,
+}
+
+impl EventReplay {
+    pub fn status(&self) -> ReplayStatus {
+        ReplayStatus {
+            recording: self.recording.is_some(),
+            recorded_events: self
+                .recording
+                .as_ref()
+                .map(|r| r.events.lock().unwrap().len())
+                .unwrap_or(0),
+            playing: self.is_playing(),
+        }
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playback.as_ref().is_some_and(|task| !task.inner().is_finished())
+    }
+
+    /// Start capturing emitted events
+    pub fn start_recording(&mut self, app_handle: &AppHandle) -> Result<(), String> {
+        if self.recording.is_some() {
+            return Err("Already recording".to_string());
+        }
+        let started = Instant::now();
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let listener_ids = CHANNELS
+            .iter()
+            .map(|&channel| {
+                let events = events.clone();
+                app_handle.listen_any(channel, move |event| {
+                    let payload = match serde_json::from_str(event.payload()) {
+                        Ok(payload) => payload,
+                        Err(e) => {
+                            warn!("Not recording unparseable {channel} payload: {e}");
+                            return;
+                        }
+                    };
+                    events.lock().unwrap().push(ScriptEvent {
+                        at_ms: started.elapsed().as_millis() as u64,
+                        channel: channel.to_string(),
+                        payload,
+                    });
+                })
+            })
+            .collect();
+        self.recording = Some(Recording { listener_ids, events });
+        info!("Event recording started");
+        Ok(())
+    }
+
+    /// Stop capturing and save what was captured, returning the script's path
+    pub fn stop_recording(&mut self, app_handle: &AppHandle, description: String) -> Result<PathBuf, String> {
+        let recording = self.recording.take().ok_or("Not recording")?;
+        for id in recording.listener_ids {
+            app_handle.unlisten(id);
+        }
+        let events = std::mem::take(&mut *recording.events.lock().unwrap());
+        let name = format!("recording-{}", chrono::Local::now().format("%Y%m%d-%H%M%S"));
+        let path = save(&name, &EventScript { description, events })?;
+        info!("Event recording saved to {}", path.display());
+        Ok(path)
+    }
+
+    /// Emit `script`'s events on its schedule, `speed` times as fast.
+    /// Replaces any replay already running.
+    pub fn play(&mut self, app_handle: AppHandle, mut script: EventScript, speed: f64) -> Result<(), String> {
+        if !(speed.is_finite() && speed > 0.0) {
+            return Err("Replay speed must be above zero".to_string());
+        }
+        self.stop_playback();
+        script.events.sort_by_key(|event| event.at_ms);
+        info!("Replaying {} events at {speed}x", script.events.len());
+
+        self.playback = Some(tauri::async_runtime::spawn(async move {
+            tokio::time::sleep(REPLAY_LEAD_IN).await;
+            let started = tokio::time::Instant::now();
+            for event in script.events {
+                let at = Duration::from_secs_f64(event.at_ms as f64 / 1000.0 / speed);
+                tokio::time::sleep_until(started + at).await;
+                if let Err(e) = app_handle.emit(&event.channel, &event.payload) {
+                    warn!("Failed to replay {} event: {e}", event.channel);
+                }
+            }
+            let _ = app_handle.emit(REPLAY_FINISHED_EVENT, ());
+            info!("Replay finished");
+        }));
+        Ok(())
+    }
+
+    pub fn stop_playback(&mut self) {
+        if let Some(task) = self.playback.take() {
+            task.abort();
+        }
+    }
+}
+
+fn recordings_dir() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("toxcord")
+        .join("recordings")
+}
+
+fn script_path(name: &str) -> Result<PathBuf, String> {
+    // Names come from the frontend; keep them inside the recordings folder
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err("Invalid recording name".to_string());
+    }
+    Ok(recordings_dir().join(format!("{name}.json")))
+}
+
+fn save(name: &str, script: &EventScript) -> Result<PathBuf, String> {
+    std::fs::create_dir_all(recordings_dir()).map_err(|e| format!("Failed to create recordings dir: {e}"))?;
+    let path = script_path(name)?;
+    let json = serde_json::to_string_pretty(script).map_err(|e| format!("Failed to serialize recording: {e}"))?;
+    std::fs::write(&path, json).map_err(|e| format!("Failed to write recording: {e}"))?;
+    Ok(path)
+}
+
+/// Read a saved script
+pub fn load(name: &str) -> Result<EventScript, String> {
+    let data = std::fs::read_to_string(script_path(name)?).map_err(|e| format!("Recording not found: {e}"))?;
+    serde_json::from_str(&data).map_err(|e| format!("Failed to parse recording: {e}"))
+}
+
+/// Saved scripts, newest first
+pub fn list() -> Vec<RecordingInfo> {
+    let Ok(entries) = std::fs::read_dir(recordings_dir()) else {
+        return Vec::new();
+    };
+    let mut recordings: Vec<RecordingInfo> = entries
+        .flatten()
+        .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|entry| {
+            let path = entry.path();
+            let name = path.file_stem()?.to_string_lossy().to_string();
+            let script = load(&name)
+                .inspect_err(|e| warn!("Ignoring recording {}: {e}", path.display()))
+                .ok()?;
+            Some(RecordingInfo {
+                name,
+                description: script.description,
+                event_count: script.events.len(),
+                duration_ms: script.events.iter().map(|e| e.at_ms).max().unwrap_or(0),
+            })
+        })
+        .collect();
+    recordings.sort_by(|a, b| b.name.cmp(&a.name));
+    recordings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_script_path_rejects_traversal() {
+        assert!(script_path("recording-20240101-120000").is_ok());
+        assert!(script_path("demo_call").is_ok());
+        assert!(script_path("../settings").is_err());
+        assert!(script_path("").is_err());
+    }
+
+    #[test]
+    fn test_script_parses_without_description() {
+        let script: EventScript = serde_json::from_str(
+            r#"{ "events": [ { "at_ms": 500, "channel": "tox://event",
+                "payload": { "type": "FriendTyping", "data": { "friend_number": 0, "is_typing": true } } } ] }"#,
+        )
+        .unwrap();
+        assert!(script.description.is_empty());
+        assert_eq!(script.events[0].at_ms, 500);
+        assert_eq!(script.events[0].payload["type"], "FriendTyping");
+    }
+}
@@ -0,0 +1,696 @@
+//! File transfer state.
+//!
+//! Tracks in-flight Tox file transfers. Callbacks write received chunks
+//! through this manager; anything that needs the Tox instance (accepting,
+//! cancelling, sending chunks) is queued as a `FileAction` and executed on
+//! the tox thread after `iterate`.
+//!
+//! Transfers are resumable: progress is checkpointed to the `file_transfers`
+//! table along with the Tox file id. When a friend reconnects, interrupted
+//! outgoing files are re-offered under the same file id and the receiver
+//! seeks to its last checkpoint instead of starting over.
+//!
+//! Files shared in group channels don't use Tox file transfers at all; see
+//! `toxcord_protocol::file_share`. Downloads in progress are tracked here too.
+//!
+//! Thumbnails go in the media cache (`toxcord_core::assets`) and are
+//! regenerated from the attachment if they have been evicted.
+
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use tracing::{info, warn};
+
+use toxcord_protocol::file_share::{ChunkProgress, FileAssembler, FileChunk, FileOffer, FileRequest, HASH_LENGTH};
+use toxcord_protocol::stickers;
+use toxcord_core::assets::{AssetKind, AssetStore};
+use toxcord_core::profile_dirs::{ASSETS_DIR, ATTACHMENTS_DIR};
+use toxcord_tox::types::FILE_ID_LENGTH;
+
+use crate::audio::voice_note;
+use crate::db::message_store::{AttachmentRecord, FileTransferRecord};
+use crate::db::MessageStore;
+
+/// Images and voice notes up to this size are accepted automatically and shown inline
+pub const MAX_INLINE_IMAGE_SIZE: u64 = 8 * 1024 * 1024;
+
+/// Longest edge of generated thumbnails, in pixels
+const THUMBNAIL_SIZE: u32 = 320;
+
+/// How much data is received between progress checkpoints in the database
+const CHECKPOINT_INTERVAL: u64 = 1024 * 1024;
+
+/// Work for the tox thread, queued from callbacks
+#[derive(Debug, Clone)]
+pub enum FileAction {
+    /// Accept an incoming transfer, seeking to `position` first if resuming
+    Accept { friend_number: u32, file_number: u32, position: u64 },
+    Cancel { friend_number: u32, file_number: u32 },
+    /// Answer a chunk request for an outgoing transfer
+    SendChunk { friend_number: u32, file_number: u32, position: u64, length: usize },
+    /// Send a file sharing packet to a group peer
+    GroupPacket { group_number: u32, peer_id: u32, data: Vec<u8> },
+}
+
+/// An incoming transfer being written to disk
+pub struct IncomingTransfer {
+    pub id: String,
+    pub message_id: String,
+    pub filename: String,
+    pub mime_type: String,
+    pub file_size: u64,
+    pub path: PathBuf,
+    pub received: u64,
+    /// `received` as of the last checkpoint written to the database
+    checkpointed: u64,
+    file: File,
+}
+
+/// An outgoing transfer being read from disk
+pub struct OutgoingTransfer {
+    pub id: String,
+    pub message_id: String,
+    pub filename: String,
+    pub file_size: u64,
+    pub path: PathBuf,
+    pub sent: u64,
+    file: File,
+}
+
+/// A file shared in a group channel being downloaded from a peer
+pub struct GroupDownload {
+    pub peer_id: u32,
+    pub message_id: String,
+    pub channel_id: String,
+    pub offer: FileOffer,
+    assembler: FileAssembler,
+}
+
+/// Result of adding a chunk to a group download
+pub enum GroupChunkResult {
+    Pending,
+    /// Ask the peer for the next window
+    Request { peer_id: u32, request: FileRequest },
+    /// The file arrived and matches its hash
+    Complete { download: Box<GroupDownload>, data: Vec<u8> },
+}
+
+#[derive(Default)]
+pub struct FileManager {
+    /// Incoming transfers keyed by (friend_number, file_number)
+    incoming: HashMap<(u32, u32), IncomingTransfer>,
+    /// Outgoing transfers keyed by (friend_number, file_number)
+    outgoing: HashMap<(u32, u32), OutgoingTransfer>,
+    /// Group file downloads keyed by (group_number, file hash)
+    group_downloads: HashMap<(u32, [u8; HASH_LENGTH]), GroupDownload>,
+}
+
+impl FileManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create the file for an incoming transfer in `dir`, the profile's
+    /// attachments directory, and start tracking it
+    #[allow(clippy::too_many_arguments)]
+    pub fn start_incoming(
+        &mut self,
+        dir: &Path,
+        friend_number: u32,
+        file_number: u32,
+        filename: &str,
+        mime_type: &str,
+        file_size: u64,
+        message_id: &str,
+    ) -> Result<&IncomingTransfer, String> {
+        let id = uuid::Uuid::new_v4().to_string();
+        let filename = sanitize_filename(filename);
+        let path = attachment_path(dir, &id, &filename)?;
+        let file = File::create(&path).map_err(|e| format!("Failed to create file: {e}"))?;
+
+        info!("Receiving {filename} ({file_size} bytes) from friend {friend_number} -> {}", path.display());
+
+        let transfer = IncomingTransfer {
+            id,
+            message_id: message_id.to_string(),
+            filename,
+            mime_type: mime_type.to_string(),
+            file_size,
+            path,
+            received: 0,
+            checkpointed: 0,
+            file,
+        };
+        self.incoming.insert((friend_number, file_number), transfer);
+        Ok(&self.incoming[&(friend_number, file_number)])
+    }
+
+    /// Reopen the partial file of an interrupted incoming transfer.
+    /// Anything past the last checkpoint is discarded; returns the position to resume from.
+    pub fn resume_incoming(
+        &mut self,
+        friend_number: u32,
+        file_number: u32,
+        record: &FileTransferRecord,
+        mime_type: &str,
+    ) -> Result<u64, String> {
+        let path = PathBuf::from(record.file_path.as_deref().ok_or("Transfer has no file path")?);
+        let file = OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .map_err(|e| format!("Failed to reopen partial file: {e}"))?;
+        let on_disk = file
+            .metadata()
+            .map_err(|e| format!("Failed to read partial file: {e}"))?
+            .len();
+        let position = (record.bytes_transferred.max(0) as u64).min(on_disk);
+        file.set_len(position)
+            .map_err(|e| format!("Failed to truncate partial file: {e}"))?;
+
+        info!("Resuming {} from friend {friend_number} at {position}/{} bytes", record.filename, record.file_size);
+
+        let transfer = IncomingTransfer {
+            id: record.id.clone(),
+            message_id: record.message_id.clone().unwrap_or_default(),
+            filename: record.filename.clone(),
+            mime_type: mime_type.to_string(),
+            file_size: record.file_size as u64,
+            path,
+            received: position,
+            checkpointed: position,
+            file,
+        };
+        self.incoming.insert((friend_number, file_number), transfer);
+        Ok(position)
+    }
+
+    /// Write a received chunk at its position
+    pub fn write_chunk(
+        &mut self,
+        friend_number: u32,
+        file_number: u32,
+        position: u64,
+        data: &[u8],
+    ) -> Result<(), String> {
+        let transfer = self
+            .incoming
+            .get_mut(&(friend_number, file_number))
+            .ok_or("Unknown transfer")?;
+
+        if position + data.len() as u64 > transfer.file_size {
+            return Err(format!("Chunk past end of file ({position} + {})", data.len()));
+        }
+
+        transfer
+            .file
+            .seek(SeekFrom::Start(position))
+            .and_then(|_| transfer.file.write_all(data))
+            .map_err(|e| format!("Failed to write chunk: {e}"))?;
+        transfer.received += data.len() as u64;
+        Ok(())
+    }
+
+    /// If enough has been received since the last checkpoint, return the
+    /// transfer id and progress to persist
+    pub fn checkpoint(&mut self, friend_number: u32, file_number: u32) -> Option<(String, u64)> {
+        let transfer = self.incoming.get_mut(&(friend_number, file_number))?;
+        if transfer.received - transfer.checkpointed < CHECKPOINT_INTERVAL {
+            return None;
+        }
+        if let Err(e) = transfer.file.flush() {
+            warn!("Failed to flush {}: {e}", transfer.path.display());
+            return None;
+        }
+        transfer.checkpointed = transfer.received;
+        Some((transfer.id.clone(), transfer.received))
+    }
+
+    /// Stop tracking a transfer that has received all its data
+    pub fn finish_incoming(&mut self, friend_number: u32, file_number: u32) -> Option<IncomingTransfer> {
+        let mut transfer = self.incoming.remove(&(friend_number, file_number))?;
+        if let Err(e) = transfer.file.flush() {
+            warn!("Failed to flush {}: {e}", transfer.path.display());
+        }
+        Some(transfer)
+    }
+
+    /// Stop tracking a transfer and delete the partial file
+    pub fn cancel_incoming(&mut self, friend_number: u32, file_number: u32) -> Option<IncomingTransfer> {
+        let transfer = self.incoming.remove(&(friend_number, file_number))?;
+        if let Err(e) = std::fs::remove_file(&transfer.path) {
+            warn!("Failed to remove partial file {}: {e}", transfer.path.display());
+        }
+        Some(transfer)
+    }
+
+    /// Start tracking an outgoing transfer that `tox.file_send` has created
+    pub fn start_outgoing(
+        &mut self,
+        friend_number: u32,
+        file_number: u32,
+        id: &str,
+        message_id: &str,
+        filename: &str,
+        path: &Path,
+    ) -> Result<(), String> {
+        let file = File::open(path).map_err(|e| format!("Failed to open file: {e}"))?;
+        let file_size = file
+            .metadata()
+            .map_err(|e| format!("Failed to read file size: {e}"))?
+            .len();
+
+        info!("Sending {filename} ({file_size} bytes) to friend {friend_number}");
+
+        let transfer = OutgoingTransfer {
+            id: id.to_string(),
+            message_id: message_id.to_string(),
+            filename: filename.to_string(),
+            file_size,
+            path: path.to_path_buf(),
+            sent: 0,
+            file,
+        };
+        self.outgoing.insert((friend_number, file_number), transfer);
+        Ok(())
+    }
+
+    pub fn outgoing(&self, friend_number: u32, file_number: u32) -> Option<&OutgoingTransfer> {
+        self.outgoing.get(&(friend_number, file_number))
+    }
+
+    /// Read the chunk a friend has requested for an outgoing transfer
+    pub fn read_chunk(
+        &mut self,
+        friend_number: u32,
+        file_number: u32,
+        position: u64,
+        length: usize,
+    ) -> Result<Vec<u8>, String> {
+        let transfer = self
+            .outgoing
+            .get_mut(&(friend_number, file_number))
+            .ok_or("Unknown transfer")?;
+
+        if position + length as u64 > transfer.file_size {
+            return Err(format!("Chunk past end of file ({position} + {length})"));
+        }
+
+        let mut data = vec![0u8; length];
+        transfer
+            .file
+            .seek(SeekFrom::Start(position))
+            .and_then(|_| transfer.file.read_exact(&mut data))
+            .map_err(|e| format!("Failed to read chunk: {e}"))?;
+        transfer.sent = transfer.sent.max(position + length as u64);
+        Ok(data)
+    }
+
+    /// Stop tracking an outgoing transfer the friend has fully received
+    pub fn finish_outgoing(&mut self, friend_number: u32, file_number: u32) -> Option<OutgoingTransfer> {
+        self.outgoing.remove(&(friend_number, file_number))
+    }
+
+    /// Stop tracking an outgoing transfer. The source file is kept since
+    /// it backs the sender's own attachment.
+    pub fn cancel_outgoing(&mut self, friend_number: u32, file_number: u32) -> Option<OutgoingTransfer> {
+        self.outgoing.remove(&(friend_number, file_number))
+    }
+
+    /// Stop tracking every transfer with a friend that went offline, keeping
+    /// partial files so they can be resumed
+    pub fn take_friend_transfers(&mut self, friend_number: u32) -> (Vec<IncomingTransfer>, Vec<OutgoingTransfer>) {
+        let incoming_keys: Vec<_> = self.incoming.keys().filter(|(f, _)| *f == friend_number).copied().collect();
+        let outgoing_keys: Vec<_> = self.outgoing.keys().filter(|(f, _)| *f == friend_number).copied().collect();
+
+        let incoming = incoming_keys
+            .iter()
+            .filter_map(|key| self.incoming.remove(key))
+            .map(|mut t| {
+                if let Err(e) = t.file.flush() {
+                    warn!("Failed to flush {}: {e}", t.path.display());
+                }
+                t
+            })
+            .collect();
+        let outgoing = outgoing_keys
+            .iter()
+            .filter_map(|key| self.outgoing.remove(key))
+            .collect();
+        (incoming, outgoing)
+    }
+
+    /// Start downloading a file offered in a group. Returns the first request to send to the peer.
+    pub fn start_group_download(
+        &mut self,
+        group_number: u32,
+        peer_id: u32,
+        offer: FileOffer,
+        message_id: &str,
+        channel_id: &str,
+    ) -> Result<FileRequest, String> {
+        let mut assembler = FileAssembler::new(&offer).map_err(|e| e.to_string())?;
+        let hash = toxcord_protocol::file_share::hash_from_hex(&offer.hash).ok_or("Invalid file hash")?;
+        let request = assembler.first_request();
+
+        info!("Downloading {} ({} bytes) from peer {peer_id} in group {group_number}", offer.filename, offer.size);
+
+        self.group_downloads.insert(
+            (group_number, hash),
+            GroupDownload {
+                peer_id,
+                message_id: message_id.to_string(),
+                channel_id: channel_id.to_string(),
+                offer,
+                assembler,
+            },
+        );
+        Ok(request)
+    }
+
+    /// Add a received chunk to its download. On error the download is dropped.
+    pub fn add_group_chunk(&mut self, group_number: u32, chunk: FileChunk) -> Result<GroupChunkResult, String> {
+        let key = (group_number, chunk.hash);
+        let download = self.group_downloads.get_mut(&key).ok_or("Unknown download")?;
+        match download.assembler.add_chunk(chunk) {
+            Ok(ChunkProgress::Pending) => Ok(GroupChunkResult::Pending),
+            Ok(ChunkProgress::WindowComplete(request)) => Ok(GroupChunkResult::Request {
+                peer_id: download.peer_id,
+                request,
+            }),
+            Ok(ChunkProgress::Complete(data)) => {
+                let download = self.group_downloads.remove(&key).ok_or("Unknown download")?;
+                Ok(GroupChunkResult::Complete {
+                    download: Box::new(download),
+                    data,
+                })
+            }
+            Err(e) => {
+                self.group_downloads.remove(&key);
+                Err(e.to_string())
+            }
+        }
+    }
+
+    /// Drop downloads from a peer that left the group
+    pub fn cancel_group_downloads_from(&mut self, group_number: u32, peer_id: u32) -> Vec<GroupDownload> {
+        let keys: Vec<_> = self
+            .group_downloads
+            .iter()
+            .filter(|((g, _), d)| *g == group_number && d.peer_id == peer_id)
+            .map(|(key, _)| *key)
+            .collect();
+        keys.iter().filter_map(|key| self.group_downloads.remove(key)).collect()
+    }
+}
+
+/// Read `length` bytes of a file starting at `offset`
+pub fn read_file_range(path: &Path, offset: u64, length: u64) -> Result<Vec<u8>, String> {
+    let mut file = File::open(path).map_err(|e| format!("Failed to open file: {e}"))?;
+    let mut data = Vec::new();
+    file.seek(SeekFrom::Start(offset))
+        .and_then(|_| file.take(length).read_to_end(&mut data))
+        .map_err(|e| format!("Failed to read file: {e}"))?;
+    Ok(data)
+}
+
+pub fn file_id_to_hex(file_id: &[u8; FILE_ID_LENGTH]) -> String {
+    file_id.iter().map(|b| format!("{b:02X}")).collect()
+}
+
+pub fn file_id_from_hex(hex: &str) -> Option<[u8; FILE_ID_LENGTH]> {
+    if hex.len() != FILE_ID_LENGTH * 2 || !hex.is_ascii() {
+        return None;
+    }
+    let mut file_id = [0u8; FILE_ID_LENGTH];
+    for (i, byte) in file_id.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok()?;
+    }
+    Some(file_id)
+}
+
+/// Write data into the profile's attachments directory. Returns the generated id and path.
+pub fn save_attachment(store: &MessageStore, filename: &str, data: &[u8]) -> Result<(String, PathBuf), String> {
+    let id = uuid::Uuid::new_v4().to_string();
+    let path = attachment_path(&attachments_dir(store), &id, &sanitize_filename(filename))?;
+    std::fs::write(&path, data).map_err(|e| format!("Failed to write attachment: {e}"))?;
+    Ok((id, path))
+}
+
+/// Read an image from the system clipboard and encode it as PNG
+pub fn clipboard_image_png() -> Result<Vec<u8>, String> {
+    let image = clipboard_image()?;
+    encode_png(image.as_raw(), image.width(), image.height())
+}
+
+/// Read an image from the system clipboard
+pub fn clipboard_image() -> Result<image::RgbaImage, String> {
+    let mut clipboard =
+        arboard::Clipboard::new().map_err(|e| format!("Failed to open clipboard: {e}"))?;
+    let image = clipboard
+        .get_image()
+        .map_err(|e| format!("No image on the clipboard: {e}"))?;
+    image::RgbaImage::from_raw(image.width as u32, image.height as u32, image.bytes.into_owned())
+        .ok_or_else(|| "Image data does not match its dimensions".to_string())
+}
+
+/// The text of the first QR code found in an image
+pub fn read_qr_code(img: &image::DynamicImage) -> Result<String, String> {
+    let gray = img.to_luma8();
+    let (width, height) = (gray.width() as usize, gray.height() as usize);
+    let mut prepared =
+        rqrr::PreparedImage::prepare_from_greyscale(width, height, |x, y| gray.get_pixel(x as u32, y as u32).0[0]);
+    let mut last_error = None;
+    for grid in prepared.detect_grids() {
+        match grid.decode() {
+            Ok((_, content)) => return Ok(content),
+            Err(e) => last_error = Some(e),
+        }
+    }
+    match last_error {
+        Some(e) => Err(format!("Failed to read QR code: {e}")),
+        None => Err("No QR code found in the image".to_string()),
+    }
+}
+
+/// Encode raw RGBA pixels as PNG
+pub fn encode_png(rgba: &[u8], width: u32, height: u32) -> Result<Vec<u8>, String> {
+    let img = image::RgbaImage::from_raw(width, height, rgba.to_vec())
+        .ok_or("Image data does not match its dimensions")?;
+    let mut png = Cursor::new(Vec::new());
+    img.write_to(&mut png, image::ImageFormat::Png)
+        .map_err(|e| format!("Failed to encode PNG: {e}"))?;
+    Ok(png.into_inner())
+}
+
+/// Build the attachment record for a file on disk, generating a thumbnail for images
+/// and reading the duration and waveform of voice notes.
+/// Decoding can be slow, so call this off the tox thread.
+pub fn build_attachment(
+    assets: &AssetStore,
+    message_id: &str,
+    transfer_id: &str,
+    filename: &str,
+    mime_type: &str,
+    file_size: u64,
+    path: &Path,
+) -> AttachmentRecord {
+    let (thumbnail_path, width, height) = if !mime_type.starts_with("image/") {
+        (None, None, None)
+    } else {
+        match cache_thumbnail(assets, path) {
+            Ok((thumb_path, w, h)) => {
+                (Some(thumb_path.to_string_lossy().to_string()), Some(w as i64), Some(h as i64))
+            }
+            Err(e) => {
+                warn!("Failed to generate thumbnail for {filename}: {e}");
+                (None, None, None)
+            }
+        }
+    };
+    let voice = (mime_type == voice_note::VOICE_NOTE_MIME)
+        .then(|| voice_note::read_info(path))
+        .flatten();
+
+    AttachmentRecord {
+        id: uuid::Uuid::new_v4().to_string(),
+        message_id: message_id.to_string(),
+        transfer_id: Some(transfer_id.to_string()),
+        filename: filename.to_string(),
+        mime_type: mime_type.to_string(),
+        file_size: file_size as i64,
+        file_path: path.to_string_lossy().to_string(),
+        thumbnail_path,
+        width,
+        height,
+        created_at: chrono::Utc::now().to_rfc3339(),
+        sha256: None,
+        duration_ms: voice.as_ref().map(|v| v.duration_ms as i64),
+        waveform: voice.map(|v| v.waveform),
+    }
+}
+
+/// Decode an image and store a PNG thumbnail of it in the media cache.
+/// Returns the thumbnail's path and the original dimensions.
+pub fn cache_thumbnail(assets: &AssetStore, source: &Path) -> Result<(PathBuf, u32, u32), String> {
+    let img = image::ImageReader::open(source)
+        .and_then(|r| r.with_guessed_format())
+        .map_err(|e| format!("Failed to open image: {e}"))?
+        .decode()
+        .map_err(|e| format!("Failed to decode image: {e}"))?;
+
+    let mut png = Cursor::new(Vec::new());
+    img.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE)
+        .write_to(&mut png, image::ImageFormat::Png)
+        .map_err(|e| format!("Failed to encode thumbnail: {e}"))?;
+    let path = assets.put(AssetKind::Thumbnail, &png.into_inner())?;
+
+    Ok((path, img.width(), img.height()))
+}
+
+/// The media cache for the loaded profile
+pub fn asset_store(store: &Arc<MessageStore>) -> AssetStore {
+    AssetStore::new(store.dir().join(ASSETS_DIR), store.clone())
+}
+
+/// MIME type for filenames we render inline (images and voice notes), based on the name
+pub fn inline_mime_type(filename: &str) -> Option<&'static str> {
+    if voice_note::is_voice_note(filename) {
+        return Some(voice_note::VOICE_NOTE_MIME);
+    }
+    let ext = Path::new(filename).extension()?.to_str()?.to_ascii_lowercase();
+    match ext.as_str() {
+        "png" => Some("image/png"),
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        "gif" => Some("image/gif"),
+        "webp" => Some("image/webp"),
+        "bmp" => Some("image/bmp"),
+        _ => None,
+    }
+}
+
+/// Strip anything from a peer-supplied filename that could escape the cache directory
+pub fn sanitize_filename(filename: &str) -> String {
+    let name = filename.rsplit(['/', '\\']).next().unwrap_or("");
+    let cleaned: String = name
+        .chars()
+        .map(|c| if c.is_control() || matches!(c, ':' | '*' | '?' | '"' | '<' | '>' | '|') { '_' } else { c })
+        .collect();
+    let cleaned = cleaned.trim_start_matches('.').trim();
+    if cleaned.is_empty() {
+        "file".to_string()
+    } else {
+        cleaned.to_string()
+    }
+}
+
+/// Message type for a file message: voice notes get their own player, and stickers show inline
+pub fn file_message_type(filename: &str) -> &'static str {
+    if voice_note::is_voice_note(filename) {
+        "voice"
+    } else if stickers::is_sticker_file(filename) {
+        "sticker"
+    } else {
+        "file"
+    }
+}
+
+fn attachment_path(dir: &Path, id: &str, filename: &str) -> Result<PathBuf, String> {
+    std::fs::create_dir_all(dir)
+        .map_err(|e| format!("Failed to create attachments directory: {e}"))?;
+    Ok(dir.join(format!("{id}_{filename}")))
+}
+
+/// The loaded profile's attachments directory
+pub fn attachments_dir(store: &MessageStore) -> PathBuf {
+    store.dir().join(ATTACHMENTS_DIR)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inline_mime_type() {
+        assert_eq!(inline_mime_type("cat.PNG"), Some("image/png"));
+        assert_eq!(inline_mime_type("photo.jpeg"), Some("image/jpeg"));
+        assert_eq!(inline_mime_type("voice-20250101-120000.ogg"), Some("audio/ogg"));
+        assert_eq!(inline_mime_type("song.ogg"), None);
+        assert_eq!(inline_mime_type("notes.txt"), None);
+        assert_eq!(inline_mime_type("noext"), None);
+    }
+
+    #[test]
+    fn test_sanitize_filename() {
+        assert_eq!(sanitize_filename("../../etc/passwd"), "passwd");
+        assert_eq!(sanitize_filename("C:\\Users\\me\\pic.png"), "pic.png");
+        assert_eq!(sanitize_filename(".hidden"), "hidden");
+        assert_eq!(sanitize_filename("a:b?.png"), "a_b_.png");
+        assert_eq!(sanitize_filename(".."), "file");
+        assert_eq!(sanitize_filename(""), "file");
+    }
+
+    #[test]
+    fn test_file_id_hex_roundtrip() {
+        let mut file_id = [0u8; FILE_ID_LENGTH];
+        file_id[0] = 0xAB;
+        file_id[31] = 0x01;
+        let hex = file_id_to_hex(&file_id);
+        assert_eq!(hex.len(), 64);
+        assert!(hex.starts_with("AB00"));
+        assert_eq!(file_id_from_hex(&hex), Some(file_id));
+        assert_eq!(file_id_from_hex(&hex.to_lowercase()), Some(file_id));
+        assert_eq!(file_id_from_hex("ABCD"), None);
+        assert_eq!(file_id_from_hex(&"ZZ".repeat(32)), None);
+    }
+
+    #[test]
+    fn test_checkpoint_interval() {
+        let dir = std::env::temp_dir().join(format!("toxcord-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("partial.bin");
+        std::fs::write(&path, vec![7u8; 100]).unwrap();
+
+        let record = FileTransferRecord {
+            id: "t1".to_string(),
+            friend_number: 3,
+            file_number: 0,
+            filename: "partial.bin".to_string(),
+            file_size: 4 * CHECKPOINT_INTERVAL as i64,
+            file_path: Some(path.to_string_lossy().to_string()),
+            direction: "incoming".to_string(),
+            status: "paused".to_string(),
+            bytes_transferred: 40,
+            message_id: None,
+            file_id: None,
+        };
+
+        let mut fm = FileManager::new();
+        // Bytes past the checkpoint are dropped
+        assert_eq!(fm.resume_incoming(3, 9, &record, "application/octet-stream").unwrap(), 40);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 40);
+
+        let chunk = vec![1u8; CHECKPOINT_INTERVAL as usize / 2];
+        fm.write_chunk(3, 9, 40, &chunk).unwrap();
+        assert_eq!(fm.checkpoint(3, 9), None);
+        fm.write_chunk(3, 9, 40 + chunk.len() as u64, &chunk).unwrap();
+        assert_eq!(fm.checkpoint(3, 9), Some(("t1".to_string(), 40 + CHECKPOINT_INTERVAL)));
+        assert_eq!(fm.checkpoint(3, 9), None);
+
+        let (incoming, outgoing) = fm.take_friend_transfers(3);
+        assert_eq!(incoming.len(), 1);
+        assert!(outgoing.is_empty());
+        assert!(fm.finish_incoming(3, 9).is_none());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_encode_png() {
+        let png = encode_png(&[255, 0, 0, 255, 0, 255, 0, 255], 2, 1).unwrap();
+        assert!(png.starts_with(b"\x89PNG"));
+        assert!(encode_png(&[0; 4], 2, 1).is_err());
+    }
+}
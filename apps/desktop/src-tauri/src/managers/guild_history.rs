@@ -0,0 +1,322 @@
+//! Guild history backfill
+//!
+//! A member who just joined a guild has empty channels, as NGC groups keep
+//! no history. When we join a guild we have no messages for, the first peer
+//! that connects is asked for the last `BACKFILL_LIMIT` messages of each
+//! channel (see `toxcord_protocol::history`). Other members answer the same
+//! request from their own database.
+//!
+//! Messages go with their senders' signatures, and we check them as if the
+//! messages had just come in, so the answering peer can't put words in
+//! anyone's mouth unnoticed. We only take messages from senders we know as
+//! members of the guild, never from ourselves, and never from the future;
+//! they're stored flagged as backfilled.
+//!
+//! Runs on the tox thread: callbacks queue a `HistoryAction` and the main
+//! loop calls [`handle`].
+
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+use tauri::{AppHandle, Emitter};
+use toxcord_core::incoming::member_verified;
+use toxcord_core::outgoing::sign_for_history;
+use toxcord_protocol::envelope::{self, ID_TAG_PREFIX};
+use toxcord_protocol::guild_manifest::FilterAction;
+use toxcord_protocol::history::{self, HistoryMessage, HistoryPacket, MAX_HISTORY_LIMIT};
+use toxcord_protocol::signing::{MessageSignature, Verification};
+use toxcord_tox::ToxInstance;
+use tracing::{debug, error, info, warn};
+
+use super::tox_manager::ToxEvent;
+use crate::db::message_store::ChannelMessageRecord;
+use crate::db::MessageStore;
+
+/// Messages asked for per channel
+const BACKFILL_LIMIT: u32 = 100;
+
+/// How long a peer gets to answer before the next one to join is asked
+const ANSWER_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Backfilled timestamps may run this far ahead of our clock
+const CLOCK_SKEW: chrono::Duration = chrono::Duration::minutes(5);
+
+pub enum HistoryAction {
+    /// We joined a group (or rejoined it on startup)
+    Joined(u32),
+    /// A peer joined a group (group number, peer ID)
+    PeerJoined(u32, u32),
+    /// A history packet arrived from a peer
+    Received(u32, u32, HistoryPacket),
+}
+
+/// Backfills in progress, by group number
+#[derive(Default)]
+pub struct Backfills {
+    /// Groups waiting for a peer to ask
+    pending: HashSet<u32>,
+    /// The peer asked, when, and how many messages it has given us
+    requested: HashMap<u32, (u32, Instant, usize)>,
+}
+
+/// Process one queued action
+pub fn handle(tox: &ToxInstance, store: &MessageStore, app_handle: &AppHandle, backfills: &mut Backfills, action: HistoryAction) {
+    match action {
+        HistoryAction::Joined(group_number) => {
+            let Some(guild_id) = server_guild_id(store, group_number) else {
+                return;
+            };
+            match store.guild_has_channel_messages(&guild_id) {
+                Ok(false) => {
+                    debug!("Guild {guild_id} has no messages, asking the first peer for its history");
+                    backfills.pending.insert(group_number);
+                }
+                Ok(true) => {}
+                Err(e) => error!("{e}"),
+            }
+        }
+        HistoryAction::PeerJoined(group_number, peer_id) => {
+            if !backfills.pending.contains(&group_number) {
+                return;
+            }
+            if backfills
+                .requested
+                .get(&group_number)
+                .is_some_and(|(_, asked_at, _)| asked_at.elapsed() < ANSWER_TIMEOUT)
+            {
+                return;
+            }
+            let request = HistoryPacket::Request { limit: BACKFILL_LIMIT };
+            match tox.group_send_custom_private_packet(group_number, peer_id, true, &request.to_bytes()) {
+                Ok(()) => {
+                    info!("Asked peer {peer_id} in group {group_number} for channel history");
+                    backfills.requested.insert(group_number, (peer_id, Instant::now(), 0));
+                }
+                Err(e) => warn!("Failed to ask peer {peer_id} in group {group_number} for history: {e}"),
+            }
+        }
+        HistoryAction::Received(group_number, peer_id, HistoryPacket::Request { limit }) => {
+            send_history(tox, store, group_number, peer_id, limit.min(MAX_HISTORY_LIMIT));
+        }
+        HistoryAction::Received(group_number, peer_id, HistoryPacket::Batch { channel, messages }) => {
+            let Some((asked, _, added)) = backfills.requested.get_mut(&group_number) else {
+                debug!("Ignoring history from peer {peer_id} in group {group_number}: not asked for");
+                return;
+            };
+            if *asked != peer_id {
+                debug!("Ignoring history from peer {peer_id} in group {group_number}: asked peer {asked}");
+                return;
+            }
+            *added += apply_batch(tox, store, group_number, peer_id, &channel, &messages);
+        }
+        HistoryAction::Received(group_number, peer_id, HistoryPacket::Done) => {
+            let Some(&(asked, _, messages_added)) = backfills.requested.get(&group_number) else {
+                return;
+            };
+            if asked != peer_id {
+                return;
+            }
+            backfills.requested.remove(&group_number);
+            backfills.pending.remove(&group_number);
+            info!("Backfilled {messages_added} messages in group {group_number} from peer {peer_id}");
+            if let Err(e) = app_handle.emit("tox://event", &ToxEvent::GuildHistoryBackfilled { group_number, messages_added }) {
+                error!("Failed to emit backfill event: {e}");
+            }
+        }
+    }
+}
+
+fn server_guild_id(store: &MessageStore, group_number: u32) -> Option<String> {
+    match store.get_guild_by_group_number_and_type(group_number as i64, "server") {
+        Ok(guild) => guild.map(|g| g.id),
+        Err(e) => {
+            error!("{e}");
+            None
+        }
+    }
+}
+
+/// Answer a request with the latest messages of each channel, oldest first
+fn send_history(tox: &ToxInstance, store: &MessageStore, group_number: u32, peer_id: u32, limit: u32) {
+    let Some(guild_id) = server_guild_id(store, group_number) else {
+        return;
+    };
+    let channels = match store.get_channels(&guild_id) {
+        Ok(channels) => channels,
+        Err(e) => {
+            error!("{e}");
+            return;
+        }
+    };
+
+    let self_pk = tox.group_self_get_public_key(group_number).map(|pk| hex_upper(&pk)).unwrap_or_default();
+    let mut packets = Vec::new();
+    for channel in channels {
+        // Signatures from before the channel was renamed no longer check out
+        let route = format!("[CH:{}]", channel.name);
+        let messages: Vec<HistoryMessage> = match store.get_channel_messages(&channel.id, limit as i64, None) {
+            Ok(messages) => messages
+                .into_iter()
+                .rev()
+                .filter(|m| !m.filtered && matches!(m.message_type.as_str(), "normal" | "action"))
+                .map(|m| {
+                    let signature = m.signature.filter(|s| s.route == route).or_else(|| {
+                        m.sender_public_key
+                            .eq_ignore_ascii_case(&self_pk)
+                            .then(|| sign_own(tox, group_number, &route, &m.id, &self_pk, &m.content))
+                            .flatten()
+                    });
+                    HistoryMessage {
+                        id: Some(m.id),
+                        sender_public_key: m.sender_public_key,
+                        sender_name: m.sender_name,
+                        content: m.content,
+                        message_type: m.message_type,
+                        timestamp: m.timestamp,
+                        signature,
+                    }
+                })
+                .collect(),
+            Err(e) => {
+                error!("{e}");
+                continue;
+            }
+        };
+        packets.extend(history::pack(&channel.name, &messages));
+    }
+    packets.push(HistoryPacket::Done.to_bytes());
+
+    debug!("Sending {} history packets to peer {peer_id} in group {group_number}", packets.len());
+    for packet in packets {
+        if let Err(e) = tox.group_send_custom_private_packet(group_number, peer_id, true, &packet) {
+            warn!("Failed to send history to peer {peer_id} in group {group_number}: {e}");
+            return;
+        }
+    }
+}
+
+/// Sign one of our own messages for history, as it would have been signed
+/// when sent behind `route` now
+fn sign_own(
+    tox: &ToxInstance,
+    group_number: u32,
+    route: &str,
+    id: &str,
+    self_pk: &str,
+    content: &str,
+) -> Option<MessageSignature> {
+    let text = if envelope::verify_id(id, self_pk, content) {
+        format!("{ID_TAG_PREFIX}{id}]{content}")
+    } else {
+        content.to_string()
+    };
+    sign_for_history(tox, group_number, route, &text)
+        .inspect_err(|e| debug!("Failed to sign message {id} for history: {e}"))
+        .ok()
+}
+
+/// Store the messages of a batch we can vouch for. Returns how many were new.
+fn apply_batch(
+    tox: &ToxInstance,
+    store: &MessageStore,
+    group_number: u32,
+    peer_id: u32,
+    channel: &str,
+    messages: &[HistoryMessage],
+) -> usize {
+    let Some(guild_id) = server_guild_id(store, group_number) else {
+        return 0;
+    };
+    let members: HashSet<String> = match store.get_guild_members(&guild_id) {
+        Ok(members) => members.into_iter().map(|m| m.public_key.to_uppercase()).collect(),
+        Err(e) => {
+            error!("{e}");
+            return 0;
+        }
+    };
+    let self_pk = tox.group_self_get_public_key(group_number).map(|pk| hex_upper(&pk)).unwrap_or_default();
+    let manifest = match store.get_guild_manifest(&guild_id) {
+        Ok(manifest) => manifest,
+        Err(e) => {
+            error!("{e}");
+            return 0;
+        }
+    };
+    let channel_id = match store.get_or_create_channel_by_name(&guild_id, channel) {
+        Ok(id) => id,
+        Err(e) => {
+            error!("{e}");
+            return 0;
+        }
+    };
+
+    let latest = chrono::Utc::now() + CLOCK_SKEW;
+    let mut added = 0;
+    for message in messages {
+        let sender = message.sender_public_key.to_uppercase();
+        if sender == self_pk || !members.contains(&sender) {
+            debug!("Dropped backfilled message from {sender} in group {group_number}: not a member we know");
+            continue;
+        }
+        let Some(timestamp) = chrono::DateTime::parse_from_rfc3339(&message.timestamp)
+            .ok()
+            .map(|t| t.with_timezone(&chrono::Utc))
+            .filter(|t| *t <= latest)
+        else {
+            debug!("Dropped backfilled message from {sender} in group {group_number}: bad timestamp");
+            continue;
+        };
+        let filtered = match manifest.word_filter.check(&message.content) {
+            Some(FilterAction::Hide) => continue,
+            Some(FilterAction::Warn) => true,
+            None => false,
+        };
+
+        let verification = message.verify(channel);
+        if verification == Verification::Invalid {
+            warn!("Backfilled message from {sender} in group {group_number} has a bad signature");
+        }
+        let sender_verified = member_verified(store, group_number, &sender, &verification);
+        let (signer_public_key, signature) = match verification {
+            Verification::Verified(key) => (Some(key), message.signature.clone()),
+            _ => (None, None),
+        };
+
+        // Keep the sender's ID when it checks out, so a message we already
+        // have is recognised as such
+        let id = message
+            .id
+            .as_deref()
+            .filter(|id| envelope::verify_id(id, &sender, &message.content))
+            .map(str::to_lowercase)
+            .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+        let record = ChannelMessageRecord {
+            id,
+            channel_id: channel_id.clone(),
+            sender_public_key: sender,
+            sender_name: message.sender_name.clone(),
+            content: message.content.clone(),
+            message_type: message.message_type.clone(),
+            timestamp: timestamp.to_rfc3339(),
+            // Old news: backfilled mentions don't notify
+            mentions_me: false,
+            notify: false,
+            filtered,
+            backfilled: true,
+            sender_verified: Some(sender_verified),
+            signer_public_key,
+            signature,
+        };
+        match store.insert_backfilled_channel_message(&record) {
+            Ok(true) => added += 1,
+            Ok(false) => {}
+            Err(e) => error!("Failed to persist backfilled message: {e}"),
+        }
+    }
+    debug!("Backfilled {added} of {} messages in #{channel} from peer {peer_id}", messages.len());
+    added
+}
+
+fn hex_upper(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02X}")).collect()
+}
@@ -1,10 +1,17 @@
 use std::sync::Arc;
 
 use tokio::sync::{oneshot, Mutex};
+use toxcord_protocol::envelope;
+use toxcord_protocol::file_share::{FileOffer, FileSharePacket};
+use toxcord_protocol::fingerprint::public_key_from_hex;
+use toxcord_protocol::stickers::StickerRef;
+use toxcord_tox::{GroupPrivacyState, GroupRole};
 use tracing::{error, info};
 
 use crate::db::message_store::{ChannelMessageRecord, ChannelRecord, GuildRecord};
 use crate::db::MessageStore;
+use crate::error::ToxcordError;
+use crate::managers::file_manager;
 use crate::managers::tox_manager::{ToxCommand, ToxManager};
 
 /// Higher-level guild abstraction that maps NGC groups to guilds.
@@ -22,17 +29,19 @@ impl GuildManager {
     }
 
     /// Create a new guild. Creates an NGC group and persists the guild + default "general" channel.
+    /// A public guild is announced on the DHT, so anyone with its chat ID can join.
     pub async fn create_guild(
         &self,
         name: &str,
+        privacy: GroupPrivacyState,
         tox_manager: &Arc<Mutex<ToxManager>>,
-    ) -> Result<GuildRecord, String> {
+    ) -> Result<GuildRecord, ToxcordError> {
         // Create the NGC group
         let (tx, rx) = oneshot::channel();
         tox_manager
             .lock()
             .await
-            .send_command(ToxCommand::GroupNew(name.to_string(), tx))
+            .send_command(ToxCommand::GroupNew(name.to_string(), privacy, tx))
             .await?;
         let group_number = rx.await.map_err(|_| "Failed to receive response".to_string())??;
 
@@ -58,22 +67,25 @@ impl GuildManager {
         let channel_id = uuid::Uuid::new_v4().to_string();
         self.store
             .insert_channel(&channel_id, &guild_id, "general", "text", 0)?;
+        if let Err(e) = self.remember_chat_id(&guild_id, group_number, tox_manager).await {
+            error!("Failed to record chat ID of guild {guild_id}: {e}");
+        }
 
         info!("Created guild '{name}' with group_number={group_number}");
 
         self.store
             .get_guild(&guild_id)?
-            .ok_or_else(|| "Guild not found after creation".to_string())
+            .ok_or_else(|| "Guild not found after creation".into())
     }
 
     /// Get all guilds from the database.
-    pub fn get_guilds(&self) -> Result<Vec<GuildRecord>, String> {
-        self.store.get_guilds()
+    pub fn get_guilds(&self) -> Result<Vec<GuildRecord>, ToxcordError> {
+        Ok(self.store.get_guilds()?)
     }
 
     /// Get channels for a guild.
-    pub fn get_guild_channels(&self, guild_id: &str) -> Result<Vec<ChannelRecord>, String> {
-        self.store.get_channels(guild_id)
+    pub fn get_guild_channels(&self, guild_id: &str) -> Result<Vec<ChannelRecord>, ToxcordError> {
+        Ok(self.store.get_channels(guild_id)?)
     }
 
     /// Add a new channel to a guild.
@@ -81,7 +93,7 @@ impl GuildManager {
         &self,
         guild_id: &str,
         name: &str,
-    ) -> Result<ChannelRecord, String> {
+    ) -> Result<ChannelRecord, ToxcordError> {
         let position = self.store.get_channel_count(guild_id)?;
         let channel_id = uuid::Uuid::new_v4().to_string();
         self.store
@@ -91,22 +103,22 @@ impl GuildManager {
         channels
             .into_iter()
             .find(|c| c.id == channel_id)
-            .ok_or_else(|| "Channel not found after creation".to_string())
+            .ok_or_else(|| "Channel not found after creation".into())
     }
 
     /// Remove a channel from a guild.
-    pub fn remove_channel(&self, _guild_id: &str, channel_id: &str) -> Result<(), String> {
-        self.store.delete_channel(channel_id)
+    pub fn remove_channel(&self, _guild_id: &str, channel_id: &str) -> Result<(), ToxcordError> {
+        Ok(self.store.delete_channel(channel_id)?)
     }
 
     /// Update a guild's name.
-    pub fn update_guild_name(&self, guild_id: &str, name: &str) -> Result<(), String> {
-        self.store.update_guild_name(guild_id, name)
+    pub fn update_guild_name(&self, guild_id: &str, name: &str) -> Result<(), ToxcordError> {
+        Ok(self.store.update_guild_name(guild_id, name)?)
     }
 
     /// Rename a channel.
-    pub fn rename_channel(&self, channel_id: &str, name: &str) -> Result<(), String> {
-        self.store.rename_channel(channel_id, name)
+    pub fn rename_channel(&self, channel_id: &str, name: &str) -> Result<(), ToxcordError> {
+        Ok(self.store.rename_channel(channel_id, name)?)
     }
 
     /// Invite a friend to the guild's NGC group.
@@ -115,11 +127,11 @@ impl GuildManager {
         guild_id: &str,
         friend_number: u32,
         tox_manager: &Arc<Mutex<ToxManager>>,
-    ) -> Result<(), String> {
+    ) -> Result<(), ToxcordError> {
         let guild = self
             .store
             .get_guild(guild_id)?
-            .ok_or("Guild not found")?;
+            .ok_or_else(|| ToxcordError::not_found("Guild not found"))?;
 
         let group_number = guild
             .metadata_group_number
@@ -142,7 +154,8 @@ impl GuildManager {
                 "Group {} no longer exists in tox instance (guild '{}'): {}. \
                  The guild record may be stale — try recreating the server.",
                 group_number, guild.name, e
-            ));
+            )
+            .into());
         }
 
         let (tx, rx) = oneshot::channel();
@@ -151,18 +164,21 @@ impl GuildManager {
             .await
             .send_command(ToxCommand::GroupInviteFriend(group_number, friend_number, tx))
             .await?;
-        rx.await
-            .map_err(|_| "Failed to receive response".to_string())?
+        Ok(rx
+            .await
+            .map_err(|_| "Failed to receive response".to_string())??)
     }
 
     /// Accept a guild invite. Creates a local guild record from the NGC group.
+    /// `password` is empty unless the group has one.
     pub async fn accept_guild_invite(
         &self,
         friend_number: u32,
         invite_data: &[u8],
         group_name: &str,
+        password: &str,
         tox_manager: &Arc<Mutex<ToxManager>>,
-    ) -> Result<GuildRecord, String> {
+    ) -> Result<GuildRecord, ToxcordError> {
         let (tx, rx) = oneshot::channel();
         tox_manager
             .lock()
@@ -170,12 +186,57 @@ impl GuildManager {
             .send_command(ToxCommand::GroupInviteAccept(
                 friend_number,
                 invite_data.to_vec(),
+                password.to_string(),
                 tx,
             ))
             .await?;
         let group_number = rx.await.map_err(|_| "Failed to receive response".to_string())??;
 
-        // Use the group name from the invite (more reliable than querying immediately)
+        let guild = self.add_joined_group(group_number, group_name, tox_manager).await?;
+        info!("Accepted guild invite, group_number={group_number}, guild_type={}", guild.guild_type);
+        Ok(guild)
+    }
+
+    /// Join a public guild by its chat ID, from a `toxcord://join` link.
+    /// Private groups can only be joined by invite.
+    pub async fn join_guild(
+        &self,
+        chat_id: &str,
+        group_name: &str,
+        password: &str,
+        tox_manager: &Arc<Mutex<ToxManager>>,
+    ) -> Result<GuildRecord, ToxcordError> {
+        let known = self
+            .store
+            .get_guild_chat_ids()?
+            .into_iter()
+            .find(|(_, known)| known.eq_ignore_ascii_case(chat_id));
+        if let Some((guild_id, _)) = known {
+            return self.rejoin_guild(&guild_id, password, tox_manager).await;
+        }
+
+        let chat_id_bytes = public_key_from_hex(chat_id).ok_or("Malformed chat ID")?;
+        let (tx, rx) = oneshot::channel();
+        tox_manager
+            .lock()
+            .await
+            .send_command(ToxCommand::GroupJoin(chat_id_bytes, password.to_string(), tx))
+            .await?;
+        let group_number = rx.await.map_err(|_| "Failed to receive response".to_string())??;
+
+        let guild = self.add_joined_group(group_number, group_name, tox_manager).await?;
+        info!("Joined guild by chat ID, group_number={group_number}");
+        Ok(guild)
+    }
+
+    /// Create the local guild record for a group we just joined
+    async fn add_joined_group(
+        &self,
+        group_number: u32,
+        group_name: &str,
+        tox_manager: &Arc<Mutex<ToxManager>>,
+    ) -> Result<GuildRecord, ToxcordError> {
+        // Use the group name we were given (more reliable than querying immediately)
         let raw_name = if group_name.is_empty() {
             // Fallback: try to query from Tox
             let (info_tx, info_rx) = oneshot::channel();
@@ -208,12 +269,131 @@ impl GuildManager {
         let channel_id = uuid::Uuid::new_v4().to_string();
         self.store
             .insert_channel(&channel_id, &guild_id, channel_name, "text", 0)?;
-
-        info!("Accepted guild invite, group_number={group_number}, guild_type={guild_type}");
+        if let Err(e) = self.remember_chat_id(&guild_id, group_number, tox_manager).await {
+            error!("Failed to record chat ID of guild {guild_id}: {e}");
+        }
 
         self.store
             .get_guild(&guild_id)?
-            .ok_or_else(|| "Guild not found after creation".to_string())
+            .ok_or_else(|| "Guild not found after creation".into())
+    }
+
+    /// Store a guild's chat ID, so its group can be joined again if it's lost
+    async fn remember_chat_id(
+        &self,
+        guild_id: &str,
+        group_number: u32,
+        tox_manager: &Arc<Mutex<ToxManager>>,
+    ) -> Result<(), ToxcordError> {
+        let (tx, rx) = oneshot::channel();
+        tox_manager
+            .lock()
+            .await
+            .send_command(ToxCommand::GroupGetInfo(group_number, tx))
+            .await?;
+        let info = rx.await.map_err(|_| "Failed to receive response".to_string())??;
+        Ok(self.store.set_guild_chat_id(guild_id, &info.chat_id)?)
+    }
+
+    /// Join a guild's group again by its chat ID, when the group is missing
+    /// from the tox instance. With a password, the group is left and joined
+    /// again even if it's there, as a join refused for a wrong password
+    /// leaves it behind.
+    pub async fn rejoin_guild(
+        &self,
+        guild_id: &str,
+        password: &str,
+        tox_manager: &Arc<Mutex<ToxManager>>,
+    ) -> Result<GuildRecord, ToxcordError> {
+        let guild = self.store.get_guild(guild_id)?.ok_or_else(|| ToxcordError::not_found("Guild not found"))?;
+        let chat_id = self
+            .store
+            .get_guild_chat_id(guild_id)?
+            .ok_or("This guild's chat ID was never recorded, so it can't be rejoined")?;
+
+        // Nothing to do if the group is still there
+        if let Some(group_number) = guild.metadata_group_number {
+            let (tx, rx) = oneshot::channel();
+            tox_manager
+                .lock()
+                .await
+                .send_command(ToxCommand::GroupGetInfo(group_number as u32, tx))
+                .await?;
+            if let Ok(Ok(info)) = rx.await {
+                if info.chat_id.eq_ignore_ascii_case(&chat_id) {
+                    if password.is_empty() {
+                        return Ok(guild);
+                    }
+                    let (leave_tx, leave_rx) = oneshot::channel();
+                    tox_manager
+                        .lock()
+                        .await
+                        .send_command(ToxCommand::GroupLeave(group_number as u32, leave_tx))
+                        .await?;
+                    leave_rx.await.map_err(|_| "Failed to receive response".to_string())??;
+                }
+            }
+        }
+
+        let chat_id_bytes = public_key_from_hex(&chat_id).ok_or("Malformed chat ID")?;
+        let (tx, rx) = oneshot::channel();
+        tox_manager
+            .lock()
+            .await
+            .send_command(ToxCommand::GroupJoin(chat_id_bytes, password.to_string(), tx))
+            .await?;
+        let group_number = rx.await.map_err(|_| "Failed to receive response".to_string())??;
+        self.store.update_guild_group_number(guild_id, group_number as i64)?;
+
+        info!("Rejoined guild '{}' as group_number={group_number}", guild.name);
+
+        self.store
+            .get_guild(guild_id)?
+            .ok_or_else(|| "Guild not found after rejoining".into())
+    }
+
+    /// Set the password needed to join a guild, or remove it with an empty
+    /// one. Founder only; members already in the guild stay.
+    pub async fn set_guild_password(
+        &self,
+        guild_id: &str,
+        password: &str,
+        tox_manager: &Arc<Mutex<ToxManager>>,
+    ) -> Result<(), ToxcordError> {
+        let guild = self.store.get_guild(guild_id)?.ok_or_else(|| ToxcordError::not_found("Guild not found"))?;
+        let group_number = guild
+            .metadata_group_number
+            .ok_or("Guild has no group number")? as u32;
+
+        let (role_tx, role_rx) = oneshot::channel();
+        tox_manager
+            .lock()
+            .await
+            .send_command(ToxCommand::GroupGetSelfRole(group_number, role_tx))
+            .await?;
+        let role = role_rx
+            .await
+            .map_err(|_| "Failed to receive response".to_string())??;
+        if role != GroupRole::Founder {
+            return Err(ToxcordError::NotPermitted(
+                "Only the founder can set the guild password".to_string(),
+            ));
+        }
+
+        let (tx, rx) = oneshot::channel();
+        tox_manager
+            .lock()
+            .await
+            .send_command(ToxCommand::GroupSetPassword(group_number, password.to_string(), tx))
+            .await?;
+        rx.await.map_err(|_| "Failed to receive response".to_string())??;
+
+        info!(
+            "{} the password of guild '{}'",
+            if password.is_empty() { "Removed" } else { "Set" },
+            guild.name
+        );
+        Ok(())
     }
 
     /// Create a DM group chat with selected friends.
@@ -222,14 +402,14 @@ impl GuildManager {
         name: &str,
         friend_numbers: &[u32],
         tox_manager: &Arc<Mutex<ToxManager>>,
-    ) -> Result<GuildRecord, String> {
+    ) -> Result<GuildRecord, ToxcordError> {
         // Create the NGC group with [DM] prefix so recipients know it's a DM group
         let tox_group_name = format!("[DM]{}", name);
         let (tx, rx) = oneshot::channel();
         tox_manager
             .lock()
             .await
-            .send_command(ToxCommand::GroupNew(tox_group_name, tx))
+            .send_command(ToxCommand::GroupNew(tox_group_name, GroupPrivacyState::Private, tx))
             .await?;
         let group_number = rx.await.map_err(|_| "Failed to receive response".to_string())??;
 
@@ -255,6 +435,9 @@ impl GuildManager {
         let channel_id = uuid::Uuid::new_v4().to_string();
         self.store
             .insert_channel(&channel_id, &guild_id, "messages", "text", 0)?;
+        if let Err(e) = self.remember_chat_id(&guild_id, group_number, tox_manager).await {
+            error!("Failed to record chat ID of guild {guild_id}: {e}");
+        }
 
         // Invite all selected friends
         for &friend_number in friend_numbers {
@@ -273,7 +456,7 @@ impl GuildManager {
 
         self.store
             .get_guild(&guild_id)?
-            .ok_or_else(|| "DM group not found after creation".to_string())
+            .ok_or_else(|| "DM group not found after creation".into())
     }
 
     /// Send a message to a DM group (uses [DM] prefix).
@@ -282,22 +465,35 @@ impl GuildManager {
         guild_id: &str,
         content: &str,
         tox_manager: &Arc<Mutex<ToxManager>>,
-    ) -> Result<ChannelMessageRecord, String> {
+    ) -> Result<ChannelMessageRecord, ToxcordError> {
         let guild = self
             .store
             .get_guild(guild_id)?
-            .ok_or("DM group not found")?;
+            .ok_or_else(|| ToxcordError::not_found("DM group not found"))?;
 
         if guild.guild_type != "dm_group" {
-            return Err("Not a DM group".to_string());
+            return Err(ToxcordError::invalid("Not a DM group"));
         }
 
         let group_number = guild
             .metadata_group_number
             .ok_or("DM group has no group number")? as u32;
 
-        // Prefix message with [DM] for DM group routing
-        let prefixed_content = format!("[DM]{}", content);
+        // Get our own public key
+        let (pk_tx, pk_rx) = oneshot::channel();
+        tox_manager
+            .lock()
+            .await
+            .send_command(ToxCommand::GroupGetSelfPk(group_number, pk_tx))
+            .await?;
+        let self_pk = pk_rx
+            .await
+            .map_err(|_| "Failed to receive response".to_string())?
+            .unwrap_or_default();
+
+        // Tag with the message's ID, then prefix with [DM] for DM group routing
+        let (msg_id, tagged) = envelope::seal(&self_pk, chrono::Utc::now().timestamp_millis(), content);
+        let prefixed_content = format!("[DM]{}", tagged);
 
         let (tx, rx) = oneshot::channel();
         tox_manager
@@ -312,18 +508,6 @@ impl GuildManager {
         rx.await
             .map_err(|_| "Failed to receive response".to_string())??;
 
-        // Get our own public key
-        let (pk_tx, pk_rx) = oneshot::channel();
-        tox_manager
-            .lock()
-            .await
-            .send_command(ToxCommand::GroupGetSelfPk(group_number, pk_tx))
-            .await?;
-        let self_pk = pk_rx
-            .await
-            .map_err(|_| "Failed to receive response".to_string())?
-            .unwrap_or_default();
-
         // Get our display name
         let (info_tx, info_rx) = oneshot::channel();
         tox_manager
@@ -343,7 +527,6 @@ impl GuildManager {
             .map(|c| c.id.clone())
             .unwrap_or_else(|| format!("dm_group_{group_number}"));
 
-        let msg_id = uuid::Uuid::new_v4().to_string();
         let timestamp = chrono::Utc::now().to_rfc3339();
 
         let record = ChannelMessageRecord {
@@ -354,6 +537,13 @@ impl GuildManager {
             content: content.to_string(),
             message_type: "normal".to_string(),
             timestamp,
+            mentions_me: false,
+            notify: false,
+            filtered: false,
+            backfilled: false,
+            sender_verified: None,
+            signer_public_key: None,
+            signature: None,
         };
 
         self.store.insert_channel_message(&record)?;
@@ -367,11 +557,36 @@ impl GuildManager {
         channel_id: &str,
         content: &str,
         tox_manager: &Arc<Mutex<ToxManager>>,
-    ) -> Result<ChannelMessageRecord, String> {
+    ) -> Result<ChannelMessageRecord, ToxcordError> {
+        self.send_channel_text(guild_id, channel_id, content, "normal", tox_manager)
+            .await
+    }
+
+    /// Send a sticker to a channel as a reference. Only for channels whose
+    /// members all have the pack; see `stickers::all_have_pack`.
+    pub async fn send_channel_sticker(
+        &self,
+        guild_id: &str,
+        channel_id: &str,
+        reference: &StickerRef,
+        tox_manager: &Arc<Mutex<ToxManager>>,
+    ) -> Result<ChannelMessageRecord, ToxcordError> {
+        self.send_channel_text(guild_id, channel_id, &reference.to_text(), "sticker", tox_manager)
+            .await
+    }
+
+    async fn send_channel_text(
+        &self,
+        guild_id: &str,
+        channel_id: &str,
+        content: &str,
+        message_type: &str,
+        tox_manager: &Arc<Mutex<ToxManager>>,
+    ) -> Result<ChannelMessageRecord, ToxcordError> {
         let guild = self
             .store
             .get_guild(guild_id)?
-            .ok_or("Guild not found")?;
+            .ok_or_else(|| ToxcordError::not_found("Guild not found"))?;
 
         let group_number = guild
             .metadata_group_number
@@ -385,8 +600,24 @@ impl GuildManager {
             .map(|c| c.name.clone())
             .unwrap_or_else(|| "general".to_string());
 
-        // Prefix message with channel name: [CH:general]content
-        let prefixed_content = format!("[CH:{}]{}", channel_name, content);
+        self.check_can_post(guild_id, group_number, channel_id, &channel_name, tox_manager)
+            .await?;
+
+        // Get our own public key
+        let (pk_tx, pk_rx) = oneshot::channel();
+        tox_manager
+            .lock()
+            .await
+            .send_command(ToxCommand::GroupGetSelfPk(group_number, pk_tx))
+            .await?;
+        let self_pk = pk_rx
+            .await
+            .map_err(|_| "Failed to receive response".to_string())?
+            .unwrap_or_default();
+
+        // Tag with the message's ID, then prefix with channel name: [CH:general][ID:...]content
+        let (msg_id, tagged) = envelope::seal(&self_pk, chrono::Utc::now().timestamp_millis(), content);
+        let prefixed_content = format!("[CH:{}]{}", channel_name, tagged);
 
         info!("Sending message to group {} channel '{}': {:?}",
               group_number, channel_name, content.chars().take(50).collect::<String>());
@@ -408,14 +639,104 @@ impl GuildManager {
             }
             Ok(Err(e)) => {
                 error!("Failed to send message to group {}: {}", group_number, e);
-                return Err(format!("Failed to send message: {}", e));
+                return Err(e);
             }
             Err(_) => {
                 error!("Channel closed when sending to group {}", group_number);
-                return Err("Failed to receive response from Tox thread".to_string());
+                return Err("Failed to receive response from Tox thread".into());
             }
         }
 
+        // Get our display name
+        let (info_tx, info_rx) = oneshot::channel();
+        tox_manager
+            .lock()
+            .await
+            .send_command(ToxCommand::GetProfileInfo(info_tx))
+            .await?;
+        let self_name = info_rx
+            .await
+            .map(|p| p.name)
+            .unwrap_or_default();
+
+        let timestamp = chrono::Utc::now().to_rfc3339();
+
+        let record = ChannelMessageRecord {
+            id: msg_id,
+            channel_id: channel_id.to_string(),
+            sender_public_key: self_pk,
+            sender_name: self_name,
+            content: content.to_string(),
+            message_type: message_type.to_string(),
+            timestamp,
+            mentions_me: false,
+            notify: false,
+            filtered: false,
+            backfilled: false,
+            sender_verified: None,
+            signer_public_key: None,
+            signature: None,
+        };
+
+        self.store.insert_channel_message(&record)?;
+        Ok(record)
+    }
+
+    /// Share a file in a channel: broadcast the offer and record our own message.
+    /// The file must already be stored as an attachment (with its hash) under
+    /// `message_id`, since peers start requesting it as soon as the offer arrives.
+    pub async fn send_channel_file(
+        &self,
+        guild_id: &str,
+        channel_id: &str,
+        message_id: &str,
+        mut offer: FileOffer,
+        tox_manager: &Arc<Mutex<ToxManager>>,
+    ) -> Result<ChannelMessageRecord, ToxcordError> {
+        let guild = self
+            .store
+            .get_guild(guild_id)?
+            .ok_or_else(|| ToxcordError::not_found("Guild not found"))?;
+
+        let group_number = guild
+            .metadata_group_number
+            .ok_or("Guild has no group number")? as u32;
+
+        // Same routing as text messages: channel name for servers, none for DM groups
+        offer.channel = if guild.guild_type == "dm_group" {
+            None
+        } else {
+            let channels = self.store.get_channels(guild_id)?;
+            Some(
+                channels
+                    .iter()
+                    .find(|c| c.id == channel_id)
+                    .map(|c| c.name.clone())
+                    .unwrap_or_else(|| "general".to_string()),
+            )
+        };
+        if let Some(channel_name) = &offer.channel {
+            self.check_can_post(guild_id, group_number, channel_id, channel_name, tox_manager)
+                .await?;
+        }
+
+        info!("Sharing {} ({} bytes) in group {}", offer.filename, offer.size, group_number);
+
+        let filename = offer.filename.clone();
+        let (tx, rx) = oneshot::channel();
+        tox_manager
+            .lock()
+            .await
+            .send_command(ToxCommand::GroupSendCustomPacket(
+                group_number,
+                FileSharePacket::Offer(offer).to_bytes(),
+                tx,
+            ))
+            .await?;
+        rx.await
+            .map_err(|_| "Failed to receive response".to_string())?
+            .map_err(|e| format!("Failed to share file: {e}"))?;
+
         // Get our own public key
         let (pk_tx, pk_rx) = oneshot::channel();
         tox_manager
@@ -440,38 +761,92 @@ impl GuildManager {
             .map(|p| p.name)
             .unwrap_or_default();
 
-        let msg_id = uuid::Uuid::new_v4().to_string();
-        let timestamp = chrono::Utc::now().to_rfc3339();
-
         let record = ChannelMessageRecord {
-            id: msg_id,
+            id: message_id.to_string(),
             channel_id: channel_id.to_string(),
             sender_public_key: self_pk,
             sender_name: self_name,
-            content: content.to_string(),
-            message_type: "normal".to_string(),
-            timestamp,
+            message_type: file_manager::file_message_type(&filename).to_string(),
+            content: filename,
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            mentions_me: false,
+            notify: false,
+            filtered: false,
+            backfilled: false,
+            sender_verified: None,
+            signer_public_key: None,
+            signature: None,
         };
 
         self.store.insert_channel_message(&record)?;
         Ok(record)
     }
 
-    /// Get channel messages with pagination.
-    pub fn get_channel_messages(
+    /// Refuse to send until we've accepted the guild's rules if it asks us to,
+    /// in announcement channels unless we're a founder or moderator, and while
+    /// our slow-mode cooldown is running (founders and moderators are exempt).
+    async fn check_can_post(
         &self,
+        guild_id: &str,
+        group_number: u32,
         channel_id: &str,
-        limit: i64,
-        before_timestamp: Option<&str>,
-    ) -> Result<Vec<ChannelMessageRecord>, String> {
-        self.store
-            .get_channel_messages(channel_id, limit, before_timestamp)
-    }
+        channel_name: &str,
+        tox_manager: &Arc<Mutex<ToxManager>>,
+    ) -> Result<(), ToxcordError> {
+        let manifest = self.store.get_guild_manifest(guild_id)?;
+        let accepted = self.store.get_accepted_guild_rules(guild_id)?;
+        if !manifest.onboarding.may_post(accepted.as_deref()) {
+            return Err(ToxcordError::NotPermitted(
+                "Accept this server's rules before posting".to_string(),
+            ));
+        }
+        let settings = manifest.channel(channel_name);
+        if settings.slow_mode_secs == 0 && !settings.announcement {
+            return Ok(());
+        }
 
-    /// Get the guild associated with a group number (for mapping incoming events).
-    #[allow(dead_code)]
-    pub fn get_guild_by_group_number(&self, group_number: i64) -> Result<Option<GuildRecord>, String> {
-        self.store.get_guild_by_group_number(group_number)
+        let (role_tx, role_rx) = oneshot::channel();
+        tox_manager
+            .lock()
+            .await
+            .send_command(ToxCommand::GroupGetSelfRole(group_number, role_tx))
+            .await?;
+        let role = role_rx
+            .await
+            .map_err(|_| "Failed to receive response".to_string())??;
+        if matches!(role, GroupRole::Founder | GroupRole::Moderator) {
+            return Ok(());
+        }
+        if settings.announcement {
+            return Err(ToxcordError::NotPermitted(format!(
+                "Only founders and moderators can post in #{channel_name}"
+            )));
+        }
+
+        let (pk_tx, pk_rx) = oneshot::channel();
+        tox_manager
+            .lock()
+            .await
+            .send_command(ToxCommand::GroupGetSelfPk(group_number, pk_tx))
+            .await?;
+        let self_pk = pk_rx
+            .await
+            .map_err(|_| "Failed to receive response".to_string())??;
+
+        let Some(last_sent) = self.store.get_last_channel_message_time(channel_id, &self_pk)? else {
+            return Ok(());
+        };
+        let last_sent = chrono::DateTime::parse_from_rfc3339(&last_sent)
+            .map(|t| t.timestamp())
+            .unwrap_or_default();
+        let remaining = settings.cooldown_remaining(last_sent, chrono::Utc::now().timestamp());
+        if remaining > 0 {
+            return Err(ToxcordError::SlowMode {
+                channel: channel_name.to_string(),
+                remaining_secs: remaining,
+            });
+        }
+        Ok(())
     }
 
     /// Delete a guild and leave its NGC group.
@@ -479,11 +854,11 @@ impl GuildManager {
         &self,
         guild_id: &str,
         tox_manager: &Arc<Mutex<ToxManager>>,
-    ) -> Result<(), String> {
+    ) -> Result<(), ToxcordError> {
         let guild = self
             .store
             .get_guild(guild_id)?
-            .ok_or("Guild not found")?;
+            .ok_or_else(|| ToxcordError::not_found("Guild not found"))?;
 
         if let Some(group_number) = guild.metadata_group_number {
             let (tx, rx) = oneshot::channel();
@@ -497,6 +872,6 @@ impl GuildManager {
             }
         }
 
-        self.store.delete_guild(guild_id)
+        Ok(self.store.delete_guild(guild_id)?)
     }
 }
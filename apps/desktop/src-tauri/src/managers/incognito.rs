@@ -0,0 +1,73 @@
+//! Incognito conversations
+//!
+//! Messages with a friend whose conversation is in incognito are delivered
+//! and shown but never stored, so they stay out of the database and the
+//! search index and are gone once the app closes. Incognito is switched on
+//! per conversation for the session; friends chosen in the profile settings
+//! start in it every time the profile loads.
+
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+
+use crate::db::MessageStore;
+
+/// Profile setting holding the public keys of friends whose conversations
+/// start in incognito, as JSON
+pub const INCOGNITO_FRIENDS_SETTING: &str = "incognito_friends";
+
+/// Shared between the tox thread, its callbacks and commands
+#[derive(Clone, Default)]
+pub struct Incognito {
+    /// Friend numbers of conversations in incognito
+    active: Arc<Mutex<HashSet<u32>>>,
+}
+
+impl Incognito {
+    /// Start the conversations of friends marked incognito by default
+    pub fn load(&self, store: &MessageStore) -> Result<(), String> {
+        let mut active = HashSet::new();
+        for public_key in default_friends(store)? {
+            if let Some(friend_number) = store.get_friend_number_by_public_key(&public_key)? {
+                active.insert(friend_number);
+            }
+        }
+        if let Ok(mut current) = self.active.lock() {
+            *current = active;
+        }
+        Ok(())
+    }
+
+    pub fn is_active(&self, friend_number: u32) -> bool {
+        self.active.lock().is_ok_and(|active| active.contains(&friend_number))
+    }
+
+    pub fn set_active(&self, friend_number: u32, enabled: bool) {
+        if let Ok(mut active) = self.active.lock() {
+            if enabled {
+                active.insert(friend_number);
+            } else {
+                active.remove(&friend_number);
+            }
+        }
+    }
+}
+
+/// Public keys of friends whose conversations start in incognito
+pub fn default_friends(store: &MessageStore) -> Result<Vec<String>, String> {
+    match store.get_setting(INCOGNITO_FRIENDS_SETTING)? {
+        Some(json) => serde_json::from_str(&json).map_err(|e| format!("Failed to parse incognito friends: {e}")),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Choose whether a friend's conversation starts in incognito
+pub fn set_default(store: &MessageStore, public_key: &str, enabled: bool) -> Result<(), String> {
+    let public_key = public_key.to_uppercase();
+    let mut friends = default_friends(store)?;
+    friends.retain(|key| *key != public_key);
+    if enabled {
+        friends.push(public_key);
+    }
+    let json = serde_json::to_string(&friends).map_err(|e| format!("Failed to serialize incognito friends: {e}"))?;
+    store.set_setting(INCOGNITO_FRIENDS_SETTING, &json)
+}
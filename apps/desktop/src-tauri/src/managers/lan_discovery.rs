@@ -0,0 +1,191 @@
+//! LAN Discovery
+//!
+//! Toxcore's local discovery finds DHT nodes on the LAN but never tells us who
+//! is behind them. While enabled, this broadcasts our Tox address and name
+//! over UDP and collects the announcements of other Toxcord instances, so they
+//! can be listed and added with one click.
+//!
+//! Discovery is opt-in: announcing makes our Tox ID visible to everyone on
+//! the local network.
+
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, SocketAddr, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tauri::{AppHandle, Emitter};
+use toxcord_protocol::lan::{
+    LanAnnouncement, ANNOUNCE_INTERVAL_SECS, LAN_DISCOVERY_PORT, PEER_TIMEOUT_SECS,
+};
+use tracing::{debug, error, info, warn};
+
+use super::tox_manager::ToxEvent;
+
+/// How long a receive may block before the thread checks for stop/announce
+const RECV_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// A Toxcord instance seen on the local network
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct LanPeer {
+    pub address: String,
+    pub public_key: String,
+    pub name: String,
+    /// IP the announcement came from
+    pub ip: String,
+    #[serde(skip)]
+    last_seen: Instant,
+}
+
+/// Handle to the discovery thread. Dropping it stops discovery.
+pub struct LanDiscovery {
+    stop: Arc<AtomicBool>,
+    announcement: Arc<Mutex<LanAnnouncement>>,
+    peers: Arc<Mutex<HashMap<String, LanPeer>>>,
+}
+
+impl LanDiscovery {
+    /// Bind the discovery port and start announcing
+    pub fn start(app_handle: AppHandle, announcement: LanAnnouncement) -> Result<Self, String> {
+        let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, LAN_DISCOVERY_PORT))
+            .map_err(|e| format!("Failed to bind LAN discovery port {LAN_DISCOVERY_PORT}: {e}"))?;
+        socket
+            .set_broadcast(true)
+            .map_err(|e| format!("Failed to enable broadcast: {e}"))?;
+        socket
+            .set_read_timeout(Some(RECV_TIMEOUT))
+            .map_err(|e| format!("Failed to set socket timeout: {e}"))?;
+
+        let discovery = Self {
+            stop: Arc::new(AtomicBool::new(false)),
+            announcement: Arc::new(Mutex::new(announcement)),
+            peers: Arc::new(Mutex::new(HashMap::new())),
+        };
+
+        let stop = discovery.stop.clone();
+        let announcement = discovery.announcement.clone();
+        let peers = discovery.peers.clone();
+        std::thread::Builder::new()
+            .name("lan-discovery".into())
+            .spawn(move || run_discovery(socket, app_handle, stop, announcement, peers))
+            .map_err(|e| format!("Failed to spawn LAN discovery thread: {e}"))?;
+
+        info!("LAN discovery started on port {LAN_DISCOVERY_PORT}");
+        Ok(discovery)
+    }
+
+    /// Change what we announce (after a name or nospam change)
+    pub fn set_announcement(&self, announcement: LanAnnouncement) {
+        if let Ok(mut current) = self.announcement.lock() {
+            *current = announcement;
+        }
+    }
+
+    /// Peers currently on the network, sorted by name
+    pub fn peers(&self) -> Vec<LanPeer> {
+        let mut peers: Vec<LanPeer> = self
+            .peers
+            .lock()
+            .map(|p| p.values().cloned().collect())
+            .unwrap_or_default();
+        peers.sort_by_key(|p| p.name.to_lowercase());
+        peers
+    }
+
+    /// Address a peer announced, by public key
+    pub fn peer_address(&self, public_key: &str) -> Option<String> {
+        let peers = self.peers.lock().ok()?;
+        peers.get(&public_key.to_uppercase()).map(|p| p.address.clone())
+    }
+}
+
+impl Drop for LanDiscovery {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+fn run_discovery(
+    socket: UdpSocket,
+    app_handle: AppHandle,
+    stop: Arc<AtomicBool>,
+    announcement: Arc<Mutex<LanAnnouncement>>,
+    peers: Arc<Mutex<HashMap<String, LanPeer>>>,
+) {
+    let broadcast = SocketAddr::from((Ipv4Addr::BROADCAST, LAN_DISCOVERY_PORT));
+    let interval = Duration::from_secs(ANNOUNCE_INTERVAL_SECS);
+    let timeout = Duration::from_secs(PEER_TIMEOUT_SECS);
+    let mut last_announce: Option<Instant> = None;
+    let mut buf = [0u8; 1024];
+
+    while !stop.load(Ordering::Relaxed) {
+        let own = match announcement.lock() {
+            Ok(a) => a.clone(),
+            Err(_) => break,
+        };
+
+        if last_announce.is_none_or(|t| t.elapsed() >= interval) {
+            if let Err(e) = socket.send_to(&own.to_bytes(), broadcast) {
+                warn!("Failed to send LAN announcement: {e}");
+            }
+            last_announce = Some(Instant::now());
+        }
+
+        match socket.recv_from(&mut buf) {
+            Ok((len, from)) => {
+                let Some(peer) = LanAnnouncement::from_bytes(&buf[..len]) else {
+                    continue;
+                };
+                let public_key = peer.public_key();
+                if public_key == own.public_key() {
+                    continue;
+                }
+                let Ok(mut peers) = peers.lock() else {
+                    break;
+                };
+                let is_new = !peers.contains_key(&public_key);
+                let entry = LanPeer {
+                    address: peer.address.to_uppercase(),
+                    public_key: public_key.clone(),
+                    name: peer.name,
+                    ip: from.ip().to_string(),
+                    last_seen: Instant::now(),
+                };
+                if is_new {
+                    debug!("LAN peer found: {} at {}", entry.name, entry.ip);
+                    if let Err(e) = app_handle.emit("tox://event", &ToxEvent::LanPeerFound {
+                        address: entry.address.clone(),
+                        public_key: entry.public_key.clone(),
+                        name: entry.name.clone(),
+                    }) {
+                        error!("Failed to emit LAN peer event: {e}");
+                    }
+                }
+                peers.insert(public_key, entry);
+            }
+            Err(e) if matches!(e.kind(), std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut) => {}
+            Err(e) => {
+                warn!("LAN discovery receive failed: {e}");
+                std::thread::sleep(RECV_TIMEOUT);
+            }
+        }
+
+        // Forget peers that stopped announcing
+        let Ok(mut peers) = peers.lock() else {
+            break;
+        };
+        let expired: Vec<String> = peers
+            .iter()
+            .filter(|(_, p)| p.last_seen.elapsed() > timeout)
+            .map(|(pk, _)| pk.clone())
+            .collect();
+        for public_key in expired {
+            peers.remove(&public_key);
+            if let Err(e) = app_handle.emit("tox://event", &ToxEvent::LanPeerLost { public_key }) {
+                error!("Failed to emit LAN peer event: {e}");
+            }
+        }
+    }
+
+    info!("LAN discovery stopped");
+}
@@ -1,4 +1,19 @@
 pub mod av_manager;
+pub mod bot_api;
+pub mod bridge_manager;
+pub mod command_palette;
+pub mod device_sync;
+pub mod event_replay;
+pub mod file_manager;
+pub mod guild_history;
 pub mod guild_manager;
 pub mod i2p_manager;
+pub mod incognito;
+pub mod lan_discovery;
+pub mod power_saving;
+pub mod rich_presence;
+pub mod shortcut_manager;
+pub mod stickers;
+pub mod supervisor;
 pub mod tox_manager;
+pub mod usage_stats;
@@ -0,0 +1,248 @@
+//! Power saving for laptops.
+//!
+//! Toxcore wants iterating every few dozen milliseconds, which keeps the CPU
+//! from ever settling. While power saving and idle (no calls, nothing sent or
+//! received for a while) the tox thread iterates about once a second, tries
+//! the bootstrap nodes again less often while offline, and writes queued
+//! messages and the profile in bigger, rarer batches.
+
+use std::time::{Duration, Instant};
+
+use tracing::info;
+
+/// When power saving kicks in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PowerSavingMode {
+    Off,
+    /// While the computer runs on its battery
+    #[default]
+    OnBattery,
+    Always,
+}
+
+/// How long after the last message or call before we count as idle
+const IDLE_AFTER: Duration = Duration::from_secs(2 * 60);
+
+/// Least time between iterations while idle
+const IDLE_ITERATION_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How often the battery is checked in `OnBattery` mode
+const BATTERY_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Time between bootstrap attempts while offline from the DHT
+const REBOOTSTRAP_INTERVAL: Duration = Duration::from_secs(60);
+const POWER_SAVING_REBOOTSTRAP_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Time between writes of queued messages
+const WRITE_FLUSH_INTERVAL: Duration = Duration::from_millis(100);
+const IDLE_WRITE_FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Least time between batched profile saves
+const PROFILE_SAVE_INTERVAL: Duration = Duration::from_secs(10);
+const IDLE_PROFILE_SAVE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Decides the tox thread's pace from the mode, the battery and activity
+pub struct PowerSaver {
+    mode: PowerSavingMode,
+    on_battery: bool,
+    last_battery_check: Option<Instant>,
+    last_activity: Instant,
+    in_call: bool,
+}
+
+impl PowerSaver {
+    pub fn new(mode: PowerSavingMode) -> Self {
+        Self {
+            mode,
+            on_battery: false,
+            last_battery_check: None,
+            last_activity: Instant::now(),
+            in_call: false,
+        }
+    }
+
+    pub fn set_mode(&mut self, mode: PowerSavingMode) {
+        info!("Power saving mode: {mode:?}");
+        self.mode = mode;
+        // Check the battery right away rather than on the old schedule
+        self.last_battery_check = None;
+    }
+
+    /// A message was sent or received
+    pub fn activity(&mut self) {
+        self.last_activity = Instant::now();
+    }
+
+    /// Check the battery when it's due and note whether a call is going on.
+    /// Called every iteration.
+    pub fn update(&mut self, in_call: bool) {
+        if in_call || self.in_call {
+            self.last_activity = Instant::now();
+        }
+        self.in_call = in_call;
+
+        if self.mode != PowerSavingMode::OnBattery
+            || self.last_battery_check.is_some_and(|checked| checked.elapsed() < BATTERY_CHECK_INTERVAL)
+        {
+            return;
+        }
+        self.last_battery_check = Some(Instant::now());
+        let on_battery = on_battery();
+        if on_battery != self.on_battery {
+            info!("{}", if on_battery { "Running on battery" } else { "Running on mains power" });
+            self.on_battery = on_battery;
+        }
+    }
+
+    /// Whether the mode has power saving on right now
+    pub fn saving(&self) -> bool {
+        match self.mode {
+            PowerSavingMode::Off => false,
+            PowerSavingMode::OnBattery => self.on_battery,
+            PowerSavingMode::Always => true,
+        }
+    }
+
+    /// Power saving, with no call and no messages for a while
+    pub fn idle(&self) -> bool {
+        self.saving() && !self.in_call && self.last_activity.elapsed() >= IDLE_AFTER
+    }
+
+    /// How long to wait before the next iteration, given toxcore's own
+    /// recommendation
+    pub fn iteration_interval(&self, recommended: Duration) -> Duration {
+        if self.idle() {
+            recommended.max(IDLE_ITERATION_INTERVAL)
+        } else {
+            recommended
+        }
+    }
+
+    pub fn rebootstrap_interval(&self) -> Duration {
+        if self.saving() {
+            POWER_SAVING_REBOOTSTRAP_INTERVAL
+        } else {
+            REBOOTSTRAP_INTERVAL
+        }
+    }
+
+    pub fn write_flush_interval(&self) -> Duration {
+        if self.idle() {
+            IDLE_WRITE_FLUSH_INTERVAL
+        } else {
+            WRITE_FLUSH_INTERVAL
+        }
+    }
+
+    pub fn profile_save_interval(&self) -> Duration {
+        if self.idle() {
+            IDLE_PROFILE_SAVE_INTERVAL
+        } else {
+            PROFILE_SAVE_INTERVAL
+        }
+    }
+}
+
+/// Whether the computer is running on its battery. Computers without one,
+/// and systems we can't ask, count as on mains power.
+#[cfg(target_os = "linux")]
+pub fn on_battery() -> bool {
+    let Ok(supplies) = std::fs::read_dir("/sys/class/power_supply") else {
+        return false;
+    };
+    let mut discharging = false;
+    for supply in supplies.flatten() {
+        let path = supply.path();
+        let read = |name: &str| std::fs::read_to_string(path.join(name)).unwrap_or_default().trim().to_string();
+        match read("type").as_str() {
+            "Mains" | "USB" if read("online") == "1" => return false,
+            // Batteries of mice and other devices have the "Device" scope
+            "Battery" if read("scope") != "Device" => discharging |= read("status") == "Discharging",
+            _ => {}
+        }
+    }
+    discharging
+}
+
+#[cfg(target_os = "macos")]
+pub fn on_battery() -> bool {
+    std::process::Command::new("pmset")
+        .args(["-g", "batt"])
+        .output()
+        .is_ok_and(|output| String::from_utf8_lossy(&output.stdout).contains("'Battery Power'"))
+}
+
+#[cfg(target_os = "windows")]
+pub fn on_battery() -> bool {
+    // SYSTEM_POWER_STATUS. Only the AC line status is read; the other
+    // fields are there for the C layout Windows writes into.
+    #[repr(C)]
+    #[derive(Default)]
+    struct SystemPowerStatus {
+        ac_line_status: u8,
+        _battery_flag: u8,
+        _battery_life_percent: u8,
+        _system_status_flag: u8,
+        _battery_life_time: u32,
+        _battery_full_life_time: u32,
+    }
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetSystemPowerStatus(status: *mut SystemPowerStatus) -> i32;
+    }
+
+    let mut status = SystemPowerStatus::default();
+    // SAFETY: the struct matches SYSTEM_POWER_STATUS and outlives the call
+    let ok = unsafe { GetSystemPowerStatus(&mut status) } != 0;
+    // 0 is offline, 1 online and 255 unknown
+    ok && status.ac_line_status == 0
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+pub fn on_battery() -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_power_saving_pace() {
+        let recommended = Duration::from_millis(50);
+
+        let mut saver = PowerSaver::new(PowerSavingMode::Off);
+        saver.last_activity -= IDLE_AFTER;
+        assert!(!saver.saving());
+        assert_eq!(saver.iteration_interval(recommended), recommended);
+        assert_eq!(saver.rebootstrap_interval(), REBOOTSTRAP_INTERVAL);
+
+        saver.set_mode(PowerSavingMode::Always);
+        assert!(saver.idle());
+        assert_eq!(saver.iteration_interval(recommended), IDLE_ITERATION_INTERVAL);
+        assert_eq!(saver.rebootstrap_interval(), POWER_SAVING_REBOOTSTRAP_INTERVAL);
+        assert_eq!(saver.write_flush_interval(), IDLE_WRITE_FLUSH_INTERVAL);
+        assert_eq!(saver.profile_save_interval(), IDLE_PROFILE_SAVE_INTERVAL);
+
+        // A message brings back the normal pace, but not normal bootstrapping
+        saver.activity();
+        assert!(saver.saving() && !saver.idle());
+        assert_eq!(saver.iteration_interval(recommended), recommended);
+        assert_eq!(saver.rebootstrap_interval(), POWER_SAVING_REBOOTSTRAP_INTERVAL);
+
+        // So does a call, for as long as it lasts
+        saver.last_activity -= IDLE_AFTER;
+        saver.update(true);
+        assert!(!saver.idle());
+        saver.last_activity -= IDLE_AFTER;
+        assert!(!saver.idle());
+
+        // The call's end counts as activity
+        saver.update(false);
+        assert!(!saver.idle());
+        saver.last_activity -= IDLE_AFTER;
+        assert!(saver.idle());
+    }
+}
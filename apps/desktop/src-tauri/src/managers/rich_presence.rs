@@ -0,0 +1,209 @@
+//! Rich presence
+//!
+//! Our activity and custom status are sent to every online friend and every
+//! group when they change, and again to each friend or group peer as they
+//! come online. Those received from peers are cached by public key so friend
+//! and member listings can include them.
+//!
+//! Activities are live state and never persisted; peers resend theirs
+//! whenever we reconnect. Our custom status is saved with the profile and
+//! cleared by the tox thread once it expires.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use toxcord_protocol::rich_presence::{Activity, CustomStatus, PresencePacket};
+use toxcord_tox::ToxInstance;
+use tracing::debug;
+
+use crate::db::MessageStore;
+
+/// Profile setting holding our custom status as JSON
+pub const CUSTOM_STATUS_SETTING: &str = "custom_status";
+
+/// Work queued by callbacks for the tox thread
+pub enum PresenceAction {
+    /// A friend came online
+    FriendOnline(u32),
+    /// A peer joined a group (group number, peer ID)
+    PeerJoined(u32, u32),
+}
+
+#[derive(Default)]
+struct PresenceState {
+    own_activity: Option<Activity>,
+    own_status: Option<CustomStatus>,
+    /// Peer activities by uppercase public key
+    activities: HashMap<String, Activity>,
+    /// Peer custom statuses by uppercase public key
+    statuses: HashMap<String, CustomStatus>,
+}
+
+/// Shared between the tox thread, its callbacks and commands
+#[derive(Clone, Default)]
+pub struct RichPresence {
+    state: Arc<Mutex<PresenceState>>,
+}
+
+impl RichPresence {
+    pub fn own_activity(&self) -> Option<Activity> {
+        self.state.lock().ok()?.own_activity.clone()
+    }
+
+    /// Cached activity of a friend or group peer
+    pub fn activity(&self, public_key: &str) -> Option<Activity> {
+        self.state.lock().ok()?.activities.get(&public_key.to_uppercase()).cloned()
+    }
+
+    /// Cache a peer's activity. Returns true if it changed.
+    pub fn set_activity(&self, public_key: &str, activity: Option<Activity>) -> bool {
+        let Ok(mut state) = self.state.lock() else {
+            return false;
+        };
+        let public_key = public_key.to_uppercase();
+        let changed = state.activities.get(&public_key) != activity.as_ref();
+        match activity {
+            Some(activity) => state.activities.insert(public_key, activity),
+            None => state.activities.remove(&public_key),
+        };
+        changed
+    }
+
+    pub fn own_custom_status(&self) -> Option<CustomStatus> {
+        self.state.lock().ok()?.own_status.clone()
+    }
+
+    /// Cached custom status of a friend or group peer, unless it has expired
+    pub fn custom_status(&self, public_key: &str) -> Option<CustomStatus> {
+        let now = chrono::Utc::now().timestamp();
+        let state = self.state.lock().ok()?;
+        state.statuses.get(&public_key.to_uppercase()).filter(|s| !s.is_expired(now)).cloned()
+    }
+
+    /// Cache a peer's custom status. Returns true if it changed.
+    pub fn set_custom_status(&self, public_key: &str, status: Option<CustomStatus>) -> bool {
+        let Ok(mut state) = self.state.lock() else {
+            return false;
+        };
+        let public_key = public_key.to_uppercase();
+        let status = status.filter(|s| !s.is_expired(chrono::Utc::now().timestamp()));
+        let changed = state.statuses.get(&public_key) != status.as_ref();
+        match status {
+            Some(status) => state.statuses.insert(public_key, status),
+            None => state.statuses.remove(&public_key),
+        };
+        changed
+    }
+
+    /// Restore our saved custom status when the profile loads
+    pub fn load_own_custom_status(&self, store: &MessageStore) -> Result<(), String> {
+        let status = match store.get_setting(CUSTOM_STATUS_SETTING)? {
+            Some(json) => serde_json::from_str(&json).map_err(|e| format!("Failed to parse custom status: {e}"))?,
+            None => None,
+        };
+        if let Ok(mut state) = self.state.lock() {
+            state.own_status = status;
+        }
+        Ok(())
+    }
+
+    /// Set our activity and send it to every online friend and group (tox thread only)
+    pub fn set_own_activity(&self, tox: &ToxInstance, activity: Option<Activity>) {
+        if let Ok(mut state) = self.state.lock() {
+            state.own_activity = activity.clone();
+        }
+        broadcast(tox, &PresencePacket::Activity { activity });
+    }
+
+    /// Save our custom status and send it to every online friend and group (tox thread only)
+    pub fn set_own_custom_status(
+        &self,
+        tox: &ToxInstance,
+        store: &MessageStore,
+        status: Option<CustomStatus>,
+    ) -> Result<(), String> {
+        let json = serde_json::to_string(&status).map_err(|e| format!("Failed to serialize custom status: {e}"))?;
+        store.set_setting(CUSTOM_STATUS_SETTING, &json)?;
+        if let Ok(mut state) = self.state.lock() {
+            state.own_status = status.clone();
+        }
+        broadcast(tox, &PresencePacket::CustomStatus { status });
+        Ok(())
+    }
+
+    /// Clear custom statuses that have expired (tox thread only). Ours is also
+    /// cleared for peers. Returns the public keys whose status was cleared.
+    pub fn expire_custom_statuses(&self, tox: &ToxInstance, store: &MessageStore) -> Vec<String> {
+        let now = chrono::Utc::now().timestamp();
+        let (own_expired, mut expired) = {
+            let Ok(mut state) = self.state.lock() else {
+                return Vec::new();
+            };
+            let own_expired = state.own_status.as_ref().is_some_and(|s| s.is_expired(now));
+            let expired: Vec<String> = state
+                .statuses
+                .iter()
+                .filter(|(_, s)| s.is_expired(now))
+                .map(|(public_key, _)| public_key.clone())
+                .collect();
+            for public_key in &expired {
+                state.statuses.remove(public_key);
+            }
+            (own_expired, expired)
+        };
+
+        if own_expired {
+            match self.set_own_custom_status(tox, store, None) {
+                Ok(()) => expired.push(tox.self_public_key().0.to_uppercase()),
+                Err(e) => debug!("Failed to clear expired custom status: {e}"),
+            }
+        }
+        expired
+    }
+
+    /// Send our activity and custom status to a peer that just came online (tox thread only)
+    pub fn handle(&self, tox: &ToxInstance, action: PresenceAction) {
+        // Peers start without either for us, so there's nothing to clear
+        let (activity, status) = match self.state.lock() {
+            Ok(state) => (state.own_activity.clone(), state.own_status.clone()),
+            Err(_) => return,
+        };
+        let packets = activity
+            .map(|a| PresencePacket::Activity { activity: Some(a) })
+            .into_iter()
+            .chain(status.map(|s| PresencePacket::CustomStatus { status: Some(s) }));
+
+        for packet in packets {
+            let result = match action {
+                PresenceAction::FriendOnline(friend_number) => {
+                    tox.friend_send_lossless_packet(friend_number, &packet.to_friend_packet())
+                }
+                PresenceAction::PeerJoined(group_number, peer_id) => {
+                    tox.group_send_custom_private_packet(group_number, peer_id, true, &packet.to_group_packet())
+                }
+            };
+            if let Err(e) = result {
+                debug!("Failed to send presence: {e}");
+            }
+        }
+    }
+}
+
+/// Send a presence packet to every online friend and every group
+fn broadcast(tox: &ToxInstance, packet: &PresencePacket) {
+    let friend_packet = packet.to_friend_packet();
+    for friend_number in tox.friend_list() {
+        if tox.friend_connection_status(friend_number).is_connected() {
+            if let Err(e) = tox.friend_send_lossless_packet(friend_number, &friend_packet) {
+                debug!("Failed to send presence to friend {friend_number}: {e}");
+            }
+        }
+    }
+
+    let group_packet = packet.to_group_packet();
+    for group_number in tox.group_list() {
+        if let Err(e) = tox.group_send_custom_packet(group_number, true, &group_packet) {
+            debug!("Failed to send presence to group {group_number}: {e}");
+        }
+    }
+}
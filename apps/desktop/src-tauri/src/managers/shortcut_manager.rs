@@ -0,0 +1,134 @@
+//! Global keyboard shortcuts.
+//!
+//! Registers the bindings from `AppSettings` with the OS through the
+//! global-shortcut plugin so they work while the window is unfocused or
+//! hidden in the tray. Actions are forwarded to the Tox thread.
+
+use std::str::FromStr;
+
+use tauri::{AppHandle, Manager};
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut, ShortcutState};
+use tracing::{error, info, warn};
+
+use super::av_manager::CallStatus;
+use crate::commands::calls::call_preferences;
+use crate::settings::ShortcutBindings;
+use crate::AppState;
+
+/// Action triggered by a global shortcut
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShortcutAction {
+    ToggleMute,
+    ToggleDeafen,
+    PushToTalk,
+    AnswerOrHangup,
+}
+
+pub struct ShortcutManager;
+
+impl ShortcutManager {
+    /// Check that every binding parses as an accelerator
+    pub fn validate(bindings: &ShortcutBindings) -> Result<(), String> {
+        for (_, accelerator) in Self::bound(bindings) {
+            Shortcut::from_str(accelerator)
+                .map_err(|e| format!("Invalid shortcut '{accelerator}': {e}"))?;
+        }
+        Ok(())
+    }
+
+    /// Replace all registered shortcuts with `bindings`.
+    /// Keeps going if one binding fails (e.g. taken by another app) and reports it.
+    pub fn apply(app: &AppHandle, bindings: &ShortcutBindings) -> Result<(), String> {
+        let global_shortcut = app.global_shortcut();
+        if let Err(e) = global_shortcut.unregister_all() {
+            warn!("Failed to unregister shortcuts: {e}");
+        }
+
+        let mut failed = Vec::new();
+        for (action, accelerator) in Self::bound(bindings) {
+            let result = global_shortcut.on_shortcut(accelerator, move |app, _shortcut, event| {
+                Self::handle(app, action, event.state);
+            });
+            match result {
+                Ok(()) => info!("Registered global shortcut {accelerator} for {action:?}"),
+                Err(e) => {
+                    warn!("Failed to register global shortcut {accelerator}: {e}");
+                    failed.push(format!("{accelerator} ({e})"));
+                }
+            }
+        }
+
+        if failed.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("Failed to register shortcuts: {}", failed.join(", ")))
+        }
+    }
+
+    fn bound(bindings: &ShortcutBindings) -> Vec<(ShortcutAction, &str)> {
+        [
+            (ShortcutAction::ToggleMute, &bindings.toggle_mute),
+            (ShortcutAction::ToggleDeafen, &bindings.toggle_deafen),
+            (ShortcutAction::PushToTalk, &bindings.push_to_talk),
+            (ShortcutAction::AnswerOrHangup, &bindings.answer_or_hangup),
+        ]
+        .into_iter()
+        .filter_map(|(action, accel)| {
+            accel
+                .as_deref()
+                .filter(|a| !a.trim().is_empty())
+                .map(|a| (action, a))
+        })
+        .collect()
+    }
+
+    fn handle(app: &AppHandle, action: ShortcutAction, key_state: ShortcutState) {
+        // Only push-to-talk cares about key release
+        if key_state == ShortcutState::Released && action != ShortcutAction::PushToTalk {
+            return;
+        }
+
+        let app = app.clone();
+        tauri::async_runtime::spawn(async move {
+            let state = app.state::<AppState>();
+            let guard = state.tox_manager.lock().await;
+            let Some(manager) = guard.as_ref() else {
+                return;
+            };
+            let mgr = manager.lock().await;
+
+            let result = match action {
+                ShortcutAction::ToggleMute => match mgr.get_global_audio_state().await {
+                    Ok((muted, _)) => mgr.set_global_mute(!muted).await,
+                    Err(e) => Err(e),
+                },
+                ShortcutAction::ToggleDeafen => match mgr.get_global_audio_state().await {
+                    Ok((_, deafened)) => mgr.set_global_deafen(!deafened).await,
+                    Err(e) => Err(e),
+                },
+                ShortcutAction::PushToTalk => {
+                    mgr.set_global_mute(key_state == ShortcutState::Released).await
+                }
+                ShortcutAction::AnswerOrHangup => match mgr.list_calls().await {
+                    Ok(calls) => {
+                        if let Some(call) = calls.iter().find(|c| c.state == CallStatus::RingingIncoming) {
+                            match call_preferences(&state, call.friend_number).await {
+                                Ok(quality) => mgr.answer(call.friend_number, call.has_video, quality).await,
+                                Err(e) => Err(e),
+                            }
+                        } else if let Some(call) = calls.first() {
+                            mgr.hangup(call.friend_number).await
+                        } else {
+                            Ok(())
+                        }
+                    }
+                    Err(e) => Err(e),
+                },
+            };
+
+            if let Err(e) = result {
+                error!("Global shortcut {action:?} failed: {e}");
+            }
+        });
+    }
+}
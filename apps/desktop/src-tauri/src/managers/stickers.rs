@@ -0,0 +1,118 @@
+//! Sticker packs
+//!
+//! Packs are imported from image files, which go in the media cache as
+//! stickers so they're kept until the pack is removed. A sticker is sent as a
+//! reference to peers known to have its pack (see
+//! `toxcord_protocol::stickers`) and as an image file to anyone else.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use toxcord_core::assets::AssetKind;
+use toxcord_protocol::stickers::{self, Sticker, StickerPack, StickerRef, MAX_STICKER_SIZE};
+
+use crate::db::message_store::StickerPackRecord;
+use crate::db::MessageStore;
+use crate::managers::file_manager;
+
+/// What the frontend needs to show a sticker
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct StickerInfo {
+    pub pack_hash: String,
+    pub index: u32,
+    /// None if we don't have the pack
+    pub pack_name: Option<String>,
+    pub mime_type: Option<String>,
+    /// The cached image, None if we don't have the pack
+    pub path: Option<String>,
+}
+
+/// Install a pack from image files, in order
+pub fn import_pack(store: &Arc<MessageStore>, name: &str, paths: &[PathBuf]) -> Result<StickerPackRecord, String> {
+    let mut images = Vec::with_capacity(paths.len());
+    for path in paths {
+        let filename = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+        let mime_type = stickers::sticker_mime_type(filename)
+            .ok_or_else(|| format!("{filename} isn't a PNG, GIF or WebP image"))?;
+        let data = std::fs::read(path).map_err(|e| format!("Failed to read {filename}: {e}"))?;
+        if data.len() as u64 > MAX_STICKER_SIZE {
+            return Err(format!("{filename} is larger than {} KB", MAX_STICKER_SIZE / 1024));
+        }
+        images.push((data, mime_type));
+    }
+
+    let pack = StickerPack {
+        name: name.trim().to_string(),
+        stickers: images
+            .iter()
+            .map(|(data, mime_type)| Sticker {
+                hash: blake3::hash(data).to_hex().to_string(),
+                mime_type: mime_type.to_string(),
+            })
+            .collect(),
+    };
+    pack.validate()?;
+
+    let assets = file_manager::asset_store(store);
+    for (data, _) in &images {
+        assets.put(AssetKind::Sticker, data)?;
+    }
+    store.insert_sticker_pack(&pack)?;
+    store
+        .get_sticker_pack(&pack.hash())?
+        .ok_or_else(|| "Sticker pack was not saved".to_string())
+}
+
+/// Remove a pack and the images no other pack uses. Returns false if it wasn't installed.
+pub fn delete_pack(store: &Arc<MessageStore>, pack_hash: &str) -> Result<bool, String> {
+    let Some(pack) = store.get_sticker_pack(pack_hash)? else {
+        return Ok(false);
+    };
+    store.delete_sticker_pack(&pack.hash)?;
+
+    let still_used: Vec<String> = store
+        .get_sticker_packs()?
+        .into_iter()
+        .flat_map(|p| p.stickers)
+        .map(|s| s.hash)
+        .collect();
+    let assets = file_manager::asset_store(store);
+    for sticker in pack.stickers {
+        if !still_used.contains(&sticker.hash) {
+            assets.remove(&sticker.hash)?;
+        }
+    }
+    Ok(true)
+}
+
+/// The cached image and MIME type of one of our stickers
+pub fn image(store: &Arc<MessageStore>, reference: &StickerRef) -> Result<(PathBuf, String), String> {
+    let pack = store.get_sticker_pack(&reference.pack)?.ok_or("Sticker pack not installed")?;
+    let sticker = pack.stickers.get(reference.index as usize).ok_or("No such sticker in the pack")?;
+    let path = file_manager::asset_store(store)
+        .get(&sticker.hash)?
+        .ok_or("Sticker image is missing; import the pack again")?;
+    Ok((path, sticker.mime_type.clone()))
+}
+
+/// Look a sticker up for display
+pub fn resolve(store: &Arc<MessageStore>, reference: &StickerRef) -> StickerInfo {
+    let pack = store.get_sticker_pack(&reference.pack).ok().flatten();
+    let found = image(store, reference).ok();
+    StickerInfo {
+        pack_hash: reference.pack.clone(),
+        index: reference.index,
+        pack_name: pack.map(|p| p.name),
+        mime_type: found.as_ref().map(|(_, mime_type)| mime_type.clone()),
+        path: found.map(|(path, _)| path.to_string_lossy().to_string()),
+    }
+}
+
+/// Whether all of these peers are known to have a pack
+pub fn all_have_pack(store: &MessageStore, pack_hash: &str, public_keys: &[String]) -> Result<bool, String> {
+    let holders = store.get_sticker_pack_holders(pack_hash)?;
+    Ok(!public_keys.is_empty()
+        && public_keys
+            .iter()
+            .all(|pk| holders.iter().any(|h| h.eq_ignore_ascii_case(pk))))
+}
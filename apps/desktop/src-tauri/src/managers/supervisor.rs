@@ -0,0 +1,136 @@
+//! Tox thread supervision
+//!
+//! The tox thread runs under `run`, which starts it again from the saved
+//! profile if it panics, so a bug in one command doesn't leave us offline
+//! until the app restarts. The thread saves the profile as it unwinds.
+//!
+//! The thread beats a heartbeat every iteration and a watchdog tells the
+//! frontend when it stops, e.g. when a call blocks. A stalled thread still
+//! owns the Tox instance, so it can only be reported, not replaced.
+
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tauri::{AppHandle, Emitter};
+use tracing::{error, warn};
+
+use super::tox_manager::{NetworkMode, ToxEvent};
+
+/// Crashes within `CRASH_WINDOW` after which we stop restarting
+const MAX_CRASHES: usize = 5;
+const CRASH_WINDOW: Duration = Duration::from_secs(10 * 60);
+
+/// How long without a heartbeat before the thread counts as stalled
+const STALLED_AFTER: Duration = Duration::from_secs(30);
+const WATCHDOG_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Shared between the tox thread, its supervisor and the watchdog
+pub struct Supervision {
+    /// The profile password, kept current so a restart can decrypt the profile
+    password: Mutex<String>,
+    /// The network mode, kept current so a restart comes back in it
+    network_mode: Mutex<NetworkMode>,
+    last_beat: Mutex<Instant>,
+    stopped: AtomicBool,
+}
+
+impl Supervision {
+    pub fn new(password: &str, network_mode: NetworkMode) -> Self {
+        Self {
+            password: Mutex::new(password.to_string()),
+            network_mode: Mutex::new(network_mode),
+            last_beat: Mutex::new(Instant::now()),
+            stopped: AtomicBool::new(false),
+        }
+    }
+
+    pub fn password(&self) -> String {
+        self.password.lock().map(|p| p.clone()).unwrap_or_default()
+    }
+
+    pub fn set_password(&self, password: &str) {
+        if let Ok(mut current) = self.password.lock() {
+            *current = password.to_string();
+        }
+    }
+
+    pub fn network_mode(&self) -> NetworkMode {
+        self.network_mode.lock().map(|m| *m).unwrap_or_default()
+    }
+
+    pub fn set_network_mode(&self, network_mode: NetworkMode) {
+        if let Ok(mut current) = self.network_mode.lock() {
+            *current = network_mode;
+        }
+    }
+
+    /// Called by the tox thread every iteration
+    pub fn beat(&self) {
+        if let Ok(mut last_beat) = self.last_beat.lock() {
+            *last_beat = Instant::now();
+        }
+    }
+
+    fn since_last_beat(&self) -> Duration {
+        self.last_beat.lock().map(|t| t.elapsed()).unwrap_or_default()
+    }
+}
+
+/// Run the tox thread until it returns, restarting it when it panics.
+/// `thread` gets whether this run is a restart.
+pub fn run(app_handle: &AppHandle, supervision: Arc<Supervision>, mut thread: impl FnMut(bool)) {
+    spawn_watchdog(app_handle.clone(), supervision.clone());
+
+    let mut crashes: Vec<Instant> = Vec::new();
+    let mut restart = false;
+    loop {
+        supervision.beat();
+        let Err(payload) = std::panic::catch_unwind(AssertUnwindSafe(|| thread(restart))) else {
+            break;
+        };
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_default();
+        error!("Tox thread crashed: {message}");
+
+        crashes.retain(|at| at.elapsed() < CRASH_WINDOW);
+        crashes.push(Instant::now());
+        if crashes.len() > MAX_CRASHES {
+            error!("Tox thread crashed {} times in {CRASH_WINDOW:?}, not restarting it", crashes.len());
+            emit(app_handle, &ToxEvent::ToxThreadStopped);
+            break;
+        }
+
+        warn!("Restarting the tox thread");
+        emit(app_handle, &ToxEvent::ToxThreadRestarted { crashes: crashes.len() });
+        restart = true;
+    }
+    supervision.stopped.store(true, Ordering::SeqCst);
+}
+
+fn spawn_watchdog(app_handle: AppHandle, supervision: Arc<Supervision>) {
+    std::thread::spawn(move || {
+        let mut stalled = false;
+        while !supervision.stopped.load(Ordering::SeqCst) {
+            std::thread::sleep(WATCHDOG_INTERVAL);
+            let now_stalled = supervision.since_last_beat() >= STALLED_AFTER;
+            if now_stalled != stalled {
+                stalled = now_stalled;
+                if stalled {
+                    warn!("Tox thread hasn't run for {STALLED_AFTER:?}");
+                }
+                emit(&app_handle, &ToxEvent::ToxThreadStalled { stalled });
+            }
+        }
+    });
+}
+
+fn emit(app_handle: &AppHandle, event: &ToxEvent) {
+    if let Err(e) = app_handle.emit("tox://event", event) {
+        error!("Failed to emit tox thread event: {e}");
+    }
+}
@@ -1,17 +1,52 @@
+use std::collections::HashMap;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
 
 use tauri::{AppHandle, Emitter, Manager};
 use tokio::sync::{mpsc, oneshot, Mutex};
 use tracing::{debug, error, info, warn};
 
+use toxcord_core::conversation_lock::ConversationLocks;
+use toxcord_core::incoming::{self, FriendText, FriendTextError, GroupSender, GroupText, IncomingTexts};
+use toxcord_core::outgoing::{sign_group_text, text_id};
+use toxcord_core::profile_dirs::{self, ProfileDir};
+use toxcord_core::profile_file;
+use toxcord_protocol::codec::{split_tagged, TOX_MAX_MESSAGE_LENGTH};
+use toxcord_protocol::file_share::{self, FileChunk, FileOffer, FileRequest, FileSharePacket};
+use toxcord_protocol::fingerprint::public_key_from_hex;
+use toxcord_protocol::group_invites::InviteNotice;
+use toxcord_protocol::device_sync::SyncMessage;
+use toxcord_protocol::disappearing::TimerPacket;
+use toxcord_protocol::envelope;
+use toxcord_protocol::guild_events::{self, EventPacket};
+use toxcord_protocol::guild_layout::GuildLayout;
+use toxcord_protocol::guild_manifest::{BaseRole, GuildManifest};
+use toxcord_protocol::history::HistoryPacket;
+use toxcord_protocol::markdown;
+use toxcord_protocol::polls::PollPacket;
+use toxcord_protocol::purge::PurgePacket;
+use toxcord_protocol::rich_presence::{Activity, CustomStatus, PresencePacket};
+use toxcord_protocol::status_notes::StatusNote;
 use toxcord_tox::callbacks::ToxEventHandler;
-use toxcord_tox::tox::{decrypt_savedata, default_bootstrap_nodes, encrypt_savedata, is_data_encrypted};
+use toxcord_tox::tox::{decrypt_savedata, default_bootstrap_nodes, is_data_encrypted};
 use toxcord_tox::types::*;
 use toxcord_tox::{AudioFrame, ProxyType, ToxAvEventHandler, ToxAvInstance, ToxInstance, ToxOptionsBuilder, VideoFrame};
 
-use super::av_manager::{AvManager, CallState, CallStatus, TauriAvEventHandler, ToxAvEvent};
+use super::av_manager::{AvManager, CallState, CallStatus, StallReason, TauriAvEventHandler, ToxAvEvent, VideoStream};
+use super::device_sync::{self, DeviceSyncAction};
+use super::guild_history::{self, Backfills, HistoryAction};
+use super::file_manager::{self, FileAction, FileManager, GroupChunkResult, GroupDownload, IncomingTransfer};
+use super::incognito::Incognito;
+use super::power_saving::{PowerSaver, PowerSavingMode};
+use super::rich_presence::{PresenceAction, RichPresence};
+use super::stickers::{self, StickerInfo};
+use super::supervisor::{self, Supervision};
+use crate::accessibility::Conversation;
 use crate::audio::{AudioCapture, AudioMixer, AudioPlayback};
+use crate::error::ToxcordError;
+use crate::video::frames::VideoSource;
 use crate::video::{ScreenCapture, VideoCapture, VideoCaptureError, VideoFrameData};
 use crate::AppState;
 
@@ -66,6 +101,18 @@ impl ProxyConfig {
         }
     }
 
+    /// The proxy as a URL for HTTP clients. SOCKS uses `socks5h` so host names
+    /// are resolved by the proxy, as they must be for Tor and I2P.
+    pub fn url(&self) -> Option<String> {
+        let host = self.host.as_ref()?;
+        let scheme = match self.proxy_type {
+            ProxyType::Socks5 => "socks5h",
+            ProxyType::Http => "http",
+            ProxyType::None => return None,
+        };
+        Some(format!("{scheme}://{host}:{}", self.port))
+    }
+
     /// Create proxy config from embedded I2P router
     #[cfg(feature = "i2p")]
     pub fn from_i2p(i2p_manager: &super::i2p_manager::I2pManager) -> Self {
@@ -73,39 +120,99 @@ impl ProxyConfig {
     }
 }
 
+/// How the Tox instance reaches the network
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NetworkMode {
+    /// Bootstrap to the public DHT and its TCP relays
+    #[default]
+    Normal,
+    /// Only peers found by local discovery; no bootstrap, relays or proxy
+    LanOnly,
+    /// The tox thread stops iterating, so nothing is sent or received
+    Offline,
+}
+
+use crate::db::message_store::{CallPreferences, GroupInviteRecord};
 use crate::db::MessageStore;
 
 /// Commands sent to the Tox thread via mpsc channel
 pub enum ToxCommand {
     GetAddress(oneshot::Sender<ToxAddress>),
+    GetNospam(oneshot::Sender<u32>),
+    /// The profile's secret key, for its recovery phrase
+    GetSecretKey(oneshot::Sender<[u8; 32]>),
+    /// Change the nospam; replies with the new address
+    SetNospam(u32, oneshot::Sender<Result<ToxAddress, String>>),
+    /// Save the profile encrypted with a new password, used for every later save
+    ChangePassword(String, oneshot::Sender<Result<(), String>>),
     GetConnectionStatus(oneshot::Sender<ConnectionStatus>),
     GetProfileInfo(oneshot::Sender<ProfileInfo>),
+    GetDiagnostics(oneshot::Sender<ToxDiagnostics>),
+    GetDhtInfo(oneshot::Sender<DhtInfo>),
     SetName(String, oneshot::Sender<Result<(), String>>),
     SetStatusMessage(String, oneshot::Sender<Result<(), String>>),
+    SetStatus(UserStatus, oneshot::Sender<Result<(), String>>),
+    /// Set or clear our rich presence activity and broadcast it
+    SetActivity(Option<Activity>, oneshot::Sender<()>),
+    /// Set or clear our custom status, save it and broadcast it
+    SetCustomStatus(Option<CustomStatus>, oneshot::Sender<Result<(), String>>),
+    /// Post a status note to every friend, queueing it for those offline
+    PostStatusNote(String, oneshot::Sender<Result<StatusNote, String>>),
+    /// Set a friend conversation's disappearing timer in seconds (0 for off) and send it to them
+    SetDisappearingTimer(u32, u32, oneshot::Sender<Result<(), String>>),
     FriendAdd(String, String, oneshot::Sender<Result<u32, String>>),
     FriendAccept([u8; 32], oneshot::Sender<Result<u32, String>>),
     FriendDelete(u32, oneshot::Sender<Result<(), String>>),
     FriendList(oneshot::Sender<Vec<FriendInfo>>),
-    FriendSendMessage(u32, String, oneshot::Sender<Result<u32, String>>),
+    /// Nudge toxcore into reconnecting to an offline friend, or every
+    /// offline friend with None; replies with the friends it retried
+    RefreshFriendConnections(Option<u32>, oneshot::Sender<Result<Vec<u32>, ToxcordError>>),
+    /// Sync with a linked device now
+    SyncDevice(u32, oneshot::Sender<Result<(), String>>),
+    FriendSendMessage(u32, String, oneshot::Sender<Result<u32, ToxcordError>>),
     SetTyping(u32, bool, oneshot::Sender<Result<(), String>>),
+    /// Save the profile now, rather than with the next batched save
     SaveProfile(oneshot::Sender<Result<(), String>>),
+    /// The computer is about to sleep: save the profile and end calls
+    Suspend(oneshot::Sender<()>),
+    /// The computer woke up: end calls left over and bootstrap again
+    Resume(oneshot::Sender<()>),
+    /// Switch network mode. Leaving the public DHT for LAN only restarts the
+    /// Tox instance, as toxcore can't forget the nodes it already knows.
+    SetNetworkMode(NetworkMode, oneshot::Sender<()>),
+    SetPowerSaving(PowerSavingMode, oneshot::Sender<()>),
     Shutdown(oneshot::Sender<()>),
     // Group commands
-    GroupNew(String, oneshot::Sender<Result<u32, String>>),
+    GroupNew(String, GroupPrivacyState, oneshot::Sender<Result<u32, String>>),
     GroupJoin([u8; 32], String, oneshot::Sender<Result<u32, String>>),
     GroupLeave(u32, oneshot::Sender<Result<(), String>>),
     GroupInviteFriend(u32, u32, oneshot::Sender<Result<(), String>>),
-    GroupInviteAccept(u32, Vec<u8>, oneshot::Sender<Result<u32, String>>),
-    GroupSendMessage(u32, String, oneshot::Sender<Result<u32, String>>),
+    // Friend number, invite data, group password (empty for none)
+    GroupInviteAccept(u32, Vec<u8>, String, oneshot::Sender<Result<u32, String>>),
+    GroupSendMessage(u32, String, oneshot::Sender<Result<u32, ToxcordError>>),
     GroupSendCustomPacket(u32, Vec<u8>, oneshot::Sender<Result<(), String>>),
     GroupGetList(oneshot::Sender<Vec<GroupInfo>>),
     GroupGetPeerList(u32, oneshot::Sender<Vec<GroupPeerInfo>>),
     GroupSetTopic(u32, String, oneshot::Sender<Result<(), String>>),
+    // Empty password removes it
+    GroupSetPassword(u32, String, oneshot::Sender<Result<(), String>>),
     GroupSetRole(u32, u32, u8, oneshot::Sender<Result<(), String>>),
     GroupKickPeer(u32, u32, oneshot::Sender<Result<(), String>>),
     GroupGetInfo(u32, oneshot::Sender<Result<GroupInfo, String>>),
     GroupGetSelfPk(u32, oneshot::Sender<Result<String, String>>),
+    GroupGetSelfRole(u32, oneshot::Sender<Result<GroupRole, String>>),
     GroupReconnect(u32, oneshot::Sender<Result<(), String>>),
+    // File transfer commands
+    /// Offer a file on disk to a friend; replies with the Tox file number
+    FileSend {
+        friend_number: u32,
+        transfer_id: String,
+        message_id: String,
+        filename: String,
+        path: PathBuf,
+        reply: oneshot::Sender<Result<u32, String>>,
+    },
     // ToxAV commands
     AvCall {
         friend_number: u32,
@@ -139,6 +246,15 @@ pub enum ToxCommand {
         friend_number: u32,
         reply: oneshot::Sender<Result<(), String>>,
     },
+    /// Move camera capture to another device without touching the calls
+    AvSwitchCamera {
+        device_index: Option<u32>,
+        reply: oneshot::Sender<Result<(), String>>,
+    },
+    AvSwitchAudioOutput {
+        device: Option<String>,
+        reply: oneshot::Sender<Result<(), String>>,
+    },
     AvSendAudioFrame {
         friend_number: u32,
         pcm: Vec<i16>,
@@ -150,6 +266,32 @@ pub enum ToxCommand {
         friend_number: u32,
         reply: oneshot::Sender<Option<CallState>>,
     },
+    /// Mute/unmute the microphone for all calls
+    AvSetGlobalMute {
+        muted: bool,
+        reply: oneshot::Sender<Result<(), String>>,
+    },
+    /// Deafen/undeafen incoming audio for all calls
+    AvSetGlobalDeafen {
+        deafened: bool,
+        reply: oneshot::Sender<Result<(), String>>,
+    },
+    /// Get global (muted, deafened) state
+    AvGetGlobalAudioState {
+        reply: oneshot::Sender<(bool, bool)>,
+    },
+    AvListCalls {
+        reply: oneshot::Sender<Vec<CallState>>,
+    },
+    AvListVideoStreams {
+        reply: oneshot::Sender<Vec<VideoStream>>,
+    },
+    /// Show or stop showing a peer's video
+    AvSetStreamSubscribed {
+        friend_number: u32,
+        subscribed: bool,
+        reply: oneshot::Sender<Result<(), String>>,
+    },
 }
 
 /// Events emitted to the frontend via Tauri
@@ -157,24 +299,83 @@ pub enum ToxCommand {
 #[serde(tag = "type", content = "data")]
 pub enum ToxEvent {
     ConnectionStatus { connected: bool, status: String },
+    SelfStatus { status: String },
+    // Our Tox address changed after a nospam rotation
+    SelfAddressChanged { address: String },
+    // The computer woke from sleep; friends and groups should be reloaded
+    Resumed { slept_secs: u64 },
+    // The network mode was switched: "normal", "lan_only" or "offline"
+    NetworkModeChanged { mode: NetworkMode },
     FriendRequest { public_key: String, message: String },
-    FriendMessage { friend_number: u32, message_type: String, message: String, id: String, timestamp: String },
+    // `formatted` is the parsed markdown of text messages (empty for files)
+    // `locked` when the message is encrypted with a conversation passphrase we haven't entered
+    // `expires_at` (Unix seconds) when the conversation has disappearing messages on
+    // `incognito` when the message wasn't stored
+    // `sticker` for sticker references (message_type "sticker")
+    FriendMessage { friend_number: u32, message_type: String, message: String, id: String, timestamp: String, formatted: Vec<markdown::Node>, locked: bool, expires_at: Option<i64>, incognito: bool, sticker: Option<StickerInfo> },
     FriendName { friend_number: u32, name: String },
     FriendStatusMessage { friend_number: u32, message: String },
     FriendStatus { friend_number: u32, status: String },
-    FriendConnectionStatus { friend_number: u32, connected: bool, status: String },
+    // A friend number now has a different public key than we had on record
+    FriendKeyChanged { friend_number: u32, old_public_key: String, new_public_key: String, was_verified: bool },
+    // `connection_type` is "direct", "relayed" or "none"
+    FriendConnectionStatus { friend_number: u32, connected: bool, status: String, connection_type: String },
     FriendTyping { friend_number: u32, is_typing: bool },
     // Group events
-    GroupInvite { friend_number: u32, invite_data: Vec<u8>, group_name: String },
+    GroupInvite { invite_id: String, friend_number: u32, invite_data: Vec<u8>, group_name: String, password_required: bool },
     GroupSelfJoin { group_number: u32 },
     GroupJoinFail { group_number: u32, fail_type: String },
     GroupPeerJoin { group_number: u32, peer_id: u32, name: String, public_key: String },
     GroupPeerExit { group_number: u32, peer_id: u32, name: String },
     GroupPeerName { group_number: u32, peer_id: u32, name: String },
-    GroupMessage { group_number: u32, peer_id: u32, sender_name: String, sender_pk: String, message: String, message_type: String, id: String, timestamp: String, channel_id: String },
+    GroupMessage { group_number: u32, peer_id: u32, sender_name: String, sender_pk: String, message: String, message_type: String, id: String, timestamp: String, channel_id: String, formatted: Vec<markdown::Node>, mentions_me: bool, filtered: bool, sender_verified: Option<bool>, signer_public_key: Option<String>, sticker: Option<StickerInfo> },
     GroupTopicChange { group_number: u32, topic: String },
     GroupCustomPacket { group_number: u32, peer_id: u32, data: Vec<u8> },
     GroupPeerStatus { group_number: u32, peer_id: u32, status: String },
+    // Attachment events
+    AttachmentReady { message_id: String, attachment_id: String, filename: String, mime_type: String, file_size: i64, width: Option<i64>, height: Option<i64>, has_thumbnail: bool },
+    // Scheduled message went out ("sent"), was queued for an offline friend ("queued") or "failed"
+    ScheduledMessage { id: String, status: String, message_id: Option<String>, target_type: String, target_id: String, timestamp: String },
+    // Message sent to a friend automatically: the away auto-reply, or after declining their call in Do Not Disturb
+    AutoReply { friend_number: u32, id: String, message: String, timestamp: String, expires_at: Option<i64>, incognito: bool },
+    // We or the friend changed the conversation's disappearing timer (0 when off)
+    DisappearingTimerChanged { friend_number: u32, seconds: u32 },
+    // Disappearing messages were deleted
+    DirectMessagesExpired { friend_number: u32, message_ids: Vec<String> },
+    // A friend sent plain text in a locked conversation, which was dropped
+    UnprotectedMessageRefused { friend_number: u32 },
+    // LAN discovery
+    LanPeerFound { address: String, public_key: String, name: String },
+    LanPeerLost { public_key: String },
+    // Data received from one of our linked devices
+    DeviceSynced { friend_number: u32, friends_added: usize, guilds_joined: usize, messages_added: usize },
+    // Channel history from another member was merged after joining a guild
+    GuildHistoryBackfilled { group_number: u32, messages_added: usize },
+    // A friend or group peer changed their rich presence activity
+    ActivityChanged { public_key: String, activity: Option<Activity> },
+    // A friend or group peer changed their custom status, or it expired (ours included)
+    CustomStatusChanged { public_key: String, status: Option<CustomStatus> },
+    // A friend posted a status note
+    StatusNoteReceived { friend_number: u32, note: StatusNote },
+    // A poll got a vote or closed
+    PollUpdated { poll_id: String, tallies: Vec<i64>, closed: bool },
+    // A moderator deleted messages from a channel
+    ChannelMessagesPurged { channel_id: String, count: usize },
+    // A guild event was scheduled or got an RSVP
+    GuildEventUpdated { guild_id: String, event_id: String },
+    // A guild event starts in 15 minutes
+    GuildEventReminder { guild_id: String, event_id: String, title: String, start_time: i64 },
+    // A founder or moderator changed the guild's manifest (e.g. slow mode) or
+    // its channels
+    GuildManifestChanged { guild_id: String },
+    // A message from an IRC/Matrix bridge, posted to the channel as our own
+    BridgedMessage { channel_id: String, id: String, sender_name: String, content: String, timestamp: String },
+    // The tox thread crashed and was started again from the saved profile
+    ToxThreadRestarted { crashes: usize },
+    // The tox thread crashed too often and won't be restarted
+    ToxThreadStopped,
+    // The tox thread stopped (or resumed) running its loop
+    ToxThreadStalled { stalled: bool },
 }
 
 /// ToxEventHandler implementation that emits Tauri events and persists to DB
@@ -183,6 +384,36 @@ struct TauriEventHandler {
     store: Arc<MessageStore>,
     /// Sender to queue offline flushes for the tox thread to process
     offline_flush_tx: std::sync::mpsc::Sender<u32>,
+    /// In-flight file transfers
+    file_manager: Arc<std::sync::Mutex<FileManager>>,
+    /// Sender to queue file accept/cancel for the tox thread to process
+    file_action_tx: std::sync::mpsc::Sender<FileAction>,
+    /// Sender to queue friends who messaged us, for the away auto-reply
+    auto_reply_tx: std::sync::mpsc::Sender<u32>,
+    /// Sender to queue linked device sync work
+    device_sync_tx: std::sync::mpsc::Sender<DeviceSyncAction>,
+    /// Sender to queue guild history backfill work
+    history_tx: std::sync::mpsc::Sender<HistoryAction>,
+    /// Activities of peers, and ours to send them
+    rich_presence: RichPresence,
+    /// Sender to queue peers that need our activity
+    presence_tx: std::sync::mpsc::Sender<PresenceAction>,
+    /// Keys of conversations locked with a passphrase
+    conversation_locks: ConversationLocks,
+    /// Conversations whose messages aren't stored
+    incognito: Incognito,
+    /// Public keys of group peers by (group number, peer ID), for when they leave
+    group_peers: std::sync::Mutex<HashMap<(u32, u32), String>>,
+    /// Call state, to tag messages received during a call
+    av_manager: Arc<std::sync::Mutex<AvManager>>,
+    /// Parts of long texts from friends and group peers, until the rest arrive
+    texts: IncomingTexts,
+    /// Notices sent ahead of group invites, by friend number
+    invite_notices: std::sync::Mutex<HashMap<u32, InviteNotice>>,
+    /// When we last joined each group, by group number
+    self_joins: std::sync::Mutex<HashMap<u32, Instant>>,
+    /// Set when a message arrives, so power saving knows we aren't idle
+    message_activity: Arc<AtomicBool>,
     /// Raw tox pointer for querying peer info during callbacks.
     /// SAFETY: Only accessed on the tox thread during iterate_with_userdata.
     tox_raw: *mut toxcord_tox_sys::Tox,
@@ -199,6 +430,281 @@ impl TauriEventHandler {
         }
     }
 
+    /// Cache a friend's or group peer's activity or custom status and tell the
+    /// frontend if it changed
+    fn on_peer_presence(&self, public_key: &str, packet: PresencePacket) {
+        if public_key.is_empty() {
+            return;
+        }
+        let public_key = public_key.to_uppercase();
+        match packet {
+            PresencePacket::Activity { activity } => {
+                if self.rich_presence.set_activity(&public_key, activity.clone()) {
+                    self.emit(ToxEvent::ActivityChanged { public_key, activity });
+                }
+            }
+            PresencePacket::CustomStatus { status } => {
+                if self.rich_presence.set_custom_status(&public_key, status.clone()) {
+                    self.emit(ToxEvent::CustomStatusChanged { public_key, status });
+                }
+            }
+        }
+    }
+
+    /// A poll posted by a peer, or a peer's vote
+    fn on_poll_packet(&self, group_number: u32, peer_id: u32, packet: PollPacket) {
+        let sender_pk = self.query_peer_public_key(group_number, peer_id);
+        if sender_pk.is_empty() {
+            return;
+        }
+        match packet {
+            PollPacket::Create { poll } => {
+                let sender_name = self.query_peer_name(group_number, peer_id);
+                let (channel_id, _) = self.parse_group_message(group_number, &format!("[CH:{}]", poll.channel));
+                let timestamp = chrono::Utc::now().to_rfc3339();
+                let record = crate::db::message_store::PollRecord {
+                    id: poll.id.clone(),
+                    channel_id: channel_id.clone(),
+                    creator_public_key: sender_pk.clone(),
+                    creator_name: sender_name.clone(),
+                    question: poll.question.clone(),
+                    options: poll.options,
+                    closes_at: poll.closes_at,
+                    closed: false,
+                };
+                if let Err(e) = self.store.insert_poll(&record) {
+                    error!("{e}");
+                    return;
+                }
+                // The poll shows up in the channel as a message with the poll's ID
+                if let Err(e) = self.store.insert_channel_message(&crate::db::message_store::ChannelMessageRecord {
+                    id: poll.id.clone(),
+                    channel_id: channel_id.clone(),
+                    sender_public_key: sender_pk.clone(),
+                    sender_name: sender_name.clone(),
+                    content: poll.question.clone(),
+                    message_type: "poll".to_string(),
+                    timestamp: timestamp.clone(),
+                    mentions_me: false,
+                    notify: self.notifies(&channel_id, false),
+                    filtered: false,
+                    backfilled: false,
+                    sender_verified: None,
+                    signer_public_key: None,
+                    signature: None,
+                }) {
+                    error!("Failed to persist poll message: {e}");
+                }
+                self.emit(ToxEvent::GroupMessage {
+                    group_number,
+                    peer_id,
+                    sender_name,
+                    sender_pk,
+                    formatted: Vec::new(),
+                    message: poll.question,
+                    message_type: "poll".to_string(),
+                    id: poll.id,
+                    timestamp,
+                    channel_id,
+                    mentions_me: false,
+                    filtered: false,
+                    sender_verified: None,
+                    signer_public_key: None,
+                    sticker: None,
+                });
+            }
+            PollPacket::Vote { poll_id, option } => {
+                let poll = match self.store.get_poll(&poll_id) {
+                    Ok(Some(poll)) => poll,
+                    Ok(None) => return,
+                    Err(e) => {
+                        error!("{e}");
+                        return;
+                    }
+                };
+                let open = !poll.closed && chrono::Utc::now().timestamp() < poll.closes_at;
+                if !open || option as usize >= poll.options.len() {
+                    debug!("Ignoring vote on poll {poll_id}: closed or invalid option");
+                    return;
+                }
+                let tallies = self
+                    .store
+                    .set_poll_vote(&poll_id, &sender_pk, option)
+                    .and_then(|_| self.store.get_poll_tallies(&poll_id, poll.options.len()));
+                match tallies {
+                    Ok(tallies) => self.emit(ToxEvent::PollUpdated { poll_id, tallies, closed: false }),
+                    Err(e) => error!("{e}"),
+                }
+            }
+        }
+    }
+
+    /// Messages deleted in bulk by a moderator
+    fn on_purge_packet(&self, group_number: u32, peer_id: u32, packet: PurgePacket) {
+        if !matches!(self.query_peer_role(group_number, peer_id), Some(GroupRole::Founder | GroupRole::Moderator)) {
+            debug!("Ignoring purge from non-moderator {peer_id} in group {group_number}");
+            return;
+        }
+        let Some(guild_id) = self.guild_id_for_group(group_number) else {
+            return;
+        };
+        let channel = match self.store.get_channels(&guild_id) {
+            Ok(channels) => channels.into_iter().find(|c| c.name == packet.channel()),
+            Err(e) => {
+                error!("{e}");
+                return;
+            }
+        };
+        let Some(channel) = channel else {
+            return;
+        };
+
+        let to_rfc3339 = |secs: Option<i64>| {
+            secs.and_then(|s| chrono::DateTime::from_timestamp(s, 0)).map(|t| t.to_rfc3339())
+        };
+        let (result, member) = match &packet {
+            PurgePacket::Range { since, before, .. } => (
+                self.store.delete_channel_messages_in_range(
+                    &channel.id,
+                    to_rfc3339(*since).as_deref(),
+                    to_rfc3339(*before).as_deref(),
+                ),
+                None,
+            ),
+            PurgePacket::FromMember { public_key, .. } => (
+                self.store.delete_channel_messages_from(&channel.id, public_key),
+                Some(public_key.as_str()),
+            ),
+        };
+        match result {
+            Ok(count) => {
+                info!("Purged {count} messages from channel {} by moderator request", channel.id);
+                self.record_audit(group_number, "message_purge", peer_id, member, &channel.name, &format!("{count} messages"));
+                self.emit(ToxEvent::ChannelMessagesPurged { channel_id: channel.id, count });
+            }
+            Err(e) => error!("{e}"),
+        }
+    }
+
+    /// An event scheduled by a peer, or a peer's RSVP
+    fn on_event_packet(&self, group_number: u32, peer_id: u32, packet: EventPacket) {
+        let sender_pk = self.query_peer_public_key(group_number, peer_id);
+        let Some(guild_id) = self.guild_id_for_group(group_number) else {
+            return;
+        };
+        if sender_pk.is_empty() {
+            return;
+        }
+        let result = match packet {
+            EventPacket::Create { event } => {
+                let record = crate::db::message_store::GuildEventRecord {
+                    id: event.id,
+                    guild_id: guild_id.clone(),
+                    creator_public_key: sender_pk,
+                    creator_name: self.query_peer_name(group_number, peer_id),
+                    title: event.title,
+                    description: event.description,
+                    start_time: event.start_time,
+                };
+                self.store.insert_guild_event(&record).map(|_| record.id)
+            }
+            EventPacket::Rsvp { event_id, status } => match self.store.get_guild_event(&event_id) {
+                // RSVPs only count for events of the guild they arrive in
+                Ok(Some(event)) if event.guild_id == guild_id => self
+                    .store
+                    .set_event_rsvp(&event_id, &sender_pk, status.as_str())
+                    .map(|_| event_id),
+                Ok(_) => {
+                    debug!("Ignoring RSVP to unknown event {event_id}");
+                    return;
+                }
+                Err(e) => Err(e),
+            },
+        };
+        match result {
+            Ok(event_id) => self.emit(ToxEvent::GuildEventUpdated { guild_id, event_id }),
+            Err(e) => error!("{e}"),
+        }
+    }
+
+    /// The manifest of the guild a channel belongs to, with the channel's name
+    fn channel_manifest(&self, channel_id: &str) -> Option<(GuildManifest, String)> {
+        incoming::channel_manifest(&self.store, channel_id)
+    }
+
+    /// Keep a guild manifest from a founder, moderator or co-founder if it's
+    /// newer than ours
+    fn on_guild_manifest(&self, group_number: u32, peer_id: u32, manifest: GuildManifest) {
+        let Some(guild_id) = self.guild_id_for_group(group_number) else {
+            return;
+        };
+        match self.store.get_guild_manifest(&guild_id) {
+            Ok(current) if manifest.version > current.version => {
+                let moderator = matches!(
+                    self.query_peer_role(group_number, peer_id),
+                    Some(GroupRole::Founder | GroupRole::Moderator)
+                );
+                if !moderator && !current.can_manage(&self.query_peer_public_key(group_number, peer_id)) {
+                    debug!("Ignoring guild manifest from non-moderator {peer_id} in group {group_number}");
+                    return;
+                }
+                if let Err(e) = self.store.set_guild_manifest(&guild_id, &manifest) {
+                    error!("{e}");
+                    return;
+                }
+                if !manifest.owner.is_empty() {
+                    if let Err(e) = self.store.update_guild_owner(&guild_id, &manifest.owner) {
+                        error!("{e}");
+                    }
+                }
+                info!("Guild {guild_id} manifest updated to version {}", manifest.version);
+                self.emit(ToxEvent::GuildManifestChanged { guild_id });
+            }
+            Ok(_) => {}
+            Err(e) => error!("{e}"),
+        }
+    }
+
+    /// Merge channel layout changes a founder or moderator made themselves and
+    /// rename, move, create or delete our channels to match
+    fn on_guild_layout(&self, group_number: u32, peer_id: u32, layout: GuildLayout) {
+        let Some(guild_id) = self.guild_id_for_group(group_number) else {
+            return;
+        };
+        let sender = self.query_peer_public_key(group_number, peer_id);
+        let moderator = matches!(
+            self.query_peer_role(group_number, peer_id),
+            Some(GroupRole::Founder | GroupRole::Moderator)
+        );
+        if !moderator && !self.store.get_guild_manifest(&guild_id).is_ok_and(|m| m.can_manage(&sender)) {
+            debug!("Ignoring channel layout from non-moderator {peer_id} in group {group_number}");
+            return;
+        }
+
+        let mut current = match self.store.get_guild_layout(&guild_id) {
+            Ok(current) => current,
+            Err(e) => {
+                error!("{e}");
+                return;
+            }
+        };
+        if !current.merge_from(&layout, &sender) {
+            return;
+        }
+        if let Err(e) = self.store.set_guild_layout(&guild_id, &current) {
+            error!("{e}");
+            return;
+        }
+        match self.store.apply_channel_layout(&guild_id, &current) {
+            Ok(true) => {
+                info!("Guild {guild_id} channels updated from the shared layout");
+                self.emit(ToxEvent::GuildManifestChanged { guild_id });
+            }
+            Ok(false) => {}
+            Err(e) => error!("{e}"),
+        }
+    }
+
     /// Query a peer's name from the tox instance during a callback.
     fn query_peer_name(&self, group_number: u32, peer_id: u32) -> String {
         unsafe {
@@ -233,297 +739,279 @@ impl TauriEventHandler {
         }
     }
 
-    /// Parse group message prefix and return (channel_id, content).
-    /// Supports: [CH:name] for guild channels, [DM] for DM groups, or no prefix (fallback).
-    fn parse_group_message(&self, group_number: u32, message: &str) -> (String, String) {
-        info!("parse_group_message: group={} msg_preview={:?}",
-              group_number, message.chars().take(30).collect::<String>());
-
-        // Try to parse [CH:name] prefix for guild channel messages
-        if message.starts_with("[CH:") {
-            if let Some(end) = message.find(']') {
-                let channel_name = &message[4..end];
-                let content = message[end + 1..].to_string();
-                info!("[CH] Parsed [CH:{}] prefix, looking up server by group_number={}", channel_name, group_number);
-
-                // Look up server specifically by guild_type="server" to avoid collision with DM groups
-                let guild_result = self.store.get_guild_by_group_number_and_type(group_number as i64, "server");
-                info!("[CH] Guild lookup result: {:?}", guild_result.as_ref().map(|g| g.as_ref().map(|gg| &gg.name)));
-
-                if let Some(channel_id) = guild_result
-                    .ok()
-                    .flatten()
-                    .and_then(|guild| {
-                        let ch_result = self.store.get_or_create_channel_by_name(&guild.id, channel_name);
-                        info!("[CH] get_or_create_channel_by_name result for '{}': {:?}", channel_name, ch_result);
-                        ch_result.ok()
-                    })
-                {
-                    info!("[CH] Successfully routed to channel_id={}", channel_id);
-                    return (channel_id, content);
-                }
-                warn!("[CH] Failed to route [CH:{}] message - server or channel lookup failed", channel_name);
-            }
-        }
-
-        // Try to parse [DM] prefix for DM group messages
-        if message.starts_with("[DM]") {
-            let content = message[4..].to_string();
-            info!("[DM] Parsing DM group message for group_number={}", group_number);
-
-            // For DM groups, look up specifically by guild_type="dm_group" to avoid collision with servers
-            let guild_result = self.store.get_guild_by_group_number_and_type(group_number as i64, "dm_group");
-            info!("[DM] Guild lookup result: {:?}", guild_result.as_ref().map(|g| g.as_ref().map(|gg| (&gg.id, &gg.name, &gg.guild_type))));
-
-            if let Some(channel_id) = guild_result
-                .ok()
-                .flatten()
-                .and_then(|guild| {
-                    let channels_result = self.store.get_channels(&guild.id);
-                    info!("[DM] Channels lookup for guild {}: {:?}", guild.id, channels_result.as_ref().map(|chs| chs.iter().map(|c| (&c.id, &c.name)).collect::<Vec<_>>()));
-                    channels_result
-                        .ok()
-                        .and_then(|channels| channels.first().map(|c| c.id.clone()))
-                })
-            {
-                info!("[DM] Successfully routed to channel_id={}", channel_id);
-                return (channel_id, content);
-            }
-            warn!("[DM] Failed to find dm_group for group_number={}, using fallback", group_number);
-            return (format!("dm_group_{group_number}"), content);
-        }
-
-        // Fallback: no prefix, route to first channel of guild
-        let channel_id = self
-            .store
-            .get_guild_by_group_number(group_number as i64)
-            .ok()
-            .flatten()
-            .and_then(|guild| {
-                self.store
-                    .get_channels(&guild.id)
-                    .ok()
-                    .and_then(|channels| channels.first().map(|c| c.id.clone()))
-            })
-            .unwrap_or_else(|| format!("group_{group_number}"));
-
-        (channel_id, message.to_string())
+    /// Query a peer's role during a callback.
+    fn query_peer_role(&self, group_number: u32, peer_id: u32) -> Option<GroupRole> {
+        unsafe {
+            let mut err = toxcord_tox_sys::Tox_Err_Group_Peer_Query::default();
+            let role = toxcord_tox_sys::tox_group_peer_get_role(self.tox_raw, group_number, peer_id, &mut err);
+            (err == 0).then(|| GroupRole::from_raw(role as u32))
+        }
     }
-}
-
-impl ToxEventHandler for TauriEventHandler {
-    fn on_self_connection_status(&self, status: ConnectionStatus) {
-        let status_str = match status {
-            ConnectionStatus::None => "none",
-            ConnectionStatus::Tcp => "tcp",
-            ConnectionStatus::Udp => "udp",
-        };
-        info!("Connection status: {status_str}");
 
-        // I2P/Proxy verification logging
-        // When using I2P or any SOCKS/HTTP proxy, UDP is disabled and only TCP should be used
-        match status {
-            ConnectionStatus::Udp => {
-                warn!("[I2P-CHECK] UDP connection detected - traffic is NOT routed through I2P/proxy!");
-            }
-            ConnectionStatus::Tcp => {
-                info!("[I2P-CHECK] TCP connection confirmed - traffic is routed through proxy (I2P/Tor if configured)");
-            }
-            ConnectionStatus::None => {
-                debug!("[I2P-CHECK] No connection yet");
+    /// Query our own public key in a group during a callback.
+    fn query_self_public_key(&self, group_number: u32) -> String {
+        unsafe {
+            let mut pk = [0u8; 32];
+            let mut err = toxcord_tox_sys::Tox_Err_Group_Self_Query::default();
+            let ok = toxcord_tox_sys::tox_group_self_get_public_key(
+                self.tox_raw, group_number, pk.as_mut_ptr(), &mut err,
+            );
+            if ok {
+                pk.iter().map(|b| format!("{b:02X}")).collect()
+            } else {
+                String::new()
             }
         }
+    }
 
-        self.emit(ToxEvent::ConnectionStatus {
-            connected: status.is_connected(),
-            status: status_str.to_string(),
-        });
+    /// Remember a peer of the guild behind `group_number` for mention
+    /// autocomplete and the offline member list
+    fn cache_guild_member(&self, group_number: u32, public_key: &str, name: &str) {
+        incoming::cache_guild_member(&self.store, group_number, public_key, name);
     }
 
-    fn on_friend_request(&self, public_key: &[u8; 32], message: &str) {
-        let pk_hex: String = public_key.iter().map(|b| format!("{b:02X}")).collect();
-        info!("Friend request from {pk_hex}");
+    /// Whether a message in a channel counts as unread, by the channel's
+    /// notification level
+    fn notifies(&self, channel_id: &str, mentions_me: bool) -> bool {
+        incoming::notifies(&self.store, channel_id, mentions_me)
+    }
 
-        // Persist to DB
-        if let Err(e) = self.store.add_friend_request(&pk_hex, message) {
-            error!("Failed to persist friend request: {e}");
+    /// Whether a peer who just joined is new to the guild: we've never seen
+    /// them, and they aren't one of the peers Tox reports as we join ourselves
+    fn is_newcomer(&self, group_number: u32, public_key: &str) -> bool {
+        let joining = self
+            .self_joins
+            .lock()
+            .ok()
+            .and_then(|joins| joins.get(&group_number).copied())
+            .is_none_or(|joined| joined.elapsed() < WELCOME_GRACE);
+        if joining || public_key.is_empty() {
+            return false;
+        }
+        let Some(guild_id) = self.guild_id_for_group(group_number) else {
+            return false;
+        };
+        match self.store.get_guild_members(&guild_id) {
+            Ok(members) => !members.iter().any(|m| m.public_key.eq_ignore_ascii_case(public_key)),
+            Err(e) => {
+                error!("{e}");
+                false
+            }
         }
-
-        self.emit(ToxEvent::FriendRequest {
-            public_key: pk_hex,
-            message: message.to_string(),
-        });
     }
 
-    fn on_friend_message(&self, friend_number: u32, message_type: MessageType, message: &str) {
-        let mt = match message_type {
-            MessageType::Normal => "normal",
-            MessageType::Action => "action",
+    /// Show the guild's welcome message for a new member in its first channel.
+    /// Only stored and shown here; it's never sent.
+    fn show_welcome(&self, group_number: u32, peer_id: u32, public_key: &str, name: &str) {
+        let Ok(Some(guild)) = self.store.get_guild_by_group_number(group_number as i64) else {
+            return;
+        };
+        if guild.guild_type == "dm_group" {
+            return;
+        }
+        let welcome = match self.store.get_guild_manifest(&guild.id) {
+            Ok(manifest) => manifest.onboarding.welcome(name),
+            Err(e) => {
+                error!("{e}");
+                return;
+            }
+        };
+        let Some(welcome) = welcome else {
+            return;
+        };
+        let Some(channel) = self.store.get_channels(&guild.id).ok().and_then(|c| c.into_iter().next()) else {
+            return;
         };
 
-        let msg_id = uuid::Uuid::new_v4().to_string();
+        let id = uuid::Uuid::new_v4().to_string();
         let timestamp = chrono::Utc::now().to_rfc3339();
-
-        // Persist incoming message to DB
-        let record = crate::db::message_store::DirectMessageRecord {
-            id: msg_id.clone(),
-            friend_number: friend_number as i64,
-            sender: "friend".to_string(),
-            content: message.to_string(),
-            message_type: mt.to_string(),
+        if let Err(e) = self.store.insert_channel_message(&crate::db::message_store::ChannelMessageRecord {
+            id: id.clone(),
+            channel_id: channel.id.clone(),
+            sender_public_key: public_key.to_string(),
+            sender_name: name.to_string(),
+            content: welcome.clone(),
+            message_type: "welcome".to_string(),
             timestamp: timestamp.clone(),
-            is_outgoing: false,
-            delivered: true,
-            read: false,
-        };
-        if let Err(e) = self.store.insert_direct_message(&record) {
-            error!("Failed to persist incoming message: {e}");
+            mentions_me: false,
+            notify: false,
+            filtered: false,
+            backfilled: false,
+            sender_verified: None,
+            signer_public_key: None,
+            signature: None,
+        }) {
+            error!("Failed to persist welcome message: {e}");
+            return;
         }
-
-        self.emit(ToxEvent::FriendMessage {
-            friend_number,
-            message_type: mt.to_string(),
-            message: message.to_string(),
-            id: msg_id,
+        self.emit(ToxEvent::GroupMessage {
+            group_number,
+            peer_id,
+            sender_name: name.to_string(),
+            sender_pk: public_key.to_string(),
+            formatted: Vec::new(),
+            message: welcome,
+            message_type: "welcome".to_string(),
+            id,
             timestamp,
+            channel_id: channel.id,
+            mentions_me: false,
+            filtered: false,
+            sender_verified: None,
+            signer_public_key: None,
+            sticker: None,
         });
     }
 
-    fn on_friend_name(&self, friend_number: u32, name: &str) {
-        // Persist to DB
-        if let Err(e) = self.store.update_friend_name(friend_number, name) {
-            error!("Failed to persist friend name: {e}");
+    /// Record a moderation action seen in the guild behind `group_number`
+    fn record_audit(
+        &self,
+        group_number: u32,
+        action: &str,
+        actor_peer_id: u32,
+        target_public_key: Option<&str>,
+        target_name: &str,
+        details: &str,
+    ) {
+        let Some(guild_id) = self.guild_id_for_group(group_number) else {
+            return;
+        };
+        let actor_public_key = self.peer_public_key(group_number, actor_peer_id);
+        let actor_name = self.query_peer_name(group_number, actor_peer_id);
+        if let Err(e) = self.store.add_audit_entry(
+            &guild_id,
+            action,
+            &actor_public_key,
+            &actor_name,
+            target_public_key,
+            target_name,
+            details,
+        ) {
+            error!("{e}");
         }
-
-        self.emit(ToxEvent::FriendName {
-            friend_number,
-            name: name.to_string(),
-        });
     }
 
-    fn on_friend_status_message(&self, friend_number: u32, message: &str) {
-        if let Err(e) = self.store.update_friend_status_message(friend_number, message) {
-            error!("Failed to persist friend status message: {e}");
+    /// A peer's public key, falling back to our map for peers Tox has already dropped
+    fn peer_public_key(&self, group_number: u32, peer_id: u32) -> String {
+        let public_key = self.query_peer_public_key(group_number, peer_id);
+        if !public_key.is_empty() {
+            return public_key;
         }
+        self.group_peers
+            .lock()
+            .ok()
+            .and_then(|peers| peers.get(&(group_number, peer_id)).cloned())
+            .unwrap_or_default()
+    }
 
-        self.emit(ToxEvent::FriendStatusMessage {
-            friend_number,
-            message: message.to_string(),
-        });
+    /// Whether we ignore the member with `public_key` in the guild behind `group_number`
+    fn is_ignored(&self, group_number: u32, public_key: &str) -> bool {
+        incoming::is_ignored(&self.store, group_number, public_key)
     }
 
-    fn on_friend_status(&self, friend_number: u32, status: UserStatus) {
-        let s = match status {
-            UserStatus::None => "online",
-            UserStatus::Away => "away",
-            UserStatus::Busy => "busy",
+    fn guild_id_for_group(&self, group_number: u32) -> Option<String> {
+        incoming::guild_id_for_group(&self.store, group_number)
+    }
+
+    /// Query the Tox file id of a transfer during a callback.
+    fn query_file_id(&self, friend_number: u32, file_number: u32) -> Option<[u8; FILE_ID_LENGTH]> {
+        unsafe {
+            let mut file_id = [0u8; FILE_ID_LENGTH];
+            let mut err = toxcord_tox_sys::Tox_Err_File_Get::default();
+            let ok = toxcord_tox_sys::tox_file_get_file_id(
+                self.tox_raw, friend_number, file_number, file_id.as_mut_ptr(), &mut err,
+            );
+            ok.then_some(file_id)
+        }
+    }
+
+    /// Pick up an interrupted incoming transfer that the friend has re-offered.
+    /// Returns false if `file_id` doesn't match a resumable transfer.
+    fn try_resume_incoming(&self, friend_number: u32, file_number: u32, file_id: &str) -> bool {
+        let record = match self.store.get_resumable_transfers(friend_number, "incoming") {
+            Ok(transfers) => transfers.into_iter().find(|t| t.file_id.as_deref() == Some(file_id)),
+            Err(e) => {
+                error!("Failed to load resumable transfers: {e}");
+                None
+            }
+        };
+        let Some(record) = record else {
+            return false;
         };
 
-        if let Err(e) = self.store.update_friend_status(friend_number, s) {
-            error!("Failed to persist friend status: {e}");
+        let mime_type = file_manager::inline_mime_type(&record.filename).unwrap_or("application/octet-stream");
+        let resumed = self
+            .file_manager
+            .lock()
+            .map_err(|e| e.to_string())
+            .and_then(|mut fm| fm.resume_incoming(friend_number, file_number, &record, mime_type));
+        match resumed {
+            Ok(position) => {
+                if let Err(e) = self.store.resume_file_transfer(&record.id, file_number) {
+                    error!("Failed to persist transfer status: {e}");
+                }
+                let _ = self.file_action_tx.send(FileAction::Accept { friend_number, file_number, position });
+                true
+            }
+            Err(e) => {
+                warn!("Can't resume {}: {e} - receiving it again", record.filename);
+                if let Err(e) = self.store.update_file_transfer_status(&record.id, "failed", record.bytes_transferred) {
+                    error!("Failed to persist transfer status: {e}");
+                }
+                false
+            }
         }
+    }
 
-        self.emit(ToxEvent::FriendStatus {
-            friend_number,
-            status: s.to_string(),
+    /// Store the attachment for a completed image transfer (thumbnail generated off-thread)
+    fn process_completed_image(&self, transfer: IncomingTransfer) {
+        let store = self.store.clone();
+        let app_handle = self.app_handle.clone();
+        std::thread::spawn(move || {
+            let attachment = file_manager::build_attachment(
+                &file_manager::asset_store(&store),
+                &transfer.message_id,
+                &transfer.id,
+                &transfer.filename,
+                &transfer.mime_type,
+                transfer.file_size,
+                &transfer.path,
+            );
+            persist_attachment(&store, &app_handle, attachment);
         });
     }
 
-    fn on_friend_connection_status(&self, friend_number: u32, status: ConnectionStatus) {
-        let s = match status {
-            ConnectionStatus::None => "none",
-            ConnectionStatus::Tcp => "tcp",
-            ConnectionStatus::Udp => "udp",
-        };
-
-        // Update last_seen when going offline
-        let going_offline = matches!(status, ConnectionStatus::None);
-        if let Err(e) = self.store.update_friend_connection_status(friend_number, s, going_offline) {
-            error!("Failed to persist friend connection status: {e}");
-        }
-
-        // If friend came online, request offline queue flush
-        if status.is_connected() {
-            let _ = self.offline_flush_tx.send(friend_number);
-        }
-
-        self.emit(ToxEvent::FriendConnectionStatus {
-            friend_number,
-            connected: status.is_connected(),
-            status: s.to_string(),
-        });
-    }
-
-    fn on_friend_typing(&self, friend_number: u32, is_typing: bool) {
-        self.emit(ToxEvent::FriendTyping {
-            friend_number,
-            is_typing,
-        });
-    }
-
-    fn on_friend_read_receipt(&self, friend_number: u32, message_id: u32) {
-        debug!("Read receipt: friend={friend_number} msg_id={message_id}");
-        // Read receipts from Tox use sequential IDs, not our UUIDs.
-        // We could map tox_msg_id -> uuid, but for now this is a no-op.
-        // The message is already marked delivered=true on successful send.
-    }
-    fn on_file_recv_control(&self, _friend_number: u32, _file_number: u32, _control: u32) {}
-    fn on_file_chunk_request(&self, _friend_number: u32, _file_number: u32, _position: u64, _length: usize) {}
-    fn on_file_recv(&self, _friend_number: u32, _file_number: u32, _kind: u32, _file_size: u64, _filename: &str) {}
-    fn on_file_recv_chunk(&self, _friend_number: u32, _file_number: u32, _position: u64, _data: &[u8]) {}
-    fn on_group_invite(&self, friend_number: u32, invite_data: &[u8], group_name: &str) {
-        info!("Group invite from friend {friend_number}: {group_name}");
-        self.emit(ToxEvent::GroupInvite {
-            friend_number,
-            invite_data: invite_data.to_vec(),
-            group_name: group_name.to_string(),
-        });
-    }
-
-    fn on_group_peer_join(&self, group_number: u32, peer_id: u32) {
-        let name = self.query_peer_name(group_number, peer_id);
-        let public_key = self.query_peer_public_key(group_number, peer_id);
-        info!("Peer joined group {group_number}: {name} ({peer_id})");
-        self.emit(ToxEvent::GroupPeerJoin {
-            group_number,
-            peer_id,
-            name,
-            public_key,
-        });
-    }
-
-    fn on_group_peer_exit(&self, group_number: u32, peer_id: u32, _exit_type: u32, name: &str, _message: &str) {
-        info!("Peer left group {group_number}: {name} ({peer_id})");
-        self.emit(ToxEvent::GroupPeerExit {
-            group_number,
-            peer_id,
-            name: name.to_string(),
-        });
-    }
-
-    fn on_group_peer_name(&self, group_number: u32, peer_id: u32, name: &str) {
-        self.emit(ToxEvent::GroupPeerName {
-            group_number,
-            peer_id,
-            name: name.to_string(),
-        });
-    }
-
-    fn on_group_message(&self, group_number: u32, peer_id: u32, message_type: MessageType, message: &str, _message_id: u32) {
-        let mt = match message_type {
-            MessageType::Normal => "normal",
-            MessageType::Action => "action",
-        };
+    /// Record a file offered in a group channel and start downloading it
+    fn on_group_file_offer(&self, group_number: u32, peer_id: u32, offer: FileOffer) {
         let sender_name = self.query_peer_name(group_number, peer_id);
         let sender_pk = self.query_peer_public_key(group_number, peer_id);
         let msg_id = uuid::Uuid::new_v4().to_string();
         let timestamp = chrono::Utc::now().to_rfc3339();
 
-        // Parse message prefix: [CH:N] for channel, [DM] for DM group
-        let (channel_id, content) = self.parse_group_message(group_number, message);
+        // Route the same way as a text message with the matching prefix
+        let prefix = match offer.channel {
+            Some(ref name) => format!("[CH:{name}]"),
+            None => "[DM]".to_string(),
+        };
+        let (channel_id, _) = self.parse_group_message(group_number, &prefix);
+
+        if self.is_ignored(group_number, &sender_pk) {
+            debug!("Not downloading a file from ignored member {sender_name} in group {group_number}");
+            return;
+        }
+
+        let announcement = self
+            .channel_manifest(&channel_id)
+            .is_some_and(|(m, name)| m.channel(&name).announcement);
+        if announcement
+            && !matches!(self.query_peer_role(group_number, peer_id), Some(GroupRole::Founder | GroupRole::Moderator))
+        {
+            debug!("Dropped a file from {sender_name} in announcement channel {channel_id}: not a moderator");
+            return;
+        }
 
-        info!("Group message received: group={} peer={} sender='{}' channel={} content_len={}",
-              group_number, peer_id, sender_name, channel_id, content.len());
+        info!("File offered in group {group_number} by {sender_name}: {} ({} bytes)", offer.filename, offer.size);
+        let notify = self.notifies(&channel_id, false);
 
         if let Err(e) = self.store.insert_channel_message(
             &crate::db::message_store::ChannelMessageRecord {
@@ -531,14 +1019,22 @@ impl ToxEventHandler for TauriEventHandler {
                 channel_id: channel_id.clone(),
                 sender_public_key: sender_pk.clone(),
                 sender_name: sender_name.clone(),
-                content: content.clone(),
-                message_type: mt.to_string(),
+                content: offer.filename.clone(),
+                message_type: file_manager::file_message_type(&offer.filename).to_string(),
                 timestamp: timestamp.clone(),
+                mentions_me: false,
+                notify,
+                filtered: false,
+                backfilled: false,
+                sender_verified: None,
+                signer_public_key: None,
+                signature: None,
             },
         ) {
-            error!("Failed to persist group message: {e}");
-        } else {
-            info!("Group message persisted successfully to channel {}", channel_id);
+            error!("Failed to persist file message: {e}");
+        }
+        if notify {
+            crate::tray::refresh_unread_badge(&self.app_handle, &self.store);
         }
 
         self.emit(ToxEvent::GroupMessage {
@@ -546,1339 +1042,4216 @@ impl ToxEventHandler for TauriEventHandler {
             peer_id,
             sender_name,
             sender_pk,
-            message: content,
-            message_type: mt.to_string(),
-            id: msg_id,
+            message: offer.filename.clone(),
+            message_type: file_manager::file_message_type(&offer.filename).to_string(),
+            id: msg_id.clone(),
             timestamp,
-            channel_id,
+            channel_id: channel_id.clone(),
+            formatted: Vec::new(),
+            mentions_me: false,
+            filtered: false,
+            sender_verified: None,
+            signer_public_key: None,
+            sticker: None,
         });
-    }
 
-    fn on_group_custom_packet(&self, group_number: u32, peer_id: u32, data: &[u8]) {
-        self.emit(ToxEvent::GroupCustomPacket {
-            group_number,
-            peer_id,
-            data: data.to_vec(),
-        });
+        let request = self
+            .file_manager
+            .lock()
+            .map_err(|e| e.to_string())
+            .and_then(|mut fm| fm.start_group_download(group_number, peer_id, offer, &msg_id, &channel_id));
+        match request {
+            Ok(request) => self.send_file_share_packet(group_number, peer_id, FileSharePacket::Request(request)),
+            Err(e) => warn!("Not downloading file from peer {peer_id} in group {group_number}: {e}"),
+        }
     }
 
-    fn on_group_custom_private_packet(&self, _group_number: u32, _peer_id: u32, _data: &[u8]) {
-        // Custom private packets will be handled by protocol routing layer
-    }
+    /// Answer a peer's request with chunks of a file we hold
+    fn serve_group_file(&self, group_number: u32, peer_id: u32, request: FileRequest) {
+        let Some(hash) = file_share::hash_from_hex(&request.hash) else {
+            return;
+        };
+        let attachment = match self.store.get_attachment_by_hash(&request.hash.to_uppercase()) {
+            Ok(Some(a)) => a,
+            Ok(None) => {
+                debug!("Peer {peer_id} in group {group_number} requested a file we don't have");
+                return;
+            }
+            Err(e) => {
+                error!("Failed to look up shared file: {e}");
+                return;
+            }
+        };
 
-    fn on_group_self_join(&self, group_number: u32) {
-        info!("Self joined group {group_number}");
-        self.emit(ToxEvent::GroupSelfJoin { group_number });
+        let length = request.length.min(file_share::REQUEST_WINDOW);
+        let path = std::path::Path::new(&attachment.file_path);
+        match file_manager::read_file_range(path, request.offset, length) {
+            Ok(data) => {
+                for chunk in file_share::split_file_range(&hash, request.offset, &data) {
+                    self.send_file_share_packet(group_number, peer_id, FileSharePacket::Chunk(chunk));
+                }
+            }
+            Err(e) => warn!("Failed to serve {} to peer {peer_id}: {e}", attachment.filename),
+        }
     }
 
-    fn on_group_join_fail(&self, group_number: u32, fail_type: u32) {
-        let ft = match fail_type {
-            0 => "peer_limit",
-            1 => "invalid_password",
-            _ => "unknown",
-        };
-        warn!("Failed to join group {group_number}: {ft}");
-        self.emit(ToxEvent::GroupJoinFail {
-            group_number,
-            fail_type: ft.to_string(),
-        });
+    fn on_group_file_chunk(&self, group_number: u32, chunk: FileChunk) {
+        let result = self
+            .file_manager
+            .lock()
+            .map_err(|e| e.to_string())
+            .and_then(|mut fm| fm.add_group_chunk(group_number, chunk));
+        match result {
+            Ok(GroupChunkResult::Pending) => {}
+            Ok(GroupChunkResult::Request { peer_id, request }) => {
+                self.send_file_share_packet(group_number, peer_id, FileSharePacket::Request(request));
+            }
+            Ok(GroupChunkResult::Complete { download, data }) => self.process_group_download(*download, data),
+            Err(e) => warn!("Group file download in group {group_number} failed: {e}"),
+        }
     }
 
-    fn on_group_topic(&self, group_number: u32, _peer_id: u32, topic: &str) {
-        self.emit(ToxEvent::GroupTopicChange {
-            group_number,
-            topic: topic.to_string(),
+    /// Save a completed group download and store it as an attachment (off-thread)
+    fn process_group_download(&self, download: GroupDownload, data: Vec<u8>) {
+        let store = self.store.clone();
+        let app_handle = self.app_handle.clone();
+        std::thread::spawn(move || {
+            let filename = file_manager::sanitize_filename(&download.offer.filename);
+            let (id, path) = match file_manager::save_attachment(&store, &filename, &data) {
+                Ok(saved) => saved,
+                Err(e) => {
+                    error!("Failed to save {filename}: {e}");
+                    return;
+                }
+            };
+            // The sender's MIME type is only a hint; go by the extension like DM transfers do
+            let mime_type = file_manager::inline_mime_type(&filename).unwrap_or("application/octet-stream");
+            let mut attachment = file_manager::build_attachment(
+                &file_manager::asset_store(&store),
+                &download.message_id,
+                &id,
+                &filename,
+                mime_type,
+                download.offer.size,
+                &path,
+            );
+            attachment.transfer_id = None;
+            attachment.sha256 = Some(download.offer.hash.to_uppercase());
+            info!("Received {filename} in channel {}", download.channel_id);
+            persist_attachment(&store, &app_handle, attachment);
         });
     }
 
-    fn on_group_peer_status(&self, group_number: u32, peer_id: u32, status: UserStatus) {
-        let s = match status {
-            UserStatus::None => "online",
-            UserStatus::Away => "away",
-            UserStatus::Busy => "busy",
-        };
-        self.emit(ToxEvent::GroupPeerStatus {
+    fn send_file_share_packet(&self, group_number: u32, peer_id: u32, packet: FileSharePacket) {
+        let _ = self.file_action_tx.send(FileAction::GroupPacket {
             group_number,
             peer_id,
-            status: s.to_string(),
+            data: packet.to_bytes(),
         });
     }
-}
 
-/// Manages the Tox instance on a dedicated thread
-pub struct ToxManager {
-    cmd_tx: mpsc::Sender<ToxCommand>,
-    #[allow(dead_code)]
-    profile_path: PathBuf,
+    /// Parse group message prefix and return (channel_id, content).
+    /// Supports: [CH:name] for guild channels, [DM] for DM groups, or no prefix (fallback).
+    fn parse_group_message(&self, group_number: u32, message: &str) -> (String, String) {
+        incoming::route_group_text(&self.store, group_number, message)
+    }
 }
 
-impl ToxManager {
-    /// Start a new ToxManager with a fresh profile
-    pub fn create_profile(
-        app_handle: AppHandle,
-        profile_name: &str,
-        password: &str,
-        display_name: &str,
-        store: Arc<MessageStore>,
-    ) -> Result<Arc<Mutex<Self>>, String> {
-        let profile_dir = get_profiles_dir();
-        std::fs::create_dir_all(&profile_dir).map_err(|e| format!("Failed to create profile dir: {e}"))?;
-
-        let profile_path = profile_dir.join(format!("{profile_name}.tox"));
+impl ToxEventHandler for TauriEventHandler {
+    fn on_self_connection_status(&self, status: ConnectionStatus) {
+        let status_str = match status {
+            ConnectionStatus::None => "none",
+            ConnectionStatus::Tcp => "tcp",
+            ConnectionStatus::Udp => "udp",
+        };
+        info!("Connection status: {status_str}");
 
-        if profile_path.exists() {
-            return Err(format!("Profile '{profile_name}' already exists"));
+        // I2P/Proxy verification logging
+        // When using I2P or any SOCKS/HTTP proxy, UDP is disabled and only TCP should be used
+        match status {
+            ConnectionStatus::Udp => {
+                warn!("[I2P-CHECK] UDP connection detected - traffic is NOT routed through I2P/proxy!");
+            }
+            ConnectionStatus::Tcp => {
+                info!("[I2P-CHECK] TCP connection confirmed - traffic is routed through proxy (I2P/Tor if configured)");
+            }
+            ConnectionStatus::None => {
+                debug!("[I2P-CHECK] No connection yet");
+            }
         }
 
-        let (cmd_tx, cmd_rx) = mpsc::channel(256);
-        let password = password.to_string();
-        let display_name = display_name.to_string();
-        let path = profile_path.clone();
-
-        // Load proxy config from environment variables
-        let proxy_config = ProxyConfig::from_env();
-
-        std::thread::spawn(move || {
-            run_tox_thread(app_handle, cmd_rx, None, &password, &path, Some(&display_name), store, None, proxy_config);
+        self.emit(ToxEvent::ConnectionStatus {
+            connected: status.is_connected(),
+            status: status_str.to_string(),
         });
-
-        Ok(Arc::new(Mutex::new(Self {
-            cmd_tx,
-            profile_path,
-        })))
     }
 
-    /// Start a ToxManager from an existing profile
-    pub fn load_profile(
-        app_handle: AppHandle,
-        profile_name: &str,
-        password: &str,
-        store: Arc<MessageStore>,
-    ) -> Result<Arc<Mutex<Self>>, String> {
-        let profile_dir = get_profiles_dir();
-        let profile_path = profile_dir.join(format!("{profile_name}.tox"));
+    fn on_friend_request(&self, public_key: &[u8; 32], message: &str) {
+        let pk_hex: String = public_key.iter().map(|b| format!("{b:02X}")).collect();
+        info!("Friend request from {pk_hex}");
 
-        if !profile_path.exists() {
-            return Err(format!("Profile '{profile_name}' not found"));
+        // Persist to DB
+        if let Err(e) = self.store.add_friend_request(&pk_hex, message) {
+            error!("Failed to persist friend request: {e}");
         }
 
-        let savedata = std::fs::read(&profile_path)
-            .map_err(|e| format!("Failed to read profile: {e}"))?;
+        self.emit(ToxEvent::FriendRequest {
+            public_key: pk_hex,
+            message: message.to_string(),
+        });
+    }
+
+    fn on_friend_message(&self, friend_number: u32, message_type: MessageType, message: &str) {
+        self.message_activity.store(true, Ordering::Relaxed);
+        let msg_id = uuid::Uuid::new_v4().to_string();
+        let timestamp = chrono::Utc::now().to_rfc3339();
 
-        let savedata = if is_data_encrypted(&savedata) {
-            decrypt_savedata(&savedata, password)
-                .map_err(|e| format!("Failed to decrypt profile: {e}"))?
+        let text = match self.texts.friend_text(&self.store, &self.conversation_locks, friend_number, message) {
+            Ok(Some(text)) => text,
+            // Waiting for the rest of a long text
+            Ok(None) => return,
+            Err(FriendTextError::Unprotected(e)) => {
+                warn!("{e}");
+                self.emit(ToxEvent::UnprotectedMessageRefused { friend_number });
+                return;
+            }
+            Err(FriendTextError::Unstorable(e)) => {
+                error!("Not storing a message from friend {friend_number}: {e}");
+                return;
+            }
+        };
+        let FriendText { stored, text: message, locked, sticker } = text;
+        let message = message.as_str();
+        let mt = incoming::message_type_name(message_type, sticker.is_some());
+        let incognito = self.incognito.is_active(friend_number);
+        let expires_at = if incognito {
+            None
         } else {
-            savedata
+            self.store.disappearing_expiry(friend_number).unwrap_or_else(|e| {
+                error!("{e}");
+                None
+            })
         };
 
-        let (cmd_tx, cmd_rx) = mpsc::channel(256);
-        let (sync_tx, sync_rx) = std::sync::mpsc::channel::<()>();
-        let password = password.to_string();
-        let path = profile_path.clone();
-
-        // Load proxy config from environment variables
-        let proxy_config = ProxyConfig::from_env();
+        // Queued like group messages; the tray badge catches up when it's written
+        let record = crate::db::message_store::DirectMessageRecord {
+            id: msg_id.clone(),
+            friend_number: friend_number as i64,
+            sender: "friend".to_string(),
+            content: stored,
+            message_type: mt.to_string(),
+            timestamp: timestamp.clone(),
+            is_outgoing: false,
+            delivered: true,
+            read: false,
+            expires_at,
+        };
+        if !incognito {
+            let session = self.av_manager.lock().ok().and_then(|m| m.call_session(friend_number));
+            if let Err(e) = self.store.queue_direct_message(record, session) {
+                error!("Failed to persist incoming message: {e}");
+            }
+        }
+        let _ = self.auto_reply_tx.send(friend_number);
 
-        std::thread::spawn(move || {
-            run_tox_thread(app_handle, cmd_rx, Some(savedata), &password, &path, None, store, Some(sync_tx), proxy_config);
+        self.emit(ToxEvent::FriendMessage {
+            friend_number,
+            message_type: mt.to_string(),
+            message: message.to_string(),
+            id: msg_id,
+            timestamp,
+            formatted: if sticker.is_some() { Vec::new() } else { markdown::parse(message) },
+            locked,
+            expires_at,
+            incognito,
+            sticker: sticker.map(|reference| stickers::resolve(&self.store, &reference)),
         });
 
-        // Wait for the sync to complete before returning
-        let _ = sync_rx.recv_timeout(std::time::Duration::from_secs(5));
-
-        Ok(Arc::new(Mutex::new(Self {
-            cmd_tx,
-            profile_path,
-        })))
+        if !locked && mt != "sticker" {
+            self.app_handle.state::<AppState>().accessibility.announce(
+                &Conversation::Friend(friend_number),
+                None,
+                message,
+                false,
+            );
+        }
     }
 
-    /// Send a command to the Tox thread
-    pub async fn send_command(&self, cmd: ToxCommand) -> Result<(), String> {
-        self.cmd_tx
-            .send(cmd)
-            .await
-            .map_err(|_| "Tox thread has shut down".to_string())
-    }
+    fn on_friend_name(&self, friend_number: u32, name: &str) {
+        // Persist to DB
+        if let Err(e) = self.store.update_friend_name(friend_number, name) {
+            error!("Failed to persist friend name: {e}");
+        }
 
-    /// Get the Tox address
-    pub async fn get_address(&self) -> Result<ToxAddress, String> {
-        let (tx, rx) = oneshot::channel();
-        self.send_command(ToxCommand::GetAddress(tx)).await?;
-        rx.await.map_err(|_| "Failed to receive response".to_string())
+        self.emit(ToxEvent::FriendName {
+            friend_number,
+            name: name.to_string(),
+        });
     }
 
-    /// Get connection status
-    pub async fn get_connection_status(&self) -> Result<ConnectionStatus, String> {
-        let (tx, rx) = oneshot::channel();
-        self.send_command(ToxCommand::GetConnectionStatus(tx)).await?;
-        rx.await.map_err(|_| "Failed to receive response".to_string())
-    }
+    fn on_friend_status_message(&self, friend_number: u32, message: &str) {
+        if let Err(e) = self.store.update_friend_status_message(friend_number, message) {
+            error!("Failed to persist friend status message: {e}");
+        }
 
-    /// Get profile info
-    pub async fn get_profile_info(&self) -> Result<ProfileInfo, String> {
-        let (tx, rx) = oneshot::channel();
-        self.send_command(ToxCommand::GetProfileInfo(tx)).await?;
-        rx.await.map_err(|_| "Failed to receive response".to_string())
+        self.emit(ToxEvent::FriendStatusMessage {
+            friend_number,
+            message: message.to_string(),
+        });
     }
 
-    /// Shutdown the Tox thread
-    pub async fn shutdown(&self) -> Result<(), String> {
-        let (tx, rx) = oneshot::channel();
-        self.send_command(ToxCommand::Shutdown(tx)).await?;
-        rx.await.map_err(|_| "Failed to shutdown".to_string())
-    }
+    fn on_friend_status(&self, friend_number: u32, status: UserStatus) {
+        let s = match status {
+            UserStatus::None => "online",
+            UserStatus::Away => "away",
+            UserStatus::Busy => "busy",
+        };
 
-    // ─── ToxAV Methods ───────────────────────────────────────────────────────
+        if let Err(e) = self.store.update_friend_status(friend_number, s) {
+            error!("Failed to persist friend status: {e}");
+        }
 
-    /// Start a call with a friend
-    pub async fn call(&self, friend_number: u32, with_video: bool) -> Result<(), String> {
-        let (tx, rx) = oneshot::channel();
-        let audio_bit_rate = 64; // Default audio bit rate (64 kbit/s)
-        let video_bit_rate = if with_video { 400 } else { 0 }; // 400 kbit/s for video
-        self.send_command(ToxCommand::AvCall {
+        self.emit(ToxEvent::FriendStatus {
             friend_number,
-            audio_bit_rate,
-            video_bit_rate,
-            reply: tx,
-        })
-        .await?;
-        rx.await.map_err(|_| "Failed to receive response".to_string())?
+            status: s.to_string(),
+        });
     }
 
-    /// Answer an incoming call
-    pub async fn answer(&self, friend_number: u32, with_video: bool) -> Result<(), String> {
-        let (tx, rx) = oneshot::channel();
-        let audio_bit_rate = 64;
-        let video_bit_rate = if with_video { 400 } else { 0 };
-        self.send_command(ToxCommand::AvAnswer {
-            friend_number,
-            audio_bit_rate,
-            video_bit_rate,
-            reply: tx,
-        })
-        .await?;
-        rx.await.map_err(|_| "Failed to receive response".to_string())?
-    }
+    fn on_friend_connection_status(&self, friend_number: u32, status: ConnectionStatus) {
+        let s = match status {
+            ConnectionStatus::None => "none",
+            ConnectionStatus::Tcp => "tcp",
+            ConnectionStatus::Udp => "udp",
+        };
 
-    /// Hangup a call
-    pub async fn hangup(&self, friend_number: u32) -> Result<(), String> {
-        let (tx, rx) = oneshot::channel();
-        self.send_command(ToxCommand::AvHangup {
-            friend_number,
-            reply: tx,
-        })
-        .await?;
-        rx.await.map_err(|_| "Failed to receive response".to_string())?
-    }
+        // Update last_seen when going offline
+        let going_offline = matches!(status, ConnectionStatus::None);
+        if let Err(e) = self.store.update_friend_connection_status(friend_number, s, going_offline) {
+            error!("Failed to persist friend connection status: {e}");
+        }
+        let presence = if going_offline {
+            self.store.end_presence_session(friend_number)
+        } else {
+            self.store.start_presence_session(friend_number)
+        };
+        if let Err(e) = presence {
+            error!("Failed to record friend presence: {e}");
+        }
+        // They resend their activity and custom status when they're back
+        if going_offline {
+            if let Ok(Some(public_key)) = self.store.get_friend_public_key(friend_number) {
+                self.rich_presence.set_activity(&public_key, None);
+                self.rich_presence.set_custom_status(&public_key, None);
+            }
+        }
 
-    /// Mute audio for a call
-    pub async fn mute_audio(&self, friend_number: u32) -> Result<(), String> {
-        let (tx, rx) = oneshot::channel();
-        self.send_command(ToxCommand::AvMuteAudio {
-            friend_number,
-            reply: tx,
-        })
-        .await?;
-        rx.await.map_err(|_| "Failed to receive response".to_string())?
-    }
+        // If friend came online, request offline queue flush (this also re-offers
+        // interrupted file transfers)
+        if status.is_connected() {
+            let _ = self.offline_flush_tx.send(friend_number);
+            let _ = self.device_sync_tx.send(DeviceSyncAction::Connected(friend_number));
+            let _ = self.presence_tx.send(PresenceAction::FriendOnline(friend_number));
+        }
 
-    /// Unmute audio for a call
-    pub async fn unmute_audio(&self, friend_number: u32) -> Result<(), String> {
-        let (tx, rx) = oneshot::channel();
-        self.send_command(ToxCommand::AvUnmuteAudio {
-            friend_number,
-            reply: tx,
-        })
-        .await?;
-        rx.await.map_err(|_| "Failed to receive response".to_string())?
-    }
+        // Tox drops in-flight transfers on disconnect; keep their progress so they can resume
+        if going_offline {
+            let taken = self.file_manager.lock().ok().map(|mut fm| fm.take_friend_transfers(friend_number));
+            if let Some((incoming, outgoing)) = taken {
+                let progress = incoming
+                    .iter()
+                    .map(|t| (&t.id, t.received))
+                    .chain(outgoing.iter().map(|t| (&t.id, t.sent)));
+                for (id, bytes) in progress {
+                    if let Err(e) = self.store.update_file_transfer_status(id, "paused", bytes as i64) {
+                        error!("Failed to persist transfer status: {e}");
+                    }
+                }
+            }
+        }
 
-    /// Hide video for a call
-    pub async fn hide_video(&self, friend_number: u32) -> Result<(), String> {
-        let (tx, rx) = oneshot::channel();
-        self.send_command(ToxCommand::AvHideVideo {
+        self.emit(ToxEvent::FriendConnectionStatus {
             friend_number,
-            reply: tx,
-        })
-        .await?;
-        rx.await.map_err(|_| "Failed to receive response".to_string())?
+            connected: status.is_connected(),
+            status: s.to_string(),
+            connection_type: status.connection_type().to_string(),
+        });
     }
 
-    /// Show video for a call
-    pub async fn show_video(&self, friend_number: u32) -> Result<(), String> {
-        let (tx, rx) = oneshot::channel();
-        self.send_command(ToxCommand::AvShowVideo {
+    fn on_friend_typing(&self, friend_number: u32, is_typing: bool) {
+        self.emit(ToxEvent::FriendTyping {
             friend_number,
-            reply: tx,
-        })
-        .await?;
-        rx.await.map_err(|_| "Failed to receive response".to_string())?
+            is_typing,
+        });
     }
 
-    /// Get call state for a friend
-    pub async fn get_call_state(&self, friend_number: u32) -> Option<CallState> {
-        let (tx, rx) = oneshot::channel();
-        if self
-            .send_command(ToxCommand::AvGetCallState {
-                friend_number,
-                reply: tx,
-            })
-            .await
-            .is_err()
-        {
-            return None;
+    fn on_friend_lossless_packet(&self, friend_number: u32, data: &[u8]) {
+        if let Some(notice) = InviteNotice::from_bytes(data) {
+            // Matched up with the invite that follows it in on_group_invite
+            if let Ok(mut notices) = self.invite_notices.lock() {
+                notices.insert(friend_number, notice);
+            }
+            return;
+        }
+        if let Some(timer) = TimerPacket::from_bytes(data) {
+            // Ours wins if it's newer; they get it when we see them come online
+            match self.store.set_disappearing_timer(friend_number, timer.seconds, timer.set_at) {
+                Ok(true) => {
+                    info!("Friend {friend_number} set disappearing messages to {}s", timer.seconds);
+                    self.emit(ToxEvent::DisappearingTimerChanged { friend_number, seconds: timer.seconds });
+                }
+                Ok(false) => {}
+                Err(e) => error!("{e}"),
+            }
+            return;
+        }
+        if let Some(note) = StatusNote::from_bytes(data) {
+            if note.is_expired(chrono::Utc::now().timestamp()) {
+                return;
+            }
+            match self.store.insert_status_note(Some(friend_number), &note) {
+                Ok(true) => self.emit(ToxEvent::StatusNoteReceived { friend_number, note }),
+                Ok(false) => {}
+                Err(e) => error!("{e}"),
+            }
+            return;
+        }
+        if let Some(packet) = PresencePacket::from_friend_packet(data) {
+            match self.store.get_friend_public_key(friend_number) {
+                Ok(Some(public_key)) => self.on_peer_presence(&public_key, packet),
+                Ok(None) => {}
+                Err(e) => error!("{e}"),
+            }
+            return;
+        }
+        match SyncMessage::from_bytes(data) {
+            Some(message) => {
+                let _ = self.device_sync_tx.send(DeviceSyncAction::Received(friend_number, message));
+            }
+            None => debug!("Ignoring unknown lossless packet from friend {friend_number}"),
         }
-        rx.await.ok().flatten()
     }
 
-    /// List available profiles
-    pub fn list_profiles() -> Vec<String> {
-        let profile_dir = get_profiles_dir();
-        if !profile_dir.exists() {
-            return vec![];
-        }
-
-        std::fs::read_dir(profile_dir)
-            .map(|entries| {
-                entries
-                    .filter_map(|e| e.ok())
-                    .filter(|e| {
-                        e.path()
-                            .extension()
-                            .map(|ext| ext == "tox")
-                            .unwrap_or(false)
-                    })
-                    .filter_map(|e| {
-                        e.path()
-                            .file_stem()
-                            .map(|s| s.to_string_lossy().to_string())
-                    })
-                    .collect()
-            })
-            .unwrap_or_default()
+    fn on_friend_read_receipt(&self, friend_number: u32, message_id: u32) {
+        debug!("Read receipt: friend={friend_number} msg_id={message_id}");
+        // Read receipts from Tox use sequential IDs, not our UUIDs.
+        // We could map tox_msg_id -> uuid, but for now this is a no-op.
+        // The message is already marked delivered=true on successful send.
     }
-}
-
-/// The main Tox event loop running on a dedicated thread
-fn run_tox_thread(
-    app_handle: AppHandle,
-    mut cmd_rx: mpsc::Receiver<ToxCommand>,
-    savedata: Option<Vec<u8>>,
-    password: &str,
-    profile_path: &PathBuf,
-    display_name: Option<&str>,
-    store: Arc<MessageStore>,
-    sync_complete_tx: Option<std::sync::mpsc::Sender<()>>,
-    proxy_config: ProxyConfig,
-) {
-    // Build Tox options with proxy configuration
-    let mut builder = ToxOptionsBuilder::new();
-
-    // Apply proxy settings if configured
-    if let Some(ref host) = proxy_config.host {
-        match proxy_config.proxy_type {
-            ProxyType::Socks5 => {
-                info!("Using SOCKS5 proxy: {}:{}", host, proxy_config.port);
-                builder = builder.proxy_socks5(host, proxy_config.port);
+    fn on_file_recv_control(&self, friend_number: u32, file_number: u32, control: u32) {
+        let Ok(mut fm) = self.file_manager.lock() else {
+            return;
+        };
+        match FileControl::from_raw(control) {
+            FileControl::Cancel => {
+                if let Some(transfer) = fm.cancel_incoming(friend_number, file_number) {
+                    info!("Friend {friend_number} cancelled transfer of {}", transfer.filename);
+                    if let Err(e) = self.store.update_file_transfer_status(&transfer.id, "cancelled", transfer.received as i64) {
+                        error!("Failed to persist transfer status: {e}");
+                    }
+                } else if let Some(transfer) = fm.cancel_outgoing(friend_number, file_number) {
+                    info!("Friend {friend_number} rejected {}", transfer.filename);
+                    if let Err(e) = self.store.update_file_transfer_status(&transfer.id, "cancelled", transfer.sent as i64) {
+                        error!("Failed to persist transfer status: {e}");
+                    }
+                }
             }
-            ProxyType::Http => {
-                info!("Using HTTP proxy: {}:{}", host, proxy_config.port);
-                builder = builder.proxy_http(host, proxy_config.port);
+            FileControl::Resume => {
+                if let Some(transfer) = fm.outgoing(friend_number, file_number) {
+                    if let Err(e) = self.store.update_file_transfer_status(&transfer.id, "active", transfer.sent as i64) {
+                        error!("Failed to persist transfer status: {e}");
+                    }
+                }
             }
-            ProxyType::None => {}
+            FileControl::Pause => {}
         }
     }
+    fn on_file_chunk_request(&self, friend_number: u32, file_number: u32, position: u64, length: usize) {
+        // A zero-length request means the friend has received everything
+        if length == 0 {
+            let finished = self
+                .file_manager
+                .lock()
+                .ok()
+                .and_then(|mut fm| fm.finish_outgoing(friend_number, file_number));
+            if let Some(transfer) = finished {
+                info!("Sent {} to friend {friend_number}", transfer.filename);
+                if let Err(e) = self.store.update_file_transfer_status(&transfer.id, "completed", transfer.file_size as i64) {
+                    error!("Failed to persist transfer status: {e}");
+                }
+                if let Err(e) = self.store.mark_message_delivered(&transfer.message_id) {
+                    error!("Failed to mark file message delivered: {e}");
+                }
+            }
+            return;
+        }
 
-    // Apply savedata if loading existing profile
-    let tox = if let Some(data) = savedata {
-        builder.savedata(data).build()
-    } else {
-        builder.build()
-    };
-
-    let tox = match tox {
-        Ok(t) => t,
-        Err(e) => {
-            error!("Failed to create Tox instance: {e}");
+        let _ = self.file_action_tx.send(FileAction::SendChunk {
+            friend_number,
+            file_number,
+            position,
+            length,
+        });
+    }
+    fn on_file_recv(&self, friend_number: u32, file_number: u32, kind: u32, file_size: u64, filename: &str) {
+        if FileKind::from_raw(kind) != FileKind::Data {
+            debug!("Ignoring non-data file transfer from friend {friend_number}");
             return;
         }
-    };
 
-    // Set display name if creating new profile
-    if let Some(name) = display_name {
-        if let Err(e) = tox.set_name(name) {
-            error!("Failed to set name: {e}");
+        let file_id = self.query_file_id(friend_number, file_number).map(|id| file_manager::file_id_to_hex(&id));
+        if let Some(ref file_id) = file_id {
+            if self.try_resume_incoming(friend_number, file_number, file_id) {
+                return;
+            }
         }
-        if let Err(e) = tox.set_status_message("Using Toxcord") {
-            error!("Failed to set status message: {e}");
+
+        // Only small images and voice notes are auto-accepted and shown inline
+        let Some(mime_type) = file_manager::inline_mime_type(filename) else {
+            info!("Not auto-accepting file '{filename}' from friend {friend_number}");
+            return;
+        };
+        if file_size == 0 || file_size > file_manager::MAX_INLINE_IMAGE_SIZE {
+            info!("Not auto-accepting '{filename}' ({file_size} bytes) from friend {friend_number}");
+            return;
         }
-    }
 
-    // Register callbacks
-    tox.register_callbacks();
+        let msg_id = uuid::Uuid::new_v4().to_string();
+        let timestamp = chrono::Utc::now().to_rfc3339();
 
-    // Channel for offline queue flush requests from callbacks
-    let (offline_flush_tx, offline_flush_rx) = std::sync::mpsc::channel::<u32>();
+        let started = self.file_manager.lock().map_err(|e| e.to_string()).and_then(|mut fm| {
+            let dir = file_manager::attachments_dir(&self.store);
+            fm.start_incoming(&dir, friend_number, file_number, filename, mime_type, file_size, &msg_id)
+                .map(|t| (t.id.clone(), t.filename.clone(), t.path.to_string_lossy().to_string()))
+        });
+        let (transfer_id, filename, path) = match started {
+            Ok(t) => t,
+            Err(e) => {
+                error!("Failed to start incoming transfer: {e}");
+                let _ = self.file_action_tx.send(FileAction::Cancel { friend_number, file_number });
+                return;
+            }
+        };
 
-    // Create event handler with DB persistence
-    let handler: Box<dyn ToxEventHandler> = Box::new(TauriEventHandler {
-        app_handle: app_handle.clone(),
-        store: store.clone(),
-        offline_flush_tx,
-        tox_raw: tox.raw(),
-    });
-    let handler_ptr = Box::into_raw(Box::new(handler));
-
-    // Create ToxAV instance (must be on same thread as Tox)
-    let toxav = match ToxAvInstance::new(&tox) {
-        Ok(av) => {
-            info!("ToxAV instance created");
-            Some(av)
-        }
-        Err(e) => {
-            error!("Failed to create ToxAV instance: {e} - calls will be disabled");
-            None
+        let transfer = crate::db::message_store::FileTransferRecord {
+            id: transfer_id,
+            friend_number: friend_number as i64,
+            file_number: file_number as i64,
+            filename: filename.clone(),
+            file_size: file_size as i64,
+            file_path: Some(path),
+            direction: "incoming".to_string(),
+            status: "active".to_string(),
+            bytes_transferred: 0,
+            message_id: Some(msg_id.clone()),
+            file_id,
+        };
+        if let Err(e) = self.store.insert_file_transfer(&transfer) {
+            error!("Failed to persist file transfer: {e}");
         }
-    };
 
-    // Create shared audio mixer for combining received audio from multiple peers
-    let mixer = Arc::new(std::sync::Mutex::new(AudioMixer::default()));
-
-    // Create AV manager and event handler for ToxAV callbacks
-    let av_manager = Arc::new(std::sync::Mutex::new(AvManager::new()));
-    let av_handler: Option<*mut Box<dyn ToxAvEventHandler>> = if toxav.is_some() {
-        let handler: Box<dyn ToxAvEventHandler> = Box::new(TauriAvEventHandler::new(
-            app_handle.clone(),
-            av_manager.clone(),
-            mixer.clone(),
-        ));
-        let handler_ptr = Box::into_raw(Box::new(handler));
-        // Register ToxAV callbacks with our handler
-        if let Some(ref av) = toxav {
-            av.register_callbacks_with_userdata(handler_ptr as *mut std::ffi::c_void);
+        // The message shows up right away; the preview follows via AttachmentReady
+        let incognito = self.incognito.is_active(friend_number);
+        let expires_at = if incognito {
+            None
+        } else {
+            self.store.disappearing_expiry(friend_number).unwrap_or_else(|e| {
+                error!("{e}");
+                None
+            })
+        };
+        let record = crate::db::message_store::DirectMessageRecord {
+            id: msg_id.clone(),
+            friend_number: friend_number as i64,
+            sender: "friend".to_string(),
+            content: filename.clone(),
+            message_type: file_manager::file_message_type(&filename).to_string(),
+            timestamp: timestamp.clone(),
+            is_outgoing: false,
+            delivered: true,
+            read: false,
+            expires_at,
+        };
+        if !incognito {
+            if let Err(e) = self.store.insert_direct_message(&record) {
+                error!("Failed to persist file message: {e}");
+            }
+            crate::tray::refresh_unread_badge(&self.app_handle, &self.store);
         }
-        Some(handler_ptr)
-    } else {
-        None
-    };
 
-    // Audio capture channel - capture thread sends frames here
-    let (audio_tx, mut audio_rx) = tokio::sync::mpsc::unbounded_channel::<Vec<i16>>();
-
-    // Audio capture and playback (managed on this thread, started when calls are active)
-    let mut audio_capture: Option<AudioCapture> = None;
-    let mut audio_playback: Option<AudioPlayback> = None;
-    let mut audio_active = false;
-
-    // Video capture channel - capture thread sends frames here
-    let (video_tx, mut video_rx) = tokio::sync::mpsc::unbounded_channel::<VideoFrameData>();
-    // Video capture error channel - capture thread sends errors here
-    let (video_error_tx, mut video_error_rx) = tokio::sync::mpsc::unbounded_channel::<VideoCaptureError>();
+        self.emit(ToxEvent::FriendMessage {
+            friend_number,
+            message_type: file_manager::file_message_type(&filename).to_string(),
+            message: filename,
+            id: msg_id,
+            timestamp,
+            formatted: Vec::new(),
+            locked: false,
+            expires_at,
+            incognito,
+            sticker: None,
+        });
 
-    // Video capture (managed on this thread, started when video calls are active)
-    let mut video_capture: Option<VideoCapture> = None;
-    let mut screen_capture: Option<ScreenCapture> = None;
-    let mut video_active = false;
-    let mut video_capture_failed = false; // Tracks if capture failed, to avoid retry loop
+        let _ = self.file_action_tx.send(FileAction::Accept { friend_number, file_number, position: 0 });
+    }
+    fn on_file_recv_chunk(&self, friend_number: u32, file_number: u32, position: u64, data: &[u8]) {
+        let Ok(mut fm) = self.file_manager.lock() else {
+            return;
+        };
 
-    // Bootstrap to DHT nodes and add TCP relays for NAT traversal fallback
-    for node in default_bootstrap_nodes() {
-        // Bootstrap for DHT discovery (UDP)
-        if let Err(e) = tox.bootstrap(&node.address, node.port, &node.public_key) {
-            warn!("Failed to bootstrap to {}: {e}", node.address);
+        // A zero-length chunk marks the end of the transfer
+        if data.is_empty() {
+            if let Some(transfer) = fm.finish_incoming(friend_number, file_number) {
+                drop(fm);
+                info!("Received {} from friend {friend_number}", transfer.filename);
+                if let Err(e) = self.store.update_file_transfer_status(&transfer.id, "completed", transfer.received as i64) {
+                    error!("Failed to persist transfer status: {e}");
+                }
+                self.process_completed_image(transfer);
+            }
+            return;
         }
 
-        // Add TCP relay for each supported port - essential for NAT traversal
-        // when direct UDP connection fails (common behind symmetric NATs/firewalls)
-        for tcp_port in &node.tcp_ports {
-            if let Err(e) = tox.add_tcp_relay(&node.address, *tcp_port, &node.public_key) {
-                warn!("Failed to add TCP relay {}:{}: {e}", node.address, tcp_port);
-            } else {
-                debug!("Added TCP relay {}:{}", node.address, tcp_port);
+        match fm.write_chunk(friend_number, file_number, position, data) {
+            Ok(()) => {
+                if let Some((id, received)) = fm.checkpoint(friend_number, file_number) {
+                    if let Err(e) = self.store.update_file_transfer_status(&id, "active", received as i64) {
+                        error!("Failed to persist transfer progress: {e}");
+                    }
+                }
+            }
+            Err(e) => {
+                error!("Incoming transfer {file_number} from friend {friend_number} failed: {e}");
+                if let Some(transfer) = fm.cancel_incoming(friend_number, file_number) {
+                    if let Err(e) = self.store.update_file_transfer_status(&transfer.id, "cancelled", transfer.received as i64) {
+                        error!("Failed to persist transfer status: {e}");
+                    }
+                }
+                let _ = self.file_action_tx.send(FileAction::Cancel { friend_number, file_number });
             }
         }
     }
+    fn on_group_invite(&self, friend_number: u32, invite_data: &[u8], group_name: &str) {
+        info!("Group invite from friend {friend_number}: {group_name}");
+        let password_required = self
+            .invite_notices
+            .lock()
+            .ok()
+            .and_then(|mut notices| notices.remove(&friend_number))
+            .is_some_and(|notice| notice.is_for(group_name) && notice.password_required);
+        let invite = GroupInviteRecord {
+            id: uuid::Uuid::new_v4().to_string(),
+            friend_number: friend_number as i64,
+            group_name: group_name.to_string(),
+            invite_data: invite_data.to_vec(),
+            received_at: chrono::Utc::now().to_rfc3339(),
+            password_required,
+        };
+        let invite_id = self.store.insert_group_invite(&invite).unwrap_or_else(|e| {
+            error!("{e}");
+            invite.id.clone()
+        });
+        let event = ToxEvent::GroupInvite {
+            invite_id: invite_id.clone(),
+            friend_number,
+            invite_data: invite.invite_data,
+            group_name: invite.group_name,
+            password_required,
+        };
 
-    info!("Bootstrap complete: {} nodes configured with TCP relay support",
-          default_bootstrap_nodes().len());
+        // Invites that need a password are always answered by hand
+        if password_required || !self.store.get_auto_accept_group_invites(friend_number).unwrap_or(false) {
+            self.emit(event);
+            return;
+        }
+        // Joining goes through this thread, so it can't wait here
+        let app_handle = self.app_handle.clone();
+        tauri::async_runtime::spawn(async move {
+            let state = app_handle.state::<AppState>();
+            match crate::commands::guilds::join_group_invite(&state, &invite_id, "").await {
+                Ok(guild) => info!("Auto-accepted invite to '{}' from friend {friend_number}", guild.name),
+                Err(e) => {
+                    // Leave it to be answered by hand
+                    warn!("Failed to auto-accept group invite from friend {friend_number}: {e}");
+                    if let Err(e) = app_handle.emit("tox://event", &event) {
+                        error!("Failed to emit Tauri event: {e}");
+                    }
+                }
+            }
+        });
+    }
 
-    // I2P/Proxy verification logging
-    match proxy_config.proxy_type {
-        ProxyType::Socks5 => {
-            info!("[I2P-CHECK] All Tox traffic routed through SOCKS5 proxy at {}:{}",
-                  proxy_config.host.as_deref().unwrap_or("127.0.0.1"), proxy_config.port);
-            info!("[I2P-CHECK] UDP disabled - using TCP relay mode only (required for I2P/Tor)");
+    fn on_group_peer_join(&self, group_number: u32, peer_id: u32) {
+        let name = self.query_peer_name(group_number, peer_id);
+        let public_key = self.query_peer_public_key(group_number, peer_id);
+        info!("Peer joined group {group_number}: {name} ({peer_id})");
+        let newcomer = self.is_newcomer(group_number, &public_key);
+        self.cache_guild_member(group_number, &public_key, &name);
+        if newcomer {
+            self.show_welcome(group_number, peer_id, &public_key, &name);
         }
-        ProxyType::Http => {
-            info!("[I2P-CHECK] All Tox traffic routed through HTTP proxy at {}:{}",
-                  proxy_config.host.as_deref().unwrap_or("127.0.0.1"), proxy_config.port);
-            info!("[I2P-CHECK] UDP disabled - using TCP relay mode only");
+        if let Ok(mut peers) = self.group_peers.lock() {
+            peers.insert((group_number, peer_id), public_key.clone());
         }
-        ProxyType::None => {
-            debug!("[I2P-CHECK] No proxy configured - direct connections enabled");
+        let _ = self.presence_tx.send(PresenceAction::PeerJoined(group_number, peer_id));
+        let _ = self.history_tx.send(HistoryAction::PeerJoined(group_number, peer_id));
+        self.emit(ToxEvent::GroupPeerJoin {
+            group_number,
+            peer_id,
+            name,
+            public_key,
+        });
+    }
+
+    fn on_group_peer_exit(&self, group_number: u32, peer_id: u32, _exit_type: u32, name: &str, _message: &str) {
+        info!("Peer left group {group_number}: {name} ({peer_id})");
+        // Tox has already dropped the peer, so its key comes from our own map
+        let public_key = self.group_peers.lock().ok().and_then(|mut peers| peers.remove(&(group_number, peer_id)));
+        if let (Some(public_key), Some(guild_id)) = (public_key, self.guild_id_for_group(group_number)) {
+            if let Err(e) = self.store.touch_guild_member(&guild_id, &public_key) {
+                error!("{e}");
+            }
         }
+        if let Ok(mut fm) = self.file_manager.lock() {
+            for download in fm.cancel_group_downloads_from(group_number, peer_id) {
+                warn!("Download of {} stopped: {name} left the group", download.offer.filename);
+            }
+        }
+        self.emit(ToxEvent::GroupPeerExit {
+            group_number,
+            peer_id,
+            name: name.to_string(),
+        });
     }
 
-    info!("Tox thread started, address: {}", tox.self_address());
+    fn on_group_peer_name(&self, group_number: u32, peer_id: u32, name: &str) {
+        let public_key = self.query_peer_public_key(group_number, peer_id);
+        self.cache_guild_member(group_number, &public_key, name);
+        self.emit(ToxEvent::GroupPeerName {
+            group_number,
+            peer_id,
+            name: name.to_string(),
+        });
+    }
 
-    // Sync existing friends to DB
-    for friend_num in tox.friend_list() {
-        let pk = tox.friend_public_key(friend_num).unwrap_or(ToxPublicKey(String::new()));
-        let name = tox.friend_name(friend_num).unwrap_or_default();
-        if let Err(e) = store.upsert_friend(friend_num, &pk.0, &name, "") {
-            error!("Failed to sync friend {friend_num} to DB: {e}");
+    fn on_group_message(&self, group_number: u32, peer_id: u32, message_type: MessageType, message: &str, _message_id: u32) {
+        self.message_activity.store(true, Ordering::Relaxed);
+        // Long messages arrive in parts; wait for the rest
+        let Some(message) = self.texts.group_text(group_number, peer_id, message) else {
+            return;
+        };
+        let sender = GroupSender {
+            peer_id,
+            name: self.query_peer_name(group_number, peer_id),
+            public_key: self.query_peer_public_key(group_number, peer_id),
+            moderator: matches!(
+                self.query_peer_role(group_number, peer_id),
+                Some(GroupRole::Founder | GroupRole::Moderator)
+            ),
+        };
+        let self_pk = self.query_self_public_key(group_number);
+        let Some(text) = incoming::receive_group_text(&self.store, group_number, &sender, &self_pk, message_type, &message)
+        else {
+            return;
+        };
+        let GroupText { record, formatted, sticker, ignored } = text;
+
+        // Queued, as reconnecting to a busy group can bring hundreds at once.
+        // A message we already have (same ID) is left alone when written.
+        if let Err(e) = self.store.queue_channel_message(record.clone()) {
+            error!("Failed to persist group message: {e}");
+        }
+        if ignored {
+            return;
         }
-    }
 
-    // Log all existing guilds before sync
-    if let Ok(all_guilds) = store.get_guilds() {
-        info!("Guilds before sync:");
-        for g in &all_guilds {
-            info!("  Guild '{}' (id={}) -> group_number={:?}", g.name, g.id, g.metadata_group_number);
+        if !record.filtered && sticker.is_none() {
+            self.app_handle.state::<AppState>().accessibility.announce(
+                &Conversation::Channel(record.channel_id.clone()),
+                Some(&record.sender_name),
+                &record.content,
+                record.mentions_me && record.notify,
+            );
         }
+
+        self.emit(ToxEvent::GroupMessage {
+            group_number,
+            peer_id,
+            sender_name: record.sender_name,
+            sender_pk: record.sender_public_key,
+            formatted,
+            message: record.content,
+            message_type: record.message_type,
+            id: record.id,
+            timestamp: record.timestamp,
+            channel_id: record.channel_id,
+            mentions_me: record.mentions_me,
+            filtered: record.filtered,
+            sender_verified: record.sender_verified,
+            signer_public_key: record.signer_public_key,
+            sticker: sticker.map(|reference| stickers::resolve(&self.store, &reference)),
+        });
     }
 
-    // Log all Tox groups
-    let group_count = tox.group_get_number_groups();
-    let tox_groups = tox.group_list();
-    info!("Tox group count: {}, groups found: {:?}", group_count, tox_groups);
+    fn on_group_custom_packet(&self, group_number: u32, peer_id: u32, data: &[u8]) {
+        if let Some(FileSharePacket::Offer(offer)) = FileSharePacket::from_bytes(data) {
+            self.on_group_file_offer(group_number, peer_id, offer);
+            return;
+        }
+        if let Some(packet) = PresencePacket::from_group_packet(data) {
+            let public_key = self.query_peer_public_key(group_number, peer_id);
+            self.on_peer_presence(&public_key, packet);
+            return;
+        }
+        if let Some(manifest) = GuildManifest::from_packet(data) {
+            self.on_guild_manifest(group_number, peer_id, manifest);
+            return;
+        }
+        if let Some(layout) = GuildLayout::from_packet(data) {
+            self.on_guild_layout(group_number, peer_id, layout);
+            return;
+        }
+        if let Some(packet) = PollPacket::from_bytes(data) {
+            self.on_poll_packet(group_number, peer_id, packet);
+            return;
+        }
+        if let Some(packet) = PurgePacket::from_bytes(data) {
+            self.on_purge_packet(group_number, peer_id, packet);
+            return;
+        }
+        if let Some(packet) = EventPacket::from_bytes(data) {
+            self.on_event_packet(group_number, peer_id, packet);
+            return;
+        }
+        self.emit(ToxEvent::GroupCustomPacket {
+            group_number,
+            peer_id,
+            data: data.to_vec(),
+        });
+    }
 
-    // Sync existing groups to DB - match by name and update group_number
-    // Also reconnect each group to ensure it can send/receive messages
-    for group_num in tox_groups {
-        if let Ok(group_info) = tox.group_get_info(group_num) {
-            info!("Tox group {}: name='{}' peers={}", group_num, group_info.name, group_info.peer_count);
-            // Try to find existing guild by name
-            match store.get_guild_by_name(&group_info.name) {
-                Ok(Some(guild)) => {
-                    // Update group_number if it changed
-                    if guild.metadata_group_number != Some(group_num as i64) {
-                        info!("Updating guild '{}' group_number: {:?} -> {}",
-                              guild.name, guild.metadata_group_number, group_num);
-                        if let Err(e) = store.update_guild_group_number(&guild.id, group_num as i64) {
-                            error!("Failed to update guild group_number: {e}");
-                        }
-                    }
-                }
-                Ok(None) => {
-                    // No guild found, create one
-                    info!("Creating guild record for Tox group '{}' ({})", group_info.name, group_num);
-                    let guild_id = uuid::Uuid::new_v4().to_string();
-                    if let Err(e) = store.insert_guild(&guild_id, &group_info.name, Some(group_num as i64), "", "server") {
-                        error!("Failed to create guild for group {}: {e}", group_num);
-                    } else {
-                        // Create default channel
-                        let channel_id = uuid::Uuid::new_v4().to_string();
-                        if let Err(e) = store.insert_channel(&channel_id, &guild_id, "general", "text", 0) {
-                            error!("Failed to create default channel: {e}");
-                        }
-                    }
-                }
-                Err(e) => {
-                    error!("Failed to lookup guild by name: {e}");
+    fn on_group_custom_private_packet(&self, group_number: u32, peer_id: u32, data: &[u8]) {
+        match FileSharePacket::from_bytes(data) {
+            Some(FileSharePacket::Request(request)) => self.serve_group_file(group_number, peer_id, request),
+            Some(FileSharePacket::Chunk(chunk)) => self.on_group_file_chunk(group_number, chunk),
+            // Other custom private packets will be handled by protocol routing layer
+            _ => {
+                if let Some(packet) = PresencePacket::from_group_packet(data) {
+                    let public_key = self.query_peer_public_key(group_number, peer_id);
+                    self.on_peer_presence(&public_key, packet);
+                } else if let Some(manifest) = GuildManifest::from_packet(data) {
+                    self.on_guild_manifest(group_number, peer_id, manifest);
+                } else if let Some(layout) = GuildLayout::from_packet(data) {
+                    self.on_guild_layout(group_number, peer_id, layout);
+                } else if let Some(packet) = HistoryPacket::from_bytes(data) {
+                    let _ = self.history_tx.send(HistoryAction::Received(group_number, peer_id, packet));
                 }
             }
-
-            // Reconnect the group to ensure it can send/receive messages after restart
-            if let Err(e) = tox.group_reconnect(group_num) {
-                warn!("Failed to reconnect group {}: {e}", group_num);
-            } else {
-                info!("Reconnected group {} to DHT", group_num);
-            }
         }
     }
 
-    // Log guilds after sync
-    if let Ok(all_guilds) = store.get_guilds() {
-        info!("Guilds after sync:");
-        for g in &all_guilds {
-            info!("  Guild '{}' (id={}) -> group_number={:?}", g.name, g.id, g.metadata_group_number);
+    fn on_group_self_join(&self, group_number: u32) {
+        info!("Self joined group {group_number}");
+        if let Ok(mut joins) = self.self_joins.lock() {
+            joins.insert(group_number, Instant::now());
         }
-        // Check for duplicate group_numbers
-        let mut seen_group_nums = std::collections::HashMap::new();
-        for g in &all_guilds {
-            if let Some(gn) = g.metadata_group_number {
-                seen_group_nums.entry(gn).or_insert_with(Vec::new).push(g.name.clone());
+        let _ = self.history_tx.send(HistoryAction::Joined(group_number));
+        self.emit(ToxEvent::GroupSelfJoin { group_number });
+    }
+
+    fn on_group_join_fail(&self, group_number: u32, fail_type: u32) {
+        let ft = match fail_type {
+            0 => "peer_limit",
+            1 => "invalid_password",
+            _ => "unknown",
+        };
+        warn!("Failed to join group {group_number}: {ft}");
+        self.emit(ToxEvent::GroupJoinFail {
+            group_number,
+            fail_type: ft.to_string(),
+        });
+    }
+
+    fn on_group_topic(&self, group_number: u32, peer_id: u32, topic: &str) {
+        // Tox repeats the current topic when we (re)join; only log changes
+        if let Some(guild_id) = self.guild_id_for_group(group_number) {
+            let last = self.store.get_last_audit_details(&guild_id, "topic_change").unwrap_or_default();
+            if last.as_deref() != Some(topic) {
+                self.record_audit(group_number, "topic_change", peer_id, None, "", topic);
             }
         }
-        for (gn, names) in &seen_group_nums {
-            if names.len() > 1 {
-                warn!("DUPLICATE group_number {}: guilds {:?} - this will cause routing issues!", gn, names);
-            }
+        self.emit(ToxEvent::GroupTopicChange {
+            group_number,
+            topic: topic.to_string(),
+        });
+    }
+
+    fn on_group_moderation(&self, group_number: u32, source_peer_id: u32, target_peer_id: u32, event: GroupModEvent) {
+        let target_public_key = self.peer_public_key(group_number, target_peer_id);
+        let target_name = self.query_peer_name(group_number, target_peer_id);
+        info!("Moderation in group {group_number}: {event:?} on {target_name} ({target_peer_id})");
+        let (action, details) = match event {
+            GroupModEvent::Kick => ("kick", ""),
+            GroupModEvent::Role(role) => ("role_change", role_name(role)),
+        };
+        self.record_audit(group_number, action, source_peer_id, Some(&target_public_key), &target_name, details);
+    }
+
+    fn on_group_peer_status(&self, group_number: u32, peer_id: u32, status: UserStatus) {
+        if self.is_ignored(group_number, &self.query_peer_public_key(group_number, peer_id)) {
+            return;
         }
+        let s = match status {
+            UserStatus::None => "online",
+            UserStatus::Away => "away",
+            UserStatus::Busy => "busy",
+        };
+        self.emit(ToxEvent::GroupPeerStatus {
+            group_number,
+            peer_id,
+            status: s.to_string(),
+        });
     }
+}
 
-    // Signal that sync is complete
-    if let Some(tx) = sync_complete_tx {
-        let _ = tx.send(());
+/// State of the Tox instance for the diagnostics report
+#[derive(Clone, serde::Serialize)]
+pub struct ToxDiagnostics {
+    pub connection: ConnectionStatus,
+    pub friends_online: usize,
+    pub friends_total: usize,
+    pub groups: Vec<GroupDiagnostics>,
+}
+
+/// Size of toxcore's DHT close list (`LCLIENT_LIST` in DHT.h)
+const DHT_CLOSELIST_CAPACITY: u16 = 1024;
+
+/// How we're attached to the Tox network, for users checking what they
+/// expose. toxcore doesn't report the address DHT nodes see us at, so there's
+/// no public IP here.
+#[derive(Clone, serde::Serialize)]
+pub struct DhtInfo {
+    pub connection: ConnectionStatus,
+    /// Nodes in our DHT close list
+    pub dht_nodes: u16,
+    /// Of those, nodes that store announcements
+    pub announce_nodes: u16,
+    pub dht_capacity: u16,
+    /// Connected to the network directly over UDP rather than through a relay
+    pub udp_active: bool,
+    /// None with UDP disabled, e.g. behind a proxy
+    pub udp_port: Option<u16>,
+    /// Port of our own TCP relay server, None unless one is running
+    pub tcp_port: Option<u16>,
+    /// TCP relays toxcore was given. It stays connected to a few of them, and
+    /// all traffic goes through those when the connection is TCP.
+    pub tcp_relays: usize,
+}
+
+#[derive(Clone, serde::Serialize)]
+pub struct GroupDiagnostics {
+    pub group_number: u32,
+    pub name: String,
+    pub connected: bool,
+    pub peer_count: u32,
+}
+
+/// Manages the Tox instance on a dedicated thread
+pub struct ToxManager {
+    cmd_tx: mpsc::Sender<ToxCommand>,
+    rich_presence: RichPresence,
+    conversation_locks: ConversationLocks,
+    incognito: Incognito,
+    profile_path: PathBuf,
+}
+
+impl ToxManager {
+    /// Start a new ToxManager with a fresh profile, with a new identity or
+    /// the one a recovery phrase gave back
+    pub fn create_profile(
+        app_handle: AppHandle,
+        profile_name: &str,
+        password: &str,
+        display_name: &str,
+        secret_key: Option<[u8; 32]>,
+        store: Arc<MessageStore>,
+    ) -> Result<Arc<Mutex<Self>>, String> {
+        let profile = ProfileDir::new(profile_name);
+        if profile.exists() {
+            return Err(format!("Profile '{profile_name}' already exists"));
+        }
+        // A restored identity starts from savedata holding just its key
+        let savedata = secret_key
+            .map(|key| {
+                ToxOptionsBuilder::new()
+                    .secret_key(key)
+                    .udp_enabled(false)
+                    .local_discovery_enabled(false)
+                    .build()
+                    .map(|tox| tox.savedata())
+                    .map_err(|e| format!("Failed to restore identity: {e}"))
+            })
+            .transpose()?;
+        std::fs::create_dir_all(&profile.dir).map_err(|e| format!("Failed to create profile dir: {e}"))?;
+        let profile_path = profile.savedata();
+
+        let (cmd_tx, cmd_rx) = mpsc::channel(256);
+
+        let rich_presence = RichPresence::default();
+        let conversation_locks = ConversationLocks::default();
+        let incognito = Incognito::default();
+
+        spawn_tox_thread(
+            app_handle,
+            cmd_rx,
+            savedata,
+            password,
+            profile_path.clone(),
+            Some(display_name.to_string()),
+            store,
+            None,
+            &rich_presence,
+            &conversation_locks,
+            &incognito,
+        );
+
+        Ok(Arc::new(Mutex::new(Self {
+            cmd_tx,
+            rich_presence,
+            conversation_locks,
+            incognito,
+            profile_path,
+        })))
     }
 
-    // Save the initial profile
-    let password = password.to_string();
-    let profile_path = profile_path.clone();
-    save_profile(&tox, &password, &profile_path);
+    /// Start a ToxManager from an existing profile
+    pub fn load_profile(
+        app_handle: AppHandle,
+        profile_name: &str,
+        password: &str,
+        store: Arc<MessageStore>,
+    ) -> Result<Arc<Mutex<Self>>, String> {
+        let profile_path = ProfileDir::new(profile_name).savedata();
 
-    // Main event loop
-    loop {
-        while let Ok(cmd) = cmd_rx.try_recv() {
-            match cmd {
-                ToxCommand::GetAddress(reply) => {
-                    let _ = reply.send(tox.self_address());
-                }
-                ToxCommand::GetConnectionStatus(reply) => {
-                    let _ = reply.send(tox.self_connection_status());
-                }
-                ToxCommand::GetProfileInfo(reply) => {
-                    let _ = reply.send(tox.profile_info());
+        if !profile_path.exists() {
+            return Err(format!("Profile '{profile_name}' not found"));
+        }
+
+        let savedata = profile_file::read_profile(&profile_path, password)?;
+
+        let (cmd_tx, cmd_rx) = mpsc::channel(256);
+        let (sync_tx, sync_rx) = std::sync::mpsc::channel::<()>();
+
+        let rich_presence = RichPresence::default();
+        let conversation_locks = ConversationLocks::default();
+        let incognito = Incognito::default();
+
+        spawn_tox_thread(
+            app_handle,
+            cmd_rx,
+            Some(savedata),
+            password,
+            profile_path.clone(),
+            None,
+            store,
+            Some(sync_tx),
+            &rich_presence,
+            &conversation_locks,
+            &incognito,
+        );
+
+        // Wait for the sync to complete before returning
+        let _ = sync_rx.recv_timeout(std::time::Duration::from_secs(5));
+
+        Ok(Arc::new(Mutex::new(Self {
+            cmd_tx,
+            rich_presence,
+            conversation_locks,
+            incognito,
+            profile_path,
+        })))
+    }
+
+    /// Our own and cached peer activities
+    pub fn rich_presence(&self) -> &RichPresence {
+        &self.rich_presence
+    }
+
+    /// Keys of conversations locked with a passphrase
+    pub fn conversation_locks(&self) -> &ConversationLocks {
+        &self.conversation_locks
+    }
+
+    /// Conversations whose messages aren't stored
+    pub fn incognito(&self) -> &Incognito {
+        &self.incognito
+    }
+
+    /// Send a command to the Tox thread
+    pub async fn send_command(&self, cmd: ToxCommand) -> Result<(), ToxcordError> {
+        // The tox thread only stops when we log out
+        self.cmd_tx.send(cmd).await.map_err(|_| ToxcordError::NotLoggedIn)
+    }
+
+    /// Get the Tox address
+    pub async fn get_address(&self) -> Result<ToxAddress, String> {
+        let (tx, rx) = oneshot::channel();
+        self.send_command(ToxCommand::GetAddress(tx)).await?;
+        rx.await.map_err(|_| "Failed to receive response".to_string())
+    }
+
+    pub async fn get_nospam(&self) -> Result<u32, String> {
+        let (tx, rx) = oneshot::channel();
+        self.send_command(ToxCommand::GetNospam(tx)).await?;
+        rx.await.map_err(|_| "Failed to receive response".to_string())
+    }
+
+    pub async fn get_secret_key(&self) -> Result<[u8; 32], String> {
+        let (tx, rx) = oneshot::channel();
+        self.send_command(ToxCommand::GetSecretKey(tx)).await?;
+        rx.await.map_err(|_| "Failed to receive response".to_string())
+    }
+
+    /// Change the nospam, returning the new address
+    pub async fn set_nospam(&self, nospam: u32) -> Result<ToxAddress, String> {
+        let (tx, rx) = oneshot::channel();
+        self.send_command(ToxCommand::SetNospam(nospam, tx)).await?;
+        rx.await.map_err(|_| "Failed to receive response".to_string())?
+    }
+
+    /// Check a password against the saved profile
+    pub fn verify_password(&self, password: &str) -> Result<(), String> {
+        let savedata = std::fs::read(&self.profile_path).map_err(|e| format!("Failed to read profile: {e}"))?;
+        let valid = if is_data_encrypted(&savedata) {
+            decrypt_savedata(&savedata, password).is_ok()
+        } else {
+            password.is_empty()
+        };
+        valid.then_some(()).ok_or_else(|| "Current password is incorrect".to_string())
+    }
+
+    /// Re-encrypt the saved profile with a new password
+    pub async fn change_password(&self, password: &str) -> Result<(), String> {
+        let (tx, rx) = oneshot::channel();
+        self.send_command(ToxCommand::ChangePassword(password.to_string(), tx)).await?;
+        rx.await.map_err(|_| "Failed to receive response".to_string())?
+    }
+
+    /// Get connection status
+    pub async fn get_connection_status(&self) -> Result<ConnectionStatus, String> {
+        let (tx, rx) = oneshot::channel();
+        self.send_command(ToxCommand::GetConnectionStatus(tx)).await?;
+        rx.await.map_err(|_| "Failed to receive response".to_string())
+    }
+
+    /// Get profile info
+    pub async fn get_profile_info(&self) -> Result<ProfileInfo, String> {
+        let (tx, rx) = oneshot::channel();
+        self.send_command(ToxCommand::GetProfileInfo(tx)).await?;
+        rx.await.map_err(|_| "Failed to receive response".to_string())
+    }
+
+    /// Get the state of the Tox instance for the diagnostics report
+    pub async fn get_diagnostics(&self) -> Result<ToxDiagnostics, String> {
+        let (tx, rx) = oneshot::channel();
+        self.send_command(ToxCommand::GetDiagnostics(tx)).await?;
+        rx.await.map_err(|_| "Failed to receive response".to_string())
+    }
+
+    /// Get the DHT and port state
+    pub async fn get_dht_info(&self) -> Result<DhtInfo, String> {
+        let (tx, rx) = oneshot::channel();
+        self.send_command(ToxCommand::GetDhtInfo(tx)).await?;
+        rx.await.map_err(|_| "Failed to receive response".to_string())
+    }
+
+    /// Shutdown the Tox thread
+    pub async fn shutdown(&self) -> Result<(), String> {
+        let (tx, rx) = oneshot::channel();
+        self.send_command(ToxCommand::Shutdown(tx)).await?;
+        rx.await.map_err(|_| "Failed to shutdown".to_string())
+    }
+
+    // ─── ToxAV Methods ───────────────────────────────────────────────────────
+
+    /// Start a call with a friend at the given quality
+    pub async fn call(&self, friend_number: u32, with_video: bool, quality: CallPreferences) -> Result<(), String> {
+        let (tx, rx) = oneshot::channel();
+        self.send_command(ToxCommand::AvCall {
+            friend_number,
+            audio_bit_rate: quality.audio_bit_rate,
+            video_bit_rate: if with_video { quality.video_bit_rate } else { 0 },
+            reply: tx,
+        })
+        .await?;
+        rx.await.map_err(|_| "Failed to receive response".to_string())?
+    }
+
+    /// Answer an incoming call at the given quality
+    pub async fn answer(&self, friend_number: u32, with_video: bool, quality: CallPreferences) -> Result<(), String> {
+        let (tx, rx) = oneshot::channel();
+        self.send_command(ToxCommand::AvAnswer {
+            friend_number,
+            audio_bit_rate: quality.audio_bit_rate,
+            video_bit_rate: if with_video { quality.video_bit_rate } else { 0 },
+            reply: tx,
+        })
+        .await?;
+        rx.await.map_err(|_| "Failed to receive response".to_string())?
+    }
+
+    /// Hangup a call
+    pub async fn hangup(&self, friend_number: u32) -> Result<(), String> {
+        let (tx, rx) = oneshot::channel();
+        self.send_command(ToxCommand::AvHangup {
+            friend_number,
+            reply: tx,
+        })
+        .await?;
+        rx.await.map_err(|_| "Failed to receive response".to_string())?
+    }
+
+    /// Mute audio for a call
+    pub async fn mute_audio(&self, friend_number: u32) -> Result<(), String> {
+        let (tx, rx) = oneshot::channel();
+        self.send_command(ToxCommand::AvMuteAudio {
+            friend_number,
+            reply: tx,
+        })
+        .await?;
+        rx.await.map_err(|_| "Failed to receive response".to_string())?
+    }
+
+    /// Unmute audio for a call
+    pub async fn unmute_audio(&self, friend_number: u32) -> Result<(), String> {
+        let (tx, rx) = oneshot::channel();
+        self.send_command(ToxCommand::AvUnmuteAudio {
+            friend_number,
+            reply: tx,
+        })
+        .await?;
+        rx.await.map_err(|_| "Failed to receive response".to_string())?
+    }
+
+    /// Hide video for a call
+    pub async fn hide_video(&self, friend_number: u32) -> Result<(), String> {
+        let (tx, rx) = oneshot::channel();
+        self.send_command(ToxCommand::AvHideVideo {
+            friend_number,
+            reply: tx,
+        })
+        .await?;
+        rx.await.map_err(|_| "Failed to receive response".to_string())?
+    }
+
+    /// Show video for a call
+    pub async fn show_video(&self, friend_number: u32) -> Result<(), String> {
+        let (tx, rx) = oneshot::channel();
+        self.send_command(ToxCommand::AvShowVideo {
+            friend_number,
+            reply: tx,
+        })
+        .await?;
+        rx.await.map_err(|_| "Failed to receive response".to_string())?
+    }
+
+    /// Switch the camera of ongoing video calls. The calls keep their
+    /// streams; only the source of the frames changes.
+    pub async fn switch_camera(&self, device_index: Option<u32>) -> Result<(), String> {
+        let (tx, rx) = oneshot::channel();
+        self.send_command(ToxCommand::AvSwitchCamera {
+            device_index,
+            reply: tx,
+        })
+        .await?;
+        rx.await.map_err(|_| "Failed to receive response".to_string())?
+    }
+
+    /// Save the profile now instead of waiting for the next batched save
+    pub async fn save_profile(&self) -> Result<(), String> {
+        let (tx, rx) = oneshot::channel();
+        self.send_command(ToxCommand::SaveProfile(tx)).await?;
+        rx.await.map_err(|_| "Failed to receive response".to_string())?
+    }
+
+    /// Prepare for the computer going to sleep
+    pub async fn suspend(&self) -> Result<(), String> {
+        let (tx, rx) = oneshot::channel();
+        self.send_command(ToxCommand::Suspend(tx)).await?;
+        rx.await.map_err(|_| "Failed to receive response".to_string())
+    }
+
+    /// Recover after the computer woke up. The Tox thread also notices a wake
+    /// on its own, from the clock jumping; this is for when it can't.
+    pub async fn resume(&self) -> Result<(), String> {
+        let (tx, rx) = oneshot::channel();
+        self.send_command(ToxCommand::Resume(tx)).await?;
+        rx.await.map_err(|_| "Failed to receive response".to_string())
+    }
+
+    pub async fn set_power_saving(&self, mode: PowerSavingMode) -> Result<(), String> {
+        let (tx, rx) = oneshot::channel();
+        self.send_command(ToxCommand::SetPowerSaving(mode, tx)).await?;
+        rx.await.map_err(|_| "Failed to receive response".to_string())
+    }
+
+    /// Switch between the public DHT, LAN only and offline
+    pub async fn set_network_mode(&self, mode: NetworkMode) -> Result<(), String> {
+        let (tx, rx) = oneshot::channel();
+        self.send_command(ToxCommand::SetNetworkMode(mode, tx)).await?;
+        rx.await.map_err(|_| "Failed to receive response".to_string())
+    }
+
+    /// Retry connecting to an offline friend, or all of them with None
+    pub async fn refresh_friend_connections(&self, friend_number: Option<u32>) -> Result<Vec<u32>, ToxcordError> {
+        let (tx, rx) = oneshot::channel();
+        self.send_command(ToxCommand::RefreshFriendConnections(friend_number, tx))
+            .await?;
+        rx.await.map_err(|_| ToxcordError::from("Failed to receive response"))?
+    }
+
+    /// Move playback of ongoing calls to another output device (None for the
+    /// default). Audio already mixed but not yet played is kept.
+    pub async fn switch_audio_output(&self, device: Option<String>) -> Result<(), String> {
+        let (tx, rx) = oneshot::channel();
+        self.send_command(ToxCommand::AvSwitchAudioOutput { device, reply: tx })
+            .await?;
+        rx.await.map_err(|_| "Failed to receive response".to_string())?
+    }
+
+    /// Get call state for a friend
+    pub async fn get_call_state(&self, friend_number: u32) -> Option<CallState> {
+        let (tx, rx) = oneshot::channel();
+        if self
+            .send_command(ToxCommand::AvGetCallState {
+                friend_number,
+                reply: tx,
+            })
+            .await
+            .is_err()
+        {
+            return None;
+        }
+        rx.await.ok().flatten()
+    }
+
+    /// Set global microphone mute (applies to all calls)
+    pub async fn set_global_mute(&self, muted: bool) -> Result<(), String> {
+        let (tx, rx) = oneshot::channel();
+        self.send_command(ToxCommand::AvSetGlobalMute { muted, reply: tx })
+            .await?;
+        rx.await.map_err(|_| "Failed to receive response".to_string())?
+    }
+
+    /// Set global deafen (incoming audio for all calls)
+    pub async fn set_global_deafen(&self, deafened: bool) -> Result<(), String> {
+        let (tx, rx) = oneshot::channel();
+        self.send_command(ToxCommand::AvSetGlobalDeafen { deafened, reply: tx })
+            .await?;
+        rx.await.map_err(|_| "Failed to receive response".to_string())?
+    }
+
+    /// Get global (muted, deafened) state
+    pub async fn get_global_audio_state(&self) -> Result<(bool, bool), String> {
+        let (tx, rx) = oneshot::channel();
+        self.send_command(ToxCommand::AvGetGlobalAudioState { reply: tx })
+            .await?;
+        rx.await.map_err(|_| "Failed to receive response".to_string())
+    }
+
+    /// Get state of all current calls
+    pub async fn list_calls(&self) -> Result<Vec<CallState>, String> {
+        let (tx, rx) = oneshot::channel();
+        self.send_command(ToxCommand::AvListCalls { reply: tx }).await?;
+        rx.await.map_err(|_| "Failed to receive response".to_string())
+    }
+
+    /// Get the video streams being received
+    pub async fn list_video_streams(&self) -> Result<Vec<VideoStream>, String> {
+        let (tx, rx) = oneshot::channel();
+        self.send_command(ToxCommand::AvListVideoStreams { reply: tx }).await?;
+        rx.await.map_err(|_| "Failed to receive response".to_string())
+    }
+
+    /// Show or stop showing a peer's video
+    pub async fn set_stream_subscribed(&self, friend_number: u32, subscribed: bool) -> Result<(), String> {
+        let (tx, rx) = oneshot::channel();
+        self.send_command(ToxCommand::AvSetStreamSubscribed {
+            friend_number,
+            subscribed,
+            reply: tx,
+        })
+        .await?;
+        rx.await.map_err(|_| "Failed to receive response".to_string())?
+    }
+
+    /// Set our user status (online/away/busy)
+    /// Offer a file to a friend. Chunks are sent as the friend requests them.
+    pub async fn send_file(
+        &self,
+        friend_number: u32,
+        transfer_id: &str,
+        message_id: &str,
+        filename: &str,
+        path: &std::path::Path,
+    ) -> Result<u32, String> {
+        let (tx, rx) = oneshot::channel();
+        self.send_command(ToxCommand::FileSend {
+            friend_number,
+            transfer_id: transfer_id.to_string(),
+            message_id: message_id.to_string(),
+            filename: filename.to_string(),
+            path: path.to_path_buf(),
+            reply: tx,
+        })
+        .await?;
+        rx.await.map_err(|_| "Failed to receive response".to_string())?
+    }
+
+    pub async fn set_status(&self, status: UserStatus) -> Result<(), String> {
+        let (tx, rx) = oneshot::channel();
+        self.send_command(ToxCommand::SetStatus(status, tx)).await?;
+        rx.await.map_err(|_| "Failed to receive response".to_string())?
+    }
+
+    /// List available profiles
+    pub fn list_profiles() -> Vec<String> {
+        profile_dirs::list_profiles()
+    }
+}
+
+/// Start the tox thread on its own OS thread, under supervision
+fn spawn_tox_thread(
+    app_handle: AppHandle,
+    mut cmd_rx: mpsc::Receiver<ToxCommand>,
+    savedata: Option<Vec<u8>>,
+    password: &str,
+    profile_path: PathBuf,
+    display_name: Option<String>,
+    store: Arc<MessageStore>,
+    sync_complete_tx: Option<std::sync::mpsc::Sender<()>>,
+    rich_presence: &RichPresence,
+    conversation_locks: &ConversationLocks,
+    incognito: &Incognito,
+) {
+    // Load proxy config from environment variables
+    let proxy_config = ProxyConfig::from_env();
+    let network_mode = app_handle
+        .state::<AppState>()
+        .settings
+        .try_lock()
+        .map(|s| s.network_mode)
+        .unwrap_or_default();
+    let supervision = Arc::new(Supervision::new(password, network_mode));
+    let rich_presence = rich_presence.clone();
+    let conversation_locks = conversation_locks.clone();
+    let incognito = incognito.clone();
+
+    std::thread::spawn(move || {
+        let mut savedata = savedata;
+        let mut display_name = display_name;
+        let mut sync_complete_tx = sync_complete_tx;
+        supervisor::run(&app_handle, supervision.clone(), |mut restart| loop {
+            if restart {
+                // Carry on from the profile the previous thread saved
+                match profile_file::read_profile(&profile_path, &supervision.password()) {
+                    Ok(data) => savedata = Some(data),
+                    Err(e) => {
+                        error!("Can't restart the tox thread: {e}");
+                        let _ = app_handle.emit("tox://event", &ToxEvent::ToxThreadStopped);
+                        return;
+                    }
+                }
+                display_name = None;
+            }
+            let switching_network = run_tox_thread(
+                app_handle.clone(),
+                &mut cmd_rx,
+                savedata.take(),
+                &profile_path,
+                display_name.take().as_deref(),
+                store.clone(),
+                sync_complete_tx.take(),
+                proxy_config.clone(),
+                rich_presence.clone(),
+                conversation_locks.clone(),
+                incognito.clone(),
+                &supervision,
+            );
+            if !switching_network {
+                return;
+            }
+            restart = true;
+        });
+    });
+}
+
+/// Saves the profile if the tox thread panics, so its restart loses nothing
+struct SaveOnPanic<'a> {
+    tox: &'a ToxInstance,
+    profile_path: &'a PathBuf,
+    supervision: &'a Supervision,
+}
+
+impl Drop for SaveOnPanic<'_> {
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            save_profile(self.tox, &self.supervision.password(), self.profile_path);
+        }
+    }
+}
+
+/// The main Tox event loop running on a dedicated thread. Returns true when
+/// it stopped to be started again in a new network mode.
+fn run_tox_thread(
+    app_handle: AppHandle,
+    cmd_rx: &mut mpsc::Receiver<ToxCommand>,
+    savedata: Option<Vec<u8>>,
+    profile_path: &PathBuf,
+    display_name: Option<&str>,
+    store: Arc<MessageStore>,
+    sync_complete_tx: Option<std::sync::mpsc::Sender<()>>,
+    proxy_config: ProxyConfig,
+    rich_presence: RichPresence,
+    conversation_locks: ConversationLocks,
+    incognito: Incognito,
+    supervision: &Supervision,
+) -> bool {
+    // Lets the loop wait on the command channel with a timeout
+    let waiter = match tokio::runtime::Builder::new_current_thread().enable_time().build() {
+        Ok(rt) => rt,
+        Err(e) => {
+            error!("Failed to create the tox thread's command waiter: {e}");
+            return false;
+        }
+    };
+
+    // LAN only leaves the proxy out, as it would turn off the UDP that local
+    // discovery needs
+    let mut network_mode = supervision.network_mode();
+    let proxy_configured = proxy_config.host.is_some();
+    let proxy_config = if network_mode == NetworkMode::LanOnly { ProxyConfig::none() } else { proxy_config };
+
+    // Build Tox options with proxy configuration
+    let mut builder = ToxOptionsBuilder::new();
+
+    // Apply proxy settings if configured
+    if let Some(ref host) = proxy_config.host {
+        match proxy_config.proxy_type {
+            ProxyType::Socks5 => {
+                info!("Using SOCKS5 proxy: {}:{}", host, proxy_config.port);
+                builder = builder.proxy_socks5(host, proxy_config.port);
+            }
+            ProxyType::Http => {
+                info!("Using HTTP proxy: {}:{}", host, proxy_config.port);
+                builder = builder.proxy_http(host, proxy_config.port);
+            }
+            ProxyType::None => {}
+        }
+    }
+
+    // Apply savedata if loading existing profile
+    let tox = if let Some(data) = savedata {
+        builder.savedata(data).build()
+    } else {
+        builder.build()
+    };
+
+    let tox = match tox {
+        Ok(t) => t,
+        Err(e) => {
+            error!("Failed to create Tox instance: {e}");
+            return false;
+        }
+    };
+
+    // Set display name if creating new profile
+    if let Some(name) = display_name {
+        if let Err(e) = tox.set_name(name) {
+            error!("Failed to set name: {e}");
+        }
+        if let Err(e) = tox.set_status_message("Using Toxcord") {
+            error!("Failed to set status message: {e}");
+        }
+    }
+
+    // Sessions left open by a previous run that didn't shut down cleanly
+    if let Err(e) = store.end_all_presence_sessions() {
+        error!("Failed to close stale presence sessions: {e}");
+    }
+
+    if let Err(e) = rich_presence.load_own_custom_status(&store) {
+        error!("{e}");
+    }
+    if let Err(e) = conversation_locks.load(&store) {
+        error!("{e}");
+    }
+    if let Err(e) = incognito.load(&store) {
+        error!("{e}");
+    }
+    if let Err(e) = app_handle.state::<AppState>().accessibility.load(&store) {
+        error!("{e}");
+    }
+    let mut last_status_expiry_check = Instant::now();
+    let mut last_poll_check = Instant::now();
+    let mut last_call_watchdog = Instant::now();
+    let mut last_event_reminder_check = Instant::now();
+    let mut last_disappearing_sweep = Instant::now();
+    let mut last_write_flush = Instant::now();
+    let mut last_bootstrap = Instant::now();
+    let mut sleep_detector = SleepDetector::new();
+
+    // Register callbacks
+    tox.register_callbacks();
+
+    // Channel for offline queue flush requests from callbacks
+    let (offline_flush_tx, offline_flush_rx) = std::sync::mpsc::channel::<u32>();
+
+    // File transfer state, shared with the callbacks
+    let file_manager = Arc::new(std::sync::Mutex::new(FileManager::new()));
+    let (file_action_tx, file_action_rx) = std::sync::mpsc::channel::<FileAction>();
+
+    // Friends who messaged us, checked against the away auto-reply settings
+    let (auto_reply_tx, auto_reply_rx) = std::sync::mpsc::channel::<u32>();
+
+    // Linked device connections and sync packets
+    let (device_sync_tx, device_sync_rx) = std::sync::mpsc::channel::<DeviceSyncAction>();
+
+    // Guild joins, peers and history packets for backfilling channels
+    let (history_tx, history_rx) = std::sync::mpsc::channel::<HistoryAction>();
+    let mut backfills = Backfills::default();
+
+    // Friends and group peers that just came online and need our activity
+    // (and, from moderators, the guild manifest)
+    let (presence_tx, presence_rx) = std::sync::mpsc::channel::<PresenceAction>();
+
+    // Call state, shared by both event handlers and the command loop
+    let av_manager = Arc::new(std::sync::Mutex::new(AvManager::new()));
+
+    // Power saving slows the loop down while nothing is going on
+    let power_saving = app_handle
+        .state::<AppState>()
+        .settings
+        .try_lock()
+        .map(|s| s.power_saving)
+        .unwrap_or_default();
+    let mut power = PowerSaver::new(power_saving);
+    let message_activity = Arc::new(AtomicBool::new(false));
+
+    // Create event handler with DB persistence
+    let handler: Box<dyn ToxEventHandler> = Box::new(TauriEventHandler {
+        app_handle: app_handle.clone(),
+        store: store.clone(),
+        offline_flush_tx,
+        file_manager: file_manager.clone(),
+        file_action_tx,
+        auto_reply_tx,
+        device_sync_tx,
+        history_tx,
+        rich_presence: rich_presence.clone(),
+        presence_tx,
+        conversation_locks: conversation_locks.clone(),
+        incognito: incognito.clone(),
+        group_peers: std::sync::Mutex::new(HashMap::new()),
+        av_manager: av_manager.clone(),
+        texts: IncomingTexts::default(),
+        invite_notices: std::sync::Mutex::new(HashMap::new()),
+        self_joins: std::sync::Mutex::new(HashMap::new()),
+        message_activity: message_activity.clone(),
+        tox_raw: tox.raw(),
+    });
+    let handler_ptr = Box::into_raw(Box::new(handler));
+
+    // Create ToxAV instance (must be on same thread as Tox)
+    let toxav = match ToxAvInstance::new(&tox) {
+        Ok(av) => {
+            info!("ToxAV instance created");
+            Some(av)
+        }
+        Err(e) => {
+            error!("Failed to create ToxAV instance: {e} - calls will be disabled");
+            None
+        }
+    };
+
+    // Create shared audio mixer for combining received audio from multiple peers
+    let mixer = Arc::new(std::sync::Mutex::new(AudioMixer::default()));
+
+    // Do Not Disturb follows our Busy status; declined calls are queued for hangup
+    let do_not_disturb = Arc::new(AtomicBool::new(tox.self_status() == UserStatus::Busy));
+    let (declined_call_tx, declined_call_rx) = std::sync::mpsc::channel::<(u32, bool)>();
+    // When each friend last got the Do Not Disturb auto-reply
+    let mut dnd_replies: HashMap<u32, Instant> = HashMap::new();
+    // Calls that rang out; the caller may get a "missed you" message, at most hourly
+    let (missed_call_tx, missed_call_rx) = std::sync::mpsc::channel::<(u32, bool)>();
+    let mut missed_call_replies: HashMap<u32, Instant> = HashMap::new();
+
+    // Create event handler for ToxAV callbacks
+    let video_frames = app_handle.state::<AppState>().video_frames.clone();
+    let av_handler: Option<*mut Box<dyn ToxAvEventHandler>> = if toxav.is_some() {
+        let handler: Box<dyn ToxAvEventHandler> = Box::new(TauriAvEventHandler::new(
+            app_handle.clone(),
+            av_manager.clone(),
+            mixer.clone(),
+            video_frames.clone(),
+            do_not_disturb.clone(),
+            declined_call_tx,
+            missed_call_tx,
+        ));
+        let handler_ptr = Box::into_raw(Box::new(handler));
+        // Register ToxAV callbacks with our handler
+        if let Some(ref av) = toxav {
+            av.register_callbacks_with_userdata(handler_ptr as *mut std::ffi::c_void);
+        }
+        Some(handler_ptr)
+    } else {
+        None
+    };
+
+    // Audio capture channel - capture thread sends frames here
+    let (audio_tx, mut audio_rx) = tokio::sync::mpsc::unbounded_channel::<Vec<i16>>();
+
+    // Audio capture and playback (managed on this thread, started when calls are active)
+    let mut audio_capture: Option<AudioCapture> = None;
+    let mut audio_playback: Option<AudioPlayback> = None;
+    // Output device selected for playback (None = default)
+    let mut output_device: Option<String> = app_handle
+        .state::<AppState>()
+        .selected_speaker
+        .try_lock()
+        .ok()
+        .and_then(|g| g.clone());
+    let mut audio_active = false;
+
+    // Video capture channel - capture thread sends frames here
+    let (video_tx, mut video_rx) = tokio::sync::mpsc::unbounded_channel::<VideoFrameData>();
+    // Video capture error channel - capture thread sends errors here
+    let (video_error_tx, mut video_error_rx) = tokio::sync::mpsc::unbounded_channel::<VideoCaptureError>();
+
+    // Video capture (managed on this thread, started when video calls are active)
+    let mut video_capture: Option<VideoCapture> = None;
+    let mut screen_capture: Option<ScreenCapture> = None;
+    let mut video_active = false;
+    let mut video_capture_failed = false; // Tracks if capture failed, to avoid retry loop
+
+    // Only the normal mode joins the public DHT
+    let mut bootstrapped = network_mode == NetworkMode::Normal;
+    if bootstrapped {
+        bootstrap(&tox);
+    } else {
+        info!("Starting in {network_mode:?} network mode, not bootstrapping");
+    }
+
+    // I2P/Proxy verification logging
+    match proxy_config.proxy_type {
+        ProxyType::Socks5 => {
+            info!("[I2P-CHECK] All Tox traffic routed through SOCKS5 proxy at {}:{}",
+                  proxy_config.host.as_deref().unwrap_or("127.0.0.1"), proxy_config.port);
+            info!("[I2P-CHECK] UDP disabled - using TCP relay mode only (required for I2P/Tor)");
+        }
+        ProxyType::Http => {
+            info!("[I2P-CHECK] All Tox traffic routed through HTTP proxy at {}:{}",
+                  proxy_config.host.as_deref().unwrap_or("127.0.0.1"), proxy_config.port);
+            info!("[I2P-CHECK] UDP disabled - using TCP relay mode only");
+        }
+        ProxyType::None => {
+            debug!("[I2P-CHECK] No proxy configured - direct connections enabled");
+        }
+    }
+
+    info!("Tox thread started, address: {}", tox.self_address());
+
+    // Sync existing friends to DB
+    for friend_num in tox.friend_list() {
+        let pk = tox.friend_public_key(friend_num).unwrap_or(ToxPublicKey(String::new()));
+        let name = tox.friend_name(friend_num).unwrap_or_default();
+        check_friend_key(&store, &app_handle, friend_num, &pk.0);
+        if let Err(e) = store.upsert_friend(friend_num, &pk.0, &name, "") {
+            error!("Failed to sync friend {friend_num} to DB: {e}");
+        }
+    }
+
+    // Log all existing guilds before sync
+    if let Ok(all_guilds) = store.get_guilds() {
+        info!("Guilds before sync:");
+        for g in &all_guilds {
+            info!("  Guild '{}' (id={}) -> group_number={:?}", g.name, g.id, g.metadata_group_number);
+        }
+    }
+
+    // Log all Tox groups
+    let group_count = tox.group_get_number_groups();
+    let tox_groups = tox.group_list();
+    info!("Tox group count: {}, groups found: {:?}", group_count, tox_groups);
+
+    // Sync existing groups to DB - match by chat ID (or name, for guilds from
+    // before chat IDs were stored) and update group_number
+    // Also reconnect each group to ensure it can send/receive messages
+    let chat_ids = store.get_guild_chat_ids().unwrap_or_else(|e| {
+        error!("{e}");
+        Vec::new()
+    });
+    for group_num in tox_groups {
+        if let Ok(group_info) = tox.group_get_info(group_num) {
+            info!("Tox group {}: name='{}' peers={}", group_num, group_info.name, group_info.peer_count);
+            let existing = match chat_ids.iter().find(|(_, c)| c.eq_ignore_ascii_case(&group_info.chat_id)) {
+                Some((guild_id, _)) => store.get_guild(guild_id),
+                None => store.get_guild_by_name(&group_info.name),
+            };
+            match existing {
+                Ok(Some(guild)) => {
+                    if let Err(e) = store.set_guild_chat_id(&guild.id, &group_info.chat_id) {
+                        error!("{e}");
+                    }
+                    // Update group_number if it changed
+                    if guild.metadata_group_number != Some(group_num as i64) {
+                        info!("Updating guild '{}' group_number: {:?} -> {}",
+                              guild.name, guild.metadata_group_number, group_num);
+                        if let Err(e) = store.update_guild_group_number(&guild.id, group_num as i64) {
+                            error!("Failed to update guild group_number: {e}");
+                        }
+                    }
+                }
+                Ok(None) => {
+                    // No guild found, create one
+                    info!("Creating guild record for Tox group '{}' ({})", group_info.name, group_num);
+                    let guild_id = uuid::Uuid::new_v4().to_string();
+                    if let Err(e) = store.insert_guild(&guild_id, &group_info.name, Some(group_num as i64), "", "server") {
+                        error!("Failed to create guild for group {}: {e}", group_num);
+                    } else {
+                        if let Err(e) = store.set_guild_chat_id(&guild_id, &group_info.chat_id) {
+                            error!("{e}");
+                        }
+                        // Create default channel
+                        let channel_id = uuid::Uuid::new_v4().to_string();
+                        if let Err(e) = store.insert_channel(&channel_id, &guild_id, "general", "text", 0) {
+                            error!("Failed to create default channel: {e}");
+                        }
+                    }
+                }
+                Err(e) => {
+                    error!("Failed to lookup guild by name: {e}");
+                }
+            }
+
+            // Reconnect the group to ensure it can send/receive messages after restart
+            if let Err(e) = tox.group_reconnect(group_num) {
+                warn!("Failed to reconnect group {}: {e}", group_num);
+            } else {
+                info!("Reconnected group {} to DHT", group_num);
+            }
+        }
+    }
+
+    // Guilds whose group was lost from the savedata are joined again
+    rejoin_lost_guilds(&tox, &store);
+
+    // Log guilds after sync
+    if let Ok(all_guilds) = store.get_guilds() {
+        info!("Guilds after sync:");
+        for g in &all_guilds {
+            info!("  Guild '{}' (id={}) -> group_number={:?}", g.name, g.id, g.metadata_group_number);
+        }
+        // Check for duplicate group_numbers
+        let mut seen_group_nums = std::collections::HashMap::new();
+        for g in &all_guilds {
+            if let Some(gn) = g.metadata_group_number {
+                seen_group_nums.entry(gn).or_insert_with(Vec::new).push(g.name.clone());
+            }
+        }
+        for (gn, names) in &seen_group_nums {
+            if names.len() > 1 {
+                warn!("DUPLICATE group_number {}: guilds {:?} - this will cause routing issues!", gn, names);
+            }
+        }
+    }
+
+    // Signal that sync is complete
+    if let Some(tx) = sync_complete_tx {
+        let _ = tx.send(());
+    }
+
+    // Save the initial profile
+    let mut password = supervision.password();
+    let profile_path = profile_path.clone();
+    save_profile(&tox, &password, &profile_path);
+    let mut saves = SaveScheduler::new();
+    let _save_on_panic = SaveOnPanic { tox: &tox, profile_path: &profile_path, supervision };
+
+    // Main event loop
+    let mut next_cmd = None;
+    loop {
+        supervision.beat();
+
+        while let Some(cmd) = next_cmd.take().or_else(|| cmd_rx.try_recv().ok()) {
+            match cmd {
+                ToxCommand::GetAddress(reply) => {
+                    let _ = reply.send(tox.self_address());
+                }
+                ToxCommand::GetNospam(reply) => {
+                    let _ = reply.send(tox.self_nospam());
+                }
+                ToxCommand::GetSecretKey(reply) => {
+                    let _ = reply.send(tox.self_secret_key());
+                }
+                ToxCommand::SetNospam(nospam, reply) => {
+                    let old_address = tox.self_address();
+                    tox.set_nospam(nospam);
+                    saves.mark_dirty();
+                    let address = tox.self_address();
+                    info!("Nospam changed, new address: {address}");
+                    if let Err(e) = store.record_nospam_change(old_address.as_str(), address.as_str()) {
+                        error!("{e}");
+                    }
+                    if let Err(e) = app_handle.emit("tox://event", &ToxEvent::SelfAddressChanged {
+                        address: address.to_string(),
+                    }) {
+                        error!("Failed to emit address change event: {e}");
+                    }
+                    let _ = reply.send(Ok(address));
+                }
+                ToxCommand::GetConnectionStatus(reply) => {
+                    let _ = reply.send(tox.self_connection_status());
+                }
+                ToxCommand::GetProfileInfo(reply) => {
+                    let _ = reply.send(tox.profile_info());
+                }
+                ToxCommand::GetDiagnostics(reply) => {
+                    let friends = tox.friend_list();
+                    let groups = tox
+                        .group_list()
+                        .into_iter()
+                        .filter_map(|number| tox.group_get_info(number).ok())
+                        .map(|info| GroupDiagnostics {
+                            group_number: info.number,
+                            connected: tox.group_is_connected(info.number),
+                            name: info.name,
+                            peer_count: info.peer_count,
+                        })
+                        .collect();
+                    let _ = reply.send(ToxDiagnostics {
+                        connection: tox.self_connection_status(),
+                        friends_online: friends
+                            .iter()
+                            .filter(|&&f| tox.friend_connection_status(f).is_connected())
+                            .count(),
+                        friends_total: friends.len(),
+                        groups,
+                    });
+                }
+                ToxCommand::GetDhtInfo(reply) => {
+                    let connection = tox.self_connection_status();
+                    let (dht_nodes, announce_nodes) = tox.dht_node_count();
+                    let _ = reply.send(DhtInfo {
+                        connection,
+                        dht_nodes,
+                        announce_nodes,
+                        dht_capacity: DHT_CLOSELIST_CAPACITY,
+                        udp_active: connection == ConnectionStatus::Udp,
+                        udp_port: tox.self_udp_port(),
+                        tcp_port: tox.self_tcp_port(),
+                        tcp_relays: default_bootstrap_nodes().iter().map(|n| n.tcp_ports.len()).sum(),
+                    });
+                }
+                ToxCommand::SetName(name, reply) => {
+                    let result = tox.set_name(&name).map_err(|e| e.to_string());
+                    if result.is_ok() {
+                        saves.mark_dirty();
+                    }
+                    let _ = reply.send(result);
+                }
+                ToxCommand::SetStatusMessage(msg, reply) => {
+                    let result = tox.set_status_message(&msg).map_err(|e| e.to_string());
+                    if result.is_ok() {
+                        saves.mark_dirty();
+                    }
+                    let _ = reply.send(result);
+                }
+                ToxCommand::SetStatus(status, reply) => {
+                    tox.set_status(status);
+                    do_not_disturb.store(status == UserStatus::Busy, Ordering::Relaxed);
+                    saves.mark_dirty();
+                    let status_str = match status {
+                        UserStatus::None => "online",
+                        UserStatus::Away => "away",
+                        UserStatus::Busy => "busy",
+                    };
+                    if let Err(e) = app_handle.emit("tox://event", &ToxEvent::SelfStatus {
+                        status: status_str.to_string(),
+                    }) {
+                        error!("Failed to emit self status event: {e}");
+                    }
+                    let _ = reply.send(Ok(()));
+                }
+                ToxCommand::FriendAdd(address, message, reply) => {
+                    let result = tox.friend_add(&address, &message).map_err(|e| e.to_string());
+                    if let Ok(friend_num) = &result {
+                        saves.mark_dirty();
+                        // Persist new friend to DB
+                        let pk = tox.friend_public_key(*friend_num).unwrap_or(ToxPublicKey(String::new()));
+                        if let Err(e) = store.upsert_friend(*friend_num, &pk.0, "", "") {
+                            error!("Failed to persist friend: {e}");
+                        }
+                        if let Err(e) = store.set_friend_tox_id(*friend_num, &address.to_uppercase()) {
+                            error!("Failed to persist friend Tox ID: {e}");
+                        }
+                    }
+                    let _ = reply.send(result);
+                }
+                ToxCommand::FriendAccept(pk, reply) => {
+                    let result = tox.friend_add_norequest(&pk).map_err(|e| e.to_string());
+                    if let Ok(friend_num) = &result {
+                        saves.mark_dirty();
+                        let pk_hex: String = pk.iter().map(|b| format!("{b:02X}")).collect();
+                        if let Err(e) = store.upsert_friend(*friend_num, &pk_hex, "", "") {
+                            error!("Failed to persist accepted friend: {e}");
+                        }
+                    }
+                    let _ = reply.send(result);
+                }
+                ToxCommand::RefreshFriendConnections(friend_number, reply) => {
+                    let result = refresh_friend_connections(&tox, friend_number, network_mode);
+                    if result.as_ref().is_ok_and(|retried| !retried.is_empty()) {
+                        saves.mark_dirty();
+                    }
+                    let _ = reply.send(result);
+                }
+                ToxCommand::FriendDelete(num, reply) => {
+                    let result = tox.friend_delete(num).map_err(|e| e.to_string());
+                    if result.is_ok() {
+                        saves.mark_dirty();
+                        if let Err(e) = store.remove_friend(num) {
+                            error!("Failed to remove friend from DB: {e}");
+                        }
+                    }
+                    let _ = reply.send(result);
+                }
+                ToxCommand::SetActivity(activity, reply) => {
+                    rich_presence.set_own_activity(&tox, activity);
+                    let _ = reply.send(());
+                }
+                ToxCommand::SetCustomStatus(status, reply) => {
+                    let _ = reply.send(rich_presence.set_own_custom_status(&tox, &store, status));
+                }
+                ToxCommand::PostStatusNote(text, reply) => {
+                    let _ = reply.send(post_status_note(&tox, &store, &text));
+                }
+                ToxCommand::ChangePassword(new_password, reply) => {
+                    let result = write_profile(&tox, &new_password, &profile_path);
+                    if result.is_ok() {
+                        password = new_password;
+                        supervision.set_password(&password);
+                        saves.saved();
+                    }
+                    let _ = reply.send(result);
+                }
+                ToxCommand::SetDisappearingTimer(friend_number, seconds, reply) => {
+                    // Always newer than what's saved, so it replaces the friend's choice too
+                    let now = chrono::Utc::now().timestamp();
+                    let result = store.get_disappearing_timer(friend_number).and_then(|saved| {
+                        let set_at = saved.map_or(now, |(_, set_at)| now.max(set_at + 1));
+                        store.set_disappearing_timer(friend_number, seconds, set_at)
+                    });
+                    // Offline friends get it when they come online
+                    if result.is_ok() && tox.friend_connection_status(friend_number).is_connected() {
+                        send_disappearing_timer(&tox, &store, friend_number);
+                    }
+                    let _ = reply.send(result.map(|_| ()));
+                }
+                ToxCommand::SyncDevice(friend_number, reply) => {
+                    let result = match store.get_linked_device(friend_number) {
+                        Ok(Some(_)) if tox.friend_connection_status(friend_number).is_connected() => {
+                            if device_sync::handle(&tox, &store, &app_handle, DeviceSyncAction::Connected(friend_number)) {
+                                saves.mark_dirty();
+                            }
+                            Ok(())
+                        }
+                        Ok(Some(_)) => Err("Device is offline".to_string()),
+                        Ok(None) => Err("Friend is not a linked device".to_string()),
+                        Err(e) => Err(e),
+                    };
+                    let _ = reply.send(result);
+                }
+                ToxCommand::FriendList(reply) => {
+                    let friends: Vec<FriendInfo> = tox
+                        .friend_list()
+                        .into_iter()
+                        .map(|num| FriendInfo {
+                            number: num,
+                            public_key: tox.friend_public_key(num).unwrap_or(ToxPublicKey(String::new())),
+                            name: tox.friend_name(num).unwrap_or_default(),
+                            status_message: String::new(),
+                            status: UserStatus::None,
+                            connection_status: tox.friend_connection_status(num),
+                        })
+                        .collect();
+                    let _ = reply.send(friends);
+                }
+                ToxCommand::FriendSendMessage(num, msg, reply) => {
+                    power.activity();
+                    let result = send_friend_text(&tox, &conversation_locks, num, &msg);
+                    let _ = reply.send(result);
+                }
+                ToxCommand::SetTyping(num, typing, reply) => {
+                    let result = tox.self_set_typing(num, typing).map_err(|e| e.to_string());
+                    let _ = reply.send(result);
+                }
+                ToxCommand::GroupNew(name, privacy, reply) => {
+                    let self_name = tox.self_name();
+                    let result = tox
+                        .group_new(privacy, &name, &self_name)
+                        .map_err(|e| e.to_string());
+                    if result.is_ok() {
+                        saves.mark_dirty();
+                    }
+                    let _ = reply.send(result);
+                }
+                ToxCommand::GroupJoin(chat_id, pwd, reply) => {
+                    let self_name = tox.self_name();
+                    let result = tox
+                        .group_join(&chat_id, &self_name, &pwd)
+                        .map_err(|e| e.to_string());
+                    if result.is_ok() {
+                        saves.mark_dirty();
+                    }
+                    let _ = reply.send(result);
+                }
+                ToxCommand::GroupLeave(group_number, reply) => {
+                    let result = tox.group_leave(group_number, "").map_err(|e| e.to_string());
+                    if result.is_ok() {
+                        saves.mark_dirty();
+                    }
+                    let _ = reply.send(result);
+                }
+                ToxCommand::GroupInviteFriend(group_number, friend_number, reply) => {
+                    // Let them know a password will be needed before the invite arrives
+                    if tox.group_get_password(group_number).is_ok_and(|password| !password.is_empty()) {
+                        let notice = InviteNotice {
+                            group_name: tox.group_get_name(group_number).unwrap_or_default(),
+                            password_required: true,
+                        };
+                        if let Err(e) = tox.friend_send_lossless_packet(friend_number, &notice.to_bytes()) {
+                            warn!("Failed to send invite notice to friend {friend_number}: {e}");
+                        }
+                    }
+                    let result = tox
+                        .group_invite_friend(group_number, friend_number)
+                        .map_err(|e| e.to_string());
+                    let _ = reply.send(result);
+                }
+                ToxCommand::GroupInviteAccept(friend_number, invite_data, password, reply) => {
+                    let self_name = tox.self_name();
+                    let result = tox
+                        .group_invite_accept(friend_number, &invite_data, &self_name, &password)
+                        .map_err(|e| e.to_string());
+                    if result.is_ok() {
+                        saves.mark_dirty();
+                    }
+                    let _ = reply.send(result);
+                }
+                ToxCommand::GroupSendMessage(group_number, msg, reply) => {
+                    power.activity();
+                    let result = send_group_text(&tox, group_number, &msg);
+                    let _ = reply.send(result);
+                }
+                ToxCommand::GroupSendCustomPacket(group_number, data, reply) => {
+                    let result = tox
+                        .group_send_custom_packet(group_number, true, &data)
+                        .map_err(|e| e.to_string());
+                    let _ = reply.send(result);
+                }
+                ToxCommand::GroupGetList(reply) => {
+                    let groups: Vec<GroupInfo> = tox
+                        .group_list()
+                        .into_iter()
+                        .filter_map(|num| tox.group_get_info(num).ok())
+                        .collect();
+                    let _ = reply.send(groups);
+                }
+                ToxCommand::GroupGetPeerList(group_number, reply) => {
+                    // Get our own peer ID to know the range; iterate from 0 up
+                    let mut peers = Vec::new();
+                    // c-toxcore doesn't have a direct peer list API; we iterate possible peer IDs
+                    // Using self peer ID + reasonable range. A practical limit:
+                    let limit = tox.group_peer_count(group_number).unwrap_or(100);
+                    for peer_id in 0..limit {
+                        if let Ok(info) = tox.group_get_peer_info(group_number, peer_id) {
+                            if !info.public_key.is_empty() {
+                                peers.push(info);
+                            }
+                        }
+                    }
+                    let _ = reply.send(peers);
+                }
+                ToxCommand::GroupSetTopic(group_number, topic, reply) => {
+                    let result = tox
+                        .group_set_topic(group_number, &topic)
+                        .map_err(|e| e.to_string());
+                    if result.is_ok() {
+                        record_own_audit(&tox, &store, group_number, "topic_change", None, &topic);
+                    }
+                    let _ = reply.send(result);
+                }
+                ToxCommand::GroupSetPassword(group_number, password, reply) => {
+                    let result = tox
+                        .group_founder_set_password(group_number, &password)
+                        .map_err(|e| e.to_string());
+                    if result.is_ok() {
+                        saves.mark_dirty();
+                    }
+                    let _ = reply.send(result);
+                }
+                ToxCommand::GroupSetRole(group_number, peer_id, role, reply) => {
+                    let group_role = GroupRole::from_raw(role as u32);
+                    let target = audit_target(&tox, group_number, peer_id);
+                    let result = tox
+                        .group_set_role(group_number, peer_id, group_role)
+                        .map_err(|e| e.to_string());
+                    if result.is_ok() {
+                        record_own_audit(&tox, &store, group_number, "role_change", target, role_name(group_role));
+                    }
+                    let _ = reply.send(result);
+                }
+                ToxCommand::GroupKickPeer(group_number, peer_id, reply) => {
+                    // Once kicked the peer can't be queried
+                    let target = audit_target(&tox, group_number, peer_id);
+                    let result = tox
+                        .group_kick_peer(group_number, peer_id)
+                        .map_err(|e| e.to_string());
+                    if result.is_ok() {
+                        record_own_audit(&tox, &store, group_number, "kick", target, "");
+                    }
+                    let _ = reply.send(result);
+                }
+                ToxCommand::GroupGetSelfRole(group_number, reply) => {
+                    let result = tox
+                        .group_self_get_role(group_number)
+                        .map_err(|e| e.to_string());
+                    let _ = reply.send(result);
+                }
+                ToxCommand::GroupGetInfo(group_number, reply) => {
+                    let result = tox
+                        .group_get_info(group_number)
+                        .map_err(|e| e.to_string());
+                    let _ = reply.send(result);
+                }
+                ToxCommand::GroupGetSelfPk(group_number, reply) => {
+                    let result = tox
+                        .group_self_get_public_key(group_number)
+                        .map(|pk| pk.iter().map(|b| format!("{b:02X}")).collect())
+                        .map_err(|e| e.to_string());
+                    let _ = reply.send(result);
+                }
+                ToxCommand::GroupReconnect(group_number, reply) => {
+                    let result = tox
+                        .group_reconnect(group_number)
+                        .map_err(|e| e.to_string());
+                    let _ = reply.send(result);
+                }
+                // ToxAV commands
+                ToxCommand::FileSend {
+                    friend_number,
+                    transfer_id,
+                    message_id,
+                    filename,
+                    path,
+                    reply,
+                } => {
+                    let file_size = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+                    let result = tox
+                        .file_send(friend_number, FileKind::Data, file_size, None, &filename)
+                        .map_err(|e| e.to_string())
+                        .and_then(|file_number| {
+                            file_manager
+                                .lock()
+                                .map_err(|e| e.to_string())?
+                                .start_outgoing(friend_number, file_number, &transfer_id, &message_id, &filename, &path)
+                                .map(|_| file_number)
+                        });
+                    // Recorded here, before any control or chunk callback can refer to it
+                    if let Ok(file_number) = result {
+                        let record = crate::db::message_store::FileTransferRecord {
+                            id: transfer_id,
+                            friend_number: friend_number as i64,
+                            file_number: file_number as i64,
+                            filename,
+                            file_size: file_size as i64,
+                            file_path: Some(path.to_string_lossy().to_string()),
+                            direction: "outgoing".to_string(),
+                            status: "pending".to_string(),
+                            bytes_transferred: 0,
+                            message_id: Some(message_id),
+                            file_id: tox
+                                .file_get_file_id(friend_number, file_number)
+                                .ok()
+                                .map(|id| file_manager::file_id_to_hex(&id)),
+                        };
+                        if let Err(e) = store.insert_file_transfer(&record) {
+                            error!("Failed to persist file transfer: {e}");
+                        }
+                    }
+                    let _ = reply.send(result);
+                }
+                ToxCommand::AvCall {
+                    friend_number,
+                    audio_bit_rate,
+                    video_bit_rate,
+                    reply,
+                } => {
+                    let result = if let Some(ref av) = toxav {
+                        match av.call(friend_number, audio_bit_rate, video_bit_rate) {
+                            Ok(()) => {
+                                // Register the call with the manager
+                                let with_video = video_bit_rate > 0;
+                                if let Ok(mut mgr) = av_manager.lock() {
+                                    mgr.start_call(friend_number, with_video);
+                                }
+                                Ok(())
+                            }
+                            Err(e) => Err(e.to_string()),
+                        }
+                    } else {
+                        Err("ToxAV not available".to_string())
+                    };
+                    let _ = reply.send(result);
+                }
+                ToxCommand::AvAnswer {
+                    friend_number,
+                    audio_bit_rate,
+                    video_bit_rate,
+                    reply,
+                } => {
+                    let result = if let Some(ref av) = toxav {
+                        match av.answer(friend_number, audio_bit_rate, video_bit_rate) {
+                            Ok(()) => {
+                                info!("Successfully answered call from friend {}", friend_number);
+                                // Manually transition call to InProgress since ToxAV callback may not fire
+                                if let Ok(mut mgr) = av_manager.lock() {
+                                    // Create a synthetic "active" state to transition the call
+                                    let active_state = toxcord_tox::CallStateFlags {
+                                        error: false,
+                                        finished: false,
+                                        sending_audio: true,
+                                        sending_video: video_bit_rate > 0,
+                                        accepting_audio: true,
+                                        accepting_video: video_bit_rate > 0,
+                                    };
+                                    mgr.update_call_state(friend_number, active_state);
+                                    info!("Transitioned call with friend {} to InProgress after answer", friend_number);
+                                }
+                                // Emit state change to frontend
+                                use crate::managers::av_manager::ToxAvEvent;
+                                let event = ToxAvEvent::CallStateChange {
+                                    friend_number,
+                                    state: "in_progress".to_string(),
+                                    sending_audio: true,
+                                    sending_video: video_bit_rate > 0,
+                                    accepting_audio: true,
+                                    accepting_video: video_bit_rate > 0,
+                                };
+                                if let Err(e) = app_handle.emit("toxav://event", &event) {
+                                    error!("Failed to emit call state change: {e}");
+                                }
+                                Ok(())
+                            }
+                            Err(e) => {
+                                error!("Failed to answer call from friend {}: {}", friend_number, e);
+                                Err(e.to_string())
+                            }
+                        }
+                    } else {
+                        Err("ToxAV not available".to_string())
+                    };
+                    let _ = reply.send(result);
+                }
+                ToxCommand::AvHangup { friend_number, reply } => {
+                    let result = if let Some(ref av) = toxav {
+                        match av.hangup(friend_number) {
+                            Ok(()) => {
+                                // Clean up the call in the manager
+                                if let Ok(mut mgr) = av_manager.lock() {
+                                    mgr.end_call(friend_number);
+                                }
+                                // Clean up mixer source
+                                if let Ok(mut m) = mixer.lock() {
+                                    m.remove_source(friend_number);
+                                }
+                                Ok(())
+                            }
+                            Err(e) => Err(e.to_string()),
+                        }
+                    } else {
+                        Err("ToxAV not available".to_string())
+                    };
+                    let _ = reply.send(result);
+                }
+                ToxCommand::AvMuteAudio { friend_number, reply } => {
+                    let result = if let Some(ref av) = toxav {
+                        match av.mute_audio(friend_number) {
+                            Ok(()) => {
+                                if let Ok(mut mgr) = av_manager.lock() {
+                                    mgr.set_audio_muted(friend_number, true);
+                                }
+                                Ok(())
+                            }
+                            Err(e) => Err(e.to_string()),
+                        }
+                    } else {
+                        Err("ToxAV not available".to_string())
+                    };
+                    let _ = reply.send(result);
+                }
+                ToxCommand::AvUnmuteAudio { friend_number, reply } => {
+                    let result = if let Some(ref av) = toxav {
+                        match av.unmute_audio(friend_number) {
+                            Ok(()) => {
+                                if let Ok(mut mgr) = av_manager.lock() {
+                                    mgr.set_audio_muted(friend_number, false);
+                                }
+                                Ok(())
+                            }
+                            Err(e) => Err(e.to_string()),
+                        }
+                    } else {
+                        Err("ToxAV not available".to_string())
+                    };
+                    let _ = reply.send(result);
+                }
+                ToxCommand::AvHideVideo { friend_number, reply } => {
+                    let result = if let Some(ref av) = toxav {
+                        match av.hide_video(friend_number) {
+                            Ok(()) => {
+                                // Update av_manager state
+                                if let Ok(mut mgr) = av_manager.lock() {
+                                    mgr.set_video_muted(friend_number, true);
+                                }
+                                info!("Video hidden for friend {}", friend_number);
+                                Ok(())
+                            }
+                            Err(e) => Err(e.to_string()),
+                        }
+                    } else {
+                        Err("ToxAV not available".to_string())
+                    };
+                    let _ = reply.send(result);
                 }
-                ToxCommand::SetName(name, reply) => {
-                    let result = tox.set_name(&name).map_err(|e| e.to_string());
-                    if result.is_ok() {
-                        save_profile(&tox, &password, &profile_path);
-                    }
+                ToxCommand::AvShowVideo { friend_number, reply } => {
+                    let result = if let Some(ref av) = toxav {
+                        match av.show_video(friend_number) {
+                            Ok(()) => {
+                                // Update av_manager state
+                                if let Ok(mut mgr) = av_manager.lock() {
+                                    mgr.set_video_muted(friend_number, false);
+                                }
+                                info!("Video shown for friend {}", friend_number);
+                                Ok(())
+                            }
+                            Err(e) => Err(e.to_string()),
+                        }
+                    } else {
+                        Err("ToxAV not available".to_string())
+                    };
                     let _ = reply.send(result);
                 }
-                ToxCommand::SetStatusMessage(msg, reply) => {
-                    let result = tox.set_status_message(&msg).map_err(|e| e.to_string());
-                    if result.is_ok() {
-                        save_profile(&tox, &password, &profile_path);
-                    }
+                ToxCommand::AvSwitchCamera { device_index, reply } => {
+                    // Without a running camera the next capture picks up the selected device
+                    let result = if video_capture.is_some() {
+                        // The old capture has to release its device first
+                        video_capture = None;
+                        let background = app_handle.state::<AppState>().video_background.clone();
+                        match VideoCapture::start_with_device(
+                            device_index,
+                            background,
+                            video_tx.clone(),
+                            video_error_tx.clone(),
+                        ) {
+                            Ok(capture) => {
+                                video_capture = Some(capture);
+                                info!("Switched camera to device {:?}", device_index);
+                                Ok(())
+                            }
+                            Err(e) => {
+                                // Let the loop retry, and report it as it does
+                                video_active = false;
+                                Err(format!("Failed to switch camera: {e}"))
+                            }
+                        }
+                    } else {
+                        Ok(())
+                    };
                     let _ = reply.send(result);
                 }
-                ToxCommand::FriendAdd(address, message, reply) => {
-                    let result = tox.friend_add(&address, &message).map_err(|e| e.to_string());
-                    if let Ok(friend_num) = &result {
-                        save_profile(&tox, &password, &profile_path);
-                        // Persist new friend to DB
-                        let pk = tox.friend_public_key(*friend_num).unwrap_or(ToxPublicKey(String::new()));
-                        if let Err(e) = store.upsert_friend(*friend_num, &pk.0, "", "") {
-                            error!("Failed to persist friend: {e}");
+                ToxCommand::AvSwitchAudioOutput { device, reply } => {
+                    // Without a call the next playback starts on the selected device
+                    let result = if audio_playback.is_some() {
+                        // Stop the old stream first so two don't drain the mixer at once.
+                        // The mixer itself is shared and keeps its buffered audio.
+                        audio_playback = None;
+                        match AudioPlayback::start_with_device(device.as_deref(), mixer.clone()) {
+                            Ok(playback) => {
+                                audio_playback = Some(playback);
+                                info!("Switched audio output to {:?}", device);
+                                output_device = device;
+                                Ok(())
+                            }
+                            Err(e) => {
+                                // Keep the call audible on the device it was using
+                                audio_playback = AudioPlayback::start_with_device(output_device.as_deref(), mixer.clone())
+                                    .map_err(|e| error!("Failed to restore audio playback: {e}"))
+                                    .ok();
+                                Err(format!("Failed to switch audio output: {e}"))
+                            }
                         }
-                    }
+                    } else {
+                        output_device = device;
+                        Ok(())
+                    };
                     let _ = reply.send(result);
                 }
-                ToxCommand::FriendAccept(pk, reply) => {
-                    let result = tox.friend_add_norequest(&pk).map_err(|e| e.to_string());
-                    if let Ok(friend_num) = &result {
-                        save_profile(&tox, &password, &profile_path);
-                        let pk_hex: String = pk.iter().map(|b| format!("{b:02X}")).collect();
-                        if let Err(e) = store.upsert_friend(*friend_num, &pk_hex, "", "") {
-                            error!("Failed to persist accepted friend: {e}");
+                ToxCommand::AvSendAudioFrame {
+                    friend_number,
+                    pcm,
+                    sample_count,
+                    channels,
+                    sampling_rate,
+                } => {
+                    if let Some(ref av) = toxav {
+                        let frame = AudioFrame {
+                            pcm,
+                            sample_count,
+                            channels,
+                            sampling_rate,
+                        };
+                        if let Err(e) = av.audio_send_frame(friend_number, &frame) {
+                            debug!("Failed to send audio frame: {e}");
                         }
                     }
-                    let _ = reply.send(result);
                 }
-                ToxCommand::FriendDelete(num, reply) => {
-                    let result = tox.friend_delete(num).map_err(|e| e.to_string());
-                    if result.is_ok() {
-                        save_profile(&tox, &password, &profile_path);
-                        if let Err(e) = store.remove_friend(num) {
-                            error!("Failed to remove friend from DB: {e}");
+                ToxCommand::AvGetCallState { friend_number, reply } => {
+                    // Get call state from the AV manager using blocking access
+                    let state = if let Ok(mgr) = av_manager.lock() {
+                        mgr.get_call(friend_number).cloned()
+                    } else {
+                        None
+                    };
+                    let _ = reply.send(state);
+                }
+                ToxCommand::AvSetGlobalMute { muted, reply } => {
+                    if let Ok(mut mgr) = av_manager.lock() {
+                        mgr.set_muted(muted);
+                    }
+                    let event = ToxAvEvent::GlobalMuteChanged { muted };
+                    if let Err(e) = app_handle.emit("toxav://event", &event) {
+                        error!("Failed to emit mute event: {e}");
+                    }
+                    crate::tray::set_mute_checked(&app_handle, muted);
+                    let _ = reply.send(Ok(()));
+                }
+                ToxCommand::AvSetGlobalDeafen { deafened, reply } => {
+                    if let Ok(mut mgr) = av_manager.lock() {
+                        mgr.set_deafened(deafened);
+                    }
+                    if deafened {
+                        // Drop anything already buffered so playback stops immediately
+                        if let Ok(mut m) = mixer.lock() {
+                            m.clear();
                         }
                     }
-                    let _ = reply.send(result);
+                    let event = ToxAvEvent::GlobalDeafenChanged { deafened };
+                    if let Err(e) = app_handle.emit("toxav://event", &event) {
+                        error!("Failed to emit deafen event: {e}");
+                    }
+                    let _ = reply.send(Ok(()));
                 }
-                ToxCommand::FriendList(reply) => {
-                    let friends: Vec<FriendInfo> = tox
-                        .friend_list()
-                        .into_iter()
-                        .map(|num| FriendInfo {
-                            number: num,
-                            public_key: tox.friend_public_key(num).unwrap_or(ToxPublicKey(String::new())),
-                            name: tox.friend_name(num).unwrap_or_default(),
-                            status_message: String::new(),
-                            status: UserStatus::None,
-                            connection_status: tox.friend_connection_status(num),
-                        })
-                        .collect();
-                    let _ = reply.send(friends);
+                ToxCommand::AvGetGlobalAudioState { reply } => {
+                    let state = if let Ok(mgr) = av_manager.lock() {
+                        (mgr.is_muted(), mgr.is_deafened())
+                    } else {
+                        (false, false)
+                    };
+                    let _ = reply.send(state);
                 }
-                ToxCommand::FriendSendMessage(num, msg, reply) => {
-                    let result = tox
-                        .friend_send_message(num, MessageType::Normal, &msg)
-                        .map_err(|e| e.to_string());
-                    let _ = reply.send(result);
+                ToxCommand::AvListCalls { reply } => {
+                    let calls = if let Ok(mgr) = av_manager.lock() {
+                        mgr.get_all_calls().into_iter().cloned().collect()
+                    } else {
+                        vec![]
+                    };
+                    let _ = reply.send(calls);
                 }
-                ToxCommand::SetTyping(num, typing, reply) => {
-                    let result = tox.self_set_typing(num, typing).map_err(|e| e.to_string());
-                    let _ = reply.send(result);
+                ToxCommand::AvListVideoStreams { reply } => {
+                    let streams = av_manager.lock().map(|mgr| mgr.video_streams()).unwrap_or_default();
+                    let _ = reply.send(streams);
                 }
-                ToxCommand::GroupNew(name, reply) => {
-                    let self_name = tox.self_name();
-                    let result = tox
-                        .group_new(GroupPrivacyState::Private, &name, &self_name)
-                        .map_err(|e| e.to_string());
-                    if result.is_ok() {
-                        save_profile(&tox, &password, &profile_path);
+                ToxCommand::AvSetStreamSubscribed { friend_number, subscribed, reply } => {
+                    let found = av_manager
+                        .lock()
+                        .map(|mut mgr| mgr.set_stream_subscribed(friend_number, subscribed))
+                        .unwrap_or(false);
+                    if found && !subscribed {
+                        // Don't leave the last frame up
+                        video_frames.remove(VideoSource::Friend(friend_number));
                     }
-                    let _ = reply.send(result);
+                    let _ = reply.send(if found {
+                        Ok(())
+                    } else {
+                        Err(format!("No call with friend {friend_number}"))
+                    });
                 }
-                ToxCommand::GroupJoin(chat_id, pwd, reply) => {
-                    let self_name = tox.self_name();
-                    let result = tox
-                        .group_join(&chat_id, &self_name, &pwd)
-                        .map_err(|e| e.to_string());
+                ToxCommand::SaveProfile(reply) => {
+                    let result = write_profile(&tox, &password, &profile_path);
                     if result.is_ok() {
-                        save_profile(&tox, &password, &profile_path);
+                        saves.saved();
                     }
                     let _ = reply.send(result);
                 }
-                ToxCommand::GroupLeave(group_number, reply) => {
-                    let result = tox.group_leave(group_number, "").map_err(|e| e.to_string());
-                    if result.is_ok() {
-                        save_profile(&tox, &password, &profile_path);
+                ToxCommand::Suspend(reply) => {
+                    info!("Preparing for sleep");
+                    save_profile(&tox, &password, &profile_path);
+                    saves.saved();
+                    if let Some(ref av) = toxav {
+                        drop_all_calls(av, &av_manager, &mixer, &video_frames, &app_handle);
                     }
-                    let _ = reply.send(result);
+                    let _ = reply.send(());
                 }
-                ToxCommand::GroupInviteFriend(group_number, friend_number, reply) => {
-                    let result = tox
-                        .group_invite_friend(group_number, friend_number)
-                        .map_err(|e| e.to_string());
-                    let _ = reply.send(result);
+                ToxCommand::Resume(reply) => {
+                    resume_after_sleep(&tox, toxav.as_ref(), &av_manager, &mixer, &video_frames, &app_handle, Duration::ZERO, network_mode);
+                    let _ = reply.send(());
                 }
-                ToxCommand::GroupInviteAccept(friend_number, invite_data, reply) => {
-                    let self_name = tox.self_name();
-                    let result = tox
-                        .group_invite_accept(friend_number, &invite_data, &self_name, "")
-                        .map_err(|e| e.to_string());
-                    if result.is_ok() {
+                ToxCommand::SetNetworkMode(mode, reply) => {
+                    info!("Switching network mode from {network_mode:?} to {mode:?}");
+                    supervision.set_network_mode(mode);
+                    // Whether this instance can't be brought into the mode
+                    let restart = match mode {
+                        NetworkMode::Normal => proxy_configured && proxy_config.host.is_none(),
+                        NetworkMode::LanOnly => bootstrapped || proxy_config.host.is_some(),
+                        NetworkMode::Offline => false,
+                    };
+                    if restart || mode == NetworkMode::Offline {
+                        // Calls can't carry on without iterating
+                        if let Some(ref av) = toxav {
+                            drop_all_calls(av, &av_manager, &mixer, &video_frames, &app_handle);
+                        }
+                    }
+                    if restart {
+                        info!("Restarting the Tox instance for {mode:?} network mode");
                         save_profile(&tox, &password, &profile_path);
+                        emit_network_mode(&app_handle, mode, ConnectionStatus::None);
+                        let _ = reply.send(());
+                        unsafe {
+                            let _ = Box::from_raw(handler_ptr);
+                            if let Some(av_ptr) = av_handler {
+                                let _ = Box::from_raw(av_ptr);
+                            }
+                        }
+                        return true;
                     }
-                    let _ = reply.send(result);
+                    if mode == NetworkMode::Normal && !bootstrapped {
+                        bootstrap(&tox);
+                        bootstrapped = true;
+                    }
+                    network_mode = mode;
+                    let status = if mode == NetworkMode::Offline { ConnectionStatus::None } else { tox.self_connection_status() };
+                    emit_network_mode(&app_handle, mode, status);
+                    let _ = reply.send(());
                 }
-                ToxCommand::GroupSendMessage(group_number, msg, reply) => {
-                    let result = tox
-                        .group_send_message(group_number, MessageType::Normal, &msg)
-                        .map_err(|e| e.to_string());
-                    let _ = reply.send(result);
+                ToxCommand::SetPowerSaving(mode, reply) => {
+                    power.set_mode(mode);
+                    let _ = reply.send(());
                 }
-                ToxCommand::GroupSendCustomPacket(group_number, data, reply) => {
-                    let result = tox
-                        .group_send_custom_packet(group_number, true, &data)
-                        .map_err(|e| e.to_string());
-                    let _ = reply.send(result);
+                ToxCommand::Shutdown(reply) => {
+                    save_profile(&tox, &password, &profile_path);
+                    if let Err(e) = store.end_all_presence_sessions() {
+                        error!("Failed to close presence sessions: {e}");
+                    }
+                    info!("Tox thread shutting down");
+                    let _ = reply.send(());
+                    // Clean up handler pointers
+                    unsafe {
+                        let _ = Box::from_raw(handler_ptr);
+                        if let Some(av_ptr) = av_handler {
+                            let _ = Box::from_raw(av_ptr);
+                        }
+                    }
+                    // ToxAV will be dropped automatically when toxav goes out of scope
+                    return false;
                 }
-                ToxCommand::GroupGetList(reply) => {
-                    let groups: Vec<GroupInfo> = tox
-                        .group_list()
-                        .into_iter()
-                        .filter_map(|num| tox.group_get_info(num).ok())
-                        .collect();
-                    let _ = reply.send(groups);
+            }
+        }
+
+        // Offline pauses toxcore: nothing is sent or received until we iterate again
+        let online = network_mode != NetworkMode::Offline;
+
+        // Run tox_iterate with the handler as user_data
+        if online {
+            tox.iterate_with_userdata(handler_ptr as *mut std::ffi::c_void);
+        }
+
+        // Run toxav_iterate
+        if let Some(av) = toxav.as_ref().filter(|_| online) {
+            av.iterate();
+
+            while let Ok((friend_number, with_video)) = declined_call_rx.try_recv() {
+                decline_call(&tox, av, &store, &app_handle, &conversation_locks, &incognito, &mut dnd_replies, friend_number);
+                record_missed_call(&store, &app_handle, friend_number, with_video, MissedCallReason::DoNotDisturb);
+            }
+            while let Ok((friend_number, with_video)) = missed_call_rx.try_recv() {
+                record_missed_call(&store, &app_handle, friend_number, with_video, MissedCallReason::NoAnswer);
+                send_missed_call_reply(&tox, &store, &app_handle, &conversation_locks, &incognito, &mut missed_call_replies, friend_number);
+            }
+        }
+
+        // Time spent in calls that just ended, for usage statistics
+        let finished_calls = av_manager.lock().map(|mut mgr| mgr.take_finished_call_time()).unwrap_or_default();
+        for (friend_number, seconds) in finished_calls {
+            if let Err(e) = store.record_call_time(friend_number as i64, seconds) {
+                warn!("{e}");
+            }
+        }
+
+        // Accept/cancel file transfers and send chunks and group file packets queued by callbacks
+        while let Ok(action) = file_action_rx.try_recv() {
+            let (friend_number, file_number, control) = match action {
+                FileAction::Accept { friend_number, file_number, position } => {
+                    // Seeking is only allowed before the transfer is accepted
+                    if position > 0 {
+                        if let Err(e) = tox.file_seek(friend_number, file_number, position) {
+                            warn!("Failed to seek file {file_number} from friend {friend_number} to {position}: {e}");
+                        }
+                    }
+                    (friend_number, file_number, FileControl::Resume)
                 }
-                ToxCommand::GroupGetPeerList(group_number, reply) => {
-                    // Get our own peer ID to know the range; iterate from 0 up
-                    let mut peers = Vec::new();
-                    // c-toxcore doesn't have a direct peer list API; we iterate possible peer IDs
-                    // Using self peer ID + reasonable range. A practical limit:
-                    let limit = tox.group_peer_count(group_number).unwrap_or(100);
-                    for peer_id in 0..limit {
-                        if let Ok(info) = tox.group_get_peer_info(group_number, peer_id) {
-                            if !info.public_key.is_empty() {
-                                peers.push(info);
+                FileAction::Cancel { friend_number, file_number } => (friend_number, file_number, FileControl::Cancel),
+                FileAction::SendChunk { friend_number, file_number, position, length } => {
+                    let sent = file_manager
+                        .lock()
+                        .map_err(|e| e.to_string())
+                        .and_then(|mut fm| fm.read_chunk(friend_number, file_number, position, length))
+                        .and_then(|data| {
+                            tox.file_send_chunk(friend_number, file_number, position, &data)
+                                .map_err(|e| e.to_string())
+                        });
+                    match sent {
+                        Ok(()) => continue,
+                        Err(e) => {
+                            warn!("Outgoing file {file_number} to friend {friend_number} failed: {e}");
+                            if let Some(transfer) = file_manager.lock().ok().and_then(|mut fm| fm.cancel_outgoing(friend_number, file_number)) {
+                                if let Err(e) = store.update_file_transfer_status(&transfer.id, "failed", transfer.sent as i64) {
+                                    error!("Failed to persist transfer status: {e}");
+                                }
                             }
+                            (friend_number, file_number, FileControl::Cancel)
                         }
                     }
-                    let _ = reply.send(peers);
                 }
-                ToxCommand::GroupSetTopic(group_number, topic, reply) => {
-                    let result = tox
-                        .group_set_topic(group_number, &topic)
-                        .map_err(|e| e.to_string());
-                    let _ = reply.send(result);
+                FileAction::GroupPacket { group_number, peer_id, data } => {
+                    if let Err(e) = tox.group_send_custom_private_packet(group_number, peer_id, true, &data) {
+                        warn!("Failed to send file packet to peer {peer_id} in group {group_number}: {e}");
+                    }
+                    continue;
                 }
-                ToxCommand::GroupSetRole(group_number, peer_id, role, reply) => {
-                    let group_role = GroupRole::from_raw(role as u32);
-                    let result = tox
-                        .group_set_role(group_number, peer_id, group_role)
-                        .map_err(|e| e.to_string());
-                    let _ = reply.send(result);
+            };
+            if let Err(e) = tox.file_control(friend_number, file_number, control) {
+                warn!("Failed to {control:?} file {file_number} with friend {friend_number}: {e}");
+            }
+        }
+
+        // Check if we have any active calls (in_progress state) to manage audio
+        let (has_active_call, call_count) = if let Ok(mgr) = av_manager.lock() {
+            let calls = mgr.get_all_calls();
+            let count = calls.len();
+            let active = calls.iter().any(|c| c.state == CallStatus::InProgress);
+            // Log when there are calls but audio hasn't started yet
+            if count > 0 && !audio_active {
+                for call in calls {
+                    debug!("Call state check: friend={} state={:?} audio_active={}",
+                           call.friend_number, call.state, audio_active);
                 }
-                ToxCommand::GroupKickPeer(group_number, peer_id, reply) => {
-                    let result = tox
-                        .group_kick_peer(group_number, peer_id)
-                        .map_err(|e| e.to_string());
-                    let _ = reply.send(result);
+            }
+            (active, count)
+        } else {
+            (false, 0)
+        };
+
+        // Start audio capture/playback when a call becomes active
+        if has_active_call && !audio_active {
+            info!("Starting audio for active call");
+
+            // Start audio capture (microphone)
+            match AudioCapture::start(audio_tx.clone()) {
+                Ok(capture) => {
+                    audio_capture = Some(capture);
+                    info!("Audio capture started");
                 }
-                ToxCommand::GroupGetInfo(group_number, reply) => {
-                    let result = tox
-                        .group_get_info(group_number)
-                        .map_err(|e| e.to_string());
-                    let _ = reply.send(result);
+                Err(e) => {
+                    error!("Failed to start audio capture: {e}");
                 }
-                ToxCommand::GroupGetSelfPk(group_number, reply) => {
-                    let result = tox
-                        .group_self_get_public_key(group_number)
-                        .map(|pk| pk.iter().map(|b| format!("{b:02X}")).collect())
-                        .map_err(|e| e.to_string());
-                    let _ = reply.send(result);
+            }
+
+            // Start audio playback (speakers) with the shared mixer
+            match AudioPlayback::start_with_device(output_device.as_deref(), mixer.clone()) {
+                Ok(playback) => {
+                    audio_playback = Some(playback);
+                    info!("Audio playback started");
                 }
-                ToxCommand::GroupReconnect(group_number, reply) => {
-                    let result = tox
-                        .group_reconnect(group_number)
-                        .map_err(|e| e.to_string());
-                    let _ = reply.send(result);
+                Err(e) => {
+                    error!("Failed to start audio playback: {e}");
                 }
-                // ToxAV commands
-                ToxCommand::AvCall {
-                    friend_number,
-                    audio_bit_rate,
-                    video_bit_rate,
-                    reply,
-                } => {
-                    let result = if let Some(ref av) = toxav {
-                        match av.call(friend_number, audio_bit_rate, video_bit_rate) {
-                            Ok(()) => {
-                                // Register the call with the manager
-                                let with_video = video_bit_rate > 0;
-                                if let Ok(mut mgr) = av_manager.lock() {
-                                    mgr.start_call(friend_number, with_video);
-                                }
-                                Ok(())
-                            }
-                            Err(e) => Err(e.to_string()),
-                        }
-                    } else {
-                        Err("ToxAV not available".to_string())
-                    };
-                    let _ = reply.send(result);
+            }
+
+            audio_active = true;
+        }
+
+        // Stop audio when no calls are active
+        if !has_active_call && audio_active {
+            info!("Stopping audio - no active calls");
+            audio_capture = None;
+            audio_playback = None;
+            if let Ok(mut m) = mixer.lock() {
+                m.clear();
+            }
+            audio_active = false;
+        }
+
+        // Check if we have any active video calls
+        let has_video_call = if let Ok(mgr) = av_manager.lock() {
+            let calls = mgr.get_all_calls();
+            if !calls.is_empty() && !video_active {
+                // Log call states when checking for video (use info! to be visible)
+                for c in &calls {
+                    info!(
+                        "VIDEO CHECK - friend {}: state={:?}, has_video={}, is_video_muted={}",
+                        c.friend_number, c.state, c.has_video, c.is_video_muted
+                    );
                 }
-                ToxCommand::AvAnswer {
-                    friend_number,
-                    audio_bit_rate,
-                    video_bit_rate,
-                    reply,
-                } => {
-                    let result = if let Some(ref av) = toxav {
-                        match av.answer(friend_number, audio_bit_rate, video_bit_rate) {
-                            Ok(()) => {
-                                info!("Successfully answered call from friend {}", friend_number);
-                                // Manually transition call to InProgress since ToxAV callback may not fire
-                                if let Ok(mut mgr) = av_manager.lock() {
-                                    // Create a synthetic "active" state to transition the call
-                                    let active_state = toxcord_tox::CallStateFlags {
-                                        error: false,
-                                        finished: false,
-                                        sending_audio: true,
-                                        sending_video: video_bit_rate > 0,
-                                        accepting_audio: true,
-                                        accepting_video: video_bit_rate > 0,
-                                    };
-                                    mgr.update_call_state(friend_number, active_state);
-                                    info!("Transitioned call with friend {} to InProgress after answer", friend_number);
-                                }
-                                // Emit state change to frontend
-                                use crate::managers::av_manager::ToxAvEvent;
-                                let event = ToxAvEvent::CallStateChange {
-                                    friend_number,
-                                    state: "in_progress".to_string(),
-                                    sending_audio: true,
-                                    sending_video: video_bit_rate > 0,
-                                    accepting_audio: true,
-                                    accepting_video: video_bit_rate > 0,
-                                };
-                                if let Err(e) = app_handle.emit("toxav://event", &event) {
-                                    error!("Failed to emit call state change: {e}");
-                                }
-                                Ok(())
-                            }
-                            Err(e) => {
-                                error!("Failed to answer call from friend {}: {}", friend_number, e);
-                                Err(e.to_string())
-                            }
+            }
+            calls.iter().any(|c| c.state == CallStatus::InProgress && c.has_video && !c.is_video_muted)
+        } else {
+            false
+        };
+
+        // Start video capture when a video call becomes active (and hasn't already failed)
+        if has_video_call && !video_active && !video_capture_failed {
+            // Check if screen sharing is active
+            let (is_screen_sharing, screen_share_id, screen_share_options) = {
+                let state = app_handle.state::<AppState>();
+                let sharing = state.is_screen_sharing.try_lock().ok().map(|g| *g).unwrap_or(false);
+                let screen_id = state.screen_share_id.try_lock().ok().and_then(|g| *g);
+                let options = state.screen_share_options.try_lock().ok().map(|g| *g).unwrap_or_default();
+                (sharing, screen_id, options)
+            };
+
+            if is_screen_sharing {
+                // Start screen capture
+                info!("Starting screen capture for active video call (screen_id: {:?})", screen_share_id);
+                match ScreenCapture::start(
+                    screen_share_id,
+                    screen_share_options,
+                    video_tx.clone(),
+                    video_error_tx.clone(),
+                ) {
+                    Ok(capture) => {
+                        screen_capture = Some(capture);
+                        video_active = true;
+                        info!("Screen capture started successfully");
+                    }
+                    Err(e) => {
+                        error!("Failed to start screen capture: {e}");
+                        video_capture_failed = true;
+                        let error_event = ToxAvEvent::VideoError {
+                            error: e.to_string(),
+                        };
+                        if let Err(emit_err) = app_handle.emit("toxav://local-video", &error_event) {
+                            error!("Failed to emit video error event: {emit_err}");
                         }
-                    } else {
-                        Err("ToxAV not available".to_string())
-                    };
-                    let _ = reply.send(result);
+                    }
                 }
-                ToxCommand::AvHangup { friend_number, reply } => {
-                    let result = if let Some(ref av) = toxav {
-                        match av.hangup(friend_number) {
-                            Ok(()) => {
-                                // Clean up the call in the manager
-                                if let Ok(mut mgr) = av_manager.lock() {
-                                    mgr.end_call(friend_number);
-                                }
-                                // Clean up mixer source
-                                if let Ok(mut m) = mixer.lock() {
-                                    m.remove_source(friend_number);
-                                }
-                                Ok(())
-                            }
-                            Err(e) => Err(e.to_string()),
+            } else {
+                // Start camera capture, taking the camera over from a preview
+                let selected_camera_index = {
+                    let state = app_handle.state::<AppState>();
+                    if let Ok(mut preview) = state.camera_preview.lock() {
+                        preview.take();
+                    }
+                    state.selected_camera_index.try_lock().ok().and_then(|guard| *guard)
+                };
+                info!("Starting video capture for active video call (device index: {:?})", selected_camera_index);
+                let background = app_handle.state::<AppState>().video_background.clone();
+                match VideoCapture::start_with_device(
+                    selected_camera_index,
+                    background,
+                    video_tx.clone(),
+                    video_error_tx.clone(),
+                ) {
+                    Ok(capture) => {
+                        video_capture = Some(capture);
+                        video_active = true;
+                        info!("Video capture started successfully");
+                    }
+                    Err(e) => {
+                        error!("Failed to start video capture: {e}");
+                        video_capture_failed = true;
+                        let error_event = ToxAvEvent::VideoError {
+                            error: e.to_string(),
+                        };
+                        if let Err(emit_err) = app_handle.emit("toxav://local-video", &error_event) {
+                            error!("Failed to emit video error event: {emit_err}");
                         }
+                    }
+                }
+            }
+        }
+
+        // Check if screen sharing state changed (to switch between camera and screen)
+        if has_video_call && video_active {
+            let (is_screen_sharing_now, screen_settings) = {
+                let state = app_handle.state::<AppState>();
+                let sharing = state.is_screen_sharing.try_lock().ok().map(|g| *g).unwrap_or(false);
+                let screen_id = state.screen_share_id.try_lock().ok().and_then(|g| *g);
+                let options = state.screen_share_options.try_lock().ok().map(|g| *g);
+                (sharing, options.map(|o| (screen_id, o)))
+            };
+
+            // Detect state change: screen_capture is Some means we're screen sharing, None means camera
+            let currently_screen_sharing = screen_capture.is_some();
+            // A new screen, region or rate also restarts the share
+            let screen_settings_changed = match (&screen_capture, screen_settings) {
+                (Some(capture), Some(settings)) => capture.settings() != settings,
+                _ => false,
+            };
+            if is_screen_sharing_now != currently_screen_sharing || (is_screen_sharing_now && screen_settings_changed) {
+                info!(
+                    "Screen sharing state changed: {} -> {}",
+                    currently_screen_sharing, is_screen_sharing_now
+                );
+                // Stop current capture
+                video_capture = None;
+                screen_capture = None;
+                video_active = false;
+                // Will restart with new source on next iteration
+            }
+        }
+
+        // Check for video capture errors (from capture thread)
+        while let Ok(err) = video_error_rx.try_recv() {
+            error!("Video capture thread error: {}", err.message);
+            video_capture_failed = true;
+            // Notify frontend about the video capture error
+            let error_event = ToxAvEvent::VideoError {
+                error: err.message,
+            };
+            if let Err(emit_err) = app_handle.emit("toxav://local-video", &error_event) {
+                error!("Failed to emit video error event: {emit_err}");
+            }
+            // Stop video/screen capture since it failed
+            video_capture = None;
+            screen_capture = None;
+            video_active = false;
+            video_frames.remove(VideoSource::Local);
+        }
+
+        // Stop video capture when no video calls are active
+        if !has_video_call && video_active {
+            info!("Stopping video/screen capture - no active video calls");
+            video_capture = None;
+            screen_capture = None;
+            video_active = false;
+            video_frames.remove(VideoSource::Local);
+        }
+
+        // Reset video_capture_failed when video call ends so it can retry on next call
+        if !has_video_call && video_capture_failed {
+            video_capture_failed = false;
+        }
+
+        // Send captured audio frames to all active calls
+        if let Some(ref av) = toxav {
+            let mut frame_count = 0;
+            while let Ok(pcm) = audio_rx.try_recv() {
+                frame_count += 1;
+                // Get list of friends we're in active calls with
+                // Global mute (or deafen) drops captured frames for every call
+                let active_friends: Vec<u32> = if let Ok(mgr) = av_manager.lock() {
+                    if mgr.is_muted() || mgr.is_deafened() {
+                        vec![]
                     } else {
-                        Err("ToxAV not available".to_string())
-                    };
-                    let _ = reply.send(result);
+                        mgr.get_all_calls()
+                            .iter()
+                            .filter(|c| c.state == CallStatus::InProgress && !c.is_audio_muted)
+                            .map(|c| c.friend_number)
+                            .collect()
+                    }
+                } else {
+                    vec![]
+                };
+
+                if active_friends.is_empty() && frame_count == 1 {
+                    debug!("Captured audio but no active friends to send to");
                 }
-                ToxCommand::AvMuteAudio { friend_number, reply } => {
-                    let result = if let Some(ref av) = toxav {
-                        match av.mute_audio(friend_number) {
-                            Ok(()) => {
-                                if let Ok(mut mgr) = av_manager.lock() {
-                                    mgr.set_audio_muted(friend_number, true);
-                                }
-                                Ok(())
-                            }
-                            Err(e) => Err(e.to_string()),
-                        }
-                    } else {
-                        Err("ToxAV not available".to_string())
+
+                // Send audio to each active call
+                for friend_number in active_friends {
+                    let frame = AudioFrame {
+                        pcm: pcm.clone(),
+                        sample_count: pcm.len(),
+                        channels: 1,
+                        sampling_rate: 48000,
                     };
-                    let _ = reply.send(result);
-                }
-                ToxCommand::AvUnmuteAudio { friend_number, reply } => {
-                    let result = if let Some(ref av) = toxav {
-                        match av.unmute_audio(friend_number) {
-                            Ok(()) => {
-                                if let Ok(mut mgr) = av_manager.lock() {
-                                    mgr.set_audio_muted(friend_number, false);
-                                }
-                                Ok(())
-                            }
-                            Err(e) => Err(e.to_string()),
+                    match av.audio_send_frame(friend_number, &frame) {
+                        Ok(()) => {
+                            debug!("Sent {} samples to friend {}", pcm.len(), friend_number);
                         }
-                    } else {
-                        Err("ToxAV not available".to_string())
-                    };
-                    let _ = reply.send(result);
-                }
-                ToxCommand::AvHideVideo { friend_number, reply } => {
-                    let result = if let Some(ref av) = toxav {
-                        match av.hide_video(friend_number) {
-                            Ok(()) => {
-                                // Update av_manager state
-                                if let Ok(mut mgr) = av_manager.lock() {
-                                    mgr.set_video_muted(friend_number, true);
-                                }
-                                info!("Video hidden for friend {}", friend_number);
-                                Ok(())
-                            }
-                            Err(e) => Err(e.to_string()),
+                        Err(e) => {
+                            debug!("Failed to send audio frame to friend {}: {e}", friend_number);
                         }
-                    } else {
-                        Err("ToxAV not available".to_string())
-                    };
-                    let _ = reply.send(result);
+                    }
                 }
-                ToxCommand::AvShowVideo { friend_number, reply } => {
-                    let result = if let Some(ref av) = toxav {
-                        match av.show_video(friend_number) {
-                            Ok(()) => {
-                                // Update av_manager state
-                                if let Ok(mut mgr) = av_manager.lock() {
-                                    mgr.set_video_muted(friend_number, false);
-                                }
-                                info!("Video shown for friend {}", friend_number);
-                                Ok(())
-                            }
-                            Err(e) => Err(e.to_string()),
-                        }
-                    } else {
-                        Err("ToxAV not available".to_string())
-                    };
-                    let _ = reply.send(result);
+            }
+        }
+
+        // Send captured video frames to all active video calls
+        if let Some(ref av) = toxav {
+            let mut video_frame_count = 0;
+            while let Ok(frame) = video_rx.try_recv() {
+                video_frame_count += 1;
+                if video_frame_count <= 3 {
+                    info!("Video frame {}: {}x{}, Y={} U={} V={} bytes",
+                           video_frame_count, frame.width, frame.height,
+                           frame.y.len(), frame.u.len(), frame.v.len());
                 }
-                ToxCommand::AvSendAudioFrame {
-                    friend_number,
-                    pcm,
-                    sample_count,
-                    channels,
-                    sampling_rate,
-                } => {
-                    if let Some(ref av) = toxav {
-                        let frame = AudioFrame {
-                            pcm,
-                            sample_count,
-                            channels,
-                            sampling_rate,
-                        };
-                        if let Err(e) = av.audio_send_frame(friend_number, &frame) {
-                            debug!("Failed to send audio frame: {e}");
-                        }
+
+                // Get list of friends we're in active video calls with
+                let active_video_friends: Vec<u32> = if let Ok(mgr) = av_manager.lock() {
+                    mgr.get_all_calls()
+                        .iter()
+                        .filter(|c| c.state == CallStatus::InProgress && c.has_video && !c.is_video_muted)
+                        .map(|c| c.friend_number)
+                        .collect()
+                } else {
+                    vec![]
+                };
+
+                // Send video to each active video call
+                for friend_number in &active_video_friends {
+                    let tox_frame = VideoFrame::new(
+                        frame.y.clone(),
+                        frame.u.clone(),
+                        frame.v.clone(),
+                        frame.width,
+                        frame.height,
+                    );
+                    if let Err(e) = tox_frame.validate() {
+                        debug!("Invalid video frame: {e}");
+                        continue;
+                    }
+                    if let Err(e) = av.video_send_frame(*friend_number, &tox_frame) {
+                        debug!("Failed to send video frame to friend {}: {e}", friend_number);
                     }
                 }
-                ToxCommand::AvGetCallState { friend_number, reply } => {
-                    // Get call state from the AV manager using blocking access
-                    let state = if let Ok(mgr) = av_manager.lock() {
-                        mgr.get_call(friend_number).cloned()
-                    } else {
-                        None
+
+                // Local preview for the frontend
+                video_frames.publish_yuv(VideoSource::Local, &frame.planes());
+            }
+        }
+
+        // Process offline queue flush requests
+        while let Ok(friend_number) = offline_flush_rx.try_recv() {
+            let queued = store.get_offline_messages_for("friend", &friend_number.to_string());
+            if let Ok(messages) = queued {
+                for (queue_id, msg_type, content) in messages {
+                    let sent = match msg_type.as_str() {
+                        STATUS_NOTE_QUEUE_TYPE => send_queued_status_note(&tox, friend_number, &content),
+                        _ => send_friend_text(&tox, &conversation_locks, friend_number, &content).is_ok(),
                     };
-                    let _ = reply.send(state);
+                    if sent {
+                        if let Err(e) = store.remove_offline_message(queue_id) {
+                            error!("Failed to remove offline message {queue_id}: {e}");
+                        } else {
+                            info!("Flushed offline message {queue_id} to friend {friend_number}");
+                        }
+                    }
                 }
-                ToxCommand::SaveProfile(reply) => {
-                    save_profile(&tox, &password, &profile_path);
-                    let _ = reply.send(Ok(()));
+            }
+            reoffer_file_transfers(&tox, &store, &file_manager, friend_number);
+        }
+
+        send_due_scheduled_messages(&tox, &store, &app_handle, &conversation_locks);
+
+        while let Ok(friend_number) = auto_reply_rx.try_recv() {
+            reply_while_away(&tox, &store, &app_handle, &conversation_locks, &incognito, friend_number);
+        }
+
+        while let Ok(action) = device_sync_rx.try_recv() {
+            if device_sync::handle(&tox, &store, &app_handle, action) {
+                saves.mark_dirty();
+            }
+        }
+
+        while let Ok(action) = history_rx.try_recv() {
+            guild_history::handle(&tox, &store, &app_handle, &mut backfills, action);
+        }
+
+        while let Ok(action) = presence_rx.try_recv() {
+            match action {
+                PresenceAction::PeerJoined(group_number, peer_id) => {
+                    send_guild_manifest(&tox, &store, group_number, peer_id);
+                }
+                PresenceAction::FriendOnline(friend_number) => {
+                    send_disappearing_timer(&tox, &store, friend_number);
+                }
+            }
+            rich_presence.handle(&tox, action);
+        }
+
+        if last_poll_check.elapsed() >= POLL_CLOSE_CHECK_INTERVAL {
+            last_poll_check = Instant::now();
+            close_due_polls(&store, &app_handle);
+        }
+
+        if message_activity.swap(false, Ordering::Relaxed) {
+            power.activity();
+        }
+        power.update(av_manager.lock().is_ok_and(|mgr| mgr.has_any_call()));
+
+        // Toxcore retries its known nodes by itself, but after a long time
+        // offline they may all be gone
+        if network_mode == NetworkMode::Normal
+            && !tox.self_connection_status().is_connected()
+            && last_bootstrap.elapsed() >= power.rebootstrap_interval()
+        {
+            last_bootstrap = Instant::now();
+            bootstrap(&tox);
+        }
+
+        if saves.is_due(power.profile_save_interval()) {
+            save_profile(&tox, &password, &profile_path);
+            saves.saved();
+        }
+
+        if let Some(slept) = sleep_detector.check() {
+            resume_after_sleep(&tox, toxav.as_ref(), &av_manager, &mixer, &video_frames, &app_handle, slept, network_mode);
+        }
+
+        if last_call_watchdog.elapsed() >= CALL_WATCHDOG_INTERVAL {
+            last_call_watchdog = Instant::now();
+            if let Some(ref av) = toxav {
+                drop_stalled_calls(&tox, av, &av_manager, &mixer, &video_frames, &app_handle);
+            }
+        }
+
+        if last_event_reminder_check.elapsed() >= EVENT_REMINDER_CHECK_INTERVAL {
+            last_event_reminder_check = Instant::now();
+            send_event_reminders(&store, &app_handle);
+        }
+
+        if last_write_flush.elapsed() >= power.write_flush_interval() {
+            last_write_flush = Instant::now();
+            match store.flush_writes() {
+                Ok(0) => {}
+                Ok(_) => crate::tray::refresh_unread_badge(&app_handle, &store),
+                Err(e) => error!("Failed to write queued messages: {e}"),
+            }
+        }
+
+        if last_disappearing_sweep.elapsed() >= DISAPPEARING_SWEEP_INTERVAL {
+            last_disappearing_sweep = Instant::now();
+            sweep_expired_messages(&store, &app_handle);
+        }
+
+        if last_status_expiry_check.elapsed() >= STATUS_EXPIRY_CHECK_INTERVAL {
+            last_status_expiry_check = Instant::now();
+            if let Err(e) = store.delete_expired_status_notes(chrono::Utc::now().timestamp()) {
+                error!("{e}");
+            }
+            for public_key in rich_presence.expire_custom_statuses(&tox, &store) {
+                if let Err(e) = app_handle.emit("tox://event", &ToxEvent::CustomStatusChanged { public_key, status: None }) {
+                    error!("Failed to emit custom status event: {e}");
+                }
+            }
+        }
+
+        // Wait out the recommended interval (or longer, when power saving),
+        // waking as soon as a command arrives
+        let interval = power.iteration_interval(tox.iteration_interval());
+        next_cmd = match waiter.block_on(tokio::time::timeout(interval, cmd_rx.recv())) {
+            Ok(Some(cmd)) => Some(cmd),
+            // The manager is gone without shutting us down; keep the old pace
+            Ok(None) => {
+                std::thread::sleep(interval);
+                None
+            }
+            Err(_) => None,
+        };
+    }
+}
+
+/// Warn the frontend if a friend's public key differs from the one on record
+fn check_friend_key(store: &MessageStore, app_handle: &AppHandle, friend_number: u32, public_key: &str) {
+    let old_public_key = match store.get_friend_public_key(friend_number) {
+        Ok(Some(old)) if !old.is_empty() && !public_key.is_empty() && !old.eq_ignore_ascii_case(public_key) => old,
+        Ok(_) => return,
+        Err(e) => {
+            error!("{e}");
+            return;
+        }
+    };
+    let was_verified = matches!(store.get_friend_verification(friend_number), Ok(Some(_)));
+    warn!("Public key of friend {friend_number} changed (verified: {was_verified})");
+
+    let event = ToxEvent::FriendKeyChanged {
+        friend_number,
+        old_public_key,
+        new_public_key: public_key.to_string(),
+        was_verified,
+    };
+    if let Err(e) = app_handle.emit("tox://event", &event) {
+        error!("Failed to emit key change event: {e}");
+    }
+}
+
+/// Bootstrap to DHT nodes and add TCP relays for NAT traversal fallback
+fn bootstrap(tox: &ToxInstance) {
+    for node in default_bootstrap_nodes() {
+        // Bootstrap for DHT discovery (UDP)
+        if let Err(e) = tox.bootstrap(&node.address, node.port, &node.public_key) {
+            warn!("Failed to bootstrap to {}: {e}", node.address);
+        }
+
+        // Add TCP relay for each supported port - essential for NAT traversal
+        // when direct UDP connection fails (common behind symmetric NATs/firewalls)
+        for tcp_port in &node.tcp_ports {
+            if let Err(e) = tox.add_tcp_relay(&node.address, *tcp_port, &node.public_key) {
+                warn!("Failed to add TCP relay {}:{}: {e}", node.address, tcp_port);
+            } else {
+                debug!("Added TCP relay {}:{}", node.address, tcp_port);
+            }
+        }
+    }
+
+    info!("Bootstrap complete: {} nodes configured with TCP relay support",
+          default_bootstrap_nodes().len());
+}
+
+/// Tell the frontend the network mode changed, along with the connection
+/// status it leaves us in
+fn emit_network_mode(app_handle: &AppHandle, mode: NetworkMode, status: ConnectionStatus) {
+    let events = [
+        ToxEvent::NetworkModeChanged { mode },
+        ToxEvent::ConnectionStatus {
+            connected: status.is_connected(),
+            status: match status {
+                ConnectionStatus::None => "none",
+                ConnectionStatus::Tcp => "tcp",
+                ConnectionStatus::Udp => "udp",
+            }
+            .to_string(),
+        },
+    ];
+    for event in events {
+        if let Err(e) = app_handle.emit("tox://event", &event) {
+            error!("Failed to emit network mode event: {e}");
+        }
+    }
+}
+
+/// Make toxcore look for offline friends again, e.g. after a VPN was switched
+/// on. The DHT is bootstrapped afresh (in the normal network mode), and each friend is removed and added
+/// back, which restarts its connection attempt. A friend is only re-added when
+/// toxcore will give it back the same friend number (the lowest free one), so
+/// our records stay attached to it; others just get the fresh bootstrap.
+fn refresh_friend_connections(
+    tox: &ToxInstance,
+    only: Option<u32>,
+    network_mode: NetworkMode,
+) -> Result<Vec<u32>, ToxcordError> {
+    let friends = tox.friend_list();
+    if only.is_some_and(|f| !friends.contains(&f)) {
+        return Err(ToxcordError::not_found("Friend not found"));
+    }
+    if network_mode == NetworkMode::Normal {
+        bootstrap(tox);
+    }
+
+    let mut retried = Vec::new();
+    for &friend_number in friends.iter().filter(|&&f| only.is_none_or(|only| only == f)) {
+        if tox.friend_connection_status(friend_number).is_connected() {
+            continue;
+        }
+        if !(0..friend_number).all(|n| friends.contains(&n)) {
+            debug!("Not re-adding friend {friend_number}, its number would change");
+            continue;
+        }
+        let Some(public_key) = tox
+            .friend_public_key(friend_number)
+            .and_then(|pk| public_key_from_hex(&pk.0))
+        else {
+            continue;
+        };
+        if let Err(e) = tox.friend_delete(friend_number) {
+            warn!("Failed to remove friend {friend_number} to reconnect: {e}");
+            continue;
+        }
+        match tox.friend_add_norequest(&public_key) {
+            Ok(n) if n == friend_number => retried.push(friend_number),
+            Ok(n) => error!("Friend {friend_number} came back as {n} after reconnecting"),
+            Err(e) => error!("Failed to add friend {friend_number} back after reconnecting: {e}"),
+        }
+    }
+    info!("Retrying connections to friends {retried:?}");
+    Ok(retried)
+}
+
+/// Join the groups of guilds that have a chat ID but no group in the tox
+/// instance, e.g. after the savedata was lost or corrupted
+fn rejoin_lost_guilds(tox: &ToxInstance, store: &MessageStore) {
+    let guilds = match store.get_guild_chat_ids() {
+        Ok(guilds) => guilds,
+        Err(e) => {
+            error!("{e}");
+            return;
+        }
+    };
+    let known: Vec<String> = tox
+        .group_list()
+        .into_iter()
+        .filter_map(|g| tox.group_get_chat_id(g).ok())
+        .map(|c| c.iter().map(|b| format!("{b:02X}")).collect())
+        .collect();
+    let self_name = tox.self_name();
+
+    for (guild_id, chat_id) in guilds {
+        if known.iter().any(|k| k.eq_ignore_ascii_case(&chat_id)) {
+            continue;
+        }
+        let Some(chat_id_bytes) = public_key_from_hex(&chat_id) else {
+            warn!("Guild {guild_id} has a malformed chat ID");
+            continue;
+        };
+        match tox.group_join(&chat_id_bytes, &self_name, "") {
+            Ok(group_number) => {
+                info!("Rejoined lost group of guild {guild_id} as group {group_number}");
+                if let Err(e) = store.update_guild_group_number(&guild_id, group_number as i64) {
+                    error!("Failed to update guild group_number: {e}");
                 }
-                ToxCommand::Shutdown(reply) => {
-                    save_profile(&tox, &password, &profile_path);
-                    info!("Tox thread shutting down");
-                    let _ = reply.send(());
-                    // Clean up handler pointers
-                    unsafe {
-                        let _ = Box::from_raw(handler_ptr);
-                        if let Some(av_ptr) = av_handler {
-                            let _ = Box::from_raw(av_ptr);
-                        }
-                    }
-                    // ToxAV will be dropped automatically when toxav goes out of scope
-                    return;
+            }
+            Err(e) => warn!("Failed to rejoin group of guild {guild_id}: {e}"),
+        }
+    }
+}
+
+/// Send the guild manifest and channel layout to a peer that just joined, if we
+/// moderate the guild and have them, and give them the NGC role behind their
+/// custom role
+fn send_guild_manifest(tox: &ToxInstance, store: &MessageStore, group_number: u32, peer_id: u32) {
+    let Ok(Some(guild)) = store.get_guild_by_group_number(group_number as i64) else {
+        return;
+    };
+    if !matches!(tox.group_self_get_role(group_number), Ok(GroupRole::Founder | GroupRole::Moderator)) {
+        return;
+    }
+    match store.get_guild_manifest(&guild.id) {
+        Ok(manifest) if manifest.version > 0 => {
+            if let Err(e) = tox.group_send_custom_private_packet(group_number, peer_id, true, &manifest.to_packet()) {
+                debug!("Failed to send guild manifest: {e}");
+            }
+            sync_custom_role(tox, &manifest, group_number, peer_id);
+        }
+        Ok(_) => {}
+        Err(e) => error!("{e}"),
+    }
+    match store.get_guild_layout(&guild.id) {
+        Ok(layout) => {
+            for packet in layout.to_packets() {
+                if let Err(e) = tox.group_send_custom_private_packet(group_number, peer_id, true, &packet) {
+                    debug!("Failed to send channel layout: {e}");
+                    break;
                 }
             }
         }
+        Err(e) => error!("{e}"),
+    }
+}
 
-        // Run tox_iterate with the handler as user_data
-        tox.iterate_with_userdata(handler_ptr as *mut std::ffi::c_void);
+/// Give a peer the NGC role their custom role maps onto, if it isn't theirs
+/// already. Only the founder can make moderators; other failures are expected
+/// for moderators too, so they're only logged.
+fn sync_custom_role(tox: &ToxInstance, manifest: &GuildManifest, group_number: u32, peer_id: u32) {
+    let Ok(pk) = tox.group_peer_get_public_key(group_number, peer_id) else {
+        return;
+    };
+    let public_key: String = pk.iter().map(|b| format!("{b:02X}")).collect();
+    let Some(custom_role) = manifest.member_role(&public_key) else {
+        return;
+    };
+    let wanted = match custom_role.base_role {
+        BaseRole::Moderator => GroupRole::Moderator,
+        BaseRole::User => GroupRole::User,
+        BaseRole::Observer => GroupRole::Observer,
+    };
+    match tox.group_peer_get_role(group_number, peer_id) {
+        Ok(role) if role == wanted || role == GroupRole::Founder => {}
+        Ok(_) => {
+            if let Err(e) = tox.group_set_role(group_number, peer_id, wanted) {
+                debug!("Failed to give {public_key} the {} role: {e}", custom_role.name);
+            }
+        }
+        Err(e) => debug!("{e}"),
+    }
+}
 
-        // Run toxav_iterate
-        if let Some(ref av) = toxav {
-            av.iterate();
+/// Audit log name of a group role
+fn role_name(role: GroupRole) -> &'static str {
+    match role {
+        GroupRole::Founder => "founder",
+        GroupRole::Moderator => "moderator",
+        GroupRole::User => "user",
+        GroupRole::Observer => "observer",
+    }
+}
+
+/// Public key and name of a peer about to be moderated
+fn audit_target(tox: &ToxInstance, group_number: u32, peer_id: u32) -> Option<(String, String)> {
+    let info = tox.group_get_peer_info(group_number, peer_id).ok()?;
+    Some((info.public_key, info.name))
+}
+
+/// Record a moderation action we took in the guild behind `group_number`
+fn record_own_audit(
+    tox: &ToxInstance,
+    store: &MessageStore,
+    group_number: u32,
+    action: &str,
+    target: Option<(String, String)>,
+    details: &str,
+) {
+    let guild_id = match store.get_guild_by_group_number(group_number as i64) {
+        Ok(Some(guild)) => guild.id,
+        Ok(None) => return,
+        Err(e) => {
+            error!("{e}");
+            return;
+        }
+    };
+    let self_public_key: String = match tox.group_self_get_public_key(group_number) {
+        Ok(pk) => pk.iter().map(|b| format!("{b:02X}")).collect(),
+        Err(e) => {
+            warn!("Failed to get own group key for the audit log: {e}");
+            return;
         }
+    };
+    let (target_public_key, target_name) = target.unzip();
+    if let Err(e) = store.add_audit_entry(
+        &guild_id,
+        action,
+        &self_public_key,
+        &tox.self_name(),
+        target_public_key.as_deref(),
+        target_name.as_deref().unwrap_or_default(),
+        details,
+    ) {
+        error!("{e}");
+    }
+}
 
-        // Check if we have any active calls (in_progress state) to manage audio
-        let (has_active_call, call_count) = if let Ok(mgr) = av_manager.lock() {
-            let calls = mgr.get_all_calls();
-            let count = calls.len();
-            let active = calls.iter().any(|c| c.state == CallStatus::InProgress);
-            // Log when there are calls but audio hasn't started yet
-            if count > 0 && !audio_active {
-                for call in calls {
-                    debug!("Call state check: friend={} state={:?} audio_active={}",
-                           call.friend_number, call.state, audio_active);
-                }
+/// Insert a finished attachment and tell the frontend its preview is ready
+fn persist_attachment(store: &MessageStore, app_handle: &AppHandle, attachment: crate::db::message_store::AttachmentRecord) {
+    if let Err(e) = store.insert_attachment(&attachment) {
+        error!("Failed to persist attachment: {e}");
+        return;
+    }
+    let event = ToxEvent::AttachmentReady {
+        message_id: attachment.message_id,
+        attachment_id: attachment.id,
+        filename: attachment.filename,
+        mime_type: attachment.mime_type,
+        file_size: attachment.file_size,
+        width: attachment.width,
+        height: attachment.height,
+        has_thumbnail: attachment.thumbnail_path.is_some(),
+    };
+    if let Err(e) = app_handle.emit("tox://event", &event) {
+        error!("Failed to emit attachment event: {e}");
+    }
+}
+
+/// Send scheduled messages whose time has come. Messages for offline friends
+/// go through the offline queue; channel messages that can't be sent fail.
+fn send_due_scheduled_messages(tox: &ToxInstance, store: &MessageStore, app_handle: &AppHandle, locks: &ConversationLocks) {
+    let now = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+    let due = match store.get_due_scheduled_messages(&now) {
+        Ok(due) => due,
+        Err(e) => {
+            error!("Failed to load scheduled messages: {e}");
+            return;
+        }
+    };
+
+    for scheduled in due {
+        let mut msg_id = uuid::Uuid::new_v4().to_string();
+        let timestamp = chrono::Utc::now().to_rfc3339();
+        let result = match scheduled.target_type.as_str() {
+            "friend" => send_scheduled_to_friend(tox, store, locks, &scheduled, &msg_id, &timestamp),
+            // Channel messages get their content-addressed ID
+            "channel" => send_scheduled_to_channel(tox, store, &scheduled, &timestamp).map(|(status, id)| {
+                msg_id = id;
+                status
+            }),
+            other => Err(format!("Unknown target type '{other}'")),
+        };
+
+        let (status, message_id) = match result {
+            Ok(status) => {
+                info!("Scheduled message {} {status}", scheduled.id);
+                (status, Some(msg_id))
+            }
+            Err(e) => {
+                warn!("Failed to send scheduled message {}: {e}", scheduled.id);
+                ("failed", None)
             }
-            (active, count)
-        } else {
-            (false, 0)
         };
+        if let Err(e) = store.update_scheduled_message_status(&scheduled.id, status, message_id.as_deref()) {
+            error!("Failed to persist scheduled message status: {e}");
+        }
 
-        // Start audio capture/playback when a call becomes active
-        if has_active_call && !audio_active {
-            info!("Starting audio for active call");
+        let event = ToxEvent::ScheduledMessage {
+            id: scheduled.id,
+            status: status.to_string(),
+            message_id,
+            target_type: scheduled.target_type,
+            target_id: scheduled.target_id,
+            timestamp,
+        };
+        if let Err(e) = app_handle.emit("tox://event", &event) {
+            error!("Failed to emit scheduled message event: {e}");
+        }
+    }
+}
 
-            // Start audio capture (microphone)
-            match AudioCapture::start(audio_tx.clone()) {
-                Ok(capture) => {
-                    audio_capture = Some(capture);
-                    info!("Audio capture started");
-                }
-                Err(e) => {
-                    error!("Failed to start audio capture: {e}");
-                }
-            }
+/// How often expired custom statuses are cleared
+const STATUS_EXPIRY_CHECK_INTERVAL: Duration = Duration::from_secs(15);
 
-            // Start audio playback (speakers) with the shared mixer
-            match AudioPlayback::start(mixer.clone()) {
-                Ok(playback) => {
-                    audio_playback = Some(playback);
-                    info!("Audio playback started");
-                }
-                Err(e) => {
-                    error!("Failed to start audio playback: {e}");
+/// Peers Tox reports this soon after we join a group were already there, so
+/// they aren't welcomed
+const WELCOME_GRACE: Duration = Duration::from_secs(30);
+
+/// How often connected calls are checked for liveness
+const CALL_WATCHDOG_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How often polls past their closing time are closed
+const POLL_CLOSE_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Close polls whose time is up and send the final tallies to the frontend
+fn close_due_polls(store: &MessageStore, app_handle: &AppHandle) {
+    let closed = match store.close_due_polls(chrono::Utc::now().timestamp()) {
+        Ok(closed) => closed,
+        Err(e) => {
+            error!("{e}");
+            return;
+        }
+    };
+    for poll_id in closed {
+        let Ok(Some(poll)) = store.get_poll(&poll_id) else {
+            continue;
+        };
+        match store.get_poll_tallies(&poll_id, poll.options.len()) {
+            Ok(tallies) => {
+                info!("Poll {poll_id} closed");
+                let event = ToxEvent::PollUpdated { poll_id, tallies, closed: true };
+                if let Err(e) = app_handle.emit("tox://event", &event) {
+                    error!("Failed to emit poll event: {e}");
                 }
             }
+            Err(e) => error!("{e}"),
+        }
+    }
+}
 
-            audio_active = true;
+/// How often upcoming guild events are checked for reminders
+const EVENT_REMINDER_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Remind the frontend of guild events starting soon
+fn send_event_reminders(store: &MessageStore, app_handle: &AppHandle) {
+    let now = chrono::Utc::now().timestamp();
+    let due = match store.take_due_event_reminders(now, guild_events::REMINDER_LEAD_SECS) {
+        Ok(due) => due,
+        Err(e) => {
+            error!("{e}");
+            return;
         }
+    };
+    for event in due {
+        info!("Guild event {} starts soon", event.id);
+        let event = ToxEvent::GuildEventReminder {
+            guild_id: event.guild_id,
+            event_id: event.id,
+            title: event.title,
+            start_time: event.start_time,
+        };
+        if let Err(e) = app_handle.emit("tox://event", &event) {
+            error!("Failed to emit event reminder: {e}");
+        }
+    }
+}
 
-        // Stop audio when no calls are active
-        if !has_active_call && audio_active {
-            info!("Stopping audio - no active calls");
-            audio_capture = None;
-            audio_playback = None;
-            if let Ok(mut m) = mixer.lock() {
-                m.clear();
-            }
-            audio_active = false;
+/// How often expired disappearing messages are deleted
+const DISAPPEARING_SWEEP_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Send a friend our disappearing timer, if the conversation has one
+fn send_disappearing_timer(tox: &ToxInstance, store: &MessageStore, friend_number: u32) {
+    let (seconds, set_at) = match store.get_disappearing_timer(friend_number) {
+        Ok(Some(timer)) => timer,
+        Ok(None) => return,
+        Err(e) => {
+            error!("{e}");
+            return;
+        }
+    };
+    let packet = TimerPacket { seconds, set_at };
+    if let Err(e) = tox.friend_send_lossless_packet(friend_number, &packet.to_bytes()) {
+        debug!("Failed to send disappearing timer to friend {friend_number}: {e}");
+    }
+}
+
+/// Offline queue entries holding a status note as JSON
+const STATUS_NOTE_QUEUE_TYPE: &str = "status_note";
+
+/// Save a status note and send it to every friend, queueing it for those offline
+fn post_status_note(tox: &ToxInstance, store: &MessageStore, text: &str) -> Result<StatusNote, String> {
+    let note = StatusNote::new(uuid::Uuid::new_v4().to_string(), text, chrono::Utc::now().timestamp())?;
+    store.insert_status_note(None, &note)?;
+
+    let packet = note.to_bytes();
+    let json = serde_json::to_string(&note).map_err(|e| format!("Failed to serialize status note: {e}"))?;
+    for friend_number in tox.friend_list() {
+        let sent = tox.friend_connection_status(friend_number).is_connected()
+            && tox.friend_send_lossless_packet(friend_number, &packet).is_ok();
+        if !sent {
+            store.queue_offline_message("friend", &friend_number.to_string(), STATUS_NOTE_QUEUE_TYPE, &json)?;
+        }
+    }
+    Ok(note)
+}
+
+/// Send a queued status note to a friend who came online. Returns true once
+/// it's done with: sent, expired or unreadable.
+fn send_queued_status_note(tox: &ToxInstance, friend_number: u32, json: &str) -> bool {
+    let note: StatusNote = match serde_json::from_str(json) {
+        Ok(note) => note,
+        Err(e) => {
+            warn!("Dropping unreadable queued status note: {e}");
+            return true;
+        }
+    };
+    if note.is_expired(chrono::Utc::now().timestamp()) {
+        return true;
+    }
+    tox.friend_send_lossless_packet(friend_number, &note.to_bytes()).is_ok()
+}
+
+/// Delete disappearing messages that have expired and let the frontend know
+fn sweep_expired_messages(store: &MessageStore, app_handle: &AppHandle) {
+    let expired = match store.delete_expired_direct_messages(chrono::Utc::now().timestamp()) {
+        Ok(expired) => expired,
+        Err(e) => {
+            error!("{e}");
+            return;
+        }
+    };
+    if expired.is_empty() {
+        return;
+    }
+
+    let mut by_friend: HashMap<u32, Vec<String>> = HashMap::new();
+    for (friend_number, message_id) in expired {
+        by_friend.entry(friend_number).or_default().push(message_id);
+    }
+    for (friend_number, message_ids) in by_friend {
+        debug!("{} disappearing messages with friend {friend_number} expired", message_ids.len());
+        let event = ToxEvent::DirectMessagesExpired { friend_number, message_ids };
+        if let Err(e) = app_handle.emit("tox://event", &event) {
+            error!("Failed to emit expired messages event: {e}");
+        }
+    }
+    crate::tray::refresh_unread_badge(app_handle, store);
+}
+
+/// How often a friend gets the Do Not Disturb or missed call message
+const DND_REPLY_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Profile setting holding the Do Not Disturb auto-reply text (empty for none)
+pub const DND_AUTO_REPLY_SETTING: &str = "dnd_auto_reply";
+
+/// Hang up a call declined by Do Not Disturb and send the auto-reply if one is set
+/// and the friend hasn't had it within the last hour
+fn decline_call(
+    tox: &ToxInstance,
+    av: &ToxAvInstance,
+    store: &MessageStore,
+    app_handle: &AppHandle,
+    locks: &ConversationLocks,
+    incognito: &Incognito,
+    dnd_replies: &mut HashMap<u32, Instant>,
+    friend_number: u32,
+) {
+    if let Err(e) = av.hangup(friend_number) {
+        warn!("Failed to decline call from friend {friend_number}: {e}");
+    }
+
+    let reply = match store.get_setting(DND_AUTO_REPLY_SETTING) {
+        Ok(reply) => reply.unwrap_or_default(),
+        Err(e) => {
+            error!("Failed to load Do Not Disturb auto-reply: {e}");
+            return;
+        }
+    };
+    if reply.trim().is_empty() {
+        return;
+    }
+    if dnd_replies.get(&friend_number).is_some_and(|sent| sent.elapsed() < DND_REPLY_INTERVAL) {
+        return;
+    }
+    if let Err(e) = send_auto_reply(tox, store, app_handle, locks, incognito, friend_number, &reply) {
+        warn!("Failed to send auto-reply to friend {friend_number}: {e}");
+        return;
+    }
+    dnd_replies.insert(friend_number, Instant::now());
+}
+
+/// Profile setting holding the message sent to callers we missed; empty for none
+pub const MISSED_CALL_REPLY_SETTING: &str = "missed_call_reply";
+
+#[derive(Debug, Clone, Copy)]
+enum MissedCallReason {
+    /// Rang until the caller gave up
+    NoAnswer,
+    /// Declined automatically because we were Busy
+    DoNotDisturb,
+}
+
+impl MissedCallReason {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::NoAnswer => "no_answer",
+            Self::DoNotDisturb => "do_not_disturb",
+        }
+    }
+}
+
+/// Hang up calls the watchdog finds stalled, cleaning up their playback and
+/// video, and tell the frontend why they ended
+fn drop_stalled_calls(
+    tox: &ToxInstance,
+    av: &ToxAvInstance,
+    av_manager: &std::sync::Mutex<AvManager>,
+    mixer: &std::sync::Mutex<AudioMixer>,
+    video_frames: &crate::video::frames::FrameBuffer,
+    app_handle: &AppHandle,
+) {
+    let stalled = match av_manager.lock() {
+        Ok(mut mgr) => mgr.stalled_calls(Instant::now(), |friend| tox.friend_connection_status(friend).is_connected()),
+        Err(_) => return,
+    };
+    for (friend_number, reason) in stalled {
+        drop_call(av, av_manager, mixer, video_frames, app_handle, friend_number, reason);
+    }
+}
+
+/// Hang up every call, ringing or connected, before the computer sleeps.
+/// Capture and playback stop by themselves once no call is left.
+fn drop_all_calls(
+    av: &ToxAvInstance,
+    av_manager: &std::sync::Mutex<AvManager>,
+    mixer: &std::sync::Mutex<AudioMixer>,
+    video_frames: &crate::video::frames::FrameBuffer,
+    app_handle: &AppHandle,
+) {
+    let calls: Vec<u32> = match av_manager.lock() {
+        Ok(mgr) => mgr.get_all_calls().iter().map(|call| call.friend_number).collect(),
+        Err(_) => return,
+    };
+    for friend_number in calls {
+        drop_call(av, av_manager, mixer, video_frames, app_handle, friend_number, StallReason::Suspended);
+    }
+}
+
+/// Hang up one call without the user asking, clean up its playback and
+/// video, and tell the frontend why it ended
+fn drop_call(
+    av: &ToxAvInstance,
+    av_manager: &std::sync::Mutex<AvManager>,
+    mixer: &std::sync::Mutex<AudioMixer>,
+    video_frames: &crate::video::frames::FrameBuffer,
+    app_handle: &AppHandle,
+    friend_number: u32,
+    reason: StallReason,
+) {
+    warn!("Dropping call with friend {friend_number}: {}", reason.as_str());
+    // The call may already be gone on the ToxAV side
+    if let Err(e) = av.hangup(friend_number) {
+        debug!("Hangup of dropped call with friend {friend_number} failed: {e}");
+    }
+    if let Ok(mut mgr) = av_manager.lock() {
+        mgr.end_call(friend_number);
+    }
+    if let Ok(mut m) = mixer.lock() {
+        m.remove_source(friend_number);
+    }
+    video_frames.remove(VideoSource::Friend(friend_number));
+    crate::pip::close(app_handle);
+
+    let event = ToxAvEvent::CallDropped {
+        friend_number,
+        reason: reason.as_str().to_string(),
+    };
+    if let Err(e) = app_handle.emit("toxav://event", &event) {
+        error!("Failed to emit call dropped event: {e}");
+    }
+}
+
+/// Batches profile saves. The savedata is encrypted and written whole, so
+/// changes only mark it dirty and it's written at most once per save
+/// interval (longer when power saving); shutdown and sleep save right away.
+struct SaveScheduler {
+    dirty: bool,
+    last_save: Instant,
+}
+
+impl SaveScheduler {
+    fn new() -> Self {
+        Self { dirty: false, last_save: Instant::now() }
+    }
+
+    fn mark_dirty(&mut self) {
+        self.dirty = true;
+    }
+
+    fn is_due(&self, interval: Duration) -> bool {
+        self.dirty && self.last_save.elapsed() >= interval
+    }
+
+    fn saved(&mut self) {
+        self.dirty = false;
+        self.last_save = Instant::now();
+    }
+}
+
+/// A gap between loop iterations this long on the wall clock means the
+/// computer was asleep; the thread itself never stalls for this long
+const SLEEP_GAP: Duration = Duration::from_secs(30);
+
+/// Notices the computer waking up, from the wall clock jumping ahead between
+/// loop iterations while the thread was frozen
+struct SleepDetector {
+    last_check: SystemTime,
+}
+
+impl SleepDetector {
+    fn new() -> Self {
+        Self { last_check: SystemTime::now() }
+    }
+
+    /// How long we slept, if we did since the last check
+    fn check(&mut self) -> Option<Duration> {
+        let now = SystemTime::now();
+        // A clock set backwards isn't a sleep
+        let gap = now.duration_since(self.last_check).unwrap_or_default();
+        self.last_check = now;
+        (gap >= SLEEP_GAP).then_some(gap)
+    }
+}
+
+/// Recover after sleep: calls didn't survive it, and the DHT and relay
+/// connections have likely gone stale, so end the calls, bootstrap again
+/// (unless we stay off the public DHT) and have the frontend reload friends
+/// and groups
+#[allow(clippy::too_many_arguments)]
+fn resume_after_sleep(
+    tox: &ToxInstance,
+    toxav: Option<&ToxAvInstance>,
+    av_manager: &std::sync::Mutex<AvManager>,
+    mixer: &std::sync::Mutex<AudioMixer>,
+    video_frames: &crate::video::frames::FrameBuffer,
+    app_handle: &AppHandle,
+    slept: Duration,
+    network_mode: NetworkMode,
+) {
+    info!("Resuming after sleep ({}s)", slept.as_secs());
+    if let Some(av) = toxav {
+        drop_all_calls(av, av_manager, mixer, video_frames, app_handle);
+    }
+    if network_mode == NetworkMode::Normal {
+        bootstrap(tox);
+    }
+    let event = ToxEvent::Resumed { slept_secs: slept.as_secs() };
+    if let Err(e) = app_handle.emit("tox://event", &event) {
+        error!("Failed to emit resume event: {e}");
+    }
+}
+
+/// Persist a missed call and tell the frontend, for the per-friend badge
+fn record_missed_call(
+    store: &MessageStore,
+    app_handle: &AppHandle,
+    friend_number: u32,
+    with_video: bool,
+    reason: MissedCallReason,
+) {
+    let record = crate::db::message_store::MissedCallRecord {
+        id: uuid::Uuid::new_v4().to_string(),
+        friend_number: friend_number as i64,
+        timestamp: chrono::Utc::now().to_rfc3339(),
+        with_video,
+        reason: reason.as_str().to_string(),
+        seen: false,
+    };
+    if let Err(e) = store.insert_missed_call(&record) {
+        error!("{e}");
+    }
+    info!("Missed call from friend {friend_number} ({})", record.reason);
+
+    let event = ToxAvEvent::MissedCall {
+        id: record.id,
+        friend_number,
+        timestamp: record.timestamp,
+        with_video,
+        reason: record.reason,
+    };
+    if let Err(e) = app_handle.emit("toxav://event", &event) {
+        error!("Failed to emit missed call event: {e}");
+    }
+}
+
+/// Send the missed call message, if one is set, at most once an hour per caller
+fn send_missed_call_reply(
+    tox: &ToxInstance,
+    store: &MessageStore,
+    app_handle: &AppHandle,
+    locks: &ConversationLocks,
+    incognito: &Incognito,
+    replies: &mut HashMap<u32, Instant>,
+    friend_number: u32,
+) {
+    let reply = match store.get_setting(MISSED_CALL_REPLY_SETTING) {
+        Ok(reply) => reply.unwrap_or_default(),
+        Err(e) => {
+            error!("Failed to load missed call message: {e}");
+            return;
         }
+    };
+    if reply.trim().is_empty() {
+        return;
+    }
+    if replies.get(&friend_number).is_some_and(|sent| sent.elapsed() < DND_REPLY_INTERVAL) {
+        return;
+    }
+    if let Err(e) = send_auto_reply(tox, store, app_handle, locks, incognito, friend_number, &reply) {
+        warn!("Failed to send missed call message to friend {friend_number}: {e}");
+        return;
+    }
+    replies.insert(friend_number, Instant::now());
+}
 
-        // Check if we have any active video calls
-        let has_video_call = if let Ok(mgr) = av_manager.lock() {
-            let calls = mgr.get_all_calls();
-            if !calls.is_empty() && !video_active {
-                // Log call states when checking for video (use info! to be visible)
-                for c in &calls {
-                    info!(
-                        "VIDEO CHECK - friend {}: state={:?}, has_video={}, is_video_muted={}",
-                        c.friend_number, c.state, c.has_video, c.is_video_muted
-                    );
-                }
-            }
-            calls.iter().any(|c| c.state == CallStatus::InProgress && c.has_video && !c.is_video_muted)
-        } else {
-            false
-        };
+/// Profile setting holding the away auto-reply configuration as JSON
+pub const AUTO_REPLY_SETTING: &str = "auto_reply";
+
+/// Reply sent to friends who message us while we're Away or Busy
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct AutoReplySettings {
+    pub enabled: bool,
+    pub message: String,
+    /// A friend gets the reply at most once per this many hours
+    pub interval_hours: u32,
+}
 
-        // Start video capture when a video call becomes active (and hasn't already failed)
-        if has_video_call && !video_active && !video_capture_failed {
-            // Check if screen sharing is active
-            let (is_screen_sharing, screen_share_id) = {
-                let state = app_handle.state::<AppState>();
-                let sharing = state.is_screen_sharing.try_lock().ok().map(|g| *g).unwrap_or(false);
-                let screen_id = state.screen_share_id.try_lock().ok().and_then(|g| *g);
-                (sharing, screen_id)
-            };
+impl Default for AutoReplySettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            message: String::new(),
+            interval_hours: 4,
+        }
+    }
+}
 
-            if is_screen_sharing {
-                // Start screen capture
-                info!("Starting screen capture for active video call (screen_id: {:?})", screen_share_id);
-                match ScreenCapture::start(screen_share_id, video_tx.clone(), video_error_tx.clone()) {
-                    Ok(capture) => {
-                        screen_capture = Some(capture);
-                        video_active = true;
-                        info!("Screen capture started successfully");
-                    }
-                    Err(e) => {
-                        error!("Failed to start screen capture: {e}");
-                        video_capture_failed = true;
-                        let error_event = ToxAvEvent::VideoError {
-                            error: e.to_string(),
-                        };
-                        if let Err(emit_err) = app_handle.emit("toxav://local-video", &error_event) {
-                            error!("Failed to emit video error event: {emit_err}");
-                        }
-                    }
-                }
-            } else {
-                // Start camera capture
-                let selected_camera_index = {
-                    let state = app_handle.state::<AppState>();
-                    state.selected_camera_index.try_lock().ok().and_then(|guard| *guard)
-                };
-                info!("Starting video capture for active video call (device index: {:?})", selected_camera_index);
-                match VideoCapture::start_with_device(selected_camera_index, video_tx.clone(), video_error_tx.clone()) {
-                    Ok(capture) => {
-                        video_capture = Some(capture);
-                        video_active = true;
-                        info!("Video capture started successfully");
-                    }
-                    Err(e) => {
-                        error!("Failed to start video capture: {e}");
-                        video_capture_failed = true;
-                        let error_event = ToxAvEvent::VideoError {
-                            error: e.to_string(),
-                        };
-                        if let Err(emit_err) = app_handle.emit("toxav://local-video", &error_event) {
-                            error!("Failed to emit video error event: {emit_err}");
-                        }
-                    }
-                }
-            }
+impl AutoReplySettings {
+    pub fn load(store: &MessageStore) -> Result<Self, String> {
+        match store.get_setting(AUTO_REPLY_SETTING)? {
+            Some(json) => serde_json::from_str(&json).map_err(|e| format!("Failed to parse auto-reply settings: {e}")),
+            None => Ok(Self::default()),
         }
+    }
 
-        // Check if screen sharing state changed (to switch between camera and screen)
-        if has_video_call && video_active {
-            let (is_screen_sharing_now, _) = {
-                let state = app_handle.state::<AppState>();
-                let sharing = state.is_screen_sharing.try_lock().ok().map(|g| *g).unwrap_or(false);
-                let screen_id = state.screen_share_id.try_lock().ok().and_then(|g| *g);
-                (sharing, screen_id)
-            };
+    pub fn save(&self, store: &MessageStore) -> Result<(), String> {
+        let json = serde_json::to_string(self).map_err(|e| format!("Failed to serialize auto-reply settings: {e}"))?;
+        store.set_setting(AUTO_REPLY_SETTING, &json)
+    }
+}
 
-            // Detect state change: screen_capture is Some means we're screen sharing, None means camera
-            let currently_screen_sharing = screen_capture.is_some();
-            if is_screen_sharing_now != currently_screen_sharing {
-                info!(
-                    "Screen sharing state changed: {} -> {}",
-                    currently_screen_sharing, is_screen_sharing_now
-                );
-                // Stop current capture
-                video_capture = None;
-                screen_capture = None;
-                video_active = false;
-                // Will restart with new source on next iteration
-            }
+/// Send the away auto-reply to a friend who messaged us, if we're Away or Busy
+/// and they haven't had it within the configured interval
+fn reply_while_away(
+    tox: &ToxInstance,
+    store: &MessageStore,
+    app_handle: &AppHandle,
+    locks: &ConversationLocks,
+    incognito: &Incognito,
+    friend_number: u32,
+) {
+    if tox.self_status() == UserStatus::None {
+        return;
+    }
+    let settings = match AutoReplySettings::load(store) {
+        Ok(settings) => settings,
+        Err(e) => {
+            error!("{e}");
+            return;
         }
+    };
+    if !settings.enabled || settings.message.trim().is_empty() {
+        return;
+    }
 
-        // Check for video capture errors (from capture thread)
-        while let Ok(err) = video_error_rx.try_recv() {
-            error!("Video capture thread error: {}", err.message);
-            video_capture_failed = true;
-            // Notify frontend about the video capture error
-            let error_event = ToxAvEvent::VideoError {
-                error: err.message,
-            };
-            if let Err(emit_err) = app_handle.emit("toxav://local-video", &error_event) {
-                error!("Failed to emit video error event: {emit_err}");
+    match store.claim_auto_reply(friend_number, settings.interval_hours.max(1)) {
+        Ok(true) => {
+            if let Err(e) = send_auto_reply(tox, store, app_handle, locks, incognito, friend_number, &settings.message) {
+                warn!("Failed to send auto-reply to friend {friend_number}: {e}");
             }
-            // Stop video/screen capture since it failed
-            video_capture = None;
-            screen_capture = None;
-            video_active = false;
         }
+        Ok(false) => {}
+        Err(e) => error!("{e}"),
+    }
+}
 
-        // Stop video capture when no video calls are active
-        if !has_video_call && video_active {
-            info!("Stopping video/screen capture - no active video calls");
-            video_capture = None;
-            screen_capture = None;
-            video_active = false;
-        }
+/// Send a text message to a friend, encrypted first if the conversation is
+/// locked. Returns the Tox message ID of the last part.
+fn send_friend_text(
+    tox: &ToxInstance,
+    locks: &ConversationLocks,
+    friend_number: u32,
+    message: &str,
+) -> Result<u32, ToxcordError> {
+    let parts = locks.seal_for_sending(friend_number, message, text_id())?;
+    let mut message_id = 0;
+    for part in parts {
+        message_id = tox
+            .friend_send_message(friend_number, MessageType::Normal, &part)?;
+    }
+    Ok(message_id)
+}
 
-        // Reset video_capture_failed when video call ends so it can retry on next call
-        if !has_video_call && video_capture_failed {
-            video_capture_failed = false;
-        }
+/// Send a text to a group, signed and split into tagged parts if it's long
+fn send_group_text(tox: &ToxInstance, group_number: u32, message: &str) -> Result<u32, ToxcordError> {
+    let message = sign_group_text(tox, group_number, message)?;
+    let mut message_id = 0;
+    for part in split_tagged(&message, TOX_MAX_MESSAGE_LENGTH, text_id())? {
+        message_id = tox
+            .group_send_message(group_number, MessageType::Normal, &part)?;
+    }
+    Ok(message_id)
+}
 
-        // Send captured audio frames to all active calls
-        if let Some(ref av) = toxav {
-            let mut frame_count = 0;
-            while let Ok(pcm) = audio_rx.try_recv() {
-                frame_count += 1;
-                // Get list of friends we're in active calls with
-                let active_friends: Vec<u32> = if let Ok(mgr) = av_manager.lock() {
-                    mgr.get_all_calls()
-                        .iter()
-                        .filter(|c| c.state == CallStatus::InProgress && !c.is_audio_muted)
-                        .map(|c| c.friend_number)
-                        .collect()
-                } else {
-                    vec![]
-                };
+/// Send an automatic text reply to a friend, persist it and let the frontend know
+fn send_auto_reply(
+    tox: &ToxInstance,
+    store: &MessageStore,
+    app_handle: &AppHandle,
+    locks: &ConversationLocks,
+    incognito: &Incognito,
+    friend_number: u32,
+    message: &str,
+) -> Result<(), String> {
+    // Nothing is sent that couldn't be stored safely
+    let content = locks.content_to_store(friend_number, message)?;
+    send_friend_text(tox, locks, friend_number, message)?;
+
+    let id = uuid::Uuid::new_v4().to_string();
+    let timestamp = chrono::Utc::now().to_rfc3339();
+    let incognito = incognito.is_active(friend_number);
+    let mut expires_at = None;
+    if !incognito {
+        expires_at = store.disappearing_expiry(friend_number)?;
+        store.insert_direct_message(&crate::db::message_store::DirectMessageRecord {
+            id: id.clone(),
+            friend_number: friend_number as i64,
+            sender: "self".to_string(),
+            content,
+            message_type: "normal".to_string(),
+            timestamp: timestamp.clone(),
+            is_outgoing: true,
+            delivered: false,
+            read: false,
+            expires_at,
+        })?;
+    }
 
-                if active_friends.is_empty() && frame_count == 1 {
-                    debug!("Captured audio but no active friends to send to");
-                }
+    let event = ToxEvent::AutoReply {
+        friend_number,
+        id,
+        message: message.to_string(),
+        timestamp,
+        expires_at,
+        incognito,
+    };
+    if let Err(e) = app_handle.emit("tox://event", &event) {
+        error!("Failed to emit auto-reply event: {e}");
+    }
+    Ok(())
+}
 
-                // Send audio to each active call
-                for friend_number in active_friends {
-                    let frame = AudioFrame {
-                        pcm: pcm.clone(),
-                        sample_count: pcm.len(),
-                        channels: 1,
-                        sampling_rate: 48000,
-                    };
-                    match av.audio_send_frame(friend_number, &frame) {
-                        Ok(()) => {
-                            debug!("Sent {} samples to friend {}", pcm.len(), friend_number);
-                        }
-                        Err(e) => {
-                            debug!("Failed to send audio frame to friend {}: {e}", friend_number);
-                        }
-                    }
-                }
-            }
-        }
+fn send_scheduled_to_friend(
+    tox: &ToxInstance,
+    store: &MessageStore,
+    locks: &ConversationLocks,
+    scheduled: &crate::db::message_store::ScheduledMessageRecord,
+    msg_id: &str,
+    timestamp: &str,
+) -> Result<&'static str, String> {
+    let friend_number: u32 = scheduled.target_id.parse().map_err(|_| "Invalid friend number".to_string())?;
+    let content = locks.content_to_store(friend_number, &scheduled.content)?;
+
+    let delivered = send_friend_text(tox, locks, friend_number, &scheduled.content).is_ok();
+    if !delivered {
+        store.queue_offline_message("friend", &scheduled.target_id, "text", &scheduled.content)?;
+    }
 
-        // Send captured video frames to all active video calls
-        if let Some(ref av) = toxav {
-            let mut video_frame_count = 0;
-            while let Ok(frame) = video_rx.try_recv() {
-                video_frame_count += 1;
-                if video_frame_count <= 3 {
-                    info!("Video frame {}: {}x{}, Y={} U={} V={} bytes",
-                           video_frame_count, frame.width, frame.height,
-                           frame.y.len(), frame.u.len(), frame.v.len());
-                }
+    store.insert_direct_message(&crate::db::message_store::DirectMessageRecord {
+        id: msg_id.to_string(),
+        friend_number: friend_number as i64,
+        sender: "self".to_string(),
+        content,
+        message_type: "normal".to_string(),
+        timestamp: timestamp.to_string(),
+        is_outgoing: true,
+        delivered,
+        read: false,
+        expires_at: store.disappearing_expiry(friend_number)?,
+    })?;
+
+    Ok(if delivered { "sent" } else { "queued" })
+}
 
-                // Get list of friends we're in active video calls with
-                let active_video_friends: Vec<u32> = if let Ok(mgr) = av_manager.lock() {
-                    mgr.get_all_calls()
-                        .iter()
-                        .filter(|c| c.state == CallStatus::InProgress && c.has_video && !c.is_video_muted)
-                        .map(|c| c.friend_number)
-                        .collect()
-                } else {
-                    vec![]
-                };
+/// Returns the status and the message's ID
+fn send_scheduled_to_channel(
+    tox: &ToxInstance,
+    store: &MessageStore,
+    scheduled: &crate::db::message_store::ScheduledMessageRecord,
+    timestamp: &str,
+) -> Result<(&'static str, String), String> {
+    let guild_id = scheduled.guild_id.as_deref().ok_or("No guild for channel message")?;
+    let guild = store.get_guild(guild_id)?.ok_or("Guild not found")?;
+    let group_number = guild.metadata_group_number.ok_or("Guild has no group number")? as u32;
+
+    let sender_public_key: String = tox
+        .group_self_get_public_key(group_number)
+        .map(|pk| pk.iter().map(|b| format!("{b:02X}")).collect())
+        .unwrap_or_default();
+    let (msg_id, tagged) = envelope::seal(&sender_public_key, chrono::Utc::now().timestamp_millis(), &scheduled.content);
+
+    // Same routing prefixes as GuildManager
+    let prefixed_content = if guild.guild_type == "dm_group" {
+        format!("[DM]{tagged}")
+    } else {
+        let channel_name = store
+            .get_channels(guild_id)?
+            .into_iter()
+            .find(|c| c.id == scheduled.target_id)
+            .map(|c| c.name)
+            .ok_or("Channel not found")?;
+        format!("[CH:{channel_name}]{tagged}")
+    };
 
-                // Send video to each active video call
-                for friend_number in &active_video_friends {
-                    let tox_frame = VideoFrame::new(
-                        frame.y.clone(),
-                        frame.u.clone(),
-                        frame.v.clone(),
-                        frame.width,
-                        frame.height,
-                    );
-                    if let Err(e) = tox_frame.validate() {
-                        debug!("Invalid video frame: {e}");
-                        continue;
-                    }
-                    if let Err(e) = av.video_send_frame(*friend_number, &tox_frame) {
-                        debug!("Failed to send video frame to friend {}: {e}", friend_number);
-                    }
-                }
+    send_group_text(tox, group_number, &prefixed_content)?;
+
+    store.insert_channel_message(&crate::db::message_store::ChannelMessageRecord {
+        id: msg_id.clone(),
+        channel_id: scheduled.target_id.clone(),
+        sender_public_key,
+        sender_name: tox.self_name(),
+        content: scheduled.content.clone(),
+        message_type: "normal".to_string(),
+        timestamp: timestamp.to_string(),
+        mentions_me: false,
+        notify: false,
+        filtered: false,
+        backfilled: false,
+        sender_verified: None,
+        signer_public_key: None,
+        signature: None,
+    })?;
+
+    Ok(("sent", msg_id))
+}
 
-                // Emit local preview to frontend (combine YUV into single buffer)
-                let mut data = Vec::with_capacity(frame.y.len() + frame.u.len() + frame.v.len());
-                data.extend_from_slice(&frame.y);
-                data.extend_from_slice(&frame.u);
-                data.extend_from_slice(&frame.v);
+/// Offer interrupted outgoing transfers to a friend again under their original
+/// file id, so the friend can seek past what it already has
+fn reoffer_file_transfers(
+    tox: &ToxInstance,
+    store: &MessageStore,
+    file_manager: &std::sync::Mutex<FileManager>,
+    friend_number: u32,
+) {
+    let transfers = match store.get_resumable_transfers(friend_number, "outgoing") {
+        Ok(t) => t,
+        Err(e) => {
+            error!("Failed to load resumable transfers: {e}");
+            return;
+        }
+    };
 
-                let event = ToxAvEvent::VideoFrame {
-                    friend_number: 0, // 0 indicates local preview
-                    width: frame.width,
-                    height: frame.height,
-                    data,
-                };
-                if let Err(e) = app_handle.emit("toxav://local-video", &event) {
-                    debug!("Failed to emit local video frame: {e}");
-                }
+    for transfer in transfers {
+        let (Some(path), Some(file_id)) = (
+            transfer.file_path.as_deref().map(PathBuf::from),
+            transfer.file_id.as_deref().and_then(file_manager::file_id_from_hex),
+        ) else {
+            continue;
+        };
+        if !path.exists() {
+            warn!("Can't re-offer {}: {} is gone", transfer.filename, path.display());
+            if let Err(e) = store.update_file_transfer_status(&transfer.id, "failed", transfer.bytes_transferred) {
+                error!("Failed to persist transfer status: {e}");
             }
+            continue;
         }
 
-        // Process offline queue flush requests
-        while let Ok(friend_number) = offline_flush_rx.try_recv() {
-            let queued = store.get_offline_messages_for("friend", &friend_number.to_string());
-            if let Ok(messages) = queued {
-                for (queue_id, _msg_type, content) in messages {
-                    let chunks = toxcord_protocol::codec::split_friend_message(&content);
-                    let mut all_sent = true;
-                    for chunk in &chunks {
-                        if tox.friend_send_message(friend_number, MessageType::Normal, chunk).is_err() {
-                            all_sent = false;
-                            break;
-                        }
-                    }
-                    if all_sent {
-                        if let Err(e) = store.remove_offline_message(queue_id) {
-                            error!("Failed to remove offline message {queue_id}: {e}");
-                        } else {
-                            info!("Flushed offline message {queue_id} to friend {friend_number}");
-                        }
-                    }
+        let result = tox
+            .file_send(friend_number, FileKind::Data, transfer.file_size as u64, Some(&file_id), &transfer.filename)
+            .map_err(|e| e.to_string())
+            .and_then(|file_number| {
+                file_manager
+                    .lock()
+                    .map_err(|e| e.to_string())?
+                    .start_outgoing(
+                        friend_number,
+                        file_number,
+                        &transfer.id,
+                        transfer.message_id.as_deref().unwrap_or_default(),
+                        &transfer.filename,
+                        &path,
+                    )
+                    .map(|_| file_number)
+            });
+
+        match result {
+            Ok(file_number) => {
+                info!("Re-offered {} to friend {friend_number}", transfer.filename);
+                if let Err(e) = store.resume_file_transfer(&transfer.id, file_number) {
+                    error!("Failed to persist transfer status: {e}");
                 }
             }
+            // Left as-is so the next reconnect tries again
+            Err(e) => warn!("Failed to re-offer {} to friend {friend_number}: {e}", transfer.filename),
         }
-
-        // Sleep for the recommended interval
-        let interval = tox.iteration_interval();
-        std::thread::sleep(interval);
     }
 }
 
 /// Save the Tox profile to disk (encrypted)
 fn save_profile(tox: &ToxInstance, password: &str, path: &PathBuf) {
-    let savedata = tox.savedata();
-
-    let data = if !password.is_empty() {
-        match encrypt_savedata(&savedata, password) {
-            Ok(encrypted) => encrypted,
-            Err(e) => {
-                error!("Failed to encrypt profile: {e}");
-                savedata
-            }
-        }
-    } else {
-        savedata
-    };
-
-    if let Err(e) = std::fs::write(path, &data) {
-        error!("Failed to save profile to {}: {e}", path.display());
-    } else {
-        debug!("Profile saved to {}", path.display());
+    match write_profile(tox, password, path) {
+        Ok(()) => debug!("Profile saved to {}", path.display()),
+        Err(e) => error!("{e}"),
     }
 }
 
-/// Get the profiles directory
-fn get_profiles_dir() -> PathBuf {
-    dirs::data_dir()
-        .unwrap_or_else(|| PathBuf::from("."))
-        .join("toxcord")
-        .join("profiles")
+fn write_profile(tox: &ToxInstance, password: &str, path: &PathBuf) -> Result<(), String> {
+    profile_file::write_profile(path, &tox.savedata(), password)
 }
@@ -0,0 +1,57 @@
+//! Usage statistics
+//!
+//! Messages sent and received, call time and file data per conversation per
+//! day, counted in the profile database as things happen (see migration v43).
+//! They're only ever read back here, for the "Your year on Toxcord" panel, and
+//! are never sent anywhere.
+
+use chrono::Datelike;
+use serde::Serialize;
+
+use crate::db::message_store::{ConversationUsageRecord, UsageDayRecord};
+use crate::db::MessageStore;
+
+/// Conversations listed as most active
+pub const TOP_CONVERSATIONS: i64 = 5;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UsageStats {
+    pub year: i32,
+    /// Days with any activity, oldest first
+    pub days: Vec<UsageDayRecord>,
+    /// Sums over the year; `day` is empty
+    pub totals: UsageDayRecord,
+    /// The day with the most messages
+    pub busiest_day: Option<String>,
+    pub top_conversations: Vec<ConversationUsageRecord>,
+}
+
+/// Statistics for a calendar year, this year if None
+pub fn for_year(store: &MessageStore, year: Option<i32>) -> Result<UsageStats, String> {
+    let year = year.unwrap_or_else(|| chrono::Local::now().year());
+    let (from, to) = (format!("{year:04}-01-01"), format!("{year:04}-12-31"));
+    let days = store.get_usage_days(&from, &to)?;
+    let top_conversations = store.get_top_conversations(&from, &to, TOP_CONVERSATIONS)?;
+
+    let totals = days.iter().fold(UsageDayRecord::default(), |mut totals, day| {
+        totals.messages_sent += day.messages_sent;
+        totals.messages_received += day.messages_received;
+        totals.call_seconds += day.call_seconds;
+        totals.bytes_sent += day.bytes_sent;
+        totals.bytes_received += day.bytes_received;
+        totals
+    });
+    let busiest_day = days
+        .iter()
+        .filter(|day| day.messages_sent + day.messages_received > 0)
+        .max_by_key(|day| day.messages_sent + day.messages_received)
+        .map(|day| day.day.clone());
+
+    Ok(UsageStats {
+        year,
+        days,
+        totals,
+        busiest_day,
+        top_conversations,
+    })
+}
@@ -0,0 +1,78 @@
+//! Picture-in-picture call window.
+//!
+//! A small always-on-top window showing the friend in a call, so they stay
+//! visible while the main window is hidden or covered. It loads the same
+//! frontend, which draws the friend's video from the `video://` protocol and
+//! follows the call from the `toxav://event` stream every window receives.
+//! Its position is saved to the app settings whenever it closes.
+
+use tauri::{AppHandle, Manager, WebviewUrl, WebviewWindowBuilder, Window};
+use tracing::{info, warn};
+
+use crate::settings::WindowPosition;
+use crate::AppState;
+
+pub const PIP_WINDOW: &str = "call-pip";
+
+/// Initial size in logical pixels
+const PIP_SIZE: (f64, f64) = (320.0, 180.0);
+
+pub fn is_open(app: &AppHandle) -> bool {
+    app.get_webview_window(PIP_WINDOW).is_some()
+}
+
+/// Open the window for a friend's call, at its saved position if there is one
+pub fn open(app: &AppHandle, friend_number: u32, position: Option<WindowPosition>) -> Result<(), String> {
+    // The window is told which call to show before the frontend loads
+    let script = format!("window.__TOXCORD_PIP_FRIEND__ = {friend_number};");
+    let mut builder = WebviewWindowBuilder::new(app, PIP_WINDOW, WebviewUrl::App("index.html".into()))
+        .title("Toxcord Call")
+        .inner_size(PIP_SIZE.0, PIP_SIZE.1)
+        .min_inner_size(PIP_SIZE.0 / 2.0, PIP_SIZE.1 / 2.0)
+        .always_on_top(true)
+        .skip_taskbar(true)
+        .initialization_script(&script);
+    if let Some(position) = position {
+        builder = builder.position(position.x as f64, position.y as f64);
+    }
+    builder
+        .build()
+        .map_err(|e| format!("Failed to open call window: {e}"))?;
+    info!("Opened picture-in-picture window for friend {friend_number}");
+    Ok(())
+}
+
+/// Close the window if it's open. Calls are one at a time, so this is also
+/// how it goes away when a call ends.
+pub fn close(app: &AppHandle) {
+    if let Some(window) = app.get_webview_window(PIP_WINDOW) {
+        if let Err(e) = window.close() {
+            warn!("Failed to close call window: {e}");
+        }
+    }
+}
+
+/// Save the window's position as it closes. Called from the window event
+/// handler on `CloseRequested`.
+pub fn remember_position(window: &Window) {
+    let position = match (window.outer_position(), window.scale_factor()) {
+        (Ok(position), Ok(scale)) => position.to_logical::<f64>(scale),
+        (Err(e), _) | (_, Err(e)) => {
+            warn!("Failed to read call window position: {e}");
+            return;
+        }
+    };
+    let position = WindowPosition {
+        x: position.x.round() as i32,
+        y: position.y.round() as i32,
+    };
+    let app = window.app_handle().clone();
+    tauri::async_runtime::spawn(async move {
+        let state = app.state::<AppState>();
+        let mut settings = state.settings.lock().await;
+        settings.pip_position = Some(position);
+        if let Err(e) = settings.save() {
+            warn!("{e}");
+        }
+    });
+}
@@ -0,0 +1,140 @@
+//! App-wide settings.
+//!
+//! Stored as JSON in the data directory, next to `profiles/`. These are
+//! device settings that apply before any profile is unlocked (shortcuts,
+//! launch behaviour), so they can't live in the encrypted profile database.
+
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::accessibility::AccessibilitySettings;
+use crate::db::message_store::CallPreferences;
+use crate::managers::power_saving::PowerSavingMode;
+use crate::managers::tox_manager::NetworkMode;
+use crate::updater::UpdateSettings;
+use crate::video::background::BackgroundMode;
+
+/// Global shortcut bindings, as accelerator strings (e.g. "CmdOrControl+Shift+M").
+/// `None` leaves the action unbound.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ShortcutBindings {
+    pub toggle_mute: Option<String>,
+    pub toggle_deafen: Option<String>,
+    /// Held to transmit; the mic is muted again on release
+    pub push_to_talk: Option<String>,
+    /// Answers a ringing call, or hangs up the current one
+    pub answer_or_hangup: Option<String>,
+}
+
+impl Default for ShortcutBindings {
+    fn default() -> Self {
+        Self {
+            toggle_mute: Some("CmdOrControl+Shift+M".to_string()),
+            toggle_deafen: Some("CmdOrControl+Shift+D".to_string()),
+            push_to_talk: None,
+            answer_or_hangup: Some("CmdOrControl+Shift+Enter".to_string()),
+        }
+    }
+}
+
+/// Local bot API server (off by default)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BotApiSettings {
+    pub enabled: bool,
+    pub port: u16,
+    /// Generated the first time the API is enabled
+    pub token: String,
+}
+
+impl Default for BotApiSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: crate::managers::bot_api::DEFAULT_PORT,
+            token: String::new(),
+        }
+    }
+}
+
+/// Video shown in calls
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VideoSettings {
+    /// Incoming video is scaled down to fit within this before it's drawn
+    pub max_output_width: u16,
+    pub max_output_height: u16,
+    /// Effect applied to our camera video
+    pub background_mode: BackgroundMode,
+}
+
+impl Default for VideoSettings {
+    fn default() -> Self {
+        let (max_output_width, max_output_height) = crate::video::frames::DEFAULT_MAX_OUTPUT;
+        Self {
+            max_output_width,
+            max_output_height,
+            background_mode: BackgroundMode::Off,
+        }
+    }
+}
+
+/// A window's top-left corner, in logical pixels
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct WindowPosition {
+    pub x: i32,
+    pub y: i32,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppSettings {
+    pub shortcuts: ShortcutBindings,
+    pub bot_api: BotApiSettings,
+    pub video: VideoSettings,
+    /// Call quality for friends without their own preferences
+    pub call_quality: CallPreferences,
+    /// Where the picture-in-picture call window was last closed
+    pub pip_position: Option<WindowPosition>,
+    /// Normal, LAN only or offline; applies to every profile
+    pub network_mode: NetworkMode,
+    /// When the tox thread slows down to save battery
+    pub power_saving: PowerSavingMode,
+    pub accessibility: AccessibilitySettings,
+    pub updates: UpdateSettings,
+}
+
+impl AppSettings {
+    /// Load settings from disk, falling back to defaults if missing or unreadable
+    pub fn load() -> Self {
+        let path = settings_path();
+        let Ok(data) = std::fs::read_to_string(&path) else {
+            return Self::default();
+        };
+        serde_json::from_str(&data).unwrap_or_else(|e| {
+            warn!("Failed to parse {}: {e} - using defaults", path.display());
+            Self::default()
+        })
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let path = settings_path();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create settings directory: {e}"))?;
+        }
+        let data = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize settings: {e}"))?;
+        std::fs::write(&path, data).map_err(|e| format!("Failed to write settings: {e}"))
+    }
+}
+
+fn settings_path() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("toxcord")
+        .join("settings.json")
+}
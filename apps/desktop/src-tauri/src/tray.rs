@@ -0,0 +1,233 @@
+//! System tray integration.
+//!
+//! Keeps Toxcord connected when the main window is closed (the window is
+//! hidden instead), and exposes status switching, microphone mute and quit
+//! from the tray menu. The tray icon carries an unread badge driven by the
+//! unread counts in the message store.
+
+use tauri::image::Image;
+use tauri::menu::{CheckMenuItem, CheckMenuItemBuilder, MenuBuilder, MenuEvent, SubmenuBuilder};
+use tauri::tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent};
+use tauri::{AppHandle, Manager, Wry};
+use tracing::{error, warn};
+
+use toxcord_tox::types::UserStatus;
+
+use crate::db::MessageStore;
+use crate::AppState;
+
+/// Launch flag that keeps the main window hidden in the tray on startup.
+/// Passed by the OS autostart entry.
+pub const START_MINIMIZED_FLAG: &str = "--minimized";
+
+const TRAY_ID: &str = "toxcord-tray";
+const MAIN_WINDOW: &str = "main";
+
+/// Tray handles that need updating after setup
+struct TrayState {
+    mute_item: CheckMenuItem<Wry>,
+    /// Icon without badge, used as the base for redrawing
+    base_icon: Option<Image<'static>>,
+}
+
+/// Build the tray icon and its menu. Called once from the Tauri setup hook.
+pub fn setup_tray(app: &AppHandle) -> tauri::Result<()> {
+    let status_menu = SubmenuBuilder::new(app, "Status")
+        .text("status_online", "Online")
+        .text("status_away", "Away")
+        .text("status_busy", "Busy")
+        .build()?;
+
+    let mute_item = CheckMenuItemBuilder::with_id("mute_mic", "Mute Microphone")
+        .checked(false)
+        .build(app)?;
+
+    let menu = MenuBuilder::new(app)
+        .text("show", "Show Toxcord")
+        .separator()
+        .item(&status_menu)
+        .item(&mute_item)
+        .separator()
+        .text("quit", "Quit")
+        .build()?;
+
+    let base_icon = app.default_window_icon().cloned().map(|i| i.to_owned());
+
+    let mut builder = TrayIconBuilder::with_id(TRAY_ID)
+        .menu(&menu)
+        .tooltip("Toxcord")
+        .show_menu_on_left_click(false)
+        .on_menu_event(handle_menu_event)
+        .on_tray_icon_event(|tray, event| {
+            if let TrayIconEvent::Click {
+                button: MouseButton::Left,
+                button_state: MouseButtonState::Up,
+                ..
+            } = event
+            {
+                show_main_window(tray.app_handle());
+            }
+        });
+    if let Some(ref icon) = base_icon {
+        builder = builder.icon(icon.clone());
+    }
+    builder.build(app)?;
+
+    app.manage(TrayState {
+        mute_item,
+        base_icon,
+    });
+
+    Ok(())
+}
+
+/// Bring the main window back from the tray
+pub fn show_main_window(app: &AppHandle) {
+    if let Some(window) = app.get_webview_window(MAIN_WINDOW) {
+        let _ = window.show();
+        let _ = window.unminimize();
+        let _ = window.set_focus();
+    }
+}
+
+fn handle_menu_event(app: &AppHandle, event: MenuEvent) {
+    match event.id().as_ref() {
+        "show" => show_main_window(app),
+        "status_online" => set_status(app, UserStatus::None),
+        "status_away" => set_status(app, UserStatus::Away),
+        "status_busy" => set_status(app, UserStatus::Busy),
+        "mute_mic" => {
+            // The check item has already toggled itself by the time we get here
+            let muted = app
+                .try_state::<TrayState>()
+                .and_then(|t| t.mute_item.is_checked().ok())
+                .unwrap_or(false);
+            set_global_mute(app, muted);
+        }
+        "quit" => quit(app),
+        _ => {}
+    }
+}
+
+fn set_status(app: &AppHandle, status: UserStatus) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let state = app.state::<AppState>();
+        let guard = state.tox_manager.lock().await;
+        let Some(manager) = guard.as_ref() else {
+            warn!("Tray status change ignored: not logged in");
+            return;
+        };
+        let mgr = manager.lock().await;
+        if let Err(e) = mgr.set_status(status).await {
+            error!("Failed to set status from tray: {e}");
+        }
+    });
+}
+
+fn set_global_mute(app: &AppHandle, muted: bool) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let state = app.state::<AppState>();
+        let guard = state.tox_manager.lock().await;
+        let Some(manager) = guard.as_ref() else {
+            // Nothing to mute yet, keep the menu in sync with reality
+            set_mute_checked(&app, false);
+            return;
+        };
+        let mgr = manager.lock().await;
+        if let Err(e) = mgr.set_global_mute(muted).await {
+            error!("Failed to set mute from tray: {e}");
+            set_mute_checked(&app, !muted);
+        }
+    });
+}
+
+/// Shut down the Tox thread cleanly (saving the profile) and exit
+fn quit(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let state = app.state::<AppState>();
+        let manager = state.tox_manager.lock().await.take();
+        if let Some(manager) = manager {
+            let mgr = manager.lock().await;
+            if let Err(e) = mgr.shutdown().await {
+                error!("Failed to shut down Tox on quit: {e}");
+            }
+        }
+        *state.message_store.lock().await = None;
+        app.exit(0);
+    });
+}
+
+/// Reflect the global mute state in the tray menu
+pub fn set_mute_checked(app: &AppHandle, muted: bool) {
+    if let Some(tray) = app.try_state::<TrayState>() {
+        if let Err(e) = tray.mute_item.set_checked(muted) {
+            error!("Failed to update tray mute item: {e}");
+        }
+    }
+}
+
+/// Recompute the unread DM and channel mention counts from the store and update the badge
+pub fn refresh_unread_badge(app: &AppHandle, store: &MessageStore) {
+    match (store.get_unread_counts(), store.get_unread_mention_counts()) {
+        (Ok(dms), Ok(mentions)) => {
+            let total: i64 = dms.iter().map(|(_, count)| count).sum::<i64>()
+                + mentions.iter().map(|(_, count)| count).sum::<i64>();
+            set_unread_count(app, total);
+        }
+        (Err(e), _) | (_, Err(e)) => error!("Failed to load unread counts for tray: {e}"),
+    }
+}
+
+/// Update the tray tooltip, title and icon badge for `count` unread messages
+pub fn set_unread_count(app: &AppHandle, count: i64) {
+    let Some(tray) = app.tray_by_id(TRAY_ID) else {
+        return;
+    };
+    let tray_state = app.try_state::<TrayState>();
+
+    let tooltip = if count > 0 {
+        format!("Toxcord - {count} unread")
+    } else {
+        "Toxcord".to_string()
+    };
+    let _ = tray.set_tooltip(Some(tooltip));
+
+    // Title is shown next to the icon on macOS and some Linux panels
+    let title = if count > 0 { Some(count.to_string()) } else { None };
+    let _ = tray.set_title(title);
+
+    if let Some(base) = tray_state.as_ref().and_then(|t| t.base_icon.as_ref()) {
+        let icon = if count > 0 {
+            let rgba = draw_badge(base.rgba(), base.width(), base.height());
+            Image::new_owned(rgba, base.width(), base.height())
+        } else {
+            base.clone()
+        };
+        if let Err(e) = tray.set_icon(Some(icon)) {
+            error!("Failed to update tray icon: {e}");
+        }
+    }
+}
+
+/// Draw a red dot in the top-right corner of an RGBA icon
+fn draw_badge(rgba: &[u8], width: u32, height: u32) -> Vec<u8> {
+    let mut out = rgba.to_vec();
+    let radius = (width.min(height) as f32) * 0.22;
+    let cx = width as f32 - radius - 1.0;
+    let cy = radius + 1.0;
+
+    for y in 0..height {
+        for x in 0..width {
+            let dx = x as f32 + 0.5 - cx;
+            let dy = y as f32 + 0.5 - cy;
+            if dx * dx + dy * dy <= radius * radius {
+                let i = ((y * width + x) * 4) as usize;
+                out[i..i + 4].copy_from_slice(&[0xED, 0x42, 0x45, 0xFF]);
+            }
+        }
+    }
+    out
+}
@@ -0,0 +1,193 @@
+//! Update checks and self-update.
+//!
+//! A little after launch and then every few hours, the release manifest
+//! configured under `plugins.updater` in `tauri.conf.json` is fetched. If it
+//! names a newer version, it's kept as the available update and announced on
+//! `update://available` with its changelog. Installing downloads the package
+//! for this platform, checks it against the manifest's minisign signature with
+//! the public key baked in at build time (`TOXCORD_UPDATER_PUBKEY`), installs it
+//! and restarts. Builds without a key can still tell the user about releases,
+//! but won't install them.
+//!
+//! Requests go through the proxy Tox uses, if one is configured, and nothing
+//! is fetched in LAN only or offline mode.
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, Url};
+use tauri_plugin_updater::{Update, UpdaterExt};
+use tracing::{info, warn};
+
+use crate::managers::tox_manager::{NetworkMode, ProxyConfig};
+use crate::AppState;
+
+pub const UPDATE_AVAILABLE_EVENT: &str = "update://available";
+pub const UPDATE_PROGRESS_EVENT: &str = "update://progress";
+
+/// Public key release packages are signed with, set when building releases
+const UPDATER_PUBKEY: Option<&str> = option_env!("TOXCORD_UPDATER_PUBKEY");
+
+/// Wait after launch before the first check, so it doesn't compete with startup
+const FIRST_CHECK_DELAY: Duration = Duration::from_secs(60);
+const CHECK_INTERVAL: Duration = Duration::from_secs(6 * 60 * 60);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Update checks (on by default)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UpdateSettings {
+    /// Check for new releases in the background
+    pub enabled: bool,
+    /// A release the user chose not to be told about again
+    pub skipped_version: Option<String>,
+}
+
+impl Default for UpdateSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            skipped_version: None,
+        }
+    }
+}
+
+/// A release newer than the running version
+#[derive(Debug, Clone, Serialize)]
+pub struct UpdateInfo {
+    pub version: String,
+    pub current_version: String,
+    /// Changelog from the manifest
+    pub notes: Option<String>,
+    /// RFC 3339
+    pub date: Option<String>,
+    /// False if this build has no key to verify packages with
+    pub can_install: bool,
+}
+
+impl UpdateInfo {
+    fn from_update(update: &Update) -> Self {
+        Self {
+            version: update.version.clone(),
+            current_version: update.current_version.clone(),
+            notes: update.body.clone().filter(|notes| !notes.trim().is_empty()),
+            date: update.raw_json.get("pub_date").and_then(|d| d.as_str()).map(str::to_string),
+            can_install: can_install(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct UpdateProgress {
+    downloaded: u64,
+    /// None if the server didn't say
+    total: Option<u64>,
+}
+
+fn can_install() -> bool {
+    UPDATER_PUBKEY.is_some_and(|key| !key.trim().is_empty())
+}
+
+/// Check now and then in the background, while checks are enabled
+pub fn spawn_checker(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(FIRST_CHECK_DELAY).await;
+        loop {
+            let settings = app.state::<AppState>().settings.lock().await.updates.clone();
+            if settings.enabled {
+                match check(&app).await {
+                    Ok(Some(update)) if settings.skipped_version.as_deref() != Some(update.version.as_str()) => {
+                        announce(&app, &update);
+                    }
+                    Ok(_) => {}
+                    Err(e) => warn!("Update check failed: {e}"),
+                }
+            }
+            tokio::time::sleep(CHECK_INTERVAL).await;
+        }
+    });
+}
+
+/// Show the update banner
+pub fn announce(app: &AppHandle, update: &UpdateInfo) {
+    if let Err(e) = app.emit(UPDATE_AVAILABLE_EVENT, update) {
+        warn!("Failed to emit update event: {e}");
+    }
+}
+
+/// Fetch the release manifest and keep the update it offers, if newer
+pub async fn check(app: &AppHandle) -> Result<Option<UpdateInfo>, String> {
+    let state = app.state::<AppState>();
+    match state.settings.lock().await.network_mode {
+        NetworkMode::Normal => {}
+        NetworkMode::LanOnly => return Err("Updates can't be checked in LAN only mode".to_string()),
+        NetworkMode::Offline => return Err("Updates can't be checked while offline".to_string()),
+    }
+
+    let mut builder = app.updater_builder().timeout(REQUEST_TIMEOUT);
+    if let Some(key) = UPDATER_PUBKEY {
+        builder = builder.pubkey(key);
+    }
+    if let Some(proxy) = ProxyConfig::from_env().url() {
+        let proxy = Url::parse(&proxy).map_err(|e| format!("Invalid proxy {proxy}: {e}"))?;
+        builder = builder.proxy(proxy);
+    }
+    let updater = builder.build().map_err(|e| format!("Failed to set up the updater: {e}"))?;
+
+    let update = updater
+        .check()
+        .await
+        .map_err(|e| format!("Failed to fetch the release manifest: {e}"))?;
+    let info = update.as_ref().map(UpdateInfo::from_update);
+    match &info {
+        Some(info) => info!("Update available: {} -> {}", info.current_version, info.version),
+        None => info!("No update available"),
+    }
+    *state.available_update.lock().await = update;
+    Ok(info)
+}
+
+/// The update found by the last check, if any
+pub async fn available(app: &AppHandle) -> Option<UpdateInfo> {
+    let state = app.state::<AppState>();
+    let update = state.available_update.lock().await;
+    update.as_ref().map(UpdateInfo::from_update)
+}
+
+/// Download, verify and install the available update, then restart into it
+pub async fn install(app: &AppHandle) -> Result<(), String> {
+    if !can_install() {
+        return Err("This build can't install updates; download the new version from the releases page".to_string());
+    }
+    let update = app
+        .state::<AppState>()
+        .available_update
+        .lock()
+        .await
+        .clone()
+        .ok_or("No update available")?;
+
+    info!("Installing update {}", update.version);
+    let mut downloaded = 0u64;
+    update
+        .download_and_install(
+            |chunk, total| {
+                downloaded += chunk as u64;
+                let _ = app.emit(UPDATE_PROGRESS_EVENT, UpdateProgress { downloaded, total });
+            },
+            || info!("Update downloaded"),
+        )
+        .await
+        .map_err(|e| format!("Failed to install the update: {e}"))?;
+
+    // Shut Tox down cleanly, saving the profile, as quitting from the tray does
+    let state = app.state::<AppState>();
+    let manager = state.tox_manager.lock().await.take();
+    if let Some(manager) = manager {
+        if let Err(e) = manager.lock().await.shutdown().await {
+            warn!("Failed to shut down Tox before restarting: {e}");
+        }
+    }
+    *state.message_store.lock().await = None;
+    app.restart()
+}
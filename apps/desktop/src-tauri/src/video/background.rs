@@ -0,0 +1,223 @@
+//! Background blur for outgoing camera video.
+//!
+//! There is no person segmentation: the blur keeps an ellipse around the
+//! centre of the picture, where a face usually is, and blurs the rest,
+//! feathering between the two. It's applied to the captured RGB frame before
+//! YUV conversion.
+//!
+//! The blur is computed on a downscaled copy of the frame. When a frame takes
+//! longer than its budget the copy is made smaller, and if even the smallest
+//! copy is too slow the effect is switched off for the rest of the capture, so
+//! a slow machine gets a sharp background rather than a stuttering call.
+
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+/// Downscale factors tried, from best looking to cheapest
+const SCALES: [usize; 3] = [4, 8, 16];
+
+/// Radius of the box blur on the downscaled copy, in its pixels
+const BLUR_RADIUS: usize = 3;
+
+/// Frames over budget in a row before moving to a cheaper scale
+const SLOW_FRAMES_BEFORE_DEGRADING: u32 = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackgroundMode {
+    #[default]
+    Off,
+    Blur,
+}
+
+/// The mode selected by the user, shared with running captures
+#[derive(Default)]
+pub struct BackgroundSetting(AtomicU8);
+
+impl BackgroundSetting {
+    pub fn new(mode: BackgroundMode) -> Self {
+        let setting = Self::default();
+        setting.set(mode);
+        setting
+    }
+
+    pub fn get(&self) -> BackgroundMode {
+        match self.0.load(Ordering::Relaxed) {
+            1 => BackgroundMode::Blur,
+            _ => BackgroundMode::Off,
+        }
+    }
+
+    pub fn set(&self, mode: BackgroundMode) {
+        self.0.store(mode as u8, Ordering::Relaxed);
+    }
+}
+
+/// Blur state for one capture
+pub struct BackgroundBlur {
+    /// Time one frame may spend on the effect
+    budget: Duration,
+    /// Index into `SCALES`
+    scale: usize,
+    slow_frames: u32,
+    /// Set once even the cheapest scale is over budget
+    disabled: bool,
+}
+
+impl BackgroundBlur {
+    pub fn new(budget: Duration) -> Self {
+        Self {
+            budget,
+            scale: 0,
+            slow_frames: 0,
+            disabled: false,
+        }
+    }
+
+    /// Apply the effect to an RGB24 frame, if the mode calls for it
+    pub fn process(&mut self, mode: BackgroundMode, rgb: &mut [u8], width: usize, height: usize) {
+        if mode == BackgroundMode::Off || self.disabled {
+            return;
+        }
+        let started = Instant::now();
+        blur_background(rgb, width, height, SCALES[self.scale]);
+        self.record(started.elapsed());
+    }
+
+    fn record(&mut self, elapsed: Duration) {
+        if elapsed <= self.budget {
+            self.slow_frames = 0;
+            return;
+        }
+        self.slow_frames += 1;
+        if self.slow_frames < SLOW_FRAMES_BEFORE_DEGRADING {
+            return;
+        }
+        self.slow_frames = 0;
+        if self.scale + 1 < SCALES.len() {
+            self.scale += 1;
+            info!("Background blur over budget ({elapsed:?}), downscaling by {}", SCALES[self.scale]);
+        } else {
+            self.disabled = true;
+            warn!("Background blur too slow on this machine ({elapsed:?} a frame), turning it off");
+        }
+    }
+}
+
+/// Blur everything outside the central ellipse of an RGB24 frame
+fn blur_background(rgb: &mut [u8], width: usize, height: usize, scale: usize) {
+    if width < scale * 2 || height < scale * 2 || rgb.len() < width * height * 3 {
+        return;
+    }
+    let (small_w, small_h) = (width / scale, height / scale);
+    let mut small = downscale(rgb, width, small_w, small_h, scale);
+    box_blur(&mut small, small_w, small_h, BLUR_RADIUS);
+
+    // The sharp region: a little above centre, where a head sits in frame
+    let (cx, cy) = (width as f32 / 2.0, height as f32 * 0.45);
+    let (rx, ry) = (width as f32 * 0.3, height as f32 * 0.5);
+    for y in 0..height {
+        let dy = (y as f32 - cy) / ry;
+        let sy = (y / scale).min(small_h - 1);
+        for x in 0..width {
+            let dx = (x as f32 - cx) / rx;
+            // 0 inside the ellipse, 1 beyond its feathered edge
+            let alpha = (((dx * dx + dy * dy).sqrt() - 1.0) / 0.25).clamp(0.0, 1.0);
+            if alpha == 0.0 {
+                continue;
+            }
+            let sx = (x / scale).min(small_w - 1);
+            let (idx, sidx) = ((y * width + x) * 3, (sy * small_w + sx) * 3);
+            for c in 0..3 {
+                let sharp = rgb[idx + c] as f32;
+                rgb[idx + c] = (sharp + (small[sidx + c] as f32 - sharp) * alpha) as u8;
+            }
+        }
+    }
+}
+
+/// Average `scale` x `scale` blocks
+fn downscale(rgb: &[u8], width: usize, small_w: usize, small_h: usize, scale: usize) -> Vec<u8> {
+    let mut small = vec![0u8; small_w * small_h * 3];
+    let area = (scale * scale) as u32;
+    for sy in 0..small_h {
+        for sx in 0..small_w {
+            let mut sum = [0u32; 3];
+            for y in sy * scale..(sy + 1) * scale {
+                let row = (y * width + sx * scale) * 3;
+                for px in rgb[row..row + scale * 3].chunks_exact(3) {
+                    sum[0] += px[0] as u32;
+                    sum[1] += px[1] as u32;
+                    sum[2] += px[2] as u32;
+                }
+            }
+            let idx = (sy * small_w + sx) * 3;
+            for c in 0..3 {
+                small[idx + c] = (sum[c] / area) as u8;
+            }
+        }
+    }
+    small
+}
+
+/// Separable box blur, clamping at the edges
+fn box_blur(rgb: &mut [u8], width: usize, height: usize, radius: usize) {
+    let mut tmp = vec![0u8; rgb.len()];
+    let pass = |src: &[u8], dst: &mut [u8], horizontal: bool| {
+        let (len, lines) = if horizontal { (width, height) } else { (height, width) };
+        let at = |line: usize, i: usize| if horizontal { (line * width + i) * 3 } else { (i * width + line) * 3 };
+        for line in 0..lines {
+            for i in 0..len {
+                let (lo, hi) = (i.saturating_sub(radius), (i + radius).min(len - 1));
+                let mut sum = [0u32; 3];
+                for j in lo..=hi {
+                    let idx = at(line, j);
+                    sum[0] += src[idx] as u32;
+                    sum[1] += src[idx + 1] as u32;
+                    sum[2] += src[idx + 2] as u32;
+                }
+                let count = (hi - lo + 1) as u32;
+                let idx = at(line, i);
+                for c in 0..3 {
+                    dst[idx + c] = (sum[c] / count) as u8;
+                }
+            }
+        }
+    };
+    pass(rgb, &mut tmp, true);
+    pass(&tmp, rgb, false);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blur_keeps_centre_sharp() {
+        let (width, height) = (64, 48);
+        // Alternating black and white columns
+        let mut rgb: Vec<u8> = (0..width * height)
+            .flat_map(|i| if i % 2 == 0 { [0u8; 3] } else { [255u8; 3] })
+            .collect();
+        let original = rgb.clone();
+
+        blur_background(&mut rgb, width, height, 4);
+
+        let centre = ((height / 2) * width + width / 2) * 3;
+        assert_eq!(rgb[centre..centre + 3], original[centre..centre + 3]);
+        // A corner is averaged towards grey
+        assert!(rgb[3] < 200 && rgb[3] > 60);
+    }
+
+    #[test]
+    fn test_degrades_when_over_budget() {
+        let mut blur = BackgroundBlur::new(Duration::from_millis(10));
+        for _ in 0..SLOW_FRAMES_BEFORE_DEGRADING * SCALES.len() as u32 {
+            blur.record(Duration::from_millis(20));
+        }
+        assert!(blur.disabled);
+    }
+}
@@ -11,7 +11,8 @@ use nokhwa::Camera;
 use tokio::sync::mpsc;
 use tracing::{debug, error, info, warn};
 
-use super::convert::rgb_to_yuv420;
+use super::background::{BackgroundBlur, BackgroundSetting};
+use super::convert::{rgb_to_yuv420, YuvPlanes};
 use super::{VideoDevice, VideoError, VideoResult, DEFAULT_VIDEO_FPS, DEFAULT_VIDEO_HEIGHT, DEFAULT_VIDEO_WIDTH};
 
 /// Video frame data in YUV420 format ready for ToxAV.
@@ -24,6 +25,23 @@ pub struct VideoFrameData {
     pub height: u16,
 }
 
+impl VideoFrameData {
+    /// The planes, which are stored without padding
+    pub fn planes(&self) -> YuvPlanes<'_> {
+        let width = self.width as usize;
+        YuvPlanes {
+            y: &self.y,
+            u: &self.u,
+            v: &self.v,
+            y_stride: width,
+            u_stride: width / 2,
+            v_stride: width / 2,
+            width,
+            height: self.height as usize,
+        }
+    }
+}
+
 /// Video capture error sent via channel
 #[derive(Debug, Clone)]
 pub struct VideoCaptureError {
@@ -40,15 +58,18 @@ pub struct VideoCapture {
 impl VideoCapture {
     /// Start capturing video from the default camera.
     pub fn start(
+        background: Arc<BackgroundSetting>,
         frame_tx: mpsc::UnboundedSender<VideoFrameData>,
         error_tx: mpsc::UnboundedSender<VideoCaptureError>,
     ) -> VideoResult<Self> {
-        Self::start_with_device(None, frame_tx, error_tx)
+        Self::start_with_device(None, background, frame_tx, error_tx)
     }
 
     /// Start capturing video from a specific device (or default if None).
+    /// `background` is read every frame, so changes apply right away.
     pub fn start_with_device(
         device_index: Option<u32>,
+        background: Arc<BackgroundSetting>,
         frame_tx: mpsc::UnboundedSender<VideoFrameData>,
         error_tx: mpsc::UnboundedSender<VideoCaptureError>,
     ) -> VideoResult<Self> {
@@ -60,7 +81,7 @@ impl VideoCapture {
         let thread = thread::Builder::new()
             .name("video-capture".into())
             .spawn(move || {
-                if let Err(e) = Self::capture_loop(index, frame_tx, running_clone) {
+                if let Err(e) = Self::capture_loop(index, background, frame_tx, running_clone) {
                     error!("Video capture error: {e}");
                     // Send error to main thread so it can emit to frontend
                     let _ = error_tx.send(VideoCaptureError {
@@ -79,6 +100,7 @@ impl VideoCapture {
 
     fn capture_loop(
         device_index: u32,
+        background: Arc<BackgroundSetting>,
         frame_tx: mpsc::UnboundedSender<VideoFrameData>,
         running: Arc<AtomicBool>,
     ) -> VideoResult<()> {
@@ -115,6 +137,8 @@ impl VideoCapture {
         let frame_interval = Duration::from_millis(1000 / DEFAULT_VIDEO_FPS as u64);
         let mut last_frame_time = Instant::now();
         let mut frame_count = 0u64;
+        // Leave most of each frame's time for decoding, conversion and encoding
+        let mut blur = BackgroundBlur::new(frame_interval / 3);
 
         while running.load(Ordering::Relaxed) {
             // Rate limiting
@@ -134,7 +158,7 @@ impl VideoCapture {
             };
 
             // Decode to RGB
-            let rgb_data = match frame.decode_image::<RgbFormat>() {
+            let mut rgb_data = match frame.decode_image::<RgbFormat>() {
                 Ok(img) => img.into_raw(),
                 Err(e) => {
                     warn!("CAMERA: Failed to decode frame: {e}");
@@ -142,6 +166,8 @@ impl VideoCapture {
                 }
             };
 
+            blur.process(background.get(), &mut rgb_data, width, height);
+
             // Convert to YUV420
             let (y, u, v) = rgb_to_yuv420(&rgb_data, width, height);
 
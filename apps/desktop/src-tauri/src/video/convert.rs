@@ -1,7 +1,8 @@
 //! RGB to YUV420 conversion for ToxAV.
 //!
 //! ToxAV requires video frames in YUV420 planar format.
-//! This module provides conversion from common camera formats.
+//! This module provides conversion from common camera formats, and back to
+//! RGBA (scaled down to fit a size) for display.
 
 /// Convert RGB24 buffer to YUV420 planar format.
 ///
@@ -106,6 +107,100 @@ pub fn rgba_to_yuv420(rgba: &[u8], width: usize, height: usize) -> (Vec<u8>, Vec
     (y_plane, u_plane, v_plane)
 }
 
+/// A YUV420 frame as ToxAV hands it over: each plane's rows may be padded
+/// out to its stride
+pub struct YuvPlanes<'a> {
+    pub y: &'a [u8],
+    pub u: &'a [u8],
+    pub v: &'a [u8],
+    pub y_stride: usize,
+    pub u_stride: usize,
+    pub v_stride: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+/// The largest size with the same aspect ratio that fits within `max_width` x `max_height`
+pub fn fit_within(width: usize, height: usize, max_width: usize, max_height: usize) -> (usize, usize) {
+    if width <= max_width && height <= max_height {
+        return (width, height);
+    }
+    let scale = (max_width as f64 / width as f64).min(max_height as f64 / height as f64);
+    (
+        ((width as f64 * scale).round() as usize).max(1),
+        ((height as f64 * scale).round() as usize).max(1),
+    )
+}
+
+/// Convert a YUV420 frame to RGBA at `out_width` x `out_height`, dropping
+/// stride padding and sampling nearest pixels when scaling down.
+///
+/// Uses the BT.601 coefficients `rgb_to_yuv420` encodes with. Returns None if
+/// the planes are too short for the dimensions and strides.
+pub fn yuv420_to_rgba(frame: &YuvPlanes, out_width: usize, out_height: usize) -> Option<Vec<u8>> {
+    let (w, h) = (frame.width, frame.height);
+    if w == 0 || h == 0 || out_width == 0 || out_height == 0 {
+        return None;
+    }
+    // Encoders differ on rounding odd sizes, so only rely on the smaller chroma size
+    let (uv_w, uv_h) = ((w / 2).max(1), (h / 2).max(1));
+    let fits = |plane: &[u8], stride: usize, cols: usize, rows: usize| {
+        stride >= cols && plane.len() >= (rows - 1) * stride + cols
+    };
+    if !fits(frame.y, frame.y_stride, w, h)
+        || !fits(frame.u, frame.u_stride, uv_w, uv_h)
+        || !fits(frame.v, frame.v_stride, uv_w, uv_h)
+    {
+        return None;
+    }
+
+    let mut rgba = vec![255u8; out_width * out_height * 4];
+    for oy in 0..out_height {
+        let sy = oy * h / out_height;
+        let y_row = &frame.y[sy * frame.y_stride..];
+        let uv_y = (sy / 2).min(uv_h - 1);
+        let u_row = &frame.u[uv_y * frame.u_stride..];
+        let v_row = &frame.v[uv_y * frame.v_stride..];
+        for ox in 0..out_width {
+            let sx = ox * w / out_width;
+            let y = y_row[sx] as f32;
+            let uv_x = (sx / 2).min(uv_w - 1);
+            let u = u_row[uv_x] as f32 - 128.0;
+            let v = v_row[uv_x] as f32 - 128.0;
+
+            let idx = (oy * out_width + ox) * 4;
+            rgba[idx] = (y + 1.402 * v).clamp(0.0, 255.0) as u8;
+            rgba[idx + 1] = (y - 0.344 * u - 0.714 * v).clamp(0.0, 255.0) as u8;
+            rgba[idx + 2] = (y + 1.772 * u).clamp(0.0, 255.0) as u8;
+        }
+    }
+    Some(rgba)
+}
+
+/// Copy the `region` (x, y, width, height) of an RGBA32 image, scaled to
+/// `out_width` x `out_height` by sampling nearest pixels. The region must lie
+/// within the image.
+pub fn crop_scale_rgba(
+    rgba: &[u8],
+    width: usize,
+    region: (usize, usize, usize, usize),
+    out_width: usize,
+    out_height: usize,
+) -> Vec<u8> {
+    let (rx, ry, rw, rh) = region;
+    let mut out = vec![0u8; out_width * out_height * 4];
+    for oy in 0..out_height {
+        let sy = ry + oy * rh / out_height;
+        for ox in 0..out_width {
+            let sx = rx + ox * rw / out_width;
+            let src = (sy * width + sx) * 4;
+            let dst = (oy * out_width + ox) * 4;
+            out[dst..dst + 4].copy_from_slice(&rgba[src..src + 4]);
+        }
+    }
+    out
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -144,4 +239,48 @@ mod tests {
         assert!(u.iter().all(|&val| val == 128));
         assert!(v.iter().all(|&val| val == 128));
     }
+
+    #[test]
+    fn test_fit_within() {
+        assert_eq!(fit_within(640, 480, 1280, 720), (640, 480));
+        assert_eq!(fit_within(3840, 2160, 1280, 720), (1280, 720));
+        assert_eq!(fit_within(1080, 1920, 1280, 720), (405, 720));
+    }
+
+    #[test]
+    fn test_yuv420_to_rgba_drops_padding() {
+        // 4x2 white frame with rows padded to 8 bytes
+        let y = [255, 255, 255, 255, 0, 0, 0, 0, 255, 255, 255, 255, 0, 0, 0, 0];
+        let uv = [128, 128, 0, 0];
+        let frame = YuvPlanes {
+            y: &y,
+            u: &uv,
+            v: &uv,
+            y_stride: 8,
+            u_stride: 4,
+            v_stride: 4,
+            width: 4,
+            height: 2,
+        };
+
+        let rgba = yuv420_to_rgba(&frame, 4, 2).unwrap();
+        assert_eq!(rgba.len(), 4 * 2 * 4);
+        assert!(rgba.iter().all(|&c| c == 255));
+
+        let scaled = yuv420_to_rgba(&frame, 2, 1).unwrap();
+        assert_eq!(scaled.len(), 2 * 4);
+        assert!(yuv420_to_rgba(&YuvPlanes { y: &y[..10], ..frame }, 4, 2).is_none());
+    }
+
+    #[test]
+    fn test_crop_scale_rgba() {
+        // 4x2 image whose pixels are numbered 0..8
+        let rgba: Vec<u8> = (0..8u8).flat_map(|i| [i; 4]).collect();
+
+        let cropped = crop_scale_rgba(&rgba, 4, (1, 0, 2, 2), 2, 2);
+        assert_eq!(cropped.chunks(4).map(|p| p[0]).collect::<Vec<_>>(), vec![1, 2, 5, 6]);
+
+        let scaled = crop_scale_rgba(&rgba, 4, (0, 0, 4, 2), 2, 1);
+        assert_eq!(scaled.chunks(4).map(|p| p[0]).collect::<Vec<_>>(), vec![0, 2]);
+    }
 }
@@ -0,0 +1,185 @@
+//! Video frame transport to the frontend.
+//!
+//! Emitting frames as events serializes megabytes a second into JSON.
+//! Instead the latest frame of each source is kept here and served as raw
+//! bytes over the `video://` protocol. The frontend polls
+//! `video://localhost/<source>?after=<seq>` once per animation frame and gets
+//! `204 No Content` until a newer frame arrives, so nothing is copied for
+//! frames it doesn't draw.
+//!
+//! Frames are converted to RGBA here, scaled down to fit the output limit,
+//! so the frontend can draw them as they are.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use tauri::http::{Request, Response, StatusCode};
+
+use super::convert::{self, YuvPlanes};
+
+/// URI scheme the frames are served under
+pub const VIDEO_PROTOCOL: &str = "video";
+
+/// Where a frame comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VideoSource {
+    /// Our camera or screen, for the preview
+    Local,
+    /// A friend in a call
+    Friend(u32),
+}
+
+impl VideoSource {
+    /// Parse the path of a request: `/local` or `/friend/<number>`
+    pub fn from_path(path: &str) -> Option<Self> {
+        let mut parts = path.trim_matches('/').split('/');
+        let source = match (parts.next()?, parts.next()) {
+            ("local", None) => Self::Local,
+            ("friend", Some(number)) => Self::Friend(number.parse().ok()?),
+            _ => return None,
+        };
+        parts.next().is_none().then_some(source)
+    }
+}
+
+/// Largest frame served when no limit is set
+pub const DEFAULT_MAX_OUTPUT: (u16, u16) = (1280, 720);
+
+/// A frame in RGBA
+pub struct Frame {
+    /// Increases with every frame published, across all sources
+    pub seq: u64,
+    pub width: u16,
+    pub height: u16,
+    pub data: Vec<u8>,
+}
+
+/// The latest frame of each source
+pub struct FrameBuffer {
+    frames: Mutex<HashMap<VideoSource, Arc<Frame>>>,
+    next_seq: AtomicU64,
+    /// Frames are scaled down to fit within this (width, height)
+    max_output: Mutex<(u16, u16)>,
+}
+
+impl Default for FrameBuffer {
+    fn default() -> Self {
+        Self {
+            frames: Mutex::new(HashMap::new()),
+            next_seq: AtomicU64::new(0),
+            max_output: Mutex::new(DEFAULT_MAX_OUTPUT),
+        }
+    }
+}
+
+impl FrameBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_max_output(&self, width: u16, height: u16) {
+        if let Ok(mut max_output) = self.max_output.lock() {
+            *max_output = (width.max(1), height.max(1));
+        }
+    }
+
+    /// Convert a YUV420 frame and replace the source's frame with it
+    pub fn publish_yuv(&self, source: VideoSource, frame: &YuvPlanes) {
+        let (max_width, max_height) = self.max_output.lock().map(|m| *m).unwrap_or(DEFAULT_MAX_OUTPUT);
+        let (width, height) = convert::fit_within(frame.width, frame.height, max_width as usize, max_height as usize);
+        match convert::yuv420_to_rgba(frame, width, height) {
+            Some(rgba) => self.publish(source, width as u16, height as u16, rgba),
+            None => tracing::debug!("Dropping malformed {}x{} video frame", frame.width, frame.height),
+        }
+    }
+
+    /// Replace a source's frame with an RGBA one
+    pub fn publish(&self, source: VideoSource, width: u16, height: u16, data: Vec<u8>) {
+        let seq = self.next_seq.fetch_add(1, Ordering::Relaxed) + 1;
+        let frame = Arc::new(Frame { seq, width, height, data });
+        if let Ok(mut frames) = self.frames.lock() {
+            frames.insert(source, frame);
+        }
+    }
+
+    /// A source's frame, if it's newer than `after`
+    pub fn latest_after(&self, source: VideoSource, after: u64) -> Option<Arc<Frame>> {
+        let frames = self.frames.lock().ok()?;
+        frames.get(&source).filter(|f| f.seq > after).cloned()
+    }
+
+    /// Drop a source's frame when it stops, so a stale picture isn't shown
+    pub fn remove(&self, source: VideoSource) {
+        if let Ok(mut frames) = self.frames.lock() {
+            frames.remove(&source);
+        }
+    }
+
+    /// Answer a `video://` request
+    pub fn serve(&self, request: &Request<Vec<u8>>) -> Response<Vec<u8>> {
+        let Some(source) = VideoSource::from_path(request.uri().path()) else {
+            return empty_response(StatusCode::NOT_FOUND);
+        };
+        let after = request
+            .uri()
+            .query()
+            .and_then(|q| q.split('&').find_map(|p| p.strip_prefix("after=")))
+            .and_then(|seq| seq.parse().ok())
+            .unwrap_or(0);
+
+        match self.latest_after(source, after) {
+            Some(frame) => Response::builder()
+                .header("Content-Type", "application/octet-stream")
+                .header("Access-Control-Allow-Origin", "*")
+                .header("Access-Control-Expose-Headers", "X-Frame-Seq, X-Frame-Width, X-Frame-Height")
+                .header("Cache-Control", "no-store")
+                .header("X-Frame-Seq", frame.seq)
+                .header("X-Frame-Width", frame.width)
+                .header("X-Frame-Height", frame.height)
+                .body(frame.data.clone())
+                .unwrap_or_else(|_| empty_response(StatusCode::INTERNAL_SERVER_ERROR)),
+            None => empty_response(StatusCode::NO_CONTENT),
+        }
+    }
+}
+
+fn empty_response(status: StatusCode) -> Response<Vec<u8>> {
+    let mut response = Response::new(Vec::new());
+    *response.status_mut() = status;
+    response
+        .headers_mut()
+        .insert("Access-Control-Allow-Origin", tauri::http::HeaderValue::from_static("*"));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_source_from_path() {
+        assert_eq!(VideoSource::from_path("/local"), Some(VideoSource::Local));
+        assert_eq!(VideoSource::from_path("/friend/3"), Some(VideoSource::Friend(3)));
+        assert_eq!(VideoSource::from_path("/friend/x"), None);
+        assert_eq!(VideoSource::from_path("/friend/3/extra"), None);
+        assert_eq!(VideoSource::from_path("/other"), None);
+    }
+
+    #[test]
+    fn test_latest_after() {
+        let frames = FrameBuffer::new();
+        assert!(frames.latest_after(VideoSource::Local, 0).is_none());
+
+        frames.publish(VideoSource::Local, 2, 2, vec![0; 6]);
+        let first = frames.latest_after(VideoSource::Local, 0).unwrap();
+        assert!(frames.latest_after(VideoSource::Local, first.seq).is_none());
+        assert!(frames.latest_after(VideoSource::Friend(0), 0).is_none());
+
+        frames.publish(VideoSource::Local, 2, 2, vec![1; 6]);
+        assert_eq!(frames.latest_after(VideoSource::Local, first.seq).unwrap().data, vec![1; 6]);
+
+        frames.remove(VideoSource::Local);
+        assert!(frames.latest_after(VideoSource::Local, 0).is_none());
+    }
+}
@@ -1,17 +1,20 @@
 //! Video capture module for ToxAV.
 //!
 //! This module provides:
-//! - Video capture from camera (via nokhwa)
+//! - Video capture from camera (via nokhwa), with optional background blur
 //! - Screen capture for screen sharing (via xcap)
 //! - RGB to YUV420 conversion for ToxAV
-//! - Frame transport to frontend
+//! - Frame transport to frontend (over the `video://` protocol)
 
+pub mod background;
 pub mod capture;
 pub mod convert;
+pub mod frames;
+pub mod preview;
 pub mod screen;
 
 pub use capture::{VideoCapture, VideoCaptureError, VideoFrameData};
-pub use screen::{ScreenCapture, ScreenInfo};
+pub use screen::{ScreenCapture, ScreenInfo, ScreenShareOptions};
 
 /// Default video configuration
 pub const DEFAULT_VIDEO_WIDTH: u32 = 640;
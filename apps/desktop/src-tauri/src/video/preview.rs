@@ -0,0 +1,65 @@
+//! Camera preview outside of calls.
+//!
+//! Runs a `VideoCapture` on its own so users can check their camera before
+//! joining a call. Frames go to the frame buffer as the local source, the
+//! same as during a call, and capture errors to the `toxav://local-video`
+//! event. A call that starts video takes the camera over from the preview.
+
+use std::sync::Arc;
+use std::thread;
+
+use tauri::{AppHandle, Emitter};
+use tracing::{error, info};
+
+use super::background::BackgroundSetting;
+use super::frames::{FrameBuffer, VideoSource};
+use super::{VideoCapture, VideoResult};
+use crate::managers::av_manager::ToxAvEvent;
+
+pub struct CameraPreview {
+    _capture: VideoCapture,
+    video_frames: Arc<FrameBuffer>,
+}
+
+impl CameraPreview {
+    pub fn start(
+        app_handle: AppHandle,
+        device_index: Option<u32>,
+        background: Arc<BackgroundSetting>,
+        video_frames: Arc<FrameBuffer>,
+    ) -> VideoResult<Self> {
+        let (frame_tx, mut frame_rx) = tokio::sync::mpsc::unbounded_channel();
+        let (error_tx, mut error_rx) = tokio::sync::mpsc::unbounded_channel();
+        let capture = VideoCapture::start_with_device(device_index, background, frame_tx, error_tx)?;
+
+        // Ends when the capture thread stops and drops its sender
+        let frames = video_frames.clone();
+        thread::Builder::new()
+            .name("camera-preview".into())
+            .spawn(move || {
+                while let Some(frame) = frame_rx.blocking_recv() {
+                    frames.publish_yuv(VideoSource::Local, &frame.planes());
+                }
+                if let Ok(err) = error_rx.try_recv() {
+                    let event = ToxAvEvent::VideoError { error: err.message };
+                    if let Err(e) = app_handle.emit("toxav://local-video", &event) {
+                        error!("Failed to emit video error event: {e}");
+                    }
+                }
+            })
+            .map_err(|e| super::VideoError::Init(format!("Failed to spawn preview thread: {e}")))?;
+
+        info!("Camera preview started");
+        Ok(Self {
+            _capture: capture,
+            video_frames,
+        })
+    }
+}
+
+impl Drop for CameraPreview {
+    fn drop(&mut self) {
+        self.video_frames.remove(VideoSource::Local);
+        info!("Camera preview stopped");
+    }
+}
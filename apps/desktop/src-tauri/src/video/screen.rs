@@ -1,4 +1,8 @@
 //! Screen capture for screen sharing via ToxAV.
+//!
+//! A share can be limited to part of a screen, and a preset picks between
+//! a readable picture at a low frame rate and a smaller one at a high rate,
+//! so sharing a 4K display doesn't saturate the upload.
 
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
@@ -10,7 +14,7 @@ use tracing::{error, info, warn};
 use xcap::Monitor;
 
 use super::capture::{VideoCaptureError, VideoFrameData};
-use super::convert::rgba_to_yuv420;
+use super::convert::{self, rgba_to_yuv420};
 use super::{VideoError, VideoResult, DEFAULT_VIDEO_FPS};
 
 /// Screen information for selection UI.
@@ -23,11 +27,69 @@ pub struct ScreenInfo {
     pub is_primary: bool,
 }
 
+/// Part of a screen to share, in the screen's pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct CropRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Trade-off between detail and motion
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScreenSharePreset {
+    /// Full resolution at a low rate, for reading text and code
+    Text,
+    #[default]
+    Balanced,
+    /// A smaller picture at a high rate, for video and animation
+    Motion,
+}
+
+impl ScreenSharePreset {
+    fn fps(self) -> u32 {
+        match self {
+            Self::Text => 5,
+            Self::Balanced => DEFAULT_VIDEO_FPS,
+            Self::Motion => 30,
+        }
+    }
+
+    /// Largest frame sent, as (width, height)
+    fn max_size(self) -> (usize, usize) {
+        match self {
+            Self::Text => (3840, 2160),
+            Self::Balanced => (1920, 1080),
+            Self::Motion => (1280, 720),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct ScreenShareOptions {
+    /// None shares the whole screen
+    pub region: Option<CropRect>,
+    pub preset: ScreenSharePreset,
+    /// Overrides the preset's frame rate
+    pub fps: Option<u32>,
+}
+
+impl ScreenShareOptions {
+    pub fn fps(&self) -> u32 {
+        self.fps.unwrap_or(self.preset.fps()).clamp(1, 60)
+    }
+}
+
 /// Screen capture for sharing screen content.
 /// Captures screen frames and converts to YUV420 for ToxAV.
 pub struct ScreenCapture {
     _thread: thread::JoinHandle<()>,
     running: Arc<AtomicBool>,
+    screen_id: Option<u32>,
+    options: ScreenShareOptions,
 }
 
 impl ScreenCapture {
@@ -70,6 +132,7 @@ impl ScreenCapture {
     /// Start capturing a specific screen (or primary if None).
     pub fn start(
         screen_id: Option<u32>,
+        options: ScreenShareOptions,
         frame_tx: mpsc::UnboundedSender<VideoFrameData>,
         error_tx: mpsc::UnboundedSender<VideoCaptureError>,
     ) -> VideoResult<Self> {
@@ -79,7 +142,7 @@ impl ScreenCapture {
         let thread = thread::Builder::new()
             .name("screen-capture".into())
             .spawn(move || {
-                if let Err(e) = Self::capture_loop(screen_id, frame_tx, running_clone) {
+                if let Err(e) = Self::capture_loop(screen_id, options, frame_tx, running_clone) {
                     error!("Screen capture error: {e}");
                     let _ = error_tx.send(VideoCaptureError {
                         message: e.to_string(),
@@ -88,15 +151,23 @@ impl ScreenCapture {
             })
             .map_err(|e| VideoError::Init(format!("Failed to spawn screen capture thread: {e}")))?;
 
-        info!("Screen capture started");
+        info!("Screen capture started ({options:?})");
         Ok(Self {
             _thread: thread,
             running,
+            screen_id,
+            options,
         })
     }
 
+    /// The screen and options this capture was started with
+    pub fn settings(&self) -> (Option<u32>, ScreenShareOptions) {
+        (self.screen_id, self.options)
+    }
+
     fn capture_loop(
         screen_id: Option<u32>,
+        options: ScreenShareOptions,
         frame_tx: mpsc::UnboundedSender<VideoFrameData>,
         running: Arc<AtomicBool>,
     ) -> VideoResult<()> {
@@ -133,7 +204,7 @@ impl ScreenCapture {
             monitor.height()
         );
 
-        let frame_interval = Duration::from_millis(1000 / DEFAULT_VIDEO_FPS as u64);
+        let frame_interval = Duration::from_millis(1000 / options.fps() as u64);
         let mut last_frame_time = Instant::now();
         let mut frame_count = 0u64;
 
@@ -154,14 +225,22 @@ impl ScreenCapture {
                 }
             };
 
-            let width = image.width() as usize;
-            let height = image.height() as usize;
+            let (screen_width, screen_height) = (image.width() as usize, image.height() as usize);
+            let Some(region) = crop_region(options.region, screen_width, screen_height) else {
+                warn!("SCREEN: {screen_width}x{screen_height} screen too small to share");
+                continue;
+            };
+            let (width, height) = output_size(region, options.preset.max_size());
 
             // xcap returns RGBA data
-            let rgba_data = image.as_raw();
+            let rgba_data = if region == (0, 0, screen_width, screen_height) && (width, height) == (region.2, region.3) {
+                image.into_raw()
+            } else {
+                convert::crop_scale_rgba(image.as_raw(), screen_width, region, width, height)
+            };
 
             // Convert RGBA to YUV420
-            let (y, u, v) = rgba_to_yuv420(rgba_data, width, height);
+            let (y, u, v) = rgba_to_yuv420(&rgba_data, width, height);
 
             let frame_data = VideoFrameData {
                 y,
@@ -204,3 +283,45 @@ impl Drop for ScreenCapture {
         self.stop();
     }
 }
+
+/// The part of a screen to capture as (x, y, width, height): the requested
+/// rectangle clipped to the screen, with even sides for YUV420. None if less
+/// than 2x2 is left.
+fn crop_region(region: Option<CropRect>, width: usize, height: usize) -> Option<(usize, usize, usize, usize)> {
+    let (x, y, w, h) = match region {
+        Some(r) => (r.x as usize, r.y as usize, r.width as usize, r.height as usize),
+        None => (0, 0, width, height),
+    };
+    let (x, y) = (x.min(width), y.min(height));
+    let w = w.min(width - x) & !1;
+    let h = h.min(height - y) & !1;
+    (w >= 2 && h >= 2).then_some((x, y, w, h))
+}
+
+/// Size to send a region at, scaled down to fit `max` and kept even
+fn output_size(region: (usize, usize, usize, usize), max: (usize, usize)) -> (usize, usize) {
+    let (width, height) = convert::fit_within(region.2, region.3, max.0, max.1);
+    ((width & !1).max(2), (height & !1).max(2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crop_region_clips_to_screen() {
+        assert_eq!(crop_region(None, 1920, 1080), Some((0, 0, 1920, 1080)));
+        let rect = CropRect { x: 1000, y: 500, width: 1500, height: 301 };
+        assert_eq!(crop_region(Some(rect), 1920, 1080), Some((1000, 500, 920, 300)));
+        let outside = CropRect { x: 2000, y: 0, width: 100, height: 100 };
+        assert_eq!(crop_region(Some(outside), 1920, 1080), None);
+    }
+
+    #[test]
+    fn test_output_size_fits_preset() {
+        let full = (0, 0, 3840, 2160);
+        assert_eq!(output_size(full, ScreenSharePreset::Motion.max_size()), (1280, 720));
+        assert_eq!(output_size(full, ScreenSharePreset::Text.max_size()), (3840, 2160));
+        assert_eq!(output_size((0, 0, 1001, 333), (500, 500)), (500, 166));
+    }
+}
@@ -1,13 +1,26 @@
 import { useAuthStore } from "./stores/authStore";
 import { LoginPage } from "./pages/LoginPage";
 import { HomePage } from "./pages/HomePage";
+import { CallPipWindow } from "./components/call/CallPipWindow";
+import { CrashReportPrompt } from "./components/layout/CrashReportPrompt";
+import { pipFriendNumber } from "./api/calls";
 
 function App() {
   const isLoggedIn = useAuthStore((s) => s.isLoggedIn);
+  const pipFriend = pipFriendNumber();
+
+  if (pipFriend !== null) {
+    return (
+      <div className="h-screen w-screen bg-black">
+        <CallPipWindow friendNumber={pipFriend} />
+      </div>
+    );
+  }
 
   return (
     <div className="h-screen w-screen bg-discord-dark">
       {isLoggedIn ? <HomePage /> : <LoginPage />}
+      <CrashReportPrompt />
     </div>
   );
 }
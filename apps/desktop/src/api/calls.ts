@@ -1,5 +1,6 @@
-import { invoke } from "@tauri-apps/api/core";
+import { invoke } from "./errors";
 import { listen, UnlistenFn } from "@tauri-apps/api/event";
+import { convertFileSrc } from "@tauri-apps/api/core";
 
 // ─── Types ───────────────────────────────────────────────────────────
 
@@ -18,6 +19,14 @@ export interface CallState {
   is_audio_muted: boolean;
   is_video_muted: boolean;
   started_at: string | null;
+  /** Set once connected; messages sent during the call are tagged with it */
+  session_id: string | null;
+}
+
+export interface DoNotDisturbInfo {
+  enabled: boolean;
+  /** Sent to callers at most once an hour while enabled; empty for none */
+  auto_reply: string;
 }
 
 export interface AudioDevice {
@@ -32,11 +41,32 @@ export interface VideoDevice {
   is_default: boolean;
 }
 
+/** Our own camera or screen, or a friend in a call */
+export type VideoSource = "local" | { friend: number };
+
 export interface VideoFramePayload {
+  seq: number;
+  width: number;
+  height: number;
+  /** RGBA, already scaled to fit the output limit */
+  data: Uint8Array;
+}
+
+/** Video being received from a peer */
+export interface VideoStream {
   friend_number: number;
   width: number;
   height: number;
-  data: number[];
+  /** Whether its frames are served for display */
+  subscribed: boolean;
+}
+
+export type BackgroundMode = "off" | "blur";
+
+export interface VideoSettings {
+  max_output_width: number;
+  max_output_height: number;
+  background_mode: BackgroundMode;
 }
 
 export type ToxAvEvent =
@@ -63,22 +93,44 @@ export type ToxAvEvent =
       type: "AudioLevelUpdate";
       data: { friend_number: number; level: number };
     }
-  | {
-      type: "VideoFrame";
-      data: VideoFramePayload;
-    }
   | {
       type: "VideoError";
       data: { error: string };
+    }
+  | {
+      type: "GlobalMuteChanged";
+      data: { muted: boolean };
+    }
+  | {
+      type: "GlobalDeafenChanged";
+      data: { deafened: boolean };
+    }
+  | {
+      type: "VideoStreamStarted";
+      data: { friend_number: number; width: number; height: number };
+    }
+  | {
+      type: "VideoStreamEnded";
+      data: { friend_number: number };
+    }
+  | {
+      /** A call ended because the peer went silent or offline, or the computer slept */
+      type: "CallDropped";
+      data: { friend_number: number; reason: "no_media" | "peer_offline" | "suspended" };
+    }
+  | {
+      type: "MissedCall";
+      data: Omit<MissedCall, "seen">;
     };
 
 // ─── Call Management ─────────────────────────────────────────────────
 
+/** Without `withVideo`, the camera follows the friend's call preferences */
 export async function callFriend(
   friendNumber: number,
-  withVideo: boolean = false,
+  withVideo?: boolean,
 ): Promise<void> {
-  return invoke("call_friend", { friendNumber, withVideo });
+  return invoke("call_friend", { friendNumber, withVideo: withVideo ?? null });
 }
 
 export async function answerCall(
@@ -92,6 +144,17 @@ export async function hangupCall(friendNumber: number): Promise<void> {
   return invoke("hangup_call", { friendNumber });
 }
 
+/** Open or close the picture-in-picture call window; returns whether it's open */
+export async function toggleCallPip(friendNumber: number): Promise<boolean> {
+  return invoke("toggle_call_pip", { friendNumber });
+}
+
+/** The friend whose call this window shows, when it's the picture-in-picture window */
+export function pipFriendNumber(): number | null {
+  const friend = (window as { __TOXCORD_PIP_FRIEND__?: number }).__TOXCORD_PIP_FRIEND__;
+  return typeof friend === "number" ? friend : null;
+}
+
 export async function toggleMute(
   friendNumber: number,
   muted: boolean,
@@ -99,6 +162,14 @@ export async function toggleMute(
   return invoke("toggle_mute", { friendNumber, muted });
 }
 
+export async function setGlobalMute(muted: boolean): Promise<void> {
+  return invoke("set_global_mute", { muted });
+}
+
+export async function setGlobalDeafen(deafened: boolean): Promise<void> {
+  return invoke("set_global_deafen", { deafened });
+}
+
 export async function toggleVideo(
   friendNumber: number,
   enabled: boolean,
@@ -112,6 +183,21 @@ export async function getCallState(
   return invoke("get_call_state", { friendNumber });
 }
 
+// ─── Video Streams ───────────────────────────────────────────────────
+
+export async function listVideoStreams(): Promise<VideoStream[]> {
+  return invoke("list_video_streams");
+}
+
+export async function subscribeVideoStream(friendNumber: number): Promise<void> {
+  return invoke("subscribe_video_stream", { friendNumber });
+}
+
+/** Stop receiving frames for a stream that isn't on screen */
+export async function unsubscribeVideoStream(friendNumber: number): Promise<void> {
+  return invoke("unsubscribe_video_stream", { friendNumber });
+}
+
 // ─── Audio Devices ───────────────────────────────────────────────────
 
 export async function listAudioInputDevices(): Promise<AudioDevice[]> {
@@ -134,6 +220,7 @@ export async function setAudioInputDevice(deviceId: string): Promise<void> {
   return invoke("set_audio_input_device", { deviceId });
 }
 
+/** Moves playback of a call in progress to the device right away */
 export async function setAudioOutputDevice(deviceId: string): Promise<void> {
   return invoke("set_audio_output_device", { deviceId });
 }
@@ -142,6 +229,11 @@ export async function setVideoDevice(deviceId: string): Promise<void> {
   return invoke("set_video_device", { deviceId });
 }
 
+/** Select a camera, switching to it right away if it's in use */
+export async function switchCamera(deviceId: string): Promise<void> {
+  return invoke("switch_camera", { deviceId });
+}
+
 // ─── Camera Diagnostics ───────────────────────────────────────────────
 
 export interface CameraStatus {
@@ -173,14 +265,148 @@ export async function listScreens(): Promise<ScreenInfo[]> {
   return invoke("list_screens");
 }
 
-export async function startScreenShare(screenId?: number): Promise<void> {
-  return invoke("start_screen_share", { screenId });
+/** Part of a screen to share, in the screen's pixels */
+export interface CropRect {
+  x: number;
+  y: number;
+  width: number;
+  height: number;
+}
+
+/** "text" sends full resolution at a low rate, "motion" a smaller picture at a high rate */
+export type ScreenSharePreset = "text" | "balanced" | "motion";
+
+export interface ScreenShareOptions {
+  region?: CropRect | null;
+  preset?: ScreenSharePreset;
+  /** Overrides the preset's frame rate */
+  fps?: number | null;
+}
+
+export async function startScreenShare(
+  screenId?: number,
+  options?: ScreenShareOptions,
+): Promise<void> {
+  return invoke("start_screen_share", { screenId, options });
 }
 
 export async function stopScreenShare(): Promise<void> {
   return invoke("stop_screen_share");
 }
 
+// ─── Do Not Disturb ──────────────────────────────────────────────────
+
+/** Do Not Disturb is tied to the Busy status: incoming calls are declined */
+export async function setDoNotDisturb(enabled: boolean): Promise<void> {
+  return invoke("set_do_not_disturb", { enabled });
+}
+
+export async function getDoNotDisturb(): Promise<DoNotDisturbInfo> {
+  return invoke("get_do_not_disturb");
+}
+
+export async function setDndAutoReply(message: string): Promise<void> {
+  return invoke("set_dnd_auto_reply", { message });
+}
+
+export async function getVideoSettings(): Promise<VideoSettings> {
+  return invoke("get_video_settings");
+}
+
+/** Largest size incoming video is drawn at; it's scaled down to fit */
+export async function setVideoMaxOutput(width: number, height: number): Promise<void> {
+  return invoke("set_video_max_output", { width, height });
+}
+
+/** Effect applied to our camera video; takes effect immediately */
+export async function setVideoBackgroundMode(mode: BackgroundMode): Promise<void> {
+  return invoke("set_video_background_mode", { mode });
+}
+
+// ─── Call Quality ────────────────────────────────────────────────────
+
+export interface CallPreferences {
+  /** kbit/s */
+  audio_bit_rate: number;
+  /** kbit/s, used when the call has video */
+  video_bit_rate: number;
+  /** Whether calls start with the camera on */
+  video_by_default: boolean;
+}
+
+/** A friend's own call preferences, or null if they use the defaults */
+export async function getCallPreferences(friendNumber: number): Promise<CallPreferences | null> {
+  return invoke("get_call_preferences", { friendNumber });
+}
+
+/** Applies from the next call with the friend */
+export async function setCallPreferences(
+  friendNumber: number,
+  preferences: CallPreferences,
+): Promise<void> {
+  return invoke("set_call_preferences", { friendNumber, preferences });
+}
+
+export async function clearCallPreferences(friendNumber: number): Promise<void> {
+  return invoke("clear_call_preferences", { friendNumber });
+}
+
+/** Call quality for friends without their own preferences */
+export async function getDefaultCallPreferences(): Promise<CallPreferences> {
+  return invoke("get_default_call_preferences");
+}
+
+export async function setDefaultCallPreferences(preferences: CallPreferences): Promise<void> {
+  return invoke("set_default_call_preferences", { preferences });
+}
+
+/** The call quality that applies to a friend */
+export async function getEffectiveCallPreferences(friendNumber: number): Promise<CallPreferences> {
+  return (await getCallPreferences(friendNumber)) ?? getDefaultCallPreferences();
+}
+
+/** Show the selected camera outside a call; frames arrive as the local source */
+export async function startCameraPreview(): Promise<void> {
+  return invoke("start_camera_preview");
+}
+
+export async function stopCameraPreview(): Promise<void> {
+  return invoke("stop_camera_preview");
+}
+
+// ─── Missed Calls ────────────────────────────────────────────────────
+
+export interface MissedCall {
+  id: string;
+  friend_number: number;
+  timestamp: string;
+  with_video: boolean;
+  /** "no_answer" when it rang out, "do_not_disturb" when declined automatically */
+  reason: string;
+  seen: boolean;
+}
+
+export async function getMissedCalls(friendNumber?: number, limit?: number): Promise<MissedCall[]> {
+  return invoke("get_missed_calls", { friendNumber: friendNumber ?? null, limit: limit ?? null });
+}
+
+export async function getMissedCallCounts(): Promise<{ friend_number: number; count: number }[]> {
+  return invoke("get_missed_call_counts");
+}
+
+export async function markMissedCallsSeen(friendNumber: number): Promise<void> {
+  return invoke("mark_missed_calls_seen", { friendNumber });
+}
+
+export async function getMissedCallReply(): Promise<string> {
+  return invoke("get_missed_call_reply");
+}
+
+/** Message sent to callers whose call rang out; empty turns it off */
+export async function setMissedCallReply(message: string): Promise<void> {
+  return invoke("set_missed_call_reply", { message });
+}
+
 // ─── Event Listening ─────────────────────────────────────────────────
 
 export function onToxAvEvent(
@@ -191,23 +417,43 @@ export function onToxAvEvent(
   });
 }
 
-export function onLocalVideoFrame(
+/**
+ * Poll the `video://` protocol for a source's frames once per animation frame,
+ * calling back with each new one. Returns a function that stops polling.
+ */
+export function pollVideoFrames(
+  source: VideoSource,
   callback: (frame: VideoFramePayload) => void,
-): Promise<UnlistenFn> {
-  return listen<{ type: "VideoFrame"; data: VideoFramePayload }>(
-    "toxav://local-video",
-    (event) => {
-      callback(event.payload.data);
-    },
-  );
-}
-
-export function onRemoteVideoFrame(
-  callback: (frame: VideoFramePayload) => void,
-): Promise<UnlistenFn> {
-  return listen<ToxAvEvent>("toxav://event", (event) => {
-    if (event.payload.type === "VideoFrame") {
-      callback(event.payload.data);
+): () => void {
+  const path = source === "local" ? "local" : `friend/${source.friend}`;
+  // The base differs by platform (video://localhost/ or http://video.localhost/)
+  const url = convertFileSrc("", "video") + path;
+  let after = 0;
+  let stopped = false;
+  let handle = 0;
+
+  const poll = async () => {
+    try {
+      const response = await fetch(`${url}?after=${after}`, { cache: "no-store" });
+      if (response.status === 200) {
+        const frame: VideoFramePayload = {
+          seq: Number(response.headers.get("X-Frame-Seq")),
+          width: Number(response.headers.get("X-Frame-Width")),
+          height: Number(response.headers.get("X-Frame-Height")),
+          data: new Uint8Array(await response.arrayBuffer()),
+        };
+        after = frame.seq;
+        if (!stopped) callback(frame);
+      }
+    } catch (e) {
+      console.warn("[Video] Failed to fetch frame:", e);
     }
-  });
+    if (!stopped) handle = requestAnimationFrame(poll);
+  };
+  handle = requestAnimationFrame(poll);
+
+  return () => {
+    stopped = true;
+    cancelAnimationFrame(handle);
+  };
 }
@@ -0,0 +1,57 @@
+import { invoke as tauriInvoke, InvokeArgs } from "@tauri-apps/api/core";
+
+/** What went wrong in a backend command */
+export type ToxcordErrorCode =
+  | "not_logged_in"
+  | "already_logged_in"
+  | "wrong_password"
+  | "not_found"
+  | "invalid_input"
+  | "friend_offline"
+  | "message_too_long"
+  | "slow_mode"
+  | "not_permitted"
+  | "tox"
+  | "audio"
+  | "video"
+  | "other";
+
+/** Error thrown by `invoke` when a command fails */
+export class ToxcordError extends Error {
+  code: ToxcordErrorCode;
+  /** For `slow_mode`: seconds until the user can send again */
+  remainingSecs: number | null;
+
+  constructor(code: ToxcordErrorCode, message: string, remainingSecs: number | null = null) {
+    super(message);
+    this.name = "ToxcordError";
+    this.code = code;
+    this.remainingSecs = remainingSecs;
+  }
+
+  // So `String(e)` shows just the message, as it did for string errors
+  toString() {
+    return this.message;
+  }
+}
+
+export function isToxcordError(e: unknown, code?: ToxcordErrorCode): e is ToxcordError {
+  return e instanceof ToxcordError && (code === undefined || e.code === code);
+}
+
+/** Call a backend command, turning its `{ code, message }` errors into `ToxcordError` */
+export async function invoke<T>(cmd: string, args?: InvokeArgs): Promise<T> {
+  try {
+    return await tauriInvoke<T>(cmd, args);
+  } catch (e) {
+    if (typeof e === "object" && e !== null && "code" in e && "message" in e) {
+      const { code, message, remaining_secs } = e as {
+        code: ToxcordErrorCode;
+        message: string;
+        remaining_secs?: number | null;
+      };
+      throw new ToxcordError(code, message, remaining_secs ?? null);
+    }
+    throw e;
+  }
+}
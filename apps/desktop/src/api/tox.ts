@@ -1,4 +1,4 @@
-import { invoke } from "@tauri-apps/api/core";
+import { invoke } from "./errors";
 import { listen, UnlistenFn } from "@tauri-apps/api/event";
 
 export interface ProfileInfo {
@@ -12,6 +12,26 @@ export interface ConnectionInfo {
   status: "none" | "tcp" | "udp";
 }
 
+export type ActivityKind = "playing" | "listening" | "watching" | "streaming" | "competing";
+
+export interface Activity {
+  kind: ActivityKind;
+  name: string;
+  details: string | null;
+  /** Unix seconds */
+  started_at: number | null;
+}
+
+export interface CustomStatus {
+  emoji: string | null;
+  text: string;
+  /** Unix seconds */
+  expires_at: number | null;
+}
+
+/** "direct" over UDP, "relayed" through a TCP relay */
+export type ConnectionType = "none" | "relayed" | "direct";
+
 export interface FriendInfo {
   friend_number: number;
   public_key: string;
@@ -19,8 +39,34 @@ export interface FriendInfo {
   status_message: string;
   user_status: "none" | "online" | "away" | "busy";
   connection_status: "none" | "tcp" | "udp";
+  connection_type: ConnectionType;
   last_seen: string | null;
   notes: string;
+  activity: Activity | null;
+  custom_status: CustomStatus | null;
+  /** Null when the friend isn't in a group */
+  group_id: string | null;
+  /** Order within the group */
+  group_position: number;
+}
+
+export interface FriendGroup {
+  id: string;
+  name: string;
+  position: number;
+  collapsed: boolean;
+  created_at: string;
+}
+
+/** Presence times are UTC, formatted `YYYY-MM-DD HH:MM:SS` */
+export interface PresenceSummary {
+  last_seen: string | null;
+  online_since: string | null;
+  session_secs: number | null;
+  /** Seconds online per UTC day over the last 7 days, oldest first */
+  days: { date: string; online_secs: number }[];
+  /** Seconds online per UTC hour of day over the same 7 days */
+  hours: number[];
 }
 
 export interface FriendRequest {
@@ -29,6 +75,18 @@ export interface FriendRequest {
   received_at: string;
 }
 
+/** Parsed message markdown, as produced by the backend */
+export type MarkdownNode =
+  | { type: "text"; text: string }
+  | { type: "bold"; children: MarkdownNode[] }
+  | { type: "italic"; children: MarkdownNode[] }
+  | { type: "spoiler"; children: MarkdownNode[] }
+  | { type: "code"; code: string }
+  | { type: "code_block"; language: string | null; code: string }
+  | { type: "block_quote"; children: MarkdownNode[] }
+  | { type: "line_break" }
+  | { type: "mention"; public_key: string };
+
 export interface DirectMessage {
   id: string;
   friend_number: number;
@@ -39,6 +97,15 @@ export interface DirectMessage {
   is_outgoing: boolean;
   delivered: boolean;
   read: boolean;
+  formatted: MarkdownNode[];
+  /** Encrypted with a conversation passphrase we haven't entered; `content` is empty */
+  locked?: boolean;
+  /** When a disappearing message is deleted, in Unix seconds */
+  expires_at?: number | null;
+  /** Sent or received in incognito, so it only exists in memory */
+  incognito?: boolean;
+  /** For sticker references received this session */
+  sticker?: StickerInfo | null;
 }
 
 export interface SendMessageResult {
@@ -47,6 +114,28 @@ export interface SendMessageResult {
   delivered: boolean;
   queued: boolean;
   error?: string;
+  formatted: MarkdownNode[];
+  expires_at: number | null;
+  incognito: boolean;
+}
+
+export interface Attachment {
+  id: string;
+  message_id: string;
+  transfer_id: string | null;
+  filename: string;
+  mime_type: string;
+  file_size: number;
+  file_path: string;
+  thumbnail_path: string | null;
+  width: number | null;
+  height: number | null;
+  created_at: string;
+  sha256: string | null;
+  /** Voice notes only */
+  duration_ms: number | null;
+  /** Voice notes only: peak level (0-255) per bar */
+  waveform: number[] | null;
 }
 
 // ─── Guild types ──────────────────────────────────────────────────
@@ -60,15 +149,34 @@ export interface GuildInfo {
   created_at: string;
 }
 
+/** A group invite waiting to be accepted or declined */
+export interface GroupInvite {
+  id: string;
+  friend_number: number;
+  group_name: string;
+  invite_data: number[];
+  received_at: string;
+  /** The inviter said joining needs the group's password */
+  password_required: boolean;
+}
+
 export interface ChannelInfo {
   id: string;
   guild_id: string;
   name: string;
   topic: string;
+  /** "text", or "announcement" when only founders and moderators can post */
   channel_type: string;
   position: number;
+  /** Seconds between messages per member; 0 when slow mode is off */
+  slow_mode_secs: number;
+  /** Which new messages notify and count as unread */
+  notification_level: NotificationLevel;
 }
 
+/** "all" messages, only "mentions", or "nothing" */
+export type NotificationLevel = "all" | "mentions" | "nothing";
+
 export interface ChannelMessage {
   id: string;
   channel_id: string;
@@ -78,35 +186,91 @@ export interface ChannelMessage {
   message_type: string;
   timestamp: string;
   is_own: boolean;
+  formatted: MarkdownNode[];
+  mentions_me: boolean;
+  /** Matched the guild word filter; shown behind a warning */
+  filtered: boolean;
+  /** Came from another member's history after we joined */
+  backfilled: boolean;
+  /** Signed by the Tox identity we know for the sender; null if it wasn't checked */
+  sender_verified: boolean | null;
+  /** The Tox key that signed the message, if it was signed */
+  signer_public_key: string | null;
+  /** For sticker references received this session */
+  sticker?: StickerInfo | null;
+}
+
+export interface MentionableMember {
+  public_key: string;
+  name: string;
+  /** Text to insert into the message to mention this member */
+  mention: string;
 }
 
 export interface GuildMember {
-  peer_id: number;
+  /** Null for offline members */
+  peer_id: number | null;
   name: string;
   public_key: string;
   role: string;
+  /** "online", "away", "busy" or "offline" */
   status: string;
+  /** When an offline member was last seen */
+  last_seen: string | null;
+  activity: Activity | null;
+  custom_status: CustomStatus | null;
+  /** We ignore this member */
+  ignored: boolean;
+  /** Cosmetic role from the guild manifest */
+  custom_role: CustomRole | null;
 }
 
 export type ToxEvent =
   | { type: "ConnectionStatus"; data: { connected: boolean; status: string } }
+  | { type: "SelfStatus"; data: { status: "online" | "away" | "busy" } }
+  | { type: "SelfAddressChanged"; data: { address: string } }
+  | { type: "Resumed"; data: { slept_secs: number } }
+  | { type: "NetworkModeChanged"; data: { mode: NetworkMode } }
   | { type: "FriendRequest"; data: { public_key: string; message: string } }
-  | { type: "FriendMessage"; data: { friend_number: number; message_type: string; message: string; id: string; timestamp: string } }
+  | { type: "FriendMessage"; data: { friend_number: number; message_type: string; message: string; id: string; timestamp: string; formatted: MarkdownNode[]; locked: boolean; expires_at: number | null; incognito: boolean; sticker: StickerInfo | null } }
   | { type: "FriendName"; data: { friend_number: number; name: string } }
   | { type: "FriendStatusMessage"; data: { friend_number: number; message: string } }
   | { type: "FriendStatus"; data: { friend_number: number; status: string } }
-  | { type: "FriendConnectionStatus"; data: { friend_number: number; connected: boolean; status: string } }
+  | { type: "FriendKeyChanged"; data: { friend_number: number; old_public_key: string; new_public_key: string; was_verified: boolean } }
+  | { type: "FriendConnectionStatus"; data: { friend_number: number; connected: boolean; status: string; connection_type: ConnectionType } }
   | { type: "FriendTyping"; data: { friend_number: number; is_typing: boolean } }
-  | { type: "GroupInvite"; data: { friend_number: number; invite_data: number[]; group_name: string } }
+  | { type: "GroupInvite"; data: { invite_id: string; friend_number: number; invite_data: number[]; group_name: string; password_required: boolean } }
   | { type: "GroupSelfJoin"; data: { group_number: number } }
   | { type: "GroupJoinFail"; data: { group_number: number; fail_type: string } }
   | { type: "GroupPeerJoin"; data: { group_number: number; peer_id: number; name: string; public_key: string } }
   | { type: "GroupPeerExit"; data: { group_number: number; peer_id: number; name: string } }
   | { type: "GroupPeerName"; data: { group_number: number; peer_id: number; name: string } }
-  | { type: "GroupMessage"; data: { group_number: number; peer_id: number; sender_name: string; sender_pk: string; message: string; message_type: string; id: string; timestamp: string; channel_id: string } }
+  | { type: "GroupMessage"; data: { group_number: number; peer_id: number; sender_name: string; sender_pk: string; message: string; message_type: string; id: string; timestamp: string; channel_id: string; formatted: MarkdownNode[]; mentions_me: boolean; filtered: boolean; sender_verified: boolean | null; signer_public_key: string | null; sticker: StickerInfo | null } }
   | { type: "GroupTopicChange"; data: { group_number: number; topic: string } }
   | { type: "GroupCustomPacket"; data: { group_number: number; peer_id: number; data: number[] } }
-  | { type: "GroupPeerStatus"; data: { group_number: number; peer_id: number; status: string } };
+  | { type: "GroupPeerStatus"; data: { group_number: number; peer_id: number; status: string } }
+  | { type: "AttachmentReady"; data: { message_id: string; attachment_id: string; filename: string; mime_type: string; file_size: number; width: number | null; height: number | null; has_thumbnail: boolean } }
+  | { type: "ScheduledMessage"; data: { id: string; status: "sent" | "queued" | "failed"; message_id: string | null; target_type: "friend" | "channel"; target_id: string; timestamp: string } }
+  | { type: "AutoReply"; data: { friend_number: number; id: string; message: string; timestamp: string; expires_at: number | null; incognito: boolean } }
+  | { type: "DisappearingTimerChanged"; data: { friend_number: number; seconds: number } }
+  | { type: "DirectMessagesExpired"; data: { friend_number: number; message_ids: string[] } }
+  | { type: "UnprotectedMessageRefused"; data: { friend_number: number } }
+  | { type: "LanPeerFound"; data: { address: string; public_key: string; name: string } }
+  | { type: "LanPeerLost"; data: { public_key: string } }
+  | { type: "DeviceSynced"; data: { friend_number: number; friends_added: number; guilds_joined: number; messages_added: number } }
+  | { type: "GuildHistoryBackfilled"; data: { group_number: number; messages_added: number } }
+  | { type: "ActivityChanged"; data: { public_key: string; activity: Activity | null } }
+  | { type: "CustomStatusChanged"; data: { public_key: string; status: CustomStatus | null } }
+  | { type: "StatusNoteReceived"; data: { friend_number: number; note: StatusNote } }
+  | { type: "GuildManifestChanged"; data: { guild_id: string } }
+  | { type: "PollUpdated"; data: { poll_id: string; tallies: number[]; closed: boolean } }
+  | { type: "ChannelMessagesPurged"; data: { channel_id: string; count: number } }
+  | { type: "GuildEventUpdated"; data: { guild_id: string; event_id: string } }
+  | { type: "GuildEventReminder"; data: { guild_id: string; event_id: string; title: string; start_time: number } }
+  | { type: "BridgedMessage"; data: { channel_id: string; id: string; sender_name: string; content: string; timestamp: string } }
+  | { type: "ToxThreadRestarted"; data: { crashes: number } }
+  | { type: "ToxThreadStopped" }
+  | { type: "ToxThreadStalled"; data: { stalled: boolean } };
 
 // ─── Profile management ─────────────────────────────────────────────
 
@@ -122,6 +286,16 @@ export async function createProfile(
   return invoke("create_profile", { profileName, password, displayName });
 }
 
+/** Create a profile with the identity a recovery phrase was made from */
+export async function restoreProfile(
+  profileName: string,
+  password: string,
+  displayName: string,
+  recoveryPhrase: string,
+): Promise<ProfileInfo> {
+  return invoke("restore_profile", { profileName, password, displayName, recoveryPhrase });
+}
+
 export async function loadProfile(
   profileName: string,
   password: string,
@@ -137,6 +311,36 @@ export async function deleteProfile(profileName: string): Promise<void> {
   return invoke("delete_profile", { profileName });
 }
 
+/** Where a profile's data lives, and the size of each part in bytes */
+export interface ProfileStorageInfo {
+  path: string;
+  total_bytes: number;
+  savedata_bytes: number;
+  database_bytes: number;
+  attachments_bytes: number;
+  cache_bytes: number;
+  logs_bytes: number;
+}
+
+export async function getProfileStorageInfo(profileName: string): Promise<ProfileStorageInfo> {
+  return invoke("get_profile_storage_info", { profileName });
+}
+
+/** Moves a profile's directory to a new name */
+export async function renameProfile(profileName: string, newName: string): Promise<void> {
+  return invoke("rename_profile", { profileName, newName });
+}
+
+/** Copies a profile with its messages and attachments; the copy has the same Tox ID */
+export async function duplicateProfile(profileName: string, newName: string): Promise<void> {
+  return invoke("duplicate_profile", { profileName, newName });
+}
+
+/** Re-encrypts the profile and message database with the new password */
+export async function changePassword(currentPassword: string, newPassword: string): Promise<void> {
+  return invoke("change_password", { currentPassword, newPassword });
+}
+
 export async function getToxId(): Promise<string> {
   return invoke("get_tox_id");
 }
@@ -157,6 +361,69 @@ export async function setStatusMessage(message: string): Promise<void> {
   return invoke("set_status_message", { message });
 }
 
+export async function setUserStatus(status: "online" | "away" | "busy"): Promise<void> {
+  return invoke("set_user_status", { status });
+}
+
+export async function setActivity(kind: ActivityKind, name: string, details?: string): Promise<Activity> {
+  return invoke("set_activity", { kind, name, details: details ?? null });
+}
+
+export async function clearActivity(): Promise<void> {
+  return invoke("clear_activity");
+}
+
+export async function getActivity(): Promise<Activity | null> {
+  return invoke("get_activity");
+}
+
+/** `expiresAt` is in Unix seconds */
+export async function setCustomStatus(text: string, emoji?: string, expiresAt?: number): Promise<CustomStatus> {
+  return invoke("set_custom_status", { emoji: emoji ?? null, text, expiresAt: expiresAt ?? null });
+}
+
+export async function clearCustomStatus(): Promise<void> {
+  return invoke("clear_custom_status");
+}
+
+export async function getCustomStatus(): Promise<CustomStatus | null> {
+  return invoke("get_custom_status");
+}
+
+export interface NospamChange {
+  old_address: string;
+  new_address: string;
+  changed_at: string;
+}
+
+/** The nospam part of our Tox ID, 8 hex characters */
+export async function getNospam(): Promise<string> {
+  return invoke("get_nospam");
+}
+
+/** Returns the new Tox ID. Requests sent to the old one stop arriving. */
+export async function setNospam(nospam: string): Promise<string> {
+  return invoke("set_nospam", { nospam });
+}
+
+export async function randomizeNospam(): Promise<string> {
+  return invoke("randomize_nospam");
+}
+
+export async function getNospamHistory(): Promise<NospamChange[]> {
+  return invoke("get_nospam_history");
+}
+
+export interface RecoveryPhrase {
+  phrase: string;
+  qr_svg: string;
+}
+
+/** Anyone with the phrase can take over our identity */
+export async function getRecoveryPhrase(password: string): Promise<RecoveryPhrase> {
+  return invoke("get_recovery_phrase", { password });
+}
+
 // ─── Friends ─────────────────────────────────────────────────────────
 
 export async function addFriend(toxId: string, message: string): Promise<number> {
@@ -179,10 +446,260 @@ export async function getFriends(): Promise<FriendInfo[]> {
   return invoke("get_friends");
 }
 
+export interface FriendsPage {
+  friends: FriendInfo[];
+  total: number;
+  /** Pass to getFriendsChangedSince to get what changed after this page */
+  revision: number;
+}
+
+export async function getFriendsPage(offset: number, limit: number): Promise<FriendsPage> {
+  return invoke("get_friends_page", { offset, limit });
+}
+
+export interface FriendChanges {
+  /** Changed or added friends, in list order */
+  friends: FriendInfo[];
+  removed: number[];
+  revision: number;
+}
+
+export async function getFriendsChangedSince(revision: number): Promise<FriendChanges> {
+  return invoke("get_friends_changed_since", { revision });
+}
+
 export async function getFriendRequests(): Promise<FriendRequest[]> {
   return invoke("get_friend_requests");
 }
 
+export async function getFriendPresenceSummary(friendNumber: number): Promise<PresenceSummary> {
+  return invoke("get_friend_presence_summary", { friendNumber });
+}
+
+/** Retry the connection to a friend; false when only the network was bootstrapped again */
+export async function refreshFriendConnection(friendNumber: number): Promise<boolean> {
+  return invoke("refresh_friend_connection", { friendNumber });
+}
+
+/** Retry the connections to every offline friend; returns the friends retried */
+export async function reconnectAllFriends(): Promise<number[]> {
+  return invoke("reconnect_all_friends");
+}
+
+export interface FriendConnectionInfo {
+  friend_number: number;
+  connection_type: ConnectionType;
+  /** When the connection was last established */
+  last_handshake_at: string | null;
+  last_seen: string | null;
+}
+
+export async function getFriendConnectionInfo(friendNumber: number): Promise<FriendConnectionInfo> {
+  return invoke("get_friend_connection_info", { friendNumber });
+}
+
+// ─── Identity verification ──────────────────────────────────────────
+
+export interface IdentityFingerprint {
+  public_key: string;
+  fingerprint: string;
+  /** SVG markup of the verification QR code */
+  qr_svg: string;
+}
+
+export interface FriendVerification {
+  state: "verified" | "unverified" | "key_changed";
+  public_key: string;
+  fingerprint: string;
+  /** 60 digits both sides see; compare out of band */
+  safety_number: string;
+  verified_at: string | null;
+}
+
+export async function getIdentityFingerprint(): Promise<IdentityFingerprint> {
+  return invoke("get_identity_fingerprint");
+}
+
+export async function getFriendVerification(friendNumber: number): Promise<FriendVerification> {
+  return invoke("get_friend_verification", { friendNumber });
+}
+
+/** `scannedCode` is the friend's QR payload, checked against their key when given */
+export async function verifyFriend(friendNumber: number, scannedCode?: string): Promise<void> {
+  return invoke("verify_friend", { friendNumber, scannedCode });
+}
+
+export async function unverifyFriend(friendNumber: number): Promise<void> {
+  return invoke("unverify_friend", { friendNumber });
+}
+
+// ─── Contact sharing ────────────────────────────────────────────────
+
+/** A toxcord:// link; the message kinds point at a message rather than a contact */
+export type ContactLink =
+  | { kind: "add_friend"; tox_id: string; name: string | null }
+  | { kind: "join_guild"; chat_id: string; name: string | null }
+  | { kind: "channel_message"; chat_id: string; channel: string; message_id: string }
+  | { kind: "direct_message"; public_key: string; message_id: string };
+
+export interface ContactQr {
+  /** toxcord://add/<tox id>?name=... */
+  uri: string;
+  /** SVG markup of the QR code */
+  qr_svg: string;
+}
+
+export async function getToxIdQr(): Promise<ContactQr> {
+  return invoke("get_tox_id_qr");
+}
+
+/** Accepts toxcord:// links, tox: URIs and bare Tox IDs */
+export async function parseContactLink(text: string): Promise<ContactLink> {
+  return invoke("parse_contact_link", { text });
+}
+
+/** Read a QR code from an image file, or from the clipboard when no path is given */
+export async function scanContactQr(path?: string): Promise<ContactLink> {
+  return invoke("scan_contact_qr", { path });
+}
+
+/** Send `contact`'s card to a friend, or our own when `contact` is omitted */
+export async function shareContact(friendNumber: number, contact?: number): Promise<SendMessageResult> {
+  return invoke("share_contact", { friendNumber, contact });
+}
+
+const CONTACT_CARD = /^toxcord:\/\/add\/([0-9a-f]{76})(?:\?name=([^&\s]*))?$/i;
+
+/** The Tox ID and name in a contact card message, if it is one */
+export function parseContactCard(content: string): { toxId: string; name: string | null } | null {
+  const match = CONTACT_CARD.exec(content.trim());
+  if (!match) return null;
+  let name: string | null = null;
+  try {
+    name = match[2] ? decodeURIComponent(match[2].replace(/\+/g, " ")) : null;
+  } catch {
+    // Keep the card without its name
+  }
+  return { toxId: match[1].toUpperCase(), name };
+}
+
+// ─── LAN discovery ──────────────────────────────────────────────────
+
+export interface LanPeer {
+  address: string;
+  public_key: string;
+  name: string;
+  ip: string;
+  is_friend: boolean;
+}
+
+/** Opt-in: announces our Tox ID to everyone on the local network */
+export async function startLanDiscovery(): Promise<void> {
+  return invoke("start_lan_discovery");
+}
+
+export async function stopLanDiscovery(): Promise<void> {
+  return invoke("stop_lan_discovery");
+}
+
+export async function isLanDiscoveryEnabled(): Promise<boolean> {
+  return invoke("is_lan_discovery_enabled");
+}
+
+export async function getLanPeers(): Promise<LanPeer[]> {
+  return invoke("get_lan_peers");
+}
+
+/** Send a friend request to a LAN peer; returns the new friend number */
+export async function addLanPeer(publicKey: string, message?: string): Promise<number> {
+  return invoke("add_lan_peer", { publicKey, message });
+}
+
+// ─── Linked devices ─────────────────────────────────────────────────
+
+export interface LinkedDevice {
+  friend_number: number;
+  public_key: string;
+  device_name: string;
+  linked_at: string;
+  last_synced_at: string | null;
+  received_until: string | null;
+}
+
+/** Mark a friend as another device of ours; both devices must link each other */
+export async function linkDevice(friendNumber: number): Promise<void> {
+  return invoke("link_device", { friendNumber });
+}
+
+export async function unlinkDevice(friendNumber: number): Promise<boolean> {
+  return invoke("unlink_device", { friendNumber });
+}
+
+export async function getLinkedDevices(): Promise<LinkedDevice[]> {
+  return invoke("get_linked_devices");
+}
+
+export async function syncDevice(friendNumber: number): Promise<void> {
+  return invoke("sync_device", { friendNumber });
+}
+
+export interface StatusNote {
+  id: string;
+  text: string;
+  /** Unix seconds */
+  posted_at: number;
+  /** Unix seconds */
+  expires_at: number;
+}
+
+export interface FriendStatus {
+  friend_number: number;
+  friend_name: string;
+  note: StatusNote;
+}
+
+/** Post a note to every friend; it expires after a day */
+export async function postStatusNote(text: string): Promise<StatusNote> {
+  return invoke("post_status_note", { text });
+}
+
+export async function getOwnStatusNotes(): Promise<StatusNote[]> {
+  return invoke("get_own_status_notes");
+}
+
+export async function getFriendStatuses(): Promise<FriendStatus[]> {
+  return invoke("get_friend_statuses");
+}
+
+export async function getFriendGroups(): Promise<FriendGroup[]> {
+  return invoke("get_friend_groups");
+}
+
+export async function createFriendGroup(name: string): Promise<FriendGroup> {
+  return invoke("create_friend_group", { name });
+}
+
+export async function renameFriendGroup(groupId: string, name: string): Promise<void> {
+  return invoke("rename_friend_group", { groupId, name });
+}
+
+export async function deleteFriendGroup(groupId: string): Promise<void> {
+  return invoke("delete_friend_group", { groupId });
+}
+
+export async function reorderFriendGroups(groupIds: string[]): Promise<void> {
+  return invoke("reorder_friend_groups", { groupIds });
+}
+
+export async function setFriendGroupCollapsed(groupId: string, collapsed: boolean): Promise<void> {
+  return invoke("set_friend_group_collapsed", { groupId, collapsed });
+}
+
+/** Pass `null` to ungroup; without a position the friend goes last */
+export async function setFriendGroup(friendNumber: number, groupId: string | null, position?: number): Promise<void> {
+  return invoke("set_friend_group", { friendNumber, groupId, position: position ?? null });
+}
+
 // ─── Direct Messages ────────────────────────────────────────────────
 
 export async function sendDirectMessage(
@@ -192,6 +709,14 @@ export async function sendDirectMessage(
   return invoke("send_direct_message", { friendNumber, message });
 }
 
+/** Messages exchanged during a call, oldest first; defaults to the call in progress */
+export async function getCallMessages(
+  friendNumber: number,
+  sessionId?: string,
+): Promise<DirectMessage[]> {
+  return invoke("get_call_messages", { friendNumber, sessionId: sessionId ?? null });
+}
+
 export async function getDirectMessages(
   friendNumber: number,
   limit?: number,
@@ -200,101 +725,774 @@ export async function getDirectMessages(
   return invoke("get_direct_messages", { friendNumber, limit, beforeTimestamp });
 }
 
-export async function setTyping(
-  friendNumber: number,
-  isTyping: boolean,
-): Promise<void> {
-  return invoke("set_typing", { friendNumber, isTyping });
+/** Position of a message; a page starting there holds the messages before it */
+export interface MessageCursor {
+  timestamp: string;
+  id: string;
 }
 
-export async function markMessagesRead(friendNumber: number): Promise<void> {
-  return invoke("mark_messages_read", { friendNumber });
+export interface MessagePage<T> {
+  /** Newest first */
+  messages: T[];
+  has_more: boolean;
+  /** Messages in the conversation when the page was read */
+  total: number;
 }
 
-// ─── Guilds ─────────────────────────────────────────────────────────
-
-export async function createGuild(name: string): Promise<GuildInfo> {
-  return invoke("create_guild", { name });
+/** A message we sent, for recalling it in the composer */
+export interface OwnMessage {
+  id: string;
+  content: string;
+  message_type: string;
+  timestamp: string;
 }
 
-export async function getGuilds(): Promise<GuildInfo[]> {
-  return invoke("get_guilds");
+/**
+ * Our most recent text messages with a friend or in a channel, newest first.
+ * The first is the one up-arrow edits; the rest are recalled as the user
+ * keeps pressing up.
+ */
+export async function getOwnRecentMessages(
+  targetType: "friend" | "channel",
+  targetId: string,
+  limit?: number,
+): Promise<OwnMessage[]> {
+  return invoke("get_own_recent_messages", { targetType, targetId, limit });
 }
 
-export async function getGuildChannels(guildId: string): Promise<ChannelInfo[]> {
-  return invoke("get_guild_channels", { guildId });
+export async function getDirectMessagesPage(
+  friendNumber: number,
+  limit?: number,
+  before?: MessageCursor,
+): Promise<MessagePage<DirectMessage>> {
+  return invoke("get_direct_messages_page", { friendNumber, limit, before: before ?? null });
 }
 
-export async function createChannel(guildId: string, name: string): Promise<ChannelInfo> {
-  return invoke("create_channel", { guildId, name });
+/** A message, or a date (ISO 8601) standing for the first message from then on */
+export type MessageAnchor =
+  | { kind: "message"; value: string }
+  | { kind: "timestamp"; value: string };
+
+export interface MessageWindow<T> {
+  /** Newest first */
+  messages: T[];
+  /** The message the window is centered on; null if the conversation is empty */
+  target_id: string | null;
+  has_older: boolean;
+  has_newer: boolean;
 }
 
-export async function deleteChannel(guildId: string, channelId: string): Promise<void> {
-  return invoke("delete_channel", { guildId, channelId });
+/** Up to `context` messages either side of a message or a date in a friend's DMs */
+export async function getDirectMessagesAround(
+  friendNumber: number,
+  anchor: MessageAnchor,
+  context?: number,
+): Promise<MessageWindow<DirectMessage>> {
+  return invoke("get_messages_around", { conversation: { type: "friend", id: friendNumber }, anchor, context });
 }
 
-export async function sendChannelMessage(
-  guildId: string,
+/** Up to `context` messages either side of a message or a date in a channel */
+export async function getChannelMessagesAround(
   channelId: string,
-  message: string,
-): Promise<ChannelMessage> {
-  return invoke("send_channel_message", { guildId, channelId, message });
+  anchor: MessageAnchor,
+  context?: number,
+): Promise<MessageWindow<ChannelMessage>> {
+  return invoke("get_messages_around", { conversation: { type: "channel", id: channelId }, anchor, context });
 }
 
-export async function getChannelMessages(
-  channelId: string,
-  limit?: number,
-  beforeTimestamp?: string,
-): Promise<ChannelMessage[]> {
-  return invoke("get_channel_messages", { channelId, limit, beforeTimestamp });
+/** A toxcord:// link to a message. Links to direct messages only work for us. */
+export async function getMessageLink(conversation: Conversation, messageId: string): Promise<string> {
+  return invoke("get_message_link", { conversation, messageId });
 }
 
-export async function inviteToGuild(guildId: string, friendNumber: number): Promise<void> {
-  return invoke("invite_to_guild", { guildId, friendNumber });
+export interface MessageLocation {
+  conversation: Conversation;
+  /** The guild, for a channel message */
+  guild_id: string | null;
+  message_id: string;
 }
 
-export async function acceptGuildInvite(
-  friendNumber: number,
-  inviteData: number[],
-  groupName: string,
-): Promise<GuildInfo> {
-  return invoke("accept_guild_invite", { friendNumber, inviteData, groupName });
+/** Find the conversation a message link (see `parseContactLink`) points at */
+export async function resolveMessageLink(link: ContactLink): Promise<MessageLocation> {
+  return invoke("resolve_message_link", { link });
 }
 
-export async function getGuildMembers(guildId: string): Promise<GuildMember[]> {
-  return invoke("get_guild_members", { guildId });
+export interface MessageRevision {
+  content: string;
+  /** When this version was sent or edited in */
+  written_at: string;
 }
 
-export async function setChannelTopic(
-  guildId: string,
-  channelId: string,
-  topic: string,
-): Promise<void> {
-  return invoke("set_channel_topic", { guildId, channelId, topic });
+export interface MessageHistory {
+  /** The friend, for a direct message */
+  friend_number: number | null;
+  /** Oldest first; the last is what the message says now */
+  revisions: MessageRevision[];
 }
 
-export async function kickMember(guildId: string, peerId: number): Promise<void> {
-  return invoke("kick_member", { guildId, peerId });
+/** What a message said before each edit */
+export async function getMessageHistory(messageId: string): Promise<MessageHistory> {
+  return invoke("get_message_history", { messageId });
 }
 
-export async function setMemberRole(
-  guildId: string,
-  peerId: number,
-  role: string,
+export async function setTyping(
+  friendNumber: number,
+  isTyping: boolean,
 ): Promise<void> {
-  return invoke("set_member_role", { guildId, peerId, role });
+  return invoke("set_typing", { friendNumber, isTyping });
 }
 
-export async function renameGuild(guildId: string, name: string): Promise<void> {
-  return invoke("rename_guild", { guildId, name });
+export async function markMessagesRead(friendNumber: number): Promise<void> {
+  return invoke("mark_messages_read", { friendNumber });
 }
 
-export async function renameChannel(channelId: string, name: string): Promise<void> {
-  return invoke("rename_channel", { channelId, name });
-}
+// ─── Scheduled messages ─────────────────────────────────────────────
 
-export async function leaveGuild(guildId: string): Promise<void> {
-  return invoke("leave_guild", { guildId });
+export interface ScheduledMessage {
+  id: string;
+  target_type: "friend" | "channel";
+  /** Friend number or channel id */
+  target_id: string;
+  guild_id: string | null;
+  content: string;
+  send_at: string;
+  status: "pending" | "sent" | "queued" | "cancelled" | "failed";
+  message_id: string | null;
+  created_at: string;
+}
+
+export type ScheduleTarget =
+  | { type: "friend"; friendNumber: number }
+  | { type: "channel"; guildId: string; channelId: string };
+
+export async function scheduleMessage(
+  target: ScheduleTarget,
+  content: string,
+  sendAt: Date,
+): Promise<ScheduledMessage> {
+  return invoke("schedule_message", {
+    targetType: target.type,
+    targetId: target.type === "friend" ? String(target.friendNumber) : target.channelId,
+    guildId: target.type === "channel" ? target.guildId : null,
+    content,
+    sendAt: sendAt.toISOString(),
+  });
+}
+
+export async function getScheduledMessages(): Promise<ScheduledMessage[]> {
+  return invoke("get_scheduled_messages");
+}
+
+export async function cancelScheduledMessage(id: string): Promise<void> {
+  return invoke("cancel_scheduled_message", { id });
+}
+
+export type ConversationKind = "dm" | "dm_group" | "channel";
+
+export interface RecentConversation {
+  kind: ConversationKind;
+  /** Friend public key, DM group guild id, or channel id */
+  id: string;
+  name: string;
+  friend_number: number | null;
+  guild_id: string | null;
+  guild_name: string | null;
+  last_activity: string | null;
+  /** Unread messages for DMs, unread mentions for DM groups and channels */
+  unread_count: number;
+  starred: boolean;
+}
+
+export async function starConversation(kind: ConversationKind, id: string): Promise<void> {
+  return invoke("star_conversation", { kind, id });
+}
+
+export async function unstarConversation(kind: ConversationKind, id: string): Promise<void> {
+  return invoke("unstar_conversation", { kind, id });
+}
+
+/** Most recently active first, for the quick switcher */
+export async function getRecentConversations(limit?: number): Promise<RecentConversation[]> {
+  return invoke("get_recent_conversations", { limit: limit ?? null });
+}
+
+// ─── Usage statistics ───────────────────────────────────────────────
+
+export interface UsageDay {
+  /** YYYY-MM-DD; empty in totals */
+  day: string;
+  messages_sent: number;
+  messages_received: number;
+  call_seconds: number;
+  /** Completed file transfers */
+  bytes_sent: number;
+  bytes_received: number;
+}
+
+export interface ConversationUsage {
+  kind: ConversationKind;
+  /** Friend public key or channel id */
+  id: string;
+  /** Empty if the friend or channel is gone */
+  name: string;
+  guild_name: string | null;
+  messages_sent: number;
+  messages_received: number;
+  call_seconds: number;
+}
+
+export interface UsageStats {
+  year: number;
+  /** Days with any activity, oldest first */
+  days: UsageDay[];
+  totals: UsageDay;
+  busiest_day: string | null;
+  top_conversations: ConversationUsage[];
+}
+
+/** Statistics kept on this device only; this year if none is given */
+export async function getUsageStats(year?: number): Promise<UsageStats> {
+  return invoke("get_usage_stats", { year: year ?? null });
+}
+
+// ─── Dictionary ─────────────────────────────────────────────────────
+
+/** Words added to the profile's custom spellcheck dictionary */
+export async function getDictionaryWords(): Promise<string[]> {
+  return invoke("get_dictionary_words");
+}
+
+export async function addDictionaryWord(word: string): Promise<void> {
+  return invoke("add_dictionary_word", { word });
+}
+
+export async function removeDictionaryWord(word: string): Promise<void> {
+  return invoke("remove_dictionary_word", { word });
+}
+
+// ─── Auto-reply ─────────────────────────────────────────────────────
+
+/** Reply sent to friends who message us while we're Away or Busy */
+export interface AutoReplySettings {
+  enabled: boolean;
+  message: string;
+  /** A friend gets the reply at most once per this many hours */
+  interval_hours: number;
+}
+
+export async function getAutoReply(): Promise<AutoReplySettings> {
+  return invoke("get_auto_reply");
+}
+
+export async function setAutoReply(settings: AutoReplySettings): Promise<void> {
+  return invoke("set_auto_reply", { settings });
+}
+
+// ─── Conversation Locks ─────────────────────────────────────────────
+
+export interface ConversationLock {
+  enabled: boolean;
+  /** The passphrase has been entered since login */
+  unlocked: boolean;
+  store_plaintext: boolean;
+}
+
+export async function getConversationLock(friendNumber: number): Promise<ConversationLock> {
+  return invoke("get_conversation_lock", { friendNumber });
+}
+
+/**
+ * Encrypts messages with a passphrase agreed with the friend, who must enable it too.
+ * Also used to enter the passphrase again after logging in.
+ */
+export async function enableConversationLock(
+  friendNumber: number,
+  passphrase: string,
+  storePlaintext: boolean,
+): Promise<ConversationLock> {
+  return invoke("enable_conversation_lock", { friendNumber, passphrase, storePlaintext });
+}
+
+export async function disableConversationLock(friendNumber: number): Promise<void> {
+  return invoke("disable_conversation_lock", { friendNumber });
+}
+
+// ─── Disappearing Messages ──────────────────────────────────────────
+
+/** Seconds messages with a friend last; 0 when off */
+export async function getDisappearingTimer(friendNumber: number): Promise<number> {
+  return invoke("get_disappearing_timer", { friendNumber });
+}
+
+/** Also applies on the friend's side; whoever changes it last wins */
+export async function setDisappearingTimer(friendNumber: number, seconds: number): Promise<void> {
+  return invoke("set_disappearing_timer", { friendNumber, seconds });
+}
+
+// ─── Incognito ──────────────────────────────────────────────────────
+
+export interface IncognitoInfo {
+  /** Messages in the conversation aren't stored right now */
+  active: boolean;
+  /** The conversation starts in incognito every time the profile loads */
+  default: boolean;
+}
+
+export async function getIncognito(friendNumber: number): Promise<IncognitoInfo> {
+  return invoke("get_incognito", { friendNumber });
+}
+
+/** Until the profile is closed */
+export async function setIncognito(friendNumber: number, enabled: boolean): Promise<void> {
+  return invoke("set_incognito", { friendNumber, enabled });
+}
+
+/** Also switches the conversation now */
+export async function setIncognitoDefault(friendNumber: number, enabled: boolean): Promise<void> {
+  return invoke("set_incognito_default", { friendNumber, enabled });
+}
+
+// ─── Attachments ────────────────────────────────────────────────────
+
+export async function getMessageAttachments(messageId: string): Promise<Attachment[]> {
+  return invoke("get_message_attachments", { messageId });
+}
+
+/** PNG thumbnail bytes (or the original image if no thumbnail exists) */
+export async function getAttachmentThumbnail(attachmentId: string): Promise<ArrayBuffer> {
+  return invoke("get_attachment_thumbnail", { attachmentId });
+}
+
+export async function openAttachment(attachmentId: string): Promise<void> {
+  return invoke("open_attachment", { attachmentId });
+}
+
+// ─── Media Cache ─────────────────────────────────────────────────────
+
+export interface MediaCacheUsage {
+  count: number;
+  bytes: number;
+  limit_bytes: number;
+}
+
+export async function getMediaCacheUsage(): Promise<MediaCacheUsage> {
+  return invoke("get_media_cache_usage");
+}
+
+export async function setMediaCacheLimit(limitMb: number): Promise<void> {
+  return invoke("set_media_cache_limit", { limitMb });
+}
+
+/** Empties the media cache and returns the bytes freed */
+export async function clearMediaCache(): Promise<number> {
+  return invoke("clear_media_cache");
+}
+
+export interface SendImageResult {
+  id: string;
+  timestamp: string;
+  filename: string;
+  message_type: string;
+  attachment: Attachment;
+  delivered: boolean;
+  error: string | null;
+}
+
+/** Send to a friend, or to a guild channel when guildId and channelId are given */
+export async function sendClipboardImage(target: {
+  friendNumber?: number;
+  guildId?: string;
+  channelId?: string;
+}): Promise<SendImageResult> {
+  return invoke("send_clipboard_image", {
+    friendNumber: target.friendNumber,
+    guildId: target.guildId,
+    channelId: target.channelId,
+  });
+}
+
+export interface ShareFileResult {
+  id: string;
+  channel_id: string;
+  timestamp: string;
+  filename: string;
+  message_type: string;
+  attachment: Attachment;
+}
+
+/** Share a file (up to 8 MB) in a guild channel */
+export async function sendChannelFile(
+  guildId: string,
+  channelId: string,
+  path: string,
+): Promise<ShareFileResult> {
+  return invoke("send_channel_file", { guildId, channelId, path });
+}
+
+/**
+ * Record a voice note and send it to a friend or guild channel. Resolves once
+ * stopVoiceMessage is called or maxDurationSecs runs out; null if cancelled.
+ */
+export async function recordVoiceMessage(
+  target: { friendNumber?: number; guildId?: string; channelId?: string },
+  maxDurationSecs?: number,
+): Promise<SendImageResult | ShareFileResult | null> {
+  return invoke("record_voice_message", {
+    friendNumber: target.friendNumber,
+    guildId: target.guildId,
+    channelId: target.channelId,
+    maxDurationSecs,
+  });
+}
+
+/** Stop the voice note being recorded; send=false discards it */
+export async function stopVoiceMessage(send: boolean): Promise<void> {
+  return invoke("stop_voice_message", { send });
+}
+
+// ─── Stickers ───────────────────────────────────────────────────────
+
+export interface Sticker {
+  hash: string;
+  mime_type: string;
+}
+
+export interface StickerPack {
+  hash: string;
+  name: string;
+  stickers: Sticker[];
+  added_at: string;
+}
+
+/** A sticker as shown in a message; pack_name and path are null for packs we don't have */
+export interface StickerInfo {
+  pack_hash: string;
+  index: number;
+  pack_name: string | null;
+  mime_type: string | null;
+  path: string | null;
+}
+
+/** Install a pack from PNG, GIF or WebP files (up to 512 KB each), in order */
+export async function importStickerPack(name: string, paths: string[]): Promise<StickerPack> {
+  return invoke("import_sticker_pack", { name, paths });
+}
+
+export async function getStickerPacks(): Promise<StickerPack[]> {
+  return invoke("get_sticker_packs");
+}
+
+export async function deleteStickerPack(packHash: string): Promise<boolean> {
+  return invoke("delete_sticker_pack", { packHash });
+}
+
+/** Look up the sticker a message refers to; null if it isn't a sticker reference */
+export async function resolveSticker(reference: string): Promise<StickerInfo | null> {
+  return invoke("resolve_sticker", { reference });
+}
+
+export async function getStickerImage(packHash: string, index: number): Promise<ArrayBuffer> {
+  return invoke("get_sticker_image", { packHash, index });
+}
+
+/**
+ * Send a sticker to a friend or guild channel. It goes as a compact reference
+ * to those known to have the pack, and as an image file otherwise.
+ */
+export async function sendSticker(
+  target: { friendNumber?: number; guildId?: string; channelId?: string },
+  packHash: string,
+  index: number,
+): Promise<{ id: string; timestamp: string; sticker: StickerInfo } & Record<string, unknown>> {
+  return invoke("send_sticker", {
+    friendNumber: target.friendNumber,
+    guildId: target.guildId,
+    channelId: target.channelId,
+    packHash,
+    index,
+  });
+}
+
+// ─── Accessibility ──────────────────────────────────────────────────
+
+export interface AccessibilitySettings {
+  /** Read out messages in the conversation being viewed */
+  tts_enabled: boolean;
+  /** Also read out mentions in other conversations */
+  speak_mentions: boolean;
+  /** Multiple of the voice's normal rate (0.25 to 4) */
+  speech_rate: number;
+}
+
+export type Conversation =
+  | { type: "friend"; id: number }
+  | { type: "channel"; id: string };
+
+export async function getAccessibilitySettings(): Promise<AccessibilitySettings> {
+  return invoke("get_accessibility_settings");
+}
+
+export async function setAccessibilitySettings(settings: AccessibilitySettings): Promise<void> {
+  return invoke("set_accessibility_settings", { settings });
+}
+
+/** The conversation on screen, or null when the window loses focus */
+export async function setFocusedConversation(conversation: Conversation | null): Promise<void> {
+  return invoke("set_focused_conversation", { conversation });
+}
+
+/** Leave a conversation out of text-to-speech announcements, or put it back */
+export async function setConversationTts(conversation: Conversation, muted: boolean): Promise<void> {
+  return invoke("set_conversation_tts", { conversation, muted });
+}
+
+export async function getMutedTtsConversations(): Promise<Conversation[]> {
+  return invoke("get_muted_tts_conversations");
+}
+
+/** Read text aloud, cutting off anything being read */
+export async function speakMessage(text: string): Promise<void> {
+  return invoke("speak_message", { text });
+}
+
+export async function stopSpeaking(): Promise<void> {
+  return invoke("stop_speaking");
+}
+
+export type PaletteAction =
+  | { type: "open_dm"; friend_number: number }
+  | { type: "open_dm_group"; guild_id: string }
+  | { type: "open_channel"; guild_id: string; channel_id: string }
+  | { type: "open_guild"; guild_id: string }
+  | { type: "call_friend"; friend_number: number; video: boolean }
+  | { type: "hang_up"; friend_number: number }
+  | { type: "accept_invite"; invite_id: string }
+  | { type: "set_mute"; muted: boolean }
+  | { type: "set_deafen"; deafened: boolean }
+  | { type: "open_page"; page: "friends" | "settings" };
+
+export interface PaletteCommand {
+  title: string;
+  subtitle: string | null;
+  action: PaletteAction;
+}
+
+/** Command palette entries fuzzy-matching what's been typed, best first */
+export async function queryCommands(prefix: string): Promise<PaletteCommand[]> {
+  return invoke("query_commands", { prefix });
+}
+
+// ─── Guilds ─────────────────────────────────────────────────────────
+
+/**
+ * Creates a guild, needing `password` to join if one is given. A public guild
+ * can be joined by anyone with its chat ID.
+ */
+export async function createGuild(
+  name: string,
+  password?: string | null,
+  isPublic = false,
+): Promise<GuildInfo> {
+  return invoke("create_guild", { name, password, public: isPublic });
+}
+
+export async function getGuilds(): Promise<GuildInfo[]> {
+  return invoke("get_guilds");
+}
+
+export async function getGuildChannels(guildId: string): Promise<ChannelInfo[]> {
+  return invoke("get_guild_channels", { guildId });
+}
+
+export async function createChannel(guildId: string, name: string): Promise<ChannelInfo> {
+  return invoke("create_channel", { guildId, name });
+}
+
+export async function deleteChannel(guildId: string, channelId: string): Promise<void> {
+  return invoke("delete_channel", { guildId, channelId });
+}
+
+export async function reorderChannels(guildId: string, channelIds: string[]): Promise<void> {
+  return invoke("reorder_channels", { guildId, channelIds });
+}
+
+export async function sendChannelMessage(
+  guildId: string,
+  channelId: string,
+  message: string,
+): Promise<ChannelMessage> {
+  return invoke("send_channel_message", { guildId, channelId, message });
+}
+
+export async function getChannelMessages(
+  channelId: string,
+  limit?: number,
+  beforeTimestamp?: string,
+): Promise<ChannelMessage[]> {
+  return invoke("get_channel_messages", { channelId, limit, beforeTimestamp });
+}
+
+export async function getChannelMessagesPage(
+  channelId: string,
+  limit?: number,
+  before?: MessageCursor,
+): Promise<MessagePage<ChannelMessage>> {
+  return invoke("get_channel_messages_page", { channelId, limit, before: before ?? null });
+}
+
+export async function getMentionableMembers(
+  channelId: string,
+  prefix: string,
+  limit?: number,
+): Promise<MentionableMember[]> {
+  return invoke("get_mentionable_members", { channelId, prefix, limit });
+}
+
+export async function markMentionsRead(channelId: string): Promise<void> {
+  return invoke("mark_mentions_read", { channelId });
+}
+
+/** Pass `null` to go back to the default for the channel's guild */
+export async function setChannelNotificationLevel(
+  channelId: string,
+  level: NotificationLevel | null,
+): Promise<void> {
+  return invoke("set_channel_notification_level", { channelId, level });
+}
+
+export async function inviteToGuild(guildId: string, friendNumber: number): Promise<void> {
+  return invoke("invite_to_guild", { guildId, friendNumber });
+}
+
+export async function getGroupInvites(): Promise<GroupInvite[]> {
+  return invoke("get_group_invites");
+}
+
+export async function acceptGroupInvite(inviteId: string, password?: string | null): Promise<GuildInfo> {
+  return invoke("accept_group_invite", { inviteId, password });
+}
+
+export async function declineGroupInvite(inviteId: string): Promise<void> {
+  return invoke("decline_group_invite", { inviteId });
+}
+
+export async function getGroupInviteAutoAccept(friendNumber: number): Promise<boolean> {
+  return invoke("get_group_invite_auto_accept", { friendNumber });
+}
+
+export async function setGroupInviteAutoAccept(friendNumber: number, enabled: boolean): Promise<void> {
+  return invoke("set_group_invite_auto_accept", { friendNumber, enabled });
+}
+
+export async function getGuildMembers(guildId: string): Promise<GuildMember[]> {
+  return invoke("get_guild_members", { guildId });
+}
+
+export async function setChannelTopic(
+  guildId: string,
+  channelId: string,
+  topic: string,
+): Promise<void> {
+  return invoke("set_channel_topic", { guildId, channelId, topic });
+}
+
+export async function kickMember(guildId: string, peerId: number): Promise<void> {
+  return invoke("kick_member", { guildId, peerId });
+}
+
+export async function setMemberRole(
+  guildId: string,
+  peerId: number,
+  role: string,
+): Promise<void> {
+  return invoke("set_member_role", { guildId, peerId, role });
+}
+
+/** Hand the guild to a member; we stay on as a co-founder. Owner only. */
+export async function transferOwnership(guildId: string, peerId: number): Promise<void> {
+  return invoke("transfer_ownership", { guildId, peerId });
+}
+
+export async function renameGuild(guildId: string, name: string): Promise<void> {
+  return invoke("rename_guild", { guildId, name });
+}
+
+export async function renameChannel(channelId: string, name: string): Promise<void> {
+  return invoke("rename_channel", { channelId, name });
+}
+
+export async function leaveGuild(guildId: string): Promise<void> {
+  return invoke("leave_guild", { guildId });
+}
+
+/** Join a public guild from a toxcord://join link */
+export async function joinGuildLink(
+  chatId: string,
+  name?: string | null,
+  password?: string | null,
+): Promise<GuildInfo> {
+  return invoke("join_guild_link", { chatId, name, password });
+}
+
+/**
+ * Join a guild's group again by its chat ID, if it was lost from the profile
+ * or joining was refused for a wrong password
+ */
+export async function rejoinGuild(guildId: string, password?: string | null): Promise<GuildInfo> {
+  return invoke("rejoin_guild", { guildId, password });
+}
+
+/** Sets the password needed to join a guild; empty removes it. Founder only. */
+export async function setGuildPassword(guildId: string, password: string): Promise<void> {
+  return invoke("set_guild_password", { guildId, password });
+}
+
+export async function getGuildPasswordProtected(guildId: string): Promise<boolean> {
+  return invoke("get_guild_password_protected", { guildId });
+}
+
+/** Hides a member's messages, files and status changes, only for us */
+export async function ignoreGuildMember(guildId: string, publicKey: string): Promise<void> {
+  return invoke("ignore_guild_member", { guildId, publicKey });
+}
+
+export async function unignoreGuildMember(guildId: string, publicKey: string): Promise<void> {
+  return invoke("unignore_guild_member", { guildId, publicKey });
+}
+
+/** Whether messages from ignored members are stored, hidden, rather than dropped */
+export async function getKeepIgnoredMessages(): Promise<boolean> {
+  return invoke("get_keep_ignored_messages");
+}
+
+export async function setKeepIgnoredMessages(keep: boolean): Promise<void> {
+  return invoke("set_keep_ignored_messages", { keep });
+}
+
+// ─── Guild directory ────────────────────────────────────────────────
+
+/** A public guild in the directory */
+export interface PublicGuild {
+  chat_id: string;
+  name: string;
+  description: string;
+  /** Listed with the app rather than added by the user */
+  curated: boolean;
+  /** Our guild for this chat ID, if we're in it */
+  joined_guild_id: string | null;
+}
+
+/** Curated public guilds and those the user added; join with joinGuildLink */
+export async function browsePublicGuilds(): Promise<PublicGuild[]> {
+  return invoke("browse_public_guilds");
+}
+
+export async function addPublicGuild(chatId: string, name: string, description: string): Promise<void> {
+  return invoke("add_public_guild", { chatId, name, description });
+}
+
+export async function removePublicGuild(chatId: string): Promise<boolean> {
+  return invoke("remove_public_guild", { chatId });
 }
 
 export async function createDmGroup(
@@ -315,6 +1513,573 @@ export async function getDmGroups(): Promise<GuildInfo[]> {
   return invoke("get_dm_groups");
 }
 
+// ─── Channel bridges ────────────────────────────────────────────────
+
+export type BridgeProtocol = "irc" | "matrix";
+
+export interface ChannelBridgeInfo {
+  channel_id: string;
+  guild_id: string;
+  protocol: BridgeProtocol;
+  /** IRC host[:port], or Matrix homeserver URL */
+  server: string;
+  /** IRC channel, or Matrix room ID/alias */
+  target: string;
+  nickname: string;
+  use_tls: boolean;
+  enabled: boolean;
+  created_at: string;
+  running: boolean;
+}
+
+export interface LinkChannelBridgeOptions {
+  protocol: BridgeProtocol;
+  server: string;
+  target: string;
+  nickname?: string;
+  accessToken?: string;
+  useTls?: boolean;
+}
+
+export async function linkChannelBridge(
+  guildId: string,
+  channelId: string,
+  options: LinkChannelBridgeOptions,
+): Promise<ChannelBridgeInfo> {
+  return invoke("link_channel_bridge", {
+    guildId,
+    channelId,
+    protocol: options.protocol,
+    server: options.server,
+    target: options.target,
+    nickname: options.nickname ?? null,
+    accessToken: options.accessToken ?? null,
+    useTls: options.useTls ?? null,
+  });
+}
+
+export async function unlinkChannelBridge(guildId: string, channelId: string): Promise<void> {
+  return invoke("unlink_channel_bridge", { guildId, channelId });
+}
+
+export async function setChannelBridgeEnabled(guildId: string, channelId: string, enabled: boolean): Promise<void> {
+  return invoke("set_channel_bridge_enabled", { guildId, channelId, enabled });
+}
+
+export async function getChannelBridges(guildId: string): Promise<ChannelBridgeInfo[]> {
+  return invoke("get_channel_bridges", { guildId });
+}
+
+/** Founders and moderators only; 0 turns slow mode off */
+export async function setChannelSlowMode(guildId: string, channelId: string, seconds: number): Promise<void> {
+  return invoke("set_channel_slow_mode", { guildId, channelId, seconds });
+}
+
+/** hide drops matching messages; warn keeps them behind a warning */
+export type FilterAction = "hide" | "warn";
+
+export interface FilterRule {
+  pattern: string;
+  /** Treat pattern as a regular expression instead of a whole word */
+  regex: boolean;
+}
+
+export interface WordFilter {
+  rules: FilterRule[];
+  action: FilterAction;
+}
+
+export async function getWordFilter(guildId: string): Promise<WordFilter> {
+  return invoke("get_word_filter", { guildId });
+}
+
+/** Founders and moderators only */
+export async function setWordFilter(guildId: string, rules: FilterRule[], action: FilterAction): Promise<void> {
+  return invoke("set_word_filter", { guildId, rules, action });
+}
+
+export interface Onboarding {
+  /** Shown when someone new joins; {name} is replaced by their name */
+  welcome_message: string;
+  rules: string;
+  require_rules_ack: boolean;
+}
+
+export interface OnboardingInfo extends Onboarding {
+  /** We've accepted the current rules, or don't have to */
+  may_post: boolean;
+}
+
+export async function getGuildOnboarding(guildId: string): Promise<OnboardingInfo> {
+  return invoke("get_guild_onboarding", { guildId });
+}
+
+/** Founders and moderators only */
+export async function setGuildOnboarding(guildId: string, onboarding: Onboarding): Promise<void> {
+  return invoke("set_guild_onboarding", { guildId, ...onboarding });
+}
+
+/** Only recorded locally; changed rules have to be accepted again */
+export async function acceptGuildRules(guildId: string): Promise<void> {
+  return invoke("accept_guild_rules", { guildId });
+}
+
+/** The NGC role members of a custom role are given */
+export type BaseRole = "moderator" | "user" | "observer";
+
+export interface CustomRole {
+  id: number;
+  name: string;
+  /** #rrggbb */
+  color: string;
+  /** An emoji, or empty */
+  icon: string;
+  base_role: BaseRole;
+}
+
+/** Highest first */
+export async function getGuildRoles(guildId: string): Promise<CustomRole[]> {
+  return invoke("get_guild_roles", { guildId });
+}
+
+/** Founders and moderators only; returns the new role's id */
+export async function createGuildRole(
+  guildId: string,
+  name: string,
+  color: string,
+  icon: string,
+  baseRole: BaseRole,
+): Promise<number> {
+  return invoke("create_guild_role", { guildId, name, color, icon, baseRole });
+}
+
+/** Founders and moderators only */
+export async function updateGuildRole(guildId: string, role: CustomRole): Promise<void> {
+  return invoke("update_guild_role", { guildId, role });
+}
+
+/** Founders and moderators only */
+export async function deleteGuildRole(guildId: string, roleId: number): Promise<void> {
+  return invoke("delete_guild_role", { guildId, roleId });
+}
+
+/** Founders and moderators only; `roleIds` lists every role, highest first */
+export async function reorderGuildRoles(guildId: string, roleIds: number[]): Promise<void> {
+  return invoke("reorder_guild_roles", { guildId, roleIds });
+}
+
+/** Founders and moderators only; null takes the member's role away */
+export async function assignGuildRole(guildId: string, publicKey: string, roleId: number | null): Promise<void> {
+  return invoke("assign_guild_role", { guildId, publicKey, roleId });
+}
+
+// ─── Bulk Deletion ───────────────────────────────────────────────────
+
+/**
+ * Deletes messages older than `beforeTimestamp`, or the last `lastN` messages,
+ * for the whole guild. Founders and moderators only. Returns the local count.
+ */
+export async function purgeChannelMessages(
+  guildId: string,
+  channelId: string,
+  options: { beforeTimestamp: string } | { lastN: number },
+): Promise<number> {
+  return invoke("purge_channel_messages", { guildId, channelId, ...options });
+}
+
+/** Deletes a member's messages in a channel for the whole guild. Founders and moderators only. */
+export async function deleteMessagesFromPeer(guildId: string, channelId: string, publicKey: string): Promise<number> {
+  return invoke("delete_messages_from_peer", { guildId, channelId, publicKey });
+}
+
+/** Founders and moderators only */
+export async function setChannelAnnouncement(guildId: string, channelId: string, announcement: boolean): Promise<void> {
+  return invoke("set_channel_announcement", { guildId, channelId, announcement });
+}
+
+// ─── Polls ───────────────────────────────────────────────────────────
+
+export interface PollInfo {
+  id: string;
+  channel_id: string;
+  creator_public_key: string;
+  creator_name: string;
+  question: string;
+  options: string[];
+  /** Votes per option */
+  tallies: number[];
+  /** Index of the option we voted for */
+  my_vote: number | null;
+  /** Unix seconds */
+  closes_at: number;
+  closed: boolean;
+}
+
+/** Posts a poll in the channel (shown as a message of type "poll" with the poll's id) */
+export async function createPoll(
+  guildId: string,
+  channelId: string,
+  question: string,
+  options: string[],
+  durationSecs: number,
+): Promise<PollInfo> {
+  return invoke("create_poll", { guildId, channelId, question, options, durationSecs });
+}
+
+export async function votePoll(guildId: string, pollId: string, option: number): Promise<PollInfo> {
+  return invoke("vote_poll", { guildId, pollId, option });
+}
+
+export async function getPoll(guildId: string, pollId: string): Promise<PollInfo> {
+  return invoke("get_poll", { guildId, pollId });
+}
+
+// ─── Guild Events ────────────────────────────────────────────────────
+
+export type RsvpStatus = "going" | "interested" | "not_going";
+
+export interface GuildEventInfo {
+  id: string;
+  guild_id: string;
+  creator_public_key: string;
+  creator_name: string;
+  title: string;
+  description: string;
+  /** Unix seconds */
+  start_time: number;
+  going: number;
+  interested: number;
+  my_rsvp: RsvpStatus | null;
+}
+
+export async function createEvent(
+  guildId: string,
+  title: string,
+  description: string,
+  startTime: number,
+): Promise<GuildEventInfo> {
+  return invoke("create_event", { guildId, title, description, startTime });
+}
+
+export async function rsvpEvent(guildId: string, eventId: string, status: RsvpStatus): Promise<GuildEventInfo> {
+  return invoke("rsvp_event", { guildId, eventId, status });
+}
+
+/** Upcoming events, soonest first */
+export async function getGuildEvents(guildId: string): Promise<GuildEventInfo[]> {
+  return invoke("get_guild_events", { guildId });
+}
+
+// ─── Audit Log ───────────────────────────────────────────────────────
+
+export type AuditAction =
+  | "kick"
+  | "role_change"
+  | "channel_create"
+  | "channel_delete"
+  | "channel_rename"
+  | "topic_change"
+  | "message_purge"
+  | "ownership_transfer";
+
+export interface AuditLogEntry {
+  id: number;
+  guild_id: string;
+  action: AuditAction;
+  actor_public_key: string;
+  actor_name: string;
+  /** The member acted on, for kicks, role changes and member purges */
+  target_public_key: string | null;
+  /** Member or channel name */
+  target_name: string;
+  /** New role, new channel name, new topic or how many messages were purged */
+  details: string;
+  created_at: string;
+}
+
+/** Newest first; pass the last entry's id as beforeId for the next page. Founders and moderators only. */
+export async function getAuditLog(guildId: string, limit?: number, beforeId?: number): Promise<AuditLogEntry[]> {
+  return invoke("get_audit_log", { guildId, limit, beforeId });
+}
+
+// ─── System ──────────────────────────────────────────────────────────
+
+export async function enableAutostart(): Promise<void> {
+  return invoke("enable_autostart");
+}
+
+export async function disableAutostart(): Promise<void> {
+  return invoke("disable_autostart");
+}
+
+export async function isAutostartEnabled(): Promise<boolean> {
+  return invoke("is_autostart_enabled");
+}
+
+export interface ShortcutBindings {
+  toggle_mute: string | null;
+  toggle_deafen: string | null;
+  push_to_talk: string | null;
+  answer_or_hangup: string | null;
+}
+
+export async function getShortcuts(): Promise<ShortcutBindings> {
+  return invoke("get_shortcuts");
+}
+
+export async function setShortcuts(bindings: ShortcutBindings): Promise<void> {
+  return invoke("set_shortcuts", { bindings });
+}
+
+/** Last lines of the loaded profile's log, with keys and addresses shortened */
+export async function getRecentLogs(lines?: number): Promise<string[]> {
+  return invoke("get_recent_logs", { lines: lines ?? null });
+}
+
+/** Saves the profile's logs to the downloads folder and returns the file's path */
+export async function exportLogs(): Promise<string> {
+  return invoke("export_logs");
+}
+
+/** Writes the profile to disk now instead of with the next batched save */
+export async function forceSave(): Promise<void> {
+  return invoke("force_save");
+}
+
+/** Saves the profile and ends calls before the computer sleeps */
+export async function systemSuspending(): Promise<void> {
+  return invoke("system_suspending");
+}
+
+/** Reconnects after the computer woke up, if it wasn't noticed already */
+export async function systemResumed(): Promise<void> {
+  return invoke("system_resumed");
+}
+
+// ─── Diagnostics ─────────────────────────────────────────────────────
+
+export interface DeviceStatus {
+  count: number;
+  error: string | null;
+}
+
+export interface DiagnosticsReport {
+  app_version: string;
+  toxcore_version: string;
+  /** null when not logged in */
+  tox: {
+    connection: "None" | "Tcp" | "Udp";
+    friends_online: number;
+    friends_total: number;
+    groups: { group_number: number; name: string; connected: boolean; peer_count: number }[];
+  } | null;
+  proxy: { kind: "none" | "socks5" | "http"; host: string | null; port: number };
+  /** reachable is null when the node wasn't probed, e.g. behind a proxy */
+  bootstrap_nodes: { address: string; port: number; reachable: boolean | null; latency_ms: number | null }[];
+  database: { size_bytes: number | null; schema_version: number | null } | null;
+  audio_input: DeviceStatus;
+  audio_output: DeviceStatus;
+  video_input: DeviceStatus;
+}
+
+export async function getDiagnostics(): Promise<DiagnosticsReport> {
+  return invoke("get_diagnostics");
+}
+
+export interface DhtInfo {
+  connection: "None" | "Tcp" | "Udp";
+  dht_nodes: number;
+  /** DHT nodes that store announcements */
+  announce_nodes: number;
+  dht_capacity: number;
+  /** Connected directly over UDP rather than through a relay */
+  udp_active: boolean;
+  /** null with UDP disabled, e.g. behind a proxy */
+  udp_port: number | null;
+  /** null unless we run a TCP relay */
+  tcp_port: number | null;
+  tcp_relays: number;
+}
+
+export async function getDhtInfo(): Promise<DhtInfo> {
+  return invoke("get_dht_info");
+}
+
+/** "lan_only" skips the public DHT; "offline" pauses Tox entirely */
+export type NetworkMode = "normal" | "lan_only" | "offline";
+
+export async function getNetworkMode(): Promise<NetworkMode> {
+  return invoke("get_network_mode");
+}
+
+export async function setNetworkMode(mode: NetworkMode): Promise<void> {
+  return invoke("set_network_mode", { mode });
+}
+
+/** Power saving slows Tox down while there are no calls or new messages */
+export type PowerSavingMode = "off" | "on_battery" | "always";
+
+export interface PowerSavingInfo {
+  mode: PowerSavingMode;
+  on_battery: boolean;
+}
+
+export async function getPowerSaving(): Promise<PowerSavingInfo> {
+  return invoke("get_power_saving");
+}
+
+export async function setPowerSaving(mode: PowerSavingMode): Promise<void> {
+  return invoke("set_power_saving", { mode });
+}
+
+// ─── Crash reports ───────────────────────────────────────────────────
+
+export interface CrashReport {
+  id: string;
+  time: string;
+  app_version: string;
+  toxcore_version: string;
+  os: string;
+  arch: string;
+  thread: string;
+  message: string;
+  location: string | null;
+  backtrace: string;
+  /** Last lines logged before the crash, with keys shortened */
+  log_tail: string[];
+}
+
+/** Crash reports from earlier runs not yet shared or dismissed */
+export async function getPendingCrashReports(): Promise<CrashReport[]> {
+  return invoke("get_pending_crash_reports");
+}
+
+/** Save a report to the downloads folder and open a new issue to attach it to; returns the file's path */
+export async function submitCrashReport(id: string): Promise<string> {
+  return invoke("submit_crash_report", { id });
+}
+
+export async function dismissCrashReport(id: string): Promise<void> {
+  return invoke("dismiss_crash_report", { id });
+}
+
+// ─── Event recording ─────────────────────────────────────────────────
+
+export interface RecordingInfo {
+  /** File name in the recordings folder, without .json */
+  name: string;
+  description: string;
+  event_count: number;
+  duration_ms: number;
+}
+
+export interface ReplayStatus {
+  recording: boolean;
+  /** Events captured so far by the recording in progress */
+  recorded_events: number;
+  playing: boolean;
+}
+
+export async function getEventReplayStatus(): Promise<ReplayStatus> {
+  return invoke("get_event_replay_status");
+}
+
+/** Start capturing the events the backend sends to the UI */
+export async function startEventRecording(): Promise<void> {
+  return invoke("start_event_recording");
+}
+
+/** Stop capturing and save the recording; returns its path */
+export async function stopEventRecording(description?: string): Promise<string> {
+  return invoke("stop_event_recording", { description: description ?? null });
+}
+
+export async function listEventRecordings(): Promise<RecordingInfo[]> {
+  return invoke("list_event_recordings");
+}
+
+/** Play a recording's events to the UI; only while logged out */
+export async function startEventReplay(name: string, speed?: number): Promise<void> {
+  return invoke("start_event_replay", { name, speed: speed ?? null });
+}
+
+export async function stopEventReplay(): Promise<void> {
+  return invoke("stop_event_replay");
+}
+
+/** A replay reached the end of its recording */
+export function onReplayFinished(callback: () => void): Promise<UnlistenFn> {
+  return listen("replay://finished", () => callback());
+}
+
+// ─── Bot API ─────────────────────────────────────────────────────────
+
+export interface BotApiStatus {
+  enabled: boolean;
+  running: boolean;
+  port: number;
+  token: string;
+}
+
+export async function getBotApiSettings(): Promise<BotApiStatus> {
+  return invoke("get_bot_api_settings");
+}
+
+export async function setBotApiEnabled(enabled: boolean, port?: number): Promise<BotApiStatus> {
+  return invoke("set_bot_api_enabled", { enabled, port: port ?? null });
+}
+
+export async function regenerateBotApiToken(): Promise<BotApiStatus> {
+  return invoke("regenerate_bot_api_token");
+}
+
+// ─── Updates ─────────────────────────────────────────────────────────
+
+export interface UpdateInfo {
+  version: string;
+  current_version: string;
+  /** Changelog */
+  notes: string | null;
+  date: string | null;
+  /** False if this build can't verify packages, so can only point to the release */
+  can_install: boolean;
+}
+
+export interface UpdateSettings {
+  /** Check for new releases in the background */
+  enabled: boolean;
+  /** A release the user chose not to be told about again */
+  skipped_version: string | null;
+}
+
+export interface UpdateProgress {
+  downloaded: number;
+  total: number | null;
+}
+
+/** Check for a newer release now; null if up to date */
+export async function checkForUpdates(): Promise<UpdateInfo | null> {
+  return invoke("check_for_updates");
+}
+
+/** The newer release found by the last check */
+export async function getAvailableUpdate(): Promise<UpdateInfo | null> {
+  return invoke("get_available_update");
+}
+
+/** Download and install the available update; the app restarts when done */
+export async function installUpdate(): Promise<void> {
+  return invoke("install_update");
+}
+
+export async function getUpdateSettings(): Promise<UpdateSettings> {
+  return invoke("get_update_settings");
+}
+
+export async function setUpdateSettings(settings: UpdateSettings): Promise<void> {
+  return invoke("set_update_settings", { settings });
+}
+
 // ─── Event listening ─────────────────────────────────────────────────
 
 export function onToxEvent(callback: (event: ToxEvent) => void): Promise<UnlistenFn> {
@@ -322,3 +2087,28 @@ export function onToxEvent(callback: (event: ToxEvent) => void): Promise<Unliste
     callback(event.payload);
   });
 }
+
+/** A toxcord:// link was opened from outside the app */
+export function onDeepLink(callback: (link: ContactLink) => void): Promise<UnlistenFn> {
+  return listen<ContactLink>("deep-link://open", (event) => {
+    callback(event.payload);
+  });
+}
+
+/** The last opened link not yet handled, clearing it */
+export async function takePendingLink(): Promise<ContactLink | null> {
+  return invoke("take_pending_link");
+}
+
+/** A background check found a newer release */
+export function onUpdateAvailable(callback: (update: UpdateInfo) => void): Promise<UnlistenFn> {
+  return listen<UpdateInfo>("update://available", (event) => {
+    callback(event.payload);
+  });
+}
+
+export function onUpdateProgress(callback: (progress: UpdateProgress) => void): Promise<UnlistenFn> {
+  return listen<UpdateProgress>("update://progress", (event) => {
+    callback(event.payload);
+  });
+}
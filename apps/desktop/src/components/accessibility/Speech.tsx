@@ -0,0 +1,60 @@
+import { useEffect, useState } from "react";
+import * as api from "../../api/tox";
+import type { Conversation } from "../../api/tox";
+
+function sameConversation(a: Conversation, b: Conversation): boolean {
+  return a.type === b.type && a.id === b.id;
+}
+
+/** Header button leaving a conversation out of text-to-speech announcements */
+export function ConversationTtsToggle({ conversation }: { conversation: Conversation }) {
+  const [muted, setMuted] = useState<boolean | null>(null);
+
+  useEffect(() => {
+    setMuted(null);
+    api
+      .getMutedTtsConversations()
+      .then((list) => setMuted(list.some((c) => sameConversation(c, conversation))))
+      .catch(console.error);
+    // Keyed by value, as the object is rebuilt on every render
+  }, [conversation.type, conversation.id]);
+
+  const toggle = async () => {
+    const next = !muted;
+    try {
+      await api.setConversationTts(conversation, next);
+      setMuted(next);
+    } catch (e) {
+      console.error("Failed to save text-to-speech setting:", e);
+    }
+  };
+
+  if (muted === null) return null;
+  return (
+    <button
+      onClick={toggle}
+      className={`flex h-8 items-center justify-center rounded-md px-2 text-sm transition-colors hover:bg-discord-hover ${
+        muted ? "text-discord-muted hover:text-white" : "text-discord-text"
+      }`}
+      title={muted ? "Read out new messages here" : "Don't read out new messages here"}
+      aria-label={muted ? "Read out new messages here" : "Don't read out new messages here"}
+    >
+      {muted ? "🔇" : "🗣️"}
+    </button>
+  );
+}
+
+/** Reads one message aloud; shown when hovering a message */
+export function SpeakButton({ text }: { text: string }) {
+  if (!text.trim()) return null;
+  return (
+    <button
+      onClick={() => api.speakMessage(text).catch(console.error)}
+      className="absolute right-2 top-0 hidden rounded bg-discord-sidebar px-1 text-xs text-discord-muted hover:text-white group-hover/msg:block"
+      title="Read aloud"
+      aria-label="Read aloud"
+    >
+      🔊
+    </button>
+  );
+}
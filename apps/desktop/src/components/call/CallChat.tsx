@@ -0,0 +1,94 @@
+import { useEffect, useRef, useState } from "react";
+import { DirectMessage, getCallMessages, onToxEvent, sendDirectMessage } from "../../api/tox";
+
+interface CallChatProps {
+  friendNumber: number;
+  friendName: string;
+}
+
+/**
+ * Messages sent during the current call. They're ordinary DMs tagged with
+ * the call, so they also show in the conversation and can be found later.
+ */
+export function CallChat({ friendNumber, friendName }: CallChatProps) {
+  const [messages, setMessages] = useState<DirectMessage[]>([]);
+  const [draft, setDraft] = useState("");
+  const [error, setError] = useState("");
+  const bottomRef = useRef<HTMLDivElement>(null);
+
+  useEffect(() => {
+    let unlisten: (() => void) | undefined;
+    const load = () =>
+      getCallMessages(friendNumber)
+        .then(setMessages)
+        .catch((e) => setError(String(e)));
+
+    load();
+    onToxEvent((event) => {
+      if (event.type === "FriendMessage" && event.data.friend_number === friendNumber) {
+        load();
+      }
+    }).then((fn) => {
+      unlisten = fn;
+    });
+
+    return () => {
+      unlisten?.();
+    };
+  }, [friendNumber]);
+
+  useEffect(() => {
+    bottomRef.current?.scrollIntoView({ block: "end" });
+  }, [messages]);
+
+  const send = async () => {
+    const text = draft.trim();
+    if (!text) return;
+    setError("");
+    try {
+      await sendDirectMessage(friendNumber, text);
+      setDraft("");
+      setMessages(await getCallMessages(friendNumber));
+    } catch (e) {
+      setError(String(e));
+    }
+  };
+
+  return (
+    <div className="flex w-80 flex-col border-l border-[#3f4147] bg-[#2b2d31]">
+      <div className="border-b border-[#3f4147] px-4 py-3 text-xs font-bold uppercase text-discord-muted">
+        Call Chat
+      </div>
+      <div className="flex-1 space-y-3 overflow-y-auto p-4">
+        {messages.length === 0 && (
+          <p className="text-sm text-discord-muted">
+            Messages and links shared during this call will show here.
+          </p>
+        )}
+        {messages.map((m) => (
+          <div key={m.id}>
+            <span className="text-xs font-semibold text-white">
+              {m.is_outgoing ? "You" : friendName}
+            </span>
+            <p className="whitespace-pre-wrap break-words text-sm text-discord-text">
+              {m.locked ? "Locked message" : m.content}
+            </p>
+          </div>
+        ))}
+        <div ref={bottomRef} />
+      </div>
+      {error && <p className="px-4 text-xs text-discord-red">{error}</p>}
+      <div className="p-3">
+        <input
+          value={draft}
+          onChange={(e) => setDraft(e.target.value)}
+          onKeyDown={(e) => {
+            if (e.key === "Enter") send();
+          }}
+          placeholder={`Message ${friendName}`}
+          className="w-full rounded-md bg-discord-input px-3 py-2 text-sm text-discord-text outline-none"
+        />
+      </div>
+    </div>
+  );
+}
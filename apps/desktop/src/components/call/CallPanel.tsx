@@ -1,5 +1,6 @@
 import { useState } from "react";
 import { useCallStore } from "../../stores/callStore";
+import { toggleCallPip } from "../../api/calls";
 import { LocalPreview } from "../video/LocalPreview";
 import { RemoteVideo } from "../video/RemoteVideo";
 import { DevicePicker, SettingsIcon } from "./DevicePicker";
@@ -178,6 +179,19 @@ export function CallPanel() {
             <ExpandIcon className="h-4 w-4 text-white" />
           </button>
 
+          {/* Picture-in-picture */}
+          <button
+            onClick={() =>
+              toggleCallPip(activeCall.friendNumber).catch((e) =>
+                console.error("Failed to toggle call window:", e),
+              )
+            }
+            className="flex h-8 w-8 items-center justify-center rounded-full bg-[#3c3f45] transition-colors hover:bg-[#4e5058]"
+            title="Pop out video"
+          >
+            <PopOutIcon className="h-4 w-4 text-white" />
+          </button>
+
           {/* Hangup */}
           <button
             onClick={() => hangup()}
@@ -272,6 +286,14 @@ function ScreenShareIcon({ className }: { className?: string }) {
   );
 }
 
+function PopOutIcon({ className }: { className?: string }) {
+  return (
+    <svg className={className} fill="none" stroke="currentColor" viewBox="0 0 24 24" strokeWidth={2}>
+      <path strokeLinecap="round" strokeLinejoin="round" d="M14 4h6v6m0-6l-8 8M10 6H5a1 1 0 00-1 1v12a1 1 0 001 1h12a1 1 0 001-1v-5" />
+    </svg>
+  );
+}
+
 function ExpandIcon({ className }: { className?: string }) {
   return (
     <svg className={className} fill="none" stroke="currentColor" viewBox="0 0 24 24" strokeWidth={2}>
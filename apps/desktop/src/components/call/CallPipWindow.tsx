@@ -0,0 +1,53 @@
+import { useEffect, useState } from "react";
+import { onToxAvEvent, toggleCallPip } from "../../api/calls";
+import { RemoteVideo } from "../video/RemoteVideo";
+
+interface CallPipWindowProps {
+  friendNumber: number;
+}
+
+/**
+ * Contents of the always-on-top picture-in-picture window. It runs its own
+ * copy of the frontend, so it follows the call from events rather than the
+ * main window's call store. The backend closes it when the call ends.
+ */
+export function CallPipWindow({ friendNumber }: CallPipWindowProps) {
+  const [sendingVideo, setSendingVideo] = useState(true);
+
+  useEffect(() => {
+    let unlisten: (() => void) | undefined;
+
+    onToxAvEvent((event) => {
+      if (event.type === "CallStateChange" && event.data.friend_number === friendNumber) {
+        setSendingVideo(event.data.sending_video);
+      }
+    }).then((fn) => {
+      unlisten = fn;
+    });
+
+    return () => {
+      unlisten?.();
+    };
+  }, [friendNumber]);
+
+  return (
+    <div className="group relative h-full w-full bg-black">
+      {sendingVideo ? (
+        <RemoteVideo friendNumber={friendNumber} className="h-full w-full rounded-none" />
+      ) : (
+        <div className="flex h-full w-full items-center justify-center text-sm text-discord-muted">
+          Camera off
+        </div>
+      )}
+      <button
+        onClick={() =>
+          toggleCallPip(friendNumber).catch((e) => console.error("Failed to close call window:", e))
+        }
+        className="absolute right-2 top-2 hidden rounded-md bg-black/60 px-2 py-1 text-xs font-medium text-white transition-colors hover:bg-black/80 group-hover:block"
+        title="Back to Toxcord"
+      >
+        Close
+      </button>
+    </div>
+  );
+}
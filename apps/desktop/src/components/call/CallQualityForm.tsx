@@ -0,0 +1,70 @@
+import type { CallPreferences } from "../../api/calls";
+
+const AUDIO_BIT_RATES = [16, 32, 48, 64, 96, 128];
+const VIDEO_BIT_RATES = [200, 400, 800, 1500, 3000, 5000];
+
+interface CallQualityFormProps {
+  value: CallPreferences;
+  onChange: (value: CallPreferences) => void;
+}
+
+/** Bitrate and camera choices, shared by the defaults and per-friend settings */
+export function CallQualityForm({ value, onChange }: CallQualityFormProps) {
+  return (
+    <div className="space-y-3">
+      <BitRateSelect
+        label="Audio quality"
+        rates={AUDIO_BIT_RATES}
+        value={value.audio_bit_rate}
+        onChange={(audio_bit_rate) => onChange({ ...value, audio_bit_rate })}
+      />
+      <BitRateSelect
+        label="Video quality"
+        rates={VIDEO_BIT_RATES}
+        value={value.video_bit_rate}
+        onChange={(video_bit_rate) => onChange({ ...value, video_bit_rate })}
+      />
+      <label className="flex items-center justify-between gap-4">
+        <span className="text-sm text-discord-text">Start calls with my camera on</span>
+        <input
+          type="checkbox"
+          checked={value.video_by_default}
+          onChange={(e) => onChange({ ...value, video_by_default: e.target.checked })}
+          className="h-4 w-4 rounded border-discord-muted bg-discord-input text-discord-blurple focus:ring-discord-blurple"
+        />
+      </label>
+    </div>
+  );
+}
+
+function BitRateSelect({
+  label,
+  rates,
+  value,
+  onChange,
+}: {
+  label: string;
+  rates: number[];
+  value: number;
+  onChange: (value: number) => void;
+}) {
+  // Keep a value set elsewhere selectable
+  const options = rates.includes(value) ? rates : [...rates, value].sort((a, b) => a - b);
+
+  return (
+    <label className="flex items-center justify-between gap-4">
+      <span className="text-sm text-discord-text">{label}</span>
+      <select
+        value={value}
+        onChange={(e) => onChange(Number(e.target.value))}
+        className="rounded-md bg-discord-input px-3 py-2 text-sm text-discord-text outline-none"
+      >
+        {options.map((rate) => (
+          <option key={rate} value={rate}>
+            {rate} kbit/s
+          </option>
+        ))}
+      </select>
+    </label>
+  );
+}
@@ -0,0 +1,171 @@
+import { useCallback, useEffect, useState } from "react";
+import { useGuildStore } from "../../stores/guildStore";
+import { useNavigationStore } from "../../stores/navigationStore";
+import * as api from "../../api/tox";
+import type { PublicGuild } from "../../api/tox";
+
+export function DiscoverGuildsModal({ onClose }: { onClose: () => void }) {
+  const loadGuilds = useGuildStore((s) => s.loadGuilds);
+  const openGuild = useNavigationStore((s) => s.openGuild);
+  const [guilds, setGuilds] = useState<PublicGuild[]>([]);
+  const [joining, setJoining] = useState<string | null>(null);
+  const [error, setError] = useState("");
+  const [chatId, setChatId] = useState("");
+  const [name, setName] = useState("");
+  const [description, setDescription] = useState("");
+
+  const refresh = useCallback(() => {
+    api.browsePublicGuilds().then(setGuilds).catch((e) => setError(String(e)));
+  }, []);
+
+  useEffect(() => {
+    refresh();
+  }, [refresh]);
+
+  const handleJoin = async (guild: PublicGuild) => {
+    if (guild.joined_guild_id) {
+      openGuild(guild.joined_guild_id);
+      onClose();
+      return;
+    }
+    setJoining(guild.chat_id);
+    setError("");
+    try {
+      const joined = await api.joinGuildLink(guild.chat_id, guild.name);
+      await loadGuilds();
+      openGuild(joined.id);
+      onClose();
+    } catch (e) {
+      setError(String(e));
+    } finally {
+      setJoining(null);
+    }
+  };
+
+  const handleAdd = async () => {
+    setError("");
+    try {
+      await api.addPublicGuild(chatId.trim(), name.trim(), description.trim());
+      setChatId("");
+      setName("");
+      setDescription("");
+      refresh();
+    } catch (e) {
+      setError(String(e));
+    }
+  };
+
+  const handleRemove = async (guild: PublicGuild) => {
+    try {
+      await api.removePublicGuild(guild.chat_id);
+      refresh();
+    } catch (e) {
+      setError(String(e));
+    }
+  };
+
+  return (
+    <div className="fixed inset-0 z-50 flex items-center justify-center bg-black/60">
+      <div className="w-[520px] rounded-lg bg-discord-sidebar p-6">
+        <h2 className="mb-1 text-xl font-bold text-white">Discover Public Servers</h2>
+        <p className="mb-4 text-sm text-discord-muted">
+          Public servers can be joined by anyone who knows their chat ID.
+        </p>
+
+        {error && <p className="mb-3 text-sm text-discord-red">{error}</p>}
+
+        <div className="max-h-72 space-y-1 overflow-y-auto">
+          {guilds.length === 0 ? (
+            <p className="py-4 text-center text-sm text-discord-muted">
+              No public servers listed yet. Add one by its chat ID below.
+            </p>
+          ) : (
+            guilds.map((guild) => (
+              <div
+                key={guild.chat_id}
+                className="flex items-center gap-3 rounded-md px-3 py-2 hover:bg-discord-hover"
+              >
+                <div className="flex h-10 w-10 shrink-0 items-center justify-center rounded-2xl bg-discord-channel text-sm font-bold text-white">
+                  {guild.name[0]?.toUpperCase() ?? "?"}
+                </div>
+                <div className="min-w-0 flex-1">
+                  <p className="truncate text-sm font-semibold text-white">{guild.name}</p>
+                  {guild.description && (
+                    <p className="truncate text-xs text-discord-muted">{guild.description}</p>
+                  )}
+                  <button
+                    onClick={() => navigator.clipboard.writeText(guild.chat_id)}
+                    className="truncate font-mono text-[10px] text-discord-muted hover:text-white"
+                    title="Copy chat ID"
+                  >
+                    {guild.chat_id}
+                  </button>
+                </div>
+                {!guild.curated && (
+                  <button
+                    onClick={() => handleRemove(guild)}
+                    className="text-xs text-discord-muted hover:text-discord-red"
+                    title="Remove from the directory"
+                  >
+                    Remove
+                  </button>
+                )}
+                <button
+                  onClick={() => handleJoin(guild)}
+                  disabled={joining !== null}
+                  className="rounded-md bg-discord-blurple px-3 py-1 text-sm font-medium text-white transition-colors hover:bg-discord-blurple/80 disabled:opacity-50"
+                >
+                  {guild.joined_guild_id ? "Open" : joining === guild.chat_id ? "Joining..." : "Join"}
+                </button>
+              </div>
+            ))
+          )}
+        </div>
+
+        <div className="mt-4 space-y-2 border-t border-discord-channel pt-4">
+          <label className="block text-xs font-bold uppercase text-discord-muted">
+            Add a public server
+          </label>
+          <input
+            value={chatId}
+            onChange={(e) => setChatId(e.target.value)}
+            placeholder="Chat ID (64 hex digits)"
+            className="w-full rounded-md bg-discord-input px-3 py-2 font-mono text-xs text-white placeholder-discord-muted outline-none focus:ring-2 focus:ring-discord-blurple"
+          />
+          <div className="flex gap-2">
+            <input
+              value={name}
+              onChange={(e) => setName(e.target.value)}
+              placeholder="Name"
+              maxLength={48}
+              className="w-1/3 rounded-md bg-discord-input px-3 py-2 text-sm text-white placeholder-discord-muted outline-none focus:ring-2 focus:ring-discord-blurple"
+            />
+            <input
+              value={description}
+              onChange={(e) => setDescription(e.target.value)}
+              placeholder="Description (optional)"
+              maxLength={200}
+              className="flex-1 rounded-md bg-discord-input px-3 py-2 text-sm text-white placeholder-discord-muted outline-none focus:ring-2 focus:ring-discord-blurple"
+            />
+            <button
+              onClick={handleAdd}
+              disabled={!chatId.trim() || !name.trim()}
+              className="rounded-md bg-discord-green px-3 py-2 text-sm font-medium text-white disabled:opacity-50"
+            >
+              Add
+            </button>
+          </div>
+        </div>
+
+        <div className="mt-4 flex justify-end">
+          <button
+            onClick={onClose}
+            className="rounded-md px-4 py-2 text-sm font-medium text-discord-muted hover:text-white"
+          >
+            Close
+          </button>
+        </div>
+      </div>
+    </div>
+  );
+}
@@ -8,18 +8,20 @@ type Mode = "select" | "server" | "dm_group";
 export function GuildCreateModal({ onClose }: { onClose: () => void }) {
   const [mode, setMode] = useState<Mode>("select");
   const [name, setName] = useState("");
+  const [password, setPassword] = useState("");
+  const [isPublic, setIsPublic] = useState(false);
   const [selectedFriends, setSelectedFriends] = useState<number[]>([]);
   const [isCreating, setIsCreating] = useState(false);
   const [error, setError] = useState("");
   const createGuild = useGuildStore((s) => s.createGuild);
   const createDmGroup = useGuildStore((s) => s.createDmGroup);
   const friends = useFriendStore((s) => s.friends);
-  const loadFriends = useFriendStore((s) => s.loadFriends);
+  const syncFriends = useFriendStore((s) => s.syncFriends);
   const openDmGroup = useNavigationStore((s) => s.openDmGroup);
 
   useEffect(() => {
-    loadFriends();
-  }, [loadFriends]);
+    syncFriends();
+  }, [syncFriends]);
 
   const onlineFriends = friends.filter((f) => f.connection_status !== "none");
 
@@ -31,7 +33,7 @@ export function GuildCreateModal({ onClose }: { onClose: () => void }) {
     setError("");
     try {
       if (mode === "server") {
-        await createGuild(trimmed);
+        await createGuild(trimmed, password, isPublic);
       } else if (mode === "dm_group") {
         if (selectedFriends.length === 0) {
           setError("Please select at least one friend");
@@ -162,6 +164,31 @@ export function GuildCreateModal({ onClose }: { onClose: () => void }) {
           />
         </div>
 
+        {mode === "server" && (
+          <div className="mb-4">
+            <label className="mb-2 block text-xs font-bold uppercase text-discord-muted">
+              Password (optional)
+            </label>
+            <input
+              type="password"
+              value={password}
+              onChange={(e) => setPassword(e.target.value)}
+              placeholder="Needed to join, if set"
+              className="w-full rounded-md bg-discord-input px-3 py-2 text-sm text-white placeholder-discord-muted outline-none focus:ring-2 focus:ring-discord-blurple"
+              maxLength={32}
+            />
+            <label className="mt-3 flex cursor-pointer items-center gap-2 text-sm text-discord-text">
+              <input
+                type="checkbox"
+                checked={isPublic}
+                onChange={(e) => setIsPublic(e.target.checked)}
+                className="h-4 w-4 rounded border-discord-muted bg-discord-input text-discord-blurple focus:ring-discord-blurple"
+              />
+              Public — anyone with the chat ID can join, no invite needed
+            </label>
+          </div>
+        )}
+
         {mode === "dm_group" && (
           <div className="mb-4">
             <label className="mb-2 block text-xs font-bold uppercase text-discord-muted">
@@ -0,0 +1,94 @@
+import { useEffect, useState } from "react";
+import * as api from "../../api/tox";
+
+export function GuildOnboardingModal({ guildId, onClose }: { guildId: string; onClose: () => void }) {
+  const [welcomeMessage, setWelcomeMessage] = useState("");
+  const [rules, setRules] = useState("");
+  const [requireAck, setRequireAck] = useState(false);
+  const [error, setError] = useState("");
+  const [saving, setSaving] = useState(false);
+
+  useEffect(() => {
+    api
+      .getGuildOnboarding(guildId)
+      .then((info) => {
+        setWelcomeMessage(info.welcome_message);
+        setRules(info.rules);
+        setRequireAck(info.require_rules_ack);
+      })
+      .catch((e) => setError(String(e)));
+  }, [guildId]);
+
+  const handleSave = async () => {
+    setSaving(true);
+    setError("");
+    try {
+      await api.setGuildOnboarding(guildId, {
+        welcome_message: welcomeMessage,
+        rules,
+        require_rules_ack: requireAck,
+      });
+      onClose();
+    } catch (e) {
+      setError(String(e));
+    } finally {
+      setSaving(false);
+    }
+  };
+
+  return (
+    <div className="fixed inset-0 z-50 flex items-center justify-center bg-black/60">
+      <div className="w-[520px] rounded-lg bg-discord-sidebar p-6">
+        <h2 className="mb-4 text-xl font-bold text-white">Welcome & Rules</h2>
+
+        {error && <p className="mb-3 text-sm text-discord-red">{error}</p>}
+
+        <label className="mb-1 block text-xs font-bold uppercase text-discord-muted">Welcome message</label>
+        <input
+          value={welcomeMessage}
+          onChange={(e) => setWelcomeMessage(e.target.value)}
+          placeholder="Welcome, {name}!"
+          maxLength={500}
+          className="mb-1 w-full rounded-md bg-discord-input px-3 py-2 text-sm text-white placeholder-discord-muted outline-none focus:ring-2 focus:ring-discord-blurple"
+        />
+        <p className="mb-4 text-xs text-discord-muted">
+          Shown to everyone when someone new joins. {"{name}"} becomes their name.
+        </p>
+
+        <label className="mb-1 block text-xs font-bold uppercase text-discord-muted">Rules</label>
+        <textarea
+          value={rules}
+          onChange={(e) => setRules(e.target.value)}
+          rows={8}
+          maxLength={4000}
+          className="mb-3 w-full resize-none rounded-md bg-discord-input px-3 py-2 text-sm text-white outline-none focus:ring-2 focus:ring-discord-blurple"
+        />
+
+        <label className="flex items-center gap-2 text-sm text-discord-text">
+          <input
+            type="checkbox"
+            checked={requireAck}
+            onChange={(e) => setRequireAck(e.target.checked)}
+          />
+          Members must accept the rules before posting
+        </label>
+
+        <div className="mt-6 flex justify-end gap-2">
+          <button
+            onClick={onClose}
+            className="rounded-md px-4 py-2 text-sm font-medium text-discord-muted hover:text-white"
+          >
+            Cancel
+          </button>
+          <button
+            onClick={handleSave}
+            disabled={saving}
+            className="rounded-md bg-discord-blurple px-4 py-2 text-sm font-medium text-white hover:bg-discord-blurple/80 disabled:opacity-50"
+          >
+            {saving ? "Saving..." : "Save"}
+          </button>
+        </div>
+      </div>
+    </div>
+  );
+}
@@ -0,0 +1,176 @@
+import { useCallback, useEffect, useState } from "react";
+import { useGuildStore } from "../../stores/guildStore";
+import * as api from "../../api/tox";
+import type { BaseRole, CustomRole } from "../../api/tox";
+
+const BASE_ROLES: { value: BaseRole; label: string }[] = [
+  { value: "moderator", label: "Moderator" },
+  { value: "user", label: "User" },
+  { value: "observer", label: "Observer" },
+];
+
+export function GuildRolesModal({ guildId, onClose }: { guildId: string; onClose: () => void }) {
+  const loadMembers = useGuildStore((s) => s.loadMembers);
+  const [roles, setRoles] = useState<CustomRole[]>([]);
+  const [error, setError] = useState("");
+  const [name, setName] = useState("");
+  const [color, setColor] = useState("#5865f2");
+  const [icon, setIcon] = useState("");
+  const [baseRole, setBaseRole] = useState<BaseRole>("user");
+
+  const refresh = useCallback(() => {
+    api.getGuildRoles(guildId).then(setRoles).catch((e) => setError(String(e)));
+    loadMembers(guildId);
+  }, [guildId, loadMembers]);
+
+  useEffect(() => {
+    refresh();
+  }, [refresh]);
+
+  const run = async (action: () => Promise<unknown>) => {
+    setError("");
+    try {
+      await action();
+      refresh();
+    } catch (e) {
+      setError(String(e));
+    }
+  };
+
+  const handleCreate = () =>
+    run(async () => {
+      await api.createGuildRole(guildId, name.trim(), color, icon.trim(), baseRole);
+      setName("");
+      setIcon("");
+    });
+
+  const handleMove = (index: number, by: number) => {
+    const order = roles.map((r) => r.id);
+    const [id] = order.splice(index, 1);
+    order.splice(index + by, 0, id);
+    run(() => api.reorderGuildRoles(guildId, order));
+  };
+
+  return (
+    <div className="fixed inset-0 z-50 flex items-center justify-center bg-black/60">
+      <div className="w-[520px] rounded-lg bg-discord-sidebar p-6">
+        <h2 className="mb-1 text-xl font-bold text-white">Roles</h2>
+        <p className="mb-4 text-sm text-discord-muted">
+          Roles give members a colored name. Each one also sets what its members can do.
+        </p>
+
+        {error && <p className="mb-3 text-sm text-discord-red">{error}</p>}
+
+        <div className="max-h-72 space-y-1 overflow-y-auto">
+          {roles.length === 0 ? (
+            <p className="py-4 text-center text-sm text-discord-muted">No roles yet.</p>
+          ) : (
+            roles.map((role, index) => (
+              <div key={role.id} className="flex items-center gap-2 rounded-md px-3 py-2 hover:bg-discord-hover">
+                <input
+                  type="color"
+                  value={role.color}
+                  onChange={(e) => run(() => api.updateGuildRole(guildId, { ...role, color: e.target.value }))}
+                  className="h-6 w-6 shrink-0 cursor-pointer bg-transparent"
+                  title="Color"
+                />
+                <span className="w-6 text-center">{role.icon}</span>
+                <span className="min-w-0 flex-1 truncate text-sm font-semibold" style={{ color: role.color }}>
+                  {role.name}
+                </span>
+                <select
+                  value={role.base_role}
+                  onChange={(e) =>
+                    run(() => api.updateGuildRole(guildId, { ...role, base_role: e.target.value as BaseRole }))
+                  }
+                  className="rounded bg-discord-input px-1 py-0.5 text-xs text-white"
+                  title="What members with this role can do"
+                >
+                  {BASE_ROLES.map((r) => (
+                    <option key={r.value} value={r.value}>
+                      {r.label}
+                    </option>
+                  ))}
+                </select>
+                <button
+                  onClick={() => handleMove(index, -1)}
+                  disabled={index === 0}
+                  className="text-xs text-discord-muted hover:text-white disabled:opacity-30"
+                  title="Move up"
+                >
+                  ▲
+                </button>
+                <button
+                  onClick={() => handleMove(index, 1)}
+                  disabled={index === roles.length - 1}
+                  className="text-xs text-discord-muted hover:text-white disabled:opacity-30"
+                  title="Move down"
+                >
+                  ▼
+                </button>
+                <button
+                  onClick={() => run(() => api.deleteGuildRole(guildId, role.id))}
+                  className="text-xs text-discord-muted hover:text-discord-red"
+                >
+                  Delete
+                </button>
+              </div>
+            ))
+          )}
+        </div>
+
+        <div className="mt-4 space-y-2 border-t border-discord-channel pt-4">
+          <label className="block text-xs font-bold uppercase text-discord-muted">New role</label>
+          <div className="flex gap-2">
+            <input
+              type="color"
+              value={color}
+              onChange={(e) => setColor(e.target.value)}
+              className="h-9 w-9 shrink-0 cursor-pointer bg-transparent"
+            />
+            <input
+              value={icon}
+              onChange={(e) => setIcon(e.target.value)}
+              placeholder="🙂"
+              className="w-12 rounded-md bg-discord-input px-2 py-2 text-center text-sm text-white placeholder-discord-muted outline-none focus:ring-2 focus:ring-discord-blurple"
+            />
+            <input
+              value={name}
+              onChange={(e) => setName(e.target.value)}
+              placeholder="Name"
+              maxLength={32}
+              className="flex-1 rounded-md bg-discord-input px-3 py-2 text-sm text-white placeholder-discord-muted outline-none focus:ring-2 focus:ring-discord-blurple"
+            />
+            <select
+              value={baseRole}
+              onChange={(e) => setBaseRole(e.target.value as BaseRole)}
+              className="rounded-md bg-discord-input px-2 text-sm text-white"
+            >
+              {BASE_ROLES.map((r) => (
+                <option key={r.value} value={r.value}>
+                  {r.label}
+                </option>
+              ))}
+            </select>
+            <button
+              onClick={handleCreate}
+              disabled={!name.trim()}
+              className="rounded-md bg-discord-green px-3 py-2 text-sm font-medium text-white disabled:opacity-50"
+            >
+              Add
+            </button>
+          </div>
+        </div>
+
+        <div className="mt-4 flex justify-end">
+          <button
+            onClick={onClose}
+            className="rounded-md px-4 py-2 text-sm font-medium text-discord-muted hover:text-white"
+          >
+            Close
+          </button>
+        </div>
+      </div>
+    </div>
+  );
+}
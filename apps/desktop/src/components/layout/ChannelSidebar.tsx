@@ -4,11 +4,27 @@ import { useFriendStore } from "../../stores/friendStore";
 import { useNavigationStore } from "../../stores/navigationStore";
 import { useMessageStore } from "../../stores/messageStore";
 import { useGuildStore } from "../../stores/guildStore";
+import { useCallStore } from "../../stores/callStore";
 import { InviteModal } from "../guild/InviteModal";
+import { GuildRolesModal } from "../guild/GuildRolesModal";
+import { GuildOnboardingModal } from "../guild/GuildOnboardingModal";
 import * as api from "../../api/tox";
+import type { ChannelInfo, NotificationLevel } from "../../api/tox";
 
 const EMPTY_CHANNELS: never[] = [];
 
+const NOTIFICATION_LABELS: Record<NotificationLevel, string> = {
+  all: "Notifying for all messages",
+  mentions: "Notifying for mentions only",
+  nothing: "Muted",
+};
+
+const NEXT_NOTIFICATION_LEVEL: Record<NotificationLevel, NotificationLevel> = {
+  all: "mentions",
+  mentions: "nothing",
+  nothing: "all",
+};
+
 export function ChannelSidebar() {
   const selectedGuildId = useNavigationStore((s) => s.selectedGuildId);
   const currentPage = useNavigationStore((s) => s.currentPage);
@@ -33,11 +49,14 @@ function GuildChannelSidebar({ guildId }: { guildId: string }) {
   const renameGuild = useGuildStore((s) => s.renameGuild);
   const renameChannel = useGuildStore((s) => s.renameChannel);
   const leaveGuild = useGuildStore((s) => s.leaveGuild);
+  const loadGuilds = useGuildStore((s) => s.loadGuilds);
   const selectedChannelId = useNavigationStore((s) => s.selectedChannelId);
   const openChannel = useNavigationStore((s) => s.openChannel);
   const setPage = useNavigationStore((s) => s.setPage);
 
   const [showInviteModal, setShowInviteModal] = useState(false);
+  const [showRolesModal, setShowRolesModal] = useState(false);
+  const [showOnboardingModal, setShowOnboardingModal] = useState(false);
   const [showCreateChannel, setShowCreateChannel] = useState(false);
   const [newChannelName, setNewChannelName] = useState("");
   const [showGuildMenu, setShowGuildMenu] = useState(false);
@@ -102,6 +121,15 @@ function GuildChannelSidebar({ guildId }: { guildId: string }) {
     setRenamingChannelId(null);
   };
 
+  const handleCycleNotifications = async (channel: ChannelInfo) => {
+    try {
+      await api.setChannelNotificationLevel(channel.id, NEXT_NOTIFICATION_LEVEL[channel.notification_level]);
+      await loadChannels(guildId);
+    } catch {
+      // ignore
+    }
+  };
+
   const handleLeaveGuild = async () => {
     setShowGuildMenu(false);
     if (confirm("Are you sure you want to leave this server?")) {
@@ -110,6 +138,34 @@ function GuildChannelSidebar({ guildId }: { guildId: string }) {
     }
   };
 
+  const handleRejoinGuild = async () => {
+    setShowGuildMenu(false);
+    try {
+      await api.rejoinGuild(guildId);
+      await loadGuilds();
+    } catch (e) {
+      console.error("Failed to rejoin guild:", e);
+      alert(`Couldn't rejoin this server: ${e}`);
+    }
+  };
+
+  const handleSetPassword = async () => {
+    setShowGuildMenu(false);
+    const isProtected = await api.getGuildPasswordProtected(guildId).catch(() => false);
+    const password = prompt(
+      isProtected
+        ? "New password for joining this server (leave empty to remove it):"
+        : "Password for joining this server (up to 32 characters):",
+    );
+    if (password === null) return;
+    try {
+      await api.setGuildPassword(guildId, password);
+    } catch (e) {
+      console.error("Failed to set server password:", e);
+      alert(`Couldn't set the server password: ${e}`);
+    }
+  };
+
   const handleDeleteGuild = async () => {
     setShowGuildMenu(false);
     if (confirm("Are you sure you want to delete this server? All data will be lost.")) {
@@ -206,6 +262,45 @@ function GuildChannelSidebar({ guildId }: { guildId: string }) {
                   </svg>
                   Rename Server
                 </button>
+                <button
+                  onClick={() => { setShowRolesModal(true); setShowGuildMenu(false); }}
+                  className="flex w-full items-center px-3 py-2 text-sm text-discord-muted hover:bg-discord-blurple hover:text-white"
+                >
+                  <svg className="mr-2 h-4 w-4" fill="none" viewBox="0 0 24 24" stroke="currentColor" strokeWidth={1.5}>
+                    <path strokeLinecap="round" strokeLinejoin="round" d="M9.568 3H5.25A2.25 2.25 0 003 5.25v4.318c0 .597.237 1.17.659 1.591l9.581 9.581c.699.699 1.78.872 2.607.33a18.095 18.095 0 005.223-5.223c.542-.827.369-1.908-.33-2.607L11.16 3.66A2.25 2.25 0 009.568 3z" />
+                    <path strokeLinecap="round" strokeLinejoin="round" d="M6 6h.008v.008H6V6z" />
+                  </svg>
+                  Roles
+                </button>
+                <button
+                  onClick={() => { setShowOnboardingModal(true); setShowGuildMenu(false); }}
+                  className="flex w-full items-center px-3 py-2 text-sm text-discord-muted hover:bg-discord-blurple hover:text-white"
+                >
+                  <svg className="mr-2 h-4 w-4" fill="none" viewBox="0 0 24 24" stroke="currentColor" strokeWidth={1.5}>
+                    <path strokeLinecap="round" strokeLinejoin="round" d="M19.5 14.25v-2.625a3.375 3.375 0 00-3.375-3.375h-1.5A1.125 1.125 0 0113.5 7.125v-1.5a3.375 3.375 0 00-3.375-3.375H8.25m0 12.75h7.5m-7.5 3H12M10.5 2.25H5.625c-.621 0-1.125.504-1.125 1.125v17.25c0 .621.504 1.125 1.125 1.125h12.75c.621 0 1.125-.504 1.125-1.125V11.25a9 9 0 00-9-9z" />
+                  </svg>
+                  Welcome & Rules
+                </button>
+                {guild?.owner_public_key && (
+                  <button
+                    onClick={handleSetPassword}
+                    className="flex w-full items-center px-3 py-2 text-sm text-discord-muted hover:bg-discord-blurple hover:text-white"
+                  >
+                    <svg className="mr-2 h-4 w-4" fill="none" viewBox="0 0 24 24" stroke="currentColor" strokeWidth={1.5}>
+                      <path strokeLinecap="round" strokeLinejoin="round" d="M16.5 10.5V6.75a4.5 4.5 0 10-9 0v3.75m-.75 11.25h10.5a2.25 2.25 0 002.25-2.25v-6.75a2.25 2.25 0 00-2.25-2.25H6.75a2.25 2.25 0 00-2.25 2.25v6.75a2.25 2.25 0 002.25 2.25z" />
+                    </svg>
+                    Server Password
+                  </button>
+                )}
+                <button
+                  onClick={handleRejoinGuild}
+                  className="flex w-full items-center px-3 py-2 text-sm text-discord-muted hover:bg-discord-blurple hover:text-white"
+                >
+                  <svg className="mr-2 h-4 w-4" fill="none" viewBox="0 0 24 24" stroke="currentColor" strokeWidth={1.5}>
+                    <path strokeLinecap="round" strokeLinejoin="round" d="M16.023 9.348h4.992v-.001M2.985 19.644v-4.992m0 0h4.992m-4.993 0l3.181 3.183a8.25 8.25 0 0013.803-3.7M4.031 9.865a8.25 8.25 0 0113.803-3.7l3.181 3.182m0-4.991v4.99" />
+                  </svg>
+                  Rejoin Server
+                </button>
                 <div className="my-1 mx-2 border-t border-discord-channel" />
                 <button
                   onClick={handleLeaveGuild}
@@ -301,10 +396,27 @@ function GuildChannelSidebar({ guildId }: { guildId: string }) {
                       : "text-discord-muted hover:bg-discord-hover hover:text-white"
                   }`}
                 >
-                  <span className="text-lg leading-none text-discord-muted">#</span>
-                  <span className="min-w-0 flex-1 truncate text-sm">
+                  <span
+                    className="text-lg leading-none text-discord-muted"
+                    title={channel.channel_type === "announcement" ? "Announcement channel" : undefined}
+                  >
+                    {channel.channel_type === "announcement" ? "📢" : "#"}
+                  </span>
+                  <span className={`min-w-0 flex-1 truncate text-sm ${channel.notification_level === "nothing" ? "opacity-50" : ""}`}>
                     {channel.name}
                   </span>
+                  <span
+                    onClick={(e) => {
+                      e.stopPropagation();
+                      handleCycleNotifications(channel);
+                    }}
+                    className={`flex-shrink-0 text-xs hover:text-white group-hover/ch:block ${
+                      channel.notification_level === "all" ? "hidden" : ""
+                    }`}
+                    title={NOTIFICATION_LABELS[channel.notification_level]}
+                  >
+                    {channel.notification_level === "nothing" ? "🔕" : channel.notification_level === "mentions" ? "@" : "🔔"}
+                  </span>
                   <svg
                     onClick={(e) => {
                       e.stopPropagation();
@@ -348,6 +460,12 @@ function GuildChannelSidebar({ guildId }: { guildId: string }) {
       {showInviteModal && (
         <InviteModal guildId={guildId} onClose={() => setShowInviteModal(false)} />
       )}
+      {showRolesModal && (
+        <GuildRolesModal guildId={guildId} onClose={() => setShowRolesModal(false)} />
+      )}
+      {showOnboardingModal && (
+        <GuildOnboardingModal guildId={guildId} onClose={() => setShowOnboardingModal(false)} />
+      )}
     </>
   );
 }
@@ -355,12 +473,13 @@ function GuildChannelSidebar({ guildId }: { guildId: string }) {
 function DMSidebar() {
   const { displayName, statusMessage, isConnected, connectionType, logout } =
     useAuthStore();
-  const { friends, friendRequests, loadFriends, loadFriendRequests } =
+  const { friends, friendRequests, syncFriends, loadFriendRequests } =
     useFriendStore();
   const { currentPage, selectedFriendNumber, selectedDmGroupId, setPage, openDM, openDmGroup } =
     useNavigationStore();
   const { dmGroups, loadDmGroups } = useGuildStore();
   const unreadCounts = useMessageStore((s) => s.unreadCounts);
+  const missedCalls = useCallStore((s) => s.missedCalls);
 
   const [isEditingName, setIsEditingName] = useState(false);
   const [editName, setEditName] = useState(displayName ?? "");
@@ -371,10 +490,10 @@ function DMSidebar() {
   const pendingCount = friendRequests.length;
 
   useEffect(() => {
-    loadFriends();
+    syncFriends();
     loadFriendRequests();
     loadDmGroups();
-  }, [loadFriends, loadFriendRequests, loadDmGroups]);
+  }, [syncFriends, loadFriendRequests, loadDmGroups]);
 
   const handleNameSave = async () => {
     if (editName.trim() && editName !== displayName) {
@@ -473,6 +592,7 @@ function DMSidebar() {
                   currentPage === "dm" &&
                   selectedFriendNumber === friend.friend_number;
                 const unread = unreadCounts[friend.friend_number] ?? 0;
+                const missed = missedCalls[friend.friend_number] ?? 0;
                 const isOnline = friend.connection_status !== "none";
 
                 return (
@@ -498,6 +618,15 @@ function DMSidebar() {
                     <span className="min-w-0 flex-1 truncate text-sm">
                       {friend.name || friend.public_key.slice(0, 8) + "..."}
                     </span>
+                    {missed > 0 && (
+                      <span
+                        className="flex h-4 items-center gap-0.5 rounded-full bg-discord-red/20 px-1 text-xs font-bold text-discord-red"
+                        title={`${missed} missed call${missed === 1 ? "" : "s"}`}
+                      >
+                        <MissedCallIcon className="h-3 w-3" />
+                        {missed}
+                      </span>
+                    )}
                     {unread > 0 && (
                       <span className="flex h-4 min-w-4 items-center justify-center rounded-full bg-discord-red px-1 text-xs font-bold text-white">
                         {unread}
@@ -698,13 +827,13 @@ function CreateGroupDMModal({ onClose }: { onClose: () => void }) {
   const [selectedFriends, setSelectedFriends] = useState<number[]>([]);
   const [isCreating, setIsCreating] = useState(false);
   const [error, setError] = useState("");
-  const { friends, loadFriends } = useFriendStore();
+  const { friends, syncFriends } = useFriendStore();
   const createDmGroup = useGuildStore((s) => s.createDmGroup);
   const openDmGroup = useNavigationStore((s) => s.openDmGroup);
 
   useEffect(() => {
-    loadFriends();
-  }, [loadFriends]);
+    syncFriends();
+  }, [syncFriends]);
 
   const onlineFriends = friends.filter((f) => f.connection_status !== "none");
 
@@ -813,3 +942,11 @@ function CreateGroupDMModal({ onClose }: { onClose: () => void }) {
     </div>
   );
 }
+
+function MissedCallIcon({ className }: { className?: string }) {
+  return (
+    <svg className={className} fill="currentColor" viewBox="0 0 24 24">
+      <path d="M19.59 7L12 14.59 6.41 9H11V7H3v8h2v-4.59l7 7 9-9L19.59 7z" />
+    </svg>
+  );
+}
@@ -0,0 +1,119 @@
+import { useEffect, useState } from "react";
+import * as api from "../../api/tox";
+import type { CrashReport } from "../../api/tox";
+
+/** After a crash, asks whether to share the report; nothing is sent unless the user chooses to */
+export function CrashReportPrompt() {
+  const [reports, setReports] = useState<CrashReport[]>([]);
+  const [showDetails, setShowDetails] = useState(false);
+  const [savedTo, setSavedTo] = useState<string | null>(null);
+  const [error, setError] = useState<string | null>(null);
+
+  useEffect(() => {
+    api.getPendingCrashReports().then(setReports).catch(console.error);
+  }, []);
+
+  const report = reports[0];
+  if (!report && !savedTo) return null;
+
+  const next = () => {
+    setReports((current) => current.slice(1));
+    setShowDetails(false);
+    setError(null);
+  };
+
+  const share = async () => {
+    setError(null);
+    try {
+      setSavedTo(await api.submitCrashReport(report.id));
+      next();
+    } catch (e) {
+      setError(String(e));
+    }
+  };
+
+  const dismiss = async () => {
+    try {
+      await api.dismissCrashReport(report.id);
+    } catch (e) {
+      console.error("Failed to dismiss crash report:", e);
+    }
+    next();
+  };
+
+  if (!report) {
+    return (
+      <div className="fixed inset-0 z-50 flex items-center justify-center bg-black/70">
+        <div className="w-[440px] rounded-lg bg-discord-sidebar p-6">
+          <h3 className="mb-2 text-lg font-semibold text-white">Thanks!</h3>
+          <p className="mb-1 text-sm text-discord-muted">The report was saved to:</p>
+          <p className="mb-4 break-all rounded bg-discord-darker p-2 font-mono text-xs text-discord-text">{savedTo}</p>
+          <p className="mb-4 text-sm text-discord-muted">
+            Attach it to the issue that just opened in your browser.
+          </p>
+          <div className="flex justify-end">
+            <button
+              onClick={() => setSavedTo(null)}
+              className="rounded-md bg-discord-blurple px-4 py-2 text-sm font-medium text-white transition-colors hover:bg-discord-blurple/80"
+            >
+              Done
+            </button>
+          </div>
+        </div>
+      </div>
+    );
+  }
+
+  return (
+    <div className="fixed inset-0 z-50 flex items-center justify-center bg-black/70">
+      <div className="w-[520px] rounded-lg bg-discord-sidebar p-6">
+        <h3 className="mb-2 text-lg font-semibold text-white">Toxcord crashed</h3>
+        <p className="mb-3 text-sm text-discord-muted">
+          A crash report was saved on {new Date(report.time).toLocaleString()}. Sharing it helps get the problem fixed.
+          Keys are shortened and your home folder is hidden, but check it first: it includes recent log lines.
+        </p>
+        <p className="mb-3 break-words rounded bg-discord-darker p-2 font-mono text-xs text-discord-text">
+          {report.message}
+        </p>
+
+        <button
+          onClick={() => setShowDetails(!showDetails)}
+          className="mb-3 text-xs text-discord-blurple hover:underline"
+        >
+          {showDetails ? "Hide report" : "Show full report"}
+        </button>
+        {showDetails && (
+          <pre className="mb-3 max-h-64 overflow-auto rounded bg-discord-darker p-2 font-mono text-xs text-discord-text">
+            {[
+              `Version ${report.app_version}, toxcore ${report.toxcore_version}, ${report.os} ${report.arch}`,
+              `Thread ${report.thread}${report.location ? ` at ${report.location}` : ""}`,
+              "",
+              report.backtrace,
+              "",
+              ...report.log_tail,
+            ].join("\n")}
+          </pre>
+        )}
+
+        {error && <p className="mb-3 text-sm text-discord-red">{error}</p>}
+        <div className="flex justify-end gap-2">
+          <button
+            onClick={dismiss}
+            className="rounded-md px-4 py-2 text-sm text-discord-muted transition-colors hover:text-white"
+          >
+            Don't Share
+          </button>
+          <button
+            onClick={share}
+            className="rounded-md bg-discord-blurple px-4 py-2 text-sm font-medium text-white transition-colors hover:bg-discord-blurple/80"
+          >
+            Share Report
+          </button>
+        </div>
+        {reports.length > 1 && (
+          <p className="mt-3 text-xs text-discord-muted">{reports.length - 1} more after this one</p>
+        )}
+      </div>
+    </div>
+  );
+}
@@ -0,0 +1,125 @@
+import { useEffect, useState } from "react";
+import * as api from "../../api/tox";
+import type { ContactLink } from "../../api/tox";
+import { useFriendStore } from "../../stores/friendStore";
+import { useGuildStore } from "../../stores/guildStore";
+import { useNavigationStore } from "../../stores/navigationStore";
+
+const DEFAULT_REQUEST_MESSAGE = "Hello! I'd like to add you on Toxcord.";
+
+type PromptLink = Extract<ContactLink, { kind: "add_friend" | "join_guild" }>;
+
+/** Message links open the conversation they point at without asking */
+async function openMessageLink(link: ContactLink) {
+  const location = await api.resolveMessageLink(link);
+  const navigation = useNavigationStore.getState();
+  if (location.conversation.type === "friend") {
+    navigation.openDM(location.conversation.id);
+  } else if (location.guild_id) {
+    navigation.openChannel(location.guild_id, location.conversation.id);
+  }
+}
+
+/** Asks before acting on a toxcord:// link opened from outside the app */
+export function DeepLinkPrompt() {
+  const [link, setLink] = useState<PromptLink | null>(null);
+  const [message, setMessage] = useState(DEFAULT_REQUEST_MESSAGE);
+  const [error, setError] = useState<string | null>(null);
+  const [busy, setBusy] = useState(false);
+
+  useEffect(() => {
+    const show = (next: ContactLink | null) => {
+      if (!next) return;
+      if (next.kind === "channel_message" || next.kind === "direct_message") {
+        openMessageLink(next).catch(console.error);
+        return;
+      }
+      setLink(next);
+      setMessage(DEFAULT_REQUEST_MESSAGE);
+      setError(null);
+    };
+
+    // A link opened before the profile was loaded is waiting for us
+    api.takePendingLink().then(show).catch(console.error);
+    let unlisten: (() => void) | undefined;
+    api
+      .onDeepLink(() => api.takePendingLink().then(show).catch(console.error))
+      .then((fn) => {
+        unlisten = fn;
+      });
+    return () => unlisten?.();
+  }, []);
+
+  if (!link) return null;
+
+  const confirm = async () => {
+    setBusy(true);
+    setError(null);
+    try {
+      if (link.kind === "add_friend") {
+        await api.addFriend(link.tox_id, message.trim());
+        await useFriendStore.getState().loadFriends();
+        useNavigationStore.getState().setPage("friends");
+      } else {
+        const guild = await api.joinGuildLink(link.chat_id, link.name);
+        await useGuildStore.getState().loadGuilds();
+        useNavigationStore.getState().openGuild(guild.id);
+      }
+      setLink(null);
+    } catch (e) {
+      setError(String(e));
+    } finally {
+      setBusy(false);
+    }
+  };
+
+  const isFriend = link.kind === "add_friend";
+  const id = isFriend ? link.tox_id : link.chat_id;
+
+  return (
+    <div className="fixed inset-0 z-50 flex items-center justify-center bg-black/70">
+      <div className="w-[440px] rounded-lg bg-discord-sidebar p-6">
+        <h3 className="mb-2 text-lg font-semibold text-white">
+          {isFriend ? "Add Friend" : "Join Guild"}
+          {link.name ? `: ${link.name}` : ""}
+        </h3>
+        <p className="mb-1 text-sm text-discord-muted">
+          {isFriend
+            ? "A link asked to send a friend request to this Tox ID:"
+            : "A link asked to join the guild with this ID:"}
+        </p>
+        <p className="mb-4 break-all rounded bg-discord-darker p-2 font-mono text-xs text-discord-text">{id}</p>
+
+        {isFriend && (
+          <>
+            <label className="mb-1 block text-xs font-bold uppercase text-discord-muted">Message</label>
+            <input
+              type="text"
+              value={message}
+              onChange={(e) => setMessage(e.target.value)}
+              className="mb-4 w-full rounded-md bg-discord-darker p-2.5 text-sm text-white outline-none focus:ring-2 focus:ring-discord-blurple"
+            />
+          </>
+        )}
+
+        {error && <p className="mb-3 text-sm text-discord-red">{error}</p>}
+
+        <div className="flex justify-end gap-2">
+          <button
+            onClick={() => setLink(null)}
+            className="rounded-md px-4 py-2 text-sm text-discord-text hover:underline"
+          >
+            Cancel
+          </button>
+          <button
+            onClick={confirm}
+            disabled={busy}
+            className="rounded-md bg-discord-blurple px-4 py-2 text-sm font-medium text-white hover:bg-discord-blurple/80 disabled:opacity-50"
+          >
+            {isFriend ? "Send Friend Request" : "Join Guild"}
+          </button>
+        </div>
+      </div>
+    </div>
+  );
+}
@@ -1,18 +1,63 @@
+import { useEffect, useState } from "react";
 import { useGuildStore } from "../../stores/guildStore";
 import { useNavigationStore } from "../../stores/navigationStore";
+import { useChannelMessageStore } from "../../stores/channelMessageStore";
+import * as api from "../../api/tox";
+import type { CustomRole, GuildMember } from "../../api/tox";
 
 const EMPTY_MEMBERS: never[] = [];
 
 export function MemberSidebar() {
   const selectedGuildId = useNavigationStore((s) => s.selectedGuildId);
+  const selectedChannelId = useNavigationStore((s) => s.selectedChannelId);
   const members = useGuildStore((s) =>
     selectedGuildId ? (s.members[selectedGuildId] ?? EMPTY_MEMBERS) : EMPTY_MEMBERS,
   );
+  const loadMembers = useGuildStore((s) => s.loadMembers);
+  const loadMessages = useChannelMessageStore((s) => s.loadMessages);
+  const [roles, setRoles] = useState<CustomRole[]>([]);
 
-  const founders = members.filter((m) => m.role === "founder");
-  const moderators = members.filter((m) => m.role === "moderator");
-  const users = members.filter((m) => m.role === "user");
-  const observers = members.filter((m) => m.role === "observer");
+  // Members changing means the manifest may have too
+  useEffect(() => {
+    if (!selectedGuildId) return;
+    api.getGuildRoles(selectedGuildId).then(setRoles).catch(() => setRoles([]));
+  }, [selectedGuildId, members]);
+
+  const assignRole = async (member: GuildMember, roleId: number | null) => {
+    if (!selectedGuildId) return;
+    try {
+      await api.assignGuildRole(selectedGuildId, member.public_key, roleId);
+      await loadMembers(selectedGuildId);
+    } catch (e) {
+      console.error("Failed to assign role:", e);
+    }
+  };
+
+  const toggleIgnored = async (member: GuildMember) => {
+    if (!selectedGuildId) return;
+    try {
+      if (member.ignored) {
+        await api.unignoreGuildMember(selectedGuildId, member.public_key);
+      } else {
+        await api.ignoreGuildMember(selectedGuildId, member.public_key);
+      }
+      await loadMembers(selectedGuildId);
+      // Their stored messages leave or come back
+      if (selectedChannelId) await loadMessages(selectedChannelId);
+    } catch (e) {
+      console.error("Failed to change ignored member:", e);
+    }
+  };
+
+  const online = members.filter((m) => m.status !== "offline");
+  const offline = members.filter((m) => m.status === "offline");
+  // Online members with a custom role are listed under it, in role order
+  const byRole = roles.map((role) => ({ role, list: online.filter((m) => m.custom_role?.id === role.id) }));
+  const unroled = online.filter((m) => !m.custom_role || !roles.some((r) => r.id === m.custom_role?.id));
+  const founders = unroled.filter((m) => m.role === "founder");
+  const moderators = unroled.filter((m) => m.role === "moderator");
+  const users = unroled.filter((m) => m.role === "user");
+  const observers = unroled.filter((m) => m.role === "observer");
 
   const renderSection = (title: string, list: typeof members) => {
     if (list.length === 0) return null;
@@ -25,7 +70,8 @@ export function MemberSidebar() {
           const isOnline = member.status !== "offline";
           return (
             <div
-              key={member.peer_id}
+              key={member.public_key}
+              title={!isOnline && member.last_seen ? `Last seen ${member.last_seen}` : undefined}
               className="group flex items-center gap-2.5 rounded-md px-2 py-1 hover:bg-discord-hover"
             >
               <div className="relative">
@@ -42,9 +88,36 @@ export function MemberSidebar() {
                 className={`min-w-0 flex-1 truncate text-sm ${
                   isOnline ? "text-white" : "text-discord-muted"
                 }`}
+                style={member.custom_role && isOnline ? { color: member.custom_role.color } : undefined}
+                title={member.custom_role?.name}
               >
+                {member.custom_role?.icon && <span className="mr-1">{member.custom_role.icon}</span>}
                 {member.name || member.public_key.slice(0, 8) + "..."}
               </span>
+              {roles.length > 0 && (
+                <select
+                  value={member.custom_role?.id ?? ""}
+                  onChange={(e) => assignRole(member, e.target.value ? Number(e.target.value) : null)}
+                  className="hidden w-16 rounded bg-discord-input text-xs text-white group-hover:block"
+                  title="Role"
+                >
+                  <option value="">No role</option>
+                  {roles.map((role) => (
+                    <option key={role.id} value={role.id}>
+                      {role.name}
+                    </option>
+                  ))}
+                </select>
+              )}
+              <button
+                onClick={() => toggleIgnored(member)}
+                className={`text-xs hover:text-white ${
+                  member.ignored ? "text-discord-red" : "hidden text-discord-muted group-hover:block"
+                }`}
+                title={member.ignored ? "Stop ignoring" : "Ignore — hide their messages, only for you"}
+              >
+                {member.ignored ? "Ignored" : "Ignore"}
+              </button>
             </div>
           );
         })}
@@ -64,10 +137,14 @@ export function MemberSidebar() {
           </p>
         ) : (
           <>
+            {byRole.map(({ role, list }) => (
+              <div key={role.id}>{renderSection(role.name, list)}</div>
+            ))}
             {renderSection("Founder", founders)}
             {renderSection("Moderators", moderators)}
             {renderSection("Members", users)}
             {renderSection("Observers", observers)}
+            {renderSection("Offline", offline)}
           </>
         )}
       </div>
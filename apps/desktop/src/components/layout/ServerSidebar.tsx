@@ -1,22 +1,27 @@
 import { useEffect, useState } from "react";
 import { useAuthStore } from "../../stores/authStore";
 import { useNavigationStore } from "../../stores/navigationStore";
-import { useGuildStore } from "../../stores/guildStore";
+import { useGuildStore, type GuildInvite } from "../../stores/guildStore";
+import { setGroupInviteAutoAccept } from "../../api/tox";
 import { useFriendStore } from "../../stores/friendStore";
 import { GuildCreateModal } from "../guild/GuildCreateModal";
+import { DiscoverGuildsModal } from "../guild/DiscoverGuildsModal";
 
 export function ServerSidebar() {
   const isConnected = useAuthStore((s) => s.isConnected);
+  const threadStatus = useAuthStore((s) => s.threadStatus);
   const { currentPage, setPage, selectedGuildId } = useNavigationStore();
   const openGuild = useNavigationStore((s) => s.openGuild);
-  const { guilds, loadGuilds, pendingInvites, acceptInvite, dismissInvite } =
+  const { guilds, loadGuilds, loadInvites, pendingInvites, acceptInvite, dismissInvite } =
     useGuildStore();
   const [showCreateModal, setShowCreateModal] = useState(false);
   const [showInvites, setShowInvites] = useState(false);
+  const [showDiscover, setShowDiscover] = useState(false);
 
   useEffect(() => {
     loadGuilds();
-  }, [loadGuilds]);
+    loadInvites();
+  }, [loadGuilds, loadInvites]);
 
   const isHome = currentPage === "home" || currentPage === "friends" || currentPage === "dm" || currentPage === "dm_group";
 
@@ -145,6 +150,20 @@ export function ServerSidebar() {
           </button>
         </div>
 
+        {/* Discover public servers button */}
+        <div className="mt-2">
+          <button
+            onClick={() => setShowDiscover(true)}
+            className="flex h-12 w-12 items-center justify-center rounded-2xl bg-discord-channel text-discord-green transition-all duration-200 hover:rounded-xl hover:bg-discord-green hover:text-white"
+            title="Discover Public Servers"
+          >
+            <svg className="h-6 w-6" fill="none" viewBox="0 0 24 24" stroke="currentColor" strokeWidth={1.5}>
+              <path strokeLinecap="round" strokeLinejoin="round" d="M12 21a9 9 0 100-18 9 9 0 000 18z" />
+              <path strokeLinecap="round" strokeLinejoin="round" d="M15.5 8.5l-2 5-5 2 2-5 5-2z" />
+            </svg>
+          </button>
+        </div>
+
         {/* Settings button */}
         <div className="mt-2">
           <button
@@ -167,10 +186,24 @@ export function ServerSidebar() {
         <div className="mt-2">
           <div
             className={`h-3 w-3 rounded-full ${
-              isConnected ? "bg-discord-green" : "bg-discord-red"
+              threadStatus === "stopped"
+                ? "bg-discord-red"
+                : threadStatus === "stalled"
+                  ? "bg-discord-yellow animate-pulse"
+                  : isConnected
+                    ? "bg-discord-green"
+                    : "bg-discord-red"
             }`}
             title={
-              isConnected ? "Connected to TOX network" : "Connecting..."
+              threadStatus === "stopped"
+                ? "Networking stopped after repeated crashes — restart Toxcord"
+                : threadStatus === "stalled"
+                  ? "Networking isn't responding"
+                  : threadStatus === "restarted"
+                    ? `Reconnected after a crash${isConnected ? "" : " — connecting..."}`
+                    : isConnected
+                      ? "Connected to TOX network"
+                      : "Connecting..."
             }
           />
         </div>
@@ -180,6 +213,10 @@ export function ServerSidebar() {
         <GuildCreateModal onClose={() => setShowCreateModal(false)} />
       )}
 
+      {showDiscover && (
+        <DiscoverGuildsModal onClose={() => setShowDiscover(false)} />
+      )}
+
       {showInvites && (
         <PendingInvitesModal
           invites={pendingInvites}
@@ -190,7 +227,7 @@ export function ServerSidebar() {
               // error is logged in the store
             }
           }}
-          onDismiss={(friendNumber) => dismissInvite(friendNumber)}
+          onDismiss={(inviteId) => dismissInvite(inviteId)}
           onClose={() => setShowInvites(false)}
         />
       )}
@@ -204,12 +241,25 @@ function PendingInvitesModal({
   onDismiss,
   onClose,
 }: {
-  invites: { friendNumber: number; inviteData: number[]; groupName: string }[];
-  onAccept: (invite: { friendNumber: number; inviteData: number[]; groupName: string }) => void;
-  onDismiss: (friendNumber: number) => void;
+  invites: GuildInvite[];
+  onAccept: (invite: GuildInvite) => void;
+  onDismiss: (inviteId: string) => void;
   onClose: () => void;
 }) {
   const friends = useFriendStore((s) => s.friends);
+  // Friends whose later invites should be joined without asking
+  const [alwaysJoin, setAlwaysJoin] = useState<Record<number, boolean>>({});
+
+  const accept = async (invite: GuildInvite) => {
+    if (alwaysJoin[invite.friendNumber]) {
+      try {
+        await setGroupInviteAutoAccept(invite.friendNumber, true);
+      } catch (e) {
+        console.error("Failed to save auto-accept:", e);
+      }
+    }
+    onAccept(invite);
+  };
 
   const getFriendName = (friendNumber: number) => {
     const friend = friends.find((f) => f.friend_number === friendNumber);
@@ -233,7 +283,7 @@ function PendingInvitesModal({
 
             return (
             <div
-              key={invite.friendNumber}
+              key={invite.id}
               className="flex items-center gap-3 rounded-md bg-discord-chat p-3"
             >
               <div className="min-w-0 flex-1">
@@ -243,18 +293,29 @@ function PendingInvitesModal({
                 <p className="text-xs text-discord-muted">
                   {isDmGroup ? "DM Group" : "Server"} from {getFriendName(invite.friendNumber)}
                 </p>
+                <label className="mt-1 flex items-center gap-1.5 text-xs text-discord-muted">
+                  <input
+                    type="checkbox"
+                    checked={alwaysJoin[invite.friendNumber] ?? false}
+                    onChange={(e) =>
+                      setAlwaysJoin((a) => ({ ...a, [invite.friendNumber]: e.target.checked }))
+                    }
+                    className="h-3 w-3"
+                  />
+                  Always join invites from this friend
+                </label>
               </div>
               <button
-                onClick={() => onAccept(invite)}
+                onClick={() => accept(invite)}
                 className="rounded-md bg-discord-green px-3 py-1 text-xs font-medium text-white hover:bg-discord-green/80"
               >
                 Join
               </button>
               <button
-                onClick={() => onDismiss(invite.friendNumber)}
+                onClick={() => onDismiss(invite.id)}
                 className="rounded-md bg-discord-channel px-3 py-1 text-xs font-medium text-discord-muted hover:bg-discord-hover hover:text-white"
               >
-                Dismiss
+                Decline
               </button>
             </div>
           );
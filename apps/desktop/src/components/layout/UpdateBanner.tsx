@@ -0,0 +1,126 @@
+import { useEffect, useState } from "react";
+import * as api from "../../api/tox";
+import type { UpdateInfo, UpdateProgress } from "../../api/tox";
+
+/** Tells the user about a newer release, with its changelog, and installs it if asked */
+export function UpdateBanner() {
+  const [update, setUpdate] = useState<UpdateInfo | null>(null);
+  const [showNotes, setShowNotes] = useState(false);
+  const [progress, setProgress] = useState<UpdateProgress | null>(null);
+  const [installing, setInstalling] = useState(false);
+  const [error, setError] = useState<string | null>(null);
+
+  useEffect(() => {
+    // Found before this page was shown; skipped versions only come from background checks
+    Promise.all([api.getAvailableUpdate(), api.getUpdateSettings()])
+      .then(([available, settings]) => {
+        if (available && available.version !== settings.skipped_version) setUpdate(available);
+      })
+      .catch(console.error);
+
+    const unlisteners: Promise<() => void>[] = [
+      api.onUpdateAvailable((next) => {
+        setUpdate(next);
+        setError(null);
+      }),
+      api.onUpdateProgress(setProgress),
+    ];
+    return () => {
+      unlisteners.forEach((p) => p.then((fn) => fn()));
+    };
+  }, []);
+
+  if (!update) return null;
+
+  const install = async () => {
+    setInstalling(true);
+    setError(null);
+    try {
+      await api.installUpdate();
+    } catch (e) {
+      setError(String(e));
+      setInstalling(false);
+      setProgress(null);
+    }
+  };
+
+  const skip = async () => {
+    try {
+      const settings = await api.getUpdateSettings();
+      await api.setUpdateSettings({ ...settings, skipped_version: update.version });
+    } catch (e) {
+      console.error("Failed to skip update:", e);
+    }
+    setUpdate(null);
+  };
+
+  const percent =
+    progress?.total ? Math.min(100, Math.round((progress.downloaded / progress.total) * 100)) : null;
+
+  return (
+    <div className="fixed bottom-4 right-4 z-40 w-[360px] rounded-lg bg-discord-sidebar p-4 shadow-lg">
+      <div className="mb-1 flex items-start justify-between gap-2">
+        <h3 className="text-sm font-semibold text-white">Toxcord {update.version} is available</h3>
+        {!installing && (
+          <button
+            onClick={() => setUpdate(null)}
+            className="text-discord-muted hover:text-white"
+            title="Later"
+            aria-label="Dismiss"
+          >
+            ✕
+          </button>
+        )}
+      </div>
+      <p className="mb-2 text-xs text-discord-muted">You have {update.current_version}.</p>
+
+      {update.notes && (
+        <>
+          <button
+            onClick={() => setShowNotes(!showNotes)}
+            className="mb-2 text-xs text-discord-blurple hover:underline"
+          >
+            {showNotes ? "Hide changes" : "What's new"}
+          </button>
+          {showNotes && (
+            <pre className="mb-2 max-h-48 overflow-y-auto whitespace-pre-wrap rounded bg-discord-darker p-2 font-sans text-xs text-discord-text">
+              {update.notes}
+            </pre>
+          )}
+        </>
+      )}
+
+      {installing ? (
+        <p className="text-xs text-discord-text">
+          {percent !== null ? `Downloading… ${percent}%` : "Downloading…"} Toxcord will restart when it's done.
+        </p>
+      ) : update.can_install ? (
+        <div className="flex gap-2">
+          <button
+            onClick={install}
+            className="rounded-md bg-discord-blurple px-3 py-1.5 text-sm font-medium text-white transition-colors hover:bg-discord-blurple/80"
+          >
+            Update and Restart
+          </button>
+          <button
+            onClick={skip}
+            className="rounded-md px-3 py-1.5 text-sm text-discord-muted transition-colors hover:text-white"
+          >
+            Skip This Version
+          </button>
+        </div>
+      ) : (
+        <div className="flex items-center justify-between gap-2">
+          <p className="text-xs text-discord-muted">Download it from the Toxcord releases page.</p>
+          <button
+            onClick={skip}
+            className="shrink-0 rounded-md px-3 py-1.5 text-sm text-discord-muted transition-colors hover:text-white"
+          >
+            Skip This Version
+          </button>
+        </div>
+      )}
+      {error && <p className="mt-2 text-xs text-discord-red">{error}</p>}
+    </div>
+  );
+}
@@ -0,0 +1,65 @@
+import { useEffect, useState } from "react";
+import * as api from "../../api/tox";
+import type { StickerInfo } from "../../api/tox";
+
+/**
+ * A sticker message: a reference to a pack we may have, or a sticker sent as
+ * an image file (the message's attachment).
+ */
+export function StickerImage({
+  messageId,
+  content,
+  sticker,
+}: {
+  messageId: string;
+  content: string;
+  sticker?: StickerInfo | null;
+}) {
+  const [url, setUrl] = useState<string | null>(null);
+  const [missingPack, setMissingPack] = useState(false);
+
+  useEffect(() => {
+    let cancelled = false;
+    let objectUrl: string | null = null;
+
+    const load = async () => {
+      let data: ArrayBuffer;
+      let mimeType: string;
+      const info = sticker ?? (await api.resolveSticker(content));
+      if (info) {
+        if (!info.path) {
+          if (!cancelled) setMissingPack(true);
+          return;
+        }
+        data = await api.getStickerImage(info.pack_hash, info.index);
+        mimeType = info.mime_type ?? "image/png";
+      } else {
+        const [attachment] = await api.getMessageAttachments(messageId);
+        if (!attachment) return;
+        data = await api.getAttachmentThumbnail(attachment.id);
+        mimeType = attachment.mime_type;
+      }
+      if (cancelled) return;
+      objectUrl = URL.createObjectURL(new Blob([data], { type: mimeType }));
+      setUrl(objectUrl);
+    };
+    load().catch((e) => console.error("Failed to load sticker:", e));
+
+    return () => {
+      cancelled = true;
+      if (objectUrl) URL.revokeObjectURL(objectUrl);
+    };
+  }, [messageId, content, sticker]);
+
+  if (missingPack) {
+    return (
+      <p className="text-sm italic text-discord-muted" title="Sent from a sticker pack you don't have">
+        [Sticker]
+      </p>
+    );
+  }
+  if (!url) {
+    return <div className="h-40 w-40" />;
+  }
+  return <img src={url} alt="Sticker" className="max-h-40 max-w-40 object-contain" />;
+}
@@ -2,6 +2,7 @@ import { useCallStore } from "../../stores/callStore";
 import { LocalPreview } from "./LocalPreview";
 import { RemoteVideo } from "./RemoteVideo";
 import { DevicePicker, SettingsIcon } from "../call/DevicePicker";
+import { CallChat } from "../call/CallChat";
 import { useState } from "react";
 
 /**
@@ -58,51 +59,55 @@ export function FullscreenVideoModal() {
         </button>
       </div>
 
-      {/* Video area - fills remaining space */}
-      <div className="flex flex-1 items-center justify-center gap-6 p-6">
-        {/* Remote participant - larger */}
-        <div className="flex flex-col items-center">
-          <div className="relative overflow-hidden rounded-xl bg-[#2b2d31]">
-            {isVideoEnabled ? (
-              <RemoteVideo
-                friendNumber={activeCall.friendNumber}
-                className="h-[70vh] max-h-[800px] w-auto max-w-[70vw] object-contain"
-              />
-            ) : (
-              <div className="flex h-[50vh] w-[50vh] max-h-[500px] max-w-[500px] items-center justify-center">
-                <div className="flex h-32 w-32 items-center justify-center rounded-full bg-discord-blurple">
-                  <span className="text-5xl font-bold text-white">
-                    {activeCall.friendName[0]?.toUpperCase()}
-                  </span>
+      <div className="relative flex min-h-0 flex-1">
+        {/* Video area - fills remaining space */}
+        <div className="relative flex flex-1 items-center justify-center gap-6 p-6">
+          {/* Remote participant - larger */}
+          <div className="flex flex-col items-center">
+            <div className="relative overflow-hidden rounded-xl bg-[#2b2d31]">
+              {isVideoEnabled ? (
+                <RemoteVideo
+                  friendNumber={activeCall.friendNumber}
+                  className="h-[70vh] max-h-[800px] w-auto max-w-[70vw] object-contain"
+                />
+              ) : (
+                <div className="flex h-[50vh] w-[50vh] max-h-[500px] max-w-[500px] items-center justify-center">
+                  <div className="flex h-32 w-32 items-center justify-center rounded-full bg-discord-blurple">
+                    <span className="text-5xl font-bold text-white">
+                      {activeCall.friendName[0]?.toUpperCase()}
+                    </span>
+                  </div>
                 </div>
-              </div>
-            )}
+              )}
+            </div>
+            <span className="mt-3 text-base font-medium text-white">
+              {activeCall.friendName}
+            </span>
           </div>
-          <span className="mt-3 text-base font-medium text-white">
-            {activeCall.friendName}
-          </span>
-        </div>
 
-        {/* Local participant - smaller, positioned in corner */}
-        <div className="absolute bottom-24 right-6 flex flex-col items-center">
-          <div className="relative overflow-hidden rounded-lg bg-[#2b2d31] shadow-xl">
-            {isVideoEnabled ? (
-              <LocalPreview className="h-40 w-56 object-cover" />
-            ) : (
-              <div className="flex h-40 w-56 items-center justify-center">
-                <div className="flex h-16 w-16 items-center justify-center rounded-full bg-discord-green">
-                  <span className="text-xl font-bold text-white">You</span>
+          {/* Local participant - smaller, positioned in corner */}
+          <div className="absolute bottom-6 right-6 flex flex-col items-center">
+            <div className="relative overflow-hidden rounded-lg bg-[#2b2d31] shadow-xl">
+              {isVideoEnabled ? (
+                <LocalPreview className="h-40 w-56 object-cover" />
+              ) : (
+                <div className="flex h-40 w-56 items-center justify-center">
+                  <div className="flex h-16 w-16 items-center justify-center rounded-full bg-discord-green">
+                    <span className="text-xl font-bold text-white">You</span>
+                  </div>
                 </div>
-              </div>
-            )}
-            {isMuted && (
-              <div className="absolute bottom-2 right-2 rounded-full bg-discord-red p-1.5">
-                <MicOffIcon className="h-4 w-4 text-white" />
-              </div>
-            )}
+              )}
+              {isMuted && (
+                <div className="absolute bottom-2 right-2 rounded-full bg-discord-red p-1.5">
+                  <MicOffIcon className="h-4 w-4 text-white" />
+                </div>
+              )}
+            </div>
+            <span className="mt-2 text-sm font-medium text-discord-muted">You</span>
           </div>
-          <span className="mt-2 text-sm font-medium text-discord-muted">You</span>
         </div>
+
+        <CallChat friendNumber={activeCall.friendNumber} friendName={activeCall.friendName} />
       </div>
 
       {/* Controls bar */}
@@ -1,7 +1,7 @@
 import { useRef, useEffect, useState } from "react";
 import { listen } from "@tauri-apps/api/event";
 import { VideoCanvas, VideoCanvasHandle } from "./VideoCanvas";
-import { VideoFramePayload } from "../../api/calls";
+import { pollVideoFrames, VideoFramePayload } from "../../api/calls";
 
 interface LocalPreviewProps {
   className?: string;
@@ -16,46 +16,19 @@ export function LocalPreview({ className }: LocalPreviewProps) {
   useEffect(() => {
     let unlisten: (() => void) | null = null;
 
-    console.log("[LocalPreview] Setting up video frame listener for toxav://local-video");
-
-    // Listen directly to see raw events
-    listen<unknown>("toxav://local-video", (event) => {
-      const payload = event.payload as Record<string, unknown>;
-
-      // Check for error event first
-      if (payload && payload.type === "VideoError" && payload.data) {
-        const errorData = payload.data as { error: string };
-        console.error("[LocalPreview] Video capture error:", errorData.error);
-        setError(errorData.error);
-        return;
-      }
-
-      // Log raw event for debugging
-      frameCountRef.current++;
-      if (frameCountRef.current <= 5) {
-        console.log("[LocalPreview] Raw event received:", {
-          eventName: event.event,
-          payloadType: typeof event.payload,
-          payloadKeys: payload && typeof payload === 'object' ? Object.keys(payload) : 'N/A',
-        });
-      }
-
-      // Extract the frame data - handle both wrapped and unwrapped formats
-      let frame: VideoFramePayload | null = null;
-
-      if (payload && payload.type === "VideoFrame" && payload.data) {
-        // Wrapped format: { type: "VideoFrame", data: VideoFramePayload }
-        frame = payload.data as VideoFramePayload;
-      } else if (payload && "width" in payload && "height" in payload && "data" in payload) {
-        // Direct format: VideoFramePayload
-        frame = payload as unknown as VideoFramePayload;
-      }
-
-      if (!frame) {
-        console.warn("[LocalPreview] Could not extract frame from payload");
-        return;
+    // Capture errors still arrive as events
+    listen<{ type: string; data: { error: string } }>("toxav://local-video", (event) => {
+      if (event.payload.type === "VideoError") {
+        console.error("[LocalPreview] Video capture error:", event.payload.data.error);
+        setError(event.payload.data.error);
       }
+    }).then((fn) => {
+      unlisten = fn;
+    }).catch((err) => {
+      console.error("[LocalPreview] Failed to register listener:", err);
+    });
 
+    const handleFrame = (frame: VideoFramePayload) => {
       if (!canvasRef.current) {
         console.warn("[LocalPreview] Canvas ref not ready");
         return;
@@ -63,43 +36,33 @@ export function LocalPreview({ className }: LocalPreviewProps) {
 
       const { width, height, data } = frame;
 
+      frameCountRef.current++;
       if (frameCountRef.current <= 3) {
-        console.log(`[LocalPreview] Frame ${frameCountRef.current}: ${width}x${height}, data length: ${data?.length}`);
+        console.log(`[LocalPreview] Frame ${frameCountRef.current}: ${width}x${height}, data length: ${data.length}`);
       }
 
-      if (!data || data.length === 0) {
+      if (data.length === 0) {
         console.warn("[LocalPreview] Empty frame data received");
         return;
       }
 
-      const ySize = width * height;
-      const uvSize = Math.floor(width / 2) * Math.floor(height / 2);
-      const expectedSize = ySize + uvSize * 2;
-
-      if (data.length !== expectedSize) {
-        console.warn(`[LocalPreview] Unexpected data size: ${data.length}, expected: ${expectedSize}`);
+      if (data.length !== width * height * 4) {
+        console.warn(`[LocalPreview] Unexpected data size: ${data.length}, expected: ${width * height * 4}`);
+        return;
       }
 
-      // Convert number[] to Uint8Array and split into Y, U, V planes
-      const yuvData = new Uint8Array(data);
-      const y = yuvData.subarray(0, ySize);
-      const u = yuvData.subarray(ySize, ySize + uvSize);
-      const v = yuvData.subarray(ySize + uvSize, ySize + uvSize * 2);
-
-      canvasRef.current.renderFrame(y, u, v, width, height);
+      canvasRef.current.renderFrame(data, width, height);
 
       if (!hasReceivedFrame) {
         setHasReceivedFrame(true);
       }
-    }).then((fn) => {
-      unlisten = fn;
-      console.log("[LocalPreview] Video frame listener registered successfully");
-    }).catch((err) => {
-      console.error("[LocalPreview] Failed to register listener:", err);
-    });
+    };
+
+    const stop = pollVideoFrames("local", handleFrame);
 
     return () => {
-      console.log("[LocalPreview] Cleaning up video frame listener");
+      console.log("[LocalPreview] Stopping local video frames");
+      stop();
       unlisten?.();
     };
   }, [hasReceivedFrame]);
@@ -1,6 +1,6 @@
 import { useRef, useEffect, useState } from "react";
 import { VideoCanvas, VideoCanvasHandle } from "./VideoCanvas";
-import { onRemoteVideoFrame, VideoFramePayload } from "../../api/calls";
+import { pollVideoFrames, VideoFramePayload } from "../../api/calls";
 
 interface RemoteVideoProps {
   friendNumber: number;
@@ -13,12 +13,7 @@ export function RemoteVideo({ friendNumber, className }: RemoteVideoProps) {
   const frameCountRef = useRef(0);
 
   useEffect(() => {
-    let unlisten: (() => void) | null = null;
-
     const handleFrame = (frame: VideoFramePayload) => {
-      // Only render frames from the specified friend
-      if (frame.friend_number !== friendNumber) return;
-
       if (!canvasRef.current) {
         console.warn("[RemoteVideo] Canvas ref not ready");
         return;
@@ -37,36 +32,24 @@ export function RemoteVideo({ friendNumber, className }: RemoteVideoProps) {
         return;
       }
 
-      const ySize = width * height;
-      const uvSize = Math.floor(width / 2) * Math.floor(height / 2);
-      const expectedSize = ySize + uvSize * 2;
-
-      if (data.length !== expectedSize) {
-        console.warn(`[RemoteVideo] Unexpected data size: ${data.length}, expected: ${expectedSize}`);
+      if (data.length !== width * height * 4) {
+        console.warn(`[RemoteVideo] Unexpected data size: ${data.length}, expected: ${width * height * 4}`);
+        return;
       }
 
-      // Convert number[] to Uint8Array and split into Y, U, V planes
-      const yuvData = new Uint8Array(data);
-      const y = yuvData.subarray(0, ySize);
-      const u = yuvData.subarray(ySize, ySize + uvSize);
-      const v = yuvData.subarray(ySize + uvSize, ySize + uvSize * 2);
-
-      canvasRef.current.renderFrame(y, u, v, width, height);
+      canvasRef.current.renderFrame(data, width, height);
 
       if (!hasReceivedFrame) {
         setHasReceivedFrame(true);
       }
     };
 
-    console.log(`[RemoteVideo] Setting up video frame listener for friend ${friendNumber}`);
-    onRemoteVideoFrame(handleFrame).then((fn) => {
-      unlisten = fn;
-      console.log(`[RemoteVideo] Video frame listener ready for friend ${friendNumber}`);
-    });
+    console.log(`[RemoteVideo] Polling video frames for friend ${friendNumber}`);
+    const stop = pollVideoFrames({ friend: friendNumber }, handleFrame);
 
     return () => {
-      console.log(`[RemoteVideo] Cleaning up video frame listener for friend ${friendNumber}`);
-      unlisten?.();
+      console.log(`[RemoteVideo] Stopping video frames for friend ${friendNumber}`);
+      stop();
     };
   }, [friendNumber, hasReceivedFrame]);
 
@@ -17,36 +17,19 @@ const VERTEX_SHADER = `
   }
 `;
 
-// Fragment shader - YUV to RGB conversion (BT.601)
+// Fragment shader - frames arrive as RGBA, converted and scaled by the backend
 const FRAGMENT_SHADER = `
   precision mediump float;
-  uniform sampler2D u_yTexture;
-  uniform sampler2D u_uTexture;
-  uniform sampler2D u_vTexture;
+  uniform sampler2D u_texture;
   varying vec2 v_texCoord;
 
   void main() {
-    float y = texture2D(u_yTexture, v_texCoord).r;
-    float u = texture2D(u_uTexture, v_texCoord).r - 0.5;
-    float v = texture2D(u_vTexture, v_texCoord).r - 0.5;
-
-    // BT.601 YUV to RGB conversion
-    float r = y + 1.402 * v;
-    float g = y - 0.344 * u - 0.714 * v;
-    float b = y + 1.772 * u;
-
-    gl_FragColor = vec4(r, g, b, 1.0);
+    gl_FragColor = texture2D(u_texture, v_texCoord);
   }
 `;
 
 export interface VideoCanvasHandle {
-  renderFrame: (
-    y: Uint8Array,
-    u: Uint8Array,
-    v: Uint8Array,
-    width: number,
-    height: number,
-  ) => void;
+  renderFrame: (rgba: Uint8Array, width: number, height: number) => void;
 }
 
 interface VideoCanvasProps {
@@ -58,11 +41,7 @@ export const VideoCanvas = forwardRef<VideoCanvasHandle, VideoCanvasProps>(
     const canvasRef = useRef<HTMLCanvasElement>(null);
     const glRef = useRef<WebGLRenderingContext | null>(null);
     const programRef = useRef<WebGLProgram | null>(null);
-    const texturesRef = useRef<{
-      y: WebGLTexture;
-      u: WebGLTexture;
-      v: WebGLTexture;
-    } | null>(null);
+    const textureRef = useRef<WebGLTexture | null>(null);
     const lastDimensionsRef = useRef<{ width: number; height: number } | null>(
       null,
     );
@@ -125,49 +104,32 @@ export const VideoCanvas = forwardRef<VideoCanvasHandle, VideoCanvasProps>(
       gl.enableVertexAttribArray(texCoordLoc);
       gl.vertexAttribPointer(texCoordLoc, 2, gl.FLOAT, false, 16, 8);
 
-      // Create textures for Y, U, V planes
-      const yTexture = createTexture(gl);
-      const uTexture = createTexture(gl);
-      const vTexture = createTexture(gl);
-
-      if (!yTexture || !uTexture || !vTexture) {
-        console.error("Failed to create textures");
+      // Create the frame texture
+      const texture = createTexture(gl);
+      if (!texture) {
+        console.error("Failed to create texture");
         return;
       }
 
-      texturesRef.current = { y: yTexture, u: uTexture, v: vTexture };
-
-      // Set texture uniforms
-      gl.uniform1i(gl.getUniformLocation(program, "u_yTexture"), 0);
-      gl.uniform1i(gl.getUniformLocation(program, "u_uTexture"), 1);
-      gl.uniform1i(gl.getUniformLocation(program, "u_vTexture"), 2);
+      textureRef.current = texture;
+      gl.uniform1i(gl.getUniformLocation(program, "u_texture"), 0);
 
       return () => {
         gl.deleteProgram(program);
         gl.deleteShader(vertexShader);
         gl.deleteShader(fragmentShader);
-        if (texturesRef.current) {
-          gl.deleteTexture(texturesRef.current.y);
-          gl.deleteTexture(texturesRef.current.u);
-          gl.deleteTexture(texturesRef.current.v);
-        }
+        gl.deleteTexture(texture);
       };
     }, []);
 
-    // Render a YUV frame
+    // Render an RGBA frame
     const renderFrame = useCallback(
-      (
-        y: Uint8Array,
-        u: Uint8Array,
-        v: Uint8Array,
-        width: number,
-        height: number,
-      ) => {
+      (rgba: Uint8Array, width: number, height: number) => {
         const gl = glRef.current;
-        const textures = texturesRef.current;
+        const texture = textureRef.current;
         const canvas = canvasRef.current;
 
-        if (!gl || !textures || !canvas) return;
+        if (!gl || !texture || !canvas) return;
 
         // Update canvas size if dimensions changed
         const lastDims = lastDimensionsRef.current;
@@ -178,52 +140,18 @@ export const VideoCanvas = forwardRef<VideoCanvasHandle, VideoCanvasProps>(
           lastDimensionsRef.current = { width, height };
         }
 
-        const uvWidth = width / 2;
-        const uvHeight = height / 2;
-
-        // Upload Y plane
         gl.activeTexture(gl.TEXTURE0);
-        gl.bindTexture(gl.TEXTURE_2D, textures.y);
+        gl.bindTexture(gl.TEXTURE_2D, texture);
         gl.texImage2D(
           gl.TEXTURE_2D,
           0,
-          gl.LUMINANCE,
+          gl.RGBA,
           width,
           height,
           0,
-          gl.LUMINANCE,
-          gl.UNSIGNED_BYTE,
-          y,
-        );
-
-        // Upload U plane
-        gl.activeTexture(gl.TEXTURE1);
-        gl.bindTexture(gl.TEXTURE_2D, textures.u);
-        gl.texImage2D(
-          gl.TEXTURE_2D,
-          0,
-          gl.LUMINANCE,
-          uvWidth,
-          uvHeight,
-          0,
-          gl.LUMINANCE,
-          gl.UNSIGNED_BYTE,
-          u,
-        );
-
-        // Upload V plane
-        gl.activeTexture(gl.TEXTURE2);
-        gl.bindTexture(gl.TEXTURE_2D, textures.v);
-        gl.texImage2D(
-          gl.TEXTURE_2D,
-          0,
-          gl.LUMINANCE,
-          uvWidth,
-          uvHeight,
-          0,
-          gl.LUMINANCE,
+          gl.RGBA,
           gl.UNSIGNED_BYTE,
-          v,
+          rgba,
         );
 
         // Draw
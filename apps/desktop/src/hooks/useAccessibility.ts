@@ -0,0 +1,46 @@
+import { useEffect, useState } from "react";
+import { useNavigationStore } from "../stores/navigationStore";
+import { useGuildStore } from "../stores/guildStore";
+import * as api from "../api/tox";
+import type { Conversation } from "../api/tox";
+
+/**
+ * Keeps the backend told which conversation is on screen, so text-to-speech
+ * only reads out its messages (and mentions elsewhere). Nothing is focused
+ * while the window is in the background.
+ */
+export function useAccessibility() {
+  const currentPage = useNavigationStore((s) => s.currentPage);
+  const friendNumber = useNavigationStore((s) => s.selectedFriendNumber);
+  const channelId = useNavigationStore((s) => s.selectedChannelId);
+  const dmGroupId = useNavigationStore((s) => s.selectedDmGroupId);
+  const dmGroupChannelId = useGuildStore((s) =>
+    dmGroupId ? s.channels[dmGroupId]?.[0]?.id ?? null : null,
+  );
+  const [windowFocused, setWindowFocused] = useState(() => document.hasFocus());
+
+  useEffect(() => {
+    const onFocus = () => setWindowFocused(true);
+    const onBlur = () => setWindowFocused(false);
+    window.addEventListener("focus", onFocus);
+    window.addEventListener("blur", onBlur);
+    return () => {
+      window.removeEventListener("focus", onFocus);
+      window.removeEventListener("blur", onBlur);
+    };
+  }, []);
+
+  useEffect(() => {
+    let conversation: Conversation | null = null;
+    if (windowFocused) {
+      if (currentPage === "dm" && friendNumber !== null) {
+        conversation = { type: "friend", id: friendNumber };
+      } else if (currentPage === "guild" && channelId) {
+        conversation = { type: "channel", id: channelId };
+      } else if (currentPage === "dm_group" && dmGroupChannelId) {
+        conversation = { type: "channel", id: dmGroupChannelId };
+      }
+    }
+    api.setFocusedConversation(conversation).catch(console.error);
+  }, [windowFocused, currentPage, friendNumber, channelId, dmGroupChannelId]);
+}
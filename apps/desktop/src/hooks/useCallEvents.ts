@@ -8,6 +8,8 @@ export function useCallEvents() {
   const updateCallState = useCallStore((s) => s.updateCallState);
   const endCall = useCallStore((s) => s.endCall);
   const updateDuration = useCallStore((s) => s.updateDuration);
+  const loadMissedCalls = useCallStore((s) => s.loadMissedCalls);
+  const addMissedCall = useCallStore((s) => s.addMissedCall);
   const activeCallStatus = useCallStore((s) => s.activeCall?.status);
 
   // Timer for updating call duration
@@ -34,6 +36,11 @@ export function useCallEvents() {
     };
   }, [activeCallStatus, updateDuration]);
 
+  // Missed calls from before this session
+  useEffect(() => {
+    loadMissedCalls();
+  }, [loadMissedCalls]);
+
   // Handle ToxAV events
   useEffect(() => {
     let unlisten: (() => void) | undefined;
@@ -71,6 +78,14 @@ export function useCallEvents() {
           endCall(event.data.friend_number, event.data.reason);
           break;
 
+        case "CallDropped":
+          endCall(event.data.friend_number, event.data.reason);
+          break;
+
+        case "MissedCall":
+          addMissedCall(event.data.friend_number);
+          break;
+
         case "AudioLevelUpdate":
           // TODO: Use for voice activity indicators
           break;
@@ -82,5 +97,5 @@ export function useCallEvents() {
     return () => {
       unlisten?.();
     };
-  }, [setIncomingCall, updateCallState, endCall]);
+  }, [setIncomingCall, updateCallState, endCall, addMissedCall]);
 }
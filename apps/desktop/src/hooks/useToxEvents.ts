@@ -1,22 +1,27 @@
 import { useEffect } from "react";
-import { onToxEvent, ToxEvent } from "../api/tox";
+import { onToxEvent, reconnectAllFriends, rejoinGuild, ToxEvent } from "../api/tox";
 import { useAuthStore } from "../stores/authStore";
 import { useFriendStore } from "../stores/friendStore";
 import { useMessageStore } from "../stores/messageStore";
 import { useGuildStore } from "../stores/guildStore";
 import { useChannelMessageStore } from "../stores/channelMessageStore";
 import { useNavigationStore } from "../stores/navigationStore";
+import { usePollStore } from "../stores/pollStore";
+import { useEventStore } from "../stores/eventStore";
 
 export function useToxEvents() {
   const setConnectionStatus = useAuthStore((s) => s.setConnectionStatus);
+  const setThreadStatus = useAuthStore((s) => s.setThreadStatus);
   const {
     addIncomingRequest,
     updateFriendName,
     updateFriendStatusMessage,
     updateFriendStatus,
     updateFriendConnectionStatus,
+    addStatusNote,
+    loadFriends,
   } = useFriendStore();
-  const { addIncomingMessage, setFriendTyping } = useMessageStore();
+  const { addIncomingMessage, setFriendTyping, removeMessages, applyDisappearingTimer } = useMessageStore();
   const {
     addGuildInvite,
     loadGuilds,
@@ -28,6 +33,10 @@ export function useToxEvents() {
     refreshChannels,
   } = useGuildStore();
   const addChannelMessage = useChannelMessageStore((s) => s.addIncomingMessage);
+  const loadChannelMessages = useChannelMessageStore((s) => s.loadMessages);
+  const applyPollUpdate = usePollStore((s) => s.applyUpdate);
+  const loadEvents = useEventStore((s) => s.loadEvents);
+  const addEventReminder = useEventStore((s) => s.addReminder);
 
   useEffect(() => {
     let unlisten: (() => void) | undefined;
@@ -37,6 +46,13 @@ export function useToxEvents() {
         case "ConnectionStatus":
           setConnectionStatus(event.data.connected, event.data.status);
           break;
+        case "Resumed":
+        case "NetworkModeChanged":
+          // Statuses went stale while the computer slept or the network changed
+          loadFriends();
+          loadGuilds();
+          loadDmGroups();
+          break;
         case "FriendRequest":
           addIncomingRequest(event.data.public_key, event.data.message);
           break;
@@ -51,8 +67,24 @@ export function useToxEvents() {
             is_outgoing: false,
             delivered: true,
             read: false,
+            locked: event.data.locked,
+            expires_at: event.data.expires_at,
+            incognito: event.data.incognito,
+            sticker: event.data.sticker,
           });
           break;
+        case "DirectMessagesExpired":
+          removeMessages(event.data.friend_number, event.data.message_ids);
+          break;
+        case "UnprotectedMessageRefused":
+          console.warn(`Dropped an unencrypted message in the locked conversation with friend ${event.data.friend_number}`);
+          break;
+        case "StatusNoteReceived":
+          addStatusNote(event.data.friend_number, event.data.note);
+          break;
+        case "DisappearingTimerChanged":
+          applyDisappearingTimer(event.data.friend_number, event.data.seconds);
+          break;
         case "FriendName":
           updateFriendName(event.data.friend_number, event.data.name);
           break;
@@ -67,6 +99,7 @@ export function useToxEvents() {
             event.data.friend_number,
             event.data.connected,
             event.data.status,
+            event.data.connection_type,
           );
           break;
         case "FriendTyping":
@@ -75,9 +108,10 @@ export function useToxEvents() {
         // Group events
         case "GroupInvite":
           addGuildInvite({
+            id: event.data.invite_id,
             friendNumber: event.data.friend_number,
-            inviteData: event.data.invite_data,
             groupName: event.data.group_name,
+            passwordRequired: event.data.password_required,
           });
           break;
         case "GroupSelfJoin":
@@ -92,6 +126,9 @@ export function useToxEvents() {
             public_key: event.data.public_key,
             role: "user",
             status: "online",
+            last_seen: null,
+            activity: null,
+            custom_status: null,
           });
           break;
         case "GroupPeerExit":
@@ -149,9 +186,26 @@ export function useToxEvents() {
             message_type: event.data.message_type,
             timestamp: event.data.timestamp,
             is_own: false,
+            formatted: event.data.formatted,
+            mentions_me: event.data.mentions_me,
+            filtered: event.data.filtered,
+            backfilled: false,
+            sender_verified: event.data.sender_verified,
+            signer_public_key: event.data.signer_public_key,
+            sticker: event.data.sticker,
           });
           break;
         }
+        case "GuildHistoryBackfilled": {
+          const guild = useGuildStore.getState().guilds.find((g) => g.group_number === event.data.group_number);
+          if (guild) {
+            // Backfilling can add channels we hadn't seen yet
+            refreshChannels(guild.id);
+            const selectedChannelId = useNavigationStore.getState().selectedChannelId;
+            if (selectedChannelId) loadChannelMessages(selectedChannelId);
+          }
+          break;
+        }
         case "GroupPeerStatus":
           updateMemberStatus(
             event.data.group_number,
@@ -159,8 +213,54 @@ export function useToxEvents() {
             event.data.status,
           );
           break;
+        case "PollUpdated":
+          applyPollUpdate(event.data.poll_id, event.data.tallies, event.data.closed);
+          break;
+        case "ChannelMessagesPurged":
+          loadChannelMessages(event.data.channel_id);
+          break;
+        case "GuildEventUpdated":
+          loadEvents(event.data.guild_id);
+          break;
+        case "GuildEventReminder":
+          addEventReminder({
+            guildId: event.data.guild_id,
+            eventId: event.data.event_id,
+            title: event.data.title,
+            startTime: event.data.start_time,
+          });
+          break;
+        case "GuildManifestChanged":
+          refreshChannels(event.data.guild_id);
+          break;
+        case "ToxThreadRestarted":
+          // The new thread starts offline; reload what it set up again
+          setThreadStatus("restarted");
+          setConnectionStatus(false, "none");
+          loadFriends();
+          loadGuilds();
+          loadDmGroups();
+          break;
+        case "ToxThreadStopped":
+          setThreadStatus("stopped");
+          setConnectionStatus(false, "none");
+          break;
+        case "ToxThreadStalled":
+          setThreadStatus(event.data.stalled ? "stalled" : "running");
+          break;
+        case "GroupJoinFail": {
+          if (event.data.fail_type !== "invalid_password") break;
+          // Ask for the password and join again with it
+          const guild = useGuildStore.getState().guilds.find((g) => g.group_number === event.data.group_number);
+          if (!guild) break;
+          const password = prompt(`"${guild.name}" needs a password to join:`);
+          if (password === null) break;
+          rejoinGuild(guild.id, password)
+            .then(() => loadGuilds())
+            .catch((e) => alert(`Couldn't join "${guild.name}": ${e}`));
+          break;
+        }
         case "GroupTopicChange":
-        case "GroupJoinFail":
         case "GroupCustomPacket":
           // Handled elsewhere or not yet needed
           break;
@@ -174,13 +274,18 @@ export function useToxEvents() {
     };
   }, [
     setConnectionStatus,
+    setThreadStatus,
     addIncomingRequest,
     addIncomingMessage,
     setFriendTyping,
+    removeMessages,
+    applyDisappearingTimer,
     updateFriendName,
     updateFriendStatusMessage,
     updateFriendStatus,
     updateFriendConnectionStatus,
+    addStatusNote,
+    loadFriends,
     addGuildInvite,
     loadGuilds,
     loadDmGroups,
@@ -189,6 +294,19 @@ export function useToxEvents() {
     updateMemberName,
     updateMemberStatus,
     addChannelMessage,
+    loadChannelMessages,
     refreshChannels,
+    applyPollUpdate,
+    loadEvents,
+    addEventReminder,
   ]);
+
+  // After a network change, like a VPN switched on, look for offline friends again
+  useEffect(() => {
+    const onOnline = () => {
+      reconnectAllFriends().catch((e) => console.error("Failed to reconnect friends:", e));
+    };
+    window.addEventListener("online", onOnline);
+    return () => window.removeEventListener("online", onOnline);
+  }, []);
 }
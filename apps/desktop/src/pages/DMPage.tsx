@@ -7,7 +7,11 @@ import * as api from "../api/tox";
 import type { DirectMessage } from "../api/tox";
 import { MiniCallIndicator } from "../components/call/VoiceCallUI";
 import { CallPanel } from "../components/call/CallPanel";
+import { CallQualityForm } from "../components/call/CallQualityForm";
+import * as callsApi from "../api/calls";
 import { FullscreenVideoModal } from "../components/video/FullscreenVideoModal";
+import { StickerImage } from "../components/stickers/StickerImage";
+import { ConversationTtsToggle, SpeakButton } from "../components/accessibility/Speech";
 
 const EMPTY_MESSAGES: never[] = [];
 
@@ -98,20 +102,26 @@ function DMHeader({
 
       {/* Call controls */}
       <div className="flex items-center gap-2">
+        <ConversationTtsToggle conversation={{ type: "friend", id: friendNumber }} />
+        <IncognitoButton friendNumber={friendNumber} />
+        <DisappearingTimerSelect friendNumber={friendNumber} />
+        <ConversationLockButton friendNumber={friendNumber} />
+        <ShareContactButton friendNumber={friendNumber} />
+        <CallQualityButton friendNumber={friendNumber} />
         {isInCallWithFriend ? (
           <MiniCallIndicator />
         ) : (
           <>
             {/* Voice call button */}
             <button
-              onClick={() => startCall(friendNumber, name, false)}
+              onClick={() => startCall(friendNumber, name)}
               disabled={!isOnline}
               className={`flex h-8 w-8 items-center justify-center rounded-md transition-colors ${
                 isOnline
                   ? "text-discord-muted hover:bg-discord-hover hover:text-white"
                   : "cursor-not-allowed text-discord-muted/50"
               }`}
-              title={isOnline ? "Start voice call" : "Friend is offline"}
+              title={isOnline ? "Start call" : "Friend is offline"}
             >
               <PhoneIcon className="h-5 w-5" />
             </button>
@@ -135,6 +145,323 @@ function DMHeader({
   );
 }
 
+function IncognitoButton({ friendNumber }: { friendNumber: number }) {
+  const [info, setInfo] = useState<api.IncognitoInfo | null>(null);
+
+  useEffect(() => {
+    setInfo(null);
+    api.getIncognito(friendNumber).then(setInfo).catch(console.error);
+  }, [friendNumber]);
+
+  const toggle = async () => {
+    const active = !info?.active;
+    try {
+      // Turning it off here also stops the conversation starting in incognito
+      if (!active && info?.default) {
+        await api.setIncognitoDefault(friendNumber, false);
+      } else {
+        await api.setIncognito(friendNumber, active);
+      }
+      setInfo({ active, default: active && (info?.default ?? false) });
+    } catch (e) {
+      console.error("Failed to switch incognito:", e);
+    }
+  };
+
+  const toggleDefault = async () => {
+    const enabled = !info?.default;
+    try {
+      await api.setIncognitoDefault(friendNumber, enabled);
+      setInfo({ active: enabled, default: enabled });
+    } catch (e) {
+      console.error("Failed to save incognito setting:", e);
+    }
+  };
+
+  return (
+    <div className="flex items-center gap-1">
+      <button
+        onClick={toggle}
+        className={`flex h-8 items-center justify-center rounded-md px-2 text-sm transition-colors hover:bg-discord-hover ${
+          info?.active ? "bg-discord-input text-white" : "text-discord-muted hover:text-white"
+        }`}
+        title={info?.active ? "Incognito: messages aren't saved" : "Go incognito (don't save messages)"}
+      >
+        🕶️
+      </button>
+      {info?.active && (
+        <label className="flex items-center gap-1 text-xs text-discord-muted" title="Start this conversation in incognito every time">
+          <input type="checkbox" checked={info.default} onChange={toggleDefault} />
+          Always
+        </label>
+      )}
+    </div>
+  );
+}
+
+const DISAPPEARING_OPTIONS: { label: string; seconds: number }[] = [
+  { label: "Off", seconds: 0 },
+  { label: "30 seconds", seconds: 30 },
+  { label: "5 minutes", seconds: 5 * 60 },
+  { label: "1 hour", seconds: 60 * 60 },
+  { label: "1 day", seconds: 24 * 60 * 60 },
+  { label: "1 week", seconds: 7 * 24 * 60 * 60 },
+];
+
+function DisappearingTimerSelect({ friendNumber }: { friendNumber: number }) {
+  const seconds = useMessageStore((s) => s.disappearingTimers[friendNumber] ?? 0);
+  const loadDisappearingTimer = useMessageStore((s) => s.loadDisappearingTimer);
+  const setDisappearingTimer = useMessageStore((s) => s.setDisappearingTimer);
+
+  useEffect(() => {
+    loadDisappearingTimer(friendNumber);
+  }, [friendNumber, loadDisappearingTimer]);
+
+  const options = DISAPPEARING_OPTIONS.some((o) => o.seconds === seconds)
+    ? DISAPPEARING_OPTIONS
+    : [...DISAPPEARING_OPTIONS, { label: formatRemaining(seconds), seconds }];
+
+  return (
+    <select
+      value={seconds}
+      onChange={(e) => setDisappearingTimer(friendNumber, Number(e.target.value)).catch(console.error)}
+      className="rounded bg-discord-input px-2 py-1 text-xs text-discord-text"
+      title="Disappearing messages"
+    >
+      {options.map((o) => (
+        <option key={o.seconds} value={o.seconds}>
+          ⏱ {o.label}
+        </option>
+      ))}
+    </select>
+  );
+}
+
+/** Time left before a disappearing message is deleted, updated every second */
+function ExpiryCountdown({ expiresAt }: { expiresAt: number }) {
+  const [now, setNow] = useState(() => Math.floor(Date.now() / 1000));
+
+  useEffect(() => {
+    const timer = setInterval(() => setNow(Math.floor(Date.now() / 1000)), 1000);
+    return () => clearInterval(timer);
+  }, []);
+
+  return (
+    <span className="ml-1 text-xs text-discord-muted" title="Disappearing message">
+      ⏱ {formatRemaining(Math.max(0, expiresAt - now))}
+    </span>
+  );
+}
+
+function formatRemaining(seconds: number): string {
+  if (seconds < 60) return `${seconds}s`;
+  if (seconds < 60 * 60) return `${Math.floor(seconds / 60)}m`;
+  if (seconds < 24 * 60 * 60) return `${Math.floor(seconds / 3600)}h`;
+  return `${Math.floor(seconds / 86400)}d`;
+}
+
+/** Send the friend our own contact card or another friend's */
+function ShareContactButton({ friendNumber }: { friendNumber: number }) {
+  const friends = useFriendStore((s) => s.friends);
+  const loadMessages = useMessageStore((s) => s.loadMessages);
+  const [open, setOpen] = useState(false);
+  const [error, setError] = useState<string | null>(null);
+
+  const share = async (contact?: number) => {
+    setOpen(false);
+    setError(null);
+    try {
+      await api.shareContact(friendNumber, contact);
+      await loadMessages(friendNumber);
+    } catch (e) {
+      setError(String(e));
+    }
+  };
+
+  return (
+    <div className="relative">
+      <button
+        onClick={() => setOpen(!open)}
+        className="flex h-8 items-center justify-center rounded-md px-2 text-sm text-discord-muted transition-colors hover:bg-discord-hover hover:text-white"
+        title={error ?? "Share a contact"}
+      >
+        📇
+      </button>
+      {open && (
+        <div className="absolute right-0 top-9 z-20 max-h-64 w-56 overflow-y-auto rounded-md bg-discord-darker p-1 shadow-lg">
+          <button
+            onClick={() => share()}
+            className="w-full rounded px-2 py-1.5 text-left text-sm text-discord-text hover:bg-discord-hover"
+          >
+            My contact card
+          </button>
+          {friends
+            .filter((f) => f.friend_number !== friendNumber)
+            .map((f) => (
+              <button
+                key={f.friend_number}
+                onClick={() => share(f.friend_number)}
+                className="w-full truncate rounded px-2 py-1.5 text-left text-sm text-discord-text hover:bg-discord-hover"
+              >
+                {f.name || f.public_key.slice(0, 16) + "..."}
+              </button>
+            ))}
+        </div>
+      )}
+    </div>
+  );
+}
+
+/** A shared Tox ID with a button to send it a friend request */
+function ContactCard({ card, isOwn }: { card: { toxId: string; name: string | null }; isOwn: boolean }) {
+  const friends = useFriendStore((s) => s.friends);
+  const addFriend = useFriendStore((s) => s.addFriend);
+  const [sent, setSent] = useState(false);
+  const isFriend = friends.some((f) => card.toxId.startsWith(f.public_key.toUpperCase()));
+
+  const add = async () => {
+    await addFriend(card.toxId, "Hello! I'd like to add you on Toxcord.");
+    if (!useFriendStore.getState().error) setSent(true);
+  };
+
+  return (
+    <div className="my-1 flex w-80 items-center gap-3 rounded-lg bg-discord-darker p-3">
+      <div className="flex h-10 w-10 flex-shrink-0 items-center justify-center rounded-full bg-discord-blurple text-sm font-bold text-white">
+        {card.name?.[0]?.toUpperCase() ?? "?"}
+      </div>
+      <div className="min-w-0 flex-1">
+        <p className="truncate text-sm font-medium text-white">{card.name ?? "Tox contact"}</p>
+        <p className="truncate font-mono text-xs text-discord-muted" title={card.toxId}>
+          {card.toxId.slice(0, 16)}...
+        </p>
+      </div>
+      {!isOwn && (
+        <button
+          onClick={add}
+          disabled={isFriend || sent}
+          className="rounded-md bg-discord-blurple px-3 py-1.5 text-xs font-medium text-white hover:bg-discord-blurple/80 disabled:cursor-not-allowed disabled:opacity-50"
+        >
+          {isFriend ? "Friends" : sent ? "Sent" : "Add Friend"}
+        </button>
+      )}
+    </div>
+  );
+}
+
+function ConversationLockButton({ friendNumber }: { friendNumber: number }) {
+  const loadMessages = useMessageStore((s) => s.loadMessages);
+  const [lock, setLock] = useState<api.ConversationLock | null>(null);
+
+  useEffect(() => {
+    setLock(null);
+    api.getConversationLock(friendNumber).then(setLock).catch(console.error);
+  }, [friendNumber]);
+
+  const handleClick = async () => {
+    try {
+      if (lock?.enabled && lock.unlocked) {
+        if (!confirm("Turn off the passphrase lock? Stored messages will be decrypted.")) return;
+        await api.disableConversationLock(friendNumber);
+        setLock({ enabled: false, unlocked: false, store_plaintext: false });
+      } else {
+        const passphrase = prompt(
+          lock?.enabled
+            ? "Enter the passphrase for this conversation"
+            : "Choose a passphrase to share with your friend. They must enable the same one.",
+        );
+        if (!passphrase) return;
+        const storePlaintext = lock?.enabled
+          ? lock.store_plaintext
+          : !confirm("Keep stored messages encrypted? You will need the passphrase to read them after logging in.");
+        setLock(await api.enableConversationLock(friendNumber, passphrase, storePlaintext));
+      }
+      await loadMessages(friendNumber);
+    } catch (e) {
+      alert(String(e));
+    }
+  };
+
+  const title = !lock?.enabled
+    ? "Lock conversation with a passphrase"
+    : lock.unlocked
+      ? "Conversation locked — click to turn off"
+      : "Enter conversation passphrase";
+
+  return (
+    <button
+      onClick={handleClick}
+      className={`flex h-8 w-8 items-center justify-center rounded-md transition-colors hover:bg-discord-hover ${
+        lock?.enabled ? "text-discord-green" : "text-discord-muted hover:text-white"
+      }`}
+      title={title}
+    >
+      {lock?.enabled && !lock.unlocked ? "🔐" : "🔒"}
+    </button>
+  );
+}
+
+/** Call quality for this friend, overriding the defaults from settings */
+function CallQualityButton({ friendNumber }: { friendNumber: number }) {
+  const [open, setOpen] = useState(false);
+  const [preferences, setPreferences] = useState<callsApi.CallPreferences | null>(null);
+  const [custom, setCustom] = useState(false);
+
+  useEffect(() => {
+    if (!open) return;
+    Promise.all([callsApi.getCallPreferences(friendNumber), callsApi.getDefaultCallPreferences()])
+      .then(([own, defaults]) => {
+        setCustom(own !== null);
+        setPreferences(own ?? defaults);
+      })
+      .catch(console.error);
+  }, [open, friendNumber]);
+
+  const handleChange = async (value: callsApi.CallPreferences) => {
+    setPreferences(value);
+    try {
+      await callsApi.setCallPreferences(friendNumber, value);
+      setCustom(true);
+    } catch (e) {
+      alert(String(e));
+    }
+  };
+
+  const handleReset = async () => {
+    try {
+      await callsApi.clearCallPreferences(friendNumber);
+      setPreferences(await callsApi.getDefaultCallPreferences());
+      setCustom(false);
+    } catch (e) {
+      alert(String(e));
+    }
+  };
+
+  return (
+    <div className="relative">
+      <button
+        onClick={() => setOpen(!open)}
+        className="flex h-8 w-8 items-center justify-center rounded-md text-discord-muted transition-colors hover:bg-discord-hover hover:text-white"
+        title="Call quality"
+      >
+        <SlidersIcon className="h-5 w-5" />
+      </button>
+      {open && preferences && (
+        <div className="absolute right-0 top-10 z-20 w-80 rounded-lg bg-discord-sidebar p-4 shadow-lg">
+          <CallQualityForm value={preferences} onChange={handleChange} />
+          <div className="mt-3 flex items-center justify-between text-xs text-discord-muted">
+            <span>{custom ? "Custom for this friend" : "Using your defaults"}</span>
+            {custom && (
+              <button onClick={handleReset} className="text-discord-blurple hover:underline">
+                Use defaults
+              </button>
+            )}
+          </div>
+        </div>
+      )}
+    </div>
+  );
+}
+
 // Icons for call buttons
 function PhoneIcon({ className }: { className?: string }) {
   return (
@@ -144,6 +471,14 @@ function PhoneIcon({ className }: { className?: string }) {
   );
 }
 
+function SlidersIcon({ className }: { className?: string }) {
+  return (
+    <svg className={className} fill="currentColor" viewBox="0 0 24 24">
+      <path d="M3 17v2h6v-2H3zM3 5v2h10V5H3zm10 16v-2h8v-2h-8v-2h-2v6h2zM7 9v2H3v2h4v2h2V9H7zm14 4v-2H11v2h10zm-6-4h2V7h4V5h-4V3h-2v6z" />
+    </svg>
+  );
+}
+
 function VideoIcon({ className }: { className?: string }) {
   return (
     <svg className={className} fill="currentColor" viewBox="0 0 24 24">
@@ -158,6 +493,7 @@ function MessageArea({ friendNumber, friendName }: { friendNumber: number; frien
   const hasMore = useMessageStore((s) => s.hasMore[friendNumber] ?? true);
   const loadMessages = useMessageStore((s) => s.loadMessages);
   const markRead = useMessageStore((s) => s.markRead);
+  const markMissedCallsSeen = useCallStore((s) => s.markMissedCallsSeen);
   const typing = useMessageStore((s) => s.typing[friendNumber] ?? false);
 
   const parentRef = useRef<HTMLDivElement>(null);
@@ -168,7 +504,8 @@ function MessageArea({ friendNumber, friendName }: { friendNumber: number; frien
   useEffect(() => {
     loadMessages(friendNumber);
     markRead(friendNumber);
-  }, [friendNumber, loadMessages, markRead]);
+    markMissedCallsSeen(friendNumber);
+  }, [friendNumber, loadMessages, markRead, markMissedCallsSeen]);
 
   // Mark as read when messages arrive and we're viewing this conversation
   useEffect(() => {
@@ -192,10 +529,10 @@ function MessageArea({ friendNumber, friendName }: { friendNumber: number; frien
 
     // Load more when scrolled to top
     if (scrollTop < 100 && hasMore && !isLoading && messages.length > 0) {
-      const oldestTimestamp = messages[0]?.timestamp;
-      if (oldestTimestamp) {
+      const oldest = messages[0];
+      if (oldest) {
         const prevHeight = scrollHeight;
-        loadMessages(friendNumber, oldestTimestamp).then(() => {
+        loadMessages(friendNumber, { timestamp: oldest.timestamp, id: oldest.id }).then(() => {
           // Maintain scroll position after loading older messages
           if (parentRef.current) {
             const newHeight = parentRef.current.scrollHeight;
@@ -320,16 +657,33 @@ function MessageGroup({
 
           {group.messages.map((msg) => (
             <div key={msg.id} className="group/msg relative">
-              {msg.message_type === "action" ? (
+              {msg.locked ? (
+                <p className="text-sm italic text-discord-muted">
+                  🔒 Encrypted message — enter the conversation passphrase to read it
+                </p>
+              ) : msg.message_type === "sticker" ? (
+                <StickerImage messageId={msg.id} content={msg.content} sticker={msg.sticker} />
+              ) : msg.message_type === "action" ? (
                 <p className="text-sm italic text-discord-muted">
                   * {senderName} {msg.content}
                 </p>
+              ) : api.parseContactCard(msg.content) ? (
+                <ContactCard card={api.parseContactCard(msg.content)!} isOwn={msg.is_outgoing} />
               ) : (
                 <p className="text-sm text-discord-text leading-[1.375rem]">{msg.content}</p>
               )}
+              {!msg.locked && (msg.message_type === "normal" || msg.message_type === "action") && (
+                <SpeakButton text={msg.content} />
+              )}
               {msg.is_outgoing && !msg.delivered && (
                 <span className="ml-1 text-xs text-discord-muted">(queued)</span>
               )}
+              {msg.expires_at != null && <ExpiryCountdown expiresAt={msg.expires_at} />}
+              {msg.incognito && (
+                <span className="ml-1 text-xs text-discord-muted" title="Not saved (incognito)">
+                  🕶️
+                </span>
+              )}
             </div>
           ))}
         </div>
@@ -3,6 +3,7 @@ import { useNavigationStore } from "../stores/navigationStore";
 import { useGuildStore } from "../stores/guildStore";
 import { useChannelMessageStore } from "../stores/channelMessageStore";
 import type { ChannelMessage } from "../api/tox";
+import { StickerImage } from "../components/stickers/StickerImage";
 
 const EMPTY_MESSAGES: never[] = [];
 const EMPTY_CHANNELS: never[] = [];
@@ -154,10 +155,10 @@ function MessageArea({ channelId, groupName }: { channelId: string; groupName: s
     setIsAtBottom(scrollHeight - scrollTop - clientHeight < 50);
 
     if (scrollTop < 100 && hasMore && !isLoading && messages.length > 0) {
-      const oldestTimestamp = messages[0]?.timestamp;
-      if (oldestTimestamp) {
+      const oldest = messages[0];
+      if (oldest) {
         const prevHeight = scrollHeight;
-        loadMessages(channelId, oldestTimestamp).then(() => {
+        loadMessages(channelId, { timestamp: oldest.timestamp, id: oldest.id }).then(() => {
           if (parentRef.current) {
             const newHeight = parentRef.current.scrollHeight;
             parentRef.current.scrollTop = newHeight - prevHeight;
@@ -262,7 +263,9 @@ function MessageGroup({
 
           {group.messages.map((msg) => (
             <div key={msg.id} className="group/msg relative">
-              {msg.message_type === "action" ? (
+              {msg.message_type === "sticker" ? (
+                <StickerImage messageId={msg.id} content={msg.content} sticker={msg.sticker} />
+              ) : msg.message_type === "action" ? (
                 <p className="text-sm italic text-discord-muted">
                   * {group.senderName} {msg.content}
                 </p>
@@ -2,7 +2,14 @@ import { useEffect, useState } from "react";
 import { useFriendStore } from "../stores/friendStore";
 import { useNavigationStore } from "../stores/navigationStore";
 import { useGuildStore } from "../stores/guildStore";
-import type { FriendInfo } from "../api/tox";
+import {
+  getToxIdQr,
+  parseContactLink,
+  refreshFriendConnection,
+  scanContactQr,
+  type ContactQr,
+  type FriendInfo,
+} from "../api/tox";
 
 type Tab = "online" | "all" | "pending" | "add";
 
@@ -85,6 +92,8 @@ export function FriendsPage() {
         </div>
       </div>
 
+      <StatusNotesBar />
+
       {/* Error */}
       {error && (
         <div className="mx-4 mt-2 rounded bg-discord-red/20 p-3 text-sm text-discord-red">
@@ -122,16 +131,96 @@ export function FriendsPage() {
   );
 }
 
+/** Post a note for friends and see theirs; notes disappear after a day */
+function StatusNotesBar() {
+  const [text, setText] = useState("");
+  const { statusNotes, ownStatusNotes, loadStatusNotes, postStatusNote } = useFriendStore();
+
+  useEffect(() => {
+    loadStatusNotes();
+  }, [loadStatusNotes]);
+
+  // Notes expire while the page is open too
+  const now = Date.now() / 1000;
+  const notes = [
+    ...ownStatusNotes.map((note) => ({ name: "You", note })),
+    ...statusNotes.map((s) => ({ name: s.friend_name, note: s.note })),
+  ].filter(({ note }) => note.expires_at > now);
+
+  const handlePost = async (e: React.FormEvent) => {
+    e.preventDefault();
+    if (!text.trim()) return;
+    await postStatusNote(text.trim());
+    setText("");
+  };
+
+  return (
+    <div className="border-b border-discord-dark px-4 py-2">
+      <form onSubmit={handlePost} className="flex gap-2">
+        <input
+          type="text"
+          value={text}
+          onChange={(e) => setText(e.target.value)}
+          placeholder="Post a note for your friends (gone in 24 hours)"
+          maxLength={500}
+          className="flex-1 rounded bg-discord-input px-3 py-1.5 text-sm text-discord-text placeholder-discord-muted outline-none"
+        />
+        <button
+          type="submit"
+          disabled={!text.trim()}
+          className="rounded bg-discord-blurple px-3 py-1.5 text-sm font-medium text-white transition-colors hover:bg-discord-blurple/80 disabled:opacity-50"
+        >
+          Post
+        </button>
+      </form>
+      {notes.length > 0 && (
+        <div className="mt-2 flex gap-2 overflow-x-auto">
+          {notes.map(({ name, note }) => (
+            <div
+              key={`${name}-${note.id}`}
+              className="w-48 flex-shrink-0 rounded bg-discord-sidebar px-3 py-2"
+              title={new Date(note.posted_at * 1000).toLocaleString()}
+            >
+              <div className="truncate text-xs font-semibold text-white">{name}</div>
+              <p className="line-clamp-3 text-sm text-discord-text">{note.text}</p>
+            </div>
+          ))}
+        </div>
+      )}
+    </div>
+  );
+}
+
 function AddFriendPanel() {
   const [toxId, setToxId] = useState("");
   const [message, setMessage] = useState("Hello! I'd like to add you on Toxcord.");
   const [success, setSuccess] = useState(false);
+  const [linkError, setLinkError] = useState<string | null>(null);
+  const [ownQr, setOwnQr] = useState<ContactQr | null>(null);
   const { addFriend, isLoading } = useFriendStore();
 
+  useEffect(() => {
+    getToxIdQr().then(setOwnQr).catch(console.error);
+  }, []);
+
   const handleSubmit = async (e: React.FormEvent) => {
     e.preventDefault();
     if (!toxId.trim()) return;
-    await addFriend(toxId.trim(), message.trim());
+    setLinkError(null);
+    // Links and tox: URIs are read down to the Tox ID
+    let address: string;
+    try {
+      const link = await parseContactLink(toxId);
+      if (link.kind !== "add_friend") {
+        setLinkError(link.kind === "join_guild" ? "That's a guild invite, not a Tox ID" : "That's a message link, not a Tox ID");
+        return;
+      }
+      address = link.tox_id;
+    } catch (e) {
+      setLinkError(String(e));
+      return;
+    }
+    await addFriend(address, message.trim());
     if (!useFriendStore.getState().error) {
       setToxId("");
       setSuccess(true);
@@ -139,12 +228,26 @@ function AddFriendPanel() {
     }
   };
 
+  const scanClipboard = async () => {
+    setLinkError(null);
+    try {
+      const link = await scanContactQr();
+      if (link.kind === "add_friend") {
+        setToxId(link.tox_id);
+      } else {
+        setLinkError("That QR code is a guild invite, not a Tox ID");
+      }
+    } catch (e) {
+      setLinkError(String(e));
+    }
+  };
+
   return (
     <div className="p-4">
       <div className="mb-6">
         <h4 className="mb-1 text-sm font-bold uppercase text-white">Add Friend</h4>
         <p className="text-sm text-discord-muted">
-          You can add friends by entering their Tox ID (76 characters).
+          You can add friends by entering their Tox ID (76 characters) or pasting their toxcord:// link.
         </p>
       </div>
 
@@ -155,7 +258,7 @@ function AddFriendPanel() {
               type="text"
               value={toxId}
               onChange={(e) => setToxId(e.target.value)}
-              placeholder="Enter a Tox ID"
+              placeholder="Enter a Tox ID or link"
               className="flex-1 rounded-md bg-discord-input p-2.5 text-sm text-white placeholder-discord-muted outline-none focus:ring-2 focus:ring-discord-blurple"
               autoFocus
             />
@@ -166,8 +269,18 @@ function AddFriendPanel() {
             >
               {isLoading ? "Sending..." : "Send Friend Request"}
             </button>
+            <button
+              type="button"
+              onClick={scanClipboard}
+              className="rounded-md bg-discord-input px-3 py-2.5 text-sm text-discord-text transition-colors hover:bg-discord-hover"
+              title="Read a QR code from an image on the clipboard"
+            >
+              Scan QR
+            </button>
           </div>
 
+          {linkError && <p className="mt-2 text-sm text-discord-red">{linkError}</p>}
+
           {success && (
             <p className="mt-2 text-sm text-discord-green">
               Friend request sent successfully!
@@ -187,6 +300,28 @@ function AddFriendPanel() {
           />
         </div>
       </form>
+
+      {ownQr && (
+        <div className="mt-6 flex items-center gap-4 rounded-lg bg-discord-darker p-4">
+          <img
+            src={`data:image/svg+xml;utf8,${encodeURIComponent(ownQr.qr_svg)}`}
+            alt="QR code of your Tox ID"
+            className="h-32 w-32 flex-shrink-0 rounded bg-white p-1"
+          />
+          <div className="min-w-0">
+            <h4 className="mb-1 text-sm font-bold uppercase text-white">Your Contact</h4>
+            <p className="mb-2 text-sm text-discord-muted">
+              Let a friend scan this, or send them your link.
+            </p>
+            <button
+              onClick={() => navigator.clipboard.writeText(ownQr.uri)}
+              className="rounded-md bg-discord-input px-3 py-1.5 text-xs text-discord-text transition-colors hover:bg-discord-hover"
+            >
+              Copy Link
+            </button>
+          </div>
+        </div>
+      )}
     </div>
   );
 }
@@ -305,8 +440,16 @@ function FriendListPanel({
 
             {/* Info */}
             <div className="min-w-0 flex-1">
-              <p className="truncate text-sm font-medium text-white">
+              <p className="flex items-center gap-1.5 truncate text-sm font-medium text-white">
                 {friend.name || friend.public_key.slice(0, 16) + "..."}
+                {friend.connection_type === "relayed" && (
+                  <span
+                    className="rounded bg-discord-channel px-1 text-[10px] font-semibold uppercase text-discord-muted"
+                    title="Connected through a TCP relay, not directly, so messages and calls may be slower"
+                  >
+                    Relayed
+                  </span>
+                )}
               </p>
               <p className="truncate text-xs text-discord-muted">
                 {friend.connection_status !== "none"
@@ -363,6 +506,19 @@ function FriendListPanel({
           >
             Message
           </button>
+          {friends.find((f) => f.friend_number === contextMenu.friendNumber)?.connection_status === "none" && (
+            <button
+              onClick={() => {
+                refreshFriendConnection(contextMenu.friendNumber).catch((e) =>
+                  console.error("Failed to reconnect friend:", e),
+                );
+                setContextMenu(null);
+              }}
+              className="w-full px-3 py-1.5 text-left text-sm text-discord-text hover:bg-discord-blurple hover:text-white"
+            >
+              Try Reconnecting
+            </button>
+          )}
           <button
             onClick={() => {
               removeFriend(contextMenu.friendNumber);
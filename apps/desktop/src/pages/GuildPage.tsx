@@ -3,7 +3,12 @@ import { useGuildStore } from "../stores/guildStore";
 import { useChannelMessageStore } from "../stores/channelMessageStore";
 import { useNavigationStore } from "../stores/navigationStore";
 import { MemberSidebar } from "../components/layout/MemberSidebar";
-import type { ChannelMessage } from "../api/tox";
+import { StickerImage } from "../components/stickers/StickerImage";
+import { ConversationTtsToggle, SpeakButton } from "../components/accessibility/Speech";
+import { usePollStore } from "../stores/pollStore";
+import { useEventStore } from "../stores/eventStore";
+import * as api from "../api/tox";
+import type { ChannelMessage, OnboardingInfo } from "../api/tox";
 
 const EMPTY_CHANNELS: never[] = [];
 const EMPTY_MESSAGES: never[] = [];
@@ -61,21 +66,50 @@ function ChannelView({
 }) {
   return (
     <div className="flex flex-1 flex-col bg-discord-chat">
-      <ChannelHeader name={channelName} topic={channelTopic} guildId={guildId} />
-      <ChannelMessages channelId={channelId} />
+      <ChannelHeader name={channelName} topic={channelTopic} guildId={guildId} channelId={channelId} />
+      <EventReminders guildId={guildId} />
+      <ChannelMessages guildId={guildId} channelId={channelId} />
       <ChannelInput guildId={guildId} channelId={channelId} channelName={channelName} />
     </div>
   );
 }
 
+function EventReminders({ guildId }: { guildId: string }) {
+  const reminders = useEventStore((s) => s.reminders);
+  const dismissReminder = useEventStore((s) => s.dismissReminder);
+
+  return (
+    <>
+      {reminders
+        .filter((r) => r.guildId === guildId)
+        .map((r) => (
+          <div
+            key={r.eventId}
+            className="mx-4 mb-2 flex items-center justify-between rounded bg-discord-blurple px-3 py-2 text-sm text-white"
+          >
+            <span>
+              📅 <span className="font-semibold">{r.title}</span> starts at{" "}
+              {new Date(r.startTime * 1000).toLocaleTimeString([], { hour: "2-digit", minute: "2-digit" })}
+            </span>
+            <button onClick={() => dismissReminder(r.eventId)} className="ml-4 hover:text-discord-muted">
+              ✕
+            </button>
+          </div>
+        ))}
+    </>
+  );
+}
+
 function ChannelHeader({
   name,
   topic,
   guildId,
+  channelId,
 }: {
   name: string;
   topic: string;
   guildId: string;
+  channelId: string;
 }) {
   const [showMenu, setShowMenu] = useState(false);
   const leaveGuild = useGuildStore((s) => s.leaveGuild);
@@ -110,7 +144,8 @@ function ChannelHeader({
         )}
       </div>
 
-      <div className="relative">
+      <div className="relative flex items-center gap-1">
+        <ConversationTtsToggle conversation={{ type: "channel", id: channelId }} />
         <button
           onClick={() => setShowMenu(!showMenu)}
           className="rounded p-1 text-discord-muted hover:bg-discord-hover hover:text-white"
@@ -153,7 +188,7 @@ function ChannelHeader({
   );
 }
 
-function ChannelMessages({ channelId }: { channelId: string }) {
+function ChannelMessages({ guildId, channelId }: { guildId: string; channelId: string }) {
   const messages = useChannelMessageStore((s) => s.messages[channelId] ?? EMPTY_MESSAGES);
   const isLoading = useChannelMessageStore((s) => s.isLoading);
   const hasMore = useChannelMessageStore((s) => s.hasMore[channelId] ?? true);
@@ -178,10 +213,10 @@ function ChannelMessages({ channelId }: { channelId: string }) {
     setIsAtBottom(scrollHeight - scrollTop - clientHeight < 50);
 
     if (scrollTop < 100 && hasMore && !isLoading && messages.length > 0) {
-      const oldestTimestamp = messages[0]?.timestamp;
-      if (oldestTimestamp) {
+      const oldest = messages[0];
+      if (oldest) {
         const prevHeight = scrollHeight;
-        loadMessages(channelId, oldestTimestamp).then(() => {
+        loadMessages(channelId, { timestamp: oldest.timestamp, id: oldest.id }).then(() => {
           if (parentRef.current) {
             const newHeight = parentRef.current.scrollHeight;
             parentRef.current.scrollTop = newHeight - prevHeight;
@@ -221,6 +256,7 @@ function ChannelMessages({ channelId }: { channelId: string }) {
         {groupedMessages.map((group, groupIdx) => (
           <MessageGroup
             key={group.messages[0].id}
+            guildId={guildId}
             group={group}
             showDateSeparator={
               groupIdx === 0 ||
@@ -244,9 +280,11 @@ interface MessageGroupData {
 }
 
 function MessageGroup({
+  guildId,
   group,
   showDateSeparator,
 }: {
+  guildId: string;
   group: MessageGroupData;
   showDateSeparator: boolean;
 }) {
@@ -290,8 +328,16 @@ function MessageGroup({
           </div>
 
           {group.messages.map((msg) => (
-            <div key={msg.id}>
-              {msg.message_type === "action" ? (
+            <div key={msg.id} className="group/msg relative">
+              {msg.filtered ? (
+                <FilteredMessage content={msg.content} />
+              ) : msg.message_type === "welcome" ? (
+                <p className="text-sm text-discord-green">👋 {msg.content}</p>
+              ) : msg.message_type === "poll" ? (
+                <PollCard guildId={guildId} pollId={msg.id} />
+              ) : msg.message_type === "sticker" ? (
+                <StickerImage messageId={msg.id} content={msg.content} sticker={msg.sticker} />
+              ) : msg.message_type === "action" ? (
                 <p className="text-sm italic text-discord-muted">
                   * {group.senderName} {msg.content}
                 </p>
@@ -300,6 +346,21 @@ function MessageGroup({
                   {msg.content}
                 </p>
               )}
+              {!msg.filtered && (msg.message_type === "normal" || msg.message_type === "action") && (
+                <SpeakButton text={`${group.senderName}: ${msg.content}`} />
+              )}
+              {msg.sender_verified === false && (
+                <p
+                  className="text-xs text-discord-yellow"
+                  title={
+                    msg.signer_public_key
+                      ? `Signed by a Tox identity we don't know for this member: ${msg.signer_public_key}`
+                      : "This message wasn't signed by the sender's Tox identity"
+                  }
+                >
+                  Unverified sender
+                </p>
+              )}
             </div>
           ))}
         </div>
@@ -308,6 +369,74 @@ function MessageGroup({
   );
 }
 
+function PollCard({ guildId, pollId }: { guildId: string; pollId: string }) {
+  const poll = usePollStore((s) => s.polls[pollId]);
+  const loadPoll = usePollStore((s) => s.loadPoll);
+  const vote = usePollStore((s) => s.vote);
+
+  useEffect(() => {
+    loadPoll(guildId, pollId);
+  }, [guildId, pollId, loadPoll]);
+
+  if (!poll) return null;
+  const total = poll.tallies.reduce((sum, n) => sum + n, 0);
+
+  return (
+    <div className="my-1 max-w-md rounded-lg bg-discord-sidebar p-3">
+      <p className="mb-2 text-sm font-semibold text-white">{poll.question}</p>
+      <div className="space-y-1">
+        {poll.options.map((option, i) => {
+          const count = poll.tallies[i] ?? 0;
+          const percent = total ? Math.round((count / total) * 100) : 0;
+          return (
+            <button
+              key={i}
+              disabled={poll.closed}
+              onClick={() => vote(guildId, pollId, i)}
+              className={`relative w-full overflow-hidden rounded px-2 py-1 text-left text-sm text-discord-text ${
+                poll.my_vote === i ? "ring-1 ring-discord-blurple" : ""
+              } ${poll.closed ? "cursor-default" : "hover:bg-discord-hover"}`}
+            >
+              <div
+                className="absolute inset-y-0 left-0 bg-discord-blurple/30"
+                style={{ width: `${percent}%` }}
+              />
+              <span className="relative flex justify-between">
+                <span>{option}</span>
+                <span className="text-discord-muted">{count}</span>
+              </span>
+            </button>
+          );
+        })}
+      </div>
+      <p className="mt-2 text-xs text-discord-muted">
+        {total} {total === 1 ? "vote" : "votes"} ·{" "}
+        {poll.closed
+          ? "Poll closed"
+          : `Closes ${new Date(poll.closes_at * 1000).toLocaleString()}`}
+      </p>
+    </div>
+  );
+}
+
+function FilteredMessage({ content }: { content: string }) {
+  const [revealed, setRevealed] = useState(false);
+
+  if (revealed) {
+    return (
+      <p className="text-sm leading-[1.375rem] text-discord-text">{content}</p>
+    );
+  }
+  return (
+    <button
+      onClick={() => setRevealed(true)}
+      className="text-sm italic text-discord-muted hover:underline"
+    >
+      Hidden by the word filter. Click to show.
+    </button>
+  );
+}
+
 function ChannelInput({
   guildId,
   channelId,
@@ -320,6 +449,13 @@ function ChannelInput({
   const [content, setContent] = useState("");
   const sendMessage = useChannelMessageStore((s) => s.sendMessage);
   const textareaRef = useRef<HTMLTextAreaElement>(null);
+  // Reloaded with the channels, which GuildManifestChanged refreshes
+  const channels = useGuildStore((s) => s.channels[guildId]);
+  const [onboarding, setOnboarding] = useState<OnboardingInfo | null>(null);
+
+  useEffect(() => {
+    api.getGuildOnboarding(guildId).then(setOnboarding).catch(() => setOnboarding(null));
+  }, [guildId, channels]);
 
   useEffect(() => {
     textareaRef.current?.focus();
@@ -353,6 +489,18 @@ function ChannelInput({
     }
   };
 
+  if (onboarding && !onboarding.may_post) {
+    return (
+      <RulesGate
+        rules={onboarding.rules}
+        onAccept={async () => {
+          await api.acceptGuildRules(guildId);
+          setOnboarding({ ...onboarding, may_post: true });
+        }}
+      />
+    );
+  }
+
   return (
     <div className="px-4 pb-6 pt-0">
       <div className="rounded-lg bg-discord-input">
@@ -376,6 +524,28 @@ function ChannelInput({
   );
 }
 
+function RulesGate({ rules, onAccept }: { rules: string; onAccept: () => Promise<void> }) {
+  const [error, setError] = useState("");
+
+  return (
+    <div className="px-4 pb-6 pt-0">
+      <div className="rounded-lg bg-discord-sidebar p-4">
+        <p className="mb-2 text-sm font-semibold text-white">Accept this server's rules to start posting</p>
+        <div className="mb-3 max-h-40 overflow-y-auto whitespace-pre-wrap rounded bg-discord-input p-3 text-sm text-discord-text">
+          {rules}
+        </div>
+        {error && <p className="mb-2 text-sm text-discord-red">{error}</p>}
+        <button
+          onClick={() => onAccept().catch((e) => setError(String(e)))}
+          className="rounded-md bg-discord-blurple px-4 py-2 text-sm font-medium text-white hover:bg-discord-blurple/80"
+        >
+          I accept
+        </button>
+      </div>
+    </div>
+  );
+}
+
 // ─── Helpers ──────────────────────────────────────────────────────────
 
 function groupMessages(messages: ChannelMessage[]): MessageGroupData[] {
@@ -1,13 +1,17 @@
 import { useEffect } from "react";
 import { useAuthStore } from "../stores/authStore";
 import { useNavigationStore } from "../stores/navigationStore";
+import { useFriendStore } from "../stores/friendStore";
 import { useToxEvents } from "../hooks/useToxEvents";
 import { useCallEvents } from "../hooks/useCallEvents";
+import { useAccessibility } from "../hooks/useAccessibility";
 import { getConnectionStatus } from "../api/tox";
 import { ServerSidebar } from "../components/layout/ServerSidebar";
 import { ChannelSidebar } from "../components/layout/ChannelSidebar";
 import { MainContent } from "../components/layout/MainContent";
 import { CallOverlay } from "../components/call/CallOverlay";
+import { DeepLinkPrompt } from "../components/layout/DeepLinkPrompt";
+import { UpdateBanner } from "../components/layout/UpdateBanner";
 import { FriendsPage } from "./FriendsPage";
 import { DMPage } from "./DMPage";
 import { GuildPage } from "./GuildPage";
@@ -17,9 +21,14 @@ import { SettingsPage } from "./SettingsPage";
 export function HomePage() {
   useToxEvents();
   useCallEvents();
+  useAccessibility();
 
   const setConnectionStatus = useAuthStore((s) => s.setConnectionStatus);
   const currentPage = useNavigationStore((s) => s.currentPage);
+  const resetFriends = useFriendStore((s) => s.reset);
+
+  // The friends list belongs to this profile
+  useEffect(() => resetFriends, [resetFriends]);
 
   useEffect(() => {
     const poll = async () => {
@@ -61,6 +70,8 @@ export function HomePage() {
         {renderContent()}
       </div>
       <CallOverlay />
+      <DeepLinkPrompt />
+      <UpdateBanner />
     </>
   );
 }
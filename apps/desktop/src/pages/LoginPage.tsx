@@ -1,5 +1,7 @@
 import { useEffect, useState } from "react";
 import { useAuthStore } from "../stores/authStore";
+import * as api from "../api/tox";
+import type { RecordingInfo } from "../api/tox";
 
 export function LoginPage() {
   const {
@@ -10,24 +12,57 @@ export function LoginPage() {
     createProfile,
     loadProfile,
     deleteProfile,
+    renameProfile,
+    duplicateProfile,
+    startReplay,
     clearError,
   } = useAuthStore();
 
-  const [mode, setMode] = useState<"select" | "create" | "login">("select");
+  const [mode, setMode] = useState<"select" | "create" | "restore" | "login">("select");
   const [profileName, setProfileName] = useState("");
   const [password, setPassword] = useState("");
   const [displayName, setDisplayName] = useState("");
+  const [recoveryPhrase, setRecoveryPhrase] = useState("");
   const [selectedProfile, setSelectedProfile] = useState<string | null>(null);
   const [profileToDelete, setProfileToDelete] = useState<string | null>(null);
+  const [profileToCopy, setProfileToCopy] = useState<{ name: string; action: "rename" | "duplicate" } | null>(null);
+  const [newProfileName, setNewProfileName] = useState("");
+  const [recordings, setRecordings] = useState<RecordingInfo[]>([]);
+  const [selectedRecording, setSelectedRecording] = useState("");
 
   useEffect(() => {
     loadProfiles();
   }, [loadProfiles]);
 
+  useEffect(() => {
+    api
+      .listEventRecordings()
+      .then((list) => {
+        setRecordings(list);
+        if (list.length > 0) setSelectedRecording(list[0].name);
+      })
+      .catch(console.error);
+  }, []);
+
   const handleCreate = async (e: React.FormEvent) => {
     e.preventDefault();
     if (!profileName.trim() || !displayName.trim()) return;
-    await createProfile(profileName.trim(), password, displayName.trim());
+    if (mode === "restore") {
+      if (!recoveryPhrase.trim()) return;
+      await createProfile(profileName.trim(), password, displayName.trim(), recoveryPhrase.trim());
+    } else {
+      await createProfile(profileName.trim(), password, displayName.trim());
+    }
+  };
+
+  const handleRenameOrDuplicate = async (e: React.FormEvent) => {
+    e.preventDefault();
+    if (!profileToCopy || !newProfileName.trim()) return;
+    const done =
+      profileToCopy.action === "rename"
+        ? await renameProfile(profileToCopy.name, newProfileName.trim())
+        : await duplicateProfile(profileToCopy.name, newProfileName.trim());
+    if (done) setProfileToCopy(null);
   };
 
   const handleLogin = async (e: React.FormEvent) => {
@@ -83,6 +118,30 @@ export function LoginPage() {
                         </div>
                         <span className="font-medium">{name}</span>
                       </button>
+                      <button
+                        onClick={() => {
+                          setProfileToCopy({ name, action: "rename" });
+                          setNewProfileName(name);
+                        }}
+                        className="rounded-md bg-discord-input p-3 text-discord-muted transition-colors hover:bg-discord-hover hover:text-white"
+                        title="Rename profile"
+                      >
+                        <svg className="h-5 w-5" fill="none" viewBox="0 0 24 24" stroke="currentColor" strokeWidth={2}>
+                          <path strokeLinecap="round" strokeLinejoin="round" d="M15.232 5.232l3.536 3.536M9 13l6.232-6.232a2.5 2.5 0 013.536 3.536L12.536 16.536 8 18l1.464-4.536z" />
+                        </svg>
+                      </button>
+                      <button
+                        onClick={() => {
+                          setProfileToCopy({ name, action: "duplicate" });
+                          setNewProfileName(`${name}-copy`);
+                        }}
+                        className="rounded-md bg-discord-input p-3 text-discord-muted transition-colors hover:bg-discord-hover hover:text-white"
+                        title="Duplicate profile"
+                      >
+                        <svg className="h-5 w-5" fill="none" viewBox="0 0 24 24" stroke="currentColor" strokeWidth={2}>
+                          <path strokeLinecap="round" strokeLinejoin="round" d="M8 16H6a2 2 0 01-2-2V6a2 2 0 012-2h8a2 2 0 012 2v2m-6 12h8a2 2 0 002-2v-8a2 2 0 00-2-2h-8a2 2 0 00-2 2v8a2 2 0 002 2z" />
+                        </svg>
+                      </button>
                       <button
                         onClick={(e) => {
                           e.stopPropagation();
@@ -111,16 +170,66 @@ export function LoginPage() {
             >
               Create New Profile
             </button>
+            <button
+              onClick={() => setMode("restore")}
+              className="mt-2 w-full text-sm text-discord-muted hover:text-white"
+            >
+              Restore from a recovery phrase
+            </button>
+            {recordings.length > 0 && (
+              <div className="mt-4 flex items-center gap-2">
+                <select
+                  value={selectedRecording}
+                  onChange={(e) => setSelectedRecording(e.target.value)}
+                  className="flex-1 rounded-md bg-discord-input p-2 text-sm text-white outline-none"
+                  title="Replays a recorded session without connecting to Tox"
+                >
+                  {recordings.map((r) => (
+                    <option key={r.name} value={r.name}>
+                      {r.description || r.name} ({r.event_count} events)
+                    </option>
+                  ))}
+                </select>
+                <button
+                  onClick={() => startReplay(selectedRecording)}
+                  disabled={isLoading || !selectedRecording}
+                  className="rounded-md bg-discord-input px-3 py-2 text-sm text-discord-muted transition-colors hover:bg-discord-hover hover:text-white disabled:opacity-50"
+                >
+                  Replay
+                </button>
+              </div>
+            )}
           </div>
         )}
 
         {/* Create profile form */}
-        {mode === "create" && (
+        {(mode === "create" || mode === "restore") && (
           <form onSubmit={handleCreate}>
             <h2 className="mb-4 text-lg font-semibold text-white">
-              Create Profile
+              {mode === "restore" ? "Restore Profile" : "Create Profile"}
             </h2>
 
+            {mode === "restore" && (
+              <div className="mb-4">
+                <label className="mb-1 block text-xs font-bold uppercase text-discord-muted">
+                  Recovery Phrase
+                </label>
+                <textarea
+                  value={recoveryPhrase}
+                  onChange={(e) => setRecoveryPhrase(e.target.value)}
+                  rows={3}
+                  placeholder="24 words, separated by spaces"
+                  spellCheck={false}
+                  className="w-full resize-none rounded-md bg-discord-darker p-2.5 font-mono text-sm text-white placeholder-discord-muted outline-none focus:ring-2 focus:ring-discord-blurple"
+                  autoFocus
+                />
+                <p className="mt-1 text-xs text-discord-muted">
+                  Brings back your Tox ID. Friends and servers have to be added again, and only run one
+                  profile with this identity at a time.
+                </p>
+              </div>
+            )}
+
             <div className="mb-4">
               <label className="mb-1 block text-xs font-bold uppercase text-discord-muted">
                 Profile Name
@@ -131,7 +240,7 @@ export function LoginPage() {
                 onChange={(e) => setProfileName(e.target.value)}
                 placeholder="my-profile"
                 className="w-full rounded-md bg-discord-darker p-2.5 text-white placeholder-discord-muted outline-none focus:ring-2 focus:ring-discord-blurple"
-                autoFocus
+                autoFocus={mode === "create"}
               />
               <p className="mt-1 text-xs text-discord-muted">
                 Used as the filename for your encrypted profile
@@ -169,15 +278,20 @@ export function LoginPage() {
 
             <button
               type="submit"
-              disabled={isLoading || !profileName.trim() || !displayName.trim()}
+              disabled={
+                isLoading || !profileName.trim() || !displayName.trim() || (mode === "restore" && !recoveryPhrase.trim())
+              }
               className="w-full rounded-md bg-discord-blurple p-3 font-medium text-white transition-colors hover:bg-discord-blurple/80 disabled:cursor-not-allowed disabled:opacity-50"
             >
-              {isLoading ? "Creating..." : "Create Profile"}
+              {isLoading ? "Creating..." : mode === "restore" ? "Restore Profile" : "Create Profile"}
             </button>
 
             <button
               type="button"
-              onClick={() => setMode("select")}
+              onClick={() => {
+                setMode("select");
+                setRecoveryPhrase("");
+              }}
               className="mt-3 w-full text-sm text-discord-muted hover:text-white"
             >
               Back
@@ -242,6 +356,50 @@ export function LoginPage() {
         </div>
       </div>
 
+      {/* Rename / duplicate modal */}
+      {profileToCopy && (
+        <div className="fixed inset-0 z-50 flex items-center justify-center bg-black/60">
+          <form onSubmit={handleRenameOrDuplicate} className="w-[400px] rounded-lg bg-discord-sidebar p-6">
+            <h2 className="mb-2 text-xl font-bold text-white">
+              {profileToCopy.action === "rename" ? "Rename Profile" : "Duplicate Profile"}
+            </h2>
+            <p className="mb-4 text-sm text-discord-muted">
+              {profileToCopy.action === "rename" ? (
+                <>New name for <span className="font-semibold text-white">{profileToCopy.name}</span>.</>
+              ) : (
+                <>
+                  The copy keeps the same Tox ID, friends and messages as{" "}
+                  <span className="font-semibold text-white">{profileToCopy.name}</span>. Don't use both at once.
+                </>
+              )}
+            </p>
+            <input
+              type="text"
+              value={newProfileName}
+              onChange={(e) => setNewProfileName(e.target.value)}
+              className="mb-4 w-full rounded-md bg-discord-darker p-2.5 text-white placeholder-discord-muted outline-none focus:ring-2 focus:ring-discord-blurple"
+              autoFocus
+            />
+            <div className="flex justify-end gap-3">
+              <button
+                type="button"
+                onClick={() => setProfileToCopy(null)}
+                className="rounded-md px-4 py-2 text-sm font-medium text-discord-muted hover:text-white"
+              >
+                Cancel
+              </button>
+              <button
+                type="submit"
+                disabled={isLoading || !newProfileName.trim() || newProfileName.trim() === profileToCopy.name}
+                className="rounded-md bg-discord-blurple px-4 py-2 text-sm font-medium text-white transition-colors hover:bg-discord-blurple/80 disabled:opacity-50"
+              >
+                {profileToCopy.action === "rename" ? "Rename" : "Duplicate"}
+              </button>
+            </div>
+          </form>
+        </div>
+      )}
+
       {/* Delete confirmation modal */}
       {profileToDelete && (
         <div className="fixed inset-0 z-50 flex items-center justify-center bg-black/60">
@@ -2,6 +2,18 @@ import { useEffect, useState } from "react";
 import { useAuthStore } from "../stores/authStore";
 import { useNavigationStore } from "../stores/navigationStore";
 import * as api from "../api/tox";
+import {
+  getDefaultCallPreferences,
+  getVideoSettings,
+  setDefaultCallPreferences,
+  setVideoBackgroundMode,
+  setVideoMaxOutput,
+  startCameraPreview,
+  stopCameraPreview,
+} from "../api/calls";
+import type { CallPreferences } from "../api/calls";
+import { CallQualityForm } from "../components/call/CallQualityForm";
+import { LocalPreview } from "../components/video";
 
 export function SettingsPage() {
   const { displayName, statusMessage } = useAuthStore();
@@ -166,6 +178,40 @@ export function SettingsPage() {
             </div>
           </section>
 
+          {/* Password Section */}
+          <PasswordSection />
+          <RecoveryPhraseSection />
+
+          {/* Troubleshooting Section */}
+          <NetworkSection />
+          <TroubleshootingSection />
+
+          {/* Logs Section */}
+          <LogsSection />
+          <EventRecordingSection />
+
+          {/* Media Cache Section */}
+          <MediaCacheSection />
+
+          <StorageSection />
+
+          {/* Usage Statistics Section */}
+          <UsageStatsSection />
+
+          {/* Video Section */}
+          <VideoSection />
+
+          {/* Call Quality Section */}
+          <CallQualitySection />
+
+          <IgnoredMembersSection />
+
+          {/* Accessibility Section */}
+          <AccessibilitySection />
+
+          {/* Updates Section */}
+          <UpdatesSection />
+
           {/* Appearance Section (placeholder) */}
           <section className="mb-10">
             <h3 className="mb-4 text-xs font-bold uppercase text-discord-muted">
@@ -182,3 +228,1114 @@ export function SettingsPage() {
     </div>
   );
 }
+
+function NetworkSection() {
+  const [info, setInfo] = useState<api.DhtInfo | null>(null);
+  const [mode, setMode] = useState<api.NetworkMode | null>(null);
+  const [power, setPower] = useState<api.PowerSavingInfo | null>(null);
+  const [error, setError] = useState("");
+
+  useEffect(() => {
+    const load = () => api.getDhtInfo().then(setInfo).catch(() => setInfo(null));
+    load();
+    api.getNetworkMode().then(setMode).catch(() => {});
+    api.getPowerSaving().then(setPower).catch(() => {});
+    const interval = setInterval(load, 5000);
+    return () => clearInterval(interval);
+  }, []);
+
+  const changeMode = async (value: api.NetworkMode) => {
+    setError("");
+    try {
+      await api.setNetworkMode(value);
+      setMode(value);
+    } catch (e) {
+      setError(String(e));
+    }
+  };
+
+  const changePowerSaving = async (value: api.PowerSavingMode) => {
+    setError("");
+    try {
+      await api.setPowerSaving(value);
+      setPower((p) => p && { ...p, mode: value });
+    } catch (e) {
+      setError(String(e));
+    }
+  };
+
+  return (
+    <section className="mb-10">
+      <h3 className="mb-4 text-xs font-bold uppercase text-discord-muted">
+        Network
+      </h3>
+      <div className="space-y-3 rounded-lg bg-discord-sidebar p-4">
+        <label className="flex items-center justify-between gap-4">
+          <span className="text-sm text-discord-text">
+            Network mode
+            <span className="block text-xs text-discord-muted">
+              LAN only reaches friends on this network without the public DHT. Offline stops all Tox traffic.
+            </span>
+          </span>
+          <select
+            value={mode ?? ""}
+            disabled={mode === null}
+            onChange={(e) => changeMode(e.target.value as api.NetworkMode)}
+            className="rounded-md bg-discord-input px-3 py-2 text-sm text-discord-text outline-none"
+          >
+            <option value="normal">Normal</option>
+            <option value="lan_only">LAN only</option>
+            <option value="offline">Offline</option>
+          </select>
+        </label>
+        <label className="flex items-center justify-between gap-4">
+          <span className="text-sm text-discord-text">
+            Power saving
+            <span className="block text-xs text-discord-muted">
+              Checks the network less often while there are no calls or new messages. Messages may take a
+              second longer to arrive.{power?.on_battery && " Running on battery now."}
+            </span>
+          </span>
+          <select
+            value={power?.mode ?? ""}
+            disabled={power === null}
+            onChange={(e) => changePowerSaving(e.target.value as api.PowerSavingMode)}
+            className="rounded-md bg-discord-input px-3 py-2 text-sm text-discord-text outline-none"
+          >
+            <option value="off">Off</option>
+            <option value="on_battery">On battery</option>
+            <option value="always">Always</option>
+          </select>
+        </label>
+        {error && <p className="text-sm text-discord-red">{error}</p>}
+        {info ? (
+          <dl className="grid grid-cols-[auto_1fr] gap-x-4 gap-y-1 text-sm">
+            <dt className="text-discord-muted">Connection</dt>
+            <dd className="text-discord-text">
+              {info.connection === "None"
+                ? "Offline"
+                : info.udp_active
+                  ? "Direct (UDP)"
+                  : "Through TCP relays"}
+            </dd>
+            <dt className="text-discord-muted">DHT nodes</dt>
+            <dd className="text-discord-text">
+              {info.dht_nodes}/{info.dht_capacity}, {info.announce_nodes} storing announcements
+            </dd>
+            <dt className="text-discord-muted">UDP port</dt>
+            <dd className="text-discord-text">{info.udp_port ?? "UDP disabled"}</dd>
+            <dt className="text-discord-muted">TCP relay server</dt>
+            <dd className="text-discord-text">{info.tcp_port ?? "Not running"}</dd>
+            <dt className="text-discord-muted">Known TCP relays</dt>
+            <dd className="text-discord-text">{info.tcp_relays}</dd>
+          </dl>
+        ) : (
+          <p className="text-sm text-discord-muted">Not connected</p>
+        )}
+      </div>
+    </section>
+  );
+}
+
+function TroubleshootingSection() {
+  const [report, setReport] = useState<api.DiagnosticsReport | null>(null);
+  const [running, setRunning] = useState(false);
+  const [copied, setCopied] = useState(false);
+  const [error, setError] = useState("");
+
+  const handleRun = async () => {
+    setError("");
+    setCopied(false);
+    setRunning(true);
+    try {
+      setReport(await api.getDiagnostics());
+    } catch (e) {
+      setError(String(e));
+    }
+    setRunning(false);
+  };
+
+  const handleCopy = async () => {
+    if (!report) return;
+    await navigator.clipboard.writeText(JSON.stringify(report, null, 2));
+    setCopied(true);
+    setTimeout(() => setCopied(false), 2000);
+  };
+
+  const devices = (status: api.DeviceStatus) =>
+    status.error ? `unavailable (${status.error})` : `${status.count} found`;
+
+  const buttonClass =
+    "rounded-md bg-discord-input px-4 py-2 text-sm font-medium text-white transition-colors hover:bg-discord-hover disabled:opacity-50";
+
+  return (
+    <section className="mb-10">
+      <h3 className="mb-4 text-xs font-bold uppercase text-discord-muted">
+        Troubleshooting
+      </h3>
+      <div className="space-y-3 rounded-lg bg-discord-sidebar p-4">
+        <p className="text-sm text-discord-muted">
+          Check your connection, database and devices.
+        </p>
+        <div className="flex gap-2">
+          <button onClick={handleRun} disabled={running} className={buttonClass}>
+            {running ? "Running..." : report ? "Run Again" : "Run Diagnostics"}
+          </button>
+          {report && (
+            <button onClick={handleCopy} className={buttonClass}>
+              {copied ? "Copied!" : "Copy Report"}
+            </button>
+          )}
+        </div>
+        {error && <p className="text-sm text-discord-red">{error}</p>}
+        {report && (
+          <dl className="grid grid-cols-[auto_1fr] gap-x-4 gap-y-1 text-sm">
+            <dt className="text-discord-muted">Version</dt>
+            <dd className="text-discord-text">
+              Toxcord {report.app_version}, toxcore {report.toxcore_version}
+            </dd>
+            <dt className="text-discord-muted">Connection</dt>
+            <dd className="text-discord-text">
+              {report.tox
+                ? `${report.tox.connection === "None" ? "Offline" : report.tox.connection.toUpperCase()}, ${report.tox.friends_online}/${report.tox.friends_total} friends online`
+                : "Not logged in"}
+            </dd>
+            <dt className="text-discord-muted">Proxy</dt>
+            <dd className="text-discord-text">
+              {report.proxy.kind === "none"
+                ? "None"
+                : `${report.proxy.kind.toUpperCase()} ${report.proxy.host}:${report.proxy.port}`}
+            </dd>
+            <dt className="text-discord-muted">Bootstrap</dt>
+            <dd className="text-discord-text">
+              {report.bootstrap_nodes.some((n) => n.reachable !== null)
+                ? `${report.bootstrap_nodes.filter((n) => n.reachable).length}/${report.bootstrap_nodes.length} nodes reachable`
+                : "Not checked behind a proxy"}
+            </dd>
+            {report.tox && (
+              <>
+                <dt className="text-discord-muted">Groups</dt>
+                <dd className="text-discord-text">
+                  {report.tox.groups.filter((g) => g.connected).length}/{report.tox.groups.length} connected
+                </dd>
+              </>
+            )}
+            {report.database && (
+              <>
+                <dt className="text-discord-muted">Database</dt>
+                <dd className="text-discord-text">
+                  {report.database.size_bytes !== null
+                    ? `${(report.database.size_bytes / 1024 / 1024).toFixed(1)} MB`
+                    : "Unknown size"}
+                  , schema v{report.database.schema_version ?? "?"}
+                </dd>
+              </>
+            )}
+            <dt className="text-discord-muted">Microphones</dt>
+            <dd className="text-discord-text">{devices(report.audio_input)}</dd>
+            <dt className="text-discord-muted">Speakers</dt>
+            <dd className="text-discord-text">{devices(report.audio_output)}</dd>
+            <dt className="text-discord-muted">Cameras</dt>
+            <dd className="text-discord-text">{devices(report.video_input)}</dd>
+          </dl>
+        )}
+      </div>
+    </section>
+  );
+}
+
+function LogsSection() {
+  const [lines, setLines] = useState<string[] | null>(null);
+  const [exported, setExported] = useState("");
+  const [error, setError] = useState("");
+
+  const handleShow = async () => {
+    setError("");
+    try {
+      setLines(await api.getRecentLogs(500));
+    } catch (e) {
+      setError(String(e));
+    }
+  };
+
+  const handleExport = async () => {
+    setError("");
+    try {
+      setExported(await api.exportLogs());
+    } catch (e) {
+      setError(String(e));
+    }
+  };
+
+  const buttonClass =
+    "rounded-md bg-discord-input px-4 py-2 text-sm font-medium text-white transition-colors hover:bg-discord-hover";
+
+  return (
+    <section className="mb-10">
+      <h3 className="mb-4 text-xs font-bold uppercase text-discord-muted">
+        Logs
+      </h3>
+      <div className="space-y-3 rounded-lg bg-discord-sidebar p-4">
+        <p className="text-sm text-discord-muted">
+          Attach your logs when reporting a bug. Keys and Tox IDs are shortened in them.
+        </p>
+        <div className="flex gap-2">
+          <button onClick={handleShow} className={buttonClass}>
+            {lines ? "Refresh" : "Show Logs"}
+          </button>
+          <button onClick={handleExport} className={buttonClass}>
+            Export
+          </button>
+        </div>
+        {exported && <p className="text-sm text-discord-green break-all">Saved to {exported}</p>}
+        {error && <p className="text-sm text-discord-red">{error}</p>}
+        {lines && (
+          <pre className="max-h-80 overflow-auto rounded-md bg-discord-input p-3 font-mono text-xs text-discord-text whitespace-pre-wrap break-all">
+            {lines.length ? lines.join("\n") : "No logs yet"}
+          </pre>
+        )}
+      </div>
+    </section>
+  );
+}
+
+function EventRecordingSection() {
+  const [status, setStatus] = useState<api.ReplayStatus | null>(null);
+  const [description, setDescription] = useState("");
+  const [saved, setSaved] = useState("");
+  const [error, setError] = useState("");
+
+  const refresh = () => api.getEventReplayStatus().then(setStatus).catch((e) => setError(String(e)));
+
+  useEffect(() => {
+    refresh();
+  }, []);
+
+  // Keep the event count moving while recording
+  useEffect(() => {
+    if (!status?.recording) return;
+    const interval = setInterval(refresh, 2000);
+    return () => clearInterval(interval);
+  }, [status?.recording]);
+
+  const start = async () => {
+    setError("");
+    setSaved("");
+    try {
+      await api.startEventRecording();
+      await refresh();
+    } catch (e) {
+      setError(String(e));
+    }
+  };
+
+  const stop = async () => {
+    setError("");
+    try {
+      setSaved(await api.stopEventRecording(description.trim() || undefined));
+      setDescription("");
+      await refresh();
+    } catch (e) {
+      setError(String(e));
+    }
+  };
+
+  const buttonClass =
+    "rounded-md bg-discord-input px-4 py-2 text-sm font-medium text-white transition-colors hover:bg-discord-hover";
+
+  return (
+    <section className="mb-10">
+      <h3 className="mb-4 text-xs font-bold uppercase text-discord-muted">
+        Event Recording
+      </h3>
+      <div className="space-y-3 rounded-lg bg-discord-sidebar p-4">
+        <p className="text-sm text-discord-muted">
+          Record what happens in this session to replay it later from the login screen, without connecting to Tox.
+          Recordings include message text and names as they arrived, and stay on this computer.
+        </p>
+        {status?.recording ? (
+          <div className="flex items-center gap-2">
+            <input
+              type="text"
+              value={description}
+              onChange={(e) => setDescription(e.target.value)}
+              placeholder="Description (optional)"
+              className="flex-1 rounded-md bg-discord-input p-2 text-sm text-white outline-none"
+            />
+            <button onClick={stop} className={buttonClass}>
+              Stop and Save
+            </button>
+          </div>
+        ) : (
+          <button onClick={start} disabled={!status} className={`${buttonClass} disabled:opacity-50`}>
+            Start Recording
+          </button>
+        )}
+        {status?.recording && (
+          <p className="text-sm text-discord-text">Recording… {status.recorded_events} events so far</p>
+        )}
+        {saved && <p className="text-sm text-discord-green break-all">Saved to {saved}</p>}
+        {error && <p className="text-sm text-discord-red">{error}</p>}
+      </div>
+    </section>
+  );
+}
+
+function MediaCacheSection() {
+  const [usage, setUsage] = useState<api.MediaCacheUsage | null>(null);
+  const [limitMb, setLimitMb] = useState("");
+  const [error, setError] = useState("");
+
+  const load = async () => {
+    try {
+      const current = await api.getMediaCacheUsage();
+      setUsage(current);
+      setLimitMb(String(Math.round(current.limit_bytes / 1024 / 1024)));
+    } catch (e) {
+      setError(String(e));
+    }
+  };
+
+  useEffect(() => {
+    load();
+  }, []);
+
+  const handleSaveLimit = async () => {
+    const mb = Number(limitMb);
+    if (!Number.isInteger(mb) || mb < 0) {
+      setError("Enter a size in MB");
+      return;
+    }
+    setError("");
+    try {
+      await api.setMediaCacheLimit(mb);
+      await load();
+    } catch (e) {
+      setError(String(e));
+    }
+  };
+
+  const handleClear = async () => {
+    setError("");
+    try {
+      await api.clearMediaCache();
+      await load();
+    } catch (e) {
+      setError(String(e));
+    }
+  };
+
+  const buttonClass =
+    "rounded-md bg-discord-input px-4 py-2 text-sm font-medium text-white transition-colors hover:bg-discord-hover";
+
+  return (
+    <section className="mb-10">
+      <h3 className="mb-4 text-xs font-bold uppercase text-discord-muted">
+        Media Cache
+      </h3>
+      <div className="space-y-3 rounded-lg bg-discord-sidebar p-4">
+        <p className="text-sm text-discord-muted">
+          Thumbnails and other media are kept on disk up to this size. The least recently
+          viewed are removed first and fetched again when needed.
+        </p>
+        {usage && (
+          <p className="text-sm text-discord-text">
+            {usage.count} files, {(usage.bytes / 1024 / 1024).toFixed(1)} MB used
+          </p>
+        )}
+        <div className="flex items-center gap-2">
+          <input
+            type="number"
+            min={0}
+            value={limitMb}
+            onChange={(e) => setLimitMb(e.target.value)}
+            className="w-28 rounded-md bg-discord-input px-3 py-2 text-sm text-discord-text outline-none"
+          />
+          <span className="text-sm text-discord-muted">MB</span>
+          <button onClick={handleSaveLimit} className={buttonClass}>
+            Save Limit
+          </button>
+          <button onClick={handleClear} className={buttonClass}>
+            Clear Cache
+          </button>
+        </div>
+        {error && <p className="text-sm text-discord-red">{error}</p>}
+      </div>
+    </section>
+  );
+}
+
+function formatMb(bytes: number): string {
+  return `${(bytes / 1024 / 1024).toFixed(1)} MB`;
+}
+
+function formatCallTime(seconds: number): string {
+  const hours = Math.floor(seconds / 3600);
+  const minutes = Math.round((seconds % 3600) / 60);
+  return hours > 0 ? `${hours} h ${minutes} min` : `${minutes} min`;
+}
+
+function UsageStatsSection() {
+  const thisYear = new Date().getFullYear();
+  const [year, setYear] = useState(thisYear);
+  const [stats, setStats] = useState<api.UsageStats | null>(null);
+  const [error, setError] = useState("");
+
+  useEffect(() => {
+    setStats(null);
+    api.getUsageStats(year).then(setStats).catch((e) => setError(String(e)));
+  }, [year]);
+
+  const totals = stats?.totals;
+  const busiest = stats?.days.find((d) => d.day === stats.busiest_day);
+  const maxMessages = Math.max(1, ...(stats?.top_conversations.map((c) => c.messages_sent + c.messages_received) ?? []));
+
+  return (
+    <section className="mb-10">
+      <h3 className="mb-4 text-xs font-bold uppercase text-discord-muted">
+        Your Year on Toxcord
+      </h3>
+      <div className="space-y-4 rounded-lg bg-discord-sidebar p-4">
+        <div className="flex items-center justify-between gap-4">
+          <p className="text-xs text-discord-muted">Counted on this device only. Nothing here is ever sent anywhere.</p>
+          <div className="flex items-center gap-2">
+            <button
+              onClick={() => setYear(year - 1)}
+              className="rounded px-2 text-discord-muted hover:text-white"
+              aria-label="Previous year"
+            >
+              ‹
+            </button>
+            <span className="text-sm font-semibold text-white">{year}</span>
+            <button
+              onClick={() => setYear(year + 1)}
+              disabled={year >= thisYear}
+              className="rounded px-2 text-discord-muted hover:text-white disabled:opacity-30"
+              aria-label="Next year"
+            >
+              ›
+            </button>
+          </div>
+        </div>
+
+        {totals && (
+          <div className="grid grid-cols-2 gap-3 sm:grid-cols-4">
+            {[
+              ["Messages sent", totals.messages_sent.toLocaleString()],
+              ["Messages received", totals.messages_received.toLocaleString()],
+              ["In calls", formatCallTime(totals.call_seconds)],
+              ["Files sent / received", `${formatMb(totals.bytes_sent)} / ${formatMb(totals.bytes_received)}`],
+            ].map(([label, value]) => (
+              <div key={label} className="rounded bg-discord-darker p-3">
+                <p className="text-lg font-semibold text-white">{value}</p>
+                <p className="text-xs text-discord-muted">{label}</p>
+              </div>
+            ))}
+          </div>
+        )}
+
+        {stats && busiest && (
+          <p className="text-sm text-discord-text">
+            Busiest day: {new Date(`${busiest.day}T00:00:00`).toLocaleDateString()}, with{" "}
+            {(busiest.messages_sent + busiest.messages_received).toLocaleString()} messages.{" "}
+            Active on {stats.days.length} {stats.days.length === 1 ? "day" : "days"}.
+          </p>
+        )}
+
+        {stats && stats.top_conversations.length > 0 && (
+          <div>
+            <p className="mb-2 text-xs font-bold uppercase text-discord-muted">Most active conversations</p>
+            <ul className="space-y-2">
+              {stats.top_conversations.map((c) => {
+                const messages = c.messages_sent + c.messages_received;
+                const name = c.name
+                  ? c.kind === "channel"
+                    ? `#${c.name}${c.guild_name ? ` in ${c.guild_name}` : ""}`
+                    : c.name
+                  : "Removed conversation";
+                return (
+                  <li key={`${c.kind}:${c.id}`}>
+                    <div className="flex justify-between text-sm">
+                      <span className="truncate text-discord-text">{name}</span>
+                      <span className="shrink-0 text-discord-muted">
+                        {messages.toLocaleString()} messages
+                        {c.call_seconds > 0 && `, ${formatCallTime(c.call_seconds)} in calls`}
+                      </span>
+                    </div>
+                    <div className="mt-1 h-1.5 rounded bg-discord-darker">
+                      <div
+                        className="h-1.5 rounded bg-discord-blurple"
+                        style={{ width: `${(messages / maxMessages) * 100}%` }}
+                      />
+                    </div>
+                  </li>
+                );
+              })}
+            </ul>
+          </div>
+        )}
+
+        {stats && stats.days.length === 0 && (
+          <p className="text-sm text-discord-muted">Nothing recorded for {year}.</p>
+        )}
+        {error && <p className="text-sm text-discord-red">{error}</p>}
+      </div>
+    </section>
+  );
+}
+
+function StorageSection() {
+  const profileName = useAuthStore((s) => s.profileName);
+  const [info, setInfo] = useState<api.ProfileStorageInfo | null>(null);
+  const [error, setError] = useState("");
+
+  useEffect(() => {
+    if (!profileName) return;
+    api.getProfileStorageInfo(profileName).then(setInfo).catch((e) => setError(String(e)));
+  }, [profileName]);
+
+  const parts: [string, keyof api.ProfileStorageInfo][] = [
+    ["Profile", "savedata_bytes"],
+    ["Messages", "database_bytes"],
+    ["Attachments", "attachments_bytes"],
+    ["Media cache", "cache_bytes"],
+    ["Logs", "logs_bytes"],
+  ];
+
+  return (
+    <section className="mb-10">
+      <h3 className="mb-4 text-xs font-bold uppercase text-discord-muted">
+        Storage
+      </h3>
+      <div className="space-y-3 rounded-lg bg-discord-sidebar p-4">
+        {info && (
+          <>
+            <p className="text-sm text-discord-muted">
+              Everything this profile keeps is in{" "}
+              <span className="select-all break-all font-mono text-discord-text">{info.path}</span>
+            </p>
+            <ul className="space-y-1 text-sm text-discord-text">
+              {parts.map(([label, key]) => (
+                <li key={key} className="flex justify-between">
+                  <span>{label}</span>
+                  <span>{formatMb(info[key] as number)}</span>
+                </li>
+              ))}
+              <li className="flex justify-between border-t border-discord-input pt-1 font-semibold">
+                <span>Total</span>
+                <span>{formatMb(info.total_bytes)}</span>
+              </li>
+            </ul>
+          </>
+        )}
+        {error && <p className="text-sm text-discord-red">{error}</p>}
+      </div>
+    </section>
+  );
+}
+
+const VIDEO_OUTPUT_PRESETS = [
+  { label: "480p", width: 854, height: 480 },
+  { label: "720p", width: 1280, height: 720 },
+  { label: "1080p", width: 1920, height: 1080 },
+  { label: "1440p", width: 2560, height: 1440 },
+];
+
+function VideoSection() {
+  const [height, setHeight] = useState<number | null>(null);
+  const [blur, setBlur] = useState(false);
+  const [previewing, setPreviewing] = useState(false);
+  const [error, setError] = useState("");
+
+  useEffect(() => {
+    getVideoSettings()
+      .then((s) => {
+        setHeight(s.max_output_height);
+        setBlur(s.background_mode === "blur");
+      })
+      .catch((e) => setError(String(e)));
+  }, []);
+
+  // Don't leave the camera on when leaving settings
+  useEffect(() => {
+    if (!previewing) return;
+    return () => {
+      stopCameraPreview().catch(() => {});
+    };
+  }, [previewing]);
+
+  const toggleBlur = async (enabled: boolean) => {
+    setError("");
+    try {
+      await setVideoBackgroundMode(enabled ? "blur" : "off");
+      setBlur(enabled);
+    } catch (e) {
+      setError(String(e));
+    }
+  };
+
+  const togglePreview = async () => {
+    setError("");
+    try {
+      if (previewing) {
+        await stopCameraPreview();
+        setPreviewing(false);
+      } else {
+        await startCameraPreview();
+        setPreviewing(true);
+      }
+    } catch (e) {
+      setError(String(e));
+    }
+  };
+
+  const handleChange = async (value: string) => {
+    const preset = VIDEO_OUTPUT_PRESETS.find((p) => String(p.height) === value);
+    if (!preset) return;
+    setError("");
+    try {
+      await setVideoMaxOutput(preset.width, preset.height);
+      setHeight(preset.height);
+    } catch (e) {
+      setError(String(e));
+    }
+  };
+
+  return (
+    <section className="mb-10">
+      <h3 className="mb-4 text-xs font-bold uppercase text-discord-muted">
+        Video
+      </h3>
+      <div className="space-y-3 rounded-lg bg-discord-sidebar p-4">
+        <label className="flex items-center justify-between gap-4">
+          <span className="text-sm text-discord-text">
+            Maximum resolution for incoming video
+          </span>
+          <select
+            value={height ?? ""}
+            onChange={(e) => handleChange(e.target.value)}
+            className="rounded-md bg-discord-input px-3 py-2 text-sm text-discord-text outline-none"
+          >
+            {height !== null && !VIDEO_OUTPUT_PRESETS.some((p) => p.height === height) && (
+              <option value={height}>{height}p</option>
+            )}
+            {VIDEO_OUTPUT_PRESETS.map((p) => (
+              <option key={p.label} value={p.height}>
+                {p.label}
+              </option>
+            ))}
+          </select>
+        </label>
+        <p className="text-sm text-discord-muted">
+          Lower resolutions use less CPU during calls.
+        </p>
+        <label className="flex items-center justify-between gap-4">
+          <span className="text-sm text-discord-text">
+            Blur my background
+            <span className="block text-xs text-discord-muted">
+              Keeps the centre of the picture sharp. Turns itself off if your computer can't keep up.
+            </span>
+          </span>
+          <input
+            type="checkbox"
+            checked={blur}
+            onChange={(e) => toggleBlur(e.target.checked)}
+            className="h-4 w-4 rounded border-discord-muted bg-discord-input text-discord-blurple focus:ring-discord-blurple"
+          />
+        </label>
+        <button
+          onClick={togglePreview}
+          className="rounded-md bg-discord-input px-4 py-2 text-sm font-medium text-white transition-colors hover:bg-discord-hover"
+        >
+          {previewing ? "Stop Camera" : "Test Camera"}
+        </button>
+        {previewing && <LocalPreview className="aspect-video w-80" />}
+        {error && <p className="text-sm text-discord-red">{error}</p>}
+      </div>
+    </section>
+  );
+}
+
+const SPEECH_RATES = [0.75, 1, 1.25, 1.5, 2];
+
+function AccessibilitySection() {
+  const [settings, setSettings] = useState<api.AccessibilitySettings | null>(null);
+  const [error, setError] = useState("");
+
+  useEffect(() => {
+    api.getAccessibilitySettings().then(setSettings).catch((e) => setError(String(e)));
+  }, []);
+
+  const update = async (changes: Partial<api.AccessibilitySettings>) => {
+    if (!settings) return;
+    const next = { ...settings, ...changes };
+    setError("");
+    try {
+      await api.setAccessibilitySettings(next);
+      setSettings(next);
+    } catch (e) {
+      setError(String(e));
+    }
+  };
+
+  return (
+    <section className="mb-10">
+      <h3 className="mb-4 text-xs font-bold uppercase text-discord-muted">
+        Accessibility
+      </h3>
+      <div className="space-y-3 rounded-lg bg-discord-sidebar p-4">
+        <label className="flex items-center justify-between gap-4">
+          <span className="text-sm text-discord-text">
+            Read messages aloud
+            <span className="block text-xs text-discord-muted">
+              New messages in the conversation you have open are spoken with your system's voice. Use 🔇 in a
+              conversation's header to leave it out.
+            </span>
+          </span>
+          <input
+            type="checkbox"
+            checked={settings?.tts_enabled ?? false}
+            disabled={!settings}
+            onChange={(e) => update({ tts_enabled: e.target.checked })}
+            className="h-4 w-4 rounded border-discord-muted bg-discord-input text-discord-blurple focus:ring-discord-blurple"
+          />
+        </label>
+        <label className="flex items-center justify-between gap-4">
+          <span className="text-sm text-discord-text">Also read mentions from other conversations</span>
+          <input
+            type="checkbox"
+            checked={settings?.speak_mentions ?? false}
+            disabled={!settings?.tts_enabled}
+            onChange={(e) => update({ speak_mentions: e.target.checked })}
+            className="h-4 w-4 rounded border-discord-muted bg-discord-input text-discord-blurple focus:ring-discord-blurple"
+          />
+        </label>
+        <label className="flex items-center justify-between gap-4">
+          <span className="text-sm text-discord-text">Speech rate</span>
+          <select
+            value={settings?.speech_rate ?? 1}
+            disabled={!settings}
+            onChange={(e) => update({ speech_rate: Number(e.target.value) })}
+            className="rounded-md bg-discord-input px-3 py-2 text-sm text-discord-text outline-none"
+          >
+            {settings && !SPEECH_RATES.includes(settings.speech_rate) && (
+              <option value={settings.speech_rate}>{settings.speech_rate}×</option>
+            )}
+            {SPEECH_RATES.map((rate) => (
+              <option key={rate} value={rate}>
+                {rate}×
+              </option>
+            ))}
+          </select>
+        </label>
+        <button
+          onClick={() => api.speakMessage("This is how messages will sound.").catch((e) => setError(String(e)))}
+          className="rounded-md bg-discord-input px-4 py-2 text-sm font-medium text-white transition-colors hover:bg-discord-hover"
+        >
+          Test Voice
+        </button>
+        {error && <p className="text-sm text-discord-red">{error}</p>}
+      </div>
+    </section>
+  );
+}
+
+function UpdatesSection() {
+  const [settings, setSettings] = useState<api.UpdateSettings | null>(null);
+  const [checking, setChecking] = useState(false);
+  const [result, setResult] = useState("");
+  const [error, setError] = useState("");
+
+  useEffect(() => {
+    api.getUpdateSettings().then(setSettings).catch((e) => setError(String(e)));
+  }, []);
+
+  const setEnabled = async (enabled: boolean) => {
+    if (!settings) return;
+    const next = { ...settings, enabled };
+    setError("");
+    try {
+      await api.setUpdateSettings(next);
+      setSettings(next);
+    } catch (e) {
+      setError(String(e));
+    }
+  };
+
+  const check = async () => {
+    setChecking(true);
+    setResult("");
+    setError("");
+    try {
+      const update = await api.checkForUpdates();
+      setResult(update ? `Toxcord ${update.version} is available.` : "Toxcord is up to date.");
+    } catch (e) {
+      setError(String(e));
+    } finally {
+      setChecking(false);
+    }
+  };
+
+  return (
+    <section className="mb-10">
+      <h3 className="mb-4 text-xs font-bold uppercase text-discord-muted">
+        Updates
+      </h3>
+      <div className="space-y-3 rounded-lg bg-discord-sidebar p-4">
+        <label className="flex items-center justify-between gap-4">
+          <span className="text-sm text-discord-text">
+            Check for updates automatically
+            <span className="block text-xs text-discord-muted">
+              Looks for new releases every few hours, through your proxy if you use one. Nothing is installed
+              without asking.
+            </span>
+          </span>
+          <input
+            type="checkbox"
+            checked={settings?.enabled ?? false}
+            disabled={!settings}
+            onChange={(e) => setEnabled(e.target.checked)}
+            className="h-4 w-4 rounded border-discord-muted bg-discord-input text-discord-blurple focus:ring-discord-blurple"
+          />
+        </label>
+        <div className="flex items-center gap-3">
+          <button
+            onClick={check}
+            disabled={checking}
+            className="rounded-md bg-discord-input px-4 py-2 text-sm font-medium text-white transition-colors hover:bg-discord-hover disabled:opacity-50"
+          >
+            {checking ? "Checking…" : "Check Now"}
+          </button>
+          {result && <span className="text-sm text-discord-muted">{result}</span>}
+        </div>
+        {error && <p className="text-sm text-discord-red">{error}</p>}
+      </div>
+    </section>
+  );
+}
+
+function CallQualitySection() {
+  const [preferences, setPreferences] = useState<CallPreferences | null>(null);
+  const [error, setError] = useState("");
+
+  useEffect(() => {
+    getDefaultCallPreferences()
+      .then(setPreferences)
+      .catch((e) => setError(String(e)));
+  }, []);
+
+  const handleChange = async (value: CallPreferences) => {
+    setError("");
+    try {
+      await setDefaultCallPreferences(value);
+      setPreferences(value);
+    } catch (e) {
+      setError(String(e));
+    }
+  };
+
+  return (
+    <section className="mb-10">
+      <h3 className="mb-4 text-xs font-bold uppercase text-discord-muted">
+        Call Quality
+      </h3>
+      <div className="space-y-3 rounded-lg bg-discord-sidebar p-4">
+        {preferences && <CallQualityForm value={preferences} onChange={handleChange} />}
+        <p className="text-sm text-discord-muted">
+          Used for every friend unless you set their own from the conversation header. Lower
+          bitrates hold up better on slow connections.
+        </p>
+        {error && <p className="text-sm text-discord-red">{error}</p>}
+      </div>
+    </section>
+  );
+}
+
+function IgnoredMembersSection() {
+  const [keep, setKeep] = useState<boolean | null>(null);
+  const [error, setError] = useState("");
+
+  useEffect(() => {
+    api.getKeepIgnoredMessages()
+      .then(setKeep)
+      .catch((e) => setError(String(e)));
+  }, []);
+
+  const handleChange = async (value: boolean) => {
+    setError("");
+    try {
+      await api.setKeepIgnoredMessages(value);
+      setKeep(value);
+    } catch (e) {
+      setError(String(e));
+    }
+  };
+
+  return (
+    <section className="mb-10">
+      <h3 className="mb-4 text-xs font-bold uppercase text-discord-muted">
+        Ignored Members
+      </h3>
+      <div className="space-y-3 rounded-lg bg-discord-sidebar p-4">
+        <label className="flex items-center justify-between gap-4">
+          <span className="text-sm text-discord-text">Keep their messages, hidden</span>
+          <input
+            type="checkbox"
+            checked={keep ?? false}
+            disabled={keep === null}
+            onChange={(e) => handleChange(e.target.checked)}
+            className="h-4 w-4 rounded border-discord-muted bg-discord-input text-discord-blurple focus:ring-discord-blurple"
+          />
+        </label>
+        <p className="text-sm text-discord-muted">
+          Ignore a server member from the member list to stop seeing their messages and status.
+          Messages they send are dropped, or with this on, stored out of sight and shown again if
+          you stop ignoring them.
+        </p>
+        {error && <p className="text-sm text-discord-red">{error}</p>}
+      </div>
+    </section>
+  );
+}
+
+function PasswordSection() {
+  const [current, setCurrent] = useState("");
+  const [next, setNext] = useState("");
+  const [confirm, setConfirm] = useState("");
+  const [error, setError] = useState("");
+  const [done, setDone] = useState(false);
+  const [saving, setSaving] = useState(false);
+
+  const handleChange = async () => {
+    setError("");
+    setDone(false);
+    if (next !== confirm) {
+      setError("Passwords don't match");
+      return;
+    }
+    setSaving(true);
+    try {
+      await api.changePassword(current, next);
+      setCurrent("");
+      setNext("");
+      setConfirm("");
+      setDone(true);
+    } catch (e) {
+      setError(String(e));
+    }
+    setSaving(false);
+  };
+
+  const inputClass =
+    "w-full rounded-md bg-discord-input px-3 py-2 text-sm text-white placeholder-discord-muted outline-none focus:ring-2 focus:ring-discord-blurple";
+
+  return (
+    <section className="mb-10">
+      <h3 className="mb-4 text-xs font-bold uppercase text-discord-muted">
+        Password
+      </h3>
+      <div className="space-y-3 rounded-lg bg-discord-sidebar p-4">
+        <p className="text-sm text-discord-muted">
+          Your profile and message history are encrypted with your password.
+        </p>
+        <input type="password" value={current} onChange={(e) => setCurrent(e.target.value)} className={inputClass} placeholder="Current password" />
+        <input type="password" value={next} onChange={(e) => setNext(e.target.value)} className={inputClass} placeholder="New password" />
+        <input type="password" value={confirm} onChange={(e) => setConfirm(e.target.value)} className={inputClass} placeholder="Confirm new password" />
+        {error && <p className="text-sm text-discord-red">{error}</p>}
+        {done && <p className="text-sm text-discord-green">Password changed</p>}
+        <button
+          onClick={handleChange}
+          disabled={saving || !next}
+          className="rounded-md bg-discord-blurple px-4 py-2 text-sm font-medium text-white transition-colors hover:bg-discord-blurple/80 disabled:opacity-50"
+        >
+          {saving ? "Changing..." : "Change Password"}
+        </button>
+      </div>
+    </section>
+  );
+}
+
+function RecoveryPhraseSection() {
+  const [understood, setUnderstood] = useState(false);
+  const [password, setPassword] = useState("");
+  const [recovery, setRecovery] = useState<api.RecoveryPhrase | null>(null);
+  const [error, setError] = useState("");
+
+  const handleReveal = async () => {
+    setError("");
+    try {
+      setRecovery(await api.getRecoveryPhrase(password));
+      setPassword("");
+    } catch (e) {
+      setError(String(e));
+    }
+  };
+
+  const handleHide = () => {
+    setRecovery(null);
+    setUnderstood(false);
+  };
+
+  return (
+    <section className="mb-10">
+      <h3 className="mb-4 text-xs font-bold uppercase text-discord-muted">
+        Recovery Phrase
+      </h3>
+      <div className="space-y-3 rounded-lg bg-discord-sidebar p-4">
+        <p className="text-sm text-discord-muted">
+          24 words that bring back your Tox ID if you lose every device with this profile on it. Write them
+          down or print the QR code and keep it somewhere safe and offline.
+        </p>
+        <p className="rounded bg-discord-red/20 p-3 text-sm text-discord-red">
+          Anyone who sees your recovery phrase can become you: read new messages sent to you and talk to
+          your friends in your name. Never share it, type it into a website or store it in the cloud.
+        </p>
+        {recovery ? (
+          <>
+            <ol className="grid grid-cols-4 gap-2 font-mono text-sm text-white">
+              {recovery.phrase.split(" ").map((word, i) => (
+                <li key={i} className="rounded bg-discord-input px-2 py-1">
+                  <span className="mr-1 text-discord-muted">{i + 1}.</span>
+                  {word}
+                </li>
+              ))}
+            </ol>
+            <img
+              src={`data:image/svg+xml;utf8,${encodeURIComponent(recovery.qr_svg)}`}
+              alt="Recovery phrase QR code"
+              className="h-48 w-48 rounded bg-white p-2"
+            />
+            <button
+              onClick={handleHide}
+              className="rounded-md bg-discord-input px-4 py-2 text-sm font-medium text-white transition-colors hover:bg-discord-hover"
+            >
+              Hide
+            </button>
+          </>
+        ) : (
+          <>
+            <label className="flex items-center gap-2 text-sm text-discord-text">
+              <input type="checkbox" checked={understood} onChange={(e) => setUnderstood(e.target.checked)} />
+              I understand that anyone with this phrase can take over my identity
+            </label>
+            <input
+              type="password"
+              value={password}
+              onChange={(e) => setPassword(e.target.value)}
+              className="w-full rounded-md bg-discord-input px-3 py-2 text-sm text-white placeholder-discord-muted outline-none focus:ring-2 focus:ring-discord-blurple"
+              placeholder="Current password"
+            />
+            {error && <p className="text-sm text-discord-red">{error}</p>}
+            <button
+              onClick={handleReveal}
+              disabled={!understood}
+              className="rounded-md bg-discord-red px-4 py-2 text-sm font-medium text-white transition-colors hover:bg-discord-red/80 disabled:opacity-50"
+            >
+              Show Recovery Phrase
+            </button>
+          </>
+        )}
+      </div>
+    </section>
+  );
+}
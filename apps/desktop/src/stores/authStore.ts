@@ -12,17 +12,26 @@ interface AuthState {
   isLoading: boolean;
   error: string | null;
   profiles: string[];
+  // State of the backend's tox thread, when it isn't simply running
+  threadStatus: "running" | "restarted" | "stalled" | "stopped";
+  // Showing a recorded session instead of a real profile
+  isReplay: boolean;
 
   loadProfiles: () => Promise<void>;
-  createProfile: (profileName: string, password: string, displayName: string) => Promise<void>;
+  // With a recovery phrase, the profile gets the identity it was made from
+  createProfile: (profileName: string, password: string, displayName: string, recoveryPhrase?: string) => Promise<void>;
   loadProfile: (profileName: string, password: string) => Promise<void>;
   deleteProfile: (profileName: string) => Promise<void>;
+  renameProfile: (profileName: string, newName: string) => Promise<boolean>;
+  duplicateProfile: (profileName: string, newName: string) => Promise<boolean>;
+  startReplay: (recording: string) => Promise<void>;
   logout: () => Promise<void>;
   setConnectionStatus: (connected: boolean, status: string) => void;
+  setThreadStatus: (status: AuthState["threadStatus"]) => void;
   clearError: () => void;
 }
 
-export const useAuthStore = create<AuthState>((set) => ({
+export const useAuthStore = create<AuthState>((set, get) => ({
   isLoggedIn: false,
   profileName: null,
   toxId: null,
@@ -33,6 +42,8 @@ export const useAuthStore = create<AuthState>((set) => ({
   isLoading: false,
   error: null,
   profiles: [],
+  threadStatus: "running",
+  isReplay: false,
 
   loadProfiles: async () => {
     try {
@@ -43,10 +54,12 @@ export const useAuthStore = create<AuthState>((set) => ({
     }
   },
 
-  createProfile: async (profileName, password, displayName) => {
+  createProfile: async (profileName, password, displayName, recoveryPhrase) => {
     set({ isLoading: true, error: null });
     try {
-      const info = await api.createProfile(profileName, password, displayName);
+      const info = recoveryPhrase
+        ? await api.restoreProfile(profileName, password, displayName, recoveryPhrase)
+        : await api.createProfile(profileName, password, displayName);
       set({
         isLoggedIn: true,
         profileName,
@@ -89,9 +102,57 @@ export const useAuthStore = create<AuthState>((set) => ({
     }
   },
 
+  renameProfile: async (profileName, newName) => {
+    set({ isLoading: true, error: null });
+    try {
+      await api.renameProfile(profileName, newName);
+      const profiles = await api.listProfiles();
+      set({ profiles, isLoading: false });
+      return true;
+    } catch (e) {
+      set({ error: String(e), isLoading: false });
+      return false;
+    }
+  },
+
+  duplicateProfile: async (profileName, newName) => {
+    set({ isLoading: true, error: null });
+    try {
+      await api.duplicateProfile(profileName, newName);
+      const profiles = await api.listProfiles();
+      set({ profiles, isLoading: false });
+      return true;
+    } catch (e) {
+      set({ error: String(e), isLoading: false });
+      return false;
+    }
+  },
+
+  startReplay: async (recording) => {
+    set({ isLoading: true, error: null });
+    try {
+      await api.startEventReplay(recording);
+      set({
+        isLoggedIn: true,
+        isReplay: true,
+        profileName: null,
+        toxId: null,
+        displayName: "Replay",
+        statusMessage: recording,
+        isLoading: false,
+      });
+    } catch (e) {
+      set({ error: String(e), isLoading: false });
+    }
+  },
+
   logout: async () => {
     try {
-      await api.logout();
+      if (get().isReplay) {
+        await api.stopEventReplay();
+      } else {
+        await api.logout();
+      }
     } catch (_e) {
       // Ignore errors during logout
     }
@@ -103,6 +164,8 @@ export const useAuthStore = create<AuthState>((set) => ({
       statusMessage: null,
       isConnected: false,
       connectionType: "none",
+      threadStatus: "running",
+      isReplay: false,
     });
   },
 
@@ -110,5 +173,7 @@ export const useAuthStore = create<AuthState>((set) => ({
     set({ isConnected: connected, connectionType: status });
   },
 
+  setThreadStatus: (threadStatus) => set({ threadStatus }),
+
   clearError: () => set({ error: null }),
 }));
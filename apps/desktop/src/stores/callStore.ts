@@ -1,6 +1,6 @@
 import { create } from "zustand";
 import * as api from "../api/calls";
-import type { CallStatus, ScreenInfo } from "../api/calls";
+import type { CallStatus, ScreenInfo, ScreenShareOptions } from "../api/calls";
 
 interface ActiveCall {
   friendNumber: number;
@@ -38,6 +38,10 @@ interface CallStoreState {
   isScreenSharing: boolean;
   /** Available screens for sharing */
   availableScreens: ScreenInfo[];
+  /** Region and frame rate used for screen sharing */
+  screenShareOptions: ScreenShareOptions;
+  /** Unseen missed calls per friend */
+  missedCalls: Record<number, number>;
   /** Whether video is in fullscreen mode */
   isFullscreen: boolean;
 
@@ -55,6 +59,7 @@ interface CallStoreState {
   toggleDeafen: () => void;
   toggleVideo: () => Promise<void>;
   toggleScreenShare: () => Promise<void>;
+  setScreenShareOptions: (options: ScreenShareOptions) => Promise<void>;
   toggleFullscreen: () => void;
 
   // Device selection
@@ -72,6 +77,11 @@ interface CallStoreState {
   }) => void;
   endCall: (friendNumber: number, reason: string) => void;
   updateDuration: () => void;
+
+  // Missed calls
+  loadMissedCalls: () => Promise<void>;
+  addMissedCall: (friendNumber: number) => void;
+  markMissedCallsSeen: (friendNumber: number) => Promise<void>;
 }
 
 export const useCallStore = create<CallStoreState>((set, get) => ({
@@ -82,14 +92,18 @@ export const useCallStore = create<CallStoreState>((set, get) => ({
   isDeafened: false,
   isScreenSharing: false,
   availableScreens: [],
+  screenShareOptions: { preset: "balanced" },
+  missedCalls: {},
   isFullscreen: false,
   selectedMicId: null,
   selectedSpeakerId: null,
   selectedCameraId: null,
 
-  startCall: async (friendNumber, friendName, withVideo = false) => {
+  startCall: async (friendNumber, friendName, requestedVideo) => {
     set({ isConnecting: true });
     try {
+      const withVideo =
+        requestedVideo ?? (await api.getEffectiveCallPreferences(friendNumber)).video_by_default;
       await api.callFriend(friendNumber, withVideo);
       set({
         activeCall: {
@@ -204,9 +218,9 @@ export const useCallStore = create<CallStoreState>((set, get) => ({
     set({ selectedCameraId: id });
     console.log("[CallStore] Selected camera:", id);
     try {
-      await api.setVideoDevice(id);
+      await api.switchCamera(id);
     } catch (e) {
-      console.error("Failed to set video device:", e);
+      console.error("Failed to switch camera:", e);
     }
   },
 
@@ -246,7 +260,7 @@ export const useCallStore = create<CallStoreState>((set, get) => ({
 
         const primary = screens.find((s) => s.is_primary) || screens[0];
         if (primary) {
-          await api.startScreenShare(primary.id);
+          await api.startScreenShare(primary.id, get().screenShareOptions);
           set({ isScreenSharing: true, availableScreens: screens });
           console.log("[CallStore] Screen sharing started:", primary.name);
         } else {
@@ -258,6 +272,19 @@ export const useCallStore = create<CallStoreState>((set, get) => ({
     }
   },
 
+  setScreenShareOptions: async (options) => {
+    set({ screenShareOptions: options });
+    const { isScreenSharing, availableScreens } = get();
+    if (!isScreenSharing) return;
+    // Restart the running share with the new options
+    const primary = availableScreens.find((s) => s.is_primary) || availableScreens[0];
+    try {
+      await api.startScreenShare(primary?.id, options);
+    } catch (e) {
+      console.error("Failed to update screen share:", e);
+    }
+  },
+
   toggleFullscreen: () => {
     set((s) => ({ isFullscreen: !s.isFullscreen }));
   },
@@ -309,4 +336,31 @@ export const useCallStore = create<CallStoreState>((set, get) => ({
       return s;
     });
   },
+
+  loadMissedCalls: async () => {
+    try {
+      const counts = await api.getMissedCallCounts();
+      set({
+        missedCalls: Object.fromEntries(counts.map((c) => [c.friend_number, c.count])),
+      });
+    } catch (e) {
+      console.error("Failed to load missed calls:", e);
+    }
+  },
+
+  addMissedCall: (friendNumber) => {
+    set((s) => ({
+      missedCalls: { ...s.missedCalls, [friendNumber]: (s.missedCalls[friendNumber] ?? 0) + 1 },
+    }));
+  },
+
+  markMissedCallsSeen: async (friendNumber) => {
+    if (!get().missedCalls[friendNumber]) return;
+    try {
+      await api.markMissedCallsSeen(friendNumber);
+      set((s) => ({ missedCalls: { ...s.missedCalls, [friendNumber]: 0 } }));
+    } catch (e) {
+      console.error("Failed to mark missed calls seen:", e);
+    }
+  },
 }));
@@ -1,13 +1,14 @@
 import { create } from "zustand";
 import * as api from "../api/tox";
-import type { ChannelMessage } from "../api/tox";
+import type { ChannelMessage, MessageCursor } from "../api/tox";
 
 interface ChannelMessageState {
   messages: Record<string, ChannelMessage[]>;
   isLoading: boolean;
   hasMore: Record<string, boolean>;
 
-  loadMessages: (channelId: string, beforeTimestamp?: string) => Promise<void>;
+  /** Load the newest messages, or the ones before `before` */
+  loadMessages: (channelId: string, before?: MessageCursor) => Promise<void>;
   sendMessage: (guildId: string, channelId: string, content: string) => Promise<void>;
   sendDmGroupMessage: (dmGroupId: string, channelId: string, content: string) => Promise<void>;
   addIncomingMessage: (channelId: string, msg: ChannelMessage) => void;
@@ -21,23 +22,19 @@ export const useChannelMessageStore = create<ChannelMessageState>((set) => ({
   isLoading: false,
   hasMore: {},
 
-  loadMessages: async (channelId, beforeTimestamp) => {
+  loadMessages: async (channelId, before) => {
     set({ isLoading: true });
     try {
-      const messages = await api.getChannelMessages(
-        channelId,
-        PAGE_SIZE,
-        beforeTimestamp,
-      );
+      const page = await api.getChannelMessagesPage(channelId, PAGE_SIZE, before);
 
       set((s) => {
-        const existing = beforeTimestamp ? (s.messages[channelId] ?? []) : [];
-        const reversed = [...messages].reverse();
+        const existing = before ? (s.messages[channelId] ?? []) : [];
+        const reversed = [...page.messages].reverse();
         const merged = [...reversed, ...existing];
 
         return {
           messages: { ...s.messages, [channelId]: merged },
-          hasMore: { ...s.hasMore, [channelId]: messages.length === PAGE_SIZE },
+          hasMore: { ...s.hasMore, [channelId]: page.has_more },
           isLoading: false,
         };
       });
@@ -0,0 +1,65 @@
+import { create } from "zustand";
+import * as api from "../api/tox";
+import type { GuildEventInfo, RsvpStatus } from "../api/tox";
+
+export interface EventReminder {
+  guildId: string;
+  eventId: string;
+  title: string;
+  startTime: number;
+}
+
+interface EventState {
+  /** Upcoming events by guild ID */
+  events: Record<string, GuildEventInfo[]>;
+  reminders: EventReminder[];
+
+  loadEvents: (guildId: string) => Promise<void>;
+  createEvent: (guildId: string, title: string, description: string, startTime: number) => Promise<void>;
+  rsvp: (guildId: string, eventId: string, status: RsvpStatus) => Promise<void>;
+  addReminder: (reminder: EventReminder) => void;
+  dismissReminder: (eventId: string) => void;
+}
+
+export const useEventStore = create<EventState>((set, get) => ({
+  events: {},
+  reminders: [],
+
+  loadEvents: async (guildId) => {
+    try {
+      const events = await api.getGuildEvents(guildId);
+      set((s) => ({ events: { ...s.events, [guildId]: events } }));
+    } catch (e) {
+      console.error("Failed to load events:", e);
+    }
+  },
+
+  createEvent: async (guildId, title, description, startTime) => {
+    await api.createEvent(guildId, title, description, startTime);
+    await get().loadEvents(guildId);
+  },
+
+  rsvp: async (guildId, eventId, status) => {
+    try {
+      const event = await api.rsvpEvent(guildId, eventId, status);
+      set((s) => ({
+        events: {
+          ...s.events,
+          [guildId]: (s.events[guildId] ?? []).map((e) => (e.id === eventId ? event : e)),
+        },
+      }));
+    } catch (e) {
+      console.error("Failed to RSVP:", e);
+    }
+  },
+
+  addReminder: (reminder) => {
+    set((s) => ({
+      reminders: [...s.reminders.filter((r) => r.eventId !== reminder.eventId), reminder],
+    }));
+  },
+
+  dismissReminder: (eventId) => {
+    set((s) => ({ reminders: s.reminders.filter((r) => r.eventId !== eventId) }));
+  },
+}));
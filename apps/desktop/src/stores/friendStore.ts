@@ -1,41 +1,100 @@
 import { create } from "zustand";
 import * as api from "../api/tox";
-import type { FriendInfo, FriendRequest } from "../api/tox";
+import type { FriendInfo, FriendRequest, FriendStatus, StatusNote } from "../api/tox";
+
+/** Friends fetched per request when loading the whole list */
+const FRIENDS_PAGE_SIZE = 200;
 
 interface FriendState {
   friends: FriendInfo[];
+  /** Revision of the friends list we have, null until it's loaded */
+  revision: number | null;
   friendRequests: FriendRequest[];
+  /** Friends' status notes that haven't expired, newest first */
+  statusNotes: FriendStatus[];
+  ownStatusNotes: StatusNote[];
   isLoading: boolean;
   error: string | null;
 
   // Actions
   loadFriends: () => Promise<void>;
+  /** Fetch only what changed since the last load, or the whole list if it isn't loaded */
+  syncFriends: () => Promise<void>;
   loadFriendRequests: () => Promise<void>;
   addFriend: (toxId: string, message: string) => Promise<void>;
   acceptRequest: (publicKey: string) => Promise<void>;
   denyRequest: (publicKey: string) => Promise<void>;
   removeFriend: (friendNumber: number) => Promise<void>;
+  loadStatusNotes: () => Promise<void>;
+  postStatusNote: (text: string) => Promise<void>;
 
   // Event-driven updates
   updateFriendName: (friendNumber: number, name: string) => void;
   updateFriendStatusMessage: (friendNumber: number, message: string) => void;
   updateFriendStatus: (friendNumber: number, status: string) => void;
-  updateFriendConnectionStatus: (friendNumber: number, connected: boolean, status: string) => void;
+  updateFriendConnectionStatus: (
+    friendNumber: number,
+    connected: boolean,
+    status: string,
+    connectionType: FriendInfo["connection_type"],
+  ) => void;
   addIncomingRequest: (publicKey: string, message: string) => void;
+  addStatusNote: (friendNumber: number, note: StatusNote) => void;
 
+  /** Forget the list, e.g. on logout */
+  reset: () => void;
   clearError: () => void;
 }
 
-export const useFriendStore = create<FriendState>((set) => ({
+export const useFriendStore = create<FriendState>((set, get) => ({
   friends: [],
+  revision: null,
   friendRequests: [],
+  statusNotes: [],
+  ownStatusNotes: [],
   isLoading: false,
   error: null,
 
   loadFriends: async () => {
     try {
-      const friends = await api.getFriends();
-      set({ friends });
+      const first = await api.getFriendsPage(0, FRIENDS_PAGE_SIZE);
+      const friends = [...first.friends];
+      while (friends.length < first.total) {
+        const page = await api.getFriendsPage(friends.length, FRIENDS_PAGE_SIZE);
+        if (page.friends.length === 0) break;
+        friends.push(...page.friends);
+      }
+      // Changes made while paging come in the next sync
+      set({ friends, revision: first.revision });
+    } catch (e) {
+      set({ error: String(e) });
+    }
+  },
+
+  syncFriends: async () => {
+    const { revision, loadFriends } = get();
+    if (revision === null) {
+      return loadFriends();
+    }
+    try {
+      const changes = await api.getFriendsChangedSince(revision);
+      const current = get().friends;
+      const byNumber = new Map(current.map((f) => [f.friend_number, f]));
+      // New friends and group moves change the order, which only the full list has
+      const moved = changes.friends.some((f) => {
+        const old = byNumber.get(f.friend_number);
+        return !old || old.group_id !== f.group_id || old.group_position !== f.group_position;
+      });
+      if (moved) {
+        return loadFriends();
+      }
+      const changed = new Map(changes.friends.map((f) => [f.friend_number, f]));
+      set({
+        friends: current
+          .filter((f) => !changes.removed.includes(f.friend_number))
+          .map((f) => changed.get(f.friend_number) ?? f),
+        revision: changes.revision,
+      });
     } catch (e) {
       set({ error: String(e) });
     }
@@ -54,9 +113,8 @@ export const useFriendStore = create<FriendState>((set) => ({
     set({ isLoading: true, error: null });
     try {
       await api.addFriend(toxId, message);
-      // Reload friend list after adding
-      const friends = await api.getFriends();
-      set({ friends, isLoading: false });
+      await get().syncFriends();
+      set({ isLoading: false });
     } catch (e) {
       set({ error: String(e), isLoading: false });
     }
@@ -66,12 +124,11 @@ export const useFriendStore = create<FriendState>((set) => ({
     set({ isLoading: true, error: null });
     try {
       await api.acceptFriendRequest(publicKey);
-      // Reload both lists
-      const [friends, friendRequests] = await Promise.all([
-        api.getFriends(),
+      const [, friendRequests] = await Promise.all([
+        get().syncFriends(),
         api.getFriendRequests(),
       ]);
-      set({ friends, friendRequests, isLoading: false });
+      set({ friendRequests, isLoading: false });
     } catch (e) {
       set({ error: String(e), isLoading: false });
     }
@@ -99,6 +156,27 @@ export const useFriendStore = create<FriendState>((set) => ({
     }
   },
 
+  loadStatusNotes: async () => {
+    try {
+      const [statusNotes, ownStatusNotes] = await Promise.all([
+        api.getFriendStatuses(),
+        api.getOwnStatusNotes(),
+      ]);
+      set({ statusNotes, ownStatusNotes });
+    } catch (e) {
+      set({ error: String(e) });
+    }
+  },
+
+  postStatusNote: async (text) => {
+    try {
+      const note = await api.postStatusNote(text);
+      set((s) => ({ ownStatusNotes: [note, ...s.ownStatusNotes] }));
+    } catch (e) {
+      set({ error: String(e) });
+    }
+  },
+
   updateFriendName: (friendNumber, name) => {
     set((s) => ({
       friends: s.friends.map((f) =>
@@ -125,13 +203,14 @@ export const useFriendStore = create<FriendState>((set) => ({
     }));
   },
 
-  updateFriendConnectionStatus: (friendNumber, connected, status) => {
+  updateFriendConnectionStatus: (friendNumber, connected, status, connectionType) => {
     set((s) => ({
       friends: s.friends.map((f) =>
         f.friend_number === friendNumber
           ? {
               ...f,
               connection_status: status as FriendInfo["connection_status"],
+              connection_type: connectionType,
               last_seen: !connected ? new Date().toISOString() : f.last_seen,
             }
           : f,
@@ -158,5 +237,14 @@ export const useFriendStore = create<FriendState>((set) => ({
     });
   },
 
+  addStatusNote: (friendNumber, note) => {
+    set((s) => {
+      const friend = s.friends.find((f) => f.friend_number === friendNumber);
+      const status = { friend_number: friendNumber, friend_name: friend?.name ?? "", note };
+      return { statusNotes: [status, ...s.statusNotes] };
+    });
+  },
+
+  reset: () => set({ friends: [], revision: null, friendRequests: [], statusNotes: [], ownStatusNotes: [] }),
   clearError: () => set({ error: null }),
 }));
@@ -2,10 +2,12 @@ import { create } from "zustand";
 import * as api from "../api/tox";
 import type { GuildInfo, ChannelInfo, GuildMember } from "../api/tox";
 
-interface GuildInvite {
+export interface GuildInvite {
+  id: string;
   friendNumber: number;
-  inviteData: number[];
   groupName: string;
+  /** Joining needs the group's password */
+  passwordRequired: boolean;
 }
 
 interface GuildState {
@@ -19,7 +21,7 @@ interface GuildState {
 
   loadGuilds: () => Promise<void>;
   loadDmGroups: () => Promise<void>;
-  createGuild: (name: string) => Promise<void>;
+  createGuild: (name: string, password?: string, isPublic?: boolean) => Promise<void>;
   createDmGroup: (name: string, friendNumbers: number[]) => Promise<GuildInfo>;
   leaveGuild: (guildId: string) => Promise<void>;
   renameGuild: (guildId: string, name: string) => Promise<void>;
@@ -29,9 +31,10 @@ interface GuildState {
   loadChannels: (guildId: string) => Promise<void>;
   loadMembers: (guildId: string) => Promise<void>;
   createChannel: (guildId: string, name: string) => Promise<void>;
+  loadInvites: () => Promise<void>;
   addGuildInvite: (invite: GuildInvite) => void;
   acceptInvite: (invite: GuildInvite) => Promise<void>;
-  dismissInvite: (friendNumber: number) => void;
+  dismissInvite: (inviteId: string) => Promise<void>;
   updateGuildFromEvent: (groupNumber: number, update: Partial<GuildInfo>) => void;
   addMember: (groupNumber: number, member: GuildMember) => void;
   removeMember: (groupNumber: number, peerId: number) => void;
@@ -67,9 +70,9 @@ export const useGuildStore = create<GuildState>((set, get) => ({
     }
   },
 
-  createGuild: async (name) => {
+  createGuild: async (name, password, isPublic) => {
     try {
-      const guild = await api.createGuild(name);
+      const guild = await api.createGuild(name, password || null, isPublic);
       set((s) => ({ guilds: [...s.guilds, guild] }));
       // Auto-select the new guild
       get().selectGuild(guild.id);
@@ -192,34 +195,51 @@ export const useGuildStore = create<GuildState>((set, get) => ({
     }
   },
 
+  loadInvites: async () => {
+    try {
+      const invites = await api.getGroupInvites();
+      set({
+        pendingInvites: invites.map((i) => ({
+          id: i.id,
+          friendNumber: i.friend_number,
+          groupName: i.group_name,
+          passwordRequired: i.password_required,
+        })),
+      });
+    } catch (e) {
+      console.error("Failed to load invites:", e);
+    }
+  },
+
   addGuildInvite: (invite) => {
     set((s) => ({
       pendingInvites: [...s.pendingInvites.filter(
-        (i) => i.friendNumber !== invite.friendNumber,
+        (i) => i.id !== invite.id,
       ), invite],
     }));
   },
 
   acceptInvite: async (invite) => {
+    let password: string | null = null;
+    if (invite.passwordRequired) {
+      password = prompt(`"${invite.groupName}" needs a password to join:`);
+      if (password === null) return;
+    }
     try {
-      const guild = await api.acceptGuildInvite(
-        invite.friendNumber,
-        invite.inviteData,
-        invite.groupName,
-      );
+      const guild = await api.acceptGroupInvite(invite.id, password);
       // Add to the correct list based on guild_type
       if (guild.guild_type === "dm_group") {
         set((s) => ({
           dmGroups: [...s.dmGroups, guild],
           pendingInvites: s.pendingInvites.filter(
-            (i) => i.friendNumber !== invite.friendNumber,
+            (i) => i.id !== invite.id,
           ),
         }));
       } else {
         set((s) => ({
           guilds: [...s.guilds, guild],
           pendingInvites: s.pendingInvites.filter(
-            (i) => i.friendNumber !== invite.friendNumber,
+            (i) => i.id !== invite.id,
           ),
         }));
       }
@@ -229,10 +249,15 @@ export const useGuildStore = create<GuildState>((set, get) => ({
     }
   },
 
-  dismissInvite: (friendNumber) => {
+  dismissInvite: async (inviteId) => {
+    try {
+      await api.declineGroupInvite(inviteId);
+    } catch (e) {
+      console.error("Failed to decline invite:", e);
+    }
     set((s) => ({
       pendingInvites: s.pendingInvites.filter(
-        (i) => i.friendNumber !== friendNumber,
+        (i) => i.id !== inviteId,
       ),
     }));
   },
@@ -250,7 +275,10 @@ export const useGuildStore = create<GuildState>((set, get) => ({
       members: {
         ...s.members,
         [group.id]: [
-          ...(s.members[group.id] ?? []).filter((m) => m.peer_id !== member.peer_id),
+          // Also replaces the member's offline entry
+          ...(s.members[group.id] ?? []).filter(
+            (m) => m.peer_id !== member.peer_id && m.public_key !== member.public_key,
+          ),
           member,
         ],
       },
@@ -265,7 +293,12 @@ export const useGuildStore = create<GuildState>((set, get) => ({
     set((s) => ({
       members: {
         ...s.members,
-        [group.id]: (s.members[group.id] ?? []).filter((m) => m.peer_id !== peerId),
+        // Keep them listed as offline, like the member cache does
+        [group.id]: (s.members[group.id] ?? []).map((m) =>
+          m.peer_id === peerId
+            ? { ...m, peer_id: null, status: "offline", last_seen: new Date().toISOString(), activity: null, custom_status: null }
+            : m,
+        ),
       },
     }));
   },
@@ -1,6 +1,6 @@
 import { create } from "zustand";
 import * as api from "../api/tox";
-import type { DirectMessage } from "../api/tox";
+import type { DirectMessage, MessageCursor } from "../api/tox";
 
 interface MessageState {
   /** Messages keyed by friend_number */
@@ -13,14 +13,22 @@ interface MessageState {
   isLoading: boolean;
   /** Whether there are more messages to load (for scroll-to-load-more) */
   hasMore: Record<number, boolean>;
+  /** Disappearing message timers in seconds keyed by friend_number (0 when off) */
+  disappearingTimers: Record<number, number>;
 
   // Actions
-  loadMessages: (friendNumber: number, beforeTimestamp?: string) => Promise<void>;
+  /** Load the newest messages, or the ones before `before` */
+  loadMessages: (friendNumber: number, before?: MessageCursor) => Promise<void>;
   sendMessage: (friendNumber: number, content: string) => Promise<void>;
   addIncomingMessage: (msg: DirectMessage) => void;
   setFriendTyping: (friendNumber: number, isTyping: boolean) => void;
   markRead: (friendNumber: number) => Promise<void>;
   clearConversation: (friendNumber: number) => void;
+  removeMessages: (friendNumber: number, messageIds: string[]) => void;
+  loadDisappearingTimer: (friendNumber: number) => Promise<void>;
+  setDisappearingTimer: (friendNumber: number, seconds: number) => Promise<void>;
+  /** Apply a timer change made by the friend */
+  applyDisappearingTimer: (friendNumber: number, seconds: number) => void;
 }
 
 const PAGE_SIZE = 50;
@@ -31,25 +39,26 @@ export const useMessageStore = create<MessageState>((set) => ({
   unreadCounts: {},
   isLoading: false,
   hasMore: {},
+  disappearingTimers: {},
 
-  loadMessages: async (friendNumber, beforeTimestamp) => {
+  loadMessages: async (friendNumber, before) => {
     set({ isLoading: true });
     try {
-      const messages = await api.getDirectMessages(
-        friendNumber,
-        PAGE_SIZE,
-        beforeTimestamp,
-      );
+      const page = await api.getDirectMessagesPage(friendNumber, PAGE_SIZE, before);
 
       set((s) => {
-        const existing = beforeTimestamp ? (s.conversations[friendNumber] ?? []) : [];
+        // Incognito messages aren't in the DB, so keep the ones we have
+        const current = s.conversations[friendNumber] ?? [];
+        const existing = before ? current : current.filter((m) => m.incognito);
         // Messages come from DB in DESC order, reverse to chronological
-        const reversed = [...messages].reverse();
-        const merged = [...reversed, ...existing];
+        const reversed = [...page.messages].reverse();
+        const merged = before
+          ? [...reversed, ...existing]
+          : [...reversed, ...existing].sort((a, b) => a.timestamp.localeCompare(b.timestamp));
 
         return {
           conversations: { ...s.conversations, [friendNumber]: merged },
-          hasMore: { ...s.hasMore, [friendNumber]: messages.length === PAGE_SIZE },
+          hasMore: { ...s.hasMore, [friendNumber]: page.has_more },
           isLoading: false,
         };
       });
@@ -61,6 +70,11 @@ export const useMessageStore = create<MessageState>((set) => ({
   sendMessage: async (friendNumber, content) => {
     try {
       const result = await api.sendDirectMessage(friendNumber, content);
+      // Incognito messages that couldn't be sent aren't queued
+      if (!result.delivered && !result.queued) {
+        console.error("Failed to send message:", result.error);
+        return;
+      }
 
       // Add the sent message to the conversation immediately
       const msg: DirectMessage = {
@@ -73,6 +87,8 @@ export const useMessageStore = create<MessageState>((set) => ({
         is_outgoing: true,
         delivered: result.delivered,
         read: false,
+        expires_at: result.expires_at,
+        incognito: result.incognito,
       };
 
       set((s) => ({
@@ -136,4 +152,32 @@ export const useMessageStore = create<MessageState>((set) => ({
       return { conversations: rest };
     });
   },
+
+  removeMessages: (friendNumber, messageIds) => {
+    const ids = new Set(messageIds);
+    set((s) => ({
+      conversations: {
+        ...s.conversations,
+        [friendNumber]: (s.conversations[friendNumber] ?? []).filter((m) => !ids.has(m.id)),
+      },
+    }));
+  },
+
+  loadDisappearingTimer: async (friendNumber) => {
+    try {
+      const seconds = await api.getDisappearingTimer(friendNumber);
+      set((s) => ({ disappearingTimers: { ...s.disappearingTimers, [friendNumber]: seconds } }));
+    } catch (e) {
+      console.error("Failed to load disappearing timer:", e);
+    }
+  },
+
+  setDisappearingTimer: async (friendNumber, seconds) => {
+    await api.setDisappearingTimer(friendNumber, seconds);
+    set((s) => ({ disappearingTimers: { ...s.disappearingTimers, [friendNumber]: seconds } }));
+  },
+
+  applyDisappearingTimer: (friendNumber, seconds) => {
+    set((s) => ({ disappearingTimers: { ...s.disappearingTimers, [friendNumber]: seconds } }));
+  },
 }));
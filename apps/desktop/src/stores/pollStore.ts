@@ -0,0 +1,41 @@
+import { create } from "zustand";
+import * as api from "../api/tox";
+import type { PollInfo } from "../api/tox";
+
+interface PollState {
+  polls: Record<string, PollInfo>;
+
+  loadPoll: (guildId: string, pollId: string) => Promise<void>;
+  vote: (guildId: string, pollId: string, option: number) => Promise<void>;
+  applyUpdate: (pollId: string, tallies: number[], closed: boolean) => void;
+}
+
+export const usePollStore = create<PollState>((set) => ({
+  polls: {},
+
+  loadPoll: async (guildId, pollId) => {
+    try {
+      const poll = await api.getPoll(guildId, pollId);
+      set((s) => ({ polls: { ...s.polls, [pollId]: poll } }));
+    } catch (e) {
+      console.error("Failed to load poll:", e);
+    }
+  },
+
+  vote: async (guildId, pollId, option) => {
+    try {
+      const poll = await api.votePoll(guildId, pollId, option);
+      set((s) => ({ polls: { ...s.polls, [pollId]: poll } }));
+    } catch (e) {
+      console.error("Failed to vote:", e);
+    }
+  },
+
+  applyUpdate: (pollId, tallies, closed) => {
+    set((s) => {
+      const poll = s.polls[pollId];
+      if (!poll) return s;
+      return { polls: { ...s.polls, [pollId]: { ...poll, tallies, closed } } };
+    });
+  },
+}));
@@ -0,0 +1,191 @@
+//! Content-addressed media cache
+//!
+//! Avatars, thumbnails, guild icons and cached attachments are stored once
+//! per content, named by their BLAKE3 hash, with their size and last use in
+//! the `assets` table. When the cache grows past its limit the least recently
+//! used are evicted, so everything in it must be possible to fetch or
+//! generate again. Sticker images are the exception: they're kept until
+//! their pack is removed.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use tracing::{info, warn};
+
+use crate::db::MessageStore;
+
+/// Profile setting holding the cache size limit in bytes
+pub const MEDIA_CACHE_LIMIT_SETTING: &str = "media_cache_limit";
+
+/// Size limit when none is set
+pub const DEFAULT_MEDIA_CACHE_LIMIT: u64 = 500 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AssetKind {
+    Avatar,
+    Thumbnail,
+    GuildIcon,
+    Attachment,
+    /// Part of an installed sticker pack; never evicted
+    Sticker,
+}
+
+impl AssetKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Avatar => "avatar",
+            Self::Thumbnail => "thumbnail",
+            Self::GuildIcon => "guild_icon",
+            Self::Attachment => "attachment",
+            Self::Sticker => "sticker",
+        }
+    }
+}
+
+/// How much the cache holds
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct AssetUsage {
+    pub count: i64,
+    pub bytes: i64,
+    pub limit_bytes: u64,
+}
+
+pub struct AssetStore {
+    dir: PathBuf,
+    store: Arc<MessageStore>,
+}
+
+impl AssetStore {
+    pub fn new(dir: PathBuf, store: Arc<MessageStore>) -> Self {
+        Self { dir, store }
+    }
+
+    /// Store content, returning the path of the cached copy. Content already
+    /// cached is only marked as used.
+    pub fn put(&self, kind: AssetKind, data: &[u8]) -> Result<PathBuf, String> {
+        let hash = blake3::hash(data).to_hex().to_string();
+        let path = self.blob_path(&hash);
+        if !path.exists() {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create cache directory: {e}"))?;
+            }
+            // Write under a temporary name so a crash can't leave a partial blob
+            let tmp = path.with_extension("tmp");
+            std::fs::write(&tmp, data).map_err(|e| format!("Failed to write cached file: {e}"))?;
+            std::fs::rename(&tmp, &path).map_err(|e| format!("Failed to write cached file: {e}"))?;
+        }
+        self.store.record_asset(&hash, kind.as_str(), data.len() as i64)?;
+        self.evict(Some(&hash))?;
+        Ok(path)
+    }
+
+    /// Path of cached content, marking it as used. None if it isn't cached
+    /// (any more), in which case the caller fetches or generates it again.
+    pub fn get(&self, hash: &str) -> Result<Option<PathBuf>, String> {
+        if !is_hash(hash) {
+            return Ok(None);
+        }
+        let path = self.blob_path(hash);
+        if !path.exists() {
+            self.store.delete_asset(hash)?;
+            return Ok(None);
+        }
+        if !self.store.touch_asset(hash)? {
+            // On disk from before the metadata was lost; adopt it
+            let size = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+            self.store.record_asset(hash, AssetKind::Attachment.as_str(), size as i64)?;
+        }
+        Ok(Some(path))
+    }
+
+    /// The hash of a path returned by `get`, e.g. one stored in a record
+    pub fn hash_of(&self, path: &Path) -> Option<String> {
+        let name = path.file_name()?.to_str()?;
+        (path.starts_with(&self.dir) && is_hash(name)).then(|| name.to_string())
+    }
+
+    fn blob_path(&self, hash: &str) -> PathBuf {
+        self.dir.join(&hash[..2]).join(hash)
+    }
+
+    pub fn usage(&self) -> Result<AssetUsage, String> {
+        let (count, bytes) = self.store.asset_usage()?;
+        Ok(AssetUsage {
+            count,
+            bytes,
+            limit_bytes: self.limit()?,
+        })
+    }
+
+    pub fn limit(&self) -> Result<u64, String> {
+        Ok(self
+            .store
+            .get_setting(MEDIA_CACHE_LIMIT_SETTING)?
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_MEDIA_CACHE_LIMIT))
+    }
+
+    /// Change the size limit, evicting down to it now
+    pub fn set_limit(&self, limit_bytes: u64) -> Result<(), String> {
+        self.store.set_setting(MEDIA_CACHE_LIMIT_SETTING, &limit_bytes.to_string())?;
+        self.evict(None)
+    }
+
+    /// Remove content from the cache, e.g. a sticker whose pack was removed
+    pub fn remove(&self, hash: &str) -> Result<(), String> {
+        if !is_hash(hash) {
+            return Ok(());
+        }
+        self.store.delete_asset(hash)?;
+        self.remove_blob(hash);
+        Ok(())
+    }
+
+    /// Remove everything in the cache but stickers, returning the bytes freed
+    pub fn clear(&self) -> Result<i64, String> {
+        let (_, before) = self.store.asset_usage()?;
+        for hash in self.store.delete_all_assets()? {
+            self.remove_blob(&hash);
+        }
+        let (_, after) = self.store.asset_usage()?;
+        let bytes = before - after;
+        info!("Media cache cleared ({bytes} bytes)");
+        Ok(bytes)
+    }
+
+    /// Evict least recently used content until the cache fits its limit,
+    /// keeping `keep` (just stored)
+    fn evict(&self, keep: Option<&str>) -> Result<(), String> {
+        let limit = self.limit()? as i64;
+        let (_, mut bytes) = self.store.asset_usage()?;
+        if bytes <= limit {
+            return Ok(());
+        }
+        for (hash, size) in self.store.least_recently_used_assets()? {
+            if bytes <= limit {
+                break;
+            }
+            if Some(hash.as_str()) == keep {
+                continue;
+            }
+            self.store.delete_asset(&hash)?;
+            self.remove_blob(&hash);
+            bytes -= size;
+        }
+        Ok(())
+    }
+
+    fn remove_blob(&self, hash: &str) {
+        let path = self.blob_path(hash);
+        if let Err(e) = std::fs::remove_file(&path) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                warn!("Failed to remove cached file {}: {e}", path.display());
+            }
+        }
+    }
+}
+
+/// Whether a name is a BLAKE3 hex hash, so it's safe to use as a file name
+fn is_hash(name: &str) -> bool {
+    name.len() == 64 && name.bytes().all(|b| b.is_ascii_hexdigit())
+}
@@ -0,0 +1,50 @@
+//! Headless Toxcord.
+//!
+//! ```text
+//! toxcord-daemon --profile <name> [--socket <path>]
+//! ```
+//!
+//! The profile password is read from the `TOXCORD_PASSWORD` environment variable.
+
+use std::path::PathBuf;
+
+use toxcord_core::daemon::{self, DaemonConfig};
+use toxcord_core::profile_dirs;
+
+fn main() {
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| "toxcord_core=info,toxcord_tox=info".into()),
+        )
+        .init();
+
+    let mut profile = None;
+    let mut socket = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--profile" => profile = args.next(),
+            "--socket" => socket = args.next().map(PathBuf::from),
+            _ => usage(),
+        }
+    }
+    let Some(profile) = profile else { usage() };
+    let password = std::env::var("TOXCORD_PASSWORD").unwrap_or_default();
+
+    profile_dirs::migrate_legacy_layout();
+    let mut config = DaemonConfig::for_profile(&profile, &password);
+    if let Some(socket) = socket {
+        config.socket_path = socket;
+    }
+    if let Err(e) = daemon::run(config) {
+        eprintln!("toxcord-daemon: {e}");
+        std::process::exit(1);
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("usage: toxcord-daemon --profile <name> [--socket <path>]");
+    eprintln!("The profile password is read from TOXCORD_PASSWORD.");
+    std::process::exit(2);
+}
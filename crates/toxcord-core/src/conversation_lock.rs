@@ -0,0 +1,157 @@
+//! Conversation locks
+//!
+//! A locked conversation's messages are encrypted with a key derived from a
+//! passphrase both friends entered (see `toxcord_protocol::conversation_lock`).
+//! Which conversations are locked is saved with the profile; the keys are only
+//! held in memory, so after a restart a locked conversation can't send, and its
+//! stored envelopes can't be read, until the passphrase is entered again.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use toxcord_protocol::codec::{split_tagged, TOX_MAX_MESSAGE_LENGTH};
+use toxcord_protocol::conversation_lock::{self, MAX_LOCKED_CHUNK};
+use toxcord_tox::tox::PassKey;
+
+use crate::db::MessageStore;
+
+struct Lock {
+    /// Store decrypted messages instead of their envelopes
+    store_plaintext: bool,
+    key: Option<Arc<PassKey>>,
+}
+
+/// A message as received or stored
+pub enum Opened {
+    /// Not an envelope
+    Plain(String),
+    /// An envelope we could decrypt
+    Decrypted(String),
+    /// An envelope we have no key for, or that our key doesn't open
+    Locked,
+}
+
+/// Shared between the tox thread, its callbacks and commands
+#[derive(Clone, Default)]
+pub struct ConversationLocks {
+    locks: Arc<Mutex<HashMap<u32, Lock>>>,
+}
+
+impl ConversationLocks {
+    /// Restore which conversations are locked when the profile loads
+    pub fn load(&self, store: &MessageStore) -> Result<(), String> {
+        let saved = store.get_conversation_locks()?;
+        if let Ok(mut locks) = self.locks.lock() {
+            *locks = saved
+                .into_iter()
+                .map(|(friend_number, store_plaintext)| (friend_number, Lock { store_plaintext, key: None }))
+                .collect();
+        }
+        Ok(())
+    }
+
+    /// Lock a conversation with a derived key, or replace its key
+    pub fn unlock(&self, friend_number: u32, store_plaintext: bool, key: PassKey) {
+        if let Ok(mut locks) = self.locks.lock() {
+            locks.insert(friend_number, Lock { store_plaintext, key: Some(Arc::new(key)) });
+        }
+    }
+
+    pub fn remove(&self, friend_number: u32) {
+        if let Ok(mut locks) = self.locks.lock() {
+            locks.remove(&friend_number);
+        }
+    }
+
+    /// Whether the conversation is locked, and if so whether its key is loaded
+    pub fn state(&self, friend_number: u32) -> Option<bool> {
+        let locks = self.locks.lock().ok()?;
+        locks.get(&friend_number).map(|lock| lock.key.is_some())
+    }
+
+    fn key(&self, friend_number: u32) -> Option<Arc<PassKey>> {
+        self.locks.lock().ok()?.get(&friend_number)?.key.clone()
+    }
+
+    /// Split a message into the texts to send, tagged for reassembly if it
+    /// takes more than one, and encrypted if the conversation is locked. An
+    /// error if it's locked without a key.
+    pub fn seal_for_sending(&self, friend_number: u32, message: &str, id: u32) -> Result<Vec<String>, String> {
+        if self.state(friend_number).is_none() {
+            return split_tagged(message, TOX_MAX_MESSAGE_LENGTH, id);
+        }
+        let key = self
+            .key(friend_number)
+            .ok_or("This conversation is locked; enter its passphrase to send messages")?;
+        // Tagged inside the envelope, so the parts of a locked message stay private too
+        split_tagged(message, MAX_LOCKED_CHUNK, id)?
+            .iter()
+            .map(|part| {
+                key.encrypt(part.as_bytes())
+                    .map(|ciphertext| conversation_lock::seal(&ciphertext))
+                    .map_err(|e| format!("Failed to encrypt message: {e}"))
+            })
+            .collect()
+    }
+
+    /// What to store for a message: its envelope, unless the conversation
+    /// keeps plaintext or isn't locked. An error rather than plaintext if the
+    /// message can't be encrypted.
+    pub fn content_to_store(&self, friend_number: u32, message: &str) -> Result<String, String> {
+        let key = {
+            let locks = self.locks.lock().map_err(|_| "Conversation locks are poisoned")?;
+            match locks.get(&friend_number) {
+                None | Some(Lock { store_plaintext: true, .. }) => return Ok(message.to_string()),
+                Some(Lock { key: Some(key), .. }) => key.clone(),
+                Some(Lock { key: None, .. }) => {
+                    return Err("This conversation is locked; enter its passphrase to store messages".into())
+                }
+            }
+        };
+        key.encrypt(message.as_bytes())
+            .map(|ciphertext| conversation_lock::seal(&ciphertext))
+            .map_err(|e| format!("Failed to encrypt message: {e}"))
+    }
+
+    /// Whether decrypted messages from this friend may be stored as plaintext
+    pub fn stores_plaintext(&self, friend_number: u32) -> bool {
+        let Ok(locks) = self.locks.lock() else {
+            return false;
+        };
+        locks.get(&friend_number).is_some_and(|lock| lock.store_plaintext)
+    }
+
+    /// Decrypt a stored message if it's an envelope. Plain stored messages may
+    /// predate the lock or be kept as plaintext, so they're fine.
+    pub fn open(&self, friend_number: u32, text: &str) -> Opened {
+        let Some(ciphertext) = conversation_lock::open(text) else {
+            return Opened::Plain(text.to_string());
+        };
+        let plaintext = self
+            .key(friend_number)
+            .and_then(|key| key.decrypt(&ciphertext).ok())
+            .and_then(|plaintext| String::from_utf8(plaintext).ok());
+        match plaintext {
+            Some(plaintext) => Opened::Decrypted(plaintext),
+            None => Opened::Locked,
+        }
+    }
+
+    /// Decrypt a message as it arrives. Both sides of a locked conversation
+    /// only send envelopes, so plain text in one was sent unprotected, by a
+    /// client that dropped the lock, and is refused.
+    pub fn open_received(&self, friend_number: u32, text: &str) -> Result<Opened, String> {
+        let opened = self.open(friend_number, text);
+        if matches!(opened, Opened::Plain(_)) && self.state(friend_number).is_some() {
+            return Err(format!("Refused an unencrypted message in the locked conversation with friend {friend_number}"));
+        }
+        Ok(opened)
+    }
+}
+
+/// Derive a conversation key from the passphrase (slow; keep off async threads)
+pub fn derive_key(passphrase: &str, self_public_key: &str, friend_public_key: &str) -> Result<PassKey, String> {
+    conversation_lock::validate_passphrase(passphrase)?;
+    let salt = conversation_lock::salt(self_public_key, friend_public_key);
+    PassKey::derive(passphrase, &salt).map_err(|e| format!("Failed to derive conversation key: {e}"))
+}
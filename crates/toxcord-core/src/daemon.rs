@@ -0,0 +1,863 @@
+//! Headless Toxcord daemon
+//!
+//! Runs a Tox instance for a desktop profile without Tauri, so the profile
+//! can stay online on a server (e.g. to keep guilds alive) and be driven over
+//! a local JSON-RPC socket (see [`crate::rpc`]).
+//!
+//! The socket is a Unix domain socket readable only by the owning user.
+//! Clients call methods named after the desktop's `ToxCommand`s and send
+//! `subscribe` to receive Tox events as notifications.
+//!
+//! The daemon covers profile, friend, direct message and group operations.
+//! Texts are sent and received through [`crate::outgoing`] and
+//! [`crate::incoming`], like in the desktop app. Calls and file transfers
+//! stay desktop-only.
+//!
+//! The desktop app can't attach to a running daemon yet: it runs its own Tox
+//! thread, with the full command set, so a profile must not be online in the
+//! desktop app and the daemon at the same time.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::sync::{broadcast, mpsc, oneshot};
+use toxcord_protocol::codec::{split_tagged, TOX_MAX_MESSAGE_LENGTH};
+use toxcord_tox::callbacks::ToxEventHandler;
+use toxcord_tox::tox::default_bootstrap_nodes;
+use toxcord_tox::types::*;
+use toxcord_tox::{ToxInstance, ToxOptionsBuilder};
+use tracing::{debug, error, info, warn};
+
+use crate::conversation_lock::ConversationLocks;
+use crate::db::message_store::DirectMessageRecord;
+use crate::db::MessageStore;
+use crate::incoming::{self, FriendText, FriendTextError, GroupSender, GroupText, IncomingTexts};
+use crate::outgoing::{sign_group_text, text_id};
+use crate::profile_dirs::ProfileDir;
+use crate::profile_file;
+use crate::rpc::{self, Notification, Request, Response, RpcError, METHOD_NOT_FOUND};
+
+pub struct DaemonConfig {
+    pub profile_path: PathBuf,
+    pub db_path: PathBuf,
+    pub password: String,
+    pub socket_path: PathBuf,
+}
+
+impl DaemonConfig {
+    /// Config for a profile in the desktop app's profiles directory
+    pub fn for_profile(profile_name: &str, password: &str) -> Self {
+        let profile = ProfileDir::new(profile_name);
+        Self {
+            profile_path: profile.savedata(),
+            db_path: profile.database(),
+            password: password.to_string(),
+            socket_path: default_socket_path(),
+        }
+    }
+}
+
+pub fn default_socket_path() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("toxcord")
+        .join("daemon.sock")
+}
+
+/// Events pushed to subscribed clients, shaped like the desktop's `ToxEvent`
+#[derive(Clone, Serialize)]
+#[serde(tag = "type", content = "data")]
+pub enum CoreEvent {
+    ConnectionStatus { connected: bool, status: String },
+    FriendRequest { public_key: String, message: String },
+    FriendMessage { friend_number: u32, message_type: String, message: String, id: String, timestamp: String, locked: bool },
+    UnprotectedMessageRefused { friend_number: u32 },
+    FriendName { friend_number: u32, name: String },
+    FriendStatusMessage { friend_number: u32, message: String },
+    FriendStatus { friend_number: u32, status: String },
+    FriendConnectionStatus { friend_number: u32, connected: bool, status: String },
+    FriendTyping { friend_number: u32, is_typing: bool },
+    GroupInvite { friend_number: u32, invite_data: Vec<u8>, group_name: String },
+    GroupSelfJoin { group_number: u32 },
+    GroupJoinFail { group_number: u32, fail_type: String },
+    GroupPeerJoin { group_number: u32, peer_id: u32 },
+    GroupPeerExit { group_number: u32, peer_id: u32, name: String },
+    GroupPeerName { group_number: u32, peer_id: u32, name: String },
+    GroupMessage {
+        group_number: u32,
+        peer_id: u32,
+        sender_name: String,
+        sender_pk: String,
+        message: String,
+        message_type: String,
+        id: String,
+        timestamp: String,
+        channel_id: String,
+        mentions_me: bool,
+        filtered: bool,
+        sender_verified: Option<bool>,
+        signer_public_key: Option<String>,
+    },
+    GroupTopicChange { group_number: u32, topic: String },
+}
+
+enum CoreRequest {
+    Call(Request, oneshot::Sender<Result<Value, RpcError>>),
+    Shutdown(oneshot::Sender<()>),
+}
+
+/// Run the daemon until interrupted
+pub fn run(config: DaemonConfig) -> Result<(), String> {
+    let runtime = tokio::runtime::Runtime::new().map_err(|e| format!("Failed to start runtime: {e}"))?;
+    runtime.block_on(serve(config))
+}
+
+async fn serve(config: DaemonConfig) -> Result<(), String> {
+    let savedata = profile_file::read_profile(&config.profile_path, &config.password)?;
+    let store = Arc::new(MessageStore::open(&config.db_path, &config.password)?);
+
+    let (events_tx, _) = broadcast::channel::<CoreEvent>(256);
+    let (request_tx, request_rx) = mpsc::channel::<CoreRequest>(256);
+    let (ready_tx, ready_rx) = oneshot::channel::<Result<String, String>>();
+
+    let thread_events = events_tx.clone();
+    let password = config.password.clone();
+    let profile_path = config.profile_path.clone();
+    std::thread::Builder::new()
+        .name("tox-daemon".into())
+        .spawn(move || {
+            run_tox_thread(savedata, &password, &profile_path, store, thread_events, request_rx, ready_tx)
+        })
+        .map_err(|e| format!("Failed to spawn tox thread: {e}"))?;
+
+    let address = ready_rx.await.map_err(|_| "Tox thread exited during startup".to_string())??;
+    info!("Daemon online as {address}");
+
+    let result = tokio::select! {
+        result = listen(&config.socket_path, request_tx.clone(), events_tx) => result,
+        _ = tokio::signal::ctrl_c() => {
+            info!("Interrupted, shutting down");
+            Ok(())
+        }
+    };
+
+    let (tx, rx) = oneshot::channel();
+    if request_tx.send(CoreRequest::Shutdown(tx)).await.is_ok() {
+        let _ = rx.await;
+    }
+    let _ = std::fs::remove_file(&config.socket_path);
+    result
+}
+
+#[cfg(unix)]
+async fn listen(
+    socket_path: &PathBuf,
+    request_tx: mpsc::Sender<CoreRequest>,
+    events_tx: broadcast::Sender<CoreEvent>,
+) -> Result<(), String> {
+    use tokio::net::UnixListener;
+
+    if socket_path.exists() {
+        std::fs::remove_file(socket_path).map_err(|e| format!("Failed to remove stale socket: {e}"))?;
+    }
+    // Create the socket owner-only from the start, rather than restricting it
+    // after `bind` when others could already have connected
+    let umask = unsafe { libc::umask(0o177) };
+    let listener = UnixListener::bind(socket_path);
+    unsafe { libc::umask(umask) };
+    let listener = listener.map_err(|e| format!("Failed to bind {}: {e}", socket_path.display()))?;
+    info!("Listening on {}", socket_path.display());
+
+    loop {
+        let (stream, _) = listener.accept().await.map_err(|e| format!("Failed to accept client: {e}"))?;
+        tokio::spawn(handle_client(stream, request_tx.clone(), events_tx.clone()));
+    }
+}
+
+#[cfg(not(unix))]
+async fn listen(
+    _socket_path: &PathBuf,
+    _request_tx: mpsc::Sender<CoreRequest>,
+    _events_tx: broadcast::Sender<CoreEvent>,
+) -> Result<(), String> {
+    Err("The daemon socket is only supported on Unix".to_string())
+}
+
+async fn handle_client<S>(stream: S, request_tx: mpsc::Sender<CoreRequest>, events_tx: broadcast::Sender<CoreEvent>)
+where
+    S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static,
+{
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    let (reader, mut writer) = tokio::io::split(stream);
+    let (out_tx, mut out_rx) = mpsc::channel::<String>(256);
+    let writer_task = tokio::spawn(async move {
+        while let Some(line) = out_rx.recv().await {
+            if writer.write_all(line.as_bytes()).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut lines = BufReader::new(reader).lines();
+    let mut subscription: Option<tokio::task::JoinHandle<()>> = None;
+    while let Ok(Some(line)) = lines.next_line().await {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let request = match rpc::parse_request(&line) {
+            Ok(request) => request,
+            Err(response) => {
+                let _ = out_tx.send(response.to_line()).await;
+                continue;
+            }
+        };
+
+        let id = request.id.clone();
+        let result = if request.method == "subscribe" {
+            if subscription.is_none() {
+                subscription = Some(tokio::spawn(forward_events(events_tx.subscribe(), out_tx.clone())));
+            }
+            Ok(Value::Bool(true))
+        } else {
+            let (tx, rx) = oneshot::channel();
+            if request_tx.send(CoreRequest::Call(request, tx)).await.is_err() {
+                break;
+            }
+            rx.await.unwrap_or_else(|_| Err(RpcError::internal("Tox thread has shut down")))
+        };
+        // Notifications get no response
+        if let Some(id) = id {
+            let _ = out_tx.send(Response::new(id, result).to_line()).await;
+        }
+    }
+
+    if let Some(task) = subscription {
+        task.abort();
+    }
+    drop(out_tx);
+    let _ = writer_task.await;
+}
+
+async fn forward_events(mut events: broadcast::Receiver<CoreEvent>, out_tx: mpsc::Sender<String>) {
+    loop {
+        match events.recv().await {
+            Ok(event) => {
+                let params = serde_json::to_value(&event).unwrap_or_default();
+                if out_tx.send(Notification::event(params).to_line()).await.is_err() {
+                    break;
+                }
+            }
+            Err(broadcast::error::RecvError::Lagged(n)) => warn!("Client fell behind, dropped {n} events"),
+            Err(broadcast::error::RecvError::Closed) => break,
+        }
+    }
+}
+
+// ─── Tox thread ────────────────────────────────────────────────────
+
+fn run_tox_thread(
+    savedata: Vec<u8>,
+    password: &str,
+    profile_path: &Path,
+    store: Arc<MessageStore>,
+    events_tx: broadcast::Sender<CoreEvent>,
+    mut request_rx: mpsc::Receiver<CoreRequest>,
+    ready_tx: oneshot::Sender<Result<String, String>>,
+) {
+    // Lets the loop wait on the request channel with a timeout
+    let waiter = match tokio::runtime::Builder::new_current_thread().enable_time().build() {
+        Ok(rt) => rt,
+        Err(e) => {
+            let _ = ready_tx.send(Err(format!("Failed to create the tox thread's request waiter: {e}")));
+            return;
+        }
+    };
+
+    let tox = match ToxOptionsBuilder::new().savedata(savedata).build() {
+        Ok(tox) => tox,
+        Err(e) => {
+            let _ = ready_tx.send(Err(format!("Failed to create Tox instance: {e}")));
+            return;
+        }
+    };
+    tox.register_callbacks();
+
+    let locks = ConversationLocks::default();
+    if let Err(e) = locks.load(&store) {
+        error!("Failed to load conversation locks: {e}");
+    }
+    let handler: Box<dyn ToxEventHandler> = Box::new(DaemonEventHandler {
+        store: store.clone(),
+        events_tx,
+        locks: locks.clone(),
+        texts: IncomingTexts::default(),
+        tox: &tox,
+    });
+    let handler_ptr = Box::into_raw(Box::new(handler));
+
+    for node in default_bootstrap_nodes() {
+        if let Err(e) = tox.bootstrap(&node.address, node.port, &node.public_key) {
+            warn!("Failed to bootstrap to {}: {e}", node.address);
+        }
+        for tcp_port in &node.tcp_ports {
+            if let Err(e) = tox.add_tcp_relay(&node.address, *tcp_port, &node.public_key) {
+                warn!("Failed to add TCP relay {}:{}: {e}", node.address, tcp_port);
+            }
+        }
+    }
+
+    let address = tox.self_address();
+    let profile = tox.profile_info();
+    if let Err(e) = store.upsert_profile(address.as_str(), &profile.name, &profile.status_message) {
+        error!("{e}");
+    }
+    let _ = ready_tx.send(Ok(address.to_string()));
+
+    let mut next_request = None;
+    loop {
+        let mut shutdown = None;
+        while let Some(request) = next_request.take().or_else(|| request_rx.try_recv().ok()) {
+            match request {
+                CoreRequest::Call(request, reply) => {
+                    let result = dispatch(&tox, &store, &locks, &request);
+                    if matches!(result, Ok((_, true))) {
+                        save_profile(&tox, password, profile_path);
+                    }
+                    let _ = reply.send(result.map(|(value, _)| value));
+                }
+                CoreRequest::Shutdown(reply) => shutdown = Some(reply),
+            }
+        }
+        if let Some(reply) = shutdown {
+            save_profile(&tox, password, profile_path);
+            let _ = reply.send(());
+            break;
+        }
+
+        tox.iterate_with_userdata(handler_ptr as *mut std::ffi::c_void);
+
+        // Wait out the recommended interval, waking as soon as a request arrives
+        let interval = tox.iteration_interval();
+        next_request = match waiter.block_on(tokio::time::timeout(interval, request_rx.recv())) {
+            Ok(Some(request)) => Some(request),
+            // The server is gone without shutting us down; keep the old pace
+            Ok(None) => {
+                std::thread::sleep(interval);
+                None
+            }
+            Err(_) => None,
+        };
+    }
+
+    // SAFETY: the handler pointer is only used by iterate_with_userdata above
+    unsafe {
+        drop(Box::from_raw(handler_ptr));
+    }
+    info!("Tox thread stopped");
+}
+
+#[derive(Deserialize)]
+struct FriendParams {
+    friend_number: u32,
+}
+
+#[derive(Deserialize)]
+struct GroupParams {
+    group_number: u32,
+}
+
+/// Run one RPC method. Returns the result and whether the profile changed.
+fn dispatch(
+    tox: &ToxInstance,
+    store: &MessageStore,
+    locks: &ConversationLocks,
+    request: &Request,
+) -> Result<(Value, bool), RpcError> {
+    let internal = |e: String| RpcError::internal(e);
+    match request.method.as_str() {
+        "get_address" => Ok((json!(tox.self_address().to_string()), false)),
+        "get_profile_info" => {
+            let info = tox.profile_info();
+            Ok((
+                json!({
+                    "tox_id": info.tox_id.as_str(),
+                    "name": info.name,
+                    "status_message": info.status_message,
+                    "status": user_status_str(info.status),
+                }),
+                false,
+            ))
+        }
+        "get_connection_status" => {
+            let status = tox.self_connection_status();
+            Ok((json!({ "connected": status.is_connected(), "status": connection_status_str(status) }), false))
+        }
+        "set_name" => {
+            #[derive(Deserialize)]
+            struct Params {
+                name: String,
+            }
+            let p: Params = rpc::params(request)?;
+            tox.set_name(&p.name).map_err(|e| internal(e.to_string()))?;
+            Ok((Value::Null, true))
+        }
+        "set_status_message" => {
+            #[derive(Deserialize)]
+            struct Params {
+                message: String,
+            }
+            let p: Params = rpc::params(request)?;
+            tox.set_status_message(&p.message).map_err(|e| internal(e.to_string()))?;
+            Ok((Value::Null, true))
+        }
+        "set_status" => {
+            #[derive(Deserialize)]
+            struct Params {
+                status: String,
+            }
+            let p: Params = rpc::params(request)?;
+            let status = match p.status.as_str() {
+                "online" => UserStatus::None,
+                "away" => UserStatus::Away,
+                "busy" => UserStatus::Busy,
+                other => return Err(RpcError::new(rpc::INVALID_PARAMS, format!("Unknown status: {other}"))),
+            };
+            tox.set_status(status);
+            Ok((Value::Null, true))
+        }
+        "friend_add" => {
+            #[derive(Deserialize)]
+            struct Params {
+                tox_id: String,
+                message: String,
+            }
+            let p: Params = rpc::params(request)?;
+            let friend_number = tox.friend_add(&p.tox_id, &p.message).map_err(|e| internal(e.to_string()))?;
+            let pk = tox.friend_public_key(friend_number).map(|pk| pk.0).unwrap_or_default();
+            store.upsert_friend(friend_number, &pk, "", "").map_err(internal)?;
+            Ok((json!(friend_number), true))
+        }
+        "friend_accept" => {
+            #[derive(Deserialize)]
+            struct Params {
+                public_key: String,
+            }
+            let p: Params = rpc::params(request)?;
+            let pk = toxcord_protocol::fingerprint::public_key_from_hex(&p.public_key)
+                .ok_or_else(|| RpcError::new(rpc::INVALID_PARAMS, "Invalid public key"))?;
+            let friend_number = tox.friend_add_norequest(&pk).map_err(|e| internal(e.to_string()))?;
+            store.upsert_friend(friend_number, &p.public_key.to_uppercase(), "", "").map_err(internal)?;
+            store.remove_friend_request(&p.public_key).map_err(internal)?;
+            Ok((json!(friend_number), true))
+        }
+        "friend_delete" => {
+            let p: FriendParams = rpc::params(request)?;
+            tox.friend_delete(p.friend_number).map_err(|e| internal(e.to_string()))?;
+            store.remove_friend(p.friend_number).map_err(internal)?;
+            Ok((Value::Null, true))
+        }
+        "friend_list" => Ok((json!(store.get_friends().map_err(internal)?), false)),
+        "get_friend_requests" => Ok((json!(store.get_friend_requests().map_err(internal)?), false)),
+        "friend_send_message" => {
+            #[derive(Deserialize)]
+            struct Params {
+                friend_number: u32,
+                message: String,
+            }
+            let p: Params = rpc::params(request)?;
+            // Nothing is sent that couldn't be stored safely
+            let content = locks.content_to_store(p.friend_number, &p.message).map_err(internal)?;
+            for part in locks.seal_for_sending(p.friend_number, &p.message, text_id()).map_err(internal)? {
+                tox.friend_send_message(p.friend_number, MessageType::Normal, &part)
+                    .map_err(|e| internal(e.to_string()))?;
+            }
+            let record = DirectMessageRecord {
+                id: uuid::Uuid::new_v4().to_string(),
+                friend_number: p.friend_number as i64,
+                sender: "self".to_string(),
+                content,
+                message_type: "normal".to_string(),
+                timestamp: chrono::Utc::now().to_rfc3339(),
+                is_outgoing: true,
+                delivered: true,
+                read: true,
+                expires_at: store.disappearing_expiry(p.friend_number).map_err(internal)?,
+            };
+            store.insert_direct_message(&record).map_err(internal)?;
+            Ok((json!({ "id": record.id, "timestamp": record.timestamp }), false))
+        }
+        "get_direct_messages" => {
+            #[derive(Deserialize)]
+            struct Params {
+                friend_number: u32,
+                limit: Option<i64>,
+                before_timestamp: Option<String>,
+            }
+            let p: Params = rpc::params(request)?;
+            let messages = store
+                .get_direct_messages(p.friend_number, p.limit.unwrap_or(50), p.before_timestamp.as_deref())
+                .map_err(internal)?;
+            Ok((json!(messages), false))
+        }
+        "group_new" => {
+            #[derive(Deserialize)]
+            struct Params {
+                name: String,
+            }
+            let p: Params = rpc::params(request)?;
+            let group_number = tox
+                .group_new(GroupPrivacyState::Private, &p.name, &tox.self_name())
+                .map_err(|e| internal(e.to_string()))?;
+            Ok((json!(group_number), true))
+        }
+        "group_join" => {
+            #[derive(Deserialize)]
+            struct Params {
+                chat_id: String,
+                #[serde(default)]
+                password: String,
+            }
+            let p: Params = rpc::params(request)?;
+            let chat_id = toxcord_protocol::fingerprint::public_key_from_hex(&p.chat_id)
+                .ok_or_else(|| RpcError::new(rpc::INVALID_PARAMS, "Invalid chat ID"))?;
+            let group_number = tox
+                .group_join(&chat_id, &tox.self_name(), &p.password)
+                .map_err(|e| internal(e.to_string()))?;
+            Ok((json!(group_number), true))
+        }
+        "group_invite_accept" => {
+            #[derive(Deserialize)]
+            struct Params {
+                friend_number: u32,
+                invite_data: Vec<u8>,
+                #[serde(default)]
+                password: String,
+            }
+            let p: Params = rpc::params(request)?;
+            let group_number = tox
+                .group_invite_accept(p.friend_number, &p.invite_data, &tox.self_name(), &p.password)
+                .map_err(|e| internal(e.to_string()))?;
+            Ok((json!(group_number), true))
+        }
+        "group_leave" => {
+            let p: GroupParams = rpc::params(request)?;
+            tox.group_leave(p.group_number, "").map_err(|e| internal(e.to_string()))?;
+            Ok((Value::Null, true))
+        }
+        "group_list" => {
+            let groups: Vec<GroupInfo> = tox
+                .group_list()
+                .into_iter()
+                .filter_map(|g| tox.group_get_info(g).ok())
+                .collect();
+            Ok((json!(groups), false))
+        }
+        "group_send_message" => {
+            #[derive(Deserialize)]
+            struct Params {
+                group_number: u32,
+                message: String,
+            }
+            let p: Params = rpc::params(request)?;
+            let message = sign_group_text(tox, p.group_number, &p.message).map_err(internal)?;
+            for part in split_tagged(&message, TOX_MAX_MESSAGE_LENGTH, text_id()).map_err(internal)? {
+                tox.group_send_message(p.group_number, MessageType::Normal, &part)
+                    .map_err(|e| internal(e.to_string()))?;
+            }
+            Ok((Value::Null, false))
+        }
+        other => Err(RpcError::new(METHOD_NOT_FOUND, format!("Unknown method: {other}"))),
+    }
+}
+
+fn save_profile(tox: &ToxInstance, password: &str, path: &Path) {
+    if let Err(e) = profile_file::write_profile(path, &tox.savedata(), password) {
+        error!("{e}");
+    }
+}
+
+fn user_status_str(status: UserStatus) -> &'static str {
+    match status {
+        UserStatus::None => "online",
+        UserStatus::Away => "away",
+        UserStatus::Busy => "busy",
+    }
+}
+
+fn connection_status_str(status: ConnectionStatus) -> &'static str {
+    match status {
+        ConnectionStatus::None => "none",
+        ConnectionStatus::Tcp => "tcp",
+        ConnectionStatus::Udp => "udp",
+    }
+}
+
+// ─── Callbacks ─────────────────────────────────────────────────────
+
+/// Persists friend state, direct and group messages, and publishes events to clients
+struct DaemonEventHandler {
+    store: Arc<MessageStore>,
+    events_tx: broadcast::Sender<CoreEvent>,
+    locks: ConversationLocks,
+    texts: IncomingTexts,
+    /// For querying group peers during callbacks.
+    /// SAFETY: Only used on the tox thread during iterate_with_userdata,
+    /// and the handler is dropped before the Tox instance.
+    tox: *const ToxInstance,
+}
+
+// SAFETY: DaemonEventHandler is only ever used on the tox thread
+unsafe impl Send for DaemonEventHandler {}
+
+impl DaemonEventHandler {
+    fn emit(&self, event: CoreEvent) {
+        // No subscribers is fine
+        let _ = self.events_tx.send(event);
+    }
+
+    fn tox(&self) -> &ToxInstance {
+        // SAFETY: see the field
+        unsafe { &*self.tox }
+    }
+}
+
+impl ToxEventHandler for DaemonEventHandler {
+    fn on_self_connection_status(&self, status: ConnectionStatus) {
+        info!("Connection status: {}", connection_status_str(status));
+        self.emit(CoreEvent::ConnectionStatus {
+            connected: status.is_connected(),
+            status: connection_status_str(status).to_string(),
+        });
+    }
+
+    fn on_friend_request(&self, public_key: &[u8; 32], message: &str) {
+        let public_key: String = public_key.iter().map(|b| format!("{b:02X}")).collect();
+        if let Err(e) = self.store.add_friend_request(&public_key, message) {
+            error!("Failed to persist friend request: {e}");
+        }
+        self.emit(CoreEvent::FriendRequest {
+            public_key,
+            message: message.to_string(),
+        });
+    }
+
+    fn on_friend_message(&self, friend_number: u32, message_type: MessageType, message: &str) {
+        let text = match self.texts.friend_text(&self.store, &self.locks, friend_number, message) {
+            Ok(Some(text)) => text,
+            // Waiting for the rest of a long text
+            Ok(None) => return,
+            Err(FriendTextError::Unprotected(e)) => {
+                warn!("{e}");
+                self.emit(CoreEvent::UnprotectedMessageRefused { friend_number });
+                return;
+            }
+            Err(FriendTextError::Unstorable(e)) => {
+                error!("Not storing a message from friend {friend_number}: {e}");
+                return;
+            }
+        };
+        let FriendText { stored, text, locked, sticker } = text;
+        let record = DirectMessageRecord {
+            id: uuid::Uuid::new_v4().to_string(),
+            friend_number: friend_number as i64,
+            sender: "friend".to_string(),
+            content: stored,
+            message_type: incoming::message_type_name(message_type, sticker.is_some()).to_string(),
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            is_outgoing: false,
+            delivered: true,
+            read: false,
+            expires_at: self.store.disappearing_expiry(friend_number).unwrap_or_else(|e| {
+                error!("{e}");
+                None
+            }),
+        };
+        if let Err(e) = self.store.insert_direct_message(&record) {
+            error!("Failed to persist incoming message: {e}");
+        }
+        self.emit(CoreEvent::FriendMessage {
+            friend_number,
+            message_type: record.message_type,
+            message: text,
+            id: record.id,
+            timestamp: record.timestamp,
+            locked,
+        });
+    }
+
+    fn on_friend_name(&self, friend_number: u32, name: &str) {
+        if let Err(e) = self.store.update_friend_name(friend_number, name) {
+            error!("Failed to persist friend name: {e}");
+        }
+        self.emit(CoreEvent::FriendName {
+            friend_number,
+            name: name.to_string(),
+        });
+    }
+
+    fn on_friend_status_message(&self, friend_number: u32, message: &str) {
+        if let Err(e) = self.store.update_friend_status_message(friend_number, message) {
+            error!("Failed to persist friend status message: {e}");
+        }
+        self.emit(CoreEvent::FriendStatusMessage {
+            friend_number,
+            message: message.to_string(),
+        });
+    }
+
+    fn on_friend_status(&self, friend_number: u32, status: UserStatus) {
+        let status = user_status_str(status);
+        if let Err(e) = self.store.update_friend_status(friend_number, status) {
+            error!("Failed to persist friend status: {e}");
+        }
+        self.emit(CoreEvent::FriendStatus {
+            friend_number,
+            status: status.to_string(),
+        });
+    }
+
+    fn on_friend_connection_status(&self, friend_number: u32, status: ConnectionStatus) {
+        let going_offline = !status.is_connected();
+        let s = connection_status_str(status);
+        if let Err(e) = self.store.update_friend_connection_status(friend_number, s, going_offline) {
+            error!("Failed to persist friend connection status: {e}");
+        }
+        self.emit(CoreEvent::FriendConnectionStatus {
+            friend_number,
+            connected: status.is_connected(),
+            status: s.to_string(),
+        });
+    }
+
+    fn on_friend_typing(&self, friend_number: u32, is_typing: bool) {
+        self.emit(CoreEvent::FriendTyping { friend_number, is_typing });
+    }
+
+    fn on_friend_read_receipt(&self, friend_number: u32, message_id: u32) {
+        debug!("Read receipt: friend={friend_number} msg_id={message_id}");
+    }
+
+    fn on_friend_lossless_packet(&self, friend_number: u32, _data: &[u8]) {
+        debug!("Ignoring lossless packet from friend {friend_number}");
+    }
+
+    fn on_file_recv_control(&self, _friend_number: u32, _file_number: u32, _control: u32) {}
+
+    fn on_file_chunk_request(&self, _friend_number: u32, _file_number: u32, _position: u64, _length: usize) {}
+
+    fn on_file_recv(&self, friend_number: u32, _file_number: u32, _kind: u32, _file_size: u64, filename: &str) {
+        // Never accepted, so the transfer times out on the sender's side
+        debug!("Ignoring file '{filename}' from friend {friend_number}");
+    }
+
+    fn on_file_recv_chunk(&self, _friend_number: u32, _file_number: u32, _position: u64, _data: &[u8]) {}
+
+    fn on_group_invite(&self, friend_number: u32, invite_data: &[u8], group_name: &str) {
+        self.emit(CoreEvent::GroupInvite {
+            friend_number,
+            invite_data: invite_data.to_vec(),
+            group_name: group_name.to_string(),
+        });
+    }
+
+    fn on_group_peer_join(&self, group_number: u32, peer_id: u32) {
+        self.emit(CoreEvent::GroupPeerJoin { group_number, peer_id });
+    }
+
+    fn on_group_peer_exit(&self, group_number: u32, peer_id: u32, _exit_type: u32, name: &str, _message: &str) {
+        self.emit(CoreEvent::GroupPeerExit {
+            group_number,
+            peer_id,
+            name: name.to_string(),
+        });
+    }
+
+    fn on_group_peer_name(&self, group_number: u32, peer_id: u32, name: &str) {
+        self.emit(CoreEvent::GroupPeerName {
+            group_number,
+            peer_id,
+            name: name.to_string(),
+        });
+    }
+
+    fn on_group_message(&self, group_number: u32, peer_id: u32, message_type: MessageType, message: &str, _message_id: u32) {
+        // Long messages arrive in parts; wait for the rest
+        let Some(message) = self.texts.group_text(group_number, peer_id, message) else {
+            return;
+        };
+        let tox = self.tox();
+        let hex = |key: [u8; 32]| key.iter().map(|b| format!("{b:02X}")).collect::<String>();
+        let sender = GroupSender {
+            peer_id,
+            name: tox.group_peer_get_name(group_number, peer_id).unwrap_or_default(),
+            public_key: tox.group_peer_get_public_key(group_number, peer_id).map(hex).unwrap_or_default(),
+            moderator: matches!(
+                tox.group_peer_get_role(group_number, peer_id),
+                Ok(GroupRole::Founder | GroupRole::Moderator)
+            ),
+        };
+        let self_pk = tox.group_self_get_public_key(group_number).map(hex).unwrap_or_default();
+        let Some(text) = incoming::receive_group_text(&self.store, group_number, &sender, &self_pk, message_type, &message)
+        else {
+            return;
+        };
+        let GroupText { record, ignored, .. } = text;
+        if let Err(e) = self.store.insert_channel_message(&record) {
+            error!("Failed to persist group message: {e}");
+        }
+        if ignored {
+            return;
+        }
+        self.emit(CoreEvent::GroupMessage {
+            group_number,
+            peer_id,
+            sender_name: record.sender_name,
+            sender_pk: record.sender_public_key,
+            message: record.content,
+            message_type: record.message_type,
+            id: record.id,
+            timestamp: record.timestamp,
+            channel_id: record.channel_id,
+            mentions_me: record.mentions_me,
+            filtered: record.filtered,
+            sender_verified: record.sender_verified,
+            signer_public_key: record.signer_public_key,
+        });
+    }
+
+    fn on_group_custom_packet(&self, _group_number: u32, _peer_id: u32, _data: &[u8]) {}
+
+    fn on_group_custom_private_packet(&self, _group_number: u32, _peer_id: u32, _data: &[u8]) {}
+
+    fn on_group_self_join(&self, group_number: u32) {
+        info!("Joined group {group_number}");
+        self.emit(CoreEvent::GroupSelfJoin { group_number });
+    }
+
+    fn on_group_join_fail(&self, group_number: u32, fail_type: u32) {
+        let fail_type = match fail_type {
+            0 => "peer_limit",
+            1 => "invalid_password",
+            _ => "unknown",
+        };
+        warn!("Failed to join group {group_number}: {fail_type}");
+        self.emit(CoreEvent::GroupJoinFail {
+            group_number,
+            fail_type: fail_type.to_string(),
+        });
+    }
+
+    fn on_group_topic(&self, group_number: u32, _peer_id: u32, topic: &str) {
+        self.emit(CoreEvent::GroupTopicChange {
+            group_number,
+            topic: topic.to_string(),
+        });
+    }
+
+    fn on_group_peer_status(&self, _group_number: u32, _peer_id: u32, _status: UserStatus) {}
+
+    fn on_group_moderation(&self, _group_number: u32, _source_peer_id: u32, _target_peer_id: u32, _event: GroupModEvent) {}
+}
@@ -0,0 +1,5055 @@
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use rusqlite::{Connection, DatabaseName, ErrorCode};
+use toxcord_protocol::disappearing;
+use toxcord_protocol::guild_directory::DirectoryEntry;
+use toxcord_protocol::guild_layout::GuildLayout;
+use toxcord_protocol::guild_manifest::GuildManifest;
+use toxcord_protocol::markdown;
+use toxcord_protocol::signing::MessageSignature;
+use toxcord_protocol::status_notes::StatusNote;
+use toxcord_protocol::stickers::{Sticker, StickerPack};
+use tracing::{info, warn};
+
+use super::pool::{PooledConnection, ReadPool};
+use super::schema;
+
+/// Thread-safe wrapper around an SQLCipher-encrypted SQLite database.
+/// All database operations go through this struct. Writes share one
+/// connection; reads use a pool of read-only connections so they don't wait
+/// behind writes or each other.
+pub struct MessageStore {
+    conn: Mutex<Connection>,
+    readers: ReadPool,
+    /// Incoming messages waiting to be written in one transaction
+    queued: Mutex<Vec<QueuedWrite>>,
+    /// Directory the database is in, the profile's directory
+    dir: PathBuf,
+}
+
+/// A row added with `queue_*` and written on the next `flush_writes`
+enum QueuedWrite {
+    /// With the call session it was sent during
+    Direct(DirectMessageRecord, Option<String>),
+    Channel(ChannelMessageRecord),
+}
+
+/// Why the message database couldn't be opened
+#[derive(Debug, thiserror::Error)]
+pub enum OpenError {
+    #[error("Wrong password for this profile's message database")]
+    WrongPassword,
+
+    #[error("The message database is encrypted; a password is needed")]
+    PasswordNeeded,
+
+    #[error("{0}")]
+    Failed(String),
+}
+
+impl From<String> for OpenError {
+    fn from(message: String) -> Self {
+        Self::Failed(message)
+    }
+}
+
+impl From<OpenError> for String {
+    fn from(e: OpenError) -> Self {
+        e.to_string()
+    }
+}
+
+/// What a guild's `metadata_doc` holds: the manifest, with the channel layout
+/// alongside it
+#[derive(Default, serde::Serialize, serde::Deserialize)]
+struct GuildDocument {
+    #[serde(flatten)]
+    manifest: GuildManifest,
+    #[serde(default, skip_serializing_if = "GuildLayout::is_empty")]
+    layout: GuildLayout,
+}
+
+/// Queued rows that trigger a flush without waiting for the next one
+const WRITE_BATCH_ROWS: usize = 200;
+
+/// How far apart two members' timestamps for the same channel message can be
+const BACKFILL_MATCH_SECS: i64 = 120;
+
+/// Guilds with more cached members than this default to notifying only for
+/// mentions; smaller ones notify for every message
+const LARGE_GUILD_MEMBERS: i64 = 50;
+
+/// First bytes of an unencrypted SQLite database
+const PLAINTEXT_HEADER: &[u8; 16] = b"SQLite format 3\0";
+
+/// A friend record from the database
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct FriendRecord {
+    pub friend_number: i64,
+    pub public_key: String,
+    pub name: String,
+    pub status_message: String,
+    pub user_status: String,
+    pub connection_status: String,
+    pub last_seen: Option<String>,
+    pub added_at: String,
+    pub notes: String,
+    pub group_id: Option<String>,
+    pub group_position: i64,
+}
+
+/// Friends changed or removed after a revision of the friends table
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct FriendChanges {
+    /// The revision these changes bring the caller up to
+    pub revision: i64,
+    pub changed: Vec<u32>,
+    pub removed: Vec<u32>,
+}
+
+/// A section of the friends list
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct FriendGroupRecord {
+    pub id: String,
+    pub name: String,
+    pub position: i64,
+    pub collapsed: bool,
+    pub created_at: String,
+}
+
+/// A period a friend was online. `ended_at` is None for the current session.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct PresenceSessionRecord {
+    pub started_at: String,
+    pub ended_at: Option<String>,
+}
+
+/// A friend's status note, with who posted it
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct FriendStatusRecord {
+    pub friend_number: u32,
+    pub friend_name: String,
+    pub note: StatusNote,
+}
+
+/// An installed sticker pack
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct StickerPackRecord {
+    pub hash: String,
+    pub name: String,
+    pub stickers: Vec<Sticker>,
+    pub added_at: String,
+}
+
+/// A change of our Tox address
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct NospamChangeRecord {
+    pub old_address: String,
+    pub new_address: String,
+    pub changed_at: String,
+}
+
+/// Another device of ours, linked as a friend for sync
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct LinkedDeviceRecord {
+    pub friend_number: i64,
+    pub public_key: String,
+    pub device_name: String,
+    pub linked_at: String,
+    pub last_synced_at: Option<String>,
+    pub received_until: Option<String>,
+}
+
+/// External endpoint a guild channel is bridged to
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ChannelBridgeRecord {
+    pub channel_id: String,
+    pub guild_id: String,
+    /// "irc" or "matrix"
+    pub protocol: String,
+    /// IRC host:port, or Matrix homeserver URL
+    pub server: String,
+    /// IRC channel, or Matrix room ID
+    pub target: String,
+    pub nickname: String,
+    #[serde(skip_serializing)]
+    pub access_token: String,
+    pub use_tls: bool,
+    pub enabled: bool,
+    pub created_at: String,
+}
+
+/// A pending friend request
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct FriendRequestRecord {
+    pub public_key: String,
+    pub message: String,
+    pub received_at: String,
+}
+
+/// A guild record
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct GuildRecord {
+    pub id: String,
+    pub name: String,
+    pub metadata_group_number: Option<i64>,
+    pub icon_hash: Option<String>,
+    pub owner_public_key: String,
+    pub guild_type: String, // "server" or "dm_group"
+    pub created_at: String,
+}
+
+/// A channel record
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ChannelRecord {
+    pub id: String,
+    pub guild_id: String,
+    pub name: String,
+    pub topic: String,
+    pub channel_type: String,
+    pub category: Option<String>,
+    pub position: i64,
+    pub group_number: Option<i64>,
+    pub created_at: String,
+}
+
+/// A channel message record
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ChannelMessageRecord {
+    pub id: String,
+    pub channel_id: String,
+    pub sender_public_key: String,
+    pub sender_name: String,
+    pub content: String,
+    pub message_type: String,
+    pub timestamp: String,
+    /// Whether the message mentions the local user (announcements count too)
+    pub mentions_me: bool,
+    /// Whether the message counts as unread, by the channel's notification
+    /// level when it arrived
+    #[serde(default)]
+    pub notify: bool,
+    /// Whether the message matched the guild word filter (shown behind a warning)
+    #[serde(default)]
+    pub filtered: bool,
+    /// Whether the message came from another member's history after we joined
+    #[serde(default)]
+    pub backfilled: bool,
+    /// Whether the sender's signature checked out; `None` for messages that
+    /// weren't checked, such as our own and those from before signing
+    #[serde(default)]
+    pub sender_verified: Option<bool>,
+    /// The Tox key that signed the message, if it was signed
+    #[serde(default)]
+    pub signer_public_key: Option<String>,
+    /// The sender's signature, kept to pass the message on with the channel's
+    /// history. Dropped when the message is edited.
+    #[serde(default)]
+    pub signature: Option<MessageSignature>,
+}
+
+/// A guild member we've seen, cached for mention autocomplete and for
+/// listing members while they're offline
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct GuildMemberRecord {
+    pub guild_id: String,
+    pub public_key: String,
+    pub name: String,
+    pub last_seen: String,
+    /// founder, moderator, user or observer
+    pub role: String,
+}
+
+/// A poll posted in a channel
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct PollRecord {
+    pub id: String,
+    pub channel_id: String,
+    pub creator_public_key: String,
+    pub creator_name: String,
+    pub question: String,
+    pub options: Vec<String>,
+    /// Unix seconds
+    pub closes_at: i64,
+    pub closed: bool,
+}
+
+/// A scheduled guild event
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct GuildEventRecord {
+    pub id: String,
+    pub guild_id: String,
+    pub creator_public_key: String,
+    pub creator_name: String,
+    pub title: String,
+    pub description: String,
+    /// Unix seconds
+    pub start_time: i64,
+}
+
+/// A moderation action in a guild
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct AuditLogRecord {
+    pub id: i64,
+    pub guild_id: String,
+    /// kick, role_change, channel_create, channel_delete, channel_rename or topic_change
+    pub action: String,
+    pub actor_public_key: String,
+    pub actor_name: String,
+    pub target_public_key: Option<String>,
+    pub target_name: String,
+    pub details: String,
+    pub created_at: String,
+}
+
+/// Where a page of older messages starts: the messages before the one with
+/// this timestamp and id. The id orders messages with the same timestamp.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct MessageCursor {
+    pub timestamp: String,
+    pub id: String,
+}
+
+/// A page of a conversation's messages, newest first
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct MessagePage<T> {
+    pub messages: Vec<T>,
+    /// Whether there are older messages than these
+    pub has_more: bool,
+    /// Messages in the conversation when the page was read
+    pub total: i64,
+}
+
+/// What a window of messages is centered on: a message, or a moment (RFC 3339,
+/// UTC) standing for the first message from then on
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "kind", content = "value", rename_all = "snake_case")]
+pub enum MessageAnchor {
+    Message(String),
+    Timestamp(String),
+}
+
+/// Messages around an anchor, newest first
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct MessageWindow<T> {
+    pub messages: Vec<T>,
+    /// The message the window is centered on; `None` if the conversation is empty
+    pub target_id: Option<String>,
+    /// Whether there are older messages than these
+    pub has_older: bool,
+    /// Whether there are newer messages than these
+    pub has_newer: bool,
+}
+
+/// One version of a message's content
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct MessageRevisionRecord {
+    pub content: String,
+    /// When this version was sent or edited in
+    pub written_at: String,
+}
+
+/// What a message has said, oldest first; the last revision is its content now
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct MessageHistory {
+    /// The friend, for a direct message
+    pub friend_number: Option<i64>,
+    pub revisions: Vec<MessageRevisionRecord>,
+}
+
+/// A direct message record
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct DirectMessageRecord {
+    pub id: String,
+    pub friend_number: i64,
+    pub sender: String,
+    pub content: String,
+    pub message_type: String,
+    pub timestamp: String,
+    pub is_outgoing: bool,
+    pub delivered: bool,
+    pub read: bool,
+    /// When the message disappears, in Unix seconds
+    #[serde(default)]
+    pub expires_at: Option<i64>,
+}
+
+/// A file transfer record
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct FileTransferRecord {
+    pub id: String,
+    pub friend_number: i64,
+    pub file_number: i64,
+    pub filename: String,
+    pub file_size: i64,
+    pub file_path: Option<String>,
+    pub direction: String, // "incoming" or "outgoing"
+    pub status: String,    // "pending", "active", "paused", "completed", "cancelled", "failed"
+    pub bytes_transferred: i64,
+    pub message_id: Option<String>,
+    /// Hex Tox file id, used to match a re-offered transfer to its partial file
+    pub file_id: Option<String>,
+}
+
+/// A file attached to a DM or channel message
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct AttachmentRecord {
+    pub id: String,
+    pub message_id: String,
+    pub transfer_id: Option<String>,
+    pub filename: String,
+    pub mime_type: String,
+    pub file_size: i64,
+    pub file_path: String,
+    pub thumbnail_path: Option<String>,
+    pub width: Option<i64>,
+    pub height: Option<i64>,
+    pub created_at: String,
+    /// Hex SHA-256, set for files shared in group channels
+    pub sha256: Option<String>,
+    /// Length of a voice note
+    pub duration_ms: Option<i64>,
+    /// Voice note peak levels (0-255), one per bar
+    pub waveform: Option<Vec<u8>>,
+}
+
+/// A message queued to be sent at a later time
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ScheduledMessageRecord {
+    pub id: String,
+    /// "friend" (target_id is the friend number) or "channel" (target_id is the channel id)
+    pub target_type: String,
+    pub target_id: String,
+    pub guild_id: Option<String>,
+    pub content: String,
+    /// RFC 3339 UTC, second precision
+    pub send_at: String,
+    pub status: String, // pending, sent, queued, cancelled, failed
+    /// The message created once it went out
+    pub message_id: Option<String>,
+    pub created_at: String,
+}
+
+/// An incoming call that wasn't answered
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct MissedCallRecord {
+    pub id: String,
+    pub friend_number: i64,
+    pub timestamp: String,
+    pub with_video: bool,
+    /// "no_answer" when it rang out, "do_not_disturb" when declined automatically
+    pub reason: String,
+    pub seen: bool,
+}
+
+/// A group invite waiting to be accepted or declined
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct GroupInviteRecord {
+    pub id: String,
+    pub friend_number: i64,
+    pub group_name: String,
+    pub invite_data: Vec<u8>,
+    pub received_at: String,
+    /// Joining needs the group's password
+    pub password_required: bool,
+}
+
+/// Call quality used with a friend, or by default
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct CallPreferences {
+    /// kbit/s
+    pub audio_bit_rate: u32,
+    /// kbit/s, used when the call has video
+    pub video_bit_rate: u32,
+    /// Whether calls start with the camera on
+    pub video_by_default: bool,
+}
+
+impl Default for CallPreferences {
+    fn default() -> Self {
+        Self {
+            audio_bit_rate: 64,
+            video_bit_rate: 400,
+            video_by_default: false,
+        }
+    }
+}
+
+/// A DM, DM group or guild channel, for the quick switcher
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct RecentConversationRecord {
+    /// "dm", "dm_group" or "channel"
+    pub kind: String,
+    /// Friend public key, DM group guild id, or channel id
+    pub id: String,
+    pub name: String,
+    /// Set for DMs
+    pub friend_number: Option<i64>,
+    /// Set for DM groups and channels
+    pub guild_id: Option<String>,
+    /// Set for channels
+    pub guild_name: Option<String>,
+    /// Timestamp of the newest message
+    pub last_activity: Option<String>,
+    /// Unread messages for DMs, unread mentions for DM groups and channels
+    pub unread_count: i64,
+    pub starred: bool,
+}
+
+/// Activity on one day, across all conversations
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct UsageDayRecord {
+    /// YYYY-MM-DD, local time
+    pub day: String,
+    pub messages_sent: i64,
+    pub messages_received: i64,
+    pub call_seconds: i64,
+    /// Completed file transfers
+    pub bytes_sent: i64,
+    pub bytes_received: i64,
+}
+
+/// One conversation's activity over a period
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ConversationUsageRecord {
+    /// "dm", "dm_group" or "channel"
+    pub kind: String,
+    /// Friend public key or channel id
+    pub id: String,
+    /// Empty if the friend or channel is gone
+    pub name: String,
+    /// Set for channels
+    pub guild_name: Option<String>,
+    pub messages_sent: i64,
+    pub messages_received: i64,
+    pub call_seconds: i64,
+}
+
+impl MessageStore {
+    /// Open or create a database at the given path, encrypted with the given key.
+    /// An unencrypted database from before keys were set is encrypted first.
+    pub fn open(path: &PathBuf, encryption_key: &str) -> Result<Self, OpenError> {
+        // Ensure parent directory exists
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create database directory: {e}"))?;
+        }
+
+        if !encryption_key.is_empty() && is_plaintext_database(path) {
+            encrypt_legacy_database(path, encryption_key)?;
+        }
+
+        let conn = Connection::open(path)
+            .map_err(|e| format!("Failed to open database: {e}"))?;
+
+        // Set the encryption key (SQLCipher pragma)
+        if !encryption_key.is_empty() {
+            conn.pragma_update(None, "key", encryption_key)
+                .map_err(|e| format!("Failed to set encryption key: {e}"))?;
+        }
+
+        // SQLCipher only checks the key on the first read
+        if let Err(e) = conn.query_row("SELECT count(*) FROM sqlite_master", [], |row| row.get::<_, i64>(0)) {
+            return Err(match e.sqlite_error_code() {
+                Some(ErrorCode::NotADatabase) if !encryption_key.is_empty() => OpenError::WrongPassword,
+                Some(ErrorCode::NotADatabase) => OpenError::PasswordNeeded,
+                _ => OpenError::Failed(format!("Failed to open database: {e}")),
+            });
+        }
+
+        // Performance pragmas
+        conn.execute_batch(
+            "PRAGMA journal_mode = WAL;
+             PRAGMA synchronous = NORMAL;
+             PRAGMA foreign_keys = ON;
+             PRAGMA cache_size = -8000;",
+        )
+        .map_err(|e| format!("Failed to set pragmas: {e}"))?;
+
+        // Run migrations
+        schema::initialize(&conn)
+            .map_err(|e| format!("Failed to initialize schema: {e}"))?;
+
+        info!("Database opened at {}", path.display());
+
+        Ok(Self {
+            conn: Mutex::new(conn),
+            readers: ReadPool::new(path.clone(), encryption_key),
+            queued: Mutex::new(Vec::new()),
+            dir: path.parent().map(Path::to_path_buf).unwrap_or_default(),
+        })
+    }
+
+    /// The profile's directory, which the database is in
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The writer connection. Rows in the write queue aren't in the database
+    /// yet; queries on messages use `write_messages`.
+    fn write(&self) -> Result<MutexGuard<'_, Connection>, String> {
+        self.conn.lock().map_err(|e| e.to_string())
+    }
+
+    /// The writer connection, with queued rows written first so the query
+    /// sees every message
+    fn write_messages(&self) -> Result<MutexGuard<'_, Connection>, String> {
+        let mut conn = self.write()?;
+        self.write_queued(&mut conn)?;
+        Ok(conn)
+    }
+
+    fn read(&self) -> Result<PooledConnection<'_>, String> {
+        self.readers.get()
+    }
+
+    /// A reader, with queued rows written first like `write_messages`
+    fn read_messages(&self) -> Result<PooledConnection<'_>, String> {
+        if self.queued.lock().is_ok_and(|queued| !queued.is_empty()) {
+            self.flush_writes()?;
+        }
+        self.readers.get()
+    }
+
+    /// Run store calls on the blocking thread pool, so async callers (and
+    /// whatever else is waiting on the runtime) aren't held up by slow queries
+    pub async fn call<T, F>(self: &Arc<Self>, f: F) -> Result<T, String>
+    where
+        F: FnOnce(&MessageStore) -> Result<T, String> + Send + 'static,
+        T: Send + 'static,
+    {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || f(&store))
+            .await
+            .map_err(|e| format!("Database task failed: {e}"))?
+    }
+
+    /// Re-encrypt the database with a new key, e.g. after a password change.
+    /// The database must already be encrypted.
+    pub fn rekey(&self, new_key: &str) -> Result<(), String> {
+        if new_key.is_empty() {
+            return Err("The message database can't be decrypted".to_string());
+        }
+        let conn = self.write()?;
+
+        // SQLCipher can't rekey a database in WAL mode, which needs the writer
+        // to be the only connection
+        self.readers.close_idle();
+        conn.execute_batch("PRAGMA journal_mode = DELETE;")
+            .map_err(|e| format!("Failed to leave WAL mode: {e}"))?;
+        let result = conn
+            .pragma_update(None, "rekey", new_key)
+            .map_err(|e| format!("Failed to re-encrypt database: {e}"));
+        conn.execute_batch("PRAGMA journal_mode = WAL;")
+            .map_err(|e| format!("Failed to restore WAL mode: {e}"))?;
+        result?;
+        self.readers.set_key(new_key);
+
+        info!("Database re-encrypted");
+        Ok(())
+    }
+
+    /// Schema version of the database
+    pub fn schema_version(&self) -> Result<i32, String> {
+        let conn = self.read()?;
+        conn.pragma_query_value(None, "user_version", |row| row.get(0))
+            .map_err(|e| format!("Failed to read schema version: {e}"))
+    }
+
+    /// Size of the database in bytes, not counting the WAL
+    pub fn database_size(&self) -> Result<u64, String> {
+        let conn = self.read()?;
+        let page_count: u64 = conn
+            .pragma_query_value(None, "page_count", |row| row.get(0))
+            .map_err(|e| format!("Failed to read page count: {e}"))?;
+        let page_size: u64 = conn
+            .pragma_query_value(None, "page_size", |row| row.get(0))
+            .map_err(|e| format!("Failed to read page size: {e}"))?;
+        Ok(page_count * page_size)
+    }
+
+    // ─── Write Queue ───────────────────────────────────────────────────
+
+    /// Queue an incoming direct message, tagged with the call going on if
+    /// any, to be written with others in one transaction. Floods of messages,
+    /// such as a backlog arriving on reconnect, then cost one write every
+    /// `flush_writes` rather than one each. Queries on messages write the
+    /// queue first, so they still see it; other queries don't wait for it.
+    pub fn queue_direct_message(&self, msg: DirectMessageRecord, call_session: Option<String>) -> Result<(), String> {
+        self.queue(QueuedWrite::Direct(msg, call_session))
+    }
+
+    /// Queue an incoming channel message, like `queue_direct_message`
+    pub fn queue_channel_message(&self, msg: ChannelMessageRecord) -> Result<(), String> {
+        self.queue(QueuedWrite::Channel(msg))
+    }
+
+    fn queue(&self, write: QueuedWrite) -> Result<(), String> {
+        let len = {
+            let mut queued = self.queued.lock().map_err(|e| e.to_string())?;
+            queued.push(write);
+            queued.len()
+        };
+        if len >= WRITE_BATCH_ROWS {
+            self.flush_writes()?;
+        }
+        Ok(())
+    }
+
+    /// Write queued messages now. Returns how many there were.
+    pub fn flush_writes(&self) -> Result<usize, String> {
+        let mut conn = self.write()?;
+        self.write_queued(&mut conn)
+    }
+
+    fn write_queued(&self, conn: &mut Connection) -> Result<usize, String> {
+        let queued = std::mem::take(&mut *self.queued.lock().map_err(|e| e.to_string())?);
+        if queued.is_empty() {
+            return Ok(0);
+        }
+
+        if let Err(e) = write_rows(conn, &queued) {
+            // Put the rows back, ahead of any queued since, for the next flush
+            let mut rest = self.queued.lock().map_err(|e| e.to_string())?;
+            rest.splice(0..0, queued);
+            return Err(e);
+        }
+        Ok(queued.len())
+    }
+
+    // ─── Profile ───────────────────────────────────────────────────────
+
+    pub fn upsert_profile(&self, tox_id: &str, name: &str, status_message: &str) -> Result<(), String> {
+        let conn = self.write()?;
+        conn.execute(
+            "INSERT INTO profile (id, tox_id, name, status_message) VALUES (1, ?1, ?2, ?3)
+             ON CONFLICT(id) DO UPDATE SET tox_id = ?1, name = ?2, status_message = ?3",
+            rusqlite::params![tox_id, name, status_message],
+        )
+        .map_err(|e| format!("Failed to upsert profile: {e}"))?;
+        Ok(())
+    }
+
+    /// Record a nospam change and store the new address on the profile
+    pub fn record_nospam_change(&self, old_address: &str, new_address: &str) -> Result<(), String> {
+        let conn = self.write()?;
+        conn.execute(
+            "INSERT INTO nospam_history (old_address, new_address) VALUES (?1, ?2)",
+            rusqlite::params![old_address, new_address],
+        )
+        .map_err(|e| format!("Failed to record nospam change: {e}"))?;
+        conn.execute(
+            "UPDATE profile SET tox_id = ?1 WHERE id = 1",
+            rusqlite::params![new_address],
+        )
+        .map_err(|e| format!("Failed to update profile address: {e}"))?;
+        Ok(())
+    }
+
+    /// Past Tox addresses, most recent change first
+    pub fn get_nospam_history(&self) -> Result<Vec<NospamChangeRecord>, String> {
+        let conn = self.read()?;
+        let mut stmt = conn
+            .prepare("SELECT old_address, new_address, changed_at FROM nospam_history ORDER BY id DESC")
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+
+        let history = stmt
+            .query_map([], |row| {
+                Ok(NospamChangeRecord {
+                    old_address: row.get(0)?,
+                    new_address: row.get(1)?,
+                    changed_at: row.get(2)?,
+                })
+            })
+            .map_err(|e| format!("Failed to query nospam history: {e}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect nospam history: {e}"))?;
+
+        Ok(history)
+    }
+
+    /// Read a per-profile setting
+    pub fn get_setting(&self, key: &str) -> Result<Option<String>, String> {
+        let conn = self.read()?;
+        let mut stmt = conn
+            .prepare("SELECT value FROM profile_settings WHERE key = ?1")
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+
+        let mut rows = stmt
+            .query_map(rusqlite::params![key], |row| row.get(0))
+            .map_err(|e| format!("Failed to query setting: {e}"))?;
+
+        match rows.next() {
+            Some(Ok(value)) => Ok(Some(value)),
+            Some(Err(e)) => Err(format!("Failed to read setting: {e}")),
+            None => Ok(None),
+        }
+    }
+
+    pub fn set_setting(&self, key: &str, value: &str) -> Result<(), String> {
+        let conn = self.write()?;
+        conn.execute(
+            "INSERT INTO profile_settings (key, value) VALUES (?1, ?2)
+             ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+            rusqlite::params![key, value],
+        )
+        .map_err(|e| format!("Failed to save setting: {e}"))?;
+        Ok(())
+    }
+
+    // ─── Friends ───────────────────────────────────────────────────────
+
+    pub fn upsert_friend(
+        &self,
+        friend_number: u32,
+        public_key: &str,
+        name: &str,
+        status_message: &str,
+    ) -> Result<(), String> {
+        let conn = self.write()?;
+        conn.execute(
+            "INSERT INTO friends (friend_number, public_key, name, status_message)
+             VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(friend_number) DO UPDATE SET
+                public_key = ?2, name = ?3, status_message = ?4",
+            rusqlite::params![friend_number, public_key, name, status_message],
+        )
+        .map_err(|e| format!("Failed to upsert friend: {e}"))?;
+        Ok(())
+    }
+
+    pub fn update_friend_name(&self, friend_number: u32, name: &str) -> Result<(), String> {
+        let conn = self.write()?;
+        conn.execute(
+            "UPDATE friends SET name = ?1 WHERE friend_number = ?2",
+            rusqlite::params![name, friend_number],
+        )
+        .map_err(|e| format!("Failed to update friend name: {e}"))?;
+        Ok(())
+    }
+
+    pub fn update_friend_status_message(
+        &self,
+        friend_number: u32,
+        message: &str,
+    ) -> Result<(), String> {
+        let conn = self.write()?;
+        conn.execute(
+            "UPDATE friends SET status_message = ?1 WHERE friend_number = ?2",
+            rusqlite::params![message, friend_number],
+        )
+        .map_err(|e| format!("Failed to update friend status message: {e}"))?;
+        Ok(())
+    }
+
+    pub fn update_friend_status(&self, friend_number: u32, status: &str) -> Result<(), String> {
+        let conn = self.write()?;
+        conn.execute(
+            "UPDATE friends SET user_status = ?1 WHERE friend_number = ?2",
+            rusqlite::params![status, friend_number],
+        )
+        .map_err(|e| format!("Failed to update friend status: {e}"))?;
+        Ok(())
+    }
+
+    pub fn update_friend_connection_status(
+        &self,
+        friend_number: u32,
+        status: &str,
+        update_last_seen: bool,
+    ) -> Result<(), String> {
+        let conn = self.write()?;
+        if update_last_seen {
+            conn.execute(
+                "UPDATE friends SET connection_status = ?1, last_seen = datetime('now')
+                 WHERE friend_number = ?2",
+                rusqlite::params![status, friend_number],
+            )
+        } else {
+            // Only called when the connection changes, so this is a handshake
+            conn.execute(
+                "UPDATE friends SET connection_status = ?1, last_handshake_at = datetime('now')
+                 WHERE friend_number = ?2",
+                rusqlite::params![status, friend_number],
+            )
+        }
+        .map_err(|e| format!("Failed to update friend connection: {e}"))?;
+        Ok(())
+    }
+
+    /// When the connection to a friend was last established, in SQLite's
+    /// datetime format
+    pub fn get_friend_last_handshake(&self, friend_number: u32) -> Result<Option<String>, String> {
+        let conn = self.read()?;
+        let mut stmt = conn
+            .prepare("SELECT last_handshake_at FROM friends WHERE friend_number = ?1")
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+
+        let mut rows = stmt
+            .query_map(rusqlite::params![friend_number], |row| row.get(0))
+            .map_err(|e| format!("Failed to query friend: {e}"))?;
+
+        match rows.next() {
+            Some(Ok(handshake)) => Ok(handshake),
+            Some(Err(e)) => Err(format!("Failed to read friend: {e}")),
+            None => Ok(None),
+        }
+    }
+
+    pub fn set_friend_tox_id(&self, friend_number: u32, tox_id: &str) -> Result<(), String> {
+        let conn = self.write()?;
+        conn.execute(
+            "UPDATE friends SET tox_id = ?1 WHERE friend_number = ?2",
+            rusqlite::params![tox_id, friend_number],
+        )
+        .map_err(|e| format!("Failed to update friend Tox ID: {e}"))?;
+        Ok(())
+    }
+
+    /// The Tox ID we added a friend by, if we were the one who added them
+    pub fn get_friend_tox_id(&self, friend_number: u32) -> Result<Option<String>, String> {
+        let conn = self.read()?;
+        let mut stmt = conn
+            .prepare("SELECT tox_id FROM friends WHERE friend_number = ?1")
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+
+        let mut rows = stmt
+            .query_map(rusqlite::params![friend_number], |row| row.get(0))
+            .map_err(|e| format!("Failed to query friend: {e}"))?;
+
+        match rows.next() {
+            Some(Ok(tox_id)) => Ok(tox_id),
+            Some(Err(e)) => Err(format!("Failed to read friend: {e}")),
+            None => Ok(None),
+        }
+    }
+
+    pub fn remove_friend(&self, friend_number: u32) -> Result<(), String> {
+        let conn = self.write()?;
+        conn.execute(
+            "DELETE FROM friends WHERE friend_number = ?1",
+            rusqlite::params![friend_number],
+        )
+        .map_err(|e| format!("Failed to remove friend: {e}"))?;
+        // Friend numbers get reused, so don't let the next friend inherit this history
+        conn.execute(
+            "DELETE FROM presence_log WHERE friend_number = ?1",
+            rusqlite::params![friend_number],
+        )
+        .map_err(|e| format!("Failed to remove friend presence: {e}"))?;
+        conn.execute(
+            "DELETE FROM auto_reply_log WHERE friend_number = ?1",
+            rusqlite::params![friend_number],
+        )
+        .map_err(|e| format!("Failed to remove friend auto-reply log: {e}"))?;
+        conn.execute(
+            "DELETE FROM friend_verifications WHERE friend_number = ?1",
+            rusqlite::params![friend_number],
+        )
+        .map_err(|e| format!("Failed to remove friend verification: {e}"))?;
+        conn.execute(
+            "DELETE FROM linked_devices WHERE friend_number = ?1",
+            rusqlite::params![friend_number],
+        )
+        .map_err(|e| format!("Failed to remove linked device: {e}"))?;
+        conn.execute(
+            "DELETE FROM group_invites WHERE friend_number = ?1",
+            rusqlite::params![friend_number],
+        )
+        .map_err(|e| format!("Failed to remove group invites: {e}"))?;
+        Ok(())
+    }
+
+    pub fn get_friend_number_by_public_key(&self, public_key: &str) -> Result<Option<u32>, String> {
+        let conn = self.read()?;
+        let mut stmt = conn
+            .prepare("SELECT friend_number FROM friends WHERE public_key = ?1 COLLATE NOCASE")
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+
+        let mut rows = stmt
+            .query_map(rusqlite::params![public_key], |row| row.get(0))
+            .map_err(|e| format!("Failed to query friend: {e}"))?;
+
+        match rows.next() {
+            Some(Ok(friend_number)) => Ok(Some(friend_number)),
+            Some(Err(e)) => Err(format!("Failed to read friend: {e}")),
+            None => Ok(None),
+        }
+    }
+
+    /// The public key stored for a friend number, if any
+    pub fn get_friend_public_key(&self, friend_number: u32) -> Result<Option<String>, String> {
+        let conn = self.read()?;
+        let mut stmt = conn
+            .prepare("SELECT public_key FROM friends WHERE friend_number = ?1")
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+
+        let mut rows = stmt
+            .query_map(rusqlite::params![friend_number], |row| row.get(0))
+            .map_err(|e| format!("Failed to query friend: {e}"))?;
+
+        match rows.next() {
+            Some(Ok(public_key)) => Ok(Some(public_key)),
+            Some(Err(e)) => Err(format!("Failed to read friend: {e}")),
+            None => Ok(None),
+        }
+    }
+
+    /// Whether group invites from a friend are joined without asking
+    pub fn get_auto_accept_group_invites(&self, friend_number: u32) -> Result<bool, String> {
+        let conn = self.read()?;
+        let mut stmt = conn
+            .prepare("SELECT auto_accept_group_invites FROM friends WHERE friend_number = ?1")
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+
+        let mut rows = stmt
+            .query_map(rusqlite::params![friend_number], |row| row.get(0))
+            .map_err(|e| format!("Failed to query friend: {e}"))?;
+
+        match rows.next() {
+            Some(Ok(enabled)) => Ok(enabled),
+            Some(Err(e)) => Err(format!("Failed to read auto-accept setting: {e}")),
+            None => Ok(false),
+        }
+    }
+
+    pub fn set_auto_accept_group_invites(&self, friend_number: u32, enabled: bool) -> Result<(), String> {
+        let conn = self.write()?;
+        conn.execute(
+            "UPDATE friends SET auto_accept_group_invites = ?1 WHERE friend_number = ?2",
+            rusqlite::params![enabled, friend_number],
+        )
+        .map_err(|e| format!("Failed to update auto-accept setting: {e}"))?;
+        Ok(())
+    }
+
+    // ─── Friend Groups ─────────────────────────────────────────────────
+
+    /// Create a group at the end of the friends list
+    pub fn create_friend_group(&self, name: &str) -> Result<FriendGroupRecord, String> {
+        let id = uuid::Uuid::new_v4().to_string();
+        {
+            let conn = self.write()?;
+            conn.execute(
+                "INSERT INTO friend_groups (id, name, position)
+                 VALUES (?1, ?2, (SELECT COALESCE(MAX(position) + 1, 0) FROM friend_groups))",
+                rusqlite::params![id, name],
+            )
+            .map_err(|e| format!("Failed to create friend group: {e}"))?;
+        }
+        self.get_friend_groups()?
+            .into_iter()
+            .find(|g| g.id == id)
+            .ok_or_else(|| "Failed to read new friend group".to_string())
+    }
+
+    pub fn get_friend_groups(&self) -> Result<Vec<FriendGroupRecord>, String> {
+        let conn = self.read()?;
+        let mut stmt = conn
+            .prepare("SELECT id, name, position, collapsed, created_at FROM friend_groups ORDER BY position, created_at")
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+
+        let groups = stmt
+            .query_map([], |row| {
+                Ok(FriendGroupRecord {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    position: row.get(2)?,
+                    collapsed: row.get(3)?,
+                    created_at: row.get(4)?,
+                })
+            })
+            .map_err(|e| format!("Failed to query friend groups: {e}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect friend groups: {e}"))?;
+
+        Ok(groups)
+    }
+
+    /// Returns false if the group doesn't exist
+    pub fn rename_friend_group(&self, group_id: &str, name: &str) -> Result<bool, String> {
+        let conn = self.write()?;
+        let updated = conn
+            .execute(
+                "UPDATE friend_groups SET name = ?1 WHERE id = ?2",
+                rusqlite::params![name, group_id],
+            )
+            .map_err(|e| format!("Failed to rename friend group: {e}"))?;
+        Ok(updated > 0)
+    }
+
+    pub fn set_friend_group_collapsed(&self, group_id: &str, collapsed: bool) -> Result<(), String> {
+        let conn = self.write()?;
+        conn.execute(
+            "UPDATE friend_groups SET collapsed = ?1 WHERE id = ?2",
+            rusqlite::params![collapsed, group_id],
+        )
+        .map_err(|e| format!("Failed to update friend group: {e}"))?;
+        Ok(())
+    }
+
+    /// Delete a group. Its friends move back to the ungrouped section.
+    pub fn delete_friend_group(&self, group_id: &str) -> Result<(), String> {
+        let conn = self.write()?;
+        conn.execute(
+            "UPDATE friends SET group_id = NULL, group_position = 0 WHERE group_id = ?1",
+            rusqlite::params![group_id],
+        )
+        .map_err(|e| format!("Failed to ungroup friends: {e}"))?;
+        conn.execute(
+            "DELETE FROM friend_groups WHERE id = ?1",
+            rusqlite::params![group_id],
+        )
+        .map_err(|e| format!("Failed to delete friend group: {e}"))?;
+        Ok(())
+    }
+
+    /// Set the order of groups; `group_ids` lists them top to bottom
+    pub fn reorder_friend_groups(&self, group_ids: &[String]) -> Result<(), String> {
+        let conn = self.write()?;
+        for (position, group_id) in group_ids.iter().enumerate() {
+            conn.execute(
+                "UPDATE friend_groups SET position = ?1 WHERE id = ?2",
+                rusqlite::params![position as i64, group_id],
+            )
+            .map_err(|e| format!("Failed to reorder friend groups: {e}"))?;
+        }
+        Ok(())
+    }
+
+    /// Move a friend into a group (`None` for ungrouped). Without a position
+    /// the friend goes to the end of the group; with one, friends at or after
+    /// it shift down.
+    pub fn set_friend_group(
+        &self,
+        friend_number: u32,
+        group_id: Option<&str>,
+        position: Option<i64>,
+    ) -> Result<(), String> {
+        let conn = self.write()?;
+        let position = match position {
+            Some(position) => {
+                conn.execute(
+                    "UPDATE friends SET group_position = group_position + 1
+                     WHERE group_id IS ?1 AND group_position >= ?2 AND friend_number != ?3",
+                    rusqlite::params![group_id, position, friend_number],
+                )
+                .map_err(|e| format!("Failed to reorder friends: {e}"))?;
+                position
+            }
+            None => conn
+                .query_row(
+                    "SELECT COALESCE(MAX(group_position) + 1, 0) FROM friends
+                     WHERE group_id IS ?1 AND friend_number != ?2",
+                    rusqlite::params![group_id, friend_number],
+                    |row| row.get(0),
+                )
+                .map_err(|e| format!("Failed to query friend order: {e}"))?,
+        };
+        let updated = conn
+            .execute(
+                "UPDATE friends SET group_id = ?1, group_position = ?2 WHERE friend_number = ?3",
+                rusqlite::params![group_id, position, friend_number],
+            )
+            .map_err(|e| format!("Failed to set friend group: {e}"))?;
+        if updated == 0 {
+            return Err(format!("Friend {friend_number} not found"));
+        }
+        Ok(())
+    }
+
+    // ─── Verification ──────────────────────────────────────────────────
+
+    /// Record that the user confirmed `public_key` belongs to this friend
+    pub fn set_friend_verified(&self, friend_number: u32, public_key: &str) -> Result<(), String> {
+        let conn = self.write()?;
+        conn.execute(
+            "INSERT INTO friend_verifications (friend_number, public_key) VALUES (?1, ?2)
+             ON CONFLICT(friend_number) DO UPDATE SET
+                public_key = excluded.public_key, verified_at = datetime('now')",
+            rusqlite::params![friend_number, public_key.to_uppercase()],
+        )
+        .map_err(|e| format!("Failed to save verification: {e}"))?;
+        Ok(())
+    }
+
+    pub fn clear_friend_verification(&self, friend_number: u32) -> Result<(), String> {
+        let conn = self.write()?;
+        conn.execute(
+            "DELETE FROM friend_verifications WHERE friend_number = ?1",
+            rusqlite::params![friend_number],
+        )
+        .map_err(|e| format!("Failed to clear verification: {e}"))?;
+        Ok(())
+    }
+
+    /// The verified public key and when it was verified
+    pub fn get_friend_verification(&self, friend_number: u32) -> Result<Option<(String, String)>, String> {
+        let conn = self.read()?;
+        let mut stmt = conn
+            .prepare("SELECT public_key, verified_at FROM friend_verifications WHERE friend_number = ?1")
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+
+        let mut rows = stmt
+            .query_map(rusqlite::params![friend_number], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(|e| format!("Failed to query verification: {e}"))?;
+
+        match rows.next() {
+            Some(Ok(verification)) => Ok(Some(verification)),
+            Some(Err(e)) => Err(format!("Failed to read verification: {e}")),
+            None => Ok(None),
+        }
+    }
+
+    /// Record an auto-reply to a friend unless they already got one in the last
+    /// `interval_hours`. Returns true if the reply should be sent.
+    pub fn claim_auto_reply(&self, friend_number: u32, interval_hours: u32) -> Result<bool, String> {
+        let conn = self.write()?;
+        let claimed = conn
+            .execute(
+                "INSERT INTO auto_reply_log (friend_number) VALUES (?1)
+                 ON CONFLICT(friend_number) DO UPDATE SET sent_at = datetime('now')
+                 WHERE sent_at <= datetime('now', '-' || ?2 || ' hours')",
+                rusqlite::params![friend_number, interval_hours],
+            )
+            .map_err(|e| format!("Failed to record auto-reply: {e}"))?;
+        Ok(claimed > 0)
+    }
+
+    pub fn get_friends(&self) -> Result<Vec<FriendRecord>, String> {
+        let conn = self.read()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT friend_number, public_key, name, status_message,
+                        user_status, connection_status, last_seen, added_at, notes,
+                        group_id, group_position
+                 FROM friends ORDER BY name COLLATE NOCASE",
+            )
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+
+        let friends = stmt
+            .query_map([], |row| {
+                Ok(FriendRecord {
+                    friend_number: row.get(0)?,
+                    public_key: row.get(1)?,
+                    name: row.get(2)?,
+                    status_message: row.get(3)?,
+                    user_status: row.get(4)?,
+                    connection_status: row.get(5)?,
+                    last_seen: row.get(6)?,
+                    added_at: row.get(7)?,
+                    notes: row.get(8)?,
+                    group_id: row.get(9)?,
+                    group_position: row.get(10)?,
+                })
+            })
+            .map_err(|e| format!("Failed to query friends: {e}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect friends: {e}"))?;
+
+        Ok(friends)
+    }
+
+    /// Current revision of the friends table, bumped on every change to a friend
+    pub fn friends_revision(&self) -> Result<i64, String> {
+        let conn = self.read()?;
+        conn.query_row("SELECT revision FROM friends_revision", [], |row| row.get(0))
+            .map_err(|e| format!("Failed to read friends revision: {e}"))
+    }
+
+    /// Friends changed or removed after `revision`
+    pub fn friends_changed_since(&self, revision: i64) -> Result<FriendChanges, String> {
+        let conn = self.read()?;
+        let current: i64 = conn
+            .query_row("SELECT revision FROM friends_revision", [], |row| row.get(0))
+            .map_err(|e| format!("Failed to read friends revision: {e}"))?;
+
+        // Changes made after reading `current` are left for the next call
+        let numbers = |sql: &str| -> Result<Vec<u32>, String> {
+            let mut stmt = conn.prepare(sql).map_err(|e| format!("Failed to prepare query: {e}"))?;
+            let rows = stmt
+                .query_map(rusqlite::params![revision, current], |row| row.get(0))
+                .map_err(|e| format!("Failed to query friend changes: {e}"))?
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| format!("Failed to collect friend changes: {e}"));
+            rows
+        };
+        let changed = numbers("SELECT friend_number FROM friends WHERE revision > ?1 AND revision <= ?2")?;
+        let removed = numbers("SELECT friend_number FROM removed_friends WHERE revision > ?1 AND revision <= ?2")?;
+
+        Ok(FriendChanges {
+            revision: current,
+            changed,
+            removed,
+        })
+    }
+
+    // ─── Presence ──────────────────────────────────────────────────────
+
+    /// Open an online session for a friend unless one is already open
+    /// (TCP/UDP switches report the friend as connected again)
+    pub fn start_presence_session(&self, friend_number: u32) -> Result<(), String> {
+        let conn = self.write()?;
+        conn.execute(
+            "INSERT INTO presence_log (friend_number)
+             SELECT ?1 WHERE NOT EXISTS (
+                 SELECT 1 FROM presence_log WHERE friend_number = ?1 AND ended_at IS NULL
+             )",
+            rusqlite::params![friend_number],
+        )
+        .map_err(|e| format!("Failed to start presence session: {e}"))?;
+        Ok(())
+    }
+
+    pub fn end_presence_session(&self, friend_number: u32) -> Result<(), String> {
+        let conn = self.write()?;
+        conn.execute(
+            "UPDATE presence_log SET ended_at = datetime('now')
+             WHERE friend_number = ?1 AND ended_at IS NULL",
+            rusqlite::params![friend_number],
+        )
+        .map_err(|e| format!("Failed to end presence session: {e}"))?;
+        Ok(())
+    }
+
+    /// Close every open session, since we can't see anyone while we're offline
+    pub fn end_all_presence_sessions(&self) -> Result<(), String> {
+        let conn = self.write()?;
+        conn.execute(
+            "UPDATE presence_log SET ended_at = datetime('now') WHERE ended_at IS NULL",
+            [],
+        )
+        .map_err(|e| format!("Failed to end presence sessions: {e}"))?;
+        Ok(())
+    }
+
+    /// A friend's sessions still running or ended at or after `since`, oldest first
+    pub fn get_presence_sessions(&self, friend_number: u32, since: &str) -> Result<Vec<PresenceSessionRecord>, String> {
+        let conn = self.read()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT started_at, ended_at FROM presence_log
+                 WHERE friend_number = ?1 AND (ended_at IS NULL OR ended_at >= ?2)
+                 ORDER BY started_at",
+            )
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+
+        let sessions = stmt
+            .query_map(rusqlite::params![friend_number, since], |row| {
+                Ok(PresenceSessionRecord {
+                    started_at: row.get(0)?,
+                    ended_at: row.get(1)?,
+                })
+            })
+            .map_err(|e| format!("Failed to query presence: {e}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect presence: {e}"))?;
+
+        Ok(sessions)
+    }
+
+    // ─── Status Notes ──────────────────────────────────────────────────
+
+    /// Save a status note, from a friend or ours with `friend_number` None.
+    /// Returns false if we already had it.
+    pub fn insert_status_note(&self, friend_number: Option<u32>, note: &StatusNote) -> Result<bool, String> {
+        let conn = self.write()?;
+        let inserted = conn
+            .execute(
+                "INSERT OR IGNORE INTO statuses (id, friend_number, text, posted_at, expires_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                rusqlite::params![note.id, friend_number, note.text, note.posted_at, note.expires_at],
+            )
+            .map_err(|e| format!("Failed to save status note: {e}"))?;
+        Ok(inserted > 0)
+    }
+
+    /// Our notes that haven't expired by `now` (Unix seconds), newest first
+    pub fn get_own_status_notes(&self, now: i64) -> Result<Vec<StatusNote>, String> {
+        let conn = self.read()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, text, posted_at, expires_at FROM statuses
+                 WHERE friend_number IS NULL AND expires_at > ?1 ORDER BY posted_at DESC",
+            )
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+
+        let notes = stmt
+            .query_map(rusqlite::params![now], |row| {
+                Ok(StatusNote {
+                    id: row.get(0)?,
+                    text: row.get(1)?,
+                    posted_at: row.get(2)?,
+                    expires_at: row.get(3)?,
+                })
+            })
+            .map_err(|e| format!("Failed to query status notes: {e}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect status notes: {e}"))?;
+
+        Ok(notes)
+    }
+
+    /// Friends' notes that haven't expired by `now` (Unix seconds), newest first
+    pub fn get_friend_status_notes(&self, now: i64) -> Result<Vec<FriendStatusRecord>, String> {
+        let conn = self.read()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT s.friend_number, f.name, s.id, s.text, s.posted_at, s.expires_at
+                 FROM statuses s JOIN friends f ON f.friend_number = s.friend_number
+                 WHERE s.expires_at > ?1 ORDER BY s.posted_at DESC",
+            )
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+
+        let notes = stmt
+            .query_map(rusqlite::params![now], |row| {
+                Ok(FriendStatusRecord {
+                    friend_number: row.get(0)?,
+                    friend_name: row.get(1)?,
+                    note: StatusNote {
+                        id: row.get(2)?,
+                        text: row.get(3)?,
+                        posted_at: row.get(4)?,
+                        expires_at: row.get(5)?,
+                    },
+                })
+            })
+            .map_err(|e| format!("Failed to query status notes: {e}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect status notes: {e}"))?;
+
+        Ok(notes)
+    }
+
+    /// Delete notes that expired by `now` (Unix seconds)
+    pub fn delete_expired_status_notes(&self, now: i64) -> Result<(), String> {
+        let conn = self.write()?;
+        conn.execute("DELETE FROM statuses WHERE expires_at <= ?1", rusqlite::params![now])
+            .map_err(|e| format!("Failed to delete expired status notes: {e}"))?;
+        Ok(())
+    }
+
+    // ─── Friend Requests ───────────────────────────────────────────────
+
+    pub fn add_friend_request(&self, public_key: &str, message: &str) -> Result<(), String> {
+        let conn = self.write()?;
+        conn.execute(
+            "INSERT OR REPLACE INTO friend_requests (public_key, message) VALUES (?1, ?2)",
+            rusqlite::params![public_key, message],
+        )
+        .map_err(|e| format!("Failed to add friend request: {e}"))?;
+        Ok(())
+    }
+
+    pub fn remove_friend_request(&self, public_key: &str) -> Result<(), String> {
+        let conn = self.write()?;
+        conn.execute(
+            "DELETE FROM friend_requests WHERE public_key = ?1",
+            rusqlite::params![public_key],
+        )
+        .map_err(|e| format!("Failed to remove friend request: {e}"))?;
+        Ok(())
+    }
+
+    pub fn get_friend_requests(&self) -> Result<Vec<FriendRequestRecord>, String> {
+        let conn = self.read()?;
+        let mut stmt = conn
+            .prepare("SELECT public_key, message, received_at FROM friend_requests ORDER BY received_at DESC")
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+
+        let requests = stmt
+            .query_map([], |row| {
+                Ok(FriendRequestRecord {
+                    public_key: row.get(0)?,
+                    message: row.get(1)?,
+                    received_at: row.get(2)?,
+                })
+            })
+            .map_err(|e| format!("Failed to query friend requests: {e}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect friend requests: {e}"))?;
+
+        Ok(requests)
+    }
+
+    // ─── Linked devices ────────────────────────────────────────────────
+
+    pub fn link_device(&self, friend_number: u32, public_key: &str) -> Result<(), String> {
+        let conn = self.write()?;
+        conn.execute(
+            "INSERT INTO linked_devices (friend_number, public_key) VALUES (?1, ?2)
+             ON CONFLICT(friend_number) DO UPDATE SET public_key = excluded.public_key",
+            rusqlite::params![friend_number, public_key.to_uppercase()],
+        )
+        .map_err(|e| format!("Failed to link device: {e}"))?;
+        Ok(())
+    }
+
+    pub fn unlink_device(&self, friend_number: u32) -> Result<bool, String> {
+        let conn = self.write()?;
+        let removed = conn
+            .execute(
+                "DELETE FROM linked_devices WHERE friend_number = ?1",
+                rusqlite::params![friend_number],
+            )
+            .map_err(|e| format!("Failed to unlink device: {e}"))?;
+        Ok(removed > 0)
+    }
+
+    pub fn get_linked_devices(&self) -> Result<Vec<LinkedDeviceRecord>, String> {
+        let conn = self.read()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT friend_number, public_key, device_name, linked_at, last_synced_at, received_until
+                 FROM linked_devices ORDER BY linked_at",
+            )
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+
+        let devices = stmt
+            .query_map([], |row| {
+                Ok(LinkedDeviceRecord {
+                    friend_number: row.get(0)?,
+                    public_key: row.get(1)?,
+                    device_name: row.get(2)?,
+                    linked_at: row.get(3)?,
+                    last_synced_at: row.get(4)?,
+                    received_until: row.get(5)?,
+                })
+            })
+            .map_err(|e| format!("Failed to query linked devices: {e}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect linked devices: {e}"))?;
+
+        Ok(devices)
+    }
+
+    /// The linked device behind a friend number, if it is one
+    pub fn get_linked_device(&self, friend_number: u32) -> Result<Option<LinkedDeviceRecord>, String> {
+        Ok(self
+            .get_linked_devices()?
+            .into_iter()
+            .find(|d| d.friend_number == friend_number as i64))
+    }
+
+    pub fn set_device_name(&self, friend_number: u32, device_name: &str) -> Result<(), String> {
+        let conn = self.write()?;
+        conn.execute(
+            "UPDATE linked_devices SET device_name = ?1 WHERE friend_number = ?2",
+            rusqlite::params![device_name, friend_number],
+        )
+        .map_err(|e| format!("Failed to update device name: {e}"))?;
+        Ok(())
+    }
+
+    /// Record a sync from a device, advancing `received_until` to `newest_message` if later
+    pub fn record_device_sync(&self, friend_number: u32, newest_message: Option<&str>) -> Result<(), String> {
+        let conn = self.write()?;
+        conn.execute(
+            "UPDATE linked_devices SET
+                last_synced_at = datetime('now'),
+                received_until = CASE
+                    WHEN ?1 IS NULL THEN received_until
+                    WHEN received_until IS NULL OR ?1 > received_until THEN ?1
+                    ELSE received_until
+                END
+             WHERE friend_number = ?2",
+            rusqlite::params![newest_message, friend_number],
+        )
+        .map_err(|e| format!("Failed to record device sync: {e}"))?;
+        Ok(())
+    }
+
+    // ─── Direct Messages ───────────────────────────────────────────────
+
+    pub fn insert_direct_message(&self, msg: &DirectMessageRecord) -> Result<(), String> {
+        let conn = self.write()?;
+        insert_direct_message(&conn, msg)
+    }
+
+    pub fn get_direct_messages(
+        &self,
+        friend_number: u32,
+        limit: i64,
+        before_timestamp: Option<&str>,
+    ) -> Result<Vec<DirectMessageRecord>, String> {
+        let conn = self.read_messages()?;
+
+        let (sql, params): (&str, Vec<Box<dyn rusqlite::types::ToSql>>) = if let Some(before) = before_timestamp {
+            (
+                "SELECT id, friend_number, sender, content, message_type, timestamp, is_outgoing, delivered, read, expires_at
+                 FROM direct_messages
+                 WHERE friend_number = ?1 AND timestamp < ?2
+                 ORDER BY timestamp DESC, id DESC LIMIT ?3",
+                vec![
+                    Box::new(friend_number as i64),
+                    Box::new(before.to_string()),
+                    Box::new(limit),
+                ],
+            )
+        } else {
+            (
+                "SELECT id, friend_number, sender, content, message_type, timestamp, is_outgoing, delivered, read, expires_at
+                 FROM direct_messages
+                 WHERE friend_number = ?1
+                 ORDER BY timestamp DESC, id DESC LIMIT ?2",
+                vec![
+                    Box::new(friend_number as i64),
+                    Box::new(limit),
+                ],
+            )
+        };
+
+        let mut stmt = conn
+            .prepare(sql)
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+
+        let params_refs: Vec<&dyn rusqlite::types::ToSql> = params.iter().map(|p| p.as_ref()).collect();
+
+        let messages = stmt
+            .query_map(params_refs.as_slice(), |row| {
+                Ok(DirectMessageRecord {
+                    id: row.get(0)?,
+                    friend_number: row.get(1)?,
+                    sender: row.get(2)?,
+                    content: row.get(3)?,
+                    message_type: row.get(4)?,
+                    timestamp: row.get(5)?,
+                    is_outgoing: row.get(6)?,
+                    delivered: row.get(7)?,
+                    read: row.get(8)?,
+                    expires_at: row.get(9)?,
+                })
+            })
+            .map_err(|e| format!("Failed to query messages: {e}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect messages: {e}"))?;
+
+        Ok(messages)
+    }
+
+    /// Our most recent text messages to a friend, newest first
+    pub fn get_own_recent_direct_messages(&self, friend_number: u32, limit: i64) -> Result<Vec<DirectMessageRecord>, String> {
+        let conn = self.read_messages()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, friend_number, sender, content, message_type, timestamp, is_outgoing, delivered, read, expires_at
+                 FROM direct_messages
+                 WHERE friend_number = ?1 AND is_outgoing = 1 AND message_type IN ('normal', 'action')
+                 ORDER BY timestamp DESC, id DESC LIMIT ?2",
+            )
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+
+        let messages = stmt
+            .query_map(rusqlite::params![friend_number, limit], |row| {
+                Ok(DirectMessageRecord {
+                    id: row.get(0)?,
+                    friend_number: row.get(1)?,
+                    sender: row.get(2)?,
+                    content: row.get(3)?,
+                    message_type: row.get(4)?,
+                    timestamp: row.get(5)?,
+                    is_outgoing: row.get(6)?,
+                    delivered: row.get(7)?,
+                    read: row.get(8)?,
+                    expires_at: row.get(9)?,
+                })
+            })
+            .map_err(|e| format!("Failed to query messages: {e}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect messages: {e}"))?;
+
+        Ok(messages)
+    }
+
+    /// A page of direct messages with a friend, newest first, older than `before`
+    pub fn get_direct_messages_page(
+        &self,
+        friend_number: u32,
+        limit: i64,
+        before: Option<&MessageCursor>,
+    ) -> Result<MessagePage<DirectMessageRecord>, String> {
+        let conn = self.read_messages()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, friend_number, sender, content, message_type, timestamp, is_outgoing, delivered, read, expires_at
+                 FROM direct_messages
+                 WHERE friend_number = ?1
+                   AND (?2 IS NULL OR timestamp < ?2 OR (timestamp = ?2 AND id < ?3))
+                 ORDER BY timestamp DESC, id DESC LIMIT ?4",
+            )
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+
+        // One extra row tells us whether there are more
+        let mut messages = stmt
+            .query_map(
+                rusqlite::params![
+                    friend_number,
+                    before.map(|c| &c.timestamp),
+                    before.map(|c| &c.id),
+                    limit + 1,
+                ],
+                |row| {
+                    Ok(DirectMessageRecord {
+                        id: row.get(0)?,
+                        friend_number: row.get(1)?,
+                        sender: row.get(2)?,
+                        content: row.get(3)?,
+                        message_type: row.get(4)?,
+                        timestamp: row.get(5)?,
+                        is_outgoing: row.get(6)?,
+                        delivered: row.get(7)?,
+                        read: row.get(8)?,
+                        expires_at: row.get(9)?,
+                    })
+                },
+            )
+            .map_err(|e| format!("Failed to query messages: {e}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect messages: {e}"))?;
+        let has_more = messages.len() as i64 > limit;
+        messages.truncate(limit.max(0) as usize);
+
+        let total = conn
+            .query_row(
+                "SELECT COUNT(*) FROM direct_messages WHERE friend_number = ?1",
+                rusqlite::params![friend_number],
+                |row| row.get(0),
+            )
+            .map_err(|e| format!("Failed to count messages: {e}"))?;
+
+        Ok(MessagePage {
+            messages,
+            has_more,
+            total,
+        })
+    }
+
+    /// Up to `context` direct messages either side of `anchor`, for jumping to a
+    /// message or a date. `None` if the anchor is a message we don't have.
+    pub fn get_direct_messages_around(
+        &self,
+        friend_number: u32,
+        anchor: &MessageAnchor,
+        context: i64,
+    ) -> Result<Option<MessageWindow<DirectMessageRecord>>, String> {
+        let conn = self.read_messages()?;
+        messages_around(
+            &conn,
+            "SELECT id, friend_number, sender, content, message_type, timestamp, is_outgoing, delivered, read, expires_at
+             FROM direct_messages WHERE friend_number = ?1",
+            &(friend_number as i64),
+            anchor,
+            context,
+            |row| {
+                Ok(DirectMessageRecord {
+                    id: row.get(0)?,
+                    friend_number: row.get(1)?,
+                    sender: row.get(2)?,
+                    content: row.get(3)?,
+                    message_type: row.get(4)?,
+                    timestamp: row.get(5)?,
+                    is_outgoing: row.get(6)?,
+                    delivered: row.get(7)?,
+                    read: row.get(8)?,
+                    expires_at: row.get(9)?,
+                })
+            },
+            |m| (&m.timestamp, &m.id),
+        )
+    }
+
+    /// Insert a message unless one with the same id exists. Returns true if inserted.
+    pub fn insert_direct_message_if_missing(&self, msg: &DirectMessageRecord) -> Result<bool, String> {
+        let conn = self.write_messages()?;
+        let inserted = conn
+            .execute(
+                "INSERT OR IGNORE INTO direct_messages (id, friend_number, sender, content, message_type, timestamp, is_outgoing, delivered, read, plain_content, expires_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+                rusqlite::params![
+                    msg.id,
+                    msg.friend_number,
+                    msg.sender,
+                    msg.content,
+                    msg.message_type,
+                    msg.timestamp,
+                    msg.is_outgoing,
+                    msg.delivered,
+                    msg.read,
+                    markdown::to_plain_text(&msg.content),
+                    msg.expires_at,
+                ],
+            )
+            .map_err(|e| format!("Failed to insert message: {e}"))?;
+        Ok(inserted > 0)
+    }
+
+    /// Direct messages newer than `since`, oldest first, with the friend's public key.
+    /// Conversations with linked devices are left out.
+    pub fn get_direct_messages_since(
+        &self,
+        since: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<(String, DirectMessageRecord)>, String> {
+        let conn = self.read_messages()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT f.public_key, m.id, m.friend_number, m.sender, m.content, m.message_type,
+                        m.timestamp, m.is_outgoing, m.delivered, m.read, m.expires_at
+                 FROM direct_messages m
+                 JOIN friends f ON f.friend_number = m.friend_number
+                 WHERE (?1 IS NULL OR m.timestamp > ?1)
+                   AND m.friend_number NOT IN (SELECT friend_number FROM linked_devices)
+                 ORDER BY m.timestamp ASC LIMIT ?2",
+            )
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+
+        let messages = stmt
+            .query_map(rusqlite::params![since, limit], |row| {
+                Ok((
+                    row.get(0)?,
+                    DirectMessageRecord {
+                        id: row.get(1)?,
+                        friend_number: row.get(2)?,
+                        sender: row.get(3)?,
+                        content: row.get(4)?,
+                        message_type: row.get(5)?,
+                        timestamp: row.get(6)?,
+                        is_outgoing: row.get(7)?,
+                        delivered: row.get(8)?,
+                        read: row.get(9)?,
+                        expires_at: row.get(10)?,
+                    },
+                ))
+            })
+            .map_err(|e| format!("Failed to query messages: {e}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect messages: {e}"))?;
+
+        Ok(messages)
+    }
+
+    /// Direct messages with a friend still stored as encrypted envelopes, as (ID, content)
+    pub fn get_encrypted_direct_messages(&self, friend_number: u32, prefix: &str) -> Result<Vec<(String, String)>, String> {
+        let conn = self.read_messages()?;
+        let mut stmt = conn
+            .prepare("SELECT id, content FROM direct_messages WHERE friend_number = ?1 AND substr(content, 1, length(?2)) = ?2")
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+
+        let messages = stmt
+            .query_map(rusqlite::params![friend_number, prefix], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(|e| format!("Failed to query messages: {e}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect messages: {e}"))?;
+
+        Ok(messages)
+    }
+
+    pub fn set_direct_message_content(&self, message_id: &str, content: &str) -> Result<(), String> {
+        let conn = self.write_messages()?;
+        conn.execute(
+            "UPDATE direct_messages SET content = ?2 WHERE id = ?1",
+            rusqlite::params![message_id, content],
+        )
+        .map_err(|e| format!("Failed to update message: {e}"))?;
+        Ok(())
+    }
+
+    /// Tag a direct message as sent during a call
+    pub fn set_direct_message_call_session(&self, message_id: &str, session_id: &str) -> Result<(), String> {
+        let conn = self.write_messages()?;
+        conn.execute(
+            "UPDATE direct_messages SET call_session_id = ?2 WHERE id = ?1",
+            rusqlite::params![message_id, session_id],
+        )
+        .map_err(|e| format!("Failed to tag message with call: {e}"))?;
+        Ok(())
+    }
+
+    /// Direct messages sent during a call, oldest first
+    pub fn get_call_messages(&self, session_id: &str) -> Result<Vec<DirectMessageRecord>, String> {
+        let conn = self.read_messages()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, friend_number, sender, content, message_type, timestamp, is_outgoing, delivered, read, expires_at
+                 FROM direct_messages
+                 WHERE call_session_id = ?1
+                 ORDER BY timestamp ASC, id ASC",
+            )
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+        let messages = stmt
+            .query_map([session_id], |row| {
+                Ok(DirectMessageRecord {
+                    id: row.get(0)?,
+                    friend_number: row.get(1)?,
+                    sender: row.get(2)?,
+                    content: row.get(3)?,
+                    message_type: row.get(4)?,
+                    timestamp: row.get(5)?,
+                    is_outgoing: row.get(6)?,
+                    delivered: row.get(7)?,
+                    read: row.get(8)?,
+                    expires_at: row.get(9)?,
+                })
+            })
+            .map_err(|e| format!("Failed to query call messages: {e}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect call messages: {e}"))?;
+        Ok(messages)
+    }
+
+    // ─── Conversation Locks ───────────────────────────────────────────
+
+    /// Whether a friend's conversation is locked, and if so whether decrypted
+    /// messages are stored
+    pub fn get_conversation_lock(&self, friend_number: u32) -> Result<Option<bool>, String> {
+        let conn = self.read()?;
+        let mut stmt = conn
+            .prepare("SELECT store_plaintext FROM conversation_locks WHERE friend_number = ?1")
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+
+        let mut rows = stmt
+            .query_map(rusqlite::params![friend_number], |row| row.get(0))
+            .map_err(|e| format!("Failed to query conversation lock: {e}"))?;
+
+        match rows.next() {
+            Some(Ok(store_plaintext)) => Ok(Some(store_plaintext)),
+            Some(Err(e)) => Err(format!("Failed to read conversation lock: {e}")),
+            None => Ok(None),
+        }
+    }
+
+    /// Every locked conversation as (friend number, store plaintext)
+    pub fn get_conversation_locks(&self) -> Result<Vec<(u32, bool)>, String> {
+        let conn = self.read()?;
+        let mut stmt = conn
+            .prepare("SELECT friend_number, store_plaintext FROM conversation_locks")
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+
+        let locks = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(|e| format!("Failed to query conversation locks: {e}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect conversation locks: {e}"))?;
+        Ok(locks)
+    }
+
+    pub fn set_conversation_lock(&self, friend_number: u32, store_plaintext: bool) -> Result<(), String> {
+        let conn = self.write()?;
+        conn.execute(
+            "INSERT INTO conversation_locks (friend_number, store_plaintext) VALUES (?1, ?2)
+             ON CONFLICT(friend_number) DO UPDATE SET store_plaintext = excluded.store_plaintext",
+            rusqlite::params![friend_number, store_plaintext],
+        )
+        .map_err(|e| format!("Failed to save conversation lock: {e}"))?;
+        Ok(())
+    }
+
+    pub fn remove_conversation_lock(&self, friend_number: u32) -> Result<(), String> {
+        let conn = self.write()?;
+        conn.execute(
+            "DELETE FROM conversation_locks WHERE friend_number = ?1",
+            rusqlite::params![friend_number],
+        )
+        .map_err(|e| format!("Failed to remove conversation lock: {e}"))?;
+        Ok(())
+    }
+
+    // ─── Disappearing Messages ────────────────────────────────────────
+
+    /// A friend conversation's disappearing timer as (seconds, set at), if one was ever set
+    pub fn get_disappearing_timer(&self, friend_number: u32) -> Result<Option<(u32, i64)>, String> {
+        let conn = self.read()?;
+        let mut stmt = conn
+            .prepare("SELECT seconds, set_at FROM disappearing_timers WHERE friend_number = ?1")
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+
+        let mut rows = stmt
+            .query_map(rusqlite::params![friend_number], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(|e| format!("Failed to query disappearing timer: {e}"))?;
+
+        match rows.next() {
+            Some(Ok(timer)) => Ok(Some(timer)),
+            Some(Err(e)) => Err(format!("Failed to read disappearing timer: {e}")),
+            None => Ok(None),
+        }
+    }
+
+    /// Set a conversation's timer unless a newer one is saved. Returns true if it was set.
+    pub fn set_disappearing_timer(&self, friend_number: u32, seconds: u32, set_at: i64) -> Result<bool, String> {
+        let conn = self.write()?;
+        let changed = conn
+            .execute(
+                "INSERT INTO disappearing_timers (friend_number, seconds, set_at) VALUES (?1, ?2, ?3)
+                 ON CONFLICT(friend_number) DO UPDATE SET seconds = excluded.seconds, set_at = excluded.set_at
+                 WHERE excluded.set_at > disappearing_timers.set_at",
+                rusqlite::params![friend_number, seconds, set_at],
+            )
+            .map_err(|e| format!("Failed to save disappearing timer: {e}"))?;
+        Ok(changed > 0)
+    }
+
+    /// When a message sent to or received from a friend now should expire
+    pub fn disappearing_expiry(&self, friend_number: u32) -> Result<Option<i64>, String> {
+        let seconds = self.get_disappearing_timer(friend_number)?.map_or(0, |(seconds, _)| seconds);
+        Ok(disappearing::expires_at(chrono::Utc::now().timestamp(), seconds))
+    }
+
+    /// Delete direct messages that expired by `now` (Unix seconds). The FTS
+    /// triggers drop them from search. Returns (friend number, message ID) of each.
+    /// Queued messages aren't waited for; a later sweep gets them once written.
+    pub fn delete_expired_direct_messages(&self, now: i64) -> Result<Vec<(u32, String)>, String> {
+        let conn = self.write()?;
+        let mut stmt = conn
+            .prepare(
+                "DELETE FROM direct_messages WHERE expires_at IS NOT NULL AND expires_at <= ?1
+                 RETURNING friend_number, id",
+            )
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+
+        let expired = stmt
+            .query_map(rusqlite::params![now], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(|e| format!("Failed to delete expired messages: {e}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to delete expired messages: {e}"))?;
+        Ok(expired)
+    }
+
+    pub fn mark_message_delivered(&self, message_id: &str) -> Result<(), String> {
+        let conn = self.write_messages()?;
+        conn.execute(
+            "UPDATE direct_messages SET delivered = 1 WHERE id = ?1",
+            rusqlite::params![message_id],
+        )
+        .map_err(|e| format!("Failed to mark delivered: {e}"))?;
+        Ok(())
+    }
+
+    pub fn mark_messages_read(&self, friend_number: u32) -> Result<(), String> {
+        let conn = self.write_messages()?;
+        conn.execute(
+            "UPDATE direct_messages SET read = 1
+             WHERE friend_number = ?1 AND is_outgoing = 0 AND read = 0",
+            rusqlite::params![friend_number],
+        )
+        .map_err(|e| format!("Failed to mark messages read: {e}"))?;
+        Ok(())
+    }
+
+    pub fn get_unread_counts(&self) -> Result<Vec<(i64, i64)>, String> {
+        let conn = self.read_messages()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT friend_number, COUNT(*) FROM direct_messages
+                 WHERE is_outgoing = 0 AND read = 0
+                 GROUP BY friend_number",
+            )
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+
+        let counts = stmt
+            .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?)))
+            .map_err(|e| format!("Failed to query unread counts: {e}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect unread counts: {e}"))?;
+
+        Ok(counts)
+    }
+
+    // ─── Missed Calls ──────────────────────────────────────────────────
+
+    pub fn insert_missed_call(&self, call: &MissedCallRecord) -> Result<(), String> {
+        let conn = self.write()?;
+        conn.execute(
+            "INSERT INTO missed_calls (id, friend_number, timestamp, with_video, reason, seen)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            rusqlite::params![call.id, call.friend_number, call.timestamp, call.with_video, call.reason, call.seen],
+        )
+        .map_err(|e| format!("Failed to record missed call: {e}"))?;
+        Ok(())
+    }
+
+    /// Missed calls, newest first, from one friend or everyone
+    pub fn get_missed_calls(&self, friend_number: Option<u32>, limit: i64) -> Result<Vec<MissedCallRecord>, String> {
+        let conn = self.read()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, friend_number, timestamp, with_video, reason, seen FROM missed_calls
+                 WHERE ?1 IS NULL OR friend_number = ?1
+                 ORDER BY timestamp DESC LIMIT ?2",
+            )
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+        let calls = stmt
+            .query_map(rusqlite::params![friend_number.map(|f| f as i64), limit], |row| {
+                Ok(MissedCallRecord {
+                    id: row.get(0)?,
+                    friend_number: row.get(1)?,
+                    timestamp: row.get(2)?,
+                    with_video: row.get(3)?,
+                    reason: row.get(4)?,
+                    seen: row.get(5)?,
+                })
+            })
+            .map_err(|e| format!("Failed to query missed calls: {e}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect missed calls: {e}"))?;
+        Ok(calls)
+    }
+
+    /// Unseen missed calls per friend, as (friend number, count)
+    pub fn get_unseen_missed_call_counts(&self) -> Result<Vec<(i64, i64)>, String> {
+        let conn = self.read()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT friend_number, COUNT(*) FROM missed_calls
+                 WHERE seen = 0
+                 GROUP BY friend_number",
+            )
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+        let counts = stmt
+            .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?)))
+            .map_err(|e| format!("Failed to query missed call counts: {e}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect missed call counts: {e}"))?;
+        Ok(counts)
+    }
+
+    pub fn mark_missed_calls_seen(&self, friend_number: u32) -> Result<(), String> {
+        let conn = self.write()?;
+        conn.execute(
+            "UPDATE missed_calls SET seen = 1 WHERE friend_number = ?1 AND seen = 0",
+            rusqlite::params![friend_number],
+        )
+        .map_err(|e| format!("Failed to mark missed calls seen: {e}"))?;
+        Ok(())
+    }
+
+    // ─── Group Invites ─────────────────────────────────────────────────
+
+    /// Save an invite, returning its id. A friend sending the same invite
+    /// again keeps the id it was first stored under.
+    pub fn insert_group_invite(&self, invite: &GroupInviteRecord) -> Result<String, String> {
+        let conn = self.write()?;
+        conn.execute(
+            "INSERT INTO group_invites (id, friend_number, group_name, invite_data, received_at, password_required)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+             ON CONFLICT(friend_number, invite_data) DO UPDATE SET
+                 group_name = excluded.group_name, received_at = excluded.received_at,
+                 password_required = excluded.password_required",
+            rusqlite::params![
+                invite.id,
+                invite.friend_number,
+                invite.group_name,
+                invite.invite_data,
+                invite.received_at,
+                invite.password_required
+            ],
+        )
+        .map_err(|e| format!("Failed to save group invite: {e}"))?;
+        conn.query_row(
+            "SELECT id FROM group_invites WHERE friend_number = ?1 AND invite_data = ?2",
+            rusqlite::params![invite.friend_number, invite.invite_data],
+            |row| row.get(0),
+        )
+        .map_err(|e| format!("Failed to read group invite: {e}"))
+    }
+
+    /// Pending invites, oldest first
+    pub fn get_group_invites(&self) -> Result<Vec<GroupInviteRecord>, String> {
+        let conn = self.read()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, friend_number, group_name, invite_data, received_at, password_required FROM group_invites
+                 ORDER BY received_at",
+            )
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+        let invites = stmt
+            .query_map([], Self::map_group_invite)
+            .map_err(|e| format!("Failed to query group invites: {e}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect group invites: {e}"))?;
+        Ok(invites)
+    }
+
+    pub fn get_group_invite(&self, id: &str) -> Result<Option<GroupInviteRecord>, String> {
+        let conn = self.read()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, friend_number, group_name, invite_data, received_at, password_required FROM group_invites
+                 WHERE id = ?1",
+            )
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+
+        let mut rows = stmt
+            .query_map(rusqlite::params![id], Self::map_group_invite)
+            .map_err(|e| format!("Failed to query group invite: {e}"))?;
+
+        match rows.next() {
+            Some(Ok(invite)) => Ok(Some(invite)),
+            Some(Err(e)) => Err(format!("Failed to read group invite: {e}")),
+            None => Ok(None),
+        }
+    }
+
+    /// Forget an invite once it's been accepted or declined
+    pub fn remove_group_invite(&self, id: &str) -> Result<(), String> {
+        let conn = self.write()?;
+        conn.execute("DELETE FROM group_invites WHERE id = ?1", rusqlite::params![id])
+            .map_err(|e| format!("Failed to remove group invite: {e}"))?;
+        Ok(())
+    }
+
+    fn map_group_invite(row: &rusqlite::Row) -> rusqlite::Result<GroupInviteRecord> {
+        Ok(GroupInviteRecord {
+            id: row.get(0)?,
+            friend_number: row.get(1)?,
+            group_name: row.get(2)?,
+            invite_data: row.get(3)?,
+            received_at: row.get(4)?,
+            password_required: row.get(5)?,
+        })
+    }
+
+    // ─── Call Preferences ──────────────────────────────────────────────
+
+    /// A friend's call preferences, if any were set
+    pub fn get_call_preferences(&self, friend_number: u32) -> Result<Option<CallPreferences>, String> {
+        let conn = self.read()?;
+        let mut stmt = conn
+            .prepare("SELECT audio_bit_rate, video_bit_rate, video_by_default FROM call_preferences WHERE friend_number = ?1")
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+
+        let mut rows = stmt
+            .query_map(rusqlite::params![friend_number], |row| {
+                Ok(CallPreferences {
+                    audio_bit_rate: row.get(0)?,
+                    video_bit_rate: row.get(1)?,
+                    video_by_default: row.get(2)?,
+                })
+            })
+            .map_err(|e| format!("Failed to query call preferences: {e}"))?;
+
+        match rows.next() {
+            Some(Ok(prefs)) => Ok(Some(prefs)),
+            Some(Err(e)) => Err(format!("Failed to read call preferences: {e}")),
+            None => Ok(None),
+        }
+    }
+
+    pub fn set_call_preferences(&self, friend_number: u32, prefs: &CallPreferences) -> Result<(), String> {
+        let conn = self.write()?;
+        conn.execute(
+            "INSERT INTO call_preferences (friend_number, audio_bit_rate, video_bit_rate, video_by_default)
+             VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(friend_number) DO UPDATE SET audio_bit_rate = excluded.audio_bit_rate,
+                 video_bit_rate = excluded.video_bit_rate, video_by_default = excluded.video_by_default",
+            rusqlite::params![friend_number, prefs.audio_bit_rate, prefs.video_bit_rate, prefs.video_by_default],
+        )
+        .map_err(|e| format!("Failed to save call preferences: {e}"))?;
+        Ok(())
+    }
+
+    /// Go back to the defaults for a friend
+    pub fn clear_call_preferences(&self, friend_number: u32) -> Result<(), String> {
+        let conn = self.write()?;
+        conn.execute(
+            "DELETE FROM call_preferences WHERE friend_number = ?1",
+            rusqlite::params![friend_number],
+        )
+        .map_err(|e| format!("Failed to clear call preferences: {e}"))?;
+        Ok(())
+    }
+
+    // ─── File Transfers ────────────────────────────────────────────────
+
+    pub fn insert_file_transfer(&self, transfer: &FileTransferRecord) -> Result<(), String> {
+        let conn = self.write()?;
+        conn.execute(
+            "INSERT INTO file_transfers (id, friend_number, file_number, filename, file_size, file_path, direction, status, bytes_transferred, message_id, file_id)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            rusqlite::params![
+                transfer.id,
+                transfer.friend_number,
+                transfer.file_number,
+                transfer.filename,
+                transfer.file_size,
+                transfer.file_path,
+                transfer.direction,
+                transfer.status,
+                transfer.bytes_transferred,
+                transfer.message_id,
+                transfer.file_id,
+            ],
+        )
+        .map_err(|e| format!("Failed to insert file transfer: {e}"))?;
+        Ok(())
+    }
+
+    pub fn update_file_transfer_status(
+        &self,
+        id: &str,
+        status: &str,
+        bytes_transferred: i64,
+    ) -> Result<(), String> {
+        let conn = self.write()?;
+        conn.execute(
+            "UPDATE file_transfers SET status = ?2, bytes_transferred = ?3,
+                 completed_at = CASE WHEN ?2 = 'completed' THEN datetime('now') ELSE completed_at END
+             WHERE id = ?1",
+            rusqlite::params![id, status, bytes_transferred],
+        )
+        .map_err(|e| format!("Failed to update file transfer: {e}"))?;
+        Ok(())
+    }
+
+    /// Point a resumed transfer at its new Tox file number
+    pub fn resume_file_transfer(&self, id: &str, file_number: u32) -> Result<(), String> {
+        let conn = self.write()?;
+        conn.execute(
+            "UPDATE file_transfers SET file_number = ?2, status = 'active' WHERE id = ?1",
+            rusqlite::params![id, file_number],
+        )
+        .map_err(|e| format!("Failed to resume file transfer: {e}"))?;
+        Ok(())
+    }
+
+    /// Transfers with a friend that were interrupted before completing
+    pub fn get_resumable_transfers(
+        &self,
+        friend_number: u32,
+        direction: &str,
+    ) -> Result<Vec<FileTransferRecord>, String> {
+        let conn = self.read()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, friend_number, file_number, filename, file_size, file_path, direction, status, bytes_transferred, message_id, file_id
+                 FROM file_transfers
+                 WHERE friend_number = ?1 AND direction = ?2
+                   AND status IN ('pending', 'active', 'paused') AND file_id IS NOT NULL
+                 ORDER BY started_at",
+            )
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+
+        let transfers = stmt
+            .query_map(rusqlite::params![friend_number, direction], |row| {
+                Ok(FileTransferRecord {
+                    id: row.get(0)?,
+                    friend_number: row.get(1)?,
+                    file_number: row.get(2)?,
+                    filename: row.get(3)?,
+                    file_size: row.get(4)?,
+                    file_path: row.get(5)?,
+                    direction: row.get(6)?,
+                    status: row.get(7)?,
+                    bytes_transferred: row.get(8)?,
+                    message_id: row.get(9)?,
+                    file_id: row.get(10)?,
+                })
+            })
+            .map_err(|e| format!("Failed to query file transfers: {e}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect file transfers: {e}"))?;
+
+        Ok(transfers)
+    }
+
+    // ─── Attachments ───────────────────────────────────────────────────
+
+    pub fn insert_attachment(&self, attachment: &AttachmentRecord) -> Result<(), String> {
+        let conn = self.write_messages()?;
+        conn.execute(
+            "INSERT INTO attachments (id, message_id, transfer_id, filename, mime_type, file_size, file_path, thumbnail_path, width, height, sha256, duration_ms, waveform)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+            rusqlite::params![
+                attachment.id,
+                attachment.message_id,
+                attachment.transfer_id,
+                attachment.filename,
+                attachment.mime_type,
+                attachment.file_size,
+                attachment.file_path,
+                attachment.thumbnail_path,
+                attachment.width,
+                attachment.height,
+                attachment.sha256,
+                attachment.duration_ms,
+                attachment.waveform,
+            ],
+        )
+        .map_err(|e| format!("Failed to insert attachment: {e}"))?;
+        Ok(())
+    }
+
+    pub fn get_attachment(&self, id: &str) -> Result<Option<AttachmentRecord>, String> {
+        let conn = self.read_messages()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, message_id, transfer_id, filename, mime_type, file_size, file_path, thumbnail_path, width, height, created_at, sha256, duration_ms, waveform
+                 FROM attachments WHERE id = ?1",
+            )
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+
+        let mut rows = stmt
+            .query_map(rusqlite::params![id], Self::map_attachment)
+            .map_err(|e| format!("Failed to query attachment: {e}"))?;
+
+        match rows.next() {
+            Some(row) => Ok(Some(row.map_err(|e| format!("Failed to read attachment: {e}"))?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Find a stored copy of a shared file by its hash
+    pub fn get_attachment_by_hash(&self, sha256: &str) -> Result<Option<AttachmentRecord>, String> {
+        let conn = self.read_messages()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, message_id, transfer_id, filename, mime_type, file_size, file_path, thumbnail_path, width, height, created_at, sha256, duration_ms, waveform
+                 FROM attachments WHERE sha256 = ?1 LIMIT 1",
+            )
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+
+        let mut rows = stmt
+            .query_map(rusqlite::params![sha256], Self::map_attachment)
+            .map_err(|e| format!("Failed to query attachment: {e}"))?;
+
+        match rows.next() {
+            Some(row) => Ok(Some(row.map_err(|e| format!("Failed to read attachment: {e}"))?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Point an attachment at a regenerated thumbnail
+    pub fn set_attachment_thumbnail(&self, id: &str, thumbnail_path: &str) -> Result<(), String> {
+        let conn = self.write_messages()?;
+        conn.execute(
+            "UPDATE attachments SET thumbnail_path = ?2 WHERE id = ?1",
+            rusqlite::params![id, thumbnail_path],
+        )
+        .map_err(|e| format!("Failed to update attachment: {e}"))?;
+        Ok(())
+    }
+
+    /// Rewrite stored file paths starting with `old_prefix` to start with
+    /// `new_prefix`, after the profile's directory moved. Returns the rows changed.
+    pub fn relocate_paths(&self, old_prefix: &str, new_prefix: &str) -> Result<usize, String> {
+        let conn = self.write_messages()?;
+        let mut changed = 0;
+        for (table, column) in [("attachments", "file_path"), ("attachments", "thumbnail_path"), ("file_transfers", "file_path")] {
+            changed += conn
+                .execute(
+                    &format!(
+                        "UPDATE {table} SET {column} = ?2 || substr({column}, length(?1) + 1)
+                         WHERE substr({column}, 1, length(?1)) = ?1"
+                    ),
+                    rusqlite::params![old_prefix, new_prefix],
+                )
+                .map_err(|e| format!("Failed to update file paths: {e}"))?;
+        }
+        Ok(changed)
+    }
+
+    pub fn get_attachments_for_message(&self, message_id: &str) -> Result<Vec<AttachmentRecord>, String> {
+        let conn = self.read_messages()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, message_id, transfer_id, filename, mime_type, file_size, file_path, thumbnail_path, width, height, created_at, sha256, duration_ms, waveform
+                 FROM attachments WHERE message_id = ?1 ORDER BY created_at",
+            )
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+
+        let attachments = stmt
+            .query_map(rusqlite::params![message_id], Self::map_attachment)
+            .map_err(|e| format!("Failed to query attachments: {e}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect attachments: {e}"))?;
+
+        Ok(attachments)
+    }
+
+    fn map_attachment(row: &rusqlite::Row) -> rusqlite::Result<AttachmentRecord> {
+        Ok(AttachmentRecord {
+            id: row.get(0)?,
+            message_id: row.get(1)?,
+            transfer_id: row.get(2)?,
+            filename: row.get(3)?,
+            mime_type: row.get(4)?,
+            file_size: row.get(5)?,
+            file_path: row.get(6)?,
+            thumbnail_path: row.get(7)?,
+            width: row.get(8)?,
+            height: row.get(9)?,
+            created_at: row.get(10)?,
+            sha256: row.get(11)?,
+            duration_ms: row.get(12)?,
+            waveform: row.get(13)?,
+        })
+    }
+
+    // ─── Assets ────────────────────────────────────────────────────────
+
+    /// Record content in the media cache as just used. Content that's also a
+    /// sticker stays one.
+    pub fn record_asset(&self, hash: &str, kind: &str, size: i64) -> Result<(), String> {
+        let conn = self.write()?;
+        conn.execute(
+            "INSERT INTO assets (hash, kind, size, last_used_at) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(hash) DO UPDATE SET last_used_at = excluded.last_used_at,
+                 kind = CASE WHEN excluded.kind = 'sticker' THEN 'sticker' ELSE assets.kind END",
+            rusqlite::params![hash, kind, size, chrono::Utc::now().timestamp_millis()],
+        )
+        .map_err(|e| format!("Failed to record asset: {e}"))?;
+        Ok(())
+    }
+
+    /// Mark cached content as used. False if it isn't recorded.
+    pub fn touch_asset(&self, hash: &str) -> Result<bool, String> {
+        let conn = self.write()?;
+        let updated = conn
+            .execute(
+                "UPDATE assets SET last_used_at = ?2 WHERE hash = ?1",
+                rusqlite::params![hash, chrono::Utc::now().timestamp_millis()],
+            )
+            .map_err(|e| format!("Failed to update asset: {e}"))?;
+        Ok(updated > 0)
+    }
+
+    pub fn delete_asset(&self, hash: &str) -> Result<(), String> {
+        let conn = self.write()?;
+        conn.execute("DELETE FROM assets WHERE hash = ?1", rusqlite::params![hash])
+            .map_err(|e| format!("Failed to delete asset: {e}"))?;
+        Ok(())
+    }
+
+    /// Number and total size of cached assets
+    pub fn asset_usage(&self) -> Result<(i64, i64), String> {
+        let conn = self.read()?;
+        conn.query_row("SELECT COUNT(*), COALESCE(SUM(size), 0) FROM assets", [], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })
+        .map_err(|e| format!("Failed to query assets: {e}"))
+    }
+
+    /// Hash and size of every cached asset but stickers, least recently used first
+    pub fn least_recently_used_assets(&self) -> Result<Vec<(String, i64)>, String> {
+        let conn = self.read()?;
+        let mut stmt = conn
+            .prepare("SELECT hash, size FROM assets WHERE kind != 'sticker' ORDER BY last_used_at ASC")
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+
+        let assets = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(|e| format!("Failed to query assets: {e}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect assets: {e}"))?;
+
+        Ok(assets)
+    }
+
+    /// Forget every cached asset but stickers, returning their hashes
+    pub fn delete_all_assets(&self) -> Result<Vec<String>, String> {
+        let conn = self.write()?;
+        let hashes = {
+            let mut stmt = conn
+                .prepare("SELECT hash FROM assets WHERE kind != 'sticker'")
+                .map_err(|e| format!("Failed to prepare query: {e}"))?;
+            let hashes = stmt
+                .query_map([], |row| row.get(0))
+                .map_err(|e| format!("Failed to query assets: {e}"))?
+                .collect::<Result<Vec<String>, _>>()
+                .map_err(|e| format!("Failed to collect assets: {e}"))?;
+            hashes
+        };
+        conn.execute("DELETE FROM assets WHERE kind != 'sticker'", [])
+            .map_err(|e| format!("Failed to delete assets: {e}"))?;
+        Ok(hashes)
+    }
+
+    // ─── Sticker Packs ─────────────────────────────────────────────────
+
+    /// Install a pack. Returns false if it was already installed.
+    pub fn insert_sticker_pack(&self, pack: &StickerPack) -> Result<bool, String> {
+        let stickers = serde_json::to_string(&pack.stickers).map_err(|e| format!("Failed to serialize stickers: {e}"))?;
+        let conn = self.write()?;
+        let inserted = conn
+            .execute(
+                "INSERT OR IGNORE INTO sticker_packs (hash, name, stickers) VALUES (?1, ?2, ?3)",
+                rusqlite::params![pack.hash(), pack.name, stickers],
+            )
+            .map_err(|e| format!("Failed to save sticker pack: {e}"))?;
+        Ok(inserted > 0)
+    }
+
+    /// Installed packs, oldest first
+    pub fn get_sticker_packs(&self) -> Result<Vec<StickerPackRecord>, String> {
+        let conn = self.read()?;
+        let mut stmt = conn
+            .prepare("SELECT hash, name, stickers, added_at FROM sticker_packs ORDER BY added_at, name")
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+
+        let packs = stmt
+            .query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get(1)?, row.get::<_, String>(2)?, row.get(3)?))
+            })
+            .map_err(|e| format!("Failed to query sticker packs: {e}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect sticker packs: {e}"))?;
+
+        packs
+            .into_iter()
+            .map(|(hash, name, stickers, added_at)| {
+                let stickers = serde_json::from_str(&stickers)
+                    .map_err(|e| format!("Failed to parse stickers of pack {hash}: {e}"))?;
+                Ok(StickerPackRecord { hash, name, stickers, added_at })
+            })
+            .collect()
+    }
+
+    pub fn get_sticker_pack(&self, hash: &str) -> Result<Option<StickerPackRecord>, String> {
+        Ok(self.get_sticker_packs()?.into_iter().find(|p| p.hash.eq_ignore_ascii_case(hash)))
+    }
+
+    /// Remove a pack and who we know has it. Returns false if it wasn't installed.
+    pub fn delete_sticker_pack(&self, hash: &str) -> Result<bool, String> {
+        let mut conn = self.write()?;
+        let tx = conn
+            .transaction()
+            .map_err(|e| format!("Failed to start transaction: {e}"))?;
+        let deleted = tx
+            .execute("DELETE FROM sticker_packs WHERE hash = ?1", rusqlite::params![hash])
+            .map_err(|e| format!("Failed to delete sticker pack: {e}"))?;
+        tx.execute("DELETE FROM sticker_pack_holders WHERE pack_hash = ?1", rusqlite::params![hash])
+            .map_err(|e| format!("Failed to delete sticker pack holders: {e}"))?;
+        tx.commit()
+            .map_err(|e| format!("Failed to commit transaction: {e}"))?;
+        Ok(deleted > 0)
+    }
+
+    /// Note that a peer has a pack
+    pub fn add_sticker_pack_holder(&self, pack_hash: &str, public_key: &str) -> Result<(), String> {
+        let conn = self.write()?;
+        conn.execute(
+            "INSERT OR IGNORE INTO sticker_pack_holders (pack_hash, public_key) VALUES (?1, ?2)",
+            rusqlite::params![pack_hash.to_lowercase(), public_key.to_uppercase()],
+        )
+        .map_err(|e| format!("Failed to save sticker pack holder: {e}"))?;
+        Ok(())
+    }
+
+    /// Public keys (uppercase) of the peers known to have a pack
+    pub fn get_sticker_pack_holders(&self, pack_hash: &str) -> Result<Vec<String>, String> {
+        let conn = self.read()?;
+        let mut stmt = conn
+            .prepare("SELECT public_key FROM sticker_pack_holders WHERE pack_hash = ?1")
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+
+        let holders = stmt
+            .query_map(rusqlite::params![pack_hash.to_lowercase()], |row| row.get(0))
+            .map_err(|e| format!("Failed to query sticker pack holders: {e}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect sticker pack holders: {e}"))?;
+
+        Ok(holders)
+    }
+
+    // ─── Search ────────────────────────────────────────────────────────
+
+    pub fn search_messages(&self, query: &str, limit: i64) -> Result<Vec<(String, String)>, String> {
+        let conn = self.read_messages()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT message_id, source_table FROM messages_fts
+                 WHERE content MATCH ?1 ORDER BY rank LIMIT ?2",
+            )
+            .map_err(|e| format!("Failed to prepare search: {e}"))?;
+
+        let results = stmt
+            .query_map(rusqlite::params![query, limit], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })
+            .map_err(|e| format!("Failed to search: {e}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect results: {e}"))?;
+
+        Ok(results)
+    }
+
+    // ─── Message Revisions ─────────────────────────────────────────────
+
+    /// Replace a direct or channel message's content with an edit, keeping
+    /// what it said before. Returns false if we don't have the message.
+    pub fn edit_message(&self, message_id: &str, content: &str, edited_at: &str) -> Result<bool, String> {
+        let mut conn = self.write_messages()?;
+        let tx = conn
+            .transaction()
+            .map_err(|e| format!("Failed to begin transaction: {e}"))?;
+
+        for table in ["direct_messages", "channel_messages"] {
+            // (content, indexed text, when the content was written)
+            let current: Option<(String, String, String)> = {
+                let mut stmt = tx
+                    .prepare(&format!(
+                        "SELECT content, COALESCE(plain_content, content), COALESCE(edited_at, timestamp)
+                         FROM {table} WHERE id = ?1"
+                    ))
+                    .map_err(|e| format!("Failed to prepare query: {e}"))?;
+                let mut rows = stmt
+                    .query_map(rusqlite::params![message_id], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+                    .map_err(|e| format!("Failed to query message: {e}"))?;
+                rows.next()
+                    .transpose()
+                    .map_err(|e| format!("Failed to read message: {e}"))?
+            };
+            let Some((old_content, old_indexed, written_at)) = current else {
+                continue;
+            };
+            if old_content == content {
+                return Ok(true);
+            }
+
+            let plain = markdown::to_plain_text(content);
+            tx.execute(
+                "INSERT INTO message_revisions (message_id, content, written_at) VALUES (?1, ?2, ?3)",
+                rusqlite::params![message_id, old_content, written_at],
+            )
+            .map_err(|e| format!("Failed to save message revision: {e}"))?;
+            tx.execute(
+                &format!("UPDATE {table} SET content = ?2, plain_content = ?3, edited_at = ?4 WHERE id = ?1"),
+                rusqlite::params![message_id, content, plain, edited_at],
+            )
+            .map_err(|e| format!("Failed to edit message: {e}"))?;
+            if table == "channel_messages" {
+                // The signature was for what the message said before
+                tx.execute(
+                    "UPDATE channel_messages SET signed_route = NULL, signature_tag = NULL WHERE id = ?1",
+                    rusqlite::params![message_id],
+                )
+                .map_err(|e| format!("Failed to edit message: {e}"))?;
+            }
+            // The search index isn't kept up to date by triggers on update
+            tx.execute(
+                "INSERT INTO messages_fts(messages_fts, content, message_id, source_table) VALUES ('delete', ?1, ?2, ?3)",
+                rusqlite::params![old_indexed, message_id, table],
+            )
+            .map_err(|e| format!("Failed to update search index: {e}"))?;
+            tx.execute(
+                "INSERT INTO messages_fts(content, message_id, source_table) VALUES (?1, ?2, ?3)",
+                rusqlite::params![plain, message_id, table],
+            )
+            .map_err(|e| format!("Failed to update search index: {e}"))?;
+
+            tx.commit().map_err(|e| format!("Failed to commit edit: {e}"))?;
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    /// Every version of a direct or channel message, or `None` if we don't
+    /// have it. A message that was never edited has one.
+    pub fn get_message_history(&self, message_id: &str) -> Result<Option<MessageHistory>, String> {
+        let conn = self.read_messages()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT content, COALESCE(edited_at, timestamp), friend_number FROM direct_messages WHERE id = ?1
+                 UNION ALL
+                 SELECT content, COALESCE(edited_at, timestamp), NULL FROM channel_messages WHERE id = ?1",
+            )
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+        let mut rows = stmt
+            .query_map(rusqlite::params![message_id], |row| {
+                Ok((
+                    MessageRevisionRecord {
+                        content: row.get(0)?,
+                        written_at: row.get(1)?,
+                    },
+                    row.get::<_, Option<i64>>(2)?,
+                ))
+            })
+            .map_err(|e| format!("Failed to query message: {e}"))?;
+        let Some((current, friend_number)) = rows
+            .next()
+            .transpose()
+            .map_err(|e| format!("Failed to read message: {e}"))?
+        else {
+            return Ok(None);
+        };
+
+        let mut stmt = conn
+            .prepare("SELECT content, written_at FROM message_revisions WHERE message_id = ?1 ORDER BY id")
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+        let mut revisions = stmt
+            .query_map(rusqlite::params![message_id], |row| {
+                Ok(MessageRevisionRecord {
+                    content: row.get(0)?,
+                    written_at: row.get(1)?,
+                })
+            })
+            .map_err(|e| format!("Failed to query message revisions: {e}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect message revisions: {e}"))?;
+        revisions.push(current);
+
+        Ok(Some(MessageHistory { friend_number, revisions }))
+    }
+
+    // ─── Conversations ─────────────────────────────────────────────────
+
+    /// `kind` is "dm" (friend public key), "dm_group" (guild id) or "channel"
+    pub fn star_conversation(&self, kind: &str, target_id: &str) -> Result<(), String> {
+        let conn = self.write()?;
+        conn.execute(
+            "INSERT OR IGNORE INTO starred_conversations (kind, target_id) VALUES (?1, ?2)",
+            rusqlite::params![kind, target_id],
+        )
+        .map_err(|e| format!("Failed to star conversation: {e}"))?;
+        Ok(())
+    }
+
+    pub fn unstar_conversation(&self, kind: &str, target_id: &str) -> Result<(), String> {
+        let conn = self.write()?;
+        conn.execute(
+            "DELETE FROM starred_conversations WHERE kind = ?1 AND target_id = ?2",
+            rusqlite::params![kind, target_id],
+        )
+        .map_err(|e| format!("Failed to unstar conversation: {e}"))?;
+        Ok(())
+    }
+
+    /// A channel's notification level: "all", "mentions" or "nothing". Without
+    /// one set, large guilds get "mentions" and others "all".
+    pub fn get_channel_notification_level(&self, channel_id: &str) -> Result<String, String> {
+        let conn = self.read()?;
+        conn.query_row(
+            "SELECT COALESCE(
+                (SELECT notification_level FROM conversation_settings WHERE kind = 'channel' AND target_id = ?1),
+                CASE WHEN (SELECT COUNT(*) FROM guild_members m JOIN channels c ON c.guild_id = m.guild_id
+                           WHERE c.id = ?1) > ?2
+                     THEN 'mentions' ELSE 'all' END)",
+            rusqlite::params![channel_id, LARGE_GUILD_MEMBERS],
+            |row| row.get(0),
+        )
+        .map_err(|e| format!("Failed to get notification level: {e}"))
+    }
+
+    /// Set a channel's notification level, or go back to the default with `None`
+    pub fn set_channel_notification_level(&self, channel_id: &str, level: Option<&str>) -> Result<(), String> {
+        let conn = self.write()?;
+        match level {
+            Some(level) => conn.execute(
+                "INSERT INTO conversation_settings (kind, target_id, notification_level) VALUES ('channel', ?1, ?2)
+                 ON CONFLICT(kind, target_id) DO UPDATE SET notification_level = ?2",
+                rusqlite::params![channel_id, level],
+            ),
+            None => conn.execute(
+                "UPDATE conversation_settings SET notification_level = NULL WHERE kind = 'channel' AND target_id = ?1",
+                rusqlite::params![channel_id],
+            ),
+        }
+        .map_err(|e| format!("Failed to set notification level: {e}"))?;
+        Ok(())
+    }
+
+    /// DMs, DM groups and guild text channels, most recently active first.
+    /// Conversations without messages come last.
+    pub fn get_recent_conversations(&self, limit: i64) -> Result<Vec<RecentConversationRecord>, String> {
+        let conn = self.read_messages()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT kind, id, name, friend_number, guild_id, guild_name, last_activity, unread_count,
+                        EXISTS (SELECT 1 FROM starred_conversations s WHERE s.kind = c.kind AND s.target_id = c.id)
+                 FROM (
+                    SELECT 'dm' AS kind, f.public_key AS id, f.name AS name, f.friend_number AS friend_number,
+                           NULL AS guild_id, NULL AS guild_name,
+                           (SELECT MAX(timestamp) FROM direct_messages WHERE friend_number = f.friend_number)
+                               AS last_activity,
+                           (SELECT COUNT(*) FROM direct_messages
+                            WHERE friend_number = f.friend_number AND is_outgoing = 0 AND read = 0) AS unread_count
+                    FROM friends f
+                    UNION ALL
+                    SELECT 'dm_group', g.id, g.name, NULL, g.id, NULL,
+                           (SELECT MAX(m.timestamp) FROM channel_messages m
+                            JOIN channels ch ON ch.id = m.channel_id WHERE ch.guild_id = g.id),
+                           (SELECT COUNT(*) FROM channel_messages m
+                            JOIN channels ch ON ch.id = m.channel_id
+                            WHERE ch.guild_id = g.id AND m.notify = 1 AND m.mention_read = 0)
+                    FROM guilds g WHERE g.guild_type = 'dm_group'
+                    UNION ALL
+                    SELECT 'channel', ch.id, ch.name, NULL, g.id, g.name,
+                           (SELECT MAX(timestamp) FROM channel_messages WHERE channel_id = ch.id),
+                           (SELECT COUNT(*) FROM channel_messages
+                            WHERE channel_id = ch.id AND notify = 1 AND mention_read = 0)
+                    FROM channels ch JOIN guilds g ON g.id = ch.guild_id
+                    WHERE g.guild_type = 'server' AND ch.channel_type = 'text'
+                 ) c
+                 ORDER BY last_activity IS NULL, last_activity DESC, name COLLATE NOCASE
+                 LIMIT ?1",
+            )
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+
+        let conversations = stmt
+            .query_map(rusqlite::params![limit], |row| {
+                Ok(RecentConversationRecord {
+                    kind: row.get(0)?,
+                    id: row.get(1)?,
+                    name: row.get(2)?,
+                    friend_number: row.get(3)?,
+                    guild_id: row.get(4)?,
+                    guild_name: row.get(5)?,
+                    last_activity: row.get(6)?,
+                    unread_count: row.get(7)?,
+                    starred: row.get(8)?,
+                })
+            })
+            .map_err(|e| format!("Failed to query conversations: {e}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect conversations: {e}"))?;
+
+        Ok(conversations)
+    }
+
+    // ─── Usage Statistics ──────────────────────────────────────────────
+    //
+    // Messages and file transfers are counted by triggers as they're stored.
+
+    /// Add time spent in a call with a friend to today's statistics
+    pub fn record_call_time(&self, friend_number: i64, seconds: i64) -> Result<(), String> {
+        let conn = self.write()?;
+        conn.execute(
+            "INSERT INTO usage_stats (day, kind, conversation_id, call_seconds)
+             SELECT date('now', 'localtime'), 'dm', public_key, ?2 FROM friends WHERE friend_number = ?1
+             ON CONFLICT (day, kind, conversation_id) DO UPDATE SET call_seconds = call_seconds + excluded.call_seconds",
+            rusqlite::params![friend_number, seconds],
+        )
+        .map_err(|e| format!("Failed to record call time: {e}"))?;
+        Ok(())
+    }
+
+    /// Totals for each day with any activity from `from` to `to` (YYYY-MM-DD, inclusive), oldest first
+    pub fn get_usage_days(&self, from: &str, to: &str) -> Result<Vec<UsageDayRecord>, String> {
+        let conn = self.read()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT day, SUM(messages_sent), SUM(messages_received), SUM(call_seconds),
+                        SUM(bytes_sent), SUM(bytes_received)
+                 FROM usage_stats WHERE day BETWEEN ?1 AND ?2
+                 GROUP BY day ORDER BY day",
+            )
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+        let days = stmt
+            .query_map(rusqlite::params![from, to], |row| {
+                Ok(UsageDayRecord {
+                    day: row.get(0)?,
+                    messages_sent: row.get(1)?,
+                    messages_received: row.get(2)?,
+                    call_seconds: row.get(3)?,
+                    bytes_sent: row.get(4)?,
+                    bytes_received: row.get(5)?,
+                })
+            })
+            .map_err(|e| format!("Failed to query usage: {e}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect usage: {e}"))?;
+        Ok(days)
+    }
+
+    /// The conversations with the most messages from `from` to `to`, then the most call time
+    pub fn get_top_conversations(&self, from: &str, to: &str, limit: i64) -> Result<Vec<ConversationUsageRecord>, String> {
+        let conn = self.read()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT CASE WHEN u.kind = 'dm' THEN 'dm' WHEN g.guild_type = 'dm_group' THEN 'dm_group' ELSE 'channel' END,
+                        u.conversation_id,
+                        COALESCE(CASE WHEN u.kind = 'dm' THEN f.name
+                                      WHEN g.guild_type = 'dm_group' THEN g.name
+                                      ELSE ch.name END, ''),
+                        CASE WHEN g.guild_type = 'server' THEN g.name END,
+                        SUM(u.messages_sent), SUM(u.messages_received), SUM(u.call_seconds)
+                 FROM usage_stats u
+                 LEFT JOIN friends f ON u.kind = 'dm' AND f.public_key = u.conversation_id
+                 LEFT JOIN channels ch ON u.kind = 'channel' AND ch.id = u.conversation_id
+                 LEFT JOIN guilds g ON g.id = ch.guild_id
+                 WHERE u.day BETWEEN ?1 AND ?2
+                 GROUP BY u.kind, u.conversation_id
+                 HAVING SUM(u.messages_sent + u.messages_received + u.call_seconds) > 0
+                 ORDER BY SUM(u.messages_sent + u.messages_received) DESC, SUM(u.call_seconds) DESC
+                 LIMIT ?3",
+            )
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+        let conversations = stmt
+            .query_map(rusqlite::params![from, to, limit], |row| {
+                Ok(ConversationUsageRecord {
+                    kind: row.get(0)?,
+                    id: row.get(1)?,
+                    name: row.get(2)?,
+                    guild_name: row.get(3)?,
+                    messages_sent: row.get(4)?,
+                    messages_received: row.get(5)?,
+                    call_seconds: row.get(6)?,
+                })
+            })
+            .map_err(|e| format!("Failed to query conversation usage: {e}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect conversation usage: {e}"))?;
+        Ok(conversations)
+    }
+
+    // ─── Offline Queue ─────────────────────────────────────────────────
+
+    pub fn queue_offline_message(
+        &self,
+        target_type: &str,
+        target_id: &str,
+        message_type: &str,
+        content: &str,
+    ) -> Result<(), String> {
+        let conn = self.write()?;
+        conn.execute(
+            "INSERT INTO offline_queue (target_type, target_id, message_type, content)
+             VALUES (?1, ?2, ?3, ?4)",
+            rusqlite::params![target_type, target_id, message_type, content],
+        )
+        .map_err(|e| format!("Failed to queue offline message: {e}"))?;
+        Ok(())
+    }
+
+    pub fn get_offline_messages_for(
+        &self,
+        target_type: &str,
+        target_id: &str,
+    ) -> Result<Vec<(i64, String, String)>, String> {
+        let conn = self.read()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, message_type, content FROM offline_queue
+                 WHERE target_type = ?1 AND target_id = ?2 ORDER BY created_at",
+            )
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+
+        let messages = stmt
+            .query_map(rusqlite::params![target_type, target_id], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                ))
+            })
+            .map_err(|e| format!("Failed to query offline queue: {e}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect offline messages: {e}"))?;
+
+        Ok(messages)
+    }
+
+    pub fn remove_offline_message(&self, id: i64) -> Result<(), String> {
+        let conn = self.write()?;
+        conn.execute(
+            "DELETE FROM offline_queue WHERE id = ?1",
+            rusqlite::params![id],
+        )
+        .map_err(|e| format!("Failed to remove offline message: {e}"))?;
+        Ok(())
+    }
+
+    // ─── Scheduled Messages ────────────────────────────────────────────
+
+    pub fn insert_scheduled_message(&self, msg: &ScheduledMessageRecord) -> Result<(), String> {
+        let conn = self.write()?;
+        conn.execute(
+            "INSERT INTO scheduled_messages (id, target_type, target_id, guild_id, content, send_at, status)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            rusqlite::params![
+                msg.id,
+                msg.target_type,
+                msg.target_id,
+                msg.guild_id,
+                msg.content,
+                msg.send_at,
+                msg.status,
+            ],
+        )
+        .map_err(|e| format!("Failed to insert scheduled message: {e}"))?;
+        Ok(())
+    }
+
+    /// Scheduled messages that haven't gone out yet, soonest first
+    pub fn get_pending_scheduled_messages(&self) -> Result<Vec<ScheduledMessageRecord>, String> {
+        self.query_scheduled_messages(
+            "SELECT id, target_type, target_id, guild_id, content, send_at, status, message_id, created_at
+             FROM scheduled_messages WHERE status = 'pending' ORDER BY send_at",
+            rusqlite::params![],
+        )
+    }
+
+    /// Pending scheduled messages whose send time is at or before `now` (same format as `send_at`)
+    pub fn get_due_scheduled_messages(&self, now: &str) -> Result<Vec<ScheduledMessageRecord>, String> {
+        self.query_scheduled_messages(
+            "SELECT id, target_type, target_id, guild_id, content, send_at, status, message_id, created_at
+             FROM scheduled_messages WHERE status = 'pending' AND send_at <= ?1 ORDER BY send_at",
+            rusqlite::params![now],
+        )
+    }
+
+    pub fn update_scheduled_message_status(
+        &self,
+        id: &str,
+        status: &str,
+        message_id: Option<&str>,
+    ) -> Result<(), String> {
+        let conn = self.write()?;
+        conn.execute(
+            "UPDATE scheduled_messages SET status = ?2, message_id = ?3 WHERE id = ?1",
+            rusqlite::params![id, status, message_id],
+        )
+        .map_err(|e| format!("Failed to update scheduled message: {e}"))?;
+        Ok(())
+    }
+
+    /// Cancel a scheduled message. Returns false if it was already sent (or doesn't exist).
+    pub fn cancel_scheduled_message(&self, id: &str) -> Result<bool, String> {
+        let conn = self.write()?;
+        let changed = conn
+            .execute(
+                "UPDATE scheduled_messages SET status = 'cancelled' WHERE id = ?1 AND status = 'pending'",
+                rusqlite::params![id],
+            )
+            .map_err(|e| format!("Failed to cancel scheduled message: {e}"))?;
+        Ok(changed > 0)
+    }
+
+    fn query_scheduled_messages(
+        &self,
+        sql: &str,
+        params: &[&dyn rusqlite::ToSql],
+    ) -> Result<Vec<ScheduledMessageRecord>, String> {
+        let conn = self.read()?;
+        let mut stmt = conn
+            .prepare(sql)
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+
+        let messages = stmt
+            .query_map(params, |row| {
+                Ok(ScheduledMessageRecord {
+                    id: row.get(0)?,
+                    target_type: row.get(1)?,
+                    target_id: row.get(2)?,
+                    guild_id: row.get(3)?,
+                    content: row.get(4)?,
+                    send_at: row.get(5)?,
+                    status: row.get(6)?,
+                    message_id: row.get(7)?,
+                    created_at: row.get(8)?,
+                })
+            })
+            .map_err(|e| format!("Failed to query scheduled messages: {e}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect scheduled messages: {e}"))?;
+
+        Ok(messages)
+    }
+
+    // ─── Guilds ───────────────────────────────────────────────────────
+
+    pub fn insert_guild(
+        &self,
+        id: &str,
+        name: &str,
+        group_number: Option<i64>,
+        owner_pk: &str,
+        guild_type: &str,
+    ) -> Result<(), String> {
+        let conn = self.write()?;
+        conn.execute(
+            "INSERT INTO guilds (id, name, metadata_group_number, owner_public_key, guild_type)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            rusqlite::params![id, name, group_number, owner_pk, guild_type],
+        )
+        .map_err(|e| format!("Failed to insert guild: {e}"))?;
+        Ok(())
+    }
+
+    pub fn get_guilds(&self) -> Result<Vec<GuildRecord>, String> {
+        let conn = self.read()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, name, metadata_group_number, icon_hash, owner_public_key, guild_type, created_at
+                 FROM guilds ORDER BY created_at",
+            )
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+
+        let guilds = stmt
+            .query_map([], |row| {
+                Ok(GuildRecord {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    metadata_group_number: row.get(2)?,
+                    icon_hash: row.get(3)?,
+                    owner_public_key: row.get(4)?,
+                    guild_type: row.get(5)?,
+                    created_at: row.get(6)?,
+                })
+            })
+            .map_err(|e| format!("Failed to query guilds: {e}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect guilds: {e}"))?;
+
+        Ok(guilds)
+    }
+
+    pub fn get_guild(&self, id: &str) -> Result<Option<GuildRecord>, String> {
+        let conn = self.read()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, name, metadata_group_number, icon_hash, owner_public_key, guild_type, created_at
+                 FROM guilds WHERE id = ?1",
+            )
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+
+        let mut rows = stmt
+            .query_map(rusqlite::params![id], |row| {
+                Ok(GuildRecord {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    metadata_group_number: row.get(2)?,
+                    icon_hash: row.get(3)?,
+                    owner_public_key: row.get(4)?,
+                    guild_type: row.get(5)?,
+                    created_at: row.get(6)?,
+                })
+            })
+            .map_err(|e| format!("Failed to query guild: {e}"))?;
+
+        match rows.next() {
+            Some(Ok(record)) => Ok(Some(record)),
+            Some(Err(e)) => Err(format!("Failed to read guild: {e}")),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_guild_by_group_number(&self, group_number: i64) -> Result<Option<GuildRecord>, String> {
+        let conn = self.read()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, name, metadata_group_number, icon_hash, owner_public_key, guild_type, created_at
+                 FROM guilds WHERE metadata_group_number = ?1",
+            )
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+
+        let mut rows = stmt
+            .query_map(rusqlite::params![group_number], |row| {
+                Ok(GuildRecord {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    metadata_group_number: row.get(2)?,
+                    icon_hash: row.get(3)?,
+                    owner_public_key: row.get(4)?,
+                    guild_type: row.get(5)?,
+                    created_at: row.get(6)?,
+                })
+            })
+            .map_err(|e| format!("Failed to query guild: {e}"))?;
+
+        match rows.next() {
+            Some(Ok(record)) => Ok(Some(record)),
+            Some(Err(e)) => Err(format!("Failed to read guild: {e}")),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_guild_by_group_number_and_type(&self, group_number: i64, guild_type: &str) -> Result<Option<GuildRecord>, String> {
+        let conn = self.read()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, name, metadata_group_number, icon_hash, owner_public_key, guild_type, created_at
+                 FROM guilds WHERE metadata_group_number = ?1 AND guild_type = ?2",
+            )
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+
+        let mut rows = stmt
+            .query_map(rusqlite::params![group_number, guild_type], |row| {
+                Ok(GuildRecord {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    metadata_group_number: row.get(2)?,
+                    icon_hash: row.get(3)?,
+                    owner_public_key: row.get(4)?,
+                    guild_type: row.get(5)?,
+                    created_at: row.get(6)?,
+                })
+            })
+            .map_err(|e| format!("Failed to query guild: {e}"))?;
+
+        match rows.next() {
+            Some(Ok(record)) => Ok(Some(record)),
+            Some(Err(e)) => Err(format!("Failed to read guild: {e}")),
+            None => Ok(None),
+        }
+    }
+
+    pub fn update_guild_name(&self, id: &str, name: &str) -> Result<(), String> {
+        let conn = self.write()?;
+        conn.execute(
+            "UPDATE guilds SET name = ?1 WHERE id = ?2",
+            rusqlite::params![name, id],
+        )
+        .map_err(|e| format!("Failed to update guild name: {e}"))?;
+        Ok(())
+    }
+
+    /// The guild's manifest, or an empty one (version 0) if none was received yet
+    pub fn get_guild_manifest(&self, guild_id: &str) -> Result<GuildManifest, String> {
+        let conn = self.read()?;
+        Ok(read_guild_document(&conn, guild_id)?.manifest)
+    }
+
+    pub fn set_guild_manifest(&self, guild_id: &str, manifest: &GuildManifest) -> Result<(), String> {
+        let conn = self.write()?;
+        let mut doc = read_guild_document(&conn, guild_id)?;
+        doc.manifest = manifest.clone();
+        write_guild_document(&conn, guild_id, &doc)
+    }
+
+    /// The guild's channel layout, empty if no moderator has shared one yet
+    pub fn get_guild_layout(&self, guild_id: &str) -> Result<GuildLayout, String> {
+        let conn = self.read()?;
+        Ok(read_guild_document(&conn, guild_id)?.layout)
+    }
+
+    pub fn set_guild_layout(&self, guild_id: &str, layout: &GuildLayout) -> Result<(), String> {
+        let conn = self.write()?;
+        let mut doc = read_guild_document(&conn, guild_id)?;
+        doc.layout = layout.clone();
+        write_guild_document(&conn, guild_id, &doc)
+    }
+
+    pub fn update_guild_group_number(&self, id: &str, group_number: i64) -> Result<(), String> {
+        let conn = self.write()?;
+        conn.execute(
+            "UPDATE guilds SET metadata_group_number = ?1 WHERE id = ?2",
+            rusqlite::params![group_number, id],
+        )
+        .map_err(|e| format!("Failed to update guild group_number: {e}"))?;
+        Ok(())
+    }
+
+    pub fn update_guild_owner(&self, id: &str, owner_public_key: &str) -> Result<(), String> {
+        let conn = self.write()?;
+        conn.execute(
+            "UPDATE guilds SET owner_public_key = ?1 WHERE id = ?2",
+            rusqlite::params![owner_public_key, id],
+        )
+        .map_err(|e| format!("Failed to update guild owner: {e}"))?;
+        Ok(())
+    }
+
+    pub fn get_guild_by_name(&self, name: &str) -> Result<Option<GuildRecord>, String> {
+        let conn = self.read()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, name, metadata_group_number, icon_hash, owner_public_key, guild_type, created_at
+                 FROM guilds WHERE name = ?1",
+            )
+            .map_err(|e| format!("Failed to prepare statement: {e}"))?;
+
+        let mut rows = stmt
+            .query_map(rusqlite::params![name], |row| {
+                Ok(GuildRecord {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    metadata_group_number: row.get(2)?,
+                    icon_hash: row.get(3)?,
+                    owner_public_key: row.get(4)?,
+                    guild_type: row.get(5)?,
+                    created_at: row.get(6)?,
+                })
+            })
+            .map_err(|e| format!("Failed to query guilds: {e}"))?;
+
+        match rows.next() {
+            Some(Ok(record)) => Ok(Some(record)),
+            Some(Err(e)) => Err(format!("Failed to read guild: {e}")),
+            None => Ok(None),
+        }
+    }
+
+    /// A guild's NGC chat ID, if it's been recorded
+    pub fn get_guild_chat_id(&self, id: &str) -> Result<Option<String>, String> {
+        let conn = self.read()?;
+        let mut stmt = conn
+            .prepare("SELECT chat_id FROM guilds WHERE id = ?1")
+            .map_err(|e| format!("Failed to prepare statement: {e}"))?;
+
+        let mut rows = stmt
+            .query_map(rusqlite::params![id], |row| row.get(0))
+            .map_err(|e| format!("Failed to query guilds: {e}"))?;
+
+        match rows.next() {
+            Some(Ok(chat_id)) => Ok(chat_id),
+            Some(Err(e)) => Err(format!("Failed to read guild: {e}")),
+            None => Ok(None),
+        }
+    }
+
+    pub fn set_guild_chat_id(&self, id: &str, chat_id: &str) -> Result<(), String> {
+        let conn = self.write()?;
+        conn.execute(
+            "UPDATE guilds SET chat_id = ?1 WHERE id = ?2",
+            rusqlite::params![chat_id.to_uppercase(), id],
+        )
+        .map_err(|e| format!("Failed to update guild chat ID: {e}"))?;
+        Ok(())
+    }
+
+    /// Guilds with a recorded chat ID, as (guild id, chat id)
+    pub fn get_guild_chat_ids(&self) -> Result<Vec<(String, String)>, String> {
+        let conn = self.read()?;
+        let mut stmt = conn
+            .prepare("SELECT id, chat_id FROM guilds WHERE chat_id IS NOT NULL")
+            .map_err(|e| format!("Failed to prepare statement: {e}"))?;
+        let guilds = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(|e| format!("Failed to query guilds: {e}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect guilds: {e}"))?;
+        Ok(guilds)
+    }
+
+    pub fn delete_guild(&self, id: &str) -> Result<(), String> {
+        let conn = self.write()?;
+        conn.execute(
+            "DELETE FROM guilds WHERE id = ?1",
+            rusqlite::params![id],
+        )
+        .map_err(|e| format!("Failed to delete guild: {e}"))?;
+        Ok(())
+    }
+
+    // ─── Channels ─────────────────────────────────────────────────────
+
+    pub fn insert_channel(
+        &self,
+        id: &str,
+        guild_id: &str,
+        name: &str,
+        channel_type: &str,
+        position: i64,
+    ) -> Result<(), String> {
+        let conn = self.write()?;
+        conn.execute(
+            "INSERT INTO channels (id, guild_id, name, channel_type, position)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            rusqlite::params![id, guild_id, name, channel_type, position],
+        )
+        .map_err(|e| format!("Failed to insert channel: {e}"))?;
+        Ok(())
+    }
+
+    pub fn get_channels(&self, guild_id: &str) -> Result<Vec<ChannelRecord>, String> {
+        let conn = self.read()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, guild_id, name, topic, channel_type, category, position, group_number, created_at
+                 FROM channels WHERE guild_id = ?1 ORDER BY position",
+            )
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+
+        let channels = stmt
+            .query_map(rusqlite::params![guild_id], |row| {
+                Ok(ChannelRecord {
+                    id: row.get(0)?,
+                    guild_id: row.get(1)?,
+                    name: row.get(2)?,
+                    topic: row.get(3)?,
+                    channel_type: row.get(4)?,
+                    category: row.get(5)?,
+                    position: row.get(6)?,
+                    group_number: row.get(7)?,
+                    created_at: row.get(8)?,
+                })
+            })
+            .map_err(|e| format!("Failed to query channels: {e}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect channels: {e}"))?;
+
+        Ok(channels)
+    }
+
+    pub fn get_channel(&self, id: &str) -> Result<Option<ChannelRecord>, String> {
+        let conn = self.read()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, guild_id, name, topic, channel_type, category, position, group_number, created_at
+                 FROM channels WHERE id = ?1",
+            )
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+
+        let mut rows = stmt
+            .query_map(rusqlite::params![id], |row| {
+                Ok(ChannelRecord {
+                    id: row.get(0)?,
+                    guild_id: row.get(1)?,
+                    name: row.get(2)?,
+                    topic: row.get(3)?,
+                    channel_type: row.get(4)?,
+                    category: row.get(5)?,
+                    position: row.get(6)?,
+                    group_number: row.get(7)?,
+                    created_at: row.get(8)?,
+                })
+            })
+            .map_err(|e| format!("Failed to query channel: {e}"))?;
+
+        match rows.next() {
+            Some(Ok(channel)) => Ok(Some(channel)),
+            Some(Err(e)) => Err(format!("Failed to read channel: {e}")),
+            None => Ok(None),
+        }
+    }
+
+    pub fn update_channel(&self, id: &str, name: &str, topic: &str) -> Result<(), String> {
+        let conn = self.write()?;
+        conn.execute(
+            "UPDATE channels SET name = ?1, topic = ?2 WHERE id = ?3",
+            rusqlite::params![name, topic, id],
+        )
+        .map_err(|e| format!("Failed to update channel: {e}"))?;
+        Ok(())
+    }
+
+    pub fn rename_channel(&self, id: &str, name: &str) -> Result<(), String> {
+        let conn = self.write()?;
+        conn.execute(
+            "UPDATE channels SET name = ?1 WHERE id = ?2",
+            rusqlite::params![name, id],
+        )
+        .map_err(|e| format!("Failed to rename channel: {e}"))?;
+        Ok(())
+    }
+
+    pub fn delete_channel(&self, id: &str) -> Result<(), String> {
+        let conn = self.write()?;
+        conn.execute(
+            "DELETE FROM channels WHERE id = ?1",
+            rusqlite::params![id],
+        )
+        .map_err(|e| format!("Failed to delete channel: {e}"))?;
+        Ok(())
+    }
+
+    pub fn set_channel_position(&self, id: &str, position: i64) -> Result<(), String> {
+        let conn = self.write()?;
+        conn.execute(
+            "UPDATE channels SET position = ?1 WHERE id = ?2",
+            rusqlite::params![position, id],
+        )
+        .map_err(|e| format!("Failed to move channel: {e}"))?;
+        Ok(())
+    }
+
+    /// The key a channel goes by in its guild's layout, if it's in there
+    pub fn get_channel_layout_key(&self, id: &str) -> Result<Option<String>, String> {
+        let conn = self.read()?;
+        conn.query_row(
+            "SELECT layout_key FROM channels WHERE id = ?1",
+            rusqlite::params![id],
+            |row| row.get(0),
+        )
+        .map_err(|e| format!("Failed to get channel layout key: {e}"))
+    }
+
+    pub fn set_channel_layout_key(&self, id: &str, key: &str) -> Result<(), String> {
+        let conn = self.write()?;
+        conn.execute(
+            "UPDATE channels SET layout_key = ?1 WHERE id = ?2",
+            rusqlite::params![key, id],
+        )
+        .map_err(|e| format!("Failed to set channel layout key: {e}"))?;
+        Ok(())
+    }
+
+    /// Bring a guild's channels in line with its layout. Each layout entry is
+    /// matched to the channel bound to its key, or else to an unbound channel
+    /// named as it was first shared, then renamed, moved or deleted to match;
+    /// entries we have no channel for are created. Returns whether any
+    /// channel changed.
+    pub fn apply_channel_layout(&self, guild_id: &str, layout: &GuildLayout) -> Result<bool, String> {
+        let mut conn = self.write()?;
+        let tx = conn
+            .transaction()
+            .map_err(|e| format!("Failed to begin transaction: {e}"))?;
+
+        let mut stmt = tx
+            .prepare("SELECT id, name, position, layout_key FROM channels WHERE guild_id = ?1")
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+        // (id, name, position, layout key)
+        let mut channels: Vec<(String, String, i64, Option<String>)> = stmt
+            .query_map(rusqlite::params![guild_id], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+            })
+            .map_err(|e| format!("Failed to query channels: {e}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect channels: {e}"))?;
+        drop(stmt);
+
+        let mut changed = false;
+        for (key, entry) in &layout.channels {
+            let found = channels
+                .iter()
+                .position(|c| c.3.as_deref() == Some(key.as_str()))
+                .or_else(|| channels.iter().position(|c| c.3.is_none() && c.1 == entry.origin));
+            let Some(i) = found else {
+                if entry.deleted.value {
+                    continue;
+                }
+                let id = uuid::Uuid::new_v4().to_string();
+                tx.execute(
+                    "INSERT INTO channels (id, guild_id, name, channel_type, position, layout_key)
+                     VALUES (?1, ?2, ?3, 'text', ?4, ?5)",
+                    rusqlite::params![id, guild_id, entry.name.value, entry.position.value, key],
+                )
+                .map_err(|e| format!("Failed to insert channel: {e}"))?;
+                channels.push((id, entry.name.value.clone(), entry.position.value, Some(key.clone())));
+                changed = true;
+                continue;
+            };
+
+            let (id, name, position, bound) = &mut channels[i];
+            if bound.is_none() {
+                tx.execute(
+                    "UPDATE channels SET layout_key = ?1 WHERE id = ?2",
+                    rusqlite::params![key, id.as_str()],
+                )
+                .map_err(|e| format!("Failed to set channel layout key: {e}"))?;
+                *bound = Some(key.clone());
+            }
+            if entry.deleted.value {
+                tx.execute("DELETE FROM channels WHERE id = ?1", rusqlite::params![id.as_str()])
+                    .map_err(|e| format!("Failed to delete channel: {e}"))?;
+                channels.remove(i);
+                changed = true;
+            } else if *name != entry.name.value || *position != entry.position.value {
+                tx.execute(
+                    "UPDATE channels SET name = ?1, position = ?2 WHERE id = ?3",
+                    rusqlite::params![entry.name.value, entry.position.value, id.as_str()],
+                )
+                .map_err(|e| format!("Failed to update channel: {e}"))?;
+                *name = entry.name.value.clone();
+                *position = entry.position.value;
+                changed = true;
+            }
+        }
+
+        tx.commit()
+            .map_err(|e| format!("Failed to commit channel layout: {e}"))?;
+        Ok(changed)
+    }
+
+    pub fn get_channel_count(&self, guild_id: &str) -> Result<i64, String> {
+        let conn = self.read()?;
+        let count: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM channels WHERE guild_id = ?1",
+                rusqlite::params![guild_id],
+                |row| row.get(0),
+            )
+            .map_err(|e| format!("Failed to count channels: {e}"))?;
+        Ok(count)
+    }
+
+    /// Get or create a channel by name within a guild.
+    /// Returns the channel_id.
+    pub fn get_or_create_channel_by_name(&self, guild_id: &str, channel_name: &str) -> Result<String, String> {
+        // First, try to find existing channel
+        let channels = self.get_channels(guild_id)?;
+        if let Some(channel) = channels.iter().find(|c| c.name == channel_name) {
+            return Ok(channel.id.clone());
+        }
+
+        // Channel doesn't exist, create it
+        let channel_id = uuid::Uuid::new_v4().to_string();
+        let position = channels.len() as i64;
+        self.insert_channel(&channel_id, guild_id, channel_name, "text", position)?;
+
+        Ok(channel_id)
+    }
+
+    // ─── Channel Bridges ──────────────────────────────────────────────
+
+    /// Link a channel to an external endpoint, replacing any existing bridge
+    pub fn set_channel_bridge(&self, bridge: &ChannelBridgeRecord) -> Result<(), String> {
+        let conn = self.write()?;
+        conn.execute(
+            "INSERT OR REPLACE INTO channel_bridges
+             (channel_id, guild_id, protocol, server, target, nickname, access_token, use_tls, enabled)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            rusqlite::params![
+                bridge.channel_id,
+                bridge.guild_id,
+                bridge.protocol,
+                bridge.server,
+                bridge.target,
+                bridge.nickname,
+                bridge.access_token,
+                bridge.use_tls,
+                bridge.enabled,
+            ],
+        )
+        .map_err(|e| format!("Failed to save channel bridge: {e}"))?;
+        Ok(())
+    }
+
+    /// Returns false if the channel had no bridge
+    pub fn remove_channel_bridge(&self, channel_id: &str) -> Result<bool, String> {
+        let conn = self.write()?;
+        let removed = conn
+            .execute(
+                "DELETE FROM channel_bridges WHERE channel_id = ?1",
+                rusqlite::params![channel_id],
+            )
+            .map_err(|e| format!("Failed to remove channel bridge: {e}"))?;
+        Ok(removed > 0)
+    }
+
+    pub fn set_channel_bridge_enabled(&self, channel_id: &str, enabled: bool) -> Result<(), String> {
+        let conn = self.write()?;
+        conn.execute(
+            "UPDATE channel_bridges SET enabled = ?1 WHERE channel_id = ?2",
+            rusqlite::params![enabled, channel_id],
+        )
+        .map_err(|e| format!("Failed to update channel bridge: {e}"))?;
+        Ok(())
+    }
+
+    pub fn get_channel_bridges(&self) -> Result<Vec<ChannelBridgeRecord>, String> {
+        let conn = self.read()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT channel_id, guild_id, protocol, server, target, nickname, access_token,
+                        use_tls, enabled, created_at
+                 FROM channel_bridges ORDER BY created_at",
+            )
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+
+        let bridges = stmt
+            .query_map([], |row| {
+                Ok(ChannelBridgeRecord {
+                    channel_id: row.get(0)?,
+                    guild_id: row.get(1)?,
+                    protocol: row.get(2)?,
+                    server: row.get(3)?,
+                    target: row.get(4)?,
+                    nickname: row.get(5)?,
+                    access_token: row.get(6)?,
+                    use_tls: row.get(7)?,
+                    enabled: row.get(8)?,
+                    created_at: row.get(9)?,
+                })
+            })
+            .map_err(|e| format!("Failed to query channel bridges: {e}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect channel bridges: {e}"))?;
+
+        Ok(bridges)
+    }
+
+    pub fn get_channel_bridge(&self, channel_id: &str) -> Result<Option<ChannelBridgeRecord>, String> {
+        Ok(self
+            .get_channel_bridges()?
+            .into_iter()
+            .find(|b| b.channel_id == channel_id))
+    }
+
+    // ─── Channel Messages ─────────────────────────────────────────────
+
+    /// Store a channel message. A message whose ID we already have is the same
+    /// message arriving again and is left alone; returns whether it was new.
+    pub fn insert_channel_message(&self, msg: &ChannelMessageRecord) -> Result<bool, String> {
+        let conn = self.write()?;
+        insert_channel_message(&conn, msg)
+    }
+
+    pub fn get_channel_messages(
+        &self,
+        channel_id: &str,
+        limit: i64,
+        before_timestamp: Option<&str>,
+    ) -> Result<Vec<ChannelMessageRecord>, String> {
+        let conn = self.read_messages()?;
+
+        let (sql, params): (&str, Vec<Box<dyn rusqlite::types::ToSql>>) = if let Some(before) = before_timestamp {
+            (
+                "SELECT id, channel_id, sender_public_key, sender_name, content, message_type, timestamp, mentions_me, filtered, backfilled, sender_verified, notify, signer_public_key, signed_route, signature_tag
+                 FROM channel_messages
+                 WHERE channel_id = ?1 AND timestamp < ?2
+                   AND NOT EXISTS (
+                       SELECT 1 FROM ignored_guild_members i JOIN channels c ON c.guild_id = i.guild_id
+                       WHERE c.id = channel_messages.channel_id AND i.public_key = channel_messages.sender_public_key)
+                 ORDER BY timestamp DESC, id DESC LIMIT ?3",
+                vec![
+                    Box::new(channel_id.to_string()),
+                    Box::new(before.to_string()),
+                    Box::new(limit),
+                ],
+            )
+        } else {
+            (
+                "SELECT id, channel_id, sender_public_key, sender_name, content, message_type, timestamp, mentions_me, filtered, backfilled, sender_verified, notify, signer_public_key, signed_route, signature_tag
+                 FROM channel_messages
+                 WHERE channel_id = ?1
+                   AND NOT EXISTS (
+                       SELECT 1 FROM ignored_guild_members i JOIN channels c ON c.guild_id = i.guild_id
+                       WHERE c.id = channel_messages.channel_id AND i.public_key = channel_messages.sender_public_key)
+                 ORDER BY timestamp DESC, id DESC LIMIT ?2",
+                vec![
+                    Box::new(channel_id.to_string()),
+                    Box::new(limit),
+                ],
+            )
+        };
+
+        let mut stmt = conn
+            .prepare(sql)
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+
+        let params_refs: Vec<&dyn rusqlite::types::ToSql> = params.iter().map(|p| p.as_ref()).collect();
+
+        let messages = stmt
+            .query_map(params_refs.as_slice(), |row| {
+                Ok(ChannelMessageRecord {
+                    id: row.get(0)?,
+                    channel_id: row.get(1)?,
+                    sender_public_key: row.get(2)?,
+                    sender_name: row.get(3)?,
+                    content: row.get(4)?,
+                    message_type: row.get(5)?,
+                    timestamp: row.get(6)?,
+                    mentions_me: row.get(7)?,
+                    filtered: row.get(8)?,
+                    backfilled: row.get(9)?,
+                    sender_verified: row.get(10)?,
+                    notify: row.get(11)?,
+                    signer_public_key: row.get(12)?,
+                    signature: signature_from_row(row, 13)?,
+                })
+            })
+            .map_err(|e| format!("Failed to query channel messages: {e}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect channel messages: {e}"))?;
+
+        Ok(messages)
+    }
+
+    /// Our most recent text messages in a channel, newest first
+    pub fn get_own_recent_channel_messages(
+        &self,
+        channel_id: &str,
+        self_public_key: &str,
+        limit: i64,
+    ) -> Result<Vec<ChannelMessageRecord>, String> {
+        let conn = self.read_messages()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, channel_id, sender_public_key, sender_name, content, message_type, timestamp, mentions_me, filtered, backfilled, sender_verified, notify, signer_public_key, signed_route, signature_tag
+                 FROM channel_messages
+                 WHERE channel_id = ?1 AND sender_public_key = ?2 COLLATE NOCASE AND message_type IN ('normal', 'action')
+                 ORDER BY timestamp DESC, id DESC LIMIT ?3",
+            )
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+
+        let messages = stmt
+            .query_map(rusqlite::params![channel_id, self_public_key, limit], |row| {
+                Ok(ChannelMessageRecord {
+                    id: row.get(0)?,
+                    channel_id: row.get(1)?,
+                    sender_public_key: row.get(2)?,
+                    sender_name: row.get(3)?,
+                    content: row.get(4)?,
+                    message_type: row.get(5)?,
+                    timestamp: row.get(6)?,
+                    mentions_me: row.get(7)?,
+                    filtered: row.get(8)?,
+                    backfilled: row.get(9)?,
+                    sender_verified: row.get(10)?,
+                    notify: row.get(11)?,
+                    signer_public_key: row.get(12)?,
+                    signature: signature_from_row(row, 13)?,
+                })
+            })
+            .map_err(|e| format!("Failed to query channel messages: {e}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect channel messages: {e}"))?;
+
+        Ok(messages)
+    }
+
+    /// A page of a channel's messages, newest first, older than `before`
+    pub fn get_channel_messages_page(
+        &self,
+        channel_id: &str,
+        limit: i64,
+        before: Option<&MessageCursor>,
+    ) -> Result<MessagePage<ChannelMessageRecord>, String> {
+        let conn = self.read_messages()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, channel_id, sender_public_key, sender_name, content, message_type, timestamp, mentions_me, filtered, backfilled, sender_verified, notify, signer_public_key, signed_route, signature_tag
+                 FROM channel_messages
+                 WHERE channel_id = ?1
+                   AND (?2 IS NULL OR timestamp < ?2 OR (timestamp = ?2 AND id < ?3))
+                   AND NOT EXISTS (
+                       SELECT 1 FROM ignored_guild_members i JOIN channels c ON c.guild_id = i.guild_id
+                       WHERE c.id = channel_messages.channel_id AND i.public_key = channel_messages.sender_public_key)
+                 ORDER BY timestamp DESC, id DESC LIMIT ?4",
+            )
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+
+        // One extra row tells us whether there are more
+        let mut messages = stmt
+            .query_map(
+                rusqlite::params![
+                    channel_id,
+                    before.map(|c| &c.timestamp),
+                    before.map(|c| &c.id),
+                    limit + 1,
+                ],
+                |row| {
+                    Ok(ChannelMessageRecord {
+                        id: row.get(0)?,
+                        channel_id: row.get(1)?,
+                        sender_public_key: row.get(2)?,
+                        sender_name: row.get(3)?,
+                        content: row.get(4)?,
+                        message_type: row.get(5)?,
+                        timestamp: row.get(6)?,
+                        mentions_me: row.get(7)?,
+                        filtered: row.get(8)?,
+                        backfilled: row.get(9)?,
+                        sender_verified: row.get(10)?,
+                        notify: row.get(11)?,
+                        signer_public_key: row.get(12)?,
+                        signature: signature_from_row(row, 13)?,
+                    })
+                },
+            )
+            .map_err(|e| format!("Failed to query channel messages: {e}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect channel messages: {e}"))?;
+        let has_more = messages.len() as i64 > limit;
+        messages.truncate(limit.max(0) as usize);
+
+        let total = conn
+            .query_row(
+                "SELECT COUNT(*) FROM channel_messages
+                 WHERE channel_id = ?1
+                   AND NOT EXISTS (
+                       SELECT 1 FROM ignored_guild_members i JOIN channels c ON c.guild_id = i.guild_id
+                       WHERE c.id = channel_messages.channel_id AND i.public_key = channel_messages.sender_public_key)",
+                rusqlite::params![channel_id],
+                |row| row.get(0),
+            )
+            .map_err(|e| format!("Failed to count channel messages: {e}"))?;
+
+        Ok(MessagePage {
+            messages,
+            has_more,
+            total,
+        })
+    }
+
+    /// Up to `context` channel messages either side of `anchor`, for jumping to a
+    /// message or a date. `None` if the anchor is a message we don't have.
+    pub fn get_channel_messages_around(
+        &self,
+        channel_id: &str,
+        anchor: &MessageAnchor,
+        context: i64,
+    ) -> Result<Option<MessageWindow<ChannelMessageRecord>>, String> {
+        let conn = self.read_messages()?;
+        messages_around(
+            &conn,
+            "SELECT id, channel_id, sender_public_key, sender_name, content, message_type, timestamp, mentions_me, filtered, backfilled, sender_verified, notify, signer_public_key, signed_route, signature_tag
+             FROM channel_messages
+             WHERE channel_id = ?1
+               AND NOT EXISTS (
+                   SELECT 1 FROM ignored_guild_members i JOIN channels c ON c.guild_id = i.guild_id
+                   WHERE c.id = channel_messages.channel_id AND i.public_key = channel_messages.sender_public_key)",
+            &channel_id,
+            anchor,
+            context,
+            |row| {
+                Ok(ChannelMessageRecord {
+                    id: row.get(0)?,
+                    channel_id: row.get(1)?,
+                    sender_public_key: row.get(2)?,
+                    sender_name: row.get(3)?,
+                    content: row.get(4)?,
+                    message_type: row.get(5)?,
+                    timestamp: row.get(6)?,
+                    mentions_me: row.get(7)?,
+                    filtered: row.get(8)?,
+                    backfilled: row.get(9)?,
+                    sender_verified: row.get(10)?,
+                    notify: row.get(11)?,
+                    signer_public_key: row.get(12)?,
+                    signature: signature_from_row(row, 13)?,
+                })
+            },
+            |m| (&m.timestamp, &m.id),
+        )
+    }
+
+    /// Delete a channel's messages with timestamps at or after `since` and
+    /// before `before` (RFC 3339); a missing bound is open. Returns how many
+    /// were deleted.
+    pub fn delete_channel_messages_in_range(
+        &self,
+        channel_id: &str,
+        since: Option<&str>,
+        before: Option<&str>,
+    ) -> Result<usize, String> {
+        let conn = self.write_messages()?;
+        conn.execute(
+            "DELETE FROM channel_messages
+             WHERE channel_id = ?1 AND (?2 IS NULL OR timestamp >= ?2) AND (?3 IS NULL OR timestamp < ?3)",
+            rusqlite::params![channel_id, since, before],
+        )
+        .map_err(|e| format!("Failed to delete channel messages: {e}"))
+    }
+
+    /// Delete every message a member sent in a channel. Returns how many were deleted.
+    pub fn delete_channel_messages_from(&self, channel_id: &str, public_key: &str) -> Result<usize, String> {
+        let conn = self.write_messages()?;
+        conn.execute(
+            "DELETE FROM channel_messages WHERE channel_id = ?1 AND UPPER(sender_public_key) = ?2",
+            rusqlite::params![channel_id, public_key.to_uppercase()],
+        )
+        .map_err(|e| format!("Failed to delete channel messages: {e}"))
+    }
+
+    /// Channels with unread messages that notify us (by their notification
+    /// level, mentions by default), with their counts
+    pub fn get_unread_mention_counts(&self) -> Result<Vec<(String, i64)>, String> {
+        let conn = self.read_messages()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT channel_id, COUNT(*) FROM channel_messages
+                 WHERE notify = 1 AND mention_read = 0
+                 GROUP BY channel_id",
+            )
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+
+        let counts = stmt
+            .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?)))
+            .map_err(|e| format!("Failed to query mention counts: {e}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect mention counts: {e}"))?;
+
+        Ok(counts)
+    }
+
+    /// Timestamp of the newest message `sender_public_key` sent in a channel
+    pub fn get_last_channel_message_time(&self, channel_id: &str, sender_public_key: &str) -> Result<Option<String>, String> {
+        let conn = self.read_messages()?;
+        conn.query_row(
+            "SELECT MAX(timestamp) FROM channel_messages WHERE channel_id = ?1 AND sender_public_key = ?2",
+            rusqlite::params![channel_id, sender_public_key.to_uppercase()],
+            |row| row.get(0),
+        )
+        .map_err(|e| format!("Failed to get last message time: {e}"))
+    }
+
+    /// Whether any of a guild's channels has messages
+    pub fn guild_has_channel_messages(&self, guild_id: &str) -> Result<bool, String> {
+        let conn = self.read_messages()?;
+        conn.query_row(
+            "SELECT EXISTS (SELECT 1 FROM channel_messages m JOIN channels c ON c.id = m.channel_id WHERE c.guild_id = ?1)",
+            rusqlite::params![guild_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| format!("Failed to check guild messages: {e}"))
+    }
+
+    /// Store a message from another member's history unless we already have
+    /// it, by ID or, for messages from clients without content-addressed IDs,
+    /// by likeness. Each member stamps messages when they arrive, so a copy is
+    /// one from the same sender with the same content within
+    /// `BACKFILL_MATCH_SECS`.
+    /// Returns whether it was inserted.
+    pub fn insert_backfilled_channel_message(&self, msg: &ChannelMessageRecord) -> Result<bool, String> {
+        let conn = self.write_messages()?;
+        let exists: bool = conn
+            .query_row(
+                "SELECT EXISTS (
+                     SELECT 1 FROM channel_messages
+                     WHERE channel_id = ?1 AND UPPER(sender_public_key) = ?2 AND content = ?3
+                       AND ABS(julianday(timestamp) - julianday(?4)) * 86400 < ?5
+                 )",
+                rusqlite::params![
+                    msg.channel_id,
+                    msg.sender_public_key.to_uppercase(),
+                    msg.content,
+                    msg.timestamp,
+                    BACKFILL_MATCH_SECS,
+                ],
+                |row| row.get(0),
+            )
+            .map_err(|e| format!("Failed to check for backfilled message: {e}"))?;
+        if exists {
+            return Ok(false);
+        }
+        insert_channel_message(&conn, msg)
+    }
+
+    pub fn mark_mentions_read(&self, channel_id: &str) -> Result<(), String> {
+        let conn = self.write_messages()?;
+        conn.execute(
+            "UPDATE channel_messages SET mention_read = 1
+             WHERE channel_id = ?1 AND notify = 1 AND mention_read = 0",
+            rusqlite::params![channel_id],
+        )
+        .map_err(|e| format!("Failed to mark mentions read: {e}"))?;
+        Ok(())
+    }
+
+    // ─── Guild Members ────────────────────────────────────────────────
+
+    /// Record a member of a guild (or refresh their name and last-seen time)
+    pub fn upsert_guild_member(&self, guild_id: &str, public_key: &str, name: &str) -> Result<(), String> {
+        let conn = self.write()?;
+        conn.execute(
+            "INSERT INTO guild_members (guild_id, public_key, name) VALUES (?1, ?2, ?3)
+             ON CONFLICT(guild_id, public_key) DO UPDATE SET name = excluded.name, last_seen = datetime('now')",
+            rusqlite::params![guild_id, public_key.to_uppercase(), name],
+        )
+        .map_err(|e| format!("Failed to upsert guild member: {e}"))?;
+        Ok(())
+    }
+
+    /// Whether `identity_key`, the Tox key that signed a member's message, is
+    /// the identity we know for them. The first identity a member signs with
+    /// is kept for them, and only trusted then if it's a friend's.
+    pub fn check_member_identity(&self, guild_id: &str, public_key: &str, identity_key: &str) -> Result<bool, String> {
+        let conn = self.write()?;
+        let (known, is_friend): (Option<String>, bool) = conn
+            .query_row(
+                "SELECT (SELECT identity_key FROM guild_members WHERE guild_id = ?1 AND public_key = ?2),
+                        EXISTS(SELECT 1 FROM friends WHERE public_key = ?3 COLLATE NOCASE)",
+                rusqlite::params![guild_id, public_key.to_uppercase(), identity_key],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .map_err(|e| format!("Failed to query member identity: {e}"))?;
+        if let Some(known) = known {
+            return Ok(known.eq_ignore_ascii_case(identity_key));
+        }
+
+        conn.execute(
+            "UPDATE guild_members SET identity_key = ?3 WHERE guild_id = ?1 AND public_key = ?2",
+            rusqlite::params![guild_id, public_key.to_uppercase(), identity_key.to_uppercase()],
+        )
+        .map_err(|e| format!("Failed to store member identity: {e}"))?;
+        Ok(is_friend)
+    }
+
+    /// Members of the guild a channel belongs to whose name starts with `prefix`
+    /// (case-insensitive), most recently seen first
+    pub fn get_mentionable_members(
+        &self,
+        channel_id: &str,
+        prefix: &str,
+        limit: i64,
+    ) -> Result<Vec<GuildMemberRecord>, String> {
+        let pattern = format!("{}%", prefix.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_"));
+        let conn = self.read()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT m.guild_id, m.public_key, m.name, m.last_seen, m.role
+                 FROM guild_members m JOIN channels c ON c.guild_id = m.guild_id
+                 WHERE c.id = ?1 AND m.name != '' AND m.name LIKE ?2 ESCAPE '\\'
+                 ORDER BY m.last_seen DESC LIMIT ?3",
+            )
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+
+        let members = stmt
+            .query_map(rusqlite::params![channel_id, pattern, limit], |row| {
+                Ok(GuildMemberRecord {
+                    guild_id: row.get(0)?,
+                    public_key: row.get(1)?,
+                    name: row.get(2)?,
+                    last_seen: row.get(3)?,
+                    role: row.get(4)?,
+                })
+            })
+            .map_err(|e| format!("Failed to query guild members: {e}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect guild members: {e}"))?;
+
+        Ok(members)
+    }
+
+    /// Every cached member of a guild, by name
+    pub fn get_guild_members(&self, guild_id: &str) -> Result<Vec<GuildMemberRecord>, String> {
+        let conn = self.read()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT guild_id, public_key, name, last_seen, role FROM guild_members
+                 WHERE guild_id = ?1 ORDER BY name COLLATE NOCASE",
+            )
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+
+        let members = stmt
+            .query_map(rusqlite::params![guild_id], |row| {
+                Ok(GuildMemberRecord {
+                    guild_id: row.get(0)?,
+                    public_key: row.get(1)?,
+                    name: row.get(2)?,
+                    last_seen: row.get(3)?,
+                    role: row.get(4)?,
+                })
+            })
+            .map_err(|e| format!("Failed to query guild members: {e}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect guild members: {e}"))?;
+
+        Ok(members)
+    }
+
+    /// Record a cached member's current role
+    pub fn set_guild_member_role(&self, guild_id: &str, public_key: &str, role: &str) -> Result<(), String> {
+        let conn = self.write()?;
+        conn.execute(
+            "UPDATE guild_members SET role = ?1 WHERE guild_id = ?2 AND public_key = ?3",
+            rusqlite::params![role, guild_id, public_key.to_uppercase()],
+        )
+        .map_err(|e| format!("Failed to update guild member role: {e}"))?;
+        Ok(())
+    }
+
+    /// Refresh a member's last-seen time, e.g. as they leave
+    pub fn touch_guild_member(&self, guild_id: &str, public_key: &str) -> Result<(), String> {
+        let conn = self.write()?;
+        conn.execute(
+            "UPDATE guild_members SET last_seen = datetime('now') WHERE guild_id = ?1 AND public_key = ?2",
+            rusqlite::params![guild_id, public_key.to_uppercase()],
+        )
+        .map_err(|e| format!("Failed to update guild member: {e}"))?;
+        Ok(())
+    }
+
+    /// Ignore a guild member, or stop ignoring them. Their stored channel
+    /// messages are left out of history while they're ignored.
+    pub fn set_guild_member_ignored(&self, guild_id: &str, public_key: &str, ignored: bool) -> Result<(), String> {
+        let conn = self.write()?;
+        if ignored {
+            conn.execute(
+                "INSERT OR IGNORE INTO ignored_guild_members (guild_id, public_key) VALUES (?1, ?2)",
+                rusqlite::params![guild_id, public_key.to_uppercase()],
+            )
+        } else {
+            conn.execute(
+                "DELETE FROM ignored_guild_members WHERE guild_id = ?1 AND public_key = ?2",
+                rusqlite::params![guild_id, public_key.to_uppercase()],
+            )
+        }
+        .map_err(|e| format!("Failed to update ignored member: {e}"))?;
+        Ok(())
+    }
+
+    pub fn is_guild_member_ignored(&self, guild_id: &str, public_key: &str) -> Result<bool, String> {
+        let conn = self.read()?;
+        conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM ignored_guild_members WHERE guild_id = ?1 AND public_key = ?2)",
+            rusqlite::params![guild_id, public_key.to_uppercase()],
+            |row| row.get(0),
+        )
+        .map_err(|e| format!("Failed to query ignored members: {e}"))
+    }
+
+    /// Public keys of the members of a guild we ignore
+    pub fn get_ignored_guild_members(&self, guild_id: &str) -> Result<Vec<String>, String> {
+        let conn = self.read()?;
+        let mut stmt = conn
+            .prepare("SELECT public_key FROM ignored_guild_members WHERE guild_id = ?1 ORDER BY ignored_at")
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+
+        let keys = stmt
+            .query_map(rusqlite::params![guild_id], |row| row.get(0))
+            .map_err(|e| format!("Failed to query ignored members: {e}"))?
+            .collect::<Result<Vec<String>, _>>()
+            .map_err(|e| format!("Failed to collect ignored members: {e}"))?;
+
+        Ok(keys)
+    }
+
+    // ─── Guild Rules ──────────────────────────────────────────────────
+
+    /// Record that we accepted the guild's rules as they are now
+    pub fn accept_guild_rules(&self, guild_id: &str, rules_hash: &str) -> Result<(), String> {
+        let conn = self.write()?;
+        conn.execute(
+            "INSERT INTO guild_rules_acks (guild_id, rules_hash) VALUES (?1, ?2)
+             ON CONFLICT(guild_id) DO UPDATE SET rules_hash = ?2, accepted_at = datetime('now')",
+            rusqlite::params![guild_id, rules_hash],
+        )
+        .map_err(|e| format!("Failed to accept guild rules: {e}"))?;
+        Ok(())
+    }
+
+    /// Hash of the rules we last accepted in a guild, if any
+    pub fn get_accepted_guild_rules(&self, guild_id: &str) -> Result<Option<String>, String> {
+        let conn = self.read()?;
+        let mut stmt = conn
+            .prepare("SELECT rules_hash FROM guild_rules_acks WHERE guild_id = ?1")
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+
+        let mut rows = stmt
+            .query_map(rusqlite::params![guild_id], |row| row.get(0))
+            .map_err(|e| format!("Failed to query accepted guild rules: {e}"))?;
+
+        match rows.next() {
+            Some(Ok(hash)) => Ok(Some(hash)),
+            Some(Err(e)) => Err(format!("Failed to read accepted guild rules: {e}")),
+            None => Ok(None),
+        }
+    }
+
+    // ─── Polls ────────────────────────────────────────────────────────
+
+    /// Store a poll. A poll we already have is left alone.
+    pub fn insert_poll(&self, poll: &PollRecord) -> Result<(), String> {
+        let options = serde_json::to_string(&poll.options).map_err(|e| format!("Failed to serialize poll options: {e}"))?;
+        let conn = self.write()?;
+        conn.execute(
+            "INSERT OR IGNORE INTO polls
+             (id, channel_id, creator_public_key, creator_name, question, options, closes_at, closed)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            rusqlite::params![
+                poll.id,
+                poll.channel_id,
+                poll.creator_public_key.to_uppercase(),
+                poll.creator_name,
+                poll.question,
+                options,
+                poll.closes_at,
+                poll.closed,
+            ],
+        )
+        .map_err(|e| format!("Failed to insert poll: {e}"))?;
+        Ok(())
+    }
+
+    pub fn get_poll(&self, id: &str) -> Result<Option<PollRecord>, String> {
+        let conn = self.read()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, channel_id, creator_public_key, creator_name, question, options, closes_at, closed
+                 FROM polls WHERE id = ?1",
+            )
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+
+        let mut rows = stmt
+            .query_map(rusqlite::params![id], |row| {
+                let options: String = row.get(5)?;
+                Ok(PollRecord {
+                    id: row.get(0)?,
+                    channel_id: row.get(1)?,
+                    creator_public_key: row.get(2)?,
+                    creator_name: row.get(3)?,
+                    question: row.get(4)?,
+                    options: serde_json::from_str(&options).unwrap_or_default(),
+                    closes_at: row.get(6)?,
+                    closed: row.get(7)?,
+                })
+            })
+            .map_err(|e| format!("Failed to query poll: {e}"))?;
+
+        match rows.next() {
+            Some(Ok(poll)) => Ok(Some(poll)),
+            Some(Err(e)) => Err(format!("Failed to read poll: {e}")),
+            None => Ok(None),
+        }
+    }
+
+    /// Record a member's vote, replacing any earlier one
+    pub fn set_poll_vote(&self, poll_id: &str, voter_public_key: &str, option: u32) -> Result<(), String> {
+        let conn = self.write()?;
+        conn.execute(
+            "INSERT OR REPLACE INTO poll_votes (poll_id, voter_public_key, option_index, voted_at)
+             VALUES (?1, ?2, ?3, datetime('now'))",
+            rusqlite::params![poll_id, voter_public_key.to_uppercase(), option],
+        )
+        .map_err(|e| format!("Failed to record vote: {e}"))?;
+        Ok(())
+    }
+
+    pub fn get_poll_vote(&self, poll_id: &str, voter_public_key: &str) -> Result<Option<u32>, String> {
+        let conn = self.read()?;
+        let mut stmt = conn
+            .prepare("SELECT option_index FROM poll_votes WHERE poll_id = ?1 AND voter_public_key = ?2")
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+
+        let mut rows = stmt
+            .query_map(rusqlite::params![poll_id, voter_public_key.to_uppercase()], |row| row.get(0))
+            .map_err(|e| format!("Failed to query vote: {e}"))?;
+
+        match rows.next() {
+            Some(Ok(option)) => Ok(Some(option)),
+            Some(Err(e)) => Err(format!("Failed to read vote: {e}")),
+            None => Ok(None),
+        }
+    }
+
+    /// Vote count for each of a poll's `option_count` options
+    pub fn get_poll_tallies(&self, poll_id: &str, option_count: usize) -> Result<Vec<i64>, String> {
+        let conn = self.read()?;
+        let mut stmt = conn
+            .prepare("SELECT option_index, COUNT(*) FROM poll_votes WHERE poll_id = ?1 GROUP BY option_index")
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+
+        let counts = stmt
+            .query_map(rusqlite::params![poll_id], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?)))
+            .map_err(|e| format!("Failed to query tallies: {e}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect tallies: {e}"))?;
+
+        let mut tallies = vec![0; option_count];
+        for (option, count) in counts {
+            if let Some(tally) = usize::try_from(option).ok().and_then(|i| tallies.get_mut(i)) {
+                *tally = count;
+            }
+        }
+        Ok(tallies)
+    }
+
+    /// Close open polls whose time is up. Returns their IDs.
+    pub fn close_due_polls(&self, now: i64) -> Result<Vec<String>, String> {
+        let conn = self.write()?;
+        let mut stmt = conn
+            .prepare("SELECT id FROM polls WHERE closed = 0 AND closes_at <= ?1")
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+
+        let ids = stmt
+            .query_map(rusqlite::params![now], |row| row.get(0))
+            .map_err(|e| format!("Failed to query polls: {e}"))?
+            .collect::<Result<Vec<String>, _>>()
+            .map_err(|e| format!("Failed to collect polls: {e}"))?;
+
+        conn.execute(
+            "UPDATE polls SET closed = 1 WHERE closed = 0 AND closes_at <= ?1",
+            rusqlite::params![now],
+        )
+        .map_err(|e| format!("Failed to close polls: {e}"))?;
+        Ok(ids)
+    }
+
+    // ─── Guild Events ─────────────────────────────────────────────────
+
+    /// Store an event. An event we already have is left alone.
+    pub fn insert_guild_event(&self, event: &GuildEventRecord) -> Result<(), String> {
+        let conn = self.write()?;
+        conn.execute(
+            "INSERT OR IGNORE INTO guild_events
+             (id, guild_id, creator_public_key, creator_name, title, description, start_time)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            rusqlite::params![
+                event.id,
+                event.guild_id,
+                event.creator_public_key.to_uppercase(),
+                event.creator_name,
+                event.title,
+                event.description,
+                event.start_time,
+            ],
+        )
+        .map_err(|e| format!("Failed to insert guild event: {e}"))?;
+        Ok(())
+    }
+
+    pub fn get_guild_event(&self, id: &str) -> Result<Option<GuildEventRecord>, String> {
+        let conn = self.read()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, guild_id, creator_public_key, creator_name, title, description, start_time
+                 FROM guild_events WHERE id = ?1",
+            )
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+
+        let mut rows = stmt
+            .query_map(rusqlite::params![id], Self::map_guild_event)
+            .map_err(|e| format!("Failed to query guild event: {e}"))?;
+
+        match rows.next() {
+            Some(Ok(event)) => Ok(Some(event)),
+            Some(Err(e)) => Err(format!("Failed to read guild event: {e}")),
+            None => Ok(None),
+        }
+    }
+
+    /// Events of a guild starting at or after `since` (Unix seconds), soonest first
+    pub fn get_guild_events(&self, guild_id: &str, since: i64) -> Result<Vec<GuildEventRecord>, String> {
+        let conn = self.read()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, guild_id, creator_public_key, creator_name, title, description, start_time
+                 FROM guild_events WHERE guild_id = ?1 AND start_time >= ?2
+                 ORDER BY start_time ASC",
+            )
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+
+        let events = stmt
+            .query_map(rusqlite::params![guild_id, since], Self::map_guild_event)
+            .map_err(|e| format!("Failed to query guild events: {e}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect guild events: {e}"))?;
+        Ok(events)
+    }
+
+    fn map_guild_event(row: &rusqlite::Row) -> rusqlite::Result<GuildEventRecord> {
+        Ok(GuildEventRecord {
+            id: row.get(0)?,
+            guild_id: row.get(1)?,
+            creator_public_key: row.get(2)?,
+            creator_name: row.get(3)?,
+            title: row.get(4)?,
+            description: row.get(5)?,
+            start_time: row.get(6)?,
+        })
+    }
+
+    /// Record a member's RSVP, replacing any earlier one
+    pub fn set_event_rsvp(&self, event_id: &str, public_key: &str, status: &str) -> Result<(), String> {
+        let conn = self.write()?;
+        conn.execute(
+            "INSERT OR REPLACE INTO event_rsvps (event_id, public_key, status, updated_at)
+             VALUES (?1, ?2, ?3, datetime('now'))",
+            rusqlite::params![event_id, public_key.to_uppercase(), status],
+        )
+        .map_err(|e| format!("Failed to record RSVP: {e}"))?;
+        Ok(())
+    }
+
+    /// RSVPs to an event as (public key, status)
+    pub fn get_event_rsvps(&self, event_id: &str) -> Result<Vec<(String, String)>, String> {
+        let conn = self.read()?;
+        let mut stmt = conn
+            .prepare("SELECT public_key, status FROM event_rsvps WHERE event_id = ?1 ORDER BY updated_at ASC")
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+
+        let rsvps = stmt
+            .query_map(rusqlite::params![event_id], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(|e| format!("Failed to query RSVPs: {e}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect RSVPs: {e}"))?;
+        Ok(rsvps)
+    }
+
+    /// Events starting within `lead` seconds of `now` (Unix seconds) that
+    /// haven't been reminded of yet, marked as reminded. Events that already
+    /// started are skipped.
+    pub fn take_due_event_reminders(&self, now: i64, lead: i64) -> Result<Vec<GuildEventRecord>, String> {
+        let conn = self.write()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, guild_id, creator_public_key, creator_name, title, description, start_time
+                 FROM guild_events WHERE reminded = 0 AND start_time > ?1 AND start_time <= ?1 + ?2",
+            )
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+
+        let events = stmt
+            .query_map(rusqlite::params![now, lead], Self::map_guild_event)
+            .map_err(|e| format!("Failed to query guild events: {e}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect guild events: {e}"))?;
+
+        conn.execute(
+            "UPDATE guild_events SET reminded = 1 WHERE reminded = 0 AND start_time > ?1 AND start_time <= ?1 + ?2",
+            rusqlite::params![now, lead],
+        )
+        .map_err(|e| format!("Failed to mark event reminders: {e}"))?;
+        Ok(events)
+    }
+
+    // ─── Audit Log ────────────────────────────────────────────────────
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_audit_entry(
+        &self,
+        guild_id: &str,
+        action: &str,
+        actor_public_key: &str,
+        actor_name: &str,
+        target_public_key: Option<&str>,
+        target_name: &str,
+        details: &str,
+    ) -> Result<(), String> {
+        let conn = self.write()?;
+        conn.execute(
+            "INSERT INTO audit_log
+             (guild_id, action, actor_public_key, actor_name, target_public_key, target_name, details)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            rusqlite::params![
+                guild_id,
+                action,
+                actor_public_key.to_uppercase(),
+                actor_name,
+                target_public_key.map(str::to_uppercase),
+                target_name,
+                details,
+            ],
+        )
+        .map_err(|e| format!("Failed to add audit log entry: {e}"))?;
+        Ok(())
+    }
+
+    /// Newest entries first. Pass the last `id` seen as `before_id` for the next page.
+    pub fn get_audit_log(&self, guild_id: &str, limit: i64, before_id: Option<i64>) -> Result<Vec<AuditLogRecord>, String> {
+        let conn = self.read()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, guild_id, action, actor_public_key, actor_name, target_public_key,
+                        target_name, details, created_at
+                 FROM audit_log WHERE guild_id = ?1 AND id < ?2
+                 ORDER BY id DESC LIMIT ?3",
+            )
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+
+        let entries = stmt
+            .query_map(rusqlite::params![guild_id, before_id.unwrap_or(i64::MAX), limit], |row| {
+                Ok(AuditLogRecord {
+                    id: row.get(0)?,
+                    guild_id: row.get(1)?,
+                    action: row.get(2)?,
+                    actor_public_key: row.get(3)?,
+                    actor_name: row.get(4)?,
+                    target_public_key: row.get(5)?,
+                    target_name: row.get(6)?,
+                    details: row.get(7)?,
+                    created_at: row.get(8)?,
+                })
+            })
+            .map_err(|e| format!("Failed to query audit log: {e}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect audit log: {e}"))?;
+
+        Ok(entries)
+    }
+
+    /// Details of the newest entry with this action, e.g. the last recorded topic
+    pub fn get_last_audit_details(&self, guild_id: &str, action: &str) -> Result<Option<String>, String> {
+        let conn = self.read()?;
+        let mut stmt = conn
+            .prepare("SELECT details FROM audit_log WHERE guild_id = ?1 AND action = ?2 ORDER BY id DESC LIMIT 1")
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+
+        let mut rows = stmt
+            .query_map(rusqlite::params![guild_id, action], |row| row.get(0))
+            .map_err(|e| format!("Failed to query audit log: {e}"))?;
+
+        match rows.next() {
+            Some(Ok(details)) => Ok(Some(details)),
+            Some(Err(e)) => Err(format!("Failed to read audit log: {e}")),
+            None => Ok(None),
+        }
+    }
+
+    // ─── Dictionary ───────────────────────────────────────────────────
+
+    /// Add a word to the custom spellcheck dictionary (case-insensitive, no-op if present)
+    pub fn add_dictionary_word(&self, word: &str) -> Result<(), String> {
+        let conn = self.write()?;
+        conn.execute(
+            "INSERT OR IGNORE INTO dictionary_words (word) VALUES (?1)",
+            rusqlite::params![word],
+        )
+        .map_err(|e| format!("Failed to add dictionary word: {e}"))?;
+        Ok(())
+    }
+
+    /// Remove a word from the custom dictionary. Returns false if it wasn't there.
+    pub fn remove_dictionary_word(&self, word: &str) -> Result<bool, String> {
+        let conn = self.write()?;
+        let removed = conn
+            .execute(
+                "DELETE FROM dictionary_words WHERE word = ?1",
+                rusqlite::params![word],
+            )
+            .map_err(|e| format!("Failed to remove dictionary word: {e}"))?;
+        Ok(removed > 0)
+    }
+
+    pub fn get_dictionary_words(&self) -> Result<Vec<String>, String> {
+        let conn = self.read()?;
+        let mut stmt = conn
+            .prepare("SELECT word FROM dictionary_words ORDER BY word")
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+
+        let words = stmt
+            .query_map([], |row| row.get(0))
+            .map_err(|e| format!("Failed to query dictionary: {e}"))?
+            .collect::<Result<Vec<String>, _>>()
+            .map_err(|e| format!("Failed to collect dictionary: {e}"))?;
+
+        Ok(words)
+    }
+
+    // ─── Guild Directory ──────────────────────────────────────────────
+
+    /// Add a public guild to the directory, replacing one with the same chat ID
+    pub fn add_directory_guild(&self, entry: &DirectoryEntry) -> Result<(), String> {
+        let conn = self.write()?;
+        conn.execute(
+            "INSERT INTO guild_directory (chat_id, name, description) VALUES (?1, ?2, ?3)
+             ON CONFLICT(chat_id) DO UPDATE SET name = excluded.name, description = excluded.description",
+            rusqlite::params![entry.chat_id, entry.name, entry.description],
+        )
+        .map_err(|e| format!("Failed to add guild to directory: {e}"))?;
+        Ok(())
+    }
+
+    /// Returns false if the guild wasn't in the directory
+    pub fn remove_directory_guild(&self, chat_id: &str) -> Result<bool, String> {
+        let conn = self.write()?;
+        let removed = conn
+            .execute(
+                "DELETE FROM guild_directory WHERE chat_id = ?1",
+                rusqlite::params![chat_id.to_ascii_uppercase()],
+            )
+            .map_err(|e| format!("Failed to remove guild from directory: {e}"))?;
+        Ok(removed > 0)
+    }
+
+    /// Guilds the user added to the directory
+    pub fn get_directory_guilds(&self) -> Result<Vec<DirectoryEntry>, String> {
+        let conn = self.read()?;
+        let mut stmt = conn
+            .prepare("SELECT chat_id, name, description FROM guild_directory ORDER BY added_at")
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+
+        let entries = stmt
+            .query_map([], |row| {
+                Ok(DirectoryEntry {
+                    chat_id: row.get(0)?,
+                    name: row.get(1)?,
+                    description: row.get(2)?,
+                    curated: false,
+                })
+            })
+            .map_err(|e| format!("Failed to query guild directory: {e}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect guild directory: {e}"))?;
+
+        Ok(entries)
+    }
+}
+
+impl Drop for MessageStore {
+    fn drop(&mut self) {
+        if let Err(e) = self.flush_writes() {
+            warn!("Failed to write queued messages: {e}");
+        }
+    }
+}
+
+/// Whether the file at `path` is an SQLite database without SQLCipher encryption
+fn is_plaintext_database(path: &Path) -> bool {
+    let mut header = [0u8; 16];
+    std::fs::File::open(path)
+        .and_then(|mut file| file.read_exact(&mut header))
+        .is_ok_and(|()| &header == PLAINTEXT_HEADER)
+}
+
+/// Encrypt a database created before SQLCipher keys were set, in place. It's
+/// exported into a new encrypted file which then replaces the original.
+fn encrypt_legacy_database(path: &Path, key: &str) -> Result<(), String> {
+    info!("Encrypting legacy database at {}", path.display());
+    let encrypted_path = path.with_extension("db.encrypting");
+    let _ = std::fs::remove_file(&encrypted_path);
+
+    let export = || -> rusqlite::Result<()> {
+        let conn = Connection::open(path)?;
+        conn.execute(
+            "ATTACH DATABASE ?1 AS encrypted KEY ?2",
+            rusqlite::params![encrypted_path.to_string_lossy(), key],
+        )?;
+        conn.query_row("SELECT sqlcipher_export('encrypted')", [], |_| Ok(()))?;
+        // The schema version isn't part of the export
+        let version: i32 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+        conn.pragma_update(Some(DatabaseName::Attached("encrypted")), "user_version", version)?;
+        conn.execute_batch("DETACH DATABASE encrypted;")?;
+        // Closing folds the WAL back into the original
+        conn.close().map_err(|(_, e)| e)
+    };
+    if let Err(e) = export() {
+        let _ = std::fs::remove_file(&encrypted_path);
+        return Err(format!("Failed to encrypt legacy database: {e}"));
+    }
+
+    std::fs::rename(&encrypted_path, path).map_err(|e| format!("Failed to replace legacy database: {e}"))?;
+    info!("Legacy database encrypted");
+    Ok(())
+}
+
+/// Write queued rows in one transaction. Nothing is written if it fails.
+fn write_rows(conn: &mut Connection, queued: &[QueuedWrite]) -> Result<(), String> {
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("Failed to begin transaction: {e}"))?;
+    for write in queued {
+        // A failed row (e.g. a duplicate id) doesn't hold back the rest
+        let result = match write {
+            QueuedWrite::Direct(msg, call_session) => insert_direct_message(&tx, msg).and_then(|()| {
+                let Some(session_id) = call_session else {
+                    return Ok(());
+                };
+                tx.execute(
+                    "UPDATE direct_messages SET call_session_id = ?2 WHERE id = ?1",
+                    rusqlite::params![msg.id, session_id],
+                )
+                .map(|_| ())
+                .map_err(|e| format!("Failed to tag message with call: {e}"))
+            }),
+            QueuedWrite::Channel(msg) => insert_channel_message(&tx, msg).map(|_| ()),
+        };
+        if let Err(e) = result {
+            warn!("{e}");
+        }
+    }
+    tx.commit()
+        .map_err(|e| format!("Failed to commit queued messages: {e}"))?;
+    Ok(())
+}
+
+fn insert_direct_message(conn: &Connection, msg: &DirectMessageRecord) -> Result<(), String> {
+    conn.execute(
+        "INSERT INTO direct_messages (id, friend_number, sender, content, message_type, timestamp, is_outgoing, delivered, read, plain_content, expires_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+        rusqlite::params![
+            msg.id,
+            msg.friend_number,
+            msg.sender,
+            msg.content,
+            msg.message_type,
+            msg.timestamp,
+            msg.is_outgoing,
+            msg.delivered,
+            msg.read,
+            markdown::to_plain_text(&msg.content),
+            msg.expires_at,
+        ],
+    )
+    .map_err(|e| format!("Failed to insert message: {e}"))?;
+    Ok(())
+}
+
+fn read_guild_document(conn: &Connection, guild_id: &str) -> Result<GuildDocument, String> {
+    let doc: Option<Vec<u8>> = conn
+        .query_row(
+            "SELECT metadata_doc FROM guilds WHERE id = ?1",
+            rusqlite::params![guild_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| format!("Failed to get guild metadata: {e}"))?;
+    Ok(doc
+        .and_then(|doc| serde_json::from_slice(&doc).ok())
+        .unwrap_or_default())
+}
+
+fn write_guild_document(conn: &Connection, guild_id: &str, doc: &GuildDocument) -> Result<(), String> {
+    let doc = serde_json::to_vec(doc).map_err(|e| format!("Failed to serialize guild metadata: {e}"))?;
+    conn.execute(
+        "UPDATE guilds SET metadata_doc = ?1, last_synced = datetime('now') WHERE id = ?2",
+        rusqlite::params![doc, guild_id],
+    )
+    .map_err(|e| format!("Failed to save guild metadata: {e}"))?;
+    Ok(())
+}
+
+/// The messages around `anchor` in one conversation. `conversation_sql`
+/// selects the conversation's messages, taking the conversation as `?1`;
+/// `key` reads a message's timestamp and id.
+fn messages_around<T>(
+    conn: &Connection,
+    conversation_sql: &str,
+    conversation: &dyn rusqlite::types::ToSql,
+    anchor: &MessageAnchor,
+    context: i64,
+    map: fn(&rusqlite::Row) -> rusqlite::Result<T>,
+    key: fn(&T) -> (&String, &String),
+) -> Result<Option<MessageWindow<T>>, String> {
+    let context = context.max(0);
+    let query = |sql: String, params: &[&dyn rusqlite::types::ToSql]| -> Result<Vec<T>, String> {
+        let mut stmt = conn
+            .prepare(&sql)
+            .map_err(|e| format!("Failed to prepare query: {e}"))?;
+        let messages = stmt
+            .query_map(params, map)
+            .map_err(|e| format!("Failed to query messages: {e}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect messages: {e}"));
+        messages
+    };
+
+    // A date starts before every message at that moment
+    let start = match anchor {
+        MessageAnchor::Message(id) => {
+            let found = query(format!("SELECT * FROM ({conversation_sql}) WHERE id = ?2"), &[conversation, id])?;
+            let Some(message) = found.first() else {
+                return Ok(None);
+            };
+            let (timestamp, id) = key(message);
+            MessageCursor { timestamp: timestamp.clone(), id: id.clone() }
+        }
+        MessageAnchor::Timestamp(timestamp) => MessageCursor { timestamp: timestamp.clone(), id: String::new() },
+    };
+
+    // The target and what follows it, oldest first, and what precedes it,
+    // newest first. One extra row each way tells us whether there are more.
+    let mut newer = query(
+        format!(
+            "SELECT * FROM ({conversation_sql})
+             WHERE timestamp > ?2 OR (timestamp = ?2 AND id >= ?3)
+             ORDER BY timestamp ASC, id ASC LIMIT ?4"
+        ),
+        &[conversation, &start.timestamp, &start.id, &(context + 2)],
+    )?;
+    let mut older = query(
+        format!(
+            "SELECT * FROM ({conversation_sql})
+             WHERE timestamp < ?2 OR (timestamp = ?2 AND id < ?3)
+             ORDER BY timestamp DESC, id DESC LIMIT ?4"
+        ),
+        &[conversation, &start.timestamp, &start.id, &(context + 1)],
+    )?;
+
+    let has_newer = newer.len() as i64 > context + 1;
+    newer.truncate(context as usize + 1);
+    let has_older = older.len() as i64 > context;
+    older.truncate(context as usize);
+
+    // A date after the last message lands on the last message
+    let target_id = newer.first().or(older.first()).map(|m| key(m).1.clone());
+    newer.reverse();
+    newer.append(&mut older);
+    Ok(Some(MessageWindow {
+        messages: newer,
+        target_id,
+        has_older,
+        has_newer,
+    }))
+}
+
+fn insert_channel_message(conn: &Connection, msg: &ChannelMessageRecord) -> Result<bool, String> {
+    let inserted = conn.execute(
+        "INSERT OR IGNORE INTO channel_messages (id, channel_id, sender_public_key, sender_name, content, message_type, timestamp, plain_content, mentions_me, filtered, backfilled, sender_verified, notify, signer_public_key, signed_route, signature_tag)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)",
+        rusqlite::params![
+            msg.id,
+            msg.channel_id,
+            msg.sender_public_key,
+            msg.sender_name,
+            msg.content,
+            msg.message_type,
+            msg.timestamp,
+            markdown::to_plain_text(&msg.content),
+            msg.mentions_me,
+            msg.filtered,
+            msg.backfilled,
+            msg.sender_verified,
+            msg.notify,
+            msg.signer_public_key,
+            msg.signature.as_ref().map(|s| &s.route),
+            msg.signature.as_ref().map(|s| &s.tag),
+        ],
+    )
+    .map_err(|e| format!("Failed to insert channel message: {e}"))?;
+    Ok(inserted > 0)
+}
+
+/// A kept signature from its route and tag columns, starting at `index`
+fn signature_from_row(row: &rusqlite::Row, index: usize) -> rusqlite::Result<Option<MessageSignature>> {
+    let route: Option<String> = row.get(index)?;
+    let tag: Option<String> = row.get(index + 1)?;
+    Ok(route.zip(tag).map(|(route, tag)| MessageSignature { route, tag }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(id: &str) -> DirectMessageRecord {
+        DirectMessageRecord {
+            id: id.to_string(),
+            friend_number: 0,
+            sender: "friend".to_string(),
+            content: "hello".to_string(),
+            message_type: "normal".to_string(),
+            timestamp: "2024-05-10 12:00:00".to_string(),
+            is_outgoing: false,
+            delivered: true,
+            read: false,
+            expires_at: None,
+        }
+    }
+
+    #[test]
+    fn test_failed_flush_keeps_queue() {
+        let dir = std::env::temp_dir().join(format!("toxcord-test-{}", uuid::Uuid::new_v4()));
+        let store = MessageStore::open(&dir.join("messages.db"), "").unwrap();
+        store.upsert_friend(0, "key", "friend", "").unwrap();
+        store.queue_direct_message(message("a"), None).unwrap();
+        store.queue_direct_message(message("b"), None).unwrap();
+
+        // A deferred foreign key that's never satisfied fails the commit
+        store
+            .write()
+            .unwrap()
+            .execute_batch(
+                "CREATE TEMP TABLE parent (id INTEGER PRIMARY KEY);
+                 CREATE TEMP TABLE child (parent_id INTEGER REFERENCES parent(id) DEFERRABLE INITIALLY DEFERRED);
+                 CREATE TEMP TRIGGER fail_commit AFTER INSERT ON main.direct_messages
+                 BEGIN INSERT INTO child VALUES (1); END;",
+            )
+            .unwrap();
+        assert!(store.flush_writes().is_err());
+
+        store.write().unwrap().execute_batch("DROP TRIGGER fail_commit;").unwrap();
+        store.queue_direct_message(message("c"), None).unwrap();
+        assert_eq!(store.flush_writes(), Ok(3));
+        assert_eq!(store.get_direct_messages(0, 10, None).unwrap().len(), 3);
+
+        drop(store);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_friend_changes_bump_revision() {
+        let dir = std::env::temp_dir().join(format!("toxcord-test-{}", uuid::Uuid::new_v4()));
+        let store = MessageStore::open(&dir.join("messages.db"), "").unwrap();
+        store.upsert_friend(0, "key", "friend", "").unwrap();
+
+        let revision = store.friends_revision().unwrap();
+        store.set_auto_accept_group_invites(0, true).unwrap();
+        let changes = store.friends_changed_since(revision).unwrap();
+        assert_eq!(changes.changed, [0]);
+        assert!(changes.revision > revision);
+
+        // Writing the same value again isn't a change
+        store.set_auto_accept_group_invites(0, true).unwrap();
+        assert_eq!(store.friends_revision().unwrap(), changes.revision);
+
+        drop(store);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_open_with_wrong_password() {
+        let dir = std::env::temp_dir().join(format!("toxcord-test-{}", uuid::Uuid::new_v4()));
+        let path = dir.join("messages.db");
+        drop(MessageStore::open(&path, "right").unwrap());
+
+        assert!(matches!(MessageStore::open(&path, "wrong"), Err(OpenError::WrongPassword)));
+        assert!(matches!(MessageStore::open(&path, ""), Err(OpenError::PasswordNeeded)));
+        assert!(MessageStore::open(&path, "right").is_ok());
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -1,4 +1,6 @@
 pub mod schema;
 pub mod message_store;
+pub mod pool;
+pub mod presence;
 
 pub use message_store::MessageStore;
@@ -0,0 +1,116 @@
+//! Read-only connections for `MessageStore`.
+//!
+//! The database is in WAL mode, so readers see the last committed state
+//! without waiting for the writer or for each other. Connections are opened
+//! as needed and up to `MAX_IDLE` are kept for reuse.
+
+use std::ops::Deref;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use rusqlite::{Connection, OpenFlags};
+
+/// Most idle connections kept open
+const MAX_IDLE: usize = 4;
+
+pub(crate) struct ReadPool {
+    path: PathBuf,
+    key: Mutex<String>,
+    /// Idle connections with the generation they were opened in
+    idle: Mutex<Vec<(u64, Connection)>>,
+    /// Bumped when the key changes; connections from older generations are closed
+    generation: AtomicU64,
+}
+
+impl ReadPool {
+    pub fn new(path: PathBuf, key: &str) -> Self {
+        Self {
+            path,
+            key: Mutex::new(key.to_string()),
+            idle: Mutex::new(Vec::new()),
+            generation: AtomicU64::new(0),
+        }
+    }
+
+    pub fn get(&self) -> Result<PooledConnection<'_>, String> {
+        let generation = self.generation.load(Ordering::SeqCst);
+        let idle = self.idle.lock().map_err(|e| e.to_string())?.pop();
+        let conn = match idle {
+            Some((opened_in, conn)) if opened_in == generation => conn,
+            _ => self.open()?,
+        };
+        Ok(PooledConnection {
+            pool: self,
+            generation,
+            conn: Some(conn),
+        })
+    }
+
+    fn open(&self) -> Result<Connection, String> {
+        let conn = Connection::open_with_flags(
+            &self.path,
+            OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX | OpenFlags::SQLITE_OPEN_URI,
+        )
+        .map_err(|e| format!("Failed to open database for reading: {e}"))?;
+
+        let key = self.key.lock().map_err(|e| e.to_string())?.clone();
+        if !key.is_empty() {
+            conn.pragma_update(None, "key", &key)
+                .map_err(|e| format!("Failed to set encryption key: {e}"))?;
+        }
+        conn.execute_batch("PRAGMA cache_size = -8000;")
+            .map_err(|e| format!("Failed to set pragmas: {e}"))?;
+        Ok(conn)
+    }
+
+    /// Close idle connections, e.g. before the writer changes the journal mode
+    pub fn close_idle(&self) {
+        if let Ok(mut idle) = self.idle.lock() {
+            idle.clear();
+        }
+    }
+
+    /// Open connections with a new key from now on
+    pub fn set_key(&self, key: &str) {
+        if let Ok(mut current) = self.key.lock() {
+            *current = key.to_string();
+        }
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        self.close_idle();
+    }
+
+    fn put_back(&self, generation: u64, conn: Connection) {
+        if generation != self.generation.load(Ordering::SeqCst) {
+            return;
+        }
+        if let Ok(mut idle) = self.idle.lock() {
+            if idle.len() < MAX_IDLE {
+                idle.push((generation, conn));
+            }
+        }
+    }
+}
+
+/// A read-only connection, returned to the pool when dropped
+pub(crate) struct PooledConnection<'a> {
+    pool: &'a ReadPool,
+    generation: u64,
+    conn: Option<Connection>,
+}
+
+impl Deref for PooledConnection<'_> {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        self.conn.as_ref().expect("connection is only taken on drop")
+    }
+}
+
+impl Drop for PooledConnection<'_> {
+    fn drop(&mut self) {
+        if let Some(conn) = self.conn.take() {
+            self.pool.put_back(self.generation, conn);
+        }
+    }
+}
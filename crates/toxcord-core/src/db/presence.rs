@@ -0,0 +1,168 @@
+//! Friend presence summaries built from the presence_log sessions.
+//!
+//! Session times are SQLite `datetime('now')` strings (UTC, `YYYY-MM-DD HH:MM:SS`).
+
+use chrono::{DateTime, Duration, NaiveDateTime, TimeZone, Timelike, Utc};
+
+use super::message_store::PresenceSessionRecord;
+
+/// Number of days covered by the online pattern
+pub const PATTERN_DAYS: i64 = 7;
+
+const DB_TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct PresenceSummary {
+    /// When the friend was last online (None if they are online now or never were)
+    pub last_seen: Option<String>,
+    /// Start of the current session if the friend is online
+    pub online_since: Option<String>,
+    /// Length of the current session in seconds
+    pub session_secs: Option<i64>,
+    /// Seconds online per UTC day, oldest first, ending today
+    pub days: Vec<DailyPresence>,
+    /// Seconds online per UTC hour of day over the same period
+    pub hours: Vec<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct DailyPresence {
+    /// `YYYY-MM-DD`
+    pub date: String,
+    pub online_secs: i64,
+}
+
+/// Parse a timestamp stored with SQLite's `datetime('now')`
+pub fn parse_db_time(s: &str) -> Option<DateTime<Utc>> {
+    NaiveDateTime::parse_from_str(s, DB_TIME_FORMAT)
+        .ok()
+        .map(|t| Utc.from_utc_datetime(&t))
+}
+
+/// Summarize a friend's sessions up to `now`. `last_seen` is the friend's
+/// last_seen column, used when no closed session is on record.
+pub fn summarize(
+    sessions: &[PresenceSessionRecord],
+    last_seen: Option<&str>,
+    online: bool,
+    now: DateTime<Utc>,
+) -> PresenceSummary {
+    let today = now.date_naive();
+    let start = Utc.from_utc_datetime(
+        &(today - Duration::days(PATTERN_DAYS - 1))
+            .and_hms_opt(0, 0, 0)
+            .unwrap_or_default(),
+    );
+
+    let mut days: Vec<DailyPresence> = (0..PATTERN_DAYS)
+        .map(|i| DailyPresence {
+            date: (start + Duration::days(i)).format("%Y-%m-%d").to_string(),
+            online_secs: 0,
+        })
+        .collect();
+    let mut hours = vec![0i64; 24];
+
+    let mut online_since = None;
+    let mut last_ended: Option<DateTime<Utc>> = last_seen.and_then(parse_db_time);
+
+    for session in sessions {
+        let Some(started) = parse_db_time(&session.started_at) else {
+            continue;
+        };
+        let ended = match session.ended_at.as_deref() {
+            Some(ended_at) => {
+                let Some(ended) = parse_db_time(ended_at) else {
+                    continue;
+                };
+                if last_ended.is_none_or(|t| ended > t) {
+                    last_ended = Some(ended);
+                }
+                ended
+            }
+            None => {
+                online_since = Some(started);
+                now
+            }
+        };
+
+        // Split the part inside the pattern window into hour-aligned pieces
+        let mut t = started.max(start);
+        let end = ended.min(now);
+        while t < end {
+            let hour_start = t.date_naive().and_hms_opt(t.hour(), 0, 0).unwrap_or_default();
+            let piece_end = (Utc.from_utc_datetime(&hour_start) + Duration::hours(1)).min(end);
+            let secs = (piece_end - t).num_seconds();
+            let day = (t.date_naive() - start.date_naive()).num_days() as usize;
+            if let Some(d) = days.get_mut(day) {
+                d.online_secs += secs;
+            }
+            hours[t.hour() as usize] += secs;
+            t = piece_end;
+        }
+    }
+
+    let online_since = online_since.filter(|_| online);
+    PresenceSummary {
+        last_seen: if online_since.is_some() {
+            None
+        } else {
+            last_ended.map(|t| t.format(DB_TIME_FORMAT).to_string())
+        },
+        session_secs: online_since.map(|t| (now - t).num_seconds().max(0)),
+        online_since: online_since.map(|t| t.format(DB_TIME_FORMAT).to_string()),
+        days,
+        hours,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(started_at: &str, ended_at: Option<&str>) -> PresenceSessionRecord {
+        PresenceSessionRecord {
+            started_at: started_at.to_string(),
+            ended_at: ended_at.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_summarize_sessions() {
+        let now = parse_db_time("2024-05-10 12:30:00").unwrap();
+        let sessions = [
+            // Only the part from the start of the window counts
+            session("2024-05-03 23:00:00", Some("2024-05-04 00:30:00")),
+            session("2024-05-09 22:45:00", Some("2024-05-10 01:15:00")),
+            session("2024-05-10 12:00:00", None),
+        ];
+
+        let summary = summarize(&sessions, None, true, now);
+        assert_eq!(summary.days.len(), PATTERN_DAYS as usize);
+        assert_eq!(summary.days[0].date, "2024-05-04");
+        assert_eq!(summary.days[0].online_secs, 30 * 60);
+        assert_eq!(summary.days[5].online_secs, 75 * 60);
+        assert_eq!(summary.days[6].date, "2024-05-10");
+        assert_eq!(summary.days[6].online_secs, 75 * 60 + 30 * 60);
+        assert_eq!(summary.hours[0], 30 * 60 + 60 * 60);
+        assert_eq!(summary.hours[22], 15 * 60);
+        assert_eq!(summary.hours[12], 30 * 60);
+        assert_eq!(summary.online_since.as_deref(), Some("2024-05-10 12:00:00"));
+        assert_eq!(summary.session_secs, Some(30 * 60));
+        assert_eq!(summary.last_seen, None);
+    }
+
+    #[test]
+    fn test_summarize_offline() {
+        let now = parse_db_time("2024-05-10 12:30:00").unwrap();
+        let sessions = [session("2024-05-10 08:00:00", Some("2024-05-10 09:00:00"))];
+
+        let summary = summarize(&sessions, Some("2024-05-09 10:00:00"), false, now);
+        assert_eq!(summary.last_seen.as_deref(), Some("2024-05-10 09:00:00"));
+        assert_eq!(summary.online_since, None);
+        assert_eq!(summary.session_secs, None);
+
+        let summary = summarize(&[], Some("2024-05-09 10:00:00"), false, now);
+        assert_eq!(summary.last_seen.as_deref(), Some("2024-05-09 10:00:00"));
+        assert!(summary.days.iter().all(|d| d.online_secs == 0));
+    }
+}
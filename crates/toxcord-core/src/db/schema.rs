@@ -0,0 +1,1685 @@
+use rusqlite::Connection;
+use tracing::info;
+
+const _CURRENT_SCHEMA_VERSION: i32 = 37;
+
+/// Initialize the database schema, running migrations as needed.
+pub fn initialize(conn: &Connection) -> rusqlite::Result<()> {
+    let version = get_schema_version(conn);
+    info!("Database schema version: {version}");
+
+    if version < 1 {
+        migrate_v1(conn)?;
+    }
+    if version < 2 {
+        migrate_v2(conn)?;
+    }
+    if version < 3 {
+        migrate_v3(conn)?;
+    }
+    if version < 4 {
+        migrate_v4(conn)?;
+    }
+    if version < 5 {
+        migrate_v5(conn)?;
+    }
+    if version < 6 {
+        migrate_v6(conn)?;
+    }
+    if version < 7 {
+        migrate_v7(conn)?;
+    }
+    if version < 8 {
+        migrate_v8(conn)?;
+    }
+    if version < 9 {
+        migrate_v9(conn)?;
+    }
+    if version < 10 {
+        migrate_v10(conn)?;
+    }
+    if version < 11 {
+        migrate_v11(conn)?;
+    }
+    if version < 12 {
+        migrate_v12(conn)?;
+    }
+    if version < 13 {
+        migrate_v13(conn)?;
+    }
+    if version < 14 {
+        migrate_v14(conn)?;
+    }
+    if version < 15 {
+        migrate_v15(conn)?;
+    }
+    if version < 16 {
+        migrate_v16(conn)?;
+    }
+    if version < 17 {
+        migrate_v17(conn)?;
+    }
+    if version < 18 {
+        migrate_v18(conn)?;
+    }
+    if version < 19 {
+        migrate_v19(conn)?;
+    }
+    if version < 20 {
+        migrate_v20(conn)?;
+    }
+    if version < 21 {
+        migrate_v21(conn)?;
+    }
+    if version < 22 {
+        migrate_v22(conn)?;
+    }
+    if version < 23 {
+        migrate_v23(conn)?;
+    }
+    if version < 24 {
+        migrate_v24(conn)?;
+    }
+    if version < 25 {
+        migrate_v25(conn)?;
+    }
+    if version < 26 {
+        migrate_v26(conn)?;
+    }
+    if version < 27 {
+        migrate_v27(conn)?;
+    }
+    if version < 28 {
+        migrate_v28(conn)?;
+    }
+    if version < 29 {
+        migrate_v29(conn)?;
+    }
+    if version < 30 {
+        migrate_v30(conn)?;
+    }
+    if version < 31 {
+        migrate_v31(conn)?;
+    }
+    if version < 32 {
+        migrate_v32(conn)?;
+    }
+    if version < 33 {
+        migrate_v33(conn)?;
+    }
+    if version < 34 {
+        migrate_v34(conn)?;
+    }
+    if version < 35 {
+        migrate_v35(conn)?;
+    }
+    if version < 36 {
+        migrate_v36(conn)?;
+    }
+    if version < 37 {
+        migrate_v37(conn)?;
+    }
+    if version < 38 {
+        migrate_v38(conn)?;
+    }
+    if version < 39 {
+        migrate_v39(conn)?;
+    }
+    if version < 40 {
+        migrate_v40(conn)?;
+    }
+    if version < 41 {
+        migrate_v41(conn)?;
+    }
+    if version < 42 {
+        migrate_v42(conn)?;
+    }
+    if version < 43 {
+        migrate_v43(conn)?;
+    }
+    if version < 44 {
+        migrate_v44(conn)?;
+    }
+    if version < 45 {
+        migrate_v45(conn)?;
+    }
+    if version < 46 {
+        migrate_v46(conn)?;
+    }
+    if version < 47 {
+        migrate_v47(conn)?;
+    }
+    if version < 48 {
+        migrate_v48(conn)?;
+    }
+    if version < 49 {
+        migrate_v49(conn)?;
+    }
+    if version < 50 {
+        migrate_v50(conn)?;
+    }
+    if version < 51 {
+        migrate_v51(conn)?;
+    }
+    if version < 52 {
+        migrate_v52(conn)?;
+    }
+
+    Ok(())
+}
+
+fn get_schema_version(conn: &Connection) -> i32 {
+    conn.pragma_query_value(None, "user_version", |row| row.get(0))
+        .unwrap_or(0)
+}
+
+fn set_schema_version(conn: &Connection, version: i32) -> rusqlite::Result<()> {
+    conn.pragma_update(None, "user_version", version)
+}
+
+/// Version 1: Initial schema
+fn migrate_v1(conn: &Connection) -> rusqlite::Result<()> {
+    info!("Running migration v1: initial schema");
+
+    conn.execute_batch(
+        "
+        -- Local profile metadata
+        CREATE TABLE IF NOT EXISTS profile (
+            id INTEGER PRIMARY KEY CHECK (id = 1),
+            tox_id TEXT NOT NULL,
+            name TEXT NOT NULL DEFAULT '',
+            status_message TEXT NOT NULL DEFAULT '',
+            created_at TEXT NOT NULL DEFAULT (datetime('now'))
+        );
+
+        -- Friends list (local cache, mirrors Tox friend list)
+        CREATE TABLE IF NOT EXISTS friends (
+            friend_number INTEGER PRIMARY KEY,
+            public_key TEXT NOT NULL UNIQUE,
+            name TEXT NOT NULL DEFAULT '',
+            status_message TEXT NOT NULL DEFAULT '',
+            user_status TEXT NOT NULL DEFAULT 'none',
+            connection_status TEXT NOT NULL DEFAULT 'none',
+            last_seen TEXT,
+            added_at TEXT NOT NULL DEFAULT (datetime('now')),
+            notes TEXT NOT NULL DEFAULT ''
+        );
+
+        -- Pending friend requests (inbound)
+        CREATE TABLE IF NOT EXISTS friend_requests (
+            public_key TEXT PRIMARY KEY,
+            message TEXT NOT NULL DEFAULT '',
+            received_at TEXT NOT NULL DEFAULT (datetime('now'))
+        );
+
+        -- Direct messages
+        CREATE TABLE IF NOT EXISTS direct_messages (
+            id TEXT PRIMARY KEY,
+            friend_number INTEGER NOT NULL,
+            sender TEXT NOT NULL,
+            content TEXT NOT NULL,
+            message_type TEXT NOT NULL DEFAULT 'normal',
+            timestamp TEXT NOT NULL DEFAULT (datetime('now')),
+            is_outgoing INTEGER NOT NULL DEFAULT 0,
+            delivered INTEGER NOT NULL DEFAULT 0,
+            read INTEGER NOT NULL DEFAULT 0,
+            FOREIGN KEY (friend_number) REFERENCES friends(friend_number) ON DELETE CASCADE
+        );
+        CREATE INDEX IF NOT EXISTS idx_dm_friend ON direct_messages(friend_number, timestamp);
+
+        -- Guilds
+        CREATE TABLE IF NOT EXISTS guilds (
+            id TEXT PRIMARY KEY,
+            name TEXT NOT NULL,
+            metadata_group_number INTEGER,
+            metadata_doc BLOB,
+            icon_hash TEXT,
+            owner_public_key TEXT NOT NULL,
+            created_at TEXT NOT NULL DEFAULT (datetime('now')),
+            last_synced TEXT
+        );
+
+        -- Channels within guilds
+        CREATE TABLE IF NOT EXISTS channels (
+            id TEXT PRIMARY KEY,
+            guild_id TEXT NOT NULL,
+            name TEXT NOT NULL,
+            topic TEXT NOT NULL DEFAULT '',
+            channel_type TEXT NOT NULL DEFAULT 'text',
+            category TEXT,
+            position INTEGER NOT NULL DEFAULT 0,
+            group_number INTEGER,
+            created_at TEXT NOT NULL DEFAULT (datetime('now')),
+            FOREIGN KEY (guild_id) REFERENCES guilds(id) ON DELETE CASCADE
+        );
+        CREATE INDEX IF NOT EXISTS idx_channel_guild ON channels(guild_id, position);
+
+        -- Channel messages (text channels in guilds)
+        CREATE TABLE IF NOT EXISTS channel_messages (
+            id TEXT PRIMARY KEY,
+            channel_id TEXT NOT NULL,
+            sender_public_key TEXT NOT NULL,
+            sender_name TEXT NOT NULL DEFAULT '',
+            content TEXT NOT NULL,
+            message_type TEXT NOT NULL DEFAULT 'normal',
+            timestamp TEXT NOT NULL DEFAULT (datetime('now')),
+            edited_at TEXT,
+            thread_id TEXT,
+            reply_to TEXT,
+            FOREIGN KEY (channel_id) REFERENCES channels(id) ON DELETE CASCADE
+        );
+        CREATE INDEX IF NOT EXISTS idx_cmsg_channel ON channel_messages(channel_id, timestamp);
+
+        -- Reactions
+        CREATE TABLE IF NOT EXISTS reactions (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            message_id TEXT NOT NULL,
+            message_table TEXT NOT NULL DEFAULT 'channel_messages',
+            emoji TEXT NOT NULL,
+            reactor_public_key TEXT NOT NULL,
+            timestamp TEXT NOT NULL DEFAULT (datetime('now')),
+            UNIQUE(message_id, emoji, reactor_public_key)
+        );
+        CREATE INDEX IF NOT EXISTS idx_reaction_msg ON reactions(message_id);
+
+        -- Pinned messages
+        CREATE TABLE IF NOT EXISTS pinned_messages (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            message_id TEXT NOT NULL,
+            channel_id TEXT NOT NULL,
+            pinned_by TEXT NOT NULL,
+            pinned_at TEXT NOT NULL DEFAULT (datetime('now')),
+            UNIQUE(message_id, channel_id)
+        );
+
+        -- File transfers
+        CREATE TABLE IF NOT EXISTS file_transfers (
+            id TEXT PRIMARY KEY,
+            friend_number INTEGER,
+            file_number INTEGER,
+            filename TEXT NOT NULL,
+            file_size INTEGER NOT NULL DEFAULT 0,
+            file_path TEXT,
+            direction TEXT NOT NULL DEFAULT 'incoming',
+            status TEXT NOT NULL DEFAULT 'pending',
+            bytes_transferred INTEGER NOT NULL DEFAULT 0,
+            started_at TEXT NOT NULL DEFAULT (datetime('now')),
+            completed_at TEXT
+        );
+
+        -- Offline message queue
+        CREATE TABLE IF NOT EXISTS offline_queue (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            target_type TEXT NOT NULL DEFAULT 'friend',
+            target_id TEXT NOT NULL,
+            message_type TEXT NOT NULL DEFAULT 'text',
+            content TEXT NOT NULL,
+            created_at TEXT NOT NULL DEFAULT (datetime('now')),
+            attempts INTEGER NOT NULL DEFAULT 0,
+            last_attempt TEXT
+        );
+        CREATE INDEX IF NOT EXISTS idx_offline_target ON offline_queue(target_type, target_id);
+
+        -- Full-text search for messages
+        CREATE VIRTUAL TABLE IF NOT EXISTS messages_fts USING fts5(
+            content,
+            message_id UNINDEXED,
+            source_table UNINDEXED,
+            content='',
+            tokenize='unicode61'
+        );
+
+        -- Triggers to keep FTS in sync with direct_messages
+        CREATE TRIGGER IF NOT EXISTS dm_fts_insert AFTER INSERT ON direct_messages BEGIN
+            INSERT INTO messages_fts(content, message_id, source_table)
+            VALUES (NEW.content, NEW.id, 'direct_messages');
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS dm_fts_delete AFTER DELETE ON direct_messages BEGIN
+            INSERT INTO messages_fts(messages_fts, content, message_id, source_table)
+            VALUES ('delete', OLD.content, OLD.id, 'direct_messages');
+        END;
+
+        -- Triggers to keep FTS in sync with channel_messages
+        CREATE TRIGGER IF NOT EXISTS cmsg_fts_insert AFTER INSERT ON channel_messages BEGIN
+            INSERT INTO messages_fts(content, message_id, source_table)
+            VALUES (NEW.content, NEW.id, 'channel_messages');
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS cmsg_fts_delete AFTER DELETE ON channel_messages BEGIN
+            INSERT INTO messages_fts(messages_fts, content, message_id, source_table)
+            VALUES ('delete', OLD.content, OLD.id, 'channel_messages');
+        END;
+        ",
+    )?;
+
+    set_schema_version(conn, 1)?;
+    info!("Migration v1 complete");
+    Ok(())
+}
+
+/// Version 2: Ensure guild/channel tables exist (for databases created before Phase 4)
+fn migrate_v2(conn: &Connection) -> rusqlite::Result<()> {
+    info!("Running migration v2: ensure guild tables");
+
+    conn.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS guilds (
+            id TEXT PRIMARY KEY,
+            name TEXT NOT NULL,
+            metadata_group_number INTEGER,
+            metadata_doc BLOB,
+            icon_hash TEXT,
+            owner_public_key TEXT NOT NULL,
+            created_at TEXT NOT NULL DEFAULT (datetime('now')),
+            last_synced TEXT
+        );
+
+        CREATE TABLE IF NOT EXISTS channels (
+            id TEXT PRIMARY KEY,
+            guild_id TEXT NOT NULL,
+            name TEXT NOT NULL,
+            topic TEXT NOT NULL DEFAULT '',
+            channel_type TEXT NOT NULL DEFAULT 'text',
+            category TEXT,
+            position INTEGER NOT NULL DEFAULT 0,
+            group_number INTEGER,
+            created_at TEXT NOT NULL DEFAULT (datetime('now')),
+            FOREIGN KEY (guild_id) REFERENCES guilds(id) ON DELETE CASCADE
+        );
+        CREATE INDEX IF NOT EXISTS idx_channel_guild ON channels(guild_id, position);
+
+        CREATE TABLE IF NOT EXISTS channel_messages (
+            id TEXT PRIMARY KEY,
+            channel_id TEXT NOT NULL,
+            sender_public_key TEXT NOT NULL,
+            sender_name TEXT NOT NULL DEFAULT '',
+            content TEXT NOT NULL,
+            message_type TEXT NOT NULL DEFAULT 'normal',
+            timestamp TEXT NOT NULL DEFAULT (datetime('now')),
+            edited_at TEXT,
+            thread_id TEXT,
+            reply_to TEXT,
+            FOREIGN KEY (channel_id) REFERENCES channels(id) ON DELETE CASCADE
+        );
+        CREATE INDEX IF NOT EXISTS idx_cmsg_channel ON channel_messages(channel_id, timestamp);
+
+        CREATE TRIGGER IF NOT EXISTS cmsg_fts_insert AFTER INSERT ON channel_messages BEGIN
+            INSERT INTO messages_fts(content, message_id, source_table)
+            VALUES (NEW.content, NEW.id, 'channel_messages');
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS cmsg_fts_delete AFTER DELETE ON channel_messages BEGIN
+            INSERT INTO messages_fts(messages_fts, content, message_id, source_table)
+            VALUES ('delete', OLD.content, OLD.id, 'channel_messages');
+        END;
+        ",
+    )?;
+
+    set_schema_version(conn, 2)?;
+    info!("Migration v2 complete");
+    Ok(())
+}
+
+/// Version 3: Add guild_type column to distinguish servers from DM groups
+fn migrate_v3(conn: &Connection) -> rusqlite::Result<()> {
+    info!("Running migration v3: add guild_type column");
+
+    conn.execute_batch(
+        "
+        ALTER TABLE guilds ADD COLUMN guild_type TEXT NOT NULL DEFAULT 'server';
+        ",
+    )?;
+
+    set_schema_version(conn, 3)?;
+    info!("Migration v3 complete");
+    Ok(())
+}
+
+/// Version 4: Attachments (files linked to messages) and transfer -> message link
+fn migrate_v4(conn: &Connection) -> rusqlite::Result<()> {
+    info!("Running migration v4: attachments");
+
+    conn.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS attachments (
+            id TEXT PRIMARY KEY,
+            message_id TEXT NOT NULL,
+            transfer_id TEXT,
+            filename TEXT NOT NULL,
+            mime_type TEXT NOT NULL DEFAULT 'application/octet-stream',
+            file_size INTEGER NOT NULL DEFAULT 0,
+            file_path TEXT NOT NULL,
+            thumbnail_path TEXT,
+            width INTEGER,
+            height INTEGER,
+            created_at TEXT NOT NULL DEFAULT (datetime('now'))
+        );
+        CREATE INDEX IF NOT EXISTS idx_attachment_message ON attachments(message_id);
+
+        ALTER TABLE file_transfers ADD COLUMN message_id TEXT;
+        ",
+    )?;
+
+    set_schema_version(conn, 4)?;
+    info!("Migration v4 complete");
+    Ok(())
+}
+
+/// Version 5: Tox file ids so interrupted transfers can be resumed
+fn migrate_v5(conn: &Connection) -> rusqlite::Result<()> {
+    info!("Running migration v5: resumable file transfers");
+
+    conn.execute_batch(
+        "
+        ALTER TABLE file_transfers ADD COLUMN file_id TEXT;
+        CREATE INDEX IF NOT EXISTS idx_file_transfer_file_id ON file_transfers(friend_number, file_id);
+        ",
+    )?;
+
+    set_schema_version(conn, 5)?;
+    info!("Migration v5 complete");
+    Ok(())
+}
+
+/// Version 6: Attachment hashes, so shared group files can be served to peers
+fn migrate_v6(conn: &Connection) -> rusqlite::Result<()> {
+    info!("Running migration v6: attachment hashes");
+
+    conn.execute_batch(
+        "
+        ALTER TABLE attachments ADD COLUMN sha256 TEXT;
+        CREATE INDEX IF NOT EXISTS idx_attachment_sha256 ON attachments(sha256);
+        ",
+    )?;
+
+    set_schema_version(conn, 6)?;
+    info!("Migration v6 complete");
+    Ok(())
+}
+
+/// Version 7: Voice note duration and waveform
+fn migrate_v7(conn: &Connection) -> rusqlite::Result<()> {
+    info!("Running migration v7: voice notes");
+
+    conn.execute_batch(
+        "
+        ALTER TABLE attachments ADD COLUMN duration_ms INTEGER;
+        ALTER TABLE attachments ADD COLUMN waveform BLOB;
+        ",
+    )?;
+
+    set_schema_version(conn, 7)?;
+    info!("Migration v7 complete");
+    Ok(())
+}
+
+/// Version 8: Scheduled messages
+fn migrate_v8(conn: &Connection) -> rusqlite::Result<()> {
+    info!("Running migration v8: scheduled messages");
+
+    conn.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS scheduled_messages (
+            id TEXT PRIMARY KEY,
+            target_type TEXT NOT NULL,
+            target_id TEXT NOT NULL,
+            guild_id TEXT,
+            content TEXT NOT NULL,
+            send_at TEXT NOT NULL,
+            status TEXT NOT NULL DEFAULT 'pending',
+            message_id TEXT,
+            created_at TEXT NOT NULL DEFAULT (datetime('now'))
+        );
+        CREATE INDEX IF NOT EXISTS idx_scheduled_due ON scheduled_messages(status, send_at);
+        ",
+    )?;
+
+    set_schema_version(conn, 8)?;
+    info!("Migration v8 complete");
+    Ok(())
+}
+
+/// Version 9: Index message text without markdown markup.
+/// Rows from before this migration have no plain_content and keep their raw content indexed.
+fn migrate_v9(conn: &Connection) -> rusqlite::Result<()> {
+    info!("Running migration v9: plain text search");
+
+    conn.execute_batch(
+        "
+        ALTER TABLE direct_messages ADD COLUMN plain_content TEXT;
+        ALTER TABLE channel_messages ADD COLUMN plain_content TEXT;
+
+        DROP TRIGGER IF EXISTS dm_fts_insert;
+        DROP TRIGGER IF EXISTS dm_fts_delete;
+        DROP TRIGGER IF EXISTS cmsg_fts_insert;
+        DROP TRIGGER IF EXISTS cmsg_fts_delete;
+
+        CREATE TRIGGER dm_fts_insert AFTER INSERT ON direct_messages BEGIN
+            INSERT INTO messages_fts(content, message_id, source_table)
+            VALUES (COALESCE(NEW.plain_content, NEW.content), NEW.id, 'direct_messages');
+        END;
+
+        CREATE TRIGGER dm_fts_delete AFTER DELETE ON direct_messages BEGIN
+            INSERT INTO messages_fts(messages_fts, content, message_id, source_table)
+            VALUES ('delete', COALESCE(OLD.plain_content, OLD.content), OLD.id, 'direct_messages');
+        END;
+
+        CREATE TRIGGER cmsg_fts_insert AFTER INSERT ON channel_messages BEGIN
+            INSERT INTO messages_fts(content, message_id, source_table)
+            VALUES (COALESCE(NEW.plain_content, NEW.content), NEW.id, 'channel_messages');
+        END;
+
+        CREATE TRIGGER cmsg_fts_delete AFTER DELETE ON channel_messages BEGIN
+            INSERT INTO messages_fts(messages_fts, content, message_id, source_table)
+            VALUES ('delete', COALESCE(OLD.plain_content, OLD.content), OLD.id, 'channel_messages');
+        END;
+        ",
+    )?;
+
+    set_schema_version(conn, 9)?;
+    info!("Migration v9 complete");
+    Ok(())
+}
+
+/// Version 10: Guild member cache for mention autocomplete, and mention flags
+fn migrate_v10(conn: &Connection) -> rusqlite::Result<()> {
+    info!("Running migration v10: mentions");
+
+    conn.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS guild_members (
+            guild_id TEXT NOT NULL,
+            public_key TEXT NOT NULL,
+            name TEXT NOT NULL DEFAULT '',
+            last_seen TEXT NOT NULL DEFAULT (datetime('now')),
+            PRIMARY KEY (guild_id, public_key),
+            FOREIGN KEY (guild_id) REFERENCES guilds(id) ON DELETE CASCADE
+        );
+
+        ALTER TABLE channel_messages ADD COLUMN mentions_me INTEGER NOT NULL DEFAULT 0;
+        ALTER TABLE channel_messages ADD COLUMN mention_read INTEGER NOT NULL DEFAULT 0;
+        CREATE INDEX IF NOT EXISTS idx_cmsg_mentions ON channel_messages(mentions_me, mention_read);
+        ",
+    )?;
+
+    set_schema_version(conn, 10)?;
+    info!("Migration v10 complete");
+    Ok(())
+}
+
+/// Version 11: Custom spellcheck dictionary
+fn migrate_v11(conn: &Connection) -> rusqlite::Result<()> {
+    info!("Running migration v11: custom dictionary");
+
+    conn.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS dictionary_words (
+            word TEXT PRIMARY KEY COLLATE NOCASE,
+            added_at TEXT NOT NULL DEFAULT (datetime('now'))
+        );
+        ",
+    )?;
+
+    set_schema_version(conn, 11)?;
+    info!("Migration v11 complete");
+    Ok(())
+}
+
+/// Version 12: Friend online sessions. ended_at is NULL while the friend is online.
+fn migrate_v12(conn: &Connection) -> rusqlite::Result<()> {
+    info!("Running migration v12: presence log");
+
+    conn.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS presence_log (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            friend_number INTEGER NOT NULL,
+            started_at TEXT NOT NULL DEFAULT (datetime('now')),
+            ended_at TEXT
+        );
+        CREATE INDEX IF NOT EXISTS idx_presence_friend ON presence_log(friend_number, started_at);
+        ",
+    )?;
+
+    set_schema_version(conn, 12)?;
+    info!("Migration v12 complete");
+    Ok(())
+}
+
+/// Version 13: Per-profile settings
+fn migrate_v13(conn: &Connection) -> rusqlite::Result<()> {
+    info!("Running migration v13: profile settings");
+
+    conn.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS profile_settings (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL
+        );
+        ",
+    )?;
+
+    set_schema_version(conn, 13)?;
+    info!("Migration v13 complete");
+    Ok(())
+}
+
+/// Version 14: When each friend last got the away auto-reply
+fn migrate_v14(conn: &Connection) -> rusqlite::Result<()> {
+    info!("Running migration v14: auto-reply log");
+
+    conn.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS auto_reply_log (
+            friend_number INTEGER PRIMARY KEY,
+            sent_at TEXT NOT NULL DEFAULT (datetime('now'))
+        );
+        ",
+    )?;
+
+    set_schema_version(conn, 14)?;
+    info!("Migration v14 complete");
+    Ok(())
+}
+
+/// Version 15: Out-of-band identity verification. Stores the key that was
+/// verified, so a friend whose key later differs shows up as changed.
+fn migrate_v15(conn: &Connection) -> rusqlite::Result<()> {
+    info!("Running migration v15: friend verification");
+
+    conn.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS friend_verifications (
+            friend_number INTEGER PRIMARY KEY,
+            public_key TEXT NOT NULL,
+            verified_at TEXT NOT NULL DEFAULT (datetime('now'))
+        );
+        ",
+    )?;
+
+    set_schema_version(conn, 15)?;
+    info!("Migration v15 complete");
+    Ok(())
+}
+
+/// Version 16: Tox ID (nospam) rotation history
+fn migrate_v16(conn: &Connection) -> rusqlite::Result<()> {
+    info!("Running migration v16: nospam history");
+
+    conn.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS nospam_history (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            old_address TEXT NOT NULL,
+            new_address TEXT NOT NULL,
+            changed_at TEXT NOT NULL DEFAULT (datetime('now'))
+        );
+        ",
+    )?;
+
+    set_schema_version(conn, 16)?;
+    info!("Migration v16 complete");
+    Ok(())
+}
+
+/// Version 17: own devices linked for sync
+fn migrate_v17(conn: &Connection) -> rusqlite::Result<()> {
+    info!("Running migration v17: linked devices");
+
+    conn.execute_batch(
+        "
+        -- Friends that are other devices of the same user
+        CREATE TABLE IF NOT EXISTS linked_devices (
+            friend_number INTEGER PRIMARY KEY,
+            public_key TEXT NOT NULL,
+            device_name TEXT NOT NULL DEFAULT '',
+            linked_at TEXT NOT NULL DEFAULT (datetime('now')),
+            last_synced_at TEXT,
+            -- Newest message timestamp received from this device
+            received_until TEXT
+        );
+        ",
+    )?;
+
+    set_schema_version(conn, 17)?;
+    info!("Migration v17 complete");
+    Ok(())
+}
+
+/// Version 18: channel bridges to IRC and Matrix
+fn migrate_v18(conn: &Connection) -> rusqlite::Result<()> {
+    info!("Running migration v18: channel bridges");
+
+    conn.execute_batch(
+        "
+        -- External endpoint a guild channel relays to, one per channel
+        CREATE TABLE IF NOT EXISTS channel_bridges (
+            channel_id TEXT PRIMARY KEY,
+            guild_id TEXT NOT NULL,
+            -- 'irc' or 'matrix'
+            protocol TEXT NOT NULL,
+            -- IRC host:port, or Matrix homeserver URL
+            server TEXT NOT NULL,
+            -- IRC channel, or Matrix room ID
+            target TEXT NOT NULL,
+            -- IRC nickname (unused for Matrix)
+            nickname TEXT NOT NULL DEFAULT '',
+            -- Matrix access token (unused for IRC)
+            access_token TEXT NOT NULL DEFAULT '',
+            use_tls INTEGER NOT NULL DEFAULT 1,
+            enabled INTEGER NOT NULL DEFAULT 1,
+            created_at TEXT NOT NULL DEFAULT (datetime('now')),
+            FOREIGN KEY (channel_id) REFERENCES channels(id) ON DELETE CASCADE
+        );
+        ",
+    )?;
+
+    set_schema_version(conn, 18)?;
+    info!("Migration v18 complete");
+    Ok(())
+}
+
+/// Version 19: friend groups
+fn migrate_v19(conn: &Connection) -> rusqlite::Result<()> {
+    info!("Running migration v19: friend groups");
+
+    conn.execute_batch(
+        "
+        -- User-defined sections of the friends list, e.g. Work or Gaming
+        CREATE TABLE IF NOT EXISTS friend_groups (
+            id TEXT PRIMARY KEY,
+            name TEXT NOT NULL,
+            position INTEGER NOT NULL DEFAULT 0,
+            collapsed INTEGER NOT NULL DEFAULT 0,
+            created_at TEXT NOT NULL DEFAULT (datetime('now'))
+        );
+
+        -- Friends without a group are listed after all groups
+        ALTER TABLE friends ADD COLUMN group_id TEXT REFERENCES friend_groups(id) ON DELETE SET NULL;
+        -- Order within the group
+        ALTER TABLE friends ADD COLUMN group_position INTEGER NOT NULL DEFAULT 0;
+        ",
+    )?;
+
+    set_schema_version(conn, 19)?;
+    info!("Migration v19 complete");
+    Ok(())
+}
+
+/// Version 20: starred conversations
+fn migrate_v20(conn: &Connection) -> rusqlite::Result<()> {
+    info!("Running migration v20: starred conversations");
+
+    conn.execute_batch(
+        "
+        -- Conversations pinned to the top of the quick switcher
+        CREATE TABLE IF NOT EXISTS starred_conversations (
+            -- 'dm' (friend public key), 'dm_group' (guild id) or 'channel' (channel id)
+            kind TEXT NOT NULL,
+            target_id TEXT NOT NULL,
+            starred_at TEXT NOT NULL DEFAULT (datetime('now')),
+            PRIMARY KEY (kind, target_id)
+        );
+        ",
+    )?;
+
+    set_schema_version(conn, 20)?;
+    info!("Migration v20 complete");
+    Ok(())
+}
+
+/// Version 21: guild member roles
+fn migrate_v21(conn: &Connection) -> rusqlite::Result<()> {
+    info!("Running migration v21: guild member roles");
+
+    conn.execute_batch(
+        "
+        -- Last known role, so offline members can still be listed under it
+        ALTER TABLE guild_members ADD COLUMN role TEXT NOT NULL DEFAULT 'user';
+        ",
+    )?;
+
+    set_schema_version(conn, 21)?;
+    info!("Migration v21 complete");
+    Ok(())
+}
+
+/// Version 22: moderation audit log
+fn migrate_v22(conn: &Connection) -> rusqlite::Result<()> {
+    info!("Running migration v22: audit log");
+
+    conn.execute_batch(
+        "
+        -- Moderation actions in a guild, ours and ones observed from peers
+        CREATE TABLE IF NOT EXISTS audit_log (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            guild_id TEXT NOT NULL,
+            -- kick, role_change, channel_create, channel_delete, channel_rename, topic_change
+            action TEXT NOT NULL,
+            actor_public_key TEXT NOT NULL,
+            actor_name TEXT NOT NULL DEFAULT '',
+            -- The member acted on (kick, role_change)
+            target_public_key TEXT,
+            -- Member or channel name
+            target_name TEXT NOT NULL DEFAULT '',
+            -- New role, new channel name or new topic
+            details TEXT NOT NULL DEFAULT '',
+            created_at TEXT NOT NULL DEFAULT (datetime('now')),
+            FOREIGN KEY (guild_id) REFERENCES guilds(id) ON DELETE CASCADE
+        );
+        CREATE INDEX IF NOT EXISTS idx_audit_guild ON audit_log(guild_id, id);
+        ",
+    )?;
+
+    set_schema_version(conn, 22)?;
+    info!("Migration v22 complete");
+    Ok(())
+}
+
+/// Version 23: word filter warnings on channel messages
+fn migrate_v23(conn: &Connection) -> rusqlite::Result<()> {
+    info!("Running migration v23: word filter warnings");
+
+    conn.execute_batch(
+        "
+        -- Set when a message matched the guild word filter with the warn action
+        ALTER TABLE channel_messages ADD COLUMN filtered INTEGER NOT NULL DEFAULT 0;
+        ",
+    )?;
+
+    set_schema_version(conn, 23)?;
+    info!("Migration v23 complete");
+    Ok(())
+}
+
+/// Version 24: channel polls and their votes
+fn migrate_v24(conn: &Connection) -> rusqlite::Result<()> {
+    info!("Running migration v24: polls");
+
+    conn.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS polls (
+            -- Poll UUID, also the ID of the poll's channel message
+            id TEXT PRIMARY KEY,
+            channel_id TEXT NOT NULL,
+            creator_public_key TEXT NOT NULL,
+            creator_name TEXT NOT NULL DEFAULT '',
+            question TEXT NOT NULL,
+            -- JSON array of option labels
+            options TEXT NOT NULL,
+            -- Unix seconds
+            closes_at INTEGER NOT NULL,
+            closed INTEGER NOT NULL DEFAULT 0,
+            created_at TEXT NOT NULL DEFAULT (datetime('now')),
+            FOREIGN KEY (channel_id) REFERENCES channels(id) ON DELETE CASCADE
+        );
+        CREATE INDEX IF NOT EXISTS idx_polls_open ON polls(closed, closes_at);
+
+        -- One vote per member per poll
+        CREATE TABLE IF NOT EXISTS poll_votes (
+            poll_id TEXT NOT NULL,
+            voter_public_key TEXT NOT NULL,
+            option_index INTEGER NOT NULL,
+            voted_at TEXT NOT NULL DEFAULT (datetime('now')),
+            PRIMARY KEY (poll_id, voter_public_key),
+            FOREIGN KEY (poll_id) REFERENCES polls(id) ON DELETE CASCADE
+        );
+        ",
+    )?;
+
+    set_schema_version(conn, 24)?;
+    info!("Migration v24 complete");
+    Ok(())
+}
+
+/// Version 25: scheduled guild events and RSVPs
+fn migrate_v25(conn: &Connection) -> rusqlite::Result<()> {
+    info!("Running migration v25: guild events");
+
+    conn.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS guild_events (
+            -- Event UUID
+            id TEXT PRIMARY KEY,
+            guild_id TEXT NOT NULL,
+            creator_public_key TEXT NOT NULL,
+            creator_name TEXT NOT NULL DEFAULT '',
+            title TEXT NOT NULL,
+            description TEXT NOT NULL DEFAULT '',
+            -- Unix seconds
+            start_time INTEGER NOT NULL,
+            -- Set once the reminder has been shown
+            reminded INTEGER NOT NULL DEFAULT 0,
+            created_at TEXT NOT NULL DEFAULT (datetime('now')),
+            FOREIGN KEY (guild_id) REFERENCES guilds(id) ON DELETE CASCADE
+        );
+        CREATE INDEX IF NOT EXISTS idx_guild_events_start ON guild_events(guild_id, start_time);
+
+        -- One RSVP per member per event
+        CREATE TABLE IF NOT EXISTS event_rsvps (
+            event_id TEXT NOT NULL,
+            public_key TEXT NOT NULL,
+            -- going, interested or not_going
+            status TEXT NOT NULL,
+            updated_at TEXT NOT NULL DEFAULT (datetime('now')),
+            PRIMARY KEY (event_id, public_key),
+            FOREIGN KEY (event_id) REFERENCES guild_events(id) ON DELETE CASCADE
+        );
+        ",
+    )?;
+
+    set_schema_version(conn, 25)?;
+    info!("Migration v25 complete");
+    Ok(())
+}
+
+/// Version 26: conversation locks (extra passphrase encryption for direct messages)
+fn migrate_v26(conn: &Connection) -> rusqlite::Result<()> {
+    info!("Running migration v26: conversation locks");
+
+    conn.execute_batch(
+        "
+        -- The passphrase and key are never stored; only that the lock is on
+        CREATE TABLE IF NOT EXISTS conversation_locks (
+            friend_number INTEGER PRIMARY KEY,
+            -- Store decrypted messages instead of the encrypted envelopes
+            store_plaintext INTEGER NOT NULL DEFAULT 0,
+            enabled_at TEXT NOT NULL DEFAULT (datetime('now')),
+            FOREIGN KEY (friend_number) REFERENCES friends(friend_number) ON DELETE CASCADE
+        );
+        ",
+    )?;
+
+    set_schema_version(conn, 26)?;
+    info!("Migration v26 complete");
+    Ok(())
+}
+
+/// Version 27: disappearing messages
+fn migrate_v27(conn: &Connection) -> rusqlite::Result<()> {
+    info!("Running migration v27: disappearing messages");
+
+    conn.execute_batch(
+        "
+        ALTER TABLE direct_messages ADD COLUMN expires_at INTEGER;
+        CREATE INDEX IF NOT EXISTS idx_dm_expires ON direct_messages(expires_at) WHERE expires_at IS NOT NULL;
+
+        CREATE TABLE IF NOT EXISTS disappearing_timers (
+            friend_number INTEGER PRIMARY KEY,
+            -- 0 when turned off
+            seconds INTEGER NOT NULL,
+            -- Unix seconds; the newer of ours and the friend's wins
+            set_at INTEGER NOT NULL,
+            FOREIGN KEY (friend_number) REFERENCES friends(friend_number) ON DELETE CASCADE
+        );
+        ",
+    )?;
+
+    set_schema_version(conn, 27)?;
+    info!("Migration v27 complete");
+    Ok(())
+}
+
+/// Version 28: friend revisions
+fn migrate_v28(conn: &Connection) -> rusqlite::Result<()> {
+    info!("Running migration v28: friend revisions");
+
+    conn.execute_batch(
+        "
+        -- Bumped on every change to a friend, so the friends list can be
+        -- fetched as a delta
+        CREATE TABLE IF NOT EXISTS friends_revision (
+            id INTEGER PRIMARY KEY CHECK (id = 1),
+            revision INTEGER NOT NULL
+        );
+        INSERT OR IGNORE INTO friends_revision (id, revision) VALUES (1, 0);
+
+        ALTER TABLE friends ADD COLUMN revision INTEGER NOT NULL DEFAULT 0;
+        CREATE INDEX IF NOT EXISTS idx_friends_revision ON friends(revision);
+
+        -- Friends removed at a revision
+        CREATE TABLE IF NOT EXISTS removed_friends (
+            friend_number INTEGER PRIMARY KEY,
+            revision INTEGER NOT NULL
+        );
+
+        CREATE TRIGGER IF NOT EXISTS friends_revision_insert AFTER INSERT ON friends BEGIN
+            UPDATE friends_revision SET revision = revision + 1;
+            UPDATE friends SET revision = (SELECT revision FROM friends_revision)
+            WHERE friend_number = NEW.friend_number;
+            DELETE FROM removed_friends WHERE friend_number = NEW.friend_number;
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS friends_revision_update AFTER UPDATE ON friends
+        WHEN OLD.public_key IS NOT NEW.public_key
+            OR OLD.name IS NOT NEW.name
+            OR OLD.status_message IS NOT NEW.status_message
+            OR OLD.user_status IS NOT NEW.user_status
+            OR OLD.connection_status IS NOT NEW.connection_status
+            OR OLD.last_seen IS NOT NEW.last_seen
+            OR OLD.notes IS NOT NEW.notes
+            OR OLD.group_id IS NOT NEW.group_id
+            OR OLD.group_position IS NOT NEW.group_position
+        BEGIN
+            UPDATE friends_revision SET revision = revision + 1;
+            UPDATE friends SET revision = (SELECT revision FROM friends_revision)
+            WHERE friend_number = NEW.friend_number;
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS friends_revision_delete AFTER DELETE ON friends BEGIN
+            UPDATE friends_revision SET revision = revision + 1;
+            INSERT OR REPLACE INTO removed_friends (friend_number, revision)
+            VALUES (OLD.friend_number, (SELECT revision FROM friends_revision));
+        END;
+        ",
+    )?;
+
+    set_schema_version(conn, 28)?;
+    info!("Migration v28 complete");
+    Ok(())
+}
+
+/// Version 29: message cursors
+fn migrate_v29(conn: &Connection) -> rusqlite::Result<()> {
+    info!("Running migration v29: message cursors");
+
+    conn.execute_batch(
+        "
+        -- Pages of messages are ordered by timestamp, then id for messages sent
+        -- at the same time
+        DROP INDEX IF EXISTS idx_dm_friend;
+        CREATE INDEX IF NOT EXISTS idx_dm_friend ON direct_messages(friend_number, timestamp, id);
+        DROP INDEX IF EXISTS idx_cmsg_channel;
+        CREATE INDEX IF NOT EXISTS idx_cmsg_channel ON channel_messages(channel_id, timestamp, id);
+        ",
+    )?;
+
+    set_schema_version(conn, 29)?;
+    info!("Migration v29 complete");
+    Ok(())
+}
+
+/// Version 30: media cache
+fn migrate_v30(conn: &Connection) -> rusqlite::Result<()> {
+    info!("Running migration v30: media cache");
+
+    conn.execute_batch(
+        "
+        -- Content in the media cache, stored on disk under its hash
+        CREATE TABLE IF NOT EXISTS assets (
+            -- BLAKE3 of the content, hex
+            hash TEXT PRIMARY KEY,
+            -- avatar, thumbnail, guild_icon or attachment
+            kind TEXT NOT NULL,
+            size INTEGER NOT NULL,
+            created_at TEXT NOT NULL DEFAULT (datetime('now')),
+            -- Unix milliseconds, for least recently used eviction
+            last_used_at INTEGER NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_assets_last_used ON assets(last_used_at);
+        ",
+    )?;
+
+    set_schema_version(conn, 30)?;
+    info!("Migration v30 complete");
+    Ok(())
+}
+
+/// Version 31: call session tags on direct messages
+fn migrate_v31(conn: &Connection) -> rusqlite::Result<()> {
+    info!("Running migration v31: call sessions");
+
+    conn.execute_batch(
+        "
+        -- The call a direct message was sent in, so call chat can be found later
+        ALTER TABLE direct_messages ADD COLUMN call_session_id TEXT;
+        CREATE INDEX IF NOT EXISTS idx_dm_call_session ON direct_messages(call_session_id, timestamp)
+            WHERE call_session_id IS NOT NULL;
+        ",
+    )?;
+
+    set_schema_version(conn, 31)?;
+    info!("Migration v31 complete");
+    Ok(())
+}
+
+/// Version 32: missed calls
+fn migrate_v32(conn: &Connection) -> rusqlite::Result<()> {
+    info!("Running migration v32: missed calls");
+
+    conn.execute_batch(
+        "
+        -- Incoming calls that weren't answered
+        CREATE TABLE IF NOT EXISTS missed_calls (
+            id TEXT PRIMARY KEY,
+            friend_number INTEGER NOT NULL,
+            timestamp TEXT NOT NULL,
+            with_video INTEGER NOT NULL DEFAULT 0,
+            -- no_answer or do_not_disturb
+            reason TEXT NOT NULL,
+            seen INTEGER NOT NULL DEFAULT 0
+        );
+        CREATE INDEX IF NOT EXISTS idx_missed_calls_friend ON missed_calls(friend_number, timestamp);
+        ",
+    )?;
+
+    set_schema_version(conn, 32)?;
+    info!("Migration v32 complete");
+    Ok(())
+}
+
+/// Version 33: per-friend call quality
+fn migrate_v33(conn: &Connection) -> rusqlite::Result<()> {
+    info!("Running migration v33: call preferences");
+
+    conn.execute_batch(
+        "
+        -- Bitrates and camera default for calls with a friend, overriding the
+        -- app-wide defaults
+        CREATE TABLE IF NOT EXISTS call_preferences (
+            friend_number INTEGER PRIMARY KEY,
+            -- kbit/s
+            audio_bit_rate INTEGER NOT NULL,
+            video_bit_rate INTEGER NOT NULL,
+            video_by_default INTEGER NOT NULL DEFAULT 0,
+            FOREIGN KEY (friend_number) REFERENCES friends(friend_number) ON DELETE CASCADE
+        );
+        ",
+    )?;
+
+    set_schema_version(conn, 33)?;
+    info!("Migration v33 complete");
+    Ok(())
+}
+
+/// Version 34: pending group invites and auto-accept
+fn migrate_v34(conn: &Connection) -> rusqlite::Result<()> {
+    info!("Running migration v34: group invites");
+
+    conn.execute_batch(
+        "
+        -- Group invites not yet accepted or declined, so they survive a restart
+        CREATE TABLE IF NOT EXISTS group_invites (
+            id TEXT PRIMARY KEY,
+            friend_number INTEGER NOT NULL,
+            group_name TEXT NOT NULL,
+            invite_data BLOB NOT NULL,
+            received_at TEXT NOT NULL,
+            UNIQUE (friend_number, invite_data)
+        );
+
+        -- Invites from these friends are joined without asking
+        ALTER TABLE friends ADD COLUMN auto_accept_group_invites INTEGER NOT NULL DEFAULT 0;
+        ",
+    )?;
+
+    set_schema_version(conn, 34)?;
+    info!("Migration v34 complete");
+    Ok(())
+}
+
+/// Version 35: guild chat IDs
+fn migrate_v35(conn: &Connection) -> rusqlite::Result<()> {
+    info!("Running migration v35: guild chat IDs");
+
+    conn.execute_batch(
+        "
+        -- The NGC chat ID (uppercase hex), to join the group again if it's lost
+        -- from the savedata
+        ALTER TABLE guilds ADD COLUMN chat_id TEXT;
+        ",
+    )?;
+
+    set_schema_version(conn, 35)?;
+    info!("Migration v35 complete");
+    Ok(())
+}
+
+/// Version 36: last handshake with each friend
+fn migrate_v36(conn: &Connection) -> rusqlite::Result<()> {
+    info!("Running migration v36: friend handshakes");
+
+    conn.execute_batch(
+        "
+        -- When the connection to the friend was last (re)established, directly or
+        -- through a relay
+        ALTER TABLE friends ADD COLUMN last_handshake_at TEXT;
+        ",
+    )?;
+
+    set_schema_version(conn, 36)?;
+    info!("Migration v36 complete");
+    Ok(())
+}
+
+/// Version 37: friends' Tox IDs
+fn migrate_v37(conn: &Connection) -> rusqlite::Result<()> {
+    info!("Running migration v37: friend Tox IDs");
+
+    conn.execute_batch(
+        "
+        -- The full address a friend was added by, so it can be shared in a contact
+        -- card. Friends who added us are only known by public key.
+        ALTER TABLE friends ADD COLUMN tox_id TEXT;
+        ",
+    )?;
+
+    set_schema_version(conn, 37)?;
+    info!("Migration v37 complete");
+    Ok(())
+}
+
+/// Version 38: backfilled channel messages
+fn migrate_v38(conn: &Connection) -> rusqlite::Result<()> {
+    info!("Running migration v38: backfilled channel messages");
+
+    conn.execute_batch(
+        "
+        -- Received from another member's history after joining the guild, rather
+        -- than live from the sender
+        ALTER TABLE channel_messages ADD COLUMN backfilled INTEGER NOT NULL DEFAULT 0;
+        ",
+    )?;
+
+    set_schema_version(conn, 38)?;
+    info!("Migration v38 complete");
+    Ok(())
+}
+
+/// Version 39: guild channel layout
+fn migrate_v39(conn: &Connection) -> rusqlite::Result<()> {
+    info!("Running migration v39: guild channel layout");
+
+    conn.execute_batch(
+        "
+        -- Key of the channel in the guild's shared channel layout, once bound
+        ALTER TABLE channels ADD COLUMN layout_key TEXT;
+        ",
+    )?;
+
+    set_schema_version(conn, 39)?;
+    info!("Migration v39 complete");
+    Ok(())
+}
+
+/// Version 40: signed channel messages
+fn migrate_v40(conn: &Connection) -> rusqlite::Result<()> {
+    info!("Running migration v40: signed channel messages");
+
+    conn.execute_batch(
+        "
+        -- Whether the sender's signature checked out (NULL when not checked)
+        ALTER TABLE channel_messages ADD COLUMN sender_verified INTEGER;
+        ",
+    )?;
+
+    set_schema_version(conn, 40)?;
+    info!("Migration v40 complete");
+    Ok(())
+}
+
+/// Version 41: status notes
+fn migrate_v41(conn: &Connection) -> rusqlite::Result<()> {
+    info!("Running migration v41: status notes");
+
+    conn.execute_batch(
+        "
+        -- Short notes posted to friends, ours and theirs, kept until they expire
+        CREATE TABLE IF NOT EXISTS statuses (
+            id TEXT NOT NULL,
+            -- NULL for our own notes
+            friend_number INTEGER,
+            text TEXT NOT NULL,
+            -- Unix seconds
+            posted_at INTEGER NOT NULL,
+            expires_at INTEGER NOT NULL,
+            UNIQUE (friend_number, id),
+            FOREIGN KEY (friend_number) REFERENCES friends(friend_number) ON DELETE CASCADE
+        );
+        CREATE INDEX IF NOT EXISTS idx_statuses_expires ON statuses(expires_at);
+        ",
+    )?;
+
+    set_schema_version(conn, 41)?;
+    info!("Migration v41 complete");
+    Ok(())
+}
+
+/// Version 42: sticker packs
+fn migrate_v42(conn: &Connection) -> rusqlite::Result<()> {
+    info!("Running migration v42: sticker packs");
+
+    conn.execute_batch(
+        "
+        -- Installed sticker packs; the images are in the media cache
+        CREATE TABLE IF NOT EXISTS sticker_packs (
+            -- SHA-256 of the pack's name and stickers, hex
+            hash TEXT PRIMARY KEY,
+            name TEXT NOT NULL,
+            -- JSON array of {hash, mime_type}
+            stickers TEXT NOT NULL,
+            added_at TEXT NOT NULL DEFAULT (datetime('now'))
+        );
+
+        -- Peers known to have a pack, because they sent us one of its stickers.
+        -- They get stickers from it as references rather than files.
+        CREATE TABLE IF NOT EXISTS sticker_pack_holders (
+            pack_hash TEXT NOT NULL,
+            public_key TEXT NOT NULL,
+            PRIMARY KEY (pack_hash, public_key)
+        );
+        ",
+    )?;
+
+    set_schema_version(conn, 42)?;
+    info!("Migration v42 complete");
+    Ok(())
+}
+
+/// Version 43: local usage statistics
+fn migrate_v43(conn: &Connection) -> rusqlite::Result<()> {
+    info!("Running migration v43: usage statistics");
+
+    conn.execute_batch(
+        "
+        -- Activity per conversation per local day, kept only on this device.
+        -- Counted as messages are stored, so they outlive deleted messages.
+        CREATE TABLE IF NOT EXISTS usage_stats (
+            -- YYYY-MM-DD
+            day TEXT NOT NULL,
+            -- 'dm' or 'channel'
+            kind TEXT NOT NULL,
+            -- Friend public key or channel id
+            conversation_id TEXT NOT NULL,
+            messages_sent INTEGER NOT NULL DEFAULT 0,
+            messages_received INTEGER NOT NULL DEFAULT 0,
+            call_seconds INTEGER NOT NULL DEFAULT 0,
+            -- Completed file transfers
+            bytes_sent INTEGER NOT NULL DEFAULT 0,
+            bytes_received INTEGER NOT NULL DEFAULT 0,
+            PRIMARY KEY (day, kind, conversation_id)
+        );
+
+        CREATE TRIGGER IF NOT EXISTS usage_dm_insert AFTER INSERT ON direct_messages BEGIN
+            INSERT INTO usage_stats (day, kind, conversation_id, messages_sent, messages_received)
+            SELECT date(NEW.timestamp, 'localtime'), 'dm', public_key, NEW.is_outgoing != 0, NEW.is_outgoing = 0
+            FROM friends WHERE friend_number = NEW.friend_number
+            ON CONFLICT (day, kind, conversation_id) DO UPDATE SET
+                messages_sent = messages_sent + excluded.messages_sent,
+                messages_received = messages_received + excluded.messages_received;
+        END;
+
+        -- Our own channel messages are the ones with our public key, the start of our Tox ID
+        CREATE TRIGGER IF NOT EXISTS usage_channel_insert AFTER INSERT ON channel_messages BEGIN
+            INSERT INTO usage_stats (day, kind, conversation_id, messages_sent, messages_received)
+            VALUES (
+                date(NEW.timestamp, 'localtime'), 'channel', NEW.channel_id,
+                EXISTS (SELECT 1 FROM profile WHERE id = 1 AND upper(substr(tox_id, 1, 64)) = upper(NEW.sender_public_key)),
+                NOT EXISTS (SELECT 1 FROM profile WHERE id = 1 AND upper(substr(tox_id, 1, 64)) = upper(NEW.sender_public_key))
+            )
+            ON CONFLICT (day, kind, conversation_id) DO UPDATE SET
+                messages_sent = messages_sent + excluded.messages_sent,
+                messages_received = messages_received + excluded.messages_received;
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS usage_transfer_completed AFTER UPDATE OF status ON file_transfers
+        WHEN NEW.status = 'completed' AND OLD.status != 'completed'
+        BEGIN
+            INSERT INTO usage_stats (day, kind, conversation_id, bytes_sent, bytes_received)
+            SELECT date('now', 'localtime'), 'dm', public_key,
+                   CASE WHEN NEW.direction = 'outgoing' THEN NEW.file_size ELSE 0 END,
+                   CASE WHEN NEW.direction = 'outgoing' THEN 0 ELSE NEW.file_size END
+            FROM friends WHERE friend_number = NEW.friend_number
+            ON CONFLICT (day, kind, conversation_id) DO UPDATE SET
+                bytes_sent = bytes_sent + excluded.bytes_sent,
+                bytes_received = bytes_received + excluded.bytes_received;
+        END;
+
+        -- Start from the history already stored
+        INSERT INTO usage_stats (day, kind, conversation_id, messages_sent, messages_received)
+        SELECT date(m.timestamp, 'localtime'), 'dm', f.public_key,
+               SUM(m.is_outgoing != 0), SUM(m.is_outgoing = 0)
+        FROM direct_messages m JOIN friends f ON f.friend_number = m.friend_number
+        GROUP BY 1, 3;
+
+        INSERT INTO usage_stats (day, kind, conversation_id, messages_sent, messages_received)
+        SELECT date(m.timestamp, 'localtime'), 'channel', m.channel_id,
+               SUM(upper(m.sender_public_key) IS upper(substr(p.tox_id, 1, 64))),
+               SUM(upper(m.sender_public_key) IS NOT upper(substr(p.tox_id, 1, 64)))
+        FROM channel_messages m LEFT JOIN profile p ON p.id = 1
+        GROUP BY 1, 3;
+
+        INSERT INTO usage_stats (day, kind, conversation_id, bytes_sent, bytes_received)
+        SELECT date(t.completed_at, 'localtime'), 'dm', f.public_key,
+               SUM(CASE WHEN t.direction = 'outgoing' THEN t.file_size ELSE 0 END),
+               SUM(CASE WHEN t.direction = 'outgoing' THEN 0 ELSE t.file_size END)
+        FROM file_transfers t JOIN friends f ON f.friend_number = t.friend_number
+        WHERE t.status = 'completed' AND t.completed_at IS NOT NULL
+        GROUP BY 1, 3
+        ON CONFLICT (day, kind, conversation_id) DO UPDATE SET
+            bytes_sent = excluded.bytes_sent,
+            bytes_received = excluded.bytes_received;
+        ",
+    )?;
+
+    set_schema_version(conn, 43)?;
+    info!("Migration v43 complete");
+    Ok(())
+}
+
+/// Version 44: password-protected group invites
+fn migrate_v44(conn: &Connection) -> rusqlite::Result<()> {
+    info!("Running migration v44: group invite passwords");
+
+    conn.execute_batch(
+        "
+        -- Whether the inviter said the group needs a password to join
+        ALTER TABLE group_invites ADD COLUMN password_required INTEGER NOT NULL DEFAULT 0;
+        ",
+    )?;
+
+    set_schema_version(conn, 44)?;
+    info!("Migration v44 complete");
+    Ok(())
+}
+
+/// Version 45: public guild directory
+fn migrate_v45(conn: &Connection) -> rusqlite::Result<()> {
+    info!("Running migration v45: guild directory");
+
+    conn.execute_batch(
+        "
+        -- Public guilds the user added to their directory, besides the curated ones
+        CREATE TABLE IF NOT EXISTS guild_directory (
+            chat_id TEXT PRIMARY KEY,
+            name TEXT NOT NULL,
+            description TEXT NOT NULL DEFAULT '',
+            added_at TEXT NOT NULL DEFAULT (datetime('now'))
+        );
+        ",
+    )?;
+
+    set_schema_version(conn, 45)?;
+    info!("Migration v45 complete");
+    Ok(())
+}
+
+/// Version 46: ignored guild members
+fn migrate_v46(conn: &Connection) -> rusqlite::Result<()> {
+    info!("Running migration v46: ignored guild members");
+
+    conn.execute_batch(
+        "
+        -- Members whose messages and status we don't show; only ever local
+        CREATE TABLE IF NOT EXISTS ignored_guild_members (
+            guild_id TEXT NOT NULL,
+            public_key TEXT NOT NULL,
+            ignored_at TEXT NOT NULL DEFAULT (datetime('now')),
+            PRIMARY KEY (guild_id, public_key),
+            FOREIGN KEY (guild_id) REFERENCES guilds(id) ON DELETE CASCADE
+        );
+        ",
+    )?;
+
+    set_schema_version(conn, 46)?;
+    info!("Migration v46 complete");
+    Ok(())
+}
+
+/// Version 47: guild rules acceptance
+fn migrate_v47(conn: &Connection) -> rusqlite::Result<()> {
+    info!("Running migration v47: guild rules acceptance");
+
+    conn.execute_batch(
+        "
+        -- The rules we last accepted in each guild, by hash, so changed rules
+        -- have to be accepted again
+        CREATE TABLE IF NOT EXISTS guild_rules_acks (
+            guild_id TEXT PRIMARY KEY,
+            rules_hash TEXT NOT NULL,
+            accepted_at TEXT NOT NULL DEFAULT (datetime('now')),
+            FOREIGN KEY (guild_id) REFERENCES guilds(id) ON DELETE CASCADE
+        );
+        ",
+    )?;
+
+    set_schema_version(conn, 47)?;
+    info!("Migration v47 complete");
+    Ok(())
+}
+
+/// Version 48: channel notification levels
+fn migrate_v48(conn: &Connection) -> rusqlite::Result<()> {
+    info!("Running migration v48: channel notification levels");
+
+    conn.execute_batch(
+        "
+        -- Per-conversation preferences; only channels use it so far
+        CREATE TABLE IF NOT EXISTS conversation_settings (
+            -- 'dm' (friend public key), 'dm_group' (guild id) or 'channel' (channel id)
+            kind TEXT NOT NULL,
+            target_id TEXT NOT NULL,
+            -- 'all', 'mentions' or 'nothing'; NULL for the default
+            notification_level TEXT,
+            PRIMARY KEY (kind, target_id)
+        );
+
+        -- Whether a message counts as unread, decided by the channel's
+        -- notification level when it arrives. Until now that was mentions.
+        ALTER TABLE channel_messages ADD COLUMN notify INTEGER NOT NULL DEFAULT 0;
+        UPDATE channel_messages SET notify = mentions_me;
+        CREATE INDEX IF NOT EXISTS idx_cmsg_notify ON channel_messages(notify, mention_read);
+        ",
+    )?;
+
+    set_schema_version(conn, 48)?;
+    info!("Migration v48 complete");
+    Ok(())
+}
+
+/// Version 49: what edited messages said before
+fn migrate_v49(conn: &Connection) -> rusqlite::Result<()> {
+    info!("Running migration v49: message revisions");
+
+    conn.execute_batch(
+        "
+        -- Earlier versions of a direct or channel message, kept when it's
+        -- edited. written_at is when that version was sent or edited in.
+        CREATE TABLE IF NOT EXISTS message_revisions (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            message_id TEXT NOT NULL,
+            content TEXT NOT NULL,
+            written_at TEXT NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_revisions_message ON message_revisions(message_id, id);
+
+        -- Channel messages have had this since v1
+        ALTER TABLE direct_messages ADD COLUMN edited_at TEXT;
+
+        -- A deleted or expired message takes its history with it
+        CREATE TRIGGER IF NOT EXISTS dm_revisions_delete AFTER DELETE ON direct_messages BEGIN
+            DELETE FROM message_revisions WHERE message_id = OLD.id;
+        END;
+        CREATE TRIGGER IF NOT EXISTS cmsg_revisions_delete AFTER DELETE ON channel_messages BEGIN
+            DELETE FROM message_revisions WHERE message_id = OLD.id;
+        END;
+        ",
+    )?;
+
+    set_schema_version(conn, 49)?;
+    info!("Migration v49 complete");
+    Ok(())
+}
+
+/// Version 50: who signed channel messages
+fn migrate_v50(conn: &Connection) -> rusqlite::Result<()> {
+    info!("Running migration v50: message signers");
+
+    conn.execute_batch(
+        "
+        -- The Tox key that signed a channel message (NULL when unsigned)
+        ALTER TABLE channel_messages ADD COLUMN signer_public_key TEXT;
+
+        -- The Tox key a member signs with, kept from the first signed message
+        ALTER TABLE guild_members ADD COLUMN identity_key TEXT;
+        ",
+    )?;
+
+    set_schema_version(conn, 50)?;
+    info!("Migration v50 complete");
+    Ok(())
+}
+
+/// Version 51: channel message signatures, to pass on with history
+fn migrate_v51(conn: &Connection) -> rusqlite::Result<()> {
+    info!("Running migration v51: message signatures");
+
+    conn.execute_batch(
+        "
+        -- The routing prefix and signature tag a channel message came with
+        -- (NULL when unsigned or edited since)
+        ALTER TABLE channel_messages ADD COLUMN signed_route TEXT;
+        ALTER TABLE channel_messages ADD COLUMN signature_tag TEXT;
+        ",
+    )?;
+
+    set_schema_version(conn, 51)?;
+    info!("Migration v51 complete");
+    Ok(())
+}
+
+/// Version 52: bump the friends revision for columns added since v28
+fn migrate_v52(conn: &Connection) -> rusqlite::Result<()> {
+    info!("Running migration v52: friends revision for every column");
+
+    conn.execute_batch(
+        "
+        -- Every column but revision itself, which the trigger sets
+        DROP TRIGGER IF EXISTS friends_revision_update;
+        CREATE TRIGGER friends_revision_update AFTER UPDATE ON friends
+        WHEN OLD.public_key IS NOT NEW.public_key
+            OR OLD.name IS NOT NEW.name
+            OR OLD.status_message IS NOT NEW.status_message
+            OR OLD.user_status IS NOT NEW.user_status
+            OR OLD.connection_status IS NOT NEW.connection_status
+            OR OLD.last_seen IS NOT NEW.last_seen
+            OR OLD.added_at IS NOT NEW.added_at
+            OR OLD.notes IS NOT NEW.notes
+            OR OLD.group_id IS NOT NEW.group_id
+            OR OLD.group_position IS NOT NEW.group_position
+            OR OLD.auto_accept_group_invites IS NOT NEW.auto_accept_group_invites
+            OR OLD.last_handshake_at IS NOT NEW.last_handshake_at
+            OR OLD.tox_id IS NOT NEW.tox_id
+        BEGIN
+            UPDATE friends_revision SET revision = revision + 1;
+            UPDATE friends SET revision = (SELECT revision FROM friends_revision)
+            WHERE friend_number = NEW.friend_number;
+        END;
+        ",
+    )?;
+
+    set_schema_version(conn, 52)?;
+    info!("Migration v52 complete");
+    Ok(())
+}
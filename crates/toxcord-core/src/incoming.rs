@@ -0,0 +1,405 @@
+//! Incoming texts, handled the same way by the desktop app and the daemon.
+//!
+//! Long texts arrive in tagged parts (see `toxcord_protocol::codec`) and are
+//! put back together first. A friend's text may then be a conversation lock
+//! envelope (see [`crate::conversation_lock`]). A group text starts with a
+//! routing prefix, `[CH:name]` or `[DM]`, followed by a signature over the
+//! prefix and text (see `toxcord_protocol::signing`) and an ID tag (see
+//! `toxcord_protocol::envelope`); what's left is checked against the guild's
+//! manifest and becomes the record to store. Storing and emitting events is
+//! left to the caller.
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+use toxcord_protocol::codec::TextReassembly;
+use toxcord_protocol::envelope;
+use toxcord_protocol::guild_manifest::{FilterAction, GuildManifest};
+use toxcord_protocol::markdown::{self, Node};
+use toxcord_protocol::signing::{self, MessageSignature, Verification};
+use toxcord_protocol::stickers::StickerRef;
+use toxcord_tox::types::MessageType;
+use tracing::{debug, error, info, warn};
+
+use crate::conversation_lock::{ConversationLocks, Opened};
+use crate::db::message_store::ChannelMessageRecord;
+use crate::db::MessageStore;
+
+/// How long the parts of a long text are kept waiting for the rest
+pub const TEXT_PART_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Profile setting: "true" to store messages from ignored guild members,
+/// hidden, rather than drop them
+pub const KEEP_IGNORED_MESSAGES_SETTING: &str = "keep_ignored_messages";
+
+/// Parts of long texts, until the rest arrive
+pub struct IncomingTexts {
+    /// By friend number
+    friend_parts: Mutex<TextReassembly<u32>>,
+    /// By (group number, peer ID)
+    group_parts: Mutex<TextReassembly<(u32, u32)>>,
+}
+
+impl Default for IncomingTexts {
+    fn default() -> Self {
+        Self {
+            friend_parts: Mutex::new(TextReassembly::new(TEXT_PART_TIMEOUT)),
+            group_parts: Mutex::new(TextReassembly::new(TEXT_PART_TIMEOUT)),
+        }
+    }
+}
+
+/// A friend's text, ready to store and show
+pub struct FriendText {
+    /// What to store: the text, or its envelope if the conversation doesn't
+    /// keep plaintext
+    pub stored: String,
+    /// The text; empty when `locked`
+    pub text: String,
+    /// An envelope we have no key for
+    pub locked: bool,
+    /// The text is a sticker reference
+    pub sticker: Option<StickerRef>,
+}
+
+/// Why a friend's text was dropped
+pub enum FriendTextError {
+    /// Plain text in a locked conversation
+    Unprotected(String),
+    /// The text couldn't be stored safely
+    Unstorable(String),
+}
+
+/// Who sent a group text, as toxcore reports them
+pub struct GroupSender {
+    pub peer_id: u32,
+    pub name: String,
+    /// Uppercase hex
+    pub public_key: String,
+    /// Founder or moderator of the group
+    pub moderator: bool,
+}
+
+/// A group text to store and, unless `ignored`, show
+pub struct GroupText {
+    pub record: ChannelMessageRecord,
+    pub formatted: Vec<Node>,
+    pub sticker: Option<StickerRef>,
+    /// From a member the user ignores; stored out of sight
+    pub ignored: bool,
+}
+
+impl IncomingTexts {
+    /// Open a text from a friend. `None` until the last part of a long text
+    /// arrives.
+    pub fn friend_text(
+        &self,
+        store: &MessageStore,
+        locks: &ConversationLocks,
+        friend_number: u32,
+        message: &str,
+    ) -> Result<Option<FriendText>, FriendTextError> {
+        // Parts are tagged inside any envelope
+        let reassemble = |text: String| self.friend_parts.lock().ok()?.add(friend_number, &text);
+        let opened = match locks.open_received(friend_number, message).map_err(FriendTextError::Unprotected)? {
+            Opened::Plain(text) => reassemble(text).map(Opened::Plain),
+            Opened::Decrypted(text) => reassemble(text).map(Opened::Decrypted),
+            Opened::Locked => Some(Opened::Locked),
+        };
+        let Some(opened) = opened else {
+            return Ok(None);
+        };
+
+        // Envelopes are only stored decrypted if the conversation allows it
+        let (stored, text, locked) = match opened {
+            Opened::Plain(text) => (text.clone(), text, false),
+            Opened::Decrypted(text) if locks.stores_plaintext(friend_number) => (text.clone(), text, false),
+            Opened::Decrypted(text) => {
+                let stored = locks
+                    .content_to_store(friend_number, &text)
+                    .map_err(FriendTextError::Unstorable)?;
+                (stored, text, false)
+            }
+            Opened::Locked => (message.to_string(), String::new(), true),
+        };
+
+        // A friend who sends a sticker reference has the pack
+        let sticker = if locked { None } else { StickerRef::parse(&text) };
+        if let Some(reference) = &sticker {
+            if let Ok(Some(public_key)) = store.get_friend_public_key(friend_number) {
+                if let Err(e) = store.add_sticker_pack_holder(&reference.pack, &public_key) {
+                    error!("{e}");
+                }
+            }
+        }
+        Ok(Some(FriendText { stored, text, locked, sticker }))
+    }
+
+    /// Put a group peer's text back together. `None` until its last part
+    /// arrives.
+    pub fn group_text(&self, group_number: u32, peer_id: u32, message: &str) -> Option<String> {
+        self.group_parts.lock().ok()?.add((group_number, peer_id), message)
+    }
+}
+
+/// Open a whole group text and decide what to keep of it. `None` for texts
+/// that are dropped: echoes of our own, posts by members in announcement
+/// channels, hidden by the word filter, or from ignored members.
+pub fn receive_group_text(
+    store: &MessageStore,
+    group_number: u32,
+    sender: &GroupSender,
+    self_public_key: &str,
+    message_type: MessageType,
+    message: &str,
+) -> Option<GroupText> {
+    let timestamp = chrono::Utc::now().to_rfc3339();
+    let (channel_id, tagged) = route_group_text(store, group_number, message);
+    // Messages from older clients carry no signature or ID. The prefix is
+    // signed too, so a message can't be moved to another channel.
+    let route = &message[..message.len() - tagged.len()];
+    let (verification, signed) = signing::open(&sender.public_key, route, &tagged);
+    if verification == Verification::Invalid {
+        warn!("Message from {} in group {group_number} has a bad signature", sender.name);
+    }
+    let (msg_id, content) = envelope::open(&sender.public_key, signed);
+    let content = content.to_string();
+    if msg_id.is_some() && !self_public_key.is_empty() && sender.public_key.eq_ignore_ascii_case(self_public_key) {
+        debug!("Dropped an echo of our own message in group {group_number}");
+        return None;
+    }
+    let msg_id = msg_id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+
+    info!(
+        "Group message received: group={} peer={} sender='{}' channel={} content_len={}",
+        group_number,
+        sender.peer_id,
+        sender.name,
+        channel_id,
+        content.len()
+    );
+    cache_guild_member(store, group_number, &sender.public_key, &sender.name);
+    let sender_verified = member_verified(store, group_number, &sender.public_key, &verification);
+    // A good signature is kept, to go with the message in channel history
+    let (signer_public_key, signature) = match verification {
+        Verification::Verified(key) => (
+            Some(key),
+            Some(MessageSignature {
+                route: route.to_string(),
+                tag: tagged[..tagged.len() - signed.len()].to_string(),
+            }),
+        ),
+        _ => (None, None),
+    };
+
+    let manifest = channel_manifest(store, &channel_id);
+    let settings = manifest.as_ref().map(|(m, name)| m.channel(name)).unwrap_or_default();
+    if settings.announcement && !sender.moderator {
+        debug!("Dropped a post by {} in announcement channel {channel_id}: not a moderator", sender.name);
+        return None;
+    }
+
+    let filtered = match manifest.and_then(|(m, _)| m.word_filter.check(&content)) {
+        Some(FilterAction::Hide) => {
+            debug!("Hid a message from {} in group {group_number}: matched the word filter", sender.name);
+            return None;
+        }
+        Some(FilterAction::Warn) => true,
+        None => false,
+    };
+
+    // A member who sends a sticker reference has the pack
+    let sticker = StickerRef::parse(&content);
+    if let Some(reference) = &sticker {
+        if let Err(e) = store.add_sticker_pack_holder(&reference.pack, &sender.public_key) {
+            error!("{e}");
+        }
+    }
+
+    // Messages from ignored members are dropped, or kept out of sight
+    // until they're unignored if the user chose to keep them
+    let ignored = is_ignored(store, group_number, &sender.public_key);
+    if ignored && !keep_ignored_messages(store) {
+        debug!("Dropped a message from ignored member {} in group {group_number}", sender.name);
+        return None;
+    }
+
+    let formatted = if sticker.is_some() { Vec::new() } else { markdown::parse(&content) };
+    // Announcements notify like mentions do
+    let mentions_me = !filtered
+        && !ignored
+        && (settings.announcement
+            || (!self_public_key.is_empty() && markdown::mentions(&formatted).iter().any(|key| key == self_public_key)));
+    let notify = !filtered && !ignored && notifies(store, &channel_id, mentions_me);
+
+    Some(GroupText {
+        record: ChannelMessageRecord {
+            id: msg_id,
+            channel_id,
+            sender_public_key: sender.public_key.clone(),
+            sender_name: sender.name.clone(),
+            content,
+            message_type: message_type_name(message_type, sticker.is_some()).to_string(),
+            timestamp,
+            mentions_me,
+            notify,
+            filtered,
+            backfilled: false,
+            sender_verified: Some(sender_verified),
+            signer_public_key,
+            signature,
+        },
+        formatted,
+        sticker,
+        ignored,
+    })
+}
+
+/// How a message's type is stored and shown
+pub fn message_type_name(message_type: MessageType, sticker: bool) -> &'static str {
+    match message_type {
+        _ if sticker => "sticker",
+        MessageType::Normal => "normal",
+        MessageType::Action => "action",
+    }
+}
+
+/// Split a group text into the channel it's for and the rest. `[CH:name]`
+/// is a guild channel, `[DM]` the channel of a DM group, and anything else
+/// goes to the guild's first channel.
+pub fn route_group_text(store: &MessageStore, group_number: u32, message: &str) -> (String, String) {
+    if message.starts_with("[CH:") {
+        if let Some(end) = message.find(']') {
+            let channel_name = &message[4..end];
+            let content = message[end + 1..].to_string();
+            // Servers only, as a DM group can have the same group number
+            let channel_id = store
+                .get_guild_by_group_number_and_type(group_number as i64, "server")
+                .ok()
+                .flatten()
+                .and_then(|guild| store.get_or_create_channel_by_name(&guild.id, channel_name).ok());
+            if let Some(channel_id) = channel_id {
+                return (channel_id, content);
+            }
+            warn!("Failed to route [CH:{channel_name}] message in group {group_number}: server or channel lookup failed");
+        }
+    }
+
+    if let Some(content) = message.strip_prefix("[DM]") {
+        let channel_id = store
+            .get_guild_by_group_number_and_type(group_number as i64, "dm_group")
+            .ok()
+            .flatten()
+            .and_then(|guild| first_channel(store, &guild.id));
+        return match channel_id {
+            Some(channel_id) => (channel_id, content.to_string()),
+            None => {
+                warn!("Failed to find dm_group for group_number={group_number}, using fallback");
+                (format!("dm_group_{group_number}"), content.to_string())
+            }
+        };
+    }
+
+    let channel_id = store
+        .get_guild_by_group_number(group_number as i64)
+        .ok()
+        .flatten()
+        .and_then(|guild| first_channel(store, &guild.id))
+        .unwrap_or_else(|| format!("group_{group_number}"));
+    (channel_id, message.to_string())
+}
+
+fn first_channel(store: &MessageStore, guild_id: &str) -> Option<String> {
+    store.get_channels(guild_id).ok()?.first().map(|c| c.id.clone())
+}
+
+pub fn guild_id_for_group(store: &MessageStore, group_number: u32) -> Option<String> {
+    match store.get_guild_by_group_number(group_number as i64) {
+        Ok(guild) => guild.map(|g| g.id),
+        Err(e) => {
+            error!("Failed to look up guild for group {group_number}: {e}");
+            None
+        }
+    }
+}
+
+/// The manifest of the guild a channel belongs to, with the channel's name
+pub fn channel_manifest(store: &MessageStore, channel_id: &str) -> Option<(GuildManifest, String)> {
+    let result = store.get_channel(channel_id).and_then(|channel| match channel {
+        Some(channel) => Ok(Some((store.get_guild_manifest(&channel.guild_id)?, channel.name))),
+        None => Ok(None),
+    });
+    match result {
+        Ok(manifest) => manifest,
+        Err(e) => {
+            error!("{e}");
+            None
+        }
+    }
+}
+
+/// Remember a peer of the guild behind `group_number` for mention
+/// autocomplete and the offline member list
+pub fn cache_guild_member(store: &MessageStore, group_number: u32, public_key: &str, name: &str) {
+    if public_key.is_empty() || name.is_empty() {
+        return;
+    }
+    if let Some(guild_id) = guild_id_for_group(store, group_number) {
+        if let Err(e) = store.upsert_guild_member(&guild_id, public_key, name) {
+            error!("Failed to cache guild member: {e}");
+        }
+    }
+}
+
+/// Whether a member's message was signed by the Tox identity we know for
+/// them: a friend's, or the one they signed with before
+pub fn member_verified(store: &MessageStore, group_number: u32, public_key: &str, verification: &Verification) -> bool {
+    let Verification::Verified(signer) = verification else {
+        return false;
+    };
+    let Some(guild_id) = guild_id_for_group(store, group_number) else {
+        return false;
+    };
+    match store.check_member_identity(&guild_id, public_key, signer) {
+        Ok(true) => true,
+        Ok(false) => {
+            warn!("Member {public_key} in group {group_number} signed with an identity we don't know: {signer}");
+            false
+        }
+        Err(e) => {
+            error!("{e}");
+            false
+        }
+    }
+}
+
+/// Whether we ignore the member with `public_key` in the guild behind `group_number`
+pub fn is_ignored(store: &MessageStore, group_number: u32, public_key: &str) -> bool {
+    let Some(guild_id) = guild_id_for_group(store, group_number) else {
+        return false;
+    };
+    store.is_guild_member_ignored(&guild_id, public_key).unwrap_or_else(|e| {
+        error!("{e}");
+        false
+    })
+}
+
+pub fn keep_ignored_messages(store: &MessageStore) -> bool {
+    store
+        .get_setting(KEEP_IGNORED_MESSAGES_SETTING)
+        .is_ok_and(|value| value.as_deref() == Some("true"))
+}
+
+/// Whether a message in a channel counts as unread, by the channel's
+/// notification level
+pub fn notifies(store: &MessageStore, channel_id: &str, mentions_me: bool) -> bool {
+    match store.get_channel_notification_level(channel_id).as_deref() {
+        Ok("all") => true,
+        Ok("nothing") => false,
+        Ok(_) => mentions_me,
+        Err(e) => {
+            error!("{e}");
+            mentions_me
+        }
+    }
+}
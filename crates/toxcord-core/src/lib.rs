@@ -0,0 +1,13 @@
+//! Toxcord's Tox core without the desktop UI: the message store, the media
+//! cache, how texts are sent and received, and a headless daemon that serves
+//! the core over a local JSON-RPC socket.
+
+pub mod assets;
+pub mod conversation_lock;
+pub mod daemon;
+pub mod db;
+pub mod incoming;
+pub mod outgoing;
+pub mod profile_dirs;
+pub mod profile_file;
+pub mod rpc;
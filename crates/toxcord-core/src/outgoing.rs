@@ -0,0 +1,54 @@
+//! Outgoing texts, prepared the same way by the desktop app and the daemon,
+//! so the other side can take them apart with [`crate::incoming`].
+
+use toxcord_protocol::fingerprint::public_key_from_hex;
+use toxcord_protocol::signing::{self, MessageSignature};
+use toxcord_tox::ToxInstance;
+
+/// Ties the parts of a long text together
+pub fn text_id() -> u32 {
+    uuid::Uuid::new_v4().as_u128() as u32
+}
+
+/// Sign a routed group text with our Tox key (see `signing`). The signature
+/// goes after the `[CH:..]` or `[DM]` prefix; other texts are sent as they are.
+pub fn sign_group_text(tox: &ToxInstance, group_number: u32, message: &str) -> Result<String, String> {
+    let prefix_len = if message.starts_with("[CH:") {
+        message.find(']').map(|i| i + 1)
+    } else {
+        message.starts_with("[DM]").then_some(4)
+    };
+    let Some(prefix_len) = prefix_len else {
+        return Ok(message.to_string());
+    };
+    let (prefix, text) = message.split_at(prefix_len);
+    let tag = signature_tag(tox, group_number, prefix, text)?;
+    Ok(format!("{prefix}{tag}{text}"))
+}
+
+/// Sign a text we sent behind `route` before, to pass it on with the
+/// channel's history
+pub fn sign_for_history(
+    tox: &ToxInstance,
+    group_number: u32,
+    route: &str,
+    text: &str,
+) -> Result<MessageSignature, String> {
+    Ok(MessageSignature {
+        route: route.to_string(),
+        tag: signature_tag(tox, group_number, route, text)?,
+    })
+}
+
+fn signature_tag(tox: &ToxInstance, group_number: u32, route: &str, text: &str) -> Result<String, String> {
+    let peer_public_key: String = tox
+        .group_self_get_public_key(group_number)
+        .map_err(|e| e.to_string())?
+        .iter()
+        .map(|b| format!("{b:02X}"))
+        .collect();
+    let public_key = public_key_from_hex(&tox.self_public_key().0).ok_or("Invalid self public key")?;
+    let mut random = [0u8; 64];
+    getrandom::fill(&mut random).map_err(|e| format!("Failed to sign message: {e}"))?;
+    Ok(signing::seal_tag(&tox.self_secret_key(), &public_key, &peer_public_key, route, text, &random))
+}
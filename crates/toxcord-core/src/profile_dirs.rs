@@ -0,0 +1,277 @@
+//! Where each profile keeps its data.
+//!
+//! Every profile has a directory of its own under `profiles/`:
+//!
+//! ```text
+//! profiles/<name>/profile.tox       savedata, with profile.tox.1.. backups
+//! profiles/<name>/messages.db       message store
+//! profiles/<name>/attachments/      files sent and received
+//! profiles/<name>/assets/           media cache
+//! profiles/<name>/logs/toxcord.log
+//! ```
+//!
+//! Older versions kept `<name>.tox`, `<name>.db` and `<name>.log` side by
+//! side in `profiles/`, and shared `attachments/` and `assets/` between
+//! profiles. [`migrate_legacy_layout`] moves each profile's own files into its
+//! directory. The shared directories are left alone, since messages refer to
+//! the files in them by path.
+//!
+//! The message store records attachment paths, so a directory that was
+//! renamed or copied is fixed up with [`relocate_stored_paths`] when the
+//! profile next loads.
+
+use std::path::{Path, PathBuf, MAIN_SEPARATOR};
+
+use tracing::{info, warn};
+
+use crate::db::MessageStore;
+
+pub const SAVEDATA_FILE: &str = "profile.tox";
+pub const DATABASE_FILE: &str = "messages.db";
+pub const ATTACHMENTS_DIR: &str = "attachments";
+pub const ASSETS_DIR: &str = "assets";
+pub const LOGS_DIR: &str = "logs";
+pub const LOG_FILE: &str = "toxcord.log";
+
+/// Profile setting holding the directory stored file paths were written under
+const PROFILE_DIR_SETTING: &str = "profile_dir";
+
+pub fn profiles_dir() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("toxcord")
+        .join("profiles")
+}
+
+/// The paths of one profile's data
+#[derive(Debug, Clone)]
+pub struct ProfileDir {
+    pub dir: PathBuf,
+}
+
+impl ProfileDir {
+    pub fn new(profile_name: &str) -> Self {
+        Self { dir: profiles_dir().join(profile_name) }
+    }
+
+    pub fn savedata(&self) -> PathBuf {
+        self.dir.join(SAVEDATA_FILE)
+    }
+
+    pub fn database(&self) -> PathBuf {
+        self.dir.join(DATABASE_FILE)
+    }
+
+    pub fn attachments(&self) -> PathBuf {
+        self.dir.join(ATTACHMENTS_DIR)
+    }
+
+    pub fn assets(&self) -> PathBuf {
+        self.dir.join(ASSETS_DIR)
+    }
+
+    pub fn logs(&self) -> PathBuf {
+        self.dir.join(LOGS_DIR)
+    }
+
+    pub fn log_file(&self) -> PathBuf {
+        self.logs().join(LOG_FILE)
+    }
+
+    /// A profile exists once it has savedata
+    pub fn exists(&self) -> bool {
+        self.savedata().exists()
+    }
+}
+
+/// Names of the profiles with savedata, sorted
+pub fn list_profiles() -> Vec<String> {
+    let mut names: Vec<String> = std::fs::read_dir(profiles_dir())
+        .map(|entries| {
+            entries
+                .filter_map(|e| e.ok())
+                .filter(|e| e.path().join(SAVEDATA_FILE).exists())
+                .map(|e| e.file_name().to_string_lossy().to_string())
+                .collect()
+        })
+        .unwrap_or_default();
+    names.sort();
+    names
+}
+
+/// Move profiles from the flat layout into their own directories. Returns
+/// the profiles moved.
+pub fn migrate_legacy_layout() -> Vec<String> {
+    migrate_legacy_layout_in(&profiles_dir())
+}
+
+fn migrate_legacy_layout_in(root: &Path) -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir(root) else {
+        return Vec::new();
+    };
+    let mut files: Vec<String> = entries
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().is_file())
+        .map(|e| e.file_name().to_string_lossy().to_string())
+        .collect();
+
+    // Longer names first, so "a.db" takes its files before "a" looks
+    let mut names: Vec<String> = files.iter().filter_map(|f| f.strip_suffix(".tox")).map(str::to_string).collect();
+    names.sort_by_key(|name| std::cmp::Reverse(name.len()));
+
+    // Each directory is filled under a temporary name, as a longer profile's
+    // directory can be named like a shorter one's file ("a.db" for "a")
+    let staging = |name: &str| root.join(format!(".migrating-{name}"));
+    let mut staged = Vec::new();
+    for name in names {
+        if root.join(&name).join(SAVEDATA_FILE).exists() {
+            warn!("Profile '{name}' is in both layouts, leaving the old files alone");
+            continue;
+        }
+        let dir = staging(&name);
+        if let Err(e) = std::fs::create_dir_all(dir.join(LOGS_DIR)) {
+            warn!("Failed to create a directory for profile '{name}': {e}");
+            continue;
+        }
+        files.retain(|file| {
+            let suffix = file.strip_prefix(name.as_str()).and_then(|rest| rest.strip_prefix('.'));
+            let Some(new_name) = suffix.and_then(legacy_file_name) else {
+                return true;
+            };
+            if let Err(e) = std::fs::rename(root.join(file), dir.join(&new_name)) {
+                warn!("Failed to move {file} into profile '{name}': {e}");
+            }
+            false
+        });
+        staged.push(name);
+    }
+
+    let mut migrated = Vec::new();
+    for name in staged {
+        match std::fs::rename(staging(&name), root.join(&name)) {
+            Ok(()) => {
+                info!("Moved profile '{name}' into its own directory");
+                migrated.push(name);
+            }
+            Err(e) => {
+                let staged_dir = staging(&name);
+                warn!("Failed to move profile '{name}' into its directory, its files are in {}: {e}", staged_dir.display());
+            }
+        }
+    }
+    migrated
+}
+
+/// Where a legacy file goes in the profile directory, from the part of its
+/// name after `<profile>.`: `tox.1` becomes `profile.tox.1`, `db-wal`
+/// becomes `messages.db-wal`, `log.1` becomes `logs/toxcord.log.1`. Only
+/// these exact suffixes match; anything else belongs to another profile.
+fn legacy_file_name(suffix: &str) -> Option<String> {
+    let log = format!("{LOGS_DIR}/{LOG_FILE}");
+    match suffix {
+        "tox" => Some(SAVEDATA_FILE.to_string()),
+        "db" | "db-wal" | "db-shm" => Some(format!("{DATABASE_FILE}{}", &suffix[2..])),
+        "log" | "log.1" => Some(format!("{log}{}", &suffix[3..])),
+        _ => {
+            // Savedata backups, tox.1, tox.2, ..
+            let backup = suffix.strip_prefix("tox.")?;
+            let numbered = !backup.is_empty() && backup.bytes().all(|b| b.is_ascii_digit());
+            numbered.then(|| format!("{SAVEDATA_FILE}.{backup}"))
+        }
+    }
+}
+
+/// Point the file paths in the message store at the profile's directory, if
+/// it was renamed or copied since they were written
+pub fn relocate_stored_paths(store: &MessageStore) -> Result<(), String> {
+    let current = store.dir().to_string_lossy().to_string();
+    if let Some(previous) = store.get_setting(PROFILE_DIR_SETTING)? {
+        if previous != current {
+            let moved = store.relocate_paths(&format!("{previous}{MAIN_SEPARATOR}"), &format!("{current}{MAIN_SEPARATOR}"))?;
+            info!("Profile moved from {previous}, updated {moved} file paths");
+        }
+    }
+    store.set_setting(PROFILE_DIR_SETTING, &current)
+}
+
+/// Total size of the files under a path
+pub fn disk_usage(path: &Path) -> u64 {
+    let Ok(metadata) = std::fs::symlink_metadata(path) else {
+        return 0;
+    };
+    if !metadata.is_dir() {
+        return metadata.len();
+    }
+    std::fs::read_dir(path)
+        .map(|entries| entries.filter_map(|e| e.ok()).map(|e| disk_usage(&e.path())).sum())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_legacy_file_name() {
+        assert_eq!(legacy_file_name("tox").as_deref(), Some("profile.tox"));
+        assert_eq!(legacy_file_name("tox.2").as_deref(), Some("profile.tox.2"));
+        assert_eq!(legacy_file_name("db-wal").as_deref(), Some("messages.db-wal"));
+        assert_eq!(legacy_file_name("log.1").as_deref(), Some("logs/toxcord.log.1"));
+        // Another profile's files, e.g. "alice.b.tox" seen from "alice"
+        assert_eq!(legacy_file_name("b.tox"), None);
+        assert_eq!(legacy_file_name("db.tox"), None);
+        assert_eq!(legacy_file_name("tox.x"), None);
+        assert_eq!(legacy_file_name("log.db"), None);
+        assert_eq!(legacy_file_name("toxic"), None);
+    }
+
+    #[test]
+    fn test_migrate_legacy_layout() {
+        let root = std::env::temp_dir().join(format!("toxcord-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&root).unwrap();
+        for file in ["alice.tox", "alice.tox.1", "alice.db", "alice.log", "alice.b.tox", "settings.json"] {
+            std::fs::write(root.join(file), file).unwrap();
+        }
+
+        let mut migrated = migrate_legacy_layout_in(&root);
+        migrated.sort();
+        assert_eq!(migrated, vec!["alice", "alice.b"]);
+        assert_eq!(std::fs::read_to_string(root.join("alice").join(SAVEDATA_FILE)).unwrap(), "alice.tox");
+        assert!(root.join("alice").join("profile.tox.1").exists());
+        assert!(root.join("alice").join(DATABASE_FILE).exists());
+        assert!(root.join("alice").join(LOGS_DIR).join(LOG_FILE).exists());
+        assert_eq!(std::fs::read_to_string(root.join("alice.b").join(SAVEDATA_FILE)).unwrap(), "alice.b.tox");
+        assert!(root.join("settings.json").exists());
+        assert_eq!(disk_usage(&root.join("alice")), ["alice.tox", "alice.tox.1", "alice.db", "alice.log"].iter().map(|f| f.len() as u64).sum::<u64>());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_migrate_dotted_profile_names() {
+        let root = std::env::temp_dir().join(format!("toxcord-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&root).unwrap();
+        let files = ["a.tox", "a.db", "a.db-wal", "a.log", "a.db.tox", "a.db.tox.1", "a.db.db", "a.db.log"];
+        for file in files {
+            std::fs::write(root.join(file), file).unwrap();
+        }
+
+        let mut migrated = migrate_legacy_layout_in(&root);
+        migrated.sort();
+        assert_eq!(migrated, vec!["a", "a.db"]);
+        let read = |profile: &str, file: &str| std::fs::read_to_string(root.join(profile).join(file)).unwrap();
+        assert_eq!(read("a", SAVEDATA_FILE), "a.tox");
+        assert_eq!(read("a", DATABASE_FILE), "a.db");
+        assert_eq!(read("a", "messages.db-wal"), "a.db-wal");
+        assert_eq!(read("a", &format!("{LOGS_DIR}/{LOG_FILE}")), "a.log");
+        assert_eq!(read("a.db", SAVEDATA_FILE), "a.db.tox");
+        assert_eq!(read("a.db", "profile.tox.1"), "a.db.tox.1");
+        assert_eq!(read("a.db", DATABASE_FILE), "a.db.db");
+        assert_eq!(read("a.db", &format!("{LOGS_DIR}/{LOG_FILE}")), "a.db.log");
+        for file in files {
+            assert!(!root.join(file).is_file(), "{file} was left behind");
+        }
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,186 @@
+//! Profile savedata on disk.
+//!
+//! The `.tox` file is the only copy of the identity and friend list, so it's
+//! never written in place: a save goes to a temporary file that is renamed
+//! over the profile once it's fully on disk. The previous few versions are
+//! kept next to it as `profile.tox.1` (newest) to `profile.tox.<PROFILE_BACKUPS>`,
+//! and loading falls back to them when the profile can't be read.
+//!
+//! The desktop app and the daemon both load and save profiles through
+//! [`read_profile`] and [`write_profile`].
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use toxcord_tox::tox::{decrypt_savedata, encrypt_savedata, is_data_encrypted};
+use tracing::{info, warn};
+
+/// Old versions kept of each profile
+pub const PROFILE_BACKUPS: usize = 3;
+
+/// Plain toxcore savedata starts with four zero bytes and this cookie
+const SAVEDATA_COOKIE: u32 = 0x15ed_1b1f;
+
+/// Path of the `n`th backup, 1 being the newest
+pub fn backup_path(path: &Path, n: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{n}"));
+    PathBuf::from(name)
+}
+
+/// Whether decrypted data looks like toxcore savedata, to tell a damaged
+/// file from a good one before handing it to toxcore
+pub fn is_savedata(data: &[u8]) -> bool {
+    data.len() >= 8 && data[..4] == [0; 4] && u32::from_le_bytes([data[4], data[5], data[6], data[7]]) == SAVEDATA_COOKIE
+}
+
+/// Read a profile's savedata, decrypting it if it's encrypted. A damaged
+/// profile is replaced by its newest good backup.
+pub fn read_profile(path: &Path, password: &str) -> Result<Vec<u8>, String> {
+    read_with_fallback(path, |data| {
+        let savedata = if is_data_encrypted(&data) {
+            decrypt_savedata(&data, password).map_err(|e| format!("Failed to decrypt profile: {e}"))?
+        } else {
+            data
+        };
+        if !is_savedata(&savedata) {
+            return Err("Profile is damaged".to_string());
+        }
+        Ok(savedata)
+    })
+}
+
+/// Save a profile's savedata, encrypted with `password` unless it's empty.
+/// Nothing is written if encryption fails.
+pub fn write_profile(path: &Path, savedata: &[u8], password: &str) -> Result<(), String> {
+    if password.is_empty() {
+        return write_atomic(path, savedata);
+    }
+    let encrypted = encrypt_savedata(savedata, password).map_err(|e| format!("Failed to encrypt profile, not saving: {e}"))?;
+    write_atomic(path, &encrypted)
+}
+
+/// Replace the profile with `data`, keeping the old one as the newest backup
+pub fn write_atomic(path: &Path, data: &[u8]) -> Result<(), String> {
+    let mut tmp_name = path.as_os_str().to_owned();
+    tmp_name.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_name);
+
+    let mut file = std::fs::File::create(&tmp_path)
+        .map_err(|e| format!("Failed to save profile to {}: {e}", tmp_path.display()))?;
+    file.write_all(data)
+        .and_then(|()| file.sync_all())
+        .map_err(|e| format!("Failed to save profile to {}: {e}", tmp_path.display()))?;
+    drop(file);
+
+    if path.exists() {
+        rotate_backups(path);
+    }
+    std::fs::rename(&tmp_path, path).map_err(|e| format!("Failed to save profile to {}: {e}", path.display()))
+}
+
+/// Shift the backups down one and keep the current profile as the newest.
+/// The profile stays in place, so there's always a complete copy under its name.
+fn rotate_backups(path: &Path) {
+    for n in (1..PROFILE_BACKUPS).rev() {
+        let from = backup_path(path, n);
+        if from.exists() {
+            if let Err(e) = std::fs::rename(&from, backup_path(path, n + 1)) {
+                warn!("Failed to rotate profile backup {}: {e}", from.display());
+            }
+        }
+    }
+    let newest = backup_path(path, 1);
+    let _ = std::fs::remove_file(&newest);
+    if std::fs::hard_link(path, &newest).is_err() {
+        if let Err(e) = std::fs::copy(path, &newest) {
+            warn!("Failed to back up profile to {}: {e}", newest.display());
+        }
+    }
+}
+
+/// Read the profile through `decode`, which decrypts and checks it. If that
+/// fails, the newest backup that decodes is put back in the profile's place
+/// and returned; the damaged file is kept as `profile.tox.corrupt`. When no
+/// backup decodes either, the profile's own error is returned, so a wrong
+/// password still reads as one.
+pub fn read_with_fallback(path: &Path, decode: impl Fn(Vec<u8>) -> Result<Vec<u8>, String>) -> Result<Vec<u8>, String> {
+    let error = match std::fs::read(path).map_err(|e| format!("Failed to read profile: {e}")).and_then(&decode) {
+        Ok(data) => return Ok(data),
+        Err(e) => e,
+    };
+
+    for n in 1..=PROFILE_BACKUPS {
+        let backup = backup_path(path, n);
+        let Ok(raw) = std::fs::read(&backup) else {
+            continue;
+        };
+        let Ok(data) = decode(raw) else {
+            continue;
+        };
+        warn!("Profile {} can't be read ({error}), restoring backup {n}", path.display());
+        restore_backup(path, &backup);
+        return Ok(data);
+    }
+    Err(error)
+}
+
+fn restore_backup(path: &Path, backup: &Path) {
+    if path.exists() {
+        let mut corrupt = path.as_os_str().to_owned();
+        corrupt.push(".corrupt");
+        if let Err(e) = std::fs::rename(path, PathBuf::from(corrupt)) {
+            warn!("Failed to set aside damaged profile: {e}");
+        }
+    }
+    match std::fs::copy(backup, path) {
+        Ok(_) => info!("Restored profile from {}", backup.display()),
+        Err(e) => warn!("Failed to restore profile from {}: {e}", backup.display()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn savedata(tag: u8) -> Vec<u8> {
+        let mut data = vec![0u8; 4];
+        data.extend_from_slice(&SAVEDATA_COOKIE.to_le_bytes());
+        data.push(tag);
+        data
+    }
+
+    fn check(data: Vec<u8>) -> Result<Vec<u8>, String> {
+        if is_savedata(&data) {
+            Ok(data)
+        } else {
+            Err("Profile is damaged".to_string())
+        }
+    }
+
+    #[test]
+    fn test_rotation_and_recovery() {
+        let dir = std::env::temp_dir().join(format!("toxcord-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("alice.tox");
+
+        for tag in 1..=5 {
+            write_atomic(&path, &savedata(tag)).unwrap();
+        }
+        assert_eq!(std::fs::read(&path).unwrap(), savedata(5));
+        assert_eq!(std::fs::read(backup_path(&path, 1)).unwrap(), savedata(4));
+        assert_eq!(std::fs::read(backup_path(&path, PROFILE_BACKUPS)).unwrap(), savedata(2));
+        assert!(!backup_path(&path, PROFILE_BACKUPS + 1).exists());
+
+        // A torn write falls back to the newest backup and puts it back
+        std::fs::write(&path, b"\0\0garbage").unwrap();
+        assert_eq!(read_with_fallback(&path, check).unwrap(), savedata(4));
+        assert_eq!(std::fs::read(&path).unwrap(), savedata(4));
+
+        // Nothing decodes: the profile's own error comes back
+        let err = read_with_fallback(&path, |_| Err("Failed to decrypt profile".to_string())).unwrap_err();
+        assert_eq!(err, "Failed to decrypt profile");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,173 @@
+//! JSON-RPC 2.0 over a line-delimited stream.
+//!
+//! Each request, response and notification is one JSON object per line.
+//! Tox events are pushed to subscribed clients as `event` notifications whose
+//! params have the same `{ "type", "data" }` shape as the desktop's
+//! `tox://event` payloads.
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+pub const INTERNAL_ERROR: i64 = -32603;
+
+const VERSION: &str = "2.0";
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Request {
+    pub jsonrpc: String,
+    /// Absent for notifications, which get no response
+    #[serde(default)]
+    pub id: Option<Value>,
+    pub method: String,
+    #[serde(default)]
+    pub params: Value,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl RpcError {
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+
+    /// A failed Tox or database operation
+    pub fn internal(message: impl Into<String>) -> Self {
+        Self::new(INTERNAL_ERROR, message)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Response {
+    pub jsonrpc: &'static str,
+    pub id: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<RpcError>,
+}
+
+impl Response {
+    pub fn new(id: Value, result: Result<Value, RpcError>) -> Self {
+        let (result, error) = match result {
+            Ok(value) => (Some(value), None),
+            Err(e) => (None, Some(e)),
+        };
+        Self {
+            jsonrpc: VERSION,
+            id,
+            result,
+            error,
+        }
+    }
+
+    pub fn to_line(&self) -> String {
+        let mut line = serde_json::to_string(self).unwrap_or_default();
+        line.push('\n');
+        line
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Notification {
+    pub jsonrpc: &'static str,
+    pub method: &'static str,
+    pub params: Value,
+}
+
+impl Notification {
+    /// An `event` notification carrying a serialized Tox event
+    pub fn event(params: Value) -> Self {
+        Self {
+            jsonrpc: VERSION,
+            method: "event",
+            params,
+        }
+    }
+
+    pub fn to_line(&self) -> String {
+        let mut line = serde_json::to_string(self).unwrap_or_default();
+        line.push('\n');
+        line
+    }
+}
+
+/// Parse one line. On failure returns the error response to send back.
+pub fn parse_request(line: &str) -> Result<Request, Response> {
+    let value: Value = serde_json::from_str(line)
+        .map_err(|e| Response::new(Value::Null, Err(RpcError::new(PARSE_ERROR, format!("Parse error: {e}")))))?;
+    let id = value.get("id").cloned().unwrap_or(Value::Null);
+    let request: Request = serde_json::from_value(value)
+        .map_err(|e| Response::new(id.clone(), Err(RpcError::new(INVALID_REQUEST, format!("Invalid request: {e}")))))?;
+    if request.jsonrpc != VERSION {
+        return Err(Response::new(id, Err(RpcError::new(INVALID_REQUEST, "jsonrpc must be \"2.0\""))));
+    }
+    Ok(request)
+}
+
+/// Deserialize the params of a request into `T`
+pub fn params<T: DeserializeOwned>(request: &Request) -> Result<T, RpcError> {
+    let params = if request.params.is_null() {
+        Value::Object(Default::default())
+    } else {
+        request.params.clone()
+    };
+    serde_json::from_value(params).map_err(|e| RpcError::new(INVALID_PARAMS, format!("Invalid params: {e}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_request() {
+        let request = parse_request(r#"{"jsonrpc":"2.0","id":1,"method":"friend_send_message","params":{"friend_number":3,"message":"hi"}}"#).unwrap();
+        assert_eq!(request.method, "friend_send_message");
+        assert_eq!(request.id, Some(Value::from(1)));
+
+        #[derive(Deserialize)]
+        struct SendParams {
+            friend_number: u32,
+            message: String,
+        }
+        let p: SendParams = params(&request).unwrap();
+        assert_eq!((p.friend_number, p.message.as_str()), (3, "hi"));
+
+        let notification = parse_request(r#"{"jsonrpc":"2.0","method":"subscribe"}"#).unwrap();
+        assert_eq!(notification.id, None);
+        assert!(params::<SendParams>(&notification).is_err());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let err = parse_request("{not json").unwrap_err();
+        assert_eq!(err.error.unwrap().code, PARSE_ERROR);
+
+        let err = parse_request(r#"{"jsonrpc":"2.0","id":"a"}"#).unwrap_err();
+        assert_eq!(err.id, Value::from("a"));
+        assert_eq!(err.error.unwrap().code, INVALID_REQUEST);
+
+        let err = parse_request(r#"{"jsonrpc":"1.0","id":2,"method":"x"}"#).unwrap_err();
+        assert_eq!(err.error.unwrap().code, INVALID_REQUEST);
+    }
+
+    #[test]
+    fn test_response_lines() {
+        let ok = Response::new(Value::from(1), Ok(Value::from("ABC"))).to_line();
+        assert_eq!(ok, "{\"jsonrpc\":\"2.0\",\"id\":1,\"result\":\"ABC\"}\n");
+
+        let err = Response::new(Value::from(2), Err(RpcError::new(METHOD_NOT_FOUND, "nope"))).to_line();
+        assert_eq!(err, "{\"jsonrpc\":\"2.0\",\"id\":2,\"error\":{\"code\":-32601,\"message\":\"nope\"}}\n");
+    }
+}
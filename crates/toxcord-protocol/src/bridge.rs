@@ -0,0 +1,182 @@
+//! Channel bridges to IRC and Matrix.
+//!
+//! A bridged guild channel relays every message to one external endpoint
+//! (an IRC channel or a Matrix room) and back. Relayed messages carry the
+//! original sender's name as a `<name> text` prefix, since on the other side
+//! they all come from the bridge's own account.
+//!
+//! This module holds the wire-level helpers; the connections themselves live
+//! in the desktop app.
+
+use serde::{Deserialize, Serialize};
+
+/// Longest IRC line, including the trailing CRLF (RFC 1459)
+pub const IRC_MAX_LINE: usize = 512;
+
+/// Room left in a PRIVMSG line for the `:nick!user@host ` prefix the server
+/// adds when relaying it to other clients
+const IRC_PREFIX_RESERVE: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BridgeProtocol {
+    Irc,
+    Matrix,
+}
+
+impl BridgeProtocol {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Irc => "irc",
+            Self::Matrix => "matrix",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "irc" => Some(Self::Irc),
+            "matrix" => Some(Self::Matrix),
+            _ => None,
+        }
+    }
+}
+
+/// Text of a message relayed on behalf of `sender`
+pub fn relay_text(sender: &str, content: &str) -> String {
+    format!("<{sender}> {content}")
+}
+
+/// A parsed IRC protocol line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IrcMessage {
+    /// Source, e.g. `nick!user@host` or a server name
+    pub prefix: Option<String>,
+    pub command: String,
+    pub params: Vec<String>,
+}
+
+impl IrcMessage {
+    /// Parse one line (without CRLF). IRCv3 message tags are skipped.
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut rest = line.trim_end_matches(['\r', '\n']);
+        if rest.starts_with('@') {
+            rest = rest.split_once(' ')?.1;
+        }
+        let prefix = match rest.strip_prefix(':') {
+            Some(stripped) => {
+                let (prefix, after) = stripped.split_once(' ')?;
+                rest = after;
+                Some(prefix.to_string())
+            }
+            None => None,
+        };
+
+        let (head, trailing) = match rest.split_once(" :") {
+            Some((head, trailing)) => (head, Some(trailing)),
+            None => (rest, None),
+        };
+        let mut words = head.split(' ').filter(|w| !w.is_empty());
+        let command = words.next()?.to_uppercase();
+        let mut params: Vec<String> = words.map(str::to_string).collect();
+        params.extend(trailing.map(str::to_string));
+
+        Some(Self { prefix, command, params })
+    }
+
+    /// Nickname part of the prefix
+    pub fn nick(&self) -> Option<&str> {
+        let prefix = self.prefix.as_deref()?;
+        Some(prefix.split(['!', '@']).next().unwrap_or(prefix))
+    }
+}
+
+/// Relay text for a PRIVMSG from `nick`, with CTCP ACTIONs (`/me`) as
+/// `* nick text`. Returns `None` for other CTCP requests.
+pub fn irc_relay_text(nick: &str, text: &str) -> Option<String> {
+    match text.strip_prefix('\u{1}') {
+        Some(ctcp) => {
+            let ctcp = ctcp.trim_end_matches('\u{1}');
+            ctcp.strip_prefix("ACTION ").map(|action| format!("* {nick} {action}"))
+        }
+        None => Some(relay_text(nick, text)),
+    }
+}
+
+/// PRIVMSG lines (with CRLF) carrying `text` to `target`. Newlines start a
+/// new message and long lines are split on character boundaries.
+pub fn irc_privmsg_lines(target: &str, text: &str) -> Vec<String> {
+    let overhead = format!("PRIVMSG {target} :\r\n").len() + IRC_PREFIX_RESERVE;
+    let max_text = IRC_MAX_LINE.saturating_sub(overhead).max(1);
+
+    let mut lines = Vec::new();
+    for line in text.lines().filter(|l| !l.trim().is_empty()) {
+        let mut chunk = String::new();
+        for c in line.chars() {
+            if chunk.len() + c.len_utf8() > max_text {
+                lines.push(format!("PRIVMSG {target} :{chunk}\r\n"));
+                chunk.clear();
+            }
+            chunk.push(c);
+        }
+        if !chunk.is_empty() {
+            lines.push(format!("PRIVMSG {target} :{chunk}\r\n"));
+        }
+    }
+    lines
+}
+
+/// Display name for a Matrix user ID: `@alice:example.org` -> `alice`
+pub fn matrix_localpart(user_id: &str) -> &str {
+    let id = user_id.strip_prefix('@').unwrap_or(user_id);
+    id.split(':').next().unwrap_or(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_irc_message() {
+        let msg = IrcMessage::parse(":alice!~a@host.example PRIVMSG #toxcord :hello there :)\r\n").unwrap();
+        assert_eq!(msg.nick(), Some("alice"));
+        assert_eq!(msg.command, "PRIVMSG");
+        assert_eq!(msg.params, ["#toxcord", "hello there :)"]);
+
+        let ping = IrcMessage::parse("PING :irc.example.net").unwrap();
+        assert_eq!(ping.prefix, None);
+        assert_eq!(ping.params, ["irc.example.net"]);
+
+        let tagged = IrcMessage::parse("@time=2024-05-10T12:00:00Z :srv 001 toxbridge :Welcome").unwrap();
+        assert_eq!(tagged.command, "001");
+        assert_eq!(tagged.params, ["toxbridge", "Welcome"]);
+
+        assert_eq!(IrcMessage::parse(""), None);
+        assert_eq!(irc_relay_text("bob", "hey").as_deref(), Some("<bob> hey"));
+        assert_eq!(irc_relay_text("bob", "\u{1}ACTION waves\u{1}").as_deref(), Some("* bob waves"));
+        assert_eq!(irc_relay_text("bob", "\u{1}VERSION\u{1}"), None);
+    }
+
+    #[test]
+    fn test_irc_privmsg_lines() {
+        let lines = irc_privmsg_lines("#toxcord", "<alice> one\n\n<alice> two");
+        assert_eq!(lines, ["PRIVMSG #toxcord :<alice> one\r\n", "PRIVMSG #toxcord :<alice> two\r\n"]);
+
+        let long = "é".repeat(400);
+        let lines = irc_privmsg_lines("#toxcord", &long);
+        assert!(lines.len() > 1);
+        assert!(lines.iter().all(|l| l.len() + IRC_PREFIX_RESERVE <= IRC_MAX_LINE));
+        let rejoined: String = lines
+            .iter()
+            .map(|l| l.trim_start_matches("PRIVMSG #toxcord :").trim_end_matches("\r\n"))
+            .collect();
+        assert_eq!(rejoined, long);
+    }
+
+    #[test]
+    fn test_names() {
+        assert_eq!(relay_text("alice", "hi"), "<alice> hi");
+        assert_eq!(matrix_localpart("@bob:matrix.org"), "bob");
+        assert_eq!(BridgeProtocol::parse("matrix"), Some(BridgeProtocol::Matrix));
+        assert_eq!(BridgeProtocol::parse("xmpp"), None);
+    }
+}
@@ -166,9 +166,120 @@ impl ReassemblyBuffer {
     }
 }
 
+/// Marks one part of a long text message: `[PART:<id>:<seq>/<total>]` followed
+/// by the part's text. Parts are reassembled by sender and id on receive.
+pub const PART_PREFIX: &str = "[PART:";
+
+/// Longest part tag, with every field at its widest
+pub const MAX_PART_TAG_LEN: usize = "[PART:ffffffff:65535/65535]".len();
+
+/// Most parts a text message is split into (about 85 KB)
+pub const MAX_TEXT_PARTS: usize = 64;
+
+/// Split a text message into parts of at most `max_len` bytes, tagged for
+/// reassembly. A message that fits is sent as it is, untagged.
+pub fn split_tagged(message: &str, max_len: usize, id: u32) -> Result<Vec<String>, String> {
+    if message.len() <= max_len {
+        return Ok(vec![message.to_string()]);
+    }
+    let parts = split_message(message, max_len - MAX_PART_TAG_LEN);
+    if parts.len() > MAX_TEXT_PARTS {
+        return Err("Message is too long".to_string());
+    }
+    let total = parts.len();
+    Ok(parts
+        .into_iter()
+        .enumerate()
+        .map(|(seq, part)| format!("{PART_PREFIX}{id:08x}:{seq}/{total}]{part}"))
+        .collect())
+}
+
+/// A tagged part of a text message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextPart<'a> {
+    pub id: u32,
+    pub seq: usize,
+    pub total: usize,
+    pub text: &'a str,
+}
+
+/// Parse a part tag. Returns `None` for text that isn't a valid part.
+pub fn parse_part(text: &str) -> Option<TextPart<'_>> {
+    let rest = text.strip_prefix(PART_PREFIX)?;
+    let (tag, text) = rest.split_once(']')?;
+    let (id, position) = tag.split_once(':')?;
+    let (seq, total) = position.split_once('/')?;
+    let part = TextPart {
+        id: u32::from_str_radix(id, 16).ok()?,
+        seq: seq.parse().ok()?,
+        total: total.parse().ok()?,
+        text,
+    };
+    (part.total >= 2 && part.total <= MAX_TEXT_PARTS && part.seq < part.total).then_some(part)
+}
+
+/// Most messages being reassembled at once, per buffer
+const MAX_PENDING_TEXTS: usize = 32;
+
+/// Collects tagged parts of text messages by sender until they're complete
+pub struct TextReassembly<K> {
+    pending: std::collections::HashMap<(K, u32), PendingText>,
+    timeout: std::time::Duration,
+}
+
+struct PendingText {
+    parts: Vec<Option<String>>,
+    started: std::time::Instant,
+}
+
+impl<K: Eq + std::hash::Hash + Copy> TextReassembly<K> {
+    pub fn new(timeout: std::time::Duration) -> Self {
+        Self {
+            pending: std::collections::HashMap::new(),
+            timeout,
+        }
+    }
+
+    /// Take a received text. Returns the whole message once its last part
+    /// arrives, or right away for a text that isn't a part.
+    pub fn add(&mut self, sender: K, text: &str) -> Option<String> {
+        let Some(part) = parse_part(text) else {
+            return Some(text.to_string());
+        };
+
+        let now = std::time::Instant::now();
+        let timeout = self.timeout;
+        self.pending.retain(|_, p| now.duration_since(p.started) <= timeout);
+        if self.pending.len() >= MAX_PENDING_TEXTS && !self.pending.contains_key(&(sender, part.id)) {
+            return None;
+        }
+
+        let key = (sender, part.id);
+        let pending = self.pending.entry(key).or_insert_with(|| PendingText {
+            parts: vec![None; part.total],
+            started: now,
+        });
+        // A part that disagrees about the total belongs to some other message
+        if pending.parts.len() != part.total {
+            return None;
+        }
+        pending.parts[part.seq] = Some(part.text.to_string());
+        if pending.parts.iter().any(Option::is_none) {
+            return None;
+        }
+        let pending = self.pending.remove(&key)?;
+        Some(pending.parts.into_iter().flatten().collect())
+    }
+}
+
 /// Split a text message for friend_send_message (1372 byte limit)
 pub fn split_friend_message(message: &str) -> Vec<String> {
-    if message.len() <= TOX_MAX_MESSAGE_LENGTH {
+    split_message(message, TOX_MAX_MESSAGE_LENGTH)
+}
+
+/// Split a text message into parts of at most `max_len` bytes
+pub fn split_message(message: &str, max_len: usize) -> Vec<String> {
+    if message.len() <= max_len {
         return vec![message.to_string()];
     }
 
@@ -176,13 +287,13 @@ pub fn split_friend_message(message: &str) -> Vec<String> {
     let mut remaining = message;
 
     while !remaining.is_empty() {
-        if remaining.len() <= TOX_MAX_MESSAGE_LENGTH {
+        if remaining.len() <= max_len {
             parts.push(remaining.to_string());
             break;
         }
 
         // Find a good split point (at a char boundary, prefer whitespace)
-        let mut split_at = TOX_MAX_MESSAGE_LENGTH;
+        let mut split_at = max_len;
         while split_at > 0 && !remaining.is_char_boundary(split_at) {
             split_at -= 1;
         }
@@ -247,6 +358,31 @@ mod tests {
         assert_eq!(decoded.payload, vec![1, 2, 3]);
     }
 
+    #[test]
+    fn test_tagged_parts_reassemble() {
+        assert_eq!(split_tagged("short", TOX_MAX_MESSAGE_LENGTH, 7).unwrap(), vec!["short"]);
+
+        let long = "word ".repeat(1000);
+        let mut parts = split_tagged(&long, TOX_MAX_MESSAGE_LENGTH, 7).unwrap();
+        assert!(parts.len() > 1);
+        assert!(parts.iter().all(|p| p.len() <= TOX_MAX_MESSAGE_LENGTH));
+
+        // Parts can arrive out of order, interleaved with other senders
+        parts.reverse();
+        let mut buffer = TextReassembly::new(std::time::Duration::from_secs(60));
+        let (last, rest) = parts.split_last().unwrap();
+        for part in rest {
+            assert_eq!(buffer.add(1u32, part), None);
+        }
+        assert_eq!(buffer.add(2u32, "hello").as_deref(), Some("hello"));
+        assert_eq!(buffer.add(2u32, last), None);
+        assert_eq!(buffer.add(1u32, last), Some(long));
+
+        assert!(split_tagged(&"x".repeat(200_000), TOX_MAX_MESSAGE_LENGTH, 1).is_err());
+        assert_eq!(parse_part("[PART:zz:0/2]x"), None);
+        assert_eq!(parse_part("[PART:1:2/2]x"), None);
+    }
+
     #[test]
     fn test_split_friend_message() {
         let short = "Hello!";
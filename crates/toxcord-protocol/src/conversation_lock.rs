@@ -0,0 +1,83 @@
+//! Conversation locks: an extra layer of encryption for direct messages.
+//!
+//! Both sides of a conversation enter the same passphrase, agreed out of band.
+//! Each derives a key from it with `tox_pass_key_derive_with_salt`, salted with
+//! `salt` of the two public keys so both arrive at the same key. Messages are
+//! encrypted with that key before they reach Tox and sent as text envelopes:
+//! `LOCK_PREFIX` followed by the base64 ciphertext.
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use sha2::{Digest, Sha256};
+
+use crate::codec::TOX_MAX_MESSAGE_LENGTH;
+
+/// Marks a message as an encrypted envelope
+pub const LOCK_PREFIX: &str = "[LOCK]";
+
+/// Bytes added by tox_pass_key_encrypt (TOX_PASS_ENCRYPTION_EXTRA_LENGTH)
+pub const ENCRYPTION_OVERHEAD: usize = 80;
+
+/// Longest plaintext that still fits one Tox message once sealed
+pub const MAX_LOCKED_CHUNK: usize = (TOX_MAX_MESSAGE_LENGTH - LOCK_PREFIX.len()) / 4 * 3 - ENCRYPTION_OVERHEAD;
+
+/// Shortest passphrase accepted, in characters
+pub const MIN_PASSPHRASE_LEN: usize = 8;
+
+/// Key derivation salt for the conversation between two public keys; the
+/// same whichever side computes it
+pub fn salt(public_key_a: &str, public_key_b: &str) -> [u8; 32] {
+    let (a, b) = (public_key_a.to_uppercase(), public_key_b.to_uppercase());
+    let (first, second) = if a <= b { (a, b) } else { (b, a) };
+    let mut hasher = Sha256::new();
+    hasher.update(b"toxcord-conversation-lock:");
+    hasher.update(first.as_bytes());
+    hasher.update(second.as_bytes());
+    hasher.finalize().into()
+}
+
+pub fn validate_passphrase(passphrase: &str) -> Result<(), String> {
+    if passphrase.chars().count() < MIN_PASSPHRASE_LEN {
+        return Err(format!("Passphrase must be at least {MIN_PASSPHRASE_LEN} characters"));
+    }
+    Ok(())
+}
+
+/// Wrap ciphertext in a text envelope
+pub fn seal(ciphertext: &[u8]) -> String {
+    format!("{LOCK_PREFIX}{}", STANDARD.encode(ciphertext))
+}
+
+/// The ciphertext of an envelope, or `None` if `text` isn't one
+pub fn open(text: &str) -> Option<Vec<u8>> {
+    STANDARD.decode(text.strip_prefix(LOCK_PREFIX)?).ok()
+}
+
+pub fn is_envelope(text: &str) -> bool {
+    text.starts_with(LOCK_PREFIX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_envelopes() {
+        let ciphertext = vec![0xAB; MAX_LOCKED_CHUNK + ENCRYPTION_OVERHEAD];
+        let envelope = seal(&ciphertext);
+        assert!(is_envelope(&envelope));
+        assert!(envelope.len() <= TOX_MAX_MESSAGE_LENGTH);
+        assert_eq!(open(&envelope), Some(ciphertext));
+        assert_eq!(open("hello"), None);
+        assert_eq!(open("[LOCK]not base64!"), None);
+    }
+
+    #[test]
+    fn test_salt_is_symmetric() {
+        let (alice, bob) = ("ab".repeat(32), "CD".repeat(32));
+        assert_eq!(salt(&alice, &bob), salt(&bob, &alice));
+        assert_ne!(salt(&alice, &bob), salt(&alice, &alice));
+        assert!(validate_passphrase("short").is_err());
+        assert!(validate_passphrase("correct horse").is_ok());
+    }
+}
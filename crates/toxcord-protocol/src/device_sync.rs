@@ -0,0 +1,162 @@
+//! Sync protocol between a user's own linked devices.
+//!
+//! Linked devices are Tox friends of each other that both sides flagged as
+//! their own. They exchange friend lists, guild chat IDs and direct message
+//! history over friend lossless custom packets, which Tox already encrypts
+//! end to end:
+//!
+//! - 1 byte: `DEVICE_SYNC_PACKET_ID`
+//! - JSON-encoded `SyncMessage`
+//!
+//! Lists are split with [`pack`] so every packet fits in one Tox custom packet.
+
+use serde::{Deserialize, Serialize};
+
+/// First byte of sync packets (Tox reserves 160-191 for lossless custom packets)
+pub const DEVICE_SYNC_PACKET_ID: u8 = 0xA0;
+
+/// Largest custom packet Tox will send (TOX_MAX_CUSTOM_PACKET_SIZE)
+pub const MAX_PACKET_SIZE: usize = 1373;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum SyncMessage {
+    /// Sent when a linked device comes online. `since` is the newest message
+    /// timestamp already received from the other device, so only newer
+    /// history is sent back.
+    Hello { device_name: String, since: Option<String> },
+    Friends { friends: Vec<SyncFriend> },
+    Guilds { guilds: Vec<SyncGuild> },
+    History { messages: Vec<SyncDirectMessage> },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncFriend {
+    pub public_key: String,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncGuild {
+    /// NGC chat ID (64 hex chars)
+    pub chat_id: String,
+    pub name: String,
+    pub guild_type: String,
+}
+
+/// A direct message, keyed by the conversation partner's public key since
+/// friend numbers differ between devices
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncDirectMessage {
+    pub id: String,
+    pub peer_public_key: String,
+    pub content: String,
+    pub message_type: String,
+    pub timestamp: String,
+    pub is_outgoing: bool,
+}
+
+impl SyncMessage {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = vec![DEVICE_SYNC_PACKET_ID];
+        buf.extend(serde_json::to_vec(self).unwrap_or_default());
+        buf
+    }
+
+    /// Parse a friend lossless packet. Returns `None` if it isn't a sync packet.
+    pub fn from_bytes(data: &[u8]) -> Option<Self> {
+        let (&id, payload) = data.split_first()?;
+        if id != DEVICE_SYNC_PACKET_ID {
+            return None;
+        }
+        serde_json::from_slice(payload).ok()
+    }
+}
+
+/// Split `items` into as few packets as possible, each at most
+/// `MAX_PACKET_SIZE` bytes. Items too large for a packet on their own are skipped.
+pub fn pack<T: Clone>(items: &[T], wrap: impl Fn(Vec<T>) -> SyncMessage) -> Vec<Vec<u8>> {
+    let mut packets = Vec::new();
+    let mut batch: Vec<T> = Vec::new();
+    let mut current: Option<Vec<u8>> = None;
+
+    for item in items {
+        let alone = wrap(vec![item.clone()]).to_bytes();
+        if alone.len() > MAX_PACKET_SIZE {
+            continue;
+        }
+
+        batch.push(item.clone());
+        let bytes = wrap(batch.clone()).to_bytes();
+        if bytes.len() <= MAX_PACKET_SIZE {
+            current = Some(bytes);
+        } else {
+            // Doesn't fit: flush what we had and start a new packet with this item
+            packets.extend(current.take());
+            batch = vec![item.clone()];
+            current = Some(alone);
+        }
+    }
+    packets.extend(current);
+    packets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(id: usize, content: &str) -> SyncDirectMessage {
+        SyncDirectMessage {
+            id: id.to_string(),
+            peer_public_key: "AB".repeat(32),
+            content: content.to_string(),
+            message_type: "normal".to_string(),
+            timestamp: "2024-05-10 12:00:00".to_string(),
+            is_outgoing: id.is_multiple_of(2),
+        }
+    }
+
+    #[test]
+    fn test_sync_message_roundtrip() {
+        let msg = SyncMessage::Hello {
+            device_name: "laptop".to_string(),
+            since: Some("2024-05-10 12:00:00".to_string()),
+        };
+        let bytes = msg.to_bytes();
+        assert_eq!(bytes[0], DEVICE_SYNC_PACKET_ID);
+        assert_eq!(SyncMessage::from_bytes(&bytes), Some(msg));
+        assert_eq!(SyncMessage::from_bytes(b"\xA1{}"), None);
+        assert_eq!(SyncMessage::from_bytes(&[]), None);
+    }
+
+    #[test]
+    fn test_pack_splits_into_packets() {
+        let messages: Vec<_> = (0..40).map(|i| message(i, &"x".repeat(200))).collect();
+        let packets = pack(&messages, |messages| SyncMessage::History { messages });
+        assert!(packets.len() > 1);
+        assert!(packets.iter().all(|p| p.len() <= MAX_PACKET_SIZE));
+
+        let unpacked: Vec<SyncDirectMessage> = packets
+            .iter()
+            .flat_map(|p| match SyncMessage::from_bytes(p) {
+                Some(SyncMessage::History { messages }) => messages,
+                other => panic!("unexpected packet: {other:?}"),
+            })
+            .collect();
+        assert_eq!(unpacked, messages);
+    }
+
+    #[test]
+    fn test_pack_skips_oversized_items() {
+        let messages = vec![message(0, "hi"), message(1, &"x".repeat(2000)), message(2, "there")];
+        let packets = pack(&messages, |messages| SyncMessage::History { messages });
+        assert_eq!(packets.len(), 1);
+        match SyncMessage::from_bytes(&packets[0]) {
+            Some(SyncMessage::History { messages }) => {
+                assert_eq!(messages.iter().map(|m| m.id.as_str()).collect::<Vec<_>>(), ["0", "2"]);
+            }
+            other => panic!("unexpected packet: {other:?}"),
+        }
+        assert!(pack(&[] as &[SyncFriend], |friends| SyncMessage::Friends { friends }).is_empty());
+    }
+}
@@ -0,0 +1,79 @@
+//! Disappearing messages.
+//!
+//! A friend conversation can have a timer after which its messages are
+//! deleted on both sides, counted from when each message was sent or
+//! received. Either friend sets it by sending a `TimerPacket` as a friend
+//! lossless packet after `DISAPPEARING_PACKET_ID`. Both sides resend their
+//! setting when the other comes online, and the one with the newer `set_at`
+//! wins, so changes made while offline still reach the friend.
+
+use serde::{Deserialize, Serialize};
+
+/// First byte of disappearing timer packets (Tox reserves 160-191 for lossless custom packets)
+pub const DISAPPEARING_PACKET_ID: u8 = 0xA2;
+
+/// Shortest and longest timer, in seconds
+pub const MIN_TIMER_SECS: u32 = 5;
+pub const MAX_TIMER_SECS: u32 = 4 * 7 * 24 * 60 * 60;
+
+/// Check a timer in seconds; 0 turns disappearing messages off
+pub fn validate_timer(secs: u32) -> Result<(), String> {
+    if secs != 0 && !(MIN_TIMER_SECS..=MAX_TIMER_SECS).contains(&secs) {
+        return Err("Disappearing messages can last from 5 seconds to 4 weeks".to_string());
+    }
+    Ok(())
+}
+
+/// When a message sent or received at `now` (Unix seconds) expires, if a timer is set
+pub fn expires_at(now: i64, secs: u32) -> Option<i64> {
+    (secs > 0).then(|| now + i64::from(secs))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimerPacket {
+    /// 0 turns disappearing messages off
+    pub seconds: u32,
+    /// When the timer was chosen, in Unix seconds
+    pub set_at: i64,
+}
+
+impl TimerPacket {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = vec![DISAPPEARING_PACKET_ID];
+        buf.extend(serde_json::to_vec(self).unwrap_or_default());
+        buf
+    }
+
+    /// Parse a friend lossless packet. Returns `None` if it isn't a valid timer packet.
+    pub fn from_bytes(data: &[u8]) -> Option<Self> {
+        let (&first, payload) = data.split_first()?;
+        if first != DISAPPEARING_PACKET_ID {
+            return None;
+        }
+        let packet: Self = serde_json::from_slice(payload).ok()?;
+        validate_timer(packet.seconds).is_ok().then_some(packet)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timer_packet() {
+        let packet = TimerPacket { seconds: 3600, set_at: 1_715_342_400 };
+        let bytes = packet.to_bytes();
+        assert_eq!(bytes[0], DISAPPEARING_PACKET_ID);
+        assert_eq!(TimerPacket::from_bytes(&bytes), Some(packet));
+        assert_eq!(TimerPacket::from_bytes(&[0xA1]), None);
+
+        let off = TimerPacket { seconds: 0, ..packet };
+        assert_eq!(TimerPacket::from_bytes(&off.to_bytes()), Some(off));
+        let too_short = TimerPacket { seconds: 1, ..packet };
+        assert_eq!(TimerPacket::from_bytes(&too_short.to_bytes()), None);
+
+        assert_eq!(expires_at(1_000, 60), Some(1_060));
+        assert_eq!(expires_at(1_000, 0), None);
+        assert!(validate_timer(MAX_TIMER_SECS + 1).is_err());
+    }
+}
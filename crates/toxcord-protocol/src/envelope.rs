@@ -0,0 +1,81 @@
+//! Content-addressed IDs for group messages.
+//!
+//! Each member used to give a group message its own random ID, so the same
+//! message could be stored twice: once when we sent it and again when it came
+//! back to us, or came in again with another member's history. The sender now
+//! puts an ID tag at the start of the text, after the routing prefix:
+//!
+//! `[CH:general][ID:18f5a3b2c00-<hash>]Hello`
+//!
+//! The ID is the send time (Unix milliseconds, hex) and the first 16 bytes of
+//! SHA-256 over the sender's public key, the send time and the text, so every
+//! member derives the same ID and can check it against the sender. Messages
+//! from older clients carry no tag and get a random ID as before.
+
+use sha2::{Digest, Sha256};
+
+/// Start of the ID tag
+pub const ID_TAG_PREFIX: &str = "[ID:";
+
+/// The ID of a message `sender_public_key` sent at `sent_at_ms`
+pub fn message_id(sender_public_key: &str, sent_at_ms: i64, content: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(sender_public_key.to_uppercase().as_bytes());
+    hasher.update(sent_at_ms.to_be_bytes());
+    hasher.update(content.as_bytes());
+    let hash: String = hasher.finalize()[..16].iter().map(|b| format!("{b:02x}")).collect();
+    format!("{sent_at_ms:x}-{hash}")
+}
+
+/// Whether `id` is the content-addressed ID of this message
+pub fn verify_id(id: &str, sender_public_key: &str, content: &str) -> bool {
+    let Some(sent_at_ms) = id.split_once('-').and_then(|(time, _)| i64::from_str_radix(time, 16).ok()) else {
+        return false;
+    };
+    message_id(sender_public_key, sent_at_ms, content).eq_ignore_ascii_case(id)
+}
+
+/// Tag a message for sending. Returns its ID and the tagged text.
+pub fn seal(sender_public_key: &str, sent_at_ms: i64, content: &str) -> (String, String) {
+    let id = message_id(sender_public_key, sent_at_ms, content);
+    let tagged = format!("{ID_TAG_PREFIX}{id}]{content}");
+    (id, tagged)
+}
+
+/// Take the ID tag off a received message's text. The ID is only returned if
+/// it matches the sender and text; a forged tag is dropped all the same.
+pub fn open<'a>(sender_public_key: &str, text: &'a str) -> (Option<String>, &'a str) {
+    let Some((id, content)) = text.strip_prefix(ID_TAG_PREFIX).and_then(|rest| rest.split_once(']')) else {
+        return (None, text);
+    };
+    let id = verify_id(id, sender_public_key, content).then(|| id.to_lowercase());
+    (id, content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seal_and_open() {
+        let alice = "AB".repeat(32);
+        let (id, tagged) = seal(&alice, 1_715_342_400_000, "hello [world]");
+        assert!(tagged.starts_with(ID_TAG_PREFIX));
+        assert_eq!(open(&alice, &tagged), (Some(id.clone()), "hello [world]"));
+        // Every member derives the same ID, whatever the key's case
+        assert_eq!(open(&alice.to_lowercase(), &tagged).0, Some(id));
+    }
+
+    #[test]
+    fn test_open_rejects_forged_tags() {
+        let alice = "AB".repeat(32);
+        let mallory = "CD".repeat(32);
+        let (_, tagged) = seal(&alice, 1_715_342_400_000, "hello");
+        assert_eq!(open(&mallory, &tagged), (None, "hello"));
+
+        let edited = tagged.replace("hello", "goodbye");
+        assert_eq!(open(&alice, &edited), (None, "goodbye"));
+        assert_eq!(open(&alice, "plain text"), (None, "plain text"));
+        assert!(!verify_id("not-an-id", &alice, "hello"));
+    }
+}
@@ -0,0 +1,378 @@
+//! Group file sharing.
+//!
+//! Tox file transfers only work between friends, so files in group channels
+//! are shared over NGC custom packets instead:
+//!
+//! 1. The sender broadcasts a `FileOffer` (name, size, SHA-256) as a lossless
+//!    custom packet.
+//! 2. A peer that wants the file sends a `FileRequest` for the next window of
+//!    data as a lossless custom private packet.
+//! 3. Any peer holding the file answers with `FileChunk` private packets.
+//! 4. The receiver requests window after window until it has the whole file,
+//!    then checks it against the offered hash.
+//!
+//! Lossless packets arrive in order, so a receiver only tracks how much
+//! contiguous data it has.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use crate::codec::TOX_MAX_CUSTOM_PACKET_SIZE;
+use crate::packets::PacketType;
+
+/// Length of a SHA-256 file hash
+pub const HASH_LENGTH: usize = 32;
+
+/// Largest file that can be shared in a group
+pub const MAX_GROUP_FILE_SIZE: u64 = 8 * 1024 * 1024;
+
+/// Header size for file chunks:
+/// - 1 byte: packet type
+/// - 32 bytes: file hash
+/// - 8 bytes: offset
+pub const FILE_CHUNK_HEADER_SIZE: usize = 1 + HASH_LENGTH + 8;
+
+/// Maximum file data per chunk
+pub const MAX_FILE_CHUNK_DATA: usize = TOX_MAX_CUSTOM_PACKET_SIZE - FILE_CHUNK_HEADER_SIZE;
+
+/// How much data a receiver asks for at once
+pub const REQUEST_WINDOW: u64 = 64 * MAX_FILE_CHUNK_DATA as u64;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum FileShareError {
+    #[error("Empty file")]
+    Empty,
+    #[error("File too large: {0} bytes")]
+    TooLarge(u64),
+    #[error("Invalid file hash")]
+    InvalidHash,
+    #[error("Chunk for another file")]
+    WrongFile,
+    #[error("Chunk out of order: expected offset {expected}, got {got}")]
+    OutOfOrder { expected: u64, got: u64 },
+    #[error("Chunk past end of file")]
+    PastEnd,
+    #[error("File does not match its hash")]
+    HashMismatch,
+}
+
+/// Announcement of a file shared in a channel
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileOffer {
+    /// Hex SHA-256 of the file, also used as its id
+    pub hash: String,
+    pub filename: String,
+    pub mime_type: String,
+    pub size: u64,
+    /// Channel name for guild channels, `None` in DM groups
+    pub channel: Option<String>,
+}
+
+/// Request for `length` bytes of a file starting at `offset`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileRequest {
+    pub hash: String,
+    pub offset: u64,
+    pub length: u64,
+}
+
+/// A piece of a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileChunk {
+    pub hash: [u8; HASH_LENGTH],
+    pub offset: u64,
+    pub data: Vec<u8>,
+}
+
+/// A file sharing packet, as sent over NGC custom packets
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileSharePacket {
+    Offer(FileOffer),
+    Request(FileRequest),
+    Chunk(FileChunk),
+}
+
+impl FileSharePacket {
+    /// Serialize packet to bytes for transmission
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            Self::Offer(offer) => json_packet(PacketType::FileOffer, offer),
+            Self::Request(request) => json_packet(PacketType::FileRequest, request),
+            Self::Chunk(chunk) => {
+                let mut buf = Vec::with_capacity(FILE_CHUNK_HEADER_SIZE + chunk.data.len());
+                buf.push(PacketType::FileChunk as u8);
+                buf.extend_from_slice(&chunk.hash);
+                buf.extend_from_slice(&chunk.offset.to_be_bytes());
+                buf.extend_from_slice(&chunk.data);
+                buf
+            }
+        }
+    }
+
+    /// Deserialize packet from bytes. Returns `None` for other packet types.
+    pub fn from_bytes(data: &[u8]) -> Option<Self> {
+        let (&first, payload) = data.split_first()?;
+        match PacketType::from_byte(first)? {
+            PacketType::FileOffer => serde_json::from_slice(payload).ok().map(Self::Offer),
+            PacketType::FileRequest => serde_json::from_slice(payload).ok().map(Self::Request),
+            PacketType::FileChunk => {
+                if data.len() < FILE_CHUNK_HEADER_SIZE {
+                    return None;
+                }
+                let hash = payload[..HASH_LENGTH].try_into().ok()?;
+                let offset = u64::from_be_bytes(payload[HASH_LENGTH..HASH_LENGTH + 8].try_into().ok()?);
+                Some(Self::Chunk(FileChunk {
+                    hash,
+                    offset,
+                    data: payload[HASH_LENGTH + 8..].to_vec(),
+                }))
+            }
+            _ => None,
+        }
+    }
+}
+
+fn json_packet<T: Serialize>(packet_type: PacketType, payload: &T) -> Vec<u8> {
+    let mut buf = vec![packet_type as u8];
+    // Serializing plain structs of strings and integers can't fail
+    buf.extend(serde_json::to_vec(payload).unwrap_or_default());
+    buf
+}
+
+pub fn sha256(data: &[u8]) -> [u8; HASH_LENGTH] {
+    Sha256::digest(data).into()
+}
+
+pub fn hash_to_hex(hash: &[u8; HASH_LENGTH]) -> String {
+    hash.iter().map(|b| format!("{b:02X}")).collect()
+}
+
+pub fn hash_from_hex(hex: &str) -> Option<[u8; HASH_LENGTH]> {
+    if hex.len() != HASH_LENGTH * 2 || !hex.is_ascii() {
+        return None;
+    }
+    let mut hash = [0u8; HASH_LENGTH];
+    for (i, byte) in hash.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok()?;
+    }
+    Some(hash)
+}
+
+/// Split the requested range of a file into chunks. `data` is the file
+/// content starting at `offset`.
+pub fn split_file_range(hash: &[u8; HASH_LENGTH], offset: u64, data: &[u8]) -> Vec<FileChunk> {
+    data.chunks(MAX_FILE_CHUNK_DATA)
+        .enumerate()
+        .map(|(i, chunk)| FileChunk {
+            hash: *hash,
+            offset: offset + (i * MAX_FILE_CHUNK_DATA) as u64,
+            data: chunk.to_vec(),
+        })
+        .collect()
+}
+
+/// Result of adding a chunk to a `FileAssembler`
+#[derive(Debug, PartialEq, Eq)]
+pub enum ChunkProgress {
+    /// More chunks of the current window are on their way
+    Pending,
+    /// The current window is done; send the next request
+    WindowComplete(FileRequest),
+    /// The whole file arrived and matches its hash
+    Complete(Vec<u8>),
+}
+
+/// Collects the chunks of a shared file in memory
+pub struct FileAssembler {
+    hash: [u8; HASH_LENGTH],
+    size: u64,
+    data: Vec<u8>,
+    window_end: u64,
+}
+
+impl FileAssembler {
+    pub fn new(offer: &FileOffer) -> Result<Self, FileShareError> {
+        if offer.size == 0 {
+            return Err(FileShareError::Empty);
+        }
+        if offer.size > MAX_GROUP_FILE_SIZE {
+            return Err(FileShareError::TooLarge(offer.size));
+        }
+        let hash = hash_from_hex(&offer.hash).ok_or(FileShareError::InvalidHash)?;
+        Ok(Self {
+            hash,
+            size: offer.size,
+            data: Vec::with_capacity(offer.size as usize),
+            window_end: 0,
+        })
+    }
+
+    pub fn received(&self) -> u64 {
+        self.data.len() as u64
+    }
+
+    /// The request for the first window. Call once, before any chunk arrives.
+    pub fn first_request(&mut self) -> FileRequest {
+        self.next_window()
+    }
+
+    pub fn add_chunk(&mut self, chunk: FileChunk) -> Result<ChunkProgress, FileShareError> {
+        if chunk.hash != self.hash {
+            return Err(FileShareError::WrongFile);
+        }
+        let expected = self.received();
+        // A resent chunk we already have
+        if chunk.offset < expected {
+            return Ok(ChunkProgress::Pending);
+        }
+        if chunk.offset > expected {
+            return Err(FileShareError::OutOfOrder {
+                expected,
+                got: chunk.offset,
+            });
+        }
+        if expected + chunk.data.len() as u64 > self.size {
+            return Err(FileShareError::PastEnd);
+        }
+
+        self.data.extend_from_slice(&chunk.data);
+
+        if self.received() == self.size {
+            if sha256(&self.data) != self.hash {
+                return Err(FileShareError::HashMismatch);
+            }
+            return Ok(ChunkProgress::Complete(std::mem::take(&mut self.data)));
+        }
+        if self.received() >= self.window_end {
+            return Ok(ChunkProgress::WindowComplete(self.next_window()));
+        }
+        Ok(ChunkProgress::Pending)
+    }
+
+    fn next_window(&mut self) -> FileRequest {
+        let offset = self.received();
+        let length = REQUEST_WINDOW.min(self.size - offset);
+        self.window_end = offset + length;
+        FileRequest {
+            hash: hash_to_hex(&self.hash),
+            offset,
+            length,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn offer_for(data: &[u8]) -> FileOffer {
+        FileOffer {
+            hash: hash_to_hex(&sha256(data)),
+            filename: "cat.png".to_string(),
+            mime_type: "image/png".to_string(),
+            size: data.len() as u64,
+            channel: Some("general".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_packet_roundtrip() {
+        let offer = FileSharePacket::Offer(offer_for(b"hello"));
+        assert_eq!(FileSharePacket::from_bytes(&offer.to_bytes()), Some(offer));
+
+        let request = FileSharePacket::Request(FileRequest {
+            hash: hash_to_hex(&sha256(b"hello")),
+            offset: 10,
+            length: 20,
+        });
+        assert_eq!(FileSharePacket::from_bytes(&request.to_bytes()), Some(request));
+
+        let chunk = FileSharePacket::Chunk(FileChunk {
+            hash: sha256(b"hello"),
+            offset: 1 << 40,
+            data: vec![1, 2, 3],
+        });
+        let bytes = chunk.to_bytes();
+        assert_eq!(bytes.len(), FILE_CHUNK_HEADER_SIZE + 3);
+        assert_eq!(FileSharePacket::from_bytes(&bytes), Some(chunk));
+    }
+
+    #[test]
+    fn test_other_packets_ignored() {
+        assert_eq!(FileSharePacket::from_bytes(&[]), None);
+        assert_eq!(FileSharePacket::from_bytes(b"{\"type\":\"typing\"}"), None);
+        assert_eq!(FileSharePacket::from_bytes(&[PacketType::TypingStart as u8]), None);
+        assert_eq!(FileSharePacket::from_bytes(&[PacketType::FileChunk as u8, 1, 2]), None);
+    }
+
+    #[test]
+    fn test_chunk_fits_custom_packet() {
+        let data = vec![7u8; MAX_FILE_CHUNK_DATA * 2];
+        for chunk in split_file_range(&sha256(&data), 0, &data) {
+            assert!(FileSharePacket::Chunk(chunk).to_bytes().len() <= TOX_MAX_CUSTOM_PACKET_SIZE);
+        }
+    }
+
+    #[test]
+    fn test_transfer_in_windows() {
+        let data: Vec<u8> = (0..REQUEST_WINDOW * 2 + 500).map(|i| (i % 251) as u8).collect();
+        let hash = sha256(&data);
+        let mut assembler = FileAssembler::new(&offer_for(&data)).unwrap();
+
+        let mut requests = vec![assembler.first_request()];
+        let mut file = None;
+        while file.is_none() {
+            let request = requests.last().unwrap().clone();
+            let range = request.offset as usize..(request.offset + request.length) as usize;
+            for chunk in split_file_range(&hash, request.offset, &data[range]) {
+                match assembler.add_chunk(chunk).unwrap() {
+                    ChunkProgress::Pending => {}
+                    ChunkProgress::WindowComplete(next) => requests.push(next),
+                    ChunkProgress::Complete(f) => file = Some(f),
+                }
+            }
+        }
+
+        assert_eq!(requests.len(), 3);
+        assert_eq!(requests[1].offset, REQUEST_WINDOW);
+        assert_eq!(requests[2].length, 500);
+        assert_eq!(file.unwrap(), data);
+    }
+
+    #[test]
+    fn test_hash_mismatch() {
+        let data = b"the real file".to_vec();
+        let mut assembler = FileAssembler::new(&offer_for(&data)).unwrap();
+        assembler.first_request();
+        let chunk = FileChunk {
+            hash: sha256(&data),
+            offset: 0,
+            data: b"a fake file!!".to_vec(),
+        };
+        assert_eq!(assembler.add_chunk(chunk), Err(FileShareError::HashMismatch));
+    }
+
+    #[test]
+    fn test_rejects_bad_chunks() {
+        let data = vec![1u8; 100];
+        let hash = sha256(&data);
+        let mut assembler = FileAssembler::new(&offer_for(&data)).unwrap();
+        assembler.first_request();
+
+        let skip = FileChunk { hash, offset: 50, data: vec![1; 10] };
+        assert_eq!(
+            assembler.add_chunk(skip),
+            Err(FileShareError::OutOfOrder { expected: 0, got: 50 })
+        );
+        let other = FileChunk { hash: [0; HASH_LENGTH], offset: 0, data: vec![1; 10] };
+        assert_eq!(assembler.add_chunk(other), Err(FileShareError::WrongFile));
+        let too_long = FileChunk { hash, offset: 0, data: vec![1; 101] };
+        assert_eq!(assembler.add_chunk(too_long), Err(FileShareError::PastEnd));
+
+        let mut big = offer_for(&data);
+        big.size = MAX_GROUP_FILE_SIZE + 1;
+        assert!(matches!(FileAssembler::new(&big), Err(FileShareError::TooLarge(_))));
+        assert!(matches!(FileAssembler::new(&offer_for(b"")), Err(FileShareError::Empty)));
+    }
+}
@@ -0,0 +1,103 @@
+//! Identity fingerprints for verifying Tox public keys out of band.
+//!
+//! A fingerprint is 30 digits derived from the SHA-256 of a public key, shown
+//! in six groups of five. The safety number of two users joins both
+//! fingerprints in a fixed order, so both sides see the same 60 digits.
+//! The QR code for a key carries `toxcord-verify:<PUBLIC_KEY>`.
+
+use sha2::{Digest, Sha256};
+
+pub const PUBLIC_KEY_LENGTH: usize = 32;
+
+/// Scheme of the payload encoded in verification QR codes
+pub const VERIFY_URI_SCHEME: &str = "toxcord-verify:";
+
+const FINGERPRINT_DOMAIN: &[u8] = b"toxcord-fingerprint-v1";
+const FINGERPRINT_GROUPS: usize = 6;
+const GROUP_BYTES: usize = 5;
+
+/// 30-digit fingerprint of a public key, e.g. `"04213 99120 ..."`
+pub fn fingerprint(public_key: &[u8; PUBLIC_KEY_LENGTH]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(FINGERPRINT_DOMAIN);
+    hasher.update(public_key);
+    let hash = hasher.finalize();
+
+    hash.chunks(GROUP_BYTES)
+        .take(FINGERPRINT_GROUPS)
+        .map(|chunk| {
+            let n = chunk.iter().fold(0u64, |acc, b| (acc << 8) | *b as u64);
+            format!("{:05}", n % 100_000)
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// 60-digit number two users compare to verify each other. Symmetric in its arguments.
+pub fn safety_number(a: &[u8; PUBLIC_KEY_LENGTH], b: &[u8; PUBLIC_KEY_LENGTH]) -> String {
+    let (first, second) = if a <= b { (a, b) } else { (b, a) };
+    format!("{} {}", fingerprint(first), fingerprint(second))
+}
+
+/// Payload for a verification QR code
+pub fn verification_uri(public_key: &[u8; PUBLIC_KEY_LENGTH]) -> String {
+    let hex: String = public_key.iter().map(|b| format!("{b:02X}")).collect();
+    format!("{VERIFY_URI_SCHEME}{hex}")
+}
+
+/// Public key from a scanned verification QR code
+pub fn parse_verification_uri(uri: &str) -> Option<[u8; PUBLIC_KEY_LENGTH]> {
+    uri.trim()
+        .strip_prefix(VERIFY_URI_SCHEME)
+        .and_then(public_key_from_hex)
+}
+
+pub fn public_key_from_hex(hex: &str) -> Option<[u8; PUBLIC_KEY_LENGTH]> {
+    if hex.len() != PUBLIC_KEY_LENGTH * 2 || !hex.is_ascii() {
+        return None;
+    }
+    let mut key = [0u8; PUBLIC_KEY_LENGTH];
+    for (i, byte) in key.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok()?;
+    }
+    Some(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fingerprint_format() {
+        let key = [7u8; PUBLIC_KEY_LENGTH];
+        let fp = fingerprint(&key);
+        let groups: Vec<&str> = fp.split(' ').collect();
+        assert_eq!(groups.len(), FINGERPRINT_GROUPS);
+        assert!(groups.iter().all(|g| g.len() == 5 && g.bytes().all(|b| b.is_ascii_digit())));
+        assert_eq!(fp, fingerprint(&key));
+        assert_ne!(fp, fingerprint(&[8u8; PUBLIC_KEY_LENGTH]));
+    }
+
+    #[test]
+    fn test_safety_number_is_symmetric() {
+        let a = [1u8; PUBLIC_KEY_LENGTH];
+        let b = [2u8; PUBLIC_KEY_LENGTH];
+        assert_eq!(safety_number(&a, &b), safety_number(&b, &a));
+        assert!(safety_number(&a, &b).starts_with(&fingerprint(&a)));
+    }
+
+    #[test]
+    fn test_verification_uri_roundtrip() {
+        let mut key = [0u8; PUBLIC_KEY_LENGTH];
+        key[0] = 0xAB;
+        key[31] = 0x01;
+        let uri = verification_uri(&key);
+        assert!(uri.starts_with("toxcord-verify:AB00"));
+        assert_eq!(parse_verification_uri(&uri), Some(key));
+        assert_eq!(parse_verification_uri("toxcord-verify:ABCD"), None);
+        assert_eq!(
+            parse_verification_uri(&format!("{VERIFY_URI_SCHEME}{}", "ab".repeat(32))),
+            Some([0xAB; PUBLIC_KEY_LENGTH])
+        );
+    }
+}
@@ -0,0 +1,64 @@
+//! Group invite notices.
+//!
+//! A Tox group invite carries only the group's name, so a friend invited to a
+//! password-protected group would only find out when joining fails. Before
+//! such an invite we send an `InviteNotice` as JSON in a friend lossless
+//! packet after `INVITE_NOTICE_PACKET_ID`. Lossless packets arrive in order,
+//! and Tox sends the invite itself the same way, so the notice comes first.
+
+use serde::{Deserialize, Serialize};
+
+/// First byte of invite notice packets (Tox reserves 160-191 for lossless custom packets)
+pub const INVITE_NOTICE_PACKET_ID: u8 = 0xA4;
+
+/// Longest group name Tox allows, in bytes
+const MAX_GROUP_NAME: usize = 48;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InviteNotice {
+    /// Name of the group the invite that follows is for
+    pub group_name: String,
+    pub password_required: bool,
+}
+
+impl InviteNotice {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = vec![INVITE_NOTICE_PACKET_ID];
+        buf.extend(serde_json::to_vec(self).unwrap_or_default());
+        buf
+    }
+
+    /// Parse a friend lossless packet. Returns `None` if it isn't an invite notice.
+    pub fn from_bytes(data: &[u8]) -> Option<Self> {
+        let (&first, payload) = data.split_first()?;
+        if first != INVITE_NOTICE_PACKET_ID {
+            return None;
+        }
+        let notice: Self = serde_json::from_slice(payload).ok()?;
+        (notice.group_name.len() <= MAX_GROUP_NAME).then_some(notice)
+    }
+
+    /// Whether this notice is about an invite to `group_name`
+    pub fn is_for(&self, group_name: &str) -> bool {
+        self.group_name == group_name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_invite_notice_packet() {
+        let notice = InviteNotice { group_name: "Book Club".to_string(), password_required: true };
+        let bytes = notice.to_bytes();
+        assert_eq!(bytes[0], INVITE_NOTICE_PACKET_ID);
+        assert_eq!(InviteNotice::from_bytes(&bytes), Some(notice.clone()));
+        assert!(notice.is_for("Book Club"));
+        assert!(!notice.is_for("book club"));
+
+        assert_eq!(InviteNotice::from_bytes(&[0xA3, b'{', b'}']), None);
+        let long = InviteNotice { group_name: "x".repeat(MAX_GROUP_NAME + 1), password_required: true };
+        assert_eq!(InviteNotice::from_bytes(&long.to_bytes()), None);
+    }
+}
@@ -0,0 +1,102 @@
+//! Directory of public guilds.
+//!
+//! Public groups are announced on the DHT under their chat ID, so anyone who
+//! knows the ID can join without an invite. The DHT can't be searched for
+//! them, so the directory is a list of known chat IDs: a curated one that
+//! ships with the app, plus entries the user adds.
+
+use serde::{Deserialize, Serialize};
+
+use crate::links::CHAT_ID_HEX_LENGTH;
+
+/// Longest group name Tox allows, in bytes
+const MAX_NAME: usize = 48;
+
+/// Longest description kept, in characters
+pub const MAX_DESCRIPTION: usize = 200;
+
+/// Public guilds listed for everyone, as (chat ID, name, description)
+const CURATED: &[(&str, &str, &str)] = &[];
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DirectoryEntry {
+    /// Uppercase hex
+    pub chat_id: String,
+    pub name: String,
+    pub description: String,
+    /// Listed with the app rather than added by the user
+    pub curated: bool,
+}
+
+impl DirectoryEntry {
+    /// A user-added entry. Returns `None` for a malformed chat ID or a
+    /// missing or overlong name; long descriptions are cut short.
+    pub fn new(chat_id: &str, name: &str, description: &str) -> Option<Self> {
+        let chat_id = chat_id.trim();
+        let name = name.trim();
+        if chat_id.len() != CHAT_ID_HEX_LENGTH || !chat_id.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        if name.is_empty() || name.len() > MAX_NAME {
+            return None;
+        }
+        Some(Self {
+            chat_id: chat_id.to_ascii_uppercase(),
+            name: name.to_string(),
+            description: description.trim().chars().take(MAX_DESCRIPTION).collect(),
+            curated: false,
+        })
+    }
+}
+
+/// The curated entries
+pub fn curated() -> Vec<DirectoryEntry> {
+    CURATED
+        .iter()
+        .filter_map(|&(chat_id, name, description)| DirectoryEntry::new(chat_id, name, description))
+        .map(|entry| DirectoryEntry { curated: true, ..entry })
+        .collect()
+}
+
+/// The curated entries and the user's, sorted by name. A user entry for a
+/// chat ID that's already listed is dropped.
+pub fn merge(user: Vec<DirectoryEntry>) -> Vec<DirectoryEntry> {
+    let mut entries = curated();
+    for entry in user {
+        if !entries.iter().any(|known| known.chat_id.eq_ignore_ascii_case(&entry.chat_id)) {
+            entries.push(entry);
+        }
+    }
+    entries.sort_by_key(|entry| entry.name.to_lowercase());
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_curated_entries_are_valid() {
+        assert_eq!(curated().len(), CURATED.len());
+    }
+
+    #[test]
+    fn test_directory_entries() {
+        let chat_id = "ab".repeat(32);
+        let entry = DirectoryEntry::new(&format!(" {chat_id} "), " Rust Fans ", &"x".repeat(300)).unwrap();
+        assert_eq!(entry.chat_id, chat_id.to_ascii_uppercase());
+        assert_eq!(entry.name, "Rust Fans");
+        assert_eq!(entry.description.len(), MAX_DESCRIPTION);
+        assert!(!entry.curated);
+
+        assert!(DirectoryEntry::new(&chat_id[2..], "Short ID", "").is_none());
+        assert!(DirectoryEntry::new(&"zz".repeat(32), "Not hex", "").is_none());
+        assert!(DirectoryEntry::new(&chat_id, "  ", "").is_none());
+
+        let other = DirectoryEntry::new(&"CD".repeat(32), "apples", "").unwrap();
+        let merged = merge(vec![entry.clone(), other.clone(), entry.clone()]);
+        assert_eq!(merged.len(), curated().len() + 2);
+        assert!(merged.contains(&other));
+        assert!(merged.windows(2).all(|w| w[0].name.to_lowercase() <= w[1].name.to_lowercase()));
+    }
+}
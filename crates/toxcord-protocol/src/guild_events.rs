@@ -0,0 +1,136 @@
+//! Scheduled guild events.
+//!
+//! An event is announced with `EventPacket::Create` and answered with
+//! `EventPacket::Rsvp`, both broadcast as JSON in NGC custom packets after
+//! `PacketType::GuildEvent`. Each member has one RSVP per event, keyed by their
+//! public key; answering again replaces it. Every member reminds themselves
+//! `REMINDER_LEAD_SECS` before the event starts.
+
+use serde::{Deserialize, Serialize};
+
+use crate::packets::PacketType;
+
+/// Longest event title, in bytes
+pub const MAX_TITLE_LEN: usize = 100;
+
+/// Longest event description, in bytes
+pub const MAX_DESCRIPTION_LEN: usize = 2000;
+
+/// How long before an event starts members are reminded of it (15 minutes)
+pub const REMINDER_LEAD_SECS: i64 = 15 * 60;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GuildEvent {
+    /// UUID
+    pub id: String,
+    pub title: String,
+    #[serde(default)]
+    pub description: String,
+    /// When the event starts, in Unix seconds
+    pub start_time: i64,
+}
+
+impl GuildEvent {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.title.trim().is_empty() {
+            return Err("Event title cannot be empty".to_string());
+        }
+        if self.title.len() > MAX_TITLE_LEN {
+            return Err(format!("Event title is longer than {MAX_TITLE_LEN} bytes"));
+        }
+        if self.description.len() > MAX_DESCRIPTION_LEN {
+            return Err(format!("Event description is longer than {MAX_DESCRIPTION_LEN} bytes"));
+        }
+        Ok(())
+    }
+
+    /// Whether the reminder is due at `now` (Unix seconds)
+    pub fn reminder_due(&self, now: i64) -> bool {
+        now >= self.start_time - REMINDER_LEAD_SECS
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RsvpStatus {
+    Going,
+    Interested,
+    NotGoing,
+}
+
+impl RsvpStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Going => "going",
+            Self::Interested => "interested",
+            Self::NotGoing => "not_going",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum EventPacket {
+    Create { event: GuildEvent },
+    /// The sender's answer to an event
+    Rsvp { event_id: String, status: RsvpStatus },
+}
+
+impl EventPacket {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = vec![PacketType::GuildEvent as u8];
+        buf.extend(serde_json::to_vec(self).unwrap_or_default());
+        buf
+    }
+
+    /// Parse an NGC custom packet. Returns `None` if it isn't a valid event packet.
+    pub fn from_bytes(data: &[u8]) -> Option<Self> {
+        let (&first, payload) = data.split_first()?;
+        if first != PacketType::GuildEvent as u8 {
+            return None;
+        }
+        let packet: Self = serde_json::from_slice(payload).ok()?;
+        match &packet {
+            Self::Create { event } if event.validate().is_err() => None,
+            _ => Some(packet),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event() -> GuildEvent {
+        GuildEvent {
+            id: "5c1f0b6e-2a7d-4c39-8e0a-7b2d4f6a9c13".to_string(),
+            title: "Game night".to_string(),
+            description: "Bring snacks".to_string(),
+            start_time: 1_715_342_400,
+        }
+    }
+
+    #[test]
+    fn test_event_packets() {
+        let create = EventPacket::Create { event: event() };
+        let bytes = create.to_bytes();
+        assert_eq!(bytes[0], PacketType::GuildEvent as u8);
+        assert_eq!(EventPacket::from_bytes(&bytes), Some(create));
+
+        let rsvp = EventPacket::Rsvp { event_id: event().id, status: RsvpStatus::NotGoing };
+        assert!(String::from_utf8_lossy(&rsvp.to_bytes()).contains("\"not_going\""));
+        assert_eq!(EventPacket::from_bytes(&rsvp.to_bytes()), Some(rsvp));
+        assert_eq!(EventPacket::from_bytes(&[PacketType::Poll as u8]), None);
+
+        // Invalid events from peers are dropped
+        let untitled = EventPacket::Create { event: GuildEvent { title: " ".to_string(), ..event() } };
+        assert_eq!(EventPacket::from_bytes(&untitled.to_bytes()), None);
+    }
+
+    #[test]
+    fn test_reminder_due() {
+        assert!(!event().reminder_due(1_715_342_400 - REMINDER_LEAD_SECS - 1));
+        assert!(event().reminder_due(1_715_342_400 - REMINDER_LEAD_SECS));
+        assert!(event().reminder_due(1_715_342_400));
+    }
+}
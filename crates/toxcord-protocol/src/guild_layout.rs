@@ -0,0 +1,376 @@
+//! Guild channel layout.
+//!
+//! Which channels a guild has, what they're called and in what order, shared
+//! by founders and moderators. Two moderators may rename or reorder channels
+//! at the same time with no one to settle it, so the layout is a CRDT: every
+//! field is a last-writer-wins register stamped with a Lamport clock and the
+//! writer's public key, and [`GuildLayout::merge`] takes the newer stamp of
+//! each. Merging is commutative and idempotent, so members converge whatever
+//! order changes arrive in, and a partial layout is as good as a whole one.
+//!
+//! Channels are keyed by the name they had when first shared (`origin`), or a
+//! random key if that name is taken, so members can match the layout to the
+//! channels they already have. Deleted channels stay as tombstones.
+//!
+//! Layouts travel as JSON in NGC custom packets after
+//! `PacketType::GuildLayoutSync`, split with [`GuildLayout::to_packets`].
+//! Moderators broadcast what they change and send the whole layout privately
+//! to peers as they join. Members only take the values a moderator wrote
+//! themselves (see [`GuildLayout::merge_from`]), so one moderator can't write
+//! in another's name; a joining peer gets the rest from the other moderators.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::codec::TOX_MAX_CUSTOM_PACKET_SIZE;
+use crate::packets::PacketType;
+
+/// Longest channel name, in bytes
+pub const MAX_CHANNEL_NAME: usize = 100;
+
+/// Longest channel key, in bytes
+const MAX_CHANNEL_KEY: usize = 100;
+
+/// How far past our latest clock a received one may be. Each write only moves
+/// the clock by one, so anything further is refused rather than letting a
+/// field's clock be pushed out of reach of later writes.
+const MAX_CLOCK_AHEAD: u64 = 1 << 20;
+
+/// When a value was written and by whom. Later clocks win; the public key
+/// breaks ties between writes made at the same clock.
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Stamp {
+    pub clock: u64,
+    pub actor: String,
+}
+
+/// A last-writer-wins register
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Lww<T> {
+    pub value: T,
+    pub stamp: Stamp,
+}
+
+impl<T: Clone> Lww<T> {
+    fn new(value: T, stamp: &Stamp) -> Self {
+        Self { value, stamp: stamp.clone() }
+    }
+
+    fn set(&mut self, value: T, stamp: &Stamp) {
+        self.value = value;
+        self.stamp = stamp.clone();
+    }
+
+    /// Take `other` if it was written later. Returns whether it was.
+    fn merge(&mut self, other: &Self) -> bool {
+        let newer = other.stamp > self.stamp;
+        if newer {
+            *self = other.clone();
+        }
+        newer
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChannelEntry {
+    /// The name the channel had when first shared
+    pub origin: String,
+    pub name: Lww<String>,
+    pub position: Lww<i64>,
+    pub deleted: Lww<bool>,
+}
+
+impl ChannelEntry {
+    /// Take the newer of each field, of those whose stamp is `accepted`
+    fn merge(&mut self, other: &Self, accepted: impl Fn(&Stamp) -> bool) -> bool {
+        let name = accepted(&other.name.stamp) && self.name.merge(&other.name);
+        let position = accepted(&other.position.stamp) && self.position.merge(&other.position);
+        let deleted = accepted(&other.deleted.stamp) && self.deleted.merge(&other.deleted);
+        name || position || deleted
+    }
+
+    fn stamps(&self) -> [&Stamp; 3] {
+        [&self.name.stamp, &self.position.stamp, &self.deleted.stamp]
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GuildLayout {
+    /// Channels by key, deleted ones included
+    #[serde(default)]
+    pub channels: BTreeMap<String, ChannelEntry>,
+}
+
+impl GuildLayout {
+    pub fn is_empty(&self) -> bool {
+        self.channels.is_empty()
+    }
+
+    /// A key for a channel first shared now as `origin`: the name itself, or a
+    /// random one if a channel already went by it
+    pub fn new_key(&self, origin: &str, random: impl FnOnce() -> String) -> String {
+        if self.channels.contains_key(origin) {
+            random()
+        } else {
+            origin.to_string()
+        }
+    }
+
+    /// Start tracking a channel. Does nothing if `key` is already known.
+    pub fn add_channel(&mut self, key: &str, name: &str, position: i64, actor: &str) {
+        if self.channels.contains_key(key) {
+            return;
+        }
+        let stamp = self.next_stamp(actor);
+        self.channels.insert(
+            key.to_string(),
+            ChannelEntry {
+                origin: name.to_string(),
+                name: Lww::new(name.to_string(), &stamp),
+                position: Lww::new(position, &stamp),
+                deleted: Lww::new(false, &stamp),
+            },
+        );
+    }
+
+    pub fn rename_channel(&mut self, key: &str, name: &str, actor: &str) -> Result<(), String> {
+        validate_name(name)?;
+        let stamp = self.next_stamp(actor);
+        self.entry_mut(key)?.name.set(name.to_string(), &stamp);
+        Ok(())
+    }
+
+    pub fn move_channel(&mut self, key: &str, position: i64, actor: &str) -> Result<(), String> {
+        let stamp = self.next_stamp(actor);
+        self.entry_mut(key)?.position.set(position, &stamp);
+        Ok(())
+    }
+
+    pub fn delete_channel(&mut self, key: &str, actor: &str) -> Result<(), String> {
+        let stamp = self.next_stamp(actor);
+        self.entry_mut(key)?.deleted.set(true, &stamp);
+        Ok(())
+    }
+
+    fn entry_mut(&mut self, key: &str) -> Result<&mut ChannelEntry, String> {
+        self.channels.get_mut(key).ok_or_else(|| "Channel not in the guild layout".to_string())
+    }
+
+    /// The latest clock we've seen
+    fn max_clock(&self) -> u64 {
+        self.channels
+            .values()
+            .flat_map(ChannelEntry::stamps)
+            .map(|s| s.clock)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// A stamp later than every one we've seen
+    fn next_stamp(&self, actor: &str) -> Stamp {
+        Stamp { clock: self.max_clock().saturating_add(1), actor: actor.to_uppercase() }
+    }
+
+    /// Fold in another layout. Returns whether anything changed.
+    pub fn merge(&mut self, other: &GuildLayout) -> bool {
+        self.merge_accepted(other, |_| true)
+    }
+
+    /// Fold in a layout received from `sender`, taking only the values they
+    /// wrote and whose clocks aren't too far ahead of ours. A channel we don't
+    /// have yet is only added if all of it passes. Returns whether anything
+    /// changed.
+    pub fn merge_from(&mut self, other: &GuildLayout, sender: &str) -> bool {
+        let limit = self.max_clock().saturating_add(MAX_CLOCK_AHEAD);
+        self.merge_accepted(other, |stamp| stamp.actor.eq_ignore_ascii_case(sender) && stamp.clock <= limit)
+    }
+
+    fn merge_accepted(&mut self, other: &GuildLayout, accepted: impl Fn(&Stamp) -> bool) -> bool {
+        let mut changed = false;
+        for (key, theirs) in &other.channels {
+            match self.channels.get_mut(key) {
+                Some(ours) => changed |= ours.merge(theirs, &accepted),
+                None if theirs.stamps().into_iter().all(&accepted) => {
+                    self.channels.insert(key.clone(), theirs.clone());
+                    changed = true;
+                }
+                None => {}
+            }
+        }
+        changed
+    }
+
+    /// Only the given channels, to share a change
+    pub fn select(&self, keys: &[&str]) -> GuildLayout {
+        GuildLayout {
+            channels: self
+                .channels
+                .iter()
+                .filter(|(key, _)| keys.contains(&key.as_str()))
+                .map(|(key, entry)| (key.clone(), entry.clone()))
+                .collect(),
+        }
+    }
+
+    /// Split into as few packets as possible, each at most one custom packet
+    pub fn to_packets(&self) -> Vec<Vec<u8>> {
+        let wrap = |channels: &BTreeMap<String, ChannelEntry>| {
+            let mut buf = vec![PacketType::GuildLayoutSync as u8];
+            buf.extend(serde_json::to_vec(&GuildLayout { channels: channels.clone() }).unwrap_or_default());
+            buf
+        };
+
+        let mut packets = Vec::new();
+        let mut batch = BTreeMap::new();
+        for (key, entry) in &self.channels {
+            batch.insert(key.clone(), entry.clone());
+            if batch.len() > 1 && wrap(&batch).len() > TOX_MAX_CUSTOM_PACKET_SIZE {
+                batch.remove(key);
+                packets.push(wrap(&batch));
+                batch = BTreeMap::from([(key.clone(), entry.clone())]);
+            }
+        }
+        if !batch.is_empty() {
+            packets.push(wrap(&batch));
+        }
+        packets
+    }
+
+    /// Parse an NGC custom packet. Returns `None` if it isn't a valid layout.
+    pub fn from_packet(data: &[u8]) -> Option<Self> {
+        let (&first, payload) = data.split_first()?;
+        if first != PacketType::GuildLayoutSync as u8 {
+            return None;
+        }
+        let layout: Self = serde_json::from_slice(payload).ok()?;
+        let valid = layout.channels.iter().all(|(key, entry)| {
+            !key.is_empty()
+                && key.len() <= MAX_CHANNEL_KEY
+                && validate_name(&entry.origin).is_ok()
+                && validate_name(&entry.name.value).is_ok()
+                && entry.stamps().iter().all(|s| is_public_key(&s.actor))
+        });
+        valid.then_some(layout)
+    }
+}
+
+/// Channel names end up in the `[CH:name]` message prefix, so they can't
+/// contain `]`
+pub fn validate_name(name: &str) -> Result<(), String> {
+    if name.trim().is_empty() {
+        return Err("Channel names cannot be empty".to_string());
+    }
+    if name.len() > MAX_CHANNEL_NAME {
+        return Err(format!("Channel names are limited to {MAX_CHANNEL_NAME} bytes"));
+    }
+    if name.contains(']') {
+        return Err("Channel names cannot contain ']'".to_string());
+    }
+    Ok(())
+}
+
+fn is_public_key(key: &str) -> bool {
+    key.len() == 64 && key.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_concurrent_changes_converge() {
+        let (alice, bob) = ("A".repeat(64), "B".repeat(64));
+        let mut base = GuildLayout::default();
+        base.add_channel("general", "general", 0, &alice);
+        base.add_channel("random", "random", 1, &alice);
+
+        // Both rename #general and reorder without hearing from each other
+        let mut ours = base.clone();
+        ours.rename_channel("general", "lobby", &alice).unwrap();
+        ours.move_channel("random", 0, &alice).unwrap();
+        let mut theirs = base.clone();
+        theirs.rename_channel("general", "chat", &bob).unwrap();
+        theirs.delete_channel("random", &bob).unwrap();
+
+        let mut left = ours.clone();
+        assert!(left.merge(&theirs));
+        let mut right = theirs.clone();
+        assert!(right.merge(&ours));
+        assert_eq!(left, right);
+        // Same clock: the higher public key wins the rename
+        assert_eq!(left.channels["general"].name.value, "chat");
+        // Different fields don't conflict
+        assert_eq!(left.channels["random"].position.value, 0);
+        assert!(left.channels["random"].deleted.value);
+
+        // Merging again changes nothing
+        assert!(!left.merge(&theirs));
+        // A later change beats both
+        left.rename_channel("general", "hangout", &alice).unwrap();
+        right.merge(&left.select(&["general"]));
+        assert_eq!(right.channels["general"].name.value, "hangout");
+    }
+
+    #[test]
+    fn test_layout_packets() {
+        let alice = "A".repeat(64);
+        let mut layout = GuildLayout::default();
+        for i in 0..20 {
+            layout.add_channel(&format!("channel-{i}"), &format!("channel-{i}"), i, &alice);
+        }
+        assert_eq!(layout.new_key("channel-3", || "random".to_string()), "random");
+        assert_eq!(layout.new_key("channel-30", || "random".to_string()), "channel-30");
+
+        let packets = layout.to_packets();
+        assert!(packets.len() > 1);
+        assert!(packets.iter().all(|p| p.len() <= TOX_MAX_CUSTOM_PACKET_SIZE));
+        let mut received = GuildLayout::default();
+        for packet in &packets {
+            received.merge(&GuildLayout::from_packet(packet).unwrap());
+        }
+        assert_eq!(received, layout);
+        assert!(GuildLayout::default().to_packets().is_empty());
+
+        assert!(layout.rename_channel("channel-1", "bad]name", &alice).is_err());
+        assert!(layout.rename_channel("missing", "name", &alice).is_err());
+        let mut forged = layout.select(&["channel-1"]);
+        forged.channels.get_mut("channel-1").unwrap().name.value = "bad]name".to_string();
+        assert_eq!(GuildLayout::from_packet(&forged.to_packets()[0]), None);
+        assert_eq!(GuildLayout::from_packet(&[PacketType::GuildMetaSync as u8]), None);
+    }
+
+    #[test]
+    fn test_merge_from_sender() {
+        let (alice, bob) = ("A".repeat(64), "B".repeat(64));
+        let mut base = GuildLayout::default();
+        base.add_channel("general", "general", 0, &alice);
+
+        // Bob can't pass off a write as Alice's
+        let mut forged = base.clone();
+        forged.rename_channel("general", "forged", &alice).unwrap();
+        forged.add_channel("new", "new", 1, &alice);
+        let mut ours = base.clone();
+        assert!(!ours.merge_from(&forged, &bob));
+        assert_eq!(ours, base);
+
+        // Bob's own writes go through, the rest of his layout doesn't
+        let mut theirs = forged.clone();
+        theirs.move_channel("general", 5, &bob).unwrap();
+        assert!(ours.merge_from(&theirs, &bob.to_lowercase()));
+        assert_eq!(ours.channels["general"].position.value, 5);
+        assert_eq!(ours.channels["general"].name.value, "general");
+        assert!(!ours.channels.contains_key("new"));
+
+        // A clock far past ours is refused, and clocks never wrap
+        let mut ahead = base.clone();
+        ahead.channels.get_mut("general").unwrap().name = Lww::new(
+            "ahead".to_string(),
+            &Stamp { clock: u64::MAX, actor: bob.clone() },
+        );
+        assert!(!ours.merge_from(&ahead, &bob));
+        ours.merge(&ahead);
+        ours.rename_channel("general", "later", &alice).unwrap();
+        assert_eq!(ours.channels["general"].name.stamp.clock, u64::MAX);
+    }
+}
@@ -0,0 +1,596 @@
+//! Guild manifest.
+//!
+//! Guild-wide settings chosen by founders and moderators, such as per-channel
+//! slow mode and the word filter. Channels are created locally by each member
+//! and matched by name (see the `[CH:name]` message prefix), so channel
+//! settings are keyed by name.
+//!
+//! The whole manifest travels as JSON in an NGC custom packet after
+//! `PacketType::GuildMetaSync`. Moderators broadcast it when they change it and
+//! send it privately to peers as they join. Members keep the highest `version`
+//! they have received from a founder or moderator.
+//!
+//! NGC has one founder for good, so ownership lives here too: `owner` is who
+//! manages the guild now, and `co_founders` keeps everyone who may still
+//! manage it, so the guild outlives a founder who disappears.
+//!
+//! NGC itself only knows four roles. Custom roles are cosmetic ones layered
+//! on top: each has a name, color and icon for the member list and maps onto
+//! the NGC role that decides what its members may do. Moderators set that NGC
+//! role when they assign a custom role, and again when the member rejoins.
+//!
+//! Onboarding settings greet new members and carry the guild's rules. The
+//! welcome message is shown locally by every member when someone new joins,
+//! and members can be asked to accept the rules before they post; both are
+//! enforced by each client on its own.
+
+use std::collections::BTreeMap;
+
+use regex::{RegexSet, RegexSetBuilder};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::packets::PacketType;
+
+/// Longest slow-mode delay (6 hours)
+pub const MAX_SLOW_MODE_SECS: u32 = 6 * 60 * 60;
+
+/// Most rules in a word filter
+pub const MAX_FILTER_RULES: usize = 100;
+
+/// Longest word or pattern in a word filter, in bytes
+pub const MAX_FILTER_PATTERN: usize = 200;
+
+/// Most co-founders a guild can have
+pub const MAX_CO_FOUNDERS: usize = 16;
+
+/// Most custom roles a guild can have
+pub const MAX_CUSTOM_ROLES: usize = 32;
+
+/// Longest custom role name, in characters
+pub const MAX_ROLE_NAME: usize = 32;
+
+/// Longest custom role icon (an emoji), in bytes
+pub const MAX_ROLE_ICON: usize = 32;
+
+/// Longest welcome message, in characters
+pub const MAX_WELCOME_MESSAGE: usize = 500;
+
+/// Longest rules text, in characters
+pub const MAX_RULES: usize = 4000;
+
+/// Compiled size limit for a word filter, so a peer can't send a pattern that
+/// takes forever to build
+const FILTER_SIZE_LIMIT: usize = 1 << 20;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GuildManifest {
+    /// Bumped on every change
+    pub version: u64,
+    /// Settings by channel name; channels with default settings are left out
+    #[serde(default)]
+    pub channels: BTreeMap<String, ChannelSettings>,
+    #[serde(default)]
+    pub word_filter: WordFilter,
+    /// Public key of the current owner; empty while it's still the founder
+    #[serde(default)]
+    pub owner: String,
+    /// Public keys of former owners and others who may manage the guild
+    #[serde(default)]
+    pub co_founders: Vec<String>,
+    /// Custom roles, highest first
+    #[serde(default)]
+    pub roles: Vec<CustomRole>,
+    /// Custom role id by member public key (uppercase)
+    #[serde(default)]
+    pub member_roles: BTreeMap<String, u32>,
+    #[serde(default)]
+    pub onboarding: Onboarding,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Onboarding {
+    /// Shown when someone new joins; `{name}` is replaced by their name.
+    /// Empty for none.
+    #[serde(default)]
+    pub welcome_message: String,
+    /// Empty for none
+    #[serde(default)]
+    pub rules: String,
+    /// Members must accept the rules before they can post
+    #[serde(default)]
+    pub require_rules_ack: bool,
+}
+
+impl Onboarding {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.welcome_message.chars().count() > MAX_WELCOME_MESSAGE {
+            return Err(format!("Welcome messages are limited to {MAX_WELCOME_MESSAGE} characters"));
+        }
+        if self.rules.chars().count() > MAX_RULES {
+            return Err(format!("Rules are limited to {MAX_RULES} characters"));
+        }
+        if self.require_rules_ack && self.rules.trim().is_empty() {
+            return Err("Write the rules before requiring members to accept them".to_string());
+        }
+        Ok(())
+    }
+
+    /// The welcome message for a member called `name`, if there is one
+    pub fn welcome(&self, name: &str) -> Option<String> {
+        let message = self.welcome_message.trim();
+        (!message.is_empty()).then(|| message.replace("{name}", name))
+    }
+
+    /// Identifies the current rules, so accepting them can be undone by
+    /// changing them
+    pub fn rules_hash(&self) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.rules.trim().as_bytes());
+        hasher.finalize().iter().map(|b| format!("{b:02x}")).collect()
+    }
+
+    /// Whether someone who accepted the rules with `accepted_hash`, if ever,
+    /// may post
+    pub fn may_post(&self, accepted_hash: Option<&str>) -> bool {
+        !self.require_rules_ack || accepted_hash == Some(self.rules_hash().as_str())
+    }
+}
+
+/// The NGC role a custom role grants. Founder can't be handed out.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BaseRole {
+    Moderator,
+    #[default]
+    User,
+    Observer,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CustomRole {
+    /// Unique within the guild
+    pub id: u32,
+    pub name: String,
+    /// `#rrggbb`
+    pub color: String,
+    /// An emoji shown next to member names; may be empty
+    #[serde(default)]
+    pub icon: String,
+    #[serde(default)]
+    pub base_role: BaseRole,
+}
+
+impl CustomRole {
+    pub fn validate(&self) -> Result<(), String> {
+        let name = self.name.trim();
+        if name.is_empty() || name.chars().count() > MAX_ROLE_NAME {
+            return Err(format!("Role names must be 1 to {MAX_ROLE_NAME} characters"));
+        }
+        if !is_color(&self.color) {
+            return Err("Role colors must look like #rrggbb".to_string());
+        }
+        if self.icon.len() > MAX_ROLE_ICON {
+            return Err(format!("Role icons are limited to {MAX_ROLE_ICON} bytes"));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChannelSettings {
+    /// Seconds each member waits between messages; 0 turns slow mode off
+    #[serde(default)]
+    pub slow_mode_secs: u32,
+    /// Only founders and moderators may post
+    #[serde(default)]
+    pub announcement: bool,
+}
+
+impl ChannelSettings {
+    /// Seconds left at `now` before a member who last posted at `last_sent`
+    /// may post again (both Unix seconds)
+    pub fn cooldown_remaining(&self, last_sent: i64, now: i64) -> u32 {
+        let delay = i64::from(self.slow_mode_secs);
+        (last_sent.saturating_add(delay) - now).clamp(0, delay) as u32
+    }
+}
+
+/// What happens to a message that matches the word filter
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FilterAction {
+    /// Drop the message without storing it
+    #[default]
+    Hide,
+    /// Store it but show it behind a warning
+    Warn,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FilterRule {
+    pub pattern: String,
+    /// Treat `pattern` as a regular expression instead of a word
+    #[serde(default)]
+    pub regex: bool,
+}
+
+impl FilterRule {
+    /// Words match case-insensitively and only as whole words
+    fn to_regex(&self) -> String {
+        if self.regex {
+            return format!("(?i){}", self.pattern);
+        }
+        let word = self.pattern.trim();
+        let edge = |c: Option<char>| if c.is_some_and(char::is_alphanumeric) { r"\b" } else { "" };
+        format!(
+            "(?i){}{}{}",
+            edge(word.chars().next()),
+            regex::escape(word),
+            edge(word.chars().last())
+        )
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WordFilter {
+    #[serde(default)]
+    pub rules: Vec<FilterRule>,
+    #[serde(default)]
+    pub action: FilterAction,
+}
+
+impl WordFilter {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.rules.len() > MAX_FILTER_RULES {
+            return Err(format!("A word filter can have at most {MAX_FILTER_RULES} rules"));
+        }
+        for rule in &self.rules {
+            if rule.pattern.trim().is_empty() {
+                return Err("Word filter rules cannot be empty".to_string());
+            }
+            if rule.pattern.len() > MAX_FILTER_PATTERN {
+                return Err(format!("Word filter rules are limited to {MAX_FILTER_PATTERN} bytes"));
+            }
+        }
+        self.compile().map(|_| ())
+    }
+
+    fn compile(&self) -> Result<RegexSet, String> {
+        RegexSetBuilder::new(self.rules.iter().map(FilterRule::to_regex))
+            .size_limit(FILTER_SIZE_LIMIT)
+            .build()
+            .map_err(|e| format!("Invalid word filter pattern: {e}"))
+    }
+
+    /// The action to take on `text`, if any rule matches it
+    pub fn check(&self, text: &str) -> Option<FilterAction> {
+        if self.rules.is_empty() {
+            return None;
+        }
+        let set = self.compile().ok()?;
+        set.is_match(text).then_some(self.action)
+    }
+}
+
+impl GuildManifest {
+    /// Settings of a channel, or the defaults
+    pub fn channel(&self, name: &str) -> ChannelSettings {
+        self.channels.get(name).cloned().unwrap_or_default()
+    }
+
+    pub fn set_slow_mode(&mut self, channel: &str, secs: u32) -> Result<(), String> {
+        if secs > MAX_SLOW_MODE_SECS {
+            return Err(format!("Slow mode can be at most {MAX_SLOW_MODE_SECS} seconds"));
+        }
+        self.update_channel(channel, |settings| settings.slow_mode_secs = secs);
+        Ok(())
+    }
+
+    pub fn set_announcement(&mut self, channel: &str, announcement: bool) {
+        self.update_channel(channel, |settings| settings.announcement = announcement);
+    }
+
+    fn update_channel(&mut self, channel: &str, update: impl FnOnce(&mut ChannelSettings)) {
+        let mut settings = self.channel(channel);
+        update(&mut settings);
+        if settings == ChannelSettings::default() {
+            self.channels.remove(channel);
+        } else {
+            self.channels.insert(channel.to_string(), settings);
+        }
+    }
+
+    /// Whether `public_key` is the owner or a co-founder
+    pub fn can_manage(&self, public_key: &str) -> bool {
+        !public_key.is_empty()
+            && (self.owner.eq_ignore_ascii_case(public_key)
+                || self.co_founders.iter().any(|k| k.eq_ignore_ascii_case(public_key)))
+    }
+
+    /// Hand the guild from `from` to `to`. `from` stays on as a co-founder.
+    pub fn transfer_ownership(&mut self, from: &str, to: &str) -> Result<(), String> {
+        if !is_public_key(to) {
+            return Err("Invalid public key".to_string());
+        }
+        if from.eq_ignore_ascii_case(to) {
+            return Err("You already own this guild".to_string());
+        }
+        self.co_founders.retain(|k| !k.eq_ignore_ascii_case(to) && !k.eq_ignore_ascii_case(from));
+        if self.co_founders.len() >= MAX_CO_FOUNDERS {
+            return Err(format!("A guild can have at most {MAX_CO_FOUNDERS} co-founders"));
+        }
+        self.co_founders.push(from.to_uppercase());
+        self.owner = to.to_uppercase();
+        Ok(())
+    }
+
+    pub fn role(&self, id: u32) -> Option<&CustomRole> {
+        self.roles.iter().find(|r| r.id == id)
+    }
+
+    /// The custom role of a member, if they have one
+    pub fn member_role(&self, public_key: &str) -> Option<&CustomRole> {
+        self.member_roles
+            .get(&public_key.to_uppercase())
+            .and_then(|&id| self.role(id))
+    }
+
+    /// Add a role below the existing ones; returns its id
+    pub fn add_role(&mut self, name: &str, color: &str, icon: &str, base_role: BaseRole) -> Result<u32, String> {
+        if self.roles.len() >= MAX_CUSTOM_ROLES {
+            return Err(format!("A guild can have at most {MAX_CUSTOM_ROLES} roles"));
+        }
+        let id = self.roles.iter().map(|r| r.id + 1).max().unwrap_or(1);
+        let role = CustomRole {
+            id,
+            name: name.trim().to_string(),
+            color: color.to_lowercase(),
+            icon: icon.trim().to_string(),
+            base_role,
+        };
+        role.validate()?;
+        self.roles.push(role);
+        Ok(id)
+    }
+
+    /// Replace a role's name, color, icon and NGC role
+    pub fn update_role(&mut self, role: CustomRole) -> Result<(), String> {
+        role.validate()?;
+        let existing = self.roles.iter_mut().find(|r| r.id == role.id).ok_or("Role not found")?;
+        *existing = CustomRole {
+            name: role.name.trim().to_string(),
+            color: role.color.to_lowercase(),
+            icon: role.icon.trim().to_string(),
+            ..role
+        };
+        Ok(())
+    }
+
+    /// Delete a role, taking it away from everyone who had it
+    pub fn remove_role(&mut self, id: u32) -> Result<(), String> {
+        let before = self.roles.len();
+        self.roles.retain(|r| r.id != id);
+        if self.roles.len() == before {
+            return Err("Role not found".to_string());
+        }
+        self.member_roles.retain(|_, role| *role != id);
+        Ok(())
+    }
+
+    /// Put the roles in the order of `ids`, highest first. Every role must be
+    /// listed once.
+    pub fn reorder_roles(&mut self, ids: &[u32]) -> Result<(), String> {
+        let mut sorted = ids.to_vec();
+        sorted.sort_unstable();
+        sorted.dedup();
+        let mut known: Vec<u32> = self.roles.iter().map(|r| r.id).collect();
+        known.sort_unstable();
+        if sorted.len() != ids.len() || sorted != known {
+            return Err("The new order must list every role once".to_string());
+        }
+        self.roles.sort_by_key(|r| ids.iter().position(|&id| id == r.id));
+        Ok(())
+    }
+
+    /// Give a member a custom role, or take theirs away with `None`
+    pub fn assign_role(&mut self, public_key: &str, id: Option<u32>) -> Result<(), String> {
+        if !is_public_key(public_key) {
+            return Err("Invalid public key".to_string());
+        }
+        match id {
+            Some(id) => {
+                if self.role(id).is_none() {
+                    return Err("Role not found".to_string());
+                }
+                self.member_roles.insert(public_key.to_uppercase(), id);
+            }
+            None => {
+                self.member_roles.remove(&public_key.to_uppercase());
+            }
+        }
+        Ok(())
+    }
+
+    pub fn to_packet(&self) -> Vec<u8> {
+        let mut buf = vec![PacketType::GuildMetaSync as u8];
+        buf.extend(serde_json::to_vec(self).unwrap_or_default());
+        buf
+    }
+
+    /// Parse an NGC custom packet. Returns `None` if it isn't a manifest.
+    pub fn from_packet(data: &[u8]) -> Option<Self> {
+        let (&first, payload) = data.split_first()?;
+        if first != PacketType::GuildMetaSync as u8 {
+            return None;
+        }
+        let manifest: Self = serde_json::from_slice(payload).ok()?;
+        let valid = manifest.channels.values().all(|c| c.slow_mode_secs <= MAX_SLOW_MODE_SECS)
+            && manifest.word_filter.validate().is_ok()
+            && (manifest.owner.is_empty() || is_public_key(&manifest.owner))
+            && manifest.co_founders.len() <= MAX_CO_FOUNDERS
+            && manifest.co_founders.iter().all(|k| is_public_key(k))
+            && manifest.roles.len() <= MAX_CUSTOM_ROLES
+            && manifest.roles.iter().all(|r| r.validate().is_ok())
+            && manifest
+                .member_roles
+                .iter()
+                .all(|(k, &id)| is_public_key(k) && manifest.role(id).is_some())
+            && manifest.onboarding.validate().is_ok();
+        valid.then_some(manifest)
+    }
+}
+
+fn is_public_key(key: &str) -> bool {
+    key.len() == 64 && key.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+fn is_color(color: &str) -> bool {
+    color.len() == 7 && color.starts_with('#') && color[1..].bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest_packet_roundtrip() {
+        let mut manifest = GuildManifest { version: 3, ..Default::default() };
+        manifest.set_slow_mode("general", 30).unwrap();
+        assert_eq!(manifest.channel("general").slow_mode_secs, 30);
+        assert_eq!(manifest.channel("random"), ChannelSettings::default());
+
+        let packet = manifest.to_packet();
+        assert_eq!(packet[0], PacketType::GuildMetaSync as u8);
+        assert_eq!(GuildManifest::from_packet(&packet), Some(manifest.clone()));
+        assert_eq!(GuildManifest::from_packet(&[PacketType::PresenceUpdate as u8]), None);
+
+        // Back to the defaults drops the channel entry
+        manifest.set_announcement("general", true);
+        manifest.set_slow_mode("general", 0).unwrap();
+        assert!(manifest.channel("general").announcement);
+        manifest.set_announcement("general", false);
+        assert!(manifest.channels.is_empty());
+        assert!(manifest.set_slow_mode("general", MAX_SLOW_MODE_SECS + 1).is_err());
+    }
+
+    #[test]
+    fn test_word_filter() {
+        let word = |pattern: &str| FilterRule { pattern: pattern.to_string(), regex: false };
+        let filter = WordFilter {
+            rules: vec![
+                word("spam"),
+                word("c++"),
+                FilterRule { pattern: r"free\s+nitro".to_string(), regex: true },
+            ],
+            action: FilterAction::Warn,
+        };
+        assert!(filter.validate().is_ok());
+        assert_eq!(filter.check("Buy SPAM now"), Some(FilterAction::Warn));
+        assert_eq!(filter.check("I like c++"), Some(FilterAction::Warn));
+        assert_eq!(filter.check("FREE   nitro here"), Some(FilterAction::Warn));
+        // Words only match whole
+        assert_eq!(filter.check("spammer"), None);
+        assert_eq!(WordFilter::default().check("spam"), None);
+
+        let broken = WordFilter { rules: vec![FilterRule { pattern: "(".to_string(), regex: true }], ..filter };
+        assert!(broken.validate().is_err());
+        let mut manifest = GuildManifest { version: 1, word_filter: broken, ..Default::default() };
+        assert_eq!(GuildManifest::from_packet(&manifest.to_packet()), None);
+        manifest.word_filter.rules.clear();
+        assert!(GuildManifest::from_packet(&manifest.to_packet()).is_some());
+    }
+
+    #[test]
+    fn test_transfer_ownership() {
+        let (founder, heir) = ("A".repeat(64), "b".repeat(64));
+        let mut manifest = GuildManifest::default();
+        assert!(!manifest.can_manage(&heir));
+
+        manifest.transfer_ownership(&founder, &heir).unwrap();
+        assert_eq!(manifest.owner, "B".repeat(64));
+        assert!(manifest.can_manage(&heir) && manifest.can_manage(&founder));
+        assert!(manifest.transfer_ownership(&heir, &heir).is_err());
+        assert!(manifest.transfer_ownership(&heir, "nope").is_err());
+
+        // Handing it back doesn't list anyone twice
+        manifest.transfer_ownership(&heir, &founder).unwrap();
+        assert_eq!(manifest.co_founders, vec!["B".repeat(64)]);
+        assert!(GuildManifest::from_packet(&manifest.to_packet()).is_some());
+    }
+
+    #[test]
+    fn test_custom_roles() {
+        let member = "c".repeat(64);
+        let mut manifest = GuildManifest::default();
+        let mods = manifest.add_role(" Helpers ", "#FF8800", "🛠", BaseRole::Moderator).unwrap();
+        let muted = manifest.add_role("Muted", "#808080", "", BaseRole::Observer).unwrap();
+        assert_ne!(mods, muted);
+        assert_eq!(manifest.role(mods).unwrap().name, "Helpers");
+        assert_eq!(manifest.role(mods).unwrap().color, "#ff8800");
+        assert!(manifest.add_role("", "#000000", "", BaseRole::User).is_err());
+        assert!(manifest.add_role("Red", "red", "", BaseRole::User).is_err());
+
+        manifest.assign_role(&member, Some(mods)).unwrap();
+        assert_eq!(manifest.member_role(&member).map(|r| r.id), Some(mods));
+        assert!(manifest.assign_role(&member, Some(99)).is_err());
+        assert!(manifest.assign_role("nope", Some(mods)).is_err());
+
+        manifest.reorder_roles(&[muted, mods]).unwrap();
+        assert_eq!(manifest.roles[0].id, muted);
+        assert!(manifest.reorder_roles(&[muted]).is_err());
+        assert!(manifest.reorder_roles(&[muted, muted]).is_err());
+
+        let renamed = CustomRole { name: "Staff".to_string(), ..manifest.role(mods).unwrap().clone() };
+        manifest.update_role(renamed).unwrap();
+        assert_eq!(manifest.member_role(&member).unwrap().name, "Staff");
+        assert_eq!(GuildManifest::from_packet(&manifest.to_packet()), Some(manifest.clone()));
+
+        // Deleting a role takes it away from its members
+        manifest.remove_role(mods).unwrap();
+        assert!(manifest.member_role(&member).is_none());
+        assert!(manifest.member_roles.is_empty());
+        assert!(manifest.remove_role(mods).is_err());
+        assert_ne!(manifest.add_role("New", "#00ff00", "", BaseRole::User).unwrap(), muted);
+
+        // Assignments to roles that don't exist are rejected
+        manifest.member_roles.insert(member.to_uppercase(), 99);
+        assert_eq!(GuildManifest::from_packet(&manifest.to_packet()), None);
+    }
+
+    #[test]
+    fn test_onboarding() {
+        let mut onboarding = Onboarding {
+            welcome_message: " Welcome, {name}! ".to_string(),
+            rules: "Be nice".to_string(),
+            require_rules_ack: true,
+        };
+        assert!(onboarding.validate().is_ok());
+        assert_eq!(onboarding.welcome("Bob").as_deref(), Some("Welcome, Bob!"));
+        assert_eq!(Onboarding::default().welcome("Bob"), None);
+
+        let accepted = onboarding.rules_hash();
+        assert!(!onboarding.may_post(None));
+        assert!(onboarding.may_post(Some(&accepted)));
+        // New rules need accepting again
+        onboarding.rules = "Be very nice".to_string();
+        assert!(!onboarding.may_post(Some(&accepted)));
+        assert!(Onboarding::default().may_post(None));
+
+        onboarding.rules.clear();
+        assert!(onboarding.validate().is_err());
+        let manifest = GuildManifest { onboarding, ..Default::default() };
+        assert_eq!(GuildManifest::from_packet(&manifest.to_packet()), None);
+    }
+
+    #[test]
+    fn test_cooldown_remaining() {
+        let settings = ChannelSettings { slow_mode_secs: 60, ..Default::default() };
+        assert_eq!(settings.cooldown_remaining(1_000, 1_000), 60);
+        assert_eq!(settings.cooldown_remaining(1_000, 1_045), 15);
+        assert_eq!(settings.cooldown_remaining(1_000, 1_060), 0);
+        // A clock that went backwards never means a longer wait
+        assert_eq!(settings.cooldown_remaining(1_000, 900), 60);
+        assert_eq!(ChannelSettings::default().cooldown_remaining(1_000, 1_000), 0);
+    }
+}
@@ -0,0 +1,238 @@
+//! Channel history backfill for new guild members.
+//!
+//! NGC groups keep no history, so a member who just joined sees empty
+//! channels. The new member asks one connected peer for the latest messages
+//! of every channel, and the peer answers with batches of them followed by a
+//! `Done`, all as NGC lossless custom private packets:
+//!
+//! - 1 byte: `PacketType::HistorySync`
+//! - JSON-encoded `HistoryPacket`
+//!
+//! Channels are named rather than identified, as channel IDs are local to each
+//! member. Batches are split with [`pack`] so every packet fits in one custom
+//! packet.
+//!
+//! Messages go with the signature their sender gave them (see `signing`), so
+//! the answering peer can't put words in anyone's mouth: the new member checks
+//! each with [`HistoryMessage::verify`] as if it had just come in.
+
+use serde::{Deserialize, Serialize};
+
+use crate::codec::TOX_MAX_CUSTOM_PACKET_SIZE;
+use crate::envelope::{self, ID_TAG_PREFIX};
+use crate::packets::PacketType;
+use crate::signing::{self, MessageSignature, Verification};
+
+/// Most messages a peer sends per channel, whatever the request asks for
+pub const MAX_HISTORY_LIMIT: u32 = 500;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum HistoryPacket {
+    /// Ask for the last `limit` messages of each channel
+    Request { limit: u32 },
+    /// Messages of one channel, oldest first
+    Batch { channel: String, messages: Vec<HistoryMessage> },
+    /// Every batch has been sent
+    Done,
+}
+
+/// A channel message as the answering peer stored it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistoryMessage {
+    /// The content-addressed ID (see `envelope`), if the message had one
+    #[serde(default)]
+    pub id: Option<String>,
+    pub sender_public_key: String,
+    pub sender_name: String,
+    pub content: String,
+    /// "normal" or "action"
+    pub message_type: String,
+    /// RFC 3339, when the answering peer received it
+    pub timestamp: String,
+    /// The sender's signature, if the message was signed
+    #[serde(default)]
+    pub signature: Option<MessageSignature>,
+}
+
+impl HistoryMessage {
+    /// Check the sender's signature, as if the message had just come in on
+    /// `channel`. A message signed for another channel doesn't check out.
+    pub fn verify(&self, channel: &str) -> Verification {
+        let Some(signature) = &self.signature else {
+            return Verification::Unsigned;
+        };
+        if signature.route != format!("[CH:{channel}]") {
+            return Verification::Invalid;
+        }
+        // What was signed: the text after the tag, with its ID tag if it had one
+        let text = match self
+            .id
+            .as_deref()
+            .filter(|id| envelope::verify_id(id, &self.sender_public_key, &self.content))
+        {
+            Some(id) => format!("{ID_TAG_PREFIX}{id}]{}", self.content),
+            None => self.content.clone(),
+        };
+        signing::open_kept(&self.sender_public_key, signature, &text)
+    }
+}
+
+impl HistoryPacket {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = vec![PacketType::HistorySync as u8];
+        buf.extend(serde_json::to_vec(self).unwrap_or_default());
+        buf
+    }
+
+    /// Parse an NGC custom private packet. Returns `None` if it isn't a valid
+    /// history packet.
+    pub fn from_bytes(data: &[u8]) -> Option<Self> {
+        let (&first, payload) = data.split_first()?;
+        if first != PacketType::HistorySync as u8 {
+            return None;
+        }
+        let packet: Self = serde_json::from_slice(payload).ok()?;
+        let valid = match &packet {
+            Self::Request { limit } => *limit > 0,
+            Self::Batch { channel, messages } => {
+                !channel.is_empty()
+                    && messages.iter().all(|m| {
+                        is_public_key(&m.sender_public_key) && matches!(m.message_type.as_str(), "normal" | "action")
+                    })
+            }
+            Self::Done => true,
+        };
+        valid.then_some(packet)
+    }
+}
+
+/// Split a channel's messages into as few `Batch` packets as possible, each
+/// at most one custom packet. Messages too large for a packet on their own
+/// are skipped.
+pub fn pack(channel: &str, messages: &[HistoryMessage]) -> Vec<Vec<u8>> {
+    let wrap = |messages: Vec<HistoryMessage>| {
+        HistoryPacket::Batch {
+            channel: channel.to_string(),
+            messages,
+        }
+        .to_bytes()
+    };
+
+    let mut packets = Vec::new();
+    let mut batch: Vec<HistoryMessage> = Vec::new();
+    let mut current: Option<Vec<u8>> = None;
+
+    for message in messages {
+        let alone = wrap(vec![message.clone()]);
+        if alone.len() > TOX_MAX_CUSTOM_PACKET_SIZE {
+            continue;
+        }
+
+        batch.push(message.clone());
+        let bytes = wrap(batch.clone());
+        if bytes.len() <= TOX_MAX_CUSTOM_PACKET_SIZE {
+            current = Some(bytes);
+        } else {
+            // Doesn't fit: flush what we had and start a new packet with this message
+            packets.extend(current.take());
+            batch = vec![message.clone()];
+            current = Some(alone);
+        }
+    }
+    packets.extend(current);
+    packets
+}
+
+fn is_public_key(key: &str) -> bool {
+    key.len() == 64 && key.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(content: &str) -> HistoryMessage {
+        HistoryMessage {
+            id: None,
+            sender_public_key: "AB".repeat(32),
+            sender_name: "alice".to_string(),
+            content: content.to_string(),
+            message_type: "normal".to_string(),
+            timestamp: "2024-05-10T12:00:00+00:00".to_string(),
+            signature: None,
+        }
+    }
+
+    #[test]
+    fn test_history_packet_roundtrip() {
+        let request = HistoryPacket::Request { limit: 100 };
+        let bytes = request.to_bytes();
+        assert_eq!(bytes[0], PacketType::HistorySync as u8);
+        assert_eq!(HistoryPacket::from_bytes(&bytes), Some(request));
+        assert_eq!(HistoryPacket::from_bytes(&HistoryPacket::Done.to_bytes()), Some(HistoryPacket::Done));
+
+        assert_eq!(HistoryPacket::from_bytes(&HistoryPacket::Request { limit: 0 }.to_bytes()), None);
+        let mut forged = message("hi");
+        forged.sender_public_key = "nope".to_string();
+        let bad = HistoryPacket::Batch { channel: "general".to_string(), messages: vec![forged] };
+        assert_eq!(HistoryPacket::from_bytes(&bad.to_bytes()), None);
+        assert_eq!(HistoryPacket::from_bytes(&[]), None);
+    }
+
+    #[test]
+    fn test_verify_signed_history() {
+        let secret = [7u8; 32];
+        let public = curve25519_dalek::montgomery::MontgomeryPoint::mul_base_clamped(secret).to_bytes();
+        let route = "[CH:general]";
+        let mut signed = message("hi");
+        let (id, tagged) = envelope::seal(&signed.sender_public_key, 1_715_342_400_000, "hi");
+        signed.id = Some(id);
+        signed.signature = Some(MessageSignature {
+            route: route.to_string(),
+            tag: signing::seal_tag(&secret, &public, &signed.sender_public_key, route, &tagged, &[1; 64]),
+        });
+        assert!(matches!(signed.verify("general"), Verification::Verified(_)));
+
+        // Put in another member's mouth, edited, or moved to another channel
+        let mut forged = signed.clone();
+        forged.sender_public_key = "CD".repeat(32);
+        assert_eq!(forged.verify("general"), Verification::Invalid);
+        let mut edited = signed.clone();
+        edited.content = "ho".to_string();
+        assert_eq!(edited.verify("general"), Verification::Invalid);
+        assert_eq!(signed.verify("off-topic"), Verification::Invalid);
+        assert_eq!(message("hi").verify("general"), Verification::Unsigned);
+    }
+
+    #[test]
+    fn test_pack_splits_into_packets() {
+        let messages: Vec<_> = (0..40).map(|i| message(&format!("{i} {}", "x".repeat(200)))).collect();
+        let packets = pack("general", &messages);
+        assert!(packets.len() > 1);
+        assert!(packets.iter().all(|p| p.len() <= TOX_MAX_CUSTOM_PACKET_SIZE));
+
+        let unpacked: Vec<HistoryMessage> = packets
+            .iter()
+            .flat_map(|p| match HistoryPacket::from_bytes(p) {
+                Some(HistoryPacket::Batch { channel, messages }) if channel == "general" => messages,
+                other => panic!("unexpected packet: {other:?}"),
+            })
+            .collect();
+        assert_eq!(unpacked, messages);
+    }
+
+    #[test]
+    fn test_pack_skips_oversized_messages() {
+        let messages = vec![message("hi"), message(&"x".repeat(2000)), message("there")];
+        let packets = pack("general", &messages);
+        assert_eq!(packets.len(), 1);
+        match HistoryPacket::from_bytes(&packets[0]) {
+            Some(HistoryPacket::Batch { messages, .. }) => {
+                assert_eq!(messages.iter().map(|m| m.content.as_str()).collect::<Vec<_>>(), ["hi", "there"]);
+            }
+            other => panic!("unexpected packet: {other:?}"),
+        }
+        assert!(pack("general", &[]).is_empty());
+    }
+}
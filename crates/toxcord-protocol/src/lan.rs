@@ -0,0 +1,94 @@
+//! LAN presence announcements.
+//!
+//! Tox's local discovery only finds DHT nodes, not who is behind them, so
+//! Toxcord instances that opt in broadcast a small UDP datagram on the local
+//! network with their Tox address and display name:
+//!
+//! - 8 bytes: `TOXCORD1` magic
+//! - JSON-encoded `LanAnnouncement`
+
+use serde::{Deserialize, Serialize};
+
+/// UDP port announcements are broadcast on (next to toxcore's 33445)
+pub const LAN_DISCOVERY_PORT: u16 = 33449;
+
+/// How often an instance announces itself
+pub const ANNOUNCE_INTERVAL_SECS: u64 = 5;
+
+/// A peer is forgotten after missing this many seconds of announcements
+pub const PEER_TIMEOUT_SECS: u64 = 30;
+
+const MAGIC: &[u8; 8] = b"TOXCORD1";
+
+/// Length of a Tox address in hex
+const ADDRESS_HEX_LENGTH: usize = 76;
+
+/// Longest display name accepted in an announcement (Tox's own name limit)
+const MAX_NAME_LENGTH: usize = 128;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LanAnnouncement {
+    /// Full Tox address (76 hex chars), so the peer can be added as a friend
+    pub address: String,
+    pub name: String,
+}
+
+impl LanAnnouncement {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = MAGIC.to_vec();
+        // Serializing plain structs of strings can't fail
+        buf.extend(serde_json::to_vec(self).unwrap_or_default());
+        buf
+    }
+
+    /// Parse a datagram. Returns `None` for anything that isn't a valid announcement.
+    pub fn from_bytes(data: &[u8]) -> Option<Self> {
+        let payload = data.strip_prefix(MAGIC.as_slice())?;
+        let announcement: Self = serde_json::from_slice(payload).ok()?;
+        let valid = announcement.address.len() == ADDRESS_HEX_LENGTH
+            && announcement.address.bytes().all(|b| b.is_ascii_hexdigit())
+            && announcement.name.len() <= MAX_NAME_LENGTH;
+        valid.then_some(announcement)
+    }
+
+    /// The public key part of the address (first 64 hex chars), uppercased
+    pub fn public_key(&self) -> String {
+        self.address[..64].to_uppercase()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_announcement_roundtrip() {
+        let announcement = LanAnnouncement {
+            address: "ab".repeat(38),
+            name: "Alice".to_string(),
+        };
+        let bytes = announcement.to_bytes();
+        assert!(bytes.starts_with(b"TOXCORD1"));
+        let parsed = LanAnnouncement::from_bytes(&bytes).unwrap();
+        assert_eq!(parsed, announcement);
+        assert_eq!(parsed.public_key(), "AB".repeat(32));
+    }
+
+    #[test]
+    fn test_rejects_invalid_announcements() {
+        assert_eq!(LanAnnouncement::from_bytes(b"TOXCORD1{}"), None);
+        assert_eq!(LanAnnouncement::from_bytes(b"hello"), None);
+
+        let short = LanAnnouncement {
+            address: "AB".to_string(),
+            name: "Bob".to_string(),
+        };
+        assert_eq!(LanAnnouncement::from_bytes(&short.to_bytes()), None);
+
+        let not_hex = LanAnnouncement {
+            address: "zz".repeat(38),
+            name: "Bob".to_string(),
+        };
+        assert_eq!(LanAnnouncement::from_bytes(&not_hex.to_bytes()), None);
+    }
+}
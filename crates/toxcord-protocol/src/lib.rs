@@ -1,2 +1,25 @@
+pub mod bridge;
 pub mod codec;
+pub mod conversation_lock;
+pub mod device_sync;
+pub mod disappearing;
+pub mod envelope;
+pub mod file_share;
+pub mod fingerprint;
+pub mod group_invites;
+pub mod guild_directory;
+pub mod guild_events;
+pub mod guild_layout;
+pub mod guild_manifest;
+pub mod history;
+pub mod lan;
+pub mod links;
+pub mod markdown;
 pub mod packets;
+pub mod polls;
+pub mod purge;
+pub mod recovery_phrase;
+pub mod rich_presence;
+pub mod signing;
+pub mod status_notes;
+pub mod stickers;
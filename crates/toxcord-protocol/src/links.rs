@@ -0,0 +1,260 @@
+//! Links for adding friends, joining guilds and pointing at messages.
+//!
+//! ```text
+//! toxcord://add/<TOX_ID>[?name=<display name>]
+//! toxcord://join/<CHAT_ID>[?name=<guild name>]
+//! toxcord://message/<CHAT_ID>/<channel name>/<message ID>
+//! toxcord://dm/<PUBLIC_KEY>/<message ID>
+//! ```
+//!
+//! A Tox ID is the 76 hex digit address a friend request goes to: public key,
+//! nospam and checksum. A chat ID is the 64 hex digit ID of a group. The
+//! `tox:<TOX_ID>` form other clients put in QR codes and a bare Tox ID are
+//! read as add links too.
+//!
+//! Channel messages carry the ID their sender gave them, so a message link
+//! works for every member of the guild. Direct message IDs are our own, so a
+//! `dm` link, naming the friend by public key, only works for whoever made it.
+//!
+//! A contact card is a message whose whole content is an add link.
+
+use serde::{Deserialize, Serialize};
+
+pub const LINK_SCHEME: &str = "toxcord://";
+
+/// Scheme used by other Tox clients
+const TOX_URI_SCHEME: &str = "tox:";
+
+/// Length of a hex-encoded Tox ID
+pub const TOX_ID_HEX_LENGTH: usize = 76;
+/// Length of a hex-encoded group chat ID
+pub const CHAT_ID_HEX_LENGTH: usize = 64;
+/// Length of a hex-encoded public key
+const PUBLIC_KEY_HEX_LENGTH: usize = 64;
+/// Longest message ID accepted in a link
+const MAX_MESSAGE_ID: usize = 64;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Link {
+    AddFriend { tox_id: String, name: Option<String> },
+    JoinGuild { chat_id: String, name: Option<String> },
+    ChannelMessage { chat_id: String, channel: String, message_id: String },
+    DirectMessage { public_key: String, message_id: String },
+}
+
+impl Link {
+    /// Read a link, a `tox:` URI or a bare Tox ID. IDs come back uppercase.
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        if let Some(rest) = strip_prefix_ignore_case(text, LINK_SCHEME) {
+            let (path, query) = rest.split_once('?').unwrap_or((rest, ""));
+            let (action, id) = path.trim_end_matches('/').split_once('/')?;
+            let name = query_param(query, "name");
+            return match action.to_ascii_lowercase().as_str() {
+                "add" if is_valid_tox_id(id) => Some(Link::AddFriend { tox_id: id.to_ascii_uppercase(), name }),
+                "join" if is_hex(id, CHAT_ID_HEX_LENGTH) => Some(Link::JoinGuild { chat_id: id.to_ascii_uppercase(), name }),
+                "message" => {
+                    let mut parts = id.splitn(3, '/');
+                    let (chat_id, channel, message_id) = (parts.next()?, parts.next()?, parts.next()?);
+                    let channel = percent_decode(channel);
+                    (is_hex(chat_id, CHAT_ID_HEX_LENGTH) && !channel.is_empty() && is_message_id(message_id)).then(|| {
+                        Link::ChannelMessage {
+                            chat_id: chat_id.to_ascii_uppercase(),
+                            channel,
+                            message_id: message_id.to_ascii_lowercase(),
+                        }
+                    })
+                }
+                "dm" => {
+                    let (public_key, message_id) = id.split_once('/')?;
+                    (is_hex(public_key, PUBLIC_KEY_HEX_LENGTH) && is_message_id(message_id)).then(|| Link::DirectMessage {
+                        public_key: public_key.to_ascii_uppercase(),
+                        message_id: message_id.to_ascii_lowercase(),
+                    })
+                }
+                _ => None,
+            };
+        }
+
+        let id = strip_prefix_ignore_case(text, TOX_URI_SCHEME)
+            .map(|rest| rest.trim_start_matches('/'))
+            .unwrap_or(text);
+        is_valid_tox_id(id).then(|| Link::AddFriend { tox_id: id.to_ascii_uppercase(), name: None })
+    }
+
+    pub fn to_uri(&self) -> String {
+        let (path, name) = match self {
+            Link::AddFriend { tox_id, name } => (format!("add/{tox_id}"), name),
+            Link::JoinGuild { chat_id, name } => (format!("join/{chat_id}"), name),
+            Link::ChannelMessage { chat_id, channel, message_id } => {
+                (format!("message/{chat_id}/{}/{message_id}", percent_encode(channel)), &None)
+            }
+            Link::DirectMessage { public_key, message_id } => (format!("dm/{public_key}/{message_id}"), &None),
+        };
+        match name.as_deref().filter(|n| !n.is_empty()) {
+            Some(name) => format!("{LINK_SCHEME}{path}?name={}", percent_encode(name)),
+            None => format!("{LINK_SCHEME}{path}"),
+        }
+    }
+}
+
+/// The add link a contact card carries, if `content` is one
+pub fn parse_contact_card(content: &str) -> Option<Link> {
+    let content = content.trim();
+    if !content.starts_with(LINK_SCHEME) {
+        return None;
+    }
+    Link::parse(content).filter(|link| matches!(link, Link::AddFriend { .. }))
+}
+
+/// Whether `id` is a Tox ID with a matching checksum: the last two bytes are
+/// the XOR of the byte pairs before them
+pub fn is_valid_tox_id(id: &str) -> bool {
+    if !is_hex(id, TOX_ID_HEX_LENGTH) {
+        return false;
+    }
+    let bytes: Vec<u8> = (0..id.len())
+        .step_by(2)
+        .filter_map(|i| u8::from_str_radix(&id[i..i + 2], 16).ok())
+        .collect();
+    let (body, checksum) = bytes.split_at(bytes.len() - 2);
+    let mut expected = [0u8; 2];
+    for (i, byte) in body.iter().enumerate() {
+        expected[i % 2] ^= byte;
+    }
+    expected == checksum
+}
+
+/// Message IDs are UUIDs or content-addressed IDs: letters, digits and dashes
+fn is_message_id(id: &str) -> bool {
+    !id.is_empty() && id.len() <= MAX_MESSAGE_ID && id.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-')
+}
+
+fn is_hex(s: &str, len: usize) -> bool {
+    s.len() == len && s.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+fn strip_prefix_ignore_case<'a>(text: &'a str, prefix: &str) -> Option<&'a str> {
+    let head = text.get(..prefix.len())?;
+    head.eq_ignore_ascii_case(prefix).then(|| &text[prefix.len()..])
+}
+
+fn query_param(query: &str, key: &str) -> Option<String> {
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(k, _)| *k == key)
+        .map(|(_, v)| percent_decode(v))
+        .filter(|v| !v.is_empty())
+}
+
+fn percent_encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (b as char).to_string(),
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => match bytes.get(i + 1..i + 3).and_then(hex_byte) {
+                Some(b) => {
+                    out.push(b);
+                    i += 3;
+                    continue;
+                }
+                None => out.push(b'%'),
+            },
+            b'+' => out.push(b' '),
+            b => out.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+fn hex_byte(digits: &[u8]) -> Option<u8> {
+    u8::from_str_radix(std::str::from_utf8(digits).ok()?, 16).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A Tox ID with a valid checksum
+    fn tox_id() -> String {
+        let body: Vec<u8> = (0..36).collect();
+        let mut checksum = [0u8; 2];
+        for (i, b) in body.iter().enumerate() {
+            checksum[i % 2] ^= b;
+        }
+        body.iter().chain(&checksum).map(|b| format!("{b:02X}")).collect()
+    }
+
+    #[test]
+    fn test_add_link_roundtrip() {
+        let link = Link::AddFriend { tox_id: tox_id(), name: Some("Zoë & co".to_string()) };
+        let uri = link.to_uri();
+        assert!(uri.starts_with("toxcord://add/000102"));
+        assert!(uri.ends_with("?name=Zo%C3%AB%20%26%20co"));
+        assert_eq!(Link::parse(&uri), Some(link.clone()));
+        assert_eq!(parse_contact_card(&format!("  {uri}\n")), Some(link));
+    }
+
+    #[test]
+    fn test_other_forms() {
+        let id = tox_id();
+        let plain = Some(Link::AddFriend { tox_id: id.clone(), name: None });
+        assert_eq!(Link::parse(&id.to_lowercase()), plain);
+        assert_eq!(Link::parse(&format!("tox:{id}")), plain);
+        assert_eq!(Link::parse(&format!("TOX://{id}")), plain);
+        assert_eq!(parse_contact_card(&id), None);
+
+        let chat_id = "ab".repeat(32);
+        assert_eq!(
+            Link::parse(&format!("toxcord://join/{chat_id}/")),
+            Some(Link::JoinGuild { chat_id: chat_id.to_uppercase(), name: None })
+        );
+        assert_eq!(parse_contact_card(&format!("toxcord://join/{chat_id}")), None);
+    }
+
+    #[test]
+    fn test_message_links() {
+        let chat_id = "AB".repeat(32);
+        let link = Link::ChannelMessage {
+            chat_id: chat_id.clone(),
+            channel: "off topic".to_string(),
+            message_id: "18f5a2c3e00-9f86d081884c7d65".to_string(),
+        };
+        let uri = link.to_uri();
+        assert_eq!(uri, format!("toxcord://message/{chat_id}/off%20topic/18f5a2c3e00-9f86d081884c7d65"));
+        assert_eq!(Link::parse(&uri), Some(link));
+        assert_eq!(parse_contact_card(&uri), None);
+
+        let link = Link::DirectMessage { public_key: "CD".repeat(32), message_id: "0b6f1e4a-2c1d-4f7e-9a3b-5d8c7e6f1a2b".to_string() };
+        assert_eq!(Link::parse(&link.to_uri().to_lowercase()), Some(link));
+
+        assert_eq!(Link::parse(&format!("toxcord://message/{chat_id}/general")), None);
+        assert_eq!(Link::parse(&format!("toxcord://message/{chat_id}//abc")), None);
+        assert_eq!(Link::parse(&format!("toxcord://message/{chat_id}/general/a%20b")), None);
+        assert_eq!(Link::parse(&format!("toxcord://dm/{}/abc", "CD".repeat(31))), None);
+    }
+
+    #[test]
+    fn test_rejects_bad_ids() {
+        let mut id = tox_id();
+        id.replace_range(75.., "0");
+        assert!(!is_valid_tox_id(&id));
+        assert_eq!(Link::parse(&format!("toxcord://add/{id}")), None);
+        assert_eq!(Link::parse("toxcord://add/ABCD"), None);
+        assert_eq!(Link::parse("toxcord://kick/ABCD"), None);
+        assert_eq!(percent_decode("100%"), "100%");
+    }
+}
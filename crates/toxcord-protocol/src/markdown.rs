@@ -0,0 +1,419 @@
+//! Message formatting.
+//!
+//! Parses the markdown subset supported in messages into a small AST that is
+//! sent to the frontend alongside the raw content, so every client renders a
+//! message the same way. [`plain_text`] strips the markup again; that is what
+//! gets indexed for search.
+//!
+//! Supported: `**bold**`, `*italic*` / `_italic_`, `` `code` ``, fenced code
+//! blocks with an optional language tag, `||spoilers||` and `> ` block quotes.
+//! A backslash escapes the next markup character. Unclosed markup is kept as
+//! literal text.
+//!
+//! Mentions travel as `<@PUBLIC_KEY>` (the peer's hex group public key) rather
+//! than as `@name`, so they survive renames and duplicate names. Clients
+//! resolve the key to a display name when rendering.
+
+use serde::{Deserialize, Serialize};
+
+/// A node of a formatted message
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Node {
+    Text { text: String },
+    Bold { children: Vec<Node> },
+    Italic { children: Vec<Node> },
+    Spoiler { children: Vec<Node> },
+    Code { code: String },
+    CodeBlock { language: Option<String>, code: String },
+    BlockQuote { children: Vec<Node> },
+    Mention { public_key: String },
+    LineBreak,
+}
+
+const FENCE: &str = "```";
+
+/// Length of a hex-encoded public key
+const PUBLIC_KEY_HEX_LENGTH: usize = 64;
+
+/// The wire form of a mention of `public_key`
+pub fn mention(public_key: &str) -> String {
+    format!("<@{}>", public_key.to_ascii_uppercase())
+}
+
+/// Public keys mentioned anywhere in the nodes (uppercase hex, in order, with repeats)
+pub fn mentions(nodes: &[Node]) -> Vec<String> {
+    let mut keys = Vec::new();
+    collect_mentions(nodes, &mut keys);
+    keys
+}
+
+fn collect_mentions(nodes: &[Node], keys: &mut Vec<String>) {
+    for node in nodes {
+        match node {
+            Node::Mention { public_key } => keys.push(public_key.clone()),
+            Node::Bold { children } | Node::Italic { children } | Node::Spoiler { children } | Node::BlockQuote { children } => {
+                collect_mentions(children, keys)
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Parse message content into formatting nodes
+pub fn parse(input: &str) -> Vec<Node> {
+    let mut nodes = Vec::new();
+    let mut rest = input;
+
+    // Code blocks first: nothing inside them is markup
+    while let Some(start) = rest.find(FENCE) {
+        let body_start = start + FENCE.len();
+        let Some(len) = rest[body_start..].find(FENCE) else {
+            break;
+        };
+        let before = &rest[..start];
+        parse_blocks(before.strip_suffix('\n').unwrap_or(before), &mut nodes);
+        nodes.push(code_block(&rest[body_start..body_start + len]));
+
+        let after = &rest[body_start + len + FENCE.len()..];
+        rest = after.strip_prefix('\n').unwrap_or(after);
+    }
+    parse_blocks(rest, &mut nodes);
+
+    nodes
+}
+
+/// The text of formatted nodes without any markup
+pub fn plain_text(nodes: &[Node]) -> String {
+    let mut out = String::new();
+    write_plain(nodes, &mut out);
+    out.truncate(out.trim_end_matches('\n').len());
+    out
+}
+
+/// Shorthand for `plain_text(&parse(input))`
+pub fn to_plain_text(input: &str) -> String {
+    plain_text(&parse(input))
+}
+
+fn write_plain(nodes: &[Node], out: &mut String) {
+    for node in nodes {
+        match node {
+            Node::Text { text } => out.push_str(text),
+            Node::Code { code } => out.push_str(code),
+            Node::Mention { public_key } => {
+                out.push('@');
+                out.push_str(public_key);
+            }
+            Node::Bold { children } | Node::Italic { children } | Node::Spoiler { children } => {
+                write_plain(children, out)
+            }
+            Node::LineBreak => out.push('\n'),
+            Node::CodeBlock { code, .. } => {
+                start_block(out);
+                out.push_str(code);
+                out.push('\n');
+            }
+            Node::BlockQuote { children } => {
+                start_block(out);
+                write_plain(children, out);
+                out.push('\n');
+            }
+        }
+    }
+}
+
+fn start_block(out: &mut String) {
+    if !out.is_empty() && !out.ends_with('\n') {
+        out.push('\n');
+    }
+}
+
+/// A fenced block's body: an optional language tag on the first line, then the code
+fn code_block(body: &str) -> Node {
+    let (language, code) = match body.split_once('\n') {
+        Some((first, code)) if is_language_tag(first) => (Some(first.to_string()), code),
+        Some(("", code)) => (None, code),
+        _ => (None, body),
+    };
+    Node::CodeBlock {
+        language,
+        code: code.strip_suffix('\n').unwrap_or(code).to_string(),
+    }
+}
+
+fn is_language_tag(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '#' | '.' | '_'))
+}
+
+/// Split text outside code blocks into paragraphs and block quotes
+fn parse_blocks(text: &str, nodes: &mut Vec<Node>) {
+    let mut paragraph: Vec<&str> = Vec::new();
+    let mut quote: Vec<&str> = Vec::new();
+
+    for line in text.split('\n') {
+        match line.strip_prefix("> ").or((line == ">").then_some("")) {
+            Some(quoted) => {
+                if !paragraph.is_empty() {
+                    nodes.extend(parse_inline(&paragraph.join("\n")));
+                    paragraph.clear();
+                }
+                quote.push(quoted);
+            }
+            None => {
+                if !quote.is_empty() {
+                    nodes.push(Node::BlockQuote {
+                        children: parse_inline(&quote.join("\n")),
+                    });
+                    quote.clear();
+                }
+                paragraph.push(line);
+            }
+        }
+    }
+
+    if !quote.is_empty() {
+        nodes.push(Node::BlockQuote {
+            children: parse_inline(&quote.join("\n")),
+        });
+    }
+    if !paragraph.is_empty() {
+        nodes.extend(parse_inline(&paragraph.join("\n")));
+    }
+}
+
+fn parse_inline(text: &str) -> Vec<Node> {
+    let mut nodes = Vec::new();
+    let mut buf = String::new();
+    let mut i = 0;
+
+    while let Some(c) = text[i..].chars().next() {
+        if c == '\\' {
+            if let Some(next) = text[i + 1..].chars().next().filter(|&n| is_markup(n)) {
+                buf.push(next);
+                i += 1 + next.len_utf8();
+                continue;
+            }
+        }
+        if c == '\n' {
+            flush_text(&mut buf, &mut nodes);
+            nodes.push(Node::LineBreak);
+            i += 1;
+            continue;
+        }
+        if let Some((node, len)) = parse_span(text, i) {
+            flush_text(&mut buf, &mut nodes);
+            nodes.push(node);
+            i += len;
+            continue;
+        }
+        buf.push(c);
+        i += c.len_utf8();
+    }
+
+    flush_text(&mut buf, &mut nodes);
+    nodes
+}
+
+fn flush_text(buf: &mut String, nodes: &mut Vec<Node>) {
+    if !buf.is_empty() {
+        nodes.push(Node::Text {
+            text: std::mem::take(buf),
+        });
+    }
+}
+
+fn is_markup(c: char) -> bool {
+    matches!(c, '\\' | '*' | '_' | '`' | '|' | '>')
+}
+
+/// Try to parse a span starting at byte `i`. Returns the node and its length in bytes.
+fn parse_span(text: &str, i: usize) -> Option<(Node, usize)> {
+    let rest = &text[i..];
+
+    if let Some(inner) = rest.strip_prefix('`') {
+        let len = inner.find('`').filter(|&len| len > 0)?;
+        let code = inner[..len].to_string();
+        return Some((Node::Code { code }, len + 2));
+    }
+    if let Some(inner) = rest.strip_prefix("<@") {
+        let key = inner.get(..PUBLIC_KEY_HEX_LENGTH)?;
+        if !key.chars().all(|c| c.is_ascii_hexdigit()) || !inner[PUBLIC_KEY_HEX_LENGTH..].starts_with('>') {
+            return None;
+        }
+        let public_key = key.to_ascii_uppercase();
+        return Some((Node::Mention { public_key }, PUBLIC_KEY_HEX_LENGTH + 3));
+    }
+    if let Some(inner) = rest.strip_prefix("||") {
+        let len = find_closing(inner, "||")?;
+        let children = parse_inline(&inner[..len]);
+        return Some((Node::Spoiler { children }, len + 4));
+    }
+    if let Some(inner) = rest.strip_prefix("**") {
+        let len = find_closing(inner, "**").filter(|&len| is_emphasis(&inner[..len]))?;
+        let children = parse_inline(&inner[..len]);
+        return Some((Node::Bold { children }, len + 4));
+    }
+    if let Some(inner) = rest.strip_prefix('*') {
+        let len = find_closing(inner, "*").filter(|&len| is_emphasis(&inner[..len]))?;
+        let children = parse_inline(&inner[..len]);
+        return Some((Node::Italic { children }, len + 2));
+    }
+    if let Some(inner) = rest.strip_prefix('_') {
+        // Only at word boundaries, so snake_case stays as it is
+        if text[..i].chars().next_back().is_some_and(char::is_alphanumeric) {
+            return None;
+        }
+        let len = find_closing(inner, "_").filter(|&len| {
+            is_emphasis(&inner[..len]) && !inner[len + 1..].chars().next().is_some_and(char::is_alphanumeric)
+        })?;
+        let children = parse_inline(&inner[..len]);
+        return Some((Node::Italic { children }, len + 2));
+    }
+
+    None
+}
+
+/// Emphasis can't be empty or start/end with whitespace (`2 * 3 * 4` isn't italic)
+fn is_emphasis(inner: &str) -> bool {
+    !inner.is_empty() && !inner.starts_with(char::is_whitespace) && !inner.ends_with(char::is_whitespace)
+}
+
+/// Find the closing delimiter, skipping escapes and code spans. For single-character
+/// delimiters a doubled one belongs to a nested span and is skipped too.
+fn find_closing(s: &str, delim: &str) -> Option<usize> {
+    let doubled = delim.repeat(2);
+    let mut i = 0;
+
+    while let Some(c) = s[i..].chars().next() {
+        let rest = &s[i..];
+        if c == '\\' {
+            i += 1 + rest[1..].chars().next().map_or(0, char::len_utf8);
+            continue;
+        }
+        if c == '`' {
+            if let Some(len) = rest[1..].find('`') {
+                i += len + 2;
+                continue;
+            }
+        }
+        if delim.len() == 1 && rest.starts_with(&doubled) {
+            i += 2;
+            continue;
+        }
+        if rest.starts_with(delim) && i > 0 {
+            return Some(i);
+        }
+        i += c.len_utf8();
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(s: &str) -> Node {
+        Node::Text { text: s.to_string() }
+    }
+
+    #[test]
+    fn test_plain_message() {
+        assert_eq!(parse("hello world"), vec![text("hello world")]);
+        assert!(parse("").is_empty());
+    }
+
+    #[test]
+    fn test_inline_spans() {
+        assert_eq!(
+            parse("**bold** *it* `a*b` ||secret||"),
+            vec![
+                Node::Bold { children: vec![text("bold")] },
+                text(" "),
+                Node::Italic { children: vec![text("it")] },
+                text(" "),
+                Node::Code { code: "a*b".to_string() },
+                text(" "),
+                Node::Spoiler { children: vec![text("secret")] },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_nested_emphasis() {
+        assert_eq!(
+            parse("*a **b** c*"),
+            vec![Node::Italic {
+                children: vec![text("a "), Node::Bold { children: vec![text("b")] }, text(" c")],
+            }]
+        );
+    }
+
+    #[test]
+    fn test_unclosed_and_escaped_markup_is_text() {
+        assert_eq!(parse("**not bold"), vec![text("**not bold")]);
+        assert_eq!(parse("2 * 3 * 4"), vec![text("2 * 3 * 4")]);
+        assert_eq!(parse(r"\*literal\*"), vec![text("*literal*")]);
+        assert_eq!(parse("snake_case_name"), vec![text("snake_case_name")]);
+    }
+
+    #[test]
+    fn test_code_block_with_language() {
+        assert_eq!(
+            parse("look:\n```rust\nfn main() {}\n```\ndone"),
+            vec![
+                text("look:"),
+                Node::CodeBlock {
+                    language: Some("rust".to_string()),
+                    code: "fn main() {}".to_string(),
+                },
+                text("done"),
+            ]
+        );
+        assert_eq!(
+            parse("```**not bold**```"),
+            vec![Node::CodeBlock { language: None, code: "**not bold**".to_string() }]
+        );
+    }
+
+    #[test]
+    fn test_block_quote() {
+        assert_eq!(
+            parse("> quoted *text*\n> more\nreply"),
+            vec![
+                Node::BlockQuote {
+                    children: vec![
+                        text("quoted "),
+                        Node::Italic { children: vec![text("text")] },
+                        Node::LineBreak,
+                        text("more"),
+                    ],
+                },
+                text("reply"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_plain_text() {
+        assert_eq!(to_plain_text("**hi** _there_ ||x||"), "hi there x");
+        assert_eq!(to_plain_text("a\n```js\nlet x;\n```\nb"), "a\nlet x;\nb");
+        assert_eq!(to_plain_text("> q\nafter"), "q\nafter");
+    }
+
+    #[test]
+    fn test_mentions() {
+        let key = "ab".repeat(32);
+        let nodes = parse(&format!("hey {} and **{}**, not `{}`", mention(&key), mention(&key), mention(&key)));
+        assert_eq!(nodes[1], Node::Mention { public_key: key.to_ascii_uppercase() });
+        assert_eq!(mentions(&nodes), vec![key.to_ascii_uppercase(); 2]);
+        assert_eq!(parse("<@nothex>"), vec![text("<@nothex>")]);
+    }
+
+    #[test]
+    fn test_serialized_shape() {
+        let json = serde_json::to_value(parse("**x**")).unwrap();
+        assert_eq!(json, serde_json::json!([{ "type": "bold", "children": [{ "type": "text", "text": "x" }] }]));
+    }
+}
@@ -4,16 +4,18 @@ use serde::{Deserialize, Serialize};
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[repr(u8)]
 pub enum PacketType {
-    /// Automerge sync message for guild state
+    /// Guild manifest (settings shared by moderators)
     GuildMetaSync = 0x01,
     /// Request full metadata sync from peers
     GuildMetaRequest = 0x02,
+    /// Guild channel layout (CRDT shared by moderators)
+    GuildLayoutSync = 0x03,
 
     /// Add/remove emoji reaction
     MessageReaction = 0x10,
     /// Edit message content
     MessageEdit = 0x11,
-    /// Delete message
+    /// Delete messages in bulk (moderators only)
     MessageDelete = 0x12,
     /// Pin/unpin message
     MessagePin = 0x13,
@@ -21,6 +23,8 @@ pub enum PacketType {
     ThreadCreate = 0x14,
     /// Message within a thread
     ThreadMessage = 0x15,
+    /// Channel history for a member who just joined
+    HistorySync = 0x16,
 
     /// Typing indicator start
     TypingStart = 0x20,
@@ -41,6 +45,18 @@ pub enum PacketType {
 
     /// Custom status/activity update
     PresenceUpdate = 0x50,
+
+    /// Announce a file shared in a channel
+    FileOffer = 0x60,
+    /// Ask a peer for a range of a shared file
+    FileRequest = 0x61,
+    /// A piece of a shared file
+    FileChunk = 0x62,
+
+    /// Create or vote on a channel poll
+    Poll = 0x70,
+    /// Schedule or RSVP to a guild event
+    GuildEvent = 0x71,
 }
 
 impl PacketType {
@@ -48,12 +64,14 @@ impl PacketType {
         match byte {
             0x01 => Some(Self::GuildMetaSync),
             0x02 => Some(Self::GuildMetaRequest),
+            0x03 => Some(Self::GuildLayoutSync),
             0x10 => Some(Self::MessageReaction),
             0x11 => Some(Self::MessageEdit),
             0x12 => Some(Self::MessageDelete),
             0x13 => Some(Self::MessagePin),
             0x14 => Some(Self::ThreadCreate),
             0x15 => Some(Self::ThreadMessage),
+            0x16 => Some(Self::HistorySync),
             0x20 => Some(Self::TypingStart),
             0x21 => Some(Self::TypingStop),
             0x30 => Some(Self::VoiceJoin),
@@ -62,6 +80,11 @@ impl PacketType {
             0x40 => Some(Self::InviteCreate),
             0x41 => Some(Self::InviteRequest),
             0x50 => Some(Self::PresenceUpdate),
+            0x60 => Some(Self::FileOffer),
+            0x61 => Some(Self::FileRequest),
+            0x62 => Some(Self::FileChunk),
+            0x70 => Some(Self::Poll),
+            0x71 => Some(Self::GuildEvent),
             _ => None,
         }
     }
@@ -0,0 +1,141 @@
+//! Channel polls.
+//!
+//! A poll is announced with `PollPacket::Create` and voted on with
+//! `PollPacket::Vote`, both broadcast as JSON in NGC custom packets after
+//! `PacketType::Poll`. Each member has one vote per poll, keyed by their public
+//! key; voting again replaces it. Every member tallies the votes they receive
+//! and closes the poll themselves once `closes_at` passes.
+
+use serde::{Deserialize, Serialize};
+
+use crate::packets::PacketType;
+
+/// Longest question, in bytes
+pub const MAX_QUESTION_LEN: usize = 300;
+
+/// Longest option, in bytes
+pub const MAX_OPTION_LEN: usize = 100;
+
+pub const MIN_OPTIONS: usize = 2;
+pub const MAX_OPTIONS: usize = 10;
+
+/// Shortest and longest time a poll stays open, in seconds
+pub const MIN_POLL_DURATION: i64 = 60;
+pub const MAX_POLL_DURATION: i64 = 7 * 24 * 60 * 60;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Poll {
+    /// UUID
+    pub id: String,
+    /// Name of the channel the poll was posted in
+    pub channel: String,
+    pub question: String,
+    pub options: Vec<String>,
+    /// When voting ends, in Unix seconds
+    pub closes_at: i64,
+}
+
+impl Poll {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.question.trim().is_empty() {
+            return Err("Poll question cannot be empty".to_string());
+        }
+        if self.question.len() > MAX_QUESTION_LEN {
+            return Err(format!("Poll question is longer than {MAX_QUESTION_LEN} bytes"));
+        }
+        if !(MIN_OPTIONS..=MAX_OPTIONS).contains(&self.options.len()) {
+            return Err(format!("A poll needs {MIN_OPTIONS} to {MAX_OPTIONS} options"));
+        }
+        if self.options.iter().any(|o| o.trim().is_empty() || o.len() > MAX_OPTION_LEN) {
+            return Err(format!("Poll options must be 1 to {MAX_OPTION_LEN} bytes"));
+        }
+        Ok(())
+    }
+
+    /// Whether voting has ended at `now` (Unix seconds)
+    pub fn is_closed(&self, now: i64) -> bool {
+        now >= self.closes_at
+    }
+}
+
+/// Check a poll duration in seconds
+pub fn validate_duration(secs: i64) -> Result<(), String> {
+    if !(MIN_POLL_DURATION..=MAX_POLL_DURATION).contains(&secs) {
+        return Err("Polls can run from 1 minute to 7 days".to_string());
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum PollPacket {
+    Create { poll: Poll },
+    /// The sender's vote, as an index into the poll's options
+    Vote { poll_id: String, option: u32 },
+}
+
+impl PollPacket {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = vec![PacketType::Poll as u8];
+        buf.extend(serde_json::to_vec(self).unwrap_or_default());
+        buf
+    }
+
+    /// Parse an NGC custom packet. Returns `None` if it isn't a valid poll packet.
+    pub fn from_bytes(data: &[u8]) -> Option<Self> {
+        let (&first, payload) = data.split_first()?;
+        if first != PacketType::Poll as u8 {
+            return None;
+        }
+        let packet: Self = serde_json::from_slice(payload).ok()?;
+        match &packet {
+            Self::Create { poll } if poll.validate().is_err() => None,
+            _ => Some(packet),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn poll() -> Poll {
+        Poll {
+            id: "0b6f7c1e-5d1a-4f7e-9b53-2f0c6f1d9a11".to_string(),
+            channel: "general".to_string(),
+            question: "Game night?".to_string(),
+            options: vec!["Friday".to_string(), "Saturday".to_string()],
+            closes_at: 1_715_342_400,
+        }
+    }
+
+    #[test]
+    fn test_poll_packets() {
+        let create = PollPacket::Create { poll: poll() };
+        let bytes = create.to_bytes();
+        assert_eq!(bytes[0], PacketType::Poll as u8);
+        assert_eq!(PollPacket::from_bytes(&bytes), Some(create));
+
+        let vote = PollPacket::Vote { poll_id: poll().id, option: 1 };
+        assert_eq!(PollPacket::from_bytes(&vote.to_bytes()), Some(vote));
+        assert_eq!(PollPacket::from_bytes(&[PacketType::FileOffer as u8]), None);
+
+        // Invalid polls from peers are dropped
+        let one_option = PollPacket::Create { poll: Poll { options: vec!["Yes".to_string()], ..poll() } };
+        assert_eq!(PollPacket::from_bytes(&one_option.to_bytes()), None);
+    }
+
+    #[test]
+    fn test_poll_validation() {
+        assert!(poll().validate().is_ok());
+        assert!(Poll { question: " ".to_string(), ..poll() }.validate().is_err());
+        assert!(Poll { options: vec!["a".to_string(); MAX_OPTIONS + 1], ..poll() }.validate().is_err());
+        assert!(Poll { options: vec!["a".to_string(), String::new()], ..poll() }.validate().is_err());
+
+        assert!(!poll().is_closed(1_715_342_399));
+        assert!(poll().is_closed(1_715_342_400));
+        assert!(validate_duration(3600).is_ok());
+        assert!(validate_duration(30).is_err());
+        assert!(validate_duration(MAX_POLL_DURATION + 1).is_err());
+    }
+}
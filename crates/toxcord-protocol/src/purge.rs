@@ -0,0 +1,86 @@
+//! Bulk message deletion by moderators.
+//!
+//! Message IDs are assigned locally by each member, so a purge names the
+//! messages to delete by channel name and either a time range or a sender.
+//! Purges travel as JSON in NGC custom packets after `PacketType::MessageDelete`
+//! and are only honored from founders and moderators.
+
+use serde::{Deserialize, Serialize};
+
+use crate::packets::PacketType;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum PurgePacket {
+    /// Delete a channel's messages received at or after `since` and before
+    /// `before` (Unix seconds); a missing bound is open
+    Range {
+        channel: String,
+        #[serde(default)]
+        since: Option<i64>,
+        #[serde(default)]
+        before: Option<i64>,
+    },
+    /// Delete every message a member sent in a channel
+    FromMember { channel: String, public_key: String },
+}
+
+impl PurgePacket {
+    pub fn channel(&self) -> &str {
+        match self {
+            Self::Range { channel, .. } | Self::FromMember { channel, .. } => channel,
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = vec![PacketType::MessageDelete as u8];
+        buf.extend(serde_json::to_vec(self).unwrap_or_default());
+        buf
+    }
+
+    /// Parse an NGC custom packet. Returns `None` if it isn't a valid purge.
+    pub fn from_bytes(data: &[u8]) -> Option<Self> {
+        let (&first, payload) = data.split_first()?;
+        if first != PacketType::MessageDelete as u8 {
+            return None;
+        }
+        let packet: Self = serde_json::from_slice(payload).ok()?;
+        let valid = match &packet {
+            // A purge with no bounds would wipe the channel; that needs `since: 0`
+            Self::Range { since, before, .. } => since.is_some() || before.is_some(),
+            Self::FromMember { public_key, .. } => {
+                public_key.len() == 64 && public_key.chars().all(|c| c.is_ascii_hexdigit())
+            }
+        };
+        valid.then_some(packet)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_purge_packets() {
+        let range = PurgePacket::Range {
+            channel: "general".to_string(),
+            since: Some(1_715_342_400),
+            before: None,
+        };
+        let bytes = range.to_bytes();
+        assert_eq!(bytes[0], PacketType::MessageDelete as u8);
+        assert_eq!(PurgePacket::from_bytes(&bytes), Some(range));
+
+        let member = PurgePacket::FromMember {
+            channel: "general".to_string(),
+            public_key: "AB".repeat(32),
+        };
+        assert_eq!(member.channel(), "general");
+        assert_eq!(PurgePacket::from_bytes(&member.to_bytes()), Some(member));
+
+        let unbounded = PurgePacket::Range { channel: "general".to_string(), since: None, before: None };
+        assert_eq!(PurgePacket::from_bytes(&unbounded.to_bytes()), None);
+        let bad_key = PurgePacket::FromMember { channel: "general".to_string(), public_key: "nope".to_string() };
+        assert_eq!(PurgePacket::from_bytes(&bad_key.to_bytes()), None);
+    }
+}
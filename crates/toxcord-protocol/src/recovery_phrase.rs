@@ -0,0 +1,118 @@
+//! Recovery phrases: a Tox secret key written down as words.
+//!
+//! The phrase is the BIP39 mnemonic of the 32-byte secret key, using the
+//! standard English word list: the key and the first byte of its SHA-256,
+//! read 11 bits at a time, give 24 words. Anyone holding the phrase holds the
+//! identity, so it should only ever be shown to its owner. Only the first four
+//! letters of each word are needed to read it back, as with BIP39.
+
+use sha2::{Digest, Sha256};
+
+pub const SECRET_KEY_LENGTH: usize = 32;
+
+/// Words in a phrase: 256 bits of key and 8 of checksum, 11 bits a word
+pub const PHRASE_WORDS: usize = 24;
+
+const WORD_LIST: &str = include_str!("recovery_phrase_words.txt");
+
+/// Letters of a word that identify it in the list
+const PREFIX_LENGTH: usize = 4;
+
+fn words() -> Vec<&'static str> {
+    WORD_LIST.lines().collect()
+}
+
+/// The phrase for a secret key, words separated by spaces
+pub fn to_phrase(secret_key: &[u8; SECRET_KEY_LENGTH]) -> String {
+    let words = words();
+    let checksum = Sha256::digest(secret_key)[0];
+    let bits: Vec<bool> = secret_key
+        .iter()
+        .chain(std::iter::once(&checksum))
+        .flat_map(|byte| (0..8).rev().map(move |i| byte >> i & 1 == 1))
+        .collect();
+    bits.chunks(11)
+        .map(|chunk| words[chunk.iter().fold(0, |acc, &bit| acc << 1 | bit as usize)])
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The secret key a phrase stands for. Case, extra spaces and anything
+/// after a word's first four letters are ignored.
+pub fn from_phrase(phrase: &str) -> Result<[u8; SECRET_KEY_LENGTH], String> {
+    let words = words();
+    let given: Vec<String> = phrase.split_whitespace().map(str::to_lowercase).collect();
+    if given.len() != PHRASE_WORDS {
+        return Err(format!("A recovery phrase has {PHRASE_WORDS} words, not {}", given.len()));
+    }
+
+    let mut bits = Vec::with_capacity(PHRASE_WORDS * 11);
+    for word in &given {
+        let index = words
+            .iter()
+            .position(|known| match word.get(..PREFIX_LENGTH) {
+                Some(prefix) => known.starts_with(prefix),
+                None => known == word,
+            })
+            .ok_or_else(|| format!("'{word}' isn't a recovery phrase word"))?;
+        bits.extend((0..11).rev().map(|i| index >> i & 1 == 1));
+    }
+
+    let bytes: Vec<u8> = bits
+        .chunks(8)
+        .map(|chunk| chunk.iter().fold(0u8, |acc, &bit| acc << 1 | bit as u8))
+        .collect();
+    let (key, checksum) = bytes.split_at(SECRET_KEY_LENGTH);
+    if Sha256::digest(key)[0] != checksum[0] {
+        return Err("The recovery phrase has a typo or its words are out of order".to_string());
+    }
+    let mut secret_key = [0u8; SECRET_KEY_LENGTH];
+    secret_key.copy_from_slice(key);
+    Ok(secret_key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_word_list() {
+        let words = words();
+        assert_eq!(words.len(), 2048);
+        let mut prefixes: Vec<_> = words.iter().map(|w| &w[..w.len().min(PREFIX_LENGTH)]).collect();
+        prefixes.dedup();
+        assert_eq!(prefixes.len(), words.len());
+    }
+
+    #[test]
+    fn test_bip39_vectors() {
+        let abandon = format!("{}art", "abandon ".repeat(23));
+        assert_eq!(to_phrase(&[0; 32]), abandon);
+        assert_eq!(to_phrase(&[0xff; 32]), format!("{}vote", "zoo ".repeat(23)));
+        assert_eq!(
+            to_phrase(&[0x7f; 32]),
+            "legal winner thank year wave sausage worth useful legal winner thank year wave sausage worth useful \
+             legal winner thank year wave sausage worth title"
+        );
+        assert_eq!(from_phrase(&abandon), Ok([0; 32]));
+    }
+
+    #[test]
+    fn test_phrase_roundtrip() {
+        let key: [u8; 32] = std::array::from_fn(|i| (i * 37 + 11) as u8);
+        let phrase = to_phrase(&key);
+        assert_eq!(phrase.split(' ').count(), PHRASE_WORDS);
+        assert_eq!(from_phrase(&phrase), Ok(key));
+
+        // Four letters are enough, in any case and spacing
+        let short: Vec<String> = phrase.split(' ').map(|w| w.chars().take(4).collect::<String>().to_uppercase()).collect();
+        assert_eq!(from_phrase(&format!("  {}\n", short.join("   "))), Ok(key));
+
+        let mut words: Vec<&str> = phrase.split(' ').collect();
+        words.swap(0, 1);
+        assert!(from_phrase(&words.join(" ")).is_err());
+        words[3] = "qqqq";
+        assert!(from_phrase(&words.join(" ")).unwrap_err().contains("qqqq"));
+        assert!(from_phrase("abandon abandon").is_err());
+    }
+}
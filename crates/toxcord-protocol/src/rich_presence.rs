@@ -0,0 +1,228 @@
+//! Rich presence between Toxcord peers.
+//!
+//! What a user is doing ("Playing X", "Listening to Y") and their custom
+//! status (emoji, text and expiry) go beyond the Tox status message, so they
+//! travel as a JSON `PresencePacket`:
+//!
+//! - to friends as a friend lossless packet, after `RICH_PRESENCE_PACKET_ID`
+//! - to guild peers as an NGC custom packet, after `PacketType::PresenceUpdate`
+
+use serde::{Deserialize, Serialize};
+
+use crate::packets::PacketType;
+
+/// First byte of friend presence packets (Tox reserves 160-191 for lossless custom packets)
+pub const RICH_PRESENCE_PACKET_ID: u8 = 0xA1;
+
+/// Longest activity name or details, in bytes
+pub const MAX_ACTIVITY_TEXT: usize = 128;
+
+/// Longest custom status text, in bytes
+pub const MAX_STATUS_TEXT: usize = 128;
+
+/// Longest custom status emoji, in bytes (room for ZWJ sequences)
+pub const MAX_STATUS_EMOJI: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ActivityKind {
+    Playing,
+    Listening,
+    Watching,
+    Streaming,
+    Competing,
+}
+
+impl ActivityKind {
+    fn verb(self) -> &'static str {
+        match self {
+            Self::Playing => "Playing",
+            Self::Listening => "Listening to",
+            Self::Watching => "Watching",
+            Self::Streaming => "Streaming",
+            Self::Competing => "Competing in",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Activity {
+    pub kind: ActivityKind,
+    /// Game, song or app name
+    pub name: String,
+    /// Extra line, e.g. the artist or the level being played
+    #[serde(default)]
+    pub details: Option<String>,
+    /// When the activity started, in Unix seconds
+    #[serde(default)]
+    pub started_at: Option<i64>,
+}
+
+impl Activity {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("Activity name cannot be empty".to_string());
+        }
+        if self.name.len() > MAX_ACTIVITY_TEXT {
+            return Err(format!("Activity name is longer than {MAX_ACTIVITY_TEXT} bytes"));
+        }
+        if self.details.as_ref().is_some_and(|d| d.len() > MAX_ACTIVITY_TEXT) {
+            return Err(format!("Activity details are longer than {MAX_ACTIVITY_TEXT} bytes"));
+        }
+        Ok(())
+    }
+
+    /// One-line description, e.g. "Listening to Daft Punk"
+    pub fn label(&self) -> String {
+        format!("{} {}", self.kind.verb(), self.name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CustomStatus {
+    #[serde(default)]
+    pub emoji: Option<String>,
+    pub text: String,
+    /// When the status clears itself, in Unix seconds
+    #[serde(default)]
+    pub expires_at: Option<i64>,
+}
+
+impl CustomStatus {
+    pub fn validate(&self) -> Result<(), String> {
+        let emoji = self.emoji.as_deref().unwrap_or_default();
+        if emoji.trim().is_empty() && self.text.trim().is_empty() {
+            return Err("Custom status needs an emoji or text".to_string());
+        }
+        if emoji.len() > MAX_STATUS_EMOJI || emoji.chars().any(char::is_whitespace) {
+            return Err("Custom status emoji must be a single emoji".to_string());
+        }
+        if self.text.len() > MAX_STATUS_TEXT {
+            return Err(format!("Custom status is longer than {MAX_STATUS_TEXT} bytes"));
+        }
+        Ok(())
+    }
+
+    /// Whether the status has expired at `now` (Unix seconds)
+    pub fn is_expired(&self, now: i64) -> bool {
+        self.expires_at.is_some_and(|t| t <= now)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum PresencePacket {
+    /// The sender's current activity; `None` clears it
+    Activity { activity: Option<Activity> },
+    /// The sender's custom status; `None` clears it
+    CustomStatus { status: Option<CustomStatus> },
+}
+
+impl PresencePacket {
+    pub fn to_friend_packet(&self) -> Vec<u8> {
+        self.encode(RICH_PRESENCE_PACKET_ID)
+    }
+
+    pub fn to_group_packet(&self) -> Vec<u8> {
+        self.encode(PacketType::PresenceUpdate as u8)
+    }
+
+    /// Parse a friend lossless packet. Returns `None` if it isn't a valid presence packet.
+    pub fn from_friend_packet(data: &[u8]) -> Option<Self> {
+        Self::decode(RICH_PRESENCE_PACKET_ID, data)
+    }
+
+    /// Parse an NGC custom packet. Returns `None` if it isn't a valid presence packet.
+    pub fn from_group_packet(data: &[u8]) -> Option<Self> {
+        Self::decode(PacketType::PresenceUpdate as u8, data)
+    }
+
+    fn encode(&self, id: u8) -> Vec<u8> {
+        let mut buf = vec![id];
+        buf.extend(serde_json::to_vec(self).unwrap_or_default());
+        buf
+    }
+
+    fn decode(id: u8, data: &[u8]) -> Option<Self> {
+        let (&first, payload) = data.split_first()?;
+        if first != id {
+            return None;
+        }
+        let packet: Self = serde_json::from_slice(payload).ok()?;
+        let valid = match &packet {
+            Self::Activity { activity } => activity.as_ref().is_none_or(|a| a.validate().is_ok()),
+            Self::CustomStatus { status } => status.as_ref().is_none_or(|s| s.validate().is_ok()),
+        };
+        valid.then_some(packet)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn activity(name: &str) -> Activity {
+        Activity {
+            kind: ActivityKind::Listening,
+            name: name.to_string(),
+            details: Some("Discovery".to_string()),
+            started_at: Some(1_715_342_400),
+        }
+    }
+
+    #[test]
+    fn test_presence_packet_roundtrip() {
+        let packet = PresencePacket::Activity {
+            activity: Some(activity("Daft Punk")),
+        };
+        let friend = packet.to_friend_packet();
+        assert_eq!(friend[0], RICH_PRESENCE_PACKET_ID);
+        assert_eq!(PresencePacket::from_friend_packet(&friend), Some(packet.clone()));
+        assert_eq!(PresencePacket::from_group_packet(&friend), None);
+
+        let group = packet.to_group_packet();
+        assert_eq!(group[0], PacketType::PresenceUpdate as u8);
+        assert_eq!(PresencePacket::from_group_packet(&group), Some(packet));
+
+        let cleared = PresencePacket::Activity { activity: None };
+        assert_eq!(PresencePacket::from_friend_packet(&cleared.to_friend_packet()), Some(cleared));
+        assert_eq!(PresencePacket::from_friend_packet(&[]), None);
+    }
+
+    #[test]
+    fn test_activity_validation() {
+        assert_eq!(activity("Daft Punk").label(), "Listening to Daft Punk");
+        assert!(activity("Daft Punk").validate().is_ok());
+        assert!(activity("  ").validate().is_err());
+        assert!(activity(&"x".repeat(MAX_ACTIVITY_TEXT + 1)).validate().is_err());
+
+        // Invalid activities from peers are dropped
+        let oversized = PresencePacket::Activity {
+            activity: Some(activity(&"x".repeat(MAX_ACTIVITY_TEXT + 1))),
+        };
+        assert_eq!(PresencePacket::from_friend_packet(&oversized.to_friend_packet()), None);
+    }
+
+    #[test]
+    fn test_custom_status() {
+        let status = CustomStatus {
+            emoji: Some("🏖️".to_string()),
+            text: "On vacation".to_string(),
+            expires_at: Some(1_715_342_400),
+        };
+        assert!(status.validate().is_ok());
+        assert!(!status.is_expired(1_715_342_399));
+        assert!(status.is_expired(1_715_342_400));
+        assert!(!CustomStatus { expires_at: None, ..status.clone() }.is_expired(i64::MAX));
+
+        let packet = PresencePacket::CustomStatus { status: Some(status.clone()) };
+        assert_eq!(PresencePacket::from_group_packet(&packet.to_group_packet()), Some(packet));
+
+        let emoji_only = CustomStatus { text: String::new(), ..status.clone() };
+        assert!(emoji_only.validate().is_ok());
+        let empty = CustomStatus { emoji: None, text: " ".to_string(), expires_at: None };
+        assert!(empty.validate().is_err());
+        let sentence_emoji = CustomStatus { emoji: Some("not an emoji".to_string()), ..status };
+        assert!(sentence_emoji.validate().is_err());
+    }
+}
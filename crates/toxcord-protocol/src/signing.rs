@@ -0,0 +1,243 @@
+//! Sender signatures for group messages.
+//!
+//! NGC peers are known by a per-group key, and peer IDs and names can change
+//! or be copied, so nothing ties a group message to the sender's Tox
+//! identity. The sender now signs each message with their long-term Tox key
+//! and puts the signature at the start of the text, after the routing prefix
+//! and before the ID tag (see `envelope`):
+//!
+//! `[CH:general][SIG:<Tox public key>:<signature>][ID:...]Hello`
+//!
+//! Tox keys are X25519 keys, so messages are signed with XEdDSA, which makes
+//! Ed25519 signatures with an X25519 key pair. The signature covers the
+//! sender's key in the group and the routing prefix as well as the text after
+//! the tag, so another peer can't pass a copied message off as their own, and
+//! a message can't be moved to another channel.
+//!
+//! Receivers keep the routing prefix and signature tag of a message as a
+//! [`MessageSignature`], so it can be checked again by whoever they pass the
+//! message on to (see `history`).
+//!
+//! A good signature only shows who holds the key; whether that's who the
+//! sender claims to be is up to the receiver, who compares the key with what
+//! they know of the sender.
+
+use curve25519_dalek::edwards::{CompressedEdwardsY, EdwardsPoint};
+use curve25519_dalek::montgomery::MontgomeryPoint;
+use curve25519_dalek::scalar::{clamp_integer, Scalar};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha512};
+
+/// Start of the signature tag
+pub const SIG_TAG_PREFIX: &str = "[SIG:";
+
+/// Separates what's signed from other uses of the key
+const CONTEXT: &[u8] = b"toxcord group message v2\0";
+
+/// What a received message's signature says about its sender
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verification {
+    /// Signed by the holder of this Tox public key (uppercase hex)
+    Verified(String),
+    /// No signature, e.g. from an older client
+    Unsigned,
+    /// A signature that doesn't match the message
+    Invalid,
+}
+
+/// A message's signature as it came, kept to pass the message on
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MessageSignature {
+    /// The routing prefix the message came behind
+    pub route: String,
+    /// The signature tag, `[SIG:...]`
+    pub tag: String,
+}
+
+/// Sign `message` with an X25519 secret key. `random` should be fresh random
+/// bytes for every signature.
+pub fn sign(secret_key: &[u8; 32], message: &[u8], random: &[u8; 64]) -> [u8; 64] {
+    // The Edwards key pair with the same public u-coordinate, with the sign
+    // bit of the public key cleared
+    let k = Scalar::from_bytes_mod_order(clamp_integer(*secret_key));
+    let mut a = k;
+    let mut public = EdwardsPoint::mul_base(&a).compress();
+    if public.as_bytes()[31] & 0x80 != 0 {
+        a = -k;
+        public = EdwardsPoint::mul_base(&a).compress();
+    }
+
+    let mut prefix = [0xFF; 32];
+    prefix[0] = 0xFE;
+    let r = hash_to_scalar(&[&prefix, a.as_bytes(), message, random]);
+    let big_r = EdwardsPoint::mul_base(&r).compress();
+    let h = hash_to_scalar(&[big_r.as_bytes(), public.as_bytes(), message]);
+    let s = r + h * a;
+
+    let mut signature = [0u8; 64];
+    signature[..32].copy_from_slice(big_r.as_bytes());
+    signature[32..].copy_from_slice(s.as_bytes());
+    signature
+}
+
+/// Check a signature made with [`sign`] against an X25519 public key
+pub fn verify(public_key: &[u8; 32], message: &[u8], signature: &[u8; 64]) -> bool {
+    let Some(a) = MontgomeryPoint(*public_key).to_edwards(0) else {
+        return false;
+    };
+    let mut r_bytes = [0u8; 32];
+    r_bytes.copy_from_slice(&signature[..32]);
+    let mut s_bytes = [0u8; 32];
+    s_bytes.copy_from_slice(&signature[32..]);
+    let Some(s) = Option::<Scalar>::from(Scalar::from_canonical_bytes(s_bytes)) else {
+        return false;
+    };
+    let h = hash_to_scalar(&[&r_bytes, a.compress().as_bytes(), message]);
+    // R = sB - hA
+    let r = EdwardsPoint::vartime_double_scalar_mul_basepoint(&h, &-a, &s);
+    r.compress() == CompressedEdwardsY(r_bytes)
+}
+
+fn hash_to_scalar(parts: &[&[u8]]) -> Scalar {
+    let mut hasher = Sha512::new();
+    for part in parts {
+        hasher.update(part);
+    }
+    Scalar::from_bytes_mod_order_wide(&hasher.finalize().into())
+}
+
+/// What's signed: the sender's key in the group, the routing prefix and the
+/// text after the tag
+fn signed_bytes(peer_public_key: &str, route: &str, text: &str) -> Vec<u8> {
+    let mut bytes = CONTEXT.to_vec();
+    bytes.extend(peer_public_key.to_uppercase().as_bytes());
+    bytes.extend((route.len() as u32).to_be_bytes());
+    bytes.extend(route.as_bytes());
+    bytes.extend(text.as_bytes());
+    bytes
+}
+
+/// Sign a message for sending from `peer_public_key` in a group, behind the
+/// routing prefix `route` (`[CH:..]` or `[DM]`). Returns the text with the
+/// signature tag in front, to go after the prefix.
+pub fn seal(
+    secret_key: &[u8; 32],
+    public_key: &[u8; 32],
+    peer_public_key: &str,
+    route: &str,
+    text: &str,
+    random: &[u8; 64],
+) -> String {
+    let tag = seal_tag(secret_key, public_key, peer_public_key, route, text, random);
+    format!("{tag}{text}")
+}
+
+/// Just the signature tag [`seal`] puts in front of `text`
+pub fn seal_tag(
+    secret_key: &[u8; 32],
+    public_key: &[u8; 32],
+    peer_public_key: &str,
+    route: &str,
+    text: &str,
+    random: &[u8; 64],
+) -> String {
+    let signature = sign(secret_key, &signed_bytes(peer_public_key, route, text), random);
+    format!("{SIG_TAG_PREFIX}{}:{}]", hex_upper(public_key), hex_upper(&signature))
+}
+
+/// Take the signature tag off a message from `peer_public_key` that came
+/// behind the routing prefix `route`, and check it
+pub fn open<'a>(peer_public_key: &str, route: &str, text: &'a str) -> (Verification, &'a str) {
+    let Some(rest) = text.strip_prefix(SIG_TAG_PREFIX) else {
+        return (Verification::Unsigned, text);
+    };
+    let Some((tag, text)) = rest.split_once(']') else {
+        return (Verification::Unsigned, text);
+    };
+    let verified = tag.split_once(':').and_then(|(key, signature)| {
+        let key: [u8; 32] = from_hex(key)?;
+        let signature: [u8; 64] = from_hex(signature)?;
+        verify(&key, &signed_bytes(peer_public_key, route, text), &signature).then(|| hex_upper(&key))
+    });
+    match verified {
+        Some(key) => (Verification::Verified(key), text),
+        None => (Verification::Invalid, text),
+    }
+}
+
+/// Check a kept signature against `text`, the message as it was after the tag
+pub fn open_kept(peer_public_key: &str, signature: &MessageSignature, text: &str) -> Verification {
+    let whole_tag = signature
+        .tag
+        .strip_prefix(SIG_TAG_PREFIX)
+        .and_then(|rest| rest.strip_suffix(']'))
+        .is_some_and(|inner| !inner.contains([']', '[']));
+    if !whole_tag {
+        return Verification::Invalid;
+    }
+    open(peer_public_key, &signature.route, &format!("{}{text}", signature.tag)).0
+}
+
+fn hex_upper(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02X}")).collect()
+}
+
+fn from_hex<const N: usize>(hex: &str) -> Option<[u8; N]> {
+    if hex.len() != N * 2 || !hex.is_ascii() {
+        return None;
+    }
+    let mut bytes = [0u8; N];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok()?;
+    }
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key_pair(seed: u8) -> ([u8; 32], [u8; 32]) {
+        let secret = [seed; 32];
+        (secret, MontgomeryPoint::mul_base_clamped(secret).to_bytes())
+    }
+
+    #[test]
+    fn test_sign_and_verify() {
+        for seed in 1..=8 {
+            let (secret, public) = key_pair(seed);
+            let signature = sign(&secret, b"hello", &[seed; 64]);
+            assert!(verify(&public, b"hello", &signature));
+            assert!(!verify(&public, b"goodbye", &signature));
+            assert!(!verify(&key_pair(seed + 100).1, b"hello", &signature));
+        }
+    }
+
+    #[test]
+    fn test_seal_and_open() {
+        let (secret, public) = key_pair(7);
+        let peer = "AB".repeat(32);
+        let route = "[CH:general]";
+        let sealed = seal(&secret, &public, &peer, route, "[ID:1-2]hi", &[1; 64]);
+        assert_eq!(open(&peer, route, &sealed), (Verification::Verified(hex_upper(&public)), "[ID:1-2]hi"));
+        assert_eq!(open(&peer.to_lowercase(), route, &sealed).0, Verification::Verified(hex_upper(&public)));
+
+        // Copied by another peer, edited, or moved to another channel
+        assert_eq!(open(&"CD".repeat(32), route, &sealed), (Verification::Invalid, "[ID:1-2]hi"));
+        assert_eq!(open(&peer, route, &sealed.replace("hi", "ho")).0, Verification::Invalid);
+        assert_eq!(open(&peer, "[CH:off-topic]", &sealed).0, Verification::Invalid);
+        assert_eq!(open(&peer, "[DM]", &sealed).0, Verification::Invalid);
+        assert_eq!(open(&peer, route, "[SIG:nope]hi"), (Verification::Invalid, "hi"));
+        assert_eq!(open(&peer, route, "hi"), (Verification::Unsigned, "hi"));
+
+        // Kept and checked again later
+        let kept = MessageSignature {
+            route: route.to_string(),
+            tag: seal_tag(&secret, &public, &peer, route, "[ID:1-2]hi", &[1; 64]),
+        };
+        assert_eq!(open_kept(&peer, &kept, "[ID:1-2]hi"), Verification::Verified(hex_upper(&public)));
+        assert_eq!(open_kept(&peer, &kept, "[ID:1-2]ho"), Verification::Invalid);
+        let smuggled = MessageSignature { tag: format!("{}[ID:1-2]ho", kept.tag), ..kept };
+        assert_eq!(open_kept(&peer, &smuggled, "hi"), Verification::Invalid);
+    }
+}
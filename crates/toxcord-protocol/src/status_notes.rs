@@ -0,0 +1,116 @@
+//! Status notes.
+//!
+//! A short note posted to every friend, gone a day later. Notes travel as a
+//! JSON `StatusNote` in a friend lossless packet after
+//! `STATUS_NOTE_PACKET_ID`. Friends who are offline when a note is posted get
+//! it when they next come online, unless it has expired by then.
+
+use serde::{Deserialize, Serialize};
+
+use crate::codec::TOX_MAX_CUSTOM_PACKET_SIZE;
+
+/// First byte of status note packets (Tox reserves 160-191 for lossless custom packets)
+pub const STATUS_NOTE_PACKET_ID: u8 = 0xA3;
+
+/// Longest note, in bytes
+pub const MAX_NOTE_TEXT: usize = 500;
+
+/// How long a note lasts, in seconds
+pub const NOTE_LIFETIME_SECS: i64 = 24 * 60 * 60;
+
+/// Longest note ID, in bytes
+const MAX_NOTE_ID: usize = 64;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatusNote {
+    /// Chosen by the author; unique among their notes
+    pub id: String,
+    pub text: String,
+    /// Unix seconds
+    pub posted_at: i64,
+    /// Unix seconds
+    pub expires_at: i64,
+}
+
+impl StatusNote {
+    /// A note posted at `now` (Unix seconds)
+    pub fn new(id: String, text: &str, now: i64) -> Result<Self, String> {
+        let note = Self {
+            id,
+            text: text.trim().to_string(),
+            posted_at: now,
+            expires_at: now + NOTE_LIFETIME_SECS,
+        };
+        note.validate()?;
+        if note.to_bytes().len() > TOX_MAX_CUSTOM_PACKET_SIZE {
+            return Err("Status note is too long to send".to_string());
+        }
+        Ok(note)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.id.is_empty() || self.id.len() > MAX_NOTE_ID {
+            return Err("Invalid status note ID".to_string());
+        }
+        if self.text.trim().is_empty() {
+            return Err("Status note cannot be empty".to_string());
+        }
+        if self.text.len() > MAX_NOTE_TEXT {
+            return Err(format!("Status note is longer than {MAX_NOTE_TEXT} bytes"));
+        }
+        let lifetime = self.expires_at - self.posted_at;
+        if lifetime <= 0 || lifetime > NOTE_LIFETIME_SECS {
+            return Err("Status notes last up to a day".to_string());
+        }
+        Ok(())
+    }
+
+    /// Whether the note has expired at `now` (Unix seconds)
+    pub fn is_expired(&self, now: i64) -> bool {
+        self.expires_at <= now
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = vec![STATUS_NOTE_PACKET_ID];
+        buf.extend(serde_json::to_vec(self).unwrap_or_default());
+        buf
+    }
+
+    /// Parse a friend lossless packet. Returns `None` if it isn't a valid status note.
+    pub fn from_bytes(data: &[u8]) -> Option<Self> {
+        let (&first, payload) = data.split_first()?;
+        if first != STATUS_NOTE_PACKET_ID {
+            return None;
+        }
+        let note: Self = serde_json::from_slice(payload).ok()?;
+        note.validate().is_ok().then_some(note)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_note_packet() {
+        let note = StatusNote::new("n1".to_string(), "  Off hiking today  ", 1_715_342_400).unwrap();
+        assert_eq!(note.text, "Off hiking today");
+        assert_eq!(note.expires_at, 1_715_342_400 + NOTE_LIFETIME_SECS);
+        assert!(!note.is_expired(note.posted_at));
+        assert!(note.is_expired(note.expires_at));
+
+        let bytes = note.to_bytes();
+        assert_eq!(bytes[0], STATUS_NOTE_PACKET_ID);
+        assert_eq!(StatusNote::from_bytes(&bytes), Some(note.clone()));
+        assert_eq!(StatusNote::from_bytes(&[0xA2]), None);
+
+        // Notes from peers can't outlast a day
+        let forever = StatusNote { expires_at: i64::MAX, ..note };
+        assert_eq!(StatusNote::from_bytes(&forever.to_bytes()), None);
+
+        assert!(StatusNote::new("n2".to_string(), " ", 0).is_err());
+        assert!(StatusNote::new("n3".to_string(), &"x".repeat(MAX_NOTE_TEXT + 1), 0).is_err());
+        // Escaping can make a short note too long for a packet
+        assert!(StatusNote::new("n4".to_string(), &"\u{1}".repeat(MAX_NOTE_TEXT), 0).is_err());
+    }
+}
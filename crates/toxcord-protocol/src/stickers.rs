@@ -0,0 +1,188 @@
+//! Sticker packs.
+//!
+//! A pack is a named list of images (PNG, GIF or WebP), identified by a hash
+//! of its name and images, so everyone who has the same pack derives the same
+//! ID. A sticker sent to peers known to have its pack goes as a reference in
+//! the message text:
+//!
+//! `[STICKER:<pack hash>:<index>]`
+//!
+//! Anyone else gets the image as a file, named with `STICKER_FILE_PREFIX` so
+//! it's shown as a sticker rather than a file.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Start of a sticker reference
+pub const STICKER_TAG_PREFIX: &str = "[STICKER:";
+
+/// Start of the filename of stickers sent as files
+pub const STICKER_FILE_PREFIX: &str = "sticker-";
+
+/// Most stickers in a pack
+pub const MAX_PACK_STICKERS: usize = 120;
+
+/// Longest pack name, in bytes
+pub const MAX_PACK_NAME: usize = 64;
+
+/// Largest sticker image, in bytes
+pub const MAX_STICKER_SIZE: u64 = 512 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Sticker {
+    /// BLAKE3 of the image, hex
+    pub hash: String,
+    pub mime_type: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StickerPack {
+    pub name: String,
+    pub stickers: Vec<Sticker>,
+}
+
+impl StickerPack {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("Sticker pack name cannot be empty".to_string());
+        }
+        if self.name.len() > MAX_PACK_NAME {
+            return Err(format!("Sticker pack names are limited to {MAX_PACK_NAME} bytes"));
+        }
+        if self.stickers.is_empty() || self.stickers.len() > MAX_PACK_STICKERS {
+            return Err(format!("Sticker packs hold 1 to {MAX_PACK_STICKERS} stickers"));
+        }
+        for sticker in &self.stickers {
+            if !is_hash(&sticker.hash) || !is_sticker_mime_type(&sticker.mime_type) {
+                return Err("Invalid sticker in pack".to_string());
+            }
+        }
+        Ok(())
+    }
+
+    /// The pack's ID: SHA-256 of its name and stickers, hex
+    pub fn hash(&self) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.name.as_bytes());
+        for sticker in &self.stickers {
+            hasher.update([0]);
+            hasher.update(sticker.hash.to_lowercase().as_bytes());
+            hasher.update(sticker.mime_type.as_bytes());
+        }
+        hasher.finalize().iter().map(|b| format!("{b:02x}")).collect()
+    }
+}
+
+/// A sticker by its pack and position in it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StickerRef {
+    pub pack: String,
+    pub index: u32,
+}
+
+impl StickerRef {
+    pub fn to_text(&self) -> String {
+        format!("{STICKER_TAG_PREFIX}{}:{}]", self.pack, self.index)
+    }
+
+    /// Parse a message that is nothing but a sticker reference
+    pub fn parse(text: &str) -> Option<Self> {
+        let (pack, index) = text.strip_prefix(STICKER_TAG_PREFIX)?.strip_suffix(']')?.split_once(':')?;
+        if !is_hash(pack) || index.len() > 3 {
+            return None;
+        }
+        let index: u32 = index.parse().ok()?;
+        (index < MAX_PACK_STICKERS as u32).then(|| Self {
+            pack: pack.to_lowercase(),
+            index,
+        })
+    }
+}
+
+/// MIME type of an image that can be a sticker, by its filename
+pub fn sticker_mime_type(filename: &str) -> Option<&'static str> {
+    let (_, ext) = filename.rsplit_once('.')?;
+    match ext.to_ascii_lowercase().as_str() {
+        "png" | "apng" => Some("image/png"),
+        "gif" => Some("image/gif"),
+        "webp" => Some("image/webp"),
+        _ => None,
+    }
+}
+
+fn is_sticker_mime_type(mime_type: &str) -> bool {
+    matches!(mime_type, "image/png" | "image/gif" | "image/webp")
+}
+
+/// Filename to send a sticker as a file with
+pub fn sticker_filename(reference: &StickerRef, mime_type: &str) -> String {
+    let ext = match mime_type {
+        "image/gif" => "gif",
+        "image/webp" => "webp",
+        _ => "png",
+    };
+    format!("{STICKER_FILE_PREFIX}{}-{}.{ext}", &reference.pack[..12], reference.index)
+}
+
+/// Whether a received file is a sticker
+pub fn is_sticker_file(filename: &str) -> bool {
+    filename.to_ascii_lowercase().starts_with(STICKER_FILE_PREFIX) && sticker_mime_type(filename).is_some()
+}
+
+fn is_hash(s: &str) -> bool {
+    s.len() == 64 && s.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pack() -> StickerPack {
+        StickerPack {
+            name: "Cats".to_string(),
+            stickers: vec![
+                Sticker { hash: "ab".repeat(32), mime_type: "image/png".to_string() },
+                Sticker { hash: "cd".repeat(32), mime_type: "image/gif".to_string() },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_pack_hash() {
+        let pack = pack();
+        assert!(pack.validate().is_ok());
+        assert_eq!(pack.hash(), pack.clone().hash());
+
+        let mut reordered = pack.clone();
+        reordered.stickers.reverse();
+        assert_ne!(reordered.hash(), pack.hash());
+        let mut renamed = pack.clone();
+        renamed.name = "Dogs".to_string();
+        assert_ne!(renamed.hash(), pack.hash());
+
+        let mut bad = pack.clone();
+        bad.stickers[0].mime_type = "image/jpeg".to_string();
+        assert!(bad.validate().is_err());
+        bad.stickers.clear();
+        assert!(bad.validate().is_err());
+    }
+
+    #[test]
+    fn test_sticker_ref() {
+        let reference = StickerRef { pack: pack().hash(), index: 1 };
+        let text = reference.to_text();
+        assert_eq!(StickerRef::parse(&text), Some(reference.clone()));
+        assert_eq!(StickerRef::parse(&text.to_uppercase()), Some(reference.clone()));
+
+        assert_eq!(StickerRef::parse(&format!("{text} hi")), None);
+        assert_eq!(StickerRef::parse("[STICKER:nope:1]"), None);
+        assert_eq!(StickerRef::parse(&format!("[STICKER:{}:120]", reference.pack)), None);
+        assert_eq!(StickerRef::parse(&format!("[STICKER:{}:-1]", reference.pack)), None);
+
+        let filename = sticker_filename(&reference, "image/gif");
+        assert!(filename.starts_with(STICKER_FILE_PREFIX) && filename.ends_with(".gif"));
+        assert!(is_sticker_file(&filename));
+        assert!(!is_sticker_file("sticker-notes.txt"));
+        assert!(!is_sticker_file("cat.gif"));
+    }
+}
@@ -15,6 +15,8 @@ fn main() {
         .define("BUILD_FUN_UTILS", "OFF")
         .define("AUTOTEST", "OFF")
         .define("UNITTEST", "OFF")
+        // Installs tox_private.h, for DHT statistics
+        .define("EXPERIMENTAL_API", "ON")
         .build();
 
     // Link the built library
@@ -1,3 +1,4 @@
 #include <tox/tox.h>
 #include <tox/toxav.h>
 #include <tox/toxencryptsave.h>
+#include <tox/tox_private.h>
@@ -70,6 +70,7 @@ impl ToxAvInstance {
         unsafe {
             toxav_iterate(self.toxav);
         }
+        crate::callbacks::resume_panic();
     }
 
     /// Register all ToxAV callbacks.
@@ -418,6 +419,7 @@ impl ToxAvInstance {
         unsafe {
             toxav_audio_iterate(self.toxav);
         }
+        crate::callbacks::resume_panic();
     }
 
     /// Get video iteration interval for multi-threaded mode.
@@ -435,6 +437,7 @@ impl ToxAvInstance {
         unsafe {
             toxav_video_iterate(self.toxav);
         }
+        crate::callbacks::resume_panic();
     }
 }
 
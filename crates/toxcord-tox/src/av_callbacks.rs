@@ -1,6 +1,7 @@
 //! ToxAV callback trampolines and event handler trait.
 
 use crate::av_types::CallStateFlags;
+use crate::callbacks::guard;
 
 /// Trait for handling ToxAV events. Implement this to receive audio/video callbacks.
 pub trait ToxAvEventHandler: Send + 'static {
@@ -96,11 +97,13 @@ pub unsafe extern "C" fn call_cb(
     video_enabled: bool,
     user_data: *mut std::ffi::c_void,
 ) {
-    if user_data.is_null() {
-        return;
-    }
-    let handler = extract_av_handler!(user_data);
-    handler.on_call(friend_number, audio_enabled, video_enabled);
+    guard(|| {
+        if user_data.is_null() {
+            return;
+        }
+        let handler = extract_av_handler!(user_data);
+        handler.on_call(friend_number, audio_enabled, video_enabled);
+    });
 }
 
 /// Callback for call state changes
@@ -110,12 +113,14 @@ pub unsafe extern "C" fn call_state_cb(
     state: u32,
     user_data: *mut std::ffi::c_void,
 ) {
-    if user_data.is_null() {
-        return;
-    }
-    let handler = extract_av_handler!(user_data);
-    let flags = CallStateFlags::from_raw(state);
-    handler.on_call_state(friend_number, flags);
+    guard(|| {
+        if user_data.is_null() {
+            return;
+        }
+        let handler = extract_av_handler!(user_data);
+        let flags = CallStateFlags::from_raw(state);
+        handler.on_call_state(friend_number, flags);
+    });
 }
 
 /// Callback for receiving audio frames
@@ -128,12 +133,14 @@ pub unsafe extern "C" fn audio_receive_frame_cb(
     sampling_rate: u32,
     user_data: *mut std::ffi::c_void,
 ) {
-    if user_data.is_null() || pcm.is_null() {
-        return;
-    }
-    let handler = extract_av_handler!(user_data);
-    let pcm_slice = std::slice::from_raw_parts(pcm, sample_count * channels as usize);
-    handler.on_audio_receive_frame(friend_number, pcm_slice, sample_count, channels, sampling_rate);
+    guard(|| {
+        if user_data.is_null() || pcm.is_null() {
+            return;
+        }
+        let handler = extract_av_handler!(user_data);
+        let pcm_slice = std::slice::from_raw_parts(pcm, sample_count * channels as usize);
+        handler.on_audio_receive_frame(friend_number, pcm_slice, sample_count, channels, sampling_rate);
+    });
 }
 
 /// Callback for receiving video frames
@@ -150,37 +157,39 @@ pub unsafe extern "C" fn video_receive_frame_cb(
     v_stride: i32,
     user_data: *mut std::ffi::c_void,
 ) {
-    if user_data.is_null() || y.is_null() || u.is_null() || v.is_null() {
-        return;
-    }
-    let handler = extract_av_handler!(user_data);
-
-    // Calculate plane sizes based on stride
-    let y_stride_abs = y_stride.unsigned_abs() as usize;
-    let u_stride_abs = u_stride.unsigned_abs() as usize;
-    let v_stride_abs = v_stride.unsigned_abs() as usize;
-    let h = height as usize;
-    let uv_h = h / 2;
-
-    let y_size = y_stride_abs * h;
-    let u_size = u_stride_abs * uv_h;
-    let v_size = v_stride_abs * uv_h;
-
-    let y_slice = std::slice::from_raw_parts(y, y_size);
-    let u_slice = std::slice::from_raw_parts(u, u_size);
-    let v_slice = std::slice::from_raw_parts(v, v_size);
-
-    handler.on_video_receive_frame(
-        friend_number,
-        width,
-        height,
-        y_slice,
-        u_slice,
-        v_slice,
-        y_stride,
-        u_stride,
-        v_stride,
-    );
+    guard(|| {
+        if user_data.is_null() || y.is_null() || u.is_null() || v.is_null() {
+            return;
+        }
+        let handler = extract_av_handler!(user_data);
+
+        // Calculate plane sizes based on stride
+        let y_stride_abs = y_stride.unsigned_abs() as usize;
+        let u_stride_abs = u_stride.unsigned_abs() as usize;
+        let v_stride_abs = v_stride.unsigned_abs() as usize;
+        let h = height as usize;
+        let uv_h = h / 2;
+
+        let y_size = y_stride_abs * h;
+        let u_size = u_stride_abs * uv_h;
+        let v_size = v_stride_abs * uv_h;
+
+        let y_slice = std::slice::from_raw_parts(y, y_size);
+        let u_slice = std::slice::from_raw_parts(u, u_size);
+        let v_slice = std::slice::from_raw_parts(v, v_size);
+
+        handler.on_video_receive_frame(
+            friend_number,
+            width,
+            height,
+            y_slice,
+            u_slice,
+            v_slice,
+            y_stride,
+            u_stride,
+            v_stride,
+        );
+    });
 }
 
 /// Callback for audio bit rate changes
@@ -190,11 +199,13 @@ pub unsafe extern "C" fn audio_bit_rate_cb(
     audio_bit_rate: u32,
     user_data: *mut std::ffi::c_void,
 ) {
-    if user_data.is_null() {
-        return;
-    }
-    let handler = extract_av_handler!(user_data);
-    handler.on_audio_bit_rate(friend_number, audio_bit_rate);
+    guard(|| {
+        if user_data.is_null() {
+            return;
+        }
+        let handler = extract_av_handler!(user_data);
+        handler.on_audio_bit_rate(friend_number, audio_bit_rate);
+    });
 }
 
 /// Callback for video bit rate changes
@@ -204,9 +215,11 @@ pub unsafe extern "C" fn video_bit_rate_cb(
     video_bit_rate: u32,
     user_data: *mut std::ffi::c_void,
 ) {
-    if user_data.is_null() {
-        return;
-    }
-    let handler = extract_av_handler!(user_data);
-    handler.on_video_bit_rate(friend_number, video_bit_rate);
+    guard(|| {
+        if user_data.is_null() {
+            return;
+        }
+        let handler = extract_av_handler!(user_data);
+        handler.on_video_bit_rate(friend_number, video_bit_rate);
+    });
 }
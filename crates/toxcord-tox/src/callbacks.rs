@@ -1,4 +1,8 @@
-use crate::types::{ConnectionStatus, MessageType, UserStatus};
+use std::any::Any;
+use std::cell::RefCell;
+use std::panic::{self, AssertUnwindSafe};
+
+use crate::types::{ConnectionStatus, GroupModEvent, MessageType, UserStatus};
 
 /// Trait for handling TOX events. Implement this to receive callbacks.
 pub trait ToxEventHandler: Send + 'static {
@@ -11,6 +15,7 @@ pub trait ToxEventHandler: Send + 'static {
     fn on_friend_connection_status(&self, friend_number: u32, status: ConnectionStatus);
     fn on_friend_typing(&self, friend_number: u32, is_typing: bool);
     fn on_friend_read_receipt(&self, friend_number: u32, message_id: u32);
+    fn on_friend_lossless_packet(&self, friend_number: u32, data: &[u8]);
     fn on_file_recv_control(&self, friend_number: u32, file_number: u32, control: u32);
     fn on_file_chunk_request(&self, friend_number: u32, file_number: u32, position: u64, length: usize);
     fn on_file_recv(&self, friend_number: u32, file_number: u32, kind: u32, file_size: u64, filename: &str);
@@ -26,6 +31,7 @@ pub trait ToxEventHandler: Send + 'static {
     fn on_group_join_fail(&self, group_number: u32, fail_type: u32);
     fn on_group_topic(&self, group_number: u32, peer_id: u32, topic: &str);
     fn on_group_peer_status(&self, group_number: u32, peer_id: u32, status: UserStatus);
+    fn on_group_moderation(&self, group_number: u32, source_peer_id: u32, target_peer_id: u32, event: GroupModEvent);
 }
 
 /// Convert raw C connection status to our enum
@@ -59,6 +65,31 @@ pub fn message_type_from_raw(raw: u32) -> MessageType {
 /// The user_data pointer passed to all callbacks is a raw pointer to a
 /// `Box<dyn ToxEventHandler>`. These trampolines extract it and dispatch.
 
+// Unwinding out of an `extern "C"` function aborts the process, so each
+// trampoline runs its body through `guard`: a panic is caught and kept here
+// until the iterate call that ran the callback is back in Rust, which
+// resumes it with `resume_panic`.
+thread_local! {
+    static PANIC: RefCell<Option<Box<dyn Any + Send>>> = const { RefCell::new(None) };
+}
+
+/// Run a trampoline's body, keeping a panic for `resume_panic`. Only the
+/// first panic of an iteration is kept.
+pub(crate) fn guard(body: impl FnOnce()) {
+    if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(body)) {
+        PANIC.with(|pending| {
+            pending.borrow_mut().get_or_insert(payload);
+        });
+    }
+}
+
+/// Resume a panic caught in a callback. Called after every iterate.
+pub(crate) fn resume_panic() {
+    if let Some(payload) = PANIC.with(|pending| pending.borrow_mut().take()) {
+        panic::resume_unwind(payload);
+    }
+}
+
 macro_rules! extract_handler {
     ($user_data:expr) => {{
         let handler = &*($user_data as *const Box<dyn ToxEventHandler>);
@@ -71,8 +102,10 @@ pub unsafe extern "C" fn self_connection_status_cb(
     connection_status: toxcord_tox_sys::Tox_Connection,
     user_data: *mut std::ffi::c_void,
 ) {
-    let handler = extract_handler!(user_data);
-    handler.on_self_connection_status(connection_status_from_raw(connection_status as u32));
+    guard(|| {
+        let handler = extract_handler!(user_data);
+        handler.on_self_connection_status(connection_status_from_raw(connection_status as u32));
+    });
 }
 
 pub unsafe extern "C" fn friend_request_cb(
@@ -82,10 +115,12 @@ pub unsafe extern "C" fn friend_request_cb(
     length: usize,
     user_data: *mut std::ffi::c_void,
 ) {
-    let handler = extract_handler!(user_data);
-    let pk = &*(public_key as *const [u8; 32]);
-    let msg = std::str::from_utf8(std::slice::from_raw_parts(message, length)).unwrap_or("");
-    handler.on_friend_request(pk, msg);
+    guard(|| {
+        let handler = extract_handler!(user_data);
+        let pk = &*(public_key as *const [u8; 32]);
+        let msg = std::str::from_utf8(std::slice::from_raw_parts(message, length)).unwrap_or("");
+        handler.on_friend_request(pk, msg);
+    });
 }
 
 pub unsafe extern "C" fn friend_message_cb(
@@ -96,9 +131,11 @@ pub unsafe extern "C" fn friend_message_cb(
     length: usize,
     user_data: *mut std::ffi::c_void,
 ) {
-    let handler = extract_handler!(user_data);
-    let msg = std::str::from_utf8(std::slice::from_raw_parts(message, length)).unwrap_or("");
-    handler.on_friend_message(friend_number, message_type_from_raw(message_type as u32), msg);
+    guard(|| {
+        let handler = extract_handler!(user_data);
+        let msg = std::str::from_utf8(std::slice::from_raw_parts(message, length)).unwrap_or("");
+        handler.on_friend_message(friend_number, message_type_from_raw(message_type as u32), msg);
+    });
 }
 
 pub unsafe extern "C" fn friend_name_cb(
@@ -108,9 +145,11 @@ pub unsafe extern "C" fn friend_name_cb(
     length: usize,
     user_data: *mut std::ffi::c_void,
 ) {
-    let handler = extract_handler!(user_data);
-    let n = std::str::from_utf8(std::slice::from_raw_parts(name, length)).unwrap_or("");
-    handler.on_friend_name(friend_number, n);
+    guard(|| {
+        let handler = extract_handler!(user_data);
+        let n = std::str::from_utf8(std::slice::from_raw_parts(name, length)).unwrap_or("");
+        handler.on_friend_name(friend_number, n);
+    });
 }
 
 pub unsafe extern "C" fn friend_status_message_cb(
@@ -120,9 +159,11 @@ pub unsafe extern "C" fn friend_status_message_cb(
     length: usize,
     user_data: *mut std::ffi::c_void,
 ) {
-    let handler = extract_handler!(user_data);
-    let msg = std::str::from_utf8(std::slice::from_raw_parts(message, length)).unwrap_or("");
-    handler.on_friend_status_message(friend_number, msg);
+    guard(|| {
+        let handler = extract_handler!(user_data);
+        let msg = std::str::from_utf8(std::slice::from_raw_parts(message, length)).unwrap_or("");
+        handler.on_friend_status_message(friend_number, msg);
+    });
 }
 
 pub unsafe extern "C" fn friend_status_cb(
@@ -131,8 +172,10 @@ pub unsafe extern "C" fn friend_status_cb(
     status: toxcord_tox_sys::Tox_User_Status,
     user_data: *mut std::ffi::c_void,
 ) {
-    let handler = extract_handler!(user_data);
-    handler.on_friend_status(friend_number, user_status_from_raw(status as u32));
+    guard(|| {
+        let handler = extract_handler!(user_data);
+        handler.on_friend_status(friend_number, user_status_from_raw(status as u32));
+    });
 }
 
 pub unsafe extern "C" fn friend_connection_status_cb(
@@ -141,11 +184,13 @@ pub unsafe extern "C" fn friend_connection_status_cb(
     connection_status: toxcord_tox_sys::Tox_Connection,
     user_data: *mut std::ffi::c_void,
 ) {
-    let handler = extract_handler!(user_data);
-    handler.on_friend_connection_status(
-        friend_number,
-        connection_status_from_raw(connection_status as u32),
-    );
+    guard(|| {
+        let handler = extract_handler!(user_data);
+        handler.on_friend_connection_status(
+            friend_number,
+            connection_status_from_raw(connection_status as u32),
+        );
+    });
 }
 
 pub unsafe extern "C" fn friend_typing_cb(
@@ -154,8 +199,10 @@ pub unsafe extern "C" fn friend_typing_cb(
     is_typing: bool,
     user_data: *mut std::ffi::c_void,
 ) {
-    let handler = extract_handler!(user_data);
-    handler.on_friend_typing(friend_number, is_typing);
+    guard(|| {
+        let handler = extract_handler!(user_data);
+        handler.on_friend_typing(friend_number, is_typing);
+    });
 }
 
 pub unsafe extern "C" fn friend_read_receipt_cb(
@@ -164,8 +211,24 @@ pub unsafe extern "C" fn friend_read_receipt_cb(
     message_id: u32,
     user_data: *mut std::ffi::c_void,
 ) {
-    let handler = extract_handler!(user_data);
-    handler.on_friend_read_receipt(friend_number, message_id);
+    guard(|| {
+        let handler = extract_handler!(user_data);
+        handler.on_friend_read_receipt(friend_number, message_id);
+    });
+}
+
+pub unsafe extern "C" fn friend_lossless_packet_cb(
+    _tox: *mut toxcord_tox_sys::Tox,
+    friend_number: u32,
+    data: *const u8,
+    length: usize,
+    user_data: *mut std::ffi::c_void,
+) {
+    guard(|| {
+        let handler = extract_handler!(user_data);
+        let d = std::slice::from_raw_parts(data, length);
+        handler.on_friend_lossless_packet(friend_number, d);
+    });
 }
 
 pub unsafe extern "C" fn file_recv_control_cb(
@@ -175,8 +238,10 @@ pub unsafe extern "C" fn file_recv_control_cb(
     control: toxcord_tox_sys::Tox_File_Control,
     user_data: *mut std::ffi::c_void,
 ) {
-    let handler = extract_handler!(user_data);
-    handler.on_file_recv_control(friend_number, file_number, control as u32);
+    guard(|| {
+        let handler = extract_handler!(user_data);
+        handler.on_file_recv_control(friend_number, file_number, control as u32);
+    });
 }
 
 pub unsafe extern "C" fn file_chunk_request_cb(
@@ -187,8 +252,10 @@ pub unsafe extern "C" fn file_chunk_request_cb(
     length: usize,
     user_data: *mut std::ffi::c_void,
 ) {
-    let handler = extract_handler!(user_data);
-    handler.on_file_chunk_request(friend_number, file_number, position, length);
+    guard(|| {
+        let handler = extract_handler!(user_data);
+        handler.on_file_chunk_request(friend_number, file_number, position, length);
+    });
 }
 
 pub unsafe extern "C" fn file_recv_cb(
@@ -201,10 +268,12 @@ pub unsafe extern "C" fn file_recv_cb(
     filename_length: usize,
     user_data: *mut std::ffi::c_void,
 ) {
-    let handler = extract_handler!(user_data);
-    let name =
-        std::str::from_utf8(std::slice::from_raw_parts(filename, filename_length)).unwrap_or("");
-    handler.on_file_recv(friend_number, file_number, kind, file_size, name);
+    guard(|| {
+        let handler = extract_handler!(user_data);
+        let name =
+            std::str::from_utf8(std::slice::from_raw_parts(filename, filename_length)).unwrap_or("");
+        handler.on_file_recv(friend_number, file_number, kind, file_size, name);
+    });
 }
 
 pub unsafe extern "C" fn file_recv_chunk_cb(
@@ -216,13 +285,15 @@ pub unsafe extern "C" fn file_recv_chunk_cb(
     length: usize,
     user_data: *mut std::ffi::c_void,
 ) {
-    let handler = extract_handler!(user_data);
-    let d = if length > 0 {
-        std::slice::from_raw_parts(data, length)
-    } else {
-        &[]
-    };
-    handler.on_file_recv_chunk(friend_number, file_number, position, d);
+    guard(|| {
+        let handler = extract_handler!(user_data);
+        let d = if length > 0 {
+            std::slice::from_raw_parts(data, length)
+        } else {
+            &[]
+        };
+        handler.on_file_recv_chunk(friend_number, file_number, position, d);
+    });
 }
 
 pub unsafe extern "C" fn group_invite_cb(
@@ -234,12 +305,14 @@ pub unsafe extern "C" fn group_invite_cb(
     group_name_length: usize,
     user_data: *mut std::ffi::c_void,
 ) {
-    let handler = extract_handler!(user_data);
-    let data = std::slice::from_raw_parts(invite_data, length);
-    let name =
-        std::str::from_utf8(std::slice::from_raw_parts(group_name, group_name_length))
-            .unwrap_or("");
-    handler.on_group_invite(friend_number, data, name);
+    guard(|| {
+        let handler = extract_handler!(user_data);
+        let data = std::slice::from_raw_parts(invite_data, length);
+        let name =
+            std::str::from_utf8(std::slice::from_raw_parts(group_name, group_name_length))
+                .unwrap_or("");
+        handler.on_group_invite(friend_number, data, name);
+    });
 }
 
 pub unsafe extern "C" fn group_peer_join_cb(
@@ -248,8 +321,10 @@ pub unsafe extern "C" fn group_peer_join_cb(
     peer_id: u32,
     user_data: *mut std::ffi::c_void,
 ) {
-    let handler = extract_handler!(user_data);
-    handler.on_group_peer_join(group_number, peer_id);
+    guard(|| {
+        let handler = extract_handler!(user_data);
+        handler.on_group_peer_join(group_number, peer_id);
+    });
 }
 
 pub unsafe extern "C" fn group_peer_exit_cb(
@@ -263,11 +338,13 @@ pub unsafe extern "C" fn group_peer_exit_cb(
     message_length: usize,
     user_data: *mut std::ffi::c_void,
 ) {
-    let handler = extract_handler!(user_data);
-    let n = std::str::from_utf8(std::slice::from_raw_parts(name, name_length)).unwrap_or("");
-    let msg =
-        std::str::from_utf8(std::slice::from_raw_parts(message, message_length)).unwrap_or("");
-    handler.on_group_peer_exit(group_number, peer_id, exit_type as u32, n, msg);
+    guard(|| {
+        let handler = extract_handler!(user_data);
+        let n = std::str::from_utf8(std::slice::from_raw_parts(name, name_length)).unwrap_or("");
+        let msg =
+            std::str::from_utf8(std::slice::from_raw_parts(message, message_length)).unwrap_or("");
+        handler.on_group_peer_exit(group_number, peer_id, exit_type as u32, n, msg);
+    });
 }
 
 pub unsafe extern "C" fn group_peer_name_cb(
@@ -278,9 +355,11 @@ pub unsafe extern "C" fn group_peer_name_cb(
     length: usize,
     user_data: *mut std::ffi::c_void,
 ) {
-    let handler = extract_handler!(user_data);
-    let n = std::str::from_utf8(std::slice::from_raw_parts(name, length)).unwrap_or("");
-    handler.on_group_peer_name(group_number, peer_id, n);
+    guard(|| {
+        let handler = extract_handler!(user_data);
+        let n = std::str::from_utf8(std::slice::from_raw_parts(name, length)).unwrap_or("");
+        handler.on_group_peer_name(group_number, peer_id, n);
+    });
 }
 
 pub unsafe extern "C" fn group_message_cb(
@@ -293,15 +372,17 @@ pub unsafe extern "C" fn group_message_cb(
     message_id: u32,
     user_data: *mut std::ffi::c_void,
 ) {
-    let handler = extract_handler!(user_data);
-    let msg = std::str::from_utf8(std::slice::from_raw_parts(message, length)).unwrap_or("");
-    handler.on_group_message(
-        group_number,
-        peer_id,
-        message_type_from_raw(message_type as u32),
-        msg,
-        message_id,
-    );
+    guard(|| {
+        let handler = extract_handler!(user_data);
+        let msg = std::str::from_utf8(std::slice::from_raw_parts(message, length)).unwrap_or("");
+        handler.on_group_message(
+            group_number,
+            peer_id,
+            message_type_from_raw(message_type as u32),
+            msg,
+            message_id,
+        );
+    });
 }
 
 pub unsafe extern "C" fn group_custom_packet_cb(
@@ -312,9 +393,11 @@ pub unsafe extern "C" fn group_custom_packet_cb(
     length: usize,
     user_data: *mut std::ffi::c_void,
 ) {
-    let handler = extract_handler!(user_data);
-    let d = std::slice::from_raw_parts(data, length);
-    handler.on_group_custom_packet(group_number, peer_id, d);
+    guard(|| {
+        let handler = extract_handler!(user_data);
+        let d = std::slice::from_raw_parts(data, length);
+        handler.on_group_custom_packet(group_number, peer_id, d);
+    });
 }
 
 pub unsafe extern "C" fn group_custom_private_packet_cb(
@@ -325,9 +408,11 @@ pub unsafe extern "C" fn group_custom_private_packet_cb(
     length: usize,
     user_data: *mut std::ffi::c_void,
 ) {
-    let handler = extract_handler!(user_data);
-    let d = std::slice::from_raw_parts(data, length);
-    handler.on_group_custom_private_packet(group_number, peer_id, d);
+    guard(|| {
+        let handler = extract_handler!(user_data);
+        let d = std::slice::from_raw_parts(data, length);
+        handler.on_group_custom_private_packet(group_number, peer_id, d);
+    });
 }
 
 pub unsafe extern "C" fn group_self_join_cb(
@@ -335,8 +420,10 @@ pub unsafe extern "C" fn group_self_join_cb(
     group_number: u32,
     user_data: *mut std::ffi::c_void,
 ) {
-    let handler = extract_handler!(user_data);
-    handler.on_group_self_join(group_number);
+    guard(|| {
+        let handler = extract_handler!(user_data);
+        handler.on_group_self_join(group_number);
+    });
 }
 
 pub unsafe extern "C" fn group_join_fail_cb(
@@ -345,8 +432,10 @@ pub unsafe extern "C" fn group_join_fail_cb(
     fail_type: toxcord_tox_sys::Tox_Group_Join_Fail,
     user_data: *mut std::ffi::c_void,
 ) {
-    let handler = extract_handler!(user_data);
-    handler.on_group_join_fail(group_number, fail_type as u32);
+    guard(|| {
+        let handler = extract_handler!(user_data);
+        handler.on_group_join_fail(group_number, fail_type as u32);
+    });
 }
 
 pub unsafe extern "C" fn group_topic_cb(
@@ -357,9 +446,11 @@ pub unsafe extern "C" fn group_topic_cb(
     length: usize,
     user_data: *mut std::ffi::c_void,
 ) {
-    let handler = extract_handler!(user_data);
-    let t = std::str::from_utf8(std::slice::from_raw_parts(topic, length)).unwrap_or("");
-    handler.on_group_topic(group_number, peer_id, t);
+    guard(|| {
+        let handler = extract_handler!(user_data);
+        let t = std::str::from_utf8(std::slice::from_raw_parts(topic, length)).unwrap_or("");
+        handler.on_group_topic(group_number, peer_id, t);
+    });
 }
 
 pub unsafe extern "C" fn group_peer_status_cb(
@@ -369,6 +460,22 @@ pub unsafe extern "C" fn group_peer_status_cb(
     status: toxcord_tox_sys::Tox_User_Status,
     user_data: *mut std::ffi::c_void,
 ) {
-    let handler = extract_handler!(user_data);
-    handler.on_group_peer_status(group_number, peer_id, user_status_from_raw(status as u32));
+    guard(|| {
+        let handler = extract_handler!(user_data);
+        handler.on_group_peer_status(group_number, peer_id, user_status_from_raw(status as u32));
+    });
+}
+
+pub unsafe extern "C" fn group_moderation_cb(
+    _tox: *mut toxcord_tox_sys::Tox,
+    group_number: u32,
+    source_peer_id: u32,
+    target_peer_id: u32,
+    mod_type: toxcord_tox_sys::Tox_Group_Mod_Event,
+    user_data: *mut std::ffi::c_void,
+) {
+    guard(|| {
+        let handler = extract_handler!(user_data);
+        handler.on_group_moderation(group_number, source_peer_id, target_peer_id, GroupModEvent::from_raw(mod_type as u32));
+    });
 }
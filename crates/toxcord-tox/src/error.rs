@@ -17,6 +17,12 @@ pub enum ToxError {
     #[error("Failed to send message: {0}")]
     SendMessage(String),
 
+    #[error("Friend is not connected")]
+    FriendNotConnected,
+
+    #[error("Message is too long")]
+    MessageTooLong,
+
     #[error("Failed to set name: {0}")]
     SetName(String),
 
@@ -38,6 +44,9 @@ pub enum ToxError {
     #[error("Invalid data: {0}")]
     InvalidData(String),
 
+    #[error("File transfer error: {0}")]
+    File(String),
+
     #[error("ToxAV error: {0}")]
     ToxAv(String),
 
@@ -0,0 +1,131 @@
+use toxcord_tox_sys::*;
+
+use crate::error::{ToxError, ToxResult};
+use crate::tox::ToxInstance;
+use crate::types::*;
+
+impl ToxInstance {
+    // ─── File Transfers ────────────────────────────────────────────────
+
+    /// Offer a file to a friend. Returns the file number for this transfer.
+    ///
+    /// `file_id` identifies the file across sessions; passing the same ID
+    /// again lets the receiver resume a partial transfer. `None` lets
+    /// toxcore pick a random one.
+    pub fn file_send(
+        &self,
+        friend_number: u32,
+        kind: FileKind,
+        file_size: u64,
+        file_id: Option<&[u8; FILE_ID_LENGTH]>,
+        filename: &str,
+    ) -> ToxResult<u32> {
+        unsafe {
+            let mut err = Tox_Err_File_Send::default();
+            let id_ptr = file_id.map(|id| id.as_ptr()).unwrap_or(std::ptr::null());
+            let file_number = tox_file_send(
+                self.raw(),
+                friend_number,
+                kind.to_raw(),
+                file_size,
+                id_ptr,
+                filename.as_ptr(),
+                filename.len(),
+                &mut err,
+            );
+            if file_number == u32::MAX {
+                Err(ToxError::File(format!("file_send failed: {err:?}")))
+            } else {
+                Ok(file_number)
+            }
+        }
+    }
+
+    /// Send a chunk of file data in response to a chunk request.
+    pub fn file_send_chunk(
+        &self,
+        friend_number: u32,
+        file_number: u32,
+        position: u64,
+        data: &[u8],
+    ) -> ToxResult<()> {
+        unsafe {
+            let mut err = Tox_Err_File_Send_Chunk::default();
+            let ok = tox_file_send_chunk(
+                self.raw(),
+                friend_number,
+                file_number,
+                position,
+                data.as_ptr(),
+                data.len(),
+                &mut err,
+            );
+            if ok {
+                Ok(())
+            } else {
+                Err(ToxError::File(format!("file_send_chunk failed: {err:?}")))
+            }
+        }
+    }
+
+    /// Accept (resume), pause or cancel a file transfer.
+    pub fn file_control(
+        &self,
+        friend_number: u32,
+        file_number: u32,
+        control: FileControl,
+    ) -> ToxResult<()> {
+        let raw = match control {
+            FileControl::Resume => Tox_File_Control_TOX_FILE_CONTROL_RESUME,
+            FileControl::Pause => Tox_File_Control_TOX_FILE_CONTROL_PAUSE,
+            FileControl::Cancel => Tox_File_Control_TOX_FILE_CONTROL_CANCEL,
+        };
+        unsafe {
+            let mut err = Tox_Err_File_Control::default();
+            let ok = tox_file_control(self.raw(), friend_number, file_number, raw, &mut err);
+            if ok {
+                Ok(())
+            } else {
+                Err(ToxError::File(format!("file_control failed: {err:?}")))
+            }
+        }
+    }
+
+    /// Ask the sender to continue an incoming transfer from `position`.
+    /// Must be called before accepting the transfer.
+    pub fn file_seek(&self, friend_number: u32, file_number: u32, position: u64) -> ToxResult<()> {
+        unsafe {
+            let mut err = Tox_Err_File_Seek::default();
+            let ok = tox_file_seek(self.raw(), friend_number, file_number, position, &mut err);
+            if ok {
+                Ok(())
+            } else {
+                Err(ToxError::File(format!("file_seek failed: {err:?}")))
+            }
+        }
+    }
+
+    /// Get the file ID of a transfer.
+    pub fn file_get_file_id(
+        &self,
+        friend_number: u32,
+        file_number: u32,
+    ) -> ToxResult<[u8; FILE_ID_LENGTH]> {
+        let mut file_id = [0u8; FILE_ID_LENGTH];
+        unsafe {
+            let mut err = Tox_Err_File_Get::default();
+            let ok = tox_file_get_file_id(
+                self.raw(),
+                friend_number,
+                file_number,
+                file_id.as_mut_ptr(),
+                &mut err,
+            );
+            if ok {
+                Ok(file_id)
+            } else {
+                Err(ToxError::File(format!("file_get_file_id failed: {err:?}")))
+            }
+        }
+    }
+}
@@ -146,12 +146,12 @@ impl ToxInstance {
             );
             if err == Tox_Err_Group_Send_Message_TOX_ERR_GROUP_SEND_MESSAGE_OK {
                 Ok(message_id)
+            } else if err == Tox_Err_Group_Send_Message_TOX_ERR_GROUP_SEND_MESSAGE_TOO_LONG {
+                Err(ToxError::MessageTooLong)
             } else {
                 let detail = match err {
                     Tox_Err_Group_Send_Message_TOX_ERR_GROUP_SEND_MESSAGE_GROUP_NOT_FOUND =>
                         format!("group {group_number} not found"),
-                    Tox_Err_Group_Send_Message_TOX_ERR_GROUP_SEND_MESSAGE_TOO_LONG =>
-                        "message too long".to_string(),
                     Tox_Err_Group_Send_Message_TOX_ERR_GROUP_SEND_MESSAGE_EMPTY =>
                         "message is empty".to_string(),
                     Tox_Err_Group_Send_Message_TOX_ERR_GROUP_SEND_MESSAGE_PERMISSIONS =>
@@ -384,6 +384,66 @@ impl ToxInstance {
         }
     }
 
+    /// Set the password needed to join a group, or remove it with an empty
+    /// string. Founder only.
+    pub fn group_founder_set_password(&self, group_number: u32, password: &str) -> ToxResult<()> {
+        if password.len() > GROUP_MAX_PASSWORD_LENGTH {
+            return Err(ToxError::Group(format!(
+                "password is longer than {GROUP_MAX_PASSWORD_LENGTH} bytes"
+            )));
+        }
+        unsafe {
+            let mut err = Tox_Err_Group_Founder_Set_Password::default();
+            let pwd_ptr = if password.is_empty() {
+                std::ptr::null()
+            } else {
+                password.as_ptr()
+            };
+            let ok = tox_group_founder_set_password(
+                self.raw(),
+                group_number,
+                pwd_ptr,
+                password.len(),
+                &mut err,
+            );
+            if ok {
+                Ok(())
+            } else {
+                let detail = match err {
+                    Tox_Err_Group_Founder_Set_Password_TOX_ERR_GROUP_FOUNDER_SET_PASSWORD_GROUP_NOT_FOUND =>
+                        format!("group {group_number} not found"),
+                    Tox_Err_Group_Founder_Set_Password_TOX_ERR_GROUP_FOUNDER_SET_PASSWORD_PERMISSIONS =>
+                        "only the founder can set the password".to_string(),
+                    Tox_Err_Group_Founder_Set_Password_TOX_ERR_GROUP_FOUNDER_SET_PASSWORD_FAIL_SEND =>
+                        "failed to send the new password to the group".to_string(),
+                    Tox_Err_Group_Founder_Set_Password_TOX_ERR_GROUP_FOUNDER_SET_PASSWORD_DISCONNECTED =>
+                        format!("group {group_number} is disconnected"),
+                    _ => format!("unknown error {err}"),
+                };
+                Err(ToxError::Group(format!(
+                    "group_founder_set_password failed: {detail}"
+                )))
+            }
+        }
+    }
+
+    /// Get the password of a group; empty if it has none.
+    pub fn group_get_password(&self, group_number: u32) -> ToxResult<String> {
+        unsafe {
+            let mut err = Tox_Err_Group_State_Query::default();
+            let size = tox_group_get_password_size(self.raw(), group_number, &mut err);
+            if err != Tox_Err_Group_State_Query_TOX_ERR_GROUP_STATE_QUERY_OK {
+                return Err(ToxError::Group(format!("group_get_password_size failed: {err:?}")));
+            }
+            if size == 0 {
+                return Ok(String::new());
+            }
+            let mut password = vec![0u8; size];
+            tox_group_get_password(self.raw(), group_number, password.as_mut_ptr(), &mut err);
+            Ok(String::from_utf8_lossy(&password).to_string())
+        }
+    }
+
     // ─── Peer Queries ──────────────────────────────────────────────────
 
     /// Get a peer's name.
@@ -606,6 +666,9 @@ impl ToxInstance {
         };
 
         let peer_count = self.group_peer_count(group_number).unwrap_or(0);
+        let password_protected = self
+            .group_get_password(group_number)
+            .is_ok_and(|password| !password.is_empty());
 
         Ok(GroupInfo {
             number: group_number,
@@ -614,6 +677,7 @@ impl ToxInstance {
             topic,
             privacy_state,
             peer_count,
+            password_protected,
         })
     }
 
@@ -3,7 +3,10 @@ pub mod av_callbacks;
 pub mod av_types;
 pub mod callbacks;
 pub mod error;
+pub mod files;
 pub mod groups;
+#[cfg(feature = "test-harness")]
+pub mod testing;
 pub mod tox;
 pub mod types;
 
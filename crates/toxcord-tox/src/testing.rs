@@ -0,0 +1,449 @@
+//! In-process Tox network for integration tests (feature `test-harness`).
+//!
+//! Each [`TestNode`] is a real `ToxInstance` on the loopback interface with a
+//! handler that records every callback. Nodes bootstrap off each other rather
+//! than the public network, so the tests in `tests/` need nothing but
+//! localhost and can run in CI.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::callbacks::ToxEventHandler;
+use crate::error::{ToxError, ToxResult};
+use crate::tox::{ToxInstance, ToxOptionsBuilder};
+use crate::types::*;
+
+/// How long to wait for the network to do something before giving up.
+/// Generous: CI machines are slow and the DHT takes a few seconds to settle.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A callback as recorded by [`TestNode`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    SelfConnection(ConnectionStatus),
+    FriendRequest {
+        public_key: [u8; 32],
+        message: String,
+    },
+    FriendMessage {
+        friend_number: u32,
+        message_type: MessageType,
+        message: String,
+    },
+    FriendName {
+        friend_number: u32,
+        name: String,
+    },
+    FriendConnection {
+        friend_number: u32,
+        status: ConnectionStatus,
+    },
+    FriendTyping {
+        friend_number: u32,
+        is_typing: bool,
+    },
+    ReadReceipt {
+        friend_number: u32,
+        message_id: u32,
+    },
+    LosslessPacket {
+        friend_number: u32,
+        data: Vec<u8>,
+    },
+    FileRecv {
+        friend_number: u32,
+        file_number: u32,
+        kind: FileKind,
+        file_size: u64,
+        filename: String,
+    },
+    FileRecvControl {
+        friend_number: u32,
+        file_number: u32,
+        control: FileControl,
+    },
+    FileChunkRequest {
+        friend_number: u32,
+        file_number: u32,
+        position: u64,
+        length: usize,
+    },
+    FileRecvChunk {
+        friend_number: u32,
+        file_number: u32,
+        position: u64,
+        data: Vec<u8>,
+    },
+    GroupInvite {
+        friend_number: u32,
+        invite_data: Vec<u8>,
+        group_name: String,
+    },
+    GroupSelfJoin {
+        group_number: u32,
+    },
+    GroupJoinFail {
+        group_number: u32,
+        fail_type: u32,
+    },
+    GroupPeerJoin {
+        group_number: u32,
+        peer_id: u32,
+    },
+    GroupPeerExit {
+        group_number: u32,
+        peer_id: u32,
+    },
+    GroupMessage {
+        group_number: u32,
+        peer_id: u32,
+        message_type: MessageType,
+        message: String,
+    },
+    GroupCustomPacket {
+        group_number: u32,
+        peer_id: u32,
+        data: Vec<u8>,
+    },
+    GroupTopic {
+        group_number: u32,
+        peer_id: u32,
+        topic: String,
+    },
+    /// Callbacks the tests don't look into, by name
+    Other(&'static str),
+}
+
+/// Event handler that appends every callback to a shared list
+struct RecordingHandler {
+    events: Arc<Mutex<Vec<Event>>>,
+    /// A friend message to panic on instead of recording
+    panic_on: Arc<Mutex<Option<String>>>,
+}
+
+impl RecordingHandler {
+    fn push(&self, event: Event) {
+        self.events.lock().unwrap().push(event);
+    }
+}
+
+impl ToxEventHandler for RecordingHandler {
+    fn on_self_connection_status(&self, status: ConnectionStatus) {
+        self.push(Event::SelfConnection(status));
+    }
+
+    fn on_friend_request(&self, public_key: &[u8; 32], message: &str) {
+        self.push(Event::FriendRequest {
+            public_key: *public_key,
+            message: message.to_string(),
+        });
+    }
+
+    fn on_friend_message(&self, friend_number: u32, message_type: MessageType, message: &str) {
+        let panics = {
+            let mut panic_on = self.panic_on.lock().unwrap();
+            panic_on.as_deref() == Some(message) && panic_on.take().is_some()
+        };
+        if panics {
+            panic!("{message}");
+        }
+        self.push(Event::FriendMessage {
+            friend_number,
+            message_type,
+            message: message.to_string(),
+        });
+    }
+
+    fn on_friend_name(&self, friend_number: u32, name: &str) {
+        self.push(Event::FriendName {
+            friend_number,
+            name: name.to_string(),
+        });
+    }
+
+    fn on_friend_status_message(&self, _friend_number: u32, _message: &str) {
+        self.push(Event::Other("friend_status_message"));
+    }
+
+    fn on_friend_status(&self, _friend_number: u32, _status: UserStatus) {
+        self.push(Event::Other("friend_status"));
+    }
+
+    fn on_friend_connection_status(&self, friend_number: u32, status: ConnectionStatus) {
+        self.push(Event::FriendConnection {
+            friend_number,
+            status,
+        });
+    }
+
+    fn on_friend_typing(&self, friend_number: u32, is_typing: bool) {
+        self.push(Event::FriendTyping {
+            friend_number,
+            is_typing,
+        });
+    }
+
+    fn on_friend_read_receipt(&self, friend_number: u32, message_id: u32) {
+        self.push(Event::ReadReceipt {
+            friend_number,
+            message_id,
+        });
+    }
+
+    fn on_friend_lossless_packet(&self, friend_number: u32, data: &[u8]) {
+        self.push(Event::LosslessPacket {
+            friend_number,
+            data: data.to_vec(),
+        });
+    }
+
+    fn on_file_recv_control(&self, friend_number: u32, file_number: u32, control: u32) {
+        self.push(Event::FileRecvControl {
+            friend_number,
+            file_number,
+            control: FileControl::from_raw(control),
+        });
+    }
+
+    fn on_file_chunk_request(&self, friend_number: u32, file_number: u32, position: u64, length: usize) {
+        self.push(Event::FileChunkRequest {
+            friend_number,
+            file_number,
+            position,
+            length,
+        });
+    }
+
+    fn on_file_recv(&self, friend_number: u32, file_number: u32, kind: u32, file_size: u64, filename: &str) {
+        self.push(Event::FileRecv {
+            friend_number,
+            file_number,
+            kind: FileKind::from_raw(kind),
+            file_size,
+            filename: filename.to_string(),
+        });
+    }
+
+    fn on_file_recv_chunk(&self, friend_number: u32, file_number: u32, position: u64, data: &[u8]) {
+        self.push(Event::FileRecvChunk {
+            friend_number,
+            file_number,
+            position,
+            data: data.to_vec(),
+        });
+    }
+
+    fn on_group_invite(&self, friend_number: u32, invite_data: &[u8], group_name: &str) {
+        self.push(Event::GroupInvite {
+            friend_number,
+            invite_data: invite_data.to_vec(),
+            group_name: group_name.to_string(),
+        });
+    }
+
+    fn on_group_peer_join(&self, group_number: u32, peer_id: u32) {
+        self.push(Event::GroupPeerJoin { group_number, peer_id });
+    }
+
+    fn on_group_peer_exit(&self, group_number: u32, peer_id: u32, _exit_type: u32, _name: &str, _message: &str) {
+        self.push(Event::GroupPeerExit { group_number, peer_id });
+    }
+
+    fn on_group_peer_name(&self, _group_number: u32, _peer_id: u32, _name: &str) {
+        self.push(Event::Other("group_peer_name"));
+    }
+
+    fn on_group_message(&self, group_number: u32, peer_id: u32, message_type: MessageType, message: &str, _message_id: u32) {
+        self.push(Event::GroupMessage {
+            group_number,
+            peer_id,
+            message_type,
+            message: message.to_string(),
+        });
+    }
+
+    fn on_group_custom_packet(&self, group_number: u32, peer_id: u32, data: &[u8]) {
+        self.push(Event::GroupCustomPacket {
+            group_number,
+            peer_id,
+            data: data.to_vec(),
+        });
+    }
+
+    fn on_group_custom_private_packet(&self, _group_number: u32, _peer_id: u32, _data: &[u8]) {
+        self.push(Event::Other("group_custom_private_packet"));
+    }
+
+    fn on_group_self_join(&self, group_number: u32) {
+        self.push(Event::GroupSelfJoin { group_number });
+    }
+
+    fn on_group_join_fail(&self, group_number: u32, fail_type: u32) {
+        self.push(Event::GroupJoinFail { group_number, fail_type });
+    }
+
+    fn on_group_topic(&self, group_number: u32, peer_id: u32, topic: &str) {
+        self.push(Event::GroupTopic {
+            group_number,
+            peer_id,
+            topic: topic.to_string(),
+        });
+    }
+
+    fn on_group_peer_status(&self, _group_number: u32, _peer_id: u32, _status: UserStatus) {
+        self.push(Event::Other("group_peer_status"));
+    }
+
+    fn on_group_moderation(&self, _group_number: u32, _source_peer_id: u32, _target_peer_id: u32, _event: GroupModEvent) {
+        self.push(Event::Other("group_moderation"));
+    }
+}
+
+/// A Tox instance on localhost that records its callbacks
+pub struct TestNode {
+    pub name: String,
+    pub tox: ToxInstance,
+    /// Passed to toxcore as user_data, so it must stay boxed in place
+    handler: Box<Box<dyn ToxEventHandler>>,
+    events: Arc<Mutex<Vec<Event>>>,
+    panic_on: Arc<Mutex<Option<String>>>,
+}
+
+impl TestNode {
+    /// Start a node with IPv4 UDP only and no LAN discovery, so it talks to
+    /// nothing but the nodes it's bootstrapped to
+    pub fn new(name: &str) -> ToxResult<Self> {
+        let tox = ToxOptionsBuilder::new()
+            .ipv6_enabled(false)
+            .udp_enabled(true)
+            .local_discovery_enabled(false)
+            .no_proxy()
+            .build()?;
+        tox.register_callbacks();
+        tox.set_name(name)?;
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let panic_on = Arc::new(Mutex::new(None));
+        let handler: Box<dyn ToxEventHandler> = Box::new(RecordingHandler {
+            events: events.clone(),
+            panic_on: panic_on.clone(),
+        });
+        Ok(Self {
+            name: name.to_string(),
+            tox,
+            handler: Box::new(handler),
+            events,
+            panic_on,
+        })
+    }
+
+    /// Use another node as this one's DHT bootstrap node
+    pub fn bootstrap_from(&self, other: &TestNode) -> ToxResult<()> {
+        let port = other
+            .tox
+            .self_udp_port()
+            .ok_or_else(|| ToxError::Bootstrap(format!("{} has no UDP port", other.name)))?;
+        self.tox.bootstrap("127.0.0.1", port, &other.tox.self_public_key().0)
+    }
+
+    /// Run one tox_iterate, recording any callbacks
+    pub fn iterate(&self) {
+        let handler_ptr: *const Box<dyn ToxEventHandler> = &*self.handler;
+        self.tox.iterate_with_userdata(handler_ptr as *mut std::ffi::c_void);
+    }
+
+    /// Make the handler panic, with the message as the payload, when this
+    /// friend message next arrives
+    pub fn panic_on_message(&self, message: &str) {
+        *self.panic_on.lock().unwrap() = Some(message.to_string());
+    }
+
+    /// Everything recorded so far, oldest first
+    pub fn events(&self) -> Vec<Event> {
+        self.events.lock().unwrap().clone()
+    }
+
+    /// Remove and return everything recorded so far
+    pub fn take_events(&self) -> Vec<Event> {
+        std::mem::take(&mut *self.events.lock().unwrap())
+    }
+
+    /// The first recorded event `f` picks out
+    pub fn find_event<T>(&self, f: impl FnMut(&Event) -> Option<T>) -> Option<T> {
+        self.events.lock().unwrap().iter().find_map(f)
+    }
+}
+
+/// Iterate `nodes` until `check` returns something or `timeout` passes
+pub fn run_until<T>(nodes: &[&TestNode], timeout: Duration, mut check: impl FnMut() -> Option<T>) -> Option<T> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        for node in nodes {
+            node.iterate();
+        }
+        if let Some(result) = check() {
+            return Some(result);
+        }
+        if Instant::now() >= deadline {
+            return None;
+        }
+        let interval = nodes
+            .iter()
+            .map(|node| node.tox.iteration_interval())
+            .min()
+            .unwrap_or(Duration::from_millis(50));
+        std::thread::sleep(interval.min(Duration::from_millis(50)));
+    }
+}
+
+/// Two nodes that are connected friends
+pub struct TestPair {
+    pub alice: TestNode,
+    pub bob: TestNode,
+    /// Bob's friend number on Alice's side
+    pub bob_on_alice: u32,
+    /// Alice's friend number on Bob's side
+    pub alice_on_bob: u32,
+}
+
+impl TestPair {
+    /// Start two nodes bootstrapped off each other and befriend them through
+    /// a real friend request: Alice adds Bob's address, Bob accepts.
+    pub fn befriended() -> ToxResult<Self> {
+        let alice = TestNode::new("Alice")?;
+        let bob = TestNode::new("Bob")?;
+        alice.bootstrap_from(&bob)?;
+        bob.bootstrap_from(&alice)?;
+
+        let bob_on_alice = alice.tox.friend_add(bob.tox.self_address().as_str(), "Hello from the test harness")?;
+        let request = run_until(&[&alice, &bob], DEFAULT_TIMEOUT, || {
+            bob.find_event(|event| match event {
+                Event::FriendRequest { public_key, .. } => Some(*public_key),
+                _ => None,
+            })
+        })
+        .ok_or_else(|| ToxError::FriendAdd("Bob never received Alice's friend request".into()))?;
+        let alice_on_bob = bob.tox.friend_add_norequest(&request)?;
+
+        run_until(&[&alice, &bob], DEFAULT_TIMEOUT, || {
+            let connected = alice.tox.friend_connection_status(bob_on_alice).is_connected()
+                && bob.tox.friend_connection_status(alice_on_bob).is_connected();
+            connected.then_some(())
+        })
+        .ok_or_else(|| ToxError::FriendAdd("Alice and Bob never connected".into()))?;
+
+        Ok(Self {
+            alice,
+            bob,
+            bob_on_alice,
+            alice_on_bob,
+        })
+    }
+
+    /// Iterate both nodes until `check` returns something, or panic with
+    /// `what` after [`DEFAULT_TIMEOUT`]
+    pub fn wait_for<T>(&self, what: &str, check: impl FnMut() -> Option<T>) -> T {
+        run_until(&[&self.alice, &self.bob], DEFAULT_TIMEOUT, check)
+            .unwrap_or_else(|| panic!("Timed out waiting for {what}"))
+    }
+}
@@ -34,6 +34,7 @@ pub struct ToxInstance {
 /// Builder for ToxOptions
 pub struct ToxOptionsBuilder {
     savedata: Option<Vec<u8>>,
+    secret_key: Option<[u8; TOX_SECRET_KEY_SIZE as usize]>,
     ipv6_enabled: bool,
     udp_enabled: bool,
     local_discovery_enabled: bool,
@@ -49,6 +50,7 @@ impl Default for ToxOptionsBuilder {
     fn default() -> Self {
         Self {
             savedata: None,
+            secret_key: None,
             ipv6_enabled: true,
             udp_enabled: true,
             local_discovery_enabled: true,
@@ -72,6 +74,13 @@ impl ToxOptionsBuilder {
         self
     }
 
+    /// Start a new profile with this identity instead of a random one.
+    /// Ignored if savedata is given.
+    pub fn secret_key(mut self, key: [u8; TOX_SECRET_KEY_SIZE as usize]) -> Self {
+        self.secret_key = Some(key);
+        self
+    }
+
     pub fn ipv6_enabled(mut self, enabled: bool) -> Self {
         self.ipv6_enabled = enabled;
         self
@@ -169,6 +178,12 @@ impl ToxInstance {
                     Tox_Savedata_Type_TOX_SAVEDATA_TYPE_TOX_SAVE,
                 );
                 tox_options_set_savedata_data(opts, savedata.as_ptr(), savedata.len());
+            } else if let Some(ref key) = options.secret_key {
+                tox_options_set_savedata_type(
+                    opts,
+                    Tox_Savedata_Type_TOX_SAVEDATA_TYPE_SECRET_KEY,
+                );
+                tox_options_set_savedata_data(opts, key.as_ptr(), key.len());
             }
 
             let mut new_err = Tox_Err_New::default();
@@ -216,6 +231,17 @@ impl ToxInstance {
         }
     }
 
+    /// Get the nospam value, the part of the address that can be changed
+    /// to stop friend requests sent to the old address
+    pub fn self_nospam(&self) -> u32 {
+        unsafe { tox_self_get_nospam(self.tox) }
+    }
+
+    /// Set the nospam value. Changes the Tox address; existing friends are unaffected.
+    pub fn set_nospam(&self, nospam: u32) {
+        unsafe { tox_self_set_nospam(self.tox, nospam) }
+    }
+
     /// Get the Tox public key (64 hex chars)
     pub fn self_public_key(&self) -> ToxPublicKey {
         unsafe {
@@ -225,6 +251,15 @@ impl ToxInstance {
         }
     }
 
+    /// Get the Tox secret key, for signing with the long-term identity
+    pub fn self_secret_key(&self) -> [u8; TOX_SECRET_KEY_SIZE as usize] {
+        unsafe {
+            let mut sk = [0u8; TOX_SECRET_KEY_SIZE as usize];
+            tox_self_get_secret_key(self.tox, sk.as_mut_ptr());
+            sk
+        }
+    }
+
     /// Get current connection status
     pub fn self_connection_status(&self) -> ConnectionStatus {
         unsafe {
@@ -233,6 +268,34 @@ impl ToxInstance {
         }
     }
 
+    /// UDP port toxcore is bound to; None with UDP disabled
+    pub fn self_udp_port(&self) -> Option<u16> {
+        unsafe {
+            let mut err = Tox_Err_Get_Port::default();
+            let port = tox_self_get_udp_port(self.tox, &mut err);
+            (err == Tox_Err_Get_Port_TOX_ERR_GET_PORT_OK).then_some(port)
+        }
+    }
+
+    /// Port of our own TCP relay server; None unless one is running
+    pub fn self_tcp_port(&self) -> Option<u16> {
+        unsafe {
+            let mut err = Tox_Err_Get_Port::default();
+            let port = tox_self_get_tcp_port(self.tox, &mut err);
+            (err == Tox_Err_Get_Port_TOX_ERR_GET_PORT_OK).then_some(port)
+        }
+    }
+
+    /// DHT nodes in our close list, and how many of them support announcements
+    pub fn dht_node_count(&self) -> (u16, u16) {
+        unsafe {
+            (
+                tox_dht_get_num_closelist(self.tox),
+                tox_dht_get_num_closelist_announce_capable(self.tox),
+            )
+        }
+    }
+
     /// Set the user's display name
     pub fn set_name(&self, name: &str) -> ToxResult<()> {
         unsafe {
@@ -274,6 +337,18 @@ impl ToxInstance {
         }
     }
 
+    /// Set the user's status (online/away/busy)
+    pub fn set_status(&self, status: UserStatus) {
+        let raw = match status {
+            UserStatus::None => Tox_User_Status_TOX_USER_STATUS_NONE,
+            UserStatus::Away => Tox_User_Status_TOX_USER_STATUS_AWAY,
+            UserStatus::Busy => Tox_User_Status_TOX_USER_STATUS_BUSY,
+        };
+        unsafe {
+            tox_self_set_status(self.tox, raw);
+        }
+    }
+
     /// Bootstrap to a DHT node
     pub fn bootstrap(&self, address: &str, port: u16, public_key_hex: &str) -> ToxResult<()> {
         let pk_bytes = hex_to_bytes(public_key_hex)
@@ -339,6 +414,7 @@ impl ToxInstance {
         unsafe {
             tox_iterate(self.tox, ptr::null_mut());
         }
+        crate::callbacks::resume_panic();
     }
 
     /// Run one iteration with a user_data pointer for callbacks
@@ -346,6 +422,7 @@ impl ToxInstance {
         unsafe {
             tox_iterate(self.tox, user_data);
         }
+        crate::callbacks::resume_panic();
     }
 
     /// Get the recommended iteration interval
@@ -368,6 +445,7 @@ impl ToxInstance {
             tox_callback_friend_connection_status(self.tox, Some(friend_connection_status_cb));
             tox_callback_friend_typing(self.tox, Some(friend_typing_cb));
             tox_callback_friend_read_receipt(self.tox, Some(friend_read_receipt_cb));
+            tox_callback_friend_lossless_packet(self.tox, Some(friend_lossless_packet_cb));
             tox_callback_file_recv_control(self.tox, Some(file_recv_control_cb));
             tox_callback_file_chunk_request(self.tox, Some(file_chunk_request_cb));
             tox_callback_file_recv(self.tox, Some(file_recv_cb));
@@ -383,6 +461,7 @@ impl ToxInstance {
             tox_callback_group_join_fail(self.tox, Some(group_join_fail_cb));
             tox_callback_group_topic(self.tox, Some(group_topic_cb));
             tox_callback_group_peer_status(self.tox, Some(group_peer_status_cb));
+            tox_callback_group_moderation(self.tox, Some(group_moderation_cb));
         }
     }
 
@@ -445,13 +524,40 @@ impl ToxInstance {
                 &mut err,
             );
             if message_id == u32::MAX {
-                Err(ToxError::SendMessage(format!("{err:?}")))
+                Err(match err {
+                    Tox_Err_Friend_Send_Message_TOX_ERR_FRIEND_SEND_MESSAGE_FRIEND_NOT_CONNECTED => {
+                        ToxError::FriendNotConnected
+                    }
+                    Tox_Err_Friend_Send_Message_TOX_ERR_FRIEND_SEND_MESSAGE_TOO_LONG => ToxError::MessageTooLong,
+                    _ => ToxError::SendMessage(format!("friend_send_message failed: error {err}")),
+                })
             } else {
                 Ok(message_id)
             }
         }
     }
 
+    /// Send a lossless custom packet to a friend. The first byte must be in 160..=191.
+    pub fn friend_send_lossless_packet(&self, friend_number: u32, data: &[u8]) -> ToxResult<()> {
+        unsafe {
+            let mut err = Tox_Err_Friend_Custom_Packet::default();
+            let ok = tox_friend_send_lossless_packet(
+                self.tox,
+                friend_number,
+                data.as_ptr(),
+                data.len(),
+                &mut err,
+            );
+            if ok {
+                Ok(())
+            } else {
+                Err(ToxError::SendMessage(format!(
+                    "friend_send_lossless_packet failed: {err:?}"
+                )))
+            }
+        }
+    }
+
     /// Get friend's name
     pub fn friend_name(&self, friend_number: u32) -> Option<String> {
         unsafe {
@@ -567,6 +673,11 @@ impl Drop for ToxInstance {
     }
 }
 
+/// Version of the linked c-toxcore, e.g. "0.2.20"
+pub fn toxcore_version() -> String {
+    unsafe { format!("{}.{}.{}", tox_version_major(), tox_version_minor(), tox_version_patch()) }
+}
+
 /// Encrypt savedata with a passphrase using tox_pass_encrypt
 pub fn encrypt_savedata(data: &[u8], passphrase: &str) -> ToxResult<Vec<u8>> {
     unsafe {
@@ -611,6 +722,71 @@ pub fn decrypt_savedata(data: &[u8], passphrase: &str) -> ToxResult<Vec<u8>> {
     }
 }
 
+/// A key derived from a passphrase and a fixed salt with tox_pass_key_derive_with_salt.
+///
+/// Deriving is slow on purpose; keep the key around to encrypt many small
+/// messages. Anyone with the same passphrase and salt derives the same key.
+pub struct PassKey {
+    key: *mut Tox_Pass_Key,
+}
+
+// SAFETY: a Tox_Pass_Key is immutable after derivation and only freed on drop
+unsafe impl Send for PassKey {}
+unsafe impl Sync for PassKey {}
+
+impl PassKey {
+    pub fn derive(passphrase: &str, salt: &[u8; TOX_PASS_SALT_LENGTH as usize]) -> ToxResult<Self> {
+        unsafe {
+            let mut err = Tox_Err_Key_Derivation::default();
+            let key = tox_pass_key_derive_with_salt(
+                passphrase.as_ptr(),
+                passphrase.len(),
+                salt.as_ptr(),
+                &mut err,
+            );
+            if key.is_null() {
+                Err(ToxError::Encryption(format!("Key derivation failed: {err:?}")))
+            } else {
+                Ok(Self { key })
+            }
+        }
+    }
+
+    pub fn encrypt(&self, data: &[u8]) -> ToxResult<Vec<u8>> {
+        unsafe {
+            let mut out = vec![0u8; data.len() + TOX_PASS_ENCRYPTION_EXTRA_LENGTH as usize];
+            let mut err = Tox_Err_Encryption::default();
+            if tox_pass_key_encrypt(self.key, data.as_ptr(), data.len(), out.as_mut_ptr(), &mut err) {
+                Ok(out)
+            } else {
+                Err(ToxError::Encryption(format!("{err:?}")))
+            }
+        }
+    }
+
+    /// Fails if the data was encrypted with a different passphrase or salt
+    pub fn decrypt(&self, data: &[u8]) -> ToxResult<Vec<u8>> {
+        let Some(out_len) = data.len().checked_sub(TOX_PASS_ENCRYPTION_EXTRA_LENGTH as usize) else {
+            return Err(ToxError::Decryption("Data too short".to_string()));
+        };
+        unsafe {
+            let mut out = vec![0u8; out_len];
+            let mut err = Tox_Err_Decryption::default();
+            if tox_pass_key_decrypt(self.key, data.as_ptr(), data.len(), out.as_mut_ptr(), &mut err) {
+                Ok(out)
+            } else {
+                Err(ToxError::Decryption(format!("{err:?}")))
+            }
+        }
+    }
+}
+
+impl Drop for PassKey {
+    fn drop(&mut self) {
+        unsafe { tox_pass_key_free(self.key) }
+    }
+}
+
 /// Check if data is encrypted
 pub fn is_data_encrypted(data: &[u8]) -> bool {
     unsafe { tox_is_data_encrypted(data.as_ptr()) }
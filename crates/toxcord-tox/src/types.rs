@@ -32,6 +32,16 @@ impl ConnectionStatus {
     pub fn is_connected(&self) -> bool {
         !matches!(self, ConnectionStatus::None)
     }
+
+    /// How traffic reaches the peer: "direct" over UDP, "relayed" through a
+    /// TCP relay, or "none"
+    pub fn connection_type(&self) -> &'static str {
+        match self {
+            ConnectionStatus::None => "none",
+            ConnectionStatus::Tcp => "relayed",
+            ConnectionStatus::Udp => "direct",
+        }
+    }
 }
 
 /// User status
@@ -98,6 +108,72 @@ impl GroupRole {
     }
 }
 
+/// Moderation action seen in a group
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GroupModEvent {
+    Kick,
+    /// The target's role was set to this
+    Role(GroupRole),
+}
+
+impl GroupModEvent {
+    pub fn from_raw(raw: u32) -> Self {
+        match raw {
+            0 => GroupModEvent::Kick,
+            1 => GroupModEvent::Role(GroupRole::Observer),
+            2 => GroupModEvent::Role(GroupRole::User),
+            _ => GroupModEvent::Role(GroupRole::Moderator),
+        }
+    }
+}
+
+/// File transfer kind
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FileKind {
+    Data,
+    Avatar,
+}
+
+impl FileKind {
+    pub fn from_raw(raw: u32) -> Self {
+        match raw {
+            1 => FileKind::Avatar,
+            _ => FileKind::Data,
+        }
+    }
+
+    pub fn to_raw(self) -> u32 {
+        match self {
+            FileKind::Data => 0,
+            FileKind::Avatar => 1,
+        }
+    }
+}
+
+/// File transfer control
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FileControl {
+    Resume,
+    Pause,
+    Cancel,
+}
+
+impl FileControl {
+    pub fn from_raw(raw: u32) -> Self {
+        match raw {
+            0 => FileControl::Resume,
+            1 => FileControl::Pause,
+            _ => FileControl::Cancel,
+        }
+    }
+}
+
+/// Length of a file transfer ID (also used as the resume key)
+pub const FILE_ID_LENGTH: usize = 32;
+
+/// Longest group password, in bytes
+pub const GROUP_MAX_PASSWORD_LENGTH: usize = 32;
+
 /// Group privacy state
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum GroupPrivacyState {
@@ -114,6 +190,8 @@ pub struct GroupInfo {
     pub topic: String,
     pub privacy_state: GroupPrivacyState,
     pub peer_count: u32,
+    /// Joining needs a password
+    pub password_protected: bool,
 }
 
 /// Group peer information
@@ -0,0 +1,331 @@
+//! End-to-end tests against two in-process Tox instances on localhost.
+//!
+//! Run with `cargo test -p toxcord-tox --features test-harness`.
+
+#![cfg(feature = "test-harness")]
+
+use std::panic::{self, AssertUnwindSafe};
+use std::time::{Duration, Instant};
+
+use toxcord_tox::testing::{Event, TestNode, TestPair, DEFAULT_TIMEOUT};
+use toxcord_tox::{FileControl, FileKind, GroupPrivacyState, MessageType, ToxError};
+
+#[test]
+fn test_friend_messages_and_receipts() {
+    let pair = TestPair::befriended().expect("befriend");
+    pair.wait_for("Bob's name", || {
+        pair.alice.find_event(|event| {
+            matches!(event, Event::FriendName { name, .. } if name == "Bob").then_some(())
+        })
+    });
+    assert_eq!(pair.alice.tox.friend_name(pair.bob_on_alice).as_deref(), Some("Bob"));
+
+    let message_id = pair
+        .alice
+        .tox
+        .friend_send_message(pair.bob_on_alice, MessageType::Normal, "Hello, Bob")
+        .expect("send");
+    pair.bob
+        .tox
+        .friend_send_message(pair.alice_on_bob, MessageType::Action, "waves")
+        .expect("send");
+
+    let received = pair.wait_for("Bob to receive the message", || {
+        pair.bob.find_event(|event| match event {
+            Event::FriendMessage { friend_number, message_type, message } if *friend_number == pair.alice_on_bob => {
+                Some((*message_type, message.clone()))
+            }
+            _ => None,
+        })
+    });
+    assert_eq!(received, (MessageType::Normal, "Hello, Bob".to_string()));
+
+    let action = pair.wait_for("Alice to receive the action", || {
+        pair.alice.find_event(|event| match event {
+            Event::FriendMessage { message_type: MessageType::Action, message, .. } => Some(message.clone()),
+            _ => None,
+        })
+    });
+    assert_eq!(action, "waves");
+
+    pair.wait_for("a read receipt", || {
+        pair.alice.find_event(|event| {
+            matches!(event, Event::ReadReceipt { message_id: id, .. } if *id == message_id).then_some(())
+        })
+    });
+}
+
+#[test]
+fn test_lossless_packets() {
+    let pair = TestPair::befriended().expect("befriend");
+    let packet = [170, 1, 2, 3];
+    pair.alice
+        .tox
+        .friend_send_lossless_packet(pair.bob_on_alice, &packet)
+        .expect("send packet");
+
+    let data = pair.wait_for("the packet", || {
+        pair.bob.find_event(|event| match event {
+            Event::LosslessPacket { data, .. } => Some(data.clone()),
+            _ => None,
+        })
+    });
+    assert_eq!(data, packet);
+}
+
+#[test]
+fn test_send_errors_are_typed() {
+    let pair = TestPair::befriended().expect("befriend");
+    let too_long = "x".repeat(2000);
+    let result = pair.alice.tox.friend_send_message(pair.bob_on_alice, MessageType::Normal, &too_long);
+    assert!(matches!(result, Err(ToxError::MessageTooLong)), "{result:?}");
+
+    // Carol is never bootstrapped, so she can't come online
+    let carol = TestNode::new("Carol").expect("carol");
+    let carol_key: [u8; 32] = hex::decode(&carol.tox.self_public_key().0).unwrap().try_into().unwrap();
+    let carol_on_alice = pair.alice.tox.friend_add_norequest(&carol_key).expect("add carol");
+    let result = pair.alice.tox.friend_send_message(carol_on_alice, MessageType::Normal, "hi");
+    assert!(matches!(result, Err(ToxError::FriendNotConnected)), "{result:?}");
+}
+
+#[test]
+fn test_handler_panic_unwinds_out_of_iterate() {
+    let pair = TestPair::befriended().expect("befriend");
+    pair.bob.panic_on_message("boom");
+    pair.alice
+        .tox
+        .friend_send_message(pair.bob_on_alice, MessageType::Normal, "boom")
+        .expect("send");
+
+    // The panic crosses toxcore and comes out of Bob's iterate, instead of
+    // aborting the process
+    let deadline = Instant::now() + DEFAULT_TIMEOUT;
+    let payload = loop {
+        pair.alice.iterate();
+        if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(|| pair.bob.iterate())) {
+            break payload;
+        }
+        assert!(Instant::now() < deadline, "Timed out waiting for the handler to panic");
+        std::thread::sleep(Duration::from_millis(20));
+    };
+    assert_eq!(payload.downcast_ref::<String>().map(String::as_str), Some("boom"));
+
+    // And Bob carries on afterwards
+    pair.alice
+        .tox
+        .friend_send_message(pair.bob_on_alice, MessageType::Normal, "still there?")
+        .expect("send");
+    pair.wait_for("the next message", || {
+        pair.bob.find_event(|event| {
+            matches!(event, Event::FriendMessage { message, .. } if message == "still there?").then_some(())
+        })
+    });
+}
+
+#[test]
+fn test_private_group_invite_and_messages() {
+    let pair = TestPair::befriended().expect("befriend");
+    let alice_group = pair
+        .alice
+        .tox
+        .group_new(GroupPrivacyState::Private, "Test Group", "Alice")
+        .expect("group_new");
+    pair.wait_for("Alice to connect to her group", || {
+        pair.alice.tox.group_is_connected(alice_group).then_some(())
+    });
+    pair.alice
+        .tox
+        .group_invite_friend(alice_group, pair.bob_on_alice)
+        .expect("invite");
+
+    let (invite_data, group_name) = pair.wait_for("the invite", || {
+        pair.bob.find_event(|event| match event {
+            Event::GroupInvite { invite_data, group_name, .. } => Some((invite_data.clone(), group_name.clone())),
+            _ => None,
+        })
+    });
+    assert_eq!(group_name, "Test Group");
+    let bob_group = pair
+        .bob
+        .tox
+        .group_invite_accept(pair.alice_on_bob, &invite_data, "Bob", "")
+        .expect("accept");
+
+    pair.wait_for("Bob to join", || {
+        let joined = pair.bob.find_event(|event| {
+            matches!(event, Event::GroupSelfJoin { group_number } if *group_number == bob_group).then_some(())
+        });
+        joined.and(pair.alice.find_event(|event| {
+            matches!(event, Event::GroupPeerJoin { group_number, .. } if *group_number == alice_group).then_some(())
+        }))
+    });
+    assert_eq!(
+        pair.alice.tox.group_get_chat_id(alice_group).unwrap(),
+        pair.bob.tox.group_get_chat_id(bob_group).unwrap()
+    );
+    assert_eq!(pair.bob.tox.group_peer_count(bob_group).unwrap(), 2);
+
+    pair.bob
+        .tox
+        .group_send_message(bob_group, MessageType::Normal, "Hi everyone")
+        .expect("group message");
+    let message = pair.wait_for("the group message", || {
+        pair.alice.find_event(|event| match event {
+            Event::GroupMessage { group_number, message, .. } if *group_number == alice_group => Some(message.clone()),
+            _ => None,
+        })
+    });
+    assert_eq!(message, "Hi everyone");
+
+    pair.alice.tox.group_set_topic(alice_group, "Testing").expect("topic");
+    pair.wait_for("the topic", || {
+        pair.bob.find_event(|event| {
+            matches!(event, Event::GroupTopic { topic, .. } if topic == "Testing").then_some(())
+        })
+    });
+    assert_eq!(pair.bob.tox.group_get_topic(bob_group).unwrap(), "Testing");
+}
+
+#[test]
+fn test_group_password() {
+    let pair = TestPair::befriended().expect("befriend");
+    let alice_group = pair
+        .alice
+        .tox
+        .group_new(GroupPrivacyState::Private, "Locked Group", "Alice")
+        .expect("group_new");
+    pair.wait_for("Alice to connect to her group", || {
+        pair.alice.tox.group_is_connected(alice_group).then_some(())
+    });
+    pair.alice
+        .tox
+        .group_founder_set_password(alice_group, "hunter2")
+        .expect("set password");
+    assert_eq!(pair.alice.tox.group_get_password(alice_group).unwrap(), "hunter2");
+    assert!(pair.alice.tox.group_get_info(alice_group).unwrap().password_protected);
+    pair.alice
+        .tox
+        .group_invite_friend(alice_group, pair.bob_on_alice)
+        .expect("invite");
+
+    let invite_data = pair.wait_for("the invite", || {
+        pair.bob.find_event(|event| match event {
+            Event::GroupInvite { invite_data, .. } => Some(invite_data.clone()),
+            _ => None,
+        })
+    });
+    let bob_group = pair
+        .bob
+        .tox
+        .group_invite_accept(pair.alice_on_bob, &invite_data, "Bob", "wrong")
+        .expect("accept");
+    pair.wait_for("the join to be refused", || {
+        pair.bob.find_event(|event| {
+            // TOX_GROUP_JOIN_FAIL_INVALID_PASSWORD
+            matches!(event, Event::GroupJoinFail { group_number, fail_type: 1 } if *group_number == bob_group)
+                .then_some(())
+        })
+    });
+
+    pair.bob.tox.group_leave(bob_group, "").expect("leave");
+    let bob_group = pair
+        .bob
+        .tox
+        .group_invite_accept(pair.alice_on_bob, &invite_data, "Bob", "hunter2")
+        .expect("accept");
+    pair.wait_for("Bob to join", || {
+        pair.bob.find_event(|event| {
+            matches!(event, Event::GroupSelfJoin { group_number } if *group_number == bob_group).then_some(())
+        })
+    });
+}
+
+#[test]
+fn test_file_transfer() {
+    let pair = TestPair::befriended().expect("befriend");
+    // A few chunks' worth, not a multiple of the chunk size
+    let contents: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+    let file_number = pair
+        .alice
+        .tox
+        .file_send(pair.bob_on_alice, FileKind::Data, contents.len() as u64, None, "test.bin")
+        .expect("file_send");
+
+    let (bob_file, file_size, filename) = pair.wait_for("the file offer", || {
+        pair.bob.find_event(|event| match event {
+            Event::FileRecv { file_number, kind: FileKind::Data, file_size, filename, .. } => {
+                Some((*file_number, *file_size, filename.clone()))
+            }
+            _ => None,
+        })
+    });
+    assert_eq!((file_size, filename.as_str()), (contents.len() as u64, "test.bin"));
+    assert_eq!(
+        pair.alice.tox.file_get_file_id(pair.bob_on_alice, file_number).unwrap(),
+        pair.bob.tox.file_get_file_id(pair.alice_on_bob, bob_file).unwrap()
+    );
+    pair.bob
+        .tox
+        .file_control(pair.alice_on_bob, bob_file, FileControl::Resume)
+        .expect("accept");
+
+    // Answer chunk requests as they come in and reassemble on Bob's side
+    let mut received = vec![0u8; contents.len()];
+    let mut received_len = 0;
+    pair.wait_for("the transfer to complete", || {
+        let mut complete = false;
+        for event in pair.alice.take_events() {
+            if let Event::FileChunkRequest { file_number: n, position, length, .. } = event {
+                if n == file_number && length > 0 {
+                    let start = position as usize;
+                    pair.alice
+                        .tox
+                        .file_send_chunk(pair.bob_on_alice, file_number, position, &contents[start..start + length])
+                        .expect("file_send_chunk");
+                }
+            }
+        }
+        for event in pair.bob.take_events() {
+            if let Event::FileRecvChunk { file_number: n, position, data, .. } = event {
+                if n != bob_file {
+                    continue;
+                }
+                if data.is_empty() {
+                    complete = true;
+                } else {
+                    let start = position as usize;
+                    received[start..start + data.len()].copy_from_slice(&data);
+                    received_len += data.len();
+                }
+            }
+        }
+        complete.then_some(())
+    });
+    assert_eq!(received_len, contents.len());
+    assert_eq!(received, contents);
+}
+
+#[test]
+fn test_file_cancel() {
+    let pair = TestPair::befriended().expect("befriend");
+    pair.alice
+        .tox
+        .file_send(pair.bob_on_alice, FileKind::Data, 1024, None, "declined.bin")
+        .expect("file_send");
+    let bob_file = pair.wait_for("the file offer", || {
+        pair.bob.find_event(|event| match event {
+            Event::FileRecv { file_number, .. } => Some(*file_number),
+            _ => None,
+        })
+    });
+    pair.bob
+        .tox
+        .file_control(pair.alice_on_bob, bob_file, FileControl::Cancel)
+        .expect("cancel");
+
+    pair.wait_for("Alice to see the cancel", || {
+        pair.alice.find_event(|event| {
+            matches!(event, Event::FileRecvControl { control: FileControl::Cancel, .. }).then_some(())
+        })
+    });
+}